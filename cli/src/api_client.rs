@@ -360,16 +360,18 @@ impl ApiClient {
         let base_url =
             std::env::var("HYPERSTACK_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string());
 
-        let api_key = Self::load_api_key_for_url(&base_url).ok();
+        Ok(Self::for_url(&base_url))
+    }
 
-        Ok(ApiClient {
-            base_url,
-            api_key,
+    /// Client for a specific API URL, using the key saved for that URL
+    pub fn for_url(base_url: &str) -> Self {
+        ApiClient {
+            base_url: base_url.to_string(),
+            api_key: Self::load_api_key_for_url(base_url).ok(),
             client: reqwest::blocking::Client::new(),
-        })
+        }
     }
 
-    #[allow(dead_code)]
     pub fn with_api_key(mut self, api_key: String) -> Self {
         self.api_key = Some(api_key);
         self
@@ -0,0 +1,318 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::{
+    parse_message, ClientMessage, FrameSequence, HyperStackError, Operation, Subscription,
+    SubscriptionDiagnostics,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use super::stats::Samples;
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Time allowed for a WebSocket handshake before it counts as failed
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// First reconnect delay when the server gives no hint; doubled per retry
+const RETRY_BASE: Duration = Duration::from_millis(500);
+const RETRY_MAX: Duration = Duration::from_secs(30);
+
+/// What one view's subscriptions measured
+#[derive(Debug, Default)]
+pub struct ViewMeasurements {
+    /// Subscribe sent to ack received
+    pub ack: Samples,
+    /// Subscribe sent to the last snapshot batch received
+    pub snapshot: Samples,
+    /// Live frames received after the snapshot
+    pub frames: u64,
+}
+
+/// What one benchmark connection measured over its lifetime, across reconnects
+#[derive(Debug, Default)]
+pub struct ConnectionReport {
+    /// Whether any handshake succeeded
+    pub connected: bool,
+    pub connect: Samples,
+    /// Round trips of protocol pings, answered by the server behind any
+    /// frames already queued for the connection
+    pub heartbeat: Samples,
+    pub views: HashMap<String, ViewMeasurements>,
+    pub bytes: u64,
+    /// Frames missing from a subscription's sequence
+    pub dropped_frames: u64,
+    /// Connections lost without the server asking the client to back off
+    pub disconnects: u64,
+    /// Handshakes refused or connections closed by the server to shed load
+    pub sheds: u64,
+    pub failed_connects: u64,
+    pub last_error: Option<String>,
+}
+
+/// Holds back new connections while the server is shedding load
+#[derive(Clone)]
+pub struct Backoff {
+    resume_at: Arc<Mutex<Instant>>,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self {
+            resume_at: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn pause(&self, delay: Duration) {
+        let mut resume_at = self.resume_at.lock().unwrap();
+        *resume_at = (*resume_at).max(Instant::now() + delay);
+    }
+
+    pub async fn wait(&self) {
+        let resume_at = *self.resume_at.lock().unwrap();
+        tokio::time::sleep_until(resume_at).await;
+    }
+}
+
+/// How a connection's session ended
+enum SessionEnd {
+    Stopped,
+    /// Closed by the server to shed load, with its suggested delay
+    Shed(Option<Duration>),
+    Disconnected,
+}
+
+/// Subscriptions of one connection that are waiting on their ack or snapshot
+#[derive(Default)]
+struct Session {
+    awaiting_ack: HashMap<String, Instant>,
+    awaiting_snapshot: HashMap<String, (Instant, Option<Duration>)>,
+    sequences: HashMap<String, u64>,
+    ping_sent: Option<Instant>,
+}
+
+impl Session {
+    fn record_message(&mut self, bytes: &[u8], report: &mut ConnectionReport) {
+        report.bytes += bytes.len() as u64;
+        let (sequence, frame) = parse_message(bytes);
+        if let Some(sequence) = sequence {
+            report.dropped_frames += self.observe(&sequence);
+        }
+        let Some(frame) = frame else {
+            return;
+        };
+
+        let view = frame.entity_name().to_string();
+        match frame.operation() {
+            Operation::Subscribed => {
+                let Some(sent) = self.awaiting_ack.remove(&view) else {
+                    return;
+                };
+                let measurements = report.views.entry(view.clone()).or_default();
+                measurements.ack.record(sent.elapsed());
+                let diagnostics = frame.data.get("diagnostics").cloned().and_then(|value| {
+                    serde_json::from_value::<SubscriptionDiagnostics>(value).ok()
+                });
+                // An empty snapshot isn't sent at all, so the ack completes it
+                if diagnostics.is_some_and(|d| d.snapshot_bytes == 0) {
+                    measurements.snapshot.record(sent.elapsed());
+                } else {
+                    self.awaiting_snapshot.insert(view, (sent, None));
+                }
+            }
+            Operation::Snapshot => {
+                if let Some((sent, last_batch)) = self.awaiting_snapshot.get_mut(&view) {
+                    *last_batch = Some(sent.elapsed());
+                }
+            }
+            Operation::RetentionNotice | Operation::FrameTooLarge => {}
+            _ => {
+                self.finish_snapshot(&view, report);
+                report.views.entry(view).or_default().frames += 1;
+            }
+        }
+    }
+
+    /// Record the snapshot of `view` as complete at its last batch
+    fn finish_snapshot(&mut self, view: &str, report: &mut ConnectionReport) {
+        if let Some((_, Some(elapsed))) = self.awaiting_snapshot.remove(view) {
+            report
+                .views
+                .entry(view.to_string())
+                .or_default()
+                .snapshot
+                .record(elapsed);
+        }
+    }
+
+    fn finish(mut self, report: &mut ConnectionReport) {
+        let views: Vec<String> = self.awaiting_snapshot.keys().cloned().collect();
+        for view in views {
+            self.finish_snapshot(&view, report);
+        }
+    }
+
+    /// Number of frames skipped before `sequence`
+    fn observe(&mut self, sequence: &FrameSequence) -> u64 {
+        let last = self
+            .sequences
+            .insert(sequence.sub.clone(), sequence.frame_seq);
+        match last {
+            Some(last) if !sequence.resync && sequence.frame_seq > last + 1 => {
+                sequence.frame_seq - last - 1
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Resolves once the benchmark is told to stop
+async fn stopped(stop: &mut watch::Receiver<bool>) {
+    let _ = stop.wait_for(|stop| *stop).await;
+}
+
+/// Keep a connection subscribed to `views` until `stop`, reconnecting after
+/// disconnects and backing off when the server sheds it
+pub async fn run_connection(
+    url: String,
+    views: Vec<String>,
+    ping_interval: Duration,
+    backoff: Backoff,
+    mut stop: watch::Receiver<bool>,
+) -> ConnectionReport {
+    let mut report = ConnectionReport::default();
+    let mut retry = RETRY_BASE;
+
+    loop {
+        tokio::select! {
+            _ = backoff.wait() => {}
+            _ = stopped(&mut stop) => return report,
+        }
+
+        let started = Instant::now();
+        let connected = tokio::select! {
+            result = tokio::time::timeout(CONNECT_TIMEOUT, connect_async(url.as_str())) => result,
+            _ = stopped(&mut stop) => return report,
+        };
+        let ws = match connected {
+            Ok(Ok((ws, _))) => ws,
+            Ok(Err(WsError::Http(response)))
+                if response.status().as_u16() == 429 || response.status().as_u16() == 503 =>
+            {
+                report.sheds += 1;
+                backoff.pause(HyperStackError::http_retry_hint(&response).unwrap_or(retry));
+                retry = (retry * 2).min(RETRY_MAX);
+                continue;
+            }
+            Ok(Err(error)) => {
+                report.failed_connects += 1;
+                let error = HyperStackError::from(error);
+                report.last_error = Some(error.to_string());
+                if !error.should_retry() {
+                    return report;
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(retry) => {}
+                    _ = stopped(&mut stop) => return report,
+                }
+                retry = (retry * 2).min(RETRY_MAX);
+                continue;
+            }
+            Err(_) => {
+                report.failed_connects += 1;
+                report.last_error = Some(format!(
+                    "Handshake timed out after {}s",
+                    CONNECT_TIMEOUT.as_secs()
+                ));
+                continue;
+            }
+        };
+        report.connect.record(started.elapsed());
+        report.connected = true;
+        retry = RETRY_BASE;
+
+        match run_session(ws, &views, ping_interval, &mut report, &mut stop).await {
+            SessionEnd::Stopped => return report,
+            SessionEnd::Shed(hint) => {
+                report.sheds += 1;
+                backoff.pause(hint.unwrap_or(retry));
+                retry = (retry * 2).min(RETRY_MAX);
+            }
+            SessionEnd::Disconnected => report.disconnects += 1,
+        }
+    }
+}
+
+async fn run_session(
+    ws: WsStream,
+    views: &[String],
+    ping_interval: Duration,
+    report: &mut ConnectionReport,
+    stop: &mut watch::Receiver<bool>,
+) -> SessionEnd {
+    let (mut ws_tx, mut ws_rx) = ws.split();
+    let mut session = Session::default();
+
+    for view in views {
+        let sub = Subscription::new(view.as_str()).with_diagnostics(true);
+        let Ok(msg) = serde_json::to_string(&ClientMessage::Subscribe(sub)) else {
+            continue;
+        };
+        session.awaiting_ack.insert(view.clone(), Instant::now());
+        if ws_tx.send(Message::Text(msg)).await.is_err() {
+            return SessionEnd::Disconnected;
+        }
+    }
+
+    let mut ping_timer = tokio::time::interval_at(Instant::now() + ping_interval, ping_interval);
+
+    let end = loop {
+        tokio::select! {
+            msg = ws_rx.next() => match msg {
+                Some(Ok(Message::Binary(bytes))) => session.record_message(&bytes, report),
+                Some(Ok(Message::Text(text))) => session.record_message(text.as_bytes(), report),
+                Some(Ok(Message::Ping(payload))) => {
+                    let _ = ws_tx.send(Message::Pong(payload)).await;
+                }
+                Some(Ok(Message::Pong(_))) => {
+                    if let Some(sent) = session.ping_sent.take() {
+                        report.heartbeat.record(sent.elapsed());
+                    }
+                }
+                Some(Ok(Message::Close(frame))) => {
+                    let hint = frame
+                        .as_ref()
+                        .and_then(|frame| HyperStackError::close_reason_retry_hint(&frame.reason));
+                    let shed = frame.as_ref().is_some_and(|frame| frame.code == CloseCode::Again);
+                    break if hint.is_some() || shed {
+                        SessionEnd::Shed(hint)
+                    } else {
+                        SessionEnd::Disconnected
+                    };
+                }
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => break SessionEnd::Disconnected,
+            },
+            _ = ping_timer.tick() => {
+                // Only one ping is outstanding at a time, so a slow pong isn't counted twice
+                if session.ping_sent.is_none() {
+                    session.ping_sent = Some(Instant::now());
+                    if ws_tx.send(Message::Ping(Vec::new())).await.is_err() {
+                        break SessionEnd::Disconnected;
+                    }
+                }
+            }
+            _ = stopped(stop) => {
+                let _ = ws_tx.send(Message::Close(None)).await;
+                break SessionEnd::Stopped;
+            }
+        }
+    };
+
+    session.finish(report);
+    end
+}
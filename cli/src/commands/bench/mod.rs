@@ -0,0 +1,610 @@
+//! `hs bench` - load-test a stack's WebSocket endpoint.
+//!
+//! Opens connections over a ramp, subscribes each to part of a weighted mix
+//! of views, and measures handshakes, acks, snapshots, and heartbeat round
+//! trips until the duration runs out. Connections the server sheds wait out
+//! its suggested delay, and hold back connections still to be opened.
+
+mod client;
+mod stats;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use colored::Colorize;
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+use crate::commands::stream::{resolve_url, token};
+use crate::ui;
+use client::{Backoff, ConnectionReport};
+use stats::{Percentiles, Samples};
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// View to subscribe to, optionally weighted: EntityName/mode[=WEIGHT]
+    /// (repeatable, e.g. --view OreRound/latest=3 --view OreMiner/list)
+    #[arg(long = "view", value_name = "VIEW", required = true)]
+    pub views: Vec<String>,
+
+    /// WebSocket URL override
+    #[arg(long)]
+    pub url: Option<String>,
+
+    /// Stack name (resolves URL from hyperstack.toml)
+    #[arg(short, long)]
+    pub stack: Option<String>,
+
+    /// Number of concurrent connections
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub connections: usize,
+
+    /// Views each connection subscribes to, drawn from the weighted mix
+    #[arg(long, default_value_t = 1)]
+    pub views_per_connection: usize,
+
+    /// Seconds over which the connections are opened
+    #[arg(long, default_value_t = 5)]
+    pub ramp: u64,
+
+    /// Seconds to run for, ramp included
+    #[arg(long, default_value_t = 30)]
+    pub duration: u64,
+
+    /// Milliseconds between heartbeat pings on each connection
+    #[arg(long, default_value_t = 1000)]
+    pub heartbeat_ms: u64,
+}
+
+/// A view and its share of the subscriptions
+#[derive(Debug, PartialEq)]
+struct WeightedView {
+    view: String,
+    weight: usize,
+}
+
+struct BenchOptions {
+    ramp: Duration,
+    heartbeat: Duration,
+}
+
+#[derive(Debug, Serialize)]
+struct BenchReport {
+    url: String,
+    connections: usize,
+    /// Connections whose handshake succeeded at least once
+    connected: usize,
+    elapsed_secs: f64,
+    connect_ms: Option<Percentiles>,
+    ack_ms: Option<Percentiles>,
+    snapshot_ms: Option<Percentiles>,
+    heartbeat_ms: Option<Percentiles>,
+    frames: u64,
+    frames_per_sec: f64,
+    bytes: u64,
+    dropped_frames: u64,
+    disconnects: u64,
+    sheds: u64,
+    failed_connects: u64,
+    views: Vec<ViewReport>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ViewReport {
+    view: String,
+    subscriptions: usize,
+    ack_ms: Option<Percentiles>,
+    snapshot_ms: Option<Percentiles>,
+    frames: u64,
+}
+
+pub fn run(args: BenchArgs, config_path: &str, json: bool) -> Result<()> {
+    if args.connections == 0 {
+        bail!("--connections must be at least 1");
+    }
+    if args.views_per_connection == 0 {
+        bail!("--views-per-connection must be at least 1");
+    }
+    if args.heartbeat_ms == 0 {
+        bail!("--heartbeat-ms must be at least 1");
+    }
+
+    let mix = parse_view_mix(&args.views)?;
+    let url = resolve_url(
+        args.url.as_deref(),
+        args.stack.as_deref(),
+        config_path,
+        &mix[0].view,
+    )?;
+    let url = token::ensure_hosted_ws_token(url)?;
+
+    let plans = assign_views(&mix, args.connections, args.views_per_connection);
+    let duration = Duration::from_secs(args.duration);
+    let options = BenchOptions {
+        ramp: Duration::from_secs(args.ramp).min(duration),
+        heartbeat: Duration::from_millis(args.heartbeat_ms),
+    };
+
+    eprintln!(
+        "Opening {} connection{} to {} over {}s, running for {}s ...",
+        args.connections,
+        if args.connections == 1 { "" } else { "s" },
+        token::redact_hs_token_for_display(&url),
+        options.ramp.as_secs(),
+        args.duration
+    );
+
+    let rt = tokio::runtime::Runtime::new().context("Failed to create async runtime")?;
+    let report = rt.block_on(bench(&url, plans, &options, async move {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {}
+            _ = tokio::signal::ctrl_c() => eprintln!("Interrupted, closing connections ..."),
+        }
+    }));
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+
+    if report.connected == 0 {
+        match report.errors.first() {
+            Some(error) => bail!("No connection could be opened: {}", error),
+            None => bail!("No connection could be opened"),
+        }
+    }
+    Ok(())
+}
+
+/// Parse `--view` values of the form `view` or `view=weight`
+fn parse_view_mix(specs: &[String]) -> Result<Vec<WeightedView>> {
+    let mut mix: Vec<WeightedView> = Vec::new();
+    for spec in specs {
+        let (view, weight) = match spec.rsplit_once('=') {
+            Some((view, weight)) => {
+                let weight = weight
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|weight| *weight > 0)
+                    .with_context(|| {
+                        format!("Invalid weight in --view {spec}: expected a positive integer")
+                    })?;
+                (view.trim(), weight)
+            }
+            None => (spec.trim(), 1),
+        };
+        if !view.contains('/') {
+            bail!("Invalid view '{view}': expected EntityName/mode (e.g. OreRound/latest)");
+        }
+        match mix.iter_mut().find(|entry| entry.view == view) {
+            Some(entry) => entry.weight += weight,
+            None => mix.push(WeightedView {
+                view: view.to_string(),
+                weight,
+            }),
+        }
+    }
+    Ok(mix)
+}
+
+/// The views each connection subscribes to, spreading the connections over
+/// the mix in proportion to the weights
+fn assign_views(
+    mix: &[WeightedView],
+    connections: usize,
+    per_connection: usize,
+) -> Vec<Vec<String>> {
+    let slots: Vec<&str> = mix
+        .iter()
+        .flat_map(|entry| std::iter::repeat_n(entry.view.as_str(), entry.weight))
+        .collect();
+    let per_connection = per_connection.min(mix.len());
+
+    (0..connections)
+        .map(|connection| {
+            let mut views: Vec<String> = Vec::with_capacity(per_connection);
+            for offset in 0..slots.len() {
+                if views.len() == per_connection {
+                    break;
+                }
+                let view = slots[(connection * per_connection + offset) % slots.len()];
+                if !views.iter().any(|assigned| assigned == view) {
+                    views.push(view.to_string());
+                }
+            }
+            views
+        })
+        .collect()
+}
+
+/// Open one connection per plan over the ramp and measure them until `stop_when`
+async fn bench(
+    url: &str,
+    plans: Vec<Vec<String>>,
+    options: &BenchOptions,
+    stop_when: impl Future<Output = ()>,
+) -> BenchReport {
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let backoff = Backoff::new();
+    let started = Instant::now();
+    let connections = plans.len();
+
+    let mut subscriptions: BTreeMap<String, usize> = BTreeMap::new();
+    let mut tasks = JoinSet::new();
+    for (index, views) in plans.into_iter().enumerate() {
+        for view in &views {
+            *subscriptions.entry(view.clone()).or_default() += 1;
+        }
+        let opens_at = started + options.ramp.mul_f64(index as f64 / connections as f64);
+        let url = url.to_string();
+        let heartbeat = options.heartbeat;
+        let backoff = backoff.clone();
+        let mut stop = stop_rx.clone();
+        tasks.spawn(async move {
+            tokio::select! {
+                _ = tokio::time::sleep_until(opens_at) => {}
+                _ = stop.wait_for(|stop| *stop) => return ConnectionReport::default(),
+            }
+            client::run_connection(url, views, heartbeat, backoff, stop).await
+        });
+    }
+
+    stop_when.await;
+    let _ = stop_tx.send(true);
+    let elapsed = started.elapsed();
+
+    let mut reports = Vec::with_capacity(connections);
+    while let Some(report) = tasks.join_next().await {
+        if let Ok(report) = report {
+            reports.push(report);
+        }
+    }
+
+    summarize(
+        token::redact_hs_token_for_display(url),
+        connections,
+        subscriptions,
+        reports,
+        elapsed,
+    )
+}
+
+fn summarize(
+    url: String,
+    connections: usize,
+    subscriptions: BTreeMap<String, usize>,
+    reports: Vec<ConnectionReport>,
+    elapsed: Duration,
+) -> BenchReport {
+    let mut connect = Samples::default();
+    let mut heartbeat = Samples::default();
+    let mut ack = Samples::default();
+    let mut snapshot = Samples::default();
+    let mut view_samples: BTreeMap<String, (Samples, Samples, u64)> = BTreeMap::new();
+    let mut errors: Vec<String> = Vec::new();
+
+    for report in &reports {
+        connect.merge(&report.connect);
+        heartbeat.merge(&report.heartbeat);
+        for (view, measured) in &report.views {
+            ack.merge(&measured.ack);
+            snapshot.merge(&measured.snapshot);
+            let (view_ack, view_snapshot, frames) = view_samples.entry(view.clone()).or_default();
+            view_ack.merge(&measured.ack);
+            view_snapshot.merge(&measured.snapshot);
+            *frames += measured.frames;
+        }
+        if let Some(error) = &report.last_error {
+            if !errors.contains(error) {
+                errors.push(error.clone());
+            }
+        }
+    }
+
+    let views: Vec<ViewReport> = subscriptions
+        .into_iter()
+        .map(|(view, subscriptions)| {
+            let (ack, snapshot, frames) = view_samples.remove(&view).unwrap_or_default();
+            ViewReport {
+                view,
+                subscriptions,
+                ack_ms: ack.percentiles(),
+                snapshot_ms: snapshot.percentiles(),
+                frames,
+            }
+        })
+        .collect();
+    let frames: u64 = views.iter().map(|view| view.frames).sum();
+    let sum = |field: fn(&ConnectionReport) -> u64| reports.iter().map(field).sum::<u64>();
+
+    BenchReport {
+        url,
+        connections,
+        connected: reports.iter().filter(|report| report.connected).count(),
+        elapsed_secs: elapsed.as_secs_f64(),
+        connect_ms: connect.percentiles(),
+        ack_ms: ack.percentiles(),
+        snapshot_ms: snapshot.percentiles(),
+        heartbeat_ms: heartbeat.percentiles(),
+        frames,
+        frames_per_sec: frames as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        bytes: sum(|report| report.bytes),
+        dropped_frames: sum(|report| report.dropped_frames),
+        disconnects: sum(|report| report.disconnects),
+        sheds: sum(|report| report.sheds),
+        failed_connects: sum(|report| report.failed_connects),
+        views,
+        errors,
+    }
+}
+
+fn print_report(report: &BenchReport) {
+    ui::print_section(&format!("Bench {}", report.url));
+    println!(
+        "{}/{} connections opened over {:.1}s",
+        report.connected, report.connections, report.elapsed_secs
+    );
+    println!();
+
+    println!(
+        "{}",
+        format!(
+            "{:<14} {:>7} {:>10} {:>10} {:>10} {:>10}",
+            "Metric", "Count", "p50", "p90", "p99", "max"
+        )
+        .bold()
+    );
+    for (name, percentiles) in [
+        ("connect", &report.connect_ms),
+        ("ack", &report.ack_ms),
+        ("snapshot", &report.snapshot_ms),
+        ("heartbeat rtt", &report.heartbeat_ms),
+    ] {
+        match percentiles {
+            Some(p) => println!(
+                "{:<14} {:>7} {:>10} {:>10} {:>10} {:>10}",
+                name,
+                p.count,
+                format_ms(p.p50),
+                format_ms(p.p90),
+                format_ms(p.p99),
+                format_ms(p.max)
+            ),
+            None => println!("{:<14} {:>7} {:>10}", name, 0, "-".dimmed()),
+        }
+    }
+
+    println!();
+    println!(
+        "{:<16}{} ({:.1}/s), {}",
+        "Frames",
+        report.frames,
+        report.frames_per_sec,
+        HumanBytes(report.bytes)
+    );
+    let count = |value: u64| {
+        if value == 0 {
+            value.to_string().green()
+        } else {
+            value.to_string().yellow()
+        }
+    };
+    println!("{:<16}{}", "Dropped frames", count(report.dropped_frames));
+    println!("{:<16}{}", "Disconnects", count(report.disconnects));
+    println!("{:<16}{}", "Sheds", count(report.sheds));
+    println!("{:<16}{}", "Failed connects", count(report.failed_connects));
+
+    let width = report
+        .views
+        .iter()
+        .map(|view| view.view.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    println!();
+    println!(
+        "{}",
+        format!(
+            "{:<width$} {:>5} {:>10} {:>10} {:>13} {:>13} {:>8}",
+            "View",
+            "Subs",
+            "ack p50",
+            "ack p99",
+            "snapshot p50",
+            "snapshot p99",
+            "Frames",
+            width = width
+        )
+        .bold()
+    );
+    for view in &report.views {
+        let (ack_p50, ack_p99) = percentile_pair(&view.ack_ms);
+        let (snapshot_p50, snapshot_p99) = percentile_pair(&view.snapshot_ms);
+        println!(
+            "{:<width$} {:>5} {:>10} {:>10} {:>13} {:>13} {:>8}",
+            view.view,
+            view.subscriptions,
+            ack_p50,
+            ack_p99,
+            snapshot_p50,
+            snapshot_p99,
+            view.frames,
+            width = width
+        );
+    }
+
+    for error in &report.errors {
+        println!();
+        ui::print_warning(error);
+    }
+}
+
+fn percentile_pair(percentiles: &Option<Percentiles>) -> (String, String) {
+    match percentiles {
+        Some(p) => (format_ms(p.p50), format_ms(p.p99)),
+        None => ("-".to_string(), "-".to_string()),
+    }
+}
+
+fn format_ms(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.2}s", ms / 1000.0)
+    } else {
+        format!("{:.1}ms", ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperstack_interpreter::compiler::MultiEntityBytecode;
+    use hyperstack_interpreter::Mutation;
+    use hyperstack_server::{
+        BackgroundHandle, Delivery, Filters, Mode, MutationBatch, ParserSetupFn, Projection,
+        Server, Spec, ViewIndex, ViewSpec,
+    };
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    fn mix(specs: &[&str]) -> Vec<WeightedView> {
+        let specs: Vec<String> = specs.iter().map(|spec| spec.to_string()).collect();
+        parse_view_mix(&specs).unwrap()
+    }
+
+    #[test]
+    fn test_parse_view_mix() {
+        assert_eq!(
+            mix(&["OreRound/latest=3", "OreMiner/list", "OreRound/latest"]),
+            vec![
+                WeightedView {
+                    view: "OreRound/latest".to_string(),
+                    weight: 4
+                },
+                WeightedView {
+                    view: "OreMiner/list".to_string(),
+                    weight: 1
+                },
+            ]
+        );
+        assert!(parse_view_mix(&["OreRound/latest=0".to_string()]).is_err());
+        assert!(parse_view_mix(&["OreRound".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_assign_views_follows_weights() {
+        let plans = assign_views(&mix(&["A/list=3", "B/list"]), 8, 1);
+        let count = |view: &str| plans.iter().filter(|plan| plan[0] == view).count();
+        assert_eq!((count("A/list"), count("B/list")), (6, 2));
+
+        let plans = assign_views(&mix(&["A/list=3", "B/list"]), 2, 5);
+        assert!(plans
+            .iter()
+            .all(|plan| plan.len() == 2 && plan[0] != plan[1]));
+    }
+
+    /// A stack with one list view, updated every 20ms
+    async fn serve() -> (SocketAddr, BackgroundHandle) {
+        let setup: ParserSetupFn = Arc::new(|mutations_tx, _health, _reconnection| {
+            Box::pin(async move {
+                for n in 0u64.. {
+                    let batch = MutationBatch::new(
+                        vec![Mutation::new(
+                            "Token",
+                            json!(format!("token-{}", n % 5)),
+                            json!({ "n": n }),
+                        )]
+                        .into(),
+                    );
+                    mutations_tx.send(batch).await?;
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                Ok(())
+            })
+        });
+        let spec =
+            Spec::new(MultiEntityBytecode::new().build(), "bench_test").with_parser_setup(setup);
+
+        let mut views = ViewIndex::new();
+        views.add_spec(ViewSpec {
+            id: "Token/list".to_string(),
+            export: "Token".to_string(),
+            mode: Mode::List,
+            projection: Projection::all(),
+            filters: Filters::all(),
+            delivery: Delivery::default(),
+            pipeline: None,
+            source_view: None,
+        });
+
+        let (stream_router, background) = Server::builder()
+            .spec(spec)
+            .views(views)
+            .websocket()
+            .build()
+            .expect("runtime should build")
+            .into_router_parts();
+        let background = background.spawn_background(&tokio::runtime::Handle::current());
+
+        let app = axum::Router::new().nest("/stream", stream_router);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+        (addr, background)
+    }
+
+    #[tokio::test]
+    async fn test_bench_against_in_process_server() {
+        let (addr, background) = serve().await;
+        let plans = assign_views(&mix(&["Token/list"]), 3, 1);
+        let options = BenchOptions {
+            ramp: Duration::from_millis(150),
+            heartbeat: Duration::from_millis(100),
+        };
+
+        let report = bench(
+            &format!("ws://{addr}/stream"),
+            plans,
+            &options,
+            tokio::time::sleep(Duration::from_secs(1)),
+        )
+        .await;
+
+        assert_eq!((report.connections, report.connected), (3, 3));
+        assert_eq!(report.connect_ms.unwrap().count, 3);
+        assert_eq!(report.ack_ms.unwrap().count, 3);
+        assert_eq!(report.snapshot_ms.unwrap().count, 3);
+        assert!(report.heartbeat_ms.is_some());
+        assert!(report.frames > 0);
+        assert_eq!(report.views.len(), 1);
+        assert_eq!(report.views[0].subscriptions, 3);
+        assert_eq!(
+            (
+                report.dropped_frames,
+                report.disconnects,
+                report.sheds,
+                report.failed_connects
+            ),
+            (0, 0, 0, 0)
+        );
+        assert!(report.errors.is_empty());
+
+        background.shutdown();
+    }
+}
@@ -0,0 +1,88 @@
+use serde::Serialize;
+use std::time::Duration;
+
+/// Latency samples of one metric, in milliseconds
+#[derive(Debug, Default, Clone)]
+pub struct Samples {
+    millis: Vec<f64>,
+}
+
+impl Samples {
+    pub fn record(&mut self, elapsed: Duration) {
+        self.millis.push(elapsed.as_secs_f64() * 1000.0);
+    }
+
+    pub fn merge(&mut self, other: &Samples) {
+        self.millis.extend_from_slice(&other.millis);
+    }
+
+    /// Percentile breakdown of the samples, or `None` without any
+    pub fn percentiles(&self) -> Option<Percentiles> {
+        if self.millis.is_empty() {
+            return None;
+        }
+        let mut sorted = self.millis.clone();
+        sorted.sort_by(f64::total_cmp);
+        let mean = sorted.iter().sum::<f64>() / sorted.len() as f64;
+
+        Some(Percentiles {
+            count: sorted.len(),
+            min: sorted[0],
+            p50: nearest_rank(&sorted, 50.0),
+            p90: nearest_rank(&sorted, 90.0),
+            p99: nearest_rank(&sorted, 99.0),
+            max: sorted[sorted.len() - 1],
+            mean,
+        })
+    }
+}
+
+/// Distribution of a metric, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub count: usize,
+    pub min: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+/// The smallest sample at or above `percentile` percent of the sorted samples
+fn nearest_rank(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles_use_nearest_rank() {
+        let mut samples = Samples::default();
+        for ms in (1..=100).rev() {
+            samples.record(Duration::from_millis(ms));
+        }
+
+        let percentiles = samples.percentiles().unwrap();
+        assert_eq!(percentiles.count, 100);
+        assert_eq!(percentiles.min, 1.0);
+        assert_eq!(percentiles.p50, 50.0);
+        assert_eq!(percentiles.p90, 90.0);
+        assert_eq!(percentiles.p99, 99.0);
+        assert_eq!(percentiles.max, 100.0);
+        assert_eq!(percentiles.mean, 50.5);
+    }
+
+    #[test]
+    fn test_single_sample_is_every_percentile() {
+        let mut samples = Samples::default();
+        samples.record(Duration::from_millis(7));
+
+        let percentiles = samples.percentiles().unwrap();
+        assert_eq!((percentiles.p50, percentiles.p99), (7.0, 7.0));
+        assert!(Samples::default().percentiles().is_none());
+    }
+}
@@ -0,0 +1,418 @@
+//! Dynamic shell completion.
+//!
+//! The scripts emitted by `hs --completions <shell>` call the hidden
+//! `hs __complete -- <words>` subcommand, which answers from the local
+//! hyperstack.toml and a cached list of remote stacks. Completion never
+//! touches the network; a stale cache is refreshed by a detached
+//! `hs __complete --refresh` process, and commands that already list stacks
+//! or deployments update the cache as a side effect.
+
+use anyhow::Result;
+use clap::Command;
+use clap_complete::Shell;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::api_client::{ApiClient, DeploymentResponse, Spec};
+use crate::config::{get_api_url, HyperstackConfig};
+
+/// Remote data older than this triggers a background refresh.
+const CACHE_TTL_SECS: u64 = 10 * 60;
+
+/// Subcommand paths whose first positional argument is a stack name.
+const STACK_NAME_COMMANDS: &[&[&str]] = &[
+    &["up"],
+    &["push"],
+    &["explore"],
+    &["stack", "push"],
+    &["stack", "show"],
+    &["stack", "versions"],
+    &["stack", "delete"],
+    &["stack", "rollback"],
+    &["stack", "stop"],
+    &["sdk", "create", "typescript"],
+    &["sdk", "create", "rust"],
+    &["build", "create"],
+];
+
+/// Remote stack names and deployment branches, stored in
+/// ~/.hyperstack/completion-cache.json.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CompletionCache {
+    pub api_url: String,
+    pub refreshed_at: u64,
+    pub stacks: Vec<String>,
+    /// Branches of recent deployments, keyed by stack name
+    pub branches: BTreeMap<String, Vec<String>>,
+}
+
+impl CompletionCache {
+    fn path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".hyperstack").join("completion-cache.json"))
+    }
+
+    /// Load the cache for the current API URL, or an empty one.
+    pub fn load() -> Self {
+        let api_url = get_api_url(None);
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str::<Self>(&contents).ok())
+            .filter(|cache| cache.api_url == api_url)
+            .unwrap_or_default()
+    }
+
+    pub fn from_remote(specs: &[Spec], deployments: &[DeploymentResponse]) -> Self {
+        let mut branches: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for deployment in deployments {
+            if let Some(branch) = &deployment.branch {
+                let entry = branches.entry(deployment.spec_name.clone()).or_default();
+                if !entry.contains(branch) {
+                    entry.push(branch.clone());
+                }
+            }
+        }
+
+        Self {
+            api_url: get_api_url(None),
+            refreshed_at: now_secs(),
+            stacks: specs.iter().map(|spec| spec.name.clone()).collect(),
+            branches,
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    fn is_stale(&self) -> bool {
+        now_secs().saturating_sub(self.refreshed_at) > CACHE_TTL_SECS
+    }
+}
+
+/// Best-effort cache update from data a command already fetched.
+pub fn remember_remote(specs: &[Spec], deployments: &[DeploymentResponse]) {
+    let _ = CompletionCache::from_remote(specs, deployments).save();
+}
+
+/// Entry point for `hs __complete`.
+pub fn complete(
+    cmd: &Command,
+    default_config: &str,
+    words: &[String],
+    refresh: bool,
+) -> Result<()> {
+    if refresh {
+        let client = ApiClient::new()?;
+        let specs = client.list_specs()?;
+        let deployments = client.list_deployments(100)?;
+        return CompletionCache::from_remote(&specs, &deployments).save();
+    }
+
+    let config_path = config_arg(words).unwrap_or(default_config);
+    let local_stacks: Vec<String> = HyperstackConfig::load_optional(config_path)
+        .ok()
+        .flatten()
+        .map(|config| {
+            config
+                .stacks
+                .into_iter()
+                .map(|stack| stack.name.unwrap_or(stack.stack))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let cache = CompletionCache::load();
+    if cache.is_stale() && ApiClient::load_api_key().is_ok() {
+        spawn_refresh();
+    }
+
+    for candidate in resolve(cmd, words, &local_stacks, &cache) {
+        println!("{}", candidate);
+    }
+    Ok(())
+}
+
+fn spawn_refresh() {
+    if let Ok(exe) = std::env::current_exe() {
+        let _ = std::process::Command::new(exe)
+            .args(["__complete", "--refresh"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+    }
+}
+
+/// Completion candidates for `words`, the command line up to and including
+/// the word under the cursor (`words[0]` is the binary name).
+pub fn resolve(
+    cmd: &Command,
+    words: &[String],
+    local_stacks: &[String],
+    cache: &CompletionCache,
+) -> Vec<String> {
+    let Some((current, preceding)) = words.split_last() else {
+        return Vec::new();
+    };
+    let preceding = preceding.get(1..).unwrap_or_default();
+
+    // Walk subcommands, collecting positional arguments along the way.
+    let mut command = cmd;
+    let mut path: Vec<&str> = Vec::new();
+    let mut positionals: Vec<&str> = Vec::new();
+    let mut iter = preceding.iter();
+    while let Some(word) = iter.next() {
+        if word.starts_with('-') {
+            if !word.contains('=') && takes_value(command, cmd, word) {
+                iter.next();
+            }
+            continue;
+        }
+        match command.find_subcommand(word) {
+            Some(sub) if positionals.is_empty() => {
+                command = sub;
+                path.push(sub.get_name());
+            }
+            _ => positionals.push(word),
+        }
+    }
+
+    let takes_stack = STACK_NAME_COMMANDS.contains(&path.as_slice());
+
+    let candidates: Vec<String> = match preceding.last().map(String::as_str) {
+        Some("--branch") | Some("-b") if takes_stack => {
+            let branches: BTreeSet<&String> = match positionals.first() {
+                Some(stack) => cache.branches.get(*stack).into_iter().flatten().collect(),
+                None => cache.branches.values().flatten().collect(),
+            };
+            branches.into_iter().cloned().collect()
+        }
+        Some(flag) if flag.starts_with('-') && takes_value(command, cmd, flag) => Vec::new(),
+        _ if current.starts_with('-') => flag_names(command, cmd),
+        _ if takes_stack && positionals.is_empty() => local_stacks
+            .iter()
+            .chain(&cache.stacks)
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        _ if positionals.is_empty() => command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .map(|sub| sub.get_name().to_string())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(current.as_str()))
+        .collect()
+}
+
+fn takes_value(command: &Command, root: &Command, flag: &str) -> bool {
+    let flag = flag.split('=').next().unwrap_or(flag);
+    command
+        .get_arguments()
+        .chain(root.get_arguments().filter(|arg| arg.is_global_set()))
+        .find(|arg| {
+            flag.strip_prefix("--")
+                .map(|long| arg.get_long() == Some(long))
+                .unwrap_or_else(|| {
+                    let mut chars = flag.chars().skip(1);
+                    chars.next().is_some_and(|c| arg.get_short() == Some(c))
+                        && chars.next().is_none()
+                })
+        })
+        .is_some_and(|arg| arg.get_action().takes_values())
+}
+
+fn flag_names(command: &Command, root: &Command) -> Vec<String> {
+    command
+        .get_arguments()
+        .chain(root.get_arguments().filter(|arg| arg.is_global_set()))
+        .filter(|arg| !arg.is_hide_set())
+        .filter_map(|arg| arg.get_long().map(|long| format!("--{}", long)))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn config_arg(words: &[String]) -> Option<&str> {
+    words
+        .windows(2)
+        .find_map(|pair| matches!(pair[0].as_str(), "-c" | "--config").then_some(pair[1].as_str()))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Shell script that wires `hs` completion to `hs __complete`, or `None`
+/// for shells that only get clap's static completions.
+pub fn script(shell: Shell) -> Option<&'static str> {
+    match shell {
+        Shell::Bash => Some(
+            r#"_hs() {
+    local IFS=$'\n'
+    COMPREPLY=($(hs __complete -- "${COMP_WORDS[@]:0:COMP_CWORD+1}" 2>/dev/null))
+}
+complete -o default -F _hs hs
+"#,
+        ),
+        Shell::Zsh => Some(
+            r#"#compdef hs
+_hs() {
+    local -a candidates
+    candidates=(${(f)"$(hs __complete -- "${(@)words[1,CURRENT]}" 2>/dev/null)"})
+    compadd -a candidates
+}
+compdef _hs hs
+"#,
+        ),
+        Shell::Fish => Some(
+            r#"function __hs_complete
+    hs __complete -- (commandline -opc) (commandline -ct) 2>/dev/null
+end
+complete -c hs -f -a '(__hs_complete)'
+"#,
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    fn cli() -> Command {
+        Command::new("hs")
+            .arg(
+                Arg::new("config")
+                    .short('c')
+                    .long("config")
+                    .global(true)
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .global(true)
+                    .action(ArgAction::SetTrue),
+            )
+            .subcommand(
+                Command::new("up")
+                    .arg(Arg::new("stack_name"))
+                    .arg(
+                        Arg::new("branch")
+                            .short('b')
+                            .long("branch")
+                            .action(ArgAction::Set),
+                    )
+                    .arg(
+                        Arg::new("dry_run")
+                            .long("dry-run")
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("stack")
+                    .subcommand(Command::new("show").arg(Arg::new("stack_name")))
+                    .subcommand(Command::new("list")),
+            )
+            .subcommand(Command::new("__complete").hide(true))
+    }
+
+    fn fixture_stacks() -> Vec<String> {
+        let config: HyperstackConfig = toml::from_str(
+            r#"
+            [project]
+            name = "fixture"
+
+            [[stacks]]
+            name = "ore"
+            stack = "OreStream"
+
+            [[stacks]]
+            stack = "PumpfunStream"
+            "#,
+        )
+        .unwrap();
+        config
+            .stacks
+            .into_iter()
+            .map(|stack| stack.name.unwrap_or(stack.stack))
+            .collect()
+    }
+
+    fn cache() -> CompletionCache {
+        CompletionCache {
+            stacks: vec!["ore".to_string(), "remote-only".to_string()],
+            branches: BTreeMap::from([
+                (
+                    "ore".to_string(),
+                    vec!["staging".to_string(), "preview-1".to_string()],
+                ),
+                ("remote-only".to_string(), vec!["dev".to_string()]),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    fn complete_line(line: &str) -> Vec<String> {
+        let mut words: Vec<String> = line.split(' ').map(str::to_string).collect();
+        if line.ends_with(' ') {
+            words.pop();
+            words.push(String::new());
+        }
+        resolve(&cli(), &words, &fixture_stacks(), &cache())
+    }
+
+    #[test]
+    fn completes_local_and_cached_stack_names() {
+        assert_eq!(
+            complete_line("hs up "),
+            vec!["PumpfunStream", "ore", "remote-only"]
+        );
+        assert_eq!(complete_line("hs up o"), vec!["ore"]);
+        assert_eq!(complete_line("hs --json stack show r"), vec!["remote-only"]);
+        assert!(complete_line("hs up ore ").is_empty());
+    }
+
+    #[test]
+    fn completes_branches_from_recent_deployments() {
+        assert_eq!(
+            complete_line("hs up ore --branch "),
+            vec!["preview-1", "staging"]
+        );
+        assert_eq!(
+            complete_line("hs up -b "),
+            vec!["dev", "preview-1", "staging"]
+        );
+    }
+
+    #[test]
+    fn completes_subcommands_and_flags() {
+        assert_eq!(complete_line("hs "), vec!["up", "stack"]);
+        assert_eq!(complete_line("hs stack "), vec!["show", "list"]);
+        assert_eq!(
+            complete_line("hs up --"),
+            vec!["--branch", "--config", "--dry-run", "--json"]
+        );
+        assert!(complete_line("hs -c ").is_empty());
+        assert_eq!(complete_line("hs -c other.toml u"), vec!["up"]);
+    }
+}
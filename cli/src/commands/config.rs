@@ -70,6 +70,7 @@ pub fn init(config_path: &str) -> Result<()> {
             rust_module_mode: false,
         }),
         build: None,
+        idls: Vec::new(),
     };
 
     let config_toml = toml::to_string_pretty(&config)?;
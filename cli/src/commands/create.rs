@@ -1,20 +1,26 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
 use dialoguer::{theme::ColorfulTheme, Input, Select};
+use hyperstack_idl::snapshot::fetch::{checked_idl, fetch_idl, FetchedIdl, IdlSource};
 use std::fs;
 use std::path::Path;
 use std::process::{Command, Stdio};
 
+use crate::commands::idl::HttpTransport;
 use crate::telemetry;
 use crate::templates::{
     customize_project, detect_package_manager, dev_command, install_command, start_command,
-    Template, TemplateManager,
+    write_custom_project, Template, TemplateManager,
 };
 use crate::ui;
 
+#[allow(clippy::too_many_arguments)]
 pub fn create(
     name: Option<String>,
     template: Option<String>,
+    program_id: Option<String>,
+    idl: Option<String>,
+    rpc_url: &str,
     offline: bool,
     force_refresh: bool,
     skip_install: bool,
@@ -34,7 +40,7 @@ pub fn create(
     let selected_template = match template {
         Some(t) => Template::from_str(&t).ok_or_else(|| {
             anyhow::anyhow!(
-                "Unknown template: {}. Available: react-ore, rust-ore, typescript-ore",
+                "Unknown template: {}. Available: react-ore, rust-ore, typescript-ore, custom",
                 t
             )
         })?,
@@ -66,6 +72,13 @@ pub fn create(
         );
     }
 
+    if selected_template.is_custom() {
+        return create_custom(&theme, &project_name, program_id, idl, rpc_url, start);
+    }
+    if program_id.is_some() || idl.is_some() {
+        anyhow::bail!("--program-id and --idl only apply to the custom template");
+    }
+
     let manager = TemplateManager::new()?;
 
     if force_refresh {
@@ -129,6 +142,91 @@ pub fn create(
     Ok(())
 }
 
+/// Generate a project for the user's own program from its IDL
+fn create_custom(
+    theme: &ColorfulTheme,
+    project_name: &str,
+    program_id: Option<String>,
+    idl: Option<String>,
+    rpc_url: &str,
+    start: std::time::Instant,
+) -> Result<()> {
+    let program_id = match program_id {
+        Some(id) => id,
+        None => Input::with_theme(theme)
+            .with_prompt("Program id")
+            .interact_text()
+            .context("Failed to read program id")?,
+    };
+    let idl: String = match idl {
+        Some(idl) => idl,
+        None => Input::with_theme(theme)
+            .with_prompt("IDL path or URL (leave empty to read it from the chain)")
+            .allow_empty(true)
+            .interact_text()
+            .context("Failed to read IDL location")?,
+    };
+
+    ui::print_step("Fetching IDL...");
+    let (fetched, registry) = fetch_custom_idl(&program_id, idl.trim(), rpc_url)?;
+    if let Some(id) = fetched.program_id.as_deref().filter(|id| *id != program_id) {
+        println!(
+            "  {} The IDL names program {}, not {}",
+            "!".yellow().bold(),
+            id,
+            program_id
+        );
+    }
+
+    ui::print_step(&format!(
+        "Creating {} from the {} IDL...",
+        project_name.bold(),
+        fetched.name.cyan()
+    ));
+
+    let project_dir = Path::new(project_name);
+    fs::create_dir_all(project_dir)
+        .with_context(|| format!("Failed to create directory: {}", project_name))?;
+    write_custom_project(project_dir, project_name, &program_id, &fetched, registry)?;
+
+    println!("  {} Project scaffolded", ui::symbols::SUCCESS.green());
+    println!();
+    print_custom_next_steps(project_name);
+
+    telemetry::record_create_completed(Template::Custom.display_name(), start.elapsed());
+
+    Ok(())
+}
+
+/// Fetch the IDL from a URL, read it from a file, or read the program's IDL
+/// account when `idl` is empty. Also returns the URL to pin as its registry.
+fn fetch_custom_idl(
+    program_id: &str,
+    idl: &str,
+    rpc_url: &str,
+) -> Result<(FetchedIdl, Option<String>)> {
+    if idl.starts_with("http://") || idl.starts_with("https://") {
+        let source = IdlSource::Registry {
+            url: idl.to_string(),
+        };
+        let fetched = fetch_idl(&source, &HttpTransport::new(), None)?;
+        return Ok((fetched, Some(idl.to_string())));
+    }
+
+    if !idl.is_empty() {
+        let json = fs::read(idl).with_context(|| format!("Failed to read IDL: {}", idl))?;
+        let fetched = checked_idl(&json, None).with_context(|| format!("Invalid IDL: {}", idl))?;
+        return Ok((fetched, None));
+    }
+
+    let source = IdlSource::OnChain {
+        program_id: program_id.to_string(),
+        rpc_url: rpc_url.to_string(),
+    };
+    let fetched = fetch_idl(&source, &HttpTransport::new(), None)?;
+    Ok((fetched, None))
+}
+
 fn run_npm_install(project_dir: &Path, pm: &str) -> Result<bool> {
     ui::print_step("Installing dependencies...");
 
@@ -214,6 +312,29 @@ fn print_rust_next_steps(project_name: &str) {
     println!();
 }
 
+fn print_custom_next_steps(project_name: &str) {
+    println!(
+        "{} {}",
+        ui::symbols::SUCCESS.green().bold(),
+        "Ready!".bold()
+    );
+    println!();
+    println!(
+        "Set {} in {}, uncomment the fields to map in {}, then run:",
+        "YELLOWSTONE_ENDPOINT".cyan(),
+        ".env".cyan(),
+        "src/stack.rs".cyan()
+    );
+    println!();
+    println!(
+        "  {} {} && {}",
+        "$".dimmed(),
+        format!("cd {}", project_name).cyan(),
+        "cargo run".cyan()
+    );
+    println!();
+}
+
 fn print_ts_cli_next_steps(project_name: &str, pm: &str, install_succeeded: bool) {
     println!(
         "{} {}",
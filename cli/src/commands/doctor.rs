@@ -0,0 +1,717 @@
+//! `hs doctor` - environment diagnostics.
+//!
+//! Runs a checklist of local and network checks concurrently, each with its
+//! own timeout, and reports pass/warn/fail with a remediation hint. Local
+//! checks never need credentials; the auth check is skipped when no API key
+//! is saved.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::process::Command;
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Error as WsError};
+
+use crate::api_client::ApiClient;
+use crate::config::{self, discover_ast_files, HyperstackConfig};
+use crate::ui;
+
+/// Time allowed for each check before it is reported as failed
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Needed by every stack runtime to reach its Yellowstone gRPC source
+const REQUIRED_STACK_ENV: &[&str] = &["YELLOWSTONE_ENDPOINT"];
+
+/// Variables that can stand in for a required one
+const ENV_ALTERNATIVES: &[(&str, &str)] = &[("YELLOWSTONE_ENDPOINT", "YELLOWSTONE_ENDPOINTS")];
+
+/// How deep to look for Rust sources that read environment variables
+const ENV_SCAN_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Check {
+    name: String,
+    status: CheckStatus,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn pass(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Pass, message)
+    }
+
+    fn warn(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Warn, message)
+    }
+
+    fn fail(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Fail, message)
+    }
+
+    fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct DoctorOutput {
+    checks: Vec<Check>,
+    passed: usize,
+    warnings: usize,
+    failed: usize,
+}
+
+type CheckFuture = Pin<Box<dyn Future<Output = Check> + Send>>;
+
+pub fn doctor(config_path: &str, json: bool) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new().context("Failed to create async runtime")?;
+    let checks = rt.block_on(run_checks(Path::new(config_path), Path::new(".")));
+    // Don't wait on blocking checks that already timed out
+    rt.shutdown_background();
+
+    let count = |status| checks.iter().filter(|c| c.status == status).count();
+    let output = DoctorOutput {
+        passed: count(CheckStatus::Pass),
+        warnings: count(CheckStatus::Warn),
+        failed: count(CheckStatus::Fail),
+        checks,
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        print_report(&output);
+    }
+
+    if output.failed > 0 {
+        anyhow::bail!(
+            "{} check{} failed",
+            output.failed,
+            if output.failed == 1 { "" } else { "s" }
+        );
+    }
+    Ok(())
+}
+
+fn print_report(output: &DoctorOutput) {
+    ui::print_section("Hyperstack Doctor");
+    println!();
+
+    let width = output
+        .checks
+        .iter()
+        .map(|c| c.name.len())
+        .max()
+        .unwrap_or(0);
+    for check in &output.checks {
+        let symbol = match check.status {
+            CheckStatus::Pass => ui::symbols::SUCCESS.green().bold(),
+            CheckStatus::Warn => ui::symbols::WARNING.yellow().bold(),
+            CheckStatus::Fail => ui::symbols::FAILURE.red().bold(),
+        };
+        println!(
+            "{} {:width$}  {}",
+            symbol,
+            check.name.bold(),
+            check.message,
+            width = width
+        );
+        if let Some(hint) = &check.hint {
+            println!(
+                "  {:width$}  {} {}",
+                "",
+                ui::symbols::ARROW.dimmed(),
+                hint.dimmed()
+            );
+        }
+    }
+
+    println!();
+    println!(
+        "{} passed, {} warning{}, {} failed",
+        output.passed.to_string().green(),
+        output.warnings.to_string().yellow(),
+        if output.warnings == 1 { "" } else { "s" },
+        output.failed.to_string().red()
+    );
+}
+
+async fn run_checks(config_path: &Path, base: &Path) -> Vec<Check> {
+    let api_url = config::get_api_url(None);
+    let api_key = ApiClient::load_api_key_for_url(&api_url).ok();
+    let stack_urls: Vec<(String, String)> = HyperstackConfig::load_optional(config_path)
+        .ok()
+        .flatten()
+        .map(|config| {
+            config
+                .stacks
+                .into_iter()
+                .filter_map(|stack| {
+                    let name = stack.name.unwrap_or(stack.stack);
+                    stack.url.map(|url| (name, url))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut checks: Vec<(String, CheckFuture)> = Vec::new();
+
+    let path = config_path.to_path_buf();
+    checks.push((
+        "config".into(),
+        blocking("config", move || check_config(&path)),
+    ));
+
+    let dir = base.to_path_buf();
+    checks.push((
+        "stack files".into(),
+        blocking("stack files", move || check_stack_files(&dir)),
+    ));
+
+    checks.push((
+        "toolchain".into(),
+        blocking("toolchain", || check_toolchain(command_version)),
+    ));
+
+    let dir = base.to_path_buf();
+    checks.push((
+        "environment".into(),
+        blocking("environment", move || {
+            check_environment(&dir, |name| std::env::var_os(name).is_some())
+        }),
+    ));
+
+    checks.push((
+        "credentials".into(),
+        Box::pin(std::future::ready(check_credentials(
+            &api_url,
+            api_key.as_deref(),
+        ))),
+    ));
+
+    let url = api_url.clone();
+    checks.push((
+        "auth".into(),
+        blocking("auth", move || check_auth(&url, api_key)),
+    ));
+
+    let url = api_url.clone();
+    checks.push(("api".into(), Box::pin(async move { check_api(&url).await })));
+
+    for (stack, url) in stack_urls {
+        let name = format!("websocket {}", stack);
+        checks.push((
+            name.clone(),
+            Box::pin(async move { check_websocket(&name, &url).await }),
+        ));
+    }
+
+    futures_util::future::join_all(
+        checks
+            .into_iter()
+            .map(|(name, check)| with_timeout(name, CHECK_TIMEOUT, check)),
+    )
+    .await
+}
+
+async fn with_timeout(
+    name: String,
+    timeout: Duration,
+    check: impl Future<Output = Check>,
+) -> Check {
+    match tokio::time::timeout(timeout, check).await {
+        Ok(check) => check,
+        Err(_) => Check::fail(name, format!("Timed out after {}s", timeout.as_secs()))
+            .with_hint("Check your network connection, then run `hs doctor` again"),
+    }
+}
+
+fn blocking<F>(name: &'static str, check: F) -> CheckFuture
+where
+    F: FnOnce() -> Check + Send + 'static,
+{
+    Box::pin(async move {
+        tokio::task::spawn_blocking(check)
+            .await
+            .unwrap_or_else(|e| Check::fail(name, format!("Check panicked: {}", e)))
+    })
+}
+
+fn check_config(path: &Path) -> Check {
+    if !path.exists() {
+        return Check::warn("config", format!("{} not found", path.display()))
+            .with_hint("Run `hs init` to create one");
+    }
+
+    match HyperstackConfig::load(path) {
+        Ok(config) => Check::pass(
+            "config",
+            format!(
+                "{} parsed ({} stack{})",
+                path.display(),
+                config.stacks.len(),
+                if config.stacks.len() == 1 { "" } else { "s" }
+            ),
+        ),
+        Err(e) => Check::fail("config", format!("{:#}", e))
+            .with_hint("Fix the file, then run `hs config validate`"),
+    }
+}
+
+/// Stack files must load with this CLI's AST support. This stands in for a
+/// CLI/API version check, since the API does not report its version.
+fn check_stack_files(base: &Path) -> Check {
+    let discovered = match discover_ast_files(Some(base)) {
+        Ok(discovered) => discovered,
+        Err(e) => return Check::fail("stack files", format!("{:#}", e)),
+    };
+
+    if discovered.is_empty() {
+        return Check::warn("stack files", "No .hyperstack/*.stack.json files found")
+            .with_hint("Build your stack crate with `cargo build` to generate them");
+    }
+
+    let mut unreadable = Vec::new();
+    for ast in &discovered {
+        let loaded = fs::read_to_string(&ast.path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                hyperstack_interpreter::versioned::load_stack_spec(&json).map_err(|e| e.to_string())
+            });
+        if let Err(e) = loaded {
+            unreadable.push(format!("{}: {}", ast.path.display(), e));
+        }
+    }
+
+    if !unreadable.is_empty() {
+        return Check::fail("stack files", unreadable.join("; ")).with_hint(
+            "Rebuild the stack with a matching hyperstack version, or upgrade with `cargo install hyperstack-cli`",
+        );
+    }
+
+    let names: Vec<&str> = discovered.iter().map(|a| a.stack_id.as_str()).collect();
+    Check::pass(
+        "stack files",
+        format!("Found {} ({})", names.len(), names.join(", ")),
+    )
+}
+
+fn command_version(program: &str) -> Option<String> {
+    let output = Command::new(program).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn check_toolchain(version_of: impl Fn(&str) -> Option<String>) -> Check {
+    let mut versions = vec![format!("hs {}", env!("CARGO_PKG_VERSION"))];
+    let mut missing = Vec::new();
+    for program in ["rustc", "cargo"] {
+        match version_of(program) {
+            Some(version) => versions.push(version),
+            None => missing.push(program),
+        }
+    }
+
+    if missing.is_empty() {
+        Check::pass("toolchain", versions.join(", "))
+    } else {
+        Check::warn("toolchain", format!("{} not found", missing.join(" and ")))
+            .with_hint("Install Rust from https://rustup.rs to build stacks locally")
+    }
+}
+
+fn check_environment(base: &Path, is_set: impl Fn(&str) -> bool) -> Check {
+    let has_local_stacks = discover_ast_files(Some(base))
+        .map(|discovered| !discovered.is_empty())
+        .unwrap_or(false);
+
+    let mut required = referenced_env_vars(base);
+    if has_local_stacks {
+        required.extend(REQUIRED_STACK_ENV.iter().map(|name| name.to_string()));
+    }
+
+    if required.is_empty() {
+        return Check::pass("environment", "No environment variables referenced");
+    }
+
+    let from_dotenv = dotenv_names(base);
+    let provided = |name: &str| is_set(name) || from_dotenv.contains(name);
+    let missing: Vec<&str> = required
+        .iter()
+        .map(String::as_str)
+        .filter(|name| {
+            !provided(name)
+                && !ENV_ALTERNATIVES
+                    .iter()
+                    .any(|(required, alternative)| required == name && provided(alternative))
+        })
+        .collect();
+
+    if missing.is_empty() {
+        Check::pass(
+            "environment",
+            format!(
+                "{} variable{} set",
+                required.len(),
+                if required.len() == 1 { "" } else { "s" }
+            ),
+        )
+    } else {
+        Check::warn("environment", format!("Not set: {}", missing.join(", ")))
+            .with_hint("Export them or add them to .env before running a stack locally")
+    }
+}
+
+/// Names passed to `env::var`/`env::var_os` in Rust sources under `base`
+fn referenced_env_vars(base: &Path) -> BTreeSet<String> {
+    let pattern = Regex::new(r#"env::var(?:_os)?\(\s*"([A-Za-z_][A-Za-z0-9_]*)"\s*\)"#)
+        .expect("env var pattern should compile");
+    let mut names = BTreeSet::new();
+    scan_rust_sources(base, 0, &mut |source| {
+        for capture in pattern.captures_iter(source) {
+            names.insert(capture[1].to_string());
+        }
+    });
+    names
+}
+
+fn scan_rust_sources(dir: &Path, depth: usize, visit: &mut dyn FnMut(&str)) {
+    if depth > ENV_SCAN_DEPTH {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if path.is_dir() {
+            if file_name.starts_with('.') || file_name == "target" || file_name == "node_modules" {
+                continue;
+            }
+            scan_rust_sources(&path, depth + 1, visit);
+        } else if file_name.ends_with(".rs") {
+            if let Ok(source) = fs::read_to_string(&path) {
+                visit(&source);
+            }
+        }
+    }
+}
+
+/// Variables defined in the `.env.local` and `.env` files a stack runtime loads
+fn dotenv_names(base: &Path) -> HashSet<String> {
+    [".env.local", ".env"]
+        .iter()
+        .filter_map(|file| fs::read_to_string(base.join(file)).ok())
+        .flat_map(|contents| {
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .filter_map(|line| {
+                    let line = line.strip_prefix("export ").unwrap_or(line);
+                    line.split_once('=')
+                        .map(|(name, _)| name.trim().to_string())
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn check_credentials(api_url: &str, api_key: Option<&str>) -> Check {
+    match api_key {
+        Some(_) => Check::pass("credentials", format!("API key saved for {}", api_url)),
+        None => Check::warn("credentials", format!("No API key saved for {}", api_url))
+            .with_hint("Run `hs auth login` to deploy and manage stacks"),
+    }
+}
+
+fn check_auth(api_url: &str, api_key: Option<String>) -> Check {
+    let Some(api_key) = api_key else {
+        return Check::warn("auth", "Skipped, not logged in");
+    };
+
+    match ApiClient::for_url(api_url)
+        .with_api_key(api_key)
+        .list_specs()
+    {
+        Ok(specs) => Check::pass(
+            "auth",
+            format!(
+                "API key accepted ({} stack{})",
+                specs.len(),
+                if specs.len() == 1 { "" } else { "s" }
+            ),
+        ),
+        Err(e) => Check::fail("auth", format!("{:#}", e))
+            .with_hint("Run `hs auth login` to replace an invalid or expired key"),
+    }
+}
+
+async fn check_api(api_url: &str) -> Check {
+    match reqwest::Client::new().get(api_url).send().await {
+        Ok(response) => Check::pass(
+            "api",
+            format!(
+                "{} reachable (HTTP {})",
+                api_url,
+                response.status().as_u16()
+            ),
+        ),
+        Err(e) => Check::fail("api", format!("Could not reach {}: {}", api_url, e))
+            .with_hint("Check your network connection and HYPERSTACK_API_URL"),
+    }
+}
+
+async fn check_websocket(name: &str, url: &str) -> Check {
+    match connect_async(url).await {
+        Ok((mut ws, _)) => {
+            let _ = ws.close(None).await;
+            Check::pass(name, format!("Connected to {}", url))
+        }
+        // The server answered, it just didn't upgrade this anonymous request
+        Err(WsError::Http(response)) => Check::pass(
+            name,
+            format!("{} reachable (HTTP {})", url, response.status().as_u16()),
+        ),
+        Err(e) => Check::fail(name, format!("Could not connect to {}: {}", url, e)).with_hint(
+            "Check the stack's url in hyperstack.toml, or run `hs status` to see if it is deployed",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hs-doctor-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Answer every HTTP request on a local port with the given status and body
+    fn mock_api(status: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        url
+    }
+
+    /// A local address with nothing listening on it
+    fn closed_port() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        addr.to_string()
+    }
+
+    #[test]
+    fn config_missing_invalid_and_valid() {
+        let dir = temp_dir();
+        let path = dir.join("hyperstack.toml");
+        assert_eq!(check_config(&path).status, CheckStatus::Warn);
+
+        fs::write(&path, "[project\nname = ").unwrap();
+        assert_eq!(check_config(&path).status, CheckStatus::Fail);
+
+        fs::write(
+            &path,
+            "[project]\nname = \"demo\"\n\n[[stacks]]\nstack = \"OreStream\"\n",
+        )
+        .unwrap();
+        let check = check_config(&path);
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(check.message.contains("1 stack)"), "{}", check.message);
+    }
+
+    #[test]
+    fn stack_files_flag_unsupported_ast_version() {
+        let dir = temp_dir();
+        assert_eq!(check_stack_files(&dir).status, CheckStatus::Warn);
+
+        let stacks = dir.join(".hyperstack");
+        fs::create_dir_all(&stacks).unwrap();
+        fs::write(
+            stacks.join("Demo.stack.json"),
+            r#"{"ast_version":"0.0.1","stack_name":"Demo","entities":[]}"#,
+        )
+        .unwrap();
+        let check = check_stack_files(&dir);
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(check.message.contains("Demo"));
+
+        fs::write(
+            stacks.join("Future.stack.json"),
+            r#"{"ast_version":"99.0.0","stack_name":"Future","entities":[]}"#,
+        )
+        .unwrap();
+        let check = check_stack_files(&dir);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.message.contains("99.0.0"), "{}", check.message);
+        assert!(check.hint.unwrap().contains("cargo install hyperstack-cli"));
+    }
+
+    #[test]
+    fn toolchain_warns_on_missing_cargo() {
+        let check = check_toolchain(|program| Some(format!("{} 1.85.0", program)));
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(check.message.contains("rustc 1.85.0"));
+        assert!(check.message.contains("cargo 1.85.0"));
+
+        let check =
+            check_toolchain(|program| (program == "rustc").then(|| "rustc 1.85.0".to_string()));
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert_eq!(check.message, "cargo not found");
+    }
+
+    #[test]
+    fn environment_reads_process_env_and_dotenv() {
+        let dir = temp_dir();
+        assert_eq!(check_environment(&dir, |_| false).status, CheckStatus::Pass);
+
+        fs::create_dir_all(dir.join(".hyperstack")).unwrap();
+        fs::write(
+            dir.join(".hyperstack/Demo.stack.json"),
+            r#"{"ast_version":"0.0.1","stack_name":"Demo","entities":[]}"#,
+        )
+        .unwrap();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("src/main.rs"),
+            r#"fn main() { let _ = std::env::var("RPC_URL"); }"#,
+        )
+        .unwrap();
+
+        let check = check_environment(&dir, |_| false);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert_eq!(check.message, "Not set: RPC_URL, YELLOWSTONE_ENDPOINT");
+
+        fs::write(
+            dir.join(".env"),
+            "# grpc\nexport YELLOWSTONE_ENDPOINT=http://localhost:10000\n",
+        )
+        .unwrap();
+        let check = check_environment(&dir, |name| name == "RPC_URL");
+        assert_eq!(check.status, CheckStatus::Pass, "{}", check.message);
+        fs::remove_file(dir.join(".env")).unwrap();
+        let check = check_environment(&dir, |name| {
+            name == "RPC_URL" || name == "YELLOWSTONE_ENDPOINTS"
+        });
+        assert_eq!(check.status, CheckStatus::Pass, "{}", check.message);
+    }
+
+    #[test]
+    fn credentials_are_checked_locally() {
+        let url = "https://api.example.com";
+        assert_eq!(
+            check_credentials(url, Some("hsk_1")).status,
+            CheckStatus::Pass
+        );
+        let check = check_credentials(url, None);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.hint.unwrap().contains("hs auth login"));
+    }
+
+    #[test]
+    fn auth_skipped_accepted_and_rejected() {
+        assert_eq!(
+            check_auth("http://127.0.0.1:1", None).status,
+            CheckStatus::Warn
+        );
+
+        let url = mock_api("200 OK", "[]");
+        let check = check_auth(&url, Some("hsk_valid".to_string()));
+        assert_eq!(check.status, CheckStatus::Pass, "{}", check.message);
+
+        let url = mock_api("401 Unauthorized", r#"{"error":"invalid api key"}"#);
+        let check = check_auth(&url, Some("hsk_expired".to_string()));
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(
+            check.message.contains("invalid api key"),
+            "{}",
+            check.message
+        );
+    }
+
+    #[tokio::test]
+    async fn api_reachability() {
+        let url = mock_api("404 Not Found", "{}");
+        let check = check_api(&url).await;
+        assert_eq!(check.status, CheckStatus::Pass, "{}", check.message);
+        assert!(check.message.contains("HTTP 404"));
+
+        let check = check_api(&format!("http://{}", closed_port())).await;
+        assert_eq!(check.status, CheckStatus::Fail);
+    }
+
+    #[tokio::test]
+    async fn websocket_reachability() {
+        // A plain HTTP answer still shows the host is reachable
+        let url = mock_api("401 Unauthorized", "{}").replace("http://", "ws://");
+        let check = check_websocket("websocket demo", &url).await;
+        assert_eq!(check.status, CheckStatus::Pass, "{}", check.message);
+
+        let check = check_websocket("websocket demo", &format!("ws://{}", closed_port())).await;
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(check.name, "websocket demo");
+    }
+
+    #[tokio::test]
+    async fn slow_checks_fail_with_timeout() {
+        let check = with_timeout(
+            "api".to_string(),
+            Duration::from_millis(10),
+            std::future::pending(),
+        )
+        .await;
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(check.name, "api");
+        assert!(check.message.starts_with("Timed out"));
+    }
+}
@@ -209,7 +209,7 @@ pub fn show(name: &str, entity: Option<&str>, json: bool) -> Result<()> {
     Ok(())
 }
 
-fn print_entity_detail(entity: &EntitySchema) {
+pub(crate) fn print_entity_detail(entity: &EntitySchema) {
     println!("\n{} {}", "Entity:".bold(), entity.name.green().bold());
     println!("  Primary key: {}", entity.primary_keys.join(", ").cyan());
 
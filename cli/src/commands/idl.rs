@@ -1,17 +1,20 @@
 use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use colored::Colorize;
 use serde::Serialize;
 
+use crate::config::{HyperstackConfig, IdlPinConfig};
 use hyperstack_idl::analysis::{
     build_account_index, classify_accounts, extract_pda_graph, extract_type_graph,
     find_account_usage, find_connections, find_links, AccountCategory, SeedKind,
 };
 use hyperstack_idl::discriminator::compute_discriminator;
+use hyperstack_idl::error::IdlSnapshotError;
 use hyperstack_idl::parse::parse_idl_file;
 use hyperstack_idl::search::{search_idl, suggest_similar, IdlSection, MatchType, SearchResult};
+use hyperstack_idl::snapshot::fetch::{fetch_idl, IdlSource, IdlTransport};
 use hyperstack_idl::types::{
     IdlAccount, IdlField, IdlInstruction, IdlSpec, IdlType, IdlTypeArrayElement, IdlTypeDef,
     IdlTypeDefKind, IdlTypeDefinedInner,
@@ -575,10 +578,53 @@ pub enum IdlCommands {
         #[arg(long)]
         suggest_hs: bool,
     },
+
+    /// Download a program's IDL from its on-chain IDL account or a registry,
+    /// checked against its pin in hyperstack.toml
+    Fetch {
+        /// Program whose IDL to fetch
+        program_id: String,
+
+        /// Record the IDL's content hash as its pin in hyperstack.toml
+        #[arg(long)]
+        pin: bool,
+
+        /// Solana RPC endpoint to read the IDL account from
+        #[arg(
+            long,
+            env = "SOLANA_RPC_URL",
+            default_value = "https://api.mainnet-beta.solana.com"
+        )]
+        rpc_url: String,
+
+        /// Fetch the IDL JSON from this URL instead of the chain
+        #[arg(long)]
+        registry: Option<String>,
+
+        /// Where to write the IDL (default: the pinned path, or idl/<name>.json)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
 }
 
-pub fn run(args: IdlArgs) -> Result<()> {
+pub fn run(args: IdlArgs, config_path: &str) -> Result<()> {
     match args.command {
+        IdlCommands::Fetch {
+            ref program_id,
+            pin,
+            ref rpc_url,
+            ref registry,
+            ref out,
+        } => {
+            return fetch(
+                config_path,
+                program_id,
+                pin,
+                rpc_url,
+                registry.as_deref(),
+                out.as_deref(),
+            );
+        }
         IdlCommands::Summary { ref path } => {
             let idl = load_idl(path)?;
             let format = if idl.address.is_some() {
@@ -1508,3 +1554,155 @@ pub fn run(args: IdlArgs) -> Result<()> {
 
     Ok(())
 }
+
+/// Makes the IDL fetch's requests over HTTP
+pub(crate) struct HttpTransport {
+    client: reqwest::blocking::Client,
+}
+
+impl HttpTransport {
+    pub(crate) fn new() -> Self {
+        Self {
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+}
+
+impl IdlTransport for HttpTransport {
+    fn account_data(&self, rpc_url: &str, address: &str) -> Result<Option<Vec<u8>>, String> {
+        use base64::Engine;
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getAccountInfo",
+            "params": [address, { "encoding": "base64" }],
+        });
+        let response: serde_json::Value = self
+            .client
+            .post(rpc_url)
+            .json(&request)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .map_err(|e| e.to_string())?;
+
+        if let Some(error) = response.get("error") {
+            return Err(error["message"]
+                .as_str()
+                .map_or_else(|| error.to_string(), str::to_string));
+        }
+        let value = &response["result"]["value"];
+        if value.is_null() {
+            return Ok(None);
+        }
+        let data = value["data"][0]
+            .as_str()
+            .ok_or("the response has no base64 account data")?;
+        base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map(Some)
+            .map_err(|e| format!("invalid base64 account data: {}", e))
+    }
+
+    fn get(&self, url: &str) -> Result<Vec<u8>, String> {
+        self.client
+            .get(url)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.bytes())
+            .map(|bytes| bytes.to_vec())
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn fetch(
+    config_path: &str,
+    program_id: &str,
+    pin: bool,
+    rpc_url: &str,
+    registry: Option<&str>,
+    out: Option<&Path>,
+) -> Result<()> {
+    let mut config = HyperstackConfig::load_optional(config_path)?;
+    let pinned = config
+        .as_ref()
+        .and_then(|config| config.find_idl(program_id))
+        .cloned();
+
+    let registry = registry
+        .map(str::to_string)
+        .or_else(|| pinned.as_ref().and_then(|p| p.registry.clone()));
+    let source = match &registry {
+        Some(url) => IdlSource::Registry { url: url.clone() },
+        None => IdlSource::OnChain {
+            program_id: program_id.to_string(),
+            rpc_url: rpc_url.to_string(),
+        },
+    };
+    // Re-pinning takes whatever the source has now
+    let expected = pinned.as_ref().filter(|_| !pin).map(|p| p.hash.as_str());
+
+    let transport = HttpTransport::new();
+    let fetched = fetch_idl(&source, &transport, expected).map_err(|e| match e {
+        IdlSnapshotError::HashMismatch { .. } => anyhow::anyhow!(
+            "{}\n\nThe IDL at the source changed since it was pinned in {}. \
+             Review the change, then run with --pin to accept it.",
+            e,
+            config_path
+        ),
+        e => anyhow::anyhow!(e),
+    })?;
+
+    if let Some(id) = fetched.program_id.as_deref().filter(|id| *id != program_id) {
+        println!(
+            "{} The IDL names program {}, not {}",
+            "!".yellow().bold(),
+            id,
+            program_id
+        );
+    }
+
+    let relative = out
+        .map(Path::to_path_buf)
+        .or_else(|| pinned.as_ref().map(|p| PathBuf::from(&p.path)))
+        .unwrap_or_else(|| PathBuf::from("idl").join(format!("{}.json", fetched.name)));
+    let base = Path::new(config_path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fetched.write(&base.join(&relative))?;
+    println!(
+        "{} Wrote {} ({})",
+        "✓".green().bold(),
+        relative.display(),
+        fetched.hash.dimmed()
+    );
+
+    if !pin {
+        if pinned.is_none() {
+            println!("  Not pinned. Run with --pin to record its hash.");
+        }
+        return Ok(());
+    }
+
+    let config = config.as_mut().with_context(|| {
+        format!(
+            "No {} to record the pin in. Run `hs init` first.",
+            config_path
+        )
+    })?;
+    config.pin_idl(IdlPinConfig {
+        program_id: program_id.to_string(),
+        path: relative.to_string_lossy().into_owned(),
+        hash: fetched.hash.clone(),
+        registry,
+    });
+    config.save(config_path)?;
+    println!("{} Pinned in {}", "✓".green().bold(), config_path);
+    println!(
+        "  Check it at build time with idl_hash = \"{}\" in #[hyperstack(...)]",
+        fetched.hash
+    );
+    Ok(())
+}
@@ -0,0 +1,381 @@
+use anyhow::{bail, Result};
+
+/// How query results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            _ => bail!("Unknown format '{}'. Expected 'table' or 'json'.", s),
+        }
+    }
+}
+
+/// A single line entered at the `hs inspect` prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    Views,
+    Get {
+        view: String,
+        key: String,
+        format: Option<OutputFormat>,
+    },
+    List {
+        view: String,
+        limit: Option<usize>,
+        sort: Option<Vec<String>>,
+        desc: bool,
+        format: Option<OutputFormat>,
+    },
+    Watch {
+        view: String,
+        key: Option<String>,
+        format: Option<OutputFormat>,
+    },
+    Schema {
+        entity: Option<String>,
+    },
+    Format(OutputFormat),
+    Exit,
+}
+
+/// Command names offered by tab completion
+pub const COMMAND_NAMES: &[&str] = &[
+    "get", "list", "watch", "schema", "views", "format", "help", "exit",
+];
+
+pub const HELP: &str = "\
+Commands:
+  views                                  List views exposed by the stack
+  get <Entity/view> <key> [--json]       Fetch a single entity by key
+  list <Entity/view> [--limit N] [--sort path.to.field] [--desc] [--json]
+                                         Fetch the current snapshot of a view
+  watch <Entity/view> [key] [--json]     Stream updates until Ctrl+C
+  schema [Entity]                        Show the stack or entity schema
+  format <table|json>                    Set the default output format
+  help                                   Show this help
+  exit                                   Leave the inspector (also Ctrl+D)";
+
+/// Parse a prompt line. Returns `None` for blank lines.
+pub fn parse(line: &str) -> Result<Option<Command>> {
+    let tokens = tokenize(line)?;
+    let Some((name, rest)) = tokens.split_first() else {
+        return Ok(None);
+    };
+
+    let command = match name.as_str() {
+        "help" | "?" => {
+            expect_no_args(name, rest)?;
+            Command::Help
+        }
+        "views" => {
+            expect_no_args(name, rest)?;
+            Command::Views
+        }
+        "exit" | "quit" => {
+            expect_no_args(name, rest)?;
+            Command::Exit
+        }
+        "format" => match rest {
+            [format] => Command::Format(OutputFormat::parse(format)?),
+            _ => bail!("Usage: format <table|json>"),
+        },
+        "schema" => match rest {
+            [] => Command::Schema { entity: None },
+            [entity] => Command::Schema {
+                entity: Some(entity.clone()),
+            },
+            _ => bail!("Usage: schema [Entity]"),
+        },
+        "get" => {
+            let opts = Options::parse(rest, &[])?;
+            match opts.positional.as_slice() {
+                [view, key] => Command::Get {
+                    view: parse_view(view)?,
+                    key: key.clone(),
+                    format: opts.format,
+                },
+                _ => bail!("Usage: get <Entity/view> <key> [--json]"),
+            }
+        }
+        "list" => {
+            let opts = Options::parse(rest, &["--limit", "--sort"])?;
+            let [view] = opts.positional.as_slice() else {
+                bail!("Usage: list <Entity/view> [--limit N] [--sort path.to.field] [--desc] [--json]");
+            };
+            let limit = match opts.value("--limit") {
+                Some(limit) => match limit.parse::<usize>() {
+                    Ok(limit) if limit > 0 => Some(limit),
+                    _ => bail!("--limit expects a positive number, got '{}'", limit),
+                },
+                None => None,
+            };
+            let sort = opts
+                .value("--sort")
+                .map(|path| path.split('.').map(str::to_string).collect());
+            if opts.desc && sort.is_none() {
+                bail!("--desc requires --sort");
+            }
+            Command::List {
+                view: parse_view(view)?,
+                limit,
+                sort,
+                desc: opts.desc,
+                format: opts.format,
+            }
+        }
+        "watch" => {
+            let opts = Options::parse(rest, &[])?;
+            match opts.positional.as_slice() {
+                [view] => Command::Watch {
+                    view: parse_view(view)?,
+                    key: None,
+                    format: opts.format,
+                },
+                [view, key] => Command::Watch {
+                    view: parse_view(view)?,
+                    key: Some(key.clone()),
+                    format: opts.format,
+                },
+                _ => bail!("Usage: watch <Entity/view> [key] [--json]"),
+            }
+        }
+        other => bail!("Unknown command '{}'. Type 'help' for a list.", other),
+    };
+
+    Ok(Some(command))
+}
+
+fn expect_no_args(name: &str, rest: &[String]) -> Result<()> {
+    if !rest.is_empty() {
+        bail!("'{}' takes no arguments", name);
+    }
+    Ok(())
+}
+
+fn parse_view(view: &str) -> Result<String> {
+    match view.split_once('/') {
+        Some((entity, mode)) if !entity.is_empty() && !mode.is_empty() => Ok(view.to_string()),
+        _ => bail!(
+            "Invalid view '{}'. Expected Entity/view (e.g. OreRound/latest)",
+            view
+        ),
+    }
+}
+
+/// Flags shared by the query commands
+struct Options {
+    positional: Vec<String>,
+    values: Vec<(String, String)>,
+    desc: bool,
+    format: Option<OutputFormat>,
+}
+
+impl Options {
+    fn parse(tokens: &[String], value_flags: &[&str]) -> Result<Self> {
+        let mut opts = Options {
+            positional: Vec::new(),
+            values: Vec::new(),
+            desc: false,
+            format: None,
+        };
+
+        let mut iter = tokens.iter();
+        while let Some(token) = iter.next() {
+            match token.as_str() {
+                "--json" => opts.format = Some(OutputFormat::Json),
+                "--table" => opts.format = Some(OutputFormat::Table),
+                "--desc" if value_flags.contains(&"--sort") => opts.desc = true,
+                flag if value_flags.contains(&flag) => {
+                    let Some(value) = iter.next() else {
+                        bail!("{} expects a value", flag);
+                    };
+                    opts.values.push((flag.to_string(), value.clone()));
+                }
+                flag if flag.starts_with("--") => bail!("Unknown option '{}'", flag),
+                _ => opts.positional.push(token.clone()),
+            }
+        }
+
+        Ok(opts)
+    }
+
+    fn value(&self, flag: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(name, _)| name == flag)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Split a line on whitespace, honouring single and double quotes
+fn tokenize(line: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quote: Option<char> = None;
+
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_token = true;
+            }
+            None if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        bail!("Unterminated quote");
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(line: &str) -> Command {
+        parse(line).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_blank_line() {
+        assert_eq!(parse("").unwrap(), None);
+        assert_eq!(parse("   ").unwrap(), None);
+    }
+
+    #[test]
+    fn test_get() {
+        assert_eq!(
+            parse_ok("get OreRound/state 42"),
+            Command::Get {
+                view: "OreRound/state".to_string(),
+                key: "42".to_string(),
+                format: None,
+            }
+        );
+        assert_eq!(
+            parse_ok("get OreRound/state 42 --json"),
+            Command::Get {
+                view: "OreRound/state".to_string(),
+                key: "42".to_string(),
+                format: Some(OutputFormat::Json),
+            }
+        );
+        assert!(parse("get OreRound/state").is_err());
+        assert!(parse("get OreRound 42").is_err());
+    }
+
+    #[test]
+    fn test_list_with_options() {
+        assert_eq!(
+            parse_ok("list OreMiner/list --limit 10 --sort rewards.rewards_sol"),
+            Command::List {
+                view: "OreMiner/list".to_string(),
+                limit: Some(10),
+                sort: Some(vec!["rewards".to_string(), "rewards_sol".to_string()]),
+                desc: false,
+                format: None,
+            }
+        );
+        assert_eq!(
+            parse_ok("list OreMiner/list --sort rewards.rewards_sol --desc --json"),
+            Command::List {
+                view: "OreMiner/list".to_string(),
+                limit: None,
+                sort: Some(vec!["rewards".to_string(), "rewards_sol".to_string()]),
+                desc: true,
+                format: Some(OutputFormat::Json),
+            }
+        );
+    }
+
+    #[test]
+    fn test_list_rejects_bad_options() {
+        assert!(parse("list OreMiner/list --limit").is_err());
+        assert!(parse("list OreMiner/list --limit ten").is_err());
+        assert!(parse("list OreMiner/list --limit 0").is_err());
+        assert!(parse("list OreMiner/list --desc").is_err());
+        assert!(parse("list OreMiner/list --bogus").is_err());
+        assert!(parse("list").is_err());
+    }
+
+    #[test]
+    fn test_watch() {
+        assert_eq!(
+            parse_ok("watch OreRound/latest"),
+            Command::Watch {
+                view: "OreRound/latest".to_string(),
+                key: None,
+                format: None,
+            }
+        );
+        assert_eq!(
+            parse_ok("watch OreRound/state 7"),
+            Command::Watch {
+                view: "OreRound/state".to_string(),
+                key: Some("7".to_string()),
+                format: None,
+            }
+        );
+        assert!(parse("watch OreRound/latest --limit 5").is_err());
+    }
+
+    #[test]
+    fn test_schema_and_simple_commands() {
+        assert_eq!(parse_ok("schema"), Command::Schema { entity: None });
+        assert_eq!(
+            parse_ok("schema OreRound"),
+            Command::Schema {
+                entity: Some("OreRound".to_string())
+            }
+        );
+        assert_eq!(parse_ok("views"), Command::Views);
+        assert_eq!(parse_ok("help"), Command::Help);
+        assert_eq!(parse_ok("quit"), Command::Exit);
+        assert_eq!(parse_ok("format json"), Command::Format(OutputFormat::Json));
+        assert!(parse("format yaml").is_err());
+        assert!(parse("views extra").is_err());
+        assert!(parse("frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_quoted_arguments() {
+        assert_eq!(
+            parse_ok("get OreMiner/state \"key with spaces\""),
+            Command::Get {
+                view: "OreMiner/state".to_string(),
+                key: "key with spaces".to_string(),
+                format: None,
+            }
+        );
+        assert_eq!(
+            tokenize("get A/b ''").unwrap(),
+            vec!["get".to_string(), "A/b".to_string(), String::new()]
+        );
+        assert!(parse("get OreMiner/state \"unterminated").is_err());
+    }
+}
@@ -0,0 +1,410 @@
+mod command;
+mod render;
+mod session;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use colored::Colorize;
+use hyperstack_sdk::Subscription;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+
+use crate::api_client::{ApiClient, RegistrySchemaResponse};
+use crate::commands::stream::{token, validate_ws_url};
+use crate::config::HyperstackConfig;
+use command::{Command, OutputFormat, COMMAND_NAMES};
+use session::{QueryResult, Session};
+
+#[derive(Args)]
+pub struct InspectArgs {
+    /// Stack name (resolves URL from hyperstack.toml or the registry)
+    pub stack: String,
+
+    /// WebSocket URL override
+    #[arg(long)]
+    pub url: Option<String>,
+
+    /// Print results as JSON instead of tables
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub fn run(args: InspectArgs, config_path: &str) -> Result<()> {
+    // Fetch the schema before starting the async runtime; the API client is blocking
+    let schema = fetch_schema(&args.stack);
+
+    let url = resolve_url(&args, config_path, schema.as_ref())?;
+    let url = token::ensure_hosted_ws_token(url)?;
+
+    eprintln!(
+        "Connecting to {} ...",
+        token::redact_hs_token_for_display(&url)
+    );
+
+    let rt = tokio::runtime::Runtime::new().context("Failed to create async runtime")?;
+    let session = rt.block_on(Session::connect(url))?;
+
+    let mut inspector = Inspector {
+        session,
+        schema,
+        format: if args.json {
+            OutputFormat::Json
+        } else {
+            OutputFormat::Table
+        },
+    };
+
+    eprintln!("Connected to {}.", inspector.session.display_url());
+    if inspector.schema.is_none() {
+        eprintln!(
+            "{}",
+            "Schema not available for this stack; `views`, `schema` and view completion are disabled."
+                .dimmed()
+        );
+    }
+    eprintln!("Type 'help' for commands, Ctrl+D to exit.\n");
+
+    let mut editor: Editor<InspectHelper, rustyline::history::DefaultHistory> =
+        Editor::new().context("Failed to initialize line editor")?;
+    editor.set_helper(Some(InspectHelper::new(inspector.schema.as_ref())));
+
+    let prompt = format!("{}> ", args.stack);
+    loop {
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e).context("Failed to read input"),
+        };
+        let _ = editor.add_history_entry(line.as_str());
+
+        let command = match command::parse(&line) {
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                continue;
+            }
+        };
+
+        if command == Command::Exit {
+            break;
+        }
+        if let Err(e) = rt.block_on(inspector.execute(command)) {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+        }
+    }
+
+    Ok(())
+}
+
+struct Inspector {
+    session: Session,
+    schema: Option<RegistrySchemaResponse>,
+    format: OutputFormat,
+}
+
+impl Inspector {
+    async fn execute(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Help => println!("{}", command::HELP),
+            Command::Exit => {}
+            Command::Format(format) => self.format = format,
+            Command::Views => self.print_views()?,
+            Command::Schema { entity } => self.print_schema(entity.as_deref())?,
+            Command::Get { view, key, format } => {
+                let sub = Subscription::new(&view).with_key(key.clone());
+                let entities = match self.session.query(sub).await? {
+                    QueryResult::Entities(entities) => entities,
+                    QueryResult::Interrupted => return Ok(()),
+                };
+                let Some((key, data)) = entities.into_iter().find(|(k, _)| *k == key) else {
+                    bail!("No entity with key '{}' in {}", key, view);
+                };
+                match format.unwrap_or(self.format) {
+                    OutputFormat::Json => render::print_json(&data),
+                    OutputFormat::Table => render::print_entity(&key, &data),
+                }
+            }
+            Command::List {
+                view,
+                limit,
+                sort,
+                desc,
+                format,
+            } => {
+                let mut sub = Subscription::new(&view);
+                // Server-side take only matches the requested order when we don't re-sort
+                if let (Some(limit), None) = (limit, &sort) {
+                    sub = sub.with_take(limit as u32);
+                }
+                let mut entities = match self.session.query(sub).await? {
+                    QueryResult::Entities(entities) => entities,
+                    QueryResult::Interrupted => return Ok(()),
+                };
+                let total = entities.len();
+                if let Some(path) = &sort {
+                    render::sort_entities(&mut entities, path, desc);
+                }
+                if let Some(limit) = limit {
+                    entities.truncate(limit);
+                }
+
+                match format.unwrap_or(self.format) {
+                    OutputFormat::Json => {
+                        let items: Vec<_> = entities
+                            .iter()
+                            .map(|(key, data)| serde_json::json!({"key": key, "data": data}))
+                            .collect();
+                        render::print_json(&serde_json::Value::Array(items));
+                    }
+                    OutputFormat::Table => {
+                        render::print_entities(&entities, sort.as_deref());
+                        println!(
+                            "{}",
+                            format!("{} of {} entities", entities.len(), total).dimmed()
+                        );
+                    }
+                }
+            }
+            Command::Watch { view, key, format } => {
+                let mut sub = Subscription::new(&view);
+                if let Some(key) = key {
+                    sub = sub.with_key(key);
+                }
+                let format = format.unwrap_or(self.format);
+                eprintln!("{}", format!("Watching {} (Ctrl+C to stop)", view).dimmed());
+
+                self.session
+                    .watch(sub, |op, key, data| match format {
+                        OutputFormat::Json => println!(
+                            "{}",
+                            serde_json::json!({"op": op, "key": key, "data": data})
+                        ),
+                        OutputFormat::Table => {
+                            println!("{} {}", op.cyan(), key.bold());
+                            println!("  {}", data);
+                        }
+                    })
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn require_schema(&self) -> Result<&RegistrySchemaResponse> {
+        self.schema
+            .as_ref()
+            .context("Schema not available for this stack")
+    }
+
+    fn print_views(&self) -> Result<()> {
+        let schema = self.require_schema()?;
+        for entity in &schema.schema.entities {
+            for view in &entity.views {
+                println!("  {:<40} {}", view.id.green(), view.mode.dimmed());
+            }
+        }
+        Ok(())
+    }
+
+    fn print_schema(&self, entity: Option<&str>) -> Result<()> {
+        let schema = self.require_schema()?;
+        match entity {
+            Some(name) => {
+                let entity = schema
+                    .schema
+                    .entities
+                    .iter()
+                    .find(|e| e.name.eq_ignore_ascii_case(name))
+                    .with_context(|| {
+                        format!(
+                            "Entity '{}' not found. Available entities: {}",
+                            name,
+                            entity_names(schema).join(", ")
+                        )
+                    })?;
+                match self.format {
+                    OutputFormat::Json => println!("{}", serde_json::to_string_pretty(entity)?),
+                    OutputFormat::Table => super::explore::print_entity_detail(entity),
+                }
+            }
+            None => match self.format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&schema.schema)?),
+                OutputFormat::Table => {
+                    for entity in &schema.schema.entities {
+                        println!(
+                            "  {}  key: {}  ({} fields, {} views)",
+                            entity.name.green().bold(),
+                            entity.primary_keys.join(", ").cyan(),
+                            entity.fields.len(),
+                            entity.views.len()
+                        );
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+fn entity_names(schema: &RegistrySchemaResponse) -> Vec<String> {
+    schema
+        .schema
+        .entities
+        .iter()
+        .map(|e| e.name.clone())
+        .collect()
+}
+
+/// Look up the stack schema in the public registry, then among the user's own stacks
+fn fetch_schema(name: &str) -> Option<RegistrySchemaResponse> {
+    let client = ApiClient::new().ok()?;
+    if let Ok(schema) = client.get_registry_schema(name) {
+        return Some(schema);
+    }
+    let spec = client.get_spec_by_name(name).ok().flatten()?;
+    client.get_spec_schema(spec.id).ok()
+}
+
+fn resolve_url(
+    args: &InspectArgs,
+    config_path: &str,
+    schema: Option<&RegistrySchemaResponse>,
+) -> Result<String> {
+    if let Some(url) = &args.url {
+        validate_ws_url(url)?;
+        return Ok(url.clone());
+    }
+
+    let config = HyperstackConfig::load_optional(config_path)?;
+    if let Some(url) = config
+        .as_ref()
+        .and_then(|config| config.find_stack(&args.stack))
+        .and_then(|stack| stack.url.as_ref())
+    {
+        validate_ws_url(url)?;
+        return Ok(url.clone());
+    }
+
+    if let Some(schema) = schema {
+        validate_ws_url(&schema.websocket_url)?;
+        return Ok(schema.websocket_url.clone());
+    }
+
+    bail!(
+        "Could not determine WebSocket URL for stack '{}'.\n\
+         Set `url` for the stack in hyperstack.toml or pass --url wss://your-stack.stack.usehyperstack.com",
+        args.stack
+    )
+}
+
+/// Tab completion for command names, view ids, and entity names
+struct InspectHelper {
+    views: Vec<String>,
+    entities: Vec<String>,
+}
+
+impl InspectHelper {
+    fn new(schema: Option<&RegistrySchemaResponse>) -> Self {
+        let views = schema
+            .map(|schema| {
+                schema
+                    .schema
+                    .entities
+                    .iter()
+                    .flat_map(|e| e.views.iter().map(|v| v.id.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let entities = schema.map(entity_names).unwrap_or_default();
+        Self { views, entities }
+    }
+
+    fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let preceding: Vec<&str> = line[..start].split_whitespace().collect();
+
+        let options: Vec<&str> = match preceding.as_slice() {
+            [] => COMMAND_NAMES.to_vec(),
+            ["get" | "list" | "watch"] => self.views.iter().map(String::as_str).collect(),
+            ["schema"] => self.entities.iter().map(String::as_str).collect(),
+            ["format"] => vec!["table", "json"],
+            _ => Vec::new(),
+        };
+
+        let matches = options
+            .into_iter()
+            .filter(|option| option.starts_with(word))
+            .map(str::to_string)
+            .collect();
+        (start, matches)
+    }
+}
+
+impl Completer for InspectHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, matches) = self.candidates(&line[..pos]);
+        let pairs = matches
+            .into_iter()
+            .map(|m| Pair {
+                display: m.clone(),
+                replacement: m,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for InspectHelper {
+    type Hint = String;
+}
+
+impl Highlighter for InspectHelper {}
+
+impl Validator for InspectHelper {}
+
+impl Helper for InspectHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_candidates() {
+        let helper = InspectHelper {
+            views: vec![
+                "OreRound/latest".to_string(),
+                "OreRound/state".to_string(),
+                "OreMiner/list".to_string(),
+            ],
+            entities: vec!["OreRound".to_string(), "OreMiner".to_string()],
+        };
+
+        assert_eq!(helper.candidates("wa"), (0, vec!["watch".to_string()]));
+        assert_eq!(
+            helper.candidates("get OreR"),
+            (
+                4,
+                vec!["OreRound/latest".to_string(), "OreRound/state".to_string()]
+            )
+        );
+        assert_eq!(
+            helper.candidates("schema OreM"),
+            (7, vec!["OreMiner".to_string()])
+        );
+        assert_eq!(helper.candidates("get OreRound/state "), (19, Vec::new()));
+    }
+}
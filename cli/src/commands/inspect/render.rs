@@ -0,0 +1,193 @@
+use colored::Colorize;
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// Columns shown in list tables before the rest are elided
+const MAX_COLUMNS: usize = 8;
+/// Longest cell value printed before truncation
+const MAX_CELL_WIDTH: usize = 32;
+
+/// Print a single entity as a field/value table
+pub fn print_entity(key: &str, data: &Value) {
+    let mut fields = Vec::new();
+    flatten(data, "", &mut fields);
+
+    let rows: Vec<Vec<String>> = fields
+        .into_iter()
+        .map(|(path, value)| vec![path, format_cell(value, usize::MAX)])
+        .collect();
+
+    println!("{} {}", "key:".dimmed(), key.bold());
+    print_table(&["field".to_string(), "value".to_string()], &rows);
+}
+
+/// Print a set of entities as a table with one row per entity.
+///
+/// Columns are the leaf fields in order of first appearance; `pinned` paths
+/// (e.g. the sort field) are shown first.
+pub fn print_entities(entities: &[(String, Value)], pinned: Option<&[String]>) {
+    let pinned = pinned.map(|path| path.join("."));
+    let mut columns: Vec<String> = pinned.iter().cloned().collect();
+    let mut flattened = Vec::with_capacity(entities.len());
+
+    for (_, data) in entities {
+        let mut fields = Vec::new();
+        flatten(data, "", &mut fields);
+        for (path, _) in &fields {
+            if !columns.contains(path) {
+                columns.push(path.clone());
+            }
+        }
+        flattened.push(fields);
+    }
+
+    let hidden = columns.len().saturating_sub(MAX_COLUMNS);
+    columns.truncate(MAX_COLUMNS);
+
+    let mut header = vec!["key".to_string()];
+    header.extend(columns.iter().cloned());
+
+    let rows: Vec<Vec<String>> = entities
+        .iter()
+        .zip(&flattened)
+        .map(|((key, _), fields)| {
+            let mut row = vec![truncate(key, MAX_CELL_WIDTH)];
+            row.extend(columns.iter().map(|column| {
+                fields
+                    .iter()
+                    .find(|(path, _)| path == column)
+                    .map(|(_, value)| format_cell(value, MAX_CELL_WIDTH))
+                    .unwrap_or_default()
+            }));
+            row
+        })
+        .collect();
+
+    print_table(&header, &rows);
+    if hidden > 0 {
+        println!(
+            "{}",
+            format!("({} more columns, use --json to see all fields)", hidden).dimmed()
+        );
+    }
+}
+
+pub fn print_json(value: &Value) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to serialize result: {}", e),
+    }
+}
+
+/// Order entities by the value at `path`; entities missing the field sort last
+pub fn sort_entities(entities: &mut [(String, Value)], path: &[String], desc: bool) {
+    entities.sort_by(
+        |(_, a), (_, b)| match (resolve_path(a, path), resolve_path(b, path)) {
+            (Some(a), Some(b)) => {
+                let ordering = compare_values(a, b);
+                if desc {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+    );
+}
+
+fn resolve_path<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    let mut current = value;
+    for segment in path {
+        current = current.get(segment)?;
+    }
+    (!current.is_null()).then_some(current)
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    // Large integers arrive as strings, so compare numerically when both sides parse
+    match (as_number(a), as_number(b)) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Collect leaf values keyed by dot path. Arrays are kept whole.
+fn flatten<'a>(value: &'a Value, prefix: &str, out: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (field, child) in map {
+                let path = if prefix.is_empty() {
+                    field.clone()
+                } else {
+                    format!("{}.{}", prefix, field)
+                };
+                flatten(child, &path, out);
+            }
+        }
+        _ => out.push((prefix.to_string(), value)),
+    }
+}
+
+fn format_cell(value: &Value, max_width: usize) -> String {
+    let text = match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) if max_width != usize::MAX => format!("[{} items]", items.len()),
+        other => other.to_string(),
+    };
+    truncate(&text, max_width)
+}
+
+fn truncate(text: &str, max_width: usize) -> String {
+    if text.chars().count() <= max_width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn print_table(header: &[String], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: &[String]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", format_row(header).bold());
+    println!(
+        "{}",
+        widths
+            .iter()
+            .map(|w| "-".repeat(*w))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .dimmed()
+    );
+    for row in rows {
+        println!("{}", format_row(row));
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{bail, Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::{
+    deep_merge_with_append, parse_frame, parse_snapshot_entities, ClientMessage, Frame, Operation,
+    Subscription, Unsubscription,
+};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::commands::stream::token;
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// How long a query waits for the server before giving up
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Quiet period after the last snapshot batch before a query is considered complete
+const SNAPSHOT_SETTLE: Duration = Duration::from_millis(300);
+
+/// Outcome of a query that can be interrupted with Ctrl+C
+pub enum QueryResult {
+    Entities(Vec<(String, Value)>),
+    Interrupted,
+}
+
+/// A WebSocket connection to a stack, reconnected on demand
+pub struct Session {
+    url: String,
+    ws: Option<WsStream>,
+}
+
+impl Session {
+    pub async fn connect(url: String) -> Result<Self> {
+        let mut session = Session { url, ws: None };
+        session.socket().await?;
+        Ok(session)
+    }
+
+    pub fn display_url(&self) -> String {
+        token::redact_hs_token_for_display(&self.url)
+    }
+
+    async fn socket(&mut self) -> Result<&mut WsStream> {
+        if self.ws.is_none() {
+            let (ws, _) = connect_async(&self.url).await.map_err(|err| {
+                anyhow::anyhow!("Failed to connect to {}: {}", self.display_url(), err)
+            })?;
+            self.ws = Some(ws);
+        }
+        Ok(self.ws.as_mut().expect("socket was just connected"))
+    }
+
+    async fn send(&mut self, msg: &ClientMessage) -> Result<()> {
+        let text = serde_json::to_string(msg).context("Failed to serialize message")?;
+
+        // The server drops idle connections, so reconnect once if the socket went away
+        // while the prompt was waiting for input
+        if self
+            .socket()
+            .await?
+            .send(Message::Text(text.clone()))
+            .await
+            .is_err()
+        {
+            self.ws = None;
+            self.socket()
+                .await?
+                .send(Message::Text(text))
+                .await
+                .context("Failed to send message")?;
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, sub: &Subscription) {
+        let _ = self
+            .send(&ClientMessage::Unsubscribe(Unsubscription::from(sub)))
+            .await;
+    }
+
+    /// Receive the next frame for `view`, or `None` once `deadline` passes
+    async fn next_frame(&mut self, view: &str, deadline: Instant) -> Result<Option<Frame>> {
+        loop {
+            let ws = self.socket().await?;
+            let msg = match tokio::time::timeout_at(deadline, ws.next()).await {
+                Ok(msg) => msg,
+                Err(_) => return Ok(None),
+            };
+
+            let frame = match msg {
+                Some(Ok(Message::Binary(bytes))) => parse_frame(&bytes).ok(),
+                Some(Ok(Message::Text(text))) => serde_json::from_str::<Frame>(&text).ok(),
+                Some(Ok(Message::Ping(payload))) => {
+                    let _ = ws.send(Message::Pong(payload)).await;
+                    None
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    self.ws = None;
+                    bail!("Connection closed by server");
+                }
+                Some(Err(e)) => {
+                    self.ws = None;
+                    bail!("WebSocket error: {}", e);
+                }
+                Some(Ok(_)) => None,
+            };
+
+            // Frames for views from earlier commands can still be in flight
+            match frame {
+                Some(frame)
+                    if frame.entity == view
+                        && !matches!(
+                            frame.operation(),
+                            Operation::Subscribed | Operation::RetentionNotice
+                        ) =>
+                {
+                    return Ok(Some(frame))
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Subscribe, collect the initial snapshot, and unsubscribe again
+    pub async fn query(&mut self, sub: Subscription) -> Result<QueryResult> {
+        self.send(&ClientMessage::Subscribe(sub.clone())).await?;
+
+        let mut entities: Vec<(String, Value)> = Vec::new();
+        let timeout = Instant::now() + QUERY_TIMEOUT;
+        let mut deadline = timeout;
+
+        let shutdown = tokio::signal::ctrl_c();
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                frame = self.next_frame(&sub.view, deadline) => {
+                    let Some(frame) = frame? else { break };
+                    let was_snapshot = frame.is_snapshot();
+                    apply_frame(&mut entities, frame);
+                    if !was_snapshot {
+                        // First live update: the snapshot, if any, is complete
+                        break;
+                    }
+                    deadline = (Instant::now() + SNAPSHOT_SETTLE).min(timeout);
+                }
+                _ = &mut shutdown => {
+                    self.unsubscribe(&sub).await;
+                    return Ok(QueryResult::Interrupted);
+                }
+            }
+        }
+
+        self.unsubscribe(&sub).await;
+        Ok(QueryResult::Entities(entities))
+    }
+
+    /// Subscribe and pass every update to `on_update` as (op, key, merged entity)
+    /// until Ctrl+C
+    pub async fn watch(
+        &mut self,
+        sub: Subscription,
+        mut on_update: impl FnMut(&str, &str, &Value),
+    ) -> Result<()> {
+        self.send(&ClientMessage::Subscribe(sub.clone())).await?;
+
+        let mut entities: Vec<(String, Value)> = Vec::new();
+        let ping_period = Duration::from_secs(30);
+        let mut ping_interval = tokio::time::interval_at(Instant::now() + ping_period, ping_period);
+
+        let shutdown = tokio::signal::ctrl_c();
+        tokio::pin!(shutdown);
+
+        loop {
+            let far_future = Instant::now() + Duration::from_secs(3600);
+            tokio::select! {
+                frame = self.next_frame(&sub.view, far_future) => {
+                    let Some(frame) = frame? else { continue };
+                    if frame.is_snapshot() {
+                        for entity in parse_snapshot_entities(&frame.data) {
+                            on_update(&frame.op, &entity.key, &entity.data);
+                        }
+                        apply_frame(&mut entities, frame);
+                        continue;
+                    }
+
+                    let op = frame.op.clone();
+                    let key = frame.key.clone();
+                    apply_frame(&mut entities, frame);
+                    let merged = entities
+                        .iter()
+                        .find(|(k, _)| *k == key)
+                        .map(|(_, data)| data.clone())
+                        .unwrap_or(Value::Null);
+                    on_update(&op, &key, &merged);
+                }
+                _ = ping_interval.tick() => {
+                    self.send(&ClientMessage::Ping).await?;
+                }
+                _ = &mut shutdown => break,
+            }
+        }
+
+        self.unsubscribe(&sub).await;
+        Ok(())
+    }
+}
+
+/// Merge a frame into the entities collected so far, keeping arrival order
+fn apply_frame(entities: &mut Vec<(String, Value)>, frame: Frame) {
+    let mut upsert = |key: String, data: Value| match entities.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, existing)) => *existing = data,
+        None => entities.push((key, data)),
+    };
+
+    // Later parts of a split upsert only carry the remaining fields
+    let continued = frame.continuation.is_some_and(|part| part.part > 1);
+    match frame.operation() {
+        Operation::Snapshot => {
+            for entity in parse_snapshot_entities(&frame.data) {
+                upsert(entity.key, entity.data);
+            }
+        }
+        Operation::Upsert | Operation::Create if !continued => upsert(frame.key, frame.data),
+        Operation::Upsert | Operation::Create | Operation::Patch => match entities
+            .iter_mut()
+            .find(|(k, _)| *k == frame.key)
+        {
+            Some((_, existing)) => deep_merge_with_append(existing, &frame.data, &frame.append, ""),
+            None => entities.push((frame.key, frame.data)),
+        },
+        Operation::Delete => entities.retain(|(k, _)| *k != frame.key),
+        Operation::Subscribed
+        | Operation::RetentionNotice
+        | Operation::History
+        | Operation::FrameTooLarge => {}
+    }
+}
@@ -1,9 +1,13 @@
 pub mod auth;
+pub mod bench;
 pub mod build;
+pub mod complete;
 pub mod config;
 pub mod create;
+pub mod doctor;
 pub mod explore;
 pub mod idl;
+pub mod inspect;
 pub mod sdk;
 pub mod stack;
 pub mod status;
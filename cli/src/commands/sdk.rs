@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
+use hyperstack_interpreter::BigNumberMode;
 use std::fs;
 use std::path::Path;
 
@@ -81,6 +82,7 @@ pub fn create_typescript(
     output_override: Option<String>,
     package_name_override: Option<String>,
     url_override: Option<String>,
+    big_numbers: BigNumberMode,
 ) -> Result<()> {
     println!(
         "{} Looking for stack '{}'...",
@@ -161,7 +163,7 @@ pub fn create_typescript(
 
     println!("\n{} Generating TypeScript SDK...", "→".blue().bold());
 
-    generate_typescript_sdk_from_ast(&ast, &output_path, &package_name, stack_url)?;
+    generate_typescript_sdk_from_ast(&ast, &output_path, &package_name, stack_url, big_numbers)?;
 
     println!(
         "{} Successfully generated TypeScript SDK!",
@@ -201,6 +203,7 @@ fn generate_typescript_sdk_from_ast(
     output_path: &Path,
     package_name: &str,
     url: Option<String>,
+    big_numbers: BigNumberMode,
 ) -> Result<()> {
     let stack_spec = load_stack_spec(ast)?;
 
@@ -229,6 +232,7 @@ fn generate_typescript_sdk_from_ast(
         generate_helpers: true,
         export_const_name: "STACK".to_string(),
         url,
+        big_numbers,
     };
 
     let output = hyperstack_interpreter::typescript::compile_stack_spec(stack_spec, Some(config))
@@ -260,6 +264,7 @@ fn load_stack_spec(
     Ok(stack_spec)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_rust(
     config_path: &str,
     stack_name: &str,
@@ -267,6 +272,8 @@ pub fn create_rust(
     crate_name_override: Option<String>,
     module_flag: bool,
     url_override: Option<String>,
+    with_cli: bool,
+    with_contract_tests: bool,
 ) -> Result<()> {
     println!(
         "{} Looking for stack '{}'...",
@@ -345,6 +352,8 @@ pub fn create_rust(
         sdk_version: "0.2".to_string(),
         module_mode: as_module,
         url: stack_url,
+        with_cli,
+        with_contract_tests,
     };
 
     let output = hyperstack_interpreter::rust::compile_stack_spec(stack_spec, Some(rust_config))
@@ -374,6 +383,21 @@ pub fn create_rust(
             crate_name.cyan(),
             output_dir.display()
         );
+        if let Some(cli) = &output.cli {
+            println!("\n  Try the command line binary:");
+            println!(
+                "    cargo run --manifest-path {} --bin {} -- --help",
+                output_dir.join("Cargo.toml").display(),
+                cli.name.cyan()
+            );
+        }
+        if output.contract_test.is_some() {
+            println!("\n  Check a deployment against the stack:");
+            println!(
+                "    cargo test --manifest-path {} --test live_contract -- --ignored",
+                output_dir.join("Cargo.toml").display()
+            );
+        }
     }
 
     telemetry::record_sdk_generated("rust");
@@ -1,6 +1,6 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use colored::Colorize;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::thread;
 use std::time::Duration;
@@ -9,6 +9,7 @@ use crate::api_client::{
     ApiClient, Build, BuildStatus, CreateBuildRequest, CreateSpecRequest, DeploymentResponse,
     DeploymentStatus, Spec as ApiSpec, DEFAULT_DOMAIN_SUFFIX,
 };
+use crate::commands::stream::snapshot::SnapshotPlayer;
 use crate::config::{resolve_stacks_to_push, DiscoveredAst, HyperstackConfig};
 use crate::telemetry;
 
@@ -165,6 +166,7 @@ pub fn list(json: bool) -> Result<()> {
 
     let specs = client.list_specs()?;
     let deployments = client.list_deployments(100)?;
+    crate::commands::complete::remember_remote(&specs, &deployments);
 
     let deployment_map: HashMap<i32, _> = deployments.into_iter().map(|d| (d.spec_id, d)).collect();
 
@@ -772,6 +774,197 @@ pub fn stop(stack_name: &str, branch: Option<&str>, force: bool) -> Result<()> {
     Ok(())
 }
 
+/// Progress of a debug bundle, as reported by `/admin/debug-bundle`
+#[derive(Deserialize)]
+struct DebugBundleStatus {
+    state: String,
+    #[serde(default)]
+    completed_files: usize,
+    #[serde(default)]
+    total_files: usize,
+    current_file: Option<String>,
+    #[serde(default)]
+    omitted: Vec<OmittedFile>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OmittedFile {
+    name: String,
+    reason: String,
+}
+
+pub fn debug_bundle(
+    stack_name: &str,
+    url: Option<&str>,
+    output: Option<&str>,
+    timeout_secs: u64,
+) -> Result<()> {
+    // Only send the API key to the hosted deployment, never to an arbitrary --url
+    let (base_url, api_key) = match url {
+        Some(url) => (url.trim_end_matches('/').to_string(), None),
+        None => {
+            let client = ApiClient::new()?;
+            println!("{} Looking up stack '{}'...", "→".blue().bold(), stack_name);
+            let spec = client
+                .get_spec_by_name(stack_name)?
+                .ok_or_else(|| anyhow::anyhow!("Stack '{}' not found", stack_name))?;
+            let base_url = spec
+                .websocket_url(DEFAULT_DOMAIN_SUFFIX)
+                .replacen("wss://", "https://", 1)
+                .replacen("ws://", "http://", 1);
+            (base_url, ApiClient::load_api_key().ok())
+        }
+    };
+
+    let http = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .context("Failed to build HTTP client")?;
+    let authorize = |request: reqwest::blocking::RequestBuilder| match &api_key {
+        Some(key) => request.header("Authorization", format!("Bearer {}", key.trim())),
+        None => request,
+    };
+    let endpoint = format!("{}/admin/debug-bundle", base_url);
+
+    println!(
+        "{} Requesting debug bundle from {}...",
+        "→".blue().bold(),
+        base_url.cyan()
+    );
+    let response = authorize(http.post(&endpoint))
+        .send()
+        .with_context(|| format!("Failed to reach {}", endpoint))?;
+    if !response.status().is_success() {
+        bail!(
+            "Debug bundle request failed ({}): {}",
+            response.status(),
+            response.text().unwrap_or_default()
+        );
+    }
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(timeout_secs);
+    let mut last_file = None;
+    let status = loop {
+        let status: DebugBundleStatus = authorize(http.get(&endpoint))
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .context("Failed to read debug bundle status")?;
+
+        match status.state.as_str() {
+            "ready" => break status,
+            "failed" => bail!(
+                "Debug bundle failed: {}",
+                status.error.as_deref().unwrap_or("unknown error")
+            ),
+            _ => {}
+        }
+
+        if status.current_file.is_some() && status.current_file != last_file {
+            println!(
+                "  {} [{}/{}] {}",
+                chrono_now().dimmed(),
+                status.completed_files + 1,
+                status.total_files,
+                status.current_file.as_deref().unwrap_or_default()
+            );
+            last_file = status.current_file;
+        }
+
+        if std::time::Instant::now() >= deadline {
+            bail!(
+                "Timed out after {}s waiting for the debug bundle",
+                timeout_secs
+            );
+        }
+        thread::sleep(Duration::from_secs(1));
+    };
+
+    let archive = authorize(http.get(format!("{}/download", endpoint)))
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.bytes())
+        .context("Failed to download debug bundle")?;
+
+    let path = match output {
+        Some(path) => path.to_string(),
+        None => format!(
+            "{}-debug-{}.tar.gz",
+            stack_name,
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ),
+    };
+    std::fs::write(&path, &archive)
+        .with_context(|| format!("Failed to write debug bundle to {}", path))?;
+
+    println!(
+        "{} Debug bundle written to {} ({} bytes)",
+        "✓".green().bold(),
+        path.cyan(),
+        archive.len()
+    );
+    for file in &status.omitted {
+        println!(
+            "  {} {} omitted: {}",
+            "!".yellow().bold(),
+            file.name,
+            file.reason
+        );
+    }
+
+    Ok(())
+}
+
+/// zstd level the server compresses frames at
+const SERVER_ZSTD_LEVEL: i32 = 3;
+
+pub fn train_dict(recordings: &[String], output: &str, max_size: usize) -> Result<()> {
+    let mut samples = Vec::new();
+    for path in recordings {
+        for recorded in SnapshotPlayer::load(path)?.frames {
+            samples.push(serde_json::to_vec(&recorded.frame)?);
+        }
+    }
+    if samples.is_empty() {
+        bail!("The recordings hold no frames to train on");
+    }
+
+    println!(
+        "{} Training a dictionary of up to {} bytes on {} frames...",
+        "→".blue().bold(),
+        max_size,
+        samples.len()
+    );
+    let dictionary = zstd::dict::from_samples(&samples, max_size)
+        .context("Failed to train the dictionary, try recording more frames")?;
+    std::fs::write(output, &dictionary)
+        .with_context(|| format!("Failed to write dictionary to {}", output))?;
+
+    let raw: usize = samples.iter().map(Vec::len).sum();
+    let mut plain = zstd::bulk::Compressor::new(SERVER_ZSTD_LEVEL)?;
+    let mut trained = zstd::bulk::Compressor::with_dictionary(SERVER_ZSTD_LEVEL, &dictionary)?;
+    let (mut plain_size, mut trained_size) = (0, 0);
+    for sample in &samples {
+        plain_size += plain.compress(sample)?.len();
+        trained_size += trained.compress(sample)?.len();
+    }
+
+    println!(
+        "{} Dictionary written to {} ({} bytes)",
+        "✓".green().bold(),
+        output.cyan(),
+        dictionary.len()
+    );
+    println!(
+        "  Recorded frames compress {:.1}x with zstd alone, {:.1}x with the dictionary",
+        raw as f64 / plain_size as f64,
+        raw as f64 / trained_size as f64
+    );
+
+    Ok(())
+}
+
 fn find_deployment<'a>(
     deployments: &'a [DeploymentResponse],
     spec_id: i32,
@@ -41,6 +41,7 @@ pub fn status(json: bool) -> Result<()> {
     let specs = client.list_specs()?;
     let builds = client.list_builds(Some(50), None)?;
     let deployments = client.list_deployments(50)?;
+    crate::commands::complete::remember_remote(&specs, &deployments);
 
     let active_deployments: Vec<_> = deployments
         .iter()
@@ -2,7 +2,7 @@ use anyhow::{Context, Result};
 use futures_util::{SinkExt, StreamExt};
 use hyperstack_sdk::{
     deep_merge_with_append, parse_frame, parse_snapshot_entities, try_parse_subscribed_frame,
-    ClientMessage, Frame, Operation,
+    ClientMessage, Frame, FrameTooLarge, Operation, RetentionNotice,
 };
 use std::collections::{HashMap, HashSet};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
@@ -417,7 +417,15 @@ fn process_frame(frame: Frame, view: &str, state: &mut StreamState) -> Result<bo
         recorder.record(&frame);
     }
 
-    let op = frame.operation();
+    // Later parts of a split upsert only carry the remaining fields
+    let op = match frame.operation() {
+        Operation::Upsert | Operation::Create
+            if frame.continuation.is_some_and(|part| part.part > 1) =>
+        {
+            Operation::Patch
+        }
+        op => op,
+    };
     let op_str = &frame.op;
 
     // Check if this op type is allowed by --ops (but always process snapshots
@@ -540,7 +548,24 @@ fn process_frame(frame: Frame, view: &str, state: &mut StreamState) -> Result<bo
                 return Ok(true);
             }
         }
-        Operation::Subscribed => {}
+        // The CLI never asks for append history
+        Operation::Subscribed | Operation::History => {}
+        Operation::RetentionNotice => {
+            if let Ok(notice) = serde_json::from_value::<RetentionNotice>(frame.data) {
+                eprintln!(
+                    "Note: {} is at its server cap of {} entities; older entities are being evicted",
+                    view, notice.cap
+                );
+            }
+        }
+        Operation::FrameTooLarge => {
+            if let Ok(notice) = serde_json::from_value::<FrameTooLarge>(frame.data) {
+                eprintln!(
+                    "Note: {} in {} was left out: its {} byte frame exceeds the server limit of {} bytes",
+                    frame.key, view, notice.bytes, notice.max_bytes
+                );
+            }
+        }
     }
 
     Ok(false)
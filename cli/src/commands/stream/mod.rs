@@ -1,9 +1,9 @@
 mod client;
 mod filter;
 mod output;
-mod snapshot;
+pub(crate) mod snapshot;
 mod store;
-mod token;
+pub(crate) mod token;
 #[cfg(feature = "tui")]
 mod tui;
 
@@ -128,7 +128,12 @@ pub fn run(args: StreamArgs, config_path: &str) -> Result<()> {
         None => bail!("<VIEW> argument is required (e.g. OreRound/latest)"),
     };
 
-    let url = resolve_url(&args, config_path, view)?;
+    let url = resolve_url(
+        args.url.as_deref(),
+        args.stack.as_deref(),
+        config_path,
+        view,
+    )?;
     let url = token::ensure_hosted_ws_token(url)?;
 
     let rt = tokio::runtime::Runtime::new().context("Failed to create async runtime")?;
@@ -205,24 +210,31 @@ pub fn build_subscription(view: &str, args: &StreamArgs) -> Subscription {
     sub
 }
 
-fn validate_ws_url(url: &str) -> Result<()> {
+pub(crate) fn validate_ws_url(url: &str) -> Result<()> {
     if !url.starts_with("ws://") && !url.starts_with("wss://") {
         bail!("Invalid URL scheme. Expected ws:// or wss://, got: {}", url);
     }
     Ok(())
 }
 
-fn resolve_url(args: &StreamArgs, config_path: &str, view: &str) -> Result<String> {
+/// Resolve the WebSocket URL from `--url`, `--stack`, or the stack in the
+/// config whose entity matches `view`
+pub(crate) fn resolve_url(
+    url: Option<&str>,
+    stack: Option<&str>,
+    config_path: &str,
+    view: &str,
+) -> Result<String> {
     // 1. Explicit --url
-    if let Some(url) = &args.url {
+    if let Some(url) = url {
         validate_ws_url(url)?;
-        return Ok(url.clone());
+        return Ok(url.to_string());
     }
 
     let config = HyperstackConfig::load_optional(config_path)?;
 
     // 2. Explicit --stack name
-    if let Some(stack_name) = &args.stack {
+    if let Some(stack_name) = stack {
         if let Some(config) = &config {
             if let Some(stack) = config.find_stack(stack_name) {
                 if let Some(url) = &stack.url {
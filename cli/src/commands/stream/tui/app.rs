@@ -1,4 +1,4 @@
-use hyperstack_sdk::{parse_snapshot_entities, Frame, Operation};
+use hyperstack_sdk::{parse_snapshot_entities, Frame, Operation, RetentionNotice};
 use ratatui::widgets::ListState;
 use serde_json::Value;
 use std::collections::{HashSet, VecDeque};
@@ -300,6 +300,14 @@ impl App {
             Operation::Subscribed => {
                 self.set_status("Subscribed");
             }
+            Operation::RetentionNotice => {
+                if let Ok(notice) = serde_json::from_value::<RetentionNotice>(frame.data) {
+                    self.set_status(&format!(
+                        "Server keeps the most recent {} entities; older ones are evicted",
+                        notice.cap
+                    ));
+                }
+            }
         }
 
         self.raw_frames
@@ -0,0 +1,351 @@
+//! Post-deploy health watch for `hs up --watch-health`.
+//!
+//! A build completes as soon as the new image is rolled out, while the
+//! deployment may still be backfilling or failing to reach its data source.
+//! The watch polls the deployment's `/readyz` and `/stats` until it reports
+//! ready, its processed slot advances between two polls and it has sent at
+//! least one frame. A ready deployment whose slot stops advancing fails the
+//! watch early; anything else gives up after the timeout.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+use crate::ui;
+
+/// How long a ready deployment may go without its slot advancing
+pub const DEFAULT_STALL_AFTER: Duration = Duration::from_secs(60);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct HealthWatch {
+    pub timeout: Duration,
+    pub stall_after: Duration,
+    pub poll_interval: Duration,
+}
+
+impl HealthWatch {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            stall_after: DEFAULT_STALL_AFTER,
+            poll_interval: Duration::from_secs(ui::STATUS_POLL_INTERVAL_SECS),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Healthy,
+    Stalled,
+    TimedOut,
+}
+
+/// One poll of the deployment
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HealthSample {
+    pub ready: bool,
+    /// Body of `/readyz`, or why it couldn't be fetched
+    pub readiness: String,
+    pub last_slot: Option<u64>,
+    pub frames_sent: Option<u64>,
+    /// The slot went up since the previous poll
+    pub slot_advancing: bool,
+    /// The full `/stats` payload
+    pub stats: Option<Value>,
+}
+
+impl HealthSample {
+    fn is_healthy(&self) -> bool {
+        self.ready && self.slot_advancing && self.frames_sent.unwrap_or(0) > 0
+    }
+}
+
+/// The health document printed by `--json` and when the watch fails
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub url: String,
+    pub verdict: Verdict,
+    pub elapsed_secs: f64,
+    pub polls: u32,
+    pub last: HealthSample,
+}
+
+/// Poll the deployment serving `websocket_url` until it is healthy, stalls
+/// or `watch.timeout` passes, handing every sample to `on_sample`.
+pub fn watch_health(
+    websocket_url: &str,
+    watch: &HealthWatch,
+    mut on_sample: impl FnMut(&HealthSample),
+) -> Result<HealthReport> {
+    let base = http_base_url(websocket_url)?;
+    let client = reqwest::blocking::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Failed to build HTTP client")?;
+
+    let start = Instant::now();
+    let mut polls = 0;
+    let mut highest_slot: Option<u64> = None;
+    let mut was_ready = false;
+    // Last time the slot advanced or the deployment became ready
+    let mut progress_at = start;
+
+    loop {
+        let mut sample = poll(&client, &base);
+        polls += 1;
+
+        if let Some(slot) = sample.last_slot {
+            if highest_slot.is_some_and(|highest| slot > highest) {
+                sample.slot_advancing = true;
+                progress_at = Instant::now();
+            }
+            highest_slot = Some(highest_slot.map_or(slot, |highest| highest.max(slot)));
+        }
+        if sample.ready && !was_ready {
+            progress_at = Instant::now();
+        }
+        was_ready = sample.ready;
+        on_sample(&sample);
+
+        let verdict = if sample.is_healthy() {
+            Some(Verdict::Healthy)
+        } else if sample.ready && progress_at.elapsed() >= watch.stall_after {
+            Some(Verdict::Stalled)
+        } else if start.elapsed() >= watch.timeout {
+            Some(Verdict::TimedOut)
+        } else {
+            None
+        };
+
+        if let Some(verdict) = verdict {
+            return Ok(HealthReport {
+                url: base,
+                verdict,
+                elapsed_secs: start.elapsed().as_secs_f64(),
+                polls,
+                last: sample,
+            });
+        }
+
+        let remaining = watch.timeout.saturating_sub(start.elapsed());
+        std::thread::sleep(watch.poll_interval.min(remaining));
+    }
+}
+
+fn poll(client: &reqwest::blocking::Client, base: &str) -> HealthSample {
+    let (ready, readiness) = match client.get(format!("{}/readyz", base)).send() {
+        Ok(response) => {
+            let ready = response.status().is_success();
+            let body = response.text().unwrap_or_default();
+            (ready, body.trim().to_string())
+        }
+        Err(e) => (false, e.to_string()),
+    };
+
+    let stats = client
+        .get(format!("{}/stats", base))
+        .send()
+        .ok()
+        .filter(|response| response.status().is_success())
+        .and_then(|response| response.json::<Value>().ok());
+
+    HealthSample {
+        ready,
+        readiness,
+        last_slot: stats.as_ref().and_then(|stats| stats["last_slot"].as_u64()),
+        frames_sent: stats
+            .as_ref()
+            .and_then(|stats| stats["frames_sent"].as_u64()),
+        slot_advancing: false,
+        stats,
+    }
+}
+
+/// The HTTP address of the server behind a deployment's WebSocket URL
+pub fn http_base_url(websocket_url: &str) -> Result<String> {
+    let mut url = url::Url::parse(websocket_url)
+        .with_context(|| format!("Invalid deployment URL: {}", websocket_url))?;
+    let scheme = match url.scheme() {
+        "wss" | "https" => "https",
+        "ws" | "http" => "http",
+        other => anyhow::bail!("Unsupported deployment URL scheme: {}", other),
+    };
+    url.set_scheme(scheme)
+        .map_err(|_| anyhow::anyhow!("Invalid deployment URL: {}", websocket_url))?;
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// One progress line for a sample
+pub fn format_sample(sample: &HealthSample) -> String {
+    let readiness = if sample.ready {
+        format!("{} ready", ui::symbols::ACTIVE.green())
+    } else if sample.readiness.is_empty() {
+        format!("{} not ready", ui::symbols::INACTIVE.dimmed())
+    } else {
+        format!(
+            "{} not ready ({})",
+            ui::symbols::INACTIVE.dimmed(),
+            sample.readiness
+        )
+    };
+    let slot = match sample.last_slot {
+        Some(slot) if sample.slot_advancing => format!("slot {} {}", slot, "↑".green()),
+        Some(slot) => format!("slot {}", slot),
+        None => "slot -".to_string(),
+    };
+    let frames = match sample.frames_sent {
+        Some(frames) => format!("frames {}", frames),
+        None => "frames -".to_string(),
+    };
+    format!("  {}  {}  {}", readiness, slot, frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    /// A deployment whose slot moves `slot_step` on every `/stats` request
+    struct MockDeployment {
+        ready: bool,
+        slot: AtomicU64,
+        slot_step: u64,
+        frames_sent: u64,
+    }
+
+    async fn serve(deployment: MockDeployment) -> SocketAddr {
+        let app = Router::new()
+            .route(
+                "/readyz",
+                get(|State(deployment): State<Arc<MockDeployment>>| async move {
+                    if deployment.ready {
+                        (StatusCode::OK, "READY")
+                    } else {
+                        (StatusCode::SERVICE_UNAVAILABLE, "NOT READY")
+                    }
+                }),
+            )
+            .route(
+                "/stats",
+                get(|State(deployment): State<Arc<MockDeployment>>| async move {
+                    let slot = deployment
+                        .slot
+                        .fetch_add(deployment.slot_step, Ordering::Relaxed);
+                    Json(json!({ "last_slot": slot, "frames_sent": deployment.frames_sent }))
+                }),
+            )
+            .with_state(Arc::new(deployment));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    async fn watch(addr: SocketAddr, watch: HealthWatch) -> (HealthReport, usize) {
+        tokio::task::spawn_blocking(move || {
+            let mut samples = 0;
+            let report = watch_health(&format!("ws://{}", addr), &watch, |_| samples += 1)
+                .expect("watch should run");
+            (report, samples)
+        })
+        .await
+        .unwrap()
+    }
+
+    fn quick_watch(timeout: Duration, stall_after: Duration) -> HealthWatch {
+        HealthWatch {
+            timeout,
+            stall_after,
+            poll_interval: Duration::from_millis(20),
+        }
+    }
+
+    #[test]
+    fn test_http_base_url() {
+        assert_eq!(
+            http_base_url("wss://token-abc.stack.usehyperstack.com").unwrap(),
+            "https://token-abc.stack.usehyperstack.com"
+        );
+        assert_eq!(
+            http_base_url("ws://localhost:8878/stream/").unwrap(),
+            "http://localhost:8878/stream"
+        );
+        assert!(http_base_url("ftp://example.com").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn healthy_once_the_slot_advances_and_frames_are_served() {
+        let addr = serve(MockDeployment {
+            ready: true,
+            slot: AtomicU64::new(100),
+            slot_step: 1,
+            frames_sent: 3,
+        })
+        .await;
+
+        let (report, samples) = watch(
+            addr,
+            quick_watch(Duration::from_secs(5), Duration::from_secs(5)),
+        )
+        .await;
+        assert_eq!(report.verdict, Verdict::Healthy);
+        assert_eq!(report.polls, 2, "the slot needs two polls to advance");
+        assert_eq!(samples, 2);
+        assert_eq!(report.last.last_slot, Some(101));
+        assert_eq!(report.last.stats.unwrap()["frames_sent"], json!(3));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_ready_deployment_whose_slot_stops_is_stalled() {
+        let addr = serve(MockDeployment {
+            ready: true,
+            slot: AtomicU64::new(100),
+            slot_step: 0,
+            frames_sent: 3,
+        })
+        .await;
+
+        let (report, _) = watch(
+            addr,
+            quick_watch(Duration::from_secs(5), Duration::from_millis(200)),
+        )
+        .await;
+        assert_eq!(report.verdict, Verdict::Stalled);
+        assert!(report.elapsed_secs < 5.0);
+        assert!(report.last.ready);
+        assert!(!report.last.slot_advancing);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_deployment_that_never_gets_ready_times_out() {
+        let addr = serve(MockDeployment {
+            ready: false,
+            slot: AtomicU64::new(100),
+            slot_step: 1,
+            frames_sent: 0,
+        })
+        .await;
+
+        let (report, samples) = watch(
+            addr,
+            quick_watch(Duration::from_millis(300), Duration::from_millis(50)),
+        )
+        .await;
+        assert_eq!(report.verdict, Verdict::TimedOut);
+        assert!(samples > 1);
+        assert!(!report.last.ready);
+        assert_eq!(report.last.readiness, "NOT READY");
+    }
+}
@@ -1,3 +1,5 @@
+mod health;
+
 use anyhow::Result;
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
@@ -8,6 +10,8 @@ use crate::config::{resolve_stacks_to_push, HyperstackConfig};
 use crate::telemetry;
 use crate::ui;
 
+pub use health::HealthWatch;
+
 fn generate_short_uuid() -> String {
     use std::time::{SystemTime, UNIX_EPOCH};
     let timestamp = SystemTime::now()
@@ -23,6 +27,8 @@ pub fn up(
     branch: Option<String>,
     preview: bool,
     dry_run: bool,
+    watch: Option<HealthWatch>,
+    json: bool,
 ) -> Result<()> {
     let start = std::time::Instant::now();
     let config = HyperstackConfig::load_optional(config_path)?;
@@ -54,7 +60,16 @@ pub fn up(
     }
 
     for ast in &stacks {
-        deploy_single_stack(&client, ast, branch.as_deref())?;
+        let websocket_url = deploy_single_stack(&client, ast, branch.as_deref())?;
+        if let Some(watch) = &watch {
+            let websocket_url = websocket_url.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Build for {} reported no WebSocket URL to watch",
+                    ast.stack_name
+                )
+            })?;
+            watch_deployment_health(&websocket_url, watch, json)?;
+        }
         println!();
     }
 
@@ -137,11 +152,12 @@ fn get_expected_url(client: &Option<ApiClient>, stack_name: &str, branch: Option
     }
 }
 
+/// Deploy one stack, returning the WebSocket URL of its deployment
 fn deploy_single_stack(
     client: &ApiClient,
     ast: &crate::config::DiscoveredAst,
     branch: Option<&str>,
-) -> Result<()> {
+) -> Result<Option<String>> {
     ui::print_divider();
     if let Some(branch_name) = branch {
         println!(
@@ -224,12 +240,10 @@ fn deploy_single_stack(
     ui::print_numbered_step(3, "Building & deploying...");
     println!();
 
-    watch_build_progress(client, build_response.build_id)?;
-
-    Ok(())
+    watch_build_progress(client, build_response.build_id)
 }
 
-fn watch_build_progress(client: &ApiClient, build_id: i32) -> Result<()> {
+fn watch_build_progress(client: &ApiClient, build_id: i32) -> Result<Option<String>> {
     let mut last_phase: Option<String> = None;
     let progress_bar = ProgressBar::new(100);
     progress_bar.set_style(
@@ -297,11 +311,48 @@ fn watch_build_progress(client: &ApiClient, build_id: i32) -> Result<()> {
                 _ => {}
             }
 
-            break;
+            return Ok(build.websocket_url.clone());
         }
 
         std::thread::sleep(Duration::from_millis(ui::DEFAULT_POLL_INTERVAL_MS));
     }
+}
 
-    Ok(())
+fn watch_deployment_health(websocket_url: &str, watch: &HealthWatch, json: bool) -> Result<()> {
+    println!();
+    ui::print_numbered_step(4, "Watching health...");
+
+    let report = health::watch_health(websocket_url, watch, |sample| {
+        let line = health::format_sample(sample);
+        // Keep stdout for the health document under --json
+        if json {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    })?;
+
+    let document = serde_json::to_string_pretty(&report)?;
+    match report.verdict {
+        health::Verdict::Healthy => {
+            if json {
+                println!("{}", document);
+            } else {
+                println!();
+                ui::print_success("Deployment is healthy!");
+            }
+            Ok(())
+        }
+        health::Verdict::Stalled => {
+            println!("{}", document);
+            anyhow::bail!(
+                "Deployment stalled: its slot has not advanced for {}s",
+                watch.stall_after.as_secs()
+            )
+        }
+        health::Verdict::TimedOut => {
+            println!("{}", document);
+            anyhow::bail!("Deployment not healthy after {}s", watch.timeout.as_secs())
+        }
+    }
 }
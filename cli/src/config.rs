@@ -30,6 +30,10 @@ pub struct HyperstackConfig {
 
     #[serde(default)]
     pub build: Option<BuildConfig>,
+
+    /// IDLs fetched with `hs idl fetch --pin`, by program
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub idls: Vec<IdlPinConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +76,22 @@ fn default_watch() -> bool {
     true
 }
 
+/// An IDL pinned to a content hash, see `hyperstack_idl::snapshot::pin`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IdlPinConfig {
+    pub program_id: String,
+
+    /// Where the IDL is written, relative to hyperstack.toml
+    pub path: String,
+
+    /// Content hash the fetched IDL must match
+    pub hash: String,
+
+    /// URL to fetch the IDL from instead of the program's IDL account
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StackConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -110,6 +130,13 @@ impl HyperstackConfig {
         Ok(config)
     }
 
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let contents = toml::to_string_pretty(self)?;
+        fs::write(path, contents)
+            .with_context(|| format!("Failed to write config file: {}", path.display()))
+    }
+
     pub fn load_optional<P: AsRef<Path>>(path: P) -> Result<Option<Self>> {
         let path = path.as_ref();
         if !path.exists() {
@@ -132,6 +159,15 @@ impl HyperstackConfig {
             }
         }
 
+        let mut programs = HashSet::new();
+        for idl in &self.idls {
+            if !programs.insert(idl.program_id.as_str()) {
+                anyhow::bail!("Duplicate IDL pin for program: {}", idl.program_id);
+            }
+            hyperstack_idl::snapshot::pin::validate_pin(&idl.hash)
+                .with_context(|| format!("Invalid IDL pin for program {}", idl.program_id))?;
+        }
+
         Ok(())
     }
 
@@ -149,6 +185,22 @@ impl HyperstackConfig {
         })
     }
 
+    pub fn find_idl(&self, program_id: &str) -> Option<&IdlPinConfig> {
+        self.idls.iter().find(|idl| idl.program_id == program_id)
+    }
+
+    /// Record `pin`, replacing any earlier pin of its program
+    pub fn pin_idl(&mut self, pin: IdlPinConfig) {
+        match self
+            .idls
+            .iter_mut()
+            .find(|idl| idl.program_id == pin.program_id)
+        {
+            Some(existing) => *existing = pin,
+            None => self.idls.push(pin),
+        }
+    }
+
     pub fn get_output_dir(&self) -> &str {
         self.sdk
             .as_ref()
@@ -425,6 +477,35 @@ pub fn resolve_stacks_to_push(
 mod tests {
     use super::*;
 
+    #[test]
+    fn idl_pins_round_trip_and_replace_by_program() {
+        let mut config: HyperstackConfig = toml::from_str(
+            r#"
+            [project]
+            name = "demo"
+
+            [[idls]]
+            program_id = "oreV3EG1i9BEgiAJ8b177Z2S2rMarzak4NMv1kULvWv"
+            path = "idl/ore.json"
+            hash = "sha256:0000000000000000000000000000000000000000000000000000000000000000"
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let mut pin = config.idls[0].clone();
+        pin.hash = format!("sha256:{}", "1".repeat(64));
+        config.pin_idl(pin.clone());
+        assert_eq!(config.idls, vec![pin.clone()]);
+
+        let reloaded: HyperstackConfig =
+            toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(reloaded.find_idl(&pin.program_id), Some(&pin));
+
+        config.idls[0].hash = "sha256:nope".to_string();
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_to_kebab_case() {
         assert_eq!(to_kebab_case("SettlementGame"), "settlement-game");
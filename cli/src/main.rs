@@ -16,6 +16,7 @@
 //! - `hs stack list` - List all stacks
 //! - `hs stack show` - Show stack details
 //! - `hs sdk create` - Generate TypeScript/Rust SDK
+//! - `hs doctor` - Diagnose environment and connectivity problems
 //!
 //! See `hs --help` for the full command reference.
 
@@ -28,6 +29,7 @@ use std::process;
 mod api_client;
 mod commands;
 mod config;
+mod skeleton;
 mod telemetry;
 mod templates;
 mod ui;
@@ -68,10 +70,26 @@ enum Commands {
         /// Project name (creates directory)
         name: Option<String>,
 
-        /// Template: react-ore, rust-ore
+        /// Template: react-ore, rust-ore, typescript-ore, custom
         #[arg(short, long)]
         template: Option<String>,
 
+        /// Program to build the custom template's stack for
+        #[arg(long)]
+        program_id: Option<String>,
+
+        /// IDL for the custom template: a file path or URL (default: the program's IDL account)
+        #[arg(long, value_name = "PATH|URL")]
+        idl: Option<String>,
+
+        /// Solana RPC endpoint to read the program's IDL account from
+        #[arg(
+            long,
+            env = "SOLANA_RPC_URL",
+            default_value = "https://api.mainnet-beta.solana.com"
+        )]
+        rpc_url: String,
+
         /// Use cached templates only (no network)
         #[arg(long)]
         offline: bool,
@@ -104,11 +122,23 @@ enum Commands {
         /// Show what would be deployed without actually deploying
         #[arg(long)]
         dry_run: bool,
+
+        /// After deploying, wait until the deployment is ready, its slot is
+        /// advancing and it has served a frame
+        #[arg(long)]
+        watch_health: bool,
+
+        /// Seconds to wait for a healthy deployment with --watch-health
+        #[arg(long, default_value = "300", requires = "watch_health")]
+        health_timeout: u64,
     },
 
     /// Show overview of stacks, builds, and deployments
     Status,
 
+    /// Diagnose your environment: config, stack files, auth, and connectivity
+    Doctor,
+
     /// Discover stacks and explore their schemas
     Explore {
         /// Stack name to explore
@@ -153,6 +183,24 @@ enum Commands {
 
     /// Stream live entity data from a deployed stack via WebSocket
     Stream(commands::stream::StreamArgs),
+
+    /// Interactively query a deployed stack (get, list, watch, schema)
+    Inspect(commands::inspect::InspectArgs),
+
+    /// Load-test a deployed stack's WebSocket endpoint
+    Bench(commands::bench::BenchArgs),
+
+    /// Dynamic completion backend called by the `--completions` scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        /// Refresh the cached remote stack list instead of completing
+        #[arg(long)]
+        refresh: bool,
+
+        /// Command line words, ending with the word being completed
+        #[arg(last = true)]
+        words: Vec<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -183,6 +231,11 @@ enum CreateCommands {
         /// WebSocket URL for the stack (overrides config)
         #[arg(long)]
         url: Option<String>,
+
+        /// How the server sends 64-bit integers: number, auto or string.
+        /// With string, fields typed as 64-bit integers are typed `string`.
+        #[arg(long, default_value = "auto")]
+        big_numbers: hyperstack_interpreter::BigNumberMode,
     },
 
     /// Generate Rust SDK crate
@@ -205,6 +258,15 @@ enum CreateCommands {
         /// WebSocket URL for the stack (overrides config)
         #[arg(long)]
         url: Option<String>,
+
+        /// Also generate a command line binary for querying the stack's views
+        #[arg(long, conflicts_with = "module")]
+        with_cli: bool,
+
+        /// Also generate an ignored test checking a live deployment against the
+        /// stack, run with `cargo test --test live_contract -- --ignored`
+        #[arg(long, conflicts_with = "module")]
+        with_contract_tests: bool,
     },
 }
 
@@ -342,6 +404,39 @@ enum StackCommands {
         #[arg(short, long)]
         force: bool,
     },
+
+    /// Assemble a redacted debug bundle of a running stack and download it
+    DebugBundle {
+        /// Name of the stack
+        stack_name: String,
+
+        /// Base URL of the stack's admin endpoints (default: the deployed stack's URL)
+        #[arg(long)]
+        url: Option<String>,
+
+        /// File to write the bundle to (default: <stack>-debug-<timestamp>.tar.gz)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Seconds to wait for the bundle to be assembled
+        #[arg(long, default_value = "300")]
+        timeout: u64,
+    },
+
+    /// Train a zstd compression dictionary on frames recorded with `hs stream --save`
+    TrainDict {
+        /// Recordings to train on
+        #[arg(required = true)]
+        recordings: Vec<String>,
+
+        /// File to write the dictionary to
+        #[arg(short, long, default_value = "frames.dict")]
+        output: String,
+
+        /// Largest dictionary size in bytes
+        #[arg(long, default_value = "32768")]
+        max_size: usize,
+    },
 }
 
 #[derive(Subcommand)]
@@ -414,8 +509,15 @@ fn main() {
     }
 
     if let Some(shell) = cli.completions {
-        let mut cmd = Cli::command();
-        generate(shell, &mut cmd, "hs", &mut io::stdout());
+        match commands::complete::script(shell) {
+            Some(script) => print!("{}", script),
+            None => generate(shell, &mut Cli::command(), "hs", &mut io::stdout()),
+        }
+        return;
+    }
+
+    if let Some(Commands::Complete { refresh, words }) = &cli.command {
+        let _ = commands::complete::complete(&Cli::command(), &cli.config, words, *refresh);
         return;
     }
 
@@ -451,6 +553,7 @@ fn command_name(cmd: &Commands) -> &'static str {
         Commands::Init => "init",
         Commands::Up { .. } => "up",
         Commands::Status => "status",
+        Commands::Doctor => "doctor",
         Commands::Explore { .. } => "explore",
         Commands::Push { .. } => "push",
         Commands::Sdk(_) => "sdk",
@@ -461,6 +564,9 @@ fn command_name(cmd: &Commands) -> &'static str {
         Commands::Telemetry(_) => "telemetry",
         Commands::Idl(_) => "idl",
         Commands::Stream(_) => "stream",
+        Commands::Inspect(_) => "inspect",
+        Commands::Bench(_) => "bench",
+        Commands::Complete { .. } => "__complete",
     }
 }
 
@@ -474,18 +580,43 @@ fn run(cli: Cli) -> anyhow::Result<()> {
         Commands::Create {
             name,
             template,
+            program_id,
+            idl,
+            rpc_url,
             offline,
             force_refresh,
             skip_install,
-        } => commands::create::create(name, template, offline, force_refresh, skip_install),
+        } => commands::create::create(
+            name,
+            template,
+            program_id,
+            idl,
+            &rpc_url,
+            offline,
+            force_refresh,
+            skip_install,
+        ),
         Commands::Init => commands::config::init(&cli.config),
         Commands::Up {
             stack_name,
             branch,
             preview,
             dry_run,
-        } => commands::up::up(&cli.config, stack_name.as_deref(), branch, preview, dry_run),
+            watch_health,
+            health_timeout,
+        } => commands::up::up(
+            &cli.config,
+            stack_name.as_deref(),
+            branch,
+            preview,
+            dry_run,
+            watch_health.then(|| {
+                commands::up::HealthWatch::new(std::time::Duration::from_secs(health_timeout))
+            }),
+            cli.json,
+        ),
         Commands::Status => commands::status::status(cli.json),
+        Commands::Doctor => commands::doctor::doctor(&cli.config, cli.json),
         Commands::Explore { name, entity } => match name {
             Some(name) => commands::explore::show(&name, entity.as_deref(), cli.json),
             None => commands::explore::list(cli.json),
@@ -498,12 +629,14 @@ fn run(cli: Cli) -> anyhow::Result<()> {
                     output,
                     package_name,
                     url,
+                    big_numbers,
                 } => commands::sdk::create_typescript(
                     &cli.config,
                     &stack_name,
                     output,
                     package_name,
                     url,
+                    big_numbers,
                 ),
                 CreateCommands::Rust {
                     stack_name,
@@ -511,6 +644,8 @@ fn run(cli: Cli) -> anyhow::Result<()> {
                     crate_name,
                     module,
                     url,
+                    with_cli,
+                    with_contract_tests,
                 } => commands::sdk::create_rust(
                     &cli.config,
                     &stack_name,
@@ -518,6 +653,8 @@ fn run(cli: Cli) -> anyhow::Result<()> {
                     crate_name,
                     module,
                     url,
+                    with_cli,
+                    with_contract_tests,
                 ),
             },
             SdkCommands::List => commands::sdk::list(&cli.config),
@@ -568,6 +705,22 @@ fn run(cli: Cli) -> anyhow::Result<()> {
                 branch,
                 force,
             } => commands::stack::stop(&stack_name, branch.as_deref(), force),
+            StackCommands::DebugBundle {
+                stack_name,
+                url,
+                output,
+                timeout,
+            } => commands::stack::debug_bundle(
+                &stack_name,
+                url.as_deref(),
+                output.as_deref(),
+                timeout,
+            ),
+            StackCommands::TrainDict {
+                recordings,
+                output,
+                max_size,
+            } => commands::stack::train_dict(&recordings, &output, max_size),
         },
         Commands::Build(build_cmd) => match build_cmd {
             BuildCommands::Create {
@@ -591,8 +744,13 @@ fn run(cli: Cli) -> anyhow::Result<()> {
                 json,
             } => commands::build::status(build_id, watch, json || cli.json),
         },
-        Commands::Idl(args) => commands::idl::run(args),
+        Commands::Idl(args) => commands::idl::run(args, &cli.config),
         Commands::Stream(args) => commands::stream::run(args, &cli.config),
+        Commands::Inspect(args) => commands::inspect::run(args, &cli.config),
+        Commands::Bench(args) => commands::bench::run(args, &cli.config, cli.json),
+        Commands::Complete { refresh, words } => {
+            commands::complete::complete(&Cli::command(), &cli.config, &words, refresh)
+        }
         Commands::Telemetry(telemetry_cmd) => match telemetry_cmd {
             TelemetryCommands::Status => commands::telemetry::status(),
             TelemetryCommands::Enable => commands::telemetry::enable(),
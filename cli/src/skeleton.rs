@@ -0,0 +1,354 @@
+//! Starter stack source generated from a program's IDL, for
+//! `hs create --template custom`.
+//!
+//! Each account type becomes an entity keyed by the account address, with a
+//! commented-out `#[map]` line for every field of the account. Field types
+//! follow what the stack receives for the IDL type: public keys and 128-bit
+//! integers arrive as strings, fixed arrays as vectors.
+
+use hyperstack_idl::types::{
+    to_snake_case, IdlAccount, IdlField, IdlSpec, IdlType, IdlTypeArrayElement, IdlTypeDefKind,
+    IdlTypeDefinedInner,
+};
+
+/// Name of the stack module for a project, e.g. `my_app_stream`
+pub fn module_name(project_name: &str) -> String {
+    let words: Vec<String> = project_name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| to_snake_case(word).to_lowercase())
+        .collect();
+    let name = if words.is_empty() {
+        "stack".to_string()
+    } else {
+        words.join("_")
+    };
+    let name = if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("stack_{}", name)
+    } else {
+        name
+    };
+    format!("{}_stream", name)
+}
+
+/// Name the stack is deployed under, e.g. `MyAppStream` for `my_app_stream`
+pub fn stack_name(module_name: &str) -> String {
+    module_name
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Module the `#[hyperstack]` macro generates the program's types into
+fn sdk_module(idl: &IdlSpec) -> String {
+    format!("{}_sdk", idl.get_name())
+}
+
+/// Source of a `#[hyperstack]` module for `idl`, read from `idl_path`
+/// relative to the crate root
+pub fn generate_stack_module(idl: &IdlSpec, idl_path: &str, module_name: &str) -> String {
+    let sdk = sdk_module(idl);
+    let mut out = String::new();
+
+    out.push_str("use hyperstack::prelude::*;\n\n");
+    out.push_str(&format!("#[hyperstack(idl = \"{}\")]\n", idl_path));
+    out.push_str(&format!("pub mod {} {{\n", module_name));
+
+    if idl.accounts.is_empty() {
+        out.push_str(&format!(
+            "    // {} declares no accounts. Add entities that map its instructions\n    // with #[map({}::instructions::Name::field)].\n",
+            idl.get_name(),
+            sdk
+        ));
+    }
+
+    for (index, account) in idl.accounts.iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        push_entity(&mut out, idl, &sdk, account);
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn push_entity(out: &mut String, idl: &IdlSpec, sdk: &str, account: &IdlAccount) {
+    let name = &account.name;
+    let path = format!("{}::accounts::{}", sdk, name);
+
+    for doc in &account.docs {
+        out.push_str(&format!("    /// {}\n", doc.trim()));
+    }
+    out.push_str(&format!("    #[entity(name = \"{}\")]\n", name));
+    out.push_str(&format!("    pub struct {} {{\n", name));
+    out.push_str(&format!(
+        "        #[map({}::__account_address, primary_key, strategy = SetOnce)]\n",
+        path
+    ));
+    out.push_str("        pub address: String,\n");
+
+    let fields = account_fields(idl, account);
+    if fields.is_empty() {
+        out.push_str("        // The IDL doesn't describe this account's fields\n");
+    }
+    for field in fields {
+        let field_type = rust_type(&field.type_, sdk);
+        out.push('\n');
+        // The path names the field as the IDL does, the struct in snake case
+        out.push_str(&format!(
+            "        // #[map({}::{}, strategy = LastWrite)]\n",
+            path, field.name
+        ));
+        out.push_str(&format!(
+            "        // pub {}: Option<{}>,\n",
+            to_snake_case(&field.name),
+            field_type
+        ));
+    }
+
+    out.push_str("    }\n");
+}
+
+/// Fields of an account, from its own type (Steel) or the type of the same
+/// name (Anchor)
+fn account_fields<'a>(idl: &'a IdlSpec, account: &'a IdlAccount) -> &'a [IdlField] {
+    let kind = account.type_def.as_ref().or_else(|| {
+        idl.types
+            .iter()
+            .find(|type_def| type_def.name == account.name)
+            .map(|type_def| &type_def.type_def)
+    });
+    match kind {
+        Some(IdlTypeDefKind::Struct { fields, .. }) => fields,
+        _ => &[],
+    }
+}
+
+/// `Cargo.toml` of the generated crate, which builds the stack and the
+/// server that runs it
+pub fn generate_cargo_toml(project_name: &str) -> String {
+    let version = env!("CARGO_PKG_VERSION");
+    format!(
+        r#"[package]
+name = "{project_name}"
+version = "0.1.0"
+edition = "2021"
+publish = false
+
+[dependencies]
+hyperstack = {{ version = "{version}", features = ["full"] }}
+tokio = {{ version = "1.0", features = ["full"] }}
+anyhow = "1.0"
+dotenvy = "0.15"
+tracing-subscriber = {{ version = "0.3", features = ["env-filter"] }}
+
+# Required for entity structs and IDL-generated types
+serde = {{ version = "1.0", features = ["derive"] }}
+serde_json = "1.0"
+borsh = {{ version = "1.5", features = ["derive"] }}
+solana-pubkey = {{ version = "2.2", features = ["serde", "borsh"] }}
+
+# TLS crypto provider for rustls (required by yellowstone-vixen/tonic)
+rustls = {{ version = "0.23", default-features = false, features = ["ring"] }}
+"#
+    )
+}
+
+/// `src/main.rs` serving the stack in `src/stack.rs` over WebSocket
+pub fn generate_server_main(module_name: &str) -> String {
+    format!(
+        r#"mod stack;
+
+use hyperstack::server::Server;
+use stack::{module_name};
+use std::net::SocketAddr;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {{
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .expect("Failed to install rustls crypto provider");
+
+    dotenvy::dotenv().ok();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+
+    println!("Starting server on [::]:8878...");
+
+    Server::builder()
+        .spec({module_name}::spec())
+        .websocket()
+        .bind("[::]:8878".parse::<SocketAddr>()?)
+        .health_monitoring()
+        .start()
+        .await?;
+
+    Ok(())
+}}
+"#
+    )
+}
+
+/// `.env.example` listing what the server reads from the environment
+pub fn generate_env_example() -> String {
+    r#"# Yellowstone gRPC endpoint streaming the program's accounts and transactions
+YELLOWSTONE_ENDPOINT=
+# Access token for the endpoint, if it needs one
+YELLOWSTONE_X_TOKEN=
+
+RUST_LOG=info
+"#
+    .to_string()
+}
+
+/// Rust type of an entity field holding a value of `idl_type`
+pub fn rust_type(idl_type: &IdlType, sdk: &str) -> String {
+    match idl_type {
+        IdlType::Simple(name) => match name.as_str() {
+            "bool" | "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32" | "i64" | "f32"
+            | "f64" => name.clone(),
+            "u128" | "i128" | "pubkey" | "publicKey" | "string" => "String".to_string(),
+            "bytes" => "Vec<u8>".to_string(),
+            _ => "serde_json::Value".to_string(),
+        },
+        // The entity field is optional already
+        IdlType::Option(option) => rust_type(&option.option, sdk),
+        IdlType::Vec(vec) => format!("Vec<{}>", rust_type(&vec.vec, sdk)),
+        IdlType::Array(array) => match array.array.first() {
+            Some(IdlTypeArrayElement::Nested(item)) => format!("Vec<{}>", rust_type(item, sdk)),
+            Some(IdlTypeArrayElement::Type(item)) => {
+                format!("Vec<{}>", rust_type(&IdlType::Simple(item.clone()), sdk))
+            }
+            _ => "serde_json::Value".to_string(),
+        },
+        IdlType::HashMap(_) => "serde_json::Value".to_string(),
+        IdlType::Defined(defined) => {
+            let name = match &defined.defined {
+                IdlTypeDefinedInner::Named { name } => name,
+                IdlTypeDefinedInner::Simple(name) => name,
+            };
+            format!("{}::types::{}", sdk, name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../tests/fixtures/vault_idl.json");
+
+    fn fixture() -> IdlSpec {
+        serde_json::from_str(FIXTURE).expect("fixture IDL should parse")
+    }
+
+    #[test]
+    fn names_follow_the_project() {
+        assert_eq!(module_name("my-vaults"), "my_vaults_stream");
+        assert_eq!(module_name("MyVaults"), "my_vaults_stream");
+        assert_eq!(module_name("2024 app"), "stack_2024_app_stream");
+        assert_eq!(stack_name("my_vaults_stream"), "MyVaultsStream");
+    }
+
+    #[test]
+    fn idl_types_translate_to_entity_field_types() {
+        let ty = |json: &str| rust_type(&serde_json::from_str(json).unwrap(), "vault_sdk");
+
+        assert_eq!(ty(r#""u64""#), "u64");
+        assert_eq!(ty(r#""pubkey""#), "String");
+        assert_eq!(ty(r#""publicKey""#), "String");
+        assert_eq!(ty(r#""u128""#), "String");
+        assert_eq!(ty(r#""bytes""#), "Vec<u8>");
+        assert_eq!(ty(r#"{ "option": "i64" }"#), "i64");
+        assert_eq!(ty(r#"{ "vec": "pubkey" }"#), "Vec<String>");
+        assert_eq!(ty(r#"{ "array": ["u8", 32] }"#), "Vec<u8>");
+        assert_eq!(ty(r#"{ "array": [{ "vec": "u16" }, 4] }"#), "Vec<Vec<u16>>");
+        assert_eq!(
+            ty(r#"{ "defined": { "name": "Fees" } }"#),
+            "vault_sdk::types::Fees"
+        );
+        assert_eq!(ty(r#"{ "defined": "Fees" }"#), "vault_sdk::types::Fees");
+        assert_eq!(
+            ty(r#"{ "hashMap": ["string", "u64"] }"#),
+            "serde_json::Value"
+        );
+    }
+
+    #[test]
+    fn every_account_becomes_an_entity_with_its_fields_commented_out() {
+        let source = generate_stack_module(&fixture(), "idl/vault.json", "vaults_stream");
+
+        assert!(source.starts_with(
+            "use hyperstack::prelude::*;\n\n#[hyperstack(idl = \"idl/vault.json\")]\npub mod vaults_stream {\n"
+        ));
+
+        // Anchor accounts take their fields from the type of the same name
+        assert!(source.contains(
+            "    /// A vault holding deposits for one owner\n    #[entity(name = \"Vault\")]\n    pub struct Vault {\n        #[map(vault_sdk::accounts::Vault::__account_address, primary_key, strategy = SetOnce)]\n        pub address: String,\n"
+        ));
+        assert!(source.contains(
+            "        // #[map(vault_sdk::accounts::Vault::owner, strategy = LastWrite)]\n        // pub owner: Option<String>,\n"
+        ));
+        assert!(source.contains(
+            "        // #[map(vault_sdk::accounts::Vault::totalDeposited, strategy = LastWrite)]\n        // pub total_deposited: Option<String>,\n"
+        ));
+        assert!(source.contains("        // pub fees: Option<vault_sdk::types::Fees>,\n"));
+        assert!(source.contains("        // pub history: Option<Vec<u64>>,\n"));
+
+        // Steel accounts carry their fields inline
+        assert!(source.contains(
+            "        // #[map(vault_sdk::accounts::Config::admin, strategy = LastWrite)]\n        // pub admin: Option<String>,\n"
+        ));
+
+        // Accounts the IDL gives no fields for still get a keyed entity
+        assert!(source.contains(
+            "    pub struct Receipt {\n        #[map(vault_sdk::accounts::Receipt::__account_address, primary_key, strategy = SetOnce)]\n        pub address: String,\n        // The IDL doesn't describe this account's fields\n    }\n"
+        ));
+
+        // Only the key is mapped until fields are uncommented
+        assert_eq!(source.matches("\n        #[map(").count(), 3);
+        assert!(source.ends_with("    }\n}\n"));
+    }
+
+    #[test]
+    fn server_runs_the_generated_module() {
+        let main = generate_server_main("vaults_stream");
+        assert!(main.starts_with("mod stack;\n"));
+        assert!(main.contains("use stack::vaults_stream;\n"));
+        assert!(main.contains(".spec(vaults_stream::spec())"));
+
+        let manifest = generate_cargo_toml("my-vaults");
+        assert!(manifest.contains("name = \"my-vaults\"\n"));
+        assert!(manifest.contains(&format!(
+            "hyperstack = {{ version = \"{}\", features = [\"full\"] }}",
+            env!("CARGO_PKG_VERSION")
+        )));
+
+        let env = generate_env_example();
+        assert!(env.contains("YELLOWSTONE_ENDPOINT=\n"));
+        assert!(env.contains("YELLOWSTONE_X_TOKEN=\n"));
+    }
+
+    #[test]
+    fn idl_without_accounts_explains_what_to_map() {
+        let mut idl = fixture();
+        idl.accounts.clear();
+
+        let source = generate_stack_module(&idl, "idl/vault.json", "vaults_stream");
+
+        assert!(source.contains("// vault declares no accounts."));
+        assert!(!source.contains("#[entity"));
+    }
+}
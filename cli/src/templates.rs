@@ -1,26 +1,38 @@
 //! Template fetching, caching, and extraction for `hs create`.
 //!
 //! Templates are downloaded from GitHub releases and cached locally
-//! in `~/.hyperstack/templates/{version}/`.
+//! in `~/.hyperstack/templates/{version}/`. The `custom` template is
+//! generated from a program's IDL instead, see [`write_custom_project`].
 
 use anyhow::{Context, Result};
 use flate2::read::GzDecoder;
+use hyperstack_idl::snapshot::fetch::FetchedIdl;
+use hyperstack_idl::types::IdlSpec;
 use std::fs::{self, File};
 use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 use tar::Archive;
 
+use crate::config::{HyperstackConfig, IdlPinConfig, ProjectConfig, StackConfig};
+use crate::skeleton;
+
 /// Available project templates.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Template {
     React,
     Rust,
     Typescript,
+    Custom,
 }
 
 impl Template {
     /// All available templates.
-    pub const ALL: &'static [Template] = &[Template::React, Template::Rust, Template::Typescript];
+    pub const ALL: &'static [Template] = &[
+        Template::React,
+        Template::Rust,
+        Template::Typescript,
+        Template::Custom,
+    ];
 
     /// Template directory name (as stored in tarball).
     pub fn dir_name(&self) -> &'static str {
@@ -28,6 +40,7 @@ impl Template {
             Template::React => "ore-react",
             Template::Rust => "ore-rust",
             Template::Typescript => "ore-typescript",
+            Template::Custom => "custom",
         }
     }
 
@@ -37,6 +50,7 @@ impl Template {
             Template::React => "react-ore",
             Template::Rust => "rust-ore",
             Template::Typescript => "typescript-ore",
+            Template::Custom => "custom",
         }
     }
 
@@ -46,6 +60,7 @@ impl Template {
             Template::React => "ORE mining rounds viewer (React + Vite)",
             Template::Rust => "ORE mining rounds client (Rust + Tokio)",
             Template::Typescript => "ORE mining rounds client (TypeScript CLI)",
+            Template::Custom => "Stack and server for your own program, from its IDL",
         }
     }
 
@@ -55,6 +70,7 @@ impl Template {
             "react-ore" | "ore-react" => Some(Template::React),
             "rust-ore" | "ore-rust" => Some(Template::Rust),
             "typescript-ore" | "ore-typescript" | "ts-ore" | "ore-ts" => Some(Template::Typescript),
+            "custom" => Some(Template::Custom),
             _ => None,
         }
     }
@@ -66,6 +82,11 @@ impl Template {
     pub fn is_typescript_cli(&self) -> bool {
         matches!(self, Template::Typescript)
     }
+
+    /// Whether the template is generated rather than downloaded.
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Template::Custom)
+    }
 }
 
 /// Template manager handles fetching, caching, and extracting templates.
@@ -153,6 +174,7 @@ impl TemplateManager {
             let path_str = path.to_string_lossy();
             let is_template = Template::ALL
                 .iter()
+                .filter(|t| !t.is_custom())
                 .any(|t| path_str.starts_with(t.dir_name()));
 
             if !is_template {
@@ -323,6 +345,82 @@ fn copy_env_example(project_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Generate a `custom` project for the program whose IDL is `idl`.
+///
+/// Writes the IDL to `idl/<name>.json`, a stack module under `src/stack.rs`
+/// with one entity per account, a server in `src/main.rs`, and a
+/// `hyperstack.toml` that pins the IDL for `hs idl fetch`.
+pub fn write_custom_project(
+    project_dir: &Path,
+    project_name: &str,
+    program_id: &str,
+    idl: &FetchedIdl,
+    registry: Option<String>,
+) -> Result<()> {
+    let spec: IdlSpec = serde_json::from_slice(&idl.json).context("Failed to parse IDL")?;
+    let idl_path = format!("idl/{}.json", idl.name);
+    let module_name = skeleton::module_name(project_name);
+
+    idl.write(&project_dir.join(&idl_path))?;
+
+    let src_dir = project_dir.join("src");
+    fs::create_dir_all(&src_dir)
+        .with_context(|| format!("Failed to create directory: {:?}", src_dir))?;
+
+    let files = [
+        (
+            project_dir.join("Cargo.toml"),
+            skeleton::generate_cargo_toml(project_name),
+        ),
+        (
+            src_dir.join("stack.rs"),
+            skeleton::generate_stack_module(&spec, &idl_path, &module_name),
+        ),
+        (
+            src_dir.join("main.rs"),
+            skeleton::generate_server_main(&module_name),
+        ),
+        (
+            project_dir.join(".env.example"),
+            skeleton::generate_env_example(),
+        ),
+        (
+            project_dir.join(".gitignore"),
+            "/target\n.env\n".to_string(),
+        ),
+    ];
+    for (path, contents) in files {
+        fs::write(&path, contents).with_context(|| format!("Failed to write {:?}", path))?;
+    }
+
+    let config = HyperstackConfig {
+        project: ProjectConfig {
+            name: project_name.to_string(),
+        },
+        stacks: vec![StackConfig {
+            name: Some(project_name.to_string()),
+            stack: skeleton::stack_name(&module_name),
+            description: None,
+            typescript_output_file: None,
+            rust_output_crate: None,
+            rust_module: None,
+            url: None,
+        }],
+        sdk: None,
+        build: None,
+        idls: vec![IdlPinConfig {
+            program_id: program_id.to_string(),
+            path: idl_path,
+            hash: idl.hash.clone(),
+            registry,
+        }],
+    };
+    config.save(project_dir.join("hyperstack.toml"))?;
+
+    copy_env_example(project_dir)?;
+    Ok(())
+}
+
 /// Detect package manager: first from npm_config_user_agent (when run via npx/pnpm dlx/etc),
 /// then by checking which package managers are installed on the system.
 pub fn detect_package_manager() -> &'static str {
@@ -25,6 +25,18 @@ fn print_treasury(treasury: &OreTreasury) {
     println!();
 }
 
+/// The first round the view delivers, if it has been assigned an id.
+async fn first_round(view: ViewHandle<OreRound>) -> Option<OreRound> {
+    let round = view.listen().next().await?;
+    round.id.round_id.is_some().then_some(round)
+}
+
+/// The first treasury the view delivers, if it has an address.
+async fn first_treasury(view: ViewHandle<OreTreasury>) -> Option<OreTreasury> {
+    let treasury = view.listen().next().await?;
+    treasury.id.address.is_some().then_some(treasury)
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let hs = HyperStack::<OreStreamStack>::builder()
@@ -38,23 +50,69 @@ async fn main() -> anyhow::Result<()> {
     let treasury_view = hs.views.ore_treasury.list();
 
     let round_handle = tokio::spawn(async move {
-        let mut stream = round_view.listen().take(1);
-        while let Some(round) = stream.next().await {
-            if round.id.round_id.is_some() {
-                print_round(&round);
-            }
+        if let Some(round) = first_round(round_view).await {
+            print_round(&round);
         }
     });
 
     let treasury_handle = tokio::spawn(async move {
-        let mut stream = treasury_view.listen().take(1);
-        while let Some(treasury) = stream.next().await {
-            if treasury.id.address.is_some() {
-                print_treasury(&treasury);
-            }
+        if let Some(treasury) = first_treasury(treasury_view).await {
+            print_treasury(&treasury);
         }
     });
 
     let _ = tokio::join!(round_handle, treasury_handle);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(round_id: Option<u64>) -> OreRound {
+        let mut round = OreRound::default();
+        round.id.round_id = round_id;
+        round
+    }
+
+    #[tokio::test]
+    async fn first_round_returns_the_latest_round() {
+        let mock = MockHyperStack::<OreStreamStack>::new();
+        let consumer = tokio::spawn(first_round(mock.views.ore_round.latest()));
+
+        mock.wait_for_subscription("OreRound/latest").await;
+        mock.views.ore_round.latest().push(round(Some(42))).await;
+
+        let round = consumer.await.unwrap().expect("round should be delivered");
+        assert_eq!(round.id.round_id, Some(42));
+        assert_eq!(mock.subscriptions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn first_round_skips_rounds_without_an_id() {
+        let mock = MockHyperStack::<OreStreamStack>::new();
+        let consumer = tokio::spawn(first_round(mock.views.ore_round.latest()));
+
+        mock.wait_for_subscription("OreRound/latest").await;
+        mock.views.ore_round.latest().push(round(None)).await;
+
+        assert!(consumer.await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn first_treasury_reads_the_treasury_list() {
+        let mock = MockHyperStack::<OreStreamStack>::new();
+        let consumer = tokio::spawn(first_treasury(mock.views.ore_treasury.list()));
+
+        mock.wait_for_subscription("OreTreasury/list").await;
+        let mut treasury = OreTreasury::default();
+        treasury.id.address = Some("treasury".to_string());
+        mock.views.ore_treasury.list().push(treasury).await;
+
+        let treasury = consumer
+            .await
+            .unwrap()
+            .expect("treasury should be delivered");
+        assert_eq!(treasury.id.address.as_deref(), Some("treasury"));
+    }
+}
@@ -71,3 +71,82 @@ fn available_label(section: &str) -> String {
         format!("Available {}", section)
     }
 }
+
+/// Errors from pinning IDLs to a content hash and fetching them, see
+/// [`snapshot::pin`](crate::snapshot::pin)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdlSnapshotError {
+    /// Not JSON, or JSON that isn't an IDL
+    InvalidIdl {
+        reason: String,
+    },
+    /// A pin that isn't `sha256:` followed by 64 hex digits
+    InvalidPin {
+        pin: String,
+    },
+    /// The IDL's content hash differs from its pin
+    HashMismatch {
+        expected: String,
+        actual: String,
+    },
+    InvalidProgramId {
+        program_id: String,
+    },
+    /// The program has no on-chain IDL account
+    AccountNotFound {
+        program_id: String,
+        address: String,
+    },
+    /// An IDL account whose data can't be decoded
+    InvalidAccount {
+        address: String,
+        reason: String,
+    },
+    /// A request to the RPC node or registry failed
+    Transport {
+        url: String,
+        reason: String,
+    },
+    Io {
+        path: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for IdlSnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdlSnapshotError::InvalidIdl { reason } => write!(f, "Invalid IDL: {}", reason),
+            IdlSnapshotError::InvalidPin { pin } => write!(
+                f,
+                "Invalid IDL hash '{}'. Expected sha256: followed by 64 hex digits",
+                pin
+            ),
+            IdlSnapshotError::HashMismatch { expected, actual } => write!(
+                f,
+                "IDL hash {} does not match the pinned {}",
+                actual, expected
+            ),
+            IdlSnapshotError::InvalidProgramId { program_id } => {
+                write!(f, "Invalid program id '{}'", program_id)
+            }
+            IdlSnapshotError::AccountNotFound {
+                program_id,
+                address,
+            } => write!(
+                f,
+                "Program {} has no IDL account at {}. Was its IDL published with `anchor idl init`?",
+                program_id, address
+            ),
+            IdlSnapshotError::InvalidAccount { address, reason } => {
+                write!(f, "Invalid IDL account {}: {}", address, reason)
+            }
+            IdlSnapshotError::Transport { url, reason } => {
+                write!(f, "Request to {} failed: {}", url, reason)
+            }
+            IdlSnapshotError::Io { path, reason } => write!(f, "{}: {}", path, reason),
+        }
+    }
+}
+
+impl std::error::Error for IdlSnapshotError {}
@@ -50,6 +50,34 @@ pub enum InstructionFieldKind {
 pub struct InstructionFieldLookup<'a> {
     pub instruction: &'a IdlInstruction,
     pub kind: InstructionFieldKind,
+    /// The field's name as the IDL spells it
+    pub name: &'a str,
+}
+
+/// An instruction account found by [`lookup_instruction_account`]
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionAccountLookup<'a> {
+    pub instruction: &'a IdlInstruction,
+    /// The account's name as the IDL spells it
+    pub name: &'a str,
+    /// Position of the account in the instruction's account list
+    pub index: usize,
+    /// Whether the name wasn't in the IDL and the account was found by
+    /// position instead
+    pub by_position: bool,
+}
+
+/// Whether two names refer to the same item regardless of case and of
+/// snake or camel case, so `bondingCurve`, `BondingCurve` and
+/// `bonding_curve` all match.
+pub fn names_match(a: &str, b: &str) -> bool {
+    let normalized = |name: &str| {
+        name.chars()
+            .filter(|c| *c != '_')
+            .map(|c| c.to_ascii_lowercase())
+            .collect::<String>()
+    };
+    a.eq_ignore_ascii_case(b) || normalized(a) == normalized(b)
 }
 
 fn build_not_found_error(input: &str, section: String, available: Vec<String>) -> IdlSearchError {
@@ -112,26 +140,29 @@ pub fn lookup_instruction_field<'a>(
     field_name: &str,
 ) -> Result<InstructionFieldLookup<'a>, IdlSearchError> {
     let instruction = lookup_instruction(idl, instruction_name)?;
-    // Use case-insensitive matching to stay consistent with lookup_instruction.
-    if instruction
+    // Accounts match in any case or style, as program upgrades rename them
+    // between conventions; args stay case-insensitive like lookup_instruction.
+    if let Some(account) = instruction
         .accounts
         .iter()
-        .any(|account| account.name.eq_ignore_ascii_case(field_name))
+        .find(|account| names_match(&account.name, field_name))
     {
         return Ok(InstructionFieldLookup {
             instruction,
             kind: InstructionFieldKind::Account,
+            name: &account.name,
         });
     }
 
-    if instruction
+    if let Some(arg) = instruction
         .args
         .iter()
-        .any(|arg| arg.name.eq_ignore_ascii_case(field_name))
+        .find(|arg| arg.name.eq_ignore_ascii_case(field_name))
     {
         return Ok(InstructionFieldLookup {
             instruction,
             kind: InstructionFieldKind::Arg,
+            name: &arg.name,
         });
     }
 
@@ -148,6 +179,53 @@ pub fn lookup_instruction_field<'a>(
     ))
 }
 
+/// Find an instruction account by name, or by its position `index` when
+/// the IDL has no account by that name, as after a program upgrade renamed
+/// it.
+pub fn lookup_instruction_account<'a>(
+    idl: &'a IdlSpec,
+    instruction_name: &str,
+    account_name: &str,
+    index: Option<usize>,
+) -> Result<InstructionAccountLookup<'a>, IdlSearchError> {
+    let instruction = lookup_instruction(idl, instruction_name)?;
+    if let Some((position, account)) = instruction
+        .accounts
+        .iter()
+        .enumerate()
+        .find(|(_, account)| names_match(&account.name, account_name))
+    {
+        return Ok(InstructionAccountLookup {
+            instruction,
+            name: &account.name,
+            index: position,
+            by_position: false,
+        });
+    }
+
+    if let Some((position, account)) =
+        index.and_then(|index| Some((index, instruction.accounts.get(index)?)))
+    {
+        return Ok(InstructionAccountLookup {
+            instruction,
+            name: &account.name,
+            index: position,
+            by_position: true,
+        });
+    }
+
+    let available = instruction
+        .accounts
+        .iter()
+        .map(|account| account.name.clone())
+        .collect();
+    Err(build_not_found_error(
+        account_name,
+        format!("accounts of instruction '{}'", instruction.name),
+        available,
+    ))
+}
+
 /// Suggest similar names from a list of candidates using fuzzy matching.
 ///
 /// Returns candidates sorted by edit distance (closest first).
@@ -377,4 +455,39 @@ mod tests {
             lookup_account(&idl, "bondingCurve").expect("should match case-insensitively");
         assert_eq!(account.name, "BondingCurve");
     }
+
+    #[test]
+    fn test_account_names_match_across_conventions() {
+        assert!(names_match("bondingCurve", "bonding_curve"));
+        assert!(names_match("BondingCurve", "bonding_curve"));
+        assert!(names_match("bonding_curve", "bonding_curve"));
+        assert!(!names_match("bondingCurve", "associated_bonding_curve"));
+    }
+
+    #[test]
+    fn test_lookup_instruction_account_by_name_or_position() {
+        use crate::parse::parse_idl_file;
+        use std::path::PathBuf;
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pump.json");
+        let idl = parse_idl_file(&path).expect("should parse");
+
+        for name in ["bondingCurve", "bonding_curve"] {
+            let account = lookup_instruction_account(&idl, "buy", name, None).unwrap();
+            assert_eq!(account.name, "bonding_curve");
+            assert_eq!(account.index, 3);
+            assert!(!account.by_position);
+
+            let field = lookup_instruction_field(&idl, "buy", name).unwrap();
+            assert_eq!(field.kind, InstructionFieldKind::Account);
+            assert_eq!(field.name, "bonding_curve");
+        }
+
+        let account = lookup_instruction_account(&idl, "buy", "curve", Some(3)).unwrap();
+        assert_eq!(account.name, "bonding_curve");
+        assert!(account.by_position);
+
+        assert!(lookup_instruction_account(&idl, "buy", "curve", None).is_err());
+        assert!(lookup_instruction_account(&idl, "buy", "curve", Some(40)).is_err());
+    }
 }
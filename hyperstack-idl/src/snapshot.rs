@@ -1,4 +1,10 @@
-//! Snapshot type definitions
+//! Snapshot type definitions, and pinning IDL files to a content hash
+//! ([`pin`]) with fetching them from the chain or a registry ([`fetch`],
+//! with the `fetch` feature)
+
+#[cfg(feature = "fetch")]
+pub mod fetch;
+pub mod pin;
 
 use serde::{de::Error as DeError, Deserialize, Deserializer, Serialize};
 
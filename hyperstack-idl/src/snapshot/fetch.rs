@@ -0,0 +1,184 @@
+//! Fetching a program's IDL from its on-chain Anchor IDL account or from a
+//! registry URL, checking it against a pin and writing it locally.
+//!
+//! Requests go through the caller's [`IdlTransport`]: this crate decodes and
+//! verifies what comes back but never opens a connection itself.
+
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use flate2::read::ZlibDecoder;
+use serde_json::Value;
+use solana_pubkey::Pubkey;
+
+use super::pin::{hash_value, validate_pin};
+use crate::error::IdlSnapshotError;
+use crate::types::IdlSpec;
+
+/// Seed of the account Anchor stores a program's IDL in
+const IDL_SEED: &str = "anchor:idl";
+
+/// Account discriminator, authority and data length before the IDL
+const IDL_ACCOUNT_HEADER: usize = 8 + 32 + 4;
+
+/// Where to fetch an IDL from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdlSource {
+    /// The Anchor IDL account of `program_id`, read through `rpc_url`
+    OnChain { program_id: String, rpc_url: String },
+    /// The IDL JSON served at `url`
+    Registry { url: String },
+}
+
+/// Makes the requests a fetch needs
+pub trait IdlTransport {
+    /// Data of the account at `address`, or `None` when it doesn't exist
+    fn account_data(&self, rpc_url: &str, address: &str) -> Result<Option<Vec<u8>>, String>;
+
+    /// Body of a GET request to `url`
+    fn get(&self, url: &str) -> Result<Vec<u8>, String>;
+}
+
+/// A fetched IDL, checked and ready to write
+#[derive(Debug, Clone)]
+pub struct FetchedIdl {
+    /// The IDL as pretty-printed JSON
+    pub json: Vec<u8>,
+    /// Its pin, see [`pin`](super::pin)
+    pub hash: String,
+    /// The program it describes, when the IDL names its address
+    pub program_id: Option<String>,
+    pub name: String,
+}
+
+impl FetchedIdl {
+    /// Write the IDL to `path`, creating its directory
+    pub fn write(&self, path: &Path) -> Result<(), IdlSnapshotError> {
+        let io_error = |error: std::io::Error| IdlSnapshotError::Io {
+            path: path.display().to_string(),
+            reason: error.to_string(),
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        std::fs::write(path, &self.json).map_err(io_error)
+    }
+}
+
+/// Address of the Anchor IDL account of `program_id`
+pub fn idl_address(program_id: &str) -> Result<String, IdlSnapshotError> {
+    let program = Pubkey::from_str(program_id).map_err(|_| IdlSnapshotError::InvalidProgramId {
+        program_id: program_id.to_string(),
+    })?;
+    let (base, _) = Pubkey::find_program_address(&[], &program);
+    let address = Pubkey::create_with_seed(&base, IDL_SEED, &program).map_err(|error| {
+        IdlSnapshotError::InvalidProgramId {
+            program_id: format!("{} ({})", program_id, error),
+        }
+    })?;
+    Ok(address.to_string())
+}
+
+/// The IDL JSON held in the data of an Anchor IDL account: after the
+/// discriminator and authority, a little-endian `u32` length and that many
+/// bytes of zlib-compressed JSON
+pub fn decode_idl_account(address: &str, data: &[u8]) -> Result<Vec<u8>, IdlSnapshotError> {
+    let invalid = |reason: String| IdlSnapshotError::InvalidAccount {
+        address: address.to_string(),
+        reason,
+    };
+    let Some(header) = data.get(..IDL_ACCOUNT_HEADER) else {
+        return Err(invalid(format!(
+            "{} bytes is shorter than the {}-byte header",
+            data.len(),
+            IDL_ACCOUNT_HEADER
+        )));
+    };
+    let len = u32::from_le_bytes(header[40..44].try_into().expect("4 bytes")) as usize;
+    let compressed = data
+        .get(IDL_ACCOUNT_HEADER..IDL_ACCOUNT_HEADER + len)
+        .ok_or_else(|| {
+            invalid(format!(
+                "declares {} bytes of IDL but holds {}",
+                len,
+                data.len() - IDL_ACCOUNT_HEADER
+            ))
+        })?;
+
+    let mut json = Vec::new();
+    ZlibDecoder::new(compressed)
+        .read_to_end(&mut json)
+        .map_err(|error| invalid(format!("failed to decompress the IDL: {}", error)))?;
+    Ok(json)
+}
+
+/// Fetch the IDL from `source` and check it against `pin`, when given
+pub fn fetch_idl(
+    source: &IdlSource,
+    transport: &dyn IdlTransport,
+    pin: Option<&str>,
+) -> Result<FetchedIdl, IdlSnapshotError> {
+    if let Some(pin) = pin {
+        validate_pin(pin)?;
+    }
+
+    let json = match source {
+        IdlSource::OnChain {
+            program_id,
+            rpc_url,
+        } => {
+            let address = idl_address(program_id)?;
+            let data = transport
+                .account_data(rpc_url, &address)
+                .map_err(|reason| IdlSnapshotError::Transport {
+                    url: rpc_url.clone(),
+                    reason,
+                })?
+                .ok_or_else(|| IdlSnapshotError::AccountNotFound {
+                    program_id: program_id.clone(),
+                    address: address.clone(),
+                })?;
+            decode_idl_account(&address, &data)?
+        }
+        IdlSource::Registry { url } => {
+            transport
+                .get(url)
+                .map_err(|reason| IdlSnapshotError::Transport {
+                    url: url.clone(),
+                    reason,
+                })?
+        }
+    };
+
+    checked_idl(&json, pin)
+}
+
+/// Parse fetched IDL JSON, making sure it is an IDL that matches `pin`
+pub fn checked_idl(json: &[u8], pin: Option<&str>) -> Result<FetchedIdl, IdlSnapshotError> {
+    let invalid = |error: serde_json::Error| IdlSnapshotError::InvalidIdl {
+        reason: error.to_string(),
+    };
+    let value: Value = serde_json::from_slice(json).map_err(invalid)?;
+    let spec: IdlSpec = serde_json::from_value(value.clone()).map_err(invalid)?;
+
+    let hash = hash_value(&value);
+    if let Some(pin) = pin.filter(|pin| *pin != hash) {
+        return Err(IdlSnapshotError::HashMismatch {
+            expected: pin.to_string(),
+            actual: hash,
+        });
+    }
+
+    let mut json = serde_json::to_vec_pretty(&value).map_err(invalid)?;
+    json.push(b'\n');
+    Ok(FetchedIdl {
+        json,
+        hash,
+        program_id: spec
+            .address
+            .clone()
+            .or_else(|| spec.metadata.as_ref().and_then(|m| m.address.clone())),
+        name: spec.get_name().to_string(),
+    })
+}
@@ -0,0 +1,138 @@
+//! Content hashes that pin an IDL file to a known version.
+//!
+//! A pin is `sha256:` followed by the hex SHA-256 of the IDL's JSON with
+//! object keys sorted and no whitespace. Reformatting the file or changing
+//! its line endings keeps the pin; changing any value breaks it.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::error::IdlSnapshotError;
+
+/// Algorithm prefix of every pin
+pub const PIN_PREFIX: &str = "sha256:";
+
+/// The pin of the IDL JSON in `json`
+pub fn content_hash(json: &[u8]) -> Result<String, IdlSnapshotError> {
+    let value: Value =
+        serde_json::from_slice(json).map_err(|error| IdlSnapshotError::InvalidIdl {
+            reason: error.to_string(),
+        })?;
+    Ok(hash_value(&value))
+}
+
+/// The pin of an already parsed IDL
+pub fn hash_value(value: &Value) -> String {
+    let mut canonical = String::new();
+    write_canonical(value, &mut canonical);
+    let digest = Sha256::digest(canonical.as_bytes());
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}{}", PIN_PREFIX, hex)
+}
+
+/// Check that `pin` is a well-formed pin
+pub fn validate_pin(pin: &str) -> Result<(), IdlSnapshotError> {
+    let valid = pin.strip_prefix(PIN_PREFIX).is_some_and(|hex| {
+        hex.len() == 64
+            && hex
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(IdlSnapshotError::InvalidPin {
+            pin: pin.to_string(),
+        })
+    }
+}
+
+/// Check the IDL JSON in `json` against `pin`, returning its hash
+pub fn verify_pin(json: &[u8], pin: &str) -> Result<String, IdlSnapshotError> {
+    validate_pin(pin)?;
+    let actual = content_hash(json)?;
+    if actual == pin {
+        Ok(actual)
+    } else {
+        Err(IdlSnapshotError::HashMismatch {
+            expected: pin.to_string(),
+            actual,
+        })
+    }
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDL: &str = r#"{"name": "demo", "instructions": [], "accounts": []}"#;
+
+    #[test]
+    fn formatting_and_key_order_keep_the_hash() {
+        let reformatted =
+            "{\r\n  \"accounts\": [],\r\n  \"instructions\": [],\r\n  \"name\": \"demo\"\r\n}\r\n";
+        let hash = content_hash(IDL.as_bytes()).unwrap();
+
+        assert!(hash.starts_with(PIN_PREFIX));
+        assert_eq!(validate_pin(&hash), Ok(()));
+        assert_eq!(content_hash(reformatted.as_bytes()).unwrap(), hash);
+        assert_ne!(
+            content_hash(br#"{"name": "demo2", "instructions": [], "accounts": []}"#).unwrap(),
+            hash
+        );
+    }
+
+    #[test]
+    fn verify_pin_reports_mismatches_and_malformed_pins() {
+        let hash = content_hash(IDL.as_bytes()).unwrap();
+        assert_eq!(verify_pin(IDL.as_bytes(), &hash), Ok(hash.clone()));
+
+        let other = format!("{}{}", PIN_PREFIX, "0".repeat(64));
+        assert_eq!(
+            verify_pin(IDL.as_bytes(), &other),
+            Err(IdlSnapshotError::HashMismatch {
+                expected: other.clone(),
+                actual: hash,
+            })
+        );
+
+        for pin in ["md5:abc", "sha256:ABC", &other[..other.len() - 1]] {
+            assert_eq!(
+                verify_pin(IDL.as_bytes(), pin),
+                Err(IdlSnapshotError::InvalidPin {
+                    pin: pin.to_string()
+                })
+            );
+        }
+    }
+}
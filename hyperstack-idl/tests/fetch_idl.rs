@@ -0,0 +1,154 @@
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use hyperstack_idl::error::IdlSnapshotError;
+use hyperstack_idl::snapshot::fetch::{
+    decode_idl_account, fetch_idl, idl_address, IdlSource, IdlTransport,
+};
+use hyperstack_idl::snapshot::pin::content_hash;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+const PROGRAM_ID: &str = "oreV3EG1i9BEgiAJ8b177Z2S2rMarzak4NMv1kULvWv";
+const RPC_URL: &str = "http://rpc.test";
+
+fn fixture() -> Vec<u8> {
+    std::fs::read(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join("entropy.json"),
+    )
+    .unwrap()
+}
+
+/// Data of an Anchor IDL account holding `json`, with `slack` unused bytes
+/// after it as in an account allocated larger than its IDL
+fn idl_account(json: &[u8], slack: usize) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(json).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut data = vec![0u8; 8 + 32];
+    data.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+    data.extend_from_slice(&compressed);
+    data.extend(std::iter::repeat_n(0, slack));
+    data
+}
+
+/// Answers from fixed accounts and URLs, failing anything else
+#[derive(Default)]
+struct FixtureTransport {
+    accounts: HashMap<String, Vec<u8>>,
+    urls: HashMap<String, Vec<u8>>,
+}
+
+impl IdlTransport for FixtureTransport {
+    fn account_data(&self, rpc_url: &str, address: &str) -> Result<Option<Vec<u8>>, String> {
+        assert_eq!(rpc_url, RPC_URL);
+        Ok(self.accounts.get(address).cloned())
+    }
+
+    fn get(&self, url: &str) -> Result<Vec<u8>, String> {
+        self.urls
+            .get(url)
+            .cloned()
+            .ok_or_else(|| "404 Not Found".to_string())
+    }
+}
+
+fn on_chain() -> IdlSource {
+    IdlSource::OnChain {
+        program_id: PROGRAM_ID.to_string(),
+        rpc_url: RPC_URL.to_string(),
+    }
+}
+
+#[test]
+fn idl_account_address_is_derived_from_the_program() {
+    assert_eq!(
+        idl_address(PROGRAM_ID).unwrap(),
+        "8cgErqyohriorxbaiMZgLuiShxh29hhdbKjgDer55v54"
+    );
+    assert_eq!(
+        idl_address("not-a-key"),
+        Err(IdlSnapshotError::InvalidProgramId {
+            program_id: "not-a-key".to_string()
+        })
+    );
+}
+
+#[test]
+fn fetches_and_pins_the_on_chain_idl() {
+    let json = fixture();
+    let pin = content_hash(&json).unwrap();
+    let address = idl_address(PROGRAM_ID).unwrap();
+    let transport = FixtureTransport {
+        accounts: HashMap::from([(address, idl_account(&json, 64))]),
+        ..Default::default()
+    };
+
+    let fetched = fetch_idl(&on_chain(), &transport, None).unwrap();
+    assert_eq!(fetched.hash, pin);
+    assert_eq!(fetched.name, "entropy");
+    // Rewritten as pretty JSON, which keeps the pin
+    assert_eq!(content_hash(&fetched.json).unwrap(), pin);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("idl/entropy.json");
+    fetched.write(&path).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), fetched.json);
+
+    assert!(fetch_idl(&on_chain(), &transport, Some(&pin)).is_ok());
+}
+
+#[test]
+fn a_changed_idl_fails_its_pin() {
+    let json = fixture();
+    let pin = content_hash(&json).unwrap();
+    let mut changed: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    changed["instructions"].as_array_mut().unwrap().pop();
+    let changed = serde_json::to_vec(&changed).unwrap();
+
+    let url = "https://registry.test/entropy.json".to_string();
+    let transport = FixtureTransport {
+        urls: HashMap::from([(url.clone(), changed.clone())]),
+        ..Default::default()
+    };
+    let registry = IdlSource::Registry { url };
+
+    assert_eq!(
+        fetch_idl(&registry, &transport, Some(&pin)).unwrap_err(),
+        IdlSnapshotError::HashMismatch {
+            expected: pin,
+            actual: content_hash(&changed).unwrap(),
+        }
+    );
+}
+
+#[test]
+fn missing_and_malformed_accounts_are_reported() {
+    let address = idl_address(PROGRAM_ID).unwrap();
+    assert_eq!(
+        fetch_idl(&on_chain(), &FixtureTransport::default(), None).unwrap_err(),
+        IdlSnapshotError::AccountNotFound {
+            program_id: PROGRAM_ID.to_string(),
+            address: address.clone(),
+        }
+    );
+
+    let mut truncated = idl_account(&fixture(), 0);
+    truncated.truncate(truncated.len() - 10);
+    assert!(matches!(
+        decode_idl_account(&address, &truncated),
+        Err(IdlSnapshotError::InvalidAccount { .. })
+    ));
+
+    let not_an_idl = FixtureTransport {
+        accounts: HashMap::from([(address, idl_account(br#"{"name": 1}"#, 0))]),
+        ..Default::default()
+    };
+    assert!(matches!(
+        fetch_idl(&on_chain(), &not_an_idl, None),
+        Err(IdlSnapshotError::InvalidIdl { .. })
+    ));
+}
@@ -38,13 +38,38 @@ pub enum Transformation {
     HexDecode,
     Base58Encode,
     Base58Decode,
+    Base64Encode,
+    Base64Decode,
     ToString,
     ToNumber,
+    /// Elements `start..end` of an array, e.g. a range of bytes
+    Slice {
+        start: usize,
+        end: usize,
+    },
+    /// The first `n` elements of an array, or the whole array if shorter
+    Take(usize),
+    /// Transformations applied in order, each to the previous one's output
+    Chain(Vec<Transformation>),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ValueEncoding {
+    /// Transformation that stores a raw value with this encoding. Decimal
+    /// strings need none, the generated parsers already emit them.
+    pub fn transformation(self) -> Option<Transformation> {
+        match self {
+            ValueEncoding::DecimalString => None,
+            ValueEncoding::Base58 => Some(Transformation::Base58Encode),
+            ValueEncoding::Base64 => Some(Transformation::Base64Encode),
+        }
+    }
+}
+
+/// Write policy applied when a mapping updates its target field
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum PopulationStrategy {
     SetOnce,
+    #[default]
     LastWrite,
     Append,
     Merge,
@@ -58,6 +83,9 @@ pub enum PopulationStrategy {
     /// Track unique values and store the count
     /// Internally maintains a HashSet, exposes only the count
     UniqueCount,
+    /// Unset the field. The mapped value is ignored; the field becomes null
+    /// and the patch carries the null so caches and clients drop it
+    Clear,
 }
 
 /// Default discriminant size (8 bytes for Anchor).
@@ -74,6 +102,8 @@ pub struct ComputedFieldSpec {
     pub target_path: String,
     pub expression: ComputedExpr,
     pub result_type: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub time_dependent: bool,
 }
 
 // ==========================================================================
@@ -123,6 +153,8 @@ pub struct ResolverExtractSpec {
     pub source_path: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub transform: Option<Transformation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
@@ -260,12 +292,52 @@ pub enum ComputedExpr {
     // Context access - slot and timestamp from the update that triggered evaluation
     ContextSlot,
     ContextTimestamp,
+    Now,
+
+    // Slot/time conversions using the configured slot duration
+    SlotsToSecs {
+        expr: Box<ComputedExpr>,
+    },
+    SecsToSlots {
+        expr: Box<ComputedExpr>,
+    },
 
     /// Keccak256 hash function for computing Ethereum-compatible hashes
     /// Takes a byte array expression and returns the 32-byte hash as a Vec<u8>
     Keccak256 {
         expr: Box<ComputedExpr>,
     },
+
+    /// Aggregate over an append array: `array_sum`, `array_last` or
+    /// `array_window_avg`
+    ArrayAggregate {
+        aggregate: ArrayAggregateSpec,
+    },
+}
+
+/// Aggregate over the items of an append array.
+///
+/// Sums and window averages are maintained incrementally by the append that
+/// writes the array, rather than by rescanning it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ArrayAggregateSpec {
+    pub kind: ArrayAggregateKind,
+    /// Path of the append array, e.g. "trades.history"
+    pub path: String,
+    /// Field of each item to aggregate; the item itself when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrayAggregateKind {
+    /// Sum over every item in the array
+    Sum,
+    /// The last item appended
+    Last,
+    /// Mean over the last `n` items
+    WindowAvg { n: usize },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -341,6 +413,44 @@ pub struct SerializableStreamSpec {
     pub content_hash: Option<String>,
     #[serde(default)]
     pub views: Vec<ViewDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trace_fields: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature: Option<String>,
+    #[serde(default, skip_serializing_if = "EntityPriority::is_normal")]
+    pub priority: EntityPriority,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delete_on: Option<DeleteTrigger>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_normalizer: Option<ComputedExpr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<ItemDocs>,
+    /// Event types routed to this entity by its handlers and instruction
+    /// hooks, sorted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event_types: Vec<String>,
+}
+
+/// Priority of an entity's updates when the server sheds load.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl EntityPriority {
+    pub fn is_normal(&self) -> bool {
+        *self == EntityPriority::Normal
+    }
+}
+
+/// What deletes an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeleteTrigger {
+    AccountClosed,
 }
 
 fn default_ast_version() -> String {
@@ -357,6 +467,8 @@ pub struct IdentitySpec {
 pub struct LookupIndexSpec {
     pub field_name: String,
     pub temporal_field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_index: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -406,6 +518,7 @@ pub struct SerializableFieldMapping {
     pub target_path: String,
     pub source: MappingSource,
     pub transform: Option<Transformation>,
+    #[serde(default)]
     pub population: PopulationStrategy,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<ConditionExpr>,
@@ -415,6 +528,22 @@ pub struct SerializableFieldMapping {
     pub stop: Option<String>,
     #[serde(default = "default_emit", skip_serializing_if = "is_true")]
     pub emit: bool,
+    /// Set when the source is an instruction account the IDL no longer has
+    /// under the name the mapping was written with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_fallback: Option<AccountFallback>,
+}
+
+/// An instruction account a mapping found by its position, because the IDL
+/// it was built against names the account differently
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountFallback {
+    pub instruction: String,
+    /// The name the mapping was written with
+    pub written: String,
+    /// The name the IDL gives the account at `index`
+    pub resolved: String,
+    pub index: usize,
 }
 
 fn default_emit() -> bool {
@@ -450,6 +579,9 @@ pub enum MappingSource {
     FromContext {
         field: String,
     },
+    FromTransaction {
+        field: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -474,6 +606,12 @@ pub struct EntitySection {
     pub is_nested_struct: bool,
     #[serde(default)]
     pub parent_field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<ItemDocs>,
+    /// Name of the struct this section was imported from with
+    /// `#[section(external = ...)]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_type: Option<String>,
 }
 
 /// Language-agnostic type information for fields
@@ -491,8 +629,22 @@ pub struct FieldTypeInfo {
     /// Resolved type information for complex types (instructions, accounts, custom types)
     #[serde(default)]
     pub resolved_type: Option<ResolvedStructType>,
+    /// How the value is encoded in entity state, when it isn't stored as-is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<ValueEncoding>,
     #[serde(default = "default_emit", skip_serializing_if = "is_true")]
     pub emit: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<ItemDocs>,
+}
+
+/// Documentation from `///` comments and `#[doc_meta(...)]` on a spec item
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemDocs {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
 }
 
 /// Resolved structure type with field information from IDL
@@ -519,6 +671,19 @@ pub struct ResolvedField {
     pub base_type: BaseType,
     pub is_optional: bool,
     pub is_array: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<ValueEncoding>,
+}
+
+/// String encoding of a value that doesn't fit a plain JSON number or array
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValueEncoding {
+    /// `u128`/`i128` as a decimal string, since JSON numbers lose precision
+    DecimalString,
+    /// Fixed byte array as a base58 string, e.g. a 64-byte signature
+    Base58,
+    /// Fixed byte array as a base64 string
+    Base64,
 }
 
 /// Language-agnostic base type classification
@@ -555,6 +720,10 @@ pub enum ResolverStrategy {
     DirectField {
         field_path: FieldPath,
     },
+    KeyFrom {
+        field_path: FieldPath,
+        index_name: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -600,6 +769,15 @@ pub enum ParsedCondition {
         op: LogicalOp,
         conditions: Vec<ParsedCondition>,
     },
+    Changed {
+        field: FieldPath,
+        #[serde(default = "default_changed_initial")]
+        initial: bool,
+    },
+}
+
+fn default_changed_initial() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -946,6 +1124,9 @@ pub struct SerializableStackSpec {
     pub pdas: BTreeMap<String, BTreeMap<String, PdaDefinition>>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub instructions: Vec<InstructionDef>,
+    /// Cargo features that were active when the spec was generated
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub content_hash: Option<String>,
 }
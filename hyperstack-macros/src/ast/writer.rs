@@ -51,19 +51,77 @@ pub fn write_stack_to_file(
     Ok(())
 }
 
-/// Helper function to parse transformation string to enum
+/// Helper function to parse transformation string to enum.
+///
+/// Accepts the variant names (`HexEncode`), their snake_case spelling
+/// (`hex_encode`), the parameterized `slice(start, end)` and `take(n)`, and a
+/// bracketed chain of those (`[base58_decode, slice(0, 8), hex_encode]`).
 pub fn parse_transformation(transform_str: &str) -> Option<Transformation> {
+    let transform_str = transform_str.trim();
+    if let Some(stages) = transform_str
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+    {
+        return split_transformation_chain(stages)
+            .into_iter()
+            .map(parse_transformation)
+            .collect::<Option<Vec<_>>>()
+            .filter(|stages| !stages.is_empty())
+            .map(Transformation::Chain);
+    }
+
+    if let Some((name, args)) = transform_str
+        .strip_suffix(')')
+        .and_then(|s| s.split_once('('))
+    {
+        let args = args
+            .split(',')
+            .map(|arg| arg.trim().parse::<usize>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        return match (name.trim(), args.as_slice()) {
+            ("slice" | "Slice", &[start, end]) if start <= end => {
+                Some(Transformation::Slice { start, end })
+            }
+            ("take" | "Take", &[n]) => Some(Transformation::Take(n)),
+            _ => None,
+        };
+    }
+
     match transform_str {
-        "HexEncode" => Some(Transformation::HexEncode),
-        "HexDecode" => Some(Transformation::HexDecode),
-        "Base58Encode" => Some(Transformation::Base58Encode),
-        "Base58Decode" => Some(Transformation::Base58Decode),
-        "ToString" => Some(Transformation::ToString),
-        "ToNumber" => Some(Transformation::ToNumber),
+        "HexEncode" | "hex_encode" => Some(Transformation::HexEncode),
+        "HexDecode" | "hex_decode" => Some(Transformation::HexDecode),
+        "Base58Encode" | "base58_encode" => Some(Transformation::Base58Encode),
+        "Base58Decode" | "base58_decode" => Some(Transformation::Base58Decode),
+        "Base64Encode" | "base64_encode" => Some(Transformation::Base64Encode),
+        "Base64Decode" | "base64_decode" => Some(Transformation::Base64Decode),
+        "ToString" | "to_string" => Some(Transformation::ToString),
+        "ToNumber" | "to_number" => Some(Transformation::ToNumber),
         _ => None,
     }
 }
 
+/// Split the stages of a chain on the commas outside of stage arguments
+fn split_transformation_chain(stages: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in stages.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&stages[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !stages[start..].trim().is_empty() || !parts.is_empty() {
+        parts.push(&stages[start..]);
+    }
+    parts
+}
+
 /// Helper function to parse population strategy string to enum
 pub fn parse_population_strategy(strategy_str: &str) -> PopulationStrategy {
     match strategy_str {
@@ -76,6 +134,7 @@ pub fn parse_population_strategy(strategy_str: &str) -> PopulationStrategy {
         "Count" => PopulationStrategy::Count,
         "Min" => PopulationStrategy::Min,
         "UniqueCount" => PopulationStrategy::UniqueCount,
+        "Clear" => PopulationStrategy::Clear,
         _ => PopulationStrategy::LastWrite, // Default fallback
     }
 }
@@ -427,6 +486,10 @@ pub fn build_handlers_from_sources(
                 };
 
                 MappingSource::AsCapture { field_transforms }
+            } else if let Some(field) = mapping.transaction_field() {
+                MappingSource::FromTransaction {
+                    field: field.to_string(),
+                }
             } else {
                 let field_path = if is_cpi_event {
                     // CPI events: all fields are under "data"
@@ -498,6 +561,7 @@ pub fn build_handlers_from_sources(
                 when,
                 stop,
                 emit: mapping.emit,
+                account_fallback: None,
             });
 
             if mapping.is_primary_key {
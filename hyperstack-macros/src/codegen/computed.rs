@@ -7,7 +7,7 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use std::collections::{HashMap, HashSet};
 
-use crate::ast::{BinaryOp, ComputedExpr, ComputedFieldSpec, UnaryOp};
+use crate::ast::{ArrayAggregateKind, BinaryOp, ComputedExpr, ComputedFieldSpec, UnaryOp};
 
 /// Extract field dependencies from a computed expression.
 /// Returns a set of field names (without section prefix) that this expression depends on.
@@ -92,8 +92,16 @@ fn extract_deps_recursive(expr: &ComputedExpr, section: &str, deps: &mut HashSet
         ComputedExpr::JsonToBytes { expr } => {
             extract_deps_recursive(expr, section, deps);
         }
-        ComputedExpr::ContextSlot | ComputedExpr::ContextTimestamp => {}
-        ComputedExpr::Keccak256 { expr } => {
+        ComputedExpr::ContextSlot | ComputedExpr::ContextTimestamp | ComputedExpr::Now => {}
+        ComputedExpr::ArrayAggregate { aggregate } => {
+            let parts: Vec<&str> = aggregate.path.split('.').collect();
+            if parts.len() >= 2 && parts[0] == section {
+                deps.insert(parts[1].to_string());
+            }
+        }
+        ComputedExpr::Keccak256 { expr }
+        | ComputedExpr::SlotsToSecs { expr }
+        | ComputedExpr::SecsToSlots { expr } => {
             extract_deps_recursive(expr, section, deps);
         }
     }
@@ -108,7 +116,9 @@ fn contains_resolver_computed(expr: &ComputedExpr) -> bool {
         | ComputedExpr::Var { .. }
         | ComputedExpr::ByteArray { .. }
         | ComputedExpr::ContextSlot
-        | ComputedExpr::ContextTimestamp => false,
+        | ComputedExpr::ContextTimestamp
+        | ComputedExpr::Now
+        | ComputedExpr::ArrayAggregate { .. } => false,
         ComputedExpr::UnwrapOr { expr, .. }
         | ComputedExpr::Cast { expr, .. }
         | ComputedExpr::Paren { expr }
@@ -119,6 +129,8 @@ fn contains_resolver_computed(expr: &ComputedExpr) -> bool {
         | ComputedExpr::U64FromBeBytes { bytes: expr }
         | ComputedExpr::JsonToBytes { expr }
         | ComputedExpr::Keccak256 { expr }
+        | ComputedExpr::SlotsToSecs { expr }
+        | ComputedExpr::SecsToSlots { expr }
         | ComputedExpr::Unary { expr, .. } => contains_resolver_computed(expr),
         ComputedExpr::Binary { left, right, .. } => {
             contains_resolver_computed(left) || contains_resolver_computed(right)
@@ -652,10 +664,22 @@ pub fn generate_computed_expr_code(expr: &ComputedExpr) -> TokenStream {
             // __context_slot is Option<u64>, unwrap to u64 with 0 as default
             quote! { __context_slot.unwrap_or(0) }
         }
-        ComputedExpr::ContextTimestamp => {
+        ComputedExpr::ContextTimestamp | ComputedExpr::Now => {
             // __context_timestamp is i64, use directly
             quote! { __context_timestamp }
         }
+        ComputedExpr::SlotsToSecs { expr } => {
+            let inner = generate_computed_expr_code(expr);
+            quote! {
+                hyperstack::runtime::hyperstack_interpreter::block_time_cache::slots_to_secs((#inner) as i64)
+            }
+        }
+        ComputedExpr::SecsToSlots { expr } => {
+            let inner = generate_computed_expr_code(expr);
+            quote! {
+                hyperstack::runtime::hyperstack_interpreter::block_time_cache::secs_to_slots((#inner) as i64)
+            }
+        }
         ComputedExpr::Keccak256 { expr } => {
             let inner = generate_computed_expr_code(expr);
             quote! {
@@ -667,6 +691,29 @@ pub fn generate_computed_expr_code(expr: &ComputedExpr) -> TokenStream {
                 }
             }
         }
+        ComputedExpr::ArrayAggregate { aggregate } => {
+            // Reads the accumulator the append keeps, as Option<Value> like a field ref
+            let kind = match aggregate.kind {
+                ArrayAggregateKind::Sum => quote! { Sum },
+                ArrayAggregateKind::Last => quote! { Last },
+                ArrayAggregateKind::WindowAvg { n } => quote! { WindowAvg { n: #n } },
+            };
+            let path = aggregate.path.as_str();
+            let field = match &aggregate.field {
+                Some(field) => quote! { Some(#field.to_string()) },
+                None => quote! { None },
+            };
+            quote! {
+                hyperstack::runtime::hyperstack_interpreter::array_aggregate::evaluate(
+                    state,
+                    &hyperstack::runtime::hyperstack_interpreter::ast::ArrayAggregateSpec {
+                        kind: hyperstack::runtime::hyperstack_interpreter::ast::ArrayAggregateKind::#kind,
+                        path: #path.to_string(),
+                        field: #field,
+                    },
+                )
+            }
+        }
     }
 }
 
@@ -874,6 +921,20 @@ pub fn generate_computed_expr_code_with_cache(
                 }
             }
         }
+        ComputedExpr::SlotsToSecs { expr: inner } => {
+            let inner_code =
+                generate_computed_expr_code_with_cache(inner, section, computed_field_names);
+            quote! {
+                hyperstack::runtime::hyperstack_interpreter::block_time_cache::slots_to_secs((#inner_code) as i64)
+            }
+        }
+        ComputedExpr::SecsToSlots { expr: inner } => {
+            let inner_code =
+                generate_computed_expr_code_with_cache(inner, section, computed_field_names);
+            quote! {
+                hyperstack::runtime::hyperstack_interpreter::block_time_cache::secs_to_slots((#inner_code) as i64)
+            }
+        }
         ComputedExpr::U64FromLeBytes { bytes } => {
             let bytes_code =
                 generate_computed_expr_code_with_cache(bytes, section, computed_field_names);
@@ -187,6 +187,9 @@ pub fn generate_parsed_condition_code(condition: &ParsedCondition) -> TokenStrea
                 LogicalOp::Or => quote! { #(#condition_codes)||* },
             }
         }
+        // Change detection compares entity state across a handler run, so it
+        // is evaluated by the VM rather than against the event context.
+        ParsedCondition::Changed { .. } => quote! { true },
     }
 }
 
@@ -201,7 +201,9 @@ mod tests {
                     inner_type: None,
                     source_path: None,
                     resolved_type: None,
+                    encoding: None,
                     emit: true,
+                    docs: None,
                 },
                 FieldTypeInfo {
                     field_name: "round_address".to_string(),
@@ -212,11 +214,15 @@ mod tests {
                     inner_type: None,
                     source_path: None,
                     resolved_type: None,
+                    encoding: None,
                     emit: true,
+                    docs: None,
                 },
             ],
             is_nested_struct: false,
             parent_field: None,
+            docs: None,
+            shared_type: None,
         }];
 
         let output = generate_field_accessors(&sections);
@@ -249,6 +255,7 @@ mod tests {
                             base_type: BaseType::Pubkey,
                             is_optional: false,
                             is_array: false,
+                            encoding: None,
                         },
                         ResolvedField {
                             field_name: "created_at".to_string(),
@@ -256,6 +263,7 @@ mod tests {
                             base_type: BaseType::Timestamp,
                             is_optional: false,
                             is_array: false,
+                            encoding: None,
                         },
                     ],
                     is_instruction: false,
@@ -264,10 +272,14 @@ mod tests {
                     is_enum: false,
                     enum_variants: vec![],
                 }),
+                encoding: None,
                 emit: true,
+                docs: None,
             }],
             is_nested_struct: false,
             parent_field: None,
+            docs: None,
+            shared_type: None,
         }];
 
         let output = generate_field_accessors(&sections);
@@ -293,10 +305,14 @@ mod tests {
                     inner_type: None,
                     source_path: None,
                     resolved_type: None,
+                    encoding: None,
                     emit: true,
+                    docs: None,
                 }],
                 is_nested_struct: false,
                 parent_field: None,
+                docs: None,
+                shared_type: None,
             },
             EntitySection {
                 name: "id".to_string(),
@@ -309,10 +325,14 @@ mod tests {
                     inner_type: None,
                     source_path: None,
                     resolved_type: None,
+                    encoding: None,
                     emit: true,
+                    docs: None,
                 }],
                 is_nested_struct: false,
                 parent_field: None,
+                docs: None,
+                shared_type: None,
             },
         ];
 
@@ -349,10 +369,14 @@ mod tests {
                     is_enum: true,
                     enum_variants: vec!["Active".to_string(), "Inactive".to_string()],
                 }),
+                encoding: None,
                 emit: true,
+                docs: None,
             }],
             is_nested_struct: false,
             parent_field: None,
+            docs: None,
+            shared_type: None,
         }];
 
         let output = generate_field_accessors(&sections);
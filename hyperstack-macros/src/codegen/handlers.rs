@@ -302,6 +302,15 @@ fn build_parsed_condition_code(condition: &ParsedCondition) -> TokenStream {
                 }
             }
         }
+        ParsedCondition::Changed { field, initial } => {
+            let field_code = build_field_path_code(field);
+            quote! {
+                hyperstack::runtime::hyperstack_interpreter::ast::ParsedCondition::Changed {
+                    field: #field_code,
+                    initial: #initial,
+                }
+            }
+        }
     }
 }
 
@@ -446,6 +455,13 @@ fn build_mapping_source_code(source: &MappingSource) -> TokenStream {
                 }
             }
         }
+        MappingSource::FromTransaction { field } => {
+            quote! {
+                hyperstack::runtime::hyperstack_interpreter::ast::MappingSource::FromTransaction {
+                    field: #field.to_string(),
+                }
+            }
+        }
     }
 }
 
@@ -479,11 +495,14 @@ fn build_population_strategy_code(strategy: &PopulationStrategy) -> TokenStream
         PopulationStrategy::UniqueCount => {
             quote! { hyperstack::runtime::hyperstack_interpreter::ast::PopulationStrategy::UniqueCount }
         }
+        PopulationStrategy::Clear => {
+            quote! { hyperstack::runtime::hyperstack_interpreter::ast::PopulationStrategy::Clear }
+        }
     }
 }
 
 /// Generate code for Transformation.
-fn build_transformation_code(transform: &Transformation) -> TokenStream {
+pub(crate) fn build_transformation_code(transform: &Transformation) -> TokenStream {
     match transform {
         Transformation::HexEncode => {
             quote! { hyperstack::runtime::hyperstack_interpreter::ast::Transformation::HexEncode }
@@ -497,12 +516,28 @@ fn build_transformation_code(transform: &Transformation) -> TokenStream {
         Transformation::Base58Decode => {
             quote! { hyperstack::runtime::hyperstack_interpreter::ast::Transformation::Base58Decode }
         }
+        Transformation::Base64Encode => {
+            quote! { hyperstack::runtime::hyperstack_interpreter::ast::Transformation::Base64Encode }
+        }
+        Transformation::Base64Decode => {
+            quote! { hyperstack::runtime::hyperstack_interpreter::ast::Transformation::Base64Decode }
+        }
         Transformation::ToString => {
             quote! { hyperstack::runtime::hyperstack_interpreter::ast::Transformation::ToString }
         }
         Transformation::ToNumber => {
             quote! { hyperstack::runtime::hyperstack_interpreter::ast::Transformation::ToNumber }
         }
+        Transformation::Slice { start, end } => {
+            quote! { hyperstack::runtime::hyperstack_interpreter::ast::Transformation::Slice { start: #start, end: #end } }
+        }
+        Transformation::Take(n) => {
+            quote! { hyperstack::runtime::hyperstack_interpreter::ast::Transformation::Take(#n) }
+        }
+        Transformation::Chain(stages) => {
+            let stages_code = stages.iter().map(build_transformation_code);
+            quote! { hyperstack::runtime::hyperstack_interpreter::ast::Transformation::Chain(vec![#(#stages_code),*]) }
+        }
     }
 }
 
@@ -21,6 +21,7 @@ pub(crate) use bytecode::generate_bytecode_from_spec;
 pub(crate) use computed::generate_computed_evaluator;
 pub(crate) use computed::generate_computed_expr_code_with_cache;
 pub(crate) use field_accessors::generate_field_accessors;
+pub(crate) use handlers::build_transformation_code;
 pub(crate) use handlers::generate_handlers_from_specs;
 pub(crate) use multi_entity::generate_multi_entity_builder;
 pub(crate) use parsers::generate_parsers_from_idl;
@@ -69,6 +69,18 @@ fn generate_resolver_registry(resolver_hooks: &[ResolverHook]) -> TokenStream {
                     }
                 }
             }
+            ResolverStrategy::KeyFrom { .. } => {
+                // The compiled handler reads the key from the account data (with
+                // any primary key transform) and registers the address itself,
+                // so the update is processed right away instead of queued
+                quote! {
+                    #event_type => {
+                        Some(|_account_address: &str, _account_data: &hyperstack::runtime::serde_json::Value, _ctx: &mut hyperstack::runtime::hyperstack_interpreter::resolvers::ResolveContext| {
+                            hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(String::new())
+                        })
+                    }
+                }
+            }
         }
     });
 
@@ -8,7 +8,7 @@
 //! - Single runtime loop with configurable logging verbosity
 //! - Config-driven generation for different code paths
 
-use proc_macro2::TokenStream;
+use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
 
 /// Configuration for runtime code generation.
@@ -51,7 +51,7 @@ fn generate_slot_scheduler_task() -> TokenStream {
         {
             let scheduler = slot_scheduler.clone();
             let vm = vm.clone();
-            let bytecode = bytecode_arc.clone();
+            let live_bytecode = live_bytecode.clone();
             let runtime_resolver = runtime_resolver.clone();
             let slot_tracker = slot_tracker.clone();
             let mutations_tx = mutations_tx.clone();
@@ -93,8 +93,12 @@ fn generate_slot_scheduler_task() -> TokenStream {
 
                         for mut callback in due {
                             let state = {
-                                let vm_guard = vm.lock().unwrap_or_else(|e| e.into_inner());
-                                vm_guard.get_entity_state(callback.state_id, &callback.primary_key)
+                                let state_id = callback.state_id;
+                                let primary_key = callback.primary_key.clone();
+                                vm.call(move |vm| vm.get_entity_state(state_id, &primary_key))
+                                    .await
+                                    .ok()
+                                    .flatten()
                             };
 
                             let state = match state {
@@ -132,12 +136,12 @@ fn generate_slot_scheduler_task() -> TokenStream {
                             }
 
                             if callback.strategy == hyperstack::runtime::hyperstack_interpreter::ast::ResolveStrategy::SetOnce {
-                                // Use all(): fire resolver if any target is still null.
+                                // Use all(): fire resolver if any target is still null or pending.
                                 // Already-set fields are protected from overwrite by the
                                 // SetOnce guard in VmContext::set_value_at_path.
                                 let already_resolved = callback.extracts.iter().all(|ext| {
                                     let val = hyperstack::runtime::hyperstack_interpreter::scheduler::get_value_at_path(&state, &ext.target_path);
-                                    val.map(|v| !v.is_null()).unwrap_or(false)
+                                    !ext.is_unresolved(val.as_ref())
                                 });
                                 if already_resolved {
                                     hyperstack::runtime::tracing::info!(
@@ -197,27 +201,27 @@ fn generate_slot_scheduler_task() -> TokenStream {
                                 &hyperstack::runtime::serde_json::Value::String(url.clone()),
                             );
 
-                            // IMPORTANT: enqueue + take must stay inside the same lock guard.
+                            // IMPORTANT: enqueue + take must stay inside the same VM job.
                             // Splitting them risks lost or duplicated requests during reconnects.
                             let requests = {
-                                let mut vm_guard = vm.lock().unwrap_or_else(|e| e.into_inner());
                                 let target = hyperstack::runtime::hyperstack_interpreter::vm::ResolverTarget {
                                     state_id: callback.state_id,
                                     entity_name: callback.entity_name.clone(),
                                     primary_key: callback.primary_key.clone(),
                                     extracts: callback.extracts.clone(),
                                 };
-                                vm_guard.enqueue_resolver_request(
-                                    cache_key.clone(),
-                                    callback.resolver.clone(),
-                                    hyperstack::runtime::serde_json::Value::String(url.clone()),
-                                    target,
-                                );
-                                vm_guard.take_resolver_requests()
+                                let resolver = callback.resolver.clone();
+                                let input = hyperstack::runtime::serde_json::Value::String(url.clone());
+                                vm.call(move |vm| {
+                                    vm.enqueue_resolver_request(cache_key, resolver, input, target);
+                                    vm.take_resolver_requests()
+                                })
+                                .await
+                                .unwrap_or_default()
                             };
 
-                            let url_mutations = runtime_resolver
-                                .resolve_and_apply(&vm, bytecode.as_ref(), requests)
+                            let url_mutations = vm
+                                .resolve_and_apply(runtime_resolver.as_ref(), live_bytecode.load(), requests)
                                 .await;
 
                             if url_mutations.is_empty() {
@@ -264,7 +268,8 @@ fn generate_slot_scheduler_task() -> TokenStream {
 /// Opens a dedicated gRPC connection to stream slot updates, updating the
 /// `SlotTracker` on each new slot. This drives the scheduler to fire callbacks
 /// immediately when the target slot arrives, rather than waiting for the next
-/// account/instruction event.
+/// account/instruction event. Block metadata on the same stream feeds the
+/// slot → block time cache used for `UpdateContext` timestamps.
 fn generate_slot_subscription_task() -> TokenStream {
     quote! {
         // Helper function to parse SlotHashes sysvar data
@@ -303,17 +308,23 @@ fn generate_slot_subscription_task() -> TokenStream {
 
         {
             let slot_tracker = slot_tracker.clone();
-            let endpoint = endpoint.clone();
-            let x_token = x_token.clone();
+            let endpoints = yellowstone.endpoints.clone();
 
             hyperstack::runtime::tokio::spawn(async move {
                 hyperstack::runtime::tracing::info!("[SLOT_SUB] Starting dedicated gRPC slot subscription");
 
+                let mut reconnects = 0usize;
                 loop {
+                    // Move through the endpoints on every reconnect, so the
+                    // slot feed follows the stream onto a fallback endpoint
+                    let hyperstack::runtime::hyperstack_server::YellowstoneEndpoint { endpoint, x_token } =
+                        endpoints[reconnects % endpoints.len()].clone();
+                    reconnects += 1;
+
                     let result: Result<(), Box<dyn std::error::Error + Send + Sync>> = async {
                         use hyperstack::runtime::yellowstone_grpc_proto::geyser::{
                             SubscribeRequest, SubscribeRequestFilterSlots, SubscribeRequestFilterAccounts,
-                            subscribe_update::UpdateOneof,
+                            SubscribeRequestFilterBlocksMeta, subscribe_update::UpdateOneof,
                         };
                         use hyperstack::runtime::futures::StreamExt;
 
@@ -360,7 +371,11 @@ fn generate_slot_subscription_task() -> TokenStream {
                             transactions: std::collections::HashMap::new(),
                             transactions_status: std::collections::HashMap::new(),
                             blocks: std::collections::HashMap::new(),
-                            blocks_meta: std::collections::HashMap::new(),
+                            // Subscribe to block metadata to capture on-chain block times
+                            blocks_meta: std::collections::HashMap::from([(
+                                "block_meta_sub".to_string(),
+                                SubscribeRequestFilterBlocksMeta {},
+                            )]),
                             entry: std::collections::HashMap::new(),
                             commitment: Some(
                                 hyperstack::runtime::yellowstone_grpc_proto::geyser::CommitmentLevel::Processed as i32
@@ -376,7 +391,7 @@ fn generate_slot_subscription_task() -> TokenStream {
                         // Keep sender alive for the duration of the stream
                         let _keep_alive = sub_tx;
 
-                        hyperstack::runtime::tracing::info!("[SLOT_SUB] Connected and subscribed to slot and SlotHashes updates");
+                        hyperstack::runtime::tracing::info!("[SLOT_SUB] Connected and subscribed to slot, SlotHashes and block meta updates");
 
                         while let Some(msg) = stream.next().await {
                             match msg {
@@ -385,6 +400,14 @@ fn generate_slot_subscription_task() -> TokenStream {
                                         Some(UpdateOneof::Slot(slot_update)) => {
                                             slot_tracker.record(slot_update.slot);
                                         }
+                                        Some(UpdateOneof::BlockMeta(block_meta)) => {
+                                            if let Some(block_time) = block_meta.block_time {
+                                                hyperstack::runtime::hyperstack_interpreter::record_block_time(
+                                                    block_meta.slot,
+                                                    block_time.timestamp,
+                                                );
+                                            }
+                                        }
                                         Some(UpdateOneof::Account(account_update)) => {
                                             // Process SlotHashes sysvar update
                                             if let Some(account) = account_update.account {
@@ -437,10 +460,134 @@ fn generate_slot_subscription_task() -> TokenStream {
     }
 }
 
+/// Generate the server's account backfill function for the given parser modules.
+///
+/// The parser runtime stores a `VmHandler` in the shared `BackfillHandler`
+/// once the VM exists. Accounts fetched from RPC are wrapped in a synthetic
+/// `AccountUpdate` at the RPC slot, decoded by the account parser of the
+/// program that owns them and handled like a streamed update. Debug bundles
+/// and state snapshots read the same handler's VM. A snapshot's VM state is
+/// held in the shared `PendingVmState` until the parser runtime creates the VM,
+/// and the server's clock in `VmClock`.
+fn generate_account_backfill(parser_mods: &[Ident]) -> TokenStream {
+    quote! {
+        type BackfillHandler = std::sync::Arc<std::sync::OnceLock<VmHandler>>;
+        type PendingVmState = std::sync::Arc<std::sync::Mutex<Option<hyperstack::runtime::serde_json::Value>>>;
+        type VmClock = std::sync::Arc<std::sync::OnceLock<hyperstack::runtime::hyperstack_interpreter::SharedClock>>;
+
+        fn create_vm_state_export(backfill_handler: BackfillHandler) -> hyperstack::runtime::hyperstack_server::VmStateExportFn {
+            std::sync::Arc::new(move || {
+                let handler = backfill_handler.get()?;
+                handler.vm.blocking_call(|vm| vm.export_state()).ok()
+            })
+        }
+
+        fn create_vm_state_import(pending_vm_state: PendingVmState) -> hyperstack::runtime::hyperstack_server::VmStateImportFn {
+            std::sync::Arc::new(move |state| {
+                *pending_vm_state.lock().unwrap() = Some(state);
+            })
+        }
+
+        fn create_vm_clock(vm_clock: VmClock) -> hyperstack::runtime::hyperstack_server::VmClockFn {
+            std::sync::Arc::new(move |clock| {
+                let _ = vm_clock.set(clock);
+            })
+        }
+
+        /// A VM reading the server's clock, with the state restored from a
+        /// snapshot if one is pending
+        fn create_vm_context(pending_vm_state: &PendingVmState, vm_clock: &VmClock) -> hyperstack::runtime::hyperstack_interpreter::vm::VmContext {
+            let mut vm = hyperstack::runtime::hyperstack_interpreter::vm::VmContext::new();
+            if let Some(clock) = vm_clock.get() {
+                vm.set_clock(clock.clone());
+            }
+            if let Some(state) = pending_vm_state.lock().unwrap().take() {
+                match vm.import_state(&state) {
+                    Ok(entities) => hyperstack::runtime::tracing::info!("Restored {} entities into the VM", entities),
+                    Err(e) => hyperstack::runtime::tracing::warn!("Failed to restore the VM state, starting empty: {}", e),
+                }
+            }
+            vm
+        }
+
+        fn create_vm_snapshot(backfill_handler: BackfillHandler) -> hyperstack::runtime::hyperstack_server::VmSnapshotFn {
+            std::sync::Arc::new(move || {
+                let handler = backfill_handler.get()?;
+                handler.vm.blocking_call(|vm| vm.debug_snapshot()).ok()
+            })
+        }
+
+        fn create_vm_stats(backfill_handler: BackfillHandler) -> hyperstack::runtime::hyperstack_server::VmStatsFn {
+            std::sync::Arc::new(move || {
+                let handler = backfill_handler.get()?;
+                handler.vm.blocking_call(|vm| {
+                    hyperstack::runtime::serde_json::json!({
+                        "entity_sizes": vm.entity_size_report(10),
+                        "memory": vm.memory_report(),
+                    })
+                }).ok()
+            })
+        }
+
+        fn create_account_backfill(backfill_handler: BackfillHandler) -> hyperstack::runtime::hyperstack_server::AccountBackfillFn {
+            std::sync::Arc::new(move |account| {
+                let handler = backfill_handler.get().cloned();
+                Box::pin(async move {
+                    match handler {
+                        Some(handler) => backfill_account(&handler, account).await,
+                        None => Err(hyperstack::runtime::anyhow::anyhow!("parser runtime has not started")),
+                    }
+                })
+            })
+        }
+
+        async fn backfill_account(
+            handler: &VmHandler,
+            account: hyperstack::runtime::hyperstack_server::BackfillAccount,
+        ) -> hyperstack::runtime::anyhow::Result<bool> {
+            let update = hyperstack::runtime::yellowstone_vixen_core::AccountUpdate {
+                account: Some(hyperstack::runtime::yellowstone_grpc_proto::geyser::SubscribeUpdateAccountInfo {
+                    pubkey: hyperstack::runtime::bs58::decode(&account.address).into_vec()?,
+                    lamports: account.lamports,
+                    owner: hyperstack::runtime::bs58::decode(&account.owner).into_vec()?,
+                    executable: account.executable,
+                    rent_epoch: account.rent_epoch,
+                    data: account.data,
+                    write_version: 0,
+                    // No transaction wrote a backfilled account
+                    txn_signature: Some(vec![0; 64]),
+                }),
+                slot: account.slot,
+                is_startup: false,
+            };
+
+            #(
+                if account.owner == #parser_mods::PROGRAM_ID_STR {
+                    let parsed = hyperstack::runtime::yellowstone_vixen_core::Parser::parse(
+                        &#parser_mods::AccountParser,
+                        &update,
+                    )
+                    .await;
+                    if let Ok(value) = parsed {
+                        hyperstack::runtime::yellowstone_vixen::Handler::handle(handler, &value, &update)
+                            .await
+                            .map_err(|e| hyperstack::runtime::anyhow::anyhow!("{:?}", e))?;
+                        return Ok(true);
+                    }
+                }
+            )*
+
+            Ok(false)
+        }
+    }
+}
+
 /// Generate the VmHandler struct and its Handler trait implementations.
 ///
 /// This is the single source of truth for VmHandler generation.
-/// Uses MutationBatch with SlotContext for proper slot tracking.
+/// Uses MutationBatch with SlotContext for proper slot tracking. Each event is
+/// processed in one job on the VM thread behind a `VmHandle`, key resolution
+/// and the reprocessing of flushed PDA updates included.
 pub fn generate_vm_handler(
     state_enum_name: &str,
     instruction_enum_name: &str,
@@ -652,35 +799,74 @@ pub fn generate_vm_handler(
 
         #[derive(Clone)]
         pub struct VmHandler {
-            vm: std::sync::Arc<std::sync::Mutex<hyperstack::runtime::hyperstack_interpreter::vm::VmContext>>,
-            bytecode: std::sync::Arc<hyperstack::runtime::hyperstack_interpreter::compiler::MultiEntityBytecode>,
+            vm: hyperstack::runtime::hyperstack_interpreter::VmHandle,
+            bytecode: hyperstack::runtime::hyperstack_server::LiveBytecode,
             mutations_tx: hyperstack::runtime::tokio::sync::mpsc::Sender<hyperstack::runtime::hyperstack_server::MutationBatch>,
             health_monitor: Option<hyperstack::runtime::hyperstack_server::HealthMonitor>,
             slot_tracker: hyperstack::runtime::hyperstack_server::SlotTracker,
             runtime_resolver: hyperstack::runtime::hyperstack_interpreter::runtime_resolvers::SharedRuntimeResolver,
             slot_scheduler: std::sync::Arc<std::sync::Mutex<hyperstack::runtime::hyperstack_interpreter::scheduler::SlotScheduler>>,
+            /// Set when racing endpoints, to drop updates another endpoint already delivered
+            dedup: Option<hyperstack::runtime::hyperstack_server::SourceDedup>,
+            /// Sends the mutations of each transaction's instructions as one batch
+            txn_grouper: hyperstack::runtime::hyperstack_server::TransactionGrouper,
         }
 
         impl std::fmt::Debug for VmHandler {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 f.debug_struct("VmHandler")
-                    .field("vm", &"<VmContext>")
+                    .field("vm", &self.vm)
                     .field("bytecode", &"<MultiEntityBytecode>")
                     .finish()
             }
         }
 
         impl VmHandler {
+            /// A handler sending events to the VM thread behind `vm`
+            pub fn from_vm_handle(
+                vm: hyperstack::runtime::hyperstack_interpreter::VmHandle,
+                bytecode: hyperstack::runtime::hyperstack_server::LiveBytecode,
+                mutations_tx: hyperstack::runtime::tokio::sync::mpsc::Sender<hyperstack::runtime::hyperstack_server::MutationBatch>,
+                health_monitor: Option<hyperstack::runtime::hyperstack_server::HealthMonitor>,
+                slot_tracker: hyperstack::runtime::hyperstack_server::SlotTracker,
+                runtime_resolver: hyperstack::runtime::hyperstack_interpreter::runtime_resolvers::SharedRuntimeResolver,
+                slot_scheduler: std::sync::Arc<std::sync::Mutex<hyperstack::runtime::hyperstack_interpreter::scheduler::SlotScheduler>>,
+                dedup: Option<hyperstack::runtime::hyperstack_server::SourceDedup>,
+            ) -> Self {
+                let txn_grouper = hyperstack::runtime::hyperstack_server::TransactionGrouper::new(
+                    mutations_tx.clone(),
+                    hyperstack::runtime::hyperstack_server::TransactionGroupConfig::from_env(),
+                );
+                Self {
+                    vm,
+                    bytecode,
+                    mutations_tx,
+                    health_monitor,
+                    slot_tracker,
+                    runtime_resolver,
+                    slot_scheduler,
+                    dedup,
+                    txn_grouper,
+                }
+            }
+
+            /// The VM shared behind a lock, as before handlers sent events to
+            /// a VM thread. Kept for one release.
+            #[deprecated(note = "spawn the VM with VmHandle::spawn and use VmHandler::from_vm_handle")]
+            #[allow(dead_code)]
             pub fn new(
                 vm: std::sync::Arc<std::sync::Mutex<hyperstack::runtime::hyperstack_interpreter::vm::VmContext>>,
-                bytecode: std::sync::Arc<hyperstack::runtime::hyperstack_interpreter::compiler::MultiEntityBytecode>,
+                bytecode: hyperstack::runtime::hyperstack_server::LiveBytecode,
                 mutations_tx: hyperstack::runtime::tokio::sync::mpsc::Sender<hyperstack::runtime::hyperstack_server::MutationBatch>,
                 health_monitor: Option<hyperstack::runtime::hyperstack_server::HealthMonitor>,
                 slot_tracker: hyperstack::runtime::hyperstack_server::SlotTracker,
                 runtime_resolver: hyperstack::runtime::hyperstack_interpreter::runtime_resolvers::SharedRuntimeResolver,
                 slot_scheduler: std::sync::Arc<std::sync::Mutex<hyperstack::runtime::hyperstack_interpreter::scheduler::SlotScheduler>>,
+                dedup: Option<hyperstack::runtime::hyperstack_server::SourceDedup>,
             ) -> Self {
-                Self {
+                #[allow(deprecated)]
+                let vm = hyperstack::runtime::hyperstack_interpreter::VmHandle::spawn_shared(vm);
+                Self::from_vm_handle(
                     vm,
                     bytecode,
                     mutations_tx,
@@ -688,6 +874,37 @@ pub fn generate_vm_handler(
                     slot_tracker,
                     runtime_resolver,
                     slot_scheduler,
+                    dedup,
+                )
+            }
+
+            /// The event as the dead-letter queue keeps it, taken only while
+            /// one is attached since it copies the event
+            fn failed_event(
+                &self,
+                event_type: &str,
+                event_value: &hyperstack::runtime::serde_json::Value,
+                slot: u64,
+                signature: &str,
+            ) -> Option<hyperstack::runtime::hyperstack_server::FailedEvent> {
+                self.health_monitor.as_ref()?.failed_events()?;
+                Some(hyperstack::runtime::hyperstack_server::FailedEvent::new(
+                    event_type,
+                    event_value.clone(),
+                    "",
+                    slot,
+                    Some(signature.to_string()),
+                ))
+            }
+
+            fn record_failed_event(
+                &self,
+                failed_event: Option<hyperstack::runtime::hyperstack_server::FailedEvent>,
+                error: impl std::fmt::Display,
+            ) {
+                if let (Some(health), Some(mut event)) = (&self.health_monitor, failed_event) {
+                    event.error = error.to_string();
+                    health.record_failed_event(event);
                 }
             }
 
@@ -716,8 +933,8 @@ pub fn generate_vm_handler(
                 &self,
                 requests: Vec<hyperstack::runtime::hyperstack_interpreter::vm::ResolverRequest>,
             ) -> Vec<hyperstack::runtime::hyperstack_interpreter::Mutation> {
-                self.runtime_resolver
-                    .resolve_and_apply(&self.vm, self.bytecode.as_ref(), requests)
+                self.vm
+                    .resolve_and_apply(self.runtime_resolver.as_ref(), self.bytecode.load(), requests)
                     .await
             }
         }
@@ -737,8 +954,38 @@ pub fn generate_vm_handler(
                     health.record_event().await;
                 }
 
+                if let Some(ref dedup) = self.dedup {
+                    if !dedup.account(&account.pubkey, slot, write_version) {
+                        return Ok(());
+                    }
+                }
+
                 let account_address = hyperstack::runtime::bs58::encode(&account.pubkey).into_string();
 
+                if value.is_closed() {
+                    let bytecode = self.bytecode.load();
+                    let closed_address = account_address.clone();
+                    let tombstones = self
+                        .vm
+                        .call(move |vm| vm.close_account(&bytecode, &closed_address, slot))
+                        .await
+                        .unwrap_or_default();
+                    if !tombstones.is_empty() {
+                        self.slot_tracker.record(slot);
+                        let event_context = hyperstack::runtime::hyperstack_server::EventContext {
+                            program: #entity_name_lit.to_string(),
+                            event_kind: "account".to_string(),
+                            event_type: value.event_type().to_string(),
+                            account: Some(account_address),
+                            accounts_count: None,
+                            instructions: Vec::new(),
+                        };
+                        self.send_mutations_with_context(tombstones, slot, write_version, Some(event_context))
+                            .await;
+                    }
+                    return Ok(());
+                }
+
                 let event_type = value.event_type();
                 let mut log = hyperstack::runtime::hyperstack_interpreter::CanonicalLog::new();
                 log.set("phase", "vixen")
@@ -752,133 +999,151 @@ pub fn generate_vm_handler(
                 if let Some(obj) = event_value.as_object_mut() {
                     obj.insert("__account_address".to_string(), hyperstack::runtime::serde_json::json!(account_address));
                 }
+                let failed_event = self.failed_event(event_type, &event_value, slot, &signature);
+
+                // Loaded once so a reload never splits an event across two specs
+                let bytecode = self.bytecode.load();
+                let slot_scheduler = self.slot_scheduler.clone();
+                let job_account_address = account_address.clone();
+                let processed = self.vm.call(move |vm| {
+                    let account_address = job_account_address;
+
+                    let resolver_result = {
+                        if let Some(state_table) = vm.get_state_table_mut(0) {
+                            let mut ctx = hyperstack::runtime::hyperstack_interpreter::resolvers::ResolveContext::new(
+                                0,
+                                slot,
+                                signature.clone(),
+                                &mut state_table.pda_reverse_lookups,
+                            );
 
-                let resolver_result = {
-                    let mut vm = self.vm.lock().unwrap_or_else(|e| e.into_inner());
-
-                    if let Some(state_table) = vm.get_state_table_mut(0) {
-                        let mut ctx = hyperstack::runtime::hyperstack_interpreter::resolvers::ResolveContext::new(
-                            0,
-                            slot,
-                            signature.clone(),
-                            &mut state_table.pda_reverse_lookups,
-                        );
-
-                        if let Some(resolver_fn) = get_resolver_for_account_type(event_type) {
-                            resolver_fn(&account_address, &event_value, &mut ctx)
+                            if let Some(resolver_fn) = get_resolver_for_account_type(event_type) {
+                                resolver_fn(&account_address, &event_value, &mut ctx)
+                            } else {
+                                hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(String::new())
+                            }
                         } else {
                             hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(String::new())
                         }
-                    } else {
-                        hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(String::new())
-                    }
-                };
+                    };
 
-                match resolver_result {
-                    hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(resolved_key) => {
-                        hyperstack::runtime::tracing::info!(
-                            event_type = %event_type,
-                            account = %account_address,
-                            resolved_key = %resolved_key,
-                            slot = slot,
-                            "[PDA] Account key resolution: Found"
-                        );
-                        if !resolved_key.is_empty() {
-                            if let Some(obj) = event_value.as_object_mut() {
-                                obj.insert("__resolved_primary_key".to_string(), hyperstack::runtime::serde_json::json!(resolved_key));
+                    match resolver_result {
+                        hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(resolved_key) => {
+                            hyperstack::runtime::tracing::info!(
+                                event_type = %event_type,
+                                account = %account_address,
+                                resolved_key = %resolved_key,
+                                slot = slot,
+                                "[PDA] Account key resolution: Found"
+                            );
+                            if !resolved_key.is_empty() {
+                                if let Some(obj) = event_value.as_object_mut() {
+                                    obj.insert("__resolved_primary_key".to_string(), hyperstack::runtime::serde_json::json!(resolved_key));
+                                }
                             }
                         }
+                        hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::QueueUntil(_discriminators) => {
+                            hyperstack::runtime::tracing::info!(
+                                event_type = %event_type,
+                                pda = %account_address,
+                                slot = slot,
+                                "QueueUntil: queueing account update for later flush"
+                            );
+
+                            let _ = vm.queue_account_update(
+                                0,
+                                hyperstack::runtime::hyperstack_interpreter::QueuedAccountUpdate {
+                                    pda_address: account_address.clone(),
+                                    account_type: event_type.to_string(),
+                                    account_data: event_value,
+                                    slot,
+                                    write_version,
+                                    signature,
+                                },
+                            );
+                            return None;
+                        }
+                        hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Skip => {
+                            return None;
+                        }
                     }
-                    hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::QueueUntil(_discriminators) => {
-                        let mut vm = self.vm.lock().unwrap_or_else(|e| e.into_inner());
-                        hyperstack::runtime::tracing::info!(
-                            event_type = %event_type,
-                            pda = %account_address,
-                            slot = slot,
-                            "QueueUntil: queueing account update for later flush"
-                        );
 
-                        let _ = vm.queue_account_update(
-                            0,
-                            hyperstack::runtime::hyperstack_interpreter::QueuedAccountUpdate {
-                                pda_address: account_address.clone(),
+                    let (mutations_result, resolver_requests, scheduled_callbacks) = {
+
+                        let context = hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_account(slot, signature.clone(), write_version).with_block_time().with_program_id(parsers::PROGRAM_ID_STR);
+
+                        // Clone event data before process_event so we can cache it
+                        // for reprocessing when a PDA mapping changes at round boundaries.
+                        let event_value_for_cache = event_value.clone();
+
+                        let result = vm.process_event(&bytecode, event_value, event_type, Some(&context), Some(&mut log))
+                            .map_err(hyperstack::runtime::hyperstack_interpreter::vm::HandlerError::from);
+
+                        // Cache the last account data per PDA address.  When a PDA
+                        // mapping later changes (same PDA, different seed) the cached
+                        // data is returned for reprocessing with the corrected mapping.
+                        if result.is_ok() {
+                            // Cache under every state_id that routes this event_type so that
+                            // register_pda_reverse_lookup finds data for all participating entities.
+                            let state_ids: std::collections::HashSet<u32> = bytecode
+                                .route(event_type, context.program_id())
+                                .unwrap_or_default()
+                                .into_iter()
+                                .filter_map(|name| bytecode.entities.get(name).map(|eb| eb.state_id))
+                                .collect();
+                            let pending = hyperstack::runtime::hyperstack_interpreter::PendingAccountUpdate {
                                 account_type: event_type.to_string(),
-                                account_data: event_value,
+                                pda_address: account_address.clone(),
+                                account_data: event_value_for_cache,
                                 slot,
                                 write_version,
-                                signature,
-                            },
-                        );
-                        return Ok(());
-                    }
-                    hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Skip => {
-                        return Ok(());
-                    }
-                }
+                                signature: signature.clone(),
+                                queued_at: hyperstack::runtime::hyperstack_interpreter::Clock::unix_secs(vm.clock()),
+                                is_stale_reprocess: false,
+                            };
+                            for state_id in state_ids {
+                                vm.cache_last_account_data(state_id, &account_address, pending.clone());
+                            }
+                        }
 
-                let (mutations_result, resolver_requests, scheduled_callbacks) = {
-                    let mut vm = self.vm.lock().unwrap_or_else(|e| e.into_inner());
-
-                    let context = hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_account(slot, signature.clone(), write_version);
-
-                    // Clone event data before process_event so we can cache it
-                    // for reprocessing when a PDA mapping changes at round boundaries.
-                    let event_value_for_cache = event_value.clone();
-
-                    let result = vm.process_event(&self.bytecode, event_value, event_type, Some(&context), Some(&mut log))
-                        .map_err(|e| e.to_string());
-
-                    // Cache the last account data per PDA address.  When a PDA
-                    // mapping later changes (same PDA, different seed) the cached
-                    // data is returned for reprocessing with the corrected mapping.
-                    if result.is_ok() {
-                        // Cache under every state_id that routes this event_type so that
-                        // register_pda_reverse_lookup finds data for all participating entities.
-                        let state_ids: std::collections::HashSet<u32> = self.bytecode.event_routing
-                            .get(event_type)
-                            .map(|entities| entities.iter()
-                                .filter_map(|name| self.bytecode.entities.get(name).map(|eb| eb.state_id))
-                                .collect())
-                            .unwrap_or_default();
-                        let pending = hyperstack::runtime::hyperstack_interpreter::PendingAccountUpdate {
-                            account_type: event_type.to_string(),
-                            pda_address: account_address.clone(),
-                            account_data: event_value_for_cache,
-                            slot,
-                            write_version,
-                            signature: signature.clone(),
-                            queued_at: std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_secs() as i64,
-                            is_stale_reprocess: false,
+                        let requests = if result.is_ok() {
+                            vm.take_resolver_requests()
+                        } else {
+                            Vec::new()
                         };
-                        for state_id in state_ids {
-                            vm.cache_last_account_data(state_id, &account_address, pending.clone());
-                        }
-                    }
 
-                    let requests = if result.is_ok() {
-                        vm.take_resolver_requests()
-                    } else {
-                        Vec::new()
-                    };
+                        let scheduled = if result.is_ok() {
+                            vm.take_scheduled_callbacks()
+                        } else {
+                            Vec::new()
+                        };
 
-                    let scheduled = if result.is_ok() {
-                        vm.take_scheduled_callbacks()
-                    } else {
-                        Vec::new()
+                        (result, requests, scheduled)
                     };
 
-                    (result, requests, scheduled)
-                };
+                    if !scheduled_callbacks.is_empty() {
+                        let mut scheduler = slot_scheduler.lock().unwrap_or_else(|e| e.into_inner());
+                        for (target_slot, callback) in scheduled_callbacks {
+                            scheduler.register(target_slot, callback);
+                        }
+                    }
+
+                    Some((mutations_result, resolver_requests))
+                })
+                .await;
 
-                if !scheduled_callbacks.is_empty() {
-                    let mut scheduler = self.slot_scheduler.lock().unwrap_or_else(|e| e.into_inner());
-                    for (target_slot, callback) in scheduled_callbacks {
-                        scheduler.register(target_slot, callback);
+                let (mutations_result, resolver_requests) = match processed {
+                    Ok(Some(processed)) => processed,
+                    // Queued until its PDA is known, or skipped
+                    Ok(None) => return Ok(()),
+                    Err(e) => {
+                        self.record_failed_event(failed_event, format!("VM unavailable: {}", e));
+                        if let Some(ref health) = self.health_monitor {
+                            health.record_error(format!("VM unavailable for {}: {}", event_type, e)).await;
+                        }
+                        return Ok(());
                     }
-                }
+                };
 
                 let resolver_mutations = if mutations_result.is_ok() {
                     self.resolve_and_apply_resolvers(resolver_requests).await
@@ -898,6 +1163,7 @@ pub fn generate_vm_handler(
                             event_type: event_type.to_string(),
                             account: Some(account_address),
                             accounts_count: None,
+                            instructions: Vec::new(),
                         };
                         self.send_mutations_with_context(
                             mutations,
@@ -909,8 +1175,12 @@ pub fn generate_vm_handler(
                         Ok(())
                     }
                     Err(e) => {
+                        self.record_failed_event(failed_event, &e);
                         if let Some(ref health) = self.health_monitor {
-                            health.record_error(format!("VM error for {}: {}", event_type, e)).await;
+                            match e.vm_error() {
+                                Some(vm_error) => health.record_vm_error(event_type, vm_error).await,
+                                None => health.record_error(format!("VM error for {}: {}", event_type, e)).await,
+                            }
                         }
                         Ok(())
                     }
@@ -932,6 +1202,12 @@ pub fn generate_vm_handler(
                     health.record_event().await;
                 }
 
+                if let Some(ref dedup) = self.dedup {
+                    if !dedup.instruction(&raw_update.shared.signature) {
+                        return Ok(());
+                    }
+                }
+
                 let static_keys_vec = &raw_update.accounts;
                 let event_type = value.event_type();
                 let account_keys: Vec<String> = static_keys_vec
@@ -949,167 +1225,182 @@ pub fn generate_vm_handler(
                     .set("txn_index", txn_index)
                     .set("program", #entity_name_lit)
                     .set("accounts", account_keys);
-                let event_value = value.to_value_with_accounts(static_keys_vec);
-
-                let bytecode = self.bytecode.clone();
-                let (mutations_result, resolver_requests, scheduled_callbacks) = {
-                    let mut vm = self.vm.lock().unwrap_or_else(|e| e.into_inner());
-
-                    let context = hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_instruction(slot, signature.clone(), txn_index);
-
-                    let mut result = vm.process_event(&bytecode, event_value.clone(), event_type, Some(&context), Some(&mut log))
-                        .map_err(|e| e.to_string());
-
-                    if result.is_ok() {
-                        let hooks = get_instruction_hooks(event_type);
-                        if !hooks.is_empty() {
-                            let accounts = event_value.get("accounts")
-                                .and_then(|a| a.as_object())
-                                .map(|obj| {
-                                    obj.iter()
-                                        .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
-                                        .collect::<std::collections::HashMap<String, String>>()
-                                })
-                                .unwrap_or_default();
-
-                            let instruction_data = event_value.get("data").unwrap_or(&hyperstack::runtime::serde_json::Value::Null);
-
-                            let timestamp = vm.current_context()
-                                .map(|ctx| ctx.timestamp())
-                                .unwrap_or_else(|| std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_secs() as i64);
-
-                            // SAFETY: Carefully splitting mutable borrow into disjoint parts
-                            let vm_ptr: *mut hyperstack::runtime::hyperstack_interpreter::vm::VmContext = &mut *vm as *mut hyperstack::runtime::hyperstack_interpreter::vm::VmContext;
-
-                            let mut ctx = hyperstack::runtime::hyperstack_interpreter::resolvers::InstructionContext::with_metrics(
-                                accounts,
-                                0,
-                                &mut *vm,
-                                unsafe { (*vm_ptr).registers_mut() },
-                                2,
-                                unsafe { (*vm_ptr).path_cache() },
-                                instruction_data,
-                                Some(context.slot.unwrap_or(0)),
-                                context.signature.clone(),
-                                timestamp,
-                            );
+                let event_value = value.to_value_with_transaction(raw_update);
+                let failed_event = self.failed_event(event_type, &event_value, slot, &signature);
+
+                let bytecode = self.bytecode.load();
+                let slot_scheduler = self.slot_scheduler.clone();
+                let txn_signature = signature.clone();
+                let processed = self.vm.call(move |vm| {
+                    let (mutations_result, resolver_requests, scheduled_callbacks) = {
+
+                        let context = hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_instruction(slot, signature.clone(), txn_index).with_block_time().with_program_id(parsers::PROGRAM_ID_STR);
+
+                        let mut result = vm.process_event(&bytecode, event_value.clone(), event_type, Some(&context), Some(&mut log))
+                            .map_err(hyperstack::runtime::hyperstack_interpreter::vm::HandlerError::from);
+
+                        if result.is_ok() {
+                            let hooks = get_instruction_hooks(event_type);
+                            if !hooks.is_empty() {
+                                let accounts = event_value.get("accounts")
+                                    .and_then(|a| a.as_object())
+                                    .map(|obj| {
+                                        obj.iter()
+                                            .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
+                                            .collect::<std::collections::HashMap<String, String>>()
+                                    })
+                                    .unwrap_or_default();
+
+                                let instruction_data = event_value.get("data").unwrap_or(&hyperstack::runtime::serde_json::Value::Null);
+
+                                let timestamp = vm.current_context()
+                                    .map(|ctx| ctx.timestamp())
+                                    .unwrap_or_else(|| hyperstack::runtime::hyperstack_interpreter::Clock::unix_secs(vm.clock()));
+
+                                // SAFETY: Carefully splitting mutable borrow into disjoint parts
+                                let vm_ptr: *mut hyperstack::runtime::hyperstack_interpreter::vm::VmContext = &mut *vm as *mut hyperstack::runtime::hyperstack_interpreter::vm::VmContext;
+
+                                let mut ctx = hyperstack::runtime::hyperstack_interpreter::resolvers::InstructionContext::with_metrics(
+                                    accounts,
+                                    0,
+                                    &mut *vm,
+                                    unsafe { (*vm_ptr).registers_mut() },
+                                    2,
+                                    unsafe { (*vm_ptr).path_cache() },
+                                    instruction_data,
+                                    Some(context.slot.unwrap_or(0)),
+                                    context.signature.clone(),
+                                    timestamp,
+                                );
 
-                            for hook_fn in hooks.iter() {
-                                hook_fn(&mut ctx);
-                            }
+                                for hook_fn in hooks.iter() {
+                                    hook_fn(&mut ctx);
+                                }
 
-                            let pending_updates = ctx.take_pending_updates();
+                                let pending_updates = ctx.take_pending_updates();
 
-                            drop(ctx);
+                                drop(ctx);
 
-                            // Process pending account updates from instruction hooks
-                            if !pending_updates.is_empty() {
-                                hyperstack::runtime::tracing::info!(
-                                    count = pending_updates.len(),
-                                    event_type = %event_type,
-                                    "[PDA] Flushing pending account updates from instruction hooks"
-                                );
-                                for update in pending_updates {
+                                // Process pending account updates from instruction hooks
+                                if !pending_updates.is_empty() {
                                     hyperstack::runtime::tracing::info!(
-                                        account_type = %update.account_type,
-                                        pda = %update.pda_address,
-                                        update_slot = update.slot,
-                                        current_instruction_slot = slot,
-                                        "[PDA] Reprocessing flushed update"
+                                        count = pending_updates.len(),
+                                        event_type = %event_type,
+                                        "[PDA] Flushing pending account updates from instruction hooks"
                                     );
-                                    let resolved_key = vm.try_chained_pda_lookup(0, "default_pda_lookup", &update.pda_address);
-
-                                    let mut account_data = update.account_data;
-                                    if let Some(ref key) = resolved_key {
+                                    for update in pending_updates {
                                         hyperstack::runtime::tracing::info!(
+                                            account_type = %update.account_type,
                                             pda = %update.pda_address,
-                                            resolved_key = %key,
-                                            "[PDA] Chained PDA lookup resolved for reprocessed update"
-                                        );
-                                        if let Some(obj) = account_data.as_object_mut() {
-                                            obj.insert("__resolved_primary_key".to_string(), hyperstack::runtime::serde_json::json!(key));
-                                        }
-                                    } else {
-                                        hyperstack::runtime::tracing::warn!(
-                                            pda = %update.pda_address,
-                                            "[PDA] Chained PDA lookup returned None for reprocessed update"
+                                            update_slot = update.slot,
+                                            current_instruction_slot = slot,
+                                            "[PDA] Reprocessing flushed update"
                                         );
-                                    }
+                                        let resolved_key = vm.try_chained_pda_lookup(0, "default_pda_lookup", &update.pda_address);
 
-                                    let update_context = if update.is_stale_reprocess {
-                                        hyperstack::runtime::tracing::info!(
-                                            pda = %update.pda_address,
-                                            "[PDA] Using reprocessed context (empty sig, skip resolvers)"
-                                        );
-                                        hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_reprocessed(
-                                            update.slot,
-                                            update.write_version,
-                                        )
-                                    } else {
-                                        hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_account(
-                                            update.slot,
-                                            update.signature.clone(),
-                                            update.write_version,
-                                        )
-                                    };
-
-                                    match vm.process_event(&bytecode, account_data, &update.account_type, Some(&update_context), None) {
-                                        Ok(pending_mutations) => {
+                                        let mut account_data = update.account_data;
+                                        if let Some(ref key) = resolved_key {
                                             hyperstack::runtime::tracing::info!(
-                                                account_type = %update.account_type,
                                                 pda = %update.pda_address,
-                                                mutations = pending_mutations.len(),
-                                                is_stale = update.is_stale_reprocess,
-                                                "[PDA] Reprocessed flushed account update"
+                                                resolved_key = %key,
+                                                "[PDA] Chained PDA lookup resolved for reprocessed update"
                                             );
-                                            if let Ok(ref mut mutations) = result {
-                                                mutations.extend(pending_mutations);
+                                            if let Some(obj) = account_data.as_object_mut() {
+                                                obj.insert("__resolved_primary_key".to_string(), hyperstack::runtime::serde_json::json!(key));
                                             }
-                                        }
-                                        Err(e) => {
+                                        } else {
                                             hyperstack::runtime::tracing::warn!(
-                                                account_type = %update.account_type,
-                                                error = %e,
-                                                "[PDA] Failed to reprocess flushed account update"
+                                                pda = %update.pda_address,
+                                                "[PDA] Chained PDA lookup returned None for reprocessed update"
                                             );
                                         }
+
+                                        let update_context = if update.is_stale_reprocess {
+                                            hyperstack::runtime::tracing::info!(
+                                                pda = %update.pda_address,
+                                                "[PDA] Using reprocessed context (empty sig, skip resolvers)"
+                                            );
+                                            hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_reprocessed(
+                                                update.slot,
+                                                update.write_version,
+                                            )
+                                        } else {
+                                            hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_account(
+                                                update.slot,
+                                                update.signature.clone(),
+                                                update.write_version,
+                                            )
+                                        };
+
+                                        match vm.process_event(&bytecode, account_data, &update.account_type, Some(&update_context), None) {
+                                            Ok(pending_mutations) => {
+                                                hyperstack::runtime::tracing::info!(
+                                                    account_type = %update.account_type,
+                                                    pda = %update.pda_address,
+                                                    mutations = pending_mutations.len(),
+                                                    is_stale = update.is_stale_reprocess,
+                                                    "[PDA] Reprocessed flushed account update"
+                                                );
+                                                if let Ok(ref mut mutations) = result {
+                                                    mutations.extend(pending_mutations);
+                                                }
+                                            }
+                                            Err(e) => {
+                                                hyperstack::runtime::tracing::warn!(
+                                                    account_type = %update.account_type,
+                                                    error = %e,
+                                                    "[PDA] Failed to reprocess flushed account update"
+                                                );
+                                            }
+                                        }
                                     }
                                 }
                             }
-                        }
 
-                        // Periodic cleanup
-                        if vm.instructions_executed % 1000 == 0 {
-                            let _ = vm.cleanup_all_expired(0);
-                            let stats = vm.get_memory_stats(0);
-                            hyperstack::runtime::hyperstack_interpreter::vm_metrics::record_memory_stats(&stats, #entity_name_lit);
+                            // Periodic cleanup
+                            if vm.instructions_executed % 1000 == 0 {
+                                let _ = vm.cleanup_all_expired(0);
+                                let stats = vm.get_memory_stats(0);
+                                hyperstack::runtime::hyperstack_interpreter::vm_metrics::record_memory_stats(&stats, #entity_name_lit);
+                            }
                         }
-                    }
 
-                    let requests = if result.is_ok() {
-                        vm.take_resolver_requests()
-                    } else {
-                        Vec::new()
-                    };
+                        let requests = if result.is_ok() {
+                            vm.take_resolver_requests()
+                        } else {
+                            Vec::new()
+                        };
+
+                        let scheduled = if result.is_ok() {
+                            vm.take_scheduled_callbacks()
+                        } else {
+                            Vec::new()
+                        };
 
-                    let scheduled = if result.is_ok() {
-                        vm.take_scheduled_callbacks()
-                    } else {
-                        Vec::new()
+                        (result, requests, scheduled)
                     };
 
-                    (result, requests, scheduled)
-                };
+                    if !scheduled_callbacks.is_empty() {
+                        let mut scheduler = slot_scheduler.lock().unwrap_or_else(|e| e.into_inner());
+                        for (target_slot, callback) in scheduled_callbacks {
+                            scheduler.register(target_slot, callback);
+                        }
+                    }
 
-                if !scheduled_callbacks.is_empty() {
-                    let mut scheduler = self.slot_scheduler.lock().unwrap_or_else(|e| e.into_inner());
-                    for (target_slot, callback) in scheduled_callbacks {
-                        scheduler.register(target_slot, callback);
+                    (mutations_result, resolver_requests)
+                })
+                .await;
+
+                let (mutations_result, resolver_requests) = match processed {
+                    Ok(processed) => processed,
+                    Err(e) => {
+                        self.record_failed_event(failed_event, format!("VM unavailable: {}", e));
+                        if let Some(ref health) = self.health_monitor {
+                            health.record_error(format!("VM unavailable for {}: {}", event_type, e)).await;
+                        }
+                        return Ok(());
                     }
-                }
+                };
 
                 let resolver_mutations = if mutations_result.is_ok() {
                     self.resolve_and_apply_resolvers(resolver_requests).await
@@ -1129,19 +1420,26 @@ pub fn generate_vm_handler(
                             event_type: event_type.to_string(),
                             account: None,
                             accounts_count: Some(static_keys_vec.len()),
+                            instructions: Vec::new(),
                         };
-                        self.send_mutations_with_context(
-                            mutations,
-                            slot,
-                            txn_index as u64,
-                            Some(event_context),
-                        )
-                        .await;
+                        self.txn_grouper
+                            .add(hyperstack::runtime::hyperstack_server::InstructionMutations {
+                                signature: txn_signature,
+                                slot,
+                                txn_index: txn_index as u64,
+                                event_context,
+                                mutations,
+                            })
+                            .await;
                         Ok(())
                     }
                     Err(e) => {
+                        self.record_failed_event(failed_event, &e);
                         if let Some(ref health) = self.health_monitor {
-                            health.record_error(format!("VM error for {}: {}", event_type, e)).await;
+                            match e.vm_error() {
+                                Some(vm_error) => health.record_vm_error(event_type, vm_error).await,
+                                None => health.record_error(format!("VM error for {}: {}", event_type, e)).await,
+                            }
                         }
                         Ok(())
                     }
@@ -1196,31 +1494,58 @@ pub fn generate_spec_function(
 
     let slot_scheduler_task = generate_slot_scheduler_task();
     let slot_subscription_task = generate_slot_subscription_task();
+    let account_backfill = generate_account_backfill(&[format_ident!("parsers")]);
 
     quote! {
         pub fn spec() -> hyperstack::runtime::hyperstack_server::Spec {
+            let live_bytecode = hyperstack::runtime::hyperstack_server::LiveBytecode::new(create_multi_entity_bytecode());
             let bytecode = create_multi_entity_bytecode();
             let program_id = parsers::PROGRAM_ID_STR.to_string();
 
+            let backfill_handler = BackfillHandler::default();
+            let pending_vm_state = PendingVmState::default();
+            let vm_clock = VmClock::default();
+
             hyperstack::runtime::hyperstack_server::Spec::new(bytecode, program_id)
-                .with_parser_setup(create_parser_setup())
+                .with_parser_setup(create_parser_setup(backfill_handler.clone(), pending_vm_state.clone(), vm_clock.clone(), live_bytecode.clone()))
+                .with_vm_snapshot(create_vm_snapshot(backfill_handler.clone()))
+                .with_vm_stats(create_vm_stats(backfill_handler.clone()))
+                .with_vm_state(create_vm_state_export(backfill_handler.clone()), create_vm_state_import(pending_vm_state))
+                .with_vm_clock(create_vm_clock(vm_clock))
+                .with_account_backfill(create_account_backfill(backfill_handler))
+                .with_live_bytecode(live_bytecode)
                 #views_call
         }
 
-        fn create_parser_setup() -> hyperstack::runtime::hyperstack_server::ParserSetupFn {
+        fn create_parser_setup(
+            backfill_handler: BackfillHandler,
+            pending_vm_state: PendingVmState,
+            vm_clock: VmClock,
+            live_bytecode: hyperstack::runtime::hyperstack_server::LiveBytecode,
+        ) -> hyperstack::runtime::hyperstack_server::ParserSetupFn {
             use std::sync::Arc;
 
-            Arc::new(|mutations_tx, health_monitor, reconnection_config| {
+            Arc::new(move |mutations_tx, health_monitor, reconnection_config| {
+                let backfill_handler = backfill_handler.clone();
+                let pending_vm_state = pending_vm_state.clone();
+                let vm_clock = vm_clock.clone();
+                let live_bytecode = live_bytecode.clone();
                 Box::pin(async move {
-                    run_vixen_runtime_with_channel(mutations_tx, health_monitor, reconnection_config).await
+                    run_vixen_runtime_with_channel(mutations_tx, health_monitor, reconnection_config, backfill_handler, pending_vm_state, vm_clock, live_bytecode).await
                 })
             })
         }
 
+        #account_backfill
+
         async fn run_vixen_runtime_with_channel(
             mutations_tx: hyperstack::runtime::tokio::sync::mpsc::Sender<hyperstack::runtime::hyperstack_server::MutationBatch>,
             health_monitor: Option<hyperstack::runtime::hyperstack_server::HealthMonitor>,
             reconnection_config: hyperstack::runtime::hyperstack_server::ReconnectionConfig,
+            backfill_handler: BackfillHandler,
+            pending_vm_state: PendingVmState,
+            vm_clock: VmClock,
+            live_bytecode: hyperstack::runtime::hyperstack_server::LiveBytecode,
         ) -> hyperstack::runtime::anyhow::Result<()> {
             use hyperstack::runtime::yellowstone_vixen::config::{BufferConfig, VixenConfig};
             use hyperstack::runtime::yellowstone_vixen_yellowstone_grpc_source::YellowstoneGrpcConfig;
@@ -1237,12 +1562,7 @@ pub fn generate_spec_function(
                 hyperstack::runtime::tracing::warn!("No .env file found. Make sure environment variables are set.");
             }
 
-            let endpoint = std::env::var("YELLOWSTONE_ENDPOINT")
-                .map_err(|_| hyperstack::runtime::anyhow::anyhow!(
-                    "YELLOWSTONE_ENDPOINT environment variable must be set.\n\
-                     Example: export YELLOWSTONE_ENDPOINT=http://localhost:10000"
-                ))?;
-            let x_token = std::env::var("YELLOWSTONE_X_TOKEN").ok();
+            let yellowstone = hyperstack::runtime::hyperstack_server::YellowstoneConfig::from_env()?;
 
             let runtime_resolver: hyperstack::runtime::hyperstack_interpreter::runtime_resolvers::SharedRuntimeResolver =
                 hyperstack::runtime::hyperstack_interpreter::runtime_resolvers_factory::build_resolver()
@@ -1255,15 +1575,28 @@ pub fn generate_spec_function(
 
             let slot_tracker = hyperstack::runtime::hyperstack_server::SlotTracker::new();
             let slot_scheduler = Arc::new(Mutex::new(hyperstack::runtime::hyperstack_interpreter::scheduler::SlotScheduler::new()));
-            let mut attempt = 0u32;
-            let mut backoff = reconnection_config.initial_delay;
+            let first_connection = std::sync::atomic::AtomicBool::new(true);
 
-            let bytecode = create_multi_entity_bytecode();
+            let bytecode = live_bytecode.load();
 
             #bytecode_logging
 
-            let vm = Arc::new(Mutex::new(hyperstack::runtime::hyperstack_interpreter::vm::VmContext::new()));
-            let bytecode_arc = Arc::new(bytecode);
+            let vm = hyperstack::runtime::hyperstack_interpreter::VmHandle::spawn(
+                create_vm_context(&pending_vm_state, &vm_clock),
+            );
+
+            // Backfilled accounts share the VM but not the slot tracker, so an
+            // RPC slot never moves the resume point of the stream
+            let _ = backfill_handler.set(VmHandler::from_vm_handle(
+                vm.clone(),
+                live_bytecode.clone(),
+                mutations_tx.clone(),
+                None,
+                hyperstack::runtime::hyperstack_server::SlotTracker::new(),
+                runtime_resolver.clone(),
+                slot_scheduler.clone(),
+                None,
+            ));
 
             // Spawn slot scheduler background task
             #slot_scheduler_task
@@ -1271,96 +1604,67 @@ pub fn generate_spec_function(
             // Spawn dedicated gRPC slot subscription to drive the scheduler in real-time
             #slot_subscription_task
 
-            loop {
-                let from_slot = {
-                    let last = slot_tracker.get();
-                    if last > 0 { Some(last) } else { None }
-                };
-
-                if from_slot.is_some() {
-                    hyperstack::runtime::tracing::info!("Resuming from slot {}", from_slot.unwrap());
-                }
-
-                let vixen_config = VixenConfig {
-                    source: YellowstoneGrpcConfig {
-                        endpoint: endpoint.clone(),
-                        x_token: x_token.clone(),
-                        timeout: 60,
-                        commitment_level: None,
-                        from_slot,
-                        accept_compression: None,
-                        max_decoding_message_size: None,
-                    },
-                    buffer: BufferConfig::default(),
-                };
-
-                let handler = VmHandler::new(
-                    vm.clone(),
-                    bytecode_arc.clone(),
-                    mutations_tx.clone(),
-                    health_monitor.clone(),
-                    slot_tracker.clone(),
-                    runtime_resolver.clone(),
-                    slot_scheduler.clone(),
-                );
-
-                let account_parser = parsers::AccountParser;
-                let instruction_parser = parsers::InstructionParser;
-
-                if attempt == 0 {
-                    hyperstack::runtime::tracing::info!("Starting yellowstone-vixen runtime for {} program", #program_name);
-                    hyperstack::runtime::tracing::info!("Program ID: {}", parsers::PROGRAM_ID_STR);
-                    #parser_logging
-                }
-
-                if let Some(ref health) = health_monitor {
-                    health.record_reconnecting().await;
-                }
-
-                let account_pipeline = Pipeline::new(account_parser, [handler.clone()]);
-                let instruction_pipeline = Pipeline::new(instruction_parser, [handler]);
-
-                if let Some(ref health) = health_monitor {
-                    health.record_connection().await;
-                }
-
-                let result = hyperstack::runtime::yellowstone_vixen::Runtime::<YellowstoneGrpcSource>::builder()
-                    .account(account_pipeline)
-                    .instruction(instruction_pipeline)
-                    .build(vixen_config)
-                    .try_run_async()
-                    .await;
-
-                if let Err(e) = result {
-                    hyperstack::runtime::tracing::error!("Vixen runtime error: {:?}", e);
-                }
-
-                attempt += 1;
+            hyperstack::runtime::hyperstack_server::run_sources(
+                &yellowstone,
+                &reconnection_config,
+                health_monitor.clone(),
+                |endpoint, dedup| {
+                    let from_slot = {
+                        let last = slot_tracker.get();
+                        if last > 0 { Some(last) } else { None }
+                    };
 
-                if let Some(max) = reconnection_config.max_attempts {
-                    if attempt >= max {
-                        hyperstack::runtime::tracing::error!("Max reconnection attempts ({}) reached, giving up", max);
-                        if let Some(ref health) = health_monitor {
-                            health.record_error("Max reconnection attempts reached".into()).await;
-                        }
-                        return Err(hyperstack::runtime::anyhow::anyhow!("Max reconnection attempts reached"));
+                    if from_slot.is_some() {
+                        hyperstack::runtime::tracing::info!("Resuming from slot {}", from_slot.unwrap());
                     }
-                }
 
-                hyperstack::runtime::tracing::warn!(
-                    "gRPC stream disconnected. Reconnecting in {:?} (attempt {})",
-                    backoff,
-                    attempt
-                );
+                    let vixen_config = VixenConfig {
+                        source: YellowstoneGrpcConfig {
+                            endpoint: endpoint.endpoint,
+                            x_token: endpoint.x_token,
+                            timeout: 60,
+                            commitment_level: None,
+                            from_slot,
+                            accept_compression: None,
+                            max_decoding_message_size: None,
+                        },
+                        buffer: BufferConfig::default(),
+                    };
 
-                if let Some(ref health) = health_monitor {
-                    health.record_disconnection().await;
-                }
+                    let handler = VmHandler::from_vm_handle(
+                        vm.clone(),
+                        live_bytecode.clone(),
+                        mutations_tx.clone(),
+                        health_monitor.clone(),
+                        slot_tracker.clone(),
+                        runtime_resolver.clone(),
+                        slot_scheduler.clone(),
+                        dedup,
+                    );
 
-                hyperstack::runtime::tokio::time::sleep(backoff).await;
+                    let account_parser = parsers::AccountParser;
+                    let instruction_parser = parsers::InstructionParser;
 
-                backoff = reconnection_config.next_backoff(backoff);
-            }
+                    if first_connection.swap(false, std::sync::atomic::Ordering::Relaxed) {
+                        hyperstack::runtime::tracing::info!("Starting yellowstone-vixen runtime for {} program", #program_name);
+                        hyperstack::runtime::tracing::info!("Program ID: {}", parsers::PROGRAM_ID_STR);
+                        #parser_logging
+                    }
+                    let account_pipeline = Pipeline::new(account_parser, [handler.clone()]);
+                    let instruction_pipeline = Pipeline::new(instruction_parser, [handler]);
+
+                    async move {
+                        hyperstack::runtime::yellowstone_vixen::Runtime::<YellowstoneGrpcSource>::builder()
+                            .account(account_pipeline)
+                            .instruction(instruction_pipeline)
+                            .build(vixen_config)
+                            .try_run_async()
+                            .await
+                            .map_err(|e| hyperstack::runtime::anyhow::anyhow!("Vixen runtime error: {:?}", e))
+                    }
+                },
+            )
+            .await
         }
     }
 }
@@ -1568,35 +1872,74 @@ pub fn generate_vm_handler_struct() -> TokenStream {
 
         #[derive(Clone)]
         pub struct VmHandler {
-            vm: std::sync::Arc<std::sync::Mutex<hyperstack::runtime::hyperstack_interpreter::vm::VmContext>>,
-            bytecode: std::sync::Arc<hyperstack::runtime::hyperstack_interpreter::compiler::MultiEntityBytecode>,
+            vm: hyperstack::runtime::hyperstack_interpreter::VmHandle,
+            bytecode: hyperstack::runtime::hyperstack_server::LiveBytecode,
             mutations_tx: hyperstack::runtime::tokio::sync::mpsc::Sender<hyperstack::runtime::hyperstack_server::MutationBatch>,
             health_monitor: Option<hyperstack::runtime::hyperstack_server::HealthMonitor>,
             slot_tracker: hyperstack::runtime::hyperstack_server::SlotTracker,
             runtime_resolver: hyperstack::runtime::hyperstack_interpreter::runtime_resolvers::SharedRuntimeResolver,
             slot_scheduler: std::sync::Arc<std::sync::Mutex<hyperstack::runtime::hyperstack_interpreter::scheduler::SlotScheduler>>,
+            /// Set when racing endpoints, to drop updates another endpoint already delivered
+            dedup: Option<hyperstack::runtime::hyperstack_server::SourceDedup>,
+            /// Sends the mutations of each transaction's instructions as one batch
+            txn_grouper: hyperstack::runtime::hyperstack_server::TransactionGrouper,
         }
 
         impl std::fmt::Debug for VmHandler {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 f.debug_struct("VmHandler")
-                    .field("vm", &"<VmContext>")
+                    .field("vm", &self.vm)
                     .field("bytecode", &"<MultiEntityBytecode>")
                     .finish()
             }
         }
 
         impl VmHandler {
+            /// A handler sending events to the VM thread behind `vm`
+            pub fn from_vm_handle(
+                vm: hyperstack::runtime::hyperstack_interpreter::VmHandle,
+                bytecode: hyperstack::runtime::hyperstack_server::LiveBytecode,
+                mutations_tx: hyperstack::runtime::tokio::sync::mpsc::Sender<hyperstack::runtime::hyperstack_server::MutationBatch>,
+                health_monitor: Option<hyperstack::runtime::hyperstack_server::HealthMonitor>,
+                slot_tracker: hyperstack::runtime::hyperstack_server::SlotTracker,
+                runtime_resolver: hyperstack::runtime::hyperstack_interpreter::runtime_resolvers::SharedRuntimeResolver,
+                slot_scheduler: std::sync::Arc<std::sync::Mutex<hyperstack::runtime::hyperstack_interpreter::scheduler::SlotScheduler>>,
+                dedup: Option<hyperstack::runtime::hyperstack_server::SourceDedup>,
+            ) -> Self {
+                let txn_grouper = hyperstack::runtime::hyperstack_server::TransactionGrouper::new(
+                    mutations_tx.clone(),
+                    hyperstack::runtime::hyperstack_server::TransactionGroupConfig::from_env(),
+                );
+                Self {
+                    vm,
+                    bytecode,
+                    mutations_tx,
+                    health_monitor,
+                    slot_tracker,
+                    runtime_resolver,
+                    slot_scheduler,
+                    dedup,
+                    txn_grouper,
+                }
+            }
+
+            /// The VM shared behind a lock, as before handlers sent events to
+            /// a VM thread. Kept for one release.
+            #[deprecated(note = "spawn the VM with VmHandle::spawn and use VmHandler::from_vm_handle")]
+            #[allow(dead_code)]
             pub fn new(
                 vm: std::sync::Arc<std::sync::Mutex<hyperstack::runtime::hyperstack_interpreter::vm::VmContext>>,
-                bytecode: std::sync::Arc<hyperstack::runtime::hyperstack_interpreter::compiler::MultiEntityBytecode>,
+                bytecode: hyperstack::runtime::hyperstack_server::LiveBytecode,
                 mutations_tx: hyperstack::runtime::tokio::sync::mpsc::Sender<hyperstack::runtime::hyperstack_server::MutationBatch>,
                 health_monitor: Option<hyperstack::runtime::hyperstack_server::HealthMonitor>,
                 slot_tracker: hyperstack::runtime::hyperstack_server::SlotTracker,
                 runtime_resolver: hyperstack::runtime::hyperstack_interpreter::runtime_resolvers::SharedRuntimeResolver,
                 slot_scheduler: std::sync::Arc<std::sync::Mutex<hyperstack::runtime::hyperstack_interpreter::scheduler::SlotScheduler>>,
+                dedup: Option<hyperstack::runtime::hyperstack_server::SourceDedup>,
             ) -> Self {
-                Self {
+                #[allow(deprecated)]
+                let vm = hyperstack::runtime::hyperstack_interpreter::VmHandle::spawn_shared(vm);
+                Self::from_vm_handle(
                     vm,
                     bytecode,
                     mutations_tx,
@@ -1604,6 +1947,37 @@ pub fn generate_vm_handler_struct() -> TokenStream {
                     slot_tracker,
                     runtime_resolver,
                     slot_scheduler,
+                    dedup,
+                )
+            }
+
+            /// The event as the dead-letter queue keeps it, taken only while
+            /// one is attached since it copies the event
+            fn failed_event(
+                &self,
+                event_type: &str,
+                event_value: &hyperstack::runtime::serde_json::Value,
+                slot: u64,
+                signature: &str,
+            ) -> Option<hyperstack::runtime::hyperstack_server::FailedEvent> {
+                self.health_monitor.as_ref()?.failed_events()?;
+                Some(hyperstack::runtime::hyperstack_server::FailedEvent::new(
+                    event_type,
+                    event_value.clone(),
+                    "",
+                    slot,
+                    Some(signature.to_string()),
+                ))
+            }
+
+            fn record_failed_event(
+                &self,
+                failed_event: Option<hyperstack::runtime::hyperstack_server::FailedEvent>,
+                error: impl std::fmt::Display,
+            ) {
+                if let (Some(health), Some(mut event)) = (&self.health_monitor, failed_event) {
+                    event.error = error.to_string();
+                    health.record_failed_event(event);
                 }
             }
 
@@ -1632,8 +2006,8 @@ pub fn generate_vm_handler_struct() -> TokenStream {
                 &self,
                 requests: Vec<hyperstack::runtime::hyperstack_interpreter::vm::ResolverRequest>,
             ) -> Vec<hyperstack::runtime::hyperstack_interpreter::Mutation> {
-                self.runtime_resolver
-                    .resolve_and_apply(&self.vm, self.bytecode.as_ref(), requests)
+                self.vm
+                    .resolve_and_apply(self.runtime_resolver.as_ref(), self.bytecode.load(), requests)
                     .await
             }
         }
@@ -1664,8 +2038,38 @@ pub fn generate_account_handler_impl(
                     health.record_event().await;
                 }
 
+                if let Some(ref dedup) = self.dedup {
+                    if !dedup.account(&account.pubkey, slot, write_version) {
+                        return Ok(());
+                    }
+                }
+
                 let account_address = hyperstack::runtime::bs58::encode(&account.pubkey).into_string();
 
+                if value.is_closed() {
+                    let bytecode = self.bytecode.load();
+                    let closed_address = account_address.clone();
+                    let tombstones = self
+                        .vm
+                        .call(move |vm| vm.close_account(&bytecode, &closed_address, slot))
+                        .await
+                        .unwrap_or_default();
+                    if !tombstones.is_empty() {
+                        self.slot_tracker.record(slot);
+                        let event_context = hyperstack::runtime::hyperstack_server::EventContext {
+                            program: #program_name_lit.to_string(),
+                            event_kind: "account".to_string(),
+                            event_type: value.event_type().to_string(),
+                            account: Some(account_address),
+                            accounts_count: None,
+                            instructions: Vec::new(),
+                        };
+                        self.send_mutations_with_context(tombstones, slot, write_version, Some(event_context))
+                            .await;
+                    }
+                    return Ok(());
+                }
+
                 let event_type = value.event_type();
                 let mut log = hyperstack::runtime::hyperstack_interpreter::CanonicalLog::new();
                 log.set("phase", "vixen")
@@ -1679,121 +2083,139 @@ pub fn generate_account_handler_impl(
                 if let Some(obj) = event_value.as_object_mut() {
                     obj.insert("__account_address".to_string(), hyperstack::runtime::serde_json::json!(account_address));
                 }
+                let failed_event = self.failed_event(event_type, &event_value, slot, &signature);
+
+                // Loaded once so a reload never splits an event across two specs
+                let bytecode = self.bytecode.load();
+                let slot_scheduler = self.slot_scheduler.clone();
+                let job_account_address = account_address.clone();
+                let processed = self.vm.call(move |vm| {
+                    let account_address = job_account_address;
+
+                    let resolver_result = {
+                        if let Some(state_table) = vm.get_state_table_mut(0) {
+                            let mut ctx = hyperstack::runtime::hyperstack_interpreter::resolvers::ResolveContext::new(
+                                0,
+                                slot,
+                                signature.clone(),
+                                &mut state_table.pda_reverse_lookups,
+                            );
 
-                let resolver_result = {
-                    let mut vm = self.vm.lock().unwrap_or_else(|e| e.into_inner());
-
-                    if let Some(state_table) = vm.get_state_table_mut(0) {
-                        let mut ctx = hyperstack::runtime::hyperstack_interpreter::resolvers::ResolveContext::new(
-                            0,
-                            slot,
-                            signature.clone(),
-                            &mut state_table.pda_reverse_lookups,
-                        );
-
-                        if let Some(resolver_fn) = get_resolver_for_account_type(event_type) {
-                            resolver_fn(&account_address, &event_value, &mut ctx)
+                            if let Some(resolver_fn) = get_resolver_for_account_type(event_type) {
+                                resolver_fn(&account_address, &event_value, &mut ctx)
+                            } else {
+                                hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(String::new())
+                            }
                         } else {
                             hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(String::new())
                         }
-                    } else {
-                        hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(String::new())
-                    }
-                };
+                    };
 
-                match resolver_result {
-                    hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(resolved_key) => {
-                        hyperstack::runtime::tracing::info!(
-                            event_type = %event_type,
-                            account = %account_address,
-                            resolved_key = %resolved_key,
-                            slot = slot,
-                            "[PDA] Account key resolution: Found"
-                        );
-                        if !resolved_key.is_empty() {
-                            if let Some(obj) = event_value.as_object_mut() {
-                                obj.insert("__resolved_primary_key".to_string(), hyperstack::runtime::serde_json::json!(resolved_key));
+                    match resolver_result {
+                        hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Found(resolved_key) => {
+                            hyperstack::runtime::tracing::info!(
+                                event_type = %event_type,
+                                account = %account_address,
+                                resolved_key = %resolved_key,
+                                slot = slot,
+                                "[PDA] Account key resolution: Found"
+                            );
+                            if !resolved_key.is_empty() {
+                                if let Some(obj) = event_value.as_object_mut() {
+                                    obj.insert("__resolved_primary_key".to_string(), hyperstack::runtime::serde_json::json!(resolved_key));
+                                }
                             }
                         }
+                        hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::QueueUntil(_discriminators) => {
+                            let _ = vm.queue_account_update(
+                                0,
+                                hyperstack::runtime::hyperstack_interpreter::QueuedAccountUpdate {
+                                    pda_address: account_address.clone(),
+                                    account_type: event_type.to_string(),
+                                    account_data: event_value,
+                                    slot,
+                                    write_version,
+                                    signature,
+                                },
+                            );
+                            return None;
+                        }
+                        hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Skip => {
+                            return None;
+                        }
                     }
-                    hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::QueueUntil(_discriminators) => {
-                        let mut vm = self.vm.lock().unwrap_or_else(|e| e.into_inner());
-                        let _ = vm.queue_account_update(
-                            0,
-                            hyperstack::runtime::hyperstack_interpreter::QueuedAccountUpdate {
-                                pda_address: account_address.clone(),
+
+                    let (mutations_result, resolver_requests, scheduled_callbacks) = {
+
+                        let context = hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_account(slot, signature.clone(), write_version).with_block_time().with_program_id(#parser_mod::PROGRAM_ID_STR);
+
+                        let event_value_for_cache = event_value.clone();
+
+                        let result = vm.process_event(&bytecode, event_value, event_type, Some(&context), Some(&mut log))
+                            .map_err(hyperstack::runtime::hyperstack_interpreter::vm::HandlerError::from);
+
+                        if result.is_ok() {
+                            // Cache under every state_id that routes this event_type so that
+                            // register_pda_reverse_lookup finds data for all participating entities.
+                            let state_ids: std::collections::HashSet<u32> = bytecode
+                                .route(event_type, context.program_id())
+                                .unwrap_or_default()
+                                .into_iter()
+                                .filter_map(|name| bytecode.entities.get(name).map(|eb| eb.state_id))
+                                .collect();
+                            let pending = hyperstack::runtime::hyperstack_interpreter::PendingAccountUpdate {
                                 account_type: event_type.to_string(),
-                                account_data: event_value,
+                                pda_address: account_address.clone(),
+                                account_data: event_value_for_cache,
                                 slot,
                                 write_version,
-                                signature,
-                            },
-                        );
-                        return Ok(());
-                    }
-                    hyperstack::runtime::hyperstack_interpreter::resolvers::KeyResolution::Skip => {
-                        return Ok(());
-                    }
-                }
+                                signature: signature.clone(),
+                                queued_at: hyperstack::runtime::hyperstack_interpreter::Clock::unix_secs(vm.clock()),
+                                is_stale_reprocess: false,
+                            };
+                            for state_id in state_ids {
+                                vm.cache_last_account_data(state_id, &account_address, pending.clone());
+                            }
+                        }
 
-                let (mutations_result, resolver_requests, scheduled_callbacks) = {
-                    let mut vm = self.vm.lock().unwrap_or_else(|e| e.into_inner());
-
-                    let context = hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_account(slot, signature.clone(), write_version);
-
-                    let event_value_for_cache = event_value.clone();
-
-                    let result = vm.process_event(&self.bytecode, event_value, event_type, Some(&context), Some(&mut log))
-                        .map_err(|e| e.to_string());
-
-                    if result.is_ok() {
-                        // Cache under every state_id that routes this event_type so that
-                        // register_pda_reverse_lookup finds data for all participating entities.
-                        let state_ids: std::collections::HashSet<u32> = self.bytecode.event_routing
-                            .get(event_type)
-                            .map(|entities| entities.iter()
-                                .filter_map(|name| self.bytecode.entities.get(name).map(|eb| eb.state_id))
-                                .collect())
-                            .unwrap_or_default();
-                        let pending = hyperstack::runtime::hyperstack_interpreter::PendingAccountUpdate {
-                            account_type: event_type.to_string(),
-                            pda_address: account_address.clone(),
-                            account_data: event_value_for_cache,
-                            slot,
-                            write_version,
-                            signature: signature.clone(),
-                            queued_at: std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_secs() as i64,
-                            is_stale_reprocess: false,
+                        let requests = if result.is_ok() {
+                            vm.take_resolver_requests()
+                        } else {
+                            Vec::new()
                         };
-                        for state_id in state_ids {
-                            vm.cache_last_account_data(state_id, &account_address, pending.clone());
-                        }
-                    }
 
-                    let requests = if result.is_ok() {
-                        vm.take_resolver_requests()
-                    } else {
-                        Vec::new()
-                    };
+                        let scheduled = if result.is_ok() {
+                            vm.take_scheduled_callbacks()
+                        } else {
+                            Vec::new()
+                        };
 
-                    let scheduled = if result.is_ok() {
-                        vm.take_scheduled_callbacks()
-                    } else {
-                        Vec::new()
+                        (result, requests, scheduled)
                     };
 
-                    (result, requests, scheduled)
-                };
+                    if !scheduled_callbacks.is_empty() {
+                        let mut scheduler = slot_scheduler.lock().unwrap_or_else(|e| e.into_inner());
+                        for (target_slot, callback) in scheduled_callbacks {
+                            scheduler.register(target_slot, callback);
+                        }
+                    }
+
+                    Some((mutations_result, resolver_requests))
+                })
+                .await;
 
-                if !scheduled_callbacks.is_empty() {
-                    let mut scheduler = self.slot_scheduler.lock().unwrap_or_else(|e| e.into_inner());
-                    for (target_slot, callback) in scheduled_callbacks {
-                        scheduler.register(target_slot, callback);
+                let (mutations_result, resolver_requests) = match processed {
+                    Ok(Some(processed)) => processed,
+                    // Queued until its PDA is known, or skipped
+                    Ok(None) => return Ok(()),
+                    Err(e) => {
+                        self.record_failed_event(failed_event, format!("VM unavailable: {}", e));
+                        if let Some(ref health) = self.health_monitor {
+                            health.record_error(format!("VM unavailable for {}: {}", event_type, e)).await;
+                        }
+                        return Ok(());
                     }
-                }
+                };
 
                 let resolver_mutations = if mutations_result.is_ok() {
                     self.resolve_and_apply_resolvers(resolver_requests).await
@@ -1813,6 +2235,7 @@ pub fn generate_account_handler_impl(
                             event_type: event_type.to_string(),
                             account: Some(account_address),
                             accounts_count: None,
+                            instructions: Vec::new(),
                         };
                         self.send_mutations_with_context(
                             mutations,
@@ -1824,8 +2247,12 @@ pub fn generate_account_handler_impl(
                         Ok(())
                     }
                     Err(e) => {
+                        self.record_failed_event(failed_event, &e);
                         if let Some(ref health) = self.health_monitor {
-                            health.record_error(format!("VM error for {}: {}", event_type, e)).await;
+                            match e.vm_error() {
+                                Some(vm_error) => health.record_vm_error(event_type, vm_error).await,
+                                None => health.record_error(format!("VM error for {}: {}", event_type, e)).await,
+                            }
                         }
                         Ok(())
                     }
@@ -1859,6 +2286,12 @@ pub fn generate_instruction_handler_impl(
                     health.record_event().await;
                 }
 
+                if let Some(ref dedup) = self.dedup {
+                    if !dedup.instruction(&raw_update.shared.signature) {
+                        return Ok(());
+                    }
+                }
+
                 let static_keys_vec = &raw_update.accounts;
                 let event_type = value.event_type();
                 let mut log = hyperstack::runtime::hyperstack_interpreter::CanonicalLog::new();
@@ -1869,164 +2302,179 @@ pub fn generate_instruction_handler_impl(
                     .set("txn_index", txn_index)
                     .set("program", #entity_name_lit)
                     .set("accounts_count", static_keys_vec.len());
-                let event_value = value.to_value_with_accounts(static_keys_vec);
-
-                let bytecode = self.bytecode.clone();
-                let (mutations_result, resolver_requests, scheduled_callbacks) = {
-                    let mut vm = self.vm.lock().unwrap_or_else(|e| e.into_inner());
-
-                    let context = hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_instruction(slot, signature.clone(), txn_index);
-
-                    let mut result = vm.process_event(&bytecode, event_value.clone(), event_type, Some(&context), Some(&mut log))
-                        .map_err(|e| e.to_string());
-
-                    if result.is_ok() {
-                        let hooks = get_instruction_hooks(event_type);
-                        if !hooks.is_empty() {
-                            let accounts = event_value.get("accounts")
-                                .and_then(|a| a.as_object())
-                                .map(|obj| {
-                                    obj.iter()
-                                        .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
-                                        .collect::<std::collections::HashMap<String, String>>()
-                                })
-                                .unwrap_or_default();
-
-                            let instruction_data = event_value.get("data").unwrap_or(&hyperstack::runtime::serde_json::Value::Null);
-
-                            let timestamp = vm.current_context()
-                                .map(|ctx| ctx.timestamp())
-                                .unwrap_or_else(|| std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_secs() as i64);
-
-                            let vm_ptr: *mut hyperstack::runtime::hyperstack_interpreter::vm::VmContext = &mut *vm as *mut hyperstack::runtime::hyperstack_interpreter::vm::VmContext;
-
-                            let mut ctx = hyperstack::runtime::hyperstack_interpreter::resolvers::InstructionContext::with_metrics(
-                                accounts,
-                                0,
-                                &mut *vm,
-                                unsafe { (*vm_ptr).registers_mut() },
-                                2,
-                                unsafe { (*vm_ptr).path_cache() },
-                                instruction_data,
-                                Some(context.slot.unwrap_or(0)),
-                                context.signature.clone(),
-                                timestamp,
-                            );
+                let event_value = value.to_value_with_transaction(raw_update);
+                let failed_event = self.failed_event(event_type, &event_value, slot, &signature);
+
+                let bytecode = self.bytecode.load();
+                let slot_scheduler = self.slot_scheduler.clone();
+                let txn_signature = signature.clone();
+                let processed = self.vm.call(move |vm| {
+                    let (mutations_result, resolver_requests, scheduled_callbacks) = {
+
+                        let context = hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_instruction(slot, signature.clone(), txn_index).with_block_time().with_program_id(#parser_mod::PROGRAM_ID_STR);
+
+                        let mut result = vm.process_event(&bytecode, event_value.clone(), event_type, Some(&context), Some(&mut log))
+                            .map_err(hyperstack::runtime::hyperstack_interpreter::vm::HandlerError::from);
+
+                        if result.is_ok() {
+                            let hooks = get_instruction_hooks(event_type);
+                            if !hooks.is_empty() {
+                                let accounts = event_value.get("accounts")
+                                    .and_then(|a| a.as_object())
+                                    .map(|obj| {
+                                        obj.iter()
+                                            .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
+                                            .collect::<std::collections::HashMap<String, String>>()
+                                    })
+                                    .unwrap_or_default();
+
+                                let instruction_data = event_value.get("data").unwrap_or(&hyperstack::runtime::serde_json::Value::Null);
+
+                                let timestamp = vm.current_context()
+                                    .map(|ctx| ctx.timestamp())
+                                    .unwrap_or_else(|| hyperstack::runtime::hyperstack_interpreter::Clock::unix_secs(vm.clock()));
+
+                                let vm_ptr: *mut hyperstack::runtime::hyperstack_interpreter::vm::VmContext = &mut *vm as *mut hyperstack::runtime::hyperstack_interpreter::vm::VmContext;
+
+                                let mut ctx = hyperstack::runtime::hyperstack_interpreter::resolvers::InstructionContext::with_metrics(
+                                    accounts,
+                                    0,
+                                    &mut *vm,
+                                    unsafe { (*vm_ptr).registers_mut() },
+                                    2,
+                                    unsafe { (*vm_ptr).path_cache() },
+                                    instruction_data,
+                                    Some(context.slot.unwrap_or(0)),
+                                    context.signature.clone(),
+                                    timestamp,
+                                );
 
-                            for hook_fn in hooks.iter() {
-                                hook_fn(&mut ctx);
-                            }
+                                for hook_fn in hooks.iter() {
+                                    hook_fn(&mut ctx);
+                                }
 
-                            let pending_updates = ctx.take_pending_updates();
+                                let pending_updates = ctx.take_pending_updates();
 
-                            drop(ctx);
+                                drop(ctx);
 
-                            if !pending_updates.is_empty() {
-                                hyperstack::runtime::tracing::info!(
-                                    count = pending_updates.len(),
-                                    event_type = %event_type,
-                                    "[PDA] Flushing pending account updates from instruction hooks"
-                                );
-                                for update in pending_updates {
+                                if !pending_updates.is_empty() {
                                     hyperstack::runtime::tracing::info!(
-                                        account_type = %update.account_type,
-                                        pda = %update.pda_address,
-                                        update_slot = update.slot,
-                                        current_instruction_slot = slot,
-                                        "[PDA] Reprocessing flushed update"
+                                        count = pending_updates.len(),
+                                        event_type = %event_type,
+                                        "[PDA] Flushing pending account updates from instruction hooks"
                                     );
-                                    let resolved_key = vm.try_chained_pda_lookup(0, "default_pda_lookup", &update.pda_address);
-
-                                    let mut account_data = update.account_data;
-                                    if let Some(ref key) = resolved_key {
+                                    for update in pending_updates {
                                         hyperstack::runtime::tracing::info!(
+                                            account_type = %update.account_type,
                                             pda = %update.pda_address,
-                                            resolved_key = %key,
-                                            "[PDA] Chained PDA lookup resolved for reprocessed update"
-                                        );
-                                        if let Some(obj) = account_data.as_object_mut() {
-                                            obj.insert("__resolved_primary_key".to_string(), hyperstack::runtime::serde_json::json!(key));
-                                        }
-                                    } else {
-                                        hyperstack::runtime::tracing::warn!(
-                                            pda = %update.pda_address,
-                                            "[PDA] Chained PDA lookup returned None for reprocessed update"
+                                            update_slot = update.slot,
+                                            current_instruction_slot = slot,
+                                            "[PDA] Reprocessing flushed update"
                                         );
-                                    }
+                                        let resolved_key = vm.try_chained_pda_lookup(0, "default_pda_lookup", &update.pda_address);
 
-                                    let update_context = if update.is_stale_reprocess {
-                                        hyperstack::runtime::tracing::info!(
-                                            pda = %update.pda_address,
-                                            "[PDA] Using reprocessed context (empty sig, skip resolvers)"
-                                        );
-                                        hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_reprocessed(
-                                            update.slot,
-                                            update.write_version,
-                                        )
-                                    } else {
-                                        hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_account(
-                                            update.slot,
-                                            update.signature.clone(),
-                                            update.write_version,
-                                        )
-                                    };
-
-                                    match vm.process_event(&bytecode, account_data, &update.account_type, Some(&update_context), None) {
-                                        Ok(pending_mutations) => {
+                                        let mut account_data = update.account_data;
+                                        if let Some(ref key) = resolved_key {
                                             hyperstack::runtime::tracing::info!(
-                                                account_type = %update.account_type,
                                                 pda = %update.pda_address,
-                                                mutations = pending_mutations.len(),
-                                                is_stale = update.is_stale_reprocess,
-                                                "[PDA] Reprocessed flushed account update"
+                                                resolved_key = %key,
+                                                "[PDA] Chained PDA lookup resolved for reprocessed update"
                                             );
-                                            if let Ok(ref mut mutations) = result {
-                                                mutations.extend(pending_mutations);
+                                            if let Some(obj) = account_data.as_object_mut() {
+                                                obj.insert("__resolved_primary_key".to_string(), hyperstack::runtime::serde_json::json!(key));
                                             }
-                                        }
-                                        Err(e) => {
+                                        } else {
                                             hyperstack::runtime::tracing::warn!(
-                                                account_type = %update.account_type,
-                                                error = %e,
-                                                "[PDA] Flushed account reprocessing failed"
+                                                pda = %update.pda_address,
+                                                "[PDA] Chained PDA lookup returned None for reprocessed update"
                                             );
                                         }
+
+                                        let update_context = if update.is_stale_reprocess {
+                                            hyperstack::runtime::tracing::info!(
+                                                pda = %update.pda_address,
+                                                "[PDA] Using reprocessed context (empty sig, skip resolvers)"
+                                            );
+                                            hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_reprocessed(
+                                                update.slot,
+                                                update.write_version,
+                                            )
+                                        } else {
+                                            hyperstack::runtime::hyperstack_interpreter::UpdateContext::new_account(
+                                                update.slot,
+                                                update.signature.clone(),
+                                                update.write_version,
+                                            )
+                                        };
+
+                                        match vm.process_event(&bytecode, account_data, &update.account_type, Some(&update_context), None) {
+                                            Ok(pending_mutations) => {
+                                                hyperstack::runtime::tracing::info!(
+                                                    account_type = %update.account_type,
+                                                    pda = %update.pda_address,
+                                                    mutations = pending_mutations.len(),
+                                                    is_stale = update.is_stale_reprocess,
+                                                    "[PDA] Reprocessed flushed account update"
+                                                );
+                                                if let Ok(ref mut mutations) = result {
+                                                    mutations.extend(pending_mutations);
+                                                }
+                                            }
+                                            Err(e) => {
+                                                hyperstack::runtime::tracing::warn!(
+                                                    account_type = %update.account_type,
+                                                    error = %e,
+                                                    "[PDA] Flushed account reprocessing failed"
+                                                );
+                                            }
+                                        }
                                     }
                                 }
                             }
-                        }
 
-                        if vm.instructions_executed % 1000 == 0 {
-                            let _ = vm.cleanup_all_expired(0);
-                            let stats = vm.get_memory_stats(0);
-                            hyperstack::runtime::hyperstack_interpreter::vm_metrics::record_memory_stats(&stats, #entity_name_lit);
+                            if vm.instructions_executed % 1000 == 0 {
+                                let _ = vm.cleanup_all_expired(0);
+                                let stats = vm.get_memory_stats(0);
+                                hyperstack::runtime::hyperstack_interpreter::vm_metrics::record_memory_stats(&stats, #entity_name_lit);
+                            }
                         }
-                    }
 
-                    let requests = if result.is_ok() {
-                        vm.take_resolver_requests()
-                    } else {
-                        Vec::new()
-                    };
+                        let requests = if result.is_ok() {
+                            vm.take_resolver_requests()
+                        } else {
+                            Vec::new()
+                        };
 
-                    let scheduled = if result.is_ok() {
-                        vm.take_scheduled_callbacks()
-                    } else {
-                        Vec::new()
+                        let scheduled = if result.is_ok() {
+                            vm.take_scheduled_callbacks()
+                        } else {
+                            Vec::new()
+                        };
+
+                        (result, requests, scheduled)
                     };
 
-                    (result, requests, scheduled)
-                };
+                    if !scheduled_callbacks.is_empty() {
+                        let mut scheduler = slot_scheduler.lock().unwrap_or_else(|e| e.into_inner());
+                        for (target_slot, callback) in scheduled_callbacks {
+                            scheduler.register(target_slot, callback);
+                        }
+                    }
+
+                    (mutations_result, resolver_requests)
+                })
+                .await;
 
-                if !scheduled_callbacks.is_empty() {
-                    let mut scheduler = self.slot_scheduler.lock().unwrap_or_else(|e| e.into_inner());
-                    for (target_slot, callback) in scheduled_callbacks {
-                        scheduler.register(target_slot, callback);
+                let (mutations_result, resolver_requests) = match processed {
+                    Ok(processed) => processed,
+                    Err(e) => {
+                        self.record_failed_event(failed_event, format!("VM unavailable: {}", e));
+                        if let Some(ref health) = self.health_monitor {
+                            health.record_error(format!("VM unavailable for {}: {}", event_type, e)).await;
+                        }
+                        return Ok(());
                     }
-                }
+                };
 
                 let resolver_mutations = if mutations_result.is_ok() {
                     self.resolve_and_apply_resolvers(resolver_requests).await
@@ -2046,19 +2494,26 @@ pub fn generate_instruction_handler_impl(
                             event_type: event_type.to_string(),
                             account: None,
                             accounts_count: Some(static_keys_vec.len()),
+                            instructions: Vec::new(),
                         };
-                        self.send_mutations_with_context(
-                            mutations,
-                            slot,
-                            txn_index as u64,
-                            Some(event_context),
-                        )
-                        .await;
+                        self.txn_grouper
+                            .add(hyperstack::runtime::hyperstack_server::InstructionMutations {
+                                signature: txn_signature,
+                                slot,
+                                txn_index: txn_index as u64,
+                                event_context,
+                                mutations,
+                            })
+                            .await;
                         Ok(())
                     }
                     Err(e) => {
+                        self.record_failed_event(failed_event, &e);
                         if let Some(ref health) = self.health_monitor {
-                            health.record_error(format!("VM error for {}: {}", event_type, e)).await;
+                            match e.vm_error() {
+                                Some(vm_error) => health.record_vm_error(event_type, vm_error).await,
+                                None => health.record_error(format!("VM error for {}: {}", event_type, e)).await,
+                            }
                         }
                         Ok(())
                     }
@@ -2075,6 +2530,8 @@ pub struct PipelineInfo {
     pub program_id: String,
     pub state_enum_name: String,
     pub instruction_enum_name: String,
+    /// Only the program's accounts are parsed, as for `extra_accounts`
+    pub accounts_only: bool,
 }
 
 pub fn generate_multi_pipeline_spec_function(
@@ -2114,7 +2571,11 @@ pub fn generate_multi_pipeline_spec_function(
             let acct_var = format_ident!("account_pipeline_{}", i);
             let ix_var = format_ident!("instruction_pipeline_{}", i);
             let is_last = i == pipelines.len() - 1;
-            if is_last {
+            if p.accounts_only {
+                quote! {
+                    let #acct_var = Pipeline::new(#parser_mod::AccountParser, [handler.clone()]);
+                }
+            } else if is_last {
                 quote! {
                     let #acct_var = Pipeline::new(#parser_mod::AccountParser, [handler.clone()]);
                     let #ix_var = Pipeline::new(#parser_mod::InstructionParser, [handler]);
@@ -2131,12 +2592,16 @@ pub fn generate_multi_pipeline_spec_function(
     let pipeline_registrations: Vec<TokenStream> = pipelines
         .iter()
         .enumerate()
-        .map(|(i, _)| {
+        .map(|(i, p)| {
             let acct_var = format_ident!("account_pipeline_{}", i);
             let ix_var = format_ident!("instruction_pipeline_{}", i);
-            quote! {
-                .account(#acct_var)
-                .instruction(#ix_var)
+            if p.accounts_only {
+                quote! { .account(#acct_var) }
+            } else {
+                quote! {
+                    .account(#acct_var)
+                    .instruction(#ix_var)
+                }
             }
         })
         .collect();
@@ -2145,6 +2610,11 @@ pub fn generate_multi_pipeline_spec_function(
         let log_stmts: Vec<TokenStream> = pipelines.iter().map(|p| {
             let parser_mod = format_ident!("{}", p.parser_module_name);
             let prog_name = &p.program_name;
+            if p.accounts_only {
+                return quote! {
+                    hyperstack::runtime::tracing::info!("   - {} Account Parser ID: {}", #prog_name, hyperstack::runtime::yellowstone_vixen_core::Parser::id(&#parser_mod::AccountParser));
+                };
+            }
             quote! {
                 hyperstack::runtime::tracing::info!("   - {} Account Parser ID: {}", #prog_name, hyperstack::runtime::yellowstone_vixen_core::Parser::id(&#parser_mod::AccountParser));
                 hyperstack::runtime::tracing::info!("   - {} Instruction Parser ID: {}", #prog_name, hyperstack::runtime::yellowstone_vixen_core::Parser::id(&#parser_mod::InstructionParser));
@@ -2170,30 +2640,64 @@ pub fn generate_multi_pipeline_spec_function(
     let slot_scheduler_task = generate_slot_scheduler_task();
     let slot_subscription_task = generate_slot_subscription_task();
 
+    let parser_mods: Vec<_> = pipelines
+        .iter()
+        .map(|p| format_ident!("{}", p.parser_module_name))
+        .collect();
+    let account_backfill = generate_account_backfill(&parser_mods);
+
     quote! {
         pub fn spec() -> hyperstack::runtime::hyperstack_server::Spec {
+            let live_bytecode = hyperstack::runtime::hyperstack_server::LiveBytecode::new(create_multi_entity_bytecode());
             let bytecode = create_multi_entity_bytecode();
             let program_id = #primary_parser_mod::PROGRAM_ID_STR.to_string();
 
-            hyperstack::runtime::hyperstack_server::Spec::new(bytecode, program_id)
-                .with_parser_setup(create_parser_setup())
-                #views_call
+            let backfill_handler = BackfillHandler::default();
+            let pending_vm_state = PendingVmState::default();
+            let vm_clock = VmClock::default();
+
+            let mut spec = hyperstack::runtime::hyperstack_server::Spec::new(bytecode, program_id)
+                .with_parser_setup(create_parser_setup(backfill_handler.clone(), pending_vm_state.clone(), vm_clock.clone(), live_bytecode.clone()))
+                .with_vm_snapshot(create_vm_snapshot(backfill_handler.clone()))
+                .with_vm_stats(create_vm_stats(backfill_handler.clone()))
+                .with_vm_state(create_vm_state_export(backfill_handler.clone()), create_vm_state_import(pending_vm_state))
+                .with_vm_clock(create_vm_clock(vm_clock))
+                .with_account_backfill(create_account_backfill(backfill_handler))
+                .with_live_bytecode(live_bytecode)
+                #views_call;
+            spec.program_ids = vec![#(#parser_mods::PROGRAM_ID_STR.to_string()),*];
+            spec
         }
 
-        fn create_parser_setup() -> hyperstack::runtime::hyperstack_server::ParserSetupFn {
+        fn create_parser_setup(
+            backfill_handler: BackfillHandler,
+            pending_vm_state: PendingVmState,
+            vm_clock: VmClock,
+            live_bytecode: hyperstack::runtime::hyperstack_server::LiveBytecode,
+        ) -> hyperstack::runtime::hyperstack_server::ParserSetupFn {
             use std::sync::Arc;
 
-            Arc::new(|mutations_tx, health_monitor, reconnection_config| {
+            Arc::new(move |mutations_tx, health_monitor, reconnection_config| {
+                let backfill_handler = backfill_handler.clone();
+                let pending_vm_state = pending_vm_state.clone();
+                let vm_clock = vm_clock.clone();
+                let live_bytecode = live_bytecode.clone();
                 Box::pin(async move {
-                    run_vixen_runtime_with_channel(mutations_tx, health_monitor, reconnection_config).await
+                    run_vixen_runtime_with_channel(mutations_tx, health_monitor, reconnection_config, backfill_handler, pending_vm_state, vm_clock, live_bytecode).await
                 })
             })
         }
 
+        #account_backfill
+
         async fn run_vixen_runtime_with_channel(
             mutations_tx: hyperstack::runtime::tokio::sync::mpsc::Sender<hyperstack::runtime::hyperstack_server::MutationBatch>,
             health_monitor: Option<hyperstack::runtime::hyperstack_server::HealthMonitor>,
             reconnection_config: hyperstack::runtime::hyperstack_server::ReconnectionConfig,
+            backfill_handler: BackfillHandler,
+            pending_vm_state: PendingVmState,
+            vm_clock: VmClock,
+            live_bytecode: hyperstack::runtime::hyperstack_server::LiveBytecode,
         ) -> hyperstack::runtime::anyhow::Result<()> {
             use hyperstack::runtime::yellowstone_vixen::config::{BufferConfig, VixenConfig};
             use hyperstack::runtime::yellowstone_vixen_yellowstone_grpc_source::YellowstoneGrpcConfig;
@@ -2209,12 +2713,7 @@ pub fn generate_multi_pipeline_spec_function(
                 hyperstack::runtime::tracing::warn!("No .env file found. Make sure environment variables are set.");
             }
 
-            let endpoint = std::env::var("YELLOWSTONE_ENDPOINT")
-                .map_err(|_| hyperstack::runtime::anyhow::anyhow!(
-                    "YELLOWSTONE_ENDPOINT environment variable must be set.\n\
-                     Example: export YELLOWSTONE_ENDPOINT=http://localhost:10000"
-                ))?;
-            let x_token = std::env::var("YELLOWSTONE_X_TOKEN").ok();
+            let yellowstone = hyperstack::runtime::hyperstack_server::YellowstoneConfig::from_env()?;
 
             let runtime_resolver: hyperstack::runtime::hyperstack_interpreter::runtime_resolvers::SharedRuntimeResolver =
                 hyperstack::runtime::hyperstack_interpreter::runtime_resolvers_factory::build_resolver()
@@ -2227,15 +2726,28 @@ pub fn generate_multi_pipeline_spec_function(
 
             let slot_tracker = hyperstack::runtime::hyperstack_server::SlotTracker::new();
             let slot_scheduler = Arc::new(Mutex::new(hyperstack::runtime::hyperstack_interpreter::scheduler::SlotScheduler::new()));
-            let mut attempt = 0u32;
-            let mut backoff = reconnection_config.initial_delay;
+            let first_connection = std::sync::atomic::AtomicBool::new(true);
 
-            let bytecode = create_multi_entity_bytecode();
+            let bytecode = live_bytecode.load();
 
             #bytecode_logging
 
-            let vm = Arc::new(Mutex::new(hyperstack::runtime::hyperstack_interpreter::vm::VmContext::new()));
-            let bytecode_arc = Arc::new(bytecode);
+            let vm = hyperstack::runtime::hyperstack_interpreter::VmHandle::spawn(
+                create_vm_context(&pending_vm_state, &vm_clock),
+            );
+
+            // Backfilled accounts share the VM but not the slot tracker, so an
+            // RPC slot never moves the resume point of the stream
+            let _ = backfill_handler.set(VmHandler::from_vm_handle(
+                vm.clone(),
+                live_bytecode.clone(),
+                mutations_tx.clone(),
+                None,
+                hyperstack::runtime::hyperstack_server::SlotTracker::new(),
+                runtime_resolver.clone(),
+                slot_scheduler.clone(),
+                None,
+            ));
 
             // Spawn slot scheduler background task
             #slot_scheduler_task
@@ -2243,91 +2755,62 @@ pub fn generate_multi_pipeline_spec_function(
             // Spawn dedicated gRPC slot subscription to drive the scheduler in real-time
             #slot_subscription_task
 
-            loop {
-                let from_slot = {
-                    let last = slot_tracker.get();
-                    if last > 0 { Some(last) } else { None }
-                };
-
-                if from_slot.is_some() {
-                    hyperstack::runtime::tracing::info!("Resuming from slot {}", from_slot.unwrap());
-                }
-
-                let vixen_config = VixenConfig {
-                    source: YellowstoneGrpcConfig {
-                        endpoint: endpoint.clone(),
-                        x_token: x_token.clone(),
-                        timeout: 60,
-                        commitment_level: None,
-                        from_slot,
-                        accept_compression: None,
-                        max_decoding_message_size: None,
-                    },
-                    buffer: BufferConfig::default(),
-                };
-
-                let handler = VmHandler::new(
-                    vm.clone(),
-                    bytecode_arc.clone(),
-                    mutations_tx.clone(),
-                    health_monitor.clone(),
-                    slot_tracker.clone(),
-                    runtime_resolver.clone(),
-                    slot_scheduler.clone(),
-                );
-
-                if attempt == 0 {
-                    hyperstack::runtime::tracing::info!("Starting yellowstone-vixen runtime for {} program", #primary_program_name_lit);
-                    #(#program_id_stmts)*
-                    #parser_logging
-                }
-
-                if let Some(ref health) = health_monitor {
-                    health.record_reconnecting().await;
-                }
-
-                #(#pipeline_creations)*
-
-                if let Some(ref health) = health_monitor {
-                    health.record_connection().await;
-                }
-
-                let result = hyperstack::runtime::yellowstone_vixen::Runtime::<YellowstoneGrpcSource>::builder()
-                    #(#pipeline_registrations)*
-                    .build(vixen_config)
-                    .try_run_async()
-                    .await;
-
-                if let Err(e) = result {
-                    hyperstack::runtime::tracing::error!("Vixen runtime error: {:?}", e);
-                }
-
-                attempt += 1;
+            hyperstack::runtime::hyperstack_server::run_sources(
+                &yellowstone,
+                &reconnection_config,
+                health_monitor.clone(),
+                |endpoint, dedup| {
+                    let from_slot = {
+                        let last = slot_tracker.get();
+                        if last > 0 { Some(last) } else { None }
+                    };
 
-                if let Some(max) = reconnection_config.max_attempts {
-                    if attempt >= max {
-                        hyperstack::runtime::tracing::error!("Max reconnection attempts ({}) reached, giving up", max);
-                        if let Some(ref health) = health_monitor {
-                            health.record_error("Max reconnection attempts reached".into()).await;
-                        }
-                        return Err(hyperstack::runtime::anyhow::anyhow!("Max reconnection attempts reached"));
+                    if from_slot.is_some() {
+                        hyperstack::runtime::tracing::info!("Resuming from slot {}", from_slot.unwrap());
                     }
-                }
-
-                hyperstack::runtime::tracing::warn!(
-                    "gRPC stream disconnected. Reconnecting in {:?} (attempt {})",
-                    backoff,
-                    attempt
-                );
 
-                if let Some(ref health) = health_monitor {
-                    health.record_disconnection().await;
-                }
+                    let vixen_config = VixenConfig {
+                        source: YellowstoneGrpcConfig {
+                            endpoint: endpoint.endpoint,
+                            x_token: endpoint.x_token,
+                            timeout: 60,
+                            commitment_level: None,
+                            from_slot,
+                            accept_compression: None,
+                            max_decoding_message_size: None,
+                        },
+                        buffer: BufferConfig::default(),
+                    };
 
-                hyperstack::runtime::tokio::time::sleep(backoff).await;
+                    let handler = VmHandler::from_vm_handle(
+                        vm.clone(),
+                        live_bytecode.clone(),
+                        mutations_tx.clone(),
+                        health_monitor.clone(),
+                        slot_tracker.clone(),
+                        runtime_resolver.clone(),
+                        slot_scheduler.clone(),
+                        dedup,
+                    );
 
-                backoff = reconnection_config.next_backoff(backoff);
-            }
+                    if first_connection.swap(false, std::sync::atomic::Ordering::Relaxed) {
+                        hyperstack::runtime::tracing::info!("Starting yellowstone-vixen runtime for {} program", #primary_program_name_lit);
+                        #(#program_id_stmts)*
+                        #parser_logging
+                    }
+                    #(#pipeline_creations)*
+
+                    async move {
+                        hyperstack::runtime::yellowstone_vixen::Runtime::<YellowstoneGrpcSource>::builder()
+                            #(#pipeline_registrations)*
+                            .build(vixen_config)
+                            .try_run_async()
+                            .await
+                            .map_err(|e| hyperstack::runtime::anyhow::anyhow!("Vixen runtime error: {:?}", e))
+                    }
+                },
+            )
+            .await
         }
     }
 }
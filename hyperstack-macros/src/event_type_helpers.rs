@@ -53,6 +53,19 @@ pub fn program_name_for_type<'a>(type_str: &str, idls: IdlLookup<'a>) -> Option<
     find_idl_for_type(type_str, idls).map(|idl| idl.get_name())
 }
 
+/// Address of `idl`'s program, used to qualify the routing of its event types
+/// when several IDLs are in play and their type names may collide. `None`
+/// with a single IDL.
+pub fn program_hint(idl: Option<&IdlSpec>, idls: IdlLookup) -> Option<String> {
+    if idls.len() < 2 {
+        return None;
+    }
+    let idl = idl?;
+    idl.address
+        .clone()
+        .or_else(|| idl.metadata.as_ref().and_then(|m| m.address.clone()))
+}
+
 pub fn program_name_from_sdk_prefix(sdk_module: &str) -> &str {
     sdk_module.strip_suffix("_sdk").unwrap_or(sdk_module)
 }
@@ -1250,4 +1250,26 @@ mod tests {
             "non-array type should NOT be large"
         );
     }
+
+    #[test]
+    fn test_wide_values_emit_exact_json() {
+        let idl = parse_idl_content(include_str!(
+            "../../hyperstack-idl/tests/fixtures/wide_values.json"
+        ))
+        .expect("fixture IDL should parse");
+        let code = generate_sdk_types(&idl, "generated_sdk").to_string();
+
+        // u128/i128 are emitted as decimal strings so no precision is lost
+        assert!(
+            code.contains(
+                "object . insert (\"total_deposits\" . to_string () , hyperstack :: runtime :: serde_json :: Value :: String ((self . total_deposits) . to_string ()))"
+            ),
+            "{code}"
+        );
+        assert!(code.contains("Value :: String ((self . net_flow) . to_string ())"));
+        // Arrays above serde's size limit still (de)serialize
+        assert!(code.contains(
+            "# [serde (with = \"hyperstack::runtime::serde_helpers::big_array\")] pub last_signature : [u8 ; 64]"
+        ));
+    }
 }
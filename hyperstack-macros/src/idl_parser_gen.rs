@@ -31,6 +31,65 @@ pub fn generate_named_parsers(
     }
 }
 
+/// Generate the parser module of a program watched through `extra_accounts`.
+///
+/// Its accounts are decoded by the runtime's builtin SPL token layout and
+/// only the accounts passing one of `filters` are parsed. The program has no
+/// instruction parser.
+pub fn generate_token_account_parsers(
+    idl: &IdlSpec,
+    filters: &[String],
+    parser_module_name: &str,
+) -> TokenStream {
+    let program_name = idl.get_name();
+    let state_enum_name = format_ident!("{}State", to_pascal_case(program_name));
+    let parser_module_ident = format_ident!("{}", parser_module_name);
+
+    quote! {
+        pub mod #parser_module_ident {
+            pub const PROGRAM_ID_STR: &str =
+                hyperstack::runtime::hyperstack_server::extra_accounts::SPL_TOKEN_PROGRAM_ID;
+
+            pub type #state_enum_name = hyperstack::runtime::hyperstack_server::extra_accounts::TokenAccount;
+
+            static FILTERS: std::sync::OnceLock<Vec<hyperstack::runtime::hyperstack_server::extra_accounts::AccountFilter>> =
+                std::sync::OnceLock::new();
+
+            pub fn filters() -> &'static [hyperstack::runtime::hyperstack_server::extra_accounts::AccountFilter] {
+                FILTERS.get_or_init(|| {
+                    [#(#filters),*]
+                        .iter()
+                        .map(|filter| filter.parse().expect("Invalid account filter"))
+                        .collect()
+                })
+            }
+
+            #[derive(Debug, Copy, Clone)]
+            pub struct AccountParser;
+
+            impl hyperstack::runtime::yellowstone_vixen_core::Parser for AccountParser {
+                type Input = hyperstack::runtime::yellowstone_vixen_core::AccountUpdate;
+                type Output = #state_enum_name;
+
+                fn id(&self) -> std::borrow::Cow<'static, str> {
+                    std::borrow::Cow::Borrowed(concat!(#program_name, "::AccountParser"))
+                }
+
+                fn prefilter(&self) -> hyperstack::runtime::yellowstone_vixen_core::Prefilter {
+                    hyperstack::runtime::hyperstack_server::extra_accounts::token_account_prefilter(filters())
+                }
+
+                async fn parse(
+                    &self,
+                    acct: &hyperstack::runtime::yellowstone_vixen_core::AccountUpdate,
+                ) -> hyperstack::runtime::yellowstone_vixen_core::ParseResult<Self::Output> {
+                    hyperstack::runtime::hyperstack_server::extra_accounts::parse_token_account(acct, filters())
+                }
+            }
+        }
+    }
+}
+
 fn generate_account_parser(idl: &IdlSpec, program_id: &str) -> TokenStream {
     let program_name = idl.get_name();
     let state_enum_name = format_ident!("{}State", to_pascal_case(program_name));
@@ -75,6 +134,8 @@ fn generate_account_parser(idl: &IdlSpec, program_id: &str) -> TokenStream {
         }
     });
 
+    let closed_type_name = format!("{}::AccountClosed", program_name);
+
     let to_value_arms = idl.accounts.iter().map(|acc| {
         let variant_name = format_ident!("{}", acc.name);
 
@@ -103,7 +164,10 @@ fn generate_account_parser(idl: &IdlSpec, program_id: &str) -> TokenStream {
 
         #[derive(Debug)]
         pub enum #state_enum_name {
-            #(#state_enum_variants),*
+            #(#state_enum_variants,)*
+            /// An account of the program was closed: its lamports dropped
+            /// to zero or its data was emptied
+            Closed,
         }
 
         impl #state_enum_name {
@@ -121,21 +185,33 @@ fn generate_account_parser(idl: &IdlSpec, program_id: &str) -> TokenStream {
 
             pub fn to_json(&self) -> hyperstack::runtime::serde_json::Value {
                 match self {
-                    #(#convert_to_json_arms),*
+                    #(#convert_to_json_arms,)*
+                    #state_enum_name::Closed => {
+                        hyperstack::runtime::serde_json::json!({
+                            "type": #closed_type_name,
+                            "data": null
+                        })
+                    }
                 }
             }
 
             pub fn event_type(&self) -> &'static str {
                 match self {
-                    #(#type_name_arms),*
+                    #(#type_name_arms,)*
+                    #state_enum_name::Closed => #closed_type_name,
                 }
             }
 
             pub fn to_value(&self) -> hyperstack::runtime::serde_json::Value {
                 match self {
-                    #(#to_value_arms),*
+                    #(#to_value_arms,)*
+                    #state_enum_name::Closed => hyperstack::runtime::serde_json::Value::Null,
                 }
             }
+
+            pub fn is_closed(&self) -> bool {
+                matches!(self, #state_enum_name::Closed)
+            }
         }
 
         #[derive(Debug, Copy, Clone)]
@@ -165,6 +241,10 @@ fn generate_account_parser(idl: &IdlSpec, program_id: &str) -> TokenStream {
                     .as_ref()
                     .ok_or(hyperstack::runtime::yellowstone_vixen_core::ParseError::from("No account data"))?;
 
+                if inner.lamports == 0 || inner.data.is_empty() {
+                    return Ok(#state_enum_name::Closed);
+                }
+
                 #state_enum_name::try_unpack(&inner.data)
                     .map_err(|e| {
                         let msg = e.to_string();
@@ -486,6 +566,14 @@ fn generate_instruction_parser(idl: &IdlSpec, _program_id: &str) -> TokenStream
                     #(#to_value_with_accounts_arms_ev),*
                 }
             }
+
+            /// Like `to_value_with_accounts`, with the transaction's signature, fee payer,
+            /// fee and compute units under `__transaction`
+            pub fn to_value_with_transaction(&self, update: &hyperstack::runtime::yellowstone_vixen_core::instruction::InstructionUpdate) -> hyperstack::runtime::serde_json::Value {
+                let mut value = self.to_value_with_accounts(&update.accounts);
+                hyperstack::runtime::transaction::attach(&mut value, update);
+                value
+            }
         }
 
         #[derive(Debug, Copy, Clone)]
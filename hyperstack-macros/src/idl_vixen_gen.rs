@@ -163,20 +163,27 @@ pub fn generate_spec_function_without_registries(idl: &IdlSpec, _program_id: &st
     }
 }
 
-pub fn generate_multi_idl_spec_function(idls: &[(&IdlSpec, &str, &str)]) -> TokenStream {
+/// Generate the handlers and `spec()` for the programs of a stack.
+///
+/// Each program is `(idl, program_id, parser_module_name, accounts_only)`;
+/// programs watched through `extra_accounts` are accounts only.
+pub fn generate_multi_idl_spec_function(idls: &[(&IdlSpec, &str, &str, bool)]) -> TokenStream {
     let config = RuntimeGenConfig::for_idl();
 
     let vm_handler_struct = vixen_runtime::generate_vm_handler_struct();
 
     let handler_impls: Vec<TokenStream> = idls
         .iter()
-        .map(|(idl, _program_id, parser_module_name)| {
+        .map(|(idl, _program_id, parser_module_name, accounts_only)| {
             let program_name = idl.get_name();
             let state_enum_name = format!("{}State", to_pascal_case(program_name));
             let instruction_enum_name = format!("{}Instruction", to_pascal_case(program_name));
 
             let account_impl =
                 vixen_runtime::generate_account_handler_impl(parser_module_name, &state_enum_name);
+            if *accounts_only {
+                return account_impl;
+            }
             let instruction_impl = vixen_runtime::generate_instruction_handler_impl(
                 parser_module_name,
                 &instruction_enum_name,
@@ -192,7 +199,7 @@ pub fn generate_multi_idl_spec_function(idls: &[(&IdlSpec, &str, &str)]) -> Toke
 
     let pipeline_infos: Vec<vixen_runtime::PipelineInfo> = idls
         .iter()
-        .map(|(idl, program_id, parser_module_name)| {
+        .map(|(idl, program_id, parser_module_name, accounts_only)| {
             let program_name = idl.get_name();
             vixen_runtime::PipelineInfo {
                 parser_module_name: parser_module_name.to_string(),
@@ -200,6 +207,7 @@ pub fn generate_multi_idl_spec_function(idls: &[(&IdlSpec, &str, &str)]) -> Toke
                 program_id: program_id.to_string(),
                 state_enum_name: format!("{}State", to_pascal_case(program_name)),
                 instruction_enum_name: format!("{}Instruction", to_pascal_case(program_name)),
+                accounts_only: *accounts_only,
             }
         })
         .collect();
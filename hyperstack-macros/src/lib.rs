@@ -30,11 +30,13 @@
 //!
 //! - `#[map(...)]` - Map from account fields
 //! - `#[from_instruction(...)]` - Map from instruction fields
+//! - `#[from_transaction(...)]` - Map from transaction metadata (signature, fee payer, fee, compute units)
 //! - `#[event(...)]` - Capture instruction events
 //! - `#[snapshot(...)]` - Capture entire source data
 //! - `#[aggregate(...)]` - Aggregate field values
 //! - `#[computed(...)]` - Computed fields from other fields
 //! - `#[derive_from(...)]` - Derive values from instructions
+//! - `#[section(external = "...")]` - Use a section struct shared between stacks
 
 // Public modules - AST types needed for SDK generation
 pub(crate) mod ast;
@@ -57,7 +59,10 @@ use std::collections::HashMap;
 use syn::{ItemMod, ItemStruct};
 
 // Use the stream_spec module functions
-use stream_spec::{process_module, process_struct_with_context};
+use stream_spec::{
+    expand_shared_section, process_imported_module, process_module, process_struct_with_context,
+    ImportedSections,
+};
 
 /// Process a `#[hyperstack(...)]` attribute.
 ///
@@ -77,6 +82,21 @@ use stream_spec::{process_module, process_struct_with_context};
 /// }
 /// ```
 ///
+/// IDL paths are relative to `CARGO_MANIFEST_DIR`. `idl = env!("MY_IDL_PATH")`
+/// reads the path from an environment variable when the macro expands.
+///
+/// Instruction account mappings match the IDL's account names in any case
+/// or snake/camel style. `index = N` on `#[map]` or `#[from_instruction]`
+/// reads the account at position `N` when the IDL no longer has it by name,
+/// and `previous_idl = "old_idl.json"` checks that every instruction account
+/// mapping also resolves against an earlier version of the IDL.
+///
+/// `action = clear` on `#[from_instruction]` unsets the field when the
+/// instruction is seen, instead of writing its value.
+///
+/// `idl_hash = "sha256:..."` pins each IDL file to the content hash written
+/// by `hs idl fetch --pin`, failing the build when the file changes.
+///
 /// ## Proto-based Usage
 ///
 /// ```rust,ignore
@@ -110,7 +130,12 @@ fn expand_hyperstack(
     })?;
 
     let config = parse::parse_stream_spec_attribute(attr)?;
-    if !config.proto_files.is_empty() || !config.idl_files.is_empty() || config.skip_decoders {
+    if !config.proto_files.is_empty()
+        || !config.idl_files.is_empty()
+        || !config.previous_idl_files.is_empty()
+        || !config.extra_accounts.is_empty()
+        || config.skip_decoders
+    {
         return Err(syn::Error::new(
             input.ident.span(),
             "#[hyperstack(...)] arguments are only supported on modules",
@@ -125,25 +150,71 @@ fn expand_hyperstack(
 /// This is a marker derive that enables the following attributes on struct fields:
 /// - `#[map(...)]` - Map from account fields
 /// - `#[from_instruction(...)]` - Map from instruction fields
+/// - `#[from_transaction(...)]` - Map from transaction metadata (signature, fee payer, fee, compute units)
 /// - `#[event(...)]` - Capture instruction events
 /// - `#[snapshot(...)]` - Capture entire source
 /// - `#[aggregate(...)]` - Aggregate field values
 /// - `#[computed(...)]` - Computed fields from other fields
 /// - `#[derive_from(...)]` - Derive values from instructions
 /// - `#[resolve(...)]` - Resolve external data (token metadata via DAS API or data from URLs)
+/// - `#[doc_meta(...)]` - Extra documentation metadata (e.g. `unit = "lamports"`) for generated SDKs
 #[proc_macro_derive(
     Stream,
     attributes(
         map,
         from_instruction,
+        from_transaction,
         event,
         snapshot,
         aggregate,
         computed,
         derive_from,
-        resolve
+        resolve,
+        doc_meta
     )
 )]
 pub fn stream_derive(_input: TokenStream) -> TokenStream {
     TokenStream::new()
 }
+
+/// Derive macro for `SharedSection`.
+///
+/// Exports the field layout of a section struct so entities of other crates
+/// can use it with `#[section(external = "...")]`, and generated SDKs share
+/// one type for it:
+///
+/// ```rust,ignore
+/// // common_sections/src/lib.rs
+/// #[derive(Debug, Clone, Serialize, Deserialize, SharedSection)]
+/// pub struct TokenMetadata {
+///     pub name: Option<String>,
+///     pub symbol: Option<String>,
+/// }
+///
+/// // In a #[hyperstack] module
+/// #[entity(name = "Pool")]
+/// struct Pool {
+///     #[section(external = "common_sections::TokenMetadata")]
+///     pub metadata: TokenMetadata,
+/// }
+/// ```
+///
+/// The struct must be reachable from the crate root, as the layout is
+/// exported through a `#[macro_export]` macro.
+#[proc_macro_derive(SharedSection)]
+pub fn shared_section_derive(input: TokenStream) -> TokenStream {
+    syn::parse::<ItemStruct>(input)
+        .and_then(expand_shared_section)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Continues `#[hyperstack]` once a shared section's layout is imported.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __import_sections(input: TokenStream) -> TokenStream {
+    syn::parse::<ImportedSections>(input)
+        .and_then(process_imported_module)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -10,7 +10,10 @@ use syn::parse::{Parse, ParseStream};
 use syn::spanned::Spanned;
 use syn::{Attribute, Path, Token};
 
-use crate::ast::{ConditionExpr, FieldPath, ResolverCondition, ResolverType};
+use crate::ast::{
+    ConditionExpr, DeleteTrigger, EntityPriority, FieldPath, ItemDocs, ResolverCondition,
+    ResolverType,
+};
 use crate::diagnostic::{invalid_choice_message, ErrorCollector};
 use crate::parse::conditions as condition_parser;
 
@@ -34,6 +37,8 @@ pub struct MapAttribute {
     pub target_field_name: String,
     pub is_primary_key: bool,
     pub is_lookup_index: bool,
+    /// Module-level `shared_index!` this lookup index registers into.
+    pub shared_index: Option<String>,
     pub register_from: Vec<RegisterFromSpec>,
     pub temporal_field: Option<String>,
     pub strategy: String,
@@ -50,6 +55,9 @@ pub struct MapAttribute {
     pub stop: Option<Path>,
     pub stop_lookup_by: Option<FieldSpec>,
     pub emit: bool,
+    /// Position of the instruction account to read when the IDL has no
+    /// account by the written name (`index = N`).
+    pub account_index: Option<usize>,
 }
 
 /// A parameterized resolver transform like `ui_amount(ore_metadata.decimals)`.
@@ -146,6 +154,82 @@ fn validate_strategy<T: quote::ToTokens>(
     }
 }
 
+/// `policy = "..."` values accepted by `#[map]` and the strategy each selects
+const WRITE_POLICIES: &[(&str, &str)] = &[
+    ("set_once", "SetOnce"),
+    ("latest", "LastWrite"),
+    ("max", "Max"),
+    ("min", "Min"),
+    ("sum", "Sum"),
+];
+
+/// Resolve the write strategy from either `strategy = Ident` or `policy = "name"`.
+/// Without either the field tracks the latest value (`LastWrite`).
+fn resolve_write_strategy(
+    attr_name: &str,
+    attr: &Attribute,
+    strategy: Option<String>,
+    policy: Option<syn::LitStr>,
+    allowed: &[&str],
+) -> syn::Result<String> {
+    match (strategy, policy) {
+        (Some(_), Some(policy)) => Err(syn::Error::new_spanned(
+            &policy,
+            format!(
+                "{} accepts either `strategy` or `policy`, not both",
+                attr_name
+            ),
+        )),
+        (strategy, None) => validate_strategy(
+            attr_name,
+            strategy.unwrap_or_else(|| "LastWrite".to_string()),
+            attr,
+            allowed,
+        ),
+        (None, Some(policy)) => {
+            let allowed_policies: Vec<&str> = WRITE_POLICIES
+                .iter()
+                .filter(|(_, strategy)| allowed.contains(strategy))
+                .map(|(name, _)| *name)
+                .collect();
+            let value = policy.value();
+            WRITE_POLICIES
+                .iter()
+                .find(|(name, strategy)| *name == value && allowed.contains(strategy))
+                .map(|(_, strategy)| strategy.to_string())
+                .ok_or_else(|| {
+                    syn::Error::new_spanned(
+                        &policy,
+                        invalid_choice_message("policy", &value, attr_name, &allowed_policies),
+                    )
+                })
+        }
+    }
+}
+
+/// Resolve `action = ...` on `#[from_instruction]`. `clear` unsets the field
+/// instead of writing the mapped value, so it takes no write policy.
+fn resolve_instruction_action(
+    action: &syn::Ident,
+    strategy: Option<String>,
+    policy: Option<syn::LitStr>,
+) -> syn::Result<String> {
+    let value = action.to_string();
+    if value != "clear" {
+        return Err(syn::Error::new_spanned(
+            action,
+            invalid_choice_message("action", &value, "#[from_instruction]", &["clear"]),
+        ));
+    }
+    if strategy.is_some() || policy.is_some() {
+        return Err(syn::Error::new_spanned(
+            action,
+            "#[from_instruction] with `action = clear` takes no `strategy` or `policy`",
+        ));
+    }
+    Ok("Clear".to_string())
+}
+
 fn parse_condition_literal(literal: &syn::LitStr) -> syn::Result<ConditionExpr> {
     let expression = literal.value();
     let parsed = condition_parser::parse_condition_expression_strict(&expression)
@@ -200,6 +284,12 @@ fn parse_validated_field_path(input: ParseStream) -> syn::Result<ValidatedFieldP
 }
 
 impl MapAttribute {
+    /// Transaction metadata field read by a `#[from_transaction]` mapping
+    pub fn transaction_field(&self) -> Option<&str> {
+        self.source_field_name
+            .strip_prefix(TRANSACTION_FIELD_PREFIX)
+    }
+
     pub fn source_type_string(&self) -> String {
         self.source_type_path
             .segments
@@ -247,9 +337,11 @@ struct MapAttributeArgs {
     source_paths: Vec<Path>,
     is_primary_key: bool,
     is_lookup_index: bool,
+    shared_index: Option<String>,
     register_from: Vec<RegisterFromSpec>,
     temporal_field: Option<String>,
     strategy: Option<String>,
+    policy: Option<syn::LitStr>,
     rename: Option<String>,
     join_on: Option<FieldSpec>,
     transform: Option<String>,
@@ -259,6 +351,8 @@ struct MapAttributeArgs {
     stop: Option<Path>,
     stop_lookup_by: Option<FieldSpec>,
     emit: Option<bool>,
+    index: Option<usize>,
+    action: Option<syn::Ident>,
 }
 
 impl Parse for MapAttributeArgs {
@@ -280,9 +374,11 @@ impl Parse for MapAttributeArgs {
 
         let mut is_primary_key = false;
         let mut is_lookup_index = false;
+        let mut shared_index = None;
         let mut register_from = Vec::new();
         let mut temporal_field = None;
         let mut strategy = None;
+        let mut policy = None;
         let mut rename = None;
         let mut join_on = None;
         let mut transform = None;
@@ -292,6 +388,8 @@ impl Parse for MapAttributeArgs {
         let mut stop = None;
         let mut stop_lookup_by = None;
         let mut emit = None;
+        let mut index = None;
+        let mut action = None;
 
         while !input.is_empty() {
             input.parse::<Token![,]>()?;
@@ -313,6 +411,11 @@ impl Parse for MapAttributeArgs {
                         syn::parenthesized!(content in input);
                         register_from = parse_register_from_list(&content)?;
                     }
+                } else if ident_str == "shared_index" {
+                    input.parse::<Token![=]>()?;
+                    let shared_index_lit: syn::LitStr = input.parse()?;
+                    is_lookup_index = true;
+                    shared_index = Some(shared_index_lit.value());
                 } else if ident_str == "temporal_field" {
                     input.parse::<Token![=]>()?;
                     let temporal_lit: syn::LitStr = input.parse()?;
@@ -321,6 +424,9 @@ impl Parse for MapAttributeArgs {
                     input.parse::<Token![=]>()?;
                     let strategy_ident: syn::Ident = input.parse()?;
                     strategy = Some(strategy_ident.to_string());
+                } else if ident_str == "policy" {
+                    input.parse::<Token![=]>()?;
+                    policy = Some(input.parse::<syn::LitStr>()?);
                 } else if ident_str == "rename" {
                     input.parse::<Token![=]>()?;
                     let rename_lit: syn::LitStr = input.parse()?;
@@ -335,17 +441,21 @@ impl Parse for MapAttributeArgs {
                     }
                 } else if ident_str == "transform" {
                     input.parse::<Token![=]>()?;
-                    let transform_ident: syn::Ident = input.parse()?;
-                    if input.peek(syn::token::Paren) {
-                        let content;
-                        syn::parenthesized!(content in input);
-                        let args: proc_macro2::TokenStream = content.parse()?;
-                        resolver_transform = Some(ResolverTransformSpec {
-                            method: transform_ident.to_string(),
-                            args,
-                        });
+                    if input.peek(syn::token::Bracket) {
+                        transform = Some(parse_transform_chain(input)?);
                     } else {
-                        transform = Some(transform_ident.to_string());
+                        let transform_ident: syn::Ident = input.parse()?;
+                        if input.peek(syn::token::Paren) {
+                            let content;
+                            syn::parenthesized!(content in input);
+                            let args: proc_macro2::TokenStream = content.parse()?;
+                            resolver_transform = Some(ResolverTransformSpec {
+                                method: transform_ident.to_string(),
+                                args,
+                            });
+                        } else {
+                            transform = Some(transform_ident.to_string());
+                        }
                     }
                 } else if ident_str == "condition" {
                     input.parse::<Token![=]>()?;
@@ -366,6 +476,13 @@ impl Parse for MapAttributeArgs {
                     input.parse::<Token![=]>()?;
                     let emit_lit: syn::LitBool = input.parse()?;
                     emit = Some(emit_lit.value);
+                } else if ident_str == "index" {
+                    input.parse::<Token![=]>()?;
+                    let index_lit: syn::LitInt = input.parse()?;
+                    index = Some(index_lit.base10_parse()?);
+                } else if ident_str == "action" {
+                    input.parse::<Token![=]>()?;
+                    action = Some(input.parse::<syn::Ident>()?);
                 } else {
                     return Err(syn::Error::new(
                         ident.span(),
@@ -381,9 +498,11 @@ impl Parse for MapAttributeArgs {
             source_paths,
             is_primary_key,
             is_lookup_index,
+            shared_index,
             register_from,
             temporal_field,
             strategy,
+            policy,
             rename,
             join_on,
             transform,
@@ -393,6 +512,8 @@ impl Parse for MapAttributeArgs {
             stop,
             stop_lookup_by,
             emit,
+            index,
+            action,
         })
     }
 }
@@ -414,11 +535,19 @@ pub fn parse_map_attribute(
         ));
     }
 
-    let strategy = validate_strategy(
+    if let Some(action) = &args.action {
+        return Err(syn::Error::new_spanned(
+            action,
+            "`action` is only supported by #[from_instruction]",
+        ));
+    }
+
+    let strategy = resolve_write_strategy(
         "#[map]",
-        args.strategy.unwrap_or_else(|| "SetOnce".to_string()),
         attr,
-        &["SetOnce", "LastWrite"],
+        args.strategy,
+        args.policy,
+        &["SetOnce", "LastWrite", "Max", "Min", "Sum"],
     )?;
     let target_name = args.rename.unwrap_or_else(|| target_field_name.to_string());
     let emit = args.emit.unwrap_or(true);
@@ -440,6 +569,7 @@ pub fn parse_map_attribute(
             target_field_name: target_name.clone(),
             is_primary_key: args.is_primary_key,
             is_lookup_index: args.is_lookup_index,
+            shared_index: args.shared_index.clone(),
             register_from: args.register_from.clone(),
             temporal_field: args.temporal_field.clone(),
             strategy: strategy.clone(),
@@ -458,6 +588,7 @@ pub fn parse_map_attribute(
             stop: args.stop.clone(),
             stop_lookup_by: args.stop_lookup_by.clone(),
             emit,
+            account_index: args.index,
         });
     }
 
@@ -481,12 +612,16 @@ pub fn parse_from_instruction_attribute(
         ));
     }
 
-    let strategy = validate_strategy(
-        "#[from_instruction]",
-        args.strategy.unwrap_or_else(|| "SetOnce".to_string()),
-        attr,
-        &["SetOnce", "LastWrite"],
-    )?;
+    let strategy = match args.action {
+        Some(action) => resolve_instruction_action(&action, args.strategy, args.policy)?,
+        None => resolve_write_strategy(
+            "#[from_instruction]",
+            attr,
+            args.strategy,
+            args.policy,
+            &["SetOnce", "LastWrite"],
+        )?,
+    };
     let target_name = args.rename.unwrap_or_else(|| target_field_name.to_string());
     let emit = args.emit.unwrap_or(true);
 
@@ -505,6 +640,7 @@ pub fn parse_from_instruction_attribute(
             target_field_name: target_name.clone(),
             is_primary_key: args.is_primary_key,
             is_lookup_index: args.is_lookup_index,
+            shared_index: args.shared_index.clone(),
             register_from: args.register_from.clone(),
             temporal_field: args.temporal_field.clone(),
             strategy: strategy.clone(),
@@ -523,12 +659,179 @@ pub fn parse_from_instruction_attribute(
             stop: args.stop.clone(),
             stop_lookup_by: args.stop_lookup_by.clone(),
             emit,
+            account_index: args.index,
         });
     }
 
     Ok(Some(results))
 }
 
+/// Source field prefix marking a `#[from_transaction]` mapping
+const TRANSACTION_FIELD_PREFIX: &str = "__transaction:";
+
+/// Transaction metadata fields `#[from_transaction]` can read
+const TRANSACTION_FIELDS: &[&str] = &["signature", "fee_payer", "fee", "compute_units"];
+
+struct FromTransactionAttributeArgs {
+    from: Vec<Path>,
+    field: Option<(String, Span)>,
+    strategy: Option<syn::Ident>,
+    join_on: Option<FieldSpec>,
+    condition: Option<syn::LitStr>,
+}
+
+impl Parse for FromTransactionAttributeArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut from = Vec::new();
+        let mut field = None;
+        let mut strategy = None;
+        let mut join_on = None;
+        let mut condition = None;
+
+        while !input.is_empty() {
+            let ident: syn::Ident = input.parse()?;
+            let ident_str = ident.to_string();
+
+            input.parse::<Token![=]>()?;
+
+            if ident_str == "from" {
+                if input.peek(syn::token::Bracket) {
+                    let content;
+                    syn::bracketed!(content in input);
+                    while !content.is_empty() {
+                        from.push(content.parse()?);
+                        if !content.is_empty() {
+                            content.parse::<Token![,]>()?;
+                        }
+                    }
+                } else {
+                    from.push(input.parse()?);
+                }
+            } else if ident_str == "field" {
+                if input.peek(syn::LitStr) {
+                    let field_lit: syn::LitStr = input.parse()?;
+                    field = Some((field_lit.value(), field_lit.span()));
+                } else {
+                    let field_ident: syn::Ident = input.parse()?;
+                    field = Some((field_ident.to_string(), field_ident.span()));
+                }
+            } else if ident_str == "strategy" {
+                strategy = Some(input.parse()?);
+            } else if ident_str == "join_on" {
+                if input.peek(syn::LitStr) {
+                    let join_on_lit: syn::LitStr = input.parse()?;
+                    join_on = Some(parse_join_on_literal(&join_on_lit)?);
+                } else {
+                    join_on = Some(parse_field_spec(input)?);
+                }
+            } else if ident_str == "condition" {
+                condition = Some(input.parse()?);
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("Unknown from_transaction attribute argument: {}", ident_str),
+                ));
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(FromTransactionAttributeArgs {
+            from,
+            field,
+            strategy,
+            join_on,
+            condition,
+        })
+    }
+}
+
+/// Parse `#[from_transaction(from = Instruction, field = "fee_payer")]`.
+///
+/// Each instruction in `from` yields a mapping on that instruction's handler
+/// that reads the field from the event's `__transaction` section.
+pub fn parse_from_transaction_attribute(
+    attr: &Attribute,
+    target_field_name: &str,
+) -> syn::Result<Option<Vec<MapAttribute>>> {
+    if !attr.path().is_ident("from_transaction") {
+        return Ok(None);
+    }
+
+    let args: FromTransactionAttributeArgs = attr.parse_args()?;
+
+    if args.from.is_empty() {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "#[from_transaction] requires 'from' parameter specifying instruction type(s)",
+        ));
+    }
+
+    let (field, field_span) = args.field.ok_or_else(|| {
+        syn::Error::new_spanned(attr, "#[from_transaction] requires 'field' parameter")
+    })?;
+    if !TRANSACTION_FIELDS.contains(&field.as_str()) {
+        return Err(syn::Error::new(
+            field_span,
+            invalid_choice_message("field", &field, "#[from_transaction]", TRANSACTION_FIELDS),
+        ));
+    }
+
+    let strategy = validate_strategy(
+        "#[from_transaction]",
+        args.strategy
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "LastWrite".to_string()),
+        attr,
+        &["SetOnce", "LastWrite"],
+    )?;
+    let condition = args
+        .condition
+        .as_ref()
+        .map(parse_condition_literal)
+        .transpose()?;
+
+    Ok(Some(
+        args.from
+            .into_iter()
+            .map(|source_type_path| MapAttribute {
+                attr_span: attr.span(),
+                source_type_span: source_type_path
+                    .segments
+                    .last()
+                    .map(|segment| segment.ident.span())
+                    .unwrap_or_else(Span::call_site),
+                source_field_span: field_span,
+                is_event_source: false,
+                is_account_source: false,
+                source_type_path,
+                source_field_name: format!("{}{}", TRANSACTION_FIELD_PREFIX, field),
+                target_field_name: target_field_name.to_string(),
+                is_primary_key: false,
+                is_lookup_index: false,
+                shared_index: None,
+                register_from: Vec::new(),
+                temporal_field: None,
+                strategy: strategy.clone(),
+                join_on: args.join_on.clone(),
+                transform: None,
+                resolver_transform: None,
+                is_instruction: true,
+                is_whole_source: false,
+                lookup_by: None,
+                condition: condition.clone(),
+                when: None,
+                stop: None,
+                stop_lookup_by: None,
+                emit: true,
+                account_index: None,
+            })
+            .collect(),
+    ))
+}
+
 struct SplitSourcePath {
     source_type_path: Path,
     source_type_span: Span,
@@ -760,6 +1063,37 @@ impl Parse for EventAttributeArgs {
     }
 }
 
+// Helper function to parse a transform pipeline like `["base58_decode", "slice(0, 8)", "hex_encode"]`.
+// Returns the chain in the bracketed form understood by `parse_transformation`.
+fn parse_transform_chain(input: ParseStream) -> syn::Result<String> {
+    let content;
+    let bracket = syn::bracketed!(content in input);
+    let stages = content.parse_terminated(|stage| stage.parse::<syn::LitStr>(), Token![,])?;
+
+    if stages.is_empty() {
+        return Err(syn::Error::new(
+            bracket.span.join(),
+            "transform pipeline needs at least one stage",
+        ));
+    }
+    for stage in &stages {
+        if crate::ast::writer::parse_transformation(&stage.value()).is_none() {
+            return Err(syn::Error::new(
+                stage.span(),
+                format!(
+                    "unknown transform stage `{}` (expected hex_encode, hex_decode, base58_encode, \
+                     base58_decode, base64_encode, base64_decode, to_string, to_number, \
+                     slice(start, end) or take(n))",
+                    stage.value()
+                ),
+            ));
+        }
+    }
+
+    let stages: Vec<String> = stages.iter().map(|stage| stage.value()).collect();
+    Ok(format!("[{}]", stages.join(", ")))
+}
+
 // Helper function to parse a field spec (ident or location::ident)
 fn parse_field_spec(input: ParseStream) -> syn::Result<FieldSpec> {
     let lookahead = input.lookahead1();
@@ -1191,6 +1525,7 @@ pub struct ResolveAttribute {
     pub strategy: String,
     pub condition: Option<ValidatedResolverCondition>,
     pub schedule_at: Option<ValidatedFieldPath>,
+    pub placeholder: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -1205,6 +1540,7 @@ pub struct ResolveSpec {
     pub strategy: String,
     pub condition: Option<ValidatedResolverCondition>,
     pub schedule_at: Option<ValidatedFieldPath>,
+    pub placeholder: Option<String>,
 }
 
 struct ResolveAttributeArgs {
@@ -1219,6 +1555,7 @@ struct ResolveAttributeArgs {
     strategy: Option<String>,
     condition: Option<syn::LitStr>,
     schedule_at: Option<ValidatedFieldPath>,
+    placeholder: Option<String>,
 }
 
 impl Parse for ResolveAttributeArgs {
@@ -1234,6 +1571,7 @@ impl Parse for ResolveAttributeArgs {
         let mut strategy = None;
         let mut condition = None;
         let mut schedule_at = None;
+        let mut placeholder = None;
 
         while !input.is_empty() {
             let ident: syn::Ident = input.parse()?;
@@ -1296,6 +1634,15 @@ impl Parse for ResolveAttributeArgs {
                 condition = Some(lit);
             } else if ident_str == "schedule_at" {
                 schedule_at = Some(parse_validated_field_path(input)?);
+            } else if ident_str == "placeholder" {
+                let lit: syn::LitStr = input.parse()?;
+                if lit.value().is_empty() {
+                    return Err(syn::Error::new(
+                        lit.span(),
+                        "#[resolve] placeholder must not be empty",
+                    ));
+                }
+                placeholder = Some(lit.value());
             } else {
                 return Err(syn::Error::new(
                     ident.span(),
@@ -1320,6 +1667,7 @@ impl Parse for ResolveAttributeArgs {
             strategy,
             condition,
             schedule_at,
+            placeholder,
         })
     }
 }
@@ -1393,6 +1741,7 @@ pub fn parse_resolve_attribute(
             .map(parse_resolver_condition_literal)
             .transpose()?,
         schedule_at: args.schedule_at,
+        placeholder: args.placeholder,
     }))
 }
 
@@ -1449,6 +1798,10 @@ pub fn parse_recognized_field_attribute(
         return Ok(Some(RecognizedFieldAttribute::FromInstruction(map_attrs)));
     }
 
+    if let Some(map_attrs) = parse_from_transaction_attribute(attr, target_field_name)? {
+        return Ok(Some(RecognizedFieldAttribute::FromInstruction(map_attrs)));
+    }
+
     if let Some(event_attr) = parse_event_attribute(attr, target_field_name)? {
         return Ok(Some(RecognizedFieldAttribute::Event(event_attr)));
     }
@@ -1480,45 +1833,342 @@ pub fn has_entity_attribute(attrs: &[Attribute]) -> bool {
     attrs.iter().any(|attr| attr.path().is_ident("entity"))
 }
 
-pub fn parse_entity_name(attrs: &[Attribute]) -> Option<String> {
+/// A `trace_fields` entry on `#[entity(...)]`, e.g. `"state.round_id"`.
+#[derive(Debug, Clone)]
+pub struct TraceFieldSpec {
+    pub path: String,
+    pub span: Span,
+}
+
+/// `key_from` on `#[entity(...)]`, e.g. `"BondingCurve::mint"`: the account
+/// field the entity is keyed by.
+#[derive(Debug, Clone)]
+pub struct KeyFromSpec {
+    /// Account type, bare (`BondingCurve`) or qualified
+    /// (`pump_sdk::accounts::BondingCurve`)
+    pub account_path: Path,
+    pub field: String,
+    pub span: Span,
+}
+
+impl KeyFromSpec {
+    pub fn account_name(&self) -> String {
+        self.account_path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Lookup field an instruction names the account by (`bonding_curve_address`,
+    /// which `lookup_by = bonding_curve` also matches)
+    pub fn address_field(&self) -> String {
+        format!(
+            "{}_address",
+            crate::utils::to_snake_case(&self.account_name())
+        )
+    }
+
+    /// Whether a `#[map]` source type refers to this account
+    pub fn matches_source(&self, source_type: &str) -> bool {
+        if self.account_path.segments.len() == 1 {
+            source_type.rsplit("::").next() == Some(self.account_name().as_str())
+        } else {
+            crate::utils::path_to_string(&self.account_path) == source_type
+        }
+    }
+}
+
+/// A module-level `shared_index!("mint_to_curve")` declaration.
+#[derive(Debug, Clone)]
+pub struct SharedIndexDecl {
+    pub name: String,
+    pub span: Span,
+}
+
+pub fn is_shared_index_macro(item: &syn::Item) -> bool {
+    matches!(item, syn::Item::Macro(item_macro) if item_macro.mac.path.is_ident("shared_index"))
+}
+
+/// Collect the `shared_index!` declarations among a module's items.
+pub fn parse_shared_index_decls(items: &[syn::Item]) -> syn::Result<Vec<SharedIndexDecl>> {
+    let mut decls: Vec<SharedIndexDecl> = Vec::new();
+
+    for item in items {
+        let syn::Item::Macro(item_macro) = item else {
+            continue;
+        };
+        if !item_macro.mac.path.is_ident("shared_index") {
+            continue;
+        }
+
+        let name: syn::LitStr = item_macro.mac.parse_body()?;
+        if name.value().is_empty() {
+            return Err(syn::Error::new(
+                name.span(),
+                "shared_index! name must not be empty",
+            ));
+        }
+        if decls.iter().any(|decl| decl.name == name.value()) {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("shared index '{}' is declared more than once", name.value()),
+            ));
+        }
+        decls.push(SharedIndexDecl {
+            name: name.value(),
+            span: name.span(),
+        });
+    }
+
+    Ok(decls)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EntityAttribute {
+    pub name: Option<String>,
+    /// Cargo feature of the stack crate that must be enabled for the entity
+    /// to be compiled in
+    pub feature: Option<String>,
+    pub trace_fields: Vec<TraceFieldSpec>,
+    pub key_from: Option<KeyFromSpec>,
+    /// How long the entity's updates survive load shedding
+    pub priority: EntityPriority,
+    /// What deletes the entity
+    pub delete_on: Option<DeleteTrigger>,
+    /// Expression rewriting the raw primary key, bound to `key`
+    pub key_normalizer: Option<syn::Expr>,
+}
+
+/// Parse #[entity(name = "OreRound", feature = "trading", trace_fields = ["id.round_id"],
+/// key_from = "Round::id", priority = high, delete_on = "AccountClosed",
+/// key_normalizer = key.to_lowercase())] attributes
+pub fn parse_entity_attribute(attrs: &[Attribute]) -> syn::Result<EntityAttribute> {
+    let mut entity = EntityAttribute::default();
+
     for attr in attrs {
-        if attr.path().is_ident("entity") {
-            if let syn::Meta::List(meta_list) = &attr.meta {
-                let tokens_str = meta_list.tokens.to_string();
-                if tokens_str.contains("name") {
-                    if let Ok(parsed) = syn::parse_str::<syn::ExprAssign>(&tokens_str) {
-                        if let syn::Expr::Lit(expr_lit) = &*parsed.right {
-                            if let syn::Lit::Str(lit_str) = &expr_lit.lit {
-                                return Some(lit_str.value());
-                            }
-                        }
+        if !attr.path().is_ident("entity") {
+            continue;
+        }
+
+        if let syn::Meta::List(meta_list) = &attr.meta {
+            meta_list.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    entity.name = Some(value.value());
+                } else if meta.path.is_ident("feature") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    if value.value().is_empty() {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            "#[entity(feature = ...)] cannot be empty",
+                        ));
                     }
+                    entity.feature = Some(value.value());
+                } else if meta.path.is_ident("trace_fields") {
+                    let value = meta.value()?;
+                    let content;
+                    syn::bracketed!(content in value);
+                    let paths = content.parse_terminated(
+                        |input: ParseStream| input.parse::<syn::LitStr>(),
+                        Token![,],
+                    )?;
+                    entity
+                        .trace_fields
+                        .extend(paths.into_iter().map(|path| TraceFieldSpec {
+                            path: path.value(),
+                            span: path.span(),
+                        }));
+                } else if meta.path.is_ident("key_from") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    entity.key_from = Some(parse_key_from(&value)?);
+                } else if meta.path.is_ident("key_normalizer") {
+                    entity.key_normalizer = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("priority") {
+                    let value: syn::Ident = meta.value()?.parse()?;
+                    entity.priority = match value.to_string().as_str() {
+                        "low" => EntityPriority::Low,
+                        "normal" => EntityPriority::Normal,
+                        "high" => EntityPriority::High,
+                        other => {
+                            return Err(syn::Error::new(
+                                value.span(),
+                                invalid_choice_message(
+                                    "priority",
+                                    other,
+                                    "#[entity]",
+                                    &["low", "normal", "high"],
+                                ),
+                            ))
+                        }
+                    };
+                } else if meta.path.is_ident("delete_on") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    entity.delete_on = match value.value().as_str() {
+                        "AccountClosed" => Some(DeleteTrigger::AccountClosed),
+                        other => {
+                            return Err(syn::Error::new(
+                                value.span(),
+                                invalid_choice_message(
+                                    "delete_on",
+                                    other,
+                                    "#[entity]",
+                                    &["AccountClosed"],
+                                ),
+                            ))
+                        }
+                    };
+                } else {
+                    let argument = meta
+                        .path
+                        .get_ident()
+                        .map(ToString::to_string)
+                        .unwrap_or_default();
+                    return Err(meta.error(invalid_choice_message(
+                        "argument",
+                        &argument,
+                        "#[entity]",
+                        &[
+                            "name",
+                            "feature",
+                            "trace_fields",
+                            "key_from",
+                            "priority",
+                            "delete_on",
+                            "key_normalizer",
+                        ],
+                    )));
                 }
+                Ok(())
+            })?;
+        }
+        break;
+    }
+
+    Ok(entity)
+}
+
+fn parse_key_from(value: &syn::LitStr) -> syn::Result<KeyFromSpec> {
+    let invalid = || {
+        syn::Error::new(
+            value.span(),
+            "#[entity(key_from = ...)] expects an account field like \"BondingCurve::mint\"",
+        )
+    };
+    let mut path: Path = value.parse().map_err(|_| invalid())?;
+    if path.segments.len() < 2 {
+        return Err(invalid());
+    }
+    let field = path.segments.pop().ok_or_else(invalid)?.into_value().ident;
+    // Drop the trailing `::` left behind by the popped field
+    path.segments.pop_punct();
+
+    Ok(KeyFromSpec {
+        account_path: path,
+        field: field.to_string(),
+        span: value.span(),
+    })
+}
+
+pub fn parse_entity_name(attrs: &[Attribute]) -> Option<String> {
+    parse_entity_attribute(attrs)
+        .ok()
+        .and_then(|entity| entity.name)
+}
+
+/// Collect `///` comments and `#[doc_meta(unit = "lamports")]` on an entity,
+/// section or field for the generated SDKs.
+pub fn parse_item_docs(attrs: &[Attribute]) -> syn::Result<Option<ItemDocs>> {
+    let mut lines = Vec::new();
+    let mut unit = None;
+
+    for attr in attrs {
+        if attr.path().is_ident("doc") {
+            if let syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(text),
+                        ..
+                    }),
+                ..
+            }) = &attr.meta
+            {
+                let text = text.value();
+                lines.push(
+                    text.strip_prefix(' ')
+                        .unwrap_or(&text)
+                        .trim_end()
+                        .to_string(),
+                );
             }
-            return None;
+        } else if attr.path().is_ident("doc_meta") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("unit") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    if value.value().trim().is_empty() {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            "#[doc_meta(unit = ...)] cannot be empty",
+                        ));
+                    }
+                    unit = Some(value.value());
+                    Ok(())
+                } else {
+                    let argument = meta
+                        .path
+                        .get_ident()
+                        .map(ToString::to_string)
+                        .unwrap_or_default();
+                    Err(meta.error(invalid_choice_message(
+                        "argument",
+                        &argument,
+                        "#[doc_meta]",
+                        &["unit"],
+                    )))
+                }
+            })?;
         }
     }
-    None
+
+    let description = lines.join("\n").trim_matches('\n').to_string();
+    let docs = ItemDocs {
+        description: (!description.is_empty()).then_some(description),
+        unit,
+    };
+
+    Ok((docs != ItemDocs::default()).then_some(docs))
 }
 
 #[derive(Debug, Clone)]
 pub struct StreamSpecAttribute {
     pub proto_files: Vec<String>,
-    pub idl_files: Vec<String>,
+    /// IDL paths as written, or as read from `env!(...)`
+    pub idl_files: Vec<syn::LitStr>,
+    /// Earlier versions of the IDLs to validate instruction account
+    /// mappings against
+    pub previous_idl_files: Vec<syn::LitStr>,
     pub skip_decoders: bool,
+    pub extra_accounts: Vec<syn::LitStr>,
 }
 
 struct StreamSpecAttributeArgs {
     proto_files: Vec<String>,
-    idl_files: Vec<String>,
+    idl_files: Vec<syn::LitStr>,
+    /// Pinned content hashes of `idl_files`, in the same order
+    idl_hashes: Vec<syn::LitStr>,
+    previous_idl_files: Vec<syn::LitStr>,
     skip_decoders: bool,
+    extra_accounts: Vec<syn::LitStr>,
 }
 
 impl Parse for StreamSpecAttributeArgs {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let mut proto_files = Vec::new();
         let mut idl_files = Vec::new();
+        let mut idl_hashes = Vec::new();
+        let mut previous_idl_files = Vec::new();
         let mut skip_decoders = false;
+        let mut extra_accounts = Vec::new();
 
         while !input.is_empty() {
             let ident: syn::Ident = input.parse()?;
@@ -1547,26 +2197,51 @@ impl Parse for StreamSpecAttributeArgs {
                 }
             } else if ident_str == "idl" {
                 input.parse::<Token![=]>()?;
+                idl_files.extend(parse_idl_paths(input, "idl")?);
+            } else if ident_str == "idl_hash" {
+                input.parse::<Token![=]>()?;
 
                 if input.peek(syn::LitStr) {
-                    let lit: syn::LitStr = input.parse()?;
-                    idl_files.push(lit.value());
+                    idl_hashes.push(input.parse()?);
                 } else if input.peek(syn::token::Bracket) {
                     let content;
                     syn::bracketed!(content in input);
 
                     while !content.is_empty() {
-                        let file_lit: syn::LitStr = content.parse()?;
-                        idl_files.push(file_lit.value());
+                        idl_hashes.push(content.parse()?);
 
                         if !content.is_empty() {
                             content.parse::<Token![,]>()?;
                         }
                     }
                 } else {
-                    return Err(
-                        input.error("Expected string literal or array of string literals for idl")
-                    );
+                    return Err(input.error(
+                        "Expected string literal or array of string literals for idl_hash",
+                    ));
+                }
+            } else if ident_str == "previous_idl" {
+                input.parse::<Token![=]>()?;
+                previous_idl_files.extend(parse_idl_paths(input, "previous_idl")?);
+            } else if ident_str == "extra_accounts" {
+                input.parse::<Token![=]>()?;
+
+                if input.peek(syn::LitStr) {
+                    extra_accounts.push(input.parse()?);
+                } else if input.peek(syn::token::Bracket) {
+                    let content;
+                    syn::bracketed!(content in input);
+
+                    while !content.is_empty() {
+                        extra_accounts.push(content.parse()?);
+
+                        if !content.is_empty() {
+                            content.parse::<Token![,]>()?;
+                        }
+                    }
+                } else {
+                    return Err(input.error(
+                        "Expected string literal or array of string literals for extra_accounts",
+                    ));
                 }
             } else if ident_str == "skip_decoders" {
                 skip_decoders = true;
@@ -1585,11 +2260,40 @@ impl Parse for StreamSpecAttributeArgs {
         Ok(StreamSpecAttributeArgs {
             proto_files,
             idl_files,
+            idl_hashes,
+            previous_idl_files,
             skip_decoders,
+            extra_accounts,
         })
     }
 }
 
+/// One IDL path or a bracketed list of them, see
+/// [`parse_idl_path`](super::idl_file::parse_idl_path)
+fn parse_idl_paths(input: ParseStream, arg: &str) -> syn::Result<Vec<syn::LitStr>> {
+    if input.peek(syn::token::Bracket) {
+        let content;
+        syn::bracketed!(content in input);
+
+        let mut paths = Vec::new();
+        while !content.is_empty() {
+            paths.push(super::idl_file::parse_idl_path(&content)?);
+
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+            }
+        }
+        Ok(paths)
+    } else if input.peek(syn::LitStr) || input.peek(syn::Ident) {
+        Ok(vec![super::idl_file::parse_idl_path(input)?])
+    } else {
+        Err(input.error(format!(
+            "Expected string literal, env!(\"VAR\") or array of them for {}",
+            arg
+        )))
+    }
+}
+
 pub fn parse_stream_spec_attribute(
     attr: proc_macro::TokenStream,
 ) -> Result<StreamSpecAttribute, syn::Error> {
@@ -1597,7 +2301,9 @@ pub fn parse_stream_spec_attribute(
         return Ok(StreamSpecAttribute {
             proto_files: Vec::new(),
             idl_files: Vec::new(),
+            previous_idl_files: Vec::new(),
             skip_decoders: false,
+            extra_accounts: Vec::new(),
         });
     }
 
@@ -1612,19 +2318,37 @@ pub fn parse_stream_spec_attribute(
         }
     }
 
-    for file in &args.idl_files {
-        if file.trim().is_empty() {
+    for file in args.idl_files.iter().chain(&args.previous_idl_files) {
+        if file.value().trim().is_empty() {
             return Err(syn::Error::new(
-                proc_macro2::Span::call_site(),
+                file.span(),
                 "idl file references cannot be empty",
             ));
         }
     }
 
+    if let Some(hash) = args.idl_hashes.first() {
+        if args.idl_hashes.len() != args.idl_files.len() {
+            return Err(syn::Error::new(
+                hash.span(),
+                format!(
+                    "idl_hash has {} hash(es) for {} idl file(s). Give one per idl path, in the same order",
+                    args.idl_hashes.len(),
+                    args.idl_files.len()
+                ),
+            ));
+        }
+    }
+    for (file, hash) in args.idl_files.iter().zip(&args.idl_hashes) {
+        super::idl_file::check_idl_hash(file, hash)?;
+    }
+
     Ok(StreamSpecAttribute {
         proto_files: args.proto_files,
         idl_files: args.idl_files,
+        previous_idl_files: args.previous_idl_files,
         skip_decoders: args.skip_decoders,
+        extra_accounts: args.extra_accounts,
     })
 }
 
@@ -2245,3 +2969,126 @@ pub fn parse_view_attributes(attrs: &[Attribute]) -> syn::Result<Vec<crate::ast:
         .map(|spec| spec.view)
         .collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_transaction_maps_each_instruction_to_the_transaction_field() {
+        let attr: Attribute = syn::parse_quote! {
+            #[from_transaction(
+                from = [ore_sdk::instructions::Deploy, ore_sdk::instructions::Checkpoint],
+                field = "fee_payer",
+                strategy = SetOnce
+            )]
+        };
+
+        let mappings = parse_from_transaction_attribute(&attr, "deployer")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(
+            mappings[1].source_type_string(),
+            "ore_sdk::instructions::Checkpoint"
+        );
+        for mapping in &mappings {
+            assert!(mapping.is_instruction);
+            assert_eq!(mapping.transaction_field(), Some("fee_payer"));
+            assert_eq!(mapping.target_field_name, "deployer");
+            assert_eq!(mapping.strategy, "SetOnce");
+        }
+    }
+
+    #[test]
+    fn from_transaction_rejects_unknown_fields() {
+        let attr: Attribute = syn::parse_quote! {
+            #[from_transaction(from = ore_sdk::instructions::Deploy, field = "fee_payr")]
+        };
+
+        let error = parse_from_transaction_attribute(&attr, "deployer").unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("invalid field 'fee_payr' for #[from_transaction]"));
+    }
+
+    #[test]
+    fn map_defaults_to_the_latest_write_policy() {
+        let attr: Attribute = syn::parse_quote! {
+            #[map(accounts::Round::motherlode)]
+        };
+        let mappings = parse_map_attribute(&attr, "motherlode").unwrap().unwrap();
+        assert_eq!(mappings[0].strategy, "LastWrite");
+
+        let attr: Attribute = syn::parse_quote! {
+            #[map(accounts::Round::created_at, policy = "set_once")]
+        };
+        let mappings = parse_map_attribute(&attr, "created_at").unwrap().unwrap();
+        assert_eq!(mappings[0].strategy, "SetOnce");
+
+        let attr: Attribute = syn::parse_quote! {
+            #[from_instruction(instructions::SetExecutor::executor)]
+        };
+        let mappings = parse_from_instruction_attribute(&attr, "executor")
+            .unwrap()
+            .unwrap();
+        assert_eq!(mappings[0].strategy, "LastWrite");
+    }
+
+    #[test]
+    fn from_instruction_clear_action_selects_the_clear_strategy() {
+        let attr: Attribute = syn::parse_quote! {
+            #[from_instruction(instructions::CancelAutomation::authority, action = clear)]
+        };
+
+        let mappings = parse_from_instruction_attribute(&attr, "executor")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(mappings.len(), 1);
+        assert_eq!(mappings[0].strategy, "Clear");
+        assert_eq!(mappings[0].target_field_name, "executor");
+    }
+
+    #[test]
+    fn clear_action_rejects_write_policies_and_other_attributes() {
+        let with_policy: Attribute = syn::parse_quote! {
+            #[from_instruction(instructions::Cancel::authority, action = clear, strategy = SetOnce)]
+        };
+        let error = parse_from_instruction_attribute(&with_policy, "executor").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("takes no `strategy` or `policy`"));
+
+        let unknown: Attribute = syn::parse_quote! {
+            #[from_instruction(instructions::Cancel::authority, action = delete)]
+        };
+        let error = parse_from_instruction_attribute(&unknown, "executor").unwrap_err();
+        assert!(error.to_string().contains("invalid action 'delete'"));
+
+        let on_map: Attribute = syn::parse_quote! {
+            #[map(accounts::Automation::executor, action = clear)]
+        };
+        let error = parse_map_attribute(&on_map, "executor").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("only supported by #[from_instruction]"));
+    }
+
+    #[test]
+    fn resolve_accepts_a_placeholder() {
+        let attr: Attribute = syn::parse_quote! {
+            #[resolve(address = "So11111111111111111111111111111111111111112", placeholder = "pending")]
+        };
+
+        let resolve = parse_resolve_attribute(&attr, "metadata").unwrap().unwrap();
+        assert_eq!(resolve.placeholder.as_deref(), Some("pending"));
+
+        let attr: Attribute = syn::parse_quote! {
+            #[resolve(from = "id.mint", placeholder = "")]
+        };
+        assert!(parse_resolve_attribute(&attr, "metadata").is_err());
+    }
+}
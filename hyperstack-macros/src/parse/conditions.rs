@@ -7,6 +7,7 @@ use crate::ast::{ComparisonOp, FieldPath, LogicalOp, ParsedCondition, ResolverCo
 /// - Comparisons: "field > 100", "amount >= 1000000"
 /// - Logical ops: "amount > 100 && user != \"excluded\"", "a < 10 || a > 1000"
 /// - Field refs: "amount", "data.field", "accounts.user"
+/// - Change detection: "changed(state.owner)", "changed(state.owner, initial = false)"
 pub fn parse_condition_expression_strict(expr: &str) -> Result<ParsedCondition, String> {
     let expr = expr.trim();
 
@@ -14,6 +15,10 @@ pub fn parse_condition_expression_strict(expr: &str) -> Result<ParsedCondition,
         return Err("Condition expression cannot be empty".to_string());
     }
 
+    if let Some(parsed) = try_parse_changed(expr)? {
+        return Ok(parsed);
+    }
+
     // Try to parse as logical expression first (contains && or ||)
     if let Some(parsed) = try_parse_logical(expr)? {
         return Ok(parsed);
@@ -35,8 +40,8 @@ fn try_parse_logical(expr: &str) -> Result<Option<ParsedCondition>, String> {
         return Ok(Some(ParsedCondition::Logical {
             op: LogicalOp::Or,
             conditions: vec![
-                parse_condition_expression_strict(left)?,
-                parse_condition_expression_strict(right)?,
+                parse_logical_operand(expr, left)?,
+                parse_logical_operand(expr, right)?,
             ],
         }));
     }
@@ -49,8 +54,8 @@ fn try_parse_logical(expr: &str) -> Result<Option<ParsedCondition>, String> {
         return Ok(Some(ParsedCondition::Logical {
             op: LogicalOp::And,
             conditions: vec![
-                parse_condition_expression_strict(left)?,
-                parse_condition_expression_strict(right)?,
+                parse_logical_operand(expr, left)?,
+                parse_logical_operand(expr, right)?,
             ],
         }));
     }
@@ -58,6 +63,73 @@ fn try_parse_logical(expr: &str) -> Result<Option<ParsedCondition>, String> {
     Ok(None)
 }
 
+fn parse_logical_operand(expr: &str, operand: &str) -> Result<ParsedCondition, String> {
+    let parsed = parse_condition_expression_strict(operand)?;
+    if matches!(parsed, ParsedCondition::Changed { .. }) {
+        return Err(format!(
+            "Invalid condition expression '{}': changed(...) must be the whole condition and cannot be combined with && / ||",
+            expr
+        ));
+    }
+    Ok(parsed)
+}
+
+/// Parse `changed(state.path)` or `changed(state.path, initial = <bool>)`.
+fn try_parse_changed(expr: &str) -> Result<Option<ParsedCondition>, String> {
+    let Some(rest) = expr.strip_prefix("changed") else {
+        return Ok(None);
+    };
+    let rest = rest.trim_start();
+    let Some(inner) = rest.strip_prefix('(') else {
+        return Ok(None);
+    };
+    if find_top_level_operator(expr, "&&").is_some()
+        || find_top_level_operator(expr, "||").is_some()
+    {
+        return Ok(None);
+    }
+    let Some(inner) = inner.trim_end().strip_suffix(')') else {
+        return Err(format!(
+            "Invalid condition expression '{}': expected changed(state.<field>)",
+            expr
+        ));
+    };
+
+    let mut args = inner.split(',').map(str::trim);
+    let path = args.next().unwrap_or_default();
+    let Some(field) = path.strip_prefix("state.").filter(|f| !f.is_empty()) else {
+        return Err(format!(
+            "Invalid condition expression '{}': changed(...) takes an entity state path like 'state.owner'",
+            expr
+        ));
+    };
+
+    let mut initial = true;
+    for arg in args {
+        let value = arg
+            .strip_prefix("initial")
+            .map(str::trim_start)
+            .and_then(|v| v.strip_prefix('='))
+            .map(str::trim);
+        initial = match value {
+            Some("true") => true,
+            Some("false") => false,
+            _ => {
+                return Err(format!(
+                    "Invalid condition expression '{}': unexpected argument '{}'. Only 'initial = true' or 'initial = false' is supported",
+                    expr, arg
+                ))
+            }
+        };
+    }
+
+    let segments: Vec<&str> = field.split('.').collect();
+    Ok(Some(ParsedCondition::Changed {
+        field: FieldPath::new(&segments),
+        initial,
+    }))
+}
+
 fn try_parse_comparison(expr: &str) -> Result<ParsedCondition, String> {
     // Try each comparison operator in order (longer ones first to avoid conflicts)
     let operators = [
@@ -422,4 +494,34 @@ mod tests {
             _ => panic!("Expected comparison"),
         }
     }
+
+    #[test]
+    fn test_changed_condition() {
+        let parsed = parse_condition_expression_strict("changed(state.owner)").unwrap();
+        match parsed {
+            ParsedCondition::Changed { field, initial } => {
+                assert_eq!(field.segments, vec!["owner"]);
+                assert!(initial);
+            }
+            _ => panic!("Expected changed"),
+        }
+
+        let parsed =
+            parse_condition_expression_strict("changed(state.info.owner, initial = false)")
+                .unwrap();
+        match parsed {
+            ParsedCondition::Changed { field, initial } => {
+                assert_eq!(field.segments, vec!["info", "owner"]);
+                assert!(!initial);
+            }
+            _ => panic!("Expected changed"),
+        }
+    }
+
+    #[test]
+    fn test_changed_condition_rejects_invalid_forms() {
+        assert!(parse_condition_expression_strict("changed(owner)").is_err());
+        assert!(parse_condition_expression_strict("changed(state.owner, initial = 1)").is_err());
+        assert!(parse_condition_expression_strict("changed(state.owner) && amount > 1").is_err());
+    }
 }
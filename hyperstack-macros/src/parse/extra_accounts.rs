@@ -0,0 +1,189 @@
+//! Parsing of `extra_accounts` entries of `#[hyperstack(...)]`.
+//!
+//! An entry watches accounts of a program the stack has no IDL for:
+//!
+//! ```text
+//! "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA:(owner=<pubkey>)"
+//! ```
+//!
+//! Only programs with a builtin account layout can be watched this way; the
+//! layout is exposed to entities as a synthesized IDL, so the accounts are
+//! mapped like those of any other program.
+
+use syn::LitStr;
+
+use crate::diagnostic::invalid_choice_message;
+use crate::parse::idl::{parse_idl_content, IdlSpec};
+
+/// The SPL Token program
+pub const SPL_TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+const FILTER_KINDS: [&str; 3] = ["address", "owner", "mint"];
+
+/// The SPL token account layout as an IDL. Field names match the JSON the
+/// runtime's token account parser produces.
+const SPL_TOKEN_IDL: &str = r#"{
+  "name": "spl_token",
+  "address": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+  "instructions": [],
+  "accounts": [
+    {
+      "name": "TokenAccount",
+      "docs": ["An SPL token account."],
+      "type": {
+        "kind": "struct",
+        "fields": [
+          { "name": "mint", "type": "publicKey" },
+          { "name": "owner", "type": "publicKey" },
+          { "name": "amount", "type": "u64" },
+          { "name": "delegate", "type": { "option": "publicKey" } },
+          { "name": "state", "type": "u8" },
+          { "name": "is_native", "type": { "option": "u64" } },
+          { "name": "delegated_amount", "type": "u64" },
+          { "name": "close_authority", "type": { "option": "publicKey" } }
+        ]
+      }
+    }
+  ],
+  "types": [],
+  "events": [],
+  "errors": []
+}"#;
+
+/// Accounts of one program watched through `extra_accounts`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtraAccounts {
+    pub program_id: String,
+    /// `kind=<pubkey>` filters; an account passing any of them is watched
+    pub filters: Vec<String>,
+}
+
+impl ExtraAccounts {
+    /// The IDL describing the program's builtin account layout
+    pub fn idl(&self) -> IdlSpec {
+        parse_idl_content(SPL_TOKEN_IDL).expect("builtin SPL token IDL should parse")
+    }
+}
+
+/// Parse the `extra_accounts` entries, grouping the filters by program.
+pub fn parse_extra_accounts(entries: &[LitStr]) -> syn::Result<Vec<ExtraAccounts>> {
+    let mut programs: Vec<ExtraAccounts> = Vec::new();
+
+    for entry in entries {
+        let (program_id, filter) = parse_entry(entry)?;
+        match programs.iter_mut().find(|p| p.program_id == program_id) {
+            Some(program) => {
+                if !program.filters.contains(&filter) {
+                    program.filters.push(filter);
+                }
+            }
+            None => programs.push(ExtraAccounts {
+                program_id,
+                filters: vec![filter],
+            }),
+        }
+    }
+
+    Ok(programs)
+}
+
+fn parse_entry(entry: &LitStr) -> syn::Result<(String, String)> {
+    let value = entry.value();
+    let error = |message: String| syn::Error::new(entry.span(), message);
+
+    let (program_id, filter) = value
+        .split_once(':')
+        .and_then(|(program_id, rest)| {
+            let filter = rest.trim().strip_prefix('(')?.strip_suffix(')')?;
+            Some((program_id.trim(), filter.trim()))
+        })
+        .ok_or_else(|| {
+            error(format!(
+                "invalid extra_accounts entry '{value}'. Expected \"<program>:(<filter>=<pubkey>)\", \
+                 e.g. \"{SPL_TOKEN_PROGRAM_ID}:(owner=<pubkey>)\""
+            ))
+        })?;
+
+    if program_id != SPL_TOKEN_PROGRAM_ID {
+        return Err(error(format!(
+            "no builtin account layout for program '{program_id}'. extra_accounts supports the \
+             SPL Token program ({SPL_TOKEN_PROGRAM_ID}); add other programs' IDLs to `idl = [...]`"
+        )));
+    }
+
+    let (kind, key) = filter
+        .split_once('=')
+        .map(|(kind, key)| (kind.trim(), key.trim()))
+        .ok_or_else(|| {
+            error(format!(
+                "invalid extra_accounts filter '{filter}'. Expected <filter>=<pubkey>, one of: {}",
+                FILTER_KINDS.join(", ")
+            ))
+        })?;
+
+    if !FILTER_KINDS.contains(&kind) {
+        return Err(error(invalid_choice_message(
+            "filter",
+            kind,
+            "extra_accounts",
+            &FILTER_KINDS,
+        )));
+    }
+
+    let is_pubkey = bs58::decode(key)
+        .into_vec()
+        .is_ok_and(|bytes| bytes.len() == 32);
+    if !is_pubkey {
+        return Err(error(format!(
+            "invalid pubkey '{key}' in extra_accounts filter '{filter}'"
+        )));
+    }
+
+    Ok((program_id.to_string(), format!("{kind}={key}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VAULT: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+    fn lit(value: &str) -> LitStr {
+        LitStr::new(value, proc_macro2::Span::call_site())
+    }
+
+    #[test]
+    fn entries_are_grouped_by_program() {
+        let parsed = parse_extra_accounts(&[
+            lit(&format!("{SPL_TOKEN_PROGRAM_ID}:(owner={VAULT})")),
+            lit(&format!("{SPL_TOKEN_PROGRAM_ID}: ( mint = {VAULT} )")),
+            lit(&format!("{SPL_TOKEN_PROGRAM_ID}:(owner={VAULT})")),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            parsed,
+            vec![ExtraAccounts {
+                program_id: SPL_TOKEN_PROGRAM_ID.to_string(),
+                filters: vec![format!("owner={VAULT}"), format!("mint={VAULT}")],
+            }]
+        );
+        assert_eq!(parsed[0].idl().get_name(), "spl_token");
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        let error = |entry: String| {
+            parse_extra_accounts(&[lit(&entry)])
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(error(format!("{SPL_TOKEN_PROGRAM_ID}/owner={VAULT}"))
+            .contains("invalid extra_accounts entry"));
+        assert!(error(format!("{VAULT}:(owner={VAULT})")).contains("no builtin account layout"));
+        assert!(error(format!("{SPL_TOKEN_PROGRAM_ID}:(ownr={VAULT})"))
+            .contains("Did you mean: owner?"));
+        assert!(error(format!("{SPL_TOKEN_PROGRAM_ID}:(owner=vault)")).contains("invalid pubkey"));
+    }
+}
@@ -132,3 +132,57 @@ fn map_simple_type_bytemuck(idl_type: &str) -> String {
         _ => idl_type.to_string(),
     }
 }
+
+/// Fixed `u8` arrays longer than this are stored as encoded strings rather
+/// than JSON number arrays.
+pub const MAX_PLAIN_BYTE_ARRAY: u32 = 32;
+
+/// Longest fixed `u8` array stored as base58, which covers 64-byte
+/// signatures. Longer arrays use base64, whose cost grows linearly.
+pub const MAX_BASE58_BYTE_ARRAY: u32 = 64;
+
+/// Encoding of `u128`/`i128` values, including inside options, vectors and
+/// arrays. The generated parsers always emit these as decimal strings.
+pub fn integer_encoding(idl_type: &IdlType) -> Option<crate::ast::ValueEncoding> {
+    match idl_type {
+        IdlType::Simple(s) => matches!(s.as_str(), "u128" | "i128")
+            .then_some(crate::ast::ValueEncoding::DecimalString),
+        IdlType::Option(opt) => integer_encoding(&opt.option),
+        IdlType::Vec(vec) => integer_encoding(&vec.vec),
+        IdlType::Array(arr) => match arr.array.first() {
+            Some(IdlTypeArrayElement::Type(t)) => integer_encoding(&IdlType::Simple(t.clone())),
+            Some(IdlTypeArrayElement::Nested(nested)) => integer_encoding(nested),
+            _ => None,
+        },
+        IdlType::Defined(_) | IdlType::HashMap(_) => None,
+    }
+}
+
+/// Encoding for a fixed `u8` array too large to store as numbers, e.g.
+/// `[u8; 64]` becomes base58. Only bare arrays qualify, since the encoding
+/// transform needs a value to work on.
+pub fn byte_array_encoding(idl_type: &IdlType) -> Option<crate::ast::ValueEncoding> {
+    let IdlType::Array(arr) = idl_type else {
+        return None;
+    };
+    let (element, size) = match arr.array.as_slice() {
+        [IdlTypeArrayElement::Type(t), IdlTypeArrayElement::Size(size)] => (t.as_str(), *size),
+        [IdlTypeArrayElement::Nested(IdlType::Simple(t)), IdlTypeArrayElement::Size(size)] => {
+            (t.as_str(), *size)
+        }
+        _ => return None,
+    };
+    if element != "u8" || size <= MAX_PLAIN_BYTE_ARRAY {
+        return None;
+    }
+    Some(if size <= MAX_BASE58_BYTE_ARRAY {
+        crate::ast::ValueEncoding::Base58
+    } else {
+        crate::ast::ValueEncoding::Base64
+    })
+}
+
+/// Encoding of a value of `idl_type` once it is stored in entity state
+pub fn value_encoding(idl_type: &IdlType) -> Option<crate::ast::ValueEncoding> {
+    byte_array_encoding(idl_type).or_else(|| integer_encoding(idl_type))
+}
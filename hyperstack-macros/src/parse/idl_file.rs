@@ -0,0 +1,380 @@
+//! Loading the IDL files named in `#[hyperstack(idl = ...)]`.
+//!
+//! Paths are relative to `CARGO_MANIFEST_DIR`, and may be read from an
+//! environment variable at compile time with `idl = env!("MY_IDL_PATH")`.
+//! Errors point at the path in the attribute: a missing file names the path
+//! that was tried and the JSON files next to it, invalid JSON shows the line
+//! and column it stopped at, and each missing top-level section of the IDL
+//! gets its own message.
+//!
+//! With `idl_hash = "sha256:..."`, an IDL must also match the content hash
+//! it was pinned to by `hs idl fetch --pin`. Only the local file is checked:
+//! fetching is left to the CLI, so expanding the macro never touches the
+//! network.
+
+use std::path::{Path, PathBuf};
+
+use proc_macro2::Span;
+use syn::parse::ParseStream;
+use syn::Token;
+
+use hyperstack_idl::snapshot::pin::{content_hash, validate_pin};
+
+use crate::diagnostic::{suggestion_or_available_suffix, ErrorCollector};
+use crate::parse::idl::IdlSpec;
+
+/// Top-level sections every IDL has, with what they hold
+const REQUIRED_SECTIONS: [(&str, &str); 2] = [
+    ("instructions", "the program's instructions"),
+    ("accounts", "the program's account types"),
+];
+
+/// Sections that may be left out, but must be arrays when present
+const OPTIONAL_SECTIONS: [(&str, &str); 4] = [
+    ("types", "the types the instructions and accounts use"),
+    ("events", "the program's events"),
+    ("errors", "the program's error codes"),
+    ("constants", "the program's constants"),
+];
+
+/// Parse an IDL path: a string literal, or `env!("VAR")` to read the path
+/// from an environment variable when the macro expands. The result carries
+/// the span of what was written, for errors about the file.
+pub fn parse_idl_path(input: ParseStream) -> syn::Result<syn::LitStr> {
+    if input.peek(syn::LitStr) {
+        return input.parse();
+    }
+
+    let ident: syn::Ident = input.parse()?;
+    if ident != "env" {
+        return Err(syn::Error::new(
+            ident.span(),
+            format!(
+                "invalid IDL path '{}'. Expected a string literal or env!(\"VAR\")",
+                ident
+            ),
+        ));
+    }
+    input.parse::<Token![!]>()?;
+    let content;
+    syn::parenthesized!(content in input);
+    let var: syn::LitStr = content.parse()?;
+    if !content.is_empty() {
+        return Err(content.error("env!(...) for an IDL path takes only the variable name"));
+    }
+
+    match std::env::var(var.value()) {
+        Ok(path) if !path.trim().is_empty() => Ok(syn::LitStr::new(&path, var.span())),
+        _ => Err(syn::Error::new(
+            var.span(),
+            format!(
+                "missing environment variable '{}' for the IDL path. Set it to the IDL file, relative to CARGO_MANIFEST_DIR or absolute",
+                var.value()
+            ),
+        )),
+    }
+}
+
+/// Read and parse the IDL file at `path`, relative to `CARGO_MANIFEST_DIR`
+pub fn load_idl_file(path: &syn::LitStr) -> syn::Result<IdlSpec> {
+    let content = read_idl_file(path)?;
+    parse_idl(&path.value(), &content, path.span())
+}
+
+/// Check that the IDL file at `path` still has the pinned content `hash`.
+///
+/// A file that isn't JSON passes, to be reported when it is loaded.
+pub fn check_idl_hash(path: &syn::LitStr, hash: &syn::LitStr) -> syn::Result<()> {
+    let pin = hash.value();
+    if validate_pin(&pin).is_err() {
+        return Err(syn::Error::new(
+            hash.span(),
+            format!(
+                "invalid idl_hash '{}'. Expected sha256: followed by 64 lowercase hex digits, as written by `hs idl fetch --pin`",
+                pin
+            ),
+        ));
+    }
+
+    let content = read_idl_file(path)?;
+    match content_hash(content.as_bytes()) {
+        Ok(actual) if actual != pin => Err(syn::Error::new(
+            hash.span(),
+            format!(
+                "pinned IDL file '{}' changed: it hashes to {}, not its idl_hash. Restore the pinned IDL with `hs idl fetch <program_id>`, or update idl_hash if the change is intended",
+                path.value(),
+                actual
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn read_idl_file(path: &syn::LitStr) -> syn::Result<String> {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    let written = path.value();
+    let full_path = Path::new(&manifest_dir).join(&written);
+    let span = path.span();
+
+    std::fs::read_to_string(&full_path).map_err(|error| {
+        if error.kind() == std::io::ErrorKind::NotFound {
+            syn::Error::new(span, missing_file_message(&written, &full_path))
+        } else {
+            syn::Error::new(
+                span,
+                format!(
+                    "failed to read IDL file '{}' at {}: {}",
+                    written,
+                    full_path.display(),
+                    error
+                ),
+            )
+        }
+    })
+}
+
+fn parse_idl(written: &str, content: &str, span: Span) -> syn::Result<IdlSpec> {
+    let json: serde_json::Value = serde_json::from_str(content)
+        .map_err(|error| syn::Error::new(span, json_error_message(written, content, &error)))?;
+
+    let Some(root) = json.as_object() else {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "invalid IDL file '{}': expected a JSON object at the top level, found {}",
+                written,
+                json_kind(&json)
+            ),
+        ));
+    };
+
+    let mut errors = ErrorCollector::default();
+    for (section, holds) in REQUIRED_SECTIONS {
+        match root.get(section) {
+            None => errors.push(syn::Error::new(
+                span,
+                format!(
+                    "invalid IDL file '{}': missing \"{}\" section (an array of {}, use [] for none)",
+                    written, section, holds
+                ),
+            )),
+            Some(value) if !value.is_array() => {
+                errors.push(section_type_error(written, section, holds, value, span))
+            }
+            Some(_) => {}
+        }
+    }
+    for (section, holds) in OPTIONAL_SECTIONS {
+        if let Some(value) = root.get(section).filter(|value| !value.is_array()) {
+            errors.push(section_type_error(written, section, holds, value, span));
+        }
+    }
+    errors.finish()?;
+
+    serde_json::from_str(content)
+        .map_err(|error| syn::Error::new(span, json_error_message(written, content, &error)))
+}
+
+fn section_type_error(
+    written: &str,
+    section: &str,
+    holds: &str,
+    value: &serde_json::Value,
+    span: Span,
+) -> syn::Error {
+    syn::Error::new(
+        span,
+        format!(
+            "invalid IDL file '{}': \"{}\" should be an array of {}, found {}",
+            written,
+            section,
+            holds,
+            json_kind(value)
+        ),
+    )
+}
+
+fn json_kind(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+/// What went wrong where, with the line of the file it went wrong on
+fn json_error_message(written: &str, content: &str, error: &serde_json::Error) -> String {
+    let description = error.to_string();
+    // serde_json appends the position, which is shown separately
+    let description = description
+        .rsplit_once(" at line ")
+        .map_or(description.as_str(), |(description, _)| description);
+
+    let lines: Vec<&str> = content.lines().collect();
+    let (line, column) = match lines.get(error.line().saturating_sub(1)) {
+        Some(_) if error.column() > 0 => (error.line(), error.column()),
+        // An unexpected end is reported past the last line, point after it
+        _ => match lines.iter().rposition(|line| !line.trim().is_empty()) {
+            Some(index) => (index + 1, lines[index].chars().count() + 1),
+            None => (1, 1),
+        },
+    };
+    let mut message = format!(
+        "invalid IDL file '{}' at line {}, column {}: {}",
+        written, line, column, description
+    );
+
+    if let Some(text) = lines.get(line - 1) {
+        let (snippet, offset) = snippet(text, column);
+        let gutter = line.to_string();
+        message.push_str(&format!(
+            "\n{pad} |\n{gutter} | {snippet}\n{pad} | {caret:>width$}",
+            pad = " ".repeat(gutter.len()),
+            caret = "^",
+            width = offset + 1,
+        ));
+    }
+    message
+}
+
+/// At most `MAX` characters of `line` around `column` (1-based), and the
+/// offset of `column` in them
+fn snippet(line: &str, column: usize) -> (String, usize) {
+    const MAX: usize = 80;
+    let chars: Vec<char> = line.chars().collect();
+    let column = column.saturating_sub(1).min(chars.len());
+    let start = column
+        .saturating_sub(MAX / 2)
+        .min(chars.len().saturating_sub(MAX));
+    let end = (start + MAX).min(chars.len());
+    (chars[start..end].iter().collect(), column - start)
+}
+
+fn missing_file_message(written: &str, full_path: &Path) -> String {
+    let mut message = if Path::new(written).is_absolute() {
+        format!("missing IDL file '{}'", written)
+    } else {
+        format!(
+            "missing IDL file '{}': nothing at {} (paths are relative to CARGO_MANIFEST_DIR)",
+            written,
+            full_path.display()
+        )
+    };
+
+    let Some(dir) = full_path.parent() else {
+        return message;
+    };
+    match json_files_in(dir) {
+        Some(files) if files.is_empty() => {
+            message.push_str(&format!(". There are no .json files in {}", dir.display()))
+        }
+        Some(files) => {
+            let file_name = full_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            message.push_str(&suggestion_or_available_suffix(
+                &file_name,
+                &files,
+                &format!("JSON files in {}", dir.display()),
+            ));
+        }
+        None => message.push_str(&format!(". The directory {} doesn't exist", dir.display())),
+    }
+    message
+}
+
+/// Names of the `.json` files in `dir`, sorted, or `None` if it can't be read
+fn json_files_in(dir: &Path) -> Option<Vec<String>> {
+    let mut files: Vec<String> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| PathBuf::from(entry.file_name()))
+        .filter(|name| name.extension().is_some_and(|ext| ext == "json"))
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    Some(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_of(content: &str) -> String {
+        parse_idl("idl.json", content, Span::call_site())
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn truncated_json_points_at_where_it_stops() {
+        let message = error_of("{\n  \"instructions\": [\n    { \"name\": \"buy\",\n\n");
+
+        assert!(
+            message.starts_with(
+                "invalid IDL file 'idl.json' at line 3, column 21: EOF while parsing a value"
+            ),
+            "{message}"
+        );
+        assert!(
+            message.ends_with("3 |     { \"name\": \"buy\",\n  |                     ^"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn each_missing_section_is_reported() {
+        let message = error_of(r#"{ "name": "fake", "types": {} }"#);
+
+        assert!(
+            message.contains("missing \"instructions\" section"),
+            "{message}"
+        );
+        assert!(
+            message.contains("missing \"accounts\" section"),
+            "{message}"
+        );
+        assert!(
+            message.contains("\"types\" should be an array of the types the instructions and accounts use, found an object"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn idl_hash_must_match_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("idl.json");
+        std::fs::write(
+            &file,
+            r#"{ "name": "demo", "instructions": [], "accounts": [] }"#,
+        )
+        .unwrap();
+        let path = syn::LitStr::new(file.to_str().unwrap(), Span::call_site());
+        let lit = |value: &str| syn::LitStr::new(value, Span::call_site());
+
+        let pin = content_hash(&std::fs::read(&file).unwrap()).unwrap();
+        assert!(check_idl_hash(&path, &lit(&pin)).is_ok());
+
+        let stale = format!("sha256:{}", "0".repeat(64));
+        let message = check_idl_hash(&path, &lit(&stale)).unwrap_err().to_string();
+        assert!(
+            message.contains(&format!("it hashes to {pin}")),
+            "{message}"
+        );
+
+        let message = check_idl_hash(&path, &lit("sha256:abc"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.starts_with("invalid idl_hash 'sha256:abc'"),
+            "{message}"
+        );
+    }
+}
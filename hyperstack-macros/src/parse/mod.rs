@@ -3,13 +3,17 @@
 //! This module contains all parsing logic including:
 //! - `attributes` - Parsing of #[map], #[event], #[snapshot], etc. macro attributes
 //! - `idl` - Parsing of Anchor IDL JSON files
+//! - `idl_file` - Locating and loading the IDL files named in `#[hyperstack(idl = ...)]`
 //! - `proto` - Parsing of Protocol Buffer (.proto) files
 //! - `conditions` - Parsing of condition expressions
 //! - `pdas` - Parsing of pdas! macro blocks
+//! - `extra_accounts` - Parsing of `extra_accounts` entries of `#[hyperstack(...)]`
 
 pub mod attributes;
 pub mod conditions;
+pub mod extra_accounts;
 pub mod idl;
+pub mod idl_file;
 pub mod pda_validation;
 pub mod pdas;
 pub mod proto;
@@ -7,30 +7,36 @@
 //! The same AST is used for both inline code generation (via `codegen::generate_handlers_from_specs`)
 //! and for the `#[ast_spec]` macro, ensuring identical output.
 
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 use crate::ast::writer::{
     convert_idl_to_snapshot, parse_population_strategy, parse_transformation,
 };
 use crate::ast::{
-    ComputedFieldSpec, ConditionExpr, EntitySection, FieldPath, FieldTypeInfo, HookAction,
-    IdentitySpec, IdlSerializationSnapshot, InstructionHook, KeyResolutionStrategy,
-    LookupIndexSpec, MappingSource, ResolveStrategy, ResolverCondition, ResolverExtractSpec,
-    ResolverHook, ResolverSpec, ResolverStrategy, ResolverType, SerializableFieldMapping,
-    SerializableHandlerSpec, SerializableStreamSpec, SourceSpec,
+    AccountFallback, ComputedExpr, ComputedFieldSpec, ConditionExpr, DeleteTrigger, EntityPriority,
+    EntitySection, FieldPath, FieldTypeInfo, HookAction, IdentitySpec, IdlSerializationSnapshot,
+    InstructionHook, ItemDocs, KeyResolutionStrategy, LookupIndexSpec, MappingSource,
+    ResolveStrategy, ResolverCondition, ResolverExtractSpec, ResolverHook, ResolverSpec,
+    ResolverStrategy, ResolverType, SerializableFieldMapping, SerializableHandlerSpec,
+    SerializableStreamSpec, SourceSpec, Transformation, ValueEncoding,
 };
 use crate::diagnostic::{idl_error_to_syn, internal_codegen_error};
-use crate::event_type_helpers::{find_idl_for_type, program_name_for_type, IdlLookup};
+use crate::event_type_helpers::{
+    find_idl_for_type, program_hint, program_name_for_type, IdlLookup,
+};
 use crate::parse;
 use crate::parse::conditions as condition_parser;
 use crate::parse::idl as idl_parser;
-use crate::utils::path_to_string;
+use crate::utils::{path_to_string, to_snake_case};
 use hyperstack_idl::error::IdlSearchError;
-use hyperstack_idl::search::{lookup_account, lookup_instruction_field, InstructionFieldKind};
+use hyperstack_idl::search::{
+    lookup_account, lookup_instruction, lookup_instruction_account, lookup_instruction_field,
+    lookup_type, InstructionFieldKind,
+};
 
 use super::computed::{
     expr_contains_u64_from_bytes, extract_resolver_type_from_computed_expr,
-    parse_computed_expression, qualify_field_refs,
+    mark_time_dependent_fields, parse_computed_expression, qualify_field_refs,
 };
 use super::handlers::{find_field_in_instruction, get_join_on_field};
 
@@ -60,6 +66,11 @@ use super::handlers::{find_field_in_instruction, get_join_on_field};
 /// * `section_specs` - Entity section specifications
 /// * `idl` - Optional IDL specification for field resolution
 /// * `views` - View definitions for derived views
+/// * `trace_fields` - Field paths recorded on per-event tracing spans
+/// * `feature` - Cargo feature gating the entity, if any
+/// * `key_from` - Account field the entity is keyed by, if any
+/// * `priority` - Priority of the entity's updates under load shedding
+/// * `key_normalizer` - Expression rewriting the primary key, if any
 #[allow(clippy::too_many_arguments)]
 pub fn build_ast(
     entity_name: &str,
@@ -76,6 +87,13 @@ pub fn build_ast(
     section_specs: &[EntitySection],
     idls: IdlLookup,
     views: Vec<crate::ast::ViewDef>,
+    trace_fields: Vec<String>,
+    feature: Option<String>,
+    key_from: Option<&parse::KeyFromSpec>,
+    priority: EntityPriority,
+    delete_on: Option<DeleteTrigger>,
+    key_normalizer: Option<ComputedExpr>,
+    docs: Option<ItemDocs>,
 ) -> syn::Result<SerializableStreamSpec> {
     let idl = idls.first().map(|(_, idl)| *idl);
     let handlers = build_handlers(
@@ -88,6 +106,7 @@ pub fn build_ast(
     )?;
 
     let mut resolver_hooks_ast = build_resolver_hooks_ast(resolver_hooks, idls);
+    resolver_hooks_ast.extend(key_from.and_then(|key_from| key_from_resolver(key_from, &handlers)));
     resolver_hooks_ast.extend(auto_generate_lookup_resolvers(
         &handlers,
         &resolver_hooks_ast,
@@ -117,7 +136,7 @@ pub fn build_ast(
     let idl_snapshot = idl.map(convert_idl_to_snapshot);
 
     // Parse computed field expressions into ComputedFieldSpec
-    let computed_field_specs: Vec<ComputedFieldSpec> = computed_fields
+    let mut computed_field_specs: Vec<ComputedFieldSpec> = computed_fields
         .iter()
         .map(|(target_path, expr_tokens, field_type)| {
             let result_type = quote::quote!(#field_type).to_string();
@@ -135,15 +154,33 @@ pub fn build_ast(
                 target_path: target_path.clone(),
                 expression: qualified_expression,
                 result_type,
+                time_dependent: false,
             }
         })
         .collect();
+    mark_time_dependent_fields(&mut computed_field_specs);
 
     let resolver_specs = build_resolver_specs(resolve_specs)?;
 
+    // Fields fed through an encoding transform hold strings, not the source bytes
+    let mut section_specs = section_specs.to_vec();
+    for (target_path, encoding) in mapped_encodings(&handlers) {
+        let (section_name, field_name) = target_path
+            .split_once('.')
+            .unwrap_or(("root", target_path.as_str()));
+        if let Some(field_info) = section_specs
+            .iter_mut()
+            .filter(|section| section.name == section_name)
+            .flat_map(|section| section.fields.iter_mut())
+            .find(|field_info| field_info.field_name == field_name)
+        {
+            field_info.encoding = Some(encoding);
+        }
+    }
+
     // Build field_mappings from sections - this provides type information for ALL fields
     let mut field_mappings = BTreeMap::new();
-    for section in section_specs {
+    for section in &section_specs {
         for field_info in &section.fields {
             // Handle root-level fields (no section prefix)
             let field_path = if section.name == "root" {
@@ -197,7 +234,9 @@ pub fn build_ast(
                 inner_type: Some(resolver_type.to_string()),
                 source_path: None,
                 resolved_type: None,
+                encoding: None,
                 emit: true,
+                docs: None,
             };
             field_mappings.insert(computed_spec.target_path.clone(), field_info);
         }
@@ -215,11 +254,12 @@ pub fn build_ast(
                 .map(|(field_name, temporal_field)| LookupIndexSpec {
                     field_name: field_name.clone(),
                     temporal_field: temporal_field.clone(),
+                    shared_index: shared_index_for_field(sources_by_type, field_name),
                 })
                 .collect(),
         },
         handlers,
-        sections: section_specs.to_vec(),
+        sections: section_specs,
         field_mappings,
         resolver_hooks: resolver_hooks_ast,
         instruction_hooks: instruction_hooks_ast,
@@ -228,7 +268,15 @@ pub fn build_ast(
         computed_field_specs,
         content_hash: None,
         views,
+        trace_fields,
+        feature,
+        priority,
+        delete_on,
+        key_normalizer,
+        docs,
+        event_types: Vec::new(),
     };
+    spec.event_types = routed_event_types(&spec.handlers, &spec.instruction_hooks);
     // Compute and set the content hash
     spec.content_hash = Some(spec.try_compute_content_hash().map_err(|error| {
         internal_codegen_error(
@@ -239,6 +287,106 @@ pub fn build_ast(
     Ok(spec)
 }
 
+/// Event types `handlers` and `instruction_hooks` route to an entity, sorted
+/// and without duplicates
+fn routed_event_types(
+    handlers: &[SerializableHandlerSpec],
+    instruction_hooks: &[InstructionHook],
+) -> Vec<String> {
+    let types: BTreeSet<&String> = handlers
+        .iter()
+        .map(|handler| match &handler.source {
+            SourceSpec::Source { type_name, .. } => type_name,
+        })
+        .chain(instruction_hooks.iter().map(|hook| &hook.instruction_type))
+        .collect();
+    types.into_iter().cloned().collect()
+}
+
+/// Target fields whose mappings end in a base58/base64 encoding transform
+fn mapped_encodings(handlers: &[SerializableHandlerSpec]) -> BTreeMap<String, ValueEncoding> {
+    fn final_stage(transform: &Transformation) -> Option<&Transformation> {
+        match transform {
+            Transformation::Chain(stages) => stages.last().and_then(final_stage),
+            stage => Some(stage),
+        }
+    }
+
+    let mut encodings = BTreeMap::new();
+    for mapping in handlers.iter().flat_map(|handler| &handler.mappings) {
+        let MappingSource::FromSource {
+            transform: Some(transform),
+            ..
+        } = &mapping.source
+        else {
+            continue;
+        };
+        let encoding = match final_stage(transform) {
+            Some(Transformation::Base58Encode) => ValueEncoding::Base58,
+            Some(Transformation::Base64Encode) => ValueEncoding::Base64,
+            _ => continue,
+        };
+        encodings.insert(mapping.target_path.clone(), encoding);
+    }
+    encodings
+}
+
+/// IDL type of a top-level account field or instruction argument, looked up
+/// by its snake_case name
+fn idl_source_field_type<'a>(
+    idl: &'a idl_parser::IdlSpec,
+    type_name: &str,
+    field_name: &str,
+    is_instruction: bool,
+) -> Option<&'a idl_parser::IdlType> {
+    if is_instruction {
+        return lookup_instruction(idl, type_name)
+            .ok()?
+            .args
+            .iter()
+            .find(|arg| to_snake_case(&arg.name) == field_name)
+            .map(|arg| &arg.type_);
+    }
+    account_struct_fields(idl, type_name)?
+        .iter()
+        .find(|field| to_snake_case(&field.name) == field_name)
+        .map(|field| &field.type_)
+}
+
+fn account_struct_fields<'a>(
+    idl: &'a idl_parser::IdlSpec,
+    account_name: &str,
+) -> Option<&'a [idl_parser::IdlField]> {
+    let account = lookup_account(idl, account_name).ok()?;
+    let type_def = match &account.type_def {
+        Some(type_def) => type_def,
+        None => &lookup_type(idl, account_name).ok()?.type_def,
+    };
+    match type_def {
+        idl_parser::IdlTypeDefKind::Struct { fields, .. } => Some(fields),
+        _ => None,
+    }
+}
+
+/// Transform storing a large fixed byte array as a string, applied when the
+/// mapping doesn't name its own transform
+fn auto_byte_array_transform(idl_type: Option<&idl_parser::IdlType>) -> Option<Transformation> {
+    idl_parser::byte_array_encoding(idl_type?).and_then(ValueEncoding::transformation)
+}
+
+/// Shared index named by any `#[map(..., shared_index = "...")]` that
+/// declares `field_name` as a lookup index.
+fn shared_index_for_field(
+    sources_by_type: &BTreeMap<String, Vec<parse::MapAttribute>>,
+    field_name: &str,
+) -> Option<String> {
+    sources_by_type
+        .values()
+        .flatten()
+        .filter(|mapping| mapping.is_lookup_index && mapping.target_field_name == field_name)
+        .find_map(|mapping| mapping.shared_index.clone())
+}
+
 fn build_resolver_specs(resolve_specs: &[parse::ResolveSpec]) -> syn::Result<Vec<ResolverSpec>> {
     let mut grouped: BTreeMap<String, ResolverSpec> = BTreeMap::new();
 
@@ -293,6 +441,7 @@ fn build_resolver_specs(resolve_specs: &[parse::ResolveSpec]) -> syn::Result<Vec
             target_path: spec.target_field_name.clone(),
             source_path,
             transform: None,
+            placeholder: spec.placeholder.clone(),
         };
 
         if !entry.extracts.iter().any(|existing| {
@@ -359,6 +508,13 @@ pub fn build_and_write_ast(
     section_specs: &[EntitySection],
     idls: IdlLookup,
     views: Vec<crate::ast::ViewDef>,
+    trace_fields: Vec<String>,
+    feature: Option<String>,
+    key_from: Option<&parse::KeyFromSpec>,
+    priority: EntityPriority,
+    delete_on: Option<DeleteTrigger>,
+    key_normalizer: Option<ComputedExpr>,
+    docs: Option<ItemDocs>,
 ) -> syn::Result<SerializableStreamSpec> {
     build_ast(
         entity_name,
@@ -375,6 +531,13 @@ pub fn build_and_write_ast(
         section_specs,
         idls,
         views,
+        trace_fields,
+        feature,
+        key_from,
+        priority,
+        delete_on,
+        key_normalizer,
+        docs,
     )
 }
 
@@ -501,8 +664,9 @@ fn build_source_handler(
             continue;
         }
 
+        let mut account_fallback = None;
         let source = if mapping.is_whole_source {
-            let field_transforms = if mapping
+            let mut field_transforms = if mapping
                 .source_field_name
                 .starts_with("__snapshot_with_transforms:")
             {
@@ -524,8 +688,24 @@ fn build_source_handler(
             } else {
                 BTreeMap::new()
             };
+            if let Some(fields) = idl
+                .filter(|_| !is_instruction && !is_cpi_event)
+                .and_then(|idl| account_struct_fields(idl, account_type))
+            {
+                for field in fields {
+                    if let Some(transform) = auto_byte_array_transform(Some(&field.type_)) {
+                        field_transforms
+                            .entry(to_snake_case(&field.name))
+                            .or_insert(transform);
+                    }
+                }
+            }
 
             MappingSource::AsCapture { field_transforms }
+        } else if let Some(field) = mapping.transaction_field() {
+            MappingSource::FromTransaction {
+                field: field.to_string(),
+            }
         } else {
             let field_path = if is_cpi_event {
                 // CPI events: all fields (including identifiers like lb_pair, from) are under "data"
@@ -538,24 +718,12 @@ fn build_source_handler(
                 if mapping.source_field_name.is_empty() {
                     FieldPath::new(&["data"])
                 } else {
-                    let prefix = if let Some(idl) = idl {
-                        match lookup_instruction_field(
-                            idl,
-                            account_type,
-                            &mapping.source_field_name,
-                        )
-                        .map_err(|error| {
-                            idl_error_to_syn(span_for_map_lookup_error(mapping, &error), error)
-                        })?
-                        .kind
-                        {
-                            InstructionFieldKind::Account => "accounts",
-                            InstructionFieldKind::Arg => "data",
-                        }
-                    } else {
-                        "data"
+                    let (prefix, name, fallback) = match idl {
+                        Some(idl) => resolve_instruction_field(idl, account_type, mapping)?,
+                        None => ("data", mapping.source_field_name.clone(), None),
                     };
-                    FieldPath::new(&[prefix, &mapping.source_field_name])
+                    account_fallback = fallback;
+                    FieldPath::new(&[prefix, &name])
                 }
             } else if mapping.source_field_name.is_empty() {
                 FieldPath::new(&[])
@@ -563,13 +731,23 @@ fn build_source_handler(
                 FieldPath::new(&[&mapping.source_field_name])
             };
 
+            let transform = match &mapping.transform {
+                Some(t) => parse_transformation(t),
+                None if is_cpi_event => None,
+                None => auto_byte_array_transform(idl.and_then(|idl| {
+                    idl_source_field_type(
+                        idl,
+                        account_type,
+                        &mapping.source_field_name,
+                        is_instruction,
+                    )
+                })),
+            };
+
             MappingSource::FromSource {
                 path: field_path,
                 default: None,
-                transform: mapping
-                    .transform
-                    .as_ref()
-                    .and_then(|t| parse_transformation(t)),
+                transform,
             }
         };
 
@@ -608,6 +786,7 @@ fn build_source_handler(
             when,
             stop,
             emit: mapping.emit,
+            account_fallback,
         });
 
         if mapping.is_primary_key {
@@ -616,20 +795,11 @@ fn build_source_handler(
                 // CPI event fields are always in "data"
                 primary_field = Some(format!("data.{}", mapping.source_field_name));
             } else if is_instruction {
-                let prefix = if let Some(idl) = idl {
-                    match lookup_instruction_field(idl, account_type, &mapping.source_field_name)
-                        .map_err(|error| {
-                            idl_error_to_syn(span_for_map_lookup_error(mapping, &error), error)
-                        })?
-                        .kind
-                    {
-                        InstructionFieldKind::Account => "accounts",
-                        InstructionFieldKind::Arg => "data",
-                    }
-                } else {
-                    "data"
+                let (prefix, name, _) = match idl {
+                    Some(idl) => resolve_instruction_field(idl, account_type, mapping)?,
+                    None => ("data", mapping.source_field_name.clone(), None),
                 };
-                primary_field = Some(format!("{}.{}", prefix, mapping.source_field_name));
+                primary_field = Some(format!("{}.{}", prefix, name));
             } else {
                 primary_field = Some(mapping.source_field_name.clone());
             }
@@ -661,7 +831,12 @@ fn build_source_handler(
                     }
                 }
             };
-            format!("{}.{}", prefix, fs.ident)
+            let written = fs.ident.to_string();
+            let name = idl
+                .filter(|_| is_instruction && prefix == "accounts")
+                .and_then(|idl| lookup_instruction_account(idl, account_type, &written, None).ok())
+                .map_or(written.clone(), |account| account.name.to_string());
+            format!("{}.{}", prefix, name)
         });
 
     let key_resolution = if has_primary_key {
@@ -766,7 +941,7 @@ fn build_source_handler(
 
     Ok(Some(SerializableHandlerSpec {
         source: SourceSpec::Source {
-            program_id: None,
+            program_id: program_hint(idl, idls),
             discriminator: None,
             type_name,
             serialization,
@@ -779,6 +954,47 @@ fn build_source_handler(
     }))
 }
 
+/// Where an instruction mapping reads its field from: `accounts` under the
+/// IDL's spelling of the account, or `data`. A mapping with `index = N` whose
+/// account the IDL no longer names reads the account at that position.
+fn resolve_instruction_field(
+    idl: &idl_parser::IdlSpec,
+    instruction: &str,
+    mapping: &parse::MapAttribute,
+) -> syn::Result<(&'static str, String, Option<AccountFallback>)> {
+    let written = &mapping.source_field_name;
+    let error = match lookup_instruction_field(idl, instruction, written) {
+        Ok(field) => {
+            return Ok(match field.kind {
+                InstructionFieldKind::Account => ("accounts", field.name.to_string(), None),
+                InstructionFieldKind::Arg => ("data", written.clone(), None),
+            });
+        }
+        Err(error) => error,
+    };
+
+    let account = match mapping.account_index {
+        Some(index) => lookup_instruction_account(idl, instruction, written, Some(index)).ok(),
+        None => None,
+    };
+    match account {
+        Some(account) => Ok((
+            "accounts",
+            account.name.to_string(),
+            Some(AccountFallback {
+                instruction: account.instruction.name.clone(),
+                written: written.clone(),
+                resolved: account.name.to_string(),
+                index: account.index,
+            }),
+        )),
+        None => Err(idl_error_to_syn(
+            span_for_map_lookup_error(mapping, &error),
+            error,
+        )),
+    }
+}
+
 fn span_for_map_lookup_error(
     mapping: &parse::MapAttribute,
     error: &IdlSearchError,
@@ -847,7 +1063,6 @@ fn build_event_handler(
         return Ok(None);
     }
 
-    let program_id = parts[0];
     let instruction_type = parts[1];
     let instruction_type_pascal = idl_parser::to_pascal_case(instruction_type);
 
@@ -953,6 +1168,7 @@ fn build_event_handler(
             when: None,
             stop: None,
             emit: true,
+            account_fallback: None,
         });
     }
 
@@ -1095,7 +1311,7 @@ fn build_event_handler(
 
     Ok(Some(SerializableHandlerSpec {
         source: SourceSpec::Source {
-            program_id: Some(program_id.to_string()),
+            program_id: program_hint(idl, idls),
             discriminator: None,
             type_name,
             serialization: None,
@@ -1168,6 +1384,34 @@ fn build_resolver_hooks_ast(
         .collect()
 }
 
+/// Resolver hook of the account named by `#[entity(key_from = ...)]`
+fn key_from_resolver(
+    key_from: &parse::KeyFromSpec,
+    handlers: &[SerializableHandlerSpec],
+) -> Option<ResolverHook> {
+    let state_type = format!("{}State", key_from.account_name());
+    handlers.iter().find_map(|handler| {
+        let SourceSpec::Source {
+            type_name,
+            is_account: true,
+            ..
+        } = &handler.source
+        else {
+            return None;
+        };
+        if type_name.rsplit("::").next() != Some(state_type.as_str()) {
+            return None;
+        }
+        Some(ResolverHook {
+            account_type: type_name.clone(),
+            strategy: ResolverStrategy::KeyFrom {
+                field_path: FieldPath::new(&[&key_from.field]),
+                index_name: format!("{}_lookup_index", key_from.address_field()),
+            },
+        })
+    })
+}
+
 fn auto_generate_lookup_resolvers(
     handlers: &[SerializableHandlerSpec],
     existing_resolvers: &[ResolverHook],
@@ -1479,3 +1723,348 @@ fn build_instruction_hooks_ast(
 
     instruction_hooks_map.into_values().collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::BaseType;
+    use crate::stream_spec::sections::analyze_field_type_with_idl;
+
+    const VAULT: &str = "wide_sdk::accounts::Vault";
+    const SETTLE: &str = "wide_sdk::instructions::Settle";
+
+    fn wide_values_idl() -> idl_parser::IdlSpec {
+        idl_parser::parse_idl_content(include_str!(
+            "../../../hyperstack-idl/tests/fixtures/wide_values.json"
+        ))
+        .expect("fixture IDL should parse")
+    }
+
+    fn map(source_type: &str, source_field: &str, target: &str) -> parse::MapAttribute {
+        let span = proc_macro2::Span::call_site();
+        parse::MapAttribute {
+            attr_span: span,
+            source_type_span: span,
+            source_field_span: span,
+            is_event_source: false,
+            is_account_source: source_type == VAULT,
+            source_type_path: syn::parse_str(source_type).unwrap(),
+            source_field_name: source_field.to_string(),
+            target_field_name: target.to_string(),
+            is_primary_key: false,
+            is_lookup_index: false,
+            shared_index: None,
+            register_from: Vec::new(),
+            temporal_field: None,
+            strategy: "LastWrite".to_string(),
+            join_on: None,
+            transform: None,
+            resolver_transform: None,
+            is_instruction: source_type == SETTLE,
+            is_whole_source: source_field.is_empty(),
+            lookup_by: None,
+            condition: None,
+            when: None,
+            stop: None,
+            stop_lookup_by: None,
+            emit: true,
+            account_index: None,
+        }
+    }
+
+    fn build_vault_ast(idl: &idl_parser::IdlSpec) -> SerializableStreamSpec {
+        let idls = [("wide_sdk".to_string(), idl)];
+        let mut explicit = map(VAULT, "last_signature", "vault.signature_hex");
+        explicit.transform = Some("HexEncode".to_string());
+        let sources_by_type = BTreeMap::from([
+            (
+                VAULT.to_string(),
+                vec![
+                    map(VAULT, "total_deposits", "vault.total_deposits"),
+                    map(VAULT, "last_signature", "vault.last_signature"),
+                    map(VAULT, "merkle_root", "vault.merkle_root"),
+                    map(VAULT, "proof", "vault.proof"),
+                    map(VAULT, "", "vault.snapshot"),
+                    explicit,
+                ],
+            ),
+            (
+                SETTLE.to_string(),
+                vec![
+                    map(SETTLE, "amount", "vault.settled"),
+                    map(SETTLE, "attestation", "vault.attestation"),
+                ],
+            ),
+        ]);
+        let fields = [
+            ("total_deposits", "Option<u128>"),
+            ("last_signature", "Option<String>"),
+            ("merkle_root", "Option<Vec<u8>>"),
+            ("proof", "Option<String>"),
+            ("snapshot", "Option<wide_sdk::accounts::Vault>"),
+            ("signature_hex", "Option<String>"),
+            ("settled", "Option<u128>"),
+            ("attestation", "Option<String>"),
+        ]
+        .into_iter()
+        .map(|(name, ty)| analyze_field_type_with_idl(name, ty, &idls))
+        .collect();
+        let sections = [EntitySection {
+            name: "vault".to_string(),
+            fields,
+            is_nested_struct: true,
+            parent_field: None,
+            docs: None,
+            shared_type: None,
+        }];
+
+        build_ast(
+            "Vault",
+            &[],
+            &[],
+            &sources_by_type,
+            &BTreeMap::new(),
+            &[],
+            &[],
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+            &[],
+            &[],
+            &sections,
+            &idls,
+            Vec::new(),
+            Vec::new(),
+            None,
+            None,
+            EntityPriority::Normal,
+            None,
+            None,
+            None,
+        )
+        .expect("AST should build")
+    }
+
+    fn transforms(spec: &SerializableStreamSpec) -> BTreeMap<String, String> {
+        spec.handlers
+            .iter()
+            .flat_map(|handler| &handler.mappings)
+            .filter_map(|mapping| match &mapping.source {
+                MappingSource::FromSource { transform, .. } => Some((
+                    mapping.target_path.clone(),
+                    transform
+                        .as_ref()
+                        .map_or("none".to_string(), |t| format!("{t:?}")),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn large_byte_arrays_get_an_encoding_transform() {
+        let idl = wide_values_idl();
+        let spec = build_vault_ast(&idl);
+
+        let expected = [
+            ("vault.total_deposits", "none"),
+            ("vault.last_signature", "Base58Encode"),
+            ("vault.merkle_root", "none"),
+            ("vault.proof", "Base64Encode"),
+            ("vault.signature_hex", "HexEncode"),
+            ("vault.settled", "none"),
+            ("vault.attestation", "Base58Encode"),
+        ];
+        let expected: BTreeMap<String, String> = expected
+            .into_iter()
+            .map(|(target, transform)| (target.to_string(), transform.to_string()))
+            .collect();
+        assert_eq!(transforms(&spec), expected);
+
+        let capture = spec
+            .handlers
+            .iter()
+            .flat_map(|handler| &handler.mappings)
+            .find_map(|mapping| match &mapping.source {
+                MappingSource::AsCapture { field_transforms } => Some(field_transforms),
+                _ => None,
+            })
+            .expect("snapshot mapping should capture the account");
+        let captured: Vec<_> = capture
+            .iter()
+            .map(|(field, transform)| format!("{field}={transform:?}"))
+            .collect();
+        assert_eq!(
+            captured,
+            ["last_signature=Base58Encode", "proof=Base64Encode"]
+        );
+    }
+
+    #[test]
+    fn ast_records_value_encodings() {
+        let idl = wide_values_idl();
+        let spec = build_vault_ast(&idl);
+        let encoding = |field: &str| spec.field_mappings[field].encoding;
+
+        assert_eq!(
+            encoding("vault.total_deposits"),
+            Some(ValueEncoding::DecimalString)
+        );
+        assert_eq!(
+            encoding("vault.settled"),
+            Some(ValueEncoding::DecimalString)
+        );
+        assert_eq!(
+            encoding("vault.last_signature"),
+            Some(ValueEncoding::Base58)
+        );
+        assert_eq!(encoding("vault.attestation"), Some(ValueEncoding::Base58));
+        assert_eq!(encoding("vault.proof"), Some(ValueEncoding::Base64));
+        assert_eq!(encoding("vault.merkle_root"), None);
+        assert_eq!(encoding("vault.signature_hex"), None);
+        assert_eq!(
+            spec.sections[0].fields[1].encoding,
+            Some(ValueEncoding::Base58)
+        );
+
+        let snapshot = spec.field_mappings["vault.snapshot"]
+            .resolved_type
+            .as_ref()
+            .expect("account type should resolve");
+        let fields: BTreeMap<_, _> = snapshot
+            .fields
+            .iter()
+            .map(|field| (field.field_name.as_str(), field))
+            .collect();
+        assert_eq!(
+            fields["totalDeposits"].encoding,
+            Some(ValueEncoding::DecimalString)
+        );
+        assert_eq!(
+            fields["netFlow"].encoding,
+            Some(ValueEncoding::DecimalString)
+        );
+        assert_eq!(
+            fields["lastSignature"].encoding,
+            Some(ValueEncoding::Base58)
+        );
+        assert_eq!(fields["lastSignature"].base_type, BaseType::String);
+        assert!(!fields["lastSignature"].is_array);
+        assert_eq!(fields["proof"].encoding, Some(ValueEncoding::Base64));
+        assert_eq!(fields["merkleRoot"].encoding, None);
+        assert!(fields["merkleRoot"].is_array);
+
+        // Encodings survive the JSON the AST is written as
+        let json = serde_json::to_value(&spec.field_mappings["vault.total_deposits"]).unwrap();
+        assert_eq!(json["encoding"], "DecimalString");
+        assert!(
+            serde_json::to_value(&spec.field_mappings["vault.merkle_root"])
+                .unwrap()
+                .get("encoding")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn ast_records_the_event_types_routed_to_the_entity() {
+        let idl = wide_values_idl();
+        let spec = build_vault_ast(&idl);
+        let handler_types: BTreeSet<&String> = spec
+            .handlers
+            .iter()
+            .map(|handler| match &handler.source {
+                SourceSpec::Source { type_name, .. } => type_name,
+            })
+            .collect();
+
+        assert_eq!(spec.event_types.len(), 2);
+        assert_eq!(
+            spec.event_types.iter().collect::<BTreeSet<_>>(),
+            handler_types
+        );
+        assert!(spec.event_types.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(spec.verify_content_hash());
+    }
+
+    fn config_idl(address: &str) -> idl_parser::IdlSpec {
+        idl_parser::parse_idl_content(&format!(
+            r#"{{
+                "address": "{address}",
+                "metadata": {{ "name": "amm", "version": "0.1.0", "spec": "0.1.0" }},
+                "instructions": [],
+                "accounts": [{{ "name": "Config", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8] }}],
+                "types": [{{
+                    "name": "Config",
+                    "type": {{ "kind": "struct", "fields": [{{ "name": "fee", "type": "u64" }}] }}
+                }}]
+            }}"#
+        ))
+        .expect("config IDL should parse")
+    }
+
+    #[test]
+    fn handlers_name_their_program_when_several_idls_are_in_play() {
+        const V1: &str = "AmmV111111111111111111111111111111111111111";
+        const V2: &str = "AmmV211111111111111111111111111111111111111";
+        let (v1, v2) = (config_idl(V1), config_idl(V2));
+        let sources_by_type = BTreeMap::from([
+            (
+                "amm_v1_sdk::accounts::Config".to_string(),
+                vec![map("amm_v1_sdk::accounts::Config", "fee", "v1.fee")],
+            ),
+            (
+                "amm_v2_sdk::accounts::Config".to_string(),
+                vec![map("amm_v2_sdk::accounts::Config", "fee", "v2.fee")],
+            ),
+        ]);
+        let sources = |handlers: Vec<SerializableHandlerSpec>| -> Vec<(String, Option<String>)> {
+            handlers
+                .into_iter()
+                .map(|handler| {
+                    let SourceSpec::Source {
+                        type_name,
+                        program_id,
+                        ..
+                    } = handler.source;
+                    (type_name, program_id)
+                })
+                .collect()
+        };
+
+        let idls = [
+            ("amm_v1_sdk".to_string(), &v1),
+            ("amm_v2_sdk".to_string(), &v2),
+        ];
+        let handlers = build_handlers(
+            &sources_by_type,
+            &BTreeMap::new(),
+            &[],
+            &[],
+            &BTreeMap::new(),
+            &idls,
+        )
+        .expect("handlers should build");
+        // Both IDLs are named "amm", so only the program tells the handlers apart
+        assert_eq!(
+            sources(handlers),
+            [
+                ("amm::ConfigState".to_string(), Some(V1.to_string())),
+                ("amm::ConfigState".to_string(), Some(V2.to_string())),
+            ]
+        );
+
+        let idls = [("amm_v1_sdk".to_string(), &v1)];
+        let handlers = build_handlers(
+            &BTreeMap::from([(
+                "amm_v1_sdk::accounts::Config".to_string(),
+                vec![map("amm_v1_sdk::accounts::Config", "fee", "v1.fee")],
+            )]),
+            &BTreeMap::new(),
+            &[],
+            &[],
+            &BTreeMap::new(),
+            &idls,
+        )
+        .expect("handlers should build");
+        assert_eq!(sources(handlers), [("amm::ConfigState".to_string(), None)]);
+    }
+}
@@ -17,10 +17,15 @@
 //! - Slice syntax: `expr[start..end]`
 //! - Byte conversion: `u64::from_le_bytes(expr)`
 //! - Closures: `|x| body`
+//! - Time builtins: `now()`, `slot()`, `slots_to_secs(n)`, `secs_to_slots(n)`
+//! - Append array aggregates: `array_sum(path)`, `array_sum(path, field)`,
+//!   `array_last(path, field)`, `array_window_avg(path, field, n)`
 
 use std::collections::HashSet;
 
-use crate::ast::{BinaryOp, ComputedExpr, UnaryOp};
+use crate::ast::{
+    ArrayAggregateKind, ArrayAggregateSpec, BinaryOp, ComputedExpr, ComputedFieldSpec, UnaryOp,
+};
 use proc_macro2::TokenTree;
 
 /// Parse a computed expression from a TokenStream into a ComputedExpr AST.
@@ -31,6 +36,72 @@ pub fn parse_computed_expression(tokens: &proc_macro2::TokenStream) -> ComputedE
     resolve_bindings_in_expr(expr, &HashSet::new())
 }
 
+/// Methods a key normalizer may call.
+const KEY_NORMALIZER_METHODS: &[&str] = &[
+    "to_lowercase",
+    "to_uppercase",
+    "trim",
+    "trim_start_matches",
+    "trim_end_matches",
+    "to_base58",
+];
+
+/// Parse `#[entity(key_normalizer = ...)]`. The expression only sees the raw
+/// key, bound to `key`: entity state hasn't been read when it runs.
+pub fn parse_key_normalizer(expr: &syn::Expr) -> syn::Result<ComputedExpr> {
+    let parsed = parse_computed_expression(&quote::ToTokens::to_token_stream(expr));
+    let normalizer = resolve_bindings_in_expr(parsed, &HashSet::from(["key".to_string()]));
+    validate_key_normalizer(&normalizer)
+        .map_err(|message| syn::Error::new_spanned(expr, message))?;
+    Ok(normalizer)
+}
+
+fn validate_key_normalizer(expr: &ComputedExpr) -> Result<(), String> {
+    match expr {
+        ComputedExpr::Var { .. } | ComputedExpr::Literal { .. } => Ok(()),
+        ComputedExpr::FieldRef { path } => Err(format!(
+            "key_normalizer can only read `key`, found `{}`",
+            path
+        )),
+        ComputedExpr::Paren { expr } | ComputedExpr::Unary { expr, .. } => {
+            validate_key_normalizer(expr)
+        }
+        ComputedExpr::Binary { left, right, .. } => {
+            validate_key_normalizer(left)?;
+            validate_key_normalizer(right)
+        }
+        ComputedExpr::Let { value, body, .. } => {
+            validate_key_normalizer(value)?;
+            validate_key_normalizer(body)
+        }
+        ComputedExpr::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            validate_key_normalizer(condition)?;
+            validate_key_normalizer(then_branch)?;
+            validate_key_normalizer(else_branch)
+        }
+        ComputedExpr::MethodCall { expr, method, args } => {
+            if !KEY_NORMALIZER_METHODS.contains(&method.as_str()) {
+                return Err(crate::diagnostic::invalid_choice_message(
+                    "method",
+                    method,
+                    "key_normalizer",
+                    KEY_NORMALIZER_METHODS,
+                ));
+            }
+            validate_key_normalizer(expr)?;
+            args.iter().try_for_each(validate_key_normalizer)
+        }
+        _ => Err(
+            "key_normalizer supports `key`, literals, conditionals and string methods only"
+                .to_string(),
+        ),
+    }
+}
+
 fn resolver_for_method(method: &str) -> Option<&'static str> {
     match method {
         "ui_amount" | "raw_amount" => Some("TokenMetadata"),
@@ -78,7 +149,9 @@ pub fn expr_contains_u64_from_bytes(expr: &crate::ast::ComputedExpr) -> bool {
             then_branch,
             else_branch,
         } => expr_contains_u64_from_bytes(then_branch) || expr_contains_u64_from_bytes(else_branch),
-        ComputedExpr::Keccak256 { expr } => expr_contains_u64_from_bytes(expr),
+        ComputedExpr::Keccak256 { expr }
+        | ComputedExpr::SlotsToSecs { expr }
+        | ComputedExpr::SecsToSlots { expr } => expr_contains_u64_from_bytes(expr),
         ComputedExpr::JsonToBytes { expr } => expr_contains_u64_from_bytes(expr),
         ComputedExpr::Closure { body, .. } => expr_contains_u64_from_bytes(body),
         ComputedExpr::ResolverComputed { .. }
@@ -88,7 +161,73 @@ pub fn expr_contains_u64_from_bytes(expr: &crate::ast::ComputedExpr) -> bool {
         | ComputedExpr::ByteArray { .. }
         | ComputedExpr::None
         | ComputedExpr::ContextSlot
-        | ComputedExpr::ContextTimestamp => false,
+        | ComputedExpr::ContextTimestamp
+        | ComputedExpr::Now
+        | ComputedExpr::ArrayAggregate { .. } => false,
+    }
+}
+
+/// Mark computed fields whose value depends on evaluation time.
+///
+/// A field is time-dependent if its expression calls `now()` or reads another
+/// time-dependent computed field.
+pub fn mark_time_dependent_fields(specs: &mut [ComputedFieldSpec]) {
+    let mut time_dependent: HashSet<String> = HashSet::new();
+    loop {
+        let mut changed = false;
+        for spec in specs.iter_mut() {
+            if !spec.time_dependent && expr_depends_on_time(&spec.expression, &time_dependent) {
+                spec.time_dependent = true;
+                time_dependent.insert(spec.target_path.clone());
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+fn expr_depends_on_time(expr: &ComputedExpr, time_dependent: &HashSet<String>) -> bool {
+    let recurse = |e: &ComputedExpr| expr_depends_on_time(e, time_dependent);
+    match expr {
+        ComputedExpr::Now => true,
+        ComputedExpr::FieldRef { path } => time_dependent.iter().any(|target| {
+            path == target
+                || path
+                    .strip_prefix(target.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        }),
+        ComputedExpr::UnwrapOr { expr, .. }
+        | ComputedExpr::Cast { expr, .. }
+        | ComputedExpr::Paren { expr }
+        | ComputedExpr::Some { value: expr }
+        | ComputedExpr::Slice { expr, .. }
+        | ComputedExpr::Index { expr, .. }
+        | ComputedExpr::U64FromLeBytes { bytes: expr }
+        | ComputedExpr::U64FromBeBytes { bytes: expr }
+        | ComputedExpr::JsonToBytes { expr }
+        | ComputedExpr::Keccak256 { expr }
+        | ComputedExpr::SlotsToSecs { expr }
+        | ComputedExpr::SecsToSlots { expr }
+        | ComputedExpr::Unary { expr, .. }
+        | ComputedExpr::Closure { body: expr, .. } => recurse(expr),
+        ComputedExpr::Binary { left, right, .. } => recurse(left) || recurse(right),
+        ComputedExpr::MethodCall { expr, args, .. } => recurse(expr) || args.iter().any(recurse),
+        ComputedExpr::ResolverComputed { args, .. } => args.iter().any(recurse),
+        ComputedExpr::Let { value, body, .. } => recurse(value) || recurse(body),
+        ComputedExpr::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => recurse(condition) || recurse(then_branch) || recurse(else_branch),
+        ComputedExpr::Var { .. }
+        | ComputedExpr::Literal { .. }
+        | ComputedExpr::ByteArray { .. }
+        | ComputedExpr::None
+        | ComputedExpr::ContextSlot
+        | ComputedExpr::ContextTimestamp
+        | ComputedExpr::ArrayAggregate { .. } => false,
     }
 }
 
@@ -209,6 +348,19 @@ pub fn qualify_field_refs(expr: ComputedExpr, section: &str) -> ComputedExpr {
         },
         ComputedExpr::ContextSlot => ComputedExpr::ContextSlot,
         ComputedExpr::ContextTimestamp => ComputedExpr::ContextTimestamp,
+        ComputedExpr::Now => ComputedExpr::Now,
+        ComputedExpr::ArrayAggregate { mut aggregate } => {
+            if !aggregate.path.contains('.') {
+                aggregate.path = format!("{}.{}", section, aggregate.path);
+            }
+            ComputedExpr::ArrayAggregate { aggregate }
+        }
+        ComputedExpr::SlotsToSecs { expr } => ComputedExpr::SlotsToSecs {
+            expr: Box::new(qualify_field_refs(*expr, section)),
+        },
+        ComputedExpr::SecsToSlots { expr } => ComputedExpr::SecsToSlots {
+            expr: Box::new(qualify_field_refs(*expr, section)),
+        },
         ComputedExpr::Keccak256 { expr } => ComputedExpr::Keccak256 {
             expr: Box::new(qualify_field_refs(*expr, section)),
         },
@@ -356,6 +508,48 @@ fn parse_expr(tokens: &[proc_macro2::TokenTree], start: usize) -> (ComputedExpr,
     parse_binary_expr(tokens, start, 0)
 }
 
+/// Parse the arguments of an append array aggregate. The array and item
+/// field are dotted paths, the window size a positive integer literal.
+fn parse_array_aggregate(
+    name: &str,
+    tokens: &[proc_macro2::TokenTree],
+) -> Option<ArrayAggregateSpec> {
+    let args: Vec<&[proc_macro2::TokenTree]> = tokens
+        .split(|token| matches!(token, proc_macro2::TokenTree::Punct(p) if p.as_char() == ','))
+        .filter(|arg| !arg.is_empty())
+        .collect();
+    let path = |arg: &[proc_macro2::TokenTree]| -> Option<String> {
+        arg.iter()
+            .map(|token| match token {
+                proc_macro2::TokenTree::Ident(ident) => Some(ident.to_string()),
+                proc_macro2::TokenTree::Punct(p) if p.as_char() == '.' => Some(".".to_string()),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let (kind, array, field) = match (name, args.as_slice()) {
+        ("array_sum", [array]) => (ArrayAggregateKind::Sum, array, None),
+        ("array_sum", [array, field]) => (ArrayAggregateKind::Sum, array, Some(field)),
+        ("array_last", [array]) => (ArrayAggregateKind::Last, array, None),
+        ("array_last", [array, field]) => (ArrayAggregateKind::Last, array, Some(field)),
+        ("array_window_avg", [array, field, [proc_macro2::TokenTree::Literal(n)]]) => {
+            let n = n.to_string().parse::<usize>().ok().filter(|n| *n > 0)?;
+            (ArrayAggregateKind::WindowAvg { n }, array, Some(field))
+        }
+        _ => return None,
+    };
+
+    Some(ArrayAggregateSpec {
+        kind,
+        path: path(array)?,
+        field: match field {
+            Some(field) => Some(path(field)?),
+            None => None,
+        },
+    })
+}
+
 /// Convert FieldRefs that match bound variable names to Vars in a Let expression.
 /// This is called as a post-processing step after parsing.
 fn resolve_bindings_in_expr(expr: ComputedExpr, bindings: &HashSet<String>) -> ComputedExpr {
@@ -454,6 +648,12 @@ fn resolve_bindings_in_expr(expr: ComputedExpr, bindings: &HashSet<String>) -> C
         ComputedExpr::Keccak256 { expr } => ComputedExpr::Keccak256 {
             expr: Box::new(resolve_bindings_in_expr(*expr, bindings)),
         },
+        ComputedExpr::SlotsToSecs { expr } => ComputedExpr::SlotsToSecs {
+            expr: Box::new(resolve_bindings_in_expr(*expr, bindings)),
+        },
+        ComputedExpr::SecsToSlots { expr } => ComputedExpr::SecsToSlots {
+            expr: Box::new(resolve_bindings_in_expr(*expr, bindings)),
+        },
         ComputedExpr::Closure { param, body } => {
             // The closure param is also a binding
             let mut new_bindings = bindings.clone();
@@ -469,7 +669,9 @@ fn resolve_bindings_in_expr(expr: ComputedExpr, bindings: &HashSet<String>) -> C
         | ComputedExpr::Literal { .. }
         | ComputedExpr::ByteArray { .. }
         | ComputedExpr::ContextSlot
-        | ComputedExpr::ContextTimestamp => expr,
+        | ComputedExpr::ContextTimestamp
+        | ComputedExpr::Now
+        | ComputedExpr::ArrayAggregate { .. } => expr,
     }
 }
 
@@ -1066,6 +1268,37 @@ fn parse_primary_expr(tokens: &[proc_macro2::TokenTree], start: usize) -> (Compu
                 return (ComputedExpr::ContextTimestamp, start + 1);
             }
 
+            // Time builtins: now(), slot(), slots_to_secs(n), secs_to_slots(n)
+            if let Some(proc_macro2::TokenTree::Group(group)) = tokens.get(start + 1) {
+                if group.delimiter() == proc_macro2::Delimiter::Parenthesis {
+                    let inner_tokens: Vec<_> = group.stream().into_iter().collect();
+                    match name.as_str() {
+                        "now" if inner_tokens.is_empty() => {
+                            return (ComputedExpr::Now, start + 2);
+                        }
+                        "slot" if inner_tokens.is_empty() => {
+                            return (ComputedExpr::ContextSlot, start + 2);
+                        }
+                        "array_sum" | "array_last" | "array_window_avg" => {
+                            if let Some(aggregate) = parse_array_aggregate(&name, &inner_tokens) {
+                                return (ComputedExpr::ArrayAggregate { aggregate }, start + 2);
+                            }
+                        }
+                        "slots_to_secs" | "secs_to_slots" if !inner_tokens.is_empty() => {
+                            let (arg_expr, _) = parse_expr(&inner_tokens, 0);
+                            let expr = Box::new(arg_expr);
+                            let builtin = if name == "slots_to_secs" {
+                                ComputedExpr::SlotsToSecs { expr }
+                            } else {
+                                ComputedExpr::SecsToSlots { expr }
+                            };
+                            return (builtin, start + 2);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
             // Check for Some(expr)
             if name == "Some" && start + 1 < tokens.len() {
                 if let proc_macro2::TokenTree::Group(group) = &tokens[start + 1] {
@@ -1149,9 +1382,13 @@ fn parse_primary_expr(tokens: &[proc_macro2::TokenTree], start: usize) -> (Compu
             (ComputedExpr::FieldRef { path: name }, start + 1)
         }
 
-        // Literal (number)
+        // Literal (string or number)
         proc_macro2::TokenTree::Literal(lit) => {
             let lit_str = lit.to_string();
+            if let Ok(string) = syn::parse_str::<syn::LitStr>(&lit_str) {
+                let value = serde_json::Value::String(string.value());
+                return (ComputedExpr::Literal { value }, start + 1);
+            }
             // Parse as number
             let value = if lit_str.contains('.') {
                 // Float
@@ -1363,3 +1600,92 @@ pub fn parse_method_args(tokens: &proc_macro2::TokenStream) -> Vec<ComputedExpr>
 
     args
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+
+    fn spec(target_path: &str, expression: proc_macro2::TokenStream) -> ComputedFieldSpec {
+        let section = target_path.split('.').next().unwrap();
+        ComputedFieldSpec {
+            target_path: target_path.to_string(),
+            expression: qualify_field_refs(parse_computed_expression(&expression), section),
+            result_type: "i64".to_string(),
+            time_dependent: false,
+        }
+    }
+
+    #[test]
+    fn parses_time_builtins() {
+        let expr = parse_computed_expression(&quote! { slots_to_secs(expires_slot - slot()) });
+        let ComputedExpr::SlotsToSecs { expr } = expr else {
+            panic!("expected slots_to_secs, got {expr:?}");
+        };
+        assert!(matches!(
+            *expr,
+            ComputedExpr::Binary {
+                op: BinaryOp::Sub,
+                ref right,
+                ..
+            } if matches!(**right, ComputedExpr::ContextSlot)
+        ));
+
+        assert!(matches!(
+            parse_computed_expression(&quote! { now() }),
+            ComputedExpr::Now
+        ));
+        assert!(matches!(
+            parse_computed_expression(&quote! { secs_to_slots(60) }),
+            ComputedExpr::SecsToSlots { .. }
+        ));
+    }
+
+    #[test]
+    fn parses_array_aggregates() {
+        let aggregate =
+            |tokens| match qualify_field_refs(parse_computed_expression(&tokens), "trades") {
+                ComputedExpr::ArrayAggregate { aggregate } => aggregate,
+                other => panic!("expected an array aggregate, got {other:?}"),
+            };
+
+        assert_eq!(
+            aggregate(quote! { array_window_avg(history, size, 20) }),
+            ArrayAggregateSpec {
+                kind: ArrayAggregateKind::WindowAvg { n: 20 },
+                path: "trades.history".to_string(),
+                field: Some("size".to_string()),
+            }
+        );
+        assert_eq!(
+            aggregate(quote! { array_sum(stats.fills) }),
+            ArrayAggregateSpec {
+                kind: ArrayAggregateKind::Sum,
+                path: "stats.fills".to_string(),
+                field: None,
+            }
+        );
+        assert_eq!(
+            aggregate(quote! { array_last(history, fill.price) }).field,
+            Some("fill.price".to_string())
+        );
+        assert!(!matches!(
+            parse_computed_expression(&quote! { array_window_avg(history, size, 0) }),
+            ComputedExpr::ArrayAggregate { .. }
+        ));
+    }
+
+    #[test]
+    fn marks_time_dependence_through_computed_fields() {
+        let mut specs = vec![
+            spec("round.remaining_slots", quote! { secs_to_slots(remaining) }),
+            spec("round.remaining", quote! { expires_at - now() }),
+            spec("round.duration_slots", quote! { end_slot - start_slot }),
+        ];
+
+        mark_time_dependent_fields(&mut specs);
+
+        let flags: Vec<_> = specs.iter().map(|spec| spec.time_dependent).collect();
+        assert_eq!(flags, vec![true, true, false]);
+    }
+}
@@ -29,6 +29,7 @@ use crate::codegen;
 use crate::diagnostic::{internal_codegen_error, unknown_value_message};
 use crate::event_type_helpers::IdlLookup;
 use crate::parse;
+use crate::parse::idl::IdlSpec;
 use crate::utils::{path_to_string, to_pascal_case, to_snake_case};
 use crate::validation::{validate_semantics, ComputedFieldValidation, ValidationInput};
 
@@ -52,8 +53,9 @@ pub struct ProcessEntityResult {
 use super::ast_writer::build_and_write_ast;
 use super::computed::{
     extract_field_references_from_section, extract_section_references, parse_computed_expression,
-    qualify_field_refs,
+    parse_key_normalizer, qualify_field_refs,
 };
+use super::external_sections::{self, ExternalSections};
 use super::handlers::{
     convert_event_to_map_attributes, determine_event_instruction, extract_account_type_from_field,
 };
@@ -102,16 +104,21 @@ pub fn process_entity_struct(
     section_structs: HashMap<String, ItemStruct>,
     skip_game_event: bool,
     stack_name: &str,
+    shared_indexes: &[parse::SharedIndexDecl],
+    external_sections: &ExternalSections,
 ) -> syn::Result<ProcessEntityResult> {
     process_entity_struct_with_idl(
         input,
         entity_name,
         section_structs,
+        external_sections,
         skip_game_event,
         stack_name,
         &[],
         Vec::new(),
         Vec::new(),
+        shared_indexes,
+        &[],
     )
 }
 
@@ -127,11 +134,14 @@ pub fn process_entity_struct_with_idl(
     input: ItemStruct,
     entity_name: String,
     section_structs: HashMap<String, ItemStruct>,
+    external_sections: &ExternalSections,
     skip_game_event: bool,
     _stack_name: &str,
     idls: IdlLookup,
     resolver_hooks: Vec<parse::ResolveKeyAttribute>,
     pda_registrations: Vec<parse::RegisterPdaAttribute>,
+    shared_indexes: &[parse::SharedIndexDecl],
+    previous_idls: &[IdlSpec],
 ) -> syn::Result<ProcessEntityResult> {
     let _name = syn::Ident::new(&entity_name, input.ident.span());
     let state_name = syn::Ident::new(&format!("{}State", entity_name), input.ident.span());
@@ -171,7 +181,9 @@ pub fn process_entity_struct_with_idl(
             let field_type = &field.ty;
 
             // Check if this field is a section type (non-primitive, non-wrapper)
-            if !is_primitive_or_wrapper(field_type) {
+            if !is_primitive_or_wrapper(field_type)
+                || external_sections::parse_external_section(field)?.is_some()
+            {
                 // This field represents a section - add its name
                 all_section_names.insert(field_name);
             }
@@ -188,19 +200,30 @@ pub fn process_entity_struct_with_idl(
             let field_type = &field.ty;
             let rust_type_name = quote::quote!(#field_type).to_string();
 
-            // Check if this field references a section struct
-            if !is_primitive_or_wrapper(field_type) {
+            if let Some(path) = external_sections::parse_external_section(field)? {
+                let mut section =
+                    external_sections::imported_section(external_sections, &path, &field_name)?;
+                if let Some(docs) = parse::parse_item_docs(&field.attrs)? {
+                    section.docs = Some(docs);
+                }
+                section_specs.push(section);
+            } else if !is_primitive_or_wrapper(field_type) {
+                // Check if this field references a section struct
                 if let Type::Path(type_path) = field_type {
                     if let Some(type_ident) = type_path.path.segments.last() {
                         let type_name = type_ident.ident.to_string();
                         // Look up the section struct definition
                         if let Some(section_struct) = section_structs.get(&type_name) {
-                            let section = extract_section_from_struct_with_idl(
+                            let mut section = extract_section_from_struct_with_idl(
                                 &field_name,
                                 section_struct,
                                 None,
                                 idls,
                             )?;
+                            // Docs on the entity's field win over the section struct's
+                            if let Some(docs) = parse::parse_item_docs(&field.attrs)? {
+                                section.docs = Some(docs);
+                            }
                             section_specs.push(section);
                         } else {
                             let field_type_info = sections::analyze_field_type_with_idl(
@@ -238,6 +261,8 @@ pub fn process_entity_struct_with_idl(
             fields: root_fields,
             is_nested_struct: false,
             parent_field: None,
+            docs: None,
+            shared_type: None,
         });
     }
 
@@ -341,6 +366,7 @@ pub fn process_entity_struct_with_idl(
                                 target_field_name: snapshot_attr.target_field_name.clone(),
                                 is_primary_key: false,
                                 is_lookup_index: false,
+                                shared_index: None,
                                 register_from: Vec::new(),
                                 temporal_field: None,
                                 strategy: snapshot_attr.strategy.clone(),
@@ -355,6 +381,7 @@ pub fn process_entity_struct_with_idl(
                                 stop: None,
                                 stop_lookup_by: None,
                                 emit: true,
+                                account_index: None,
                             };
 
                             sources_by_type
@@ -402,6 +429,7 @@ pub fn process_entity_struct_with_idl(
                                 target_field_name: aggr_attr.target_field_name.clone(),
                                 is_primary_key: false,
                                 is_lookup_index: false,
+                                shared_index: None,
                                 register_from: Vec::new(),
                                 temporal_field: None,
                                 strategy: aggr_attr.strategy.clone(),
@@ -416,6 +444,7 @@ pub fn process_entity_struct_with_idl(
                                 stop: None,
                                 stop_lookup_by: None,
                                 emit: true,
+                                account_index: None,
                             };
 
                             let source_type_str = path_to_string(instr_path);
@@ -488,6 +517,7 @@ pub fn process_entity_struct_with_idl(
                             strategy: resolve_attr.strategy,
                             condition: resolve_attr.condition,
                             schedule_at: resolve_attr.schedule_at,
+                            placeholder: resolve_attr.placeholder,
                         });
                     }
                     Some(parse::RecognizedFieldAttribute::Computed(computed_attr)) => {
@@ -512,7 +542,12 @@ pub fn process_entity_struct_with_idl(
                 }
             }
 
-            if !has_attrs && !is_primitive_or_wrapper(field_type) {
+            if !has_attrs && external_sections::parse_external_section(field)?.is_some() {
+                // Imported sections carry no mappings of their own
+                state_fields.push(quote! {
+                    pub #field_name: #field_type
+                });
+            } else if !has_attrs && !is_primitive_or_wrapper(field_type) {
                 if let Type::Path(type_path) = field_type {
                     if let Some(type_ident) = type_path.path.segments.last() {
                         let type_name = type_ident.ident.to_string();
@@ -589,6 +624,10 @@ pub fn process_entity_struct_with_idl(
             view.id = format!("{}/{}", entity_name, view.id);
         }
     }
+    let entity_attr = parse::parse_entity_attribute(&input.attrs)?;
+    if let Some(key_from) = &entity_attr.key_from {
+        apply_key_from(key_from, &mut sources_by_type, &mut primary_keys)?;
+    }
     validate_semantics(ValidationInput {
         entity_name: &entity_name,
         primary_keys: &primary_keys,
@@ -602,9 +641,18 @@ pub fn process_entity_struct_with_idl(
         resolve_specs: &resolve_specs,
         section_specs: &section_specs,
         view_specs: &view_specs,
+        trace_fields: &entity_attr.trace_fields,
+        key_from: entity_attr.key_from.as_ref(),
+        shared_indexes,
         idls,
+        previous_idls,
     })?;
 
+    let key_normalizer = entity_attr
+        .key_normalizer
+        .as_ref()
+        .map(parse_key_normalizer)
+        .transpose()?;
     let views = view_specs.into_iter().map(|spec| spec.view).collect();
 
     let ast = build_and_write_ast(
@@ -622,6 +670,17 @@ pub fn process_entity_struct_with_idl(
         &section_specs,
         idls,
         views,
+        entity_attr
+            .trace_fields
+            .into_iter()
+            .map(|trace_field| trace_field.path)
+            .collect(),
+        entity_attr.feature.clone(),
+        entity_attr.key_from.as_ref(),
+        entity_attr.priority,
+        entity_attr.delete_on,
+        key_normalizer,
+        parse::parse_item_docs(&input.attrs)?,
     )?;
 
     let spec_json = serde_json::to_string(&ast).map_err(|error| {
@@ -694,6 +753,7 @@ pub fn process_entity_struct_with_idl(
                     hyperstack::runtime::hyperstack_interpreter::ast::LookupIndexSpec {
                         field_name: #field_name.to_string(),
                         temporal_field: Some(#tf.to_string()),
+                        shared_index: None,
                     }
                 }
             } else {
@@ -701,6 +761,7 @@ pub fn process_entity_struct_with_idl(
                     hyperstack::runtime::hyperstack_interpreter::ast::LookupIndexSpec {
                         field_name: #field_name.to_string(),
                         temporal_field: None,
+                        shared_index: None,
                     }
                 }
             }
@@ -781,6 +842,7 @@ fn field_emit_override(
 ) -> syn::Result<FieldTypeInfo> {
     let mut found_mapping = false;
     let mut any_emit = false;
+    field_type_info.docs = parse::parse_item_docs(&field.attrs)?;
 
     for attr in &field.attrs {
         match parse::parse_recognized_field_attribute(attr, &field_name)? {
@@ -912,6 +974,63 @@ pub fn process_map_attribute(
     field_mappings.push(map_attr.clone());
 }
 
+/// Make the mapping named by `#[entity(key_from = ...)]` the entity's primary
+/// key.
+fn apply_key_from(
+    key_from: &parse::KeyFromSpec,
+    sources_by_type: &mut BTreeMap<String, Vec<parse::MapAttribute>>,
+    primary_keys: &mut Vec<String>,
+) -> syn::Result<()> {
+    let mut key_targets = Vec::new();
+    for (source_type, mappings) in sources_by_type.iter_mut() {
+        if !key_from.matches_source(source_type) {
+            continue;
+        }
+        for mapping in mappings.iter_mut().filter(|mapping| {
+            !mapping.is_instruction
+                && !mapping.is_event_source
+                && mapping.source_field_name == key_from.field
+        }) {
+            mapping.is_primary_key = true;
+            if !key_targets.contains(&mapping.target_field_name) {
+                key_targets.push(mapping.target_field_name.clone());
+            }
+        }
+    }
+
+    if key_targets.is_empty() {
+        return Err(syn::Error::new(
+            key_from.span,
+            format!(
+                "#[entity(key_from = ...)] names '{path}::{field}', which no field of the entity \
+                 maps. Add `#[map({path}::{field})]` to the field that holds the key.",
+                path = crate::utils::path_to_string(&key_from.account_path),
+                field = key_from.field,
+            ),
+        ));
+    }
+    if let Some(other) = primary_keys
+        .iter()
+        .find(|primary_key| !key_targets.contains(primary_key))
+    {
+        return Err(syn::Error::new(
+            key_from.span,
+            format!(
+                "#[entity(key_from = ...)] already sets the primary key; remove `primary_key` \
+                 from the mapping of '{}'",
+                other
+            ),
+        ));
+    }
+
+    for target in key_targets {
+        if !primary_keys.contains(&target) {
+            primary_keys.push(target);
+        }
+    }
+    Ok(())
+}
+
 // ============================================================================
 // Computed Fields Hook Generation
 // ============================================================================
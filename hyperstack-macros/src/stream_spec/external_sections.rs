@@ -0,0 +1,331 @@
+//! Section structs shared between stacks.
+//!
+//! A struct deriving `SharedSection` in a shared crate exports its field
+//! layout, serialized as an [`EntitySection`], through a hidden
+//! `macro_rules!` at the root of that crate. An entity field annotated with
+//! `#[section(external = "common_sections::TokenMetadata")]` imports it:
+//!
+//! 1. `#[hyperstack]` finds an external section it has no layout for and
+//!    expands to a call of the export macro, handing it the module and the
+//!    layouts imported so far;
+//! 2. the export macro appends its layout and calls back into
+//!    `__import_sections`, which repeats step 1 until every layout is
+//!    imported and then processes the module as `#[hyperstack]` would.
+//!
+//! Imported sections keep the struct's name as their `shared_type`, so SDKs
+//! generate one type for every entity using it.
+
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Fields, Item, ItemMod, ItemStruct, LitStr, Token};
+
+use crate::ast::EntitySection;
+use crate::parse;
+
+use super::sections::extract_section_from_struct;
+
+/// Imported section layouts, keyed by struct name
+pub type ExternalSections = HashMap<String, EntitySection>;
+
+fn export_macro_name(type_name: &str) -> syn::Ident {
+    format_ident!("__hyperstack_section_{}", type_name)
+}
+
+/// The struct named by a field's `#[section(external = "...")]`
+pub fn parse_external_section(field: &syn::Field) -> syn::Result<Option<syn::Path>> {
+    let Some(attr) = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("section"))
+    else {
+        return Ok(None);
+    };
+
+    let mut external = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("external") {
+            let value: LitStr = meta.value()?.parse()?;
+            external = Some(value.parse::<syn::Path>()?);
+            Ok(())
+        } else {
+            Err(meta.error("unknown #[section] argument, expected `external = \"...\"`"))
+        }
+    })?;
+
+    let path = external.ok_or_else(|| {
+        syn::Error::new(
+            attr.span(),
+            "#[section] requires `external = \"crate_name::Type\"`",
+        )
+    })?;
+    let crate_segment = &path.segments[0].ident;
+    if path.segments.len() < 2 || ["crate", "self", "super"].contains(&&*crate_segment.to_string())
+    {
+        return Err(syn::Error::new(
+            path.span(),
+            "external sections are referenced through the crate defining them, e.g. `common_sections::TokenMetadata`",
+        ));
+    }
+    Ok(Some(path))
+}
+
+/// The layout imported for `path`, named after the entity field using it
+pub fn imported_section(
+    external_sections: &ExternalSections,
+    path: &syn::Path,
+    field_name: &str,
+) -> syn::Result<EntitySection> {
+    let type_name = struct_name(path);
+    let mut section = external_sections.get(&type_name).cloned().ok_or_else(|| {
+        syn::Error::new(
+            path.span(),
+            format!("external section `{type_name}` was not imported; #[section(external)] is only supported on entity fields of #[hyperstack] modules"),
+        )
+    })?;
+    section.name = field_name.to_string();
+    Ok(section)
+}
+
+fn struct_name(path: &syn::Path) -> String {
+    path.segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+        .unwrap_or_default()
+}
+
+// ============================================================================
+// Export
+// ============================================================================
+
+/// Expand `#[derive(SharedSection)]` into the macro exporting the struct's
+/// layout
+pub fn expand_shared_section(item: ItemStruct) -> syn::Result<TokenStream> {
+    if !matches!(item.fields, Fields::Named(_)) {
+        return Err(syn::Error::new(
+            item.ident.span(),
+            "SharedSection can only be derived for structs with named fields",
+        ));
+    }
+
+    let type_name = item.ident.to_string();
+    let mut section = extract_section_from_struct(&type_name, &item, None)?;
+    section.shared_type = Some(type_name.clone());
+    let schema = serde_json::to_string(&section).map_err(|error| {
+        syn::Error::new(
+            item.ident.span(),
+            format!("failed to serialize section layout: {error}"),
+        )
+    })?;
+    let schema = LitStr::new(&schema, item.ident.span());
+    let export_macro = export_macro_name(&type_name);
+
+    Ok(quote! {
+        #[doc(hidden)]
+        #[macro_export]
+        macro_rules! #export_macro {
+            ({ $($import:tt)* } [ $($schemas:tt)* ] $($rest:tt)*) => {
+                $($import)*! { [ $($schemas)* #schema, ] $($rest)* }
+            };
+        }
+    })
+}
+
+// ============================================================================
+// Import
+// ============================================================================
+
+/// Input of `__import_sections`: the layouts imported so far, the arguments
+/// of `#[hyperstack]` and the module it was applied to
+pub struct ImportedSections {
+    schemas: Vec<LitStr>,
+    pub sections: ExternalSections,
+    pub attr: TokenStream,
+    pub module: ItemMod,
+}
+
+impl Parse for ImportedSections {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::bracketed!(content in input);
+        let schemas: Vec<LitStr> = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?
+            .into_iter()
+            .collect();
+        let attr;
+        syn::parenthesized!(attr in input);
+        let attr = attr.parse()?;
+        let module = input.parse()?;
+
+        let mut sections = ExternalSections::new();
+        for schema in &schemas {
+            let section: EntitySection =
+                serde_json::from_str(&schema.value()).map_err(|error| {
+                    syn::Error::new(
+                        schema.span(),
+                        format!(
+                            "invalid shared section layout, rebuild the crate defining it: {error}"
+                        ),
+                    )
+                })?;
+            let type_name = section.shared_type.clone().unwrap_or_default();
+            sections.insert(type_name, section);
+        }
+
+        Ok(Self {
+            schemas,
+            sections,
+            attr,
+            module,
+        })
+    }
+}
+
+impl ImportedSections {
+    pub fn new(attr: TokenStream, module: ItemMod) -> Self {
+        Self {
+            schemas: Vec::new(),
+            sections: ExternalSections::new(),
+            attr,
+            module,
+        }
+    }
+
+    /// A call of the export macro of the first external section referenced
+    /// by an entity of the module and not imported yet
+    pub fn import_next(&self) -> syn::Result<Option<TokenStream>> {
+        let Some(path) = self.missing_section()? else {
+            return Ok(None);
+        };
+
+        let mut export = path.clone();
+        let crate_segment = export.segments[0].clone();
+        export.segments = std::iter::once(crate_segment).collect();
+        export
+            .segments
+            .push(export_macro_name(&struct_name(&path)).into());
+
+        let schemas = &self.schemas;
+        let attr = &self.attr;
+        let module = &self.module;
+        Ok(Some(quote! {
+            #export! {
+                { ::hyperstack::macros::__import_sections }
+                [ #(#schemas,)* ]
+                ( #attr )
+                #module
+            }
+        }))
+    }
+
+    fn missing_section(&self) -> syn::Result<Option<syn::Path>> {
+        let Some((_, items)) = &self.module.content else {
+            return Ok(None);
+        };
+
+        for item in items {
+            let Item::Struct(item_struct) = item else {
+                continue;
+            };
+            if !parse::has_entity_attribute(&item_struct.attrs) {
+                continue;
+            }
+            for field in &item_struct.fields {
+                if let Some(path) = parse_external_section(field)? {
+                    if !self.sections.contains_key(&struct_name(&path)) {
+                        return Ok(Some(path));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shared_metadata() -> ItemStruct {
+        syn::parse_quote! {
+            /// Metadata of a token
+            pub struct TokenMetadata {
+                pub name: Option<String>,
+                pub decimals: Option<u8>,
+            }
+        }
+    }
+
+    #[test]
+    fn export_carries_the_struct_layout() {
+        let export = expand_shared_section(shared_metadata())
+            .unwrap()
+            .to_string();
+        assert!(export.contains("macro_rules ! __hyperstack_section_TokenMetadata"));
+
+        let schema: syn::LitStr =
+            syn::parse_str(&export[export.find("\"{").unwrap()..=export.find("}\"").unwrap() + 1])
+                .unwrap();
+        let section: EntitySection = serde_json::from_str(&schema.value()).unwrap();
+        assert_eq!(section.shared_type.as_deref(), Some("TokenMetadata"));
+        let fields: Vec<_> = section.fields.iter().map(|f| &f.field_name).collect();
+        assert_eq!(fields, ["name", "decimals"]);
+        assert!(section.docs.is_some());
+    }
+
+    #[test]
+    fn modules_import_each_missing_section_in_turn() {
+        let module: ItemMod = syn::parse_quote! {
+            mod stream {
+                #[entity(name = "Pool")]
+                struct Pool {
+                    #[section(external = "common_sections::TokenMetadata")]
+                    base: TokenMetadata,
+                    #[section(external = "common_sections::tokens::TokenMetadata")]
+                    quote: TokenMetadata,
+                    #[section(external = "other::Fees")]
+                    fees: Fees,
+                }
+            }
+        };
+        let mut imported = ImportedSections::new(TokenStream::new(), module);
+
+        let call = imported.import_next().unwrap().unwrap().to_string();
+        assert!(call.starts_with("common_sections :: __hyperstack_section_TokenMetadata !"));
+
+        let metadata = extract_section_from_struct("TokenMetadata", &shared_metadata(), None)
+            .map(|mut section| {
+                section.shared_type = Some("TokenMetadata".to_string());
+                section
+            })
+            .unwrap();
+        imported
+            .sections
+            .insert("TokenMetadata".to_string(), metadata);
+        let call = imported.import_next().unwrap().unwrap().to_string();
+        assert!(call.starts_with("other :: __hyperstack_section_Fees !"));
+
+        let section = imported_section(
+            &imported.sections,
+            &syn::parse_quote!(common_sections::TokenMetadata),
+            "base",
+        )
+        .unwrap();
+        assert_eq!(section.name, "base");
+    }
+
+    #[test]
+    fn sections_must_name_their_crate() {
+        let field: syn::Field = syn::parse_quote! {
+            #[section(external = "crate::TokenMetadata")]
+            metadata: TokenMetadata
+        };
+        let error = parse_external_section(&field).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("through the crate defining them"));
+    }
+}
@@ -0,0 +1,278 @@
+//! Cargo feature gating of entities.
+//!
+//! An entity declared with `#[entity(feature = "trading")]` is only compiled
+//! into the stack when the stack crate is built with that feature. A proc
+//! macro can't evaluate `cfg` itself, so the active features are read from the
+//! `--cfg feature="..."` arguments of the rustc invocation expanding the macro.
+//!
+//! Disabled entities are dropped before any code is generated for them: they
+//! get no handlers, no builder registration, no views, and programs only they
+//! consume are left out of the runtime's subscriptions. Everything else in the
+//! module (PDAs, SDK types, parsers, helper functions) is compiled as usual.
+
+use std::collections::BTreeSet;
+
+use syn::ItemStruct;
+
+use crate::parse;
+
+/// The features an entity may be gated behind, and which of them are enabled.
+pub(super) struct EntityFeatures {
+    /// Features the crate is compiled with, or `None` outside of rustc (e.g.
+    /// when expanded by an IDE)
+    active: Option<BTreeSet<String>>,
+    /// Features the crate declares, when cargo passes `--check-cfg`
+    declared: Option<BTreeSet<String>>,
+    /// Whether any entity is gated behind a feature
+    gated: bool,
+    /// Whether any entity was left out
+    excluded: bool,
+}
+
+impl EntityFeatures {
+    /// Read the features of the current rustc invocation.
+    pub(super) fn detect() -> Self {
+        Self::from_args(rustc_args())
+    }
+
+    fn from_args(args: Vec<String>) -> Self {
+        let mut active = BTreeSet::new();
+        let mut declared = None;
+        let mut is_rustc = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || value.map(str::to_string).or_else(|| args.next());
+
+            match flag.as_str() {
+                "--crate-name" => is_rustc = true,
+                "--cfg" => {
+                    if let Some(feature) = value().as_deref().and_then(parse_cfg_feature) {
+                        active.insert(feature);
+                    }
+                }
+                "--check-cfg" => {
+                    if let Some(features) = value().as_deref().and_then(parse_check_cfg_features) {
+                        declared.get_or_insert_with(BTreeSet::new).extend(features);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            active: is_rustc.then_some(active),
+            declared,
+            gated: false,
+            excluded: false,
+        }
+    }
+
+    /// Drop the entities whose feature isn't enabled.
+    ///
+    /// Returns the idents of the dropped structs. Outside of rustc every entity
+    /// is kept.
+    pub(super) fn retain_enabled(
+        &mut self,
+        entity_structs: &mut Vec<ItemStruct>,
+    ) -> syn::Result<Vec<syn::Ident>> {
+        let mut excluded = Vec::new();
+        let mut kept = Vec::with_capacity(entity_structs.len());
+
+        for entity_struct in entity_structs.drain(..) {
+            let Some(feature) = parse::parse_entity_attribute(&entity_struct.attrs)?.feature else {
+                kept.push(entity_struct);
+                continue;
+            };
+            self.gated = true;
+
+            if let Some(declared) = &self.declared {
+                if !declared.contains(&feature) {
+                    let available = declared.iter().cloned().collect::<Vec<_>>().join(", ");
+                    return Err(syn::Error::new(
+                        entity_struct.ident.span(),
+                        format!(
+                            "entity is gated behind feature `{feature}`, which the crate doesn't declare \
+                             (available features: {})",
+                            if available.is_empty() { "none" } else { &available }
+                        ),
+                    ));
+                }
+            }
+
+            match &self.active {
+                Some(active) if !active.contains(&feature) => {
+                    excluded.push(entity_struct.ident.clone())
+                }
+                _ => kept.push(entity_struct),
+            }
+        }
+
+        *entity_structs = kept;
+        self.excluded = !excluded.is_empty();
+        Ok(excluded)
+    }
+
+    /// Whether any entity was left out of this build
+    pub(super) fn excluded_any(&self) -> bool {
+        self.excluded
+    }
+
+    /// The active features to record in the stack spec.
+    ///
+    /// Only stacks with gated entities record them, since they don't affect
+    /// any other stack.
+    pub(super) fn recorded(&self) -> Vec<String> {
+        match &self.active {
+            Some(active) if self.gated => active.iter().cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether the stack file reflects the real build.
+    ///
+    /// Without rustc's arguments the gated entities can't be resolved, so the
+    /// stack file from the last real build is left alone.
+    pub(super) fn can_write_stack_file(&self) -> bool {
+        !self.gated || self.active.is_some()
+    }
+}
+
+/// The arguments of the running compiler, with `@file` arguments expanded
+fn rustc_args() -> Vec<String> {
+    let mut args = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.strip_prefix('@') {
+            Some(path) => {
+                if let Ok(contents) = std::fs::read_to_string(path) {
+                    args.extend(contents.lines().map(str::to_string));
+                }
+            }
+            None => args.push(arg),
+        }
+    }
+    args
+}
+
+/// `feature="trading"` -> `trading`
+fn parse_cfg_feature(cfg: &str) -> Option<String> {
+    let value = cfg
+        .strip_prefix("feature")?
+        .trim_start()
+        .strip_prefix('=')?;
+    Some(value.trim().trim_matches('"').to_string())
+}
+
+/// `cfg(feature, values("default", "trading"))` -> `default`, `trading`
+fn parse_check_cfg_features(check_cfg: &str) -> Option<Vec<String>> {
+    let inner = check_cfg.strip_prefix("cfg(")?.strip_suffix(')')?;
+    let values = inner
+        .strip_prefix("feature")?
+        .trim_start()
+        .strip_prefix(',')?
+        .trim_start()
+        .strip_prefix("values(")?
+        .strip_suffix(')')?;
+
+    Some(
+        values
+            .split(',')
+            .map(|value| value.trim().trim_matches('"').to_string())
+            .filter(|value| !value.is_empty())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn entity(attr: proc_macro2::TokenStream) -> ItemStruct {
+        syn::parse_quote! {
+            #[entity(#attr)]
+            struct Entity {}
+        }
+    }
+
+    #[test]
+    fn reads_features_from_rustc_args() {
+        let features = EntityFeatures::from_args(args(&[
+            "--crate-name",
+            "stack",
+            "--cfg",
+            "feature=\"default\"",
+            "--cfg=feature=\"trading\"",
+            "--cfg",
+            "docsrs",
+            "--check-cfg",
+            "cfg(docsrs,test)",
+            "--check-cfg",
+            "cfg(feature, values(\"default\", \"trading\", \"lending\"))",
+        ]));
+
+        assert_eq!(
+            features.active,
+            Some(["default", "trading"].map(String::from).into())
+        );
+        assert_eq!(
+            features.declared,
+            Some(["default", "lending", "trading"].map(String::from).into())
+        );
+    }
+
+    #[test]
+    fn keeps_every_entity_outside_of_rustc() {
+        let mut features = EntityFeatures::from_args(args(&["--cfg", "feature=\"trading\""]));
+        let mut structs = vec![entity(quote::quote!(feature = "lending"))];
+
+        assert!(features.retain_enabled(&mut structs).unwrap().is_empty());
+        assert_eq!(structs.len(), 1);
+        assert!(features.recorded().is_empty());
+        assert!(!features.can_write_stack_file());
+    }
+
+    #[test]
+    fn drops_entities_whose_feature_is_disabled() {
+        let mut features = EntityFeatures::from_args(args(&[
+            "--crate-name",
+            "stack",
+            "--cfg",
+            "feature=\"trading\"",
+        ]));
+        let mut structs = vec![
+            entity(quote::quote!(name = "Always")),
+            entity(quote::quote!(feature = "trading")),
+            entity(quote::quote!(feature = "lending")),
+        ];
+
+        let excluded = features.retain_enabled(&mut structs).unwrap();
+
+        assert_eq!(structs.len(), 2);
+        assert_eq!(excluded.len(), 1);
+        assert!(features.excluded_any());
+        assert_eq!(features.recorded(), vec!["trading".to_string()]);
+    }
+
+    #[test]
+    fn rejects_undeclared_features() {
+        let mut features = EntityFeatures::from_args(args(&[
+            "--crate-name",
+            "stack",
+            "--check-cfg",
+            "cfg(feature, values(\"trading\"))",
+        ]));
+        let mut structs = vec![entity(quote::quote!(feature = "tradng"))];
+
+        let error = features.retain_enabled(&mut structs).unwrap_err();
+        assert!(error.to_string().contains("feature `tradng`"));
+        assert!(error.to_string().contains("available features: trading"));
+    }
+}
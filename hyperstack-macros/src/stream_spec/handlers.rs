@@ -276,6 +276,7 @@ pub fn convert_event_to_map_attributes(
             target_field_name: target_field.to_string(),
             is_primary_key: false,
             is_lookup_index: false,
+            shared_index: None,
             register_from: Vec::new(),
             temporal_field: None,
             strategy: event_attr.strategy.clone(),
@@ -290,6 +291,7 @@ pub fn convert_event_to_map_attributes(
             stop: None,
             stop_lookup_by: None,
             emit: true,
+            account_index: None,
         });
         return map_attrs;
     }
@@ -313,6 +315,7 @@ pub fn convert_event_to_map_attributes(
             target_field_name: format!("{}.{}", target_field, field_name),
             is_primary_key: false,
             is_lookup_index: false,
+            shared_index: None,
             register_from: Vec::new(),
             temporal_field: None,
             strategy: event_attr.strategy.clone(),
@@ -327,6 +330,7 @@ pub fn convert_event_to_map_attributes(
             stop: None,
             stop_lookup_by: None,
             emit: true,
+            account_index: None,
         });
     }
 
@@ -348,6 +352,7 @@ pub fn convert_event_to_map_attributes(
             target_field_name: format!("{}.{}", target_field, field_name),
             is_primary_key: false,
             is_lookup_index: false,
+            shared_index: None,
             register_from: Vec::new(),
             temporal_field: None,
             strategy: event_attr.strategy.clone(),
@@ -362,6 +367,7 @@ pub fn convert_event_to_map_attributes(
             stop: None,
             stop_lookup_by: None,
             emit: true,
+            account_index: None,
         });
     }
 
@@ -570,7 +576,7 @@ pub fn generate_auto_resolver_functions(hooks: &[ResolverHook]) -> proc_macro2::
                     }
                 });
             }
-            ResolverStrategy::DirectField { .. } => {}
+            ResolverStrategy::DirectField { .. } | ResolverStrategy::KeyFrom { .. } => {}
         }
     }
 
@@ -2,7 +2,8 @@
 //!
 //! This module handles processing of `#[hyperstack(idl = "...")]` modules,
 //! which generate SDK types, parsers, and entity processing from Anchor IDL files.
-//! Supports multiple IDLs for multi-program stacks.
+//! Supports multiple IDLs for multi-program stacks, plus accounts of programs
+//! without an IDL watched through `extra_accounts`.
 
 use std::collections::{BTreeMap, HashMap, HashSet};
 
@@ -13,17 +14,21 @@ use syn::{Item, ItemMod};
 use crate::ast::writer::{extract_instructions_from_idl, extract_pdas_from_idl};
 use crate::ast::SerializableStackSpec;
 use crate::codegen::generate_multi_entity_builder;
-use crate::diagnostic::{idl_error_to_syn, internal_codegen_error, parse_generated_items};
+use crate::diagnostic::{internal_codegen_error, parse_generated_items};
 use crate::idl_codegen;
 use crate::idl_parser_gen;
 use crate::idl_vixen_gen;
 use crate::parse;
+use crate::parse::extra_accounts::ExtraAccounts;
 use crate::parse::idl as idl_parser;
+use crate::parse::idl_file::load_idl_file;
 use crate::parse::pdas::PdasBlock;
 use crate::utils::{to_pascal_case, to_snake_case};
 use crate::validation::validate_pda_blocks;
 
 use super::entity::process_entity_struct_with_idl;
+use super::external_sections::ExternalSections;
+use super::features::EntityFeatures;
 use super::handlers::{
     generate_auto_resolver_functions, generate_pda_registration_functions,
     generate_resolver_functions,
@@ -35,31 +40,23 @@ struct IdlInfo {
     program_name: String,
     sdk_module_name: String,
     parser_module_name: String,
+    /// Filters of a program watched through `extra_accounts`, whose accounts
+    /// are decoded by a builtin parser rather than one generated from the IDL
+    account_filters: Option<Vec<String>>,
 }
 
 pub fn process_idl_spec(
     mut module: ItemMod,
-    idl_paths: &[String],
+    idl_paths: &[syn::LitStr],
+    previous_idl_paths: &[syn::LitStr],
+    extra_accounts: &[ExtraAccounts],
+    mut features: EntityFeatures,
+    external_sections: &ExternalSections,
 ) -> syn::Result<proc_macro2::TokenStream> {
-    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
-
     let mut idl_infos: Vec<IdlInfo> = Vec::new();
 
     for idl_path in idl_paths {
-        let full_path = std::path::Path::new(&manifest_dir).join(idl_path);
-
-        let idl = match idl_parser::parse_idl_file(&full_path) {
-            Ok(idl) => idl,
-            Err(e) => {
-                return Err(idl_error_to_syn(
-                    module.ident.span(),
-                    hyperstack_idl::error::IdlSearchError::ParseError {
-                        path: idl_path.clone(),
-                        source: e,
-                    },
-                ));
-            }
-        };
+        let idl = load_idl_file(idl_path)?;
 
         let program_id = idl
             .address
@@ -83,9 +80,43 @@ pub fn process_idl_spec(
             program_name,
             sdk_module_name,
             parser_module_name,
+            account_filters: None,
         });
     }
 
+    for extra in extra_accounts {
+        let idl = extra.idl();
+        let program_name = idl.get_name().to_string();
+
+        idl_infos.push(IdlInfo {
+            idl,
+            program_id: extra.program_id.clone(),
+            sdk_module_name: format!("{}_sdk", program_name),
+            parser_module_name: format!("{}_parsers", program_name),
+            program_name,
+            account_filters: Some(extra.filters.clone()),
+        });
+    }
+
+    let mut previous_idls = Vec::new();
+    for idl_path in previous_idl_paths {
+        let idl = load_idl_file(idl_path)?;
+        if !idl_infos
+            .iter()
+            .any(|info| info.program_name == idl.get_name())
+        {
+            return Err(syn::Error::new(
+                idl_path.span(),
+                format!(
+                    "previous_idl '{}' is for program '{}', which has no IDL in this stack",
+                    idl_path.value(),
+                    idl.get_name()
+                ),
+            ));
+        }
+        previous_idls.push(idl);
+    }
+
     let primary = &idl_infos[0];
 
     let mut all_sdk_tokens: Vec<proc_macro2::TokenStream> = Vec::new();
@@ -95,12 +126,19 @@ pub fn process_idl_spec(
         let sdk_types = idl_codegen::generate_sdk_types(&info.idl, &info.sdk_module_name);
         all_sdk_tokens.push(sdk_types);
 
-        let parsers = idl_parser_gen::generate_named_parsers(
-            &info.idl,
-            &info.program_id,
-            &info.sdk_module_name,
-            &info.parser_module_name,
-        );
+        let parsers = match &info.account_filters {
+            Some(filters) => idl_parser_gen::generate_token_account_parsers(
+                &info.idl,
+                filters,
+                &info.parser_module_name,
+            ),
+            None => idl_parser_gen::generate_named_parsers(
+                &info.idl,
+                &info.program_id,
+                &info.sdk_module_name,
+                &info.parser_module_name,
+            ),
+        };
         all_parser_tokens.push(parsers);
     }
 
@@ -148,6 +186,14 @@ pub fn process_idl_spec(
         }
     }
 
+    let has_entities = !entity_structs.is_empty();
+    let excluded_entities = features.retain_enabled(&mut entity_structs)?;
+    if let Some((_, items)) = &mut module.content {
+        items.retain(
+            |item| !matches!(item, Item::Struct(s) if excluded_entities.contains(&s.ident)),
+        );
+    }
+
     let mut all_resolver_hooks = Vec::new();
     for impl_block in &impl_blocks {
         let hooks = parse::extract_resolver_hooks(impl_block)?;
@@ -252,7 +298,7 @@ pub fn process_idl_spec(
         });
     }
 
-    if !entity_structs.is_empty() {
+    if has_entities {
         let mut all_outputs = Vec::new();
         let mut entity_names = Vec::new();
 
@@ -260,6 +306,10 @@ pub fn process_idl_spec(
             .iter()
             .map(|info| (info.sdk_module_name.clone(), &info.idl))
             .collect();
+        let shared_indexes = match &module.content {
+            Some((_, items)) => parse::parse_shared_index_decls(items)?,
+            None => Vec::new(),
+        };
 
         for entity_struct in &entity_structs {
             let entity_name = parse::parse_entity_name(&entity_struct.attrs)
@@ -270,6 +320,7 @@ pub fn process_idl_spec(
                 entity_struct.clone(),
                 entity_name.clone(),
                 section_structs.clone(),
+                external_sections,
                 has_game_event,
                 &stack_name,
                 &idl_lookup,
@@ -278,6 +329,8 @@ pub fn process_idl_spec(
                     .get(&entity_name)
                     .cloned()
                     .unwrap_or_default(),
+                &shared_indexes,
+                &previous_idls,
             )?;
 
             for hook in &result.auto_resolver_hooks {
@@ -318,7 +371,7 @@ pub fn process_idl_spec(
                     });
                     !has_declarative_attr
                 } else {
-                    true
+                    !parse::is_shared_index_macro(item)
                 }
             });
 
@@ -469,6 +522,7 @@ pub fn process_idl_spec(
                     .collect(),
                 pdas: all_pdas,
                 instructions: all_instructions,
+                features: features.recorded(),
                 content_hash: None,
             }
             .try_with_content_hash()
@@ -486,12 +540,14 @@ pub fn process_idl_spec(
                 )
             })?;
 
-            crate::ast::writer::write_stack_to_file(&stack_spec, &stack_name).map_err(|e| {
-                syn::Error::new(
-                    module.ident.span(),
-                    format!("Failed to write stack AST: {e}"),
-                )
-            })?;
+            if features.can_write_stack_file() {
+                crate::ast::writer::write_stack_to_file(&stack_spec, &stack_name).map_err(|e| {
+                    syn::Error::new(
+                        module.ident.span(),
+                        format!("Failed to write stack AST: {e}"),
+                    )
+                })?;
+            }
 
             let multi_entity_builder = generate_multi_entity_builder(
                 &entity_names,
@@ -520,14 +576,30 @@ pub fn process_idl_spec(
                 items.push(gen_item);
             }
 
+            // Programs only consumed by excluded entities aren't subscribed to
+            let subscribed_programs = if features.excluded_any() {
+                let consumed = consumed_programs(&stack_spec.entities);
+                let subscribed: Vec<&IdlInfo> = idl_infos
+                    .iter()
+                    .filter(|info| consumed.contains(info.program_name.as_str()))
+                    .collect();
+                if subscribed.is_empty() {
+                    idl_infos.iter().take(1).collect()
+                } else {
+                    subscribed
+                }
+            } else {
+                idl_infos.iter().collect()
+            };
             let spec_function = idl_vixen_gen::generate_multi_idl_spec_function(
-                &idl_infos
+                &subscribed_programs
                     .iter()
                     .map(|info| {
                         (
                             &info.idl,
                             info.program_id.as_str(),
                             info.parser_module_name.as_str(),
+                            info.account_filters.is_some(),
                         )
                     })
                     .collect::<Vec<_>>(),
@@ -563,6 +635,37 @@ pub fn process_idl_spec(
     })
 }
 
+/// Names of the programs whose accounts or instructions the entities consume
+fn consumed_programs(entities: &[crate::ast::SerializableStreamSpec]) -> HashSet<&str> {
+    let mut programs = HashSet::new();
+    for entity in entities {
+        let type_names = entity
+            .handlers
+            .iter()
+            .map(|handler| match &handler.source {
+                crate::ast::SourceSpec::Source { type_name, .. } => type_name.as_str(),
+            })
+            .chain(
+                entity
+                    .instruction_hooks
+                    .iter()
+                    .map(|hook| hook.instruction_type.as_str()),
+            )
+            .chain(
+                entity
+                    .resolver_hooks
+                    .iter()
+                    .map(|hook| hook.account_type.as_str()),
+            );
+        programs.extend(type_names.filter_map(|type_name| {
+            type_name
+                .split_once("::")
+                .map(|(program_name, _)| program_name)
+        }));
+    }
+    programs
+}
+
 fn collect_register_from_specs(
     entity_structs: &[syn::ItemStruct],
     section_structs: &HashMap<String, syn::ItemStruct>,
@@ -11,6 +11,8 @@
 //! - `sections` - Nested struct and section processing (464 LOC)
 //! - `computed` - Computed field expression parsing (461 LOC)
 //! - `ast_writer` - AST JSON file generation at compile time (620 LOC)
+//! - `features` - Cargo feature gating of entities (~150 LOC)
+//! - `external_sections` - Section structs shared between stacks (~300 LOC)
 //! - `idl_spec` - IDL-based stream processing (~300 LOC)
 //! - `proto_struct` - Proto-based struct processing (~380 LOC)
 //!
@@ -39,6 +41,8 @@
 mod ast_writer;
 pub(crate) mod computed;
 mod entity;
+mod external_sections;
+mod features;
 mod handlers;
 mod idl_spec;
 mod module;
@@ -46,7 +50,10 @@ mod proto_struct;
 mod sections;
 
 // Re-export module processing functions (used by lib.rs)
-pub use module::process_module;
+pub use module::{process_imported_module, process_module};
+
+// Re-export shared section export and import (used by lib.rs)
+pub use external_sections::{expand_shared_section, ImportedSections};
 
 // Re-export proto struct processing (used by lib.rs)
 pub use proto_struct::process_struct_with_context;
@@ -18,12 +18,16 @@ use crate::proto_codegen;
 use crate::utils::to_pascal_case;
 
 use super::entity::process_entity_struct;
+use super::external_sections::{ExternalSections, ImportedSections};
+use super::features::EntityFeatures;
 use super::proto_struct::process_struct_with_context;
 
 type ParsedProtoAttrs = (
     Vec<(String, proto_parser::ProtoAnalysis)>,
     bool,
-    Vec<String>,
+    Vec<syn::LitStr>,
+    Vec<syn::LitStr>,
+    Vec<syn::LitStr>,
 );
 
 // ============================================================================
@@ -36,19 +40,65 @@ type ParsedProtoAttrs = (
 /// - Proto-based streams with `proto = ["file.proto"]` attribute
 /// - IDL-based streams with `idl = "file.json"` attribute
 /// - Multi-entity modules with multiple `#[entity]` structs
-pub fn process_module(
+pub fn process_module(module: ItemMod, attr: TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    process_imported_module(ImportedSections::new(attr.into(), module))
+}
+
+/// Process a module once the layouts of all its external sections are
+/// imported, or import the next one.
+pub fn process_imported_module(
+    imported: ImportedSections,
+) -> syn::Result<proc_macro2::TokenStream> {
+    if let Some(import) = imported.import_next()? {
+        return Ok(import);
+    }
+    let ImportedSections {
+        sections,
+        attr,
+        module,
+        ..
+    } = imported;
+    process_module_with_sections(module, attr.into(), &sections)
+}
+
+fn process_module_with_sections(
     mut module: ItemMod,
     attr: TokenStream,
+    external_sections: &ExternalSections,
 ) -> syn::Result<proc_macro2::TokenStream> {
     let mut section_structs = HashMap::new();
     let mut main_struct = None;
     let mut entity_structs = Vec::new();
     let mut has_game_event = false;
 
-    let (proto_analyses, skip_decoders, idl_files) = parse_proto_files_from_attr(attr.clone())?;
+    let (proto_analyses, skip_decoders, idl_files, previous_idl_files, extra_accounts) =
+        parse_proto_files_from_attr(attr.clone())?;
+    let mut features = EntityFeatures::detect();
 
     if !idl_files.is_empty() {
-        return super::idl_spec::process_idl_spec(module, &idl_files);
+        let extra_accounts = parse::extra_accounts::parse_extra_accounts(&extra_accounts)?;
+        return super::idl_spec::process_idl_spec(
+            module,
+            &idl_files,
+            &previous_idl_files,
+            &extra_accounts,
+            features,
+            external_sections,
+        );
+    }
+
+    if let Some(entry) = extra_accounts.first() {
+        return Err(syn::Error::new(
+            entry.span(),
+            "extra_accounts is only supported on IDL-based modules (`idl = ...`)",
+        ));
+    }
+
+    if !previous_idl_files.is_empty() {
+        return Err(syn::Error::new(
+            module.ident.span(),
+            "previous_idl is only supported on IDL-based modules (`idl = ...`)",
+        ));
     }
 
     if let Some((_, items)) = &module.content {
@@ -88,9 +138,14 @@ pub fn process_module(
     }
 
     if !entity_structs.is_empty() {
+        features.retain_enabled(&mut entity_structs)?;
         let stack_name = to_pascal_case(&module.ident.to_string());
         let mut all_outputs = Vec::new();
         let mut entity_names = Vec::new();
+        let shared_indexes = match &module.content {
+            Some((_, items)) => parse::parse_shared_index_decls(items)?,
+            None => Vec::new(),
+        };
 
         for entity_struct in &entity_structs {
             let entity_name = parse::parse_entity_name(&entity_struct.attrs)
@@ -102,6 +157,8 @@ pub fn process_module(
                 section_structs.clone(),
                 has_game_event,
                 &stack_name,
+                &shared_indexes,
+                external_sections,
             )?;
             all_outputs.push(output);
         }
@@ -111,7 +168,7 @@ pub fn process_module(
                 if let Item::Struct(s) = item {
                     !parse::has_entity_attribute(&s.attrs)
                 } else {
-                    true
+                    !parse::is_shared_index_macro(item)
                 }
             });
 
@@ -152,6 +209,7 @@ pub fn process_module(
                 entities: entity_asts,
                 pdas: BTreeMap::new(),
                 instructions: vec![],
+                features: features.recorded(),
                 content_hash: None,
             }
             .try_with_content_hash()
@@ -169,8 +227,12 @@ pub fn process_module(
                 )
             })?;
 
-            if let Err(error) = crate::ast::writer::write_stack_to_file(&stack_spec, &stack_name) {
-                eprintln!("Warning: Failed to write stack AST: {error}");
+            if features.can_write_stack_file() {
+                if let Err(error) =
+                    crate::ast::writer::write_stack_to_file(&stack_spec, &stack_name)
+                {
+                    eprintln!("Warning: Failed to write stack AST: {error}");
+                }
             }
 
             let multi_entity_builder = generate_multi_entity_builder(
@@ -230,9 +292,17 @@ fn parse_proto_files_from_parsed_attr(
     hyperstack_attr: parse::StreamSpecAttribute,
 ) -> syn::Result<ParsedProtoAttrs> {
     let idl_files = hyperstack_attr.idl_files.clone();
+    let previous_idl_files = hyperstack_attr.previous_idl_files.clone();
+    let extra_accounts = hyperstack_attr.extra_accounts.clone();
 
     if hyperstack_attr.proto_files.is_empty() {
-        return Ok((Vec::new(), hyperstack_attr.skip_decoders, idl_files));
+        return Ok((
+            Vec::new(),
+            hyperstack_attr.skip_decoders,
+            idl_files,
+            previous_idl_files,
+            extra_accounts,
+        ));
     }
 
     let mut analyses = Vec::new();
@@ -255,7 +325,13 @@ fn parse_proto_files_from_parsed_attr(
         }
     }
 
-    Ok((analyses, hyperstack_attr.skip_decoders, idl_files))
+    Ok((
+        analyses,
+        hyperstack_attr.skip_decoders,
+        idl_files,
+        previous_idl_files,
+        extra_accounts,
+    ))
 }
 
 #[cfg(test)]
@@ -264,11 +340,13 @@ mod tests {
 
     #[test]
     fn missing_proto_file_is_non_fatal() {
-        let (analyses, skip_decoders, idl_files) =
+        let (analyses, skip_decoders, idl_files, _, _) =
             parse_proto_files_from_parsed_attr(parse::StreamSpecAttribute {
                 proto_files: vec!["missing.proto".to_string()],
                 idl_files: Vec::new(),
+                previous_idl_files: Vec::new(),
                 skip_decoders: false,
+                extra_accounts: Vec::new(),
             })
             .expect("missing proto files should remain non-fatal");
 
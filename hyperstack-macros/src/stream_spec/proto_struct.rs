@@ -161,6 +161,7 @@ pub fn process_struct_with_context(
                             strategy: resolve_attr.strategy,
                             condition: resolve_attr.condition,
                             schedule_at: resolve_attr.schedule_at,
+                            placeholder: resolve_attr.placeholder,
                         });
                     }
                     Some(_) | None => {}
@@ -243,6 +244,7 @@ pub fn process_struct_with_context(
             events_by_instruction: &events_by_instruction,
             derive_from_mappings: &derive_from_mappings,
             resolver_hooks: &[],
+            key_from: None,
         },
         &mut key_resolution_errors,
     );
@@ -316,6 +318,16 @@ pub fn process_struct_with_context(
                         hyperstack::runtime::hyperstack_interpreter::ast::PopulationStrategy::#strategy_ident,
                     )
                 }
+            } else if let Some(field) = mapping.transaction_field() {
+                quote! {
+                    hyperstack::runtime::hyperstack_interpreter::ast::TypedFieldMapping::new(
+                        #target_field.to_string(),
+                        hyperstack::runtime::hyperstack_interpreter::ast::MappingSource::FromTransaction {
+                            field: #field.to_string(),
+                        },
+                        hyperstack::runtime::hyperstack_interpreter::ast::PopulationStrategy::#strategy_ident,
+                    )
+                }
             } else {
                 // Normal field mapping
                 quote! {
@@ -331,10 +343,26 @@ pub fn process_struct_with_context(
                 }
             };
 
-            let mapping_expr = if let Some(ref transform_str) = mapping.transform {
-                let transform_ident = format_ident!("{}", transform_str);
+            let transform = match mapping.transform.as_deref() {
+                Some(transform_str) => Some(
+                    crate::ast::writer::parse_transformation(transform_str).ok_or_else(|| {
+                        syn::Error::new(
+                            mapping.attr_span,
+                            format!(
+                                "unknown transform `{}` (expected hex_encode, hex_decode, \
+                                 base58_encode, base58_decode, base64_encode, base64_decode, \
+                                 to_string or to_number)",
+                                transform_str
+                            ),
+                        )
+                    })?,
+                ),
+                None => None,
+            };
+            let mapping_expr = if let Some(ref transform) = transform {
+                let transform_code = crate::codegen::build_transformation_code(transform);
                 quote! {
-                    #mapping_expr.with_transform(hyperstack::runtime::hyperstack_interpreter::ast::Transformation::#transform_ident)
+                    #mapping_expr.with_transform(#transform_code)
                 }
             } else {
                 mapping_expr
@@ -449,6 +477,7 @@ pub fn process_struct_with_context(
                     hyperstack::runtime::hyperstack_interpreter::ast::LookupIndexSpec {
                         field_name: #field_name.to_string(),
                         temporal_field: Some(#tf.to_string()),
+                        shared_index: None,
                     }
                 }
             } else {
@@ -456,6 +485,7 @@ pub fn process_struct_with_context(
                     hyperstack::runtime::hyperstack_interpreter::ast::LookupIndexSpec {
                         field_name: #field_name.to_string(),
                         temporal_field: None,
+                        shared_index: None,
                     }
                 }
             }
@@ -564,11 +594,16 @@ pub fn process_struct_with_context(
                         Some(value) => quote! { Some(#value.to_string()) },
                         None => quote! { None },
                     };
+                    let placeholder_code = match spec.placeholder.as_ref() {
+                        Some(value) => quote! { Some(#value.to_string()) },
+                        None => quote! { None },
+                    };
                     Some(quote! {
                         hyperstack::runtime::hyperstack_interpreter::ast::ResolverExtractSpec {
                             target_path: #target.to_string(),
                             source_path: #source_code,
                             transform: None,
+                            placeholder: #placeholder_code,
                         }
                     })
                 })
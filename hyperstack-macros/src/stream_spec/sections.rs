@@ -11,10 +11,12 @@ use quote::quote;
 use syn::spanned::Spanned;
 use syn::{Fields, ItemStruct, Type};
 
-use crate::ast::{BaseType, EntitySection, FieldTypeInfo, ResolvedField, ResolvedStructType};
+use crate::ast::{
+    BaseType, EntitySection, FieldTypeInfo, ResolvedField, ResolvedStructType, ValueEncoding,
+};
 use crate::event_type_helpers::{find_idl_for_type, IdlLookup};
 use crate::parse;
-use crate::parse::idl::{IdlSpec, IdlType, IdlTypeDefKind};
+use crate::parse::idl::{integer_encoding, value_encoding, IdlSpec, IdlType, IdlTypeDefKind};
 use crate::utils::path_to_string;
 
 use super::handlers::{determine_event_instruction, extract_account_type_from_field};
@@ -25,7 +27,6 @@ use super::resolve_snapshot_source;
 // ============================================================================
 
 /// Extract section information from a struct definition.
-#[allow(dead_code)]
 pub fn extract_section_from_struct(
     section_name: &str,
     item_struct: &ItemStruct,
@@ -51,6 +52,7 @@ pub fn extract_section_from_struct_with_idl(
                 let mut field_type_info =
                     analyze_field_type_with_idl(&field_name, &rust_type_name, idls);
                 field_type_info.emit = field_emit_from_attrs(field, &field_name)?;
+                field_type_info.docs = parse::parse_item_docs(&field.attrs)?;
                 fields.push(field_type_info);
             }
         }
@@ -61,6 +63,8 @@ pub fn extract_section_from_struct_with_idl(
         fields,
         is_nested_struct: parent_field.is_some(),
         parent_field,
+        docs: parse::parse_item_docs(&item_struct.attrs)?,
+        shared_type: None,
     })
 }
 
@@ -105,7 +109,7 @@ pub fn analyze_field_type_with_idl(
     if let Some(inner) = extract_generic_inner_type(type_str, "Option") {
         let inner_info = analyze_inner_type(&inner);
         let resolved_type = if inner_info.0 == BaseType::Object {
-            resolve_complex_type(&inner, idls)
+            resolve_complex_type(&inner, idls, true)
         } else {
             None
         };
@@ -119,14 +123,16 @@ pub fn analyze_field_type_with_idl(
             inner_type: Some(inner.clone()),
             source_path: None,
             resolved_type,
+            encoding: scalar_encoding(&inner),
             emit: true,
+            docs: None,
         };
     }
 
     if let Some(inner) = extract_generic_inner_type(type_str, "Vec") {
         let inner_base_type = analyze_simple_type(&inner);
         let resolved_type = if inner_base_type == BaseType::Object {
-            resolve_complex_type(&inner, idls)
+            resolve_complex_type(&inner, idls, true)
         } else {
             None
         };
@@ -140,13 +146,15 @@ pub fn analyze_field_type_with_idl(
             inner_type: Some(inner.clone()),
             source_path: None,
             resolved_type,
+            encoding: scalar_encoding(&inner),
             emit: true,
+            docs: None,
         };
     }
 
     let base_type = analyze_simple_type(type_str);
     let resolved_type = if base_type == BaseType::Object {
-        resolve_complex_type(type_str, idls)
+        resolve_complex_type(type_str, idls, true)
     } else {
         None
     };
@@ -160,7 +168,9 @@ pub fn analyze_field_type_with_idl(
         inner_type: None,
         source_path: None,
         resolved_type,
+        encoding: scalar_encoding(type_str),
         emit: true,
+        docs: None,
     }
 }
 
@@ -176,9 +186,8 @@ fn analyze_inner_type(type_str: &str) -> (BaseType, bool) {
 /// Analyze a simple (non-generic) type string.
 fn analyze_simple_type(type_str: &str) -> BaseType {
     match type_str {
-        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" => {
-            BaseType::Integer
-        }
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128"
+        | "usize" => BaseType::Integer,
         "f32" | "f64" => BaseType::Float,
         "bool" => BaseType::Boolean,
         "String" | "&str" | "str" => BaseType::String,
@@ -196,6 +205,11 @@ fn analyze_simple_type(type_str: &str) -> BaseType {
     }
 }
 
+/// `u128`/`i128` values don't fit a JSON number and are stored as decimal strings.
+fn scalar_encoding(type_str: &str) -> Option<ValueEncoding> {
+    matches!(type_str, "u128" | "i128").then_some(ValueEncoding::DecimalString)
+}
+
 /// Extract inner type from generic like "Option<T>" -> "T".
 fn extract_generic_inner_type(type_str: &str, generic_name: &str) -> Option<String> {
     let pattern = format!("{} <", generic_name);
@@ -450,6 +464,7 @@ pub fn process_nested_struct(
                                 target_field_name: snapshot_attr.target_field_name.clone(),
                                 is_primary_key: false,
                                 is_lookup_index: false,
+                                shared_index: None,
                                 register_from: Vec::new(),
                                 temporal_field: None,
                                 strategy: snapshot_attr.strategy.clone(),
@@ -464,6 +479,7 @@ pub fn process_nested_struct(
                                 stop: None,
                                 stop_lookup_by: None,
                                 emit: true,
+                                account_index: None,
                             };
 
                             sources_by_type
@@ -506,6 +522,7 @@ pub fn process_nested_struct(
                                 target_field_name: aggr_attr.target_field_name.clone(),
                                 is_primary_key: false,
                                 is_lookup_index: false,
+                                shared_index: None,
                                 register_from: Vec::new(),
                                 temporal_field: None,
                                 strategy: aggr_attr.strategy.clone(),
@@ -520,6 +537,7 @@ pub fn process_nested_struct(
                                 stop: None,
                                 stop_lookup_by: None,
                                 emit: true,
+                                account_index: None,
                             };
 
                             let source_type_str = path_to_string(instr_path);
@@ -623,6 +641,7 @@ pub fn process_nested_struct(
                             strategy: resolve_attr.strategy,
                             condition: resolve_attr.condition,
                             schedule_at: resolve_attr.schedule_at,
+                            placeholder: resolve_attr.placeholder,
                         });
                     }
                     None => {}
@@ -642,7 +661,14 @@ pub fn process_nested_struct(
 // IDL Type Resolution
 // ============================================================================
 
-fn resolve_complex_type(type_str: &str, idls: IdlLookup) -> Option<ResolvedStructType> {
+/// Resolve `type_str` against the IDLs. Fields of account data captured whole
+/// get their byte-array encodings, matching the capture's field transforms;
+/// nested types only carry integer encodings.
+fn resolve_complex_type(
+    type_str: &str,
+    idls: IdlLookup,
+    is_captured: bool,
+) -> Option<ResolvedStructType> {
     let idl_ref = find_idl_for_type(type_str, idls)?;
 
     // Extract the simple type name from patterns like "generated_sdk :: instructions :: Buy"
@@ -660,7 +686,7 @@ fn resolve_complex_type(type_str: &str, idls: IdlLookup) -> Option<ResolvedStruc
 
     for account in &idl_ref.accounts {
         if account.name.to_lowercase() == type_name_lower {
-            let resolved = resolve_account_type(account, idl);
+            let resolved = resolve_account_type(account, idl, is_captured);
             if !resolved.fields.is_empty() || resolved.is_enum {
                 return Some(resolved);
             }
@@ -670,7 +696,15 @@ fn resolve_complex_type(type_str: &str, idls: IdlLookup) -> Option<ResolvedStruc
 
     for type_def in &idl_ref.types {
         if type_def.name.to_lowercase() == type_name_lower {
-            return Some(resolve_custom_type(type_def, idl));
+            let is_account = idl_ref
+                .accounts
+                .iter()
+                .any(|account| account.name.to_lowercase() == type_name_lower);
+            return Some(resolve_custom_type(
+                type_def,
+                idl,
+                is_captured && is_account,
+            ));
         }
     }
 
@@ -702,6 +736,7 @@ fn resolve_instruction_type(
             base_type: BaseType::Pubkey,
             is_optional: account.optional,
             is_array: false,
+            encoding: None,
         });
     }
 
@@ -715,6 +750,7 @@ fn resolve_instruction_type(
             base_type,
             is_optional,
             is_array,
+            encoding: integer_encoding(&arg.type_),
         });
     }
 
@@ -733,6 +769,7 @@ fn resolve_instruction_type(
 fn resolve_account_type(
     account: &crate::parse::idl::IdlAccount,
     idl: Option<&IdlSpec>,
+    is_captured: bool,
 ) -> ResolvedStructType {
     let mut fields = Vec::new();
 
@@ -744,15 +781,12 @@ fn resolve_account_type(
                 ..
             } => {
                 for field in struct_fields {
-                    let (field_type, base_type, is_optional, is_array, _) =
-                        analyze_idl_type_with_resolution(&field.type_, idl);
-                    fields.push(ResolvedField {
-                        field_name: field.name.clone(),
-                        field_type,
-                        base_type,
-                        is_optional,
-                        is_array,
-                    });
+                    fields.push(resolved_field(
+                        field.name.clone(),
+                        &field.type_,
+                        idl,
+                        is_captured,
+                    ));
                 }
             }
             IdlTypeDefKind::TupleStruct {
@@ -760,15 +794,7 @@ fn resolve_account_type(
                 ..
             } => {
                 for (i, field_type) in tuple_fields.iter().enumerate() {
-                    let (field_type_str, base_type, is_optional, is_array, _) =
-                        analyze_idl_type_with_resolution(field_type, idl);
-                    fields.push(ResolvedField {
-                        field_name: format!("_{}", i),
-                        field_type: field_type_str,
-                        base_type,
-                        is_optional,
-                        is_array,
-                    });
+                    fields.push(resolved_field(format!("_{}", i), field_type, idl, false));
                 }
             }
             IdlTypeDefKind::Enum { variants, .. } => {
@@ -802,6 +828,7 @@ fn resolve_account_type(
 fn resolve_custom_type(
     type_def: &crate::parse::idl::IdlTypeDef,
     idl: Option<&IdlSpec>,
+    is_captured: bool,
 ) -> ResolvedStructType {
     let mut fields = Vec::new();
 
@@ -811,15 +838,12 @@ fn resolve_custom_type(
             ..
         } => {
             for field in struct_fields {
-                let (field_type, base_type, is_optional, is_array, _) =
-                    analyze_idl_type_with_resolution(&field.type_, idl);
-                fields.push(ResolvedField {
-                    field_name: field.name.clone(),
-                    field_type,
-                    base_type,
-                    is_optional,
-                    is_array,
-                });
+                fields.push(resolved_field(
+                    field.name.clone(),
+                    &field.type_,
+                    idl,
+                    is_captured,
+                ));
             }
 
             ResolvedStructType {
@@ -837,15 +861,7 @@ fn resolve_custom_type(
             ..
         } => {
             for (i, field_type) in tuple_fields.iter().enumerate() {
-                let (field_type_str, base_type, is_optional, is_array, _) =
-                    analyze_idl_type_with_resolution(field_type, idl);
-                fields.push(ResolvedField {
-                    field_name: format!("_{}", i),
-                    field_type: field_type_str,
-                    base_type,
-                    is_optional,
-                    is_array,
-                });
+                fields.push(resolved_field(format!("_{}", i), field_type, idl, false));
             }
 
             ResolvedStructType {
@@ -875,6 +891,42 @@ fn resolve_custom_type(
     }
 }
 
+/// Resolve a struct field, recording how its value is encoded in state.
+/// Byte arrays are only encoded in captured account data, where the capture
+/// applies the matching transform.
+fn resolved_field(
+    field_name: String,
+    idl_type: &IdlType,
+    idl: Option<&IdlSpec>,
+    is_captured: bool,
+) -> ResolvedField {
+    let encoding = if is_captured {
+        value_encoding(idl_type)
+    } else {
+        integer_encoding(idl_type)
+    };
+    let (field_type, base_type, is_optional, is_array, _) =
+        analyze_idl_type_with_resolution(idl_type, idl);
+    match encoding {
+        Some(ValueEncoding::Base58 | ValueEncoding::Base64) => ResolvedField {
+            field_name,
+            field_type,
+            base_type: BaseType::String,
+            is_optional,
+            is_array: false,
+            encoding,
+        },
+        _ => ResolvedField {
+            field_name,
+            field_type,
+            base_type,
+            is_optional,
+            is_array,
+            encoding,
+        },
+    }
+}
+
 /// Analyze an IDL type and return (type_string, base_type, is_optional, is_array)
 /// Analyze IDL type with optional resolution and return (type_name, base_type, is_optional, is_array, resolved_type)
 fn analyze_idl_type_with_resolution(
@@ -964,7 +1016,7 @@ fn analyze_idl_type_with_resolution(
 
             let temp_idl_lookup: Vec<(String, &IdlSpec)> =
                 idl.into_iter().map(|i| (String::new(), i)).collect();
-            let resolved_type = resolve_complex_type(&type_name, &temp_idl_lookup);
+            let resolved_type = resolve_complex_type(&type_name, &temp_idl_lookup, false);
 
             (type_name, BaseType::Object, false, false, resolved_type)
         }
@@ -19,6 +19,7 @@ use crate::validation::idl_refs::{
 use crate::diagnostic::idl_error_to_syn;
 use crate::stream_spec::computed::{parse_computed_expression, qualify_field_refs};
 use hyperstack_idl::error::IdlSearchError;
+use hyperstack_idl::search::{lookup_instruction, lookup_instruction_account};
 use hyperstack_idl::types::IdlSpec;
 
 pub mod idl_refs;
@@ -43,7 +44,13 @@ pub struct ValidationInput<'a> {
     pub resolve_specs: &'a [parse::ResolveSpec],
     pub section_specs: &'a [EntitySection],
     pub view_specs: &'a [parse::ViewAttributeSpec],
+    pub trace_fields: &'a [parse::TraceFieldSpec],
+    pub key_from: Option<&'a parse::KeyFromSpec>,
+    pub shared_indexes: &'a [parse::SharedIndexDecl],
     pub idls: IdlLookup<'a>,
+    /// Earlier versions of the stack's IDLs (`previous_idl = ...`), whose
+    /// instruction accounts mappings must also resolve against
+    pub previous_idls: &'a [IdlSpec],
 }
 
 pub struct KeyResolutionValidationInput<'a> {
@@ -55,6 +62,7 @@ pub struct KeyResolutionValidationInput<'a> {
         &'a BTreeMap<String, Vec<(String, parse::EventAttribute, syn::Type)>>,
     pub derive_from_mappings: &'a BTreeMap<String, Vec<parse::DeriveFromAttribute>>,
     pub resolver_hooks: &'a [parse::ResolveKeyAttribute],
+    pub key_from: Option<&'a parse::KeyFromSpec>,
 }
 
 type GroupedEventMappings =
@@ -87,6 +95,7 @@ pub fn validate_semantics(input: ValidationInput<'_>) -> syn::Result<()> {
             events_by_instruction: input.events_by_instruction,
             derive_from_mappings: input.derive_from_mappings,
             resolver_hooks: input.resolver_hooks,
+            key_from: input.key_from,
         },
         &mut errors,
     );
@@ -97,6 +106,7 @@ pub fn validate_semantics(input: ValidationInput<'_>) -> syn::Result<()> {
         &known_fields,
         &available_fields,
         input.idls,
+        input.previous_idls,
         &mut errors,
     );
     validate_event_references(
@@ -108,6 +118,11 @@ pub fn validate_semantics(input: ValidationInput<'_>) -> syn::Result<()> {
         &mut errors,
     );
     validate_derive_from_references(input.derive_from_mappings, input.idls, &mut errors);
+    validate_write_policies(
+        input.sources_by_type,
+        input.events_by_instruction,
+        &mut errors,
+    );
     validate_aggregate_conditions(
         input.entity_name,
         input.aggregate_conditions,
@@ -129,6 +144,19 @@ pub fn validate_semantics(input: ValidationInput<'_>) -> syn::Result<()> {
         &available_fields,
         &mut errors,
     );
+    validate_trace_fields(
+        input.entity_name,
+        input.trace_fields,
+        &known_fields,
+        &available_fields,
+        &mut errors,
+    );
+    validate_shared_index_references(
+        input.entity_name,
+        input.sources_by_type,
+        input.shared_indexes,
+        &mut errors,
+    );
     validate_computed_fields(
         input.entity_name,
         input.computed_fields,
@@ -145,7 +173,13 @@ pub fn validate_key_resolution_paths(
     errors: &mut ErrorCollector,
 ) {
     let primary_key_leafs = primary_key_leafs(input.primary_keys);
-    let lookup_index_leafs = lookup_index_leafs(input.lookup_indexes);
+    let mut lookup_index_leafs = lookup_index_leafs(input.lookup_indexes);
+    // `key_from` registers the account's address, so instructions can look
+    // the entity up by it
+    if let Some(key_from) = input.key_from {
+        let address_field = key_from.address_field();
+        lookup_index_leafs.extend(lookup_index_leafs_of(&address_field));
+    }
 
     validate_source_handler_keys(
         input.entity_name,
@@ -221,6 +255,77 @@ fn sorted_field_paths(known_fields: &HashSet<String>) -> Vec<String> {
     values
 }
 
+/// Position of the account an `index = N` mapping falls back to when the IDL
+/// has no account by the written name
+fn resolve_account_by_position(
+    idl: &IdlSpec,
+    instruction_name: &str,
+    mapping: &parse::MapAttribute,
+) -> Option<usize> {
+    let index = mapping.account_index?;
+    lookup_instruction_account(
+        idl,
+        instruction_name,
+        &mapping.source_field_name,
+        Some(index),
+    )
+    .ok()
+    .map(|account| account.index)
+}
+
+/// A mapping of an instruction account must find it in every earlier
+/// version of the program's IDL too, by name or by its `index = N`
+/// fallback, or transactions sent before the upgrade map to null.
+fn validate_previous_instruction_accounts(
+    idl: &IdlSpec,
+    instruction_name: &str,
+    mapping: &parse::MapAttribute,
+    previous_idls: &[IdlSpec],
+    errors: &mut ErrorCollector,
+) {
+    let Ok(current) =
+        lookup_instruction_account(idl, instruction_name, &mapping.source_field_name, None)
+    else {
+        return;
+    };
+
+    for previous in previous_idls
+        .iter()
+        .filter(|previous| previous.get_name() == idl.get_name())
+    {
+        if lookup_instruction(previous, instruction_name).is_err() {
+            continue;
+        }
+        let Err(error) = lookup_instruction_account(
+            previous,
+            instruction_name,
+            &mapping.source_field_name,
+            mapping.account_index,
+        ) else {
+            continue;
+        };
+
+        let hint = match mapping.account_index {
+            None => format!(
+                ". Add `index = {}` to read the account by position in both versions",
+                current.index
+            ),
+            Some(_) => String::new(),
+        };
+        errors.push(syn::Error::new(
+            mapping.source_field_span,
+            format!(
+                "account '{}' of instruction '{}' is missing from a previous version of the '{}' IDL{}: {}",
+                mapping.source_field_name,
+                instruction_name,
+                previous.get_name(),
+                hint,
+                error
+            ),
+        ));
+    }
+}
+
 fn entity_field_error(
     entity_name: &str,
     reference: &str,
@@ -390,17 +495,19 @@ fn primary_key_leafs(primary_keys: &[String]) -> HashSet<String> {
 }
 
 fn lookup_index_leafs(lookup_indexes: &[(String, Option<String>)]) -> HashSet<String> {
-    let mut values = HashSet::new();
+    lookup_indexes
+        .iter()
+        .flat_map(|(field, _)| lookup_index_leafs_of(field))
+        .collect()
+}
 
-    for (field, _) in lookup_indexes {
-        let leaf = field.split('.').next_back().unwrap_or(field).to_string();
-        values.insert(leaf.clone());
-        if let Some(stripped) = leaf.strip_suffix("_address") {
-            values.insert(stripped.to_string());
-        }
+/// Names a lookup_by field can use for the lookup index on `field`
+fn lookup_index_leafs_of(field: &str) -> Vec<String> {
+    let leaf = field.split('.').next_back().unwrap_or(field).to_string();
+    match leaf.strip_suffix("_address") {
+        Some(stripped) => vec![stripped.to_string(), leaf],
+        None => vec![leaf],
     }
-
-    values
 }
 
 fn has_explicit_key_resolver(
@@ -886,6 +993,7 @@ fn validate_mapping_references(
     known_fields: &HashSet<String>,
     available_fields: &[String],
     idls: IdlLookup,
+    previous_idls: &[IdlSpec],
     errors: &mut ErrorCollector,
 ) {
     let mut source_types: Vec<&String> = sources_by_type.keys().collect();
@@ -942,7 +1050,19 @@ fn validate_mapping_references(
                             if let Err(error) =
                                 validate_instruction_field_spec(idl, instruction_name, &temp_field)
                             {
-                                errors.push(idl_error_to_syn(mapping.source_field_span, error));
+                                if resolve_account_by_position(idl, instruction_name, mapping)
+                                    .is_none()
+                                {
+                                    errors.push(idl_error_to_syn(mapping.source_field_span, error));
+                                }
+                            } else {
+                                validate_previous_instruction_accounts(
+                                    idl,
+                                    instruction_name,
+                                    mapping,
+                                    previous_idls,
+                                    errors,
+                                );
                             }
                         }
                     }
@@ -1175,6 +1295,60 @@ fn validate_aggregate_conditions(
     }
 }
 
+/// Every source writing to the same field must agree on its write policy; the
+/// compiled handlers would otherwise overwrite each other's values unpredictably.
+/// Clearing a field (`action = clear`) writes no value, so it goes with any policy.
+fn validate_write_policies(
+    sources_by_type: &BTreeMap<String, Vec<parse::MapAttribute>>,
+    events_by_instruction: &BTreeMap<String, Vec<(String, parse::EventAttribute, syn::Type)>>,
+    errors: &mut ErrorCollector,
+) {
+    let mut mappings: Vec<&parse::MapAttribute> = sources_by_type.values().flatten().collect();
+    mappings.sort_by(|a, b| stable_map_attribute_cmp(a, b));
+
+    let mut writes: Vec<(&str, &str, proc_macro2::Span)> = mappings
+        .iter()
+        .map(|m| {
+            (
+                m.target_field_name.as_str(),
+                m.strategy.as_str(),
+                m.attr_span,
+            )
+        })
+        .collect();
+    writes.extend(
+        events_by_instruction
+            .values()
+            .flatten()
+            .map(|(_, event, _)| {
+                (
+                    event.target_field_name.as_str(),
+                    event.strategy.as_str(),
+                    event.attr_span,
+                )
+            }),
+    );
+
+    let mut first_policy: HashMap<&str, &str> = HashMap::new();
+    let mut reported: HashSet<&str> = HashSet::new();
+    for (field, strategy, span) in writes {
+        if strategy == "Clear" {
+            continue;
+        }
+        let expected = *first_policy.entry(field).or_insert(strategy);
+        if expected != strategy && reported.insert(field) {
+            errors.push(syn::Error::new(
+                span,
+                format!(
+                    "conflicting write policies for field '{}': {} here, but {} on another source. \
+                     All mappings to a field must use the same policy.",
+                    field, strategy, expected
+                ),
+            ));
+        }
+    }
+}
+
 /// Try to construct a `FieldSpec` from a condition leaf string. Returns `None`
 /// if the leaf is not a valid Rust identifier (e.g. starts with a digit),
 /// preventing a `syn::Ident::new` panic inside the proc-macro process.
@@ -1217,6 +1391,8 @@ fn collect_condition_field_leaves_recursive(
                 collect_condition_field_leaves_recursive(sub, leaves);
             }
         }
+        // Reads entity state rather than the source event.
+        crate::ast::ParsedCondition::Changed { .. } => {}
     }
 }
 
@@ -1511,6 +1687,67 @@ fn validate_views(
     }
 }
 
+fn validate_trace_fields(
+    entity_name: &str,
+    trace_fields: &[parse::TraceFieldSpec],
+    known_fields: &HashSet<String>,
+    available_fields: &[String],
+    errors: &mut ErrorCollector,
+) {
+    let mut seen = HashSet::new();
+
+    for trace_field in trace_fields {
+        if !known_fields.contains(&trace_field.path) {
+            errors.push(entity_field_error(
+                entity_name,
+                &trace_field.path,
+                "trace field",
+                trace_field.span,
+                available_fields,
+            ));
+        } else if !seen.insert(trace_field.path.as_str()) {
+            errors.push(syn::Error::new(
+                trace_field.span,
+                format!(
+                    "duplicate trace field '{}' on entity '{}'",
+                    trace_field.path, entity_name
+                ),
+            ));
+        }
+    }
+}
+
+fn validate_shared_index_references(
+    entity_name: &str,
+    sources_by_type: &BTreeMap<String, Vec<parse::MapAttribute>>,
+    shared_indexes: &[parse::SharedIndexDecl],
+    errors: &mut ErrorCollector,
+) {
+    let declared: Vec<String> = shared_indexes
+        .iter()
+        .map(|decl| decl.name.clone())
+        .collect();
+
+    for mapping in sources_by_type.values().flatten() {
+        let Some(shared_index) = &mapping.shared_index else {
+            continue;
+        };
+        if declared.contains(shared_index) {
+            continue;
+        }
+        errors.push(syn::Error::new(
+            mapping.attr_span,
+            format!(
+                "unknown shared index '{}' on entity '{}'. Declare it in the module with shared_index!(\"{}\"){}",
+                shared_index,
+                entity_name,
+                shared_index,
+                suggestion_or_available_suffix(shared_index, &declared, "Declared shared indexes")
+            ),
+        ));
+    }
+}
+
 fn validate_computed_fields(
     entity_name: &str,
     computed_fields: &[ComputedFieldValidation],
@@ -1627,6 +1864,8 @@ fn collect_field_refs_recursive(expr: &ComputedExpr, refs: &mut HashSet<String>)
         | ComputedExpr::Slice { expr, .. }
         | ComputedExpr::Index { expr, .. }
         | ComputedExpr::Keccak256 { expr }
+        | ComputedExpr::SlotsToSecs { expr }
+        | ComputedExpr::SecsToSlots { expr }
         | ComputedExpr::JsonToBytes { expr }
         | ComputedExpr::U64FromLeBytes { bytes: expr }
         | ComputedExpr::U64FromBeBytes { bytes: expr } => {
@@ -1663,7 +1902,11 @@ fn collect_field_refs_recursive(expr: &ComputedExpr, refs: &mut HashSet<String>)
         | ComputedExpr::ByteArray { .. }
         | ComputedExpr::None
         | ComputedExpr::ContextSlot
-        | ComputedExpr::ContextTimestamp => {}
+        | ComputedExpr::ContextTimestamp
+        | ComputedExpr::Now => {}
+        ComputedExpr::ArrayAggregate { aggregate } => {
+            refs.insert(aggregate.path.clone());
+        }
     }
 }
 
@@ -0,0 +1,187 @@
+mod support;
+
+use support::{cargo_toml, escape_path, hyperstack_dir, macro_manifest_dir, TempCrate};
+
+/// One stack, built against IDLs that spell the `Buy` instruction's second
+/// account `bondingCurve`, `bonding_curve` or, after a rename, `curve`
+const SOURCE: &str = r#"use hyperstack_macros::hyperstack;
+
+#[hyperstack(idl = "fixture/fake.json")]
+mod stream {
+    #[entity(name = "Trade")]
+    struct Trade {
+        #[map(fake_sdk::instructions::Buy::bondingCurve, primary_key, strategy = SetOnce, index = 1)]
+        curve: String,
+
+        #[map(fake_sdk::instructions::Buy::amount, strategy = LastWrite)]
+        amount: u64,
+    }
+}
+
+fn main() {
+    let trade = stream::create_trade_spec();
+    for handler in &trade.handlers {
+        for mapping in &handler.mappings {
+            if mapping.target_path == "curve" {
+                println!("fallback={:?}", mapping.account_fallback);
+            }
+        }
+    }
+
+    let spec = stream::spec();
+    let accounts = [
+        hyperstack::runtime::yellowstone_vixen_core::KeyBytes([1; 32]),
+        hyperstack::runtime::yellowstone_vixen_core::KeyBytes([2; 32]),
+    ];
+    let buy = stream::parsers::FakeInstruction::Buy(stream::fake_sdk::instructions::Buy {
+        amount: 42,
+    });
+    let mut vm = hyperstack::runtime::hyperstack_interpreter::vm::VmContext::new();
+    let mutations = vm
+        .process_event(
+            &spec.bytecode,
+            buy.to_value_with_accounts(&accounts),
+            buy.event_type(),
+            None,
+            None,
+        )
+        .unwrap();
+    println!("curve={}", hyperstack::runtime::bs58::encode([2u8; 32]).into_string());
+    for mutation in mutations {
+        println!("key={} patch={}", mutation.key, mutation.patch);
+    }
+}
+"#;
+
+fn fake_idl(curve_account: &str) -> String {
+    format!(
+        r#"{{
+  "name": "fake",
+  "metadata": {{ "address": "oreV3EG1i9BEgiAJ8b177Z2S2rMarzak4NMv1kULvWv" }},
+  "instructions": [
+    {{
+      "name": "buy",
+      "discriminator": [102, 6, 61, 18, 1, 218, 235, 234],
+      "accounts": [{{ "name": "user" }}, {{ "name": "{curve_account}" }}],
+      "args": [{{ "name": "amount", "type": "u64" }}]
+    }}
+  ],
+  "accounts": [
+    {{
+      "name": "Curve",
+      "type": {{ "kind": "struct", "fields": [{{ "name": "supply", "type": "u64" }}] }}
+    }}
+  ],
+  "types": [],
+  "events": [],
+  "errors": [],
+  "constants": []
+}}"#
+    )
+}
+
+fn manifest(name: &str) -> String {
+    cargo_toml(
+        name,
+        &[
+            format!(
+                "hyperstack = {{ path = \"{}\" }}",
+                escape_path(&hyperstack_dir())
+            ),
+            format!(
+                "hyperstack-macros = {{ path = \"{}\" }}",
+                escape_path(&macro_manifest_dir())
+            ),
+            "borsh = { version = \"1.5\", features = [\"derive\"] }".to_string(),
+            "serde = { version = \"1\", features = [\"derive\"] }".to_string(),
+            "solana-pubkey = { version = \"2.2\", features = [\"serde\", \"borsh\"] }".to_string(),
+        ],
+    )
+}
+
+fn run_stack(name: &str, curve_account: &str) -> String {
+    let idl = fake_idl(curve_account);
+    let temp_crate = TempCrate::new(
+        "account-fallback-dynamic",
+        name,
+        manifest(name),
+        SOURCE,
+        &[("fixture/fake.json", idl.as_str())],
+    );
+
+    let output = temp_crate.cargo_run();
+    assert!(
+        output.status.success(),
+        "expected cargo run to succeed, stderr:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn assert_keyed_by_curve(stdout: &str) {
+    let curve = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("curve="))
+        .expect("stack should print the curve address");
+    assert!(
+        stdout.contains(&format!("key=\"{curve}\"")),
+        "expected the trade to be keyed by the curve account, stdout:\n{stdout}"
+    );
+    assert!(stdout.contains("\"amount\":42"), "stdout:\n{stdout}");
+}
+
+#[test]
+fn camel_and_snake_case_account_names_reach_the_same_handler() {
+    for (name, curve_account) in [
+        ("camel_case_account_name", "bondingCurve"),
+        ("snake_case_account_name", "bonding_curve"),
+    ] {
+        let stdout = run_stack(name, curve_account);
+        assert!(stdout.contains("fallback=None"), "stdout:\n{stdout}");
+        assert_keyed_by_curve(&stdout);
+    }
+}
+
+#[test]
+fn renamed_accounts_are_read_by_position() {
+    let stdout = run_stack("renamed_account", "curve");
+
+    assert!(
+        stdout.contains(
+            "fallback=Some(AccountFallback { instruction: \"buy\", written: \"bondingCurve\", resolved: \"curve\", index: 1 })"
+        ),
+        "stdout:\n{stdout}"
+    );
+    assert_keyed_by_curve(&stdout);
+}
+
+#[test]
+fn previous_idl_versions_must_resolve_every_account() {
+    let name = "previous_idl_missing_account";
+    let source = SOURCE
+        .replace(
+            "idl = \"fixture/fake.json\"",
+            "idl = \"fixture/fake.json\", previous_idl = \"fixture/fake_v1.json\"",
+        )
+        .replace(", index = 1", "");
+    let idl = fake_idl("bonding_curve");
+    let previous = fake_idl("curve");
+    let temp_crate = TempCrate::new(
+        "account-fallback-dynamic",
+        name,
+        manifest(name),
+        &source,
+        &[
+            ("fixture/fake.json", idl.as_str()),
+            ("fixture/fake_v1.json", previous.as_str()),
+        ],
+    );
+
+    let output = temp_crate.cargo_check();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "expected cargo check to fail");
+    assert!(
+        stderr.contains("account 'bondingCurve' of instruction 'Buy' is missing from a previous version of the 'fake' IDL. Add `index = 1`"),
+        "stderr:\n{stderr}"
+    );
+}
@@ -0,0 +1,174 @@
+mod support;
+
+use support::{cargo_toml, escape_path, hyperstack_dir, macro_manifest_dir, TempCrate};
+
+const SOURCE: &str = r#"use hyperstack_macros::hyperstack;
+
+#[hyperstack(idl = ["fixture/fake.json", "fixture/other.json"])]
+mod stream {
+    #[entity(name = "Thing")]
+    struct Thing {
+        #[map(fake_sdk::accounts::Thing::id, primary_key, strategy = SetOnce)]
+        id: String,
+    }
+
+    #[entity(name = "Position", feature = "trading")]
+    struct Position {
+        #[map(other_sdk::accounts::Position::owner, primary_key, strategy = SetOnce)]
+        owner: String,
+
+        #[map(other_sdk::accounts::Position::amount, strategy = LastWrite)]
+        amount: u64,
+    }
+}
+
+fn main() {
+    // Shared items are compiled whichever entities are enabled
+    let _ = std::any::type_name::<stream::other_sdk::accounts::Position>();
+
+    let spec = stream::spec();
+    let mut entities: Vec<_> = spec.bytecode.entities.keys().cloned().collect();
+    entities.sort();
+    println!("entities={}", entities.join(","));
+    println!("program_ids={}", spec.program_ids.join(","));
+
+    let stack_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join(".hyperstack/Stream.stack.json");
+    let stack: hyperstack::runtime::serde_json::Value =
+        hyperstack::runtime::serde_json::from_str(&std::fs::read_to_string(stack_path).unwrap())
+            .unwrap();
+    println!("features={}", stack["features"]);
+}
+"#;
+
+const FAKE_PROGRAM_ID: &str = "oreV3EG1i9BEgiAJ8b177Z2S2rMarzak4NMv1kULvWv";
+const OTHER_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+fn idl(name: &str, address: &str, account: &str, fields: &str) -> String {
+    format!(
+        r#"{{
+  "name": "{name}",
+  "metadata": {{ "address": "{address}" }},
+  "instructions": [
+    {{
+      "name": "Touch",
+      "accounts": [{{ "name": "target" }}],
+      "args": []
+    }}
+  ],
+  "accounts": [
+    {{
+      "name": "{account}",
+      "type": {{ "kind": "struct", "fields": {fields} }}
+    }}
+  ],
+  "types": [],
+  "events": [],
+  "errors": [],
+  "constants": []
+}}"#
+    )
+}
+
+fn stack_crate(name: &str) -> TempCrate {
+    let manifest = cargo_toml(
+        name,
+        &[
+            format!(
+                "hyperstack = {{ path = \"{}\" }}",
+                escape_path(&hyperstack_dir())
+            ),
+            format!(
+                "hyperstack-macros = {{ path = \"{}\" }}",
+                escape_path(&macro_manifest_dir())
+            ),
+            "borsh = { version = \"1.5\", features = [\"derive\"] }".to_string(),
+            "serde = { version = \"1\", features = [\"derive\"] }".to_string(),
+        ],
+    ) + "\n[features]\ntrading = []\n";
+
+    TempCrate::new(
+        "entity-features-dynamic",
+        name,
+        manifest,
+        SOURCE,
+        &[
+            (
+                "fixture/fake.json",
+                &idl(
+                    "fake",
+                    FAKE_PROGRAM_ID,
+                    "Thing",
+                    r#"[{ "name": "id", "type": "string" }]"#,
+                ),
+            ),
+            (
+                "fixture/other.json",
+                &idl(
+                    "other",
+                    OTHER_PROGRAM_ID,
+                    "Position",
+                    r#"[{ "name": "owner", "type": "string" }, { "name": "amount", "type": "u64" }]"#,
+                ),
+            ),
+        ],
+    )
+}
+
+fn run(temp_crate: &TempCrate, features: &[&str]) -> String {
+    let output = temp_crate.cargo_run_with_features(features);
+    assert!(
+        output.status.success(),
+        "expected cargo run to succeed with features {features:?}, stderr:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn gated_entities_follow_the_enabled_features() {
+    let temp_crate = stack_crate("gated_entities_follow_the_enabled_features");
+
+    let without = run(&temp_crate, &[]);
+    let with = run(&temp_crate, &["trading"]);
+
+    assert_ne!(
+        without, with,
+        "spec() should depend on the enabled features"
+    );
+
+    assert!(without.contains("entities=Thing\n"), "{without}");
+    assert!(
+        without.contains(&format!("program_ids={FAKE_PROGRAM_ID}\n")),
+        "{without}"
+    );
+    assert!(without.contains("features=null\n"), "{without}");
+
+    assert!(with.contains("entities=Position,Thing\n"), "{with}");
+    assert!(
+        with.contains(&format!(
+            "program_ids={FAKE_PROGRAM_ID},{OTHER_PROGRAM_ID}\n"
+        )),
+        "{with}"
+    );
+    assert!(with.contains("features=[\"trading\"]\n"), "{with}");
+}
+
+#[test]
+fn undeclared_entity_feature_is_rejected() {
+    let temp_crate = stack_crate("undeclared_entity_feature_is_rejected");
+    std::fs::write(
+        temp_crate.path().join("src/main.rs"),
+        SOURCE.replace("feature = \"trading\"", "feature = \"tradng\""),
+    )
+    .expect("rewrite main.rs");
+
+    let output = temp_crate.cargo_check();
+    assert!(!output.status.success(), "expected cargo check to fail");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("entity is gated behind feature `tradng`"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,193 @@
+mod support;
+
+use support::{cargo_toml, escape_path, hyperstack_dir, macro_manifest_dir, TempCrate};
+
+const SOURCE: &str = r#"use hyperstack_macros::hyperstack;
+use hyperstack::runtime::yellowstone_vixen_core::Parser;
+
+#[hyperstack(
+    idl = "fixture/fake.json",
+    extra_accounts = ["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA:(owner=9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM)"]
+)]
+mod stream {
+    #[entity(name = "Thing")]
+    struct Thing {
+        #[map(fake_sdk::accounts::Thing::id, primary_key, strategy = SetOnce)]
+        id: String,
+    }
+
+    #[entity(name = "Vault")]
+    struct Vault {
+        #[map(spl_token_sdk::accounts::TokenAccount::__account_address, primary_key, strategy = SetOnce)]
+        address: String,
+
+        #[map(spl_token_sdk::accounts::TokenAccount::amount, strategy = LastWrite)]
+        amount: u64,
+    }
+}
+
+fn token_account(owner: &str, amount: u64) -> hyperstack::runtime::yellowstone_vixen_core::AccountUpdate {
+    let owner = hyperstack::runtime::bs58::decode(owner).into_vec().unwrap();
+    let mut data = vec![0u8; 165];
+    data[32..64].copy_from_slice(&owner);
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    data[108] = 1;
+
+    hyperstack::runtime::yellowstone_vixen_core::AccountUpdate {
+        account: Some(hyperstack::runtime::yellowstone_grpc_proto::geyser::SubscribeUpdateAccountInfo {
+            pubkey: vec![7; 32],
+            lamports: 0,
+            owner: hyperstack::runtime::bs58::decode(stream::spl_token_parsers::PROGRAM_ID_STR)
+                .into_vec()
+                .unwrap(),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            write_version: 0,
+            txn_signature: None,
+        }),
+        slot: 1,
+        is_startup: false,
+    }
+}
+
+fn main() {
+    let spec = stream::spec();
+    println!("program_ids={}", spec.program_ids.join(","));
+    println!(
+        "routed={}",
+        !spec
+            .bytecode
+            .route("spl_token::TokenAccountState", Some(stream::spl_token_parsers::PROGRAM_ID_STR))
+            .unwrap()
+            .is_empty()
+    );
+
+    let parser = stream::spl_token_parsers::AccountParser;
+    let account_filter = parser.prefilter().account.unwrap();
+    let owners: Vec<String> = account_filter.owners.iter().map(ToString::to_string).collect();
+    println!("owners={}", owners.join(","));
+    println!("filters={:?}", stream::spl_token_parsers::filters());
+
+    let vault = hyperstack::runtime::futures::executor::block_on(
+        parser.parse(&token_account("9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM", 42)),
+    )
+    .unwrap();
+    println!("vault={}", vault.to_value());
+
+    let other = hyperstack::runtime::futures::executor::block_on(
+        parser.parse(&token_account("So11111111111111111111111111111111111111112", 42)),
+    );
+    println!("other_filtered={}", other.is_err());
+}
+"#;
+
+const FAKE_PROGRAM_ID: &str = "oreV3EG1i9BEgiAJ8b177Z2S2rMarzak4NMv1kULvWv";
+const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const VAULT: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+const FAKE_IDL: &str = r#"{
+  "name": "fake",
+  "metadata": { "address": "oreV3EG1i9BEgiAJ8b177Z2S2rMarzak4NMv1kULvWv" },
+  "instructions": [
+    {
+      "name": "Touch",
+      "accounts": [{ "name": "target" }],
+      "args": []
+    }
+  ],
+  "accounts": [
+    {
+      "name": "Thing",
+      "type": { "kind": "struct", "fields": [{ "name": "id", "type": "string" }] }
+    }
+  ],
+  "types": [],
+  "events": [],
+  "errors": [],
+  "constants": []
+}"#;
+
+fn stack_crate(name: &str) -> TempCrate {
+    let manifest = cargo_toml(
+        name,
+        &[
+            format!(
+                "hyperstack = {{ path = \"{}\" }}",
+                escape_path(&hyperstack_dir())
+            ),
+            format!(
+                "hyperstack-macros = {{ path = \"{}\" }}",
+                escape_path(&macro_manifest_dir())
+            ),
+            "borsh = { version = \"1.5\", features = [\"derive\"] }".to_string(),
+            "serde = { version = \"1\", features = [\"derive\"] }".to_string(),
+            "solana-pubkey = { version = \"2.2\", features = [\"serde\", \"borsh\"] }".to_string(),
+        ],
+    );
+
+    TempCrate::new(
+        "extra-accounts-dynamic",
+        name,
+        manifest,
+        SOURCE,
+        &[("fixture/fake.json", FAKE_IDL)],
+    )
+}
+
+#[test]
+fn extra_token_accounts_are_subscribed_and_decoded() {
+    let temp_crate = stack_crate("extra_token_accounts_are_subscribed_and_decoded");
+
+    let output = temp_crate.cargo_run();
+    assert!(
+        output.status.success(),
+        "expected cargo run to succeed, stderr:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(
+        stdout.contains(&format!(
+            "program_ids={FAKE_PROGRAM_ID},{TOKEN_PROGRAM_ID}\n"
+        )),
+        "{stdout}"
+    );
+    assert!(stdout.contains("routed=true\n"), "{stdout}");
+    assert!(
+        stdout.contains(&format!("owners={TOKEN_PROGRAM_ID}\n")),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(&format!("filters=[Owner(KeyBytes(\"{VAULT}\"))]\n")),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(&format!(r#""owner":"{VAULT}""#)),
+        "{stdout}"
+    );
+    assert!(stdout.contains(r#""amount":42"#), "{stdout}");
+    assert!(stdout.contains("other_filtered=true\n"), "{stdout}");
+}
+
+#[test]
+fn extra_accounts_without_builtin_layout_are_rejected() {
+    let temp_crate = stack_crate("extra_accounts_without_builtin_layout_are_rejected");
+    std::fs::write(
+        temp_crate.path().join("src/main.rs"),
+        SOURCE.replace(
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA:(owner=",
+            "oreV3EG1i9BEgiAJ8b177Z2S2rMarzak4NMv1kULvWv:(owner=",
+        ),
+    )
+    .expect("rewrite main.rs");
+
+    let output = temp_crate.cargo_check();
+    assert!(!output.status.success(), "expected cargo check to fail");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("no builtin account layout for program"),
+        "{stderr}"
+    );
+}
@@ -350,3 +350,53 @@ fn main() {}
         &[("fixture/minimal.json", minimal_idl())],
     );
 }
+
+#[test]
+fn key_from_keys_account_and_resolves_instruction_lookup() {
+    let source = r#"use hyperstack_macros::hyperstack;
+
+#[hyperstack(idl = "fixture/minimal.json")]
+mod valid {
+    #[entity(name = "Thing", key_from = "fake_sdk::accounts::Thing::id")]
+    struct Thing {
+        #[map(fake_sdk::accounts::Thing::id, strategy = SetOnce)]
+        id: String,
+
+        #[aggregate(from = fake_sdk::instructions::Trade, strategy = Count, lookup_by = accounts::thing)]
+        trades: u64,
+    }
+}
+
+fn main() {}
+"#;
+
+    compile_success_with_files(
+        "key_from_keys_account_and_resolves_instruction_lookup",
+        source,
+        &[("fixture/minimal.json", minimal_idl())],
+    );
+}
+
+#[test]
+fn key_from_field_must_be_mapped() {
+    let source = r#"use hyperstack_macros::hyperstack;
+
+#[hyperstack(idl = "fixture/minimal.json")]
+mod broken {
+    #[entity(name = "Thing", key_from = "fake_sdk::accounts::Position::amount")]
+    struct Thing {
+        #[map(fake_sdk::accounts::Thing::id, primary_key, strategy = SetOnce)]
+        id: String,
+    }
+}
+
+fn main() {}
+"#;
+
+    let stderr = compile_failure_stderr_with_files(
+        "key_from_field_must_be_mapped",
+        source,
+        &[("fixture/minimal.json", minimal_idl())],
+    );
+    assert!(stderr.contains("Add `#[map(fake_sdk::accounts::Position::amount)]`"));
+}
@@ -238,3 +238,76 @@ fn main() {{}}
         ],
     );
 }
+
+#[test]
+fn invalid_map_policy_is_rejected_early() {
+    let source = r#"use hyperstack_macros::hyperstack;
+
+#[hyperstack]
+mod broken {
+    #[entity(name = "Thing")]
+    struct Thing {
+        #[map(fake_sdk::accounts::Thing::value, policy = "largest")]
+        value: u64,
+    }
+}
+
+fn main() {}
+"#;
+
+    run_compile_failure(
+        "invalid_map_policy_is_rejected_early",
+        source,
+        &["invalid policy 'largest' for #[map]"],
+    );
+}
+
+#[test]
+fn map_strategy_and_policy_are_mutually_exclusive() {
+    let source = r#"use hyperstack_macros::hyperstack;
+
+#[hyperstack]
+mod broken {
+    #[entity(name = "Thing")]
+    struct Thing {
+        #[map(fake_sdk::accounts::Thing::value, strategy = LastWrite, policy = "max")]
+        value: u64,
+    }
+}
+
+fn main() {}
+"#;
+
+    run_compile_failure(
+        "map_strategy_and_policy_are_mutually_exclusive",
+        source,
+        &["#[map] accepts either `strategy` or `policy`, not both"],
+    );
+}
+
+#[test]
+fn conflicting_write_policies_are_rejected() {
+    let source = format!(
+        r#"use hyperstack_macros::hyperstack;
+
+#[hyperstack(idl = "{}")]
+mod broken {{
+    #[entity(name = "Thing")]
+    struct Thing {{
+        #[map(pump_sdk::accounts::BondingCurve::virtual_token_reserves, policy = "max")]
+        #[map(pump_sdk::accounts::BondingCurve::real_token_reserves, policy = "latest")]
+        reserves: u64,
+    }}
+}}
+
+fn main() {{}}
+"#,
+        pump_idl_path()
+    );
+
+    run_compile_failure(
+        "conflicting_write_policies_are_rejected",
+        &source,
+        &["conflicting write policies for field 'reserves'"],
+    );
+}
@@ -129,3 +129,39 @@ fn main() {}
     let stderr = compile_failure_stderr("empty_url_template_field_is_rejected", source);
     assert!(stderr.contains("Empty field reference '{}' in URL template"));
 }
+
+#[test]
+fn unknown_shared_index_suggests_declared_name() {
+    let source = format!(
+        r#"use hyperstack_macros::hyperstack;
+
+#[hyperstack(idl = "{}")]
+mod broken {{
+    shared_index!("reserves_to_curve");
+
+    #[entity(name = "Thing")]
+    struct Thing {{
+        #[map(pump_sdk::accounts::BondingCurve::complete, primary_key, strategy = SetOnce)]
+        id: bool,
+
+        #[map(
+            pump_sdk::accounts::BondingCurve::virtual_sol_reserves,
+            shared_index = "reserves_to_curv",
+            strategy = LastWrite
+        )]
+        reserves: u64,
+    }}
+}}
+
+fn main() {{}}
+"#,
+        pump_idl_path()
+    );
+
+    let stderr = compile_failure_stderr("unknown_shared_index_suggests_declared_name", &source);
+    assert!(
+        stderr.contains("unknown shared index 'reserves_to_curv' on entity 'Thing'"),
+        "stderr was:\n{stderr}"
+    );
+    assert!(stderr.contains("Did you mean: reserves_to_curve?"));
+}
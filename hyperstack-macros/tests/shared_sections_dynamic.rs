@@ -0,0 +1,153 @@
+mod support;
+
+use support::{cargo_toml, escape_path, hyperstack_dir, macro_manifest_dir, TempCrate};
+
+const COMMON_SECTIONS: &str = r#"use hyperstack_macros::SharedSection;
+use serde::{Deserialize, Serialize};
+
+/// Metadata of a token
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SharedSection)]
+pub struct TokenMetadata {
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+}
+"#;
+
+const SOURCE: &str = r#"use hyperstack_macros::hyperstack;
+
+#[hyperstack]
+mod pools {
+    use common_sections::TokenMetadata;
+
+    #[entity(name = "Pool")]
+    struct Pool {
+        #[section(external = "common_sections::TokenMetadata")]
+        pub base: TokenMetadata,
+        #[section(external = "common_sections::TokenMetadata")]
+        pub quote: TokenMetadata,
+    }
+}
+
+#[hyperstack]
+mod vaults {
+    use common_sections::TokenMetadata;
+
+    #[entity(name = "Vault")]
+    struct Vault {
+        /// Token held by the vault
+        #[section(external = "common_sections::TokenMetadata")]
+        pub token: TokenMetadata,
+    }
+}
+
+fn main() {
+    let _spec = pools::create_pool_spec();
+    let _spec = vaults::create_vault_spec();
+
+    for stack_name in ["Pools", "Vaults"] {
+        let stack_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join(format!(".hyperstack/{stack_name}.stack.json"));
+        let stack: hyperstack::runtime::serde_json::Value =
+            hyperstack::runtime::serde_json::from_str(&std::fs::read_to_string(stack_path).unwrap())
+                .unwrap();
+        for section in stack["entities"][0]["sections"].as_array().unwrap() {
+            let fields: Vec<_> = section["fields"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|field| field["field_name"].as_str().unwrap())
+                .collect();
+            println!(
+                "{stack_name}.{}: {} [{}] {}",
+                section["name"].as_str().unwrap(),
+                section["shared_type"],
+                fields.join(","),
+                section["docs"]["description"],
+            );
+        }
+    }
+}
+"#;
+
+fn stack_crate(name: &str, source: &str) -> TempCrate {
+    let hyperstack = format!(
+        "hyperstack = {{ path = \"{}\" }}",
+        escape_path(&hyperstack_dir())
+    );
+    let hyperstack_macros = format!(
+        "hyperstack-macros = {{ path = \"{}\" }}",
+        escape_path(&macro_manifest_dir())
+    );
+    let serde = "serde = { version = \"1\", features = [\"derive\"] }".to_string();
+
+    let common_manifest = cargo_toml(
+        "common_sections",
+        &[hyperstack_macros.clone(), serde.clone()],
+    )
+    .replace("[workspace]\n", "")
+    .replace(
+        "[dependencies]",
+        "[lib]\npath = \"lib.rs\"\n\n[dependencies]",
+    );
+
+    TempCrate::new(
+        "shared-sections-dynamic",
+        name,
+        cargo_toml(
+            name,
+            &[
+                hyperstack,
+                hyperstack_macros,
+                serde,
+                "common_sections = { path = \"common_sections\" }".to_string(),
+            ],
+        ),
+        source,
+        &[
+            ("common_sections/Cargo.toml", &common_manifest),
+            ("common_sections/lib.rs", COMMON_SECTIONS),
+        ],
+    )
+}
+
+#[test]
+fn stacks_share_an_external_section() {
+    let temp_crate = stack_crate("stacks_share_an_external_section", SOURCE);
+
+    let output = temp_crate.cargo_run();
+    assert!(
+        output.status.success(),
+        "expected cargo run to succeed, stderr:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in [
+        "Pools.base: \"TokenMetadata\" [name,symbol,decimals] \"Metadata of a token\"",
+        "Pools.quote: \"TokenMetadata\" [name,symbol,decimals] \"Metadata of a token\"",
+        "Vaults.token: \"TokenMetadata\" [name,symbol,decimals] \"Token held by the vault\"",
+    ] {
+        assert!(
+            stdout.lines().any(|l| l == line),
+            "missing {line}:\n{stdout}"
+        );
+    }
+}
+
+#[test]
+fn unknown_external_section_is_rejected() {
+    let source = SOURCE.replace(
+        "common_sections::TokenMetadata\")]\n        pub token",
+        "common_sections::TokenInfo\")]\n        pub token",
+    );
+    let temp_crate = stack_crate("unknown_external_section_is_rejected", &source);
+
+    let output = temp_crate.cargo_check();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("__hyperstack_section_TokenInfo"),
+        "unexpected stderr:\n{stderr}"
+    );
+}
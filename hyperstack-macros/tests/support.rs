@@ -107,4 +107,17 @@ impl TempCrate {
             .output()
             .expect("run cargo run")
     }
+
+    #[allow(dead_code)]
+    pub fn cargo_run_with_features(&self, features: &[&str]) -> Output {
+        Command::new("cargo")
+            .arg("run")
+            .arg("--quiet")
+            .arg("--features")
+            .arg(features.join(","))
+            .current_dir(self.path())
+            .env("CARGO_TARGET_DIR", self.workspace_root.join("target"))
+            .output()
+            .expect("run cargo run")
+    }
 }
@@ -1,7 +1,27 @@
+use std::path::Path;
+
 #[test]
 fn ui() {
+    // The IDL fixtures live outside the trybuild project, so tests name them
+    // through `idl = env!(...)`
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/ui/idl_errors/fixtures");
+    for (var, file) in [
+        ("HYPERSTACK_UI_MISSPELLED_IDL", "truncate.json"),
+        ("HYPERSTACK_UI_TRUNCATED_IDL", "truncated.json"),
+        (
+            "HYPERSTACK_UI_MISSING_SECTIONS_IDL",
+            "missing_sections.json",
+        ),
+        ("HYPERSTACK_UI_WRONG_SHAPE_IDL", "wrong_shape.json"),
+        ("HYPERSTACK_UI_PINNED_IDL", "pinned.json"),
+    ] {
+        std::env::set_var(var, fixtures.join(file));
+    }
+    std::env::remove_var("HYPERSTACK_UI_UNSET_IDL");
+
     let tests = trybuild::TestCases::new();
     tests.pass("tests/ui/pass/*.rs");
+    tests.compile_fail("tests/ui/idl_errors/*.rs");
     tests.compile_fail("tests/ui/map_errors/*.rs");
     tests.compile_fail("tests/ui/resolve_errors/*.rs");
     tests.compile_fail("tests/ui/validation_errors/*.rs");
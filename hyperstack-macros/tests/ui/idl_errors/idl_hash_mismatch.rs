@@ -0,0 +1,9 @@
+use hyperstack_macros::hyperstack;
+
+#[hyperstack(
+    idl = env!("HYPERSTACK_UI_PINNED_IDL"),
+    idl_hash = "sha256:0000000000000000000000000000000000000000000000000000000000000000"
+)]
+mod stream {}
+
+fn main() {}
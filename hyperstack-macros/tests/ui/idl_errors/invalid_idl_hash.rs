@@ -0,0 +1,6 @@
+use hyperstack_macros::hyperstack;
+
+#[hyperstack(idl = env!("HYPERSTACK_UI_PINNED_IDL"), idl_hash = "md5:d41d8cd98f00b204e9800998ecf8427e")]
+mod stream {}
+
+fn main() {}
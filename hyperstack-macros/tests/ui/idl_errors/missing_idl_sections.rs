@@ -0,0 +1,6 @@
+use hyperstack_macros::hyperstack;
+
+#[hyperstack(idl = env!("HYPERSTACK_UI_MISSING_SECTIONS_IDL"))]
+mod stream {}
+
+fn main() {}
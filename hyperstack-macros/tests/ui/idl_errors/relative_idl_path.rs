@@ -0,0 +1,6 @@
+use hyperstack_macros::hyperstack;
+
+#[hyperstack(idl = "idls/fake.json")]
+mod stream {}
+
+fn main() {}
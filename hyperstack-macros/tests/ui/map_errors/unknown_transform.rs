@@ -0,0 +1,11 @@
+use hyperstack_macros::hyperstack;
+
+#[hyperstack]
+struct Broken {
+    #[map(token::Mint::address, primary_key)]
+    id: String,
+    #[map(token::Mint::supply, transform = Base59Encode)]
+    value: u64,
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use hyperstack_macros::hyperstack;
+
+#[hyperstack]
+mod broken {
+    #[derive(hyperstack_macros::Stream)]
+    struct Balances {
+        /// Balance held by the thing
+        #[doc_meta(units = "lamports")]
+        value: u64,
+    }
+
+    #[entity(name = "Thing")]
+    struct Thing {
+        balances: Balances,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use hyperstack_macros::hyperstack;
+
+#[hyperstack]
+mod broken {
+    #[entity(name = "Thing", key_normalizer = key.to_lower())]
+    struct Thing {
+        base: u64,
+    }
+}
+
+fn main() {}
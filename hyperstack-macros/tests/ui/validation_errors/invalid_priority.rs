@@ -0,0 +1,11 @@
+use hyperstack_macros::hyperstack;
+
+#[hyperstack]
+mod broken {
+    #[entity(name = "Thing", priority = urgent)]
+    struct Thing {
+        base: u64,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use hyperstack_macros::hyperstack;
+
+#[hyperstack]
+mod broken {
+    #[entity(name = "Thing", trace_fields = ["ghost.value"])]
+    struct Thing {
+        base: u64,
+    }
+}
+
+fn main() {}
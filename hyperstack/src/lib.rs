@@ -250,6 +250,110 @@ pub mod runtime {
             }
         }
     }
+
+    /// Transaction-level metadata attached to instruction events.
+    ///
+    /// Instruction event values carry it under `__transaction`, where
+    /// `#[from_transaction]` mappings read it from.
+    pub mod transaction {
+        use serde_json::{Map, Value};
+        use yellowstone_vixen_core::instruction::{InstructionShared, InstructionUpdate};
+
+        /// Key of the transaction section in an instruction event value
+        pub const SECTION: &str = "__transaction";
+
+        /// Build the transaction section. Fields the update does not carry
+        /// (an empty signature, no static keys, no compute unit count) are left out.
+        pub fn to_value(shared: &InstructionShared) -> Value {
+            let mut section = Map::new();
+            if !shared.signature.is_empty() {
+                section.insert(
+                    "signature".to_string(),
+                    Value::String(bs58::encode(&shared.signature).into_string()),
+                );
+            }
+            // The fee payer is always the first static account key
+            if let Some(payer) = shared.accounts.static_keys.first() {
+                section.insert(
+                    "fee_payer".to_string(),
+                    Value::String(bs58::encode(payer).into_string()),
+                );
+            }
+            section.insert("fee".to_string(), Value::from(shared.fee));
+            if let Some(units) = shared.compute_units_consumed {
+                section.insert("compute_units".to_string(), Value::from(units));
+            }
+            Value::Object(section)
+        }
+
+        /// Attach the transaction section of `update` to an instruction event value
+        pub fn attach(event_value: &mut Value, update: &InstructionUpdate) {
+            if let Some(obj) = event_value.as_object_mut() {
+                obj.insert(SECTION.to_string(), to_value(&update.shared));
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::*;
+            use serde_json::json;
+            use std::sync::Arc;
+            use yellowstone_vixen_core::instruction::AccountKeys;
+            use yellowstone_vixen_core::KeyBytes;
+
+            fn update(shared: InstructionShared) -> InstructionUpdate {
+                InstructionUpdate {
+                    program: KeyBytes([9; 32]),
+                    accounts: vec![KeyBytes([1; 32]), KeyBytes([2; 32])],
+                    data: vec![],
+                    shared: Arc::new(shared),
+                    inner: vec![],
+                }
+            }
+
+            #[test]
+            fn attaches_fee_payer_fee_and_compute_units() {
+                let payer = [7u8; 32];
+                let update = update(InstructionShared {
+                    signature: vec![3; 64],
+                    fee: 5_000,
+                    compute_units_consumed: Some(42_000),
+                    accounts: AccountKeys {
+                        static_keys: vec![payer.to_vec(), vec![1; 32]],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+
+                let mut value = json!({ "data": { "amount": 1 }, "accounts": {} });
+                attach(&mut value, &update);
+
+                assert_eq!(
+                    value[SECTION],
+                    json!({
+                        "signature": bs58::encode([3u8; 64]).into_string(),
+                        "fee_payer": bs58::encode(payer).into_string(),
+                        "fee": 5_000,
+                        "compute_units": 42_000,
+                    })
+                );
+                assert_eq!(value["data"]["amount"], 1);
+            }
+
+            #[test]
+            fn leaves_out_metadata_the_update_does_not_carry() {
+                let update = update(InstructionShared {
+                    fee: 5_000,
+                    ..Default::default()
+                });
+
+                let mut value = json!({ "data": {} });
+                attach(&mut value, &update);
+
+                assert_eq!(value[SECTION], json!({ "fee": 5_000 }));
+            }
+        }
+    }
 }
 
 pub mod resolvers {
@@ -271,7 +375,7 @@ pub mod prelude {
     pub use hyperstack_interpreter::resolvers::TokenMetadata;
 
     #[cfg(feature = "macros")]
-    pub use hyperstack_macros::{hyperstack, Stream};
+    pub use hyperstack_macros::{hyperstack, SharedSection, Stream};
 
     // Re-export server components
     #[cfg(feature = "server")]
@@ -0,0 +1,266 @@
+//! Event processing on a computed-heavy entity.
+//!
+//! Besides timings, prints the heap allocations made per event, which is what
+//! path interning and the lightweight computed evaluator cut down.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use hyperstack_interpreter::ast::{
+    BinaryOp, ComputedExpr, ComputedFieldSpec, FieldPath, IdentitySpec, KeyResolutionStrategy,
+    MappingSource, PopulationStrategy, SourceSpec, TypedFieldMapping, TypedHandlerSpec,
+    TypedStreamSpec,
+};
+use hyperstack_interpreter::compiler::MultiEntityBytecode;
+use hyperstack_interpreter::vm::VmContext;
+use serde_json::{json, Value};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const SOURCE_FIELDS: [&str; 6] = [
+    "buy_volume",
+    "sell_volume",
+    "buy_count",
+    "sell_count",
+    "fees",
+    "rewards",
+];
+
+fn field(path: &str) -> Box<ComputedExpr> {
+    Box::new(ComputedExpr::UnwrapOr {
+        expr: Box::new(ComputedExpr::FieldRef {
+            path: path.to_string(),
+        }),
+        default: json!(0),
+    })
+}
+
+fn binary(op: BinaryOp, left: Box<ComputedExpr>, right: Box<ComputedExpr>) -> Box<ComputedExpr> {
+    Box::new(ComputedExpr::Binary { op, left, right })
+}
+
+/// Twelve computed fields over the six mapped ones, some reading others
+fn computed_specs() -> Vec<ComputedFieldSpec> {
+    let mut specs = Vec::new();
+    let mut push = |target: &str, expression: Box<ComputedExpr>| {
+        specs.push(ComputedFieldSpec {
+            target_path: target.to_string(),
+            expression: *expression,
+            result_type: "Option<u64>".to_string(),
+            time_dependent: false,
+        });
+    };
+    push(
+        "totals.volume",
+        binary(
+            BinaryOp::Add,
+            field("trading.buy_volume"),
+            field("trading.sell_volume"),
+        ),
+    );
+    push(
+        "totals.trades",
+        binary(
+            BinaryOp::Add,
+            field("trading.buy_count"),
+            field("trading.sell_count"),
+        ),
+    );
+    push(
+        "totals.net_volume",
+        binary(
+            BinaryOp::Sub,
+            field("trading.buy_volume"),
+            field("trading.sell_volume"),
+        ),
+    );
+    push(
+        "totals.income",
+        binary(
+            BinaryOp::Add,
+            field("trading.fees"),
+            field("trading.rewards"),
+        ),
+    );
+    for (i, source) in SOURCE_FIELDS.iter().enumerate() {
+        push(
+            &format!("derived.scaled_{source}"),
+            binary(
+                BinaryOp::Mul,
+                field(&format!("trading.{source}")),
+                Box::new(ComputedExpr::Literal {
+                    value: json!(i + 2),
+                }),
+            ),
+        );
+    }
+    push(
+        "derived.average_trade",
+        binary(
+            BinaryOp::Div,
+            field("totals.volume"),
+            binary(
+                BinaryOp::Add,
+                field("totals.trades"),
+                Box::new(ComputedExpr::Literal { value: json!(1) }),
+            ),
+        ),
+    );
+    push(
+        "derived.income_per_trade",
+        binary(
+            BinaryOp::Div,
+            field("totals.income"),
+            binary(
+                BinaryOp::Add,
+                field("totals.trades"),
+                Box::new(ComputedExpr::Literal { value: json!(1) }),
+            ),
+        ),
+    );
+    specs
+}
+
+fn bytecode() -> MultiEntityBytecode {
+    let mut mappings = vec![TypedFieldMapping::new(
+        "id.market".to_string(),
+        MappingSource::FromSource {
+            path: FieldPath::new(&["market"]),
+            default: None,
+            transform: None,
+        },
+        PopulationStrategy::SetOnce,
+    )];
+    for source in SOURCE_FIELDS {
+        mappings.push(TypedFieldMapping::new(
+            format!("trading.{source}"),
+            MappingSource::FromSource {
+                path: FieldPath::new(&[source]),
+                default: None,
+                transform: None,
+            },
+            PopulationStrategy::Sum,
+        ));
+    }
+    let handler = TypedHandlerSpec::new(
+        SourceSpec::Source {
+            program_id: None,
+            discriminator: None,
+            type_name: "TradeState".to_string(),
+            serialization: None,
+            is_account: true,
+        },
+        KeyResolutionStrategy::Embedded {
+            primary_field: FieldPath::new(&["market"]),
+        },
+        mappings,
+        true,
+    );
+    let specs = computed_specs();
+    let mut spec = TypedStreamSpec::<Value>::new(
+        "Market".to_string(),
+        IdentitySpec {
+            primary_keys: vec!["id.market".to_string()],
+            lookup_indexes: vec![],
+        },
+        vec![handler],
+    );
+    spec.computed_fields = specs.iter().map(|s| s.target_path.clone()).collect();
+
+    MultiEntityBytecode::new()
+        .add_entity_with_evaluator(
+            "Market".to_string(),
+            spec,
+            0,
+            Some(VmContext::create_evaluator_from_specs(specs)),
+        )
+        .build()
+}
+
+fn event(n: u64) -> Value {
+    json!({
+        "market": format!("market-{}", n % 64),
+        "buy_volume": n * 3,
+        "sell_volume": n * 2,
+        "buy_count": 1,
+        "sell_count": n % 2,
+        "fees": n % 7,
+        "rewards": n % 11,
+    })
+}
+
+fn report_allocations(bytecode: &MultiEntityBytecode) {
+    const EVENTS: u64 = 10_000;
+    let mut vm = VmContext::new();
+    // Warm up so every market exists and every path is compiled
+    for n in 0..EVENTS {
+        vm.process_event(bytecode, event(n), "TradeState", None, None)
+            .unwrap();
+    }
+    let events: Vec<Value> = (0..EVENTS).map(event).collect();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for event in events {
+        black_box(
+            vm.process_event(bytecode, event, "TradeState", None, None)
+                .unwrap(),
+        );
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "computed_fields: {:.1} allocations per event",
+        allocations as f64 / EVENTS as f64
+    );
+}
+
+fn bench_computed_fields(c: &mut Criterion) {
+    let bytecode = bytecode();
+    report_allocations(&bytecode);
+
+    let mut vm = VmContext::new();
+    let mut n = 0u64;
+    c.bench_function("process_event/computed_fields", |b| {
+        b.iter(|| {
+            n += 1;
+            black_box(
+                vm.process_event(&bytecode, event(n), "TradeState", None, None)
+                    .unwrap(),
+            )
+        })
+    });
+
+    let evaluator = VmContext::create_evaluator_from_specs(computed_specs());
+    let mut state = json!({});
+    vm.process_event(&bytecode, event(1), "TradeState", None, None)
+        .unwrap();
+    if let Some(current) = vm.get_entity_state(0, &json!("market-1")) {
+        state = current;
+    }
+    c.bench_function("evaluate/computed_fields", |b| {
+        b.iter(|| evaluator(black_box(&mut state), Some(1), 0).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_computed_fields);
+criterion_main!(benches);
@@ -0,0 +1,401 @@
+//! Aggregates over append arrays for computed fields.
+//!
+//! `array_sum(path)`, `array_last(path, field)` and
+//! `array_window_avg(path, field, n)` read an `Append` array. Scanning the
+//! array on every update costs O(n), so the `AppendToArray` that writes the
+//! array also keeps a running accumulator for each sum and window average.
+//! Accumulators live in the entity state under the hidden
+//! `__array_aggregates` field. They are never marked dirty, so they stay out
+//! of mutations, and they are kept and restored with the rest of the state.
+//!
+//! When the array cap drops items from the front, their values are taken
+//! back out of the accumulators, so the aggregates always cover exactly the
+//! items still in the array. An accumulator records the array length it was
+//! built for. One that doesn't match, such as state written before the
+//! aggregate was declared or an array overwritten as a whole, is rebuilt
+//! from the array on the next append and ignored when evaluating.
+
+use crate::ast::{ArrayAggregateKind, ArrayAggregateSpec, ComputedExpr};
+use serde_json::{json, Map, Number, Value};
+
+/// Entity state field holding the accumulators, keyed by aggregate
+pub const ACCUMULATORS_FIELD: &str = "__array_aggregates";
+
+/// Running sum over the items in an aggregate's window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Accumulator {
+    /// Length of the array the accumulator was built for
+    len: usize,
+    /// Items in the window
+    count: usize,
+    /// Items in the window with a numeric value
+    numeric: usize,
+    total: Total,
+}
+
+/// Exact while every value is an integer, floating point after that
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Total {
+    Int(i128),
+    Float(f64),
+}
+
+impl Total {
+    fn add(self, value: &Number, sign: i128) -> Self {
+        let int = value
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| value.as_u64().map(i128::from));
+        match (self, int) {
+            (Total::Int(total), Some(int)) => Total::Int(total + sign * int),
+            _ => Total::Float(self.as_f64() + sign as f64 * value.as_f64().unwrap_or(0.0)),
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Total::Int(total) => total as f64,
+            Total::Float(total) => total,
+        }
+    }
+
+    fn to_value(self) -> Value {
+        match self {
+            Total::Int(total) => u64::try_from(total)
+                .map(Value::from)
+                .or_else(|_| i64::try_from(total).map(Value::from))
+                .unwrap_or_else(|_| json!(total as f64)),
+            Total::Float(total) => json!(total),
+        }
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        let number = value.as_number()?;
+        Some(Total::Int(0).add(number, 1))
+    }
+}
+
+impl Accumulator {
+    fn empty() -> Self {
+        Self {
+            len: 0,
+            count: 0,
+            numeric: 0,
+            total: Total::Int(0),
+        }
+    }
+
+    /// Accumulate the window of `spec` over `items` from scratch
+    fn rebuild(spec: &ArrayAggregateSpec, items: &[Value]) -> Self {
+        let window = window_len(spec).min(items.len());
+        let mut accumulator = Self::empty();
+        for item in &items[items.len() - window..] {
+            accumulator.include(spec, item);
+        }
+        accumulator.len = items.len();
+        accumulator
+    }
+
+    fn include(&mut self, spec: &ArrayAggregateSpec, item: &Value) {
+        self.count += 1;
+        if let Some(value) = item_value(spec, item).and_then(Value::as_number) {
+            self.numeric += 1;
+            self.total = self.total.add(value, 1);
+        }
+    }
+
+    fn exclude(&mut self, spec: &ArrayAggregateSpec, item: &Value) {
+        self.count -= 1;
+        if let Some(value) = item_value(spec, item).and_then(Value::as_number) {
+            self.numeric -= 1;
+            self.total = self.total.add(value, -1);
+        }
+    }
+
+    fn result(&self, kind: ArrayAggregateKind) -> Option<Value> {
+        match kind {
+            ArrayAggregateKind::Sum => Some(self.total.to_value()),
+            ArrayAggregateKind::WindowAvg { .. } if self.numeric > 0 => {
+                Some(json!(self.total.as_f64() / self.numeric as f64))
+            }
+            ArrayAggregateKind::WindowAvg { .. } | ArrayAggregateKind::Last => None,
+        }
+    }
+
+    fn to_value(self) -> Value {
+        json!({
+            "len": self.len,
+            "count": self.count,
+            "numeric": self.numeric,
+            "total": self.total.to_value(),
+        })
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        let count = |field: &str| value.get(field)?.as_u64().map(|n| n as usize);
+        Some(Self {
+            len: count("len")?,
+            count: count("count")?,
+            numeric: count("numeric")?,
+            total: Total::from_value(value.get("total")?)?,
+        })
+    }
+}
+
+/// Key of an aggregate's accumulator under [`ACCUMULATORS_FIELD`]
+pub fn accumulator_key(spec: &ArrayAggregateSpec) -> String {
+    let kind = match spec.kind {
+        ArrayAggregateKind::Sum => "sum".to_string(),
+        ArrayAggregateKind::Last => "last".to_string(),
+        ArrayAggregateKind::WindowAvg { n } => format!("window_avg({})", n),
+    };
+    match &spec.field {
+        Some(field) => format!("{}:{}:{}", kind, spec.path, field),
+        None => format!("{}:{}", kind, spec.path),
+    }
+}
+
+/// Whether an aggregate keeps an accumulator. `array_last` reads the array's
+/// last item directly.
+pub fn is_accumulated(spec: &ArrayAggregateSpec) -> bool {
+    !matches!(spec.kind, ArrayAggregateKind::Last)
+}
+
+/// The accumulator stored in `state` for an aggregate, if any
+pub(crate) fn load(state: &Map<String, Value>, spec: &ArrayAggregateSpec) -> Option<Accumulator> {
+    state
+        .get(ACCUMULATORS_FIELD)?
+        .get(accumulator_key(spec))
+        .and_then(Accumulator::from_value)
+}
+
+pub(crate) fn store(
+    state: &mut Map<String, Value>,
+    spec: &ArrayAggregateSpec,
+    accumulator: Accumulator,
+) {
+    let accumulators = state
+        .entry(ACCUMULATORS_FIELD.to_string())
+        .or_insert_with(|| json!({}));
+    if !accumulators.is_object() {
+        *accumulators = json!({});
+    }
+    if let Some(accumulators) = accumulators.as_object_mut() {
+        accumulators.insert(accumulator_key(spec), accumulator.to_value());
+    }
+}
+
+/// Advance an accumulator over one append.
+///
+/// `items` is the array right after the push, before the cap removes
+/// `dropped` items from its front. `previous` is the accumulator as it was
+/// before the push; it is rebuilt when it doesn't match the array.
+pub(crate) fn advance(
+    spec: &ArrayAggregateSpec,
+    previous: Option<Accumulator>,
+    items: &[Value],
+    dropped: usize,
+) -> Accumulator {
+    let Some((appended, before)) = items.split_last() else {
+        return Accumulator::empty();
+    };
+    let mut accumulator = match previous {
+        Some(previous) if previous.len == before.len() => previous,
+        _ => Accumulator::rebuild(spec, before),
+    };
+
+    let len = items.len();
+    let window = window_len(spec);
+    accumulator.include(spec, appended);
+    if accumulator.count > window {
+        accumulator.exclude(spec, &items[len - 1 - window]);
+    }
+
+    // Items falling off the front that were still in the window
+    let window_start = len - accumulator.count;
+    for item in items.iter().take(dropped).skip(window_start) {
+        accumulator.exclude(spec, item);
+    }
+    accumulator.len = len - dropped;
+    accumulator
+}
+
+/// Evaluate an aggregate against the entity state.
+///
+/// Returns `None` when the array doesn't exist, is empty for `array_last`,
+/// or has no numeric values in the window for `array_window_avg`.
+pub fn evaluate(state: &Value, spec: &ArrayAggregateSpec) -> Option<Value> {
+    let items = spec
+        .path
+        .split('.')
+        .try_fold(state, |value, segment| value.get(segment))?
+        .as_array()?;
+
+    if !is_accumulated(spec) {
+        return items
+            .last()
+            .and_then(|item| item_value(spec, item))
+            .filter(|value| !value.is_null())
+            .cloned();
+    }
+
+    let stored = state.as_object().and_then(|state| load(state, spec));
+    let accumulator = match stored {
+        Some(accumulator) if accumulator.len == items.len() => accumulator,
+        _ => Accumulator::rebuild(spec, items),
+    };
+    accumulator.result(spec.kind)
+}
+
+/// Every array aggregate used by a computed expression
+pub fn collect_array_aggregates<'a>(expr: &'a ComputedExpr, out: &mut Vec<&'a ArrayAggregateSpec>) {
+    match expr {
+        ComputedExpr::ArrayAggregate { aggregate } => out.push(aggregate),
+        ComputedExpr::FieldRef { .. }
+        | ComputedExpr::Literal { .. }
+        | ComputedExpr::None
+        | ComputedExpr::Var { .. }
+        | ComputedExpr::ByteArray { .. }
+        | ComputedExpr::ContextSlot
+        | ComputedExpr::ContextTimestamp
+        | ComputedExpr::Now => {}
+        ComputedExpr::UnwrapOr { expr, .. }
+        | ComputedExpr::Cast { expr, .. }
+        | ComputedExpr::Paren { expr }
+        | ComputedExpr::Some { value: expr }
+        | ComputedExpr::Slice { expr, .. }
+        | ComputedExpr::Index { expr, .. }
+        | ComputedExpr::U64FromLeBytes { bytes: expr }
+        | ComputedExpr::U64FromBeBytes { bytes: expr }
+        | ComputedExpr::JsonToBytes { expr }
+        | ComputedExpr::Keccak256 { expr }
+        | ComputedExpr::SlotsToSecs { expr }
+        | ComputedExpr::SecsToSlots { expr }
+        | ComputedExpr::Unary { expr, .. }
+        | ComputedExpr::Closure { body: expr, .. } => collect_array_aggregates(expr, out),
+        ComputedExpr::Binary { left, right, .. } => {
+            collect_array_aggregates(left, out);
+            collect_array_aggregates(right, out);
+        }
+        ComputedExpr::MethodCall { expr, args, .. } => {
+            collect_array_aggregates(expr, out);
+            for arg in args {
+                collect_array_aggregates(arg, out);
+            }
+        }
+        ComputedExpr::ResolverComputed { args, .. } => {
+            for arg in args {
+                collect_array_aggregates(arg, out);
+            }
+        }
+        ComputedExpr::Let { value, body, .. } => {
+            collect_array_aggregates(value, out);
+            collect_array_aggregates(body, out);
+        }
+        ComputedExpr::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            collect_array_aggregates(condition, out);
+            collect_array_aggregates(then_branch, out);
+            collect_array_aggregates(else_branch, out);
+        }
+    }
+}
+
+fn window_len(spec: &ArrayAggregateSpec) -> usize {
+    match spec.kind {
+        ArrayAggregateKind::WindowAvg { n } => n,
+        ArrayAggregateKind::Sum | ArrayAggregateKind::Last => usize::MAX,
+    }
+}
+
+fn item_value<'a>(spec: &ArrayAggregateSpec, item: &'a Value) -> Option<&'a Value> {
+    match &spec.field {
+        Some(field) => field
+            .split('.')
+            .try_fold(item, |value, segment| value.get(segment)),
+        None => Some(item),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(kind: ArrayAggregateKind, field: Option<&str>) -> ArrayAggregateSpec {
+        ArrayAggregateSpec {
+            kind,
+            path: "trades.history".to_string(),
+            field: field.map(str::to_string),
+        }
+    }
+
+    /// Append `values` one by one under a cap, like `AppendToArray` does
+    fn append_all(spec: &ArrayAggregateSpec, values: &[Value], cap: usize) -> Value {
+        let mut state = json!({ "trades": { "history": [] } });
+        for value in values {
+            let previous = load(state.as_object().unwrap(), spec);
+            let items = state["trades"]["history"].as_array_mut().unwrap();
+            items.push(value.clone());
+            let dropped = items.len().saturating_sub(cap);
+            let accumulator = advance(spec, previous, items, dropped);
+            items.drain(0..dropped);
+            store(state.as_object_mut().unwrap(), spec, accumulator);
+        }
+        state
+    }
+
+    #[test]
+    fn sum_forgets_items_dropped_by_the_cap() {
+        let spec = spec(ArrayAggregateKind::Sum, Some("size"));
+        let trades: Vec<Value> = (1..=7).map(|size| json!({ "size": size })).collect();
+
+        let state = append_all(&spec, &trades, 4);
+
+        // 4 + 5 + 6 + 7
+        assert_eq!(evaluate(&state, &spec), Some(json!(22)));
+        let stored = load(state.as_object().unwrap(), &spec).unwrap();
+        assert_eq!(stored, Accumulator::rebuild(&spec, &trades[3..]));
+    }
+
+    #[test]
+    fn window_average_across_the_cap() {
+        let values: Vec<Value> = [10, 20, 30, 40, 50].iter().map(|v| json!(v)).collect();
+
+        // Window smaller than the cap: the cap never reaches into it
+        let small = spec(ArrayAggregateKind::WindowAvg { n: 2 }, None);
+        assert_eq!(
+            evaluate(&append_all(&small, &values, 3), &small),
+            Some(json!(45.0))
+        );
+
+        // Window larger than the cap: it shrinks to the items still there
+        let large = spec(ArrayAggregateKind::WindowAvg { n: 10 }, None);
+        assert_eq!(
+            evaluate(&append_all(&large, &values, 3), &large),
+            Some(json!(40.0))
+        );
+    }
+
+    #[test]
+    fn stale_accumulator_is_rebuilt_from_the_array() {
+        let spec = spec(ArrayAggregateKind::Sum, None);
+        let mut state = append_all(&spec, &[json!(1), json!(2)], 10);
+
+        // The array was overwritten without going through an append
+        state["trades"]["history"] = json!([5, 6, 7]);
+
+        assert_eq!(evaluate(&state, &spec), Some(json!(18)));
+    }
+
+    #[test]
+    fn last_reads_the_newest_item() {
+        let spec = spec(ArrayAggregateKind::Last, Some("price"));
+        let state = json!({ "trades": { "history": [{ "price": 1.5 }, { "price": 2.5 }] } });
+
+        assert_eq!(evaluate(&state, &spec), Some(json!(2.5)));
+        assert_eq!(evaluate(&json!({ "trades": {} }), &spec), None);
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::marker::PhantomData;
 
 pub use hyperstack_idl::snapshot::*;
@@ -97,13 +97,54 @@ pub enum Transformation {
     HexDecode,
     Base58Encode,
     Base58Decode,
+    Base64Encode,
+    Base64Decode,
     ToString,
     ToNumber,
+    /// Elements `start..end` of an array, e.g. a range of bytes
+    Slice {
+        start: usize,
+        end: usize,
+    },
+    /// The first `n` elements of an array, or the whole array if shorter
+    Take(usize),
+    /// Transformations applied in order, each to the previous one's output
+    Chain(Vec<Transformation>),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl std::fmt::Display for Transformation {
+    /// Formats the transformation the way it's written in `#[map(transform = ...)]`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transformation::HexEncode => write!(f, "hex_encode"),
+            Transformation::HexDecode => write!(f, "hex_decode"),
+            Transformation::Base58Encode => write!(f, "base58_encode"),
+            Transformation::Base58Decode => write!(f, "base58_decode"),
+            Transformation::Base64Encode => write!(f, "base64_encode"),
+            Transformation::Base64Decode => write!(f, "base64_decode"),
+            Transformation::ToString => write!(f, "to_string"),
+            Transformation::ToNumber => write!(f, "to_number"),
+            Transformation::Slice { start, end } => write!(f, "slice({}, {})", start, end),
+            Transformation::Take(n) => write!(f, "take({})", n),
+            Transformation::Chain(stages) => {
+                write!(f, "[")?;
+                for (i, stage) in stages.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", stage)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// Write policy applied when a mapping updates its target field
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum PopulationStrategy {
     SetOnce,
+    #[default]
     LastWrite,
     Append,
     Merge,
@@ -117,6 +158,9 @@ pub enum PopulationStrategy {
     /// Track unique values and store the count
     /// Internally maintains a HashSet, exposes only the count
     UniqueCount,
+    /// Unset the field. The mapped value is ignored; the field becomes null
+    /// and the patch carries the null so caches and clients drop it
+    Clear,
 }
 
 // ============================================================================
@@ -132,6 +176,11 @@ pub struct ComputedFieldSpec {
     pub expression: ComputedExpr,
     /// Result type (e.g., "Option<u64>", "Option<f64>")
     pub result_type: String,
+    /// True when the value depends on evaluation time (`now()`), directly or
+    /// through another computed field. Such fields are not reproducible on
+    /// replay and must be re-evaluated on every update.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub time_dependent: bool,
 }
 
 // ============================================================================
@@ -181,6 +230,37 @@ pub struct ResolverExtractSpec {
     pub source_path: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub transform: Option<Transformation>,
+    /// Status emitted at `target_path` as `{"status": placeholder}` while the
+    /// resolver is pending
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
+}
+
+impl ResolverExtractSpec {
+    /// Value held at `target_path` until the resolver returns
+    pub fn pending_value(&self) -> Option<Value> {
+        self.placeholder
+            .as_ref()
+            .map(|status| serde_json::json!({ "status": status }))
+    }
+
+    /// Whether `value`, read at `target_path`, still waits on the resolver:
+    /// unset, null or the placeholder
+    pub fn is_unresolved(&self, value: Option<&Value>) -> bool {
+        match value {
+            None | Some(Value::Null) => true,
+            Some(value) => self.pending_value().as_ref() == Some(value),
+        }
+    }
+}
+
+/// Placeholder status of the resolver-filled field at `target_path`, if any
+pub fn resolver_placeholder<'a>(specs: &'a [ResolverSpec], target_path: &str) -> Option<&'a str> {
+    specs
+        .iter()
+        .flat_map(|spec| &spec.extracts)
+        .find(|extract| extract.target_path == target_path)
+        .and_then(|extract| extract.placeholder.as_deref())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
@@ -344,12 +424,54 @@ pub enum ComputedExpr {
     ContextSlot,
     /// Access the unix timestamp from the current update context
     ContextTimestamp,
+    /// Evaluation-time unix seconds: the update context timestamp during event
+    /// processing, the wall clock otherwise
+    Now,
+
+    // Slot/time conversions using the configured slot duration
+    SlotsToSecs {
+        expr: Box<ComputedExpr>,
+    },
+    SecsToSlots {
+        expr: Box<ComputedExpr>,
+    },
 
     /// Keccak256 hash function for computing Ethereum-compatible hashes
     /// Takes a byte array expression and returns the 32-byte hash as a Vec<u8>
     Keccak256 {
         expr: Box<ComputedExpr>,
     },
+
+    /// Aggregate over an append array: `array_sum`, `array_last` or
+    /// `array_window_avg`
+    ArrayAggregate {
+        aggregate: ArrayAggregateSpec,
+    },
+}
+
+/// Aggregate over the items of an append array.
+///
+/// Sums and window averages are maintained incrementally by the append that
+/// writes the array, rather than by rescanning it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ArrayAggregateSpec {
+    pub kind: ArrayAggregateKind,
+    /// Path of the append array, e.g. "trades.history"
+    pub path: String,
+    /// Field of each item to aggregate; the item itself when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrayAggregateKind {
+    /// Sum over every item in the array
+    Sum,
+    /// The last item appended
+    Last,
+    /// Mean over the last `n` items
+    WindowAvg { n: usize },
 }
 
 /// Binary operators for computed expressions
@@ -421,6 +543,69 @@ pub struct SerializableStreamSpec {
     /// View definitions for derived/projected views
     #[serde(default)]
     pub views: Vec<ViewDef>,
+    /// Field paths recorded as attributes on per-event tracing spans
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trace_fields: Vec<String>,
+    /// Cargo feature of the stack crate that gates this entity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature: Option<String>,
+    /// How long this entity's updates survive load shedding
+    #[serde(default, skip_serializing_if = "EntityPriority::is_normal")]
+    pub priority: EntityPriority,
+    /// When the entity is deleted, `None` if it never is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delete_on: Option<DeleteTrigger>,
+    /// Rewrites the primary key before state is read or written
+    /// (`#[entity(key_normalizer = ...)]`), with the raw key bound to `key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_normalizer: Option<ComputedExpr>,
+    /// Documentation of the entity struct
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<ItemDocs>,
+    /// Event types routed to this entity by its handlers and instruction
+    /// hooks, sorted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event_types: Vec<String>,
+}
+
+/// Priority of an entity's updates when the server sheds load
+/// (`#[entity(priority = low|normal|high)]`). Low-priority entities are
+/// shed first, then normal ones; high-priority entities are never shed.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl EntityPriority {
+    pub fn is_normal(&self) -> bool {
+        *self == EntityPriority::Normal
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntityPriority::Low => "low",
+            EntityPriority::Normal => "normal",
+            EntityPriority::High => "high",
+        }
+    }
+}
+
+/// What deletes an entity (`#[entity(delete_on = "AccountClosed")]`).
+///
+/// A deleted entity is dropped from state and sent to clients as a
+/// tombstone. Updates from slots up to the deletion slot are ignored, so a
+/// late update can't bring it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeleteTrigger {
+    /// An account the entity was updated from is closed: its lamports drop
+    /// to zero or its data is emptied
+    AccountClosed,
 }
 
 #[derive(Debug, Clone)]
@@ -434,6 +619,13 @@ pub struct TypedStreamSpec<S> {
     pub instruction_hooks: Vec<InstructionHook>, // NEW: Instruction hooks for PDA registration
     pub resolver_specs: Vec<ResolverSpec>,
     pub computed_fields: Vec<String>, // List of computed field paths
+    pub array_aggregates: Vec<ArrayAggregateSpec>, // Aggregates kept up to date by appends
+    pub trace_fields: Vec<String>,    // Field paths recorded on per-event tracing spans
+    pub feature: Option<String>,      // Cargo feature gating the entity
+    pub priority: EntityPriority,     // Priority of the entity's updates under load
+    pub delete_on: Option<DeleteTrigger>, // What deletes the entity
+    pub key_normalizer: Option<ComputedExpr>, // Rewrites keys before state access
+    pub docs: Option<ItemDocs>,       // Documentation of the entity struct
     _phantom: PhantomData<S>,
 }
 
@@ -453,6 +645,13 @@ impl<S> TypedStreamSpec<S> {
             instruction_hooks: Vec::new(),
             resolver_specs: Vec::new(),
             computed_fields: Vec::new(),
+            array_aggregates: Vec::new(),
+            trace_fields: Vec::new(),
+            feature: None,
+            priority: EntityPriority::Normal,
+            delete_on: None,
+            key_normalizer: None,
+            docs: None,
             _phantom: PhantomData,
         }
     }
@@ -475,6 +674,13 @@ impl<S> TypedStreamSpec<S> {
             instruction_hooks: Vec::new(),
             resolver_specs: Vec::new(),
             computed_fields: Vec::new(),
+            array_aggregates: Vec::new(),
+            trace_fields: Vec::new(),
+            feature: None,
+            priority: EntityPriority::Normal,
+            delete_on: None,
+            key_normalizer: None,
+            docs: None,
             _phantom: PhantomData,
         }
     }
@@ -484,6 +690,31 @@ impl<S> TypedStreamSpec<S> {
         self
     }
 
+    pub fn with_array_aggregates(mut self, array_aggregates: Vec<ArrayAggregateSpec>) -> Self {
+        self.array_aggregates = array_aggregates;
+        self
+    }
+
+    pub fn with_trace_fields(mut self, trace_fields: Vec<String>) -> Self {
+        self.trace_fields = trace_fields;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: EntityPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_delete_on(mut self, trigger: DeleteTrigger) -> Self {
+        self.delete_on = Some(trigger);
+        self
+    }
+
+    pub fn with_key_normalizer(mut self, normalizer: ComputedExpr) -> Self {
+        self.key_normalizer = Some(normalizer);
+        self
+    }
+
     /// Get type information for a specific field path
     pub fn get_field_type(&self, path: &str) -> Option<&FieldTypeInfo> {
         self.field_mappings.get(path)
@@ -502,6 +733,12 @@ impl<S> TypedStreamSpec<S> {
         self.sections.iter().map(|s| &s.name).collect()
     }
 
+    /// Get the recipe for building this entity's canonical key
+    pub fn key_recipe(&self) -> Option<KeyRecipe> {
+        self.identity
+            .key_recipe(&self.field_mappings, &self.computed_fields)
+    }
+
     /// Convert to serializable format
     pub fn to_serializable(&self) -> SerializableStreamSpec {
         let mut spec = SerializableStreamSpec {
@@ -520,13 +757,35 @@ impl<S> TypedStreamSpec<S> {
             computed_field_specs: Vec::new(),
             content_hash: None,
             views: Vec::new(),
+            trace_fields: self.trace_fields.clone(),
+            feature: self.feature.clone(),
+            priority: self.priority,
+            delete_on: self.delete_on,
+            key_normalizer: self.key_normalizer.clone(),
+            docs: self.docs.clone(),
+            event_types: Vec::new(),
         };
+        spec.event_types = routed_event_types(&spec.handlers, &spec.instruction_hooks);
         spec.content_hash = Some(spec.compute_content_hash());
         spec
     }
 
     /// Create from serializable format
     pub fn from_serializable(spec: SerializableStreamSpec) -> Self {
+        let mut array_aggregates = Vec::new();
+        for computed in &spec.computed_field_specs {
+            crate::array_aggregate::collect_array_aggregates(
+                &computed.expression,
+                &mut array_aggregates,
+            );
+        }
+        let mut seen = HashSet::new();
+        let array_aggregates: Vec<ArrayAggregateSpec> = array_aggregates
+            .into_iter()
+            .filter(|aggregate| seen.insert(*aggregate))
+            .cloned()
+            .collect();
+
         TypedStreamSpec {
             state_name: spec.state_name,
             identity: spec.identity,
@@ -541,6 +800,13 @@ impl<S> TypedStreamSpec<S> {
             instruction_hooks: spec.instruction_hooks,
             resolver_specs: spec.resolver_specs,
             computed_fields: spec.computed_fields,
+            array_aggregates,
+            trace_fields: spec.trace_fields,
+            feature: spec.feature,
+            priority: spec.priority,
+            delete_on: spec.delete_on,
+            key_normalizer: spec.key_normalizer,
+            docs: spec.docs,
             _phantom: PhantomData,
         }
     }
@@ -552,10 +818,83 @@ pub struct IdentitySpec {
     pub lookup_indexes: Vec<LookupIndexSpec>,
 }
 
+impl IdentitySpec {
+    /// Describe the key this entity's state is stored under.
+    ///
+    /// The compiler keys entities by the value of the first primary key;
+    /// further primary keys name other fields holding that same value and are
+    /// not part of the key. The type is taken from `field_mappings`, a key
+    /// without type information is treated as a string. `None` when the
+    /// entity has no primary key.
+    pub fn key_recipe(
+        &self,
+        field_mappings: &BTreeMap<String, FieldTypeInfo>,
+        computed_fields: &[String],
+    ) -> Option<KeyRecipe> {
+        let path = self.primary_keys.first()?;
+        let field_name = path.rsplit('.').next().unwrap_or(path).to_string();
+        let base_type = field_mappings
+            .get(path)
+            .map(|info| info.base_type.clone())
+            .unwrap_or(BaseType::String);
+        Some(KeyRecipe {
+            field_path: path.clone(),
+            field_name,
+            base_type,
+            computed: computed_fields.contains(path),
+        })
+    }
+}
+
+/// Recipe for the canonical key string of an entity: the string form of its
+/// primary key value, as the server reports it in frames and accepts in
+/// `get()` and `listen()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyRecipe {
+    /// Full field path of the primary key (e.g. "id.round_id")
+    pub field_path: String,
+    /// Leaf field name (e.g. "round_id")
+    pub field_name: String,
+    pub base_type: BaseType,
+    /// True when the primary key is a computed field rather than a mapped one
+    pub computed: bool,
+}
+
+impl KeyRecipe {
+    /// The value the VM stores the entity under, from its canonical key
+    /// string. `None` when the string is not a value of the key's type.
+    pub fn key_value(&self, key: &str) -> Option<Value> {
+        match self.base_type {
+            BaseType::Integer | BaseType::Timestamp => {
+                serde_json::from_str::<serde_json::Number>(key)
+                    .ok()
+                    .filter(|number| !number.is_f64())
+                    .map(Value::Number)
+            }
+            BaseType::Boolean => key.parse().ok().map(Value::Bool),
+            _ => Some(Value::String(key.to_string())),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LookupIndexSpec {
     pub field_name: String,
     pub temporal_field: Option<String>,
+    /// Module-level shared index this field reads and writes instead of a
+    /// per-entity one, so several entities can share a single mapping.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_index: Option<String>,
+}
+
+impl LookupIndexSpec {
+    /// Last segment of the indexed field path (`id.mint` -> `mint`).
+    pub fn leaf_name(&self) -> &str {
+        self.field_name
+            .split('.')
+            .next_back()
+            .unwrap_or(&self.field_name)
+    }
 }
 
 // ============================================================================
@@ -583,6 +922,16 @@ pub enum ResolverStrategy {
 
     /// Extract primary key directly from account data (future)
     DirectField { field_path: FieldPath },
+
+    /// Key the entity by a field of this account's data (`#[entity(key_from = ...)]`).
+    ///
+    /// The account never waits on a reverse lookup: every update carries its
+    /// own key, and registers `account address -> key` in `index_name` so
+    /// sources that only know the address resolve to the same entity.
+    KeyFrom {
+        field_path: FieldPath,
+        index_name: String,
+    },
 }
 
 /// Declarative instruction hook specification
@@ -646,6 +995,19 @@ pub enum ParsedCondition {
         op: LogicalOp,
         conditions: Vec<ParsedCondition>,
     },
+
+    /// True when the entity state at `field` differs from its value before
+    /// the handler ran. `initial` decides whether a field with no previous
+    /// value counts as changed.
+    Changed {
+        field: FieldPath,
+        #[serde(default = "default_changed_initial")]
+        initial: bool,
+    },
+}
+
+fn default_changed_initial() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -771,6 +1133,7 @@ pub struct SerializableFieldMapping {
     pub target_path: String,
     pub source: MappingSource,
     pub transform: Option<Transformation>,
+    #[serde(default)]
     pub population: PopulationStrategy,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<ConditionExpr>,
@@ -780,6 +1143,22 @@ pub struct SerializableFieldMapping {
     pub stop: Option<String>,
     #[serde(default = "default_emit", skip_serializing_if = "is_true")]
     pub emit: bool,
+    /// Set when the source is an instruction account the IDL no longer has
+    /// under the name the mapping was written with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_fallback: Option<AccountFallback>,
+}
+
+/// An instruction account a mapping found by its position, because the IDL
+/// it was built against names the account differently
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountFallback {
+    pub instruction: String,
+    /// The name the mapping was written with
+    pub written: String,
+    /// The name the IDL gives the account at `index`
+    pub resolved: String,
+    pub index: usize,
 }
 
 fn default_emit() -> bool {
@@ -804,6 +1183,7 @@ pub struct TypedFieldMapping<S> {
     pub when: Option<String>,
     pub stop: Option<String>,
     pub emit: bool,
+    pub account_fallback: Option<AccountFallback>,
     _phantom: PhantomData<S>,
 }
 
@@ -818,6 +1198,7 @@ impl<S> TypedFieldMapping<S> {
             when: None,
             stop: None,
             emit: true,
+            account_fallback: None,
             _phantom: PhantomData,
         }
     }
@@ -858,6 +1239,7 @@ impl<S> TypedFieldMapping<S> {
             when: self.when.clone(),
             stop: self.stop.clone(),
             emit: self.emit,
+            account_fallback: self.account_fallback.clone(),
         }
     }
 
@@ -872,6 +1254,7 @@ impl<S> TypedFieldMapping<S> {
             when: mapping.when,
             stop: mapping.stop,
             emit: mapping.emit,
+            account_fallback: mapping.account_fallback,
             _phantom: PhantomData,
         }
     }
@@ -906,6 +1289,11 @@ pub enum MappingSource {
     FromContext {
         field: String,
     },
+    /// From the transaction that carried the instruction (signature, fee_payer, fee, compute_units)
+    /// Used by #[from_transaction]
+    FromTransaction {
+        field: String,
+    },
 }
 
 impl MappingSource {
@@ -963,8 +1351,26 @@ pub struct FieldTypeInfo {
     /// Resolved type information for complex types (instructions, accounts, custom types)
     #[serde(default)]
     pub resolved_type: Option<ResolvedStructType>,
+    /// How the value is encoded in entity state, when it isn't stored as-is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<ValueEncoding>,
     #[serde(default = "default_emit", skip_serializing_if = "is_true")]
     pub emit: bool,
+    /// Documentation of the field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<ItemDocs>,
+}
+
+/// Documentation written on an entity, section or field with `///` comments
+/// and `#[doc_meta(...)]`, carried into the generated SDKs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemDocs {
+    /// Doc comment text, one line per `///` line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Unit of the value, e.g. `lamports`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
 }
 
 /// Resolved structure type with field information from IDL
@@ -991,6 +1397,40 @@ pub struct ResolvedField {
     pub base_type: BaseType,
     pub is_optional: bool,
     pub is_array: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<ValueEncoding>,
+}
+
+/// String encoding of a value that doesn't fit a plain JSON number or array
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValueEncoding {
+    /// `u128`/`i128` as a decimal string, since JSON numbers lose precision
+    DecimalString,
+    /// Fixed byte array as a base58 string, e.g. a 64-byte signature
+    Base58,
+    /// Fixed byte array as a base64 string
+    Base64,
+}
+
+impl ValueEncoding {
+    /// Shape of a value stored with this encoding: always a string, and a
+    /// byte array collapses into a single one
+    pub fn stored_shape(self, is_array: bool) -> (BaseType, bool) {
+        match self {
+            ValueEncoding::DecimalString => (BaseType::String, is_array),
+            ValueEncoding::Base58 | ValueEncoding::Base64 => (BaseType::String, false),
+        }
+    }
+}
+
+impl ResolvedField {
+    /// Base type and array-ness of the field as it appears in entity state
+    pub fn stored_shape(&self) -> (BaseType, bool) {
+        match self.encoding {
+            Some(encoding) => encoding.stored_shape(self.is_array),
+            None => (self.base_type.clone(), self.is_array),
+        }
+    }
 }
 
 /// Language-agnostic base type classification
@@ -1020,12 +1460,21 @@ pub struct EntitySection {
     pub fields: Vec<FieldTypeInfo>,
     pub is_nested_struct: bool,
     pub parent_field: Option<String>, // If this section comes from a nested struct field
+    /// Documentation of the section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<ItemDocs>,
+    /// Name of the struct this section was imported from with
+    /// `#[section(external = ...)]`. SDKs generate one type of that name for
+    /// every section sharing it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_type: Option<String>,
 }
 
 impl FieldTypeInfo {
     pub fn new(field_name: String, rust_type_name: String) -> Self {
         let (base_type, is_optional, is_array, inner_type) =
             Self::analyze_rust_type(&rust_type_name);
+        let encoding = Self::value_encoding(inner_type.as_deref().unwrap_or(&rust_type_name));
 
         FieldTypeInfo {
             field_name: field_name.clone(),
@@ -1036,7 +1485,9 @@ impl FieldTypeInfo {
             inner_type,
             source_path: None,
             resolved_type: None,
+            encoding,
             emit: true,
+            docs: None,
         }
     }
 
@@ -1045,6 +1496,21 @@ impl FieldTypeInfo {
         self
     }
 
+    /// Base type and array-ness of the field as it appears in entity state
+    pub fn stored_shape(&self) -> (BaseType, bool) {
+        match self.encoding {
+            Some(encoding) => encoding.stored_shape(self.is_array),
+            None => (self.base_type.clone(), self.is_array),
+        }
+    }
+
+    /// Whether entity state holds the field as 64-bit integers, which
+    /// [`BigNumberMode::String`](crate::big_numbers::BigNumberMode::String)
+    /// sends as strings
+    pub fn is_wide_integer(&self) -> bool {
+        self.encoding.is_none() && crate::big_numbers::is_wide_integer_type(&self.rust_type_name)
+    }
+
     /// Analyze a Rust type string and extract structural information
     fn analyze_rust_type(rust_type: &str) -> (BaseType, bool, bool, Option<String>) {
         let type_str = rust_type.trim();
@@ -1075,9 +1541,8 @@ impl FieldTypeInfo {
 
         // Handle primitive types
         let base_type = match type_str {
-            "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" => {
-                BaseType::Integer
-            }
+            "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
+            | "u128" | "usize" => BaseType::Integer,
             "f32" | "f64" => BaseType::Float,
             "bool" => BaseType::Boolean,
             "String" | "&str" | "str" => BaseType::String,
@@ -1098,6 +1563,11 @@ impl FieldTypeInfo {
         (base_type, false, false, None)
     }
 
+    /// `u128`/`i128` values don't fit a JSON number and are stored as decimal strings
+    fn value_encoding(scalar_type: &str) -> Option<ValueEncoding> {
+        matches!(scalar_type.trim(), "u128" | "i128").then_some(ValueEncoding::DecimalString)
+    }
+
     /// Extract inner type from generic like "Option<T>" -> "T"
     fn extract_generic_inner(type_str: &str, generic_name: &str) -> Option<String> {
         let pattern = format!("{}<", generic_name);
@@ -1139,7 +1609,47 @@ pub trait FieldAccessor<S> {
 // SerializableStreamSpec Implementation
 // ============================================================================
 
+/// Event types `handlers` and `instruction_hooks` route to an entity, sorted
+/// and without duplicates
+pub fn routed_event_types(
+    handlers: &[SerializableHandlerSpec],
+    instruction_hooks: &[InstructionHook],
+) -> Vec<String> {
+    let types: BTreeSet<&String> = handlers
+        .iter()
+        .map(|handler| match &handler.source {
+            SourceSpec::Source { type_name, .. } => type_name,
+        })
+        .chain(instruction_hooks.iter().map(|hook| &hook.instruction_type))
+        .collect();
+    types.into_iter().cloned().collect()
+}
+
 impl SerializableStreamSpec {
+    /// Event types routed to this entity. ASTs written before they were
+    /// recorded derive them from the handlers and instruction hooks.
+    pub fn event_type_names(&self) -> Vec<String> {
+        if self.event_types.is_empty() {
+            routed_event_types(&self.handlers, &self.instruction_hooks)
+        } else {
+            self.event_types.clone()
+        }
+    }
+
+    /// Get the recipe for building this entity's canonical key.
+    ///
+    /// A computed key is detected from both the legacy `computed_fields`
+    /// list and `computed_field_specs`.
+    pub fn key_recipe(&self) -> Option<KeyRecipe> {
+        let mut computed = self.computed_fields.clone();
+        computed.extend(
+            self.computed_field_specs
+                .iter()
+                .map(|spec| spec.target_path.clone()),
+        );
+        self.identity.key_recipe(&self.field_mappings, &computed)
+    }
+
     /// Compute deterministic content hash (SHA256 of canonical JSON).
     ///
     /// The hash is computed over the entire spec except the content_hash field itself,
@@ -1352,6 +1862,10 @@ pub struct SerializableStackSpec {
     /// Instruction definitions for SDK code generation
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub instructions: Vec<InstructionDef>,
+    /// Cargo features of the stack crate that were enabled when this spec was
+    /// generated. Entities gated behind other features are left out.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
     /// Deterministic content hash of the entire stack
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub content_hash: Option<String>,
@@ -0,0 +1,234 @@
+//! JSON encoding of integers JavaScript numbers can't hold exactly.
+//!
+//! A JSON number above `Number.MAX_SAFE_INTEGER` (2^53 - 1) silently loses
+//! precision once a JavaScript client parses it, which corrupts lamport
+//! totals and cumulative volumes. [`BigNumberMode`] decides which integers
+//! of entity data are sent as decimal strings instead.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Largest integer a JavaScript number holds exactly, `2^53 - 1`
+pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Which integers of entity data are encoded as decimal strings.
+///
+/// Modes are ordered by how many integers they turn into strings, so the
+/// stricter of two modes is their `max`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum BigNumberMode {
+    /// Every integer stays a JSON number
+    Number,
+    /// Integers beyond `±(2^53 - 1)` become strings, wherever they are
+    #[default]
+    Auto,
+    /// Every field typed as a 64-bit integer becomes a string, whatever its
+    /// value, so a client sees one type per field. Other integers are
+    /// encoded as in [`Auto`](Self::Auto).
+    String,
+}
+
+impl BigNumberMode {
+    /// Encode the integers of entity data `data`.
+    ///
+    /// `wide_fields` are the dotted paths of the entity's 64-bit integer
+    /// fields (see [`EntityBytecode::wide_integer_fields`](crate::compiler::EntityBytecode::wide_integer_fields)),
+    /// only used by [`String`](Self::String). Values already encoded are
+    /// left as they are, so encoding twice is the same as encoding once.
+    pub fn apply(self, data: &mut Value, wide_fields: &HashSet<String>) {
+        match self {
+            BigNumberMode::Number => {}
+            BigNumberMode::Auto => stringify_unsafe_integers(data),
+            BigNumberMode::String => {
+                for path in wide_fields {
+                    if let Some(value) = lookup_mut(data, path) {
+                        stringify_integers(value);
+                    }
+                }
+                stringify_unsafe_integers(data);
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for BigNumberMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "number" => Ok(BigNumberMode::Number),
+            "auto" => Ok(BigNumberMode::Auto),
+            "string" => Ok(BigNumberMode::String),
+            other => Err(format!(
+                "unknown big number mode '{other}', expected number, auto or string"
+            )),
+        }
+    }
+}
+
+/// Turn the integers in `value` that JavaScript can't hold exactly into
+/// decimal strings
+pub fn stringify_unsafe_integers(value: &mut Value) {
+    match value {
+        Value::Object(fields) => fields.values_mut().for_each(stringify_unsafe_integers),
+        Value::Array(items) => items.iter_mut().for_each(stringify_unsafe_integers),
+        Value::Number(n) if !is_safe_integer(n) => {
+            *value = Value::String(n.to_string());
+        }
+        _ => {}
+    }
+}
+
+/// Whether `rust_type`, e.g. `Option < Vec < u64 > >`, holds 64-bit integers
+/// once its `Option` and `Vec` wrappers are peeled off
+pub fn is_wide_integer_type(rust_type: &str) -> bool {
+    let mut scalar: String = rust_type.chars().filter(|c| !c.is_whitespace()).collect();
+    while let Some(inner) = ["Option<", "Vec<"].iter().find_map(|wrapper| {
+        scalar
+            .strip_prefix(wrapper)
+            .and_then(|rest| rest.strip_suffix('>'))
+    }) {
+        scalar = inner.to_string();
+    }
+    matches!(scalar.as_str(), "u64" | "i64" | "usize" | "isize")
+}
+
+fn is_safe_integer(n: &serde_json::Number) -> bool {
+    match (n.as_u64(), n.as_i64()) {
+        (Some(n), _) => n <= MAX_SAFE_INTEGER,
+        (None, Some(n)) => n.unsigned_abs() <= MAX_SAFE_INTEGER,
+        // Floats are numbers to begin with
+        (None, None) => true,
+    }
+}
+
+/// Integers at this value, or in it when it's an array, as strings
+fn stringify_integers(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(stringify_integers),
+        Value::Number(n) if n.is_u64() || n.is_i64() => {
+            *value = Value::String(n.to_string());
+        }
+        _ => {}
+    }
+}
+
+fn lookup_mut<'a>(data: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.').try_fold(data, |value, segment| {
+        value.as_object_mut()?.get_mut(segment)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn wide(paths: &[&str]) -> HashSet<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn auto_stringifies_exactly_the_unsafe_integers() {
+        let mut data = json!({
+            "state": {
+                "safe": MAX_SAFE_INTEGER,
+                "unsafe": MAX_SAFE_INTEGER + 1,
+                "max": u64::MAX,
+                "negative_safe": -(MAX_SAFE_INTEGER as i64),
+                "negative_unsafe": -(MAX_SAFE_INTEGER as i64) - 1,
+                "ratio": 1.5e300,
+            },
+            "per_square": [1, MAX_SAFE_INTEGER + 1],
+        });
+
+        BigNumberMode::Auto.apply(&mut data, &HashSet::new());
+
+        assert_eq!(
+            data,
+            json!({
+                "state": {
+                    "safe": 9007199254740991u64,
+                    "unsafe": "9007199254740992",
+                    "max": "18446744073709551615",
+                    "negative_safe": -9007199254740991i64,
+                    "negative_unsafe": "-9007199254740992",
+                    "ratio": 1.5e300,
+                },
+                "per_square": [1, "9007199254740992"],
+            })
+        );
+    }
+
+    #[test]
+    fn string_mode_stringifies_typed_fields_of_any_size() {
+        let mut data = json!({
+            "state": { "total_deployed": 5, "per_square": [1, 2], "count": 3 },
+            "id": { "round_id": 7 },
+            "untyped": MAX_SAFE_INTEGER + 1,
+        });
+
+        BigNumberMode::String.apply(
+            &mut data,
+            &wide(&[
+                "state.total_deployed",
+                "state.per_square",
+                "id.round_id",
+                "missing.field",
+            ]),
+        );
+
+        assert_eq!(
+            data,
+            json!({
+                "state": { "total_deployed": "5", "per_square": ["1", "2"], "count": 3 },
+                "id": { "round_id": "7" },
+                "untyped": "9007199254740992",
+            })
+        );
+    }
+
+    #[test]
+    fn encoding_is_idempotent_and_number_mode_leaves_data_alone() {
+        let original = json!({ "state": { "total": u64::MAX, "count": 3 } });
+        let fields = wide(&["state.count"]);
+
+        let mut number = original.clone();
+        BigNumberMode::Number.apply(&mut number, &fields);
+        assert_eq!(number, original);
+
+        let mut once = original.clone();
+        BigNumberMode::String.apply(&mut once, &fields);
+        let mut twice = once.clone();
+        BigNumberMode::String.apply(&mut twice, &fields);
+        BigNumberMode::Auto.apply(&mut twice, &fields);
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn modes_order_by_strictness() {
+        assert_eq!(
+            BigNumberMode::Number.max(BigNumberMode::Auto),
+            BigNumberMode::Auto
+        );
+        assert_eq!(
+            BigNumberMode::String.max(BigNumberMode::Auto),
+            BigNumberMode::String
+        );
+    }
+
+    #[test]
+    fn wide_integer_types_see_through_options_and_vecs() {
+        assert!(is_wide_integer_type("u64"));
+        assert!(is_wide_integer_type("Option < i64 >"));
+        assert!(is_wide_integer_type("Option<Vec<u64>>"));
+        assert!(!is_wide_integer_type("u32"));
+        assert!(!is_wide_integer_type("u128"));
+        assert!(!is_wide_integer_type("Option<f64>"));
+        assert!(!is_wide_integer_type("Vec<u8>"));
+    }
+}
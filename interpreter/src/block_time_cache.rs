@@ -0,0 +1,105 @@
+//! Shared slot → block time cache accessible from both server and interpreter
+//!
+//! This module provides a global cache of on-chain block times that is populated
+//! by the gRPC block metadata stream and used to stamp `UpdateContext` timestamps
+//! with chain time instead of the server's wall clock.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Global block time cache (slot -> unix timestamp in seconds)
+static BLOCK_TIME_CACHE: once_cell::sync::Lazy<Arc<RwLock<BTreeMap<u64, i64>>>> =
+    once_cell::sync::Lazy::new(|| Arc::new(RwLock::new(BTreeMap::new())));
+
+/// Number of times a timestamp had to fall back to the server wall clock
+static WALL_CLOCK_FALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+/// Maximum number of block times to keep in cache (prevent unbounded growth)
+const MAX_CACHE_SIZE: usize = 1000;
+
+/// Nominal Solana slot duration
+pub const DEFAULT_SLOT_DURATION_MS: u64 = 400;
+
+/// Slot duration used by `slots_to_secs` / `secs_to_slots`
+static SLOT_DURATION_MS: AtomicU64 = AtomicU64::new(DEFAULT_SLOT_DURATION_MS);
+
+/// Record the block time for a slot in the global cache
+pub fn record_block_time(slot: u64, block_time: i64) {
+    let mut cache = BLOCK_TIME_CACHE.write().expect("RwLock poisoned");
+    cache.insert(slot, block_time);
+
+    // Prune old entries if cache is too large
+    if cache.len() > MAX_CACHE_SIZE {
+        // Remove oldest 25% of entries using pop_first for O(log n) per removal
+        let target_size = cache.len() - cache.len() / 4;
+        while cache.len() > target_size {
+            cache.pop_first();
+        }
+    }
+}
+
+/// Get the block time for a slot from the global cache
+pub fn get_block_time(slot: u64) -> Option<i64> {
+    let cache = BLOCK_TIME_CACHE.read().expect("RwLock poisoned");
+    cache.get(&slot).copied()
+}
+
+/// Current server wall clock as unix seconds, counted as a fallback.
+///
+/// Used when neither an explicit timestamp nor a block time is available.
+pub fn wall_clock_fallback() -> i64 {
+    wall_clock_fallback_from(&crate::clock::SystemClock)
+}
+
+/// [`wall_clock_fallback`] read from `clock`
+pub fn wall_clock_fallback_from(clock: &dyn crate::clock::Clock) -> i64 {
+    WALL_CLOCK_FALLBACKS.fetch_add(1, Ordering::Relaxed);
+    crate::vm_metrics::record_timestamp_wall_clock_fallback();
+    clock.unix_secs()
+}
+
+/// Override the slot duration used for slot/time conversions in computed fields
+pub fn set_slot_duration_ms(duration_ms: u64) {
+    SLOT_DURATION_MS.store(duration_ms.max(1), Ordering::Relaxed);
+}
+
+/// Slot duration currently used for slot/time conversions
+pub fn slot_duration_ms() -> u64 {
+    SLOT_DURATION_MS.load(Ordering::Relaxed)
+}
+
+/// Convert a slot count to whole seconds (truncating)
+pub fn slots_to_secs(slots: i64) -> i64 {
+    (slots as i128 * slot_duration_ms() as i128 / 1000) as i64
+}
+
+/// Convert seconds to a whole number of slots (truncating)
+pub fn secs_to_slots(secs: i64) -> i64 {
+    (secs as i128 * 1000 / slot_duration_ms() as i128) as i64
+}
+
+/// Total number of wall clock fallbacks since process start
+pub fn wall_clock_fallback_count() -> u64 {
+    WALL_CLOCK_FALLBACKS.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_time_cache() {
+        record_block_time(900_100, 1_700_000_000);
+        assert_eq!(get_block_time(900_100), Some(1_700_000_000));
+        assert_eq!(get_block_time(900_101), None);
+    }
+
+    #[test]
+    fn test_wall_clock_fallback_is_counted() {
+        let before = wall_clock_fallback_count();
+        let now = wall_clock_fallback();
+        assert!(now > 0);
+        assert!(wall_clock_fallback_count() > before);
+    }
+}
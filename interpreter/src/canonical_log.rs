@@ -4,10 +4,15 @@
 //!
 //! When the `otel` feature is enabled, trace context (trace_id, span_id) is automatically
 //! included in emitted logs for correlation with distributed traces.
+//!
+//! The most recent lines are also kept in memory, whatever the tracing level,
+//! and can be read back with [`recent_lines`].
 
+use once_cell::sync::Lazy;
 use serde::Serialize;
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
 use std::time::Instant;
 
 #[cfg(feature = "otel")]
@@ -15,6 +20,31 @@ use opentelemetry::trace::TraceContextExt;
 #[cfg(feature = "otel")]
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+/// Emitted lines kept in memory for [`recent_lines`]
+pub const RECENT_LINES_CAPACITY: usize = 1000;
+
+static RECENT_LINES: Lazy<Mutex<VecDeque<String>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(RECENT_LINES_CAPACITY)));
+
+/// Up to `n` of the most recently emitted canonical lines in this process,
+/// oldest first.
+pub fn recent_lines(n: usize) -> Vec<String> {
+    let lines = RECENT_LINES.lock().unwrap_or_else(|e| e.into_inner());
+    lines
+        .iter()
+        .skip(lines.len().saturating_sub(n))
+        .cloned()
+        .collect()
+}
+
+fn remember(line: &str) {
+    let mut lines = RECENT_LINES.lock().unwrap_or_else(|e| e.into_inner());
+    if lines.len() == RECENT_LINES_CAPACITY {
+        lines.pop_front();
+    }
+    lines.push_back(line.to_string());
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum LogLevel {
     Trace,
@@ -49,6 +79,10 @@ impl CanonicalLog {
         self
     }
 
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.data.get(key)
+    }
+
     pub fn set_level(&mut self, level: LogLevel) -> &mut Self {
         self.level = level;
         self
@@ -101,6 +135,7 @@ impl CanonicalLog {
 
         // Emit as a structured field so OTEL/Axiom can parse it, rather than embedding JSON in message body
         let canonical = serde_json::to_string(&self.data).unwrap_or_else(|_| "{}".to_string());
+        remember(&canonical);
 
         match self.level {
             LogLevel::Trace => {
@@ -157,4 +192,22 @@ mod tests {
         log.suppress();
         assert_eq!(log.data.get("cache_hits"), Some(&json!(3)));
     }
+
+    #[test]
+    fn test_emitted_lines_are_kept() {
+        let mut log = CanonicalLog::new();
+        log.set("event_type", "RecentLinesTest");
+        log.emit();
+
+        let mut suppressed = CanonicalLog::new();
+        suppressed.set("event_type", "SuppressedLinesTest");
+        suppressed.suppress();
+        drop(suppressed);
+
+        let lines = recent_lines(RECENT_LINES_CAPACITY);
+        assert!(lines.iter().any(|line| line.contains("RecentLinesTest")));
+        assert!(!lines
+            .iter()
+            .any(|line| line.contains("SuppressedLinesTest")));
+    }
 }
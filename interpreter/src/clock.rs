@@ -0,0 +1,62 @@
+//! Time source for time-based features.
+//!
+//! TTL expiry, heartbeats, stall detection and other time-based features read
+//! the time through a [`Clock`] rather than calling `SystemTime`/`Instant`
+//! directly, so tests can drive them with a
+//! [`ManualClock`](crate::testkit::ManualClock) instead of real sleeps.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub type SleepFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+pub type SharedClock = Arc<dyn Clock>;
+
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Time elapsed since the unix epoch
+    fn now_unix(&self) -> Duration;
+
+    /// Monotonic time, for measuring intervals
+    fn now_instant(&self) -> Instant;
+
+    /// Completes once `duration` has passed on this clock
+    fn sleep(&self, duration: Duration) -> SleepFuture;
+
+    /// Unix time in whole seconds
+    fn unix_secs(&self) -> i64 {
+        self.now_unix().as_secs() as i64
+    }
+
+    /// Unix time in milliseconds
+    fn unix_millis(&self) -> i64 {
+        self.now_unix().as_millis() as i64
+    }
+}
+
+/// The real clock: system time for unix timestamps and tokio's time for
+/// instants and sleeps, so it also follows a paused tokio runtime.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_unix(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+
+    fn now_instant(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Shared handle to the [`SystemClock`]
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
@@ -1,14 +1,98 @@
 use crate::ast::*;
+use crate::event_validation::{EventSchema, FieldCheck, ValueShape};
+use crate::vm::PathInterner;
+use crate::vm_error::VmError;
 use serde_json::Value;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
 use tracing;
 
 pub type Register = usize;
 
+/// Identifies the mapping a write opcode was compiled from by its position in
+/// the serialized AST, e.g. `handlers[2].mappings[0]`.
+pub type MappingId = Arc<str>;
+
+fn mapping_id(handler_index: usize, mapping_index: usize) -> MappingId {
+    format!("handlers[{}].mappings[{}]", handler_index, mapping_index).into()
+}
+
 fn stop_field_path(target_path: &str) -> String {
     format!("__stop:{}", target_path)
 }
 
+/// Register holding the pre-write snapshot for `changed(...)` conditions.
+const CHANGE_SNAPSHOT_REG: Register = 26;
+
+/// Arrange a handler's mapping section for `changed(...)` conditions: one
+/// `SnapshotFields` covering every gated path goes first, and each gated
+/// group (`SkipIfUnchanged` plus the opcodes it guards) moves to the end so
+/// it compares against the fully written state. Idempotent, so it can be
+/// re-applied after handlers are merged.
+/// Index name and scope a lookup index field compiles to. Shared indexes are
+/// addressed by their declared name, per-entity ones by the indexed field.
+fn lookup_index_target(lookup_index: &LookupIndexSpec) -> (String, IndexScope) {
+    match &lookup_index.shared_index {
+        Some(name) => (name.clone(), IndexScope::Shared),
+        None => (
+            format!("{}_lookup_index", lookup_index.leaf_name()),
+            IndexScope::StateTable,
+        ),
+    }
+}
+
+fn order_change_detection(ops: Vec<OpCode>) -> Vec<OpCode> {
+    let mut plain = Vec::new();
+    let mut gated = Vec::new();
+    let mut paths: Vec<String> = Vec::new();
+    let mut snapshot_source = None;
+
+    let mut iter = ops.into_iter();
+    while let Some(op) = iter.next() {
+        match op {
+            OpCode::SnapshotFields { .. } => {}
+            OpCode::SkipIfUnchanged {
+                object,
+                ref path,
+                skip,
+                ..
+            } => {
+                if !paths.contains(path) {
+                    paths.push(path.clone());
+                }
+                snapshot_source.get_or_insert(object);
+                gated.push(op);
+                gated.extend(iter.by_ref().take(skip));
+            }
+            op => plain.push(op),
+        }
+    }
+
+    let Some(object) = snapshot_source else {
+        return plain;
+    };
+
+    let mut ordered = Vec::with_capacity(plain.len() + gated.len() + 1);
+    ordered.push(OpCode::SnapshotFields {
+        object,
+        paths,
+        dest: CHANGE_SNAPSHOT_REG,
+    });
+    ordered.extend(plain);
+    ordered.extend(gated);
+    ordered
+}
+
+/// Where a lookup index opcode finds its index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexScope {
+    /// The entity's own state table
+    #[default]
+    StateTable,
+    /// A module-level index shared by every entity in the VM
+    Shared,
+}
+
 #[derive(Debug, Clone)]
 pub enum OpCode {
     /// Abort the handler with empty mutations when the key register is null
@@ -21,6 +105,13 @@ pub enum OpCode {
         key: Register,
         is_account_event: bool,
     },
+    /// Rewrite a non-null key register with the entity's key normalizer,
+    /// evaluated with the raw key bound to `key`. Emitted right after key
+    /// resolution so state, indexes and mutations all see the same key.
+    NormalizeKey {
+        key: Register,
+        normalizer: ComputedExpr,
+    },
     LoadEventField {
         path: FieldPath,
         dest: Register,
@@ -49,6 +140,8 @@ pub enum OpCode {
         object: Register,
         path: String,
         value: Register,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
     },
     SetFields {
         object: Register,
@@ -74,6 +167,10 @@ pub enum OpCode {
         object: Register,
         path: String,
         value: Register,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
+        /// Aggregates over this array whose accumulators the append advances
+        aggregates: Vec<ArrayAggregateSpec>,
     },
     GetCurrentTimestamp {
         dest: Register,
@@ -100,11 +197,23 @@ pub enum OpCode {
         object: Register,
         path: String,
         value: Register,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
     },
     SetFieldMax {
         object: Register,
         path: String,
         value: Register,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
+    },
+    /// Unset a field: a set value becomes null and is marked dirty, so the
+    /// patch carries the null. Fields already unset are left alone.
+    ClearField {
+        object: Register,
+        path: String,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
     },
     UpdateTemporalIndex {
         state_id: u32,
@@ -123,12 +232,14 @@ pub enum OpCode {
     UpdateLookupIndex {
         state_id: u32,
         index_name: String,
+        scope: IndexScope,
         lookup_value: Register,
         primary_key: Register,
     },
     LookupIndex {
         state_id: u32,
         index_name: String,
+        scope: IndexScope,
         lookup_value: Register,
         dest: Register,
     },
@@ -137,17 +248,23 @@ pub enum OpCode {
         object: Register,
         path: String,
         value: Register,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
     },
     /// Increment a counter field by 1
     SetFieldIncrement {
         object: Register,
         path: String,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
     },
     /// Set field to minimum value
     SetFieldMin {
         object: Register,
         path: String,
         value: Register,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
     },
     /// Set field only if a specific instruction type was seen in the same transaction.
     /// If not seen yet, defers the operation for later completion.
@@ -161,6 +278,8 @@ pub enum OpCode {
         condition_field: Option<FieldPath>,
         condition_op: Option<ComparisonOp>,
         condition_value: Option<Value>,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
     },
     /// Set field unless stopped by a specific instruction.
     /// Stop is tracked by a per-entity stop flag.
@@ -172,15 +291,20 @@ pub enum OpCode {
         stop_instruction: String,
         entity_name: String,
         key_reg: Register,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
     },
     /// Add value to unique set and update count
-    /// Maintains internal Set, field stores count
+    /// The set is kept in the state table per entity key, field stores count
     AddToUniqueSet {
         state_id: u32,
         set_name: String,
         value: Register,
         count_object: Register,
         count_path: String,
+        key: Register,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
     },
     /// Conditionally set a field based on a comparison
     ConditionalSetField {
@@ -190,6 +314,8 @@ pub enum OpCode {
         condition_field: FieldPath,
         condition_op: ComparisonOp,
         condition_value: Value,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
     },
     /// Conditionally increment a field based on a comparison
     ConditionalIncrement {
@@ -198,6 +324,26 @@ pub enum OpCode {
         condition_field: FieldPath,
         condition_op: ComparisonOp,
         condition_value: Value,
+        /// The mapping this write was compiled from, for provenance
+        mapping: Option<MappingId>,
+    },
+    /// Copy the current values of `paths` from the state object into `dest`
+    /// so `SkipIfUnchanged` can compare against them after the writes.
+    /// Placed directly after ReadOrInitState.
+    SnapshotFields {
+        object: Register,
+        paths: Vec<String>,
+        dest: Register,
+    },
+    /// Skip the next `skip` opcodes unless `path` differs from its
+    /// snapshotted value. `initial` decides whether a path with no previous
+    /// value counts as changed.
+    SkipIfUnchanged {
+        object: Register,
+        snapshot: Register,
+        path: String,
+        initial: bool,
+        skip: usize,
     },
     /// Evaluate computed fields (calls external hook if provided)
     /// computed_paths: List of paths that will be computed (for dirty tracking)
@@ -230,13 +376,54 @@ pub enum OpCode {
     },
 }
 
+impl OpCode {
+    /// The mapping a write opcode was compiled from, if any
+    pub fn mapping(&self) -> Option<&MappingId> {
+        match self {
+            OpCode::SetField { mapping, .. }
+            | OpCode::AppendToArray { mapping, .. }
+            | OpCode::SetFieldIfNull { mapping, .. }
+            | OpCode::SetFieldMax { mapping, .. }
+            | OpCode::ClearField { mapping, .. }
+            | OpCode::SetFieldSum { mapping, .. }
+            | OpCode::SetFieldIncrement { mapping, .. }
+            | OpCode::SetFieldMin { mapping, .. }
+            | OpCode::SetFieldWhen { mapping, .. }
+            | OpCode::SetFieldUnlessStopped { mapping, .. }
+            | OpCode::AddToUniqueSet { mapping, .. }
+            | OpCode::ConditionalSetField { mapping, .. }
+            | OpCode::ConditionalIncrement { mapping, .. } => mapping.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// An entity field recorded on the per-event tracing span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceField {
+    pub path: String,
+    /// The field is the entity's primary key, so the mutation key can stand
+    /// in when the patch doesn't include it
+    pub is_primary_key: bool,
+}
+
 pub struct EntityBytecode {
     pub state_id: u32,
     pub handlers: HashMap<String, Vec<OpCode>>,
     pub entity_name: String,
     pub when_events: HashSet<String>,
     pub non_emitted_fields: HashSet<String>,
+    /// Dotted paths of the fields typed as 64-bit integers
+    pub wide_integer_fields: HashSet<String>,
     pub computed_paths: Vec<String>,
+    /// Fields recorded as attributes on the per-event tracing span
+    pub trace_fields: Vec<TraceField>,
+    /// Priority of the entity's updates when the server sheds load
+    pub priority: EntityPriority,
+    /// What deletes the entity's state
+    pub delete_on: Option<DeleteTrigger>,
+    /// Shape checks on the events the handlers read, by event type
+    pub event_schemas: HashMap<String, EventSchema>,
     /// Optional callback for evaluating computed fields
     /// Parameters: state, context_slot (Option<u64>), context_timestamp (i64)
     #[allow(clippy::type_complexity)]
@@ -261,7 +448,12 @@ impl std::fmt::Debug for EntityBytecode {
             .field("entity_name", &self.entity_name)
             .field("when_events", &self.when_events)
             .field("non_emitted_fields", &self.non_emitted_fields)
+            .field("wide_integer_fields", &self.wide_integer_fields)
             .field("computed_paths", &self.computed_paths)
+            .field("trace_fields", &self.trace_fields)
+            .field("priority", &self.priority)
+            .field("delete_on", &self.delete_on)
+            .field("event_schemas", &self.event_schemas)
             .field(
                 "computed_fields_evaluator",
                 &self.computed_fields_evaluator.is_some(),
@@ -270,12 +462,46 @@ impl std::fmt::Debug for EntityBytecode {
     }
 }
 
+/// Key of [`MultiEntityBytecode::event_routing`]: an event type, qualified by
+/// the program that emits it when several IDLs are in play.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RouteKey {
+    pub event_type: String,
+    /// Address of the emitting program; `None` routes the event type from
+    /// any program
+    pub program_id: Option<String>,
+}
+
+impl RouteKey {
+    pub fn new(event_type: impl Into<String>) -> Self {
+        Self {
+            event_type: event_type.into(),
+            program_id: None,
+        }
+    }
+
+    pub fn for_program(event_type: impl Into<String>, program_id: impl Into<String>) -> Self {
+        Self {
+            event_type: event_type.into(),
+            program_id: Some(program_id.into()),
+        }
+    }
+}
+
+impl From<&str> for RouteKey {
+    fn from(event_type: &str) -> Self {
+        Self::new(event_type)
+    }
+}
+
 #[derive(Debug)]
 pub struct MultiEntityBytecode {
     pub entities: HashMap<String, EntityBytecode>,
-    pub event_routing: HashMap<String, Vec<String>>,
+    pub event_routing: HashMap<RouteKey, Vec<String>>,
     pub when_events: HashSet<String>,
     pub proto_router: crate::proto_router::ProtoRouter,
+    /// Paths compiled by the VMs running this bytecode
+    pub paths: Arc<PathInterner>,
 }
 
 impl MultiEntityBytecode {
@@ -287,9 +513,9 @@ impl MultiEntityBytecode {
         let mut event_routing = HashMap::new();
         let mut when_events = HashSet::new();
 
-        for event_type in entity_bytecode.handlers.keys() {
+        for route in compiler.route_keys(&entity_bytecode) {
             event_routing
-                .entry(event_type.clone())
+                .entry(route)
                 .or_insert_with(Vec::new)
                 .push(entity_name.clone());
         }
@@ -303,6 +529,7 @@ impl MultiEntityBytecode {
             event_routing,
             when_events,
             proto_router: crate::proto_router::ProtoRouter::new(),
+            paths: Arc::new(PathInterner::new()),
         }
     }
 
@@ -320,7 +547,73 @@ impl MultiEntityBytecode {
             event_routing,
             when_events,
             proto_router: crate::proto_router::ProtoRouter::new(),
+            paths: Arc::new(PathInterner::new()),
+        }
+    }
+
+    /// Entities handling `event_type` when emitted by `program_id`.
+    ///
+    /// Entities routed without a program receive the event type from any
+    /// program. Without a program id, an event type routed for several
+    /// programs is ambiguous and fails with [`VmError::AmbiguousEventType`].
+    pub fn route(
+        &self,
+        event_type: &str,
+        program_id: Option<&str>,
+    ) -> std::result::Result<Vec<&str>, VmError> {
+        let mut entities: Vec<&str> = self
+            .event_routing
+            .get(&RouteKey::new(event_type))
+            .map(|names| names.iter().map(String::as_str).collect())
+            .unwrap_or_default();
+
+        let qualified = match program_id {
+            Some(program_id) => self
+                .event_routing
+                .get(&RouteKey::for_program(event_type, program_id)),
+            None => {
+                let qualified = || {
+                    self.event_routing.iter().filter(|(route, _)| {
+                        route.event_type == event_type && route.program_id.is_some()
+                    })
+                };
+                if qualified().nth(1).is_some() {
+                    let mut programs: Vec<String> = qualified()
+                        .filter_map(|(route, _)| route.program_id.clone())
+                        .collect();
+                    programs.sort();
+                    return Err(VmError::AmbiguousEventType {
+                        event_type: event_type.to_string(),
+                        programs,
+                    });
+                }
+                qualified().next().map(|(_, names)| names)
+            }
+        };
+        if let Some(names) = qualified {
+            entities.extend(names.iter().map(String::as_str));
         }
+
+        Ok(entities)
+    }
+
+    /// Entity held by each state table, by state id
+    pub fn state_tables(&self) -> BTreeMap<u32, String> {
+        self.entities
+            .values()
+            .map(|entity| (entity.state_id, entity.entity_name.clone()))
+            .collect()
+    }
+
+    /// Hash of [`state_tables`](Self::state_tables). It changes when entities
+    /// are added, removed, renamed or renumbered, which makes saved VM state
+    /// unfit to restore.
+    pub fn schema_hash(&self) -> String {
+        use sha2::{Digest, Sha256};
+
+        let tables = serde_json::to_string(&self.state_tables())
+            .expect("Failed to serialize state tables for hashing");
+        hex::encode(Sha256::digest(tables.as_bytes()))
     }
 
     #[allow(clippy::new_ret_no_self)]
@@ -336,7 +629,7 @@ impl MultiEntityBytecode {
 
 pub struct MultiEntityBytecodeBuilder {
     entities: HashMap<String, EntityBytecode>,
-    event_routing: HashMap<String, Vec<String>>,
+    event_routing: HashMap<RouteKey, Vec<String>>,
     when_events: HashSet<String>,
     proto_router: crate::proto_router::ProtoRouter,
 }
@@ -383,9 +676,9 @@ impl MultiEntityBytecodeBuilder {
             entity_bytecode.computed_fields_evaluator = Some(Box::new(eval));
         }
 
-        for event_type in entity_bytecode.handlers.keys() {
+        for route in compiler.route_keys(&entity_bytecode) {
             self.event_routing
-                .entry(event_type.clone())
+                .entry(route)
                 .or_default()
                 .push(entity_name.clone());
         }
@@ -403,6 +696,7 @@ impl MultiEntityBytecodeBuilder {
             event_routing: self.event_routing,
             when_events: self.when_events,
             proto_router: self.proto_router,
+            paths: Arc::new(PathInterner::new()),
         }
     }
 }
@@ -434,9 +728,9 @@ impl<S> TypedCompiler<S> {
         let mut event_routing = HashMap::new();
         let mut when_events = HashSet::new();
 
-        for event_type in entity_bytecode.handlers.keys() {
+        for route in self.route_keys(&entity_bytecode) {
             event_routing
-                .entry(event_type.clone())
+                .entry(route)
                 .or_insert_with(Vec::new)
                 .push(self.entity_name.clone());
         }
@@ -450,9 +744,35 @@ impl<S> TypedCompiler<S> {
             event_routing,
             when_events,
             proto_router: crate::proto_router::ProtoRouter::new(),
+            paths: Arc::new(PathInterner::new()),
         }
     }
 
+    /// Routing keys of the compiled handlers. A handler is qualified by its
+    /// source's program when every source of its event type names the same one.
+    fn route_keys(&self, entity_bytecode: &EntityBytecode) -> Vec<RouteKey> {
+        entity_bytecode
+            .handlers
+            .keys()
+            .map(|event_type| {
+                let mut programs = self.spec.handlers.iter().filter_map(|handler| {
+                    let SourceSpec::Source {
+                        program_id,
+                        type_name,
+                        ..
+                    } = &handler.source;
+                    (type_name == event_type).then_some(program_id.as_deref())
+                });
+                match programs.next() {
+                    Some(Some(program_id)) if programs.all(|other| other == Some(program_id)) => {
+                        RouteKey::for_program(event_type.as_str(), program_id)
+                    }
+                    _ => RouteKey::new(event_type.as_str()),
+                }
+            })
+            .collect()
+    }
+
     fn compile_entity(&self) -> EntityBytecode {
         let mut handlers: HashMap<String, Vec<OpCode>> = HashMap::new();
         let mut when_events: HashSet<String> = HashSet::new();
@@ -485,7 +805,7 @@ impl<S> TypedCompiler<S> {
         //     }
         // }
 
-        for handler_spec in &self.spec.handlers {
+        for (handler_index, handler_spec) in self.spec.handlers.iter().enumerate() {
             for mapping in &handler_spec.mappings {
                 if let Some(when) = &mapping.when {
                     when_events.insert(when.clone());
@@ -500,7 +820,7 @@ impl<S> TypedCompiler<S> {
                         .or_insert(false);
                 }
             }
-            let opcodes = self.compile_handler(handler_spec);
+            let opcodes = self.compile_handler(handler_index, handler_spec);
             let event_type = self.get_event_type(&handler_spec.source);
 
             if let Some(existing_opcodes) = handlers.get_mut(&event_type) {
@@ -552,10 +872,10 @@ impl<S> TypedCompiler<S> {
                 }
 
                 // Rebuild: setup + existing_mappings + new_mappings + teardown
+                existing_mappings.extend(new_mappings);
                 let mut merged = Vec::new();
                 merged.extend(existing_setup);
-                merged.extend(existing_mappings);
-                merged.extend(new_mappings.clone());
+                merged.extend(order_change_detection(existing_mappings));
                 merged.extend(existing_teardown);
 
                 *existing_opcodes = merged;
@@ -612,6 +932,8 @@ impl<S> TypedCompiler<S> {
                     });
                 }
 
+                ops.extend(self.compile_key_normalization(key_reg));
+
                 ops.push(OpCode::ReadOrInitState {
                     state_id: self.state_id,
                     key: key_reg,
@@ -662,17 +984,80 @@ impl<S> TypedCompiler<S> {
             entity_name: self.entity_name.clone(),
             when_events,
             non_emitted_fields,
+            wide_integer_fields: self
+                .spec
+                .field_mappings
+                .iter()
+                .filter(|(_, field)| field.is_wide_integer())
+                .map(|(path, _)| path.clone())
+                .collect(),
             computed_paths: self.spec.computed_fields.clone(),
+            trace_fields: self
+                .spec
+                .trace_fields
+                .iter()
+                .map(|path| TraceField {
+                    path: path.clone(),
+                    is_primary_key: self.spec.identity.primary_keys == [path.as_str()],
+                })
+                .collect(),
+            priority: self.spec.priority,
+            delete_on: self.spec.delete_on,
+            event_schemas: self.event_schemas(),
             computed_fields_evaluator: None,
         }
     }
 
-    fn compile_handler(&self, spec: &TypedHandlerSpec<S>) -> Vec<OpCode> {
+    /// Checks on the event paths each handler's mappings read, typed by the
+    /// fields they write. Paths read with a default, under a condition or
+    /// into optional or untyped fields are optional.
+    fn event_schemas(&self) -> HashMap<String, EventSchema> {
+        let mut schemas: HashMap<String, EventSchema> = HashMap::new();
+        for handler in &self.spec.handlers {
+            let schema = schemas
+                .entry(self.get_event_type(&handler.source))
+                .or_default();
+            for mapping in &handler.mappings {
+                let MappingSource::FromSource {
+                    path,
+                    default,
+                    transform,
+                } = &mapping.source
+                else {
+                    continue;
+                };
+                if path
+                    .segments
+                    .first()
+                    .is_none_or(|first| first.starts_with("__"))
+                {
+                    continue;
+                }
+                let field = self.spec.field_mappings.get(&mapping.target_path);
+                let transformed = transform.is_some() || mapping.transform.is_some();
+                schema.add(FieldCheck {
+                    path: path.segments.clone(),
+                    shape: match field {
+                        Some(field) if !transformed => ValueShape::of_field(field),
+                        _ => ValueShape::Any,
+                    },
+                    required: default.is_none()
+                        && mapping.condition.is_none()
+                        && field.is_some_and(|field| !field.is_optional),
+                });
+            }
+        }
+        schemas.retain(|_, schema| !schema.is_empty());
+        schemas
+    }
+
+    fn compile_handler(&self, handler_index: usize, spec: &TypedHandlerSpec<S>) -> Vec<OpCode> {
         let mut ops = Vec::new();
         let state_reg = 2;
         let key_reg = 20;
 
         ops.extend(self.compile_key_loading(&spec.key_resolution, key_reg, &spec.mappings));
+        ops.extend(self.compile_key_normalization(key_reg));
 
         // Guard: if key resolved to null on an account-state event, abort
         // early with empty mutations so process_event can queue the update
@@ -704,17 +1089,17 @@ impl<S> TypedCompiler<S> {
         // use get_mut() which fails if the table doesn't exist yet.
         // This ordering also means stale/duplicate updates (caught by ReadOrInitState's
         // recency check) correctly skip index updates too.
-        ops.extend(self.compile_temporal_index_update(
-            &spec.key_resolution,
-            key_reg,
-            &spec.mappings,
-        ));
+        let mut mapping_ops =
+            self.compile_temporal_index_update(&spec.key_resolution, key_reg, &spec.mappings);
+        mapping_ops.extend(self.compile_key_from_registration(&spec.source, key_reg));
 
-        for mapping in &spec.mappings {
-            ops.extend(self.compile_mapping(mapping, state_reg, key_reg));
+        for (mapping_index, mapping) in spec.mappings.iter().enumerate() {
+            let id = mapping_id(handler_index, mapping_index);
+            mapping_ops.extend(self.compile_mapping(mapping, &id, state_reg, key_reg));
         }
 
-        ops.extend(self.compile_resolvers(state_reg, key_reg));
+        mapping_ops.extend(self.compile_resolvers(state_reg, key_reg));
+        ops.extend(order_change_detection(mapping_ops));
 
         // Evaluate computed fields after all mappings but before updating state
         ops.push(OpCode::EvaluateComputedFields {
@@ -773,9 +1158,54 @@ impl<S> TypedCompiler<S> {
     fn compile_mapping(
         &self,
         mapping: &TypedFieldMapping<S>,
+        id: &MappingId,
+        state_reg: Register,
+        key_reg: Register,
+    ) -> Vec<OpCode> {
+        let changed = mapping
+            .condition
+            .as_ref()
+            .and_then(|cond| cond.parsed.as_ref())
+            .and_then(|parsed| match parsed {
+                ParsedCondition::Changed { field, initial } => Some((field, *initial)),
+                _ => None,
+            });
+
+        let ops = self.compile_mapping_write(mapping, id, state_reg, key_reg);
+        let Some((field, initial)) = changed else {
+            return ops;
+        };
+
+        let mut gated = vec![OpCode::SkipIfUnchanged {
+            object: state_reg,
+            snapshot: CHANGE_SNAPSHOT_REG,
+            path: field.segments.join("."),
+            initial,
+            skip: ops.len(),
+        }];
+        gated.extend(ops);
+        gated
+    }
+
+    fn compile_mapping_write(
+        &self,
+        mapping: &TypedFieldMapping<S>,
+        id: &MappingId,
         state_reg: Register,
         key_reg: Register,
     ) -> Vec<OpCode> {
+        if let Some(fallback) = &mapping.account_fallback {
+            tracing::warn!(
+                "{}.{} reads account '{}' of {} by position {}, which the IDL calls '{}'",
+                self.entity_name,
+                mapping.target_path,
+                fallback.written,
+                fallback.instruction,
+                fallback.index,
+                fallback.resolved
+            );
+        }
+
         let mut ops = Vec::new();
         let temp_reg = 10;
 
@@ -813,6 +1243,7 @@ impl<S> TypedCompiler<S> {
                 stop_instruction: stop_instruction.clone(),
                 entity_name: self.entity_name.clone(),
                 key_reg,
+                mapping: Some(id.clone()),
             });
             return ops;
         }
@@ -838,6 +1269,8 @@ impl<S> TypedCompiler<S> {
                         tracing::warn!("Logical conditions not yet supported for #[map] when");
                         None
                     }
+                    // Gated by compile_mapping.
+                    ParsedCondition::Changed { .. } => None,
                 })
                 .unwrap_or((None, None, None));
 
@@ -851,6 +1284,7 @@ impl<S> TypedCompiler<S> {
                 condition_field,
                 condition_op,
                 condition_value,
+                mapping: Some(id.clone()),
             });
             return ops;
         }
@@ -873,6 +1307,7 @@ impl<S> TypedCompiler<S> {
                                 condition_field: field.clone(),
                                 condition_op: op.clone(),
                                 condition_value: cond_value.clone(),
+                                mapping: Some(id.clone()),
                             });
                             return ops;
                         }
@@ -884,6 +1319,7 @@ impl<S> TypedCompiler<S> {
                                 condition_field: field.clone(),
                                 condition_op: op.clone(),
                                 condition_value: cond_value.clone(),
+                                mapping: Some(id.clone()),
                             });
                             return ops;
                         }
@@ -896,84 +1332,107 @@ impl<S> TypedCompiler<S> {
                     ParsedCondition::Logical { .. } => {
                         tracing::warn!("Logical conditions not yet supported for #[map]");
                     }
+                    ParsedCondition::Changed { .. } => {}
                 }
             }
         }
 
-        match &mapping.population {
-            PopulationStrategy::Append => {
-                ops.push(OpCode::AppendToArray {
-                    object: state_reg,
-                    path: mapping.target_path.clone(),
-                    value: temp_reg,
-                });
-            }
-            PopulationStrategy::LastWrite => {
-                ops.push(OpCode::SetField {
-                    object: state_reg,
-                    path: mapping.target_path.clone(),
-                    value: temp_reg,
-                });
-            }
-            PopulationStrategy::SetOnce => {
-                ops.push(OpCode::SetFieldIfNull {
-                    object: state_reg,
-                    path: mapping.target_path.clone(),
-                    value: temp_reg,
-                });
-            }
-            PopulationStrategy::Merge => {
-                ops.push(OpCode::SetField {
-                    object: state_reg,
-                    path: mapping.target_path.clone(),
-                    value: temp_reg,
-                });
-            }
-            PopulationStrategy::Max => {
-                ops.push(OpCode::SetFieldMax {
-                    object: state_reg,
-                    path: mapping.target_path.clone(),
-                    value: temp_reg,
-                });
-            }
-            PopulationStrategy::Sum => {
-                ops.push(OpCode::SetFieldSum {
-                    object: state_reg,
-                    path: mapping.target_path.clone(),
-                    value: temp_reg,
-                });
-            }
-            PopulationStrategy::Count => {
-                // Count doesn't need the value, just increment
-                ops.push(OpCode::SetFieldIncrement {
-                    object: state_reg,
-                    path: mapping.target_path.clone(),
-                });
-            }
-            PopulationStrategy::Min => {
-                ops.push(OpCode::SetFieldMin {
-                    object: state_reg,
-                    path: mapping.target_path.clone(),
-                    value: temp_reg,
-                });
-            }
-            PopulationStrategy::UniqueCount => {
-                // UniqueCount requires maintaining an internal set
-                // The field stores the count, but we track unique values in a hidden set
-                let set_name = format!("{}_unique_set", mapping.target_path);
-                ops.push(OpCode::AddToUniqueSet {
-                    state_id: self.state_id,
-                    set_name,
-                    value: temp_reg,
-                    count_object: state_reg,
-                    count_path: mapping.target_path.clone(),
-                });
-            }
-        }
+        ops.push(self.compile_population(
+            &mapping.population,
+            state_reg,
+            &mapping.target_path,
+            temp_reg,
+            key_reg,
+            Some(id.clone()),
+        ));
 
         ops
     }
 
+    /// Select the write opcode for a mapping's population strategy.
+    ///
+    /// `SetOnce`, `Max`, `Min` and `Sum` leave the field untouched when the
+    /// incoming value is null.
+    fn compile_population(
+        &self,
+        population: &PopulationStrategy,
+        state_reg: Register,
+        target_path: &str,
+        value: Register,
+        key_reg: Register,
+        mapping: Option<MappingId>,
+    ) -> OpCode {
+        let path = target_path.to_string();
+        match population {
+            PopulationStrategy::Append => OpCode::AppendToArray {
+                object: state_reg,
+                aggregates: self
+                    .spec
+                    .array_aggregates
+                    .iter()
+                    .filter(|aggregate| {
+                        aggregate.path == path && crate::array_aggregate::is_accumulated(aggregate)
+                    })
+                    .cloned()
+                    .collect(),
+                path,
+                value,
+                mapping,
+            },
+            PopulationStrategy::LastWrite | PopulationStrategy::Merge => OpCode::SetField {
+                object: state_reg,
+                path,
+                value,
+                mapping,
+            },
+            PopulationStrategy::SetOnce => OpCode::SetFieldIfNull {
+                object: state_reg,
+                path,
+                value,
+                mapping,
+            },
+            PopulationStrategy::Max => OpCode::SetFieldMax {
+                object: state_reg,
+                path,
+                value,
+                mapping,
+            },
+            PopulationStrategy::Sum => OpCode::SetFieldSum {
+                object: state_reg,
+                path,
+                value,
+                mapping,
+            },
+            // Count doesn't need the value, just increment
+            PopulationStrategy::Count => OpCode::SetFieldIncrement {
+                object: state_reg,
+                path,
+                mapping,
+            },
+            PopulationStrategy::Min => OpCode::SetFieldMin {
+                object: state_reg,
+                path,
+                value,
+                mapping,
+            },
+            // The field stores the count; the set itself is kept per entity key
+            PopulationStrategy::UniqueCount => OpCode::AddToUniqueSet {
+                state_id: self.state_id,
+                set_name: format!("{}_unique_set", target_path),
+                value,
+                count_object: state_reg,
+                count_path: path,
+                key: key_reg,
+                mapping,
+            },
+            PopulationStrategy::Clear => OpCode::ClearField {
+                object: state_reg,
+                path,
+                mapping,
+            },
+        }
+    }
+
     fn compile_mapping_source(&self, source: &MappingSource, dest: Register) -> Vec<OpCode> {
         match source {
             MappingSource::FromSource {
@@ -1111,6 +1570,7 @@ impl<S> TypedCompiler<S> {
                         object: capture_data_reg,
                         path: field_name.clone(),
                         value: transformed_reg,
+                        mapping: None,
                     });
                 }
 
@@ -1130,6 +1590,14 @@ impl<S> TypedCompiler<S> {
                     default: Some(serde_json::json!(null)),
                 }]
             }
+            MappingSource::FromTransaction { field } => {
+                // Load from the transaction metadata attached to instruction events
+                vec![OpCode::LoadEventField {
+                    path: FieldPath::new(&["__transaction", field.as_str()]),
+                    dest,
+                    default: Some(serde_json::json!(null)),
+                }]
+            }
             MappingSource::Computed { .. } => {
                 vec![]
             }
@@ -1244,13 +1712,15 @@ impl<S> TypedCompiler<S> {
                     dest: lookup_reg,
                 });
 
-                let index_name = self.find_lookup_index_for_lookup_field(primary_field, mappings);
-                let effective_index_name =
-                    index_name.unwrap_or_else(|| "default_pda_lookup".to_string());
+                let (index_name, scope) = self
+                    .find_lookup_index_for_lookup_field(primary_field, mappings)
+                    .map(lookup_index_target)
+                    .unwrap_or_else(|| ("default_pda_lookup".to_string(), IndexScope::StateTable));
 
                 ops.push(OpCode::LookupIndex {
                     state_id: self.state_id,
-                    index_name: effective_index_name,
+                    index_name,
+                    scope,
                     lookup_value: lookup_reg,
                     dest: result_reg,
                 });
@@ -1511,7 +1981,7 @@ impl<S> TypedCompiler<S> {
         false
     }
 
-    fn find_lookup_index_for_field(&self, field_path: &FieldPath) -> Option<String> {
+    fn find_lookup_index_for_field(&self, field_path: &FieldPath) -> Option<&LookupIndexSpec> {
         if field_path.segments.is_empty() {
             return None;
         }
@@ -1519,11 +1989,7 @@ impl<S> TypedCompiler<S> {
         let lookup_field_name = field_path.segments.last().unwrap();
 
         for lookup_index in &self.spec.identity.lookup_indexes {
-            let index_field_name = lookup_index
-                .field_name
-                .split('.')
-                .next_back()
-                .unwrap_or(&lookup_index.field_name);
+            let index_field_name = lookup_index.leaf_name();
             let matches_directly = index_field_name == lookup_field_name;
             // An index field named `foo_address` is treated as an alias for the
             // bare field `foo` (for example, `mint_address` resolves handlers
@@ -1537,7 +2003,7 @@ impl<S> TypedCompiler<S> {
                 .unwrap_or(false);
 
             if matches_directly || matches_address_alias {
-                return Some(format!("{}_lookup_index", index_field_name));
+                return Some(lookup_index);
             }
         }
 
@@ -1550,7 +2016,7 @@ impl<S> TypedCompiler<S> {
         &self,
         primary_field: &FieldPath,
         mappings: &[TypedFieldMapping<S>],
-    ) -> Option<String> {
+    ) -> Option<&LookupIndexSpec> {
         // Build the primary field path string
         let primary_path = primary_field.segments.join(".");
 
@@ -1563,12 +2029,7 @@ impl<S> TypedCompiler<S> {
                     // Check if the target is a lookup index field
                     for lookup_index in &self.spec.identity.lookup_indexes {
                         if mapping.target_path == lookup_index.field_name {
-                            let index_field_name = lookup_index
-                                .field_name
-                                .split('.')
-                                .next_back()
-                                .unwrap_or(&lookup_index.field_name);
-                            return Some(format!("{}_lookup_index", index_field_name));
+                            return Some(lookup_index);
                         }
                     }
                 }
@@ -1607,11 +2068,8 @@ impl<S> TypedCompiler<S> {
 
         for lookup_index in &self.spec.identity.lookup_indexes {
             let lookup_reg = 17;
-            let source_field = lookup_index
-                .field_name
-                .split('.')
-                .next_back()
-                .unwrap_or(&lookup_index.field_name);
+            let source_field = lookup_index.leaf_name();
+            let (index_name, scope) = lookup_index_target(lookup_index);
 
             match resolution {
                 KeyResolutionStrategy::Embedded { primary_field: _ } => {
@@ -1642,32 +2100,23 @@ impl<S> TypedCompiler<S> {
                             default: None,
                         });
 
-                        let index_name = format!("{}_temporal_index", source_field);
                         ops.push(OpCode::UpdateTemporalIndex {
                             state_id: self.state_id,
-                            index_name,
+                            index_name: format!("{}_temporal_index", source_field),
                             lookup_value: lookup_reg,
                             primary_key: key_reg,
                             timestamp: timestamp_reg,
                         });
-
-                        let simple_index_name = format!("{}_lookup_index", source_field);
-                        ops.push(OpCode::UpdateLookupIndex {
-                            state_id: self.state_id,
-                            index_name: simple_index_name,
-                            lookup_value: lookup_reg,
-                            primary_key: key_reg,
-                        });
-                    } else {
-                        let index_name = format!("{}_lookup_index", source_field);
-                        ops.push(OpCode::UpdateLookupIndex {
-                            state_id: self.state_id,
-                            index_name,
-                            lookup_value: lookup_reg,
-                            primary_key: key_reg,
-                        });
                     }
 
+                    ops.push(OpCode::UpdateLookupIndex {
+                        state_id: self.state_id,
+                        index_name: index_name.clone(),
+                        scope,
+                        lookup_value: lookup_reg,
+                        primary_key: key_reg,
+                    });
+
                     // Also update PDA reverse lookup table if there's a resolver configured for this entity
                     // This allows instruction handlers to look up the primary key from PDA addresses
                     // Only do this when the source path is different (e.g., __account_address -> id.round_address)
@@ -1698,10 +2147,10 @@ impl<S> TypedCompiler<S> {
                             default: None,
                         });
 
-                        let index_name = format!("{}_lookup_index", source_field);
                         ops.push(OpCode::UpdateLookupIndex {
                             state_id: self.state_id,
-                            index_name,
+                            index_name: index_name.clone(),
+                            scope,
                             lookup_value: lookup_reg,
                             primary_key: key_reg,
                         });
@@ -1717,6 +2166,67 @@ impl<S> TypedCompiler<S> {
         ops
     }
 
+    /// Normalize the resolved key before anything reads or writes state, so
+    /// every handler of the entity agrees on one spelling of each key. Keys
+    /// that came out of a lookup index were normalized when stored, so the
+    /// normalizer must be idempotent.
+    fn compile_key_normalization(&self, key_reg: Register) -> Option<OpCode> {
+        self.spec
+            .key_normalizer
+            .as_ref()
+            .map(|normalizer| OpCode::NormalizeKey {
+                key: key_reg,
+                normalizer: normalizer.clone(),
+            })
+    }
+
+    /// Register `account address -> key` when this handler's account is the
+    /// entity's `key_from` source, so instructions and other sources that
+    /// only know the address resolve to the entity keyed by the account's
+    /// field. The address and key come from the same update, so the first
+    /// update of an account is never queued waiting for the mapping.
+    fn compile_key_from_registration(&self, source: &SourceSpec, key_reg: Register) -> Vec<OpCode> {
+        let event_type = self.get_event_type(source);
+        let Some(index_name) =
+            self.spec
+                .resolver_hooks
+                .iter()
+                .find_map(|hook| match &hook.strategy {
+                    ResolverStrategy::KeyFrom { index_name, .. }
+                        if hook.account_type == event_type =>
+                    {
+                        Some(index_name.clone())
+                    }
+                    _ => None,
+                })
+        else {
+            return Vec::new();
+        };
+
+        let address_reg = 17;
+        vec![
+            OpCode::LoadEventField {
+                path: FieldPath::new(&["__account_address"]),
+                dest: address_reg,
+                default: None,
+            },
+            OpCode::UpdateLookupIndex {
+                state_id: self.state_id,
+                index_name,
+                scope: IndexScope::StateTable,
+                lookup_value: address_reg,
+                primary_key: key_reg,
+            },
+            // Flushes instructions queued on a miss for this address
+            OpCode::UpdatePdaReverseLookup {
+                state_id: self.state_id,
+                lookup_name: "default_pda_lookup".to_string(),
+                pda_address: address_reg,
+                primary_key: key_reg,
+            },
+        ]
+    }
+
     fn get_event_type(&self, source: &SourceSpec) -> String {
         match source {
             SourceSpec::Source { type_name, .. } => type_name.clone(),
@@ -1773,6 +2283,7 @@ impl<S> TypedCompiler<S> {
                                 object: state_reg,
                                 path: target_field.clone(),
                                 value: temp_reg,
+                                mapping: None,
                             });
                         }
                     } else {
@@ -1781,6 +2292,7 @@ impl<S> TypedCompiler<S> {
                             object: state_reg,
                             path: target_field.clone(),
                             value: temp_reg,
+                            mapping: None,
                         });
                     }
                 }
@@ -1807,6 +2319,7 @@ impl<S> TypedCompiler<S> {
                             ops.push(OpCode::SetFieldIncrement {
                                 object: state_reg,
                                 path: target_field.clone(),
+                                mapping: None,
                             });
                         }
                     } else {
@@ -1814,6 +2327,7 @@ impl<S> TypedCompiler<S> {
                         ops.push(OpCode::SetFieldIncrement {
                             object: state_reg,
                             path: target_field.clone(),
+                            mapping: None,
                         });
                     }
                 }
@@ -1848,15 +2362,19 @@ impl<S> TypedCompiler<S> {
                     condition_field: field.clone(),
                     condition_op: op.clone(),
                     condition_value: cond_value.clone(),
+                    mapping: None,
                 }]
             }
-            ParsedCondition::Logical { .. } => {
-                // Logical conditions not yet supported, fall back to unconditional
-                tracing::warn!("Logical conditions not yet supported in instruction hooks");
+            ParsedCondition::Logical { .. } | ParsedCondition::Changed { .. } => {
+                // Logical and changed() conditions not yet supported, fall back to unconditional
+                tracing::warn!(
+                    "Logical and changed() conditions not yet supported in instruction hooks"
+                );
                 vec![OpCode::SetField {
                     object: state_reg,
                     path: target_field.to_string(),
                     value: value_reg,
+                    mapping: None,
                 }]
             }
         }
@@ -1881,15 +2399,212 @@ impl<S> TypedCompiler<S> {
                     condition_field: field.clone(),
                     condition_op: op.clone(),
                     condition_value: cond_value.clone(),
+                    mapping: None,
                 }]
             }
-            ParsedCondition::Logical { .. } => {
-                tracing::warn!("Logical conditions not yet supported in instruction hooks");
+            ParsedCondition::Logical { .. } | ParsedCondition::Changed { .. } => {
+                tracing::warn!(
+                    "Logical and changed() conditions not yet supported in instruction hooks"
+                );
                 vec![OpCode::SetFieldIncrement {
                     object: state_reg,
                     path: target_field.to_string(),
+                    mapping: None,
                 }]
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compiler() -> TypedCompiler<()> {
+        let identity = IdentitySpec {
+            primary_keys: vec!["id".to_string()],
+            lookup_indexes: vec![],
+        };
+        TypedCompiler::new(
+            TypedStreamSpec::new("Test".to_string(), identity, vec![]),
+            "Test".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_population_strategy_selects_write_opcode() {
+        let compiler = compiler();
+        let compile = |population: PopulationStrategy| {
+            compiler.compile_population(&population, 2, "stats.value", 10, 20, None)
+        };
+
+        assert!(matches!(
+            compile(PopulationStrategy::SetOnce),
+            OpCode::SetFieldIfNull { object: 2, ref path, value: 10, .. } if path == "stats.value"
+        ));
+        assert!(matches!(
+            compile(PopulationStrategy::LastWrite),
+            OpCode::SetField {
+                object: 2,
+                value: 10,
+                ..
+            }
+        ));
+        assert!(matches!(
+            compile(PopulationStrategy::Merge),
+            OpCode::SetField {
+                object: 2,
+                value: 10,
+                ..
+            }
+        ));
+        assert!(matches!(
+            compile(PopulationStrategy::Max),
+            OpCode::SetFieldMax {
+                object: 2,
+                value: 10,
+                ..
+            }
+        ));
+        assert!(matches!(
+            compile(PopulationStrategy::Min),
+            OpCode::SetFieldMin {
+                object: 2,
+                value: 10,
+                ..
+            }
+        ));
+        assert!(matches!(
+            compile(PopulationStrategy::Sum),
+            OpCode::SetFieldSum {
+                object: 2,
+                value: 10,
+                ..
+            }
+        ));
+        assert!(matches!(
+            compile(PopulationStrategy::Count),
+            OpCode::SetFieldIncrement { object: 2, .. }
+        ));
+        assert!(matches!(
+            compile(PopulationStrategy::Append),
+            OpCode::AppendToArray {
+                object: 2,
+                value: 10,
+                ..
+            }
+        ));
+        assert!(matches!(
+            compile(PopulationStrategy::UniqueCount),
+            OpCode::AddToUniqueSet { ref set_name, count_object: 2, key: 20, .. }
+                if set_name == "stats.value_unique_set"
+        ));
+        assert!(matches!(
+            compile(PopulationStrategy::Clear),
+            OpCode::ClearField { object: 2, ref path, .. } if path == "stats.value"
+        ));
+    }
+
+    #[test]
+    fn test_append_advances_aggregates_over_its_array() {
+        let aggregate = |kind: ArrayAggregateKind, path: &str| ArrayAggregateSpec {
+            kind,
+            path: path.to_string(),
+            field: Some("size".to_string()),
+        };
+        let sum = aggregate(ArrayAggregateKind::Sum, "trades.history");
+        let identity = IdentitySpec {
+            primary_keys: vec!["id".to_string()],
+            lookup_indexes: vec![],
+        };
+        let spec = TypedStreamSpec::<()>::new("Test".to_string(), identity, vec![])
+            .with_array_aggregates(vec![
+                sum.clone(),
+                aggregate(ArrayAggregateKind::Last, "trades.history"),
+                aggregate(ArrayAggregateKind::WindowAvg { n: 5 }, "trades.other"),
+            ]);
+        let compiler = TypedCompiler::new(spec, "Test".to_string());
+
+        let opcode = compiler.compile_population(
+            &PopulationStrategy::Append,
+            2,
+            "trades.history",
+            10,
+            20,
+            None,
+        );
+
+        let OpCode::AppendToArray { aggregates, .. } = opcode else {
+            panic!("expected AppendToArray, got {opcode:?}");
+        };
+        assert_eq!(aggregates, vec![sum]);
+    }
+
+    #[test]
+    fn test_population_defaults_to_last_write() {
+        let mapping: SerializableFieldMapping = serde_json::from_value(serde_json::json!({
+            "target_path": "value",
+            "source": {"Constant": 1},
+            "transform": null
+        }))
+        .unwrap();
+        assert!(matches!(mapping.population, PopulationStrategy::LastWrite));
+    }
+
+    #[test]
+    fn test_changed_mappings_run_after_other_writes() {
+        let set = |path: &str| OpCode::SetField {
+            object: 2,
+            path: path.to_string(),
+            value: 10,
+            mapping: None,
+        };
+        let gate = |path: &str| OpCode::SkipIfUnchanged {
+            object: 2,
+            snapshot: CHANGE_SNAPSHOT_REG,
+            path: path.to_string(),
+            initial: true,
+            skip: 1,
+        };
+
+        let first = order_change_detection(vec![gate("owner"), set("history"), set("owner")]);
+        // A merged handler appends its own mappings after the existing ones.
+        let mut merged = first.clone();
+        merged.extend(vec![
+            set("authority"),
+            gate("authority"),
+            set("authority_history"),
+        ]);
+        let ordered = order_change_detection(merged);
+
+        let summary: Vec<String> = ordered
+            .iter()
+            .map(|op| match op {
+                OpCode::SnapshotFields { paths, dest, .. } => {
+                    assert_eq!(*dest, CHANGE_SNAPSHOT_REG);
+                    format!("snapshot {}", paths.join(","))
+                }
+                OpCode::SkipIfUnchanged { path, .. } => format!("gate {path}"),
+                OpCode::SetField { path, .. } => format!("set {path}"),
+                other => panic!("unexpected opcode {other:?}"),
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                "snapshot owner,authority",
+                "set owner",
+                "set authority",
+                "gate owner",
+                "set history",
+                "gate authority",
+                "set authority_history",
+            ]
+        );
+        assert!(matches!(
+            order_change_detection(vec![set("owner")]).as_slice(),
+            [OpCode::SetField { .. }]
+        ));
+    }
+}
@@ -0,0 +1,288 @@
+//! Approximate memory accounting per entity.
+//!
+//! A single entity can outgrow every other limit: the array cap bounds each
+//! array's length but not its items, so a token with a runaway events array
+//! and a large capture can reach hundreds of KB on its own. Each
+//! `StateTable` records the estimated JSON size of every entity it writes,
+//! keeps a running total from the size deltas, and can report its largest
+//! entities.
+//!
+//! With a per-entity byte cap set, an entity that exceeds it is shrunk
+//! before it is stored. Its largest array loses its oldest items first, the
+//! same items the array cap would drop, until the entity fits or no array
+//! can give up more. The newest item of each array and hidden `__` fields
+//! are kept.
+//!
+//! The running total can drift when writes to the same entity race. Entities
+//! written since the last pass are re-measured from the stored state by
+//! [`EntitySizes::remeasure`], which the VM's periodic cleanup runs.
+
+use dashmap::{DashMap, DashSet};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Estimate the size of a JSON value in bytes
+pub fn estimate_json_size(value: &Value) -> usize {
+    match value {
+        Value::Null => 4,
+        Value::Bool(_) => 5,
+        Value::Number(_) => 8,
+        Value::String(s) => s.len() + 2,
+        Value::Array(arr) => 2 + arr.iter().map(|v| estimate_json_size(v) + 1).sum::<usize>(),
+        Value::Object(obj) => {
+            2 + obj
+                .iter()
+                .map(|(k, v)| k.len() + 3 + estimate_json_size(v) + 1)
+                .sum::<usize>()
+        }
+    }
+}
+
+/// An entity and its estimated size
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntitySize {
+    pub key: Value,
+    pub bytes: usize,
+}
+
+/// Items dropped from one array of an oversized entity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncation {
+    /// Dotted path of the array
+    pub path: String,
+    pub removed: usize,
+}
+
+/// Estimated sizes of the entities of one state table
+#[derive(Debug, Default)]
+pub struct EntitySizes {
+    sizes: DashMap<Value, usize>,
+    total: AtomicUsize,
+    /// Entities written since the last [`remeasure`](Self::remeasure)
+    touched: DashSet<Value>,
+    truncated: AtomicU64,
+}
+
+impl EntitySizes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the entity at `key` is now `bytes` large
+    pub fn record(&self, key: &Value, bytes: usize) {
+        let previous = self.sizes.insert(key.clone(), bytes).unwrap_or(0);
+        self.apply_delta(previous, bytes);
+        self.touched.insert(key.clone());
+    }
+
+    /// Forget the entity at `key`
+    pub fn remove(&self, key: &Value) {
+        if let Some((_, bytes)) = self.sizes.remove(key) {
+            self.apply_delta(bytes, 0);
+        }
+        self.touched.remove(key);
+    }
+
+    pub(crate) fn note_truncated(&self) {
+        self.truncated.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Estimated size of the entity at `key`
+    pub fn get(&self, key: &Value) -> Option<usize> {
+        self.sizes.get(key).map(|bytes| *bytes)
+    }
+
+    /// Estimated size of all entities
+    pub fn total_bytes(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Writes that exceeded the byte cap and were truncated
+    pub fn truncations(&self) -> u64 {
+        self.truncated.load(Ordering::Relaxed)
+    }
+
+    /// The `k` largest entities, largest first
+    pub fn largest(&self, k: usize) -> Vec<EntitySize> {
+        let mut sizes: Vec<EntitySize> = self
+            .sizes
+            .iter()
+            .map(|entry| EntitySize {
+                key: entry.key().clone(),
+                bytes: *entry.value(),
+            })
+            .collect();
+        sizes.sort_by_key(|size| std::cmp::Reverse(size.bytes));
+        sizes.truncate(k);
+        sizes
+    }
+
+    /// Re-measure the entities written since the last pass from `data` and
+    /// recount the total. Returns how many entities were re-measured.
+    pub fn remeasure(&self, data: &DashMap<Value, Value>) -> usize {
+        let touched: Vec<Value> = self.touched.iter().map(|key| key.clone()).collect();
+        for key in &touched {
+            self.touched.remove(key);
+            match data.get(key) {
+                Some(value) => {
+                    self.sizes.insert(key.clone(), estimate_json_size(&value));
+                }
+                None => {
+                    self.sizes.remove(key);
+                }
+            }
+        }
+        let total = self.sizes.iter().map(|entry| *entry.value()).sum();
+        self.total.store(total, Ordering::Relaxed);
+        touched.len()
+    }
+
+    fn apply_delta(&self, previous: usize, bytes: usize) {
+        if bytes >= previous {
+            self.total.fetch_add(bytes - previous, Ordering::Relaxed);
+        } else {
+            // Saturate rather than wrap when racing writes have let the
+            // total drift below an entity's size
+            let _ = self
+                .total
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                    Some(total.saturating_sub(previous - bytes))
+                });
+        }
+    }
+}
+
+/// Drop the oldest items of `value`'s largest arrays until its estimated
+/// size, `bytes`, is at most `max_bytes`. Returns the size after truncation
+/// and the arrays truncated, in the order they were first cut.
+pub fn truncate_to_fit(
+    value: &mut Value,
+    mut bytes: usize,
+    max_bytes: usize,
+) -> (usize, Vec<Truncation>) {
+    let mut arrays = Vec::new();
+    collect_arrays(value, &mut Vec::new(), &mut arrays);
+    let mut truncations: Vec<Truncation> = Vec::new();
+
+    while bytes > max_bytes {
+        let Some(largest) = arrays
+            .iter_mut()
+            .filter(|array| array.len > 1)
+            .max_by_key(|array| array.bytes)
+        else {
+            break;
+        };
+        let Some(Value::Array(items)) = pointer_mut(value, &largest.path) else {
+            break;
+        };
+        let removed = estimate_json_size(&items.remove(0)) + 1;
+        largest.len -= 1;
+        largest.bytes -= removed;
+        bytes -= removed;
+
+        let path = largest.path.join(".");
+        match truncations.iter_mut().find(|t| t.path == path) {
+            Some(truncation) => truncation.removed += 1,
+            None => truncations.push(Truncation { path, removed: 1 }),
+        }
+    }
+
+    (bytes, truncations)
+}
+
+struct ArraySize {
+    path: Vec<String>,
+    len: usize,
+    bytes: usize,
+}
+
+fn collect_arrays(value: &Value, path: &mut Vec<String>, arrays: &mut Vec<ArraySize>) {
+    match value {
+        Value::Array(items) => arrays.push(ArraySize {
+            path: path.clone(),
+            len: items.len(),
+            bytes: estimate_json_size(value),
+        }),
+        Value::Object(fields) => {
+            for (field, value) in fields {
+                if field.starts_with("__") {
+                    continue;
+                }
+                path.push(field.clone());
+                collect_arrays(value, path, arrays);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn pointer_mut<'a>(value: &'a mut Value, path: &[String]) -> Option<&'a mut Value> {
+    path.iter()
+        .try_fold(value, |value, field| value.as_object_mut()?.get_mut(field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn total_follows_size_deltas() {
+        let sizes = EntitySizes::new();
+        sizes.record(&json!("a"), 100);
+        sizes.record(&json!("b"), 40);
+        sizes.record(&json!("a"), 70);
+        assert_eq!(sizes.total_bytes(), 110);
+
+        sizes.remove(&json!("b"));
+        assert_eq!(sizes.total_bytes(), 70);
+        assert_eq!(
+            sizes.largest(5),
+            vec![EntitySize {
+                key: json!("a"),
+                bytes: 70
+            }]
+        );
+    }
+
+    #[test]
+    fn remeasure_corrects_drift_from_stored_state() {
+        let sizes = EntitySizes::new();
+        let data = DashMap::new();
+        data.insert(json!("a"), json!({ "name": "token" }));
+        sizes.record(&json!("a"), 1_000);
+        sizes.record(&json!("gone"), 50);
+
+        assert_eq!(sizes.remeasure(&data), 2);
+        let bytes = estimate_json_size(&json!({ "name": "token" }));
+        assert_eq!(sizes.get(&json!("a")), Some(bytes));
+        assert_eq!(sizes.get(&json!("gone")), None);
+        assert_eq!(sizes.total_bytes(), bytes);
+        assert_eq!(sizes.remeasure(&data), 0, "nothing written since");
+    }
+
+    #[test]
+    fn largest_arrays_lose_their_oldest_items_first() {
+        let mut value = json!({
+            "events": ["e".repeat(40), "e".repeat(40), "e".repeat(40)],
+            "trades": { "history": [1, 2, 3, 4, 5, 6] },
+            "__array_aggregates": { "hidden": [1, 2, 3] },
+        });
+        let bytes = estimate_json_size(&value);
+        let (after, truncations) = truncate_to_fit(&mut value, bytes, bytes - 60);
+
+        assert_eq!(after, estimate_json_size(&value));
+        assert!(after <= bytes - 60);
+        assert_eq!(
+            truncations,
+            vec![Truncation {
+                path: "events".to_string(),
+                removed: 2,
+            }]
+        );
+        assert_eq!(value["events"], json!(["e".repeat(40)]));
+        assert_eq!(value["__array_aggregates"]["hidden"], json!([1, 2, 3]));
+    }
+}
@@ -0,0 +1,312 @@
+//! Shape checks on incoming events, before their handlers run.
+//!
+//! A parser change that renames or retypes a field otherwise shows up as
+//! nulls scattered through entities. The compiler derives an [`EventSchema`]
+//! for each event type from the mappings that read it and the types of the
+//! fields they write:
+//!
+//! - a path read without a default into a non-optional field must be present;
+//! - numeric, boolean, string, pubkey and array fields must hold values of
+//!   that shape. Numbers may arrive as decimal strings, and pubkeys must be
+//!   32 to 44 base58 characters.
+//!
+//! Paths read through a transform, or into encoded fields, are only checked
+//! for presence.
+//!
+//! Validation is off unless enabled with
+//! [`VmContext::set_event_validation`](crate::vm::VmContext::set_event_validation)
+//! or `HYPERSTACK_EVENT_VALIDATION=warn|strict`. In [`ValidationMode::Warn`]
+//! the report is recorded as a warning and the event is processed anyway. In
+//! [`ValidationMode::Strict`] the event fails with
+//! [`VmError::InvalidEvent`](crate::vm_error::VmError::InvalidEvent) and no
+//! handler runs.
+
+use crate::ast::{BaseType, FieldTypeInfo};
+use serde_json::Value;
+use std::fmt;
+
+/// What to do with an event that fails validation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// Record a warning and process the event
+    #[default]
+    Warn,
+    /// Reject the event before any handler runs
+    Strict,
+}
+
+impl ValidationMode {
+    /// Parse `HYPERSTACK_EVENT_VALIDATION`: `warn`, `1` or `true` for
+    /// [`Warn`](Self::Warn), `strict` for [`Strict`](Self::Strict)
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "warn" | "1" | "true" => Some(ValidationMode::Warn),
+            "strict" => Some(ValidationMode::Strict),
+            _ => None,
+        }
+    }
+}
+
+/// Shape a field's value must have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueShape {
+    /// Only checked for presence
+    Any,
+    Number,
+    String,
+    Boolean,
+    Pubkey,
+    Array,
+}
+
+impl ValueShape {
+    /// Shape of the event value a mapping writes into `field`
+    pub fn of_field(field: &FieldTypeInfo) -> Self {
+        if field.encoding.is_some() {
+            return ValueShape::Any;
+        }
+        if field.is_array {
+            return ValueShape::Array;
+        }
+        match field.base_type {
+            BaseType::Integer | BaseType::Float | BaseType::Timestamp => ValueShape::Number,
+            BaseType::String => ValueShape::String,
+            BaseType::Boolean => ValueShape::Boolean,
+            BaseType::Pubkey => ValueShape::Pubkey,
+            BaseType::Array => ValueShape::Array,
+            BaseType::Object | BaseType::Binary | BaseType::Any => ValueShape::Any,
+        }
+    }
+
+    fn accepts(self, value: &Value) -> bool {
+        match (self, value) {
+            (ValueShape::Any, _) => true,
+            (ValueShape::Number, Value::Number(_)) => true,
+            (ValueShape::Number, Value::String(text)) => {
+                text.parse::<i128>().is_ok()
+                    || text.parse::<u128>().is_ok()
+                    || text.parse::<f64>().is_ok()
+            }
+            (ValueShape::String, Value::String(_)) => true,
+            (ValueShape::Boolean, Value::Bool(_)) => true,
+            (ValueShape::Pubkey, Value::String(text)) => is_pubkey(text),
+            (ValueShape::Array, Value::Array(_)) => true,
+            _ => false,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ValueShape::Any => "any",
+            ValueShape::Number => "number",
+            ValueShape::String => "string",
+            ValueShape::Boolean => "boolean",
+            ValueShape::Pubkey => "pubkey",
+            ValueShape::Array => "array",
+        }
+    }
+}
+
+fn is_pubkey(text: &str) -> bool {
+    (32..=44).contains(&text.len())
+        && text
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() && !matches!(b, b'0' | b'O' | b'I' | b'l'))
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// One path of an event and what it must hold
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldCheck {
+    pub path: Vec<String>,
+    pub shape: ValueShape,
+    /// The path must be present and not null
+    pub required: bool,
+}
+
+/// Checks for one event type, compiled from the mappings that read it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventSchema {
+    pub checks: Vec<FieldCheck>,
+}
+
+impl EventSchema {
+    /// Add `check`, merging it with an existing check on the same path: the
+    /// path is required if either requires it, and only checked for
+    /// presence if they disagree on its shape
+    pub fn add(&mut self, check: FieldCheck) {
+        match self.checks.iter_mut().find(|c| c.path == check.path) {
+            Some(existing) => {
+                existing.required |= check.required;
+                if existing.shape != check.shape {
+                    existing.shape = ValueShape::Any;
+                }
+            }
+            None => self.checks.push(check),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty()
+    }
+
+    /// Problems with `event`, empty when it is well-formed
+    pub fn validate(&self, event: &Value) -> Vec<FieldIssue> {
+        let mut issues = Vec::new();
+        for check in &self.checks {
+            let value = check
+                .path
+                .iter()
+                .try_fold(event, |value, segment| value.get(segment))
+                .filter(|value| !value.is_null());
+            match value {
+                None if check.required => issues.push(FieldIssue::Missing {
+                    path: check.path.join("."),
+                }),
+                Some(value) if !check.shape.accepts(value) => issues.push(FieldIssue::WrongShape {
+                    path: check.path.join("."),
+                    expected: check.shape,
+                    found: json_type(value),
+                }),
+                _ => {}
+            }
+        }
+        issues
+    }
+}
+
+/// A problem with one path of an event
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldIssue {
+    /// A required path is missing or null
+    Missing { path: String },
+    /// The path holds a value of the wrong shape
+    WrongShape {
+        path: String,
+        expected: ValueShape,
+        found: &'static str,
+    },
+}
+
+impl FieldIssue {
+    pub fn path(&self) -> &str {
+        match self {
+            FieldIssue::Missing { path } | FieldIssue::WrongShape { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for FieldIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldIssue::Missing { path } => write!(f, "{} is missing", path),
+            FieldIssue::WrongShape {
+                path,
+                expected,
+                found,
+            } => write!(f, "{} should be a {}, got {}", path, expected.name(), found),
+        }
+    }
+}
+
+/// Everything wrong with one event
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    pub event_type: String,
+    pub issues: Vec<FieldIssue>,
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Event {} failed validation: ", self.event_type)?;
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Validation outcomes for one event type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidationCounts {
+    /// Events checked against a schema
+    pub checked: u64,
+    /// Events with at least one issue
+    pub failed: u64,
+    /// Failed events rejected in strict mode
+    pub rejected: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> EventSchema {
+        let mut schema = EventSchema::default();
+        for (path, shape, required) in [
+            (vec!["data", "amount"], ValueShape::Number, true),
+            (vec!["accounts", "mint"], ValueShape::Pubkey, true),
+            (vec!["data", "memo"], ValueShape::String, false),
+        ] {
+            schema.add(FieldCheck {
+                path: path.into_iter().map(String::from).collect(),
+                shape,
+                required,
+            });
+        }
+        schema
+    }
+
+    #[test]
+    fn well_formed_events_pass() {
+        let event = json!({
+            "data": { "amount": "18446744073709551615" },
+            "accounts": { "mint": "So11111111111111111111111111111111111111112" },
+        });
+        assert_eq!(schema().validate(&event), vec![]);
+    }
+
+    #[test]
+    fn each_problem_is_reported_by_path() {
+        let event = json!({
+            "data": { "amt": 5, "memo": 7 },
+            "accounts": { "mint": "not-a-key" },
+        });
+        let issues = schema().validate(&event);
+        assert_eq!(
+            issues.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "data.amount is missing",
+                "accounts.mint should be a pubkey, got string",
+                "data.memo should be a string, got number",
+            ]
+        );
+    }
+
+    #[test]
+    fn merged_checks_keep_the_strictest_presence() {
+        let mut schema = schema();
+        schema.add(FieldCheck {
+            path: vec!["data".to_string(), "memo".to_string()],
+            shape: ValueShape::Number,
+            required: true,
+        });
+        assert_eq!(schema.checks.len(), 3);
+        assert_eq!(schema.checks[2].shape, ValueShape::Any);
+        assert!(schema.checks[2].required);
+    }
+}
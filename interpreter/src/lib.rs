@@ -24,10 +24,16 @@
 //!
 //! - `otel` - OpenTelemetry integration for distributed tracing and metrics
 
+pub mod array_aggregate;
 pub mod ast;
+pub mod big_numbers;
+pub mod block_time_cache;
 pub mod canonical_log;
+pub mod clock;
 pub mod compiler;
+pub mod entity_size;
 pub mod event_type_helpers;
+pub mod event_validation;
 pub mod metrics_context;
 pub mod proto_router;
 pub mod resolvers;
@@ -37,30 +43,41 @@ pub mod rust;
 pub mod scheduler;
 pub mod slot_hash_cache;
 pub mod spec_trait;
+pub mod testkit;
 pub mod typescript;
+pub mod unique_set;
 pub mod versioned;
 pub mod vm;
+pub mod vm_actor;
+pub mod vm_error;
 pub mod vm_metrics;
+pub mod vm_trace;
 
 // Re-export slot hash cache functions
 pub use slot_hash_cache::{get_slot_hash, record_slot_hash};
 
+// Re-export block time cache functions
+pub use block_time_cache::{get_block_time, record_block_time, set_slot_duration_ms};
+
+pub use big_numbers::BigNumberMode;
 pub use canonical_log::{CanonicalLog, LogLevel};
+pub use clock::{Clock, SharedClock, SystemClock};
 pub use metrics_context::{FieldAccessor, FieldRef, MetricsContext};
 pub use resolvers::{
     InstructionContext, KeyResolution, ResolveContext, ReverseLookupUpdater, TokenMetadata,
 };
 pub use runtime_resolvers::{
     InProcessResolver, ResolverApplyFuture, ResolverBatchFuture, ResolverBatchResult,
-    RuntimeResolver, RuntimeResolverBatchRequest, RuntimeResolverBatchResponse,
+    ResolverHandoff, RuntimeResolver, RuntimeResolverBatchRequest, RuntimeResolverBatchResponse,
     RuntimeResolverRequest, RuntimeResolverResponse, SharedRuntimeResolver,
 };
 pub use typescript::{write_typescript_to_file, TypeScriptCompiler, TypeScriptConfig};
 pub use vm::{
     CapacityWarning, CleanupResult, DirtyTracker, FieldChange, PendingAccountUpdate,
     PendingQueueStats, QueuedAccountUpdate, ResolverRequest, ResolverTarget, ScheduledCallback,
-    StateTableConfig, UpdateContext, VmMemoryStats,
+    StateTableConfig, UpdateContext, VmError, VmMemoryStats,
 };
+pub use vm_actor::{VmCallError, VmHandle};
 
 // Re-export macros for convenient use
 // The field! macro is the new recommended way to create field references
@@ -68,7 +85,13 @@ pub use vm::{
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 
+/// A change to one entity, emitted by the VM.
+///
+/// Emission is deterministic: the same events produce byte-identical
+/// mutations. Object keys in `patch` serialize in sorted order, and `append`
+/// lists its paths in sorted order.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mutation {
     pub export: String,
@@ -76,6 +99,47 @@ pub struct Mutation {
     pub patch: Value,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub append: Vec<String>,
+    /// Which opcode wrote each patched path, recorded only in provenance mode
+    /// (see [`vm::VmContext::set_provenance_mode`]). Never sent to clients.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub provenance: Option<BTreeMap<String, FieldProvenance>>,
+    /// The entity was deleted, see [`ast::DeleteTrigger`]. A tombstone has an
+    /// empty `patch`, and clients drop the entity under `key`.
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub tombstone: bool,
+}
+
+impl Mutation {
+    /// A mutation applying `patch` to `export`'s entity `key`
+    pub fn new(export: impl Into<String>, key: Value, patch: Value) -> Self {
+        Self {
+            export: export.into(),
+            key,
+            patch,
+            append: Vec::new(),
+            provenance: None,
+            tombstone: false,
+        }
+    }
+
+    /// A tombstone deleting `export`'s entity `key`
+    pub fn tombstone(export: impl Into<String>, key: Value) -> Self {
+        Self {
+            tombstone: true,
+            ..Self::new(export, key, Value::Object(Default::default()))
+        }
+    }
+}
+
+/// The opcode that last wrote a field in a handler run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldProvenance {
+    /// Index of the opcode within its handler
+    pub opcode: usize,
+    /// The mapping the opcode was compiled from, e.g. `handlers[2].mappings[0]`.
+    /// None for writes not compiled from a mapping, such as instruction hooks.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mapping: Option<String>,
 }
 
 /// Generic wrapper for event data that includes context metadata
@@ -86,6 +150,7 @@ pub struct Mutation {
 /// ```json
 /// {
 ///   "timestamp": 1234567890,
+///   "type": "ore::DeployIxState",
 ///   "data": { /* event-specific data */ },
 ///   "slot": 381471241,
 ///   "signature": "4xNEYTVL8DB28W87..."
@@ -95,6 +160,9 @@ pub struct Mutation {
 pub struct EventWrapper<T = Value> {
     /// Unix timestamp when the event was processed
     pub timestamp: i64,
+    /// Event type the event was routed as, e.g. `ore::DeployIxState`
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<String>,
     /// The event-specific data
     pub data: T,
     /// Optional slot number from UpdateContext
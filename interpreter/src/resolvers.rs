@@ -249,7 +249,9 @@ impl ResolverRegistry {
             | crate::ast::ComputedExpr::Var { .. }
             | crate::ast::ComputedExpr::ByteArray { .. }
             | crate::ast::ComputedExpr::ContextSlot
-            | crate::ast::ComputedExpr::ContextTimestamp => {}
+            | crate::ast::ComputedExpr::ContextTimestamp
+            | crate::ast::ComputedExpr::Now
+            | crate::ast::ComputedExpr::ArrayAggregate { .. } => {}
             crate::ast::ComputedExpr::UnwrapOr { expr, .. }
             | crate::ast::ComputedExpr::Cast { expr, .. }
             | crate::ast::ComputedExpr::Paren { expr }
@@ -260,6 +262,8 @@ impl ResolverRegistry {
             | crate::ast::ComputedExpr::U64FromBeBytes { bytes: expr }
             | crate::ast::ComputedExpr::JsonToBytes { expr }
             | crate::ast::ComputedExpr::Keccak256 { expr }
+            | crate::ast::ComputedExpr::SlotsToSecs { expr }
+            | crate::ast::ComputedExpr::SecsToSlots { expr }
             | crate::ast::ComputedExpr::Unary { expr, .. } => {
                 self.validate_computed_expr(expr, errors);
             }
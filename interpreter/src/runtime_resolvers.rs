@@ -79,104 +79,139 @@ pub trait RuntimeResolver: Send + Sync {
                 return Vec::new();
             }
 
-            let mut cached = Vec::new();
-            let mut pending = Vec::new();
-            let mut invalid = Vec::new();
+            let handoff =
+                ResolverHandoff::take(&mut vm.lock().unwrap_or_else(|e| e.into_inner()), requests);
+            let resolved = handoff.resolve(self).await;
+            handoff.apply(
+                &mut vm.lock().unwrap_or_else(|e| e.into_inner()),
+                bytecode,
+                resolved,
+            )
+        })
+    }
+}
 
-            {
-                let mut vm_guard = vm.lock().unwrap_or_else(|e| e.into_inner());
+/// Resolver requests taken from the VM, split between the ones its cache
+/// answers and the ones for the backend.
+///
+/// Taking and applying need the VM, resolving doesn't, so the VM is free to
+/// process other events while the backend answers.
+#[derive(Debug, Default)]
+pub struct ResolverHandoff {
+    cached: Vec<(ResolverRequest, Value)>,
+    pending: Vec<PendingRuntimeResolverRequest>,
+}
 
-                for request in requests {
-                    let canonical_key =
-                        runtime_resolver_cache_key(&request.resolver, &request.input);
+impl ResolverHandoff {
+    /// Split `requests`, putting the ones no backend can answer back in the
+    /// VM's queue
+    pub fn take(vm: &mut VmContext, requests: Vec<ResolverRequest>) -> Self {
+        let mut handoff = Self::default();
+        let mut invalid = Vec::new();
 
-                    if let Some(resolved_value) = vm_guard.get_cached_resolver_value(&canonical_key)
-                    {
-                        cached.push((request, resolved_value));
-                        continue;
-                    }
+        for request in requests {
+            let canonical_key = runtime_resolver_cache_key(&request.resolver, &request.input);
 
-                    match runtime_request_from_vm_request(&request) {
-                        Some(backend_request) => pending.push(PendingRuntimeResolverRequest {
-                            request,
-                            backend_request,
-                        }),
-                        None => invalid.push(request),
-                    }
-                }
+            if let Some(resolved_value) = vm.get_cached_resolver_value(&canonical_key) {
+                handoff.cached.push((request, resolved_value));
+                continue;
+            }
 
-                if !invalid.is_empty() {
-                    vm_guard.restore_resolver_requests(invalid);
-                }
+            match runtime_request_from_vm_request(&request) {
+                Some(backend_request) => handoff.pending.push(PendingRuntimeResolverRequest {
+                    request,
+                    backend_request,
+                }),
+                None => invalid.push(request),
             }
+        }
 
-            let resolved_map = if pending.is_empty() {
-                Ok(HashMap::new())
-            } else {
-                let mut unique = HashMap::new();
-                for entry in &pending {
-                    unique
-                        .entry(entry.backend_request.key().to_string())
-                        .or_insert_with(|| entry.backend_request.clone());
-                }
+        if !invalid.is_empty() {
+            vm.restore_resolver_requests(invalid);
+        }
+        handoff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cached.is_empty() && self.pending.is_empty()
+    }
 
-                let unique_requests: Vec<RuntimeResolverRequest> = unique.into_values().collect();
-                self.resolve_batch(&unique_requests).await
-            };
+    /// Ask `resolver` for the requests the cache didn't answer, once per key
+    pub async fn resolve<R: RuntimeResolver + ?Sized>(&self, resolver: &R) -> ResolverBatchResult {
+        if self.pending.is_empty() {
+            return Ok(HashMap::new());
+        }
 
-            let mut mutations = Vec::new();
-            let mut failed = Vec::new();
-            let mut vm_guard = vm.lock().unwrap_or_else(|e| e.into_inner());
+        let mut unique = HashMap::new();
+        for entry in &self.pending {
+            unique
+                .entry(entry.backend_request.key().to_string())
+                .or_insert_with(|| entry.backend_request.clone());
+        }
 
-            for (request, resolved_value) in cached {
-                match vm_guard.apply_resolver_result(bytecode, &request.cache_key, resolved_value) {
-                    Ok(mut new_mutations) => mutations.append(&mut new_mutations),
-                    Err(err) => {
-                        tracing::warn!(
-                            cache_key = %request.cache_key,
-                            error = %err,
-                            "Failed to apply cached resolver result"
-                        );
-                        failed.push(request);
-                    }
+        let unique_requests: Vec<RuntimeResolverRequest> = unique.into_values().collect();
+        resolver.resolve_batch(&unique_requests).await
+    }
+
+    /// Apply the cached values and what the backend `resolved`, putting the
+    /// requests that failed back in the VM's queue
+    pub fn apply(
+        self,
+        vm: &mut VmContext,
+        bytecode: &MultiEntityBytecode,
+        resolved: ResolverBatchResult,
+    ) -> Vec<Mutation> {
+        let mut mutations = Vec::new();
+        let mut failed = Vec::new();
+
+        for (request, resolved_value) in self.cached {
+            match vm.apply_resolver_result(bytecode, &request.cache_key, resolved_value) {
+                Ok(mut new_mutations) => mutations.append(&mut new_mutations),
+                Err(err) => {
+                    tracing::warn!(
+                        cache_key = %request.cache_key,
+                        error = %err,
+                        "Failed to apply cached resolver result"
+                    );
+                    failed.push(request);
                 }
             }
+        }
 
-            match resolved_map {
-                Ok(resolved_map) => {
-                    for entry in pending {
-                        match resolved_map.get(entry.backend_request.key()) {
-                            Some(resolved_value) => match vm_guard.apply_resolver_result(
-                                bytecode,
-                                &entry.request.cache_key,
-                                resolved_value.clone(),
-                            ) {
-                                Ok(mut new_mutations) => mutations.append(&mut new_mutations),
-                                Err(err) => {
-                                    tracing::warn!(
-                                        cache_key = %entry.request.cache_key,
-                                        error = %err,
-                                        "Failed to apply resolver result"
-                                    );
-                                    failed.push(entry.request);
-                                }
-                            },
-                            None => failed.push(entry.request),
-                        }
+        match resolved {
+            Ok(resolved_map) => {
+                for entry in self.pending {
+                    match resolved_map.get(entry.backend_request.key()) {
+                        Some(resolved_value) => match vm.apply_resolver_result(
+                            bytecode,
+                            &entry.request.cache_key,
+                            resolved_value.clone(),
+                        ) {
+                            Ok(mut new_mutations) => mutations.append(&mut new_mutations),
+                            Err(err) => {
+                                tracing::warn!(
+                                    cache_key = %entry.request.cache_key,
+                                    error = %err,
+                                    "Failed to apply resolver result"
+                                );
+                                failed.push(entry.request);
+                            }
+                        },
+                        None => failed.push(entry.request),
                     }
                 }
-                Err(err) => {
-                    tracing::warn!(error = %err, "Runtime resolver backend request failed");
-                    failed.extend(pending.into_iter().map(|entry| entry.request));
-                }
             }
-
-            if !failed.is_empty() {
-                vm_guard.restore_resolver_requests(failed);
+            Err(err) => {
+                tracing::warn!(error = %err, "Runtime resolver backend request failed");
+                failed.extend(self.pending.into_iter().map(|entry| entry.request));
             }
+        }
 
-            mutations
-        })
+        if !failed.is_empty() {
+            vm.restore_resolver_requests(failed);
+        }
+
+        mutations
     }
 }
 
@@ -1,5 +1,5 @@
 use crate::ast::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone)]
 pub struct RustOutput {
@@ -7,6 +7,17 @@ pub struct RustOutput {
     pub lib_rs: String,
     pub types_rs: String,
     pub entity_rs: String,
+    /// Command line binary, when generated with `with_cli`
+    pub cli: Option<RustBinary>,
+    /// `tests/live_contract.rs`, when generated with `with_contract_tests`
+    pub contract_test: Option<String>,
+}
+
+/// A binary target written to `src/bin/<name>.rs`
+#[derive(Debug, Clone)]
+pub struct RustBinary {
+    pub name: String,
+    pub source: String,
 }
 
 impl RustOutput {
@@ -61,6 +72,17 @@ pub fn write_rust_crate(
     std::fs::write(crate_dir.join("src/lib.rs"), &output.lib_rs)?;
     std::fs::write(crate_dir.join("src/types.rs"), &output.types_rs)?;
     std::fs::write(crate_dir.join("src/entity.rs"), &output.entity_rs)?;
+    if let Some(cli) = &output.cli {
+        std::fs::create_dir_all(crate_dir.join("src/bin"))?;
+        std::fs::write(
+            crate_dir.join(format!("src/bin/{}.rs", cli.name)),
+            &cli.source,
+        )?;
+    }
+    if let Some(contract_test) = &output.contract_test {
+        std::fs::create_dir_all(crate_dir.join("tests"))?;
+        std::fs::write(crate_dir.join("tests/live_contract.rs"), contract_test)?;
+    }
     Ok(())
 }
 
@@ -75,6 +97,45 @@ pub fn write_rust_module(
     Ok(())
 }
 
+/// The `EventWrapper` events captured by `#[event]` arrive in, with a helper
+/// classifying them by their routed event type
+const EVENT_WRAPPER_RS: &str = r#"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventWrapper<T> {
+    #[serde(default, deserialize_with = "serde_utils::deserialize_i64")]
+    pub timestamp: i64,
+    /// Event type the event was routed as, e.g. `ore::DeployIxState`
+    #[serde(default, rename = "type")]
+    pub event_type: Option<String>,
+    pub data: T,
+    #[serde(default)]
+    pub slot: Option<f64>,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl<T> EventWrapper<T> {
+    /// Classify the event as one of an entity's event types, e.g.
+    /// `event.classify::<OreRoundEventType>()`. `None` when the server
+    /// didn't send the event type.
+    pub fn classify<E: for<'a> From<&'a str>>(&self) -> Option<E> {
+        self.event_type.as_deref().map(E::from)
+    }
+}
+
+impl<T: Default> Default for EventWrapper<T> {
+    fn default() -> Self {
+        Self {
+            timestamp: 0,
+            event_type: None,
+            data: T::default(),
+            slot: None,
+            signature: None,
+        }
+    }
+}
+"#;
+
 pub(crate) struct RustCompiler {
     spec: SerializableStreamSpec,
     entity_name: String,
@@ -100,6 +161,8 @@ impl RustCompiler {
             lib_rs: self.generate_lib_rs(),
             types_rs: self.generate_types_rs(),
             entity_rs: self.generate_entity_rs(),
+            cli: None,
+            contract_test: None,
         }
     }
 
@@ -110,6 +173,9 @@ name = "{}"
 version = "0.1.0"
 edition = "2021"
 
+[features]
+test-util = ["hyperstack-sdk/test-util"]
+
 [dependencies]
 hyperstack-sdk = "{}"
 serde = {{ version = "1", features = ["derive"] }}
@@ -147,7 +213,7 @@ pub use hyperstack_sdk::{{ConnectionState, HyperStack, Stack, Update, Views}};
         for section in &self.spec.sections {
             if !Self::is_root_section(&section.name)
                 && section.fields.iter().any(|field| field.emit)
-                && generated.insert(section.name.clone())
+                && generated.insert(section_type_name(&self.entity_name, section))
             {
                 output.push_str(&self.generate_struct_for_section(section));
                 output.push_str("\n\n");
@@ -155,14 +221,21 @@ pub use hyperstack_sdk::{{ConnectionState, HyperStack, Stack, Update, Views}};
         }
 
         output.push_str(&self.generate_main_entity_struct());
+        output.push_str(&self.generate_key_struct());
+        output.push_str(&self.generate_fields_struct());
         output.push_str(&self.generate_resolved_types(&mut generated));
+        output.push_str("\n\n");
+        output.push_str(&generate_event_type_enum(
+            &self.entity_name,
+            &self.spec.event_type_names(),
+        ));
         output.push_str(&self.generate_event_wrapper());
 
         output
     }
 
     pub(crate) fn generate_struct_for_section(&self, section: &EntitySection) -> String {
-        let struct_name = format!("{}{}", self.entity_name, to_pascal_case(&section.name));
+        let struct_name = section_type_name(&self.entity_name, section);
         let mut fields = Vec::new();
 
         for field in &section.fields {
@@ -170,17 +243,21 @@ pub use hyperstack_sdk::{{ConnectionState, HyperStack, Stack, Update, Views}};
                 continue;
             }
             let field_name = to_snake_case(&field.field_name);
-            let rust_type = self.field_type_to_rust(field);
-            let serde_attr = self.serde_attr_for_field(field);
+            let target_path = format!("{}.{}", section.name, field.field_name);
+            let (rust_type, serde_attr) = self.field_type_and_serde_attr(&target_path, field);
 
             fields.push(format!(
-                "    {}\n    pub {}: {},",
-                serde_attr, field_name, rust_type
+                "{}    {}\n    pub {}: {},",
+                doc_comment(field.docs.as_ref(), "    "),
+                serde_attr,
+                field_name,
+                rust_type
             ));
         }
 
         format!(
-            "#[derive(Debug, Clone, Serialize, Deserialize, Default)]\npub struct {} {{\n{}\n}}",
+            "{}#[derive(Debug, Clone, Serialize, Deserialize, Default)]\npub struct {} {{\n{}\n}}",
+            doc_comment(section.docs.as_ref(), ""),
             struct_name,
             fields.join("\n")
         )
@@ -198,10 +275,12 @@ pub use hyperstack_sdk::{{ConnectionState, HyperStack, Stack, Update, Views}};
                 && section.fields.iter().any(|field| field.emit)
             {
                 let field_name = to_snake_case(&section.name);
-                let type_name = format!("{}{}", self.entity_name, to_pascal_case(&section.name));
+                let type_name = section_type_name(&self.entity_name, section);
                 fields.push(format!(
-                    "    #[serde(default)]\n    pub {}: {},",
-                    field_name, type_name
+                    "{}    #[serde(default)]\n    pub {}: {},",
+                    doc_comment(section.docs.as_ref(), "    "),
+                    field_name,
+                    type_name
                 ));
             }
         }
@@ -213,23 +292,195 @@ pub use hyperstack_sdk::{{ConnectionState, HyperStack, Stack, Update, Views}};
                         continue;
                     }
                     let field_name = to_snake_case(&field.field_name);
-                    let rust_type = self.field_type_to_rust(field);
-                    let serde_attr = self.serde_attr_for_field(field);
+                    let (rust_type, serde_attr) =
+                        self.field_type_and_serde_attr(&field.field_name, field);
                     fields.push(format!(
-                        "    {}\n    pub {}: {},",
-                        serde_attr, field_name, rust_type
+                        "{}    {}\n    pub {}: {},",
+                        doc_comment(field.docs.as_ref(), "    "),
+                        serde_attr,
+                        field_name,
+                        rust_type
                     ));
                 }
             }
         }
 
         format!(
-            "#[derive(Debug, Clone, Serialize, Deserialize, Default)]\npub struct {} {{\n{}\n}}",
+            "{}#[derive(Debug, Clone, Serialize, Deserialize, Default)]\npub struct {} {{\n{}\n}}",
+            doc_comment(self.spec.docs.as_ref(), ""),
             self.entity_name,
             fields.join("\n")
         )
     }
 
+    /// Generate the typed `{Entity}Key` builder for the entity's primary key.
+    ///
+    /// The key formats to (and parses from) the canonical key string described
+    /// by the AST's key recipe, so callers never hand-build key strings.
+    pub(crate) fn generate_key_struct(&self) -> String {
+        let Some(recipe) = self.spec.key_recipe() else {
+            return String::new();
+        };
+
+        let key_name = format!("{}Key", self.entity_name);
+        let name = to_snake_case(&recipe.field_name);
+        let ty = self.key_type_to_rust(&recipe);
+
+        let (new_param, new_field, parse_body) = if ty == "String" {
+            (
+                format!("{}: impl Into<String>", name),
+                format!("{}: {}.into()", name, name),
+                format!("Some(Self {{ {}: key.to_string() }})", name),
+            )
+        } else {
+            (
+                format!("{}: {}", name, ty),
+                name.clone(),
+                format!("Some(Self {{ {}: key.parse().ok()? }})", name),
+            )
+        };
+
+        format!(
+            r#"
+
+/// Typed key for `{entity}` entities (canonical format: `{{{name}}}`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct {key_name} {{
+    pub {name}: {ty},
+}}
+
+impl {key_name} {{
+    pub fn new({new_param}) -> Self {{
+        Self {{ {new_field} }}
+    }}
+
+    /// Parse a canonical key string.
+    pub fn parse(key: &str) -> Option<Self> {{
+        {parse_body}
+    }}
+}}
+
+impl std::fmt::Display for {key_name} {{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {{
+        write!(f, "{{}}", self.{name})
+    }}
+}}
+
+impl hyperstack_sdk::EntityKey for {key_name} {{
+    fn to_key_string(&self) -> String {{
+        self.to_string()
+    }}
+}}
+"#,
+            entity = self.entity_name,
+            key_name = key_name,
+            name = name,
+            ty = ty,
+            new_param = new_param,
+            new_field = new_field,
+            parse_body = parse_body,
+        )
+    }
+
+    /// Generate `{Entity}::fields()`, which returns accessors for every
+    /// scalar field as a `hyperstack_sdk::SortField` carrying how its values
+    /// compare, for client-side sorting with `ViewHandle::sorted_by`.
+    pub(crate) fn generate_fields_struct(&self) -> String {
+        let mut sortable: Vec<(Vec<String>, &str)> = Vec::new();
+        for section in &self.spec.sections {
+            for field in section.fields.iter().filter(|field| field.emit) {
+                let (base_type, is_array) = match field.encoding {
+                    // Decimal strings still compare as numbers
+                    Some(ValueEncoding::DecimalString) => (field.base_type.clone(), field.is_array),
+                    _ => field.stored_shape(),
+                };
+                if is_array {
+                    continue;
+                }
+                let kind = match base_type {
+                    BaseType::Integer | BaseType::Float | BaseType::Timestamp => "Numeric",
+                    BaseType::String | BaseType::Pubkey => "Text",
+                    BaseType::Boolean => "Bool",
+                    _ => continue,
+                };
+                let mut path = Vec::new();
+                if !Self::is_root_section(&section.name) {
+                    path.push(to_snake_case(&section.name));
+                }
+                path.push(to_snake_case(&field.field_name));
+                sortable.push((path, kind));
+            }
+        }
+        if sortable.is_empty() {
+            return String::new();
+        }
+
+        // Field names shared by several sections are prefixed with their section
+        let mut name_counts: HashMap<&str, usize> = HashMap::new();
+        for (path, _) in &sortable {
+            *name_counts.entry(path.last().unwrap()).or_default() += 1;
+        }
+
+        let accessors: Vec<String> = sortable
+            .iter()
+            .map(|(path, kind)| {
+                let field = path.last().unwrap();
+                let method = if name_counts[field.as_str()] > 1 {
+                    path.join("_")
+                } else {
+                    field.clone()
+                };
+                let segments: Vec<String> = path.iter().map(|s| format!("\"{}\"", s)).collect();
+                format!(
+                    r#"    pub fn {method}(&self) -> hyperstack_sdk::SortField {{
+        hyperstack_sdk::SortField::new(&[{segments}], hyperstack_sdk::FieldKind::{kind})
+    }}"#,
+                    method = method,
+                    segments = segments.join(", "),
+                    kind = kind,
+                )
+            })
+            .collect();
+
+        format!(
+            r#"
+
+/// Sortable fields of `{entity}`, for `ViewHandle::sorted_by`.
+pub struct {entity}Fields;
+
+impl {entity} {{
+    pub fn fields() -> {entity}Fields {{
+        {entity}Fields
+    }}
+}}
+
+impl {entity}Fields {{
+{accessors}
+}}
+"#,
+            entity = self.entity_name,
+            accessors = accessors.join("\n\n"),
+        )
+    }
+
+    /// Rust type for a key. Only integer and boolean keys get typed
+    /// parameters (the key derives `Eq`/`Hash`); everything else is passed
+    /// through as its string form.
+    fn key_type_to_rust(&self, recipe: &KeyRecipe) -> String {
+        let rust_type_name = self
+            .spec
+            .field_mappings
+            .get(&recipe.field_path)
+            .map(|info| info.rust_type_name.as_str())
+            .unwrap_or("");
+        match recipe.base_type {
+            BaseType::Integer | BaseType::Timestamp | BaseType::Boolean => {
+                self.base_type_to_rust(&recipe.base_type, rust_type_name)
+            }
+            _ => "String".to_string(),
+        }
+    }
+
     pub(crate) fn generate_resolved_types(&self, generated: &mut HashSet<String>) -> String {
         let mut output = String::new();
 
@@ -288,31 +539,7 @@ pub use hyperstack_sdk::{{ConnectionState, HyperStack, Stack, Update, Views}};
     }
 
     fn generate_event_wrapper(&self) -> String {
-        r#"
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EventWrapper<T> {
-    #[serde(default, deserialize_with = "serde_utils::deserialize_i64")]
-    pub timestamp: i64,
-    pub data: T,
-    #[serde(default)]
-    pub slot: Option<f64>,
-    #[serde(default)]
-    pub signature: Option<String>,
-}
-
-impl<T: Default> Default for EventWrapper<T> {
-    fn default() -> Self {
-        Self {
-            timestamp: 0,
-            data: T::default(),
-            slot: None,
-            signature: None,
-        }
-    }
-}
-"#
-        .to_string()
+        format!("\n{}", EVENT_WRAPPER_RS)
     }
 
     fn generate_entity_rs(&self) -> String {
@@ -342,13 +569,17 @@ impl<T: Default> Default for EventWrapper<T> {
         };
 
         let entity_views = self.generate_entity_views_struct();
+        let stack_event_types =
+            generate_stack_event_types(&stack_name, std::slice::from_ref(entity_name));
 
         format!(
-            r#"use {types_import}::{entity_name};
+            r#"use {types_import}::{{{entity_name}, {entity_name}EventType}};
 use hyperstack_sdk::{{Stack, StateView, ViewBuilder, ViewHandle, Views}};
 
 pub struct {stack_name}Stack;
 
+{stack_event_types}
+
 impl Stack for {stack_name}Stack {{
     type Views = {stack_name}StackViews;
 
@@ -377,6 +608,7 @@ impl Views for {stack_name}StackViews {{
             stack_name_kebab = stack_name_kebab,
             entity_snake = entity_snake,
             url_impl = url_impl,
+            stack_event_types = stack_event_types,
             entity_views = entity_views
         )
     }
@@ -469,13 +701,7 @@ impl {entity_name}EntityViews {{
     ///   - `Some(None)` = explicitly set to null
     ///   - `Some(Some(value))` = has value
     fn field_type_to_rust(&self, field: &FieldTypeInfo) -> String {
-        let base = self.base_type_to_rust(&field.base_type, &field.rust_type_name);
-
-        let typed = if field.is_array && !matches!(field.base_type, BaseType::Array) {
-            format!("Vec<{}>", base)
-        } else {
-            base
-        };
+        let typed = self.stored_type_to_rust(field);
 
         // All fields wrapped in Option since we receive patches
         // Optional spec fields get Option<Option<T>> to distinguish "not received" from "explicitly null"
@@ -486,6 +712,44 @@ impl {entity_name}EntityViews {{
         }
     }
 
+    fn stored_type_to_rust(&self, field: &FieldTypeInfo) -> String {
+        let (base_type, is_array) = field.stored_shape();
+        let base = self.base_type_to_rust(&base_type, &field.rust_type_name);
+
+        if is_array && !matches!(base_type, BaseType::Array) {
+            format!("Vec<{}>", base)
+        } else {
+            base
+        }
+    }
+
+    /// Rust type and `#[serde(...)]` attribute for the field at `target_path`.
+    /// Resolver targets with a placeholder are wrapped in `Resolvable<T>` so
+    /// the pending marker deserializes alongside the resolved value.
+    fn field_type_and_serde_attr(
+        &self,
+        target_path: &str,
+        field: &FieldTypeInfo,
+    ) -> (String, String) {
+        if resolver_placeholder(&self.spec.resolver_specs, target_path).is_none() {
+            return (
+                self.field_type_to_rust(field),
+                self.serde_attr_for_field(field),
+            );
+        }
+
+        let resolvable = format!(
+            "hyperstack_sdk::Resolvable<{}>",
+            self.stored_type_to_rust(field)
+        );
+        let rust_type = if field.is_optional {
+            format!("Option<Option<{}>>", resolvable)
+        } else {
+            format!("Option<{}>", resolvable)
+        };
+        (rust_type, "#[serde(default)]".to_string())
+    }
+
     fn base_type_to_rust(&self, base_type: &BaseType, rust_type_name: &str) -> String {
         match base_type {
             BaseType::Integer => {
@@ -517,10 +781,11 @@ impl {entity_name}EntityViews {{
     /// Integer fields get a `deserialize_with` pointing to the appropriate
     /// `serde_utils` function so that string-encoded big integers are handled.
     fn serde_attr_for_field(&self, field: &FieldTypeInfo) -> String {
+        let (base_type, is_array) = field.stored_shape();
         if let Some(deser_fn) = self.deserialize_with_for_type(
-            &field.base_type,
+            &base_type,
             field.is_optional,
-            field.is_array && !matches!(field.base_type, BaseType::Array),
+            is_array && !matches!(base_type, BaseType::Array),
             &field.rust_type_name,
         ) {
             format!("#[serde(default, deserialize_with = \"{}\")]", deser_fn)
@@ -531,10 +796,11 @@ impl {entity_name}EntityViews {{
 
     /// Same as `serde_attr_for_field` but for resolved struct fields.
     fn serde_attr_for_resolved_field(&self, field: &ResolvedField) -> String {
+        let (base_type, is_array) = field.stored_shape();
         if let Some(deser_fn) = self.deserialize_with_for_type(
-            &field.base_type,
+            &base_type,
             field.is_optional,
-            field.is_array,
+            is_array,
             &field.field_type,
         ) {
             format!("#[serde(default, deserialize_with = \"{}\")]", deser_fn)
@@ -580,9 +846,10 @@ impl {entity_name}EntityViews {{
     }
 
     fn resolved_field_to_rust(&self, field: &ResolvedField) -> String {
-        let base = self.base_type_to_rust(&field.base_type, &field.field_type);
+        let (base_type, is_array) = field.stored_shape();
+        let base = self.base_type_to_rust(&base_type, &field.field_type);
 
-        let typed = if field.is_array {
+        let typed = if is_array {
             format!("Vec<{}>", base)
         } else {
             base
@@ -606,6 +873,11 @@ pub struct RustStackConfig {
     pub sdk_version: String,
     pub module_mode: bool,
     pub url: Option<String>,
+    /// Also generate a clap-based command line binary (crate mode only)
+    pub with_cli: bool,
+    /// Also generate an ignored test checking a live deployment against the
+    /// stack (crate mode only)
+    pub with_contract_tests: bool,
 }
 
 impl Default for RustStackConfig {
@@ -615,6 +887,8 @@ impl Default for RustStackConfig {
             sdk_version: "0.2".to_string(),
             module_mode: false,
             url: None,
+            with_cli: false,
+            with_contract_tests: false,
         }
     }
 }
@@ -628,6 +902,16 @@ pub fn compile_stack_spec(
     config: Option<RustStackConfig>,
 ) -> Result<RustOutput, String> {
     let config = config.unwrap_or_default();
+    if config.with_cli && config.module_mode {
+        return Err("a command line binary can only be generated for a crate, not a module".into());
+    }
+    if config.with_contract_tests && config.module_mode {
+        return Err("contract tests can only be generated for a crate, not a module".into());
+    }
+    let schema_hash = stack_spec
+        .content_hash
+        .clone()
+        .unwrap_or_else(|| stack_spec.compute_content_hash());
     let stack_name = &stack_spec.stack_name;
     let stack_kebab = to_kebab_case(stack_name);
 
@@ -652,28 +936,72 @@ pub fn compile_stack_spec(
     );
     let lib_rs = generate_stack_lib_rs(stack_name, &entity_names, config.module_mode);
     let cargo_toml = generate_stack_cargo_toml(&config);
+    let cli = config.with_cli.then(|| RustBinary {
+        name: format!("{}-cli", stack_kebab),
+        source: generate_stack_cli_rs(
+            stack_name,
+            &stack_kebab,
+            &entity_specs,
+            &entity_names,
+            &config,
+        ),
+    });
+    let contract_test = config.with_contract_tests.then(|| {
+        generate_stack_contract_rs(
+            stack_name,
+            &schema_hash,
+            &entity_specs,
+            &entity_names,
+            &config,
+        )
+    });
 
     Ok(RustOutput {
         cargo_toml,
         lib_rs,
         types_rs,
         entity_rs,
+        cli,
+        contract_test,
     })
 }
 
 fn generate_stack_cargo_toml(config: &RustStackConfig) -> String {
+    let cli_dependencies = if config.with_cli {
+        r#"clap = { version = "4", features = ["derive"] }
+tokio = { version = "1", features = ["macros", "rt-multi-thread"] }
+"#
+    } else {
+        ""
+    };
+    let dev_dependencies = if config.with_contract_tests {
+        format!(
+            r#"
+[dev-dependencies]
+hyperstack-sdk = {{ version = "{}", features = ["test-util"] }}
+tokio = {{ version = "1", features = ["macros", "rt-multi-thread"] }}
+"#,
+            config.sdk_version
+        )
+    } else {
+        String::new()
+    };
+
     format!(
         r#"[package]
 name = "{}"
 version = "0.1.0"
 edition = "2021"
 
+[features]
+test-util = ["hyperstack-sdk/test-util"]
+
 [dependencies]
 hyperstack-sdk = "{}"
 serde = {{ version = "1", features = ["derive"] }}
 serde_json = "1"
-"#,
-        config.crate_name, config.sdk_version
+{}{}"#,
+        config.crate_name, config.sdk_version, cli_dependencies, dev_dependencies
     )
 }
 
@@ -718,19 +1046,21 @@ fn generate_stack_types_rs(
         let entity_name = &entity_names[i];
         let compiler = RustCompiler::new(spec.clone(), entity_name.clone(), RustConfig::default());
 
-        // Generate section structs (e.g., OreRoundId, OreRoundState)
+        // Generate section structs (e.g., OreRoundId, OreRoundState), shared
+        // sections once for the whole stack
         for section in &spec.sections {
-            if !RustCompiler::is_root_section(&section.name) {
-                let struct_name = format!("{}{}", entity_name, to_pascal_case(&section.name));
-                if generated.insert(struct_name) {
-                    output.push_str(&compiler.generate_struct_for_section(section));
-                    output.push_str("\n\n");
-                }
+            if !RustCompiler::is_root_section(&section.name)
+                && generated.insert(section_type_name(entity_name, section))
+            {
+                output.push_str(&compiler.generate_struct_for_section(section));
+                output.push_str("\n\n");
             }
         }
 
         // Generate main entity struct (e.g., OreRound, OreTreasury)
         output.push_str(&compiler.generate_main_entity_struct());
+        output.push_str(&compiler.generate_key_struct());
+        output.push_str(&compiler.generate_fields_struct());
         output.push_str("\n\n");
 
         let resolved = compiler.generate_resolved_types(&mut generated);
@@ -738,34 +1068,16 @@ fn generate_stack_types_rs(
         while !output.ends_with("\n\n") {
             output.push('\n');
         }
+
+        output.push_str(&generate_event_type_enum(
+            entity_name,
+            &spec.event_type_names(),
+        ));
+        output.push_str("\n\n");
     }
 
     // Generate EventWrapper once
-    output.push_str(
-        r#"
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EventWrapper<T> {
-    #[serde(default, deserialize_with = "serde_utils::deserialize_i64")]
-    pub timestamp: i64,
-    pub data: T,
-    #[serde(default)]
-    pub slot: Option<f64>,
-    #[serde(default)]
-    pub signature: Option<String>,
-}
-
-impl<T: Default> Default for EventWrapper<T> {
-    fn default() -> Self {
-        Self {
-            timestamp: 0,
-            data: T::default(),
-            slot: None,
-            signature: None,
-        }
-    }
-}
-"#,
-    );
+    output.push_str(EVENT_WRAPPER_RS);
 
     output
 }
@@ -784,8 +1096,10 @@ fn generate_stack_entity_rs(
         "crate::types"
     };
 
-    let entity_type_imports: Vec<String> =
-        entity_names.iter().map(|name| name.to_string()).collect();
+    let entity_type_imports: Vec<String> = entity_names
+        .iter()
+        .flat_map(|name| [name.to_string(), format!("{}EventType", name)])
+        .collect();
 
     let url_impl = match &config.url {
         Some(url) => format!(
@@ -900,6 +1214,8 @@ use hyperstack_sdk::{{Stack, StateView, ViewBuilder, ViewHandle, Views}};
 
 pub struct {stack}Stack;
 
+{stack_event_types}
+
 impl Stack for {stack}Stack {{
     type Views = {stack}StackViews;
 
@@ -927,12 +1243,680 @@ impl Views for {stack}StackViews {{
         stack = stack_name,
         stack_kebab = stack_kebab,
         url_impl = url_impl,
+        stack_event_types = generate_stack_event_types(stack_name, entity_names),
         views_fields = views_fields.join("\n"),
         views_builder = views_builder_fields.join("\n"),
         entity_views = entity_views_structs.join("\n"),
     )
 }
 
+/// Variant of an entity's event type enum for a possibly scoped event type:
+/// `ore::DeployIxState` -> `OreDeployIxState`
+fn event_type_variant(event_type: &str) -> String {
+    event_type.split("::").map(to_pascal_case).collect()
+}
+
+/// `<Entity>EventType`: the event types routed to an entity, with `Unknown`
+/// for types added after the SDK was generated
+fn generate_event_type_enum(entity_name: &str, event_types: &[String]) -> String {
+    let enum_name = format!("{}EventType", entity_name);
+    let variants: Vec<String> = event_types
+        .iter()
+        .map(|event_type| {
+            format!(
+                "    /// `{}`\n    {},",
+                event_type,
+                event_type_variant(event_type)
+            )
+        })
+        .collect();
+    let names: Vec<String> = event_types
+        .iter()
+        .map(|event_type| format!("        \"{}\",", event_type))
+        .collect();
+    let as_str_arms: Vec<String> = event_types
+        .iter()
+        .map(|event_type| {
+            format!(
+                "            Self::{} => \"{}\",",
+                event_type_variant(event_type),
+                event_type
+            )
+        })
+        .collect();
+    let from_arms: Vec<String> = event_types
+        .iter()
+        .map(|event_type| {
+            format!(
+                "            \"{}\" => Self::{},",
+                event_type,
+                event_type_variant(event_type)
+            )
+        })
+        .collect();
+
+    format!(
+        r#"/// Event types routed to `{entity}`. Types added after this SDK was
+/// generated classify as `Unknown`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum {enum_name} {{
+{variants}
+    Unknown(String),
+}}
+
+impl {enum_name} {{
+    /// Every event type routed to `{entity}`
+    pub const ALL: &'static [&'static str] = &[
+{names}
+    ];
+
+    pub fn as_str(&self) -> &str {{
+        match self {{
+{as_str_arms}
+            Self::Unknown(name) => name,
+        }}
+    }}
+}}
+
+impl From<&str> for {enum_name} {{
+    fn from(name: &str) -> Self {{
+        match name {{
+{from_arms}
+            other => Self::Unknown(other.to_string()),
+        }}
+    }}
+}}
+
+impl std::fmt::Display for {enum_name} {{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {{
+        f.write_str(self.as_str())
+    }}
+}}"#,
+        entity = entity_name,
+        enum_name = enum_name,
+        variants = variants.join("\n"),
+        names = names.join("\n"),
+        as_str_arms = as_str_arms.join("\n"),
+        from_arms = from_arms.join("\n"),
+    )
+}
+
+/// `impl <Stack>Stack` listing the event types of every entity, for tooling
+/// that enumerates them
+fn generate_stack_event_types(stack_name: &str, entity_names: &[String]) -> String {
+    let entries: Vec<String> = entity_names
+        .iter()
+        .map(|name| format!("        (\"{name}\", {name}EventType::ALL),", name = name))
+        .collect();
+
+    format!(
+        r#"impl {stack}Stack {{
+    /// Event types routed to each entity of the stack
+    pub const EVENT_TYPES: &'static [(&'static str, &'static [&'static str])] = &[
+{entries}
+    ];
+
+    /// Event types routed to `entity`, empty for entities not in the stack
+    pub fn event_types(entity: &str) -> &'static [&'static str] {{
+        Self::EVENT_TYPES
+            .iter()
+            .find(|(name, _)| *name == entity)
+            .map(|(_, types)| *types)
+            .unwrap_or(&[])
+    }}
+}}"#,
+        stack = stack_name,
+        entries = entries.join("\n"),
+    )
+}
+
+/// Subcommand names for a stack's entities: kebab-cased entity names, minus
+/// the leading word when every entity shares it (`OreRound` -> `round`).
+fn cli_entity_commands(entity_names: &[String]) -> Vec<String> {
+    let kebab: Vec<String> = entity_names.iter().map(|n| to_kebab_case(n)).collect();
+    let shared_prefix = kebab
+        .first()
+        .and_then(|first| first.split_once('-'))
+        .map(|(word, _)| format!("{}-", word))
+        .filter(|prefix| kebab.len() > 1 && kebab.iter().all(|k| k.starts_with(prefix)));
+
+    kebab
+        .iter()
+        .map(|k| match &shared_prefix {
+            Some(prefix) => k[prefix.len()..].to_string(),
+            None => k.clone(),
+        })
+        .collect()
+}
+
+/// Generate `src/bin/<stack>-cli.rs`, a clap binary with one subcommand per
+/// entity and one nested subcommand per view.
+fn generate_stack_cli_rs(
+    stack_name: &str,
+    stack_kebab: &str,
+    entity_specs: &[SerializableStreamSpec],
+    entity_names: &[String],
+    config: &RustStackConfig,
+) -> String {
+    let lib_name = config.crate_name.replace('-', "_");
+    let commands = cli_entity_commands(entity_names);
+
+    let mut entity_variants = Vec::new();
+    let mut view_enums = Vec::new();
+    let mut dispatch_arms = Vec::new();
+
+    for (i, entity_name) in entity_names.iter().enumerate() {
+        let command_enum = format!("{}Command", entity_name);
+        let views_field = to_snake_case(entity_name);
+
+        entity_variants.push(format!(
+            r#"    /// Query {entity}
+    {variant} {{
+        #[command(subcommand)]
+        view: {command_enum},
+    }},"#,
+            entity = entity_name,
+            variant = to_pascal_case(&commands[i]),
+            command_enum = command_enum,
+        ));
+
+        let mut view_variants = vec![format!(
+            r#"    /// Get one {entity} by key
+    Get {{
+        key: String,
+        #[command(flatten)]
+        args: WatchArgs,
+    }},
+    /// The {entity}/list view
+    List(ListArgs),"#,
+            entity = entity_name
+        )];
+        let mut view_arms = vec![format!(
+            r#"            {command_enum}::Get {{ key, args }} => {{
+                show_state(hs.views.{field}.state(), key, args, json).await
+            }}
+            {command_enum}::List(args) => show_list(hs.views.{field}.list(), args, json).await,"#,
+            command_enum = command_enum,
+            field = views_field,
+        )];
+
+        let derived = entity_specs[i].views.iter().filter(|v| {
+            !v.id.ends_with("/state")
+                && !v.id.ends_with("/list")
+                && v.id.starts_with(entity_name.as_str())
+        });
+        for view in derived {
+            let view_name = view.id.split('/').nth(1).unwrap_or("unknown");
+            let method = to_snake_case(view_name);
+            if method == "get" {
+                continue;
+            }
+            let variant = to_pascal_case(&method);
+            let (args, show) = match view.output {
+                ViewOutput::Single => ("WatchArgs", "show_single"),
+                ViewOutput::Collection | ViewOutput::Keyed { .. } => ("ListArgs", "show_list"),
+            };
+            view_variants.push(format!(
+                "    /// The {view_id} view\n    {variant}({args}),",
+                view_id = view.id,
+                variant = variant,
+                args = args,
+            ));
+            view_arms.push(format!(
+                "            {command_enum}::{variant}(args) => {{\n                {show}(hs.views.{field}.{method}(), args, json).await\n            }}",
+                command_enum = command_enum,
+                variant = variant,
+                show = show,
+                field = views_field,
+                method = method,
+            ));
+        }
+
+        view_enums.push(format!(
+            r#"#[derive(Subcommand)]
+enum {command_enum} {{
+{variants}
+}}"#,
+            command_enum = command_enum,
+            variants = view_variants.join("\n"),
+        ));
+        dispatch_arms.push(format!(
+            r#"        EntityCommand::{variant} {{ view }} => match view {{
+{arms}
+        }},"#,
+            variant = to_pascal_case(&commands[i]),
+            arms = view_arms.join("\n"),
+        ));
+    }
+
+    let mut output = format!(
+        r#"//! Command line access to the {stack} stack.
+//!
+//! Generated by `hs sdk create rust --with-cli`.
+
+use clap::{{Args, Parser, Subcommand}};
+use hyperstack_sdk::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use {lib}::{stack}Stack;
+
+#[derive(Parser)]
+#[command(name = "{stack_kebab}-cli", about = "Query the {stack} stack")]
+struct Cli {{
+    /// Stack WebSocket URL, overriding the generated one
+    #[arg(long, global = true)]
+    url: Option<String>,
+
+    /// Print JSON instead of a table
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    entity: EntityCommand,
+}}
+
+#[derive(Subcommand)]
+enum EntityCommand {{
+{entity_variants}
+}}
+
+{view_enums}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {{
+    let cli = Cli::parse();
+    let url = cli.url.as_deref().unwrap_or({stack}Stack::url());
+    if url.is_empty() {{
+        return Err("no URL configured for this stack, pass --url".into());
+    }}
+    let hs = HyperStack::<{stack}Stack>::builder().url(url).connect().await?;
+    let json = cli.json;
+
+    match cli.entity {{
+{dispatch_arms}
+    }}
+
+    Ok(())
+}}
+"#,
+        stack = stack_name,
+        lib = lib_name,
+        stack_kebab = stack_kebab,
+        entity_variants = entity_variants.join("\n"),
+        view_enums = view_enums.join("\n\n"),
+        dispatch_arms = dispatch_arms.join("\n"),
+    );
+    output.push_str(CLI_SUPPORT_RS);
+    output
+}
+
+/// View helpers and table rendering shared by every generated CLI
+const CLI_SUPPORT_RS: &str = r#"
+#[derive(Args)]
+struct WatchArgs {
+    /// Keep printing updates until interrupted
+    #[arg(long)]
+    watch: bool,
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// Show at most this many entities
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Keep printing updates until interrupted
+    #[arg(long)]
+    watch: bool,
+}
+
+async fn show_state<T>(view: StateView<T>, key: String, args: WatchArgs, json: bool)
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    if args.watch {
+        let mut updates = view.listen(key);
+        while let Some(entity) = updates.next().await {
+            print_entities(&[entity], json);
+        }
+    } else {
+        match view.get(key).await {
+            Some(entity) => print_entities(&[entity], json),
+            None => eprintln!("not found"),
+        }
+    }
+}
+
+async fn show_list<T>(view: ViewHandle<T>, args: ListArgs, json: bool)
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    if args.watch {
+        let mut updates = view.listen();
+        if let Some(limit) = args.limit {
+            updates = updates.take(limit as u32);
+        }
+        while let Some(entity) = updates.next().await {
+            print_entities(&[entity], json);
+        }
+    } else {
+        let mut entities = view.get().await;
+        if let Some(limit) = args.limit {
+            entities.truncate(limit);
+        }
+        print_entities(&entities, json);
+    }
+}
+
+// Only used by stacks with single-entity views
+#[allow(dead_code)]
+async fn show_single<T>(view: ViewHandle<T>, args: WatchArgs, json: bool)
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    show_list(
+        view,
+        ListArgs {
+            limit: Some(1),
+            watch: args.watch,
+        },
+        json,
+    )
+    .await
+}
+
+/// Longest value shown in a table cell
+const MAX_CELL_CHARS: usize = 32;
+
+/// Print entities as JSON lines, as `field value` rows for a single entity,
+/// or as a table with one column per field
+fn print_entities<T: Serialize>(entities: &[T], json: bool) {
+    let values: Vec<serde_json::Value> = entities
+        .iter()
+        .map(|entity| serde_json::to_value(entity).unwrap_or_default())
+        .collect();
+
+    if json {
+        for value in &values {
+            println!("{}", value);
+        }
+        return;
+    }
+
+    let rows: Vec<Vec<(String, String)>> = values
+        .iter()
+        .map(|value| {
+            let mut cells = Vec::new();
+            flatten("", value, &mut cells);
+            cells
+        })
+        .collect();
+
+    if let [row] = rows.as_slice() {
+        let width = row.iter().map(|(field, _)| field.len()).max().unwrap_or(0);
+        for (field, value) in row {
+            println!("{:<width$}  {}", field, value, width = width);
+        }
+        return;
+    }
+
+    let mut columns: Vec<&str> = Vec::new();
+    for row in &rows {
+        for (field, _) in row {
+            if !columns.contains(&field.as_str()) {
+                columns.push(field);
+            }
+        }
+    }
+    let cell = |row: &[(String, String)], column: &str| -> String {
+        let value = row
+            .iter()
+            .find(|(field, _)| field == column)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or("");
+        if value.chars().count() > MAX_CELL_CHARS {
+            let truncated: String = value.chars().take(MAX_CELL_CHARS - 1).collect();
+            format!("{}…", truncated)
+        } else {
+            value.to_string()
+        }
+    };
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|column| {
+            rows.iter()
+                .map(|row| cell(row, column).chars().count())
+                .chain([column.len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: Vec<String>| -> String {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    println!("{}", line(columns.iter().map(|c| c.to_string()).collect()));
+    for row in &rows {
+        println!("{}", line(columns.iter().map(|c| cell(row, c)).collect()));
+    }
+}
+
+/// Flatten nested objects into dotted `field value` pairs
+fn flatten(prefix: &str, value: &serde_json::Value, cells: &mut Vec<(String, String)>) {
+    match value {
+        serde_json::Value::Object(fields) if !fields.is_empty() => {
+            for (name, field) in fields {
+                let path = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                flatten(&path, field, cells);
+            }
+        }
+        serde_json::Value::Null => cells.push((prefix.to_string(), String::new())),
+        serde_json::Value::String(text) => cells.push((prefix.to_string(), text.clone())),
+        other => cells.push((prefix.to_string(), other.to_string())),
+    }
+}
+"#;
+
+/// Render docs as `///` lines (with trailing newline) at the given indent
+/// Struct generated for `section` of `entity_name`, e.g. `OreRoundState`, or
+/// the shared type the section was imported from
+/// Generate `tests/live_contract.rs`, checking every view of a live
+/// deployment with `hyperstack_sdk::contract`.
+fn generate_stack_contract_rs(
+    stack_name: &str,
+    schema_hash: &str,
+    entity_specs: &[SerializableStreamSpec],
+    entity_names: &[String],
+    config: &RustStackConfig,
+) -> String {
+    let lib_name = config.crate_name.replace('-', "_");
+
+    let mut field_consts = Vec::new();
+    let mut views = Vec::new();
+
+    for (spec, entity_name) in entity_specs.iter().zip(entity_names) {
+        let fields_const = format!("{}_FIELDS", to_snake_case(entity_name).to_uppercase());
+        let fields: Vec<String> = contract_fields(spec)
+            .into_iter()
+            .map(|(path, kind, optional)| {
+                let constructor = if optional { "optional" } else { "new" };
+                format!(
+                    "    FieldContract::{}({:?}, JsonKind::{}),",
+                    constructor, path, kind
+                )
+            })
+            .collect();
+        field_consts.push(format!(
+            "const {}: &[FieldContract] = &[\n{}\n];",
+            fields_const,
+            fields.join("\n")
+        ));
+
+        let typed = |constructor: &str, view_id: &str| {
+            format!(
+                "        ViewContract::{}({:?}, {}).typed::<{}>(),",
+                constructor, view_id, fields_const, entity_name
+            )
+        };
+        views.push(typed("list", &format!("{}/list", entity_name)));
+        views.push(typed("state", &format!("{}/state", entity_name)));
+        views.push(format!(
+            "        ViewContract::append({:?}),",
+            format!("{}/append", entity_name)
+        ));
+        for view in spec.views.iter().filter(|v| {
+            !v.id.ends_with("/state")
+                && !v.id.ends_with("/list")
+                && v.id.starts_with(entity_name.as_str())
+        }) {
+            views.push(typed("list", &view.id));
+        }
+    }
+
+    format!(
+        r#"//! Checks a live deployment of the {stack} stack against the schema this
+//! crate was generated from.
+//!
+//! Generated by `hs sdk create rust --with-contract-tests`. Run it after a
+//! deploy with `cargo test --test live_contract -- --ignored`, configured by:
+//!
+//! - `HYPERSTACK_URL`: the deployment to check, instead of the generated URL
+//! - `HYPERSTACK_CONTRACT_SKIP`: comma-separated views not to check
+//! - `HYPERSTACK_CONTRACT_TIMEOUT`: seconds to wait for each frame (default 30)
+//! - `HYPERSTACK_CONTRACT_REPORT`: where to write the JUnit report
+//!   (default `target/live-contract.xml`)
+
+use hyperstack_sdk::contract::{{ContractRunner, FieldContract, JsonKind, ViewContract}};
+use std::time::Duration;
+use {lib}::*;
+
+/// Content hash of the stack the crate was generated from
+const SCHEMA_HASH: &str = "{schema_hash}";
+
+/// Views that are legitimately empty, such as append views of quiet entities
+const SKIP: &[&str] = &[];
+
+{field_consts}
+
+fn views() -> Vec<ViewContract> {{
+    vec![
+{views}
+    ]
+}}
+
+#[tokio::test]
+#[ignore = "checks a live deployment"]
+async fn live_deployment_matches_the_schema() {{
+    let url = std::env::var("HYPERSTACK_URL").unwrap_or_else(|_| {stack}Stack::url().to_string());
+    assert!(!url.is_empty(), "no URL configured for this stack, set HYPERSTACK_URL");
+    let skip = std::env::var("HYPERSTACK_CONTRACT_SKIP").unwrap_or_default();
+    let timeout = std::env::var("HYPERSTACK_CONTRACT_TIMEOUT")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(30);
+
+    let report = ContractRunner::new(url, SCHEMA_HASH)
+        .with_timeout(Duration::from_secs(timeout))
+        .skip(SKIP.iter().copied())
+        .skip(skip.split(',').map(str::trim).filter(|view| !view.is_empty()))
+        .run(&views())
+        .await
+        .expect("the deployment should be reachable");
+
+    let path = std::env::var("HYPERSTACK_CONTRACT_REPORT")
+        .unwrap_or_else(|_| "target/live-contract.xml".to_string());
+    std::fs::write(&path, report.to_junit()).expect("the report should be written");
+    report.assert_passed();
+}}
+"#,
+        stack = stack_name,
+        lib = lib_name,
+        schema_hash = schema_hash,
+        field_consts = field_consts.join("\n\n"),
+        views = views.join("\n"),
+    )
+}
+
+/// JSON path, `JsonKind` and optionality of every emitted field of an
+/// entity, as stored in entity state. Resolved and binary fields aren't
+/// checked beyond being present.
+fn contract_fields(spec: &SerializableStreamSpec) -> Vec<(String, &'static str, bool)> {
+    let mut fields = Vec::new();
+    for section in &spec.sections {
+        for field in section.fields.iter().filter(|field| field.emit) {
+            let path = if RustCompiler::is_root_section(&section.name) {
+                field.field_name.clone()
+            } else {
+                format!("{}.{}", section.name, field.field_name)
+            };
+            let (base_type, is_array) = field.stored_shape();
+            let kind = if resolver_placeholder(&spec.resolver_specs, &path).is_some() {
+                "Any"
+            } else if is_array {
+                "Array"
+            } else {
+                match base_type {
+                    BaseType::Integer | BaseType::Timestamp => "Integer",
+                    BaseType::Float => "Number",
+                    BaseType::String | BaseType::Pubkey => "String",
+                    BaseType::Boolean => "Bool",
+                    BaseType::Object => "Object",
+                    BaseType::Array => "Array",
+                    BaseType::Binary | BaseType::Any => "Any",
+                }
+            };
+            fields.push((path, kind, field.is_optional));
+        }
+    }
+    fields
+}
+
+fn section_type_name(entity_name: &str, section: &EntitySection) -> String {
+    match &section.shared_type {
+        Some(shared_type) => shared_type.clone(),
+        None => format!("{}{}", entity_name, to_pascal_case(&section.name)),
+    }
+}
+
+fn doc_comment(docs: Option<&ItemDocs>, indent: &str) -> String {
+    let Some(docs) = docs else {
+        return String::new();
+    };
+
+    let mut lines: Vec<String> = docs
+        .description
+        .iter()
+        .flat_map(|description| description.lines())
+        .map(str::to_string)
+        .collect();
+    if let Some(unit) = &docs.unit {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(format!("Unit: {}", unit));
+    }
+
+    lines
+        .iter()
+        .map(|line| {
+            if line.is_empty() {
+                format!("{indent}///\n")
+            } else {
+                format!("{indent}/// {line}\n")
+            }
+        })
+        .collect()
+}
+
 fn to_kebab_case(s: &str) -> String {
     let mut result = String::new();
     for (i, c) in s.chars().enumerate() {
@@ -974,3 +1958,366 @@ fn to_snake_case(s: &str) -> String {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn miner_spec() -> SerializableStreamSpec {
+        let mut field_mappings = BTreeMap::new();
+        field_mappings.insert(
+            "id.authority".to_string(),
+            FieldTypeInfo::new("authority".to_string(), "Pubkey".to_string()),
+        );
+        field_mappings.insert(
+            "id.round_id".to_string(),
+            FieldTypeInfo::new("round_id".to_string(), "u64".to_string()),
+        );
+        SerializableStreamSpec {
+            ast_version: CURRENT_AST_VERSION.to_string(),
+            state_name: "OreMiner".to_string(),
+            program_id: None,
+            idl: None,
+            identity: IdentitySpec {
+                primary_keys: vec!["id.authority".to_string(), "id.round_id".to_string()],
+                lookup_indexes: vec![],
+            },
+            handlers: vec![],
+            sections: vec![],
+            field_mappings,
+            resolver_hooks: vec![],
+            resolver_specs: vec![],
+            instruction_hooks: vec![],
+            computed_fields: vec![],
+            computed_field_specs: vec![],
+            content_hash: None,
+            views: vec![],
+            trace_fields: vec![],
+            feature: None,
+            priority: EntityPriority::Normal,
+            delete_on: None,
+            key_normalizer: None,
+            docs: None,
+            event_types: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_key_recipe_uses_the_first_primary_key() {
+        let recipe = miner_spec().key_recipe().unwrap();
+        assert_eq!(recipe.field_path, "id.authority");
+        assert_eq!(recipe.field_name, "authority");
+        assert_eq!(recipe.base_type, BaseType::Pubkey);
+
+        let mut spec = miner_spec();
+        spec.identity.primary_keys.reverse();
+        let recipe = spec.key_recipe().unwrap();
+        assert_eq!(recipe.field_name, "round_id");
+        assert_eq!(recipe.base_type, BaseType::Integer);
+        assert_eq!(recipe.key_value("42"), Some(serde_json::json!(42)));
+        assert_eq!(
+            recipe.key_value("18446744073709551615"),
+            Some(serde_json::json!(u64::MAX))
+        );
+        assert_eq!(recipe.key_value("GxF2"), None);
+
+        spec.identity.primary_keys.clear();
+        assert_eq!(spec.key_recipe(), None);
+    }
+
+    #[test]
+    fn test_key_recipe_marks_computed_keys() {
+        let mut spec = miner_spec();
+        assert!(!spec.key_recipe().unwrap().computed);
+        spec.computed_fields = vec!["id.authority".to_string()];
+        assert!(spec.key_recipe().unwrap().computed);
+    }
+
+    #[test]
+    fn test_generated_key_struct() {
+        let mut spec = miner_spec();
+        spec.identity.primary_keys.reverse();
+        let compiler = RustCompiler::new(spec, "OreMiner".to_string(), RustConfig::default());
+        let key_struct = compiler.generate_key_struct();
+
+        assert!(key_struct.contains("pub struct OreMinerKey {\n    pub round_id: u64,\n}"));
+        assert!(key_struct.contains("pub fn new(round_id: u64) -> Self"));
+        assert!(key_struct.contains("Some(Self { round_id: key.parse().ok()? })"));
+        assert!(key_struct.contains("write!(f, \"{}\", self.round_id)"));
+        assert!(key_struct.contains("impl hyperstack_sdk::EntityKey for OreMinerKey"));
+
+        let compiler =
+            RustCompiler::new(miner_spec(), "OreMiner".to_string(), RustConfig::default());
+        let key_struct = compiler.generate_key_struct();
+        assert!(key_struct.contains("pub fn new(authority: impl Into<String>) -> Self"));
+        assert!(key_struct.contains("Some(Self { authority: key.to_string() })"));
+    }
+
+    #[test]
+    fn test_generated_fields_struct() {
+        let section = |name: &str, fields: &[(&str, &str)]| EntitySection {
+            name: name.to_string(),
+            fields: fields
+                .iter()
+                .map(|(field, ty)| FieldTypeInfo::new(field.to_string(), ty.to_string()))
+                .collect(),
+            is_nested_struct: false,
+            parent_field: None,
+            docs: None,
+            shared_type: None,
+        };
+        let mut spec = miner_spec();
+        spec.sections = vec![
+            section("id", &[("authority", "Pubkey"), ("round_id", "u64")]),
+            section(
+                "rewards",
+                &[("rewards_sol", "Option<f64>"), ("deployed", "Vec<u64>")],
+            ),
+            section("state", &[("round_id", "u64"), ("active", "bool")]),
+        ];
+        let compiler = RustCompiler::new(spec, "OreMiner".to_string(), RustConfig::default());
+        let fields = compiler.generate_fields_struct();
+
+        assert!(fields.contains("pub fn fields() -> OreMinerFields {"));
+        assert!(fields.contains(
+            "pub fn rewards_sol(&self) -> hyperstack_sdk::SortField {\n        hyperstack_sdk::SortField::new(&[\"rewards\", \"rewards_sol\"], hyperstack_sdk::FieldKind::Numeric)"
+        ));
+        assert!(fields
+            .contains("SortField::new(&[\"id\", \"authority\"], hyperstack_sdk::FieldKind::Text)"));
+        assert!(fields.contains("pub fn id_round_id(&self)"));
+        assert!(fields.contains("pub fn state_round_id(&self)"));
+        assert!(fields.contains("FieldKind::Bool"));
+        assert!(!fields.contains("deployed"), "arrays aren't sortable");
+    }
+
+    #[test]
+    fn test_generated_structs_carry_doc_comments() {
+        let mut rewards_sol = FieldTypeInfo::new("rewards_sol".to_string(), "u64".to_string());
+        rewards_sol.docs = Some(ItemDocs {
+            description: Some("Unclaimed SOL rewards.".to_string()),
+            unit: Some("lamports".to_string()),
+        });
+        let mut authority = FieldTypeInfo::new("authority".to_string(), "Pubkey".to_string());
+        authority.docs = Some(ItemDocs {
+            description: Some("Wallet that owns the miner.\n\nNever changes.".to_string()),
+            unit: None,
+        });
+
+        let mut spec = miner_spec();
+        spec.docs = Some(ItemDocs {
+            description: Some("A miner in an ORE round.".to_string()),
+            unit: None,
+        });
+        spec.sections = vec![
+            EntitySection {
+                name: "rewards".to_string(),
+                fields: vec![rewards_sol],
+                is_nested_struct: false,
+                parent_field: None,
+                docs: Some(ItemDocs {
+                    description: Some("Reward balances.".to_string()),
+                    unit: None,
+                }),
+                shared_type: None,
+            },
+            EntitySection {
+                name: "root".to_string(),
+                fields: vec![authority],
+                is_nested_struct: false,
+                parent_field: None,
+                docs: None,
+                shared_type: None,
+            },
+        ];
+        let compiler = RustCompiler::new(spec, "OreMiner".to_string(), RustConfig::default());
+
+        let section = compiler.generate_struct_for_section(&compiler.spec.sections[0]);
+        assert!(section.starts_with("/// Reward balances.\n#[derive("));
+        assert!(section.contains(
+            "    /// Unclaimed SOL rewards.\n    ///\n    /// Unit: lamports\n    #[serde("
+        ));
+
+        let entity = compiler.generate_main_entity_struct();
+        assert!(entity.starts_with("/// A miner in an ORE round.\n#[derive("));
+        assert!(
+            entity.contains("    /// Reward balances.\n    #[serde(default)]\n    pub rewards:")
+        );
+        assert!(entity.contains(
+            "    /// Wallet that owns the miner.\n    ///\n    /// Never changes.\n    #[serde"
+        ));
+    }
+
+    #[test]
+    fn test_shared_sections_generate_one_type() {
+        let metadata = |name: &str| EntitySection {
+            name: name.to_string(),
+            fields: vec![FieldTypeInfo::new(
+                "symbol".to_string(),
+                "Option<String>".to_string(),
+            )],
+            is_nested_struct: false,
+            parent_field: None,
+            docs: None,
+            shared_type: Some("TokenMetadata".to_string()),
+        };
+        let mut pool = miner_spec();
+        pool.state_name = "Pool".to_string();
+        pool.sections = vec![metadata("base"), metadata("quote")];
+        let mut vault = miner_spec();
+        vault.state_name = "Vault".to_string();
+        vault.sections = vec![metadata("token")];
+
+        let types =
+            generate_stack_types_rs(&[pool, vault], &["Pool".to_string(), "Vault".to_string()]);
+        assert_eq!(types.matches("pub struct TokenMetadata {").count(), 1);
+        assert!(types.contains("pub base: TokenMetadata,"), "{types}");
+        assert!(types.contains("pub quote: TokenMetadata,"));
+        assert!(types.contains("pub token: TokenMetadata,"));
+        assert!(!types.contains("PoolBase"));
+    }
+
+    fn vault_section() -> EntitySection {
+        let mut last_signature =
+            FieldTypeInfo::new("last_signature".to_string(), "Option<Vec<u8>>".to_string());
+        last_signature.encoding = Some(ValueEncoding::Base58);
+        EntitySection {
+            name: "vault".to_string(),
+            fields: vec![
+                FieldTypeInfo::new("total_deposits".to_string(), "Option<u128>".to_string()),
+                FieldTypeInfo::new("flows".to_string(), "Vec<i128>".to_string()),
+                last_signature,
+            ],
+            is_nested_struct: false,
+            parent_field: None,
+            docs: None,
+            shared_type: None,
+        }
+    }
+
+    #[test]
+    fn test_encoded_fields_are_strings() {
+        let mut spec = miner_spec();
+        spec.sections = vec![vault_section()];
+        let compiler = RustCompiler::new(spec, "OreMiner".to_string(), RustConfig::default());
+
+        let section = compiler.generate_struct_for_section(&compiler.spec.sections[0]);
+        assert!(
+            section
+                .contains("    #[serde(default)]\n    pub total_deposits: Option<Option<String>>,"),
+            "{section}"
+        );
+        assert!(section.contains("    #[serde(default)]\n    pub flows: Option<Vec<String>>,"));
+        assert!(section
+            .contains("    #[serde(default)]\n    pub last_signature: Option<Option<String>>,"));
+
+        // Decimal strings still sort as numbers, encoded bytes as text
+        let fields = compiler.generate_fields_struct();
+        assert!(
+            fields.contains("&[\"vault\", \"total_deposits\"], hyperstack_sdk::FieldKind::Numeric")
+        );
+        assert!(
+            fields.contains("&[\"vault\", \"last_signature\"], hyperstack_sdk::FieldKind::Text")
+        );
+        assert!(!fields.contains("flows"));
+    }
+
+    fn miner_stack() -> SerializableStackSpec {
+        serde_json::from_value(serde_json::json!({
+            "stack_name": "Ore",
+            "entities": [serde_json::to_value(miner_spec()).unwrap()],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_cli_entity_commands_drop_shared_prefix() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            cli_entity_commands(&names(&["OreRound", "OreTreasury", "OreMiner"])),
+            vec!["round", "treasury", "miner"]
+        );
+        assert_eq!(
+            cli_entity_commands(&names(&["PumpToken", "BondingCurve"])),
+            vec!["pump-token", "bonding-curve"]
+        );
+        assert_eq!(
+            cli_entity_commands(&names(&["OreRound"])),
+            vec!["ore-round"]
+        );
+    }
+
+    #[test]
+    fn test_cli_is_only_generated_on_request() {
+        let output = compile_stack_spec(miner_stack(), None).unwrap();
+        assert!(output.cli.is_none());
+        assert!(!output.cargo_toml.contains("clap"));
+
+        let config = RustStackConfig {
+            crate_name: "ore-stack".to_string(),
+            with_cli: true,
+            ..Default::default()
+        };
+        let output = compile_stack_spec(miner_stack(), Some(config.clone())).unwrap();
+        let cli = output.cli.unwrap();
+        assert_eq!(cli.name, "ore-cli");
+        assert!(cli.source.contains("use ore_stack::OreStack;"));
+        assert!(cli.source.contains("    OreMiner {"));
+        assert!(cli
+            .source
+            .contains("show_state(hs.views.ore_miner.state(), key, args, json)"));
+        assert!(output.cargo_toml.contains("clap = "));
+
+        let module = RustStackConfig {
+            module_mode: true,
+            ..config
+        };
+        assert!(compile_stack_spec(miner_stack(), Some(module)).is_err());
+    }
+
+    #[test]
+    fn test_contract_tests_cover_every_view_and_field() {
+        let mut spec = miner_spec();
+        spec.sections = vec![vault_section()];
+        let mut stack = miner_stack();
+        stack.entities = vec![spec];
+        stack.content_hash = Some("abc123".to_string());
+
+        let output = compile_stack_spec(stack.clone(), None).unwrap();
+        assert!(output.contract_test.is_none());
+        assert!(!output.cargo_toml.contains("[dev-dependencies]"));
+
+        let config = RustStackConfig {
+            crate_name: "ore-stack".to_string(),
+            with_contract_tests: true,
+            ..Default::default()
+        };
+        let output = compile_stack_spec(stack.clone(), Some(config.clone())).unwrap();
+        let test = output.contract_test.unwrap();
+        assert!(test.contains("use ore_stack::*;"));
+        assert!(test.contains("const SCHEMA_HASH: &str = \"abc123\";"));
+        assert!(test.contains(
+            "const ORE_MINER_FIELDS: &[FieldContract] = &[\n    \
+             FieldContract::optional(\"vault.total_deposits\", JsonKind::String),\n    \
+             FieldContract::new(\"vault.flows\", JsonKind::Array),\n    \
+             FieldContract::optional(\"vault.last_signature\", JsonKind::String),\n];"
+        ));
+        assert!(test.contains(
+            "ViewContract::list(\"OreMiner/list\", ORE_MINER_FIELDS).typed::<OreMiner>(),"
+        ));
+        assert!(test.contains(
+            "ViewContract::state(\"OreMiner/state\", ORE_MINER_FIELDS).typed::<OreMiner>(),"
+        ));
+        assert!(test.contains("ViewContract::append(\"OreMiner/append\"),"));
+        assert!(output
+            .cargo_toml
+            .contains("hyperstack-sdk = { version = \"0.2\", features = [\"test-util\"] }"));
+
+        let module = RustStackConfig {
+            module_mode: true,
+            ..config
+        };
+        assert!(compile_stack_spec(stack, Some(module)).is_err());
+    }
+}
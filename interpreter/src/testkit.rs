@@ -0,0 +1,128 @@
+//! Helpers for testing code built on the interpreter and server.
+
+use crate::clock::{Clock, SleepFuture};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tokio::sync::oneshot;
+
+/// A [`Clock`] that only moves when told to.
+///
+/// Clones share the same time. Sleeps complete once [`advance`](Self::advance)
+/// has moved the clock past their deadline, so time-based behaviour can be
+/// tested without waiting.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    state: Arc<Mutex<ManualClockState>>,
+}
+
+#[derive(Debug)]
+struct ManualClockState {
+    /// Time elapsed since the clock was created
+    elapsed: Duration,
+    unix_start: Duration,
+    instant_start: Instant,
+    sleepers: Vec<(Duration, oneshot::Sender<()>)>,
+}
+
+impl ManualClock {
+    /// A clock starting at the given unix time
+    pub fn new(unix_start: Duration) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ManualClockState {
+                elapsed: Duration::ZERO,
+                unix_start,
+                instant_start: Instant::now(),
+                sleepers: Vec::new(),
+            })),
+        }
+    }
+
+    /// A clock starting at the given unix time in seconds
+    pub fn at_unix_secs(secs: u64) -> Self {
+        Self::new(Duration::from_secs(secs))
+    }
+
+    /// Move the clock forward, waking every sleep that is now due
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.elapsed += duration;
+
+        let elapsed = state.elapsed;
+        let (due, pending) = std::mem::take(&mut state.sleepers)
+            .into_iter()
+            .partition(|(deadline, _)| *deadline <= elapsed);
+        state.sleepers = pending;
+        drop(state);
+
+        for (_, waker) in due {
+            let _ = waker.send(());
+        }
+    }
+
+    /// Number of sleeps waiting for the clock to advance
+    pub fn pending_sleeps(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.sleepers.retain(|(_, waker)| !waker.is_closed());
+        state.sleepers.len()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(UNIX_EPOCH.elapsed().unwrap_or_default())
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_unix(&self) -> Duration {
+        let state = self.state.lock().unwrap();
+        state.unix_start + state.elapsed
+    }
+
+    fn now_instant(&self) -> Instant {
+        let state = self.state.lock().unwrap();
+        state.instant_start + state.elapsed
+    }
+
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        let mut state = self.state.lock().unwrap();
+        if duration.is_zero() {
+            return Box::pin(std::future::ready(()));
+        }
+
+        let (waker, woken) = oneshot::channel();
+        let deadline = state.elapsed + duration;
+        state.sleepers.push((deadline, waker));
+        Box::pin(async move {
+            let _ = woken.await;
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[test]
+    fn sleeps_complete_once_the_clock_passes_their_deadline() {
+        let clock = ManualClock::at_unix_secs(1_700_000_000);
+        let started = clock.now_instant();
+
+        let mut short = clock.sleep(Duration::from_secs(5));
+        let mut long = clock.sleep(Duration::from_secs(10));
+        assert!((&mut short).now_or_never().is_none());
+        assert_eq!(clock.pending_sleeps(), 2);
+
+        clock.advance(Duration::from_secs(5));
+        assert!((&mut short).now_or_never().is_some());
+        assert!((&mut long).now_or_never().is_none());
+        assert_eq!(clock.pending_sleeps(), 1);
+
+        clock.advance(Duration::from_secs(5));
+        assert!((&mut long).now_or_never().is_some());
+
+        assert_eq!(clock.unix_secs(), 1_700_000_010);
+        assert_eq!(clock.now_instant() - started, Duration::from_secs(10));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::ast::*;
+use crate::big_numbers::BigNumberMode;
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 /// Output structure for TypeScript generation
@@ -28,6 +29,10 @@ pub struct TypeScriptConfig {
     pub export_const_name: String,
     /// WebSocket URL for the stack. If None, generates a placeholder comment.
     pub url: Option<String>,
+    /// How the server encodes 64-bit integers. With
+    /// [`BigNumberMode::String`], fields typed as 64-bit integers are typed
+    /// `string`.
+    pub big_numbers: BigNumberMode,
 }
 
 impl Default for TypeScriptConfig {
@@ -38,6 +43,7 @@ impl Default for TypeScriptConfig {
             interface_prefix: "".to_string(),
             export_const_name: "STACK".to_string(),
             url: None,
+            big_numbers: BigNumberMode::default(),
         }
     }
 }
@@ -66,6 +72,7 @@ pub struct TypeScriptCompiler<S> {
     handlers_json: Option<serde_json::Value>, // Raw handlers for event interface generation
     views: Vec<ViewDef>,            // View definitions for derived views
     already_emitted_types: HashSet<String>,
+    event_types: Vec<String>, // Event types routed to the entity
 }
 
 impl<S> TypeScriptCompiler<S> {
@@ -78,10 +85,23 @@ impl<S> TypeScriptCompiler<S> {
             handlers_json: None,
             views: Vec::new(),
             already_emitted_types: HashSet::new(),
+            event_types: Vec::new(),
         }
     }
 
     pub fn with_config(mut self, config: TypeScriptConfig) -> Self {
+        if config.big_numbers == BigNumberMode::String {
+            // Typed like the `u128` fields the server always sends as strings
+            let fields = self
+                .spec
+                .sections
+                .iter_mut()
+                .flat_map(|section| section.fields.iter_mut())
+                .chain(self.spec.field_mappings.values_mut());
+            for field in fields.filter(|field| field.is_wide_integer()) {
+                field.encoding = Some(ValueEncoding::DecimalString);
+            }
+        }
         self.config = config;
         self
     }
@@ -106,6 +126,11 @@ impl<S> TypeScriptCompiler<S> {
         self
     }
 
+    pub fn with_event_types(mut self, event_types: Vec<String>) -> Self {
+        self.event_types = event_types;
+        self
+    }
+
     pub fn compile(&self) -> TypeScriptOutput {
         let imports = self.generate_imports();
         let interfaces = self.generate_interfaces();
@@ -117,6 +142,20 @@ impl<S> TypeScriptCompiler<S> {
         } else {
             format!("{}\n\n{}", interfaces, schema_output.definitions)
         };
+        let key_builder = self.generate_key_builder();
+        let combined_interfaces = if key_builder.is_empty() {
+            combined_interfaces
+        } else if combined_interfaces.is_empty() {
+            key_builder
+        } else {
+            format!("{}\n\n{}", combined_interfaces, key_builder)
+        };
+        let event_types = generate_event_types(&self.entity_name, &self.event_types);
+        let combined_interfaces = if combined_interfaces.is_empty() {
+            event_types
+        } else {
+            format!("{}\n\n{}", combined_interfaces, event_types)
+        };
         let stack_definition = self.generate_stack_definition();
 
         TypeScriptOutput {
@@ -164,7 +203,10 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
         // Deduplicate fields within each section and generate interfaces
         // Skip root section - its fields will be flattened into main entity interface
         for (section_name, fields) in all_sections {
-            if !is_root_section(&section_name) && processed_types.insert(section_name.clone()) {
+            if !is_root_section(&section_name)
+                && !self.shared_section_emitted(&section_name)
+                && processed_types.insert(self.section_type_name(&section_name))
+            {
                 let deduplicated_fields = self.deduplicate_fields(fields);
                 let interface =
                     self.generate_interface_from_fields(&section_name, &deduplicated_fields);
@@ -245,9 +287,12 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
 
                 let ts_field = TypeScriptField {
                     name: field_name.to_string(),
-                    ts_type: self.mapping_to_typescript_type(mapping),
+                    ts_type: self.with_resolver_placeholder(
+                        &mapping.target_path,
+                        self.mapping_to_typescript_type(mapping),
+                    ),
                     optional: self.is_field_optional(mapping),
-                    description: None,
+                    docs: self.field_docs(section_name, field_name),
                 };
 
                 sections
@@ -257,9 +302,12 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
             } else {
                 let ts_field = TypeScriptField {
                     name: mapping.target_path.clone(),
-                    ts_type: self.mapping_to_typescript_type(mapping),
+                    ts_type: self.with_resolver_placeholder(
+                        &mapping.target_path,
+                        self.mapping_to_typescript_type(mapping),
+                    ),
                     optional: self.is_field_optional(mapping),
-                    description: None,
+                    docs: self.field_docs("Root", &mapping.target_path),
                 };
 
                 sections
@@ -309,9 +357,12 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
 
                         section_fields.push(TypeScriptField {
                             name: field_info.field_name.clone(),
-                            ts_type: self.field_type_info_to_typescript(effective_field_info),
+                            ts_type: self.with_resolver_placeholder(
+                                &field_path,
+                                self.field_type_info_to_typescript(effective_field_info),
+                            ),
                             optional: field_info.is_optional,
-                            description: None,
+                            docs: field_info.docs.clone(),
                         });
                     }
                 }
@@ -332,14 +383,12 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
                     let already_exists = section_fields.iter().any(|f| f.name == field_name);
 
                     if !already_exists {
+                        let (base_type, is_array) = field_type_info.stored_shape();
                         section_fields.push(TypeScriptField {
                             name: field_name.to_string(),
-                            ts_type: self.base_type_to_typescript(
-                                &field_type_info.base_type,
-                                field_type_info.is_array,
-                            ),
+                            ts_type: self.base_type_to_typescript(&base_type, is_array),
                             optional: field_type_info.is_optional,
-                            description: None,
+                            docs: field_type_info.docs.clone(),
                         });
                     }
                 }
@@ -361,17 +410,46 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
                 } else {
                     field.ts_type.clone()
                 };
-                format!("  {}?: {};", field.name, ts_type)
+                format!(
+                    "{}  {}?: {};",
+                    jsdoc(field.docs.as_ref(), "  "),
+                    field.name,
+                    ts_type
+                )
             })
             .collect();
 
         format!(
-            "export interface {} {{\n{}\n}}",
+            "{}export interface {} {{\n{}\n}}",
+            jsdoc(self.section_docs(name), ""),
             interface_name,
             field_definitions.join("\n")
         )
     }
 
+    fn section_docs(&self, name: &str) -> Option<&ItemDocs> {
+        self.spec
+            .sections
+            .iter()
+            .find(|section| {
+                section.name == name || (is_root_section(name) && is_root_section(&section.name))
+            })
+            .and_then(|section| section.docs.as_ref())
+    }
+
+    fn field_docs(&self, section_name: &str, field_name: &str) -> Option<ItemDocs> {
+        self.spec
+            .sections
+            .iter()
+            .filter(|section| {
+                section.name == section_name
+                    || (is_root_section(section_name) && is_root_section(&section.name))
+            })
+            .flat_map(|section| &section.fields)
+            .find(|field| field.field_name == field_name)
+            .and_then(|field| field.docs.clone())
+    }
+
     fn section_interface_name(&self, name: &str) -> String {
         if name == "Root" {
             format!(
@@ -380,22 +458,48 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
                 to_pascal_case(&self.entity_name)
             )
         } else {
-            // Create compound names like GameEvents, GameStatus, etc.
-            // Extract the base name (e.g., "Game" from "TestGame" or "SettlementGame")
-            let base_name = if self.entity_name.contains("Game") {
-                "Game"
-            } else {
-                &self.entity_name
-            };
             format!(
-                "{}{}{}",
+                "{}{}",
                 self.config.interface_prefix,
-                base_name,
-                to_pascal_case(name)
+                self.section_type_name(name)
             )
         }
     }
 
+    /// Type of the section `name`: the shared type it was imported from, or
+    /// a compound name like GameEvents or GameStatus
+    fn section_type_name(&self, name: &str) -> String {
+        let shared_type = self
+            .spec
+            .sections
+            .iter()
+            .find(|section| section.name == name)
+            .and_then(|section| section.shared_type.clone());
+        if let Some(shared_type) = shared_type {
+            return shared_type;
+        }
+
+        // Extract the base name (e.g., "Game" from "TestGame" or "SettlementGame")
+        let base_name = if self.entity_name.contains("Game") {
+            "Game"
+        } else {
+            &self.entity_name
+        };
+        format!("{}{}", base_name, to_pascal_case(name))
+    }
+
+    /// Whether section `name` has a shared type another entity of the stack
+    /// already emitted
+    fn shared_section_emitted(&self, name: &str) -> bool {
+        self.spec.sections.iter().any(|section| {
+            section.name == name
+                && section
+                    .shared_type
+                    .as_ref()
+                    .is_some_and(|shared_type| self.already_emitted_types.contains(shared_type))
+        })
+    }
+
     fn generate_main_entity_interface(&self) -> String {
         let entity_name = to_pascal_case(&self.entity_name);
 
@@ -440,14 +544,14 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
         // All fields are optional since we receive patches
         for section in sections.keys() {
             if !is_root_section(section) {
-                let base_name = if self.entity_name.contains("Game") {
-                    "Game"
-                } else {
-                    &self.entity_name
-                };
-                let section_interface_name = format!("{}{}", base_name, to_pascal_case(section));
+                let section_interface_name = self.section_type_name(section);
                 // Keep section field names as-is (snake_case from AST)
-                fields.push(format!("  {}?: {};", section, section_interface_name));
+                fields.push(format!(
+                    "{}  {}?: {};",
+                    jsdoc(self.section_docs(section), "  "),
+                    section,
+                    section_interface_name
+                ));
             }
         }
 
@@ -459,13 +563,21 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
                     if !field.emit {
                         continue;
                     }
-                    let base_ts_type = self.field_type_info_to_typescript(field);
+                    let base_ts_type = self.with_resolver_placeholder(
+                        &field.field_name,
+                        self.field_type_info_to_typescript(field),
+                    );
                     let ts_type = if field.is_optional {
                         format!("{} | null", base_ts_type)
                     } else {
                         base_ts_type
                     };
-                    fields.push(format!("  {}?: {};", field.field_name, ts_type));
+                    fields.push(format!(
+                        "{}  {}?: {};",
+                        jsdoc(field.docs.as_ref(), "  "),
+                        field.field_name,
+                        ts_type
+                    ));
                 }
             }
         }
@@ -475,7 +587,8 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
         }
 
         format!(
-            "export interface {} {{\n{}\n}}",
+            "{}export interface {} {{\n{}\n}}",
+            jsdoc(self.spec.docs.as_ref(), ""),
             entity_name,
             fields.join("\n")
         )
@@ -519,7 +632,7 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
         let all_sections = self.collect_interface_sections();
 
         for (section_name, fields) in &all_sections {
-            if is_root_section(section_name) {
+            if is_root_section(section_name) || self.shared_section_emitted(section_name) {
                 continue;
             }
             let deduplicated_fields = self.deduplicate_fields(fields.clone());
@@ -546,6 +659,7 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
     fn generate_event_wrapper_schema(&self) -> String {
         r#"export const EventWrapperSchema = <T extends z.ZodTypeAny>(data: T) => z.object({
   timestamp: z.number(),
+  type: z.string().optional(),
   data,
   slot: z.number().optional(),
   signature: z.string().optional(),
@@ -696,17 +810,12 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
 
         for section in sections.keys() {
             if !is_root_section(section) {
-                let base_name = if self.entity_name.contains("Game") {
-                    "Game"
-                } else {
-                    &self.entity_name
-                };
-                let section_interface_name = format!("{}{}", base_name, to_pascal_case(section));
+                let section_interface_name = self.section_type_name(section);
                 fields.push(TypeScriptField {
                     name: section.to_string(),
                     ts_type: section_interface_name,
                     optional: false,
-                    description: None,
+                    docs: None,
                 });
             }
         }
@@ -719,9 +828,12 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
                     }
                     fields.push(TypeScriptField {
                         name: field.field_name.clone(),
-                        ts_type: self.field_type_info_to_typescript(field),
+                        ts_type: self.with_resolver_placeholder(
+                            &field.field_name,
+                            self.field_type_info_to_typescript(field),
+                        ),
                         optional: field.is_optional,
-                        description: None,
+                        docs: None,
                     });
                 }
             }
@@ -976,6 +1088,16 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
     fn typescript_type_to_zod(&self, ts_type: &str) -> String {
         let trimmed = ts_type.trim();
 
+        if let Some((base, marker)) = trimmed.split_once(" | { status: ") {
+            if let Some(status) = marker.strip_suffix(" }") {
+                return format!(
+                    "z.union([{}, z.object({{ status: z.literal({}) }})])",
+                    self.typescript_type_to_zod(base),
+                    status
+                );
+            }
+        }
+
         if let Some(inner) = trimmed.strip_suffix("[]") {
             return format!("z.array({})", self.typescript_type_to_zod(inner));
         }
@@ -997,8 +1119,9 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
     }
 
     fn resolved_field_to_zod(&self, field: &ResolvedField) -> String {
-        let base = self.base_type_to_zod(&field.base_type);
-        if field.is_array {
+        let (base_type, is_array) = field.stored_shape();
+        let base = self.base_type_to_zod(&base_type);
+        if is_array {
             format!("z.array({})", base)
         } else {
             base
@@ -1058,6 +1181,9 @@ export const {} = {{
       state: stateView<{}>('{}/state'),
       list: listView<{}>('{}/list'),{}
     }},
+  }},
+  eventTypes: {{
+    {}: {},
   }},{}
 }} as const;
 
@@ -1077,6 +1203,8 @@ export default {};"#,
             entity_pascal,
             self.entity_name,
             derived_views,
+            self.entity_name,
+            event_types_const_name(&self.entity_name),
             schemas_block,
             entity_pascal,
             export_name,
@@ -1084,6 +1212,61 @@ export default {};"#,
         )
     }
 
+    /// Generate the typed `{Entity}Key` builder for the entity's primary key.
+    ///
+    /// `build()` returns the canonical key string accepted by `get()` and
+    /// `listen()` on state views; `parse()` reads the typed value back.
+    /// Integer keys parse to `bigint`, as u64 keys do not fit a `number`.
+    fn generate_key_builder(&self) -> String {
+        let Some(recipe) = self.spec.key_recipe() else {
+            return String::new();
+        };
+
+        let key_name = format!(
+            "{}{}Key",
+            self.config.interface_prefix,
+            to_pascal_case(&self.entity_name)
+        );
+        let name = &recipe.field_name;
+        let (param_type, parsed_type, parse_body) = match recipe.base_type {
+            BaseType::Integer | BaseType::Timestamp => (
+                "number | bigint | string",
+                "bigint",
+                format!(
+                    "if (!/^-?\\d+$/.test(key)) return null;\n    return {{ {}: BigInt(key) }};",
+                    name
+                ),
+            ),
+            BaseType::Boolean => (
+                "boolean",
+                "boolean",
+                format!(
+                    "if (key !== 'true' && key !== 'false') return null;\n    return {{ {}: key === 'true' }};",
+                    name
+                ),
+            ),
+            _ => ("string", "string", format!("return {{ {}: key }};", name)),
+        };
+
+        format!(
+            r#"/** Typed key builder for {entity} entities (canonical format: `{{{name}}}`) */
+export const {key_name} = {{
+  build({name}: {param_type}): string {{
+    return String({name});
+  }},
+  parse(key: string): {{ {name}: {parsed_type} }} | null {{
+    {parse_body}
+  }},
+}} as const;"#,
+            entity = to_pascal_case(&self.entity_name),
+            key_name = key_name,
+            name = name,
+            param_type = param_type,
+            parsed_type = parsed_type,
+            parse_body = parse_body,
+        )
+    }
+
     fn generate_derived_view_entries(&self) -> String {
         let derived_views: Vec<&ViewDef> = self
             .views
@@ -1148,28 +1331,35 @@ export default {};"#,
                     }
                     MappingSource::Constant(value) => value_to_typescript_type(value),
                     MappingSource::AsEvent { .. } => "any".to_string(),
+                    MappingSource::FromTransaction { field } => match field.as_str() {
+                        "fee" | "compute_units" => "number".to_string(),
+                        _ => "string".to_string(),
+                    },
                     _ => "any".to_string(),
                 };
 
                 // Apply transformations to type
-                if let Some(transform) = &mapping.transform {
-                    match transform {
-                        Transformation::HexEncode | Transformation::HexDecode => {
-                            "string".to_string()
-                        }
-                        Transformation::Base58Encode | Transformation::Base58Decode => {
-                            "string".to_string()
-                        }
-                        Transformation::ToString => "string".to_string(),
-                        Transformation::ToNumber => "number".to_string(),
-                    }
-                } else {
-                    base_type
+                match &mapping.transform {
+                    Some(transform) => transformed_typescript_type(transform, base_type),
+                    None => base_type,
                 }
             }
         }
     }
 
+    /// Widen a resolver target's type with its pending marker, if the
+    /// resolver declares a placeholder
+    fn with_resolver_placeholder(&self, target_path: &str, ts_type: String) -> String {
+        match crate::ast::resolver_placeholder(&self.spec.resolver_specs, target_path) {
+            Some(placeholder) => format!(
+                "{} | {{ status: {} }}",
+                ts_type,
+                serde_json::Value::String(placeholder.to_string())
+            ),
+            None => ts_type,
+        }
+    }
+
     fn field_type_info_to_typescript(&self, field_info: &FieldTypeInfo) -> String {
         if let Some(resolved) = &field_info.resolved_type {
             let interface_name = self.resolved_type_to_interface_name(resolved);
@@ -1211,7 +1401,8 @@ export default {};"#,
             }
         }
 
-        self.base_type_to_typescript(&field_info.base_type, field_info.is_array)
+        let (base_type, is_array) = field_info.stored_shape();
+        self.base_type_to_typescript(&base_type, is_array)
     }
 
     /// Find the generated event interface name for a given field
@@ -1510,9 +1701,9 @@ export default {};"#,
         // Handle different IDL type formats
         if let Some(type_str) = idl_type.as_str() {
             return match type_str {
-                "u8" | "u16" | "u32" | "u64" | "u128" | "i8" | "i16" | "i32" | "i64" | "i128" => {
-                    "number".to_string()
-                }
+                "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32" | "i64" => "number".to_string(),
+                // Emitted as decimal strings, JSON numbers would lose precision
+                "u128" | "i128" => "string".to_string(),
                 "f32" | "f64" => "number".to_string(),
                 "bool" => "boolean".to_string(),
                 "string" => "string".to_string(),
@@ -1577,9 +1768,10 @@ export default {};"#,
 
     /// Convert a resolved field to TypeScript type
     fn resolved_field_to_typescript(&self, field: &ResolvedField) -> String {
-        let base_ts = self.base_type_to_typescript(&field.base_type, false);
+        let (base_type, is_array) = field.stored_shape();
+        let base_ts = self.base_type_to_typescript(&base_type, false);
 
-        if field.is_array {
+        if is_array {
             format!("{}[]", base_ts)
         } else {
             base_ts
@@ -1609,6 +1801,8 @@ export default {};"#,
 export interface EventWrapper<T> {
   /** Unix timestamp when the event was processed */
   timestamp: number;
+  /** Event type the event was routed as, e.g. `ore::DeployIxState` */
+  type?: string;
   /** The event-specific data */
   data: T;
   /** Optional blockchain slot number */
@@ -1715,8 +1909,7 @@ struct TypeScriptField {
     name: String,
     ts_type: String,
     optional: bool,
-    #[allow(dead_code)]
-    description: Option<String>,
+    docs: Option<ItemDocs>,
 }
 
 #[derive(Debug, Clone)]
@@ -1725,6 +1918,21 @@ struct SchemaOutput {
     names: Vec<String>,
 }
 
+/// The TypeScript type of a field after `transform` is applied to a `base_type` value
+fn transformed_typescript_type(transform: &Transformation, base_type: String) -> String {
+    match transform {
+        Transformation::HexEncode | Transformation::HexDecode => "string".to_string(),
+        Transformation::Base58Encode | Transformation::Base58Decode => "string".to_string(),
+        Transformation::Base64Encode | Transformation::Base64Decode => "string".to_string(),
+        Transformation::ToString => "string".to_string(),
+        Transformation::ToNumber => "number".to_string(),
+        Transformation::Slice { .. } | Transformation::Take(_) => "number[]".to_string(),
+        Transformation::Chain(stages) => stages.iter().fold(base_type, |ty, stage| {
+            transformed_typescript_type(stage, ty)
+        }),
+    }
+}
+
 /// Convert serde_json::Value to TypeScript type string
 fn value_to_typescript_type(value: &serde_json::Value) -> String {
     match value {
@@ -1823,6 +2031,41 @@ fn normalize_for_comparison(s: &str) -> String {
         .collect()
 }
 
+/// Render docs as a JSDoc block (with trailing newline) at the given indent
+fn jsdoc(docs: Option<&ItemDocs>, indent: &str) -> String {
+    let Some(docs) = docs else {
+        return String::new();
+    };
+
+    let mut lines: Vec<String> = docs
+        .description
+        .iter()
+        .flat_map(|description| description.lines())
+        .map(|line| line.replace("*/", "*\\/"))
+        .collect();
+    if let Some(unit) = &docs.unit {
+        lines.push(format!("@unit {}", unit.replace("*/", "*\\/")));
+    }
+
+    match lines.as_slice() {
+        [] => String::new(),
+        [line] => format!("{indent}/** {line} */\n"),
+        _ => {
+            let body: String = lines
+                .iter()
+                .map(|line| {
+                    if line.is_empty() {
+                        format!("{indent} *\n")
+                    } else {
+                        format!("{indent} * {line}\n")
+                    }
+                })
+                .collect();
+            format!("{indent}/**\n{body}{indent} */\n")
+        }
+    }
+}
+
 fn is_root_section(name: &str) -> bool {
     name.eq_ignore_ascii_case("root")
 }
@@ -1894,9 +2137,11 @@ fn compile_serializable_spec_with_emitted(
     let handlers = serde_json::to_value(&spec.handlers).ok();
     let views = spec.views.clone();
 
+    let event_types = spec.event_type_names();
     let typed_spec: TypedStreamSpec<()> = TypedStreamSpec::from_serializable(spec);
 
     let compiler = TypeScriptCompiler::new(typed_spec, entity_name)
+        .with_event_types(event_types)
         .with_idl(idl)
         .with_handlers_json(handlers)
         .with_views(views)
@@ -1912,6 +2157,8 @@ pub struct TypeScriptStackConfig {
     pub generate_helpers: bool,
     pub export_const_name: String,
     pub url: Option<String>,
+    /// See [`TypeScriptConfig::big_numbers`]
+    pub big_numbers: BigNumberMode,
 }
 
 impl Default for TypeScriptStackConfig {
@@ -1921,6 +2168,7 @@ impl Default for TypeScriptStackConfig {
             generate_helpers: true,
             export_const_name: "STACK".to_string(),
             url: None,
+            big_numbers: BigNumberMode::default(),
         }
     }
 }
@@ -1983,10 +2231,16 @@ pub fn compile_stack_spec(
             interface_prefix: String::new(),
             export_const_name: config.export_const_name.clone(),
             url: config.url.clone(),
+            big_numbers: config.big_numbers,
         };
 
-        // Collect builtin type names before spec is consumed
+        // Collect builtin and shared section type names before spec is consumed
         let builtin_type_names = extract_builtin_resolver_type_names(&spec);
+        let shared_type_names: Vec<String> = spec
+            .sections
+            .iter()
+            .filter_map(|section| section.shared_type.clone())
+            .collect();
         // Clone IDL before spec is moved so we can check which enums were emitted
         let idl_for_check = spec.idl.clone();
 
@@ -2003,6 +2257,7 @@ pub fn compile_stack_spec(
             extract_emitted_enum_type_names(&output.interfaces, idl_for_check.as_ref());
         emitted_types.extend(emitted_enum_names);
         emitted_types.extend(builtin_type_names);
+        emitted_types.extend(shared_type_names);
 
         // Only take the interfaces part (not the stack_definition — we generate our own)
         if !output.interfaces.is_empty() {
@@ -2137,6 +2392,10 @@ fn generate_stack_definition_multi(
     }
 
     let views_body = entity_view_blocks.join("\n");
+    let event_types_body: Vec<String> = entity_names
+        .iter()
+        .map(|name| format!("    {}: {},", name, event_types_const_name(name)))
+        .collect();
 
     let pdas_block = generate_pdas_block(pdas, program_ids);
 
@@ -2174,6 +2433,9 @@ export const {export_name} = {{
 {url_line}
   views: {{
 {views_body}
+  }},
+  eventTypes: {{
+{event_types_body}
   }},{schemas_section}{pdas_section}
 }} as const;
 
@@ -2192,6 +2454,7 @@ export default {export_name};"#,
         stack_kebab = stack_kebab,
         url_line = url_line,
         views_body = views_body,
+        event_types_body = event_types_body.join("\n"),
         schemas_section = schemas_block,
         pdas_section = pdas_block,
         entity_union = entity_types.join(" | "),
@@ -2286,6 +2549,48 @@ function listView<T>(view: string): ViewDef<T, 'list'> {
     .to_string()
 }
 
+/// `ORE_ROUND_EVENT_TYPES` for `OreRound`
+fn event_types_const_name(entity_name: &str) -> String {
+    format!("{}_EVENT_TYPES", to_screaming_snake_case(entity_name))
+}
+
+/// The event types routed to an entity as a const array, the union of its
+/// names, and a classifier with an `{ unknown }` escape hatch for types added
+/// after the SDK was generated
+fn generate_event_types(entity_name: &str, event_types: &[String]) -> String {
+    let entity = to_pascal_case(entity_name);
+    let const_name = event_types_const_name(entity_name);
+    let names: String = event_types
+        .iter()
+        .map(|event_type| format!("\n  '{}',", event_type))
+        .collect();
+
+    format!(
+        r#"/** Every event type routed to {entity} */
+export const {const_name} = [{names}
+] as const;
+
+/** Event types routed to {entity} */
+export type {entity}EventType = (typeof {const_name})[number];
+
+/**
+ * The event type `event` was routed as, `{{ unknown }}` for types added after
+ * this SDK was generated, or `undefined` when the server didn't send one.
+ */
+export function classify{entity}Event(
+  event: {{ type?: string }},
+): {entity}EventType | {{ unknown: string }} | undefined {{
+  if (event.type === undefined) return undefined;
+  return ({const_name} as readonly string[]).includes(event.type)
+    ? (event.type as {entity}EventType)
+    : {{ unknown: event.type }};
+}}"#,
+        entity = entity,
+        const_name = const_name,
+        names = names,
+    )
+}
+
 /// Convert PascalCase to SCREAMING_SNAKE_CASE (e.g., "OreStream" -> "ORE_STREAM")
 fn to_screaming_snake_case(s: &str) -> String {
     let mut result = String::new();
@@ -2375,6 +2680,13 @@ mod tests {
                     output: ViewOutput::Collection,
                 },
             ],
+            trace_fields: vec![],
+            feature: None,
+            priority: EntityPriority::Normal,
+            delete_on: None,
+            key_normalizer: None,
+            docs: None,
+            event_types: Vec::new(),
         };
 
         let output =
@@ -2408,4 +2720,475 @@ mod tests {
             stack_def
         );
     }
+
+    #[test]
+    fn test_key_builder_codegen() {
+        let mut field_mappings = BTreeMap::new();
+        field_mappings.insert(
+            "id.authority".to_string(),
+            FieldTypeInfo::new("authority".to_string(), "Pubkey".to_string()),
+        );
+        field_mappings.insert(
+            "id.round_id".to_string(),
+            FieldTypeInfo::new("round_id".to_string(), "u64".to_string()),
+        );
+        let spec = SerializableStreamSpec {
+            ast_version: CURRENT_AST_VERSION.to_string(),
+            state_name: "OreMiner".to_string(),
+            program_id: None,
+            idl: None,
+            identity: IdentitySpec {
+                primary_keys: vec!["id.round_id".to_string(), "id.authority".to_string()],
+                lookup_indexes: vec![],
+            },
+            handlers: vec![],
+            sections: vec![],
+            field_mappings,
+            resolver_hooks: vec![],
+            resolver_specs: vec![],
+            instruction_hooks: vec![],
+            computed_fields: vec![],
+            computed_field_specs: vec![],
+            content_hash: None,
+            views: vec![],
+            trace_fields: vec![],
+            feature: None,
+            priority: EntityPriority::Normal,
+            delete_on: None,
+            key_normalizer: None,
+            docs: None,
+            event_types: Vec::new(),
+        };
+
+        let output =
+            compile_serializable_spec(spec, "OreMiner".to_string(), None).expect("should compile");
+
+        assert!(
+            output.interfaces.contains("export const OreMinerKey = {"),
+            "Expected OreMinerKey builder, got:\n{}",
+            output.interfaces
+        );
+        assert!(
+            output
+                .interfaces
+                .contains("build(round_id: number | bigint | string): string"),
+            "Expected typed build() signature, got:\n{}",
+            output.interfaces
+        );
+        assert!(
+            output
+                .interfaces
+                .contains("parse(key: string): { round_id: bigint } | null"),
+            "Expected parse() to keep u64 precision, got:\n{}",
+            output.interfaces
+        );
+        assert!(
+            output.interfaces.contains(
+                "if (!/^-?\\d+$/.test(key)) return null;\n    return { round_id: BigInt(key) };"
+            ),
+            "Expected typed parse() body, got:\n{}",
+            output.interfaces
+        );
+    }
+
+    #[test]
+    fn test_interfaces_carry_jsdoc() {
+        let mut rewards_sol = FieldTypeInfo::new("rewards_sol".to_string(), "u64".to_string());
+        rewards_sol.docs = Some(ItemDocs {
+            description: Some("Unclaimed SOL rewards.".to_string()),
+            unit: Some("lamports".to_string()),
+        });
+        let mut authority = FieldTypeInfo::new("authority".to_string(), "Pubkey".to_string());
+        authority.docs = Some(ItemDocs {
+            description: Some("Wallet that owns the miner.".to_string()),
+            unit: None,
+        });
+        let spec = SerializableStreamSpec {
+            ast_version: CURRENT_AST_VERSION.to_string(),
+            state_name: "OreMiner".to_string(),
+            program_id: None,
+            idl: None,
+            identity: IdentitySpec {
+                primary_keys: vec!["authority".to_string()],
+                lookup_indexes: vec![],
+            },
+            handlers: vec![],
+            sections: vec![
+                EntitySection {
+                    name: "rewards".to_string(),
+                    fields: vec![rewards_sol],
+                    is_nested_struct: false,
+                    parent_field: None,
+                    docs: Some(ItemDocs {
+                        description: Some("Reward balances.".to_string()),
+                        unit: None,
+                    }),
+                    shared_type: None,
+                },
+                EntitySection {
+                    name: "root".to_string(),
+                    fields: vec![authority],
+                    is_nested_struct: false,
+                    parent_field: None,
+                    docs: None,
+                    shared_type: None,
+                },
+            ],
+            field_mappings: BTreeMap::new(),
+            resolver_hooks: vec![],
+            resolver_specs: vec![],
+            instruction_hooks: vec![],
+            computed_fields: vec![],
+            computed_field_specs: vec![],
+            content_hash: None,
+            views: vec![],
+            trace_fields: vec![],
+            feature: None,
+            priority: EntityPriority::Normal,
+            delete_on: None,
+            key_normalizer: None,
+            docs: Some(ItemDocs {
+                description: Some("A miner in an ORE round.\n\nKeyed by authority.".to_string()),
+                unit: None,
+            }),
+            event_types: Vec::new(),
+        };
+
+        let output =
+            compile_serializable_spec(spec, "OreMiner".to_string(), None).expect("should compile");
+        let interfaces = &output.interfaces;
+
+        assert!(
+            interfaces.contains(
+                "/** Reward balances. */\nexport interface OreMinerRewards {\n  /**\n   * Unclaimed SOL rewards.\n   * @unit lamports\n   */\n  rewards_sol?: number;"
+            ),
+            "Expected documented section interface, got:\n{}",
+            interfaces
+        );
+        assert!(
+            interfaces.contains(
+                "/**\n * A miner in an ORE round.\n *\n * Keyed by authority.\n */\nexport interface OreMiner {\n  /** Reward balances. */\n  rewards?: OreMinerRewards;\n  /** Wallet that owns the miner. */\n  authority?: string;"
+            ),
+            "Expected documented entity interface, got:\n{}",
+            interfaces
+        );
+    }
+
+    #[test]
+    fn test_shared_sections_emit_one_interface() {
+        let entity = |name: &str, sections: &[&str]| SerializableStreamSpec {
+            ast_version: CURRENT_AST_VERSION.to_string(),
+            state_name: name.to_string(),
+            program_id: None,
+            idl: None,
+            identity: IdentitySpec {
+                primary_keys: vec![],
+                lookup_indexes: vec![],
+            },
+            handlers: vec![],
+            sections: sections
+                .iter()
+                .map(|section| EntitySection {
+                    name: section.to_string(),
+                    fields: vec![FieldTypeInfo::new(
+                        "symbol".to_string(),
+                        "Option<String>".to_string(),
+                    )],
+                    is_nested_struct: false,
+                    parent_field: None,
+                    docs: None,
+                    shared_type: Some("TokenMetadata".to_string()),
+                })
+                .collect(),
+            field_mappings: BTreeMap::new(),
+            resolver_hooks: vec![],
+            resolver_specs: vec![],
+            instruction_hooks: vec![],
+            computed_fields: vec![],
+            computed_field_specs: vec![],
+            content_hash: None,
+            views: vec![],
+            trace_fields: vec![],
+            feature: None,
+            priority: EntityPriority::Normal,
+            delete_on: None,
+            key_normalizer: None,
+            docs: None,
+            event_types: Vec::new(),
+        };
+        let stack = SerializableStackSpec {
+            ast_version: CURRENT_AST_VERSION.to_string(),
+            stack_name: "Markets".to_string(),
+            program_ids: vec![],
+            idls: vec![],
+            entities: vec![
+                entity("Pool", &["base", "quote"]),
+                entity("Vault", &["token"]),
+            ],
+            pdas: BTreeMap::new(),
+            instructions: vec![],
+            features: vec![],
+            content_hash: None,
+        };
+
+        let output = compile_stack_spec(stack, None).expect("should compile");
+        let interfaces = &output.interfaces;
+
+        assert_eq!(
+            interfaces
+                .matches("export interface TokenMetadata {")
+                .count(),
+            1,
+            "{interfaces}"
+        );
+        assert_eq!(
+            interfaces
+                .matches("export const TokenMetadataSchema")
+                .count(),
+            1
+        );
+        for field in ["base", "quote", "token"] {
+            assert!(interfaces.contains(&format!("  {field}?: TokenMetadata;")));
+        }
+        assert!(!interfaces.contains("PoolBase"));
+    }
+
+    #[test]
+    fn test_encoded_fields_are_strings() {
+        let resolved_field = |name: &str, base_type, is_array, encoding| ResolvedField {
+            field_name: name.to_string(),
+            field_type: String::new(),
+            base_type,
+            is_optional: false,
+            is_array,
+            encoding,
+        };
+        let mut last_signature =
+            FieldTypeInfo::new("last_signature".to_string(), "Option<Vec<u8>>".to_string());
+        last_signature.encoding = Some(ValueEncoding::Base58);
+        let mut snapshot = FieldTypeInfo::new("snapshot".to_string(), "Option<Vault>".to_string());
+        snapshot.resolved_type = Some(ResolvedStructType {
+            type_name: "Vault".to_string(),
+            fields: vec![
+                resolved_field(
+                    "totalDeposits",
+                    BaseType::Integer,
+                    false,
+                    Some(ValueEncoding::DecimalString),
+                ),
+                resolved_field(
+                    "lastSignature",
+                    BaseType::String,
+                    false,
+                    Some(ValueEncoding::Base58),
+                ),
+                resolved_field("merkleRoot", BaseType::Integer, true, None),
+            ],
+            is_instruction: false,
+            is_account: true,
+            is_event: false,
+            is_enum: false,
+            enum_variants: vec![],
+        });
+        let spec = SerializableStreamSpec {
+            ast_version: CURRENT_AST_VERSION.to_string(),
+            state_name: "Treasury".to_string(),
+            program_id: None,
+            idl: None,
+            identity: IdentitySpec {
+                primary_keys: vec!["id.authority".to_string()],
+                lookup_indexes: vec![],
+            },
+            handlers: vec![],
+            sections: vec![EntitySection {
+                name: "vault".to_string(),
+                fields: vec![
+                    FieldTypeInfo::new("total_deposits".to_string(), "Option<u128>".to_string()),
+                    FieldTypeInfo::new("flows".to_string(), "Vec<i128>".to_string()),
+                    last_signature,
+                    snapshot,
+                ],
+                is_nested_struct: false,
+                parent_field: None,
+                docs: None,
+                shared_type: None,
+            }],
+            field_mappings: BTreeMap::new(),
+            resolver_hooks: vec![],
+            resolver_specs: vec![],
+            instruction_hooks: vec![],
+            computed_fields: vec![],
+            computed_field_specs: vec![],
+            content_hash: None,
+            views: vec![],
+            trace_fields: vec![],
+            feature: None,
+            priority: EntityPriority::Normal,
+            delete_on: None,
+            key_normalizer: None,
+            docs: None,
+            event_types: Vec::new(),
+        };
+
+        let output =
+            compile_serializable_spec(spec, "Treasury".to_string(), None).expect("should compile");
+        let interfaces = &output.interfaces;
+        assert!(
+            interfaces.contains(
+                "export interface TreasuryVault {\n  flows?: string[];\n  last_signature?: string | null;\n  snapshot?: Vault | null;\n  total_deposits?: string | null;\n}"
+            ),
+            "{interfaces}"
+        );
+        assert!(interfaces.contains(
+            "export interface Vault {\n  totalDeposits?: string;\n  lastSignature?: string;\n  merkleRoot?: number[];\n}"
+        ));
+        assert!(interfaces.contains("  totalDeposits: z.string().optional(),"));
+        assert!(interfaces.contains("  lastSignature: z.string().optional(),"));
+        assert!(interfaces.contains("  flows: z.array(z.string()).optional(),"));
+        assert!(interfaces.contains("  total_deposits: z.string().nullable().optional(),"));
+    }
+
+    #[test]
+    fn test_wide_integers_are_strings_in_string_mode() {
+        let spec = SerializableStreamSpec {
+            ast_version: CURRENT_AST_VERSION.to_string(),
+            state_name: "Round".to_string(),
+            program_id: None,
+            idl: None,
+            identity: IdentitySpec {
+                primary_keys: vec!["id.round_id".to_string()],
+                lookup_indexes: vec![],
+            },
+            handlers: vec![],
+            sections: vec![EntitySection {
+                name: "state".to_string(),
+                fields: vec![
+                    FieldTypeInfo::new("total_deployed".to_string(), "Option<u64>".to_string()),
+                    FieldTypeInfo::new("per_square".to_string(), "Vec<i64>".to_string()),
+                    FieldTypeInfo::new("miners".to_string(), "Option<u32>".to_string()),
+                ],
+                is_nested_struct: false,
+                parent_field: None,
+                docs: None,
+                shared_type: None,
+            }],
+            field_mappings: BTreeMap::new(),
+            resolver_hooks: vec![],
+            resolver_specs: vec![],
+            instruction_hooks: vec![],
+            computed_fields: vec![],
+            computed_field_specs: vec![],
+            content_hash: None,
+            views: vec![],
+            trace_fields: vec![],
+            feature: None,
+            priority: EntityPriority::Normal,
+            delete_on: None,
+            key_normalizer: None,
+            docs: None,
+            event_types: Vec::new(),
+        };
+        let compile = |big_numbers| {
+            let config = TypeScriptConfig {
+                big_numbers,
+                ..TypeScriptConfig::default()
+            };
+            compile_serializable_spec(spec.clone(), "Round".to_string(), Some(config))
+                .expect("should compile")
+                .interfaces
+        };
+
+        let auto = compile(BigNumberMode::Auto);
+        assert!(
+            auto.contains(
+                "export interface RoundState {\n  miners?: number | null;\n  per_square?: any[];\n  total_deployed?: number | null;\n}"
+            ),
+            "{auto}"
+        );
+
+        let strings = compile(BigNumberMode::String);
+        assert!(
+            strings.contains(
+                "export interface RoundState {\n  miners?: number | null;\n  per_square?: string[];\n  total_deployed?: string | null;\n}"
+            ),
+            "{strings}"
+        );
+        assert!(strings.contains("  total_deployed: z.string().nullable().optional(),"));
+        assert!(strings.contains("  miners: z.number().nullable().optional(),"));
+    }
+
+    #[test]
+    fn test_resolver_placeholder_widens_field_type() {
+        let spec = SerializableStreamSpec {
+            ast_version: CURRENT_AST_VERSION.to_string(),
+            state_name: "Token".to_string(),
+            program_id: None,
+            idl: None,
+            identity: IdentitySpec {
+                primary_keys: vec!["id.mint".to_string()],
+                lookup_indexes: vec![],
+            },
+            handlers: vec![],
+            sections: vec![EntitySection {
+                name: "info".to_string(),
+                fields: vec![FieldTypeInfo::new(
+                    "name".to_string(),
+                    "Option<String>".to_string(),
+                )],
+                is_nested_struct: false,
+                parent_field: None,
+                docs: None,
+                shared_type: None,
+            }],
+            field_mappings: BTreeMap::new(),
+            resolver_hooks: vec![],
+            resolver_specs: vec![ResolverSpec {
+                resolver: ResolverType::Token,
+                input_path: Some("id.mint".to_string()),
+                input_value: None,
+                strategy: ResolveStrategy::SetOnce,
+                extracts: vec![ResolverExtractSpec {
+                    target_path: "info.name".to_string(),
+                    source_path: Some("name".to_string()),
+                    transform: None,
+                    placeholder: Some("pending".to_string()),
+                }],
+                condition: None,
+                schedule_at: None,
+            }],
+            instruction_hooks: vec![],
+            computed_fields: vec![],
+            computed_field_specs: vec![],
+            content_hash: None,
+            views: vec![],
+            trace_fields: vec![],
+            feature: None,
+            priority: EntityPriority::Normal,
+            delete_on: None,
+            key_normalizer: None,
+            docs: None,
+            event_types: Vec::new(),
+        };
+
+        let output =
+            compile_serializable_spec(spec, "Token".to_string(), None).expect("should compile");
+        let interfaces = &output.interfaces;
+        assert!(
+            interfaces.contains("  name?: string | { status: \"pending\" } | null;"),
+            "{interfaces}"
+        );
+        assert!(interfaces.contains(
+            "  name: z.union([z.string(), z.object({ status: z.literal(\"pending\") })]).nullable().optional(),"
+        ));
+    }
+
+    #[test]
+    fn test_jsdoc_escapes_comment_terminators() {
+        let docs = ItemDocs {
+            description: Some("Ends early */ otherwise".to_string()),
+            unit: None,
+        };
+        assert_eq!(jsdoc(Some(&docs), ""), "/** Ends early *\\/ otherwise */\n");
+        assert_eq!(jsdoc(None, "  "), "");
+    }
 }
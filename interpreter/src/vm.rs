@@ -1,19 +1,34 @@
+use crate::array_aggregate;
 use crate::ast::{
-    self, BinaryOp, ComparisonOp, ComputedExpr, ComputedFieldSpec, FieldPath, ResolveStrategy,
-    ResolverExtractSpec, ResolverType, Transformation,
+    self, ArrayAggregateSpec, BinaryOp, ComparisonOp, ComputedExpr, ComputedFieldSpec,
+    DeleteTrigger, FieldPath, ResolveStrategy, ResolverExtractSpec, ResolverType, Transformation,
 };
-use crate::compiler::{MultiEntityBytecode, OpCode};
-use crate::Mutation;
+use crate::clock::{Clock, SharedClock, SystemClock};
+use crate::compiler::{IndexScope, MappingId, MultiEntityBytecode, OpCode};
+use crate::entity_size::{estimate_json_size, truncate_to_fit, EntitySize, EntitySizes};
+use crate::event_validation::{FieldIssue, ValidationCounts, ValidationMode, ValidationReport};
+use crate::unique_set::{ExportedUniqueSet, UniqueSetStore};
+pub use crate::vm_error::{HandlerError, KeyCollision, VmError};
+use crate::{FieldProvenance, Mutation};
+use base64::prelude::{Engine as _, BASE64_STANDARD};
 use dashmap::DashMap;
 use lru::LruCache;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
 use std::time::{Duration, Instant};
 
 #[cfg(feature = "otel")]
 use tracing::instrument;
+/// [`UpdateContext::metadata`] key of the program that emitted the update
+pub const PROGRAM_ID_METADATA: &str = "program_id";
+
+/// Format version of [`VmContext::export_state`], bumped when it changes
+pub const VM_STATE_VERSION: u64 = 2;
+
 /// Context metadata for blockchain updates (accounts and instructions)
 /// This structure is designed to be extended over time with additional metadata
 #[derive(Debug, Clone, Default)]
@@ -23,7 +38,8 @@ pub struct UpdateContext {
     /// Transaction signature
     pub signature: Option<String>,
     /// Unix timestamp (seconds since epoch)
-    /// If not provided, will default to current system time when accessed
+    /// If not provided, falls back to the cached block time for `slot`, and only
+    /// then to current system time when accessed
     pub timestamp: Option<i64>,
     /// Write version for account updates (monotonically increasing per account within a slot)
     /// Used for staleness detection to reject out-of-order updates
@@ -107,14 +123,25 @@ impl UpdateContext {
         }
     }
 
-    /// Get the timestamp, falling back to current system time if not set
+    /// Fill `timestamp` from the block time cache when it isn't already set
+    pub fn with_block_time(mut self) -> Self {
+        if self.timestamp.is_none() {
+            self.timestamp = self.slot.and_then(crate::block_time_cache::get_block_time);
+        }
+        self
+    }
+
+    /// Get the timestamp, preferring the explicit value, then the chain block time
+    /// for `slot`, and falling back to current system time (counted) if neither is known
     pub fn timestamp(&self) -> i64 {
-        self.timestamp.unwrap_or_else(|| {
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i64
-        })
+        self.known_timestamp()
+            .unwrap_or_else(crate::block_time_cache::wall_clock_fallback)
+    }
+
+    /// The explicit timestamp or the chain block time for `slot`, if either is known
+    fn known_timestamp(&self) -> Option<i64> {
+        self.timestamp
+            .or_else(|| self.slot.and_then(crate::block_time_cache::get_block_time))
     }
 
     /// Create an empty context (for testing or when context is not available)
@@ -143,6 +170,21 @@ impl UpdateContext {
         self.metadata.get(key)
     }
 
+    /// Record the address of the program that emitted the update, used to
+    /// route event types that several programs share
+    pub fn with_program_id(self, program_id: impl Into<String>) -> Self {
+        self.with_metadata(
+            PROGRAM_ID_METADATA.to_string(),
+            Value::String(program_id.into()),
+        )
+    }
+
+    /// Address of the program that emitted the update, if recorded
+    pub fn program_id(&self) -> Option<&str> {
+        self.get_metadata(PROGRAM_ID_METADATA)
+            .and_then(Value::as_str)
+    }
+
     /// Convert context to JSON value for injection into event data
     pub fn to_value(&self) -> Value {
         let mut obj = serde_json::Map::new();
@@ -176,6 +218,7 @@ pub trait ComputedFieldsEvaluator {
 const MAX_PENDING_UPDATES_TOTAL: usize = 2_500;
 const MAX_PENDING_UPDATES_PER_PDA: usize = 50;
 const PENDING_UPDATE_TTL_SECONDS: i64 = 300; // 5 minutes
+const MAX_PENDING_INSTRUCTION_EVENTS_TOTAL: usize = 2_500;
 
 // Temporal index configuration - prevents unbounded history growth
 const TEMPORAL_HISTORY_TTL_SECONDS: i64 = 300; // 5 minutes, matches pending queue TTL
@@ -185,6 +228,9 @@ const MAX_TEMPORAL_ENTRIES_PER_KEY: usize = 250;
 const DEFAULT_MAX_STATE_TABLE_ENTRIES: usize = 2_500;
 const DEFAULT_MAX_ARRAY_LENGTH: usize = 100;
 
+// Unique sets larger than this switch to an approximate (HyperLogLog) count
+const DEFAULT_UNIQUE_SET_EXACT_LIMIT: usize = 10_000;
+
 const DEFAULT_MAX_LOOKUP_INDEX_ENTRIES: usize = 2_500;
 
 const DEFAULT_MAX_VERSION_TRACKER_ENTRIES: usize = 2_500;
@@ -197,6 +243,13 @@ const DEFAULT_MAX_TEMPORAL_INDEX_KEYS: usize = 2_500;
 
 const DEFAULT_MAX_PDA_REVERSE_LOOKUP_ENTRIES: usize = 2_500;
 
+// Deleted entities remembered per state table, so late updates can't bring
+// them back
+const DEFAULT_MAX_TOMBSTONES: usize = 2_500;
+
+// Largest entities listed per state table in debug snapshots
+const DEBUG_LARGEST_ENTITIES: usize = 10;
+
 const DEFAULT_MAX_RESOLVER_CACHE_ENTRIES: usize = 20_000;
 const DEFAULT_RESOLVER_CACHE_TTL_SECS: u64 = 3600; // 1 hour
 
@@ -211,6 +264,31 @@ static RESOLVER_CACHE_CAPACITY: Lazy<NonZeroUsize> = Lazy::new(|| {
     .expect("resolver cache capacity must be > 0")
 });
 
+static MAX_ENTITY_BYTES: Lazy<Option<usize>> = Lazy::new(|| {
+    std::env::var("HYPERSTACK_MAX_ENTITY_BYTES")
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|value| *value > 0)
+});
+
+static STRICT_MODE: Lazy<bool> = Lazy::new(|| {
+    std::env::var("HYPERSTACK_STRICT_MODE")
+        .map(|value| matches!(value.as_str(), "1" | "true"))
+        .unwrap_or(false)
+});
+
+static EVENT_VALIDATION: Lazy<Option<ValidationMode>> = Lazy::new(|| {
+    std::env::var("HYPERSTACK_EVENT_VALIDATION")
+        .ok()
+        .and_then(|value| ValidationMode::parse(&value))
+});
+
+static PROVENANCE_MODE: Lazy<bool> = Lazy::new(|| {
+    std::env::var("HYPERSTACK_PROVENANCE")
+        .map(|value| matches!(value.as_str(), "1" | "true"))
+        .unwrap_or(false)
+});
+
 static RESOLVER_CACHE_TTL: Lazy<Duration> = Lazy::new(|| {
     let ttl_secs = match std::env::var("HYPERSTACK_RESOLVER_CACHE_TTL_SECS") {
         Ok(value) => match value.parse::<u64>() {
@@ -244,23 +322,6 @@ fn resolver_cache_ttl() -> Duration {
     *RESOLVER_CACHE_TTL
 }
 
-/// Estimate the size of a JSON value in bytes
-fn estimate_json_size(value: &Value) -> usize {
-    match value {
-        Value::Null => 4,
-        Value::Bool(_) => 5,
-        Value::Number(_) => 8,
-        Value::String(s) => s.len() + 2,
-        Value::Array(arr) => 2 + arr.iter().map(|v| estimate_json_size(v) + 1).sum::<usize>(),
-        Value::Object(obj) => {
-            2 + obj
-                .iter()
-                .map(|(k, v)| k.len() + 3 + estimate_json_size(v) + 1)
-                .sum::<usize>()
-        }
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct CompiledPath {
     pub segments: std::sync::Arc<[String]>,
@@ -279,6 +340,41 @@ impl CompiledPath {
     }
 }
 
+/// Paths compiled for one bytecode, shared by every `VmContext` running it.
+///
+/// Owned by [`MultiEntityBytecode`], so the set is bounded by the bytecode's
+/// paths and dropped with it on reload. Each VM keeps its own `path_cache` in
+/// front of it, so this is only consulted on a VM's first use of a path.
+#[derive(Debug, Default)]
+pub struct PathInterner {
+    paths: std::sync::Mutex<HashMap<Box<str>, CompiledPath>>,
+}
+
+impl PathInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compile `path` once; later calls share the same segments.
+    pub fn intern(&self, path: &str) -> CompiledPath {
+        let mut paths = self.paths.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(compiled) = paths.get(path) {
+            return compiled.clone();
+        }
+        let compiled = CompiledPath::new(path);
+        paths.insert(path.into(), compiled.clone());
+        compiled
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 /// Represents the type of change made to a field for granular dirty tracking.
 /// This enables emitting only the actual changes rather than entire field values.
 #[derive(Debug, Clone)]
@@ -291,16 +387,58 @@ pub enum FieldChange {
 
 /// Tracks field modifications during handler execution with granular change information.
 /// This replaces the simple HashSet<String> approach to enable delta-only emissions.
+///
+/// Changes are kept in path order, so the patch built from them and its
+/// append paths come out the same on every run.
 #[derive(Debug, Clone, Default)]
 pub struct DirtyTracker {
-    changes: HashMap<String, FieldChange>,
+    changes: BTreeMap<String, FieldChange>,
+    /// Per-path origin of the changes, when recording provenance
+    provenance: Option<BTreeMap<String, FieldProvenance>>,
+    /// The opcode currently executing, attributed to the paths it marks
+    origin: Option<(usize, Option<MappingId>)>,
 }
 
 impl DirtyTracker {
     /// Create a new empty DirtyTracker
     pub fn new() -> Self {
         Self {
-            changes: HashMap::new(),
+            changes: BTreeMap::new(),
+            provenance: None,
+            origin: None,
+        }
+    }
+
+    /// Create a tracker that also records which opcode changed each path
+    pub fn with_provenance() -> Self {
+        Self {
+            provenance: Some(BTreeMap::new()),
+            ..Self::new()
+        }
+    }
+
+    /// Attribute subsequent changes to the opcode at `opcode` in the handler.
+    /// Does nothing unless recording provenance.
+    pub fn set_origin(&mut self, opcode: usize, mapping: Option<&MappingId>) {
+        if self.provenance.is_some() {
+            self.origin = Some((opcode, mapping.cloned()));
+        }
+    }
+
+    /// Per-path origin of the changes, if recording provenance
+    pub fn provenance(&self) -> Option<&BTreeMap<String, FieldProvenance>> {
+        self.provenance.as_ref()
+    }
+
+    fn record_origin(&mut self, path: &str) {
+        if let (Some(provenance), Some((opcode, mapping))) = (&mut self.provenance, &self.origin) {
+            provenance.insert(
+                path.to_string(),
+                FieldProvenance {
+                    opcode: *opcode,
+                    mapping: mapping.as_deref().map(str::to_string),
+                },
+            );
         }
     }
 
@@ -308,10 +446,12 @@ impl DirtyTracker {
     pub fn mark_replaced(&mut self, path: &str) {
         // If there was an append, it's now superseded by a full replacement
         self.changes.insert(path.to_string(), FieldChange::Replaced);
+        self.record_origin(path);
     }
 
     /// Record an appended value for a field
     pub fn mark_appended(&mut self, path: &str, value: Value) {
+        self.record_origin(path);
         match self.changes.get_mut(path) {
             Some(FieldChange::Appended(values)) => {
                 // Add to existing appended values
@@ -339,7 +479,7 @@ impl DirtyTracker {
         self.changes.len()
     }
 
-    /// Iterate over all changes
+    /// Iterate over all changes, in path order
     pub fn iter(&self) -> impl Iterator<Item = (&String, &FieldChange)> {
         self.changes.iter()
     }
@@ -350,16 +490,16 @@ impl DirtyTracker {
     }
 
     /// Consume the tracker and return the changes map
-    pub fn into_changes(self) -> HashMap<String, FieldChange> {
+    pub fn into_changes(self) -> BTreeMap<String, FieldChange> {
         self.changes
     }
 
     /// Get a reference to the changes map
-    pub fn changes(&self) -> &HashMap<String, FieldChange> {
+    pub fn changes(&self) -> &BTreeMap<String, FieldChange> {
         &self.changes
     }
 
-    /// Get paths that were appended (not replaced)
+    /// Get paths that were appended (not replaced), in path order
     pub fn appended_paths(&self) -> Vec<String> {
         self.changes
             .iter()
@@ -374,12 +514,17 @@ impl DirtyTracker {
 pub struct VmContext {
     registers: Vec<RegisterValue>,
     states: HashMap<u32, StateTable>,
+    /// Module-level lookup indexes shared by every entity's handlers
+    shared_lookup_indexes: HashMap<String, LookupIndex>,
     pub instructions_executed: u64,
     pub cache_hits: u64,
     path_cache: HashMap<String, CompiledPath>,
+    /// Interner of the bytecode last run, behind `path_cache`
+    paths: std::sync::Arc<PathInterner>,
     pub pda_cache_hits: u64,
     pub pda_cache_misses: u64,
     pub pending_queue_size: u64,
+    pub pending_instruction_event_count: u64,
     resolver_requests: VecDeque<ResolverRequest>,
     resolver_pending: HashMap<String, PendingResolverEntry>,
     resolver_cache: LruCache<String, ResolverCacheEntry>,
@@ -392,6 +537,18 @@ pub struct VmContext {
     last_pda_registered: Option<String>,
     last_lookup_index_keys: Vec<String>,
     scheduled_callbacks: Vec<(u64, ScheduledCallback)>,
+    strict: bool,
+    /// Handlers and evaluators that panicked since the VM was created
+    handler_panics: u64,
+    /// Record mutation provenance for every entity
+    provenance: bool,
+    /// Entities recording mutation provenance regardless of `provenance`
+    provenance_entities: HashSet<String>,
+    /// Time source for TTLs and timestamp fallbacks, the system clock when unset
+    clock: Option<SharedClock>,
+    /// Check events against their schemas before running handlers
+    event_validation: Option<ValidationMode>,
+    validation_counts: HashMap<String, ValidationCounts>,
 }
 
 #[derive(Debug)]
@@ -417,9 +574,10 @@ impl LookupIndex {
         self.index.lock().unwrap().get(&key).cloned()
     }
 
-    pub fn insert(&self, lookup_value: Value, primary_key: Value) {
+    /// Map `lookup_value` to `primary_key`, returning the previous mapping.
+    pub fn insert(&self, lookup_value: Value, primary_key: Value) -> Option<Value> {
         let key = value_to_cache_key(&lookup_value);
-        self.index.lock().unwrap().put(key, primary_key);
+        self.index.lock().unwrap().put(key, primary_key)
     }
 
     /// Remove an entry by lookup value.  Used when a PDA mapping changes to
@@ -429,6 +587,16 @@ impl LookupIndex {
         self.index.lock().unwrap().pop(&key);
     }
 
+    /// Entries from least to most recently used
+    pub fn entries(&self) -> Vec<(String, Value)> {
+        let index = self.index.lock().unwrap();
+        index
+            .iter()
+            .rev()
+            .map(|(key, primary_key)| (key.clone(), primary_key.clone()))
+            .collect()
+    }
+
     pub fn len(&self) -> usize {
         self.index.lock().unwrap().len()
     }
@@ -438,6 +606,47 @@ impl LookupIndex {
     }
 }
 
+/// Format of [`VmContext::export_state`]
+#[derive(Serialize, Deserialize)]
+struct ExportedVmState {
+    version: u64,
+    states: BTreeMap<u32, ExportedStateTable>,
+    shared_lookup_indexes: BTreeMap<String, Vec<(String, Value)>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportedStateTable {
+    entity_name: String,
+    entities: Vec<(Value, Value)>,
+    lookup_indexes: BTreeMap<String, Vec<(String, Value)>>,
+    pda_reverse_lookups: BTreeMap<String, Vec<(String, String)>>,
+    unique_sets: Vec<ExportedUniqueSet>,
+}
+
+fn export_lookup_indexes(
+    indexes: &HashMap<String, LookupIndex>,
+) -> BTreeMap<String, Vec<(String, Value)>> {
+    indexes
+        .iter()
+        .map(|(name, index)| (name.clone(), index.entries()))
+        .collect()
+}
+
+/// Insert exported lookup index entries. Each entry was exported under its
+/// cache key, and a string's cache key is the string itself, so inserting the
+/// key as a string value files the entry under the same key again.
+fn import_lookup_indexes(
+    indexes: &mut HashMap<String, LookupIndex>,
+    exported: BTreeMap<String, Vec<(String, Value)>>,
+) {
+    for (name, entries) in exported {
+        let index = indexes.entry(name).or_default();
+        for (lookup_key, primary_key) in entries {
+            index.insert(Value::String(lookup_key), primary_key);
+        }
+    }
+}
+
 impl Default for LookupIndex {
     fn default() -> Self {
         Self::new()
@@ -602,6 +811,15 @@ impl PdaReverseLookup {
     pub fn contains(&self, pda_address: &str) -> bool {
         self.index.peek(pda_address).is_some()
     }
+
+    /// Entries from least to most recently used
+    pub fn entries(&self) -> Vec<(String, String)> {
+        self.index
+            .iter()
+            .rev()
+            .map(|(pda, seed)| (pda.clone(), seed.clone()))
+            .collect()
+    }
 }
 
 /// Input for queueing an account update.
@@ -729,20 +947,26 @@ impl PendingResolverEntry {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PendingQueueStats {
     pub total_updates: usize,
     pub unique_pdas: usize,
     pub oldest_age_seconds: i64,
     pub largest_pda_queue_size: usize,
     pub estimated_memory_bytes: usize,
+    /// Instruction events waiting for their PDA to be registered
+    pub total_instruction_events: usize,
+    pub instruction_event_pdas: usize,
+    pub oldest_instruction_event_age_seconds: i64,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct VmMemoryStats {
     pub state_table_entity_count: usize,
     pub state_table_max_entries: usize,
     pub state_table_at_capacity: bool,
+    pub state_table_entity_bytes: usize,
+    pub oversized_entity_truncations: u64,
     pub lookup_index_count: usize,
     pub lookup_index_total_entries: usize,
     pub temporal_index_count: usize,
@@ -751,13 +975,16 @@ pub struct VmMemoryStats {
     pub pda_reverse_lookup_total_entries: usize,
     pub version_tracker_entries: usize,
     pub pending_queue_stats: Option<PendingQueueStats>,
+    pub pending_instruction_events: usize,
     pub path_cache_size: usize,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct CleanupResult {
     pub pending_updates_removed: usize,
+    pub pending_instruction_events_removed: usize,
     pub temporal_entries_removed: usize,
+    pub entities_remeasured: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -771,6 +998,11 @@ pub struct CapacityWarning {
 pub struct StateTableConfig {
     pub max_entries: usize,
     pub max_array_length: usize,
+    pub unique_set_exact_limit: usize,
+    /// Estimated size an entity may reach before its largest arrays are
+    /// truncated, see [`entity_size`](crate::entity_size). Defaults to
+    /// `HYPERSTACK_MAX_ENTITY_BYTES`, unlimited when unset.
+    pub max_entity_bytes: Option<usize>,
 }
 
 impl Default for StateTableConfig {
@@ -778,6 +1010,8 @@ impl Default for StateTableConfig {
         Self {
             max_entries: DEFAULT_MAX_STATE_TABLE_ENTRIES,
             max_array_length: DEFAULT_MAX_ARRAY_LENGTH,
+            unique_set_exact_limit: DEFAULT_UNIQUE_SET_EXACT_LIMIT,
+            max_entity_bytes: *MAX_ENTITY_BYTES,
         }
     }
 }
@@ -845,14 +1079,53 @@ pub struct StateTable {
     version_tracker: VersionTracker,
     instruction_dedup_cache: VersionTracker,
     config: StateTableConfig,
-    #[cfg_attr(not(feature = "otel"), allow(dead_code))]
     entity_name: String,
     pub recent_tx_instructions:
         std::sync::Mutex<lru::LruCache<String, std::collections::HashSet<String>>>,
     pub deferred_when_ops: DashMap<(String, String), Vec<DeferredWhenOperation>>,
+    /// Backing sets for `UniqueCount` fields, kept out of entity state
+    pub unique_sets: UniqueSetStore,
+    /// Estimated size of each entity in `data`
+    pub entity_sizes: EntitySizes,
+    /// Key of the entity each account last updated, to find the entity to
+    /// delete when the account closes
+    account_keys: std::sync::Mutex<LruCache<String, Value>>,
+    /// Slot each deleted entity was deleted at
+    tombstones: std::sync::Mutex<LruCache<Value, u64>>,
 }
 
 impl StateTable {
+    pub fn new(entity_name: String, config: StateTableConfig) -> Self {
+        StateTable {
+            data: DashMap::new(),
+            access_times: DashMap::new(),
+            lookup_indexes: HashMap::new(),
+            temporal_indexes: HashMap::new(),
+            pda_reverse_lookups: HashMap::new(),
+            pending_updates: DashMap::new(),
+            pending_instruction_events: DashMap::new(),
+            last_account_data: DashMap::new(),
+            version_tracker: VersionTracker::new(),
+            instruction_dedup_cache: VersionTracker::with_capacity(
+                DEFAULT_MAX_INSTRUCTION_DEDUP_ENTRIES,
+            ),
+            unique_sets: UniqueSetStore::new(config.unique_set_exact_limit),
+            config,
+            entity_name,
+            recent_tx_instructions: std::sync::Mutex::new(LruCache::new(
+                NonZeroUsize::new(1000).unwrap(),
+            )),
+            deferred_when_ops: DashMap::new(),
+            entity_sizes: EntitySizes::new(),
+            account_keys: std::sync::Mutex::new(LruCache::new(
+                NonZeroUsize::new(DEFAULT_MAX_VERSION_TRACKER_ENTRIES).unwrap(),
+            )),
+            tombstones: std::sync::Mutex::new(LruCache::new(
+                NonZeroUsize::new(DEFAULT_MAX_TOMBSTONES).unwrap(),
+            )),
+        }
+    }
+
     pub fn is_at_capacity(&self) -> bool {
         self.data.len() >= self.config.max_entries
     }
@@ -892,6 +1165,8 @@ impl StateTable {
         for key in to_evict {
             self.data.remove(&key);
             self.access_times.remove(&key);
+            self.unique_sets.remove_entity(&key);
+            self.entity_sizes.remove(&key);
             evicted += 1;
         }
 
@@ -903,17 +1178,52 @@ impl StateTable {
         evicted
     }
 
-    pub fn insert_with_eviction(&self, key: Value, value: Value) {
+    pub fn insert_with_eviction(&self, key: Value, mut value: Value) {
         if self.data.len() >= self.config.max_entries && !self.data.contains_key(&key) {
             #[cfg(feature = "otel")]
             crate::vm_metrics::record_state_table_at_capacity(&self.entity_name);
             let to_evict = (self.data.len() + 1).saturating_sub(self.config.max_entries);
             self.evict_lru(to_evict.max(1));
         }
+        let bytes = self.fit_entity(&key, &mut value);
         self.data.insert(key.clone(), value);
+        self.entity_sizes.record(&key, bytes);
         self.touch(&key);
     }
 
+    /// Estimate `value`'s size, truncating it to the entity byte cap first
+    fn fit_entity(&self, key: &Value, value: &mut Value) -> usize {
+        let bytes = estimate_json_size(value);
+        let Some(max_bytes) = self.config.max_entity_bytes else {
+            return bytes;
+        };
+        if bytes <= max_bytes {
+            return bytes;
+        }
+
+        let (fitted, truncations) = truncate_to_fit(value, bytes, max_bytes);
+        self.entity_sizes.note_truncated();
+        let arrays: Vec<String> = truncations
+            .iter()
+            .map(|t| format!("{} (-{})", t.path, t.removed))
+            .collect();
+        tracing::warn!(
+            entity = %self.entity_name,
+            key = %key,
+            bytes,
+            max_bytes,
+            fitted,
+            truncated = %arrays.join(", "),
+            "Entity exceeded the byte cap, truncated its largest arrays"
+        );
+        fitted
+    }
+
+    /// The `k` largest entities by estimated size, largest first
+    pub fn largest_entities(&self, k: usize) -> Vec<EntitySize> {
+        self.entity_sizes.largest(k)
+    }
+
     pub fn get_and_touch(&self, key: &Value) -> Option<Value> {
         let result = self.data.get(key).map(|v| v.clone());
         if result.is_some() {
@@ -950,6 +1260,40 @@ impl StateTable {
         true
     }
 
+    fn record_account_key(&self, account_address: &str, key: &Value) {
+        self.account_keys
+            .lock()
+            .unwrap()
+            .put(account_address.to_string(), key.clone());
+    }
+
+    /// Delete the entity `account_address` last updated, leaving a tombstone
+    /// at `slot`. Returns the entity's key, or `None` if the account never
+    /// updated one.
+    pub fn close_account(&self, account_address: &str, slot: u64) -> Option<Value> {
+        let key = self.account_keys.lock().unwrap().pop(account_address)?;
+
+        self.data.remove(&key);
+        self.access_times.remove(&key);
+        self.unique_sets.remove_entity(&key);
+        self.entity_sizes.remove(&key);
+        self.last_account_data.remove(account_address);
+
+        let mut tombstones = self.tombstones.lock().unwrap();
+        let deleted_at = tombstones.get(&key).copied().unwrap_or(0).max(slot);
+        tombstones.put(key.clone(), deleted_at);
+        Some(key)
+    }
+
+    /// Whether the entity under `key` was deleted at `slot` or later
+    pub fn is_tombstoned(&self, key: &Value, slot: u64) -> bool {
+        self.tombstones
+            .lock()
+            .unwrap()
+            .peek(key)
+            .is_some_and(|deleted_at| slot <= *deleted_at)
+    }
+
     /// Check if an instruction is a duplicate of one we've seen recently.
     /// Returns true if this exact instruction has been seen before (is a duplicate).
     /// Returns false if this is a new instruction that should be processed.
@@ -987,12 +1331,15 @@ impl VmContext {
         let mut vm = VmContext {
             registers: vec![Value::Null; 256],
             states: HashMap::new(),
+            shared_lookup_indexes: HashMap::new(),
             instructions_executed: 0,
             cache_hits: 0,
             path_cache: HashMap::new(),
+            paths: std::sync::Arc::new(PathInterner::new()),
             pda_cache_hits: 0,
             pda_cache_misses: 0,
             pending_queue_size: 0,
+            pending_instruction_event_count: 0,
             resolver_requests: VecDeque::new(),
             resolver_pending: HashMap::new(),
             resolver_cache: LruCache::new(resolver_cache_capacity()),
@@ -1005,29 +1352,17 @@ impl VmContext {
             last_pda_registered: None,
             last_lookup_index_keys: Vec::new(),
             scheduled_callbacks: Vec::new(),
+            strict: *STRICT_MODE,
+            handler_panics: 0,
+            provenance: *PROVENANCE_MODE,
+            provenance_entities: HashSet::new(),
+            clock: None,
+            event_validation: *EVENT_VALIDATION,
+            validation_counts: HashMap::new(),
         };
         vm.states.insert(
             0,
-            StateTable {
-                data: DashMap::new(),
-                access_times: DashMap::new(),
-                lookup_indexes: HashMap::new(),
-                temporal_indexes: HashMap::new(),
-                pda_reverse_lookups: HashMap::new(),
-                pending_updates: DashMap::new(),
-                pending_instruction_events: DashMap::new(),
-                last_account_data: DashMap::new(),
-                version_tracker: VersionTracker::new(),
-                instruction_dedup_cache: VersionTracker::with_capacity(
-                    DEFAULT_MAX_INSTRUCTION_DEDUP_ENTRIES,
-                ),
-                config: StateTableConfig::default(),
-                entity_name: String::new(),
-                recent_tx_instructions: std::sync::Mutex::new(LruCache::new(
-                    NonZeroUsize::new(1000).unwrap(),
-                )),
-                deferred_when_ops: DashMap::new(),
-            },
+            StateTable::new(String::new(), StateTableConfig::default()),
         );
 
         vm
@@ -1038,12 +1373,15 @@ impl VmContext {
         VmContext {
             registers: vec![Value::Null; 256],
             states: HashMap::new(),
+            shared_lookup_indexes: HashMap::new(),
             instructions_executed: 0,
             cache_hits: 0,
             path_cache: HashMap::new(),
+            paths: std::sync::Arc::new(PathInterner::new()),
             pda_cache_hits: 0,
             pda_cache_misses: 0,
             pending_queue_size: 0,
+            pending_instruction_event_count: 0,
             resolver_requests: VecDeque::new(),
             resolver_pending: HashMap::new(),
             resolver_cache: LruCache::new(resolver_cache_capacity()),
@@ -1056,6 +1394,13 @@ impl VmContext {
             last_pda_registered: None,
             last_lookup_index_keys: Vec::new(),
             scheduled_callbacks: Vec::new(),
+            strict: *STRICT_MODE,
+            handler_panics: 0,
+            provenance: *PROVENANCE_MODE,
+            provenance_entities: HashSet::new(),
+            clock: None,
+            event_validation: *EVENT_VALIDATION,
+            validation_counts: HashMap::new(),
         }
     }
 
@@ -1063,12 +1408,15 @@ impl VmContext {
         let mut vm = VmContext {
             registers: vec![Value::Null; 256],
             states: HashMap::new(),
+            shared_lookup_indexes: HashMap::new(),
             instructions_executed: 0,
             cache_hits: 0,
             path_cache: HashMap::new(),
+            paths: std::sync::Arc::new(PathInterner::new()),
             pda_cache_hits: 0,
             pda_cache_misses: 0,
             pending_queue_size: 0,
+            pending_instruction_event_count: 0,
             resolver_requests: VecDeque::new(),
             resolver_pending: HashMap::new(),
             resolver_cache: LruCache::new(resolver_cache_capacity()),
@@ -1081,30 +1429,16 @@ impl VmContext {
             last_pda_registered: None,
             last_lookup_index_keys: Vec::new(),
             scheduled_callbacks: Vec::new(),
+            strict: *STRICT_MODE,
+            handler_panics: 0,
+            provenance: *PROVENANCE_MODE,
+            provenance_entities: HashSet::new(),
+            clock: None,
+            event_validation: *EVENT_VALIDATION,
+            validation_counts: HashMap::new(),
         };
-        vm.states.insert(
-            0,
-            StateTable {
-                data: DashMap::new(),
-                access_times: DashMap::new(),
-                lookup_indexes: HashMap::new(),
-                temporal_indexes: HashMap::new(),
-                pda_reverse_lookups: HashMap::new(),
-                pending_updates: DashMap::new(),
-                pending_instruction_events: DashMap::new(),
-                last_account_data: DashMap::new(),
-                version_tracker: VersionTracker::new(),
-                instruction_dedup_cache: VersionTracker::with_capacity(
-                    DEFAULT_MAX_INSTRUCTION_DEDUP_ENTRIES,
-                ),
-                config: state_config,
-                entity_name: "default".to_string(),
-                recent_tx_instructions: std::sync::Mutex::new(LruCache::new(
-                    NonZeroUsize::new(1000).unwrap(),
-                )),
-                deferred_when_ops: DashMap::new(),
-            },
-        );
+        vm.states
+            .insert(0, StateTable::new("default".to_string(), state_config));
         vm
     }
 
@@ -1132,7 +1466,13 @@ impl VmContext {
         let cached = self.resolver_cache.get(cache_key).cloned();
 
         match cached {
-            Some(entry) if entry.cached_at.elapsed() <= resolver_cache_ttl() => {
+            Some(entry)
+                if self
+                    .clock()
+                    .now_instant()
+                    .saturating_duration_since(entry.cached_at)
+                    <= resolver_cache_ttl() =>
+            {
                 self.resolver_cache_hits += 1;
                 Some(entry.value)
             }
@@ -1158,7 +1498,7 @@ impl VmContext {
             resolver_cache_key(resolver, input),
             ResolverCacheEntry {
                 value: resolved_value.clone(),
-                cached_at: Instant::now(),
+                cached_at: self.clock().now_instant(),
             },
         );
     }
@@ -1169,6 +1509,7 @@ impl VmContext {
         cache_key: &str,
         resolved_value: Value,
     ) -> Result<Vec<Mutation>> {
+        self.use_paths_of(bytecode);
         let entry = match self.resolver_pending.remove(cache_key) {
             Some(entry) => entry,
             None => return Ok(Vec::new()),
@@ -1216,17 +1557,19 @@ impl VmContext {
                     .collect();
 
                 let context_slot = self.current_context.as_ref().and_then(|c| c.slot);
-                let context_timestamp = self
-                    .current_context
-                    .as_ref()
-                    .map(|c| c.timestamp())
-                    .unwrap_or_else(|| {
-                        std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs() as i64
-                    });
-                let eval_result = evaluator(&mut entity_state, context_slot, context_timestamp);
+                let context_timestamp = self.context_timestamp();
+                let eval_result = self.catch_panic(&target.entity_name, "resolver_result", |_| {
+                    evaluator(&mut entity_state, context_slot, context_timestamp)
+                });
+                if let Err(e) = &eval_result {
+                    if matches!(
+                        e.downcast_ref::<VmError>(),
+                        Some(VmError::HandlerPanicked { .. })
+                    ) {
+                        tracing::error!(entity = %target.entity_name, "{}", e);
+                        continue;
+                    }
+                }
 
                 if eval_result.is_ok() {
                     let mut changed_fields = Vec::new();
@@ -1245,20 +1588,22 @@ impl VmContext {
                 }
             }
 
-            state.insert_with_eviction(target.primary_key.clone(), entity_state.clone());
+            if let Some(state) = self.states.get(&target.state_id) {
+                state.insert_with_eviction(target.primary_key.clone(), entity_state.clone());
+            }
 
             if dirty_tracker.is_empty() {
                 continue;
             }
 
-            let patch = Self::build_partial_state_from_value(&entity_state, &dirty_tracker)?;
+            let mut patch = Self::build_partial_state_from_value(&entity_state, &dirty_tracker)?;
+            crate::unique_set::strip_legacy_fields(&mut patch);
 
-            mutations.push(Mutation {
-                export: target.entity_name.clone(),
-                key: target.primary_key.clone(),
+            mutations.push(Mutation::new(
+                target.entity_name.clone(),
+                target.primary_key.clone(),
                 patch,
-                append: vec![],
-            });
+            ));
         }
 
         Ok(mutations)
@@ -1278,10 +1623,7 @@ impl VmContext {
             return;
         }
 
-        let queued_at = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        let queued_at = self.clock().unix_secs();
 
         self.resolver_pending.insert(
             cache_key.clone(),
@@ -1335,6 +1677,35 @@ impl VmContext {
         Ok(())
     }
 
+    /// Write the placeholder of each extract whose target is still unset, so
+    /// subscribers see the field as pending until the resolver returns
+    fn mark_resolver_pending<F>(
+        state: &mut Value,
+        extracts: &[ResolverExtractSpec],
+        dirty_tracker: &mut DirtyTracker,
+        should_emit: &F,
+    ) -> Result<()>
+    where
+        F: Fn(&str) -> bool,
+    {
+        for extract in extracts {
+            let Some(pending) = extract.pending_value() else {
+                continue;
+            };
+            let current = Self::get_value_at_path(state, &extract.target_path);
+            if !matches!(current, None | Some(Value::Null)) {
+                continue;
+            }
+
+            Self::set_nested_field_value(state, &extract.target_path, pending)?;
+            if should_emit(&extract.target_path) {
+                dirty_tracker.mark_replaced(&extract.target_path);
+            }
+        }
+
+        Ok(())
+    }
+
     fn build_partial_state_from_value(state: &Value, tracker: &DirtyTracker) -> Result<Value> {
         if tracker.is_empty() {
             return Ok(json!({}));
@@ -1413,10 +1784,208 @@ impl VmContext {
         self.current_context = context;
     }
 
+    /// Timestamp for the event being processed: the context's chain time when
+    /// available, otherwise the server wall clock (counted as a fallback)
+    fn context_timestamp(&self) -> i64 {
+        self.current_context
+            .as_ref()
+            .and_then(UpdateContext::known_timestamp)
+            .unwrap_or_else(|| crate::block_time_cache::wall_clock_fallback_from(self.clock()))
+    }
+
     fn add_warning(&mut self, msg: String) {
         self.warnings.push(msg);
     }
 
+    /// Fail handlers on [`VmError`]s instead of recording them as warnings.
+    ///
+    /// Defaults to the `HYPERSTACK_STRICT_MODE` environment variable.
+    pub fn set_strict_mode(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn is_strict_mode(&self) -> bool {
+        self.strict
+    }
+
+    /// Record which opcode and mapping wrote each field of every mutation,
+    /// in [`Mutation::provenance`].
+    ///
+    /// Defaults to the `HYPERSTACK_PROVENANCE` environment variable.
+    pub fn set_provenance_mode(&mut self, enabled: bool) {
+        self.provenance = enabled;
+    }
+
+    /// Record provenance for one entity's mutations only
+    pub fn set_entity_provenance(&mut self, entity_name: &str, enabled: bool) {
+        if enabled {
+            self.provenance_entities.insert(entity_name.to_string());
+        } else {
+            self.provenance_entities.remove(entity_name);
+        }
+    }
+
+    pub fn records_provenance(&self, entity_name: &str) -> bool {
+        self.provenance || self.provenance_entities.contains(entity_name)
+    }
+
+    /// Read the time from `clock` instead of the system clock.
+    ///
+    /// Applies to TTL expiry, queue timestamps and the wall clock fallback for
+    /// events without a timestamp.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = Some(clock);
+    }
+
+    /// Check events against the schemas compiled from their handlers before
+    /// running them, see [`event_validation`](crate::event_validation).
+    ///
+    /// Defaults to the `HYPERSTACK_EVENT_VALIDATION` environment variable.
+    pub fn set_event_validation(&mut self, mode: Option<ValidationMode>) {
+        self.event_validation = mode;
+    }
+
+    pub fn event_validation(&self) -> Option<ValidationMode> {
+        self.event_validation
+    }
+
+    /// Validation outcomes by event type, for event types with a schema
+    pub fn validation_counts(&self) -> &HashMap<String, ValidationCounts> {
+        &self.validation_counts
+    }
+
+    /// Check `event_value` against the schemas of the entities handling it.
+    /// Fails in strict mode, otherwise warns with the report on the event's
+    /// canonical log, or through `tracing` without one.
+    fn validate_event(
+        &mut self,
+        bytecode: &MultiEntityBytecode,
+        entity_names: &[&str],
+        event_value: &Value,
+        event_type: &str,
+        mode: ValidationMode,
+        log: Option<&mut crate::canonical_log::CanonicalLog>,
+    ) -> Result<()> {
+        let mut schemas = entity_names
+            .iter()
+            .filter_map(|name| bytecode.entities.get(*name)?.event_schemas.get(event_type))
+            .peekable();
+        if schemas.peek().is_none() {
+            return Ok(());
+        }
+        let mut issues: Vec<FieldIssue> = Vec::new();
+        for schema in schemas {
+            for issue in schema.validate(event_value) {
+                if !issues.iter().any(|seen| seen.path() == issue.path()) {
+                    issues.push(issue);
+                }
+            }
+        }
+
+        let counts = self
+            .validation_counts
+            .entry(event_type.to_string())
+            .or_default();
+        counts.checked += 1;
+        if issues.is_empty() {
+            return Ok(());
+        }
+        counts.failed += 1;
+        let report = ValidationReport {
+            event_type: event_type.to_string(),
+            issues,
+        };
+        if mode == ValidationMode::Strict {
+            counts.rejected += 1;
+            return Err(VmError::InvalidEvent(report).into());
+        }
+
+        match log {
+            Some(log) => {
+                log.set(
+                    "invalid_fields",
+                    Value::Array(
+                        report
+                            .issues
+                            .iter()
+                            .map(|issue| Value::String(issue.path().to_string()))
+                            .collect(),
+                    ),
+                );
+                self.add_warning(report.to_string());
+            }
+            None => tracing::warn!(
+                event_type = %event_type,
+                issues = report.issues.len(),
+                "{}",
+                report
+            ),
+        }
+        Ok(())
+    }
+
+    /// The clock set with [`set_clock`](Self::set_clock), or the system clock
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_deref().unwrap_or(&SystemClock)
+    }
+
+    /// Warn and continue, or in strict mode fail with `error`.
+    #[inline]
+    fn report(&mut self, error: VmError) -> Result<()> {
+        if self.strict {
+            return Err(error.into());
+        }
+        self.add_warning(error.to_string());
+        Ok(())
+    }
+
+    /// Run user-provided handler or evaluator code for `entity`, turning a
+    /// panic into [`VmError::HandlerPanicked`].
+    ///
+    /// Without this boundary a panic unwinds through the caller's lock on the
+    /// VM and leaves registers half-written for the next event.
+    fn catch_panic<T>(
+        &mut self,
+        entity_name: &str,
+        event_type: &str,
+        f: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        match panic::catch_unwind(AssertUnwindSafe(|| f(self))) {
+            Ok(result) => result,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "non-string panic payload".to_string());
+                self.recover_from_panic(entity_name, event_type);
+                Err(VmError::HandlerPanicked {
+                    entity: entity_name.to_string(),
+                    event_type: event_type.to_string(),
+                    message,
+                }
+                .into())
+            }
+        }
+    }
+
+    /// Drop everything the panicked run left behind. Its dirty tracker was
+    /// local to the run, so none of its changes reach a mutation.
+    fn recover_from_panic(&mut self, entity_name: &str, event_type: &str) {
+        self.reset_registers();
+        self.last_pda_lookup_miss = None;
+        self.last_lookup_index_miss = None;
+        self.last_pda_registered = None;
+        self.last_lookup_index_keys.clear();
+        self.handler_panics += 1;
+        crate::vm_metrics::record_handler_panic(entity_name, event_type);
+    }
+
+    /// Handlers and evaluators that panicked since the VM was created
+    pub fn handler_panics(&self) -> u64 {
+        self.handler_panics
+    }
+
     pub fn take_warnings(&mut self) -> Vec<String> {
         std::mem::take(&mut self.warnings)
     }
@@ -1457,7 +2026,11 @@ impl VmContext {
 
         let mut partial = serde_json::Map::new();
 
-        for path in dirty_fields {
+        // Build in path order so the patch doesn't depend on the set's hash order
+        let mut paths: Vec<&String> = dirty_fields.iter().collect();
+        paths.sort_unstable();
+
+        for path in paths {
             let segments: Vec<&str> = path.split('.').collect();
 
             let mut current = full_state;
@@ -1557,6 +2130,15 @@ impl VmContext {
         Ok(Value::Object(partial))
     }
 
+    /// Compile paths through `bytecode`'s interner. Switching to another
+    /// bytecode, e.g. after a reload, drops the paths cached for the old one.
+    fn use_paths_of(&mut self, bytecode: &MultiEntityBytecode) {
+        if !std::sync::Arc::ptr_eq(&self.paths, &bytecode.paths) {
+            self.paths = bytecode.paths.clone();
+            self.path_cache.clear();
+        }
+    }
+
     fn get_compiled_path(&mut self, path: &str) -> CompiledPath {
         if let Some(compiled) = self.path_cache.get(path) {
             self.cache_hits += 1;
@@ -1566,7 +2148,7 @@ impl VmContext {
         }
         #[cfg(feature = "otel")]
         crate::vm_metrics::record_path_cache_miss();
-        let compiled = CompiledPath::new(path);
+        let compiled = self.paths.intern(path);
         self.path_cache.insert(path.to_string(), compiled.clone());
         compiled
     }
@@ -1589,6 +2171,19 @@ impl VmContext {
         context: Option<&UpdateContext>,
         mut log: Option<&mut crate::canonical_log::CanonicalLog>,
     ) -> Result<Vec<Mutation>> {
+        self.use_paths_of(bytecode);
+        let entity_names =
+            bytecode.route(event_type, context.and_then(UpdateContext::program_id))?;
+        if let Some(mode) = self.event_validation {
+            self.validate_event(
+                bytecode,
+                &entity_names,
+                &event_value,
+                event_type,
+                mode,
+                log.as_deref_mut(),
+            )?;
+        }
         self.current_context = context.cloned();
 
         let mut event_value = event_value;
@@ -1654,12 +2249,12 @@ impl VmContext {
             }
         }
 
-        if let Some(entity_names) = bytecode.event_routing.get(event_type) {
+        if !entity_names.is_empty() {
             for entity_name in entity_names {
                 if let Some(entity_bytecode) = bytecode.entities.get(entity_name) {
                     if let Some(handler) = entity_bytecode.handlers.get(event_type) {
                         if let Some(ref mut log) = log {
-                            log.set("entity", entity_name.clone());
+                            log.set("entity", entity_name);
                             log.inc("handlers", 1);
                         }
 
@@ -1944,6 +2539,8 @@ impl VmContext {
             }
         }
 
+        crate::vm_trace::record_trace_fields(bytecode, &all_mutations);
+
         Ok(all_mutations)
     }
 
@@ -1977,13 +2574,43 @@ impl VmContext {
             &Box<dyn Fn(&mut Value, Option<u64>, i64) -> Result<()> + Send + Sync>,
         >,
         non_emitted_fields: Option<&HashSet<String>>,
+    ) -> Result<Vec<Mutation>> {
+        self.catch_panic(entity_name, event_type, |vm| {
+            vm.run_handler(
+                handler,
+                event_value,
+                event_type,
+                override_state_id,
+                entity_name,
+                entity_evaluator,
+                non_emitted_fields,
+            )
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::too_many_arguments)]
+    fn run_handler(
+        &mut self,
+        handler: &[OpCode],
+        event_value: &Value,
+        event_type: &str,
+        override_state_id: u32,
+        entity_name: &str,
+        entity_evaluator: Option<
+            &Box<dyn Fn(&mut Value, Option<u64>, i64) -> Result<()> + Send + Sync>,
+        >,
+        non_emitted_fields: Option<&HashSet<String>>,
     ) -> Result<Vec<Mutation>> {
         self.reset_registers();
         self.last_pda_lookup_miss = None;
 
         let mut pc: usize = 0;
         let mut output = Vec::new();
-        let mut dirty_tracker = DirtyTracker::new();
+        let mut dirty_tracker = if self.records_provenance(entity_name) {
+            DirtyTracker::with_provenance()
+        } else {
+            DirtyTracker::new()
+        };
         let should_emit = |path: &str| {
             non_emitted_fields
                 .map(|fields| !fields.contains(path))
@@ -1991,6 +2618,7 @@ impl VmContext {
         };
 
         while pc < handler.len() {
+            dirty_tracker.set_origin(pc, handler[pc].mapping());
             match &handler[pc] {
                 OpCode::LoadEventField {
                     path,
@@ -2027,6 +2655,7 @@ impl VmContext {
                     object,
                     path,
                     value,
+                    ..
                 } => {
                     self.set_field_auto_vivify(*object, path, *value)?;
                     if should_emit(path) {
@@ -2064,6 +2693,22 @@ impl VmContext {
 
                     pc += 1;
                 }
+                OpCode::NormalizeKey { key, normalizer } => {
+                    if !self.registers[*key].is_null() {
+                        let raw_key = std::mem::take(&mut self.registers[*key]);
+                        let normalized = self
+                            .computed_evaluator()
+                            .normalize_key(normalizer, raw_key.clone())
+                            .map_err(|error| {
+                                format!(
+                                    "Failed to normalize {} key {}: {}",
+                                    entity_name, raw_key, error
+                                )
+                            })?;
+                        self.registers[*key] = normalized;
+                    }
+                    pc += 1;
+                }
                 OpCode::ReadOrInitState {
                     state_id: _,
                     key,
@@ -2071,29 +2716,13 @@ impl VmContext {
                     dest,
                 } => {
                     let actual_state_id = override_state_id;
-                    let entity_name_owned = entity_name.to_string();
-                    self.states
-                        .entry(actual_state_id)
-                        .or_insert_with(|| StateTable {
-                            data: DashMap::new(),
-                            access_times: DashMap::new(),
-                            lookup_indexes: HashMap::new(),
-                            temporal_indexes: HashMap::new(),
-                            pda_reverse_lookups: HashMap::new(),
-                            pending_updates: DashMap::new(),
-                            pending_instruction_events: DashMap::new(),
-                            last_account_data: DashMap::new(),
-                            version_tracker: VersionTracker::new(),
-                            instruction_dedup_cache: VersionTracker::with_capacity(
-                                DEFAULT_MAX_INSTRUCTION_DEDUP_ENTRIES,
-                            ),
-                            config: StateTableConfig::default(),
-                            entity_name: entity_name_owned,
-                            recent_tx_instructions: std::sync::Mutex::new(LruCache::new(
-                                NonZeroUsize::new(1000).unwrap(),
-                            )),
-                            deferred_when_ops: DashMap::new(),
-                        });
+                    let state = self.states.entry(actual_state_id).or_insert_with(|| {
+                        StateTable::new(String::new(), StateTableConfig::default())
+                    });
+                    // The default table is created before its entity is known
+                    if state.entity_name.is_empty() {
+                        state.entity_name = entity_name.to_string();
+                    }
                     let key_value = self.registers[*key].clone();
                     // Warn if key is null for account state events (not instruction events or CPI events)
                     let warn_null_key = key_value.is_null()
@@ -2102,10 +2731,11 @@ impl VmContext {
                         && !event_type.ends_with("CpiEvent");
 
                     if warn_null_key {
-                        self.add_warning(format!(
-                            "ReadOrInitState: key register {} is NULL for account state, event_type={}",
-                            key, event_type
-                        ));
+                        self.report(VmError::NullKey {
+                            entity: entity_name.to_string(),
+                            event_type: event_type.to_string(),
+                            register: *key,
+                        })?;
                     }
 
                     let state = self
@@ -2141,6 +2771,9 @@ impl VmContext {
                                         ));
                                         return Ok(Vec::new());
                                     }
+                                    if let Some(address) = account_address.as_str() {
+                                        state.record_account_key(address, &key_value);
+                                    }
                                 }
                             }
                             // Instruction updates: process all, but skip exact duplicates
@@ -2157,6 +2790,17 @@ impl VmContext {
                                     }
                                 }
                             }
+
+                            // A late update must not bring a deleted entity back
+                            if let Some(slot) = ctx.slot {
+                                if state.is_tombstoned(&key_value, slot) {
+                                    self.add_warning(format!(
+                                        "Update for deleted entity skipped: slot={}, key={}",
+                                        slot, key_value
+                                    ));
+                                    return Ok(Vec::new());
+                                }
+                            }
                         }
                     }
                     let value = state
@@ -2186,6 +2830,8 @@ impl VmContext {
                     object,
                     path,
                     value,
+                    aggregates,
+                    ..
                 } => {
                     let appended_value = self.registers[*value].clone();
                     let max_len = self
@@ -2193,35 +2839,31 @@ impl VmContext {
                         .get(&override_state_id)
                         .map(|s| s.max_array_length())
                         .unwrap_or(DEFAULT_MAX_ARRAY_LENGTH);
-                    self.append_to_array(*object, path, *value, max_len)?;
+                    self.append_to_array(*object, path, *value, max_len, aggregates)?;
                     if should_emit(path) {
                         dirty_tracker.mark_appended(path, appended_value);
                     }
                     pc += 1;
                 }
                 OpCode::GetCurrentTimestamp { dest } => {
-                    let timestamp = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs() as i64;
+                    let timestamp = self.context_timestamp();
                     self.registers[*dest] = json!(timestamp);
                     pc += 1;
                 }
                 OpCode::CreateEvent { dest, event_value } => {
-                    let timestamp = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs() as i64;
+                    let timestamp = self.context_timestamp();
 
-                    // Filter out __update_context from the event data
+                    // Filter out __update_context and __transaction from the event data
                     let mut event_data = self.registers[*event_value].clone();
                     if let Some(obj) = event_data.as_object_mut() {
                         obj.remove("__update_context");
+                        obj.remove("__transaction");
                     }
 
-                    // Create event with timestamp, data, and optional slot/signature from context
+                    // Create event with timestamp, type, data, and optional slot/signature from context
                     let mut event = serde_json::Map::new();
                     event.insert("timestamp".to_string(), json!(timestamp));
+                    event.insert("type".to_string(), json!(event_type));
                     event.insert("data".to_string(), event_data);
 
                     // Add slot and signature if available from current context
@@ -2241,10 +2883,7 @@ impl VmContext {
                     dest,
                     capture_value,
                 } => {
-                    let timestamp = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs() as i64;
+                    let timestamp = self.context_timestamp();
 
                     // Get the capture data (already filtered by load_field)
                     let capture_data = self.registers[*capture_value].clone();
@@ -2296,28 +2935,27 @@ impl VmContext {
                 } => {
                     let primary_key = self.registers[*key].clone();
 
-                    if primary_key.is_null() || dirty_tracker.is_empty() {
-                        let reason = if dirty_tracker.is_empty() {
-                            "no_fields_modified"
-                        } else {
-                            "null_primary_key"
-                        };
+                    if dirty_tracker.is_empty() {
                         self.add_warning(format!(
-                            "Skipping mutation for entity '{}': {} (dirty_fields={})",
-                            entity_name,
-                            reason,
-                            dirty_tracker.len()
+                            "Skipping mutation for entity '{}': no_fields_modified (dirty_fields=0)",
+                            entity_name
                         ));
+                    } else if primary_key.is_null() {
+                        self.report(VmError::NullPrimaryKey {
+                            entity: entity_name.clone(),
+                            event_type: event_type.to_string(),
+                            dirty_fields: dirty_tracker.len(),
+                        })?;
                     } else {
-                        let patch =
+                        let mut patch =
                             self.extract_partial_state_with_tracker(*state, &dirty_tracker)?;
+                        crate::unique_set::strip_legacy_fields(&mut patch);
 
                         let append = dirty_tracker.appended_paths();
                         let mutation = Mutation {
-                            export: entity_name.clone(),
-                            key: primary_key,
-                            patch,
                             append,
+                            provenance: dirty_tracker.provenance().cloned(),
+                            ..Mutation::new(entity_name.clone(), primary_key, patch)
                         };
                         output.push(mutation);
                     }
@@ -2327,6 +2965,7 @@ impl VmContext {
                     object,
                     path,
                     value,
+                    ..
                 } => {
                     let was_set = self.set_field_if_null(*object, path, *value)?;
                     if was_set && should_emit(path) {
@@ -2338,6 +2977,7 @@ impl VmContext {
                     object,
                     path,
                     value,
+                    ..
                 } => {
                     let was_updated = self.set_field_max(*object, path, *value)?;
                     if was_updated && should_emit(path) {
@@ -2345,6 +2985,13 @@ impl VmContext {
                     }
                     pc += 1;
                 }
+                OpCode::ClearField { object, path, .. } => {
+                    let was_cleared = self.clear_field(*object, path)?;
+                    if was_cleared && should_emit(path) {
+                        dirty_tracker.mark_replaced(path);
+                    }
+                    pc += 1;
+                }
                 OpCode::UpdateTemporalIndex {
                     state_id: _,
                     index_name,
@@ -2425,23 +3072,54 @@ impl VmContext {
                 OpCode::UpdateLookupIndex {
                     state_id: _,
                     index_name,
+                    scope,
                     lookup_value,
                     primary_key,
                 } => {
                     let actual_state_id = override_state_id;
-                    let state = self
-                        .states
-                        .get_mut(&actual_state_id)
-                        .ok_or("State table not found")?;
-                    let index = state
-                        .lookup_indexes
+                    let indexes = match scope {
+                        IndexScope::StateTable => {
+                            &mut self
+                                .states
+                                .get_mut(&actual_state_id)
+                                .ok_or("State table not found")?
+                                .lookup_indexes
+                        }
+                        IndexScope::Shared => &mut self.shared_lookup_indexes,
+                    };
+                    let index = indexes
                         .entry(index_name.clone())
                         .or_insert_with(LookupIndex::new);
 
                     let lookup_val = self.registers[*lookup_value].clone();
                     let pk_val = self.registers[*primary_key].clone();
 
-                    index.insert(lookup_val.clone(), pk_val);
+                    // An entity that failed to resolve its key must not erase a
+                    // mapping another entity registered
+                    if *scope == IndexScope::Shared && pk_val.is_null() {
+                        pc += 1;
+                        continue;
+                    }
+
+                    let previous = index.insert(lookup_val.clone(), pk_val.clone());
+                    if let Some(existing) = previous {
+                        if !existing.is_null() && !pk_val.is_null() && existing != pk_val {
+                            let collision = VmError::KeyCollision(Box::new(KeyCollision {
+                                entity: entity_name.to_string(),
+                                event_type: event_type.to_string(),
+                                index: index_name.clone(),
+                                lookup_value: lookup_val.clone(),
+                                existing,
+                                incoming: pk_val,
+                            }));
+                            if self.strict {
+                                return Err(collision.into());
+                            }
+                            // Outside strict mode remaps are routine, e.g. an
+                            // account reused for the next round
+                            tracing::debug!(event_type = %event_type, "{}", collision);
+                        }
+                    }
 
                     // Track lookup keys so process_event can flush queued account updates
                     if let Some(key_str) = lookup_val.as_str() {
@@ -2453,6 +3131,7 @@ impl VmContext {
                 OpCode::LookupIndex {
                     state_id: _,
                     index_name,
+                    scope,
                     lookup_value,
                     dest,
                 } => {
@@ -2462,7 +3141,10 @@ impl VmContext {
                     const MAX_CHAIN_DEPTH: usize = 5;
                     let mut iterations = 0;
 
-                    let final_result = if self.states.contains_key(&actual_state_id) {
+                    // Shared indexes can resolve before this entity's state table exists
+                    let final_result = if *scope == IndexScope::Shared
+                        || self.states.contains_key(&actual_state_id)
+                    {
                         loop {
                             iterations += 1;
                             if iterations > MAX_CHAIN_DEPTH {
@@ -2470,26 +3152,12 @@ impl VmContext {
                             }
 
                             let resolved = self
-                                .states
-                                .get(&actual_state_id)
-                                .and_then(|state| {
-                                    if let Some(index) = state.lookup_indexes.get(index_name) {
-                                        if let Some(found) = index.lookup(&current_value) {
-                                            return Some(found);
-                                        }
-                                    }
-
-                                    for (name, index) in state.lookup_indexes.iter() {
-                                        if name == index_name {
-                                            continue;
-                                        }
-                                        if let Some(found) = index.lookup(&current_value) {
-                                            return Some(found);
-                                        }
-                                    }
-
-                                    None
-                                })
+                                .lookup_in_indexes(
+                                    actual_state_id,
+                                    *scope,
+                                    index_name,
+                                    &current_value,
+                                )
                                 .unwrap_or(Value::Null);
 
                             let mut resolved_from_pda = false;
@@ -2522,8 +3190,12 @@ impl VmContext {
                                 break Value::Null;
                             }
 
-                            let can_chain =
-                                self.can_resolve_further(&resolved, actual_state_id, index_name);
+                            let can_chain = self.can_resolve_further(
+                                &resolved,
+                                actual_state_id,
+                                *scope,
+                                index_name,
+                            );
 
                             if !can_chain {
                                 if resolved_from_pda {
@@ -2549,6 +3221,7 @@ impl VmContext {
                     object,
                     path,
                     value,
+                    ..
                 } => {
                     let was_updated = self.set_field_sum(*object, path, *value)?;
                     if was_updated && should_emit(path) {
@@ -2556,7 +3229,7 @@ impl VmContext {
                     }
                     pc += 1;
                 }
-                OpCode::SetFieldIncrement { object, path } => {
+                OpCode::SetFieldIncrement { object, path, .. } => {
                     let was_updated = self.set_field_increment(*object, path)?;
                     if was_updated && should_emit(path) {
                         dirty_tracker.mark_replaced(path);
@@ -2567,6 +3240,7 @@ impl VmContext {
                     object,
                     path,
                     value,
+                    ..
                 } => {
                     let was_updated = self.set_field_min(*object, path, *value)?;
                     if was_updated && should_emit(path) {
@@ -2580,36 +3254,30 @@ impl VmContext {
                     value,
                     count_object,
                     count_path,
+                    key,
+                    ..
                 } => {
                     let value_to_add = self.registers[*value].clone();
+                    let key_value = self.registers[*key].clone();
 
-                    // Store the unique set within the entity object, not in the state table
-                    // This ensures each entity instance has its own unique set
-                    let set_field_path = format!("__unique_set:{}", set_name);
-
-                    // Get or create the unique set from the entity object
-                    let mut set: HashSet<Value> =
-                        if let Ok(existing) = self.get_field(*count_object, &set_field_path) {
-                            if !existing.is_null() {
-                                serde_json::from_value(existing).unwrap_or_default()
-                            } else {
-                                HashSet::new()
-                            }
-                        } else {
-                            HashSet::new()
-                        };
-
-                    // Add value to set
-                    let was_new = set.insert(value_to_add);
-
-                    // Store updated set back in the entity object
-                    let set_as_vec: Vec<Value> = set.iter().cloned().collect();
-                    self.registers[100] = serde_json::to_value(set_as_vec)?;
-                    self.set_field_auto_vivify(*count_object, &set_field_path, 100)?;
+                    // The set itself lives in the state table's side store; only the
+                    // count is written to the entity. Entities written by older
+                    // versions carry the set in a `__unique_set:` field, which is
+                    // migrated out of the state the first time the set is touched.
+                    let state = self
+                        .states
+                        .get(&override_state_id)
+                        .ok_or("State table not found")?;
+                    let entity_state = &mut self.registers[*count_object];
+                    let new_count =
+                        state
+                            .unique_sets
+                            .insert(&key_value, set_name, value_to_add, || {
+                                crate::unique_set::take_legacy_set(entity_state, set_name)
+                            });
 
-                    // Update the count field in the object
-                    if was_new {
-                        self.registers[100] = Value::Number(serde_json::Number::from(set.len()));
+                    if let Some(count) = new_count {
+                        self.registers[100] = Value::Number(serde_json::Number::from(count));
                         self.set_field_auto_vivify(*count_object, count_path, 100)?;
                         if should_emit(count_path) {
                             dirty_tracker.mark_replaced(count_path);
@@ -2625,6 +3293,7 @@ impl VmContext {
                     condition_field,
                     condition_op,
                     condition_value,
+                    ..
                 } => {
                     let field_value = self.load_field(event_value, condition_field, None)?;
                     let condition_met =
@@ -2638,6 +3307,40 @@ impl VmContext {
                     }
                     pc += 1;
                 }
+                OpCode::SnapshotFields {
+                    object,
+                    paths,
+                    dest,
+                } => {
+                    let mut snapshot = serde_json::Map::new();
+                    for path in paths {
+                        if let Some(previous) =
+                            Self::get_value_at_path(&self.registers[*object], path)
+                        {
+                            snapshot.insert(path.clone(), previous);
+                        }
+                    }
+                    self.registers[*dest] = Value::Object(snapshot);
+                    pc += 1;
+                }
+                OpCode::SkipIfUnchanged {
+                    object,
+                    snapshot,
+                    path,
+                    initial,
+                    skip,
+                } => {
+                    let current = Self::get_value_at_path(&self.registers[*object], path)
+                        .filter(|v| !v.is_null());
+                    let previous = self.registers[*snapshot].get(path).filter(|v| !v.is_null());
+                    let changed = match (previous, current) {
+                        (Some(previous), Some(current)) => *previous != current,
+                        (None, Some(_)) => *initial,
+                        (Some(_), None) => true,
+                        (None, None) => false,
+                    };
+                    pc += if changed { 1 } else { 1 + skip };
+                }
                 OpCode::SetFieldWhen {
                     object,
                     path,
@@ -2648,6 +3351,7 @@ impl VmContext {
                     condition_field,
                     condition_op,
                     condition_value,
+                    ..
                 } => {
                     let actual_state_id = override_state_id;
                     let condition_met = if let (Some(field), Some(op), Some(cond_value)) = (
@@ -2706,10 +3410,7 @@ impl VmContext {
                                 .as_ref()
                                 .and_then(|c| c.slot)
                                 .unwrap_or(0),
-                            deferred_at: std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap()
-                                .as_secs() as i64,
+                            deferred_at: self.clock().unix_secs(),
                             emit,
                         };
 
@@ -2720,6 +3421,12 @@ impl VmContext {
                                 .entry(key)
                                 .or_insert_with(Vec::new)
                                 .push(deferred);
+                        } else {
+                            self.report(VmError::MissingStateTable {
+                                entity: entity_name.clone(),
+                                event_type: event_type.to_string(),
+                                state_id: actual_state_id,
+                            })?;
                         }
                     }
 
@@ -2733,6 +3440,7 @@ impl VmContext {
                     stop_instruction,
                     entity_name,
                     key_reg: _,
+                    ..
                 } => {
                     let stop_value = self.get_field(*object, stop_field).unwrap_or(Value::Null);
                     let stopped = stop_value.as_bool().unwrap_or(false);
@@ -2761,6 +3469,7 @@ impl VmContext {
                     condition_field,
                     condition_op,
                     condition_value,
+                    ..
                 } => {
                     let field_value = self.load_field(event_value, condition_field, None)?;
                     let condition_met =
@@ -2784,18 +3493,9 @@ impl VmContext {
                             .map(|path| Self::get_value_at_path(&self.registers[*state], path))
                             .collect();
 
-                        let state_value = &mut self.registers[*state];
                         let context_slot = self.current_context.as_ref().and_then(|c| c.slot);
-                        let context_timestamp = self
-                            .current_context
-                            .as_ref()
-                            .map(|c| c.timestamp())
-                            .unwrap_or_else(|| {
-                                std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_secs() as i64
-                            });
+                        let context_timestamp = self.context_timestamp();
+                        let state_value = &mut self.registers[*state];
                         let eval_result = evaluator(state_value, context_slot, context_timestamp);
 
                         if eval_result.is_ok() {
@@ -2885,6 +3585,12 @@ impl VmContext {
                                                 retry_count: 0,
                                             },
                                         ));
+                                        Self::mark_resolver_pending(
+                                            &mut self.registers[*state],
+                                            extracts,
+                                            &mut dirty_tracker,
+                                            &should_emit,
+                                        )?;
                                     }
                                     pc += 1;
                                     continue;
@@ -2922,17 +3628,17 @@ impl VmContext {
                         }
 
                         if matches!(strategy, ResolveStrategy::SetOnce)
-                            // all(): fire resolver if any target is still null.
-                            // Already-set fields are protected from overwrite by
-                            // set_value_at_path's SetOnce null-source guard.
+                            // all(): fire resolver if any target is still null
+                            // or pending. Already-set fields are protected from
+                            // overwrite by set_value_at_path's SetOnce null-source guard.
                             && extracts.iter().all(|extract| {
-                                match Self::get_value_at_path(
-                                    &self.registers[*state],
-                                    &extract.target_path,
-                                ) {
-                                    Some(value) => !value.is_null(),
-                                    None => false,
-                                }
+                                !extract.is_unresolved(
+                                    Self::get_value_at_path(
+                                        &self.registers[*state],
+                                        &extract.target_path,
+                                    )
+                                    .as_ref(),
+                                )
                             })
                         {
                             pc += 1;
@@ -2963,6 +3669,12 @@ impl VmContext {
                                 input,
                                 target,
                             );
+                            Self::mark_resolver_pending(
+                                &mut self.registers[*state],
+                                extracts,
+                                &mut dirty_tracker,
+                                &should_emit,
+                            )?;
                         }
                     }
 
@@ -3150,6 +3862,27 @@ impl VmContext {
         Ok(false)
     }
 
+    /// Set the field at `path` to null. Returns whether it held a value, so
+    /// clearing a field that was never set doesn't produce a patch.
+    fn clear_field(&mut self, object_reg: Register, path: &str) -> Result<bool> {
+        let compiled = self.get_compiled_path(path);
+        let segments = compiled.segments();
+
+        let mut current = &mut self.registers[object_reg];
+        for segment in segments {
+            match current.get_mut(segment.as_str()) {
+                Some(value) => current = value,
+                None => return Ok(false),
+            }
+        }
+
+        if current.is_null() {
+            return Ok(false);
+        }
+        *current = Value::Null;
+        Ok(true)
+    }
+
     fn set_field_max(
         &mut self,
         object_reg: Register,
@@ -3160,6 +3893,11 @@ impl VmContext {
         let segments = compiled.segments();
         let new_value = self.registers[value_reg].clone();
 
+        // A missing source value must not clear or replace the tracked extreme
+        if new_value.is_null() {
+            return Ok(false);
+        }
+
         if !self.registers[object_reg].is_object() {
             self.registers[object_reg] = json!({});
         }
@@ -3226,6 +3964,11 @@ impl VmContext {
         let segments = compiled.segments();
         let new_value = &self.registers[value_reg];
 
+        // Nothing to add when the source field is absent
+        if new_value.is_null() {
+            return Ok(false);
+        }
+
         // Extract numeric value before borrowing object_reg mutably
         tracing::trace!(
             "set_field_sum: path={:?}, value={:?}, value_type={}",
@@ -3338,6 +4081,11 @@ impl VmContext {
         let segments = compiled.segments();
         let new_value = self.registers[value_reg].clone();
 
+        // A missing source value must not clear or replace the tracked extreme
+        if new_value.is_null() {
+            return Ok(false);
+        }
+
         if !self.registers[object_reg].is_object() {
             self.registers[object_reg] = json!({});
         }
@@ -3414,6 +4162,7 @@ impl VmContext {
         path: &str,
         value_reg: Register,
         max_length: usize,
+        aggregates: &[ArrayAggregateSpec],
     ) -> Result<()> {
         let compiled = self.get_compiled_path(path);
         let segments = compiled.segments();
@@ -3427,6 +4176,12 @@ impl VmContext {
             .as_object_mut()
             .ok_or("Not an object")?;
 
+        let previous: Vec<_> = aggregates
+            .iter()
+            .map(|aggregate| array_aggregate::load(obj, aggregate))
+            .collect();
+        let mut accumulators = Vec::with_capacity(aggregates.len());
+
         let mut current = obj;
         for (i, segment) in segments.iter().enumerate() {
             if i == segments.len() - 1 {
@@ -3439,8 +4194,11 @@ impl VmContext {
                     .ok_or("Path is not an array")?;
                 arr.push(value.clone());
 
-                if arr.len() > max_length {
-                    let excess = arr.len() - max_length;
+                let excess = arr.len().saturating_sub(max_length);
+                for (aggregate, previous) in aggregates.iter().zip(previous.iter()) {
+                    accumulators.push(array_aggregate::advance(aggregate, *previous, arr, excess));
+                }
+                if excess > 0 {
                     arr.drain(0..excess);
                 }
             } else {
@@ -3454,6 +4212,12 @@ impl VmContext {
             }
         }
 
+        if let Some(obj) = self.registers[object_reg].as_object_mut() {
+            for (aggregate, accumulator) in aggregates.iter().zip(accumulators) {
+                array_aggregate::store(obj, aggregate, accumulator);
+            }
+        }
+
         Ok(())
     }
 
@@ -3513,6 +4277,29 @@ impl VmContext {
                     Err("Base58Decode requires a string".into())
                 }
             }
+            Transformation::Base64Encode => {
+                if let Some(arr) = value.as_array() {
+                    let bytes: Vec<u8> = arr
+                        .iter()
+                        .filter_map(|v| v.as_u64().map(|n| n as u8))
+                        .collect();
+                    Ok(json!(BASE64_STANDARD.encode(&bytes)))
+                } else if value.is_string() {
+                    Ok(value.clone())
+                } else {
+                    Err("Base64Encode requires an array of numbers".into())
+                }
+            }
+            Transformation::Base64Decode => {
+                if let Some(s) = value.as_str() {
+                    let bytes = BASE64_STANDARD
+                        .decode(s)
+                        .map_err(|e| format!("Base64 decode error: {}", e))?;
+                    Ok(json!(bytes))
+                } else {
+                    Err("Base64Decode requires a string".into())
+                }
+            }
             Transformation::ToString => Ok(json!(value.to_string())),
             Transformation::ToNumber => {
                 if let Some(s) = value.as_str() {
@@ -3524,6 +4311,38 @@ impl VmContext {
                     Ok(value.clone())
                 }
             }
+            Transformation::Slice { start, end } => {
+                let arr = value.as_array().ok_or("Slice requires an array")?;
+                if start > end || *end > arr.len() {
+                    return Err(format!(
+                        "Slice {}..{} is out of bounds for {} elements",
+                        start,
+                        end,
+                        arr.len()
+                    )
+                    .into());
+                }
+                Ok(Value::Array(arr[*start..*end].to_vec()))
+            }
+            Transformation::Take(n) => {
+                let arr = value.as_array().ok_or("Take requires an array")?;
+                Ok(Value::Array(arr.iter().take(*n).cloned().collect()))
+            }
+            Transformation::Chain(stages) => {
+                let mut current = value.clone();
+                for (i, stage) in stages.iter().enumerate() {
+                    current = Self::apply_transformation(&current, stage).map_err(|e| {
+                        format!(
+                            "Transform stage {} of {} ({}) failed: {}",
+                            i + 1,
+                            stages.len(),
+                            stage,
+                            e
+                        )
+                    })?;
+                }
+                Ok(current)
+            }
         }
     }
 
@@ -3586,23 +4405,58 @@ impl VmContext {
         }
     }
 
-    fn can_resolve_further(&self, value: &Value, state_id: u32, index_name: &str) -> bool {
-        if let Some(state) = self.states.get(&state_id) {
-            if let Some(index) = state.lookup_indexes.get(index_name) {
-                if index.lookup(value).is_some() {
-                    return true;
-                }
+    /// Resolve `value` through the named index first, then through the
+    /// state table's other lookup indexes.
+    fn lookup_in_indexes(
+        &self,
+        state_id: u32,
+        scope: IndexScope,
+        index_name: &str,
+        value: &Value,
+    ) -> Option<Value> {
+        if scope == IndexScope::Shared {
+            if let Some(found) = self
+                .shared_lookup_indexes
+                .get(index_name)
+                .and_then(|index| index.lookup(value))
+            {
+                return Some(found);
             }
+        }
 
-            for (name, index) in state.lookup_indexes.iter() {
-                if name == index_name {
-                    continue;
-                }
-                if index.lookup(value).is_some() {
-                    return true;
-                }
+        let state = self.states.get(&state_id)?;
+        if scope == IndexScope::StateTable {
+            if let Some(found) = state
+                .lookup_indexes
+                .get(index_name)
+                .and_then(|index| index.lookup(value))
+            {
+                return Some(found);
             }
+        }
+
+        state
+            .lookup_indexes
+            .iter()
+            .filter(|(name, _)| scope == IndexScope::Shared || name.as_str() != index_name)
+            .find_map(|(_, index)| index.lookup(value))
+    }
 
+    fn can_resolve_further(
+        &self,
+        value: &Value,
+        state_id: u32,
+        scope: IndexScope,
+        index_name: &str,
+    ) -> bool {
+        if self
+            .lookup_in_indexes(state_id, scope, index_name, value)
+            .is_some()
+        {
+            return true;
+        }
+
+        if let Some(state) = self.states.get(&state_id) {
             if let Some(pda_str) = value.as_str() {
                 if let Some(pda_lookup) = state.pda_reverse_lookups.get("default_pda_lookup") {
                     if pda_lookup.contains(pda_str) {
@@ -3650,16 +4504,7 @@ impl VmContext {
         // Re-evaluate computed fields if an evaluator is provided
         if let Some(evaluator) = entity_evaluator {
             let context_slot = self.current_context.as_ref().and_then(|c| c.slot);
-            let context_timestamp = self
-                .current_context
-                .as_ref()
-                .map(|c| c.timestamp())
-                .unwrap_or_else(|| {
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs() as i64
-                });
+            let context_timestamp = self.context_timestamp();
 
             tracing::debug!(
                 entity_name = %op.entity_name,
@@ -3668,7 +4513,16 @@ impl VmContext {
                 "Re-evaluating computed fields after deferred when-op"
             );
 
-            if let Err(e) = evaluator(&mut entity_state, context_slot, context_timestamp) {
+            let eval_result = self.catch_panic(&op.entity_name, &op.when_instruction, |_| {
+                evaluator(&mut entity_state, context_slot, context_timestamp)
+            });
+            if let Err(e) = eval_result {
+                if matches!(
+                    e.downcast_ref::<VmError>(),
+                    Some(VmError::HandlerPanicked { .. })
+                ) {
+                    return Err(e);
+                }
                 tracing::warn!(
                     entity_name = %op.entity_name,
                     primary_key = %op.primary_key,
@@ -3678,7 +4532,10 @@ impl VmContext {
             }
         }
 
-        state.insert_with_eviction(op.primary_key.clone(), entity_state.clone());
+        self.states
+            .get(&state_id)
+            .ok_or("State not found")?
+            .insert_with_eviction(op.primary_key.clone(), entity_state.clone());
 
         if !op.emit {
             return Ok(vec![]);
@@ -3719,12 +4576,13 @@ impl VmContext {
             }
         }
 
-        Ok(vec![Mutation {
-            export: op.entity_name.clone(),
-            key: op.primary_key.clone(),
+        crate::unique_set::strip_legacy_fields(&mut patch);
+
+        Ok(vec![Mutation::new(
+            op.entity_name.clone(),
+            op.primary_key.clone(),
             patch,
-            append: vec![],
-        }])
+        )])
     }
 
     fn set_nested_field_value(obj: &mut Value, path: &str, value: Value) -> Result<()> {
@@ -3753,10 +4611,7 @@ impl VmContext {
     }
 
     pub fn cleanup_expired_when_ops(&mut self, state_id: u32, max_age_secs: i64) -> usize {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        let now = self.clock().unix_secs();
 
         let state = match self.states.get(&state_id) {
             Some(s) => s,
@@ -3837,6 +4692,12 @@ impl VmContext {
                 let count = evicted_updates.len();
                 self.pending_queue_size = self.pending_queue_size.saturating_sub(count as u64);
             }
+            if let Some((_, evicted_events)) = state.pending_instruction_events.remove(evicted) {
+                let count = evicted_events.len();
+                self.pending_instruction_event_count = self
+                    .pending_instruction_event_count
+                    .saturating_sub(count as u64);
+            }
         }
 
         // Flush pending updates from QueueUntil for this PDA
@@ -3852,7 +4713,11 @@ impl VmContext {
         if mapping_changed {
             if let Some(state) = self.states.get(&state_id) {
                 // Clear stale lookup-index entries for this PDA address
-                for index in state.lookup_indexes.values() {
+                for index in state
+                    .lookup_indexes
+                    .values()
+                    .chain(self.shared_lookup_indexes.values())
+                {
                     index.remove(&Value::String(pda_address.clone()));
                 }
 
@@ -3878,16 +4743,12 @@ impl VmContext {
     /// Returns the number of updates that were removed.
     /// This should be called periodically to prevent memory leaks from orphaned updates.
     pub fn cleanup_expired_pending_updates(&mut self, state_id: u32) -> usize {
+        let now = self.clock().unix_secs();
         let state = match self.states.get_mut(&state_id) {
             Some(s) => s,
             None => return 0,
         };
 
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-
         let mut removed_count = 0;
 
         // Iterate through all pending updates and remove expired ones
@@ -3919,6 +4780,42 @@ impl VmContext {
         removed_count
     }
 
+    /// Remove instruction events queued longer than the pending update TTL,
+    /// whose PDA was never registered
+    ///
+    /// Returns the number of events that were removed.
+    pub fn cleanup_expired_pending_instruction_events(&mut self, state_id: u32) -> usize {
+        let now = self.clock().unix_secs();
+        let state = match self.states.get_mut(&state_id) {
+            Some(s) => s,
+            None => return 0,
+        };
+
+        let mut removed_count = 0;
+        state
+            .pending_instruction_events
+            .retain(|_pda_address, events| {
+                let original_len = events.len();
+                events.retain(|event| now - event.queued_at <= PENDING_UPDATE_TTL_SECONDS);
+                removed_count += original_len - events.len();
+                !events.is_empty()
+            });
+
+        self.pending_instruction_event_count = self
+            .pending_instruction_event_count
+            .saturating_sub(removed_count as u64);
+
+        if removed_count > 0 {
+            #[cfg(feature = "otel")]
+            crate::vm_metrics::record_pending_instruction_events_expired(
+                removed_count as u64,
+                &state.entity_name,
+            );
+        }
+
+        removed_count
+    }
+
     /// Queue an account update for later processing when PDA reverse lookup is not yet available
     ///
     /// # Workflow
@@ -3971,6 +4868,7 @@ impl VmContext {
             }
         }
 
+        let queued_at = self.clock().unix_secs();
         let state = self
             .states
             .get_mut(&state_id)
@@ -3983,10 +4881,7 @@ impl VmContext {
             slot: update.slot,
             write_version: update.write_version,
             signature: update.signature,
-            queued_at: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i64,
+            queued_at,
             is_stale_reprocess: false,
         };
 
@@ -4025,6 +4920,14 @@ impl VmContext {
         state_id: u32,
         event: QueuedInstructionEvent,
     ) -> Result<()> {
+        if self.pending_instruction_event_count >= MAX_PENDING_INSTRUCTION_EVENTS_TOTAL as u64 {
+            self.cleanup_expired_pending_instruction_events(state_id);
+            if self.pending_instruction_event_count >= MAX_PENDING_INSTRUCTION_EVENTS_TOTAL as u64 {
+                self.drop_oldest_pending_instruction_event(state_id)?;
+            }
+        }
+
+        let queued_at = self.clock().unix_secs();
         let state = self
             .states
             .get_mut(&state_id)
@@ -4038,10 +4941,7 @@ impl VmContext {
             event_data: event.event_data,
             slot: event.slot,
             signature: event.signature,
-            queued_at: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i64,
+            queued_at,
         };
 
         let mut events = state
@@ -4051,9 +4951,13 @@ impl VmContext {
 
         if events.len() >= MAX_PENDING_UPDATES_PER_PDA {
             events.remove(0);
+        } else {
+            self.pending_instruction_event_count += 1;
         }
 
         events.push(pending);
+        #[cfg(feature = "otel")]
+        crate::vm_metrics::record_pending_instruction_event_queued(&state.entity_name);
 
         Ok(())
     }
@@ -4085,6 +4989,14 @@ impl VmContext {
         };
 
         if let Some((_, events)) = state.pending_instruction_events.remove(pda_address) {
+            self.pending_instruction_event_count = self
+                .pending_instruction_event_count
+                .saturating_sub(events.len() as u64);
+            #[cfg(feature = "otel")]
+            crate::vm_metrics::record_pending_instruction_events_flushed(
+                events.len() as u64,
+                &state.entity_name,
+            );
             events
         } else {
             Vec::new()
@@ -4095,10 +5007,7 @@ impl VmContext {
     pub fn get_pending_queue_stats(&self, state_id: u32) -> Option<PendingQueueStats> {
         let state = self.states.get(&state_id)?;
 
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        let now = self.clock().unix_secs();
 
         let mut total_updates = 0;
         let mut oldest_timestamp = now;
@@ -4121,12 +5030,30 @@ impl VmContext {
             }
         }
 
+        let mut total_instruction_events = 0;
+        let mut oldest_instruction_event = now;
+        for entry in state.pending_instruction_events.iter() {
+            let events = entry.value();
+            total_instruction_events += events.len();
+            for event in events.iter() {
+                oldest_instruction_event = oldest_instruction_event.min(event.queued_at);
+                estimated_memory += event.event_type.len()
+                    + event.pda_address.len()
+                    + event.signature.len()
+                    + 16
+                    + estimate_json_size(&event.event_data);
+            }
+        }
+
         Some(PendingQueueStats {
             total_updates,
             unique_pdas: state.pending_updates.len(),
             oldest_age_seconds: now - oldest_timestamp,
             largest_pda_queue_size: largest_pda_queue,
             estimated_memory_bytes: estimated_memory,
+            total_instruction_events,
+            instruction_event_pdas: state.pending_instruction_events.len(),
+            oldest_instruction_event_age_seconds: now - oldest_instruction_event,
         })
     }
 
@@ -4140,6 +5067,8 @@ impl VmContext {
             stats.state_table_entity_count = state.data.len();
             stats.state_table_max_entries = state.config.max_entries;
             stats.state_table_at_capacity = state.is_at_capacity();
+            stats.state_table_entity_bytes = state.entity_sizes.total_bytes();
+            stats.oversized_entity_truncations = state.entity_sizes.truncations();
 
             stats.lookup_index_count = state.lookup_indexes.len();
             stats.lookup_index_total_entries =
@@ -4162,49 +5091,253 @@ impl VmContext {
             stats.version_tracker_entries = state.version_tracker.len();
 
             stats.pending_queue_stats = self.get_pending_queue_stats(state_id);
+            stats.pending_instruction_events = state
+                .pending_instruction_events
+                .iter()
+                .map(|entry| entry.value().len())
+                .sum();
         }
 
         stats
     }
 
-    pub fn cleanup_all_expired(&mut self, state_id: u32) -> CleanupResult {
-        let pending_removed = self.cleanup_expired_pending_updates(state_id);
-        let temporal_removed = self.cleanup_temporal_indexes(state_id);
+    /// Point-in-time dump of the VM for incident forensics: the counters and
+    /// every state table's entities and index sizes.
+    pub fn debug_snapshot(&self) -> Value {
+        let mut state_ids: Vec<&u32> = self.states.keys().collect();
+        state_ids.sort();
 
-        #[cfg(feature = "otel")]
-        if let Some(state) = self.states.get(&state_id) {
-            crate::vm_metrics::record_cleanup(
-                pending_removed,
-                temporal_removed,
-                &state.entity_name,
-            );
-        }
+        let states: serde_json::Map<String, Value> = state_ids
+            .into_iter()
+            .map(|state_id| {
+                let state = &self.states[state_id];
+                let stats = self.get_memory_stats(*state_id);
+                let entities: Vec<Value> = state
+                    .data
+                    .iter()
+                    .map(|entry| json!({ "key": entry.key(), "state": entry.value() }))
+                    .collect();
+                let pending_updates: usize =
+                    state.pending_updates.iter().map(|e| e.value().len()).sum();
+
+                let table = json!({
+                    "entities": entities,
+                    "max_entries": stats.state_table_max_entries,
+                    "at_capacity": stats.state_table_at_capacity,
+                    "entity_bytes": stats.state_table_entity_bytes,
+                    "largest_entities": state.largest_entities(DEBUG_LARGEST_ENTITIES),
+                    "lookup_index_entries": stats.lookup_index_total_entries,
+                    "temporal_index_entries": stats.temporal_index_total_entries,
+                    "pda_reverse_lookup_entries": stats.pda_reverse_lookup_total_entries,
+                    "version_tracker_entries": stats.version_tracker_entries,
+                    "pending_updates": pending_updates,
+                    "pending_instruction_events": stats.pending_instruction_events,
+                });
+                (state_id.to_string(), table)
+            })
+            .collect();
 
-        CleanupResult {
-            pending_updates_removed: pending_removed,
-            temporal_entries_removed: temporal_removed,
-        }
+        json!({
+            "counters": {
+                "instructions_executed": self.instructions_executed,
+                "cache_hits": self.cache_hits,
+                "pda_cache_hits": self.pda_cache_hits,
+                "pda_cache_misses": self.pda_cache_misses,
+                "pending_queue_size": self.pending_queue_size,
+                "pending_instruction_event_count": self.pending_instruction_event_count,
+                "resolver_cache_hits": self.resolver_cache_hits,
+                "resolver_cache_misses": self.resolver_cache_misses,
+                "handler_panics": self.handler_panics,
+            },
+            "path_cache_size": self.path_cache.len(),
+            "shared_lookup_indexes": self.shared_lookup_indexes.len(),
+            "resolver_pending": self.resolver_pending.len(),
+            "scheduled_callbacks": self.scheduled_callbacks.len(),
+            "warnings": self.warnings,
+            "states": states,
+        })
     }
 
-    fn cleanup_temporal_indexes(&mut self, state_id: u32) -> usize {
-        let state = match self.states.get_mut(&state_id) {
-            Some(s) => s,
-            None => return 0,
-        };
-
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-
-        let cutoff = now - TEMPORAL_HISTORY_TTL_SECONDS;
-        let mut total_removed = 0;
-
-        for (_, index) in state.temporal_indexes.iter_mut() {
-            total_removed += index.cleanup_expired(cutoff);
-        }
+    /// Memory and pending queue stats of each state table, keyed by entity name
+    pub fn memory_report(&self) -> Value {
+        let tables: serde_json::Map<String, Value> = self
+            .states
+            .iter()
+            .map(|(state_id, state)| {
+                let stats = self.get_memory_stats(*state_id);
+                (state.entity_name.clone(), json!(stats))
+            })
+            .collect();
+        Value::Object(tables)
+    }
 
-        total_removed
+    /// Estimated entity memory of each state table, keyed by entity name,
+    /// with its `top_k` largest entities
+    pub fn entity_size_report(&self, top_k: usize) -> Value {
+        let tables: serde_json::Map<String, Value> = self
+            .states
+            .values()
+            .map(|state| {
+                let table = json!({
+                    "entities": state.data.len(),
+                    "entity_bytes": state.entity_sizes.total_bytes(),
+                    "max_entity_bytes": state.config.max_entity_bytes,
+                    "truncations": state.entity_sizes.truncations(),
+                    "largest": state.largest_entities(top_k),
+                });
+                (state.entity_name.clone(), table)
+            })
+            .collect();
+        Value::Object(tables)
+    }
+
+    /// The VM's entities and key mappings as versioned JSON, for restoring
+    /// into a fresh VM with [`import_state`](Self::import_state) after a
+    /// restart.
+    ///
+    /// Covers every state table's entities, lookup indexes, PDA reverse
+    /// lookups and the sets behind `UniqueCount` fields, and the shared lookup
+    /// indexes. Queued updates, version trackers and caches are left out and
+    /// refill from the stream.
+    pub fn export_state(&self) -> Value {
+        let mut state_ids: Vec<&u32> = self.states.keys().collect();
+        state_ids.sort();
+
+        let states = state_ids
+            .into_iter()
+            .map(|state_id| {
+                let state = &self.states[state_id];
+                let table = ExportedStateTable {
+                    entity_name: state.entity_name.clone(),
+                    entities: state
+                        .data
+                        .iter()
+                        .map(|entry| (entry.key().clone(), entry.value().clone()))
+                        .collect(),
+                    lookup_indexes: export_lookup_indexes(&state.lookup_indexes),
+                    pda_reverse_lookups: state
+                        .pda_reverse_lookups
+                        .iter()
+                        .map(|(name, lookup)| (name.clone(), lookup.entries()))
+                        .collect(),
+                    unique_sets: state.unique_sets.export(),
+                };
+                (*state_id, table)
+            })
+            .collect();
+
+        serde_json::to_value(ExportedVmState {
+            version: VM_STATE_VERSION,
+            states,
+            shared_lookup_indexes: export_lookup_indexes(&self.shared_lookup_indexes),
+        })
+        .unwrap_or(Value::Null)
+    }
+
+    /// Restore state exported by [`export_state`](Self::export_state),
+    /// returning the number of entities restored.
+    ///
+    /// Meant for a VM that has not processed events yet: restored entities
+    /// and mappings replace existing ones with the same key. Nothing is
+    /// restored from state of another [`VM_STATE_VERSION`], that fails to
+    /// parse, or whose tables hold other entities than this VM's tables of
+    /// the same state id.
+    pub fn import_state(&mut self, state: &Value) -> Result<usize> {
+        let version = state.get("version").and_then(Value::as_u64);
+        if version != Some(VM_STATE_VERSION) {
+            return Err(format!(
+                "VM state version {} is not supported, expected {}",
+                version.map_or_else(|| "(missing)".to_string(), |v| v.to_string()),
+                VM_STATE_VERSION
+            )
+            .into());
+        }
+        let exported = ExportedVmState::deserialize(state)?;
+        for (state_id, table) in &exported.states {
+            if let Some(existing) = self.states.get(state_id) {
+                if !existing.entity_name.is_empty() && existing.entity_name != table.entity_name {
+                    return Err(format!(
+                        "state table {} holds {}, the exported state has {}",
+                        state_id, existing.entity_name, table.entity_name
+                    )
+                    .into());
+                }
+            }
+        }
+
+        let mut restored = 0;
+        for (state_id, table) in exported.states {
+            let state = self
+                .states
+                .entry(state_id)
+                .or_insert_with(|| StateTable::new(String::new(), StateTableConfig::default()));
+            if state.entity_name.is_empty() {
+                state.entity_name = table.entity_name;
+            }
+            restored += table.entities.len();
+            for (key, value) in table.entities {
+                state.insert_with_eviction(key, value);
+            }
+            import_lookup_indexes(&mut state.lookup_indexes, table.lookup_indexes);
+            for (name, entries) in table.pda_reverse_lookups {
+                let lookup = state.pda_reverse_lookups.entry(name).or_insert_with(|| {
+                    PdaReverseLookup::new(DEFAULT_MAX_PDA_REVERSE_LOOKUP_ENTRIES)
+                });
+                for (pda_address, seed_value) in entries {
+                    lookup.insert(pda_address, seed_value);
+                }
+            }
+            state.unique_sets.import(table.unique_sets);
+        }
+        import_lookup_indexes(
+            &mut self.shared_lookup_indexes,
+            exported.shared_lookup_indexes,
+        );
+        Ok(restored)
+    }
+
+    pub fn cleanup_all_expired(&mut self, state_id: u32) -> CleanupResult {
+        let pending_removed = self.cleanup_expired_pending_updates(state_id);
+        let instruction_events_removed = self.cleanup_expired_pending_instruction_events(state_id);
+        let temporal_removed = self.cleanup_temporal_indexes(state_id);
+        let remeasured = self
+            .states
+            .get(&state_id)
+            .map(|state| state.entity_sizes.remeasure(&state.data))
+            .unwrap_or(0);
+
+        #[cfg(feature = "otel")]
+        if let Some(state) = self.states.get(&state_id) {
+            crate::vm_metrics::record_cleanup(
+                pending_removed,
+                temporal_removed,
+                &state.entity_name,
+            );
+        }
+
+        CleanupResult {
+            pending_updates_removed: pending_removed,
+            pending_instruction_events_removed: instruction_events_removed,
+            temporal_entries_removed: temporal_removed,
+            entities_remeasured: remeasured,
+        }
+    }
+
+    fn cleanup_temporal_indexes(&mut self, state_id: u32) -> usize {
+        let now = self.clock().unix_secs();
+        let state = match self.states.get_mut(&state_id) {
+            Some(s) => s,
+            None => return 0,
+        };
+
+        let cutoff = now - TEMPORAL_HISTORY_TTL_SECONDS;
+        let mut total_removed = 0;
+
+        for (_, index) in state.temporal_indexes.iter_mut() {
+            total_removed += index.cleanup_expired(cutoff);
+        }
+
+        total_removed
     }
 
     pub fn check_state_table_capacity(&self, state_id: u32) -> Option<CapacityWarning> {
@@ -4261,6 +5394,41 @@ impl VmContext {
         Ok(())
     }
 
+    /// Drop the oldest pending instruction event across all PDAs
+    fn drop_oldest_pending_instruction_event(&mut self, state_id: u32) -> Result<()> {
+        let state = self
+            .states
+            .get_mut(&state_id)
+            .ok_or("State table not found")?;
+
+        let oldest_pda = state
+            .pending_instruction_events
+            .iter()
+            .filter_map(|entry| {
+                let event = entry.value().first()?;
+                Some((event.queued_at, entry.key().clone()))
+            })
+            .min()
+            .map(|(_, pda)| pda);
+
+        if let Some(pda) = oldest_pda {
+            if let Some(mut events) = state.pending_instruction_events.get_mut(&pda) {
+                if !events.is_empty() {
+                    events.remove(0);
+                    self.pending_instruction_event_count =
+                        self.pending_instruction_event_count.saturating_sub(1);
+
+                    if events.is_empty() {
+                        drop(events);
+                        state.pending_instruction_events.remove(&pda);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Flush and return pending updates for a PDA for external reprocessing
     ///
     /// Returns the pending updates that were queued for this PDA address.
@@ -4304,6 +5472,35 @@ impl VmContext {
         }
     }
 
+    /// Delete the entities last updated by an account closed at `slot`,
+    /// returning a tombstone mutation for each.
+    ///
+    /// Only entities deleted on [`DeleteTrigger::AccountClosed`] are touched.
+    /// Updates from `slot` or earlier are skipped afterwards, so an
+    /// out-of-order update can't bring a deleted entity back.
+    pub fn close_account(
+        &mut self,
+        bytecode: &MultiEntityBytecode,
+        account_address: &str,
+        slot: u64,
+    ) -> Vec<Mutation> {
+        let mut entities: Vec<_> = bytecode
+            .entities
+            .values()
+            .filter(|entity| entity.delete_on == Some(DeleteTrigger::AccountClosed))
+            .collect();
+        entities.sort_by(|a, b| a.entity_name.cmp(&b.entity_name));
+
+        entities
+            .into_iter()
+            .filter_map(|entity| {
+                let state = self.states.get(&entity.state_id)?;
+                let key = state.close_account(account_address, slot)?;
+                Some(Mutation::tombstone(entity.entity_name.clone(), key))
+            })
+            .collect()
+    }
+
     /// Try to resolve a primary key via PDA reverse lookup
     pub fn try_pda_reverse_lookup(
         &mut self,
@@ -4366,12 +5563,102 @@ impl VmContext {
     // Computed Expression Evaluator (Task 5)
     // ============================================================================
 
+    /// Evaluator for computed expressions that sees this VM's current context
+    fn computed_evaluator(&self) -> ComputedEvaluator<'_> {
+        ComputedEvaluator {
+            context: self.current_context.as_ref(),
+            strict: self.strict,
+        }
+    }
+
+    /// Evaluate a computed expression AST against the current state
+    pub fn evaluate_computed_expr(&self, expr: &ComputedExpr, state: &Value) -> Result<Value> {
+        self.computed_evaluator()
+            .evaluate_computed_expr(expr, state)
+    }
+
+    /// Evaluate all computed fields for an entity and update the state
+    pub fn evaluate_computed_fields_from_ast(
+        &self,
+        state: &mut Value,
+        computed_field_specs: &[ComputedFieldSpec],
+    ) -> Result<Vec<String>> {
+        self.computed_evaluator()
+            .evaluate_computed_fields_from_ast(state, computed_field_specs)
+    }
+
+    /// Create a computed fields evaluator closure from AST specs
+    /// This returns a function that can be passed to the bytecode builder
+    pub fn create_evaluator_from_specs(
+        specs: Vec<ComputedFieldSpec>,
+    ) -> impl Fn(&mut Value, Option<u64>, i64) -> Result<()> + Send + Sync + 'static {
+        let strict = *STRICT_MODE;
+        move |state: &mut Value, context_slot: Option<u64>, context_timestamp: i64| {
+            let context = UpdateContext {
+                slot: context_slot,
+                timestamp: Some(context_timestamp),
+                ..Default::default()
+            };
+            ComputedEvaluator {
+                context: Some(&context),
+                strict,
+            }
+            .evaluate_computed_fields_from_ast(state, &specs)?;
+            Ok(())
+        }
+    }
+}
+
+/// Bytes of an address given as a byte array, a `0x`-prefixed or 64-digit
+/// hex string, or a base58 string.
+fn address_bytes(value: &Value) -> Result<Vec<u8>> {
+    if let Some(items) = value.as_array() {
+        return items
+            .iter()
+            .map(|item| {
+                item.as_u64()
+                    .and_then(|byte| u8::try_from(byte).ok())
+                    .ok_or_else(|| format!("Not a byte: {:?}", item).into())
+            })
+            .collect();
+    }
+    let s = value
+        .as_str()
+        .ok_or_else(|| format!("Not an address: {:?}", value))?;
+    let hex_digits = s
+        .strip_prefix("0x")
+        .or_else(|| (s.len() == 64).then_some(s))
+        .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()));
+    match hex_digits {
+        Some(digits) => Ok(hex::decode(digits)?),
+        None => Ok(bs58::decode(s).into_vec()?),
+    }
+}
+
+/// Evaluates computed field expressions.
+///
+/// Expressions only read the update context and strict mode, so this borrows
+/// just those instead of needing a whole `VmContext`.
+#[derive(Debug, Clone, Copy)]
+pub struct ComputedEvaluator<'a> {
+    context: Option<&'a UpdateContext>,
+    strict: bool,
+}
+
+impl ComputedEvaluator<'_> {
     /// Evaluate a computed expression AST against the current state
     /// This is the core runtime evaluator for computed fields from the AST
     pub fn evaluate_computed_expr(&self, expr: &ComputedExpr, state: &Value) -> Result<Value> {
         self.evaluate_computed_expr_with_env(expr, state, &std::collections::HashMap::new())
     }
 
+    /// Apply an entity's key normalizer to a raw key. The normalizer only
+    /// sees the key, bound to `key`; there is no entity state yet.
+    pub fn normalize_key(&self, normalizer: &ComputedExpr, key: Value) -> Result<Value> {
+        let env = std::collections::HashMap::from([("key".to_string(), key)]);
+        self.evaluate_computed_expr_with_env(normalizer, &Value::Null, &env)
+    }
+
     /// Evaluate a computed expression with a variable environment (for let bindings)
     fn evaluate_computed_expr_with_env(
         &self,
@@ -4564,18 +5851,35 @@ impl VmContext {
             ComputedExpr::Paren { expr } => self.evaluate_computed_expr_with_env(expr, state, env),
 
             ComputedExpr::ContextSlot => Ok(self
-                .current_context
-                .as_ref()
+                .context
                 .and_then(|ctx| ctx.slot)
                 .map(|s| json!(s))
                 .unwrap_or(Value::Null)),
 
             ComputedExpr::ContextTimestamp => Ok(self
-                .current_context
-                .as_ref()
+                .context
                 .map(|ctx| json!(ctx.timestamp()))
                 .unwrap_or(Value::Null)),
 
+            ComputedExpr::Now => Ok(json!(self
+                .context
+                .map(|ctx| ctx.timestamp())
+                .unwrap_or_else(crate::block_time_cache::wall_clock_fallback))),
+
+            ComputedExpr::SlotsToSecs { expr } => {
+                let val = self.evaluate_computed_expr_with_env(expr, state, env)?;
+                Ok(Self::value_as_i64(&val)
+                    .map(|slots| json!(crate::block_time_cache::slots_to_secs(slots)))
+                    .unwrap_or(Value::Null))
+            }
+
+            ComputedExpr::SecsToSlots { expr } => {
+                let val = self.evaluate_computed_expr_with_env(expr, state, env)?;
+                Ok(Self::value_as_i64(&val)
+                    .map(|secs| json!(crate::block_time_cache::secs_to_slots(secs)))
+                    .unwrap_or(Value::Null))
+            }
+
             ComputedExpr::Keccak256 { expr } => {
                 let val = self.evaluate_computed_expr_with_env(expr, state, env)?;
                 let bytes = self.value_to_bytes(&val)?;
@@ -4585,9 +5889,20 @@ impl VmContext {
                     hash.to_vec().iter().map(|b| json!(*b)).collect(),
                 ))
             }
+
+            ComputedExpr::ArrayAggregate { aggregate } => {
+                Ok(array_aggregate::evaluate(state, aggregate).unwrap_or(Value::Null))
+            }
         }
     }
 
+    /// Read a JSON number as i64, truncating floats
+    fn value_as_i64(val: &Value) -> Option<i64> {
+        val.as_i64()
+            .or_else(|| val.as_u64().map(|n| n as i64))
+            .or_else(|| val.as_f64().map(|n| n as i64))
+    }
+
     /// Convert a JSON value to a byte vector
     fn value_to_bytes(&self, val: &Value) -> Result<Vec<u8>> {
         match val {
@@ -4628,10 +5943,9 @@ impl VmContext {
 
     /// Get a field value from state by path (e.g., "section.field" or just "field")
     fn get_field_from_state(&self, state: &Value, path: &str) -> Result<Value> {
-        let segments: Vec<&str> = path.split('.').collect();
         let mut current = state;
 
-        for segment in segments {
+        for segment in path.split('.') {
             match current.get(segment) {
                 Some(v) => current = v,
                 None => return Ok(Value::Null),
@@ -4758,7 +6072,12 @@ impl VmContext {
 
         // Try f64
         if let (Some(a), Some(b)) = (left.as_f64(), right.as_f64()) {
-            return Ok(json!(float_op(a, b)));
+            let result = float_op(a, b);
+            if !result.is_finite() && self.strict {
+                return Err(VmError::NonFiniteNumber { left: a, right: b }.into());
+            }
+            // Non-finite floats have no JSON representation and become null
+            return Ok(json!(result));
         }
 
         // If either is null, return null
@@ -4926,6 +6245,33 @@ impl VmContext {
                 }
             }
             "to_string" => Ok(json!(value.to_string())),
+            "to_lowercase" | "to_uppercase" | "trim" => {
+                let s = value
+                    .as_str()
+                    .ok_or_else(|| format!("Cannot call {}() on {:?}", method, value))?;
+                Ok(json!(match method {
+                    "to_lowercase" => s.to_lowercase(),
+                    "to_uppercase" => s.to_uppercase(),
+                    _ => s.trim().to_string(),
+                }))
+            }
+            "trim_start_matches" | "trim_end_matches" => {
+                let (Some(s), Some(pattern)) =
+                    (value.as_str(), args.first().and_then(Value::as_str))
+                else {
+                    return Err(format!(
+                        "{}() requires a string and a string argument, got {:?}",
+                        method, value
+                    )
+                    .into());
+                };
+                Ok(json!(if method == "trim_start_matches" {
+                    s.trim_start_matches(pattern)
+                } else {
+                    s.trim_end_matches(pattern)
+                }))
+            }
+            "to_base58" => Ok(json!(bs58::encode(address_bytes(value)?).into_string())),
             "min" => {
                 if args.is_empty() {
                     return Err("min() requires an argument".into());
@@ -5007,6 +6353,7 @@ impl VmContext {
                     self.set_field_in_state(state, &spec.target_path, result)?;
                     updated_paths.push(spec.target_path.clone());
                 }
+                Err(e) if self.strict && e.is::<VmError>() => return Err(e),
                 Err(e) => {
                     tracing::warn!(
                         target_path = %spec.target_path,
@@ -5022,50 +6369,27 @@ impl VmContext {
 
     /// Set a field value in state by path (e.g., "section.field")
     fn set_field_in_state(&self, state: &mut Value, path: &str, value: Value) -> Result<()> {
-        let segments: Vec<&str> = path.split('.').collect();
-
-        if segments.is_empty() {
-            return Err("Empty path".into());
-        }
+        let (parents, leaf) = match path.rsplit_once('.') {
+            Some((parents, leaf)) => (Some(parents), leaf),
+            None => (None, path),
+        };
 
         // Navigate to parent, creating intermediate objects as needed
         let mut current = state;
-        for (i, segment) in segments.iter().enumerate() {
-            if i == segments.len() - 1 {
-                // Last segment - set the value
-                if let Some(obj) = current.as_object_mut() {
-                    obj.insert(segment.to_string(), value);
-                    return Ok(());
-                } else {
-                    return Err(format!("Cannot set field '{}' on non-object", segment).into());
-                }
-            } else {
-                // Intermediate segment - navigate or create
-                if !current.is_object() {
-                    *current = json!({});
-                }
-                let obj = current.as_object_mut().unwrap();
-                current = obj.entry(segment.to_string()).or_insert_with(|| json!({}));
+        for segment in parents.into_iter().flat_map(|parents| parents.split('.')) {
+            if !current.is_object() {
+                *current = json!({});
             }
+            let obj = current.as_object_mut().unwrap();
+            current = obj.entry(segment.to_string()).or_insert_with(|| json!({}));
         }
 
-        Ok(())
-    }
-
-    /// Create a computed fields evaluator closure from AST specs
-    /// This returns a function that can be passed to the bytecode builder
-    pub fn create_evaluator_from_specs(
-        specs: Vec<ComputedFieldSpec>,
-    ) -> impl Fn(&mut Value, Option<u64>, i64) -> Result<()> + Send + Sync + 'static {
-        move |state: &mut Value, context_slot: Option<u64>, context_timestamp: i64| {
-            let mut vm = VmContext::new();
-            vm.current_context = Some(UpdateContext {
-                slot: context_slot,
-                timestamp: Some(context_timestamp),
-                ..Default::default()
-            });
-            vm.evaluate_computed_fields_from_ast(state, &specs)?;
-            Ok(())
+        match current.as_object_mut() {
+            Some(obj) => {
+                obj.insert(leaf.to_string(), value);
+                Ok(())
+            }
+            None => Err(format!("Cannot set field '{}' on non-object", leaf).into()),
         }
     }
 }
@@ -5101,8 +6425,14 @@ impl crate::resolvers::ReverseLookupUpdater for VmContext {
 mod tests {
     use super::*;
     use crate::ast::{
-        BinaryOp, ComputedExpr, ComputedFieldSpec, HttpMethod, UrlResolverConfig, UrlSource,
+        BinaryOp, ComputedExpr, ComputedFieldSpec, ConditionExpr, FieldTypeInfo, HttpMethod,
+        IdentitySpec, KeyResolutionStrategy, MappingSource, ParsedCondition, PopulationStrategy,
+        SourceSpec, TypedFieldMapping, TypedHandlerSpec, TypedStreamSpec, UrlResolverConfig,
+        UrlSource,
     };
+    use crate::compiler::RouteKey;
+    use crate::testkit::ManualClock;
+    use std::sync::Arc;
 
     #[test]
     fn test_url_resolver_cache_key_uses_method_and_resolved_url() {
@@ -5148,7 +6478,9 @@ mod tests {
 
     #[test]
     fn test_expired_resolver_cache_entry_is_dropped() {
+        let clock = ManualClock::default();
         let mut vm = VmContext::new();
+        vm.set_clock(Arc::new(clock.clone()));
         let resolver = ResolverType::Url(UrlResolverConfig {
             url_source: UrlSource::FieldPath("metadata_uri".to_string()),
             method: HttpMethod::Get,
@@ -5157,18 +6489,118 @@ mod tests {
         let input = json!("https://cdn.example.com/token.json");
         let cache_key = resolver_cache_key(&resolver, &input);
 
-        vm.resolver_cache.put(
-            cache_key.clone(),
-            ResolverCacheEntry {
-                value: json!({ "name": "Token" }),
-                cached_at: Instant::now() - resolver_cache_ttl() - Duration::from_secs(1),
-            },
-        );
+        vm.cache_resolver_value(&resolver, &input, &json!({ "name": "Token" }));
+        clock.advance(resolver_cache_ttl());
+        assert!(vm.get_cached_resolver_value(&cache_key).is_some());
 
+        clock.advance(Duration::from_secs(1));
         assert!(vm.get_cached_resolver_value(&cache_key).is_none());
         assert!(vm.resolver_cache.get(&cache_key).is_none());
     }
 
+    #[test]
+    fn test_pending_updates_expire_after_ttl() {
+        let clock = ManualClock::default();
+        let mut vm = VmContext::new();
+        vm.set_clock(Arc::new(clock.clone()));
+
+        vm.queue_account_update(
+            0,
+            QueuedAccountUpdate {
+                pda_address: "pda".to_string(),
+                account_type: "RoundState".to_string(),
+                account_data: json!({}),
+                slot: 1,
+                write_version: 1,
+                signature: "sig".to_string(),
+            },
+        )
+        .unwrap();
+
+        clock.advance(Duration::from_secs(PENDING_UPDATE_TTL_SECONDS as u64));
+        assert_eq!(vm.cleanup_expired_pending_updates(0), 0);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(vm.cleanup_expired_pending_updates(0), 1);
+        assert_eq!(vm.pending_queue_size, 0);
+    }
+
+    fn instruction_event(pda_address: &str, slot: u64) -> QueuedInstructionEvent {
+        QueuedInstructionEvent {
+            pda_address: pda_address.to_string(),
+            event_type: "DeployIxState".to_string(),
+            event_data: json!({ "slot": slot }),
+            slot,
+            signature: format!("sig-{slot}"),
+        }
+    }
+
+    #[test]
+    fn test_pending_instruction_events_expire_after_ttl() {
+        let clock = ManualClock::default();
+        let mut vm = VmContext::new();
+        vm.set_clock(Arc::new(clock.clone()));
+
+        // Nothing ever registers this PDA
+        for slot in 1..=3 {
+            vm.queue_instruction_event(0, instruction_event("orphan", slot))
+                .unwrap();
+        }
+        clock.advance(Duration::from_secs(10));
+
+        let stats = vm.get_pending_queue_stats(0).unwrap();
+        assert_eq!(stats.total_updates, 0);
+        assert_eq!(stats.total_instruction_events, 3);
+        assert_eq!(stats.instruction_event_pdas, 1);
+        assert_eq!(stats.oldest_instruction_event_age_seconds, 10);
+        assert_eq!(vm.get_memory_stats(0).pending_instruction_events, 3);
+        assert_eq!(vm.pending_instruction_event_count, 3);
+
+        clock.advance(Duration::from_secs(PENDING_UPDATE_TTL_SECONDS as u64 - 10));
+        assert_eq!(
+            vm.cleanup_all_expired(0).pending_instruction_events_removed,
+            0
+        );
+
+        clock.advance(Duration::from_secs(1));
+        let result = vm.cleanup_all_expired(0);
+        assert_eq!(result.pending_instruction_events_removed, 3);
+        assert_eq!(vm.pending_instruction_event_count, 0);
+        let stats = vm.get_pending_queue_stats(0).unwrap();
+        assert_eq!(stats.total_instruction_events, 0);
+        assert_eq!(stats.instruction_event_pdas, 0);
+        assert_eq!(vm.get_memory_stats(0).pending_instruction_events, 0);
+        assert!(vm.flush_pending_instruction_events(0, "orphan").is_empty());
+    }
+
+    #[test]
+    fn test_pending_instruction_events_are_capped_across_pdas() {
+        let clock = ManualClock::default();
+        let mut vm = VmContext::new();
+        vm.set_clock(Arc::new(clock.clone()));
+
+        for i in 0..MAX_PENDING_INSTRUCTION_EVENTS_TOTAL {
+            vm.queue_instruction_event(0, instruction_event(&format!("pda-{i:04}"), i as u64))
+                .unwrap();
+        }
+        clock.advance(Duration::from_secs(1));
+        vm.queue_instruction_event(0, instruction_event("latest", 0))
+            .unwrap();
+
+        assert_eq!(
+            vm.pending_instruction_event_count,
+            MAX_PENDING_INSTRUCTION_EVENTS_TOTAL as u64
+        );
+        assert!(vm
+            .flush_pending_instruction_events(0, "pda-0000")
+            .is_empty());
+        assert_eq!(vm.flush_pending_instruction_events(0, "latest").len(), 1);
+        assert_eq!(
+            vm.pending_instruction_event_count,
+            MAX_PENDING_INSTRUCTION_EVENTS_TOTAL as u64 - 1
+        );
+    }
+
     #[test]
     fn test_computed_field_preserves_integer_type() {
         let vm = VmContext::new();
@@ -5183,6 +6615,7 @@ mod tests {
         let spec = ComputedFieldSpec {
             target_path: "trading.total_volume".to_string(),
             result_type: "Option<u64>".to_string(),
+            time_dependent: false,
             expression: ComputedExpr::Binary {
                 op: BinaryOp::Add,
                 left: Box::new(ComputedExpr::UnwrapOr {
@@ -5221,6 +6654,97 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_computed_time_builtins_use_context() {
+        let field = |path: &str| {
+            Box::new(ComputedExpr::FieldRef {
+                path: path.to_string(),
+            })
+        };
+        let spec = |target: &str, expression: ComputedExpr| ComputedFieldSpec {
+            target_path: target.to_string(),
+            result_type: "i64".to_string(),
+            time_dependent: true,
+            expression,
+        };
+        let specs = vec![
+            spec(
+                "round.secs_until_expiry",
+                ComputedExpr::Binary {
+                    op: BinaryOp::Sub,
+                    left: field("round.expires_at"),
+                    right: Box::new(ComputedExpr::Now),
+                },
+            ),
+            spec(
+                "round.secs_until_end_slot",
+                ComputedExpr::SlotsToSecs {
+                    expr: Box::new(ComputedExpr::Binary {
+                        op: BinaryOp::Sub,
+                        left: field("round.end_slot"),
+                        right: Box::new(ComputedExpr::ContextSlot),
+                    }),
+                },
+            ),
+            spec(
+                "round.grace_slots",
+                ComputedExpr::SecsToSlots {
+                    expr: field("round.grace_secs"),
+                },
+            ),
+        ];
+        let evaluator = VmContext::create_evaluator_from_specs(specs);
+        let mut state = json!({
+            "round": {
+                "expires_at": 1_700_000_090_i64,
+                "end_slot": 1_050,
+                "grace_secs": 6,
+            }
+        });
+
+        evaluator(&mut state, Some(1_000), 1_700_000_000).unwrap();
+        assert_eq!(state["round"]["secs_until_expiry"], json!(90));
+        assert_eq!(state["round"]["secs_until_end_slot"], json!(20));
+        assert_eq!(state["round"]["grace_slots"], json!(15));
+
+        crate::block_time_cache::set_slot_duration_ms(500);
+        evaluator(&mut state, Some(1_000), 1_700_000_060).unwrap();
+        crate::block_time_cache::set_slot_duration_ms(
+            crate::block_time_cache::DEFAULT_SLOT_DURATION_MS,
+        );
+        assert_eq!(state["round"]["secs_until_expiry"], json!(30));
+        assert_eq!(state["round"]["secs_until_end_slot"], json!(25));
+        assert_eq!(state["round"]["grace_slots"], json!(12));
+    }
+
+    #[test]
+    fn test_compiled_paths_are_shared_by_vms_running_one_bytecode() {
+        let bytecode = MultiEntityBytecode::new().build();
+        let mut first = VmContext::new();
+        let mut second = VmContext::new();
+        first.use_paths_of(&bytecode);
+        second.use_paths_of(&bytecode);
+
+        let a = first.get_compiled_path("interned.test.path");
+        let b = second.get_compiled_path("interned.test.path");
+        assert!(std::sync::Arc::ptr_eq(&a.segments, &b.segments));
+        assert_eq!(&*a.segments, ["interned", "test", "path"]);
+        assert_eq!(bytecode.paths.len(), 1);
+
+        // Each context still counts its own cache hits
+        second.get_compiled_path("interned.test.path");
+        assert_eq!(first.cache_hits, 0);
+        assert_eq!(second.cache_hits, 1);
+
+        // A reloaded bytecode starts from its own paths
+        let reloaded = MultiEntityBytecode::new().build();
+        second.use_paths_of(&reloaded);
+        assert!(second.path_cache().is_empty());
+        let c = second.get_compiled_path("interned.test.path");
+        assert!(!std::sync::Arc::ptr_eq(&a.segments, &c.segments));
+        assert_eq!(reloaded.paths.len(), 1);
+    }
+
     #[test]
     fn test_set_field_sum_preserves_integer_type() {
         let mut vm = VmContext::new();
@@ -5282,6 +6806,7 @@ mod tests {
             OpCode::LookupIndex {
                 state_id: 0,
                 index_name: "round_address_lookup_index".to_string(),
+                scope: IndexScope::StateTable,
                 lookup_value: 0,
                 dest: 1,
             },
@@ -5294,385 +6819,2697 @@ mod tests {
     }
 
     #[test]
-    fn test_lookup_index_no_chain() {
+    fn test_event_timestamp_uses_chain_block_time() {
         let mut vm = VmContext::new();
-
-        let state = vm.states.get_mut(&0).unwrap();
-        state
-            .lookup_indexes
-            .entry("test_index".to_string())
-            .or_insert_with(LookupIndex::new)
-            .insert(json!("key_abc"), json!(42));
+        let block_time = 1_650_000_000_i64;
+        crate::block_time_cache::record_block_time(210_000_001, block_time);
 
         let handler = vec![
             OpCode::LoadConstant {
-                value: json!("key_abc"),
-                dest: 0,
+                value: json!({"amount": 5}),
+                dest: 10,
             },
-            OpCode::LookupIndex {
-                state_id: 0,
-                index_name: "test_index".to_string(),
-                lookup_value: 0,
-                dest: 1,
+            OpCode::CreateEvent {
+                dest: 11,
+                event_value: 10,
+            },
+            OpCode::CreateCapture {
+                dest: 12,
+                capture_value: 10,
             },
+            OpCode::GetCurrentTimestamp { dest: 13 },
         ];
 
-        vm.execute_handler(&handler, &json!({}), "test", 0, "TestEntity", None, None)
+        // Replayed long after the block was produced: timestamps must be chain time
+        let context = UpdateContext::new(210_000_001, "replayed_sig".to_string());
+        vm.current_context = Some(context);
+        vm.execute_handler(&handler, &json!({}), "test", 0, "Test", None, None)
             .unwrap();
 
-        assert_eq!(vm.registers[1], json!(42));
+        assert_eq!(vm.registers[11]["timestamp"], json!(block_time));
+        assert_eq!(vm.registers[11]["slot"], json!(210_000_001));
+        assert_eq!(vm.registers[12]["timestamp"], json!(block_time));
+        assert_eq!(vm.registers[13], json!(block_time));
     }
 
     #[test]
-    fn test_conditional_set_field_with_zero_array() {
+    fn test_event_timestamp_prefers_explicit_context_timestamp() {
         let mut vm = VmContext::new();
-
-        let event_zeros = json!({
-            "value": [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                      0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
-        });
-
-        let event_nonzero = json!({
-            "value": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
-                      17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32]
-        });
-
-        let zero_32: Value = json!([
-            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0
-        ]);
+        crate::block_time_cache::record_block_time(210_000_002, 1_650_000_100);
 
         let handler = vec![
-            OpCode::CreateObject { dest: 2 },
-            OpCode::LoadEventField {
-                path: FieldPath::new(&["value"]),
+            OpCode::LoadConstant {
+                value: json!({}),
                 dest: 10,
-                default: None,
             },
-            OpCode::ConditionalSetField {
-                object: 2,
-                path: "captured_value".to_string(),
-                value: 10,
-                condition_field: FieldPath::new(&["value"]),
-                condition_op: ComparisonOp::NotEqual,
-                condition_value: zero_32,
+            OpCode::CreateEvent {
+                dest: 11,
+                event_value: 10,
             },
         ];
 
-        vm.execute_handler(&handler, &event_zeros, "test", 0, "Test", None, None)
+        vm.current_context = Some(UpdateContext::with_timestamp(
+            210_000_002,
+            "sig".to_string(),
+            1_650_000_042,
+        ));
+        vm.execute_handler(&handler, &json!({}), "test", 0, "Test", None, None)
             .unwrap();
-        assert!(
-            vm.registers[2].get("captured_value").is_none(),
-            "Field should not be set when value is all zeros"
-        );
 
-        vm.reset_registers();
-        vm.execute_handler(&handler, &event_nonzero, "test", 0, "Test", None, None)
-            .unwrap();
-        assert!(
-            vm.registers[2].get("captured_value").is_some(),
-            "Field should be set when value is non-zero"
-        );
+        assert_eq!(vm.registers[11]["timestamp"], json!(1_650_000_042));
     }
 
     #[test]
-    fn test_when_instruction_arrives_first() {
-        let mut vm = VmContext::new();
+    fn test_update_context_with_block_time() {
+        crate::block_time_cache::record_block_time(210_000_003, 1_650_000_200);
 
-        let signature = "test_sig_123".to_string();
+        let context =
+            UpdateContext::new_account(210_000_003, "sig".to_string(), 1).with_block_time();
+        assert_eq!(context.timestamp, Some(1_650_000_200));
 
-        {
-            let state = vm.states.get(&0).unwrap();
-            let mut cache = state.recent_tx_instructions.lock().unwrap();
-            let mut set = HashSet::new();
-            set.insert("RevealIxState".to_string());
-            cache.put(signature.clone(), set);
-        }
+        let unknown =
+            UpdateContext::new_account(210_000_999, "sig".to_string(), 1).with_block_time();
+        assert_eq!(unknown.timestamp, None);
 
-        vm.current_context = Some(UpdateContext::new(100, signature.clone()));
+        let before = crate::block_time_cache::wall_clock_fallback_count();
+        assert!(unknown.timestamp() > 1_650_000_200);
+        assert!(crate::block_time_cache::wall_clock_fallback_count() > before);
+    }
+
+    #[test]
+    fn test_skip_if_unchanged_suppresses_noop_rewrites() {
+        let handler = |initial: bool| {
+            vec![
+                OpCode::LoadConstant {
+                    value: json!("pk"),
+                    dest: 20,
+                },
+                OpCode::ReadOrInitState {
+                    state_id: 0,
+                    key: 20,
+                    default: json!({}),
+                    dest: 2,
+                },
+                OpCode::SnapshotFields {
+                    object: 2,
+                    paths: vec!["owner".to_string()],
+                    dest: 26,
+                },
+                OpCode::LoadEventField {
+                    path: FieldPath::new(&["owner"]),
+                    dest: 10,
+                    default: None,
+                },
+                OpCode::SetField {
+                    object: 2,
+                    path: "owner".to_string(),
+                    value: 10,
+                    mapping: None,
+                },
+                OpCode::SkipIfUnchanged {
+                    object: 2,
+                    snapshot: 26,
+                    path: "owner".to_string(),
+                    initial,
+                    skip: 2,
+                },
+                OpCode::LoadEventField {
+                    path: FieldPath::new(&["owner"]),
+                    dest: 10,
+                    default: None,
+                },
+                OpCode::AppendToArray {
+                    object: 2,
+                    path: "owner_history".to_string(),
+                    value: 10,
+                    mapping: None,
+                    aggregates: vec![],
+                },
+                OpCode::UpdateState {
+                    state_id: 0,
+                    key: 20,
+                    value: 2,
+                },
+            ]
+        };
+        let run = |vm: &mut VmContext, handler: &[OpCode], owner: &str| {
+            vm.execute_handler(
+                handler,
+                &json!({ "owner": owner }),
+                "test",
+                0,
+                "Test",
+                None,
+                None,
+            )
+            .unwrap();
+            vm.registers[2]
+                .get("owner_history")
+                .cloned()
+                .unwrap_or(Value::Null)
+        };
+
+        let mut vm = VmContext::new();
+        let counts_initial = handler(true);
+        assert_eq!(run(&mut vm, &counts_initial, "alice"), json!(["alice"]));
+        assert_eq!(run(&mut vm, &counts_initial, "alice"), json!(["alice"]));
+        assert_eq!(
+            run(&mut vm, &counts_initial, "bob"),
+            json!(["alice", "bob"])
+        );
+
+        let mut vm = VmContext::new();
+        let skips_initial = handler(false);
+        assert_eq!(run(&mut vm, &skips_initial, "alice"), Value::Null);
+        assert_eq!(run(&mut vm, &skips_initial, "alice"), Value::Null);
+        assert_eq!(run(&mut vm, &skips_initial, "bob"), json!(["bob"]));
+    }
 
+    #[test]
+    fn test_array_aggregates_across_the_cap_and_a_restart() {
+        use crate::ast::{ArrayAggregateKind, ArrayAggregateSpec};
+
+        const CAP: usize = 4;
+        let aggregate = |kind| ArrayAggregateSpec {
+            kind,
+            path: "trades.history".to_string(),
+            field: Some("size".to_string()),
+        };
+        let aggregates = vec![
+            aggregate(ArrayAggregateKind::Sum),
+            aggregate(ArrayAggregateKind::WindowAvg { n: 3 }),
+        ];
+        let specs: Vec<ComputedFieldSpec> = [
+            ("trades.total_size", ArrayAggregateKind::Sum),
+            ("trades.last_size", ArrayAggregateKind::Last),
+            ("trades.avg_size", ArrayAggregateKind::WindowAvg { n: 3 }),
+        ]
+        .into_iter()
+        .map(|(target_path, kind)| ComputedFieldSpec {
+            target_path: target_path.to_string(),
+            expression: ComputedExpr::ArrayAggregate {
+                aggregate: aggregate(kind),
+            },
+            result_type: "Option<f64>".to_string(),
+            time_dependent: false,
+        })
+        .collect();
         let handler = vec![
-            OpCode::CreateObject { dest: 2 },
             OpCode::LoadConstant {
-                value: json!("primary_key_value"),
-                dest: 1,
+                value: json!("pk"),
+                dest: 20,
             },
-            OpCode::LoadConstant {
-                value: json!("the_revealed_value"),
+            OpCode::ReadOrInitState {
+                state_id: 0,
+                key: 20,
+                default: json!({}),
+                dest: 2,
+            },
+            OpCode::LoadEventField {
+                path: FieldPath::new(&[]),
                 dest: 10,
+                default: None,
             },
-            OpCode::SetFieldWhen {
+            OpCode::AppendToArray {
                 object: 2,
-                path: "entropy_value".to_string(),
+                path: "trades.history".to_string(),
                 value: 10,
-                when_instruction: "RevealIxState".to_string(),
-                entity_name: "TestEntity".to_string(),
-                key_reg: 1,
-                condition_field: None,
-                condition_op: None,
-                condition_value: None,
+                mapping: None,
+                aggregates,
+            },
+            OpCode::UpdateState {
+                state_id: 0,
+                key: 20,
+                value: 2,
             },
         ];
+        let config = || StateTableConfig {
+            max_array_length: CAP,
+            ..Default::default()
+        };
+        let append = |vm: &mut VmContext, size: u64| -> Value {
+            vm.execute_handler(
+                &handler,
+                &json!({ "size": size }),
+                "test",
+                0,
+                "Test",
+                None,
+                None,
+            )
+            .unwrap();
+            let mut state = vm.registers[2].clone();
+            vm.evaluate_computed_fields_from_ast(&mut state, &specs)
+                .unwrap();
+            state
+        };
+        // What a full scan of the array would give
+        let expected = |sizes: &[u64]| {
+            let window = &sizes[sizes.len().saturating_sub(3)..];
+            json!({
+                "total_size": sizes.iter().sum::<u64>(),
+                "last_size": sizes.last(),
+                "avg_size": window.iter().sum::<u64>() as f64 / window.len() as f64,
+            })
+        };
+        let computed = |state: &Value| {
+            json!({
+                "total_size": state["trades"]["total_size"],
+                "last_size": state["trades"]["last_size"],
+                "avg_size": state["trades"]["avg_size"],
+            })
+        };
 
-        vm.execute_handler(
-            &handler,
-            &json!({}),
-            "VarState",
-            0,
-            "TestEntity",
-            None,
-            None,
-        )
-        .unwrap();
-
+        let mut vm = VmContext::new_with_config(config());
+        let mut sizes = Vec::new();
+        let mut state = Value::Null;
+        for size in 1..=6 {
+            state = append(&mut vm, size);
+            sizes.push(size);
+            let kept = &sizes[sizes.len().saturating_sub(CAP)..];
+            assert_eq!(computed(&state), expected(kept), "after appending {size}");
+        }
+        assert!(state[array_aggregate::ACCUMULATORS_FIELD].is_object());
+
+        // Restart from the persisted entity state
+        let persisted =
+            serde_json::to_string(&vm.states[&0].data.get(&json!("pk")).unwrap().clone()).unwrap();
+        let mut vm = VmContext::new_with_config(config());
+        vm.states[&0].insert_with_eviction(json!("pk"), serde_json::from_str(&persisted).unwrap());
+        for size in 7..=10 {
+            state = append(&mut vm, size);
+            sizes.push(size);
+            let kept = &sizes[sizes.len().saturating_sub(CAP)..];
+            assert_eq!(computed(&state), expected(kept), "after appending {size}");
+        }
+        // The accumulator came back with the state and kept in step with the array
         assert_eq!(
-            vm.registers[2].get("entropy_value").unwrap(),
-            "the_revealed_value",
-            "Field should be set when instruction was already seen"
+            state[array_aggregate::ACCUMULATORS_FIELD]["sum:trades.history:size"],
+            json!({ "len": CAP, "count": CAP, "numeric": CAP, "total": 7 + 8 + 9 + 10 })
         );
     }
 
     #[test]
-    fn test_when_account_arrives_first() {
-        let mut vm = VmContext::new();
-
-        let signature = "test_sig_456".to_string();
-
-        vm.current_context = Some(UpdateContext::new(100, signature.clone()));
+    fn test_write_policies_with_null_input() {
+        let run = |initial: Value, opcode: OpCode| {
+            let mut vm = VmContext::new();
+            let handler = vec![
+                OpCode::LoadConstant {
+                    value: json!("pk"),
+                    dest: 20,
+                },
+                OpCode::ReadOrInitState {
+                    state_id: 0,
+                    key: 20,
+                    default: initial,
+                    dest: 2,
+                },
+                OpCode::LoadConstant {
+                    value: Value::Null,
+                    dest: 10,
+                },
+                opcode,
+                OpCode::UpdateState {
+                    state_id: 0,
+                    key: 20,
+                    value: 2,
+                },
+            ];
+            vm.execute_handler(&handler, &json!({}), "test", 0, "Test", None, None)
+                .unwrap();
+            vm.registers[2].clone()
+        };
 
-        let handler = vec![
-            OpCode::CreateObject { dest: 2 },
-            OpCode::LoadConstant {
-                value: json!("pk_123"),
-                dest: 1,
+        let path = || "value".to_string();
+        let policies = [
+            OpCode::SetFieldIfNull {
+                object: 2,
+                path: path(),
+                value: 10,
+                mapping: None,
             },
-            OpCode::LoadConstant {
-                value: json!("deferred_value"),
-                dest: 10,
+            OpCode::SetFieldMax {
+                object: 2,
+                path: path(),
+                value: 10,
+                mapping: None,
             },
-            OpCode::SetFieldWhen {
+            OpCode::SetFieldMin {
                 object: 2,
-                path: "entropy_value".to_string(),
+                path: path(),
                 value: 10,
-                when_instruction: "RevealIxState".to_string(),
-                entity_name: "TestEntity".to_string(),
-                key_reg: 1,
-                condition_field: None,
-                condition_op: None,
-                condition_value: None,
+                mapping: None,
+            },
+            OpCode::SetFieldSum {
+                object: 2,
+                path: path(),
+                value: 10,
+                mapping: None,
             },
         ];
 
-        vm.execute_handler(
-            &handler,
-            &json!({}),
-            "VarState",
-            0,
-            "TestEntity",
-            None,
-            None,
-        )
-        .unwrap();
-
-        assert!(
-            vm.registers[2].get("entropy_value").is_none(),
-            "Field should not be set when instruction hasn't been seen"
-        );
-
-        let state = vm.states.get(&0).unwrap();
-        let key = (signature.clone(), "RevealIxState".to_string());
-        assert!(
-            state.deferred_when_ops.contains_key(&key),
-            "Operation should be queued"
-        );
-
-        {
-            let mut cache = state.recent_tx_instructions.lock().unwrap();
-            let mut set = HashSet::new();
-            set.insert("RevealIxState".to_string());
-            cache.put(signature.clone(), set);
-        }
-
-        let deferred = state.deferred_when_ops.remove(&key).unwrap().1;
-        for op in deferred {
-            vm.apply_deferred_when_op(0, &op, None, None).unwrap();
+        for opcode in policies {
+            // Existing values survive a null input
+            assert_eq!(
+                run(json!({"value": 5}), opcode.clone()),
+                json!({"value": 5}),
+                "{:?}",
+                opcode
+            );
+            // Unset fields stay unset rather than being written as null
+            assert_eq!(run(json!({}), opcode.clone()), json!({}), "{:?}", opcode);
         }
 
-        let state = vm.states.get(&0).unwrap();
-        let entity = state.data.get(&json!("pk_123")).unwrap();
+        // Latest always tracks the incoming value, including null
         assert_eq!(
-            entity.get("entropy_value").unwrap(),
-            "deferred_value",
-            "Field should be set after instruction arrives"
+            run(
+                json!({"value": 5}),
+                OpCode::SetField {
+                    object: 2,
+                    path: path(),
+                    value: 10,
+                    mapping: None,
+                }
+            ),
+            json!({"value": null})
         );
     }
 
     #[test]
-    fn test_when_cleanup_expired() {
-        let mut vm = VmContext::new();
+    fn test_clear_field_emits_null_in_opcode_order() {
+        let run = |initial: Value, writes: Vec<OpCode>| {
+            let mut vm = VmContext::new();
+            let mut handler = vec![
+                OpCode::LoadConstant {
+                    value: json!("pk"),
+                    dest: 20,
+                },
+                OpCode::ReadOrInitState {
+                    state_id: 0,
+                    key: 20,
+                    default: initial,
+                    dest: 2,
+                },
+                OpCode::LoadConstant {
+                    value: json!("executor-b"),
+                    dest: 10,
+                },
+            ];
+            handler.extend(writes);
+            handler.extend([
+                OpCode::UpdateState {
+                    state_id: 0,
+                    key: 20,
+                    value: 2,
+                },
+                OpCode::EmitMutation {
+                    entity_name: "Test".to_string(),
+                    key: 20,
+                    state: 2,
+                },
+            ]);
+            let mutations = vm
+                .execute_handler(&handler, &json!({}), "test", 0, "Test", None, None)
+                .unwrap();
+            let state = vm.states[&0].data.get(&json!("pk")).unwrap().clone();
+            (mutations.first().map(|m| m.patch.clone()), state)
+        };
 
-        let state = vm.states.get(&0).unwrap();
-        let key = ("old_sig".to_string(), "SomeIxState".to_string());
-        state.deferred_when_ops.insert(
-            key,
-            vec![DeferredWhenOperation {
-                entity_name: "Test".to_string(),
-                primary_key: json!("pk"),
-                field_path: "field".to_string(),
-                field_value: json!("value"),
-                when_instruction: "SomeIxState".to_string(),
-                signature: "old_sig".to_string(),
-                slot: 0,
-                deferred_at: 0,
-                emit: true,
-            }],
-        );
+        let path = || "automation.executor".to_string();
+        let clear = || OpCode::ClearField {
+            object: 2,
+            path: path(),
+            mapping: None,
+        };
+        let set = || OpCode::SetField {
+            object: 2,
+            path: path(),
+            value: 10,
+            mapping: None,
+        };
+        let assigned = json!({"automation": {"executor": "executor-a", "amount": 3}});
 
-        let removed = vm.cleanup_expired_when_ops(0, 60);
+        // A set field is nulled and the null is part of the patch
+        let (patch, state) = run(assigned.clone(), vec![clear()]);
+        assert_eq!(patch, Some(json!({"automation": {"executor": null}})));
+        assert_eq!(
+            state,
+            json!({"automation": {"executor": null, "amount": 3}})
+        );
 
-        assert_eq!(removed, 1, "Should have removed 1 expired op");
-        assert!(
-            vm.states.get(&0).unwrap().deferred_when_ops.is_empty(),
-            "Deferred ops should be empty after cleanup"
+        // Within one event, the last write wins
+        let (patch, _) = run(assigned.clone(), vec![clear(), set()]);
+        assert_eq!(
+            patch,
+            Some(json!({"automation": {"executor": "executor-b"}}))
         );
+        let (patch, _) = run(assigned, vec![set(), clear()]);
+        assert_eq!(patch, Some(json!({"automation": {"executor": null}})));
+
+        // Clearing a field that was never set changes nothing
+        let (patch, state) = run(json!({}), vec![clear()]);
+        assert_eq!(patch, None);
+        assert_eq!(state, json!({}));
     }
 
     #[test]
-    fn test_deferred_when_op_recomputes_dependent_fields() {
-        use crate::ast::{BinaryOp, ComputedExpr, ComputedFieldSpec};
-
+    fn test_unique_set_kept_out_of_entity_state() {
         let mut vm = VmContext::new();
 
-        // Create computed field specs similar to the ore stack:
-        // pre_reveal_rng depends on base_value
-        // pre_reveal_winning_square depends on pre_reveal_rng
-        let computed_specs = vec![
-            ComputedFieldSpec {
-                target_path: "results.pre_reveal_rng".to_string(),
-                result_type: "Option<u64>".to_string(),
-                expression: ComputedExpr::FieldRef {
-                    path: "entropy.base_value".to_string(),
+        let add_trader = |trader: &str| {
+            vec![
+                OpCode::LoadConstant {
+                    value: json!("entity_pk"),
+                    dest: 20,
                 },
-            },
-            ComputedFieldSpec {
-                target_path: "results.pre_reveal_winning_square".to_string(),
-                result_type: "Option<u64>".to_string(),
-                expression: ComputedExpr::MethodCall {
-                    expr: Box::new(ComputedExpr::FieldRef {
-                        path: "results.pre_reveal_rng".to_string(),
+                OpCode::ReadOrInitState {
+                    state_id: 0,
+                    key: 20,
+                    default: json!({
+                        "__unique_set:stats": {"traders_unique_set": ["legacy"]},
+                        "stats": {"traders": 1}
                     }),
-                    method: "map".to_string(),
-                    args: vec![ComputedExpr::Closure {
-                        param: "r".to_string(),
-                        body: Box::new(ComputedExpr::Binary {
-                            op: BinaryOp::Mod,
-                            left: Box::new(ComputedExpr::Var {
-                                name: "r".to_string(),
-                            }),
-                            right: Box::new(ComputedExpr::Literal {
-                                value: serde_json::json!(25),
-                            }),
-                        }),
-                    }],
+                    dest: 2,
+                },
+                OpCode::LoadConstant {
+                    value: json!(trader),
+                    dest: 10,
+                },
+                OpCode::AddToUniqueSet {
+                    state_id: 0,
+                    set_name: "stats.traders_unique_set".to_string(),
+                    value: 10,
+                    count_object: 2,
+                    count_path: "stats.traders".to_string(),
+                    key: 20,
+                    mapping: None,
+                },
+                OpCode::UpdateState {
+                    state_id: 0,
+                    key: 20,
+                    value: 2,
+                },
+                OpCode::EmitMutation {
+                    entity_name: "Test".to_string(),
+                    key: 20,
+                    state: 2,
+                },
+            ]
+        };
+
+        let mutations = vm
+            .execute_handler(
+                &add_trader("alice"),
+                &json!({}),
+                "test",
+                0,
+                "Test",
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(mutations[0].patch, json!({"stats": {"traders": 2}}));
+
+        vm.execute_handler(
+            &add_trader("bob"),
+            &json!({}),
+            "test",
+            0,
+            "Test",
+            None,
+            None,
+        )
+        .unwrap();
+        let mutations = vm
+            .execute_handler(
+                &add_trader("alice"),
+                &json!({}),
+                "test",
+                0,
+                "Test",
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(mutations.is_empty());
+
+        let state = vm.states.get(&0).unwrap();
+        let entity = state.data.get(&json!("entity_pk")).unwrap().clone();
+        assert_eq!(entity, json!({"stats": {"traders": 3}}));
+        assert_eq!(
+            state
+                .unique_sets
+                .count(&json!("entity_pk"), "stats.traders_unique_set"),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_null_account_key_warns_by_default_and_fails_in_strict_mode() {
+        let handler = vec![OpCode::ReadOrInitState {
+            state_id: 0,
+            key: 1,
+            default: json!({}),
+            dest: 2,
+        }];
+
+        let mut vm = VmContext::new();
+        vm.set_strict_mode(false);
+        vm.execute_handler(&handler, &json!({}), "PoolState", 0, "Pool", None, None)
+            .unwrap();
+        let warnings = vm.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("is NULL for account state"));
+
+        vm.set_strict_mode(true);
+        let err = vm
+            .execute_handler(&handler, &json!({}), "PoolState", 0, "Pool", None, None)
+            .unwrap_err();
+        let err = err.downcast_ref::<VmError>().expect("structured vm error");
+        assert_eq!(err.kind(), "null_key");
+        assert_eq!(err.entity(), Some("Pool"));
+        assert_eq!(err.event_type(), Some("PoolState"));
+        assert!(!vm.has_warnings());
+    }
+
+    #[test]
+    fn test_lookup_collision_is_allowed_by_default_and_fails_in_strict_mode() {
+        let remap = |pk: Value| {
+            vec![
+                OpCode::LoadConstant {
+                    value: json!("addr_1"),
+                    dest: 0,
+                },
+                OpCode::LoadConstant { value: pk, dest: 1 },
+                OpCode::UpdateLookupIndex {
+                    state_id: 0,
+                    index_name: "round_lookup".to_string(),
+                    scope: IndexScope::StateTable,
+                    lookup_value: 0,
+                    primary_key: 1,
                 },
+            ]
+        };
+
+        for strict in [false, true] {
+            let mut vm = VmContext::new();
+            vm.set_strict_mode(strict);
+            vm.execute_handler(
+                &remap(json!(1)),
+                &json!({}),
+                "RoundState",
+                0,
+                "Round",
+                None,
+                None,
+            )
+            .unwrap();
+            vm.execute_handler(
+                &remap(json!(1)),
+                &json!({}),
+                "RoundState",
+                0,
+                "Round",
+                None,
+                None,
+            )
+            .unwrap();
+            assert!(!vm.has_warnings(), "re-registering the same key is fine");
+
+            let result = vm.execute_handler(
+                &remap(json!(2)),
+                &json!({}),
+                "RoundState",
+                0,
+                "Round",
+                None,
+                None,
+            );
+            if strict {
+                let err = result.unwrap_err();
+                assert!(matches!(
+                    err.downcast_ref::<VmError>(),
+                    Some(VmError::KeyCollision(collision))
+                        if collision.existing == json!(1) && collision.incoming == json!(2)
+                ));
+            } else {
+                result.unwrap();
+                assert!(!vm.has_warnings(), "remaps are logged at debug level");
+            }
+        }
+    }
+
+    #[test]
+    fn test_non_finite_computed_value_fails_in_strict_mode() {
+        let specs = vec![ComputedFieldSpec {
+            target_path: "stats.ratio".to_string(),
+            expression: ComputedExpr::Binary {
+                op: BinaryOp::Mul,
+                left: Box::new(ComputedExpr::FieldRef {
+                    path: "stats.amount".to_string(),
+                }),
+                right: Box::new(ComputedExpr::Literal { value: json!(10.0) }),
             },
-        ];
+            result_type: "f64".to_string(),
+            time_dependent: false,
+        }];
 
-        let evaluator: Box<dyn Fn(&mut Value, Option<u64>, i64) -> Result<()> + Send + Sync> =
-            Box::new(VmContext::create_evaluator_from_specs(computed_specs));
+        let mut vm = VmContext::new();
+        vm.set_strict_mode(false);
+        let mut state = json!({ "stats": { "amount": f64::MAX } });
+        vm.evaluate_computed_fields_from_ast(&mut state, &specs)
+            .unwrap();
+        assert_eq!(state["stats"]["ratio"], Value::Null);
 
-        // Test that when we set entropy.base_value via deferred when-op,
-        // both computed fields are updated
-        let primary_key = json!("test_pk");
-        let op = DeferredWhenOperation {
-            entity_name: "TestEntity".to_string(),
-            primary_key: primary_key.clone(),
-            field_path: "entropy.base_value".to_string(),
-            field_value: json!(100),
-            when_instruction: "TestIxState".to_string(),
-            signature: "test_sig".to_string(),
-            slot: 100,
-            deferred_at: 0,
-            emit: true,
+        vm.set_strict_mode(true);
+        let err = vm
+            .evaluate_computed_fields_from_ast(&mut state, &specs)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>().map(VmError::kind),
+            Some("non_finite_number")
+        );
+    }
+
+    fn validated_mint_bytecode() -> MultiEntityBytecode {
+        use crate::ast::{
+            FieldPath, FieldTypeInfo, IdentitySpec, KeyResolutionStrategy, MappingSource,
+            PopulationStrategy, SourceSpec, TypedFieldMapping, TypedHandlerSpec, TypedStreamSpec,
         };
 
-        // Store the entity in state first
-        let initial_state = json!({
-            "results": {}
+        let mapping = |target: &str, source: &[&str]| {
+            TypedFieldMapping::new(
+                target.to_string(),
+                MappingSource::FromSource {
+                    path: FieldPath::new(source),
+                    default: None,
+                    transform: None,
+                },
+                PopulationStrategy::LastWrite,
+            )
+        };
+        let mut spec = TypedStreamSpec::<Value>::new(
+            "Mint".to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.mint".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![TypedHandlerSpec::new(
+                SourceSpec::Source {
+                    program_id: None,
+                    discriminator: None,
+                    type_name: "MintState".to_string(),
+                    serialization: None,
+                    is_account: true,
+                },
+                KeyResolutionStrategy::Embedded {
+                    primary_field: FieldPath::new(&["accounts", "mint"]),
+                },
+                vec![
+                    mapping("id.mint", &["accounts", "mint"]),
+                    mapping("state.supply", &["data", "supply"]),
+                    mapping("state.name", &["data", "name"]),
+                ],
+                true,
+            )],
+        );
+        for (path, rust_type) in [
+            ("id.mint", "Pubkey"),
+            ("state.supply", "u64"),
+            ("state.name", "Option<String>"),
+        ] {
+            let name = path.rsplit('.').next().unwrap().to_string();
+            spec.field_mappings.insert(
+                path.to_string(),
+                FieldTypeInfo::new(name, rust_type.to_string()),
+            );
+        }
+        MultiEntityBytecode::new()
+            .add_entity("Mint".to_string(), spec, 0)
+            .build()
+    }
+
+    #[test]
+    fn test_event_validation_warns_by_default_and_rejects_in_strict_mode() {
+        let bytecode = validated_mint_bytecode();
+        let well_formed = json!({
+            "accounts": { "mint": "So11111111111111111111111111111111111111112" },
+            "data": { "supply": 1000, "name": "Wrapped SOL" },
+        });
+        // A parser change renamed `supply`
+        let malformed = json!({
+            "accounts": { "mint": "So11111111111111111111111111111111111111112" },
+            "data": { "total_supply": 1000, "name": "Wrapped SOL" },
         });
-        vm.states
-            .get(&0)
-            .unwrap()
-            .insert_with_eviction(primary_key.clone(), initial_state);
 
-        // Apply the deferred when-op with the evaluator
+        let mut vm = VmContext::new();
+        vm.set_event_validation(Some(ValidationMode::Warn));
+        vm.process_event(&bytecode, well_formed.clone(), "MintState", None, None)
+            .unwrap();
+
+        let mut log = crate::canonical_log::CanonicalLog::new();
         let mutations = vm
-            .apply_deferred_when_op(
-                0,
-                &op,
-                Some(&evaluator),
-                Some(&[
-                    "results.pre_reveal_rng".to_string(),
-                    "results.pre_reveal_winning_square".to_string(),
-                ]),
+            .process_event(
+                &bytecode,
+                malformed.clone(),
+                "MintState",
+                None,
+                Some(&mut log),
             )
             .unwrap();
+        assert_eq!(mutations.len(), 1, "warn mode still runs the handler");
+        assert_eq!(log.get("invalid_fields"), Some(&json!(["data.supply"])));
+        assert_eq!(
+            log.get("warning_messages"),
+            Some(&json!([
+                "Event MintState failed validation: data.supply is missing"
+            ]))
+        );
 
-        // Check the entity state
-        let state = vm.states.get(&0).unwrap();
-        let entity = state.data.get(&primary_key).unwrap();
+        vm.set_event_validation(Some(ValidationMode::Strict));
+        let err = vm
+            .process_event(&bytecode, malformed, "MintState", None, None)
+            .unwrap_err();
+        let err = err.downcast_ref::<VmError>().expect("structured vm error");
+        assert_eq!(err.kind(), "invalid_event");
+        assert_eq!(err.event_type(), Some("MintState"));
+        let VmError::InvalidEvent(report) = err else {
+            unreachable!()
+        };
+        assert_eq!(
+            report.issues,
+            [FieldIssue::Missing {
+                path: "data.supply".to_string()
+            }]
+        );
 
-        println!(
-            "Entity state: {}",
-            serde_json::to_string_pretty(&*entity).unwrap()
+        assert_eq!(
+            vm.validation_counts()["MintState"],
+            ValidationCounts {
+                checked: 3,
+                failed: 2,
+                rejected: 1,
+            }
         );
+    }
 
-        // Verify base value was set
+    #[test]
+    fn test_event_validation_checks_field_shapes() {
+        let bytecode = validated_mint_bytecode();
+        let mut vm = VmContext::new();
+        vm.set_event_validation(Some(ValidationMode::Strict));
+
+        // Big numbers may arrive as strings, and optional fields may be null
+        let stringly = json!({
+            "accounts": { "mint": "So11111111111111111111111111111111111111112" },
+            "data": { "supply": "18446744073709551615", "name": null },
+        });
+        vm.process_event(&bytecode, stringly, "MintState", None, None)
+            .unwrap();
+
+        let mistyped = json!({
+            "accounts": { "mint": "0xdeadbeef" },
+            "data": { "supply": { "amount": 1000 }, "name": 7 },
+        });
+        let err = vm
+            .process_event(&bytecode, mistyped, "MintState", None, None)
+            .unwrap_err();
         assert_eq!(
-            entity.get("entropy").and_then(|e| e.get("base_value")),
-            Some(&json!(100)),
-            "Base value should be set"
+            err.to_string(),
+            "Event MintState failed validation: accounts.mint should be a pubkey, got string; \
+             data.supply should be a number, got object; data.name should be a string, got number"
         );
+    }
 
-        // Verify computed fields were calculated
-        let pre_reveal_rng = entity
-            .get("results")
-            .and_then(|r| r.get("pre_reveal_rng"))
-            .cloned();
-        let pre_reveal_winning_square = entity
-            .get("results")
-            .and_then(|r| r.get("pre_reveal_winning_square"))
-            .cloned();
+    #[test]
+    fn test_shared_lookup_index_resolves_across_entities() {
+        use crate::ast::{
+            FieldPath, IdentitySpec, KeyResolutionStrategy, LookupIndexSpec, MappingSource,
+            PopulationStrategy, SourceSpec, TypedFieldMapping, TypedHandlerSpec, TypedStreamSpec,
+        };
+        use crate::compiler::MultiEntityBytecode;
+
+        let mapping = |target: &str, source: &str| {
+            TypedFieldMapping::new(
+                target.to_string(),
+                MappingSource::FromSource {
+                    path: FieldPath::new(&[source]),
+                    default: None,
+                    transform: None,
+                },
+                PopulationStrategy::LastWrite,
+            )
+        };
+        let source = |type_name: &str, is_account: bool| SourceSpec::Source {
+            program_id: None,
+            discriminator: None,
+            type_name: type_name.to_string(),
+            serialization: None,
+            is_account,
+        };
+        let identity = || IdentitySpec {
+            primary_keys: vec!["id.curve".to_string()],
+            lookup_indexes: vec![LookupIndexSpec {
+                field_name: "id.mint".to_string(),
+                temporal_field: None,
+                shared_index: Some("mint_to_curve".to_string()),
+            }],
+        };
+
+        // Only the curve entity sees the account that maps a mint to its curve
+        let curve = TypedStreamSpec::<Value>::new(
+            "Curve".to_string(),
+            identity(),
+            vec![TypedHandlerSpec::new(
+                source("BondingCurveState", true),
+                KeyResolutionStrategy::Embedded {
+                    primary_field: FieldPath::new(&["curve"]),
+                },
+                vec![mapping("id.curve", "curve"), mapping("id.mint", "mint")],
+                true,
+            )],
+        );
+        // Trades only carry the mint and resolve their curve through the shared index
+        let trade = TypedStreamSpec::<Value>::new(
+            "Trade".to_string(),
+            identity(),
+            vec![TypedHandlerSpec::new(
+                source("BuyIxState", false),
+                KeyResolutionStrategy::Lookup {
+                    primary_field: FieldPath::new(&["mint"]),
+                },
+                vec![
+                    mapping("id.mint", "mint"),
+                    mapping("stats.last_amount", "amount"),
+                ],
+                true,
+            )],
+        );
+        let bytecode = MultiEntityBytecode::new()
+            .add_entity("Curve".to_string(), curve, 0)
+            .add_entity("Trade".to_string(), trade, 1)
+            .build();
+
+        let mut vm = VmContext::new();
+        vm.process_event(
+            &bytecode,
+            json!({ "curve": "curve_1", "mint": "mint_1" }),
+            "BondingCurveState",
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(vm
+            .states
+            .get(&1)
+            .is_none_or(|state| state.lookup_indexes.is_empty()));
 
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                json!({ "mint": "mint_1", "amount": 7 }),
+                "BuyIxState",
+                None,
+                None,
+            )
+            .unwrap();
+        let trade = mutations
+            .iter()
+            .find(|mutation| mutation.export == "Trade")
+            .expect("trade should resolve its key through the shared index");
+        assert_eq!(trade.key, json!("curve_1"));
+        assert_eq!(trade.patch["stats"]["last_amount"], json!(7));
+
+        // An unresolved trade leaves the shared mapping alone
+        vm.process_event(
+            &bytecode,
+            json!({ "mint": "mint_2", "amount": 1 }),
+            "BuyIxState",
+            None,
+            None,
+        )
+        .unwrap();
         assert_eq!(
-            pre_reveal_rng,
-            Some(json!(100)),
-            "pre_reveal_rng should be computed"
+            vm.shared_lookup_indexes["mint_to_curve"].lookup(&json!("mint_1")),
+            Some(json!("curve_1"))
         );
         assert_eq!(
-            pre_reveal_winning_square,
-            Some(json!(0)),
-            "pre_reveal_winning_square should be 100 % 25 = 0"
+            vm.shared_lookup_indexes["mint_to_curve"].lookup(&json!("mint_2")),
+            None
         );
+    }
 
-        // Verify mutations include computed fields
-        assert!(!mutations.is_empty(), "Should have mutations");
-        let mutation = &mutations[0];
-        let patch = &mutation.patch;
+    #[test]
+    fn test_resolver_placeholder_is_replaced_by_resolved_value() {
+        use crate::ast::{
+            FieldPath, IdentitySpec, KeyResolutionStrategy, MappingSource, PopulationStrategy,
+            ResolveStrategy, ResolverExtractSpec, ResolverSpec, SourceSpec, TypedFieldMapping,
+            TypedHandlerSpec, TypedStreamSpec,
+        };
+        use crate::compiler::MultiEntityBytecode;
 
-        assert!(
-            patch
-                .get("results")
-                .and_then(|r| r.get("pre_reveal_rng"))
-                .is_some(),
-            "Mutation should include pre_reveal_rng"
+        let mut token = TypedStreamSpec::<Value>::new(
+            "Token".to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.mint".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![TypedHandlerSpec::new(
+                SourceSpec::Source {
+                    program_id: None,
+                    discriminator: None,
+                    type_name: "MintState".to_string(),
+                    serialization: None,
+                    is_account: true,
+                },
+                KeyResolutionStrategy::Embedded {
+                    primary_field: FieldPath::new(&["mint"]),
+                },
+                vec![TypedFieldMapping::new(
+                    "id.mint".to_string(),
+                    MappingSource::FromSource {
+                        path: FieldPath::new(&["mint"]),
+                        default: None,
+                        transform: None,
+                    },
+                    PopulationStrategy::LastWrite,
+                )],
+                true,
+            )],
         );
-        assert!(
-            patch
-                .get("results")
-                .and_then(|r| r.get("pre_reveal_winning_square"))
-                .is_some(),
-            "Mutation should include pre_reveal_winning_square"
+        token.resolver_specs = vec![ResolverSpec {
+            resolver: ResolverType::Token,
+            input_path: Some("id.mint".to_string()),
+            input_value: None,
+            strategy: ResolveStrategy::SetOnce,
+            extracts: vec![ResolverExtractSpec {
+                target_path: "info.metadata".to_string(),
+                source_path: None,
+                transform: None,
+                placeholder: Some("pending".to_string()),
+            }],
+            condition: None,
+            schedule_at: None,
+        }];
+        let bytecode = MultiEntityBytecode::new()
+            .add_entity("Token".to_string(), token, 0)
+            .build();
+
+        let mut vm = VmContext::new();
+        let event = json!({ "mint": "mint_1" });
+        let mutations = vm
+            .process_event(&bytecode, event.clone(), "MintState", None, None)
+            .unwrap();
+        assert_eq!(
+            mutations[0].patch["info"]["metadata"],
+            json!({ "status": "pending" })
+        );
+        let requests = vm.take_resolver_requests();
+        assert_eq!(requests.len(), 1);
+
+        let mutations = vm
+            .apply_resolver_result(
+                &bytecode,
+                &requests[0].cache_key,
+                json!({ "name": "Ore", "decimals": 11 }),
+            )
+            .unwrap();
+        assert_eq!(mutations.len(), 1);
+        assert_eq!(
+            mutations[0].patch["info"]["metadata"],
+            json!({ "name": "Ore", "decimals": 11 })
         );
+
+        // Once resolved, SetOnce leaves the target alone
+        let mutations = vm
+            .process_event(&bytecode, event, "MintState", None, None)
+            .unwrap();
+        assert!(mutations
+            .iter()
+            .all(|mutation| mutation.patch.get("info").is_none()));
+        assert!(vm.take_resolver_requests().is_empty());
+    }
+
+    #[test]
+    fn test_key_from_merges_accounts_sharing_a_field() {
+        use crate::ast::{
+            FieldPath, IdentitySpec, KeyResolutionStrategy, MappingSource, PopulationStrategy,
+            ResolverHook, ResolverStrategy, SourceSpec, TypedFieldMapping, TypedHandlerSpec,
+            TypedStreamSpec,
+        };
+        use crate::compiler::MultiEntityBytecode;
+
+        let mapping = |target: &str, source: &str| {
+            TypedFieldMapping::new(
+                target.to_string(),
+                MappingSource::FromSource {
+                    path: FieldPath::new(&[source]),
+                    default: None,
+                    transform: None,
+                },
+                PopulationStrategy::LastWrite,
+            )
+        };
+        let source = |type_name: &str, is_account: bool| SourceSpec::Source {
+            program_id: None,
+            discriminator: None,
+            type_name: type_name.to_string(),
+            serialization: None,
+            is_account,
+        };
+
+        let mut spec = TypedStreamSpec::<Value>::new(
+            "Token".to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.mint".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![
+                TypedHandlerSpec::new(
+                    source("ThingState", true),
+                    KeyResolutionStrategy::Embedded {
+                        primary_field: FieldPath::new(&["mint"]),
+                    },
+                    vec![
+                        mapping("id.mint", "mint"),
+                        mapping("state.supply", "supply"),
+                    ],
+                    true,
+                ),
+                TypedHandlerSpec::new(
+                    source("PokeIxState", false),
+                    KeyResolutionStrategy::Lookup {
+                        primary_field: FieldPath::new(&["accounts", "thing"]),
+                    },
+                    vec![mapping("stats.last_amount", "amount")],
+                    true,
+                ),
+            ],
+        );
+        spec.resolver_hooks = vec![ResolverHook {
+            account_type: "ThingState".to_string(),
+            strategy: ResolverStrategy::KeyFrom {
+                field_path: FieldPath::new(&["mint"]),
+                index_name: "thing_address_lookup_index".to_string(),
+            },
+        }];
+        let bytecode = MultiEntityBytecode::new()
+            .add_entity("Token".to_string(), spec, 0)
+            .build();
+
+        let mut vm = VmContext::new();
+
+        // An instruction naming an account we have not seen yet waits for it
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                json!({ "accounts": { "thing": "thing_b" }, "amount": 3 }),
+                "PokeIxState",
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(mutations.is_empty());
+
+        // Two accounts holding the same mint land on the same entity, and the
+        // second one releases the queued instruction
+        let first = vm
+            .process_event(
+                &bytecode,
+                json!({ "__account_address": "thing_a", "mint": "mint_1", "supply": 10 }),
+                "ThingState",
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].key, json!("mint_1"));
+
+        let second = vm
+            .process_event(
+                &bytecode,
+                json!({ "__account_address": "thing_b", "mint": "mint_1", "supply": 20 }),
+                "ThingState",
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(second
+            .iter()
+            .all(|mutation| mutation.key == json!("mint_1")));
+        assert!(second
+            .iter()
+            .any(|mutation| mutation.patch["stats"]["last_amount"] == json!(3)));
+
+        // Later instructions resolve through either account address
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                json!({ "accounts": { "thing": "thing_a" }, "amount": 5 }),
+                "PokeIxState",
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(mutations.len(), 1);
+        assert_eq!(mutations[0].key, json!("mint_1"));
+        assert_eq!(mutations[0].patch["stats"]["last_amount"], json!(5));
+    }
+
+    #[test]
+    fn test_key_normalizer_merges_differently_formatted_keys() {
+        use crate::ast::{
+            FieldPath, IdentitySpec, KeyResolutionStrategy, MappingSource, PopulationStrategy,
+            SourceSpec, TypedFieldMapping, TypedHandlerSpec, TypedStreamSpec,
+        };
+        use crate::compiler::MultiEntityBytecode;
+
+        let mapping = |target: &str, source: &str| {
+            TypedFieldMapping::new(
+                target.to_string(),
+                MappingSource::FromSource {
+                    path: FieldPath::new(&[source]),
+                    default: None,
+                    transform: None,
+                },
+                PopulationStrategy::LastWrite,
+            )
+        };
+        let handler = |type_name: &str, field: &str| {
+            TypedHandlerSpec::new(
+                SourceSpec::Source {
+                    program_id: None,
+                    discriminator: None,
+                    type_name: type_name.to_string(),
+                    serialization: None,
+                    is_account: true,
+                },
+                KeyResolutionStrategy::Embedded {
+                    primary_field: FieldPath::new(&["mint"]),
+                },
+                vec![mapping(field, field)],
+                true,
+            )
+        };
+
+        // key.trim_start_matches("0x").to_base58()
+        let normalizer = ComputedExpr::MethodCall {
+            expr: Box::new(ComputedExpr::MethodCall {
+                expr: Box::new(ComputedExpr::Var {
+                    name: "key".to_string(),
+                }),
+                method: "trim_start_matches".to_string(),
+                args: vec![ComputedExpr::Literal { value: json!("0x") }],
+            }),
+            method: "to_base58".to_string(),
+            args: vec![],
+        };
+        let spec = TypedStreamSpec::<Value>::new(
+            "Token".to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.mint".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![
+                handler("MintState", "supply"),
+                handler("PoolState", "price"),
+            ],
+        )
+        .with_key_normalizer(normalizer);
+        let bytecode = MultiEntityBytecode::new()
+            .add_entity("Token".to_string(), spec, 0)
+            .build();
+
+        let mint = [7u8; 32];
+        let base58_mint = bs58::encode(mint).into_string();
+        let hex_mint = format!("0x{}", hex::encode_upper(mint));
+
+        let mut vm = VmContext::new();
+        let first = vm
+            .process_event(
+                &bytecode,
+                json!({ "mint": hex_mint, "supply": 10 }),
+                "MintState",
+                None,
+                None,
+            )
+            .unwrap();
+        let second = vm
+            .process_event(
+                &bytecode,
+                json!({ "mint": base58_mint, "price": 3 }),
+                "PoolState",
+                None,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(first[0].key, json!(base58_mint));
+        assert_eq!(second[0].key, json!(base58_mint));
+        let state = vm.states.get(&0).unwrap();
+        assert_eq!(state.data.len(), 1);
+        let entity = state.data.get(&json!(base58_mint)).unwrap().clone();
+        assert_eq!(entity["supply"], json!(10));
+        assert_eq!(entity["price"], json!(3));
+    }
+
+    #[test]
+    fn test_lookup_index_no_chain() {
+        let mut vm = VmContext::new();
+
+        let state = vm.states.get_mut(&0).unwrap();
+        state
+            .lookup_indexes
+            .entry("test_index".to_string())
+            .or_insert_with(LookupIndex::new)
+            .insert(json!("key_abc"), json!(42));
+
+        let handler = vec![
+            OpCode::LoadConstant {
+                value: json!("key_abc"),
+                dest: 0,
+            },
+            OpCode::LookupIndex {
+                state_id: 0,
+                index_name: "test_index".to_string(),
+                scope: IndexScope::StateTable,
+                lookup_value: 0,
+                dest: 1,
+            },
+        ];
+
+        vm.execute_handler(&handler, &json!({}), "test", 0, "TestEntity", None, None)
+            .unwrap();
+
+        assert_eq!(vm.registers[1], json!(42));
+    }
+
+    #[test]
+    fn test_conditional_set_field_with_zero_array() {
+        let mut vm = VmContext::new();
+
+        let event_zeros = json!({
+            "value": [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                      0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        });
+
+        let event_nonzero = json!({
+            "value": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+                      17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32]
+        });
+
+        let zero_32: Value = json!([
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0
+        ]);
+
+        let handler = vec![
+            OpCode::CreateObject { dest: 2 },
+            OpCode::LoadEventField {
+                path: FieldPath::new(&["value"]),
+                dest: 10,
+                default: None,
+            },
+            OpCode::ConditionalSetField {
+                object: 2,
+                path: "captured_value".to_string(),
+                value: 10,
+                condition_field: FieldPath::new(&["value"]),
+                condition_op: ComparisonOp::NotEqual,
+                condition_value: zero_32,
+                mapping: None,
+            },
+        ];
+
+        vm.execute_handler(&handler, &event_zeros, "test", 0, "Test", None, None)
+            .unwrap();
+        assert!(
+            vm.registers[2].get("captured_value").is_none(),
+            "Field should not be set when value is all zeros"
+        );
+
+        vm.reset_registers();
+        vm.execute_handler(&handler, &event_nonzero, "test", 0, "Test", None, None)
+            .unwrap();
+        assert!(
+            vm.registers[2].get("captured_value").is_some(),
+            "Field should be set when value is non-zero"
+        );
+    }
+
+    #[test]
+    fn test_when_instruction_arrives_first() {
+        let mut vm = VmContext::new();
+
+        let signature = "test_sig_123".to_string();
+
+        {
+            let state = vm.states.get(&0).unwrap();
+            let mut cache = state.recent_tx_instructions.lock().unwrap();
+            let mut set = HashSet::new();
+            set.insert("RevealIxState".to_string());
+            cache.put(signature.clone(), set);
+        }
+
+        vm.current_context = Some(UpdateContext::new(100, signature.clone()));
+
+        let handler = vec![
+            OpCode::CreateObject { dest: 2 },
+            OpCode::LoadConstant {
+                value: json!("primary_key_value"),
+                dest: 1,
+            },
+            OpCode::LoadConstant {
+                value: json!("the_revealed_value"),
+                dest: 10,
+            },
+            OpCode::SetFieldWhen {
+                object: 2,
+                path: "entropy_value".to_string(),
+                value: 10,
+                when_instruction: "RevealIxState".to_string(),
+                entity_name: "TestEntity".to_string(),
+                key_reg: 1,
+                condition_field: None,
+                condition_op: None,
+                condition_value: None,
+                mapping: None,
+            },
+        ];
+
+        vm.execute_handler(
+            &handler,
+            &json!({}),
+            "VarState",
+            0,
+            "TestEntity",
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vm.registers[2].get("entropy_value").unwrap(),
+            "the_revealed_value",
+            "Field should be set when instruction was already seen"
+        );
+    }
+
+    #[test]
+    fn test_when_account_arrives_first() {
+        let mut vm = VmContext::new();
+
+        let signature = "test_sig_456".to_string();
+
+        vm.current_context = Some(UpdateContext::new(100, signature.clone()));
+
+        let handler = vec![
+            OpCode::CreateObject { dest: 2 },
+            OpCode::LoadConstant {
+                value: json!("pk_123"),
+                dest: 1,
+            },
+            OpCode::LoadConstant {
+                value: json!("deferred_value"),
+                dest: 10,
+            },
+            OpCode::SetFieldWhen {
+                object: 2,
+                path: "entropy_value".to_string(),
+                value: 10,
+                when_instruction: "RevealIxState".to_string(),
+                entity_name: "TestEntity".to_string(),
+                key_reg: 1,
+                condition_field: None,
+                condition_op: None,
+                condition_value: None,
+                mapping: None,
+            },
+        ];
+
+        vm.execute_handler(
+            &handler,
+            &json!({}),
+            "VarState",
+            0,
+            "TestEntity",
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert!(
+            vm.registers[2].get("entropy_value").is_none(),
+            "Field should not be set when instruction hasn't been seen"
+        );
+
+        let state = vm.states.get(&0).unwrap();
+        let key = (signature.clone(), "RevealIxState".to_string());
+        assert!(
+            state.deferred_when_ops.contains_key(&key),
+            "Operation should be queued"
+        );
+
+        {
+            let mut cache = state.recent_tx_instructions.lock().unwrap();
+            let mut set = HashSet::new();
+            set.insert("RevealIxState".to_string());
+            cache.put(signature.clone(), set);
+        }
+
+        let deferred = state.deferred_when_ops.remove(&key).unwrap().1;
+        for op in deferred {
+            vm.apply_deferred_when_op(0, &op, None, None).unwrap();
+        }
+
+        let state = vm.states.get(&0).unwrap();
+        let entity = state.data.get(&json!("pk_123")).unwrap();
+        assert_eq!(
+            entity.get("entropy_value").unwrap(),
+            "deferred_value",
+            "Field should be set after instruction arrives"
+        );
+    }
+
+    #[test]
+    fn test_when_cleanup_expired() {
+        let clock = ManualClock::default();
+        let mut vm = VmContext::new();
+        vm.set_clock(Arc::new(clock.clone()));
+
+        let state = vm.states.get(&0).unwrap();
+        let key = ("old_sig".to_string(), "SomeIxState".to_string());
+        state.deferred_when_ops.insert(
+            key,
+            vec![DeferredWhenOperation {
+                entity_name: "Test".to_string(),
+                primary_key: json!("pk"),
+                field_path: "field".to_string(),
+                field_value: json!("value"),
+                when_instruction: "SomeIxState".to_string(),
+                signature: "old_sig".to_string(),
+                slot: 0,
+                deferred_at: clock.unix_secs(),
+                emit: true,
+            }],
+        );
+
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(vm.cleanup_expired_when_ops(0, 60), 0);
+
+        clock.advance(Duration::from_secs(1));
+        let removed = vm.cleanup_expired_when_ops(0, 60);
+
+        assert_eq!(removed, 1, "Should have removed 1 expired op");
+        assert!(
+            vm.states.get(&0).unwrap().deferred_when_ops.is_empty(),
+            "Deferred ops should be empty after cleanup"
+        );
+    }
+
+    #[test]
+    fn test_deferred_when_op_recomputes_dependent_fields() {
+        use crate::ast::{BinaryOp, ComputedExpr, ComputedFieldSpec};
+
+        let mut vm = VmContext::new();
+
+        // Create computed field specs similar to the ore stack:
+        // pre_reveal_rng depends on base_value
+        // pre_reveal_winning_square depends on pre_reveal_rng
+        let computed_specs = vec![
+            ComputedFieldSpec {
+                target_path: "results.pre_reveal_rng".to_string(),
+                result_type: "Option<u64>".to_string(),
+                time_dependent: false,
+                expression: ComputedExpr::FieldRef {
+                    path: "entropy.base_value".to_string(),
+                },
+            },
+            ComputedFieldSpec {
+                target_path: "results.pre_reveal_winning_square".to_string(),
+                result_type: "Option<u64>".to_string(),
+                time_dependent: false,
+                expression: ComputedExpr::MethodCall {
+                    expr: Box::new(ComputedExpr::FieldRef {
+                        path: "results.pre_reveal_rng".to_string(),
+                    }),
+                    method: "map".to_string(),
+                    args: vec![ComputedExpr::Closure {
+                        param: "r".to_string(),
+                        body: Box::new(ComputedExpr::Binary {
+                            op: BinaryOp::Mod,
+                            left: Box::new(ComputedExpr::Var {
+                                name: "r".to_string(),
+                            }),
+                            right: Box::new(ComputedExpr::Literal {
+                                value: serde_json::json!(25),
+                            }),
+                        }),
+                    }],
+                },
+            },
+        ];
+
+        let evaluator: Box<dyn Fn(&mut Value, Option<u64>, i64) -> Result<()> + Send + Sync> =
+            Box::new(VmContext::create_evaluator_from_specs(computed_specs));
+
+        // Test that when we set entropy.base_value via deferred when-op,
+        // both computed fields are updated
+        let primary_key = json!("test_pk");
+        let op = DeferredWhenOperation {
+            entity_name: "TestEntity".to_string(),
+            primary_key: primary_key.clone(),
+            field_path: "entropy.base_value".to_string(),
+            field_value: json!(100),
+            when_instruction: "TestIxState".to_string(),
+            signature: "test_sig".to_string(),
+            slot: 100,
+            deferred_at: 0,
+            emit: true,
+        };
+
+        // Store the entity in state first
+        let initial_state = json!({
+            "results": {}
+        });
+        vm.states
+            .get(&0)
+            .unwrap()
+            .insert_with_eviction(primary_key.clone(), initial_state);
+
+        // Apply the deferred when-op with the evaluator
+        let mutations = vm
+            .apply_deferred_when_op(
+                0,
+                &op,
+                Some(&evaluator),
+                Some(&[
+                    "results.pre_reveal_rng".to_string(),
+                    "results.pre_reveal_winning_square".to_string(),
+                ]),
+            )
+            .unwrap();
+
+        // Check the entity state
+        let state = vm.states.get(&0).unwrap();
+        let entity = state.data.get(&primary_key).unwrap();
+
+        println!(
+            "Entity state: {}",
+            serde_json::to_string_pretty(&*entity).unwrap()
+        );
+
+        // Verify base value was set
+        assert_eq!(
+            entity.get("entropy").and_then(|e| e.get("base_value")),
+            Some(&json!(100)),
+            "Base value should be set"
+        );
+
+        // Verify computed fields were calculated
+        let pre_reveal_rng = entity
+            .get("results")
+            .and_then(|r| r.get("pre_reveal_rng"))
+            .cloned();
+        let pre_reveal_winning_square = entity
+            .get("results")
+            .and_then(|r| r.get("pre_reveal_winning_square"))
+            .cloned();
+
+        assert_eq!(
+            pre_reveal_rng,
+            Some(json!(100)),
+            "pre_reveal_rng should be computed"
+        );
+        assert_eq!(
+            pre_reveal_winning_square,
+            Some(json!(0)),
+            "pre_reveal_winning_square should be 100 % 25 = 0"
+        );
+
+        // Verify mutations include computed fields
+        assert!(!mutations.is_empty(), "Should have mutations");
+        let mutation = &mutations[0];
+        let patch = &mutation.patch;
+
+        assert!(
+            patch
+                .get("results")
+                .and_then(|r| r.get("pre_reveal_rng"))
+                .is_some(),
+            "Mutation should include pre_reveal_rng"
+        );
+        assert!(
+            patch
+                .get("results")
+                .and_then(|r| r.get("pre_reveal_winning_square"))
+                .is_some(),
+            "Mutation should include pre_reveal_winning_square"
+        );
+    }
+
+    fn pubkey_prefix_chain() -> Transformation {
+        Transformation::Chain(vec![
+            Transformation::Base58Decode,
+            Transformation::Slice { start: 0, end: 8 },
+            Transformation::HexEncode,
+        ])
+    }
+
+    #[test]
+    fn test_transformation_chain_round_trips_a_pubkey() {
+        let pubkey = json!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+        let bytes = bs58::decode(pubkey.as_str().unwrap()).into_vec().unwrap();
+
+        let prefix = VmContext::apply_transformation(&pubkey, &pubkey_prefix_chain()).unwrap();
+        assert_eq!(prefix, json!(hex::encode(&bytes[..8])));
+
+        let round_trip = Transformation::Chain(vec![
+            Transformation::Base58Decode,
+            Transformation::Take(32),
+            Transformation::HexEncode,
+            Transformation::HexDecode,
+            Transformation::Base58Encode,
+        ]);
+        assert_eq!(
+            VmContext::apply_transformation(&pubkey, &round_trip).unwrap(),
+            pubkey
+        );
+    }
+
+    #[test]
+    fn test_transformation_chain_reports_the_failing_stage() {
+        let chain = pubkey_prefix_chain();
+        let error = |input: Value, chain: &Transformation| {
+            VmContext::apply_transformation(&input, chain)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error(json!(42), &chain),
+            "Transform stage 1 of 3 (base58_decode) failed: Base58Decode requires a string"
+        );
+        assert_eq!(
+            error(json!("2g"), &chain),
+            "Transform stage 2 of 3 (slice(0, 8)) failed: Slice 0..8 is out of bounds for 1 elements"
+        );
+
+        let chain = Transformation::Chain(vec![
+            Transformation::Base58Decode,
+            Transformation::Take(8),
+            Transformation::ToString,
+            Transformation::HexDecode,
+        ]);
+        let message = error(json!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"), &chain);
+        assert!(
+            message.starts_with("Transform stage 4 of 4 (hex_decode) failed: Hex decode error:"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_transformation_chain_stops_at_the_first_failure() {
+        let chain = Transformation::Chain(vec![
+            Transformation::Take(2),
+            Transformation::Slice { start: 1, end: 3 },
+            Transformation::HexEncode,
+        ]);
+        assert_eq!(
+            VmContext::apply_transformation(&json!([1, 2, 3]), &chain)
+                .unwrap_err()
+                .to_string(),
+            "Transform stage 2 of 3 (slice(1, 3)) failed: Slice 1..3 is out of bounds for 2 elements"
+        );
+    }
+
+    #[test]
+    fn test_wide_values_survive_a_round_trip_through_state() {
+        // What the generated parser emits for an account with `u128`, `i128`,
+        // `[u8; 64]` and `[u8; 96]` fields
+        let signature: Vec<u8> = (0..64).map(|i| (i * 7 + 3) as u8).collect();
+        let proof: Vec<u8> = (0..96).map(|i| (255 - i * 2) as u8).collect();
+        let total_deposits = u128::MAX - 12_345;
+        let net_flow = i128::MIN + 1;
+
+        let from_source = |target: &str, source: &str, transform| {
+            TypedFieldMapping::new(
+                target.to_string(),
+                MappingSource::FromSource {
+                    path: FieldPath::new(&[source]),
+                    default: None,
+                    transform,
+                },
+                PopulationStrategy::LastWrite,
+            )
+        };
+        let handler = TypedHandlerSpec::new(
+            SourceSpec::Source {
+                program_id: None,
+                discriminator: None,
+                type_name: "VaultState".to_string(),
+                serialization: None,
+                is_account: true,
+            },
+            KeyResolutionStrategy::Embedded {
+                primary_field: FieldPath::new(&["authority"]),
+            },
+            vec![
+                from_source("id.authority", "authority", None),
+                from_source("vault.total_deposits", "total_deposits", None),
+                from_source("vault.net_flow", "net_flow", None),
+                from_source(
+                    "vault.last_signature",
+                    "last_signature",
+                    Some(Transformation::Base58Encode),
+                ),
+                from_source("vault.proof", "proof", Some(Transformation::Base64Encode)),
+            ],
+            true,
+        );
+        let spec = TypedStreamSpec::<Value>::new(
+            "Vault".to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.authority".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![handler],
+        );
+        let bytecode = MultiEntityBytecode::from_single("Vault".to_string(), spec, 0);
+        let mut vm = VmContext::new();
+
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                json!({
+                    "authority": "vault-authority",
+                    "total_deposits": total_deposits.to_string(),
+                    "net_flow": net_flow.to_string(),
+                    "last_signature": signature,
+                    "proof": proof,
+                }),
+                "VaultState",
+                None,
+                None,
+            )
+            .unwrap();
+        let vault = &mutations[0].patch["vault"];
+
+        let stored_u128 = vault["total_deposits"].as_str().unwrap();
+        assert_eq!(stored_u128.parse::<u128>().unwrap(), total_deposits);
+        let stored_i128 = vault["net_flow"].as_str().unwrap();
+        assert_eq!(stored_i128.parse::<i128>().unwrap(), net_flow);
+
+        let stored_signature = &vault["last_signature"];
+        assert!(stored_signature.is_string());
+        let decoded =
+            VmContext::apply_transformation(stored_signature, &Transformation::Base58Decode)
+                .unwrap();
+        assert_eq!(decoded, json!(signature));
+
+        let stored_proof = &vault["proof"];
+        assert_eq!(stored_proof.as_str().unwrap().len(), 128);
+        let decoded =
+            VmContext::apply_transformation(stored_proof, &Transformation::Base64Decode).unwrap();
+        assert_eq!(decoded, json!(proof));
+    }
+
+    #[test]
+    fn test_base64_transforms() {
+        assert_eq!(
+            VmContext::apply_transformation(&json!([104, 105]), &Transformation::Base64Encode)
+                .unwrap(),
+            json!("aGk=")
+        );
+        // Already encoded values pass through, as with base58
+        assert_eq!(
+            VmContext::apply_transformation(&json!("aGk="), &Transformation::Base64Encode).unwrap(),
+            json!("aGk=")
+        );
+        assert_eq!(
+            VmContext::apply_transformation(&json!(7), &Transformation::Base64Encode)
+                .unwrap_err()
+                .to_string(),
+            "Base64Encode requires an array of numbers"
+        );
+        assert!(VmContext::apply_transformation(
+            &json!("not base64!"),
+            &Transformation::Base64Decode
+        )
+        .unwrap_err()
+        .to_string()
+        .starts_with("Base64 decode error:"));
+    }
+
+    #[test]
+    fn test_from_transaction_reads_the_transaction_section() {
+        let handler = TypedHandlerSpec::new(
+            SourceSpec::Source {
+                program_id: None,
+                discriminator: None,
+                type_name: "DeployIxState".to_string(),
+                serialization: None,
+                is_account: false,
+            },
+            KeyResolutionStrategy::Embedded {
+                primary_field: FieldPath::new(&["accounts", "round"]),
+            },
+            vec![
+                TypedFieldMapping::new(
+                    "id.round".to_string(),
+                    MappingSource::FromSource {
+                        path: FieldPath::new(&["accounts", "round"]),
+                        default: None,
+                        transform: None,
+                    },
+                    PopulationStrategy::SetOnce,
+                ),
+                TypedFieldMapping::new(
+                    "state.deployer".to_string(),
+                    MappingSource::FromTransaction {
+                        field: "fee_payer".to_string(),
+                    },
+                    PopulationStrategy::SetOnce,
+                ),
+                TypedFieldMapping::new(
+                    "state.compute_units".to_string(),
+                    MappingSource::FromTransaction {
+                        field: "compute_units".to_string(),
+                    },
+                    PopulationStrategy::LastWrite,
+                ),
+            ],
+            true,
+        );
+        let spec = TypedStreamSpec::<Value>::new(
+            "Round".to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.round".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![handler],
+        );
+        let bytecode = MultiEntityBytecode::from_single("Round".to_string(), spec, 0);
+        let mut vm = VmContext::new();
+
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                json!({
+                    "data": {},
+                    "accounts": {"round": "round-1"},
+                    "__transaction": {"fee_payer": "payer-1", "fee": 5000, "compute_units": 42000},
+                }),
+                "DeployIxState",
+                None,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(mutations[0].patch["state"]["deployer"], json!("payer-1"));
+        assert_eq!(mutations[0].patch["state"]["compute_units"], json!(42000));
+    }
+
+    fn round_spec_with_conditional_and_sum() -> TypedStreamSpec<Value> {
+        let from_source = |target: &str, source: &str, population| {
+            TypedFieldMapping::new(
+                target.to_string(),
+                MappingSource::FromSource {
+                    path: FieldPath::new(&[source]),
+                    default: None,
+                    transform: None,
+                },
+                population,
+            )
+        };
+        let handler = TypedHandlerSpec::new(
+            SourceSpec::Source {
+                program_id: None,
+                discriminator: None,
+                type_name: "RoundState".to_string(),
+                serialization: None,
+                is_account: true,
+            },
+            KeyResolutionStrategy::Embedded {
+                primary_field: FieldPath::new(&["round_id"]),
+            },
+            vec![
+                from_source("id.round_id", "round_id", PopulationStrategy::LastWrite),
+                from_source("state.winner", "winner", PopulationStrategy::LastWrite)
+                    .with_condition(ConditionExpr {
+                        expression: "status == \"settled\"".to_string(),
+                        parsed: Some(ParsedCondition::Comparison {
+                            field: FieldPath::new(&["status"]),
+                            op: ComparisonOp::Equal,
+                            value: json!("settled"),
+                        }),
+                    }),
+                from_source("stats.total_deployed", "deployed", PopulationStrategy::Sum),
+            ],
+            true,
+        );
+        TypedStreamSpec::<Value>::new(
+            "OreRound".to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.round_id".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![handler],
+        )
+    }
+
+    fn round_bytecode_with_conditional_and_sum() -> MultiEntityBytecode {
+        MultiEntityBytecode::from_single(
+            "OreRound".to_string(),
+            round_spec_with_conditional_and_sum(),
+            0,
+        )
+    }
+
+    #[test]
+    fn test_patches_are_byte_identical_across_runs() {
+        let mapping = |target: &str, source: &str, population| {
+            TypedFieldMapping::new(
+                target.to_string(),
+                MappingSource::FromSource {
+                    path: FieldPath::new(&[source]),
+                    default: None,
+                    transform: None,
+                },
+                population,
+            )
+        };
+        let mut mappings = vec![mapping(
+            "id.round_id",
+            "round_id",
+            PopulationStrategy::LastWrite,
+        )];
+        for (section, field) in [("state", "zeta"), ("state", "alpha"), ("meta", "mid")] {
+            mappings.push(mapping(
+                &format!("{section}.{field}"),
+                field,
+                PopulationStrategy::LastWrite,
+            ));
+        }
+        for target in ["trades.sizes", "events.log", "audit.entries"] {
+            mappings.push(mapping(target, "size", PopulationStrategy::Append));
+        }
+        let handler = TypedHandlerSpec::new(
+            SourceSpec::Source {
+                program_id: None,
+                discriminator: None,
+                type_name: "RoundState".to_string(),
+                serialization: None,
+                is_account: true,
+            },
+            KeyResolutionStrategy::Embedded {
+                primary_field: FieldPath::new(&["round_id"]),
+            },
+            mappings,
+            true,
+        );
+        let spec = TypedStreamSpec::<Value>::new(
+            "OreRound".to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.round_id".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![handler],
+        );
+        let bytecode = MultiEntityBytecode::from_single("OreRound".to_string(), spec, 0);
+
+        // Each run gets fresh hash maps, so hash order would differ between them
+        let run = || {
+            let mut vm = VmContext::new();
+            let mut emitted = Vec::new();
+            for n in 0..5 {
+                let event = json!({
+                    "round_id": 1, "zeta": n, "alpha": n * 2, "mid": "m", "size": n,
+                });
+                let mutations = vm
+                    .process_event(&bytecode, event, "RoundState", None, None)
+                    .unwrap();
+                emitted.push(serde_json::to_string(&mutations).unwrap());
+            }
+            emitted
+        };
+        let first = run();
+        for _ in 0..10 {
+            assert_eq!(run(), first);
+        }
+
+        let mutation: Vec<Mutation> = serde_json::from_str(&first[1]).unwrap();
+        assert_eq!(
+            mutation[0].append,
+            vec!["audit.entries", "events.log", "trades.sizes"]
+        );
+        assert!(first[1].starts_with(
+            r#"[{"export":"OreRound","key":1,"patch":{"audit":{"entries":[1]},"events":{"log":[1]},"id":{"round_id":1},"meta":{"mid":"m"},"state":{"alpha":2,"zeta":1}"#
+        ));
+    }
+
+    #[test]
+    fn test_key_recipe_matches_the_compiled_entity_key() {
+        let mapping = |target: &str, source: &str| {
+            TypedFieldMapping::new(
+                target.to_string(),
+                MappingSource::FromSource {
+                    path: FieldPath::new(&[source]),
+                    default: None,
+                    transform: None,
+                },
+                PopulationStrategy::LastWrite,
+            )
+        };
+        let handler = TypedHandlerSpec::new(
+            SourceSpec::Source {
+                program_id: None,
+                discriminator: None,
+                type_name: "MinerState".to_string(),
+                serialization: None,
+                is_account: true,
+            },
+            KeyResolutionStrategy::Embedded {
+                primary_field: FieldPath::new(&["round_id"]),
+            },
+            vec![
+                mapping("id.round_id", "round_id"),
+                mapping("id.authority", "authority"),
+            ],
+            true,
+        );
+        let mut spec = TypedStreamSpec::<Value>::new(
+            "OreMiner".to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.round_id".to_string(), "id.authority".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![handler],
+        );
+        spec.field_mappings.insert(
+            "id.round_id".to_string(),
+            FieldTypeInfo::new("round_id".to_string(), "u64".to_string()),
+        );
+        let recipe = spec.key_recipe().unwrap();
+        let bytecode = MultiEntityBytecode::from_single("OreMiner".to_string(), spec, 0);
+
+        let mut vm = VmContext::new();
+        let round_id = u64::MAX;
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                json!({ "round_id": round_id, "authority": "GxF2" }),
+                "MinerState",
+                None,
+                None,
+            )
+            .unwrap();
+
+        // The key a generated `OreMinerKey` builds is the one frames carry
+        let key = round_id.to_string();
+        assert_eq!(mutations[0].key.to_string(), key);
+
+        let key_value = recipe.key_value(&key).unwrap();
+        let state = vm.get_entity_state(0, &key_value).unwrap();
+        assert_eq!(state["id"]["authority"], "GxF2");
+    }
+
+    #[test]
+    fn test_provenance_names_the_writing_opcode_and_mapping() {
+        let bytecode = round_bytecode_with_conditional_and_sum();
+        let handler = &bytecode.entities["OreRound"].handlers["RoundState"];
+        let mut vm = VmContext::new();
+        vm.set_entity_provenance("OreRound", true);
+
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                json!({"round_id": 7, "winner": "miner", "status": "settled", "deployed": 5}),
+                "RoundState",
+                None,
+                None,
+            )
+            .unwrap();
+        let provenance = mutations[0].provenance.as_ref().unwrap();
+
+        let winner = &provenance["state.winner"];
+        assert_eq!(winner.mapping.as_deref(), Some("handlers[0].mappings[1]"));
+        assert!(matches!(
+            handler[winner.opcode],
+            OpCode::ConditionalSetField { .. }
+        ));
+
+        let total = &provenance["stats.total_deployed"];
+        assert_eq!(total.mapping.as_deref(), Some("handlers[0].mappings[2]"));
+        assert!(matches!(handler[total.opcode], OpCode::SetFieldSum { .. }));
+
+        // Only fields in the patch are attributed
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                json!({"round_id": 7, "winner": "other", "status": "open", "deployed": 3}),
+                "RoundState",
+                None,
+                None,
+            )
+            .unwrap();
+        let provenance = mutations[0].provenance.as_ref().unwrap();
+        assert!(!provenance.contains_key("state.winner"));
+        assert_eq!(
+            provenance["stats.total_deployed"].mapping.as_deref(),
+            Some("handlers[0].mappings[2]")
+        );
+    }
+
+    #[test]
+    fn test_provenance_is_off_by_default_and_not_serialized() {
+        let bytecode = round_bytecode_with_conditional_and_sum();
+        let mut vm = VmContext::new();
+        vm.set_provenance_mode(false);
+
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                json!({"round_id": 7, "winner": "miner", "status": "settled", "deployed": 5}),
+                "RoundState",
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(mutations[0].provenance.is_none());
+        assert!(serde_json::to_value(&mutations[0])
+            .unwrap()
+            .get("provenance")
+            .is_none());
+    }
+
+    #[test]
+    fn test_panicking_evaluator_is_reported_without_partial_mutations() {
+        let bytecode = MultiEntityBytecode::new()
+            .add_entity_with_evaluator(
+                "OreRound".to_string(),
+                round_spec_with_conditional_and_sum(),
+                0,
+                Some(|state: &mut Value, _: Option<u64>, _: i64| {
+                    let total = state["stats"]["total_deployed"].as_i64().unwrap_or(0);
+                    state["stats"]["doubled"] = json!(total * 2);
+                    if state["state"]["winner"] == json!("panic") {
+                        panic!("evaluator blew up");
+                    }
+                    Ok(())
+                }),
+            )
+            .build();
+        let event = |winner: &str, deployed: i64| json!({"round_id": 7, "winner": winner, "status": "settled", "deployed": deployed});
+        let mut vm = VmContext::new();
+
+        vm.process_event(&bytecode, event("miner", 5), "RoundState", None, None)
+            .unwrap();
+
+        let err = vm
+            .process_event(&bytecode, event("panic", 100), "RoundState", None, None)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>(),
+            Some(&VmError::HandlerPanicked {
+                entity: "OreRound".to_string(),
+                event_type: "RoundState".to_string(),
+                message: "evaluator blew up".to_string(),
+            })
+        );
+        assert_eq!(vm.handler_panics(), 1);
+        assert!(vm.registers.iter().all(Value::is_null));
+        let stored = vm.states[&0].data.get(&json!(7)).unwrap().clone();
+        assert_eq!(stored["stats"]["total_deployed"], json!(5));
+        assert_eq!(stored["state"]["winner"], json!("miner"));
+
+        // The next event sees none of the abandoned run's writes
+        let mutations = vm
+            .process_event(&bytecode, event("other", 3), "RoundState", None, None)
+            .unwrap();
+        assert_eq!(mutations.len(), 1);
+        assert_eq!(mutations[0].patch["stats"]["total_deployed"], json!(8));
+        assert_eq!(mutations[0].patch["state"]["winner"], json!("other"));
+        assert_eq!(vm.handler_panics(), 1);
+    }
+
+    #[test]
+    fn test_debug_snapshot_dumps_state_tables() {
+        let bytecode = round_bytecode_with_conditional_and_sum();
+        let mut vm = VmContext::new();
+        vm.process_event(
+            &bytecode,
+            json!({"round_id": 7, "winner": "miner", "status": "settled", "deployed": 5}),
+            "RoundState",
+            None,
+            None,
+        )
+        .unwrap();
+
+        let snapshot = vm.debug_snapshot();
+        assert!(
+            snapshot["counters"]["instructions_executed"]
+                .as_u64()
+                .unwrap()
+                > 0
+        );
+        let table = &snapshot["states"]["0"];
+        assert_eq!(table["entities"][0]["key"], json!(7));
+        assert_eq!(
+            table["entities"][0]["state"]["stats"]["total_deployed"],
+            json!(5)
+        );
+    }
+
+    #[test]
+    fn test_oversized_entities_lose_their_largest_arrays_first() {
+        const CAP: usize = 1_000;
+        let handler = vec![
+            OpCode::LoadConstant {
+                value: json!("token"),
+                dest: 20,
+            },
+            OpCode::ReadOrInitState {
+                state_id: 0,
+                key: 20,
+                default: json!({ "mint": "token" }),
+                dest: 2,
+            },
+            OpCode::LoadEventField {
+                path: FieldPath::new(&["event"]),
+                dest: 10,
+                default: None,
+            },
+            OpCode::AppendToArray {
+                object: 2,
+                path: "events".to_string(),
+                value: 10,
+                mapping: None,
+                aggregates: vec![],
+            },
+            OpCode::LoadEventField {
+                path: FieldPath::new(&["trade"]),
+                dest: 11,
+                default: None,
+            },
+            OpCode::AppendToArray {
+                object: 2,
+                path: "trades.history".to_string(),
+                value: 11,
+                mapping: None,
+                aggregates: vec![],
+            },
+            OpCode::UpdateState {
+                state_id: 0,
+                key: 20,
+                value: 2,
+            },
+        ];
+        let mut vm = VmContext::new_with_config(StateTableConfig {
+            max_entity_bytes: Some(CAP),
+            ..Default::default()
+        });
+        let append = |vm: &mut VmContext, i: usize| {
+            let event = json!({ "event": format!("{i:03}{}", "e".repeat(200)), "trade": format!("{i:03}{}", "t".repeat(40)) });
+            vm.execute_handler(&handler, &event, "test", 0, "Test", None, None)
+                .unwrap();
+        };
+        // Indexes of the appends an array still holds
+        let newest = |items: &Value| -> Vec<usize> {
+            items
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item.as_str().unwrap()[..3].parse().unwrap())
+                .collect()
+        };
+
+        for i in 0..4 {
+            append(&mut vm, i);
+        }
+        let state = vm.states[&0].data.get(&json!("token")).unwrap().clone();
+        assert_eq!(
+            newest(&state["events"]),
+            vec![1, 2, 3],
+            "events are cut first"
+        );
+        assert_eq!(newest(&state["trades"]["history"]), vec![0, 1, 2, 3]);
+
+        for i in 4..20 {
+            append(&mut vm, i);
+        }
+        let table = &vm.states[&0];
+        let state = table.data.get(&json!("token")).unwrap().clone();
+        // Once events shrank below trades, trades were cut too, oldest first
+        let events = newest(&state["events"]);
+        let trades = newest(&state["trades"]["history"]);
+        assert!(events.len() < 4, "{events:?}");
+        assert!(trades.len() > 1 && trades.len() < 20, "{trades:?}");
+        for items in [&events, &trades] {
+            assert_eq!(*items, (20 - items.len()..20).collect::<Vec<_>>());
+        }
+
+        let bytes = estimate_json_size(&state);
+        assert!(bytes <= CAP);
+        assert_eq!(table.entity_sizes.get(&json!("token")), Some(bytes));
+        assert_eq!(table.entity_sizes.total_bytes(), bytes);
+        assert!(table.entity_sizes.truncations() > 0);
+        assert_eq!(
+            vm.entity_size_report(1)[&table.entity_name]["largest"][0]["bytes"],
+            json!(bytes)
+        );
+        let memory = &vm.memory_report()[&table.entity_name];
+        assert_eq!(memory["state_table_entity_count"], json!(1));
+        assert_eq!(memory["state_table_entity_bytes"], json!(bytes));
+    }
+
+    #[test]
+    fn test_exported_state_restores_into_a_fresh_vm() {
+        let bytecode = round_bytecode_with_conditional_and_sum();
+        let round = |deployed: u64| json!({"round_id": 7, "winner": "miner", "status": "settled", "deployed": deployed});
+        let mut vm = VmContext::new();
+        vm.process_event(&bytecode, round(5), "RoundState", None, None)
+            .unwrap();
+
+        let exported = vm.export_state();
+        assert_eq!(exported["version"], json!(VM_STATE_VERSION));
+
+        let mut restored = VmContext::new();
+        assert_eq!(restored.import_state(&exported).unwrap(), 1);
+        assert_eq!(
+            restored.get_entity_state(0, &json!(7)),
+            vm.get_entity_state(0, &json!(7))
+        );
+
+        // Aggregates carry on from the restored state
+        restored
+            .process_event(&bytecode, round(3), "RoundState", None, None)
+            .unwrap();
+        let state = restored.get_entity_state(0, &json!(7)).unwrap();
+        assert_eq!(state["stats"]["total_deployed"], json!(8));
+    }
+
+    #[test]
+    fn test_exported_state_keeps_unique_counts() {
+        let add_trader = |trader: &str| {
+            vec![
+                OpCode::LoadConstant {
+                    value: json!("round"),
+                    dest: 20,
+                },
+                OpCode::ReadOrInitState {
+                    state_id: 0,
+                    key: 20,
+                    default: json!({}),
+                    dest: 2,
+                },
+                OpCode::LoadConstant {
+                    value: json!(trader),
+                    dest: 10,
+                },
+                OpCode::AddToUniqueSet {
+                    state_id: 0,
+                    set_name: "traders_unique_set".to_string(),
+                    value: 10,
+                    count_object: 2,
+                    count_path: "traders".to_string(),
+                    key: 20,
+                    mapping: None,
+                },
+                OpCode::UpdateState {
+                    state_id: 0,
+                    key: 20,
+                    value: 2,
+                },
+            ]
+        };
+        let run = |vm: &mut VmContext, trader: &str| {
+            vm.execute_handler(
+                &add_trader(trader),
+                &json!({}),
+                "test",
+                0,
+                "Round",
+                None,
+                None,
+            )
+            .unwrap();
+        };
+
+        let mut vm = VmContext::new();
+        run(&mut vm, "alice");
+        run(&mut vm, "bob");
+
+        let mut restored = VmContext::new();
+        restored.import_state(&vm.export_state()).unwrap();
+        run(&mut restored, "alice");
+        assert_eq!(
+            restored.get_entity_state(0, &json!("round")).unwrap()["traders"],
+            json!(2)
+        );
+        run(&mut restored, "carol");
+        assert_eq!(
+            restored.get_entity_state(0, &json!("round")).unwrap()["traders"],
+            json!(3)
+        );
+    }
+
+    #[test]
+    fn test_import_state_rejects_tables_of_other_entities() {
+        let bytecode = round_bytecode_with_conditional_and_sum();
+        let mut vm = VmContext::new();
+        vm.process_event(
+            &bytecode,
+            json!({"round_id": 7, "winner": "miner", "status": "settled", "deployed": 5}),
+            "RoundState",
+            None,
+            None,
+        )
+        .unwrap();
+        let mut exported = vm.export_state();
+        let (_, table) = exported["states"]
+            .as_object_mut()
+            .unwrap()
+            .iter_mut()
+            .next()
+            .unwrap();
+        table["entity_name"] = json!("Miner");
+
+        let err = vm.import_state(&exported).unwrap_err();
+        assert!(err.to_string().contains("Miner"), "{}", err);
+    }
+
+    #[test]
+    fn test_import_state_rejects_other_versions() {
+        let bytecode = round_bytecode_with_conditional_and_sum();
+        let mut vm = VmContext::new();
+        vm.process_event(
+            &bytecode,
+            json!({"round_id": 7, "winner": "miner", "status": "settled", "deployed": 5}),
+            "RoundState",
+            None,
+            None,
+        )
+        .unwrap();
+        let mut exported = vm.export_state();
+        exported["version"] = json!(VM_STATE_VERSION + 1);
+
+        let mut restored = VmContext::new();
+        let err = restored.import_state(&exported).unwrap_err();
+        assert!(err.to_string().contains("not supported"), "{}", err);
+        assert!(restored.get_entity_state(0, &json!(7)).is_none());
+
+        let err = restored
+            .import_state(&json!({"version": VM_STATE_VERSION, "states": []}))
+            .unwrap_err();
+        assert!(!err.to_string().is_empty());
+    }
+
+    fn config_spec(entity: &str, program_id: Option<&str>) -> TypedStreamSpec<Value> {
+        let handler = TypedHandlerSpec::new(
+            SourceSpec::Source {
+                program_id: program_id.map(str::to_string),
+                discriminator: None,
+                type_name: "amm::ConfigState".to_string(),
+                serialization: None,
+                is_account: true,
+            },
+            KeyResolutionStrategy::Embedded {
+                primary_field: FieldPath::new(&["authority"]),
+            },
+            vec![
+                TypedFieldMapping::new(
+                    "id.authority".to_string(),
+                    MappingSource::FromSource {
+                        path: FieldPath::new(&["authority"]),
+                        default: None,
+                        transform: None,
+                    },
+                    PopulationStrategy::LastWrite,
+                ),
+                TypedFieldMapping::new(
+                    "fee".to_string(),
+                    MappingSource::FromSource {
+                        path: FieldPath::new(&["fee"]),
+                        default: None,
+                        transform: None,
+                    },
+                    PopulationStrategy::LastWrite,
+                ),
+            ],
+            true,
+        );
+        TypedStreamSpec::<Value>::new(
+            entity.to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.authority".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![handler],
+        )
+    }
+
+    #[test]
+    fn test_colliding_event_types_are_routed_by_program() {
+        const V1: &str = "AmmV111111111111111111111111111111111111111";
+        const V2: &str = "AmmV211111111111111111111111111111111111111";
+        let bytecode = MultiEntityBytecode::new()
+            .add_entity(
+                "AmmV1Config".to_string(),
+                config_spec("AmmV1Config", Some(V1)),
+                0,
+            )
+            .add_entity(
+                "AmmV2Config".to_string(),
+                config_spec("AmmV2Config", Some(V2)),
+                0,
+            )
+            .build();
+        assert_eq!(
+            bytecode.event_routing[&RouteKey::for_program("amm::ConfigState", V2)],
+            ["AmmV2Config"]
+        );
+
+        let mut vm = VmContext::new();
+        let config = json!({"authority": "admin", "fee": 30});
+        let context = UpdateContext::new_account(1, "sig".to_string(), 1).with_program_id(V2);
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                config.clone(),
+                "amm::ConfigState",
+                Some(&context),
+                None,
+            )
+            .unwrap();
+        assert_eq!(mutations.len(), 1);
+        assert_eq!(mutations[0].export, "AmmV2Config");
+        assert_eq!(mutations[0].patch["fee"], json!(30));
+
+        // Another program's event of the same type reaches neither entity
+        let context = context.with_program_id("Other1111111111111111111111111111111111111");
+        let mutations = vm
+            .process_event(
+                &bytecode,
+                config.clone(),
+                "amm::ConfigState",
+                Some(&context),
+                None,
+            )
+            .unwrap();
+        assert!(mutations.is_empty());
+
+        let err = vm
+            .process_event(&bytecode, config, "amm::ConfigState", None, None)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>(),
+            Some(&VmError::AmbiguousEventType {
+                event_type: "amm::ConfigState".to_string(),
+                programs: vec![V1.to_string(), V2.to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_unambiguous_event_types_route_without_a_program_id() {
+        const V1: &str = "AmmV111111111111111111111111111111111111111";
+        let bytecode = MultiEntityBytecode::new()
+            .add_entity(
+                "AmmV1Config".to_string(),
+                config_spec("AmmV1Config", Some(V1)),
+                0,
+            )
+            .add_entity("AnyConfig".to_string(), config_spec("AnyConfig", None), 0)
+            .build();
+
+        assert_eq!(
+            bytecode.route("amm::ConfigState", None).unwrap(),
+            ["AnyConfig", "AmmV1Config"]
+        );
+        assert_eq!(
+            bytecode
+                .route(
+                    "amm::ConfigState",
+                    Some("Other1111111111111111111111111111111111111")
+                )
+                .unwrap(),
+            ["AnyConfig"]
+        );
+        assert!(bytecode.route("amm::PoolState", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_closed_account_deletes_its_entity_and_blocks_late_updates() {
+        let mut bytecode = validated_mint_bytecode();
+        bytecode.entities.get_mut("Mint").unwrap().delete_on = Some(DeleteTrigger::AccountClosed);
+        let mint = |supply: u64| {
+            json!({
+                "__account_address": "MintAccount1111111111111111111111111111111",
+                "accounts": { "mint": "So11111111111111111111111111111111111111112" },
+                "data": { "supply": supply, "name": "Wrapped SOL" },
+            })
+        };
+        let key = json!("So11111111111111111111111111111111111111112");
+
+        let mut vm = VmContext::new();
+        let context = UpdateContext::new_account(10, "sig".to_string(), 1);
+        vm.process_event(&bytecode, mint(1000), "MintState", Some(&context), None)
+            .unwrap();
+        assert!(vm.get_entity_state(0, &key).is_some());
+
+        let tombstones =
+            vm.close_account(&bytecode, "MintAccount1111111111111111111111111111111", 12);
+        assert_eq!(tombstones.len(), 1);
+        assert!(tombstones[0].tombstone);
+        assert_eq!(tombstones[0].export, "Mint");
+        assert_eq!(tombstones[0].key, key);
+        assert!(vm.get_entity_state(0, &key).is_none());
+
+        // An update written before the close arrives late
+        let late = UpdateContext::new_account(11, "sig".to_string(), 2);
+        let mutations = vm
+            .process_event(&bytecode, mint(900), "MintState", Some(&late), None)
+            .unwrap();
+        assert!(mutations.is_empty());
+        assert!(vm.get_entity_state(0, &key).is_none());
+
+        // The account is opened again after the close
+        let reopened = UpdateContext::new_account(13, "sig".to_string(), 3);
+        let mutations = vm
+            .process_event(&bytecode, mint(5), "MintState", Some(&reopened), None)
+            .unwrap();
+        assert_eq!(mutations.len(), 1);
+        assert_eq!(
+            vm.get_entity_state(0, &key).unwrap()["state"]["supply"],
+            json!(5)
+        );
+
+        // Entities not deleted on close are left alone
+        bytecode.entities.get_mut("Mint").unwrap().delete_on = None;
+        assert!(vm
+            .close_account(&bytecode, "MintAccount1111111111111111111111111111111", 14)
+            .is_empty());
+        assert!(vm.get_entity_state(0, &key).is_some());
     }
 }
@@ -0,0 +1,231 @@
+//! The VM on a thread of its own.
+//!
+//! [`VmHandle::spawn`] moves a [`VmContext`] onto a dedicated thread. Clones
+//! of the handle send it jobs over a bounded channel and await the result on
+//! a oneshot, so event handlers never hold a lock across, or block an async
+//! worker on, the VM. Jobs run one at a time in the order they were sent.
+//!
+//! There is one VM thread rather than a set of shards: PDA lookups, shared
+//! lookup indexes and the resolver cache span entities, so events can't be
+//! split between VMs without splitting that state too.
+//!
+//! Runtime resolvers are handed off by [`VmHandle::resolve_and_apply`]: the
+//! requests are split on the VM thread, the backend is awaited by the caller
+//! and its answers are applied in a second job, so the VM keeps processing
+//! events while resolvers are in flight.
+
+use crate::compiler::MultiEntityBytecode;
+use crate::runtime_resolvers::{ResolverHandoff, RuntimeResolver};
+use crate::vm::{ResolverRequest, VmContext};
+use crate::Mutation;
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+
+/// Jobs that may wait for the VM thread before senders wait too
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+type Job = Box<dyn FnOnce(&mut VmContext) + Send>;
+
+/// Why a job sent to the VM thread has no result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmCallError {
+    /// The VM thread has stopped
+    Stopped,
+    /// The job panicked. The VM keeps running with the state the job left.
+    Panicked,
+}
+
+impl fmt::Display for VmCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmCallError::Stopped => f.write_str("the VM thread has stopped"),
+            VmCallError::Panicked => f.write_str("the VM job panicked"),
+        }
+    }
+}
+
+impl std::error::Error for VmCallError {}
+
+/// Sends jobs to the VM thread. The thread stops once every clone is dropped.
+#[derive(Clone)]
+pub struct VmHandle {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl fmt::Debug for VmHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VmHandle")
+            .field("queued", &(self.jobs.max_capacity() - self.jobs.capacity()))
+            .finish()
+    }
+}
+
+impl VmHandle {
+    /// Run `vm` on a new thread with a queue of [`DEFAULT_QUEUE_CAPACITY`]
+    pub fn spawn(vm: VmContext) -> Self {
+        Self::spawn_with_capacity(vm, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    pub fn spawn_with_capacity(mut vm: VmContext, capacity: usize) -> Self {
+        Self::start(capacity, move |job| job(&mut vm))
+    }
+
+    /// Run jobs on a VM that is also shared behind a lock. The lock is only
+    /// taken on the VM thread.
+    #[deprecated(note = "move the VmContext into VmHandle::spawn instead of sharing it")]
+    pub fn spawn_shared(vm: Arc<Mutex<VmContext>>) -> Self {
+        Self::start(DEFAULT_QUEUE_CAPACITY, move |job| {
+            job(&mut vm.lock().unwrap_or_else(|e| e.into_inner()))
+        })
+    }
+
+    fn start(capacity: usize, mut run: impl FnMut(Job) + Send + 'static) -> Self {
+        let (jobs, mut queue) = mpsc::channel::<Job>(capacity.max(1));
+        std::thread::Builder::new()
+            .name("hyperstack-vm".to_string())
+            .spawn(move || {
+                while let Some(job) = queue.blocking_recv() {
+                    run(job);
+                }
+                tracing::debug!("VM thread stopped");
+            })
+            .expect("failed to spawn the VM thread");
+        Self { jobs }
+    }
+
+    /// Run `f` on the VM thread and wait for its result
+    pub async fn call<R, F>(&self, f: F) -> Result<R, VmCallError>
+    where
+        F: FnOnce(&mut VmContext) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        self.jobs
+            .send(job(f, reply))
+            .await
+            .map_err(|_| VmCallError::Stopped)?;
+        result.await.unwrap_or(Err(VmCallError::Stopped))
+    }
+
+    /// [`call`](Self::call) for code outside the async runtime, such as a
+    /// `spawn_blocking` closure. Panics if called from an async context.
+    pub fn blocking_call<R, F>(&self, f: F) -> Result<R, VmCallError>
+    where
+        F: FnOnce(&mut VmContext) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        self.jobs
+            .blocking_send(job(f, reply))
+            .map_err(|_| VmCallError::Stopped)?;
+        result.blocking_recv().unwrap_or(Err(VmCallError::Stopped))
+    }
+
+    /// Resolve `requests` with `resolver` and apply the results. Only taking
+    /// the requests and applying the results run on the VM thread.
+    pub async fn resolve_and_apply<R: RuntimeResolver + ?Sized>(
+        &self,
+        resolver: &R,
+        bytecode: Arc<MultiEntityBytecode>,
+        requests: Vec<ResolverRequest>,
+    ) -> Vec<Mutation> {
+        if requests.is_empty() {
+            return Vec::new();
+        }
+
+        let Ok(handoff) = self
+            .call(move |vm| ResolverHandoff::take(vm, requests))
+            .await
+        else {
+            return Vec::new();
+        };
+        if handoff.is_empty() {
+            return Vec::new();
+        }
+
+        let resolved = handoff.resolve(resolver).await;
+        self.call(move |vm| handoff.apply(vm, &bytecode, resolved))
+            .await
+            .unwrap_or_default()
+    }
+}
+
+fn job<R, F>(f: F, reply: oneshot::Sender<Result<R, VmCallError>>) -> Job
+where
+    F: FnOnce(&mut VmContext) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    Box::new(move |vm| {
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(vm))).map_err(|panic| {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            tracing::error!(error = %message, "VM job panicked, continuing");
+            VmCallError::Panicked
+        });
+        let _ = reply.send(result);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime_resolvers::{ResolverBatchFuture, RuntimeResolverRequest};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn a_panicking_job_leaves_the_vm_running() {
+        let vm = VmHandle::spawn(VmContext::new());
+
+        let count = |vm: &mut VmContext| {
+            vm.instructions_executed += 1;
+            vm.instructions_executed
+        };
+
+        assert_eq!(vm.call(count).await, Ok(1));
+        assert_eq!(
+            vm.call(|_| panic!("bad job")).await,
+            Err::<(), _>(VmCallError::Panicked)
+        );
+        assert_eq!(vm.call(count).await, Ok(2));
+    }
+
+    struct FailingResolver;
+
+    impl RuntimeResolver for FailingResolver {
+        fn resolve_batch<'a>(
+            &'a self,
+            _requests: &'a [RuntimeResolverRequest],
+        ) -> ResolverBatchFuture<'a> {
+            Box::pin(async { Ok(HashMap::new()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn unanswered_resolver_requests_go_back_to_the_vm() {
+        let vm = VmHandle::spawn(VmContext::new());
+        let request = ResolverRequest {
+            cache_key: "token:mint_1".to_string(),
+            resolver: crate::ast::ResolverType::Token,
+            input: json!("mint_1"),
+        };
+
+        let mutations = vm
+            .resolve_and_apply(
+                &FailingResolver,
+                Arc::new(MultiEntityBytecode::new().build()),
+                vec![request],
+            )
+            .await;
+
+        assert!(mutations.is_empty());
+        let requeued = vm.call(|vm| vm.take_resolver_requests()).await.unwrap();
+        assert_eq!(requeued.len(), 1);
+        assert_eq!(requeued[0].cache_key, "token:mint_1");
+    }
+}
@@ -0,0 +1,206 @@
+//! Spec bugs detected while executing handlers.
+//!
+//! By default the VM records these as warnings on the event's canonical log
+//! and carries on, dropping whatever data they affect. With strict mode
+//! enabled (see [`VmContext::set_strict_mode`](crate::vm::VmContext::set_strict_mode)
+//! or `HYPERSTACK_STRICT_MODE=1`) they fail the handler instead, so CI and
+//! staging surface them as errors.
+
+use crate::event_validation::ValidationReport;
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    /// An account state event resolved its entity key to null.
+    NullKey {
+        entity: String,
+        event_type: String,
+        register: usize,
+    },
+    /// A handler changed fields but had no primary key to emit them under.
+    NullPrimaryKey {
+        entity: String,
+        event_type: String,
+        dirty_fields: usize,
+    },
+    /// A lookup index entry was remapped to a different primary key. Outside
+    /// strict mode this is only logged at debug level, not recorded as a warning.
+    KeyCollision(Box<KeyCollision>),
+    /// A handler referenced a state table that does not exist.
+    MissingStateTable {
+        entity: String,
+        event_type: String,
+        state_id: u32,
+    },
+    /// Float arithmetic produced NaN or infinity.
+    NonFiniteNumber { left: f64, right: f64 },
+    /// A handler or computed field evaluator panicked. Raised in strict and
+    /// lenient mode alike; nothing the handler changed is emitted.
+    HandlerPanicked {
+        entity: String,
+        event_type: String,
+        message: String,
+    },
+    /// An event type routed for several programs arrived without the program
+    /// that emitted it. Raised in strict and lenient mode alike; the event is
+    /// not processed.
+    AmbiguousEventType {
+        event_type: String,
+        programs: Vec<String>,
+    },
+    /// An event failed validation in strict validation mode, see
+    /// [`event_validation`](crate::event_validation). No handler ran.
+    InvalidEvent(ValidationReport),
+}
+
+/// The remap behind a [`VmError::KeyCollision`], boxed to keep `VmError` small
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyCollision {
+    pub entity: String,
+    pub event_type: String,
+    pub index: String,
+    pub lookup_value: Value,
+    pub existing: Value,
+    pub incoming: Value,
+}
+
+impl VmError {
+    /// Short, stable name for metrics and error budgets.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VmError::NullKey { .. } => "null_key",
+            VmError::NullPrimaryKey { .. } => "null_primary_key",
+            VmError::KeyCollision(_) => "key_collision",
+            VmError::MissingStateTable { .. } => "missing_state_table",
+            VmError::NonFiniteNumber { .. } => "non_finite_number",
+            VmError::HandlerPanicked { .. } => "handler_panicked",
+            VmError::AmbiguousEventType { .. } => "ambiguous_event_type",
+            VmError::InvalidEvent(_) => "invalid_event",
+        }
+    }
+
+    /// Entity whose handler hit the error, if raised inside a handler.
+    pub fn entity(&self) -> Option<&str> {
+        match self {
+            VmError::NullKey { entity, .. }
+            | VmError::NullPrimaryKey { entity, .. }
+            | VmError::MissingStateTable { entity, .. }
+            | VmError::HandlerPanicked { entity, .. } => Some(entity),
+            VmError::KeyCollision(collision) => Some(&collision.entity),
+            VmError::NonFiniteNumber { .. }
+            | VmError::AmbiguousEventType { .. }
+            | VmError::InvalidEvent(_) => None,
+        }
+    }
+
+    /// Event type being processed, if raised inside a handler.
+    pub fn event_type(&self) -> Option<&str> {
+        match self {
+            VmError::NullKey { event_type, .. }
+            | VmError::NullPrimaryKey { event_type, .. }
+            | VmError::MissingStateTable { event_type, .. }
+            | VmError::HandlerPanicked { event_type, .. }
+            | VmError::AmbiguousEventType { event_type, .. } => Some(event_type),
+            VmError::KeyCollision(collision) => Some(&collision.event_type),
+            VmError::InvalidEvent(report) => Some(&report.event_type),
+            VmError::NonFiniteNumber { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::NullKey {
+                event_type,
+                register,
+                ..
+            } => write!(
+                f,
+                "ReadOrInitState: key register {} is NULL for account state, event_type={}",
+                register, event_type
+            ),
+            VmError::NullPrimaryKey {
+                entity,
+                dirty_fields,
+                ..
+            } => write!(
+                f,
+                "Skipping mutation for entity '{}': null_primary_key (dirty_fields={})",
+                entity, dirty_fields
+            ),
+            VmError::KeyCollision(collision) => write!(
+                f,
+                "Lookup index '{}' on entity '{}' remapped {} from {} to {}",
+                collision.index,
+                collision.entity,
+                collision.lookup_value,
+                collision.existing,
+                collision.incoming
+            ),
+            VmError::MissingStateTable {
+                entity, state_id, ..
+            } => write!(
+                f,
+                "State table {} not found for entity '{}'",
+                state_id, entity
+            ),
+            VmError::NonFiniteNumber { left, right } => write!(
+                f,
+                "Numeric operation on {} and {} produced a non-finite result",
+                left, right
+            ),
+            VmError::HandlerPanicked {
+                entity,
+                event_type,
+                message,
+            } => write!(
+                f,
+                "Handler for entity '{}' panicked on {}: {}",
+                entity, event_type, message
+            ),
+            VmError::AmbiguousEventType {
+                event_type,
+                programs,
+            } => write!(
+                f,
+                "Event type {} is routed for programs {} but arrived without a program id",
+                event_type,
+                programs.join(", ")
+            ),
+            VmError::InvalidEvent(report) => write!(f, "{}", report),
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+/// A failed `process_event`, detached from the original error so it can be
+/// held across await points. Keeps the [`VmError`] when there was one.
+#[derive(Debug, Clone)]
+pub struct HandlerError {
+    vm_error: Option<VmError>,
+    message: String,
+}
+
+impl HandlerError {
+    pub fn vm_error(&self) -> Option<&VmError> {
+        self.vm_error.as_ref()
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for HandlerError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        Self {
+            vm_error: error.downcast_ref::<VmError>().cloned(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
@@ -43,6 +43,12 @@ pub struct VmMetrics {
     pub pending_updates_queued: Counter<u64>,
     pub pending_updates_flushed: Counter<u64>,
     pub pending_updates_expired: Counter<u64>,
+    pub pending_queue_instruction_events: Gauge<i64>,
+    pub pending_instruction_events_queued: Counter<u64>,
+    pub pending_instruction_events_flushed: Counter<u64>,
+    pub pending_instruction_events_expired: Counter<u64>,
+    pub timestamp_wall_clock_fallbacks: Counter<u64>,
+    pub handler_panics: Counter<u64>,
 }
 
 #[cfg(feature = "otel")]
@@ -151,6 +157,32 @@ impl VmMetrics {
                 .u64_counter("hyperstack.vm.pending_updates.expired")
                 .with_description("Queued updates that expired")
                 .init(),
+            pending_queue_instruction_events: meter
+                .i64_gauge("hyperstack.vm.pending_queue.instruction_events")
+                .with_description("Total pending instruction events in queue")
+                .init(),
+            pending_instruction_events_queued: meter
+                .u64_counter("hyperstack.vm.pending_instruction_events.queued")
+                .with_description("Instruction events queued until their PDA is registered")
+                .init(),
+            pending_instruction_events_flushed: meter
+                .u64_counter("hyperstack.vm.pending_instruction_events.flushed")
+                .with_description("Queued instruction events flushed after PDA registration")
+                .init(),
+            pending_instruction_events_expired: meter
+                .u64_counter("hyperstack.vm.pending_instruction_events.expired")
+                .with_description("Queued instruction events that expired")
+                .init(),
+            timestamp_wall_clock_fallbacks: meter
+                .u64_counter("hyperstack.vm.timestamp.wall_clock_fallbacks")
+                .with_description(
+                    "Timestamps taken from the server clock because no block time was known",
+                )
+                .init(),
+            handler_panics: meter
+                .u64_counter("hyperstack.vm.handler_panics")
+                .with_description("Handlers and computed field evaluators that panicked")
+                .init(),
         }
     }
 }
@@ -268,6 +300,63 @@ pub fn record_pending_updates_expired(count: u64, entity: &str) {
 #[inline]
 pub fn record_pending_updates_expired(_count: u64, _entity: &str) {}
 
+#[cfg(feature = "otel")]
+pub fn record_pending_instruction_event_queued(entity: &str) {
+    get_vm_metrics()
+        .pending_instruction_events_queued
+        .add(1, &[KeyValue::new("entity", entity.to_string())]);
+}
+
+#[cfg(not(feature = "otel"))]
+#[inline]
+pub fn record_pending_instruction_event_queued(_entity: &str) {}
+
+#[cfg(feature = "otel")]
+pub fn record_pending_instruction_events_flushed(count: u64, entity: &str) {
+    get_vm_metrics()
+        .pending_instruction_events_flushed
+        .add(count, &[KeyValue::new("entity", entity.to_string())]);
+}
+
+#[cfg(not(feature = "otel"))]
+#[inline]
+pub fn record_pending_instruction_events_flushed(_count: u64, _entity: &str) {}
+
+#[cfg(feature = "otel")]
+pub fn record_pending_instruction_events_expired(count: u64, entity: &str) {
+    get_vm_metrics()
+        .pending_instruction_events_expired
+        .add(count, &[KeyValue::new("entity", entity.to_string())]);
+}
+
+#[cfg(not(feature = "otel"))]
+#[inline]
+pub fn record_pending_instruction_events_expired(_count: u64, _entity: &str) {}
+
+#[cfg(feature = "otel")]
+pub fn record_timestamp_wall_clock_fallback() {
+    get_vm_metrics().timestamp_wall_clock_fallbacks.add(1, &[]);
+}
+
+#[cfg(not(feature = "otel"))]
+#[inline]
+pub fn record_timestamp_wall_clock_fallback() {}
+
+#[cfg(feature = "otel")]
+pub fn record_handler_panic(entity: &str, event_type: &str) {
+    get_vm_metrics().handler_panics.add(
+        1,
+        &[
+            KeyValue::new("entity", entity.to_string()),
+            KeyValue::new("event_type", event_type.to_string()),
+        ],
+    );
+}
+
+#[cfg(not(feature = "otel"))]
+#[inline]
+pub fn record_handler_panic(_entity: &str, _event_type: &str) {}
+
 #[cfg(feature = "otel")]
 pub fn record_memory_stats(stats: &crate::vm::VmMemoryStats, entity: &str) {
     let m = get_vm_metrics();
@@ -303,6 +392,8 @@ pub fn record_memory_stats(stats: &crate::vm::VmMemoryStats, entity: &str) {
             .record(pq.estimated_memory_bytes as i64, attrs);
         m.pending_queue_oldest_age
             .record(pq.oldest_age_seconds as f64, attrs);
+        m.pending_queue_instruction_events
+            .record(pq.total_instruction_events as i64, attrs);
     }
 }
 
@@ -0,0 +1,281 @@
+//! Entity fields recorded as OpenTelemetry span attributes.
+//!
+//! Entities opt in with `#[entity(trace_fields = ["id.mint", "state.round_id"])]`.
+//! After an event is processed, each listed field is read from the emitted
+//! mutations and set on the `vm.process_event` span under its field path, so
+//! traces can be filtered by round or mint.
+//!
+//! When the `otel` feature is disabled, this is a no-op and no values are
+//! extracted.
+
+use crate::compiler::MultiEntityBytecode;
+use crate::Mutation;
+
+/// Longest string recorded as an attribute value. Longer values are cut to
+/// this many characters so free-form fields can't produce oversized spans.
+pub const MAX_TRACE_VALUE_CHARS: usize = 64;
+
+/// Record the configured trace fields of every mutated entity on the
+/// current span.
+///
+/// Values are taken from the mutation patch, falling back to the mutation
+/// key for primary key fields. When several mutations carry the same field,
+/// the first one wins. Only strings, numbers and booleans are recorded.
+#[cfg(feature = "otel")]
+pub fn record_trace_fields(bytecode: &MultiEntityBytecode, mutations: &[Mutation]) {
+    use std::collections::HashSet;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let span = tracing::Span::current();
+    if span.is_disabled() {
+        return;
+    }
+
+    let mut recorded = HashSet::new();
+    for mutation in mutations {
+        let Some(entity) = bytecode.entities.get(&mutation.export) else {
+            continue;
+        };
+
+        for field in &entity.trace_fields {
+            if recorded.contains(field.path.as_str()) {
+                continue;
+            }
+
+            let value = lookup_path(&mutation.patch, &field.path)
+                .or_else(|| field.is_primary_key.then_some(&mutation.key))
+                .and_then(attribute_value);
+            if let Some(value) = value {
+                span.set_attribute(field.path.clone(), value);
+                recorded.insert(field.path.as_str());
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+#[inline]
+pub fn record_trace_fields(_bytecode: &MultiEntityBytecode, _mutations: &[Mutation]) {}
+
+#[cfg(feature = "otel")]
+fn lookup_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.')
+        .try_fold(value, |current, segment| current.get(segment))
+}
+
+#[cfg(feature = "otel")]
+fn attribute_value(value: &serde_json::Value) -> Option<opentelemetry::Value> {
+    use serde_json::Value;
+
+    match value {
+        Value::String(s) => Some(truncate(s).into()),
+        Value::Bool(b) => Some((*b).into()),
+        Value::Number(n) => n
+            .as_i64()
+            .map(Into::into)
+            .or_else(|| n.as_u64().map(|n| n.to_string().into()))
+            .or_else(|| n.as_f64().map(Into::into)),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+#[cfg(feature = "otel")]
+fn truncate(value: &str) -> String {
+    match value.char_indices().nth(MAX_TRACE_VALUE_CHARS) {
+        Some((end, _)) => value[..end].to_string(),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use super::*;
+    use crate::ast::{
+        FieldPath, IdentitySpec, KeyResolutionStrategy, MappingSource, PopulationStrategy,
+        SourceSpec, TypedFieldMapping, TypedHandlerSpec, TypedStreamSpec,
+    };
+    use crate::compiler::TraceField;
+    use crate::vm::VmContext;
+    use opentelemetry::trace::{TraceResult, TracerProvider as _};
+    use opentelemetry::{Context, Key, Value};
+    use opentelemetry_sdk::export::trace::SpanData;
+    use opentelemetry_sdk::trace::{Span, SpanProcessor, TracerProvider};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Debug, Clone, Default)]
+    struct CapturedSpans(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanProcessor for CapturedSpans {
+        fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+        fn on_end(&self, span: SpanData) {
+            self.0.lock().unwrap().push(span);
+        }
+
+        fn force_flush(&self) -> TraceResult<()> {
+            Ok(())
+        }
+
+        fn shutdown(&mut self) -> TraceResult<()> {
+            Ok(())
+        }
+    }
+
+    impl CapturedSpans {
+        fn attributes(&self, span_name: &str) -> HashMap<Key, Value> {
+            let spans = self.0.lock().unwrap();
+            let span = spans
+                .iter()
+                .find(|span| span.name == span_name)
+                .unwrap_or_else(|| panic!("no span named {span_name}"));
+            span.attributes
+                .iter()
+                .map(|kv| (kv.key.clone(), kv.value.clone()))
+                .collect()
+        }
+    }
+
+    fn with_captured_spans(f: impl FnOnce()) -> CapturedSpans {
+        let captured = CapturedSpans::default();
+        let provider = TracerProvider::builder()
+            .with_span_processor(captured.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, f);
+        captured
+    }
+
+    fn round_bytecode() -> MultiEntityBytecode {
+        let mapping = |target: &str, source: &str| {
+            TypedFieldMapping::new(
+                target.to_string(),
+                MappingSource::FromSource {
+                    path: FieldPath::new(&[source]),
+                    default: None,
+                    transform: None,
+                },
+                PopulationStrategy::LastWrite,
+            )
+        };
+        let handler = TypedHandlerSpec::new(
+            SourceSpec::Source {
+                program_id: None,
+                discriminator: None,
+                type_name: "RoundState".to_string(),
+                serialization: None,
+                is_account: true,
+            },
+            KeyResolutionStrategy::Embedded {
+                primary_field: FieldPath::new(&["round_id"]),
+            },
+            vec![
+                mapping("id.round_id", "round_id"),
+                mapping("state.winner", "winner"),
+            ],
+            true,
+        );
+        let spec = TypedStreamSpec::<serde_json::Value>::new(
+            "OreRound".to_string(),
+            IdentitySpec {
+                primary_keys: vec!["id.round_id".to_string()],
+                lookup_indexes: vec![],
+            },
+            vec![handler],
+        )
+        .with_trace_fields(vec!["id.round_id".to_string(), "state.winner".to_string()]);
+
+        MultiEntityBytecode::from_single("OreRound".to_string(), spec, 0)
+    }
+
+    #[test]
+    fn trace_fields_are_compiled_from_the_spec() {
+        let bytecode = round_bytecode();
+        assert_eq!(
+            bytecode.entities["OreRound"].trace_fields,
+            vec![
+                TraceField {
+                    path: "id.round_id".to_string(),
+                    is_primary_key: true,
+                },
+                TraceField {
+                    path: "state.winner".to_string(),
+                    is_primary_key: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn process_event_span_carries_trace_fields() {
+        let bytecode = round_bytecode();
+        let mut vm = VmContext::new();
+
+        let captured = with_captured_spans(|| {
+            vm.process_event(
+                &bytecode,
+                json!({ "round_id": 42, "winner": "miner" }),
+                "RoundState",
+                None,
+                None,
+            )
+            .unwrap();
+        });
+
+        let attributes = captured.attributes("vm.process_event");
+        assert_eq!(attributes[&Key::new("id.round_id")], Value::I64(42));
+        assert_eq!(attributes[&Key::new("state.winner")], Value::from("miner"));
+    }
+
+    #[test]
+    fn falls_back_to_key_and_truncates_long_values() {
+        let bytecode = round_bytecode();
+        let mutations = vec![
+            Mutation::new(
+                "OreRound",
+                json!(42),
+                json!({ "state": { "winner": "w".repeat(100) } }),
+            ),
+            Mutation::new(
+                "OreRound",
+                json!(43),
+                json!({ "state": { "winner": "second" } }),
+            ),
+        ];
+
+        let captured = with_captured_spans(|| {
+            tracing::info_span!("vm.process_event")
+                .in_scope(|| record_trace_fields(&bytecode, &mutations));
+        });
+
+        let attributes = captured.attributes("vm.process_event");
+        assert_eq!(attributes[&Key::new("id.round_id")], Value::I64(42));
+        assert_eq!(
+            attributes[&Key::new("state.winner")],
+            Value::from("w".repeat(MAX_TRACE_VALUE_CHARS))
+        );
+    }
+
+    #[test]
+    fn skips_missing_and_structured_values() {
+        let bytecode = round_bytecode();
+        let mutations = vec![Mutation::new(
+            "OreRound",
+            json!([1, 2]),
+            json!({ "state": { "winner": { "nested": true } } }),
+        )];
+
+        let captured = with_captured_spans(|| {
+            tracing::info_span!("vm.process_event")
+                .in_scope(|| record_trace_fields(&bytecode, &mutations));
+        });
+
+        let attributes = captured.attributes("vm.process_event");
+        assert!(!attributes.contains_key(&Key::new("id.round_id")));
+        assert!(!attributes.contains_key(&Key::new("state.winner")));
+    }
+}
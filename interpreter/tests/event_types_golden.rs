@@ -0,0 +1,103 @@
+//! Golden tests for the event type enums generated for the ore stack.
+//!
+//! Run with `UPDATE_GOLDEN=1` to rewrite the files under `tests/golden`.
+
+use hyperstack_interpreter::versioned::load_stack_spec;
+use hyperstack_interpreter::{rust, typescript};
+use std::path::{Path, PathBuf};
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("interpreter should live in the workspace root")
+        .to_path_buf()
+}
+
+fn ore_stack_spec() -> hyperstack_interpreter::ast::SerializableStackSpec {
+    let ast = std::fs::read_to_string(
+        workspace_root().join("stacks/ore/.hyperstack/OreStream.stack.json"),
+    )
+    .expect("read ore stack AST");
+    load_stack_spec(&ast).expect("load ore stack AST")
+}
+
+/// Every block of `source` from a line beginning with `start` to the first
+/// line equal to `end` after the first line beginning with `last`
+fn blocks(source: &str, start: &str, last: &str, end: &str) -> Vec<String> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].starts_with(start) {
+            let last = i + lines[i..]
+                .iter()
+                .position(|line| line.starts_with(last))
+                .expect("block should have its last item");
+            let end = last
+                + lines[last..]
+                    .iter()
+                    .position(|line| *line == end)
+                    .expect("block should end");
+            blocks.push(lines[i..=end].join("\n"));
+            i = end;
+        }
+        i += 1;
+    }
+    blocks
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).expect("write golden file");
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).expect("read golden file");
+    assert_eq!(
+        actual, expected,
+        "{name} is out of date, rerun with UPDATE_GOLDEN=1"
+    );
+}
+
+#[test]
+fn ore_rust_event_types() {
+    let config = rust::RustStackConfig {
+        crate_name: "ore-stack".to_string(),
+        ..Default::default()
+    };
+    let output = rust::compile_stack_spec(ore_stack_spec(), Some(config)).expect("compile");
+
+    let mut sections = blocks(
+        &output.types_rs,
+        "/// Event types routed to",
+        "impl std::fmt::Display",
+        "}",
+    );
+    sections.extend(blocks(&output.entity_rs, "impl OreStreamStack", "}", "}"));
+
+    assert_eq!(sections.len(), 4, "{}", output.types_rs);
+    assert_golden("ore_event_types.rs.golden", &(sections.join("\n\n") + "\n"));
+}
+
+#[test]
+fn ore_typescript_event_types() {
+    let output = typescript::compile_stack_spec(ore_stack_spec(), None).expect("compile");
+
+    let mut sections = blocks(
+        &output.interfaces,
+        "/** Every event type routed to",
+        "export function classify",
+        "}",
+    );
+    sections.extend(blocks(
+        &output.stack_definition,
+        "  eventTypes: {",
+        "  eventTypes: {",
+        "  },",
+    ));
+
+    assert_eq!(sections.len(), 4, "{}", output.interfaces);
+    assert_golden("ore_event_types.ts.golden", &(sections.join("\n\n") + "\n"));
+}
@@ -0,0 +1,57 @@
+//! Compiles the Rust SDK generated with `with_cli` for the ore stack.
+
+use hyperstack_interpreter::rust::{compile_stack_spec, write_rust_crate, RustStackConfig};
+use hyperstack_interpreter::versioned::load_stack_spec;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("interpreter should live in the workspace root")
+        .to_path_buf()
+}
+
+#[test]
+fn generated_ore_cli_compiles() {
+    let root = workspace_root();
+    let ast = std::fs::read_to_string(root.join("stacks/ore/.hyperstack/OreStream.stack.json"))
+        .expect("read ore stack AST");
+    let stack_spec = load_stack_spec(&ast).expect("load ore stack AST");
+
+    let config = RustStackConfig {
+        crate_name: "ore-stack".to_string(),
+        url: Some("wss://ore.stack.usehyperstack.com".to_string()),
+        with_cli: true,
+        ..Default::default()
+    };
+    let output = compile_stack_spec(stack_spec, Some(config)).expect("compile ore stack");
+    let cli = output.cli.as_ref().expect("CLI should be generated");
+    assert!(cli.source.contains("Round {"));
+    assert!(cli.source.contains("OreRoundCommand::Latest(args)"));
+
+    let crate_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ore-stack-cli");
+    let _ = std::fs::remove_dir_all(&crate_dir);
+    write_rust_crate(&output, &crate_dir).expect("write generated crate");
+
+    // Build against the local SDK, pinned to the workspace's dependency versions
+    let sdk_path = root.join("rust/hyperstack-sdk");
+    let cargo_toml = output.cargo_toml.replace(
+        "hyperstack-sdk = \"0.2\"",
+        &format!("hyperstack-sdk = {{ path = {:?} }}", sdk_path),
+    ) + "\n[workspace]\n";
+    std::fs::write(crate_dir.join("Cargo.toml"), cargo_toml).expect("write Cargo.toml");
+    std::fs::copy(root.join("Cargo.lock"), crate_dir.join("Cargo.lock")).expect("copy lockfile");
+
+    let result = Command::new(env!("CARGO"))
+        .args(["check", "--quiet", "--bins"])
+        .current_dir(&crate_dir)
+        .env("CARGO_TARGET_DIR", root.join("target/tests/rust_cli"))
+        .output()
+        .expect("run cargo check");
+    assert!(
+        result.status.success(),
+        "generated CLI failed to compile:\n{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+}
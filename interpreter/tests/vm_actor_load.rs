@@ -0,0 +1,224 @@
+//! Load tests comparing a VM shared behind a `std::sync::Mutex`, the way
+//! event handlers used it, with the VM on its own thread behind a
+//! [`VmHandle`].
+//!
+//! Each run has handler tasks push `Trade` events into the VM on a two
+//! worker runtime and forward the mutations to a consumer, while a probe
+//! task ticks every millisecond. Event latency is the time from sending an
+//! event to holding its mutations, probe latency how late each tick ran.
+
+use hyperstack_interpreter::ast::{
+    FieldPath, IdentitySpec, KeyResolutionStrategy, MappingSource, PopulationStrategy, SourceSpec,
+    TypedFieldMapping, TypedHandlerSpec, TypedStreamSpec,
+};
+use hyperstack_interpreter::compiler::MultiEntityBytecode;
+use hyperstack_interpreter::vm::VmContext;
+use hyperstack_interpreter::{Mutation, VmHandle};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const HANDLERS: u64 = 16;
+const EVENTS_PER_HANDLER: u64 = 500;
+const PROBE_TICK: Duration = Duration::from_millis(1);
+
+fn trade_bytecode() -> Arc<MultiEntityBytecode> {
+    let mapping = |target: &str, source: &str| {
+        TypedFieldMapping::new(
+            target.to_string(),
+            MappingSource::FromSource {
+                path: FieldPath::new(&[source]),
+                default: None,
+                transform: None,
+            },
+            PopulationStrategy::LastWrite,
+        )
+    };
+    let trade = TypedStreamSpec::<Value>::new(
+        "Trade".to_string(),
+        IdentitySpec {
+            primary_keys: vec!["id.mint".to_string()],
+            lookup_indexes: vec![],
+        },
+        vec![TypedHandlerSpec::new(
+            SourceSpec::Source {
+                program_id: None,
+                discriminator: None,
+                type_name: "BuyIxState".to_string(),
+                serialization: None,
+                is_account: false,
+            },
+            KeyResolutionStrategy::Embedded {
+                primary_field: FieldPath::new(&["mint"]),
+            },
+            vec![
+                mapping("id.mint", "mint"),
+                mapping("stats.last_amount", "amount"),
+                mapping("stats.last_buyer", "buyer"),
+            ],
+            true,
+        )],
+    );
+    Arc::new(
+        MultiEntityBytecode::new()
+            .add_entity("Trade".to_string(), trade, 0)
+            .build(),
+    )
+}
+
+fn buy(handler: u64, n: u64) -> Value {
+    json!({
+        "mint": format!("mint-{}", n % 64),
+        "amount": n,
+        "buyer": format!("buyer-{handler}"),
+    })
+}
+
+#[derive(Clone)]
+enum Vm {
+    Locked(Arc<Mutex<VmContext>>),
+    Actor(VmHandle),
+}
+
+impl Vm {
+    async fn process(&self, bytecode: &Arc<MultiEntityBytecode>, event: Value) -> Vec<Mutation> {
+        match self {
+            Vm::Locked(vm) => {
+                let mut vm = vm.lock().unwrap_or_else(|e| e.into_inner());
+                vm.process_event(bytecode, event, "BuyIxState", None, None)
+                    .unwrap()
+            }
+            Vm::Actor(vm) => {
+                let bytecode = bytecode.clone();
+                vm.call(move |vm| {
+                    vm.process_event(&bytecode, event, "BuyIxState", None, None)
+                        .unwrap()
+                })
+                .await
+                .unwrap()
+            }
+        }
+    }
+}
+
+struct Run {
+    events: Vec<Duration>,
+    probes: Vec<Duration>,
+    elapsed: Duration,
+}
+
+fn p99(latencies: &[Duration]) -> Duration {
+    let mut sorted = latencies.to_vec();
+    sorted.sort();
+    sorted[(sorted.len() * 99 / 100).min(sorted.len() - 1)]
+}
+
+impl Run {
+    fn throughput(&self) -> f64 {
+        self.events.len() as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+async fn run(vm: Vm) -> Run {
+    let bytecode = trade_bytecode();
+    let (mutations_tx, mut mutations_rx) = mpsc::channel::<Vec<Mutation>>(1024);
+    let consumer = tokio::spawn(async move { while mutations_rx.recv().await.is_some() {} });
+
+    let (probe_tx, mut probe_rx) = mpsc::unbounded_channel();
+    let probe = tokio::spawn(async move {
+        let mut next = Instant::now() + PROBE_TICK;
+        loop {
+            tokio::time::sleep_until(next.into()).await;
+            let _ = probe_tx.send(next.elapsed());
+            next += PROBE_TICK;
+        }
+    });
+
+    let epoch = Instant::now();
+    let handlers: Vec<_> = (0..HANDLERS)
+        .map(|handler| {
+            let vm = vm.clone();
+            let bytecode = bytecode.clone();
+            let mutations_tx = mutations_tx.clone();
+            tokio::spawn(async move {
+                let mut latencies = Vec::new();
+                for n in 0..EVENTS_PER_HANDLER {
+                    let sent = Instant::now();
+                    let mutations = vm.process(&bytecode, buy(handler, n)).await;
+                    latencies.push(sent.elapsed());
+                    mutations_tx.send(mutations).await.unwrap();
+                }
+                latencies
+            })
+        })
+        .collect();
+    drop(mutations_tx);
+
+    let mut events = Vec::new();
+    for handler in handlers {
+        events.extend(handler.await.unwrap());
+    }
+    let elapsed = epoch.elapsed();
+    consumer.await.unwrap();
+    probe.abort();
+
+    let mut probes = Vec::new();
+    while let Ok(lateness) = probe_rx.try_recv() {
+        probes.push(lateness);
+    }
+
+    Run {
+        events,
+        probes,
+        elapsed,
+    }
+}
+
+fn locked() -> Vm {
+    Vm::Locked(Arc::new(Mutex::new(VmContext::new())))
+}
+
+fn actor() -> Vm {
+    Vm::Actor(VmHandle::spawn(VmContext::new()))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn actor_throughput_keeps_up_with_the_shared_lock() {
+    let locked = run(locked()).await;
+    let actor = run(actor()).await;
+
+    println!(
+        "locked: {:.0} events/s, p99 {:?}; actor: {:.0} events/s, p99 {:?}",
+        locked.throughput(),
+        p99(&locked.events),
+        actor.throughput(),
+        p99(&actor.events)
+    );
+    assert_eq!(actor.events.len() as u64, HANDLERS * EVENTS_PER_HANDLER);
+    assert!(
+        actor.throughput() >= locked.throughput() * 0.5,
+        "actor throughput {:.0}/s should keep up with the lock's {:.0}/s",
+        actor.throughput(),
+        locked.throughput()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn other_tasks_keep_running_while_events_wait_for_the_vm() {
+    let locked = run(locked()).await;
+    let actor = run(actor()).await;
+
+    println!(
+        "probe p99 while processing: locked {:?}, actor {:?}",
+        p99(&locked.probes),
+        p99(&actor.probes)
+    );
+    assert!(!actor.probes.is_empty());
+    assert!(
+        p99(&actor.probes) < Duration::from_millis(20),
+        "probe p99 {:?} should stay near its {:?} tick while the actor is busy",
+        p99(&actor.probes),
+        PROBE_TICK
+    );
+}
@@ -1,16 +1,22 @@
 use crate::auth::{AuthConfig, AuthToken, TokenTransport};
-use crate::config::{ConnectionConfig, HyperStackConfig};
-use crate::connection::{ConnectionManager, ConnectionState};
+use crate::config::{ConnectionConfig, HyperStackConfig, ReconnectBackoff};
+use crate::connection::{ConnectionEvent, ConnectionManager, ConnectionState};
 use crate::entity::Stack;
 use crate::error::{HyperStackError, SocketIssue};
 use crate::frame::Frame;
-use crate::store::{SharedStore, StoreConfig};
+use crate::merge::MergeBuilder;
+use crate::quality::{ConnectionQuality, QualityChange, QualityPolicy};
+use crate::scope::StreamScope;
+use crate::store::{DecodeMode, SharedStore, StoreConfig};
+use crate::telemetry::{self, FrameSampler};
 use crate::view::Views;
+use futures_util::{Stream, StreamExt};
 use std::future::Future;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::BroadcastStream;
 
 /// HyperStack client with typed views access.
 ///
@@ -54,6 +60,18 @@ impl<S: Stack> HyperStack<S> {
         self.connection.last_error().await
     }
 
+    /// Stream of the [`ConnectionEvent`]s from now on: disconnects, each
+    /// reconnect attempt and its delay, and reconnects. Events a slow
+    /// consumer falls too far behind on are skipped.
+    ///
+    /// Subscriptions are sent again on every reconnect, and list views
+    /// reconcile the store with their fresh snapshot, so watchers see
+    /// entities removed while the client was away as deletes.
+    pub fn connection_events(&self) -> impl Stream<Item = ConnectionEvent> + Send + 'static {
+        BroadcastStream::new(self.connection.subscribe_connection_events())
+            .filter_map(|event| async move { event.ok() })
+    }
+
     pub async fn last_socket_issue(&self) -> Option<SocketIssue> {
         self.connection.last_socket_issue().await
     }
@@ -62,6 +80,27 @@ impl<S: Stack> HyperStack<S> {
         self.connection.subscribe_socket_issues()
     }
 
+    /// Number of frames found missing from subscriptions so far.
+    ///
+    /// Every gap makes the client ask the server to resync the affected
+    /// subscription from a fresh snapshot.
+    pub fn frame_gaps(&self) -> u64 {
+        self.connection.frame_gaps()
+    }
+
+    /// Latest heartbeat round trip, recent frame gaps and throughput, and
+    /// the [`QualityTier`](crate::QualityTier) they add up to.
+    pub async fn connection_quality(&self) -> ConnectionQuality {
+        self.connection.connection_quality().await
+    }
+
+    /// Receive a [`QualityChange`] whenever the connection settles into a
+    /// different tier. List subscriptions have already been renegotiated
+    /// under the new tier's options when it arrives.
+    pub fn subscribe_quality_changes(&self) -> broadcast::Receiver<QualityChange> {
+        self.connection.subscribe_quality_changes()
+    }
+
     pub async fn disconnect(&self) {
         self.connection.disconnect().await;
     }
@@ -69,6 +108,18 @@ impl<S: Stack> HyperStack<S> {
     pub fn store(&self) -> &SharedStore {
         &self.store
     }
+
+    /// Create a [`StreamScope`] for running watch handlers that are
+    /// cancelled together.
+    pub fn scope(&self) -> StreamScope {
+        StreamScope::new(self.connection.clone())
+    }
+
+    /// Merge the updates of several views into one stream, ordered by slot.
+    /// See [`MergeBuilder`].
+    pub fn merge_streams<E: Send + 'static>(&self) -> MergeBuilder<E> {
+        MergeBuilder::new(self.connection.clone(), self.store.clone())
+    }
 }
 
 /// Builder for HyperStack with custom configuration.
@@ -107,13 +158,69 @@ impl<S: Stack> HyperStackBuilder<S> {
         self
     }
 
+    /// Back off exponentially between reconnect attempts instead of
+    /// following `reconnect_intervals`.
+    ///
+    /// ```ignore
+    /// let hs = HyperStack::<OreStack>::builder()
+    ///     .reconnect_backoff(ReconnectBackoff::new(
+    ///         Duration::from_millis(500),
+    ///         Duration::from_secs(30),
+    ///     ))
+    ///     .max_reconnect_attempts(20)
+    ///     .connect()
+    ///     .await?;
+    /// ```
+    pub fn reconnect_backoff(mut self, backoff: ReconnectBackoff) -> Self {
+        self.config.reconnect_backoff = Some(backoff);
+        self
+    }
+
+    /// Cap on server-suggested reconnect delays (`retry_after_ms`).
+    ///
+    /// When the server says how long to wait before reconnecting, that delay
+    /// replaces the next local backoff delay, up to this limit.
+    pub fn max_retry_hint(mut self, max: Duration) -> Self {
+        self.config.max_retry_hint = max;
+        self
+    }
+
     pub fn ping_interval(mut self, interval: Duration) -> Self {
         self.config.ping_interval = interval;
         self
     }
 
-    pub fn initial_data_timeout(mut self, timeout: Duration) -> Self {
-        self.config.initial_data_timeout = timeout;
+    /// Give up on a connection attempt whose WebSocket handshake hasn't
+    /// completed after `timeout` (default: 10s).
+    ///
+    /// A timed out attempt counts as a failed attempt, so with
+    /// `auto_reconnect` the client tries again up to `max_reconnect_attempts`
+    /// times before [`connect`](Self::connect) fails with
+    /// [`HyperStackError::Timeout`].
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = timeout;
+        self
+    }
+
+    /// How long reads such as [`ViewHandle::get`](crate::ViewHandle::get)
+    /// wait for a view's initial data (default: 5s).
+    ///
+    /// Override it per call with [`GetOptions`](crate::GetOptions).
+    pub fn snapshot_timeout(mut self, timeout: Duration) -> Self {
+        self.config.snapshot_timeout = timeout;
+        self
+    }
+
+    /// Alias for [`snapshot_timeout`](Self::snapshot_timeout).
+    pub fn initial_data_timeout(self, timeout: Duration) -> Self {
+        self.snapshot_timeout(timeout)
+    }
+
+    /// How long the server may take to acknowledge a subscription before
+    /// `get_with` and `established` fail with [`HyperStackError::Timeout`]
+    /// (default: 10s).
+    pub fn subscribe_ack_timeout(mut self, timeout: Duration) -> Self {
+        self.config.subscribe_ack_timeout = timeout;
         self
     }
 
@@ -127,6 +234,49 @@ impl<S: Stack> HyperStackBuilder<S> {
         self
     }
 
+    /// How snapshots holding entities that don't decode into their view's
+    /// type are applied.
+    ///
+    /// By default such entities are left out and reported on
+    /// [`ViewHandle::errors`](crate::ViewHandle::errors).
+    /// [`DecodeMode::Strict`] rejects the whole snapshot instead, so tests
+    /// fail fast on a type that no longer matches the server.
+    pub fn decode_mode(mut self, mode: DecodeMode) -> Self {
+        self.config.decode_mode = mode;
+        self
+    }
+
+    /// Ask the server how each subscription's initial load went.
+    ///
+    /// Subscription acks then carry [`SubscriptionDiagnostics`], available
+    /// from [`ViewHandle::diagnostics`](crate::ViewHandle::diagnostics). Meant
+    /// for tracking down slow initial loads; leave it off otherwise.
+    pub fn diagnostics(mut self, enabled: bool) -> Self {
+        self.config.diagnostics = enabled;
+        self
+    }
+
+    /// Downgrade list subscriptions while the connection is struggling.
+    ///
+    /// Quality is sampled every `ping_interval` from heartbeat round trips
+    /// and frame gaps. Once a new tier holds for the policy's
+    /// `hold_samples`, each list subscription is restarted with that tier's
+    /// [`SubscriptionOverrides`](crate::SubscriptionOverrides), and
+    /// restarted again with the original options once the connection
+    /// recovers.
+    pub fn quality_policy(mut self, policy: QualityPolicy) -> Self {
+        self.config.quality_policy = policy;
+        self
+    }
+
+    /// Record how long every `1 / rate`th frame takes to apply, as a
+    /// `hyperstack.frame` event. Only used with the `tracing` feature; see
+    /// [`telemetry`](crate::telemetry) for what is recorded.
+    pub fn frame_sample_rate(mut self, rate: f64) -> Self {
+        self.config.frame_sample_rate = rate;
+        self
+    }
+
     pub fn auth(mut self, auth: AuthConfig) -> Self {
         self.config.auth = Some(auth);
         self
@@ -221,6 +371,7 @@ impl<S: Stack> HyperStackBuilder<S> {
 
         let store_config = StoreConfig {
             max_entries_per_view: config.max_entries_per_view,
+            decode_mode: config.decode_mode,
         };
         let store = SharedStore::with_config(store_config);
         let store_clone = store.clone();
@@ -230,16 +381,24 @@ impl<S: Stack> HyperStackBuilder<S> {
         let connection_config: ConnectionConfig = config.clone().into();
         let connection = ConnectionManager::new(url, connection_config, frame_tx).await?;
 
+        let mut sampler = FrameSampler::new(config.frame_sample_rate);
         tokio::spawn(async move {
             while let Some(frame) = frame_rx.recv().await {
-                store_clone.apply_frame(frame).await;
+                match sampler.start() {
+                    Some(started) => {
+                        let (view, op) = (frame.entity.clone(), frame.op.clone());
+                        store_clone.apply_frame(frame).await;
+                        telemetry::frame_processed(started, &view, &op);
+                    }
+                    None => store_clone.apply_frame(frame).await,
+                }
             }
         });
 
         let view_builder = crate::view::ViewBuilder::new(
             connection.clone(),
             store.clone(),
-            config.initial_data_timeout,
+            config.snapshot_timeout,
         );
         let views = S::Views::from_builder(view_builder);
 
@@ -1,16 +1,87 @@
 use crate::auth::AuthConfig;
-use crate::store::DEFAULT_MAX_ENTRIES_PER_VIEW;
+use crate::quality::QualityPolicy;
+use crate::store::{DecodeMode, DEFAULT_MAX_ENTRIES_PER_VIEW};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::time::Duration;
 
+/// Exponential reconnect backoff: each attempt waits twice as long as the
+/// previous one, from `initial` up to `max`, less a random share of up to
+/// `jitter` so clients dropped together don't reconnect together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectBackoff {
+    pub initial: Duration,
+    pub max: Duration,
+    /// Share of each delay, from 0.0 to 1.0, that may be taken off at random
+    pub jitter: f64,
+}
+
+impl ReconnectBackoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            jitter: 0.2,
+        }
+    }
+
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Delay before reconnect attempt `attempt`, counting from 0, without
+    /// jitter
+    pub fn ceiling(&self, attempt: u32) -> Duration {
+        self.initial
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max)
+    }
+
+    /// Delay before reconnect attempt `attempt`, counting from 0
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = self.ceiling(attempt);
+        if self.jitter <= 0.0 {
+            return ceiling;
+        }
+        // A fresh `RandomState` is randomly seeded, which is all the
+        // randomness jitter needs
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(attempt);
+        let unit = (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64;
+        ceiling.mul_f64(1.0 - self.jitter * unit)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HyperStackConfig {
     pub auto_reconnect: bool,
     pub reconnect_intervals: Vec<Duration>,
+    /// Exponential backoff used instead of `reconnect_intervals` when set
+    pub reconnect_backoff: Option<ReconnectBackoff>,
     pub max_reconnect_attempts: u32,
+    /// Longest server-suggested reconnect delay the client will honor
+    pub max_retry_hint: Duration,
     pub ping_interval: Duration,
-    pub initial_data_timeout: Duration,
+    /// Longest a single connection attempt may take to complete the
+    /// WebSocket handshake
+    pub connect_timeout: Duration,
+    /// Longest a read waits for a view's initial data
+    pub snapshot_timeout: Duration,
+    /// Longest the server may take to acknowledge a subscription
+    pub subscribe_ack_timeout: Duration,
     pub max_entries_per_view: Option<usize>,
+    /// What happens to snapshots holding entities that don't decode
+    pub decode_mode: DecodeMode,
     pub auth: Option<AuthConfig>,
+    /// Ask the server for diagnostics about each subscription's initial load
+    pub diagnostics: bool,
+    /// How connection quality is classified and which subscription options
+    /// each tier downgrades list subscriptions to
+    pub quality_policy: QualityPolicy,
+    /// Share of frames, from 0.0 to 1.0, whose processing time is recorded
+    /// by the `tracing` feature
+    pub frame_sample_rate: f64,
 }
 
 impl Default for HyperStackConfig {
@@ -24,11 +95,19 @@ impl Default for HyperStackConfig {
                 Duration::from_secs(8),
                 Duration::from_secs(16),
             ],
+            reconnect_backoff: None,
             max_reconnect_attempts: 5,
+            max_retry_hint: Duration::from_secs(60),
             ping_interval: Duration::from_secs(15),
-            initial_data_timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(10),
+            snapshot_timeout: Duration::from_secs(5),
+            subscribe_ack_timeout: Duration::from_secs(10),
             max_entries_per_view: Some(DEFAULT_MAX_ENTRIES_PER_VIEW),
+            decode_mode: DecodeMode::default(),
             auth: None,
+            diagnostics: false,
+            quality_policy: QualityPolicy::default(),
+            frame_sample_rate: 0.01,
         }
     }
 }
@@ -37,9 +116,15 @@ impl Default for HyperStackConfig {
 pub struct ConnectionConfig {
     pub auto_reconnect: bool,
     pub reconnect_intervals: Vec<Duration>,
+    pub reconnect_backoff: Option<ReconnectBackoff>,
     pub max_reconnect_attempts: u32,
+    pub max_retry_hint: Duration,
     pub ping_interval: Duration,
+    pub connect_timeout: Duration,
+    pub subscribe_ack_timeout: Duration,
     pub auth: Option<AuthConfig>,
+    pub diagnostics: bool,
+    pub quality_policy: QualityPolicy,
 }
 
 impl From<HyperStackConfig> for ConnectionConfig {
@@ -47,10 +132,30 @@ impl From<HyperStackConfig> for ConnectionConfig {
         Self {
             auto_reconnect: config.auto_reconnect,
             reconnect_intervals: config.reconnect_intervals,
+            reconnect_backoff: config.reconnect_backoff,
             max_reconnect_attempts: config.max_reconnect_attempts,
+            max_retry_hint: config.max_retry_hint,
             ping_interval: config.ping_interval,
+            connect_timeout: config.connect_timeout,
+            subscribe_ack_timeout: config.subscribe_ack_timeout,
             auth: config.auth,
+            diagnostics: config.diagnostics,
+            quality_policy: config.quality_policy,
+        }
+    }
+}
+
+impl ConnectionConfig {
+    /// Local delay before reconnect attempt `attempt`, counting from 0
+    pub fn reconnect_delay(&self, attempt: u32) -> Duration {
+        if let Some(backoff) = self.reconnect_backoff {
+            return backoff.delay(attempt);
         }
+        self.reconnect_intervals
+            .get(attempt as usize)
+            .or(self.reconnect_intervals.last())
+            .copied()
+            .unwrap_or(Duration::from_secs(16))
     }
 }
 
@@ -4,19 +4,25 @@ use crate::auth::{
     TokenEndpointResponse, TokenTransport, MIN_REFRESH_DELAY_SECONDS,
 };
 use crate::config::ConnectionConfig;
-use crate::error::{HyperStackError, SocketIssue, SocketIssuePayload};
-use crate::frame::{parse_frame, Frame};
-use crate::subscription::{ClientMessage, Subscription, SubscriptionRegistry, Unsubscription};
-use futures_util::{SinkExt, StreamExt};
+use crate::error::{HyperStackError, SocketIssue, TimedOperation};
+use crate::frame::{Frame, FrameSequence, Operation};
+use crate::quality::{
+    ConnectionQuality, QualityChange, QualityMonitor, QualityPolicy, QualityTier,
+};
+use crate::raw::{ConnectError, RawClient, RawEvent, SubscriptionId};
+use crate::store::SharedStore;
+use crate::subscription::{
+    EntityFilter, Subscription, SubscriptionRegistry, Unsubscription, UpdateDelivery,
+};
+use crate::telemetry::{self, ConnectOutcome, ConnectSpan, SubscriptionSpans};
+use futures_util::StreamExt;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
 use tokio::time::{sleep, Sleep};
-use tokio_tungstenite::{
-    connect_async,
-    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message},
-};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConnectionState {
@@ -27,19 +33,27 @@ pub enum ConnectionState {
     Error,
 }
 
+/// A change in whether the client is connected, for surfacing connection
+/// status to users
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// The socket is open and every active subscription has been sent again
+    Connected,
+    /// The socket closed. A [`Reconnecting`](Self::Reconnecting) event
+    /// follows unless the client was told to disconnect or gave up, which
+    /// [`ConnectionManager::last_error`] explains.
+    Disconnected,
+    /// Reconnect attempt `attempt`, counting from 1, starts after `delay`
+    Reconnecting { attempt: u32, delay: Duration },
+}
+
 pub enum ConnectionCommand {
     Subscribe(Subscription),
     Unsubscribe(Unsubscription),
+    Refresh(Unsubscription),
     Disconnect,
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct RefreshAuthResponseMessage {
-    success: bool,
-    error: Option<String>,
-    expires_at: Option<u64>,
-}
-
 #[derive(Debug, Clone, Default)]
 pub struct SubscriptionOptions {
     pub take: Option<u32>,
@@ -47,6 +61,10 @@ pub struct SubscriptionOptions {
     pub with_snapshot: Option<bool>,
     pub after: Option<String>,
     pub snapshot_limit: Option<usize>,
+    pub history: Option<usize>,
+    pub delivery: Option<UpdateDelivery>,
+    pub filter: Option<EntityFilter>,
+    pub delta: Option<bool>,
 }
 
 struct ConnectionManagerInner {
@@ -60,6 +78,14 @@ struct ConnectionManagerInner {
     last_error: Arc<RwLock<Option<Arc<HyperStackError>>>>,
     last_socket_issue: Arc<RwLock<Option<SocketIssue>>>,
     socket_issue_tx: broadcast::Sender<SocketIssue>,
+    /// Gaps found in the frame sequences of subscriptions
+    frame_gaps: Arc<AtomicU64>,
+    quality: Arc<RwLock<ConnectionQuality>>,
+    quality_tx: broadcast::Sender<QualityChange>,
+    events_tx: broadcast::Sender<ConnectionEvent>,
+    /// Set for offline clients: commands are recorded instead of sent.
+    #[cfg(feature = "test-util")]
+    recorder: Option<Arc<crate::mock::CommandRecorder>>,
 }
 
 #[derive(Clone)]
@@ -80,6 +106,10 @@ impl ConnectionManager {
         let last_error = Arc::new(RwLock::new(None));
         let last_socket_issue = Arc::new(RwLock::new(None));
         let (socket_issue_tx, _) = broadcast::channel(100);
+        let frame_gaps = Arc::new(AtomicU64::new(0));
+        let quality = Arc::new(RwLock::new(ConnectionQuality::default()));
+        let (quality_tx, _) = broadcast::channel(16);
+        let (events_tx, _) = broadcast::channel(16);
 
         let inner = ConnectionManagerInner {
             url: url.clone(),
@@ -90,6 +120,12 @@ impl ConnectionManager {
             last_error: last_error.clone(),
             last_socket_issue: last_socket_issue.clone(),
             socket_issue_tx: socket_issue_tx.clone(),
+            frame_gaps: frame_gaps.clone(),
+            quality: quality.clone(),
+            quality_tx: quality_tx.clone(),
+            events_tx: events_tx.clone(),
+            #[cfg(feature = "test-util")]
+            recorder: None,
         };
 
         spawn_connection_loop(
@@ -102,6 +138,10 @@ impl ConnectionManager {
             last_error,
             last_socket_issue,
             socket_issue_tx,
+            frame_gaps,
+            quality,
+            quality_tx,
+            events_tx,
             initial_connect_tx,
         );
 
@@ -134,6 +174,27 @@ impl ConnectionManager {
         self.inner.socket_issue_tx.subscribe()
     }
 
+    /// Number of gaps found in subscription frame sequences, each of which
+    /// triggered a resync
+    pub fn frame_gaps(&self) -> u64 {
+        self.inner.frame_gaps.load(Ordering::Relaxed)
+    }
+
+    /// Latest sample of the connection's quality signals
+    pub async fn connection_quality(&self) -> ConnectionQuality {
+        *self.inner.quality.read().await
+    }
+
+    /// Tier changes, sent once a new tier has held long enough to
+    /// renegotiate subscriptions
+    pub fn subscribe_quality_changes(&self) -> broadcast::Receiver<QualityChange> {
+        self.inner.quality_tx.subscribe()
+    }
+
+    pub fn subscribe_connection_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.inner.events_tx.subscribe()
+    }
+
     pub async fn ensure_subscription(&self, view: &str, key: Option<&str>) {
         self.ensure_subscription_with_opts(view, key, SubscriptionOptions::default())
             .await
@@ -148,6 +209,7 @@ impl ConnectionManager {
         let sub = Subscription {
             view: view.to_string(),
             key: key.map(|s| s.to_string()),
+            id: None,
             partition: None,
             filters: None,
             take: opts.take,
@@ -155,39 +217,121 @@ impl ConnectionManager {
             with_snapshot: opts.with_snapshot,
             after: opts.after,
             snapshot_limit: opts.snapshot_limit,
+            history: opts.history,
+            delivery: opts.delivery,
+            diagnostics: self.inner.config.diagnostics.then_some(true),
+            fields: None,
+            filter: opts.filter.map(Box::new),
+            delta: opts.delta,
         };
 
         if !self.inner.subscriptions.read().await.contains(&sub) {
-            let _ = self
-                .inner
-                .command_tx
-                .send(ConnectionCommand::Subscribe(sub))
-                .await;
+            self.send_command(ConnectionCommand::Subscribe(sub)).await;
+        }
+    }
+
+    /// Wait for the server to acknowledge a subscription to `view`, for up to
+    /// `timeout` or the client's `subscribe_ack_timeout`.
+    ///
+    /// Offline connections never hear from a server and don't wait.
+    pub(crate) async fn wait_for_ack(
+        &self,
+        store: &SharedStore,
+        view: &str,
+        timeout: Option<Duration>,
+    ) -> Result<(), HyperStackError> {
+        if self.is_offline() {
+            return Ok(());
+        }
+
+        let timeout = timeout.unwrap_or(self.inner.config.subscribe_ack_timeout);
+        if store.wait_for_ack(view, timeout).await {
+            Ok(())
+        } else {
+            Err(HyperStackError::timeout(
+                TimedOperation::SubscribeAck,
+                Some(view),
+                timeout,
+            ))
         }
     }
 
+    /// Whether reads should skip waiting for data from a server
+    pub(crate) fn is_offline(&self) -> bool {
+        #[cfg(feature = "test-util")]
+        if self.inner.recorder.is_some() {
+            return true;
+        }
+
+        false
+    }
+
     pub async fn subscribe(&self, sub: Subscription) {
-        let _ = self
-            .inner
-            .command_tx
-            .send(ConnectionCommand::Subscribe(sub))
-            .await;
+        self.send_command(ConnectionCommand::Subscribe(sub)).await;
     }
 
     pub async fn unsubscribe(&self, unsub: Unsubscription) {
-        let _ = self
-            .inner
-            .command_tx
-            .send(ConnectionCommand::Unsubscribe(unsub))
+        self.send_command(ConnectionCommand::Unsubscribe(unsub))
             .await;
     }
 
+    /// Ask the server for a fresh snapshot of the subscription to `view` and
+    /// `key`, or open the subscription if there is none yet
+    pub async fn refresh(&self, view: &str, key: Option<&str>) {
+        let refresh = Unsubscription {
+            view: view.to_string(),
+            key: key.map(|s| s.to_string()),
+            id: None,
+        };
+        if self.inner.subscriptions.read().await.has(&refresh) {
+            self.send_command(ConnectionCommand::Refresh(refresh)).await;
+        } else {
+            self.ensure_subscription(view, key).await;
+        }
+    }
+
     pub async fn disconnect(&self) {
-        let _ = self
-            .inner
-            .command_tx
-            .send(ConnectionCommand::Disconnect)
-            .await;
+        self.send_command(ConnectionCommand::Disconnect).await;
+    }
+
+    async fn send_command(&self, command: ConnectionCommand) {
+        #[cfg(feature = "test-util")]
+        if let Some(recorder) = &self.inner.recorder {
+            recorder
+                .record(command, &self.inner.subscriptions, &self.inner.state)
+                .await;
+            return;
+        }
+
+        let _ = self.inner.command_tx.send(command).await;
+    }
+
+    /// A connection that never opens a socket and hands every command to
+    /// `recorder` instead.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn offline(recorder: Arc<crate::mock::CommandRecorder>) -> Self {
+        let (command_tx, _) = mpsc::channel(1);
+        let (socket_issue_tx, _) = broadcast::channel(1);
+        let (quality_tx, _) = broadcast::channel(1);
+        let (events_tx, _) = broadcast::channel(1);
+
+        Self {
+            inner: Arc::new(ConnectionManagerInner {
+                url: String::new(),
+                state: Arc::new(RwLock::new(ConnectionState::Connected)),
+                subscriptions: Arc::new(RwLock::new(SubscriptionRegistry::new())),
+                config: ConnectionConfig::default(),
+                command_tx,
+                last_error: Arc::new(RwLock::new(None)),
+                last_socket_issue: Arc::new(RwLock::new(None)),
+                socket_issue_tx,
+                frame_gaps: Arc::new(AtomicU64::new(0)),
+                quality: Arc::new(RwLock::new(ConnectionQuality::default())),
+                quality_tx,
+                events_tx,
+                recorder: Some(recorder),
+            }),
+        }
     }
 }
 
@@ -387,6 +531,10 @@ fn spawn_connection_loop(
     last_error: Arc<RwLock<Option<Arc<HyperStackError>>>>,
     last_socket_issue: Arc<RwLock<Option<SocketIssue>>>,
     socket_issue_tx: broadcast::Sender<SocketIssue>,
+    frame_gaps: Arc<AtomicU64>,
+    quality: Arc<RwLock<ConnectionQuality>>,
+    quality_tx: broadcast::Sender<QualityChange>,
+    events_tx: broadcast::Sender<ConnectionEvent>,
     initial_connect_tx: oneshot::Sender<Result<(), HyperStackError>>,
 ) {
     tokio::spawn(async move {
@@ -396,9 +544,15 @@ fn spawn_connection_loop(
         let mut initial_connect_tx = Some(initial_connect_tx);
         let mut force_token_refresh = false;
         let mut immediate_reconnect = false;
+        let mut retry_hint: Option<Duration> = None;
+        let mut monitor = QualityMonitor::new(config.quality_policy.clone(), Instant::now());
+        let mut subscription_spans = SubscriptionSpans::default();
+        // A Reconnecting event was sent and no Connected followed yet
+        let mut awaiting_reconnect = false;
 
         while should_run {
             *state.write().await = ConnectionState::Connecting;
+            let attempt_span = ConnectSpan::start(&url, reconnect_attempt);
 
             let token = match auth_state.resolve_token(force_token_refresh).await {
                 Ok(token) => {
@@ -406,6 +560,7 @@ fn spawn_connection_loop(
                     token
                 }
                 Err(error) => {
+                    attempt_span.finish(ConnectOutcome::AuthFailed);
                     set_last_error(&last_error, error.clone()).await;
                     *state.write().await = ConnectionState::Error;
                     report_initial_failure(&mut initial_connect_tx, error);
@@ -416,6 +571,7 @@ fn spawn_connection_loop(
             let request = match auth_state.build_request(token.as_deref()) {
                 Ok(request) => request,
                 Err(error) => {
+                    attempt_span.finish(ConnectOutcome::Failed);
                     set_last_error(&last_error, error.clone()).await;
                     *state.write().await = ConnectionState::Error;
                     report_initial_failure(&mut initial_connect_tx, error);
@@ -423,139 +579,143 @@ fn spawn_connection_loop(
                 }
             };
 
-            match connect_async(request).await {
-                Ok((ws, _)) => {
+            match tokio::time::timeout(config.connect_timeout, RawClient::connect(request)).await {
+                Ok(Ok(mut raw)) => {
+                    attempt_span.finish(ConnectOutcome::Connected);
                     clear_last_error(&last_error).await;
                     *last_socket_issue.write().await = None;
                     *state.write().await = ConnectionState::Connected;
                     reconnect_attempt = 0;
                     immediate_reconnect = false;
+                    retry_hint = None;
                     report_initial_success(&mut initial_connect_tx);
 
-                    let (mut ws_tx, mut ws_rx) = ws.split();
+                    monitor.reset_connection(Instant::now(), frame_gaps.load(Ordering::Relaxed));
                     let subs = subscriptions.read().await.all();
                     for sub in subs {
-                        let client_msg = ClientMessage::Subscribe(sub);
-                        if let Ok(msg) = serde_json::to_string(&client_msg) {
-                            let _ = ws_tx.send(Message::Text(msg)).await;
-                        }
+                        subscription_spans.requested(&sub.view, sub.key.as_deref());
+                        let _ = raw
+                            .send_subscribe(&monitor.policy().negotiate(monitor.tier(), &sub))
+                            .await;
                     }
+                    awaiting_reconnect = false;
+                    let _ = events_tx.send(ConnectionEvent::Connected);
 
+                    let mut bytes_counted = 0;
+                    // Bytes of a message whose frame follows its gap event
+                    let mut gap_bytes = 0;
                     let ping_interval = config.ping_interval;
                     let mut ping_timer = tokio::time::interval(ping_interval);
                     let mut refresh_timer = auth_state.refresh_timer();
 
                     loop {
                         tokio::select! {
-                            msg = ws_rx.next() => {
-                                match msg {
-                                    Some(Ok(Message::Binary(bytes))) => {
-                                        if let Ok(frame) = parse_frame(&bytes) {
-                                            let _ = frame_tx.send(frame).await;
-                                        }
+                            event = raw.next() => {
+                                let bytes_received = raw.bytes_received();
+                                let new_bytes = (bytes_received - bytes_counted) as usize;
+                                monitor.record_bytes(new_bytes);
+                                bytes_counted = bytes_received;
+                                let message_bytes = gap_bytes + new_bytes;
+                                gap_bytes = match event {
+                                    Some(RawEvent::Gap { .. }) => message_bytes,
+                                    _ => 0,
+                                };
+
+                                match event {
+                                    Some(RawEvent::Frame { frame, sequence }) => {
+                                        record_frame(&frame, sequence.as_ref(), message_bytes, &mut subscription_spans);
+                                        let _ = frame_tx.send(frame).await;
+                                    }
+                                    Some(RawEvent::Gap { sub, expected, received }) => {
+                                        telemetry::gap_detected(sub.as_str(), expected, received);
+                                        let gaps = frame_gaps.fetch_add(1, Ordering::Relaxed) + 1;
+                                        tracing::warn!(
+                                            "Frame gap on {}: expected frame {}, got {} ({} gaps so far), requesting a resync",
+                                            sub,
+                                            expected,
+                                            received,
+                                            gaps
+                                        );
+                                        let _ = raw.send_resync(&sub).await;
                                     }
-                                    Some(Ok(Message::Text(text))) => {
-                                        if let Some(issue) = parse_socket_issue_message(&text) {
-                                            record_socket_issue(&last_socket_issue, &socket_issue_tx, issue.clone()).await;
+                                    Some(RawEvent::SocketIssue(issue)) => {
+                                        record_socket_issue(&last_socket_issue, &socket_issue_tx, issue.clone()).await;
 
-                                            let error = HyperStackError::from_socket_issue(issue);
-                                            if error.should_refresh_token() && auth_state.has_refreshable_auth() {
-                                                auth_state.clear_cached_token();
-                                                force_token_refresh = true;
-                                                immediate_reconnect = true;
-                                            }
+                                        let issue_hint = issue.retry_after_hint();
+                                        let error = HyperStackError::from_socket_issue(issue);
+                                        if error.should_refresh_token() && auth_state.has_refreshable_auth() {
+                                            auth_state.clear_cached_token();
+                                            force_token_refresh = true;
+                                            immediate_reconnect = true;
+                                        }
 
-                                            let is_fatal = error
-                                                .socket_issue()
-                                                .map(|issue| issue.fatal)
-                                                .unwrap_or(false);
-                                            set_last_error(&last_error, error).await;
+                                        let is_fatal = error
+                                            .socket_issue()
+                                            .map(|issue| issue.fatal)
+                                            .unwrap_or(false);
+                                        set_last_error(&last_error, error).await;
 
-                                            if is_fatal {
-                                                break;
-                                            }
-                                        } else if let Some(refresh_response) = parse_refresh_auth_response(&text) {
-                                            if refresh_response.success {
-                                                if let Some(expires_at) = refresh_response.expires_at {
-                                                    auth_state.token_expiry = Some(expires_at);
-                                                }
-                                                refresh_timer = auth_state.refresh_timer();
-                                            } else {
-                                                let error = refresh_response_error(refresh_response);
-                                                if error.should_refresh_token() && auth_state.has_refreshable_auth() {
-                                                    auth_state.clear_cached_token();
-                                                    force_token_refresh = true;
-                                                }
-                                                immediate_reconnect = true;
-                                                set_last_error(&last_error, error).await;
-                                                break;
-                                            }
-                                        } else if let Ok(frame) = serde_json::from_str::<Frame>(&text) {
-                                            let _ = frame_tx.send(frame).await;
+                                        if is_fatal {
+                                            retry_hint = issue_hint;
+                                            break;
                                         }
                                     }
-                                    Some(Ok(Message::Ping(payload))) => {
-                                        let _ = ws_tx.send(Message::Pong(payload)).await;
-                                    }
-                                    Some(Ok(Message::Close(frame))) => {
-                                        if let Some(frame) = frame.as_ref() {
-                                            let reason = frame.reason.to_string();
-                                            if let Some(error) = HyperStackError::from_close_reason(&reason) {
-                                                if error.should_refresh_token() && auth_state.has_refreshable_auth() {
-                                                    auth_state.clear_cached_token();
-                                                    force_token_refresh = true;
-                                                    immediate_reconnect = true;
-                                                }
-                                                set_last_error(&last_error, error).await;
-                                            }
+                                    Some(RawEvent::AuthRefreshed { expires_at }) => {
+                                        if let Some(expires_at) = expires_at {
+                                            auth_state.token_expiry = Some(expires_at);
                                         }
-                                        break;
+                                        refresh_timer = auth_state.refresh_timer();
                                     }
-                                    Some(Err(error)) => {
-                                        let parsed_error = HyperStackError::from_tungstenite(error);
-                                        if parsed_error.should_refresh_token() && auth_state.has_refreshable_auth() {
+                                    Some(RawEvent::AuthRefreshFailed(error)) => {
+                                        if error.should_refresh_token() && auth_state.has_refreshable_auth() {
                                             auth_state.clear_cached_token();
                                             force_token_refresh = true;
-                                            immediate_reconnect = true;
                                         }
-                                        set_last_error(&last_error, parsed_error).await;
+                                        immediate_reconnect = true;
+                                        set_last_error(&last_error, error).await;
+                                        break;
+                                    }
+                                    Some(RawEvent::Pong) => {
+                                        monitor.pong_received(Instant::now());
+                                    }
+                                    Some(RawEvent::CompressionOffered { dictionary }) => {
+                                        let _ = raw.accept_compression(dictionary.as_deref()).await;
+                                    }
+                                    Some(RawEvent::Closed(closed)) => {
+                                        retry_hint = closed.retry_after;
+                                        if let Some(error) = closed.error {
+                                            if error.should_refresh_token() && auth_state.has_refreshable_auth() {
+                                                auth_state.clear_cached_token();
+                                                force_token_refresh = true;
+                                                immediate_reconnect = true;
+                                            }
+                                            set_last_error(&last_error, error).await;
+                                        }
                                         break;
                                     }
                                     None => {
                                         break;
                                     }
-                                    _ => {}
                                 }
                             }
                             cmd = command_rx.recv() => {
                                 match cmd {
                                     Some(ConnectionCommand::Subscribe(sub)) => {
                                         subscriptions.write().await.add(sub.clone());
-                                        let client_msg = ClientMessage::Subscribe(sub);
-                                        if let Ok(msg) = serde_json::to_string(&client_msg) {
-                                            let _ = ws_tx.send(Message::Text(msg)).await;
-                                        }
+                                        subscription_spans.requested(&sub.view, sub.key.as_deref());
+                                        let _ = raw
+                                            .send_subscribe(&monitor.policy().negotiate(monitor.tier(), &sub))
+                                            .await;
                                     }
                                     Some(ConnectionCommand::Unsubscribe(unsub)) => {
-                                        let sub = Subscription {
-                                            view: unsub.view.clone(),
-                                            key: unsub.key.clone(),
-                                            partition: None,
-                                            filters: None,
-                                            take: None,
-                                            skip: None,
-                                            with_snapshot: None,
-                                            after: None,
-                                            snapshot_limit: None,
-                                        };
-                                        subscriptions.write().await.remove(&sub);
-                                        let client_msg = ClientMessage::Unsubscribe(unsub);
-                                        if let Ok(msg) = serde_json::to_string(&client_msg) {
-                                            let _ = ws_tx.send(Message::Text(msg)).await;
-                                        }
+                                        subscriptions.write().await.remove(&unsub);
+                                        let _ = raw.send_unsubscribe(&SubscriptionId::from(&unsub)).await;
+                                    }
+                                    Some(ConnectionCommand::Refresh(refresh)) => {
+                                        let _ = raw.send_refresh(&SubscriptionId::from(&refresh)).await;
                                     }
                                     Some(ConnectionCommand::Disconnect) => {
-                                        let _ = ws_tx.close().await;
+                                        let _ = raw.close().await;
                                         *state.write().await = ConnectionState::Disconnected;
                                         should_run = false;
                                         break;
@@ -567,8 +727,28 @@ fn spawn_connection_loop(
                                 }
                             }
                             _ = ping_timer.tick() => {
-                                if let Ok(msg) = serde_json::to_string(&ClientMessage::Ping) {
-                                    let _ = ws_tx.send(Message::Text(msg)).await;
+                                let now = Instant::now();
+                                let change = monitor.sample(frame_gaps.load(Ordering::Relaxed), now);
+                                *quality.write().await = monitor.quality();
+                                if let Some(change) = change {
+                                    tracing::info!(
+                                        "Connection quality changed from {:?} to {:?} (rtt {:?}, {} recent gaps)",
+                                        change.from,
+                                        change.to,
+                                        change.quality.rtt,
+                                        change.quality.recent_gaps
+                                    );
+                                    if monitor.policy().renegotiates(change.from, change.to) {
+                                        let subs = subscriptions.read().await.all();
+                                        renegotiate_subscriptions(subs, monitor.policy(), change.to, &mut raw, &mut subscription_spans).await;
+                                    }
+                                    let _ = quality_tx.send(change);
+                                }
+
+                                let _ = raw.send_keepalive().await;
+                                // Protocol pings are answered by the socket itself, timing the round trip
+                                if monitor.ping_sent(now) {
+                                    let _ = raw.send_heartbeat().await;
                                 }
                             }
                             _ = wait_for_refresh_timer(&mut refresh_timer) => {
@@ -576,19 +756,11 @@ fn spawn_connection_loop(
                                 match auth_state.resolve_token(true).await {
                                     Ok(Some(token)) => {
                                         refresh_timer = auth_state.refresh_timer();
-                                        if previous_token.as_deref() != Some(token.as_str()) {
-                                            match serde_json::to_string(&ClientMessage::RefreshAuth { token }) {
-                                                Ok(message) => {
-                                                    if ws_tx.send(Message::Text(message)).await.is_err() {
-                                                        immediate_reconnect = true;
-                                                        break;
-                                                    }
-                                                }
-                                                Err(error) => {
-                                                    tracing::warn!("Failed to serialize auth refresh message: {}", error);
-                                                    refresh_timer = Some(Box::pin(sleep(Duration::from_secs(MIN_REFRESH_DELAY_SECONDS))));
-                                                }
-                                            }
+                                        if previous_token.as_deref() != Some(token.as_str())
+                                            && raw.send_refresh_auth(token).await.is_err()
+                                        {
+                                            immediate_reconnect = true;
+                                            break;
                                         }
                                     }
                                     Ok(None) => {
@@ -602,16 +774,29 @@ fn spawn_connection_loop(
                             }
                         }
                     }
+                    subscription_spans.disconnected();
+                    let _ = events_tx.send(ConnectionEvent::Disconnected);
                 }
-                Err(error) => {
-                    let parsed_error = HyperStackError::from_tungstenite(error);
-                    if parsed_error.should_refresh_token() && auth_state.has_refreshable_auth() {
+                Ok(Err(ConnectError { error, retry_after })) => {
+                    attempt_span.finish(ConnectOutcome::Failed);
+                    retry_hint = retry_after;
+                    if error.should_refresh_token() && auth_state.has_refreshable_auth() {
                         auth_state.clear_cached_token();
                         force_token_refresh = true;
                         immediate_reconnect = true;
                     }
-                    tracing::error!("Connection failed: {}", parsed_error);
-                    set_last_error(&last_error, parsed_error).await;
+                    tracing::error!("Connection failed: {}", error);
+                    set_last_error(&last_error, error).await;
+                }
+                Err(_) => {
+                    attempt_span.finish(ConnectOutcome::Timeout);
+                    let error = HyperStackError::timeout(
+                        TimedOperation::Connect,
+                        None,
+                        config.connect_timeout,
+                    );
+                    tracing::error!("Connection failed: {}", error);
+                    set_last_error(&last_error, error).await;
                 }
             }
 
@@ -625,7 +810,7 @@ fn spawn_connection_loop(
                     auth_state.clear_cached_token();
                     force_token_refresh = true;
                     immediate_reconnect = true;
-                } else if !error.should_retry() {
+                } else if !error.should_retry() && retry_hint.is_none() {
                     *state.write().await = ConnectionState::Error;
                     report_initial_failure(&mut initial_connect_tx, error.clone());
                     break;
@@ -652,26 +837,22 @@ fn spawn_connection_loop(
                 break;
             }
 
-            let delay = if immediate_reconnect {
-                Duration::from_millis(0)
-            } else {
-                config
-                    .reconnect_intervals
-                    .get(reconnect_attempt as usize)
-                    .copied()
-                    .unwrap_or_else(|| {
-                        config
-                            .reconnect_intervals
-                            .last()
-                            .copied()
-                            .unwrap_or(Duration::from_secs(16))
-                    })
+            // A delay suggested by the server replaces local backoff for this attempt
+            let delay = match retry_hint.take() {
+                Some(hint) => hint.min(config.max_retry_hint),
+                None if immediate_reconnect => Duration::from_millis(0),
+                None => config.reconnect_delay(reconnect_attempt),
             };
 
             *state.write().await = ConnectionState::Reconnecting {
                 attempt: reconnect_attempt,
             };
             reconnect_attempt += 1;
+            awaiting_reconnect = true;
+            let _ = events_tx.send(ConnectionEvent::Reconnecting {
+                attempt: reconnect_attempt,
+                delay,
+            });
 
             if !delay.is_zero() {
                 tracing::info!(
@@ -683,6 +864,10 @@ fn spawn_connection_loop(
             }
         }
 
+        if awaiting_reconnect {
+            let _ = events_tx.send(ConnectionEvent::Disconnected);
+        }
+
         if let Some(tx) = initial_connect_tx.take() {
             let error = last_error
                 .read()
@@ -695,6 +880,42 @@ fn spawn_connection_loop(
     });
 }
 
+/// Restart list subscriptions with the options of the new quality tier.
+async fn renegotiate_subscriptions(
+    subs: Vec<Subscription>,
+    policy: &QualityPolicy,
+    tier: QualityTier,
+    raw: &mut RawClient,
+    spans: &mut SubscriptionSpans,
+) {
+    for sub in subs.iter().filter(|sub| sub.key.is_none()) {
+        let _ = raw.send_unsubscribe(&SubscriptionId::from(sub)).await;
+        spans.requested(&sub.view, None);
+        let _ = raw.send_subscribe(&policy.negotiate(tier, sub)).await;
+    }
+}
+
+/// Report what a received frame means for its subscription
+fn record_frame(
+    frame: &Frame,
+    sequence: Option<&FrameSequence>,
+    bytes: usize,
+    spans: &mut SubscriptionSpans,
+) {
+    if let Some(sequence) = sequence.filter(|sequence| sequence.resync) {
+        if sequence.refresh {
+            telemetry::refresh_started(&sequence.sub);
+        } else {
+            telemetry::resync_started(&sequence.sub);
+        }
+    }
+    match frame.op.parse() {
+        Ok(Operation::Subscribed) => spans.acked(&frame.entity),
+        Ok(Operation::Snapshot) => telemetry::snapshot_received(frame, bytes),
+        _ => {}
+    }
+}
+
 async fn set_last_error(
     last_error: &Arc<RwLock<Option<Arc<HyperStackError>>>>,
     error: HyperStackError,
@@ -746,29 +967,3 @@ fn current_unix_timestamp() -> u64 {
         .unwrap_or_default()
         .as_secs()
 }
-
-fn parse_socket_issue_message(text: &str) -> Option<SocketIssue> {
-    let payload = serde_json::from_str::<SocketIssuePayload>(text).ok()?;
-    if payload.is_socket_issue() {
-        Some(payload.into_socket_issue())
-    } else {
-        None
-    }
-}
-
-fn parse_refresh_auth_response(text: &str) -> Option<RefreshAuthResponseMessage> {
-    let payload = serde_json::from_str::<RefreshAuthResponseMessage>(text).ok()?;
-    Some(payload)
-}
-
-fn refresh_response_error(response: RefreshAuthResponseMessage) -> HyperStackError {
-    let code = response
-        .error
-        .as_deref()
-        .and_then(crate::error::AuthErrorCode::from_wire);
-    let message = response
-        .error
-        .unwrap_or_else(|| "Authentication refresh failed".to_string());
-
-    HyperStackError::WebSocket { message, code }
-}
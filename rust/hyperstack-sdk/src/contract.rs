@@ -0,0 +1,629 @@
+//! Checks a live deployment against the stack an SDK was generated from.
+//!
+//! `hs sdk create rust --with-contract-tests` generates a
+//! `tests/live_contract.rs` harness describing every view of the stack as a
+//! [`ViewContract`] and handing them to a [`ContractRunner`]. The runner
+//! subscribes to the views one at a time on a single connection and checks
+//! that:
+//!
+//! - the server acknowledges every subscription,
+//! - list and state views send a snapshot whose entities decode into the
+//!   generated entity type, with each required field present in at least one
+//!   entity and every field holding the declared JSON type,
+//! - append views deliver a frame.
+//!
+//! Views that are legitimately empty can be skipped. The resulting
+//! [`ContractReport`] renders as JUnit XML for CI.
+//!
+//! ```ignore
+//! use hyperstack_sdk::contract::{ContractRunner, FieldContract, JsonKind, ViewContract};
+//!
+//! const ROUND_FIELDS: &[FieldContract] = &[FieldContract::new("state.total", JsonKind::Integer)];
+//!
+//! let report = ContractRunner::new(url, SCHEMA_HASH)
+//!     .skip(["OreRound/append"])
+//!     .run(&[ViewContract::list("OreRound/list", ROUND_FIELDS).typed::<OreRound>()])
+//!     .await?;
+//! std::fs::write("target/live-contract.xml", report.to_junit())?;
+//! report.assert_passed();
+//! ```
+//!
+//! Requires the `test-util` feature.
+
+use crate::error::HyperStackError;
+use crate::frame::{parse_snapshot_entities, Frame, SubscribedFrame};
+use crate::raw::{RawClient, RawEvent};
+use crate::subscription::Subscription;
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// How long to wait for each frame of a view by default
+pub const DEFAULT_CONTRACT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// JSON type of a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonKind {
+    String,
+    /// A number without a fraction, or a decimal string as 64-bit and wider
+    /// integers may be sent
+    Integer,
+    Number,
+    Bool,
+    Object,
+    Array,
+    /// Not checked
+    Any,
+}
+
+impl JsonKind {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            JsonKind::String => value.is_string(),
+            JsonKind::Integer => {
+                value.is_i64()
+                    || value.is_u64()
+                    || value.as_str().is_some_and(|s| s.parse::<i128>().is_ok())
+            }
+            JsonKind::Number => value.is_number(),
+            JsonKind::Bool => value.is_boolean(),
+            JsonKind::Object => value.is_object(),
+            JsonKind::Array => value.is_array(),
+            JsonKind::Any => true,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            JsonKind::String => "a string",
+            JsonKind::Integer => "an integer",
+            JsonKind::Number => "a number",
+            JsonKind::Bool => "a boolean",
+            JsonKind::Object => "an object",
+            JsonKind::Array => "an array",
+            JsonKind::Any => "anything",
+        }
+    }
+}
+
+/// A field of an entity, by its dotted path in the entity's JSON
+#[derive(Debug, Clone, Copy)]
+pub struct FieldContract {
+    pub path: &'static str,
+    pub kind: JsonKind,
+    /// Whether the field may be missing from every entity of a snapshot
+    pub optional: bool,
+}
+
+impl FieldContract {
+    pub const fn new(path: &'static str, kind: JsonKind) -> Self {
+        Self {
+            path,
+            kind,
+            optional: false,
+        }
+    }
+
+    pub const fn optional(path: &'static str, kind: JsonKind) -> Self {
+        Self {
+            path,
+            kind,
+            optional: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewKind {
+    /// Sends a snapshot of every entity
+    List,
+    /// Sends a snapshot of one entity. The key is taken from the snapshot of
+    /// the entity's list view, which must be checked first.
+    State,
+    /// Sends appended items as they happen
+    Append,
+}
+
+/// Checks an entity decodes into a generated type
+type DecodeFn = fn(&Value) -> Result<(), String>;
+
+/// What a view must deliver
+#[derive(Debug, Clone)]
+pub struct ViewContract {
+    pub view: &'static str,
+    pub kind: ViewKind,
+    pub fields: &'static [FieldContract],
+    decode: Option<DecodeFn>,
+}
+
+impl ViewContract {
+    pub fn list(view: &'static str, fields: &'static [FieldContract]) -> Self {
+        Self {
+            view,
+            kind: ViewKind::List,
+            fields,
+            decode: None,
+        }
+    }
+
+    pub fn state(view: &'static str, fields: &'static [FieldContract]) -> Self {
+        Self {
+            view,
+            kind: ViewKind::State,
+            fields,
+            decode: None,
+        }
+    }
+
+    pub fn append(view: &'static str) -> Self {
+        Self {
+            view,
+            kind: ViewKind::Append,
+            fields: &[],
+            decode: None,
+        }
+    }
+
+    /// Also require snapshot entities to decode into `T`
+    pub fn typed<T: DeserializeOwned>(mut self) -> Self {
+        self.decode = Some(decode_as::<T>);
+        self
+    }
+
+    fn entity(&self) -> &str {
+        self.view.split('/').next().unwrap_or(self.view)
+    }
+}
+
+fn decode_as<T: DeserializeOwned>(value: &Value) -> Result<(), String> {
+    T::deserialize(value).map(drop).map_err(|e| e.to_string())
+}
+
+/// Runs [`ViewContract`]s against a deployment
+#[derive(Debug, Clone)]
+pub struct ContractRunner {
+    url: String,
+    schema_hash: String,
+    timeout: Duration,
+    skip: HashSet<String>,
+}
+
+impl ContractRunner {
+    /// Check the deployment at `url` against the stack with `schema_hash`
+    pub fn new(url: impl Into<String>, schema_hash: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            schema_hash: schema_hash.into(),
+            timeout: DEFAULT_CONTRACT_TIMEOUT,
+            skip: HashSet::new(),
+        }
+    }
+
+    /// How long to wait for each frame a view must deliver
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Views not to check, such as ones that are legitimately empty
+    pub fn skip(mut self, views: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.skip.extend(views.into_iter().map(Into::into));
+        self
+    }
+
+    /// Check every view in order. Fails only when the deployment can't be
+    /// reached; what each view got wrong is in the report.
+    pub async fn run(&self, views: &[ViewContract]) -> Result<ContractReport, HyperStackError> {
+        let mut client = RawClient::connect(self.url.as_str())
+            .await
+            .map_err(|e| e.error)?;
+        let mut keys = HashMap::new();
+        let mut cases = Vec::with_capacity(views.len());
+
+        for view in views {
+            let started = Instant::now();
+            let outcome = if self.skip.contains(view.view) {
+                ContractOutcome::Skipped("on the skip list".to_string())
+            } else {
+                match self.check(&mut client, view, &mut keys).await {
+                    Ok(()) => ContractOutcome::Passed,
+                    Err(CheckError::Skipped(reason)) => ContractOutcome::Skipped(reason),
+                    Err(CheckError::Failed(reason)) => ContractOutcome::Failed(reason),
+                }
+            };
+            cases.push(ContractCase {
+                view: view.view.to_string(),
+                outcome,
+                duration: started.elapsed(),
+            });
+        }
+
+        let _ = client.close().await;
+        Ok(ContractReport {
+            schema_hash: self.schema_hash.clone(),
+            cases,
+        })
+    }
+
+    async fn check(
+        &self,
+        client: &mut RawClient,
+        view: &ViewContract,
+        keys: &mut HashMap<String, String>,
+    ) -> Result<(), CheckError> {
+        let mut sub = Subscription::new(view.view).with_diagnostics(true);
+        if view.kind == ViewKind::State {
+            let key = keys.get(view.entity()).ok_or_else(|| {
+                CheckError::Skipped(format!(
+                    "no {} entity to look up, check its list view first",
+                    view.entity()
+                ))
+            })?;
+            sub = sub.with_key(key.clone());
+        }
+        let id = client
+            .send_subscribe(&sub)
+            .await
+            .map_err(|e| CheckError::Failed(format!("failed to subscribe: {}", e)))?;
+
+        let result = self.check_subscription(client, view, keys).await;
+        let _ = client.send_unsubscribe(&id).await;
+        result
+    }
+
+    async fn check_subscription(
+        &self,
+        client: &mut RawClient,
+        view: &ViewContract,
+        keys: &mut HashMap<String, String>,
+    ) -> Result<(), CheckError> {
+        let ack = self.next_frame(client, view, "subscription ack").await?;
+        if ack.op != "subscribed" {
+            return Err(CheckError::Failed(format!(
+                "expected the subscription ack first, got a {} frame",
+                ack.op
+            )));
+        }
+        let empty = serde_json::from_value::<SubscribedFrame>(ack.data)
+            .ok()
+            .and_then(|ack| ack.diagnostics)
+            .is_some_and(|diagnostics| diagnostics.cache_entries == 0);
+
+        match view.kind {
+            ViewKind::Append => {
+                self.next_frame(client, view, "frame").await?;
+                Ok(())
+            }
+            ViewKind::List | ViewKind::State => {
+                if empty {
+                    return Err(CheckError::Failed(
+                        "the view is empty; skip it if that is expected".to_string(),
+                    ));
+                }
+                let snapshot = self.next_frame(client, view, "snapshot").await?;
+                let first_key = check_snapshot(view, &snapshot).map_err(CheckError::Failed)?;
+                if view.kind == ViewKind::List {
+                    keys.entry(view.entity().to_string()).or_insert(first_key);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The next frame of `view`, ignoring frames of views checked earlier
+    async fn next_frame(
+        &self,
+        client: &mut RawClient,
+        view: &ViewContract,
+        what: &str,
+    ) -> Result<Frame, CheckError> {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let event = tokio::time::timeout_at(deadline, client.next())
+                .await
+                .map_err(|_| {
+                    CheckError::Failed(format!("no {} within {:?}", what, self.timeout))
+                })?;
+            match event {
+                Some(RawEvent::Frame { frame, .. }) if frame.entity == view.view => {
+                    return Ok(frame)
+                }
+                Some(RawEvent::SocketIssue(issue)) if issue.fatal => {
+                    return Err(CheckError::Failed(format!(
+                        "server reported {}: {}",
+                        issue.error, issue.message
+                    )))
+                }
+                Some(RawEvent::Closed(closed)) => {
+                    let reason = closed
+                        .error
+                        .map(|e| e.to_string())
+                        .unwrap_or_else(|| "closed by the server".to_string());
+                    return Err(CheckError::Failed(format!("connection ended: {}", reason)));
+                }
+                None => return Err(CheckError::Failed("connection ended".to_string())),
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+enum CheckError {
+    Failed(String),
+    Skipped(String),
+}
+
+/// Check a snapshot's entities, returning the key of the first
+fn check_snapshot(view: &ViewContract, frame: &Frame) -> Result<String, String> {
+    if frame.op != "snapshot" {
+        return Err(format!("expected a snapshot, got a {} frame", frame.op));
+    }
+    let entities = parse_snapshot_entities(&frame.data);
+    let Some(first) = entities.first() else {
+        return Err("the snapshot has no entities".to_string());
+    };
+
+    let mut problems = Vec::new();
+    if let Some(decode) = view.decode {
+        for entity in &entities {
+            if let Err(e) = decode(&entity.data) {
+                problems.push(format!(
+                    "entity {} does not decode into the generated type: {}",
+                    entity.key, e
+                ));
+            }
+        }
+    }
+    for field in view.fields {
+        let mut present = false;
+        for entity in &entities {
+            match lookup(&entity.data, field.path) {
+                Some(Value::Null) => present = true,
+                Some(value) => {
+                    present = true;
+                    if !field.kind.matches(value) {
+                        problems.push(format!(
+                            "entity {}: {} should be {}, got {}",
+                            entity.key,
+                            field.path,
+                            field.kind.name(),
+                            value
+                        ));
+                    }
+                }
+                None => {}
+            }
+        }
+        if !present && !field.optional {
+            problems.push(format!("no entity has {}", field.path));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(first.key.clone())
+    } else {
+        Err(problems.join("\n"))
+    }
+}
+
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |value, part| value.get(part))
+}
+
+/// What [`ContractRunner::run`] found
+#[derive(Debug, Clone)]
+pub struct ContractReport {
+    /// Schema hash of the stack the views were checked against
+    pub schema_hash: String,
+    /// One case per view, in the order checked
+    pub cases: Vec<ContractCase>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContractCase {
+    pub view: String,
+    pub outcome: ContractOutcome,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContractOutcome {
+    Passed,
+    Failed(String),
+    Skipped(String),
+}
+
+impl ContractReport {
+    pub fn failures(&self) -> impl Iterator<Item = &ContractCase> {
+        self.cases
+            .iter()
+            .filter(|case| matches!(case.outcome, ContractOutcome::Failed(_)))
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Panic listing every view that failed
+    pub fn assert_passed(&self) {
+        let failures: Vec<String> = self
+            .failures()
+            .map(|case| match &case.outcome {
+                ContractOutcome::Failed(reason) => format!("{}: {}", case.view, reason),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert!(
+            failures.is_empty(),
+            "{} of {} views broke the contract of schema {}:\n{}",
+            failures.len(),
+            self.cases.len(),
+            self.schema_hash,
+            failures.join("\n")
+        );
+    }
+
+    /// The report as a JUnit XML test suite, one test case per view
+    pub fn to_junit(&self) -> String {
+        let count = |f: fn(&ContractOutcome) -> bool| {
+            self.cases.iter().filter(|case| f(&case.outcome)).count()
+        };
+        let time: Duration = self.cases.iter().map(|case| case.duration).sum();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuite name=\"live_contract\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            self.cases.len(),
+            count(|outcome| matches!(outcome, ContractOutcome::Failed(_))),
+            count(|outcome| matches!(outcome, ContractOutcome::Skipped(_))),
+            time.as_secs_f64()
+        );
+        let _ = writeln!(
+            xml,
+            "  <properties>\n    <property name=\"schema_hash\" value=\"{}\"/>\n  </properties>",
+            escape(&self.schema_hash)
+        );
+        for case in &self.cases {
+            let _ = write!(
+                xml,
+                "  <testcase classname=\"live_contract\" name=\"{}\" time=\"{:.3}\"",
+                escape(&case.view),
+                case.duration.as_secs_f64()
+            );
+            match &case.outcome {
+                ContractOutcome::Passed => xml.push_str("/>\n"),
+                ContractOutcome::Failed(reason) => {
+                    let first_line = reason.lines().next().unwrap_or_default();
+                    let _ = writeln!(
+                        xml,
+                        ">\n    <failure message=\"{}\">{}</failure>\n  </testcase>",
+                        escape(first_line),
+                        escape(reason)
+                    );
+                }
+                ContractOutcome::Skipped(reason) => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n    <skipped message=\"{}\"/>\n  </testcase>",
+                        escape(reason)
+                    );
+                }
+            }
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    struct Round {
+        state: Option<RoundState>,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    struct RoundState {
+        #[serde(
+            default,
+            deserialize_with = "crate::serde_utils::deserialize_option_u64"
+        )]
+        total: Option<u64>,
+    }
+
+    const ROUND_FIELDS: &[FieldContract] = &[
+        FieldContract::new("state.total", JsonKind::Integer),
+        FieldContract::new("state.motherlode", JsonKind::Bool),
+        FieldContract::optional("state.winner", JsonKind::String),
+    ];
+
+    fn snapshot(entities: Value) -> Frame {
+        Frame {
+            mode: crate::Mode::List,
+            entity: "Round/list".to_string(),
+            op: "snapshot".to_string(),
+            key: String::new(),
+            data: entities,
+            append: vec![],
+            seq: None,
+            continuation: None,
+            complete: None,
+        }
+    }
+
+    #[test]
+    fn snapshot_fields_are_checked_for_presence_and_type() {
+        let view = ViewContract::list("Round/list", ROUND_FIELDS).typed::<Round>();
+        let frame = snapshot(json!([
+            { "key": "1", "data": { "state": { "total": "9007199254740993", "motherlode": null } } },
+            { "key": "2", "data": { "state": { "total": 5 } } },
+        ]));
+        assert_eq!(check_snapshot(&view, &frame), Ok("1".to_string()));
+
+        let frame = snapshot(json!([
+            { "key": "1", "data": { "state": { "total": 1.5 } } },
+            { "key": "2", "data": { "state": "closed" } },
+        ]));
+        let problems = check_snapshot(&view, &frame).unwrap_err();
+        assert!(problems.contains("entity 2 does not decode"), "{problems}");
+        assert!(problems.contains("entity 1: state.total should be an integer, got 1.5"));
+        assert!(problems.contains("no entity has state.motherlode"));
+    }
+
+    #[test]
+    fn junit_report_lists_every_view() {
+        let report = ContractReport {
+            schema_hash: "abc".to_string(),
+            cases: vec![
+                ContractCase {
+                    view: "Round/list".to_string(),
+                    outcome: ContractOutcome::Passed,
+                    duration: Duration::from_millis(20),
+                },
+                ContractCase {
+                    view: "Round/state".to_string(),
+                    outcome: ContractOutcome::Failed("no <snapshot> within 1s".to_string()),
+                    duration: Duration::from_secs(1),
+                },
+                ContractCase {
+                    view: "Round/append".to_string(),
+                    outcome: ContractOutcome::Skipped("on the skip list".to_string()),
+                    duration: Duration::ZERO,
+                },
+            ],
+        };
+        assert!(!report.passed());
+
+        let xml = report.to_junit();
+        assert!(xml.contains("tests=\"3\" failures=\"1\" skipped=\"1\" time=\"1.020\""));
+        assert!(xml.contains("<property name=\"schema_hash\" value=\"abc\"/>"));
+        assert!(xml.contains("name=\"Round/list\" time=\"0.020\"/>"));
+        assert!(xml.contains("<failure message=\"no &lt;snapshot&gt; within 1s\">"));
+        assert!(xml.contains("<skipped message=\"on the skip list\"/>"));
+    }
+}
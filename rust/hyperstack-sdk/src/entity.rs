@@ -22,3 +22,36 @@ pub trait Stack: Sized + Send + Sync + 'static {
     fn name() -> &'static str;
     fn url() -> &'static str;
 }
+
+/// A key identifying a single entity in a state view.
+///
+/// Implemented for raw strings and for the typed `{Entity}Key` builders emitted
+/// by the SDK generator, so either can be passed to [`StateView`](crate::StateView)
+/// lookups.
+///
+/// ```ignore
+/// let round = hs.views.ore_round.state().get(OreRoundKey::new(42)).await;
+/// let same = hs.views.ore_round.state().get("42").await;
+/// ```
+pub trait EntityKey {
+    /// The canonical key string as sent to and received from the server.
+    fn to_key_string(&self) -> String;
+}
+
+impl EntityKey for str {
+    fn to_key_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl EntityKey for String {
+    fn to_key_string(&self) -> String {
+        self.clone()
+    }
+}
+
+impl<K: EntityKey + ?Sized> EntityKey for &K {
+    fn to_key_string(&self) -> String {
+        (**self).to_key_string()
+    }
+}
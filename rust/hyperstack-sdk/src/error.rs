@@ -1,4 +1,6 @@
+use crate::store::DecodeError;
 use serde::Deserialize;
+use std::time::Duration;
 use thiserror::Error;
 use tokio_tungstenite::tungstenite::{self, http::Response};
 
@@ -9,11 +11,19 @@ pub struct SocketIssue {
     pub code: Option<AuthErrorCode>,
     pub retryable: bool,
     pub retry_after: Option<u64>,
+    pub retry_after_ms: Option<u64>,
     pub suggested_action: Option<String>,
     pub docs_url: Option<String>,
     pub fatal: bool,
 }
 
+impl SocketIssue {
+    /// How long the server asked the client to wait before reconnecting.
+    pub fn retry_after_hint(&self) -> Option<Duration> {
+        retry_hint(self.retry_after_ms, self.retry_after)
+    }
+}
+
 impl std::fmt::Display for SocketIssue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&self.message)
@@ -31,6 +41,8 @@ pub(crate) struct SocketIssuePayload {
     #[serde(default)]
     pub retry_after: Option<u64>,
     #[serde(default)]
+    pub retry_after_ms: Option<u64>,
+    #[serde(default)]
     pub suggested_action: Option<String>,
     #[serde(default)]
     pub docs_url: Option<String>,
@@ -49,6 +61,7 @@ impl SocketIssuePayload {
             code: AuthErrorCode::from_wire(&self.code),
             retryable: self.retryable,
             retry_after: self.retry_after,
+            retry_after_ms: self.retry_after_ms,
             suggested_action: self.suggested_action,
             docs_url: self.docs_url,
             fatal: self.fatal,
@@ -179,6 +192,28 @@ impl std::fmt::Display for AuthErrorCode {
     }
 }
 
+/// An operation bounded by one of the client's timeouts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimedOperation {
+    /// Opening the WebSocket, bounded by `connect_timeout`
+    Connect,
+    /// The server acknowledging a subscription, bounded by
+    /// `subscribe_ack_timeout`
+    SubscribeAck,
+    /// A view's initial data arriving, bounded by `snapshot_timeout`
+    Snapshot,
+}
+
+impl std::fmt::Display for TimedOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Connect => "connect",
+            Self::SubscribeAck => "subscription ack",
+            Self::Snapshot => "snapshot",
+        })
+    }
+}
+
 #[derive(Error, Debug, Clone)]
 pub enum HyperStackError {
     #[error("Missing WebSocket URL")]
@@ -214,7 +249,7 @@ pub enum HyperStackError {
     },
 
     #[error("Socket issue: {0}")]
-    SocketIssue(SocketIssue),
+    SocketIssue(Box<SocketIssue>),
 
     #[error("JSON serialization error: {0}")]
     Serialization(String),
@@ -230,12 +265,48 @@ pub enum HyperStackError {
 
     #[error("Channel error: {0}")]
     ChannelError(String),
+
+    #[error("Stream handler failed: {0}")]
+    HandlerFailed(String),
+
+    #[error("Entity {key} in view {view} failed to decode: {message}")]
+    EntityDecode {
+        view: String,
+        key: String,
+        message: String,
+    },
+
+    #[error("{operation} timed out after {after:?}{}", view_context(.view))]
+    Timeout {
+        operation: TimedOperation,
+        /// The view being subscribed or read, if any
+        view: Option<String>,
+        after: Duration,
+    },
+}
+
+fn view_context(view: &Option<String>) -> String {
+    view.as_ref()
+        .map(|view| format!(" (view {view})"))
+        .unwrap_or_default()
 }
 
 #[derive(Debug, Deserialize)]
 struct ErrorPayload {
     error: Option<String>,
     code: Option<String>,
+    #[serde(default)]
+    retry_after: Option<u64>,
+    #[serde(default)]
+    retry_after_ms: Option<u64>,
+}
+
+/// JSON close reason, e.g. `{"code":"backpressure","retry_after_ms":5000}`.
+#[derive(Debug, Deserialize)]
+struct CloseReasonPayload {
+    code: String,
+    #[serde(default)]
+    retry_after_ms: Option<u64>,
 }
 
 impl HyperStackError {
@@ -252,7 +323,7 @@ impl HyperStackError {
 
     pub fn socket_issue(&self) -> Option<&SocketIssue> {
         match self {
-            Self::SocketIssue(issue) => Some(issue),
+            Self::SocketIssue(issue) => Some(issue.as_ref()),
             _ => None,
         }
     }
@@ -267,12 +338,14 @@ impl HyperStackError {
                 code.map(AuthErrorCode::should_retry).unwrap_or(true)
             }
             Self::SocketIssue(issue) => issue.retryable,
-            Self::ConnectionFailed(_) | Self::ConnectionClosed => true,
+            Self::ConnectionFailed(_) | Self::ConnectionClosed | Self::Timeout { .. } => true,
             Self::MissingUrl
             | Self::Serialization(_)
             | Self::MaxReconnectAttempts(_)
             | Self::SubscriptionFailed(_)
-            | Self::ChannelError(_) => false,
+            | Self::ChannelError(_)
+            | Self::HandlerFailed(_)
+            | Self::EntityDecode { .. } => false,
         }
     }
 
@@ -282,6 +355,14 @@ impl HyperStackError {
             .unwrap_or(false)
     }
 
+    pub(crate) fn timeout(operation: TimedOperation, view: Option<&str>, after: Duration) -> Self {
+        Self::Timeout {
+            operation,
+            view: view.map(str::to_string),
+            after,
+        }
+    }
+
     pub(crate) fn from_tungstenite(error: tungstenite::Error) -> Self {
         match error {
             tungstenite::Error::Http(response) => Self::from_http_response(response),
@@ -349,8 +430,34 @@ impl HyperStackError {
         Some(Self::ServerClosed { code, message })
     }
 
+    /// Reconnect delay suggested by a close frame reason.
+    pub fn close_reason_retry_hint(reason: &str) -> Option<Duration> {
+        serde_json::from_str::<CloseReasonPayload>(reason.trim())
+            .ok()
+            .and_then(|payload| retry_hint(payload.retry_after_ms, None))
+    }
+
+    /// Reconnect delay suggested by a rejected handshake, from the body's
+    /// `retry_after_ms`/`retry_after` or the `Retry-After` header.
+    pub fn http_retry_hint(response: &Response<Option<Vec<u8>>>) -> Option<Duration> {
+        let body_hint = response
+            .body()
+            .as_deref()
+            .and_then(|body| serde_json::from_slice::<ErrorPayload>(body).ok())
+            .and_then(|payload| retry_hint(payload.retry_after_ms, payload.retry_after));
+        let header_hint = || {
+            response
+                .headers()
+                .get("Retry-After")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs)
+        };
+        body_hint.or_else(header_hint)
+    }
+
     pub(crate) fn from_socket_issue(issue: SocketIssue) -> Self {
-        Self::SocketIssue(issue)
+        Self::SocketIssue(Box::new(issue))
     }
 }
 
@@ -360,6 +467,16 @@ impl From<serde_json::Error> for HyperStackError {
     }
 }
 
+impl From<DecodeError> for HyperStackError {
+    fn from(value: DecodeError) -> Self {
+        Self::EntityDecode {
+            message: value.error.to_string(),
+            view: value.view,
+            key: value.key,
+        }
+    }
+}
+
 impl From<tungstenite::Error> for HyperStackError {
     fn from(value: tungstenite::Error) -> Self {
         Self::from_tungstenite(value)
@@ -386,6 +503,10 @@ fn parse_error_payload(body: Option<&[u8]>) -> (Option<String>, Option<AuthError
 }
 
 fn parse_close_reason(reason: &str) -> (Option<AuthErrorCode>, String) {
+    if let Ok(payload) = serde_json::from_str::<CloseReasonPayload>(reason) {
+        return (AuthErrorCode::from_wire(&payload.code), payload.code);
+    }
+
     if let Some((wire_code, message)) = reason.split_once(':') {
         let code = AuthErrorCode::from_wire(wire_code);
         let message = message.trim();
@@ -398,6 +519,12 @@ fn parse_close_reason(reason: &str) -> (Option<AuthErrorCode>, String) {
     (None, reason.trim().to_string())
 }
 
+fn retry_hint(retry_after_ms: Option<u64>, retry_after_secs: Option<u64>) -> Option<Duration> {
+    retry_after_ms
+        .map(Duration::from_millis)
+        .or_else(|| retry_after_secs.map(Duration::from_secs))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -478,6 +605,70 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn parses_json_close_reason_with_retry_hint() {
+        let reason = r#"{"code":"egress-limit-exceeded","retry_after_ms":1500}"#;
+        let error = HyperStackError::from_close_reason(reason).expect("close reason should parse");
+
+        assert!(matches!(
+            error,
+            HyperStackError::ServerClosed {
+                code: Some(AuthErrorCode::EgressLimitExceeded),
+                ..
+            }
+        ));
+        assert_eq!(
+            HyperStackError::close_reason_retry_hint(reason),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            HyperStackError::close_reason_retry_hint("server maintenance"),
+            None
+        );
+    }
+
+    #[test]
+    fn handshake_retry_hint_prefers_body_milliseconds() {
+        let response = Response::builder()
+            .status(503)
+            .header("Retry-After", "3")
+            .body(Some(
+                br#"{"error":"overloaded","code":"overloaded","retry_after_ms":2500}"#.to_vec(),
+            ))
+            .expect("response should build");
+        assert_eq!(
+            HyperStackError::http_retry_hint(&response),
+            Some(Duration::from_millis(2500))
+        );
+
+        let response = Response::builder()
+            .status(429)
+            .header("Retry-After", "7")
+            .body(None)
+            .expect("response should build");
+        assert_eq!(
+            HyperStackError::http_retry_hint(&response),
+            Some(Duration::from_secs(7))
+        );
+    }
+
+    #[test]
+    fn timeout_names_operation_and_view() {
+        let error = HyperStackError::timeout(
+            TimedOperation::Snapshot,
+            Some("OreRound/latest"),
+            Duration::from_millis(250),
+        );
+        assert_eq!(
+            error.to_string(),
+            "snapshot timed out after 250ms (view OreRound/latest)"
+        );
+        assert!(error.should_retry());
+
+        let error = HyperStackError::timeout(TimedOperation::Connect, None, Duration::from_secs(2));
+        assert_eq!(error.to_string(), "connect timed out after 2s");
+    }
+
     #[test]
     fn socket_issue_error_uses_issue_retryability() {
         let error = HyperStackError::from_socket_issue(SocketIssue {
@@ -486,6 +677,7 @@ mod tests {
             code: Some(AuthErrorCode::SubscriptionLimitExceeded),
             retryable: false,
             retry_after: None,
+            retry_after_ms: None,
             suggested_action: Some("unsubscribe first".to_string()),
             docs_url: None,
             fatal: false,
@@ -1,13 +1,65 @@
 use flate2::read::GzDecoder;
 use serde::{Deserialize, Serialize};
-use std::io::Read;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use zstd::dict::DecoderDictionary;
 
 const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
 
 fn is_gzip(data: &[u8]) -> bool {
     data.len() >= 2 && data[0] == GZIP_MAGIC[0] && data[1] == GZIP_MAGIC[1]
 }
 
+pub(crate) fn is_zstd(data: &[u8]) -> bool {
+    data.starts_with(&ZSTD_MAGIC)
+}
+
+/// zstd dictionaries downloaded from the server.
+///
+/// The server names dictionaries by string id; zstd frames carry the numeric
+/// id of the dictionary they need, `0` for none.
+#[derive(Default)]
+pub(crate) struct ZstdDictionaries {
+    ids: HashMap<String, u32>,
+    decoders: HashMap<u32, DecoderDictionary<'static>>,
+}
+
+impl ZstdDictionaries {
+    pub(crate) fn contains(&self, id: &str) -> bool {
+        self.ids.contains_key(id)
+    }
+
+    pub(crate) fn insert(&mut self, id: &str, bytes: &[u8]) {
+        let dict_id = zstd::zstd_safe::get_dict_id(bytes).map_or(0, |id| id.get());
+        self.ids.insert(id.to_string(), dict_id);
+        self.decoders
+            .insert(dict_id, DecoderDictionary::copy(bytes));
+    }
+
+    /// Decompress a zstd frame with the dictionary it names
+    pub(crate) fn decompress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        match zstd::zstd_safe::get_dict_id_from_frame(data) {
+            Some(dict_id) => {
+                let dictionary = self.decoders.get(&dict_id.get()).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("frame needs unknown zstd dictionary {}", dict_id),
+                    )
+                })?;
+                zstd::stream::read::Decoder::with_prepared_dictionary(data, dictionary)?
+                    .read_to_end(&mut decompressed)?;
+            }
+            None => {
+                zstd::stream::read::Decoder::new(data)?.read_to_end(&mut decompressed)?;
+            }
+        }
+        Ok(decompressed)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Mode {
@@ -36,6 +88,84 @@ pub struct SubscribedFrame {
     pub mode: Mode,
     #[serde(default)]
     pub sort: Option<SortConfig>,
+    /// Present when the view is already at the server's entity cap
+    #[serde(default)]
+    pub retention: Option<RetentionNotice>,
+    /// Present when the subscription asked for diagnostics
+    #[serde(default)]
+    pub diagnostics: Option<SubscriptionDiagnostics>,
+    /// The only fields the subscription receives, when the server limits
+    /// them by the client's plan or the subscription asked for fewer
+    #[serde(default)]
+    pub fields: Option<Vec<String>>,
+    /// `snapshot_pending` when the server queued the snapshot behind others.
+    /// It follows later, still ahead of any update
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Present when the view is deprecated and served by its replacement
+    #[serde(default)]
+    pub deprecation: Option<DeprecationNotice>,
+}
+
+/// Where the time and bytes of a subscription's initial load went, as
+/// measured by the server.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionDiagnostics {
+    /// Entities in the view's cache when the snapshot was taken
+    pub cache_entries: usize,
+    /// Time the server spent assembling the snapshot
+    pub snapshot_micros: u64,
+    /// Serialized size of the snapshot frames
+    pub snapshot_bytes: u64,
+    /// Size of the snapshot frames after compression
+    pub compressed_bytes: u64,
+    /// Time from the server receiving the subscribe request to queueing the ack
+    pub queue_wait_micros: u64,
+}
+
+/// Server notice that a view is deprecated. Its subscriptions are served
+/// from `replacement` until `sunset_at`, after which the server rejects them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeprecationNotice {
+    /// The view to subscribe to instead
+    pub replacement: String,
+    /// Unix time (ms) from which subscriptions are rejected, if scheduled
+    #[serde(default)]
+    pub sunset_at: Option<u64>,
+}
+
+/// Server notice that a view is at its entity cap and evicting older entities.
+///
+/// Lists from such a view only hold the most recent `cap` entities.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetentionNotice {
+    /// Maximum number of entities the server retains for the view
+    pub cap: usize,
+    /// Evictions per second since the previous notice
+    pub eviction_rate: f64,
+    /// Unix time (ms) of the last update to the oldest retained entity
+    #[serde(default)]
+    pub oldest_retained_at: Option<i64>,
+}
+
+/// Server notice that an entity didn't fit under its frame size limit and
+/// was left out of the stream. Fetch the entity another way, e.g. over REST.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameTooLarge {
+    /// Key of the entity left out, taken from the frame
+    #[serde(default)]
+    pub key: String,
+    /// Serialized size of the frame the entity was in
+    pub bytes: usize,
+    pub max_bytes: usize,
+}
+
+/// Part `part` of `parts` of an entity frame the server split between its
+/// top-level fields to stay under its frame size limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Continuation {
+    pub part: u32,
+    pub parts: u32,
 }
 
 impl SubscribedFrame {
@@ -52,6 +182,9 @@ pub enum Operation {
     Create,
     Snapshot,
     Subscribed,
+    RetentionNotice,
+    FrameTooLarge,
+    History,
 }
 
 impl std::str::FromStr for Operation {
@@ -65,6 +198,9 @@ impl std::str::FromStr for Operation {
             "create" => Operation::Create,
             "snapshot" => Operation::Snapshot,
             "subscribed" => Operation::Subscribed,
+            "retention_notice" => Operation::RetentionNotice,
+            "frame_too_large" => Operation::FrameTooLarge,
+            "history" => Operation::History,
             _ => Operation::Upsert,
         })
     }
@@ -84,6 +220,13 @@ pub struct Frame {
     /// Sequence cursor for ordering and resume capability
     #[serde(skip_serializing_if = "Option::is_none")]
     pub seq: Option<String>,
+    /// Set on the parts of a frame split to stay under the frame size limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub continuation: Option<Continuation>,
+    /// `false` on snapshot pages followed by more pages of the same
+    /// snapshot, `true` on the last page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub complete: Option<bool>,
 }
 
 impl Frame {
@@ -98,6 +241,24 @@ impl Frame {
     pub fn is_snapshot(&self) -> bool {
         self.op == "snapshot"
     }
+
+    /// Slot the server produced the frame at, from its sequence cursor
+    pub fn slot(&self) -> Option<u64> {
+        self.seq
+            .as_deref()
+            .and_then(seq_slot)
+            .or_else(|| entity_slot(&self.data))
+    }
+}
+
+/// Slot of a `slot:ordering` sequence cursor
+pub(crate) fn seq_slot(seq: &str) -> Option<u64> {
+    seq.split(':').next()?.parse().ok()
+}
+
+/// Slot of the latest update merged into an entity, from its `_seq` field
+pub(crate) fn entity_slot(data: &serde_json::Value) -> Option<u64> {
+    data.get("_seq")?.as_str().and_then(seq_slot)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -106,6 +267,17 @@ pub struct SnapshotEntity {
     pub data: serde_json::Value,
 }
 
+/// One append item replayed in a `history` frame, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryItem {
+    pub key: String,
+    pub data: serde_json::Value,
+    #[serde(default)]
+    pub append: Vec<String>,
+    #[serde(default)]
+    pub seq: Option<String>,
+}
+
 fn decompress_gzip(data: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
     let mut decoder = GzDecoder::new(data);
     let mut decompressed = String::new();
@@ -124,6 +296,117 @@ pub fn parse_frame(bytes: &[u8]) -> Result<Frame, serde_json::Error> {
     serde_json::from_str(&text)
 }
 
+/// Position of a frame in its subscription's sequence.
+///
+/// The server numbers the frames of every subscription from 1, so a missing
+/// number means a frame was lost on the way.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameSequence {
+    /// The subscription, as `view:key` or `view:*`
+    pub sub: String,
+    pub frame_seq: u64,
+    /// Set on the first frame after a resync, which starts the sequence over
+    #[serde(default)]
+    pub resync: bool,
+    /// Also set when the resync answers a refresh request
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+/// Parse a server message into its frame sequence, if the server stamped one,
+/// and its frame, if it is one.
+///
+/// Subscription acknowledgements come back as a `subscribed` frame with the
+/// [`SubscribedFrame`] as its data. Servers that predate sequencing send no
+/// sequence.
+pub fn parse_message(bytes: &[u8]) -> (Option<FrameSequence>, Option<Frame>) {
+    let text = match is_gzip(bytes).then(|| decompress_gzip(bytes)) {
+        Some(Ok(decompressed)) => Cow::Owned(decompressed),
+        _ => String::from_utf8_lossy(bytes),
+    };
+
+    (
+        serde_json::from_str(&text).ok(),
+        serde_json::from_str(&text)
+            .ok()
+            .or_else(|| parse_ack_frame(&text)),
+    )
+}
+
+fn parse_ack_frame(text: &str) -> Option<Frame> {
+    let ack: SubscribedFrame = serde_json::from_str(text).ok()?;
+    if !SubscribedFrame::is_subscribed_frame(&ack.op) {
+        return None;
+    }
+
+    Some(Frame {
+        mode: ack.mode,
+        entity: ack.view.clone(),
+        op: ack.op.clone(),
+        key: String::new(),
+        data: serde_json::to_value(&ack).ok()?,
+        append: Vec::new(),
+        seq: None,
+        continuation: None,
+        complete: None,
+    })
+}
+
+/// Checks that each subscription's frames arrive without gaps.
+#[derive(Debug, Default)]
+pub(crate) struct SequenceTracker {
+    subs: HashMap<String, TrackedSequence>,
+}
+
+#[derive(Debug)]
+struct TrackedSequence {
+    last: u64,
+    awaiting_resync: bool,
+}
+
+impl SequenceTracker {
+    /// Record a frame, returning the number expected in its place when it
+    /// reveals a gap.
+    ///
+    /// Each gap is reported once: later frames of the subscription are let
+    /// through unchecked until a resync starts its sequence over.
+    pub(crate) fn observe(&mut self, sequence: &FrameSequence) -> Option<u64> {
+        let starts_over = sequence.resync || sequence.frame_seq == 1;
+        let tracked = match self.subs.get_mut(&sequence.sub) {
+            Some(tracked) if !starts_over => tracked,
+            _ => {
+                self.subs.insert(
+                    sequence.sub.clone(),
+                    TrackedSequence {
+                        last: sequence.frame_seq,
+                        awaiting_resync: false,
+                    },
+                );
+                return None;
+            }
+        };
+
+        if tracked.awaiting_resync {
+            return None;
+        }
+
+        let expected = tracked.last + 1;
+        if sequence.frame_seq == expected {
+            tracked.last = expected;
+            None
+        } else {
+            tracked.awaiting_resync = true;
+            Some(expected)
+        }
+    }
+
+    /// Stop tracking a subscription, e.g. after unsubscribing
+    pub(crate) fn forget(&mut self, sub: &str) {
+        self.subs.remove(sub);
+    }
+}
+
 pub fn parse_snapshot_entities(data: &serde_json::Value) -> Vec<SnapshotEntity> {
     match data {
         serde_json::Value::Array(arr) => arr
@@ -134,6 +417,16 @@ pub fn parse_snapshot_entities(data: &serde_json::Value) -> Vec<SnapshotEntity>
     }
 }
 
+pub fn parse_history_items(data: &serde_json::Value) -> Vec<HistoryItem> {
+    match data {
+        serde_json::Value::Array(arr) => arr
+            .iter()
+            .filter_map(|v| serde_json::from_value(v.clone()).ok())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
 #[allow(dead_code)]
 pub fn parse_subscribed_frame(bytes: &[u8]) -> Result<SubscribedFrame, serde_json::Error> {
     if is_gzip(bytes) {
@@ -192,6 +485,106 @@ mod tests {
         assert_eq!(frame.entity, "test/list");
     }
 
+    #[test]
+    fn test_parse_retention_notice_frame() {
+        let frame_json = r#"{"mode":"list","entity":"test/list","op":"retention_notice","key":"","data":{"cap":500,"eviction_rate":1.5,"oldest_retained_at":1700000000000}}"#;
+        let frame = parse_frame(frame_json.as_bytes()).unwrap();
+        assert_eq!(frame.operation(), Operation::RetentionNotice);
+
+        let notice: RetentionNotice = serde_json::from_value(frame.data).unwrap();
+        assert_eq!(notice.cap, 500);
+        assert_eq!(notice.eviction_rate, 1.5);
+        assert_eq!(notice.oldest_retained_at, Some(1_700_000_000_000));
+    }
+
+    #[test]
+    fn test_parse_history_frame() {
+        let frame_json = r#"{"mode":"append","entity":"trades/append","op":"history","data":[{"key":"a","data":{"n":1},"seq":"1:0"},{"key":"b","data":{"n":2}}]}"#;
+        let frame = parse_frame(frame_json.as_bytes()).unwrap();
+        assert_eq!(frame.operation(), Operation::History);
+        assert_eq!(frame.key, "");
+
+        let items = parse_history_items(&frame.data);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].key, "a");
+        assert_eq!(items[0].seq.as_deref(), Some("1:0"));
+        assert_eq!(items[1].data["n"], 2);
+    }
+
+    fn sequence(sub: &str, frame_seq: u64) -> FrameSequence {
+        FrameSequence {
+            sub: sub.to_string(),
+            frame_seq,
+            resync: false,
+            refresh: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_message_with_sequence() {
+        let message = r#"{"sub":"test/list:*","frameSeq":3,"mode":"list","entity":"test/list","op":"upsert","key":"1","data":{"id":1}}"#;
+        let (stamp, frame) = parse_message(message.as_bytes());
+        assert_eq!(stamp, Some(sequence("test/list:*", 3)));
+        assert_eq!(frame.unwrap().key, "1");
+
+        let ack = r#"{"sub":"test/list:*","frameSeq":1,"resync":true,"op":"subscribed","view":"test/list","mode":"list"}"#;
+        let (stamp, frame) = parse_message(ack.as_bytes());
+        let stamp = stamp.unwrap();
+        assert!(stamp.resync);
+        assert!(!stamp.refresh);
+        let frame = frame.unwrap();
+        assert_eq!(frame.operation(), Operation::Subscribed);
+        assert_eq!(frame.entity, "test/list");
+        assert_eq!(frame.data["mode"], "list");
+
+        let refreshed = r#"{"sub":"test/list:*","frameSeq":1,"resync":true,"refresh":true,"op":"subscribed","view":"test/list","mode":"list"}"#;
+        let (stamp, _) = parse_message(refreshed.as_bytes());
+        let stamp = stamp.unwrap();
+        assert!(stamp.resync && stamp.refresh);
+
+        let unstamped = r#"{"mode":"list","entity":"test/list","op":"upsert","key":"1","data":{}}"#;
+        let (stamp, frame) = parse_message(unstamped.as_bytes());
+        assert!(stamp.is_none());
+        assert!(frame.is_some());
+    }
+
+    #[test]
+    fn test_sequence_tracker_reports_each_gap_once() {
+        let mut tracker = SequenceTracker::default();
+        for n in 1..=3 {
+            assert_eq!(tracker.observe(&sequence("a:*", n)), None);
+        }
+        assert_eq!(tracker.observe(&sequence("b:k", 1)), None);
+
+        assert_eq!(tracker.observe(&sequence("a:*", 5)), Some(4));
+        assert_eq!(tracker.observe(&sequence("a:*", 6)), None);
+        assert_eq!(tracker.observe(&sequence("a:*", 8)), None);
+        assert_eq!(tracker.observe(&sequence("b:k", 2)), None);
+
+        assert_eq!(
+            tracker.observe(&FrameSequence {
+                resync: true,
+                ..sequence("a:*", 1)
+            }),
+            None
+        );
+        assert_eq!(tracker.observe(&sequence("a:*", 2)), None);
+        assert_eq!(tracker.observe(&sequence("a:*", 4)), Some(3));
+    }
+
+    #[test]
+    fn test_frame_slot_from_seq_or_entity() {
+        let frame_json = r#"{"mode":"state","entity":"a/state","op":"patch","key":"k","data":{"_seq":"7:000000000001"},"seq":"9:000000000002"}"#;
+        let mut frame = parse_frame(frame_json.as_bytes()).unwrap();
+        assert_eq!(frame.slot(), Some(9));
+
+        frame.seq = None;
+        assert_eq!(frame.slot(), Some(7));
+
+        frame.data = serde_json::json!({});
+        assert_eq!(frame.slot(), None);
+    }
+
     #[test]
     fn test_gzip_magic_detection() {
         assert!(is_gzip(&[0x1f, 0x8b, 0x08]));
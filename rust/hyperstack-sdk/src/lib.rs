@@ -20,33 +20,63 @@ mod auth;
 mod client;
 mod config;
 mod connection;
+#[cfg(feature = "test-util")]
+pub mod contract;
 mod entity;
 mod error;
 mod frame;
+mod merge;
+#[cfg(feature = "test-util")]
+mod mock;
+pub mod optimistic;
 pub mod prelude;
+mod quality;
+pub mod raw;
+mod resolvable;
+mod scope;
 pub mod serde_utils;
+mod sorted;
 mod store;
 mod stream;
 mod subscription;
+pub mod telemetry;
 pub mod view;
 
 pub use auth::{AuthConfig, AuthToken, TokenTransport};
 pub use client::{HyperStack, HyperStackBuilder};
-pub use config::{ConnectionConfig, HyperStackConfig};
-pub use connection::{ConnectionManager, ConnectionState};
-pub use entity::Stack;
-pub use error::{AuthErrorCode, HyperStackError, SocketIssue};
+pub use config::{ConnectionConfig, HyperStackConfig, ReconnectBackoff};
+pub use connection::{ConnectionEvent, ConnectionManager, ConnectionState};
+pub use entity::{EntityKey, Stack};
+pub use error::{AuthErrorCode, HyperStackError, SocketIssue, TimedOperation};
 pub use frame::{
-    parse_frame, parse_snapshot_entities, try_parse_subscribed_frame, Frame, Mode, Operation,
-    SnapshotEntity,
+    parse_frame, parse_history_items, parse_message, parse_snapshot_entities,
+    try_parse_subscribed_frame, Continuation, DeprecationNotice, Frame, FrameSequence,
+    FrameTooLarge, HistoryItem, Mode, Operation, RetentionNotice, SnapshotEntity, SortOrder,
+    SubscriptionDiagnostics,
+};
+pub use merge::{MergeBuilder, MergedStream, MergedUpdate};
+#[cfg(feature = "test-util")]
+pub use mock::MockHyperStack;
+pub use optimistic::{OptimisticGuard, OptimisticOptions, Reconciliation};
+pub use quality::{
+    ConnectionQuality, QualityChange, QualityPolicy, QualityTier, SubscriptionOverrides,
+};
+pub use resolvable::{PendingMarker, Resolvable};
+pub use scope::{StreamScope, UpdateKind, WatchContext};
+pub use sorted::{FieldKind, ListChange, SortField, SortedWindowStream};
+pub use store::{
+    deep_merge_with_append, DecodeError, DecodeMode, SharedStore, StoreConfig, StoreUpdate,
 };
-pub use store::{deep_merge_with_append, SharedStore, StoreConfig, StoreUpdate};
 pub use stream::{
-    EntityStream, FilterMapStream, FilteredStream, KeyFilter, MapStream, RichEntityStream,
-    RichUpdate, Update, UseStream,
+    AppendItem, AppendStream, EntityStream, FilterMapStream, FilteredStream, KeyFilter, MapStream,
+    RichEntityStream, RichUpdate, Update, UseStream,
 };
 
-pub use subscription::{ClientMessage, Subscription, Unsubscription};
+pub use subscription::{
+    ClientMessage, EntityFilter, FilterOp, Subscription, Unsubscription, UpdateDelivery,
+};
+pub use tokio_util::sync::CancellationToken;
 pub use view::{
-    RichWatchBuilder, StateView, UseBuilder, ViewBuilder, ViewHandle, Views, WatchBuilder,
+    AppendBuilder, GetOptions, RichWatchBuilder, SortedBuilder, StateView, UseBuilder, ViewBuilder,
+    ViewHandle, Views, WatchBuilder,
 };
@@ -0,0 +1,387 @@
+//! Updates from several views as one stream, ordered by slot.
+//!
+//! Each source view maps its [`Update`]s into a common type `E`, typically an
+//! enum with a variant per view:
+//!
+//! ```ignore
+//! enum Activity {
+//!     Round(Update<OreRound>),
+//!     Miner(Update<OreMiner>),
+//! }
+//!
+//! let mut activity = hs
+//!     .merge_streams()
+//!     .source(&hs.views.ore_round.list(), Activity::Round)
+//!     .source(&hs.views.ore_miner.list(), Activity::Miner)
+//!     .listen();
+//!
+//! while let Some(update) = activity.next().await {
+//!     match update {
+//!         MergedUpdate::Update { item, .. } => handle(item),
+//!         MergedUpdate::Resync { view } => println!("{view} started over"),
+//!     }
+//! }
+//! ```
+
+use crate::connection::ConnectionManager;
+use crate::frame::Operation;
+use crate::store::{SharedStore, StoreUpdate};
+use crate::stream::Update;
+use crate::view::ViewHandle;
+use futures_util::Stream;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::{Instant, Sleep};
+use tokio_stream::wrappers::BroadcastStream;
+
+const DEFAULT_REORDER_WINDOW: Duration = Duration::from_millis(50);
+const DEFAULT_MAX_BUFFERED: usize = 256;
+
+/// One item of a [`MergedStream`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergedUpdate<E> {
+    /// An update from `view`, mapped by its source
+    Update {
+        view: String,
+        /// Slot the server produced the update at, when it stamped one
+        slot: Option<u64>,
+        item: E,
+    },
+    /// `view` started over after a resync or reconnect, and its snapshot
+    /// follows. Updates from before the marker may be stale.
+    Resync { view: String },
+}
+
+impl<E> MergedUpdate<E> {
+    pub fn view(&self) -> &str {
+        match self {
+            MergedUpdate::Update { view, .. } => view,
+            MergedUpdate::Resync { view } => view,
+        }
+    }
+
+    pub fn into_item(self) -> Option<E> {
+        match self {
+            MergedUpdate::Update { item, .. } => Some(item),
+            MergedUpdate::Resync { .. } => None,
+        }
+    }
+}
+
+type MapFn<E> = Box<dyn FnMut(StoreUpdate) -> Option<E> + Send>;
+
+fn map_fn<T, E, F>(mut map: F) -> MapFn<E>
+where
+    T: DeserializeOwned,
+    F: FnMut(Update<T>) -> E + Send + 'static,
+{
+    Box::new(move |update| typed_update(update).map(&mut map))
+}
+
+/// The typed [`Update`] of a store update, `None` for updates without an
+/// entity or whose entity doesn't deserialize to `T`
+fn typed_update<T: DeserializeOwned>(update: StoreUpdate) -> Option<Update<T>> {
+    match update.operation {
+        Operation::Delete => Some(Update::Delete { key: update.key }),
+        Operation::Upsert | Operation::Create | Operation::Snapshot => {
+            let data = serde_json::from_value(update.data?).ok()?;
+            Some(Update::Upsert {
+                key: update.key,
+                data,
+            })
+        }
+        Operation::Patch => match serde_json::from_value(update.data?) {
+            Ok(data) => Some(Update::Patch {
+                key: update.key,
+                data,
+            }),
+            Err(e) => {
+                tracing::warn!(
+                    key = %update.key,
+                    error = %e,
+                    "Patch failed to deserialize to full type, skipping"
+                );
+                None
+            }
+        },
+        Operation::Subscribed
+        | Operation::RetentionNotice
+        | Operation::FrameTooLarge
+        | Operation::History => None,
+    }
+}
+
+/// Builder for a [`MergedStream`] over views of a client, from
+/// [`HyperStack::merge_streams`](crate::HyperStack::merge_streams).
+pub struct MergeBuilder<E> {
+    connection: ConnectionManager,
+    store: SharedStore,
+    views: Vec<String>,
+    sources: HashMap<String, MapFn<E>>,
+    reorder_window: Duration,
+    max_buffered: usize,
+}
+
+impl<E: Send + 'static> MergeBuilder<E> {
+    pub(crate) fn new(connection: ConnectionManager, store: SharedStore) -> Self {
+        Self {
+            connection,
+            store,
+            views: Vec::new(),
+            sources: HashMap::new(),
+            reorder_window: DEFAULT_REORDER_WINDOW,
+            max_buffered: DEFAULT_MAX_BUFFERED,
+        }
+    }
+
+    /// Merge in the updates of `view`, mapped by `map`. Adding a view again
+    /// replaces its mapping.
+    pub fn source<T, F>(mut self, view: &ViewHandle<T>, map: F) -> Self
+    where
+        T: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+        F: FnMut(Update<T>) -> E + Send + 'static,
+    {
+        let view = view.view_path().to_string();
+        if self.sources.insert(view.clone(), map_fn(map)).is_none() {
+            self.views.push(view);
+        }
+        self
+    }
+
+    /// How long an update waits for earlier ones from other views (default:
+    /// 50ms). Zero only orders updates that arrive together.
+    pub fn reorder_window(mut self, window: Duration) -> Self {
+        self.reorder_window = window;
+        self
+    }
+
+    /// Most updates the reorder buffer holds before releasing the earliest
+    /// early (default: 256).
+    pub fn max_buffered(mut self, max: usize) -> Self {
+        self.max_buffered = max;
+        self
+    }
+
+    /// Subscribe to every source view on first poll and stream their
+    /// updates.
+    pub fn listen(self) -> MergedStream<E> {
+        // Receive updates before subscribing, so none arrive unseen
+        let mut stream = MergedStream::new(self.store.subscribe())
+            .reorder_window(self.reorder_window)
+            .max_buffered(self.max_buffered);
+        stream.sources = self.sources;
+
+        let connection = self.connection;
+        let views = self.views;
+        stream.subscribing = Some(Box::pin(async move {
+            for view in views {
+                connection.ensure_subscription(&view, None).await;
+            }
+        }));
+        stream
+    }
+}
+
+struct Buffered<E> {
+    /// Slot, then arrival
+    order: (u64, u64),
+    deadline: Instant,
+    update: MergedUpdate<E>,
+}
+
+impl<E> PartialEq for Buffered<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.order == other.order
+    }
+}
+
+impl<E> Eq for Buffered<E> {}
+
+impl<E> PartialOrd for Buffered<E> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<E> Ord for Buffered<E> {
+    /// Reversed, so the heap pops the earliest update first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.order.cmp(&self.order)
+    }
+}
+
+/// Updates from several views as one stream, ordered by slot.
+///
+/// Updates arrive in the order the server sent them, which across views
+/// need not be slot order. The stream holds each update in a small reorder
+/// buffer for up to its [`reorder_window`](Self::reorder_window), so
+/// updates from other views at earlier slots can overtake it. Updates
+/// without a slot keep their place behind the latest slot seen.
+///
+/// All sources share one receiver of the store's updates: while the
+/// consumer is slow, updates from every source wait together, and a
+/// consumer that falls far enough behind to lag loses updates from all of
+/// them alike.
+pub struct MergedStream<E> {
+    inner: BroadcastStream<StoreUpdate>,
+    subscribing: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    sources: HashMap<String, MapFn<E>>,
+    reorder_window: Duration,
+    max_buffered: usize,
+    buffer: BinaryHeap<Buffered<E>>,
+    /// Released by a resync, in order
+    released: VecDeque<MergedUpdate<E>>,
+    latest_slot: u64,
+    arrivals: u64,
+    timer: Option<Pin<Box<Sleep>>>,
+    ended: bool,
+}
+
+// Updates are only ever moved, never pinned
+impl<E> Unpin for MergedStream<E> {}
+
+impl<E: Send + 'static> MergedStream<E> {
+    /// Merge the updates `rx` receives, without subscribing to anything.
+    /// Add views with [`source`](Self::source).
+    pub fn new(rx: broadcast::Receiver<StoreUpdate>) -> Self {
+        Self {
+            inner: BroadcastStream::new(rx),
+            subscribing: None,
+            sources: HashMap::new(),
+            reorder_window: DEFAULT_REORDER_WINDOW,
+            max_buffered: DEFAULT_MAX_BUFFERED,
+            buffer: BinaryHeap::new(),
+            released: VecDeque::new(),
+            latest_slot: 0,
+            arrivals: 0,
+            timer: None,
+            ended: false,
+        }
+    }
+
+    /// Merge in the updates of `view`, mapped by `map`
+    pub fn source<T, F>(mut self, view: impl Into<String>, map: F) -> Self
+    where
+        T: DeserializeOwned,
+        F: FnMut(Update<T>) -> E + Send + 'static,
+    {
+        self.sources.insert(view.into(), map_fn(map));
+        self
+    }
+
+    /// See [`MergeBuilder::reorder_window`]
+    pub fn reorder_window(mut self, window: Duration) -> Self {
+        self.reorder_window = window;
+        self
+    }
+
+    /// See [`MergeBuilder::max_buffered`]
+    pub fn max_buffered(mut self, max: usize) -> Self {
+        self.max_buffered = max.max(1);
+        self
+    }
+
+    fn receive(&mut self, update: StoreUpdate) {
+        let Some(map) = self.sources.get_mut(&update.view) else {
+            return;
+        };
+
+        if update.operation == Operation::Subscribed {
+            // Nothing from before the resync may follow its marker
+            while let Some(buffered) = self.buffer.pop() {
+                self.released.push_back(buffered.update);
+            }
+            self.released
+                .push_back(MergedUpdate::Resync { view: update.view });
+            return;
+        }
+
+        let view = update.view.clone();
+        let slot = update.slot;
+        let Some(item) = map(update) else {
+            return;
+        };
+        if let Some(slot) = slot {
+            self.latest_slot = self.latest_slot.max(slot);
+        }
+        self.arrivals += 1;
+        self.buffer.push(Buffered {
+            order: (slot.unwrap_or(self.latest_slot), self.arrivals),
+            deadline: Instant::now() + self.reorder_window,
+            update: MergedUpdate::Update { view, slot, item },
+        });
+    }
+}
+
+impl<E: Send + 'static> Stream for MergedStream<E> {
+    type Item = MergedUpdate<E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if let Some(subscribing) = &mut this.subscribing {
+            if subscribing.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.subscribing = None;
+        }
+
+        loop {
+            if let Some(update) = this.released.pop_front() {
+                return Poll::Ready(Some(update));
+            }
+
+            // Stop receiving once the buffer is full, so a slow consumer
+            // holds back every source
+            while !this.ended && this.buffer.len() < this.max_buffered {
+                match Pin::new(&mut this.inner).poll_next(cx) {
+                    Poll::Ready(Some(Ok(update))) => {
+                        this.receive(update);
+                        if !this.released.is_empty() {
+                            break;
+                        }
+                    }
+                    Poll::Ready(Some(Err(_lagged))) => {
+                        tracing::warn!("MergedStream lagged behind, some messages were dropped");
+                    }
+                    Poll::Ready(None) => this.ended = true,
+                    Poll::Pending => break,
+                }
+            }
+            if !this.released.is_empty() {
+                continue;
+            }
+
+            let Some(earliest) = this.buffer.peek() else {
+                return if this.ended {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Pending
+                };
+            };
+            if this.ended
+                || this.buffer.len() >= this.max_buffered
+                || earliest.deadline <= Instant::now()
+            {
+                let earliest = this.buffer.pop().expect("peeked");
+                return Poll::Ready(Some(earliest.update));
+            }
+
+            let deadline = earliest.deadline;
+            let timer = this
+                .timer
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+            timer.as_mut().reset(deadline);
+            if timer.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! Offline client for unit-testing code written against a generated stack.
+//!
+//! [`MockHyperStack`] exposes the same `views` as [`HyperStack`](crate::HyperStack)
+//! but never opens a socket. Tests feed entities into views directly and
+//! inspect which subscriptions the code under test opened.
+//!
+//! ```ignore
+//! use hyperstack_sdk::MockHyperStack;
+//! use hyperstack_stacks::ore::{OreRound, OreStreamStack};
+//!
+//! let mock = MockHyperStack::<OreStreamStack>::new();
+//! let latest = mock.views.ore_round.latest();
+//!
+//! let consumer = tokio::spawn(async move { latest.listen().next().await });
+//! mock.wait_for_subscription("OreRound/latest").await;
+//! mock.views.ore_round.latest().push(OreRound::default()).await;
+//!
+//! assert!(consumer.await?.is_some());
+//! ```
+//!
+//! Requires the `test-util` feature.
+
+use crate::connection::{ConnectionCommand, ConnectionManager, ConnectionState};
+use crate::entity::Stack;
+use crate::merge::MergeBuilder;
+use crate::scope::StreamScope;
+use crate::store::SharedStore;
+use crate::subscription::{Subscription, SubscriptionRegistry, Unsubscription};
+use crate::view::{ViewBuilder, Views};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, RwLock};
+
+#[derive(Debug, Default)]
+struct CommandLog {
+    subscriptions: Vec<Subscription>,
+    unsubscriptions: Vec<Unsubscription>,
+    refreshes: Vec<Unsubscription>,
+}
+
+/// Receives the commands an offline [`ConnectionManager`] would have sent.
+pub(crate) struct CommandRecorder {
+    log: watch::Sender<CommandLog>,
+}
+
+impl CommandRecorder {
+    fn new() -> Self {
+        Self {
+            log: watch::channel(CommandLog::default()).0,
+        }
+    }
+
+    /// Mirror the connection loop's bookkeeping without a socket.
+    pub(crate) async fn record(
+        &self,
+        command: ConnectionCommand,
+        registry: &RwLock<SubscriptionRegistry>,
+        state: &RwLock<ConnectionState>,
+    ) {
+        match command {
+            ConnectionCommand::Subscribe(sub) => {
+                registry.write().await.add(sub.clone());
+                self.log.send_modify(|log| log.subscriptions.push(sub));
+            }
+            ConnectionCommand::Unsubscribe(unsub) => {
+                registry.write().await.remove(&unsub);
+                self.log.send_modify(|log| log.unsubscriptions.push(unsub));
+            }
+            ConnectionCommand::Refresh(refresh) => {
+                self.log.send_modify(|log| log.refreshes.push(refresh));
+            }
+            ConnectionCommand::Disconnect => {
+                *state.write().await = ConnectionState::Disconnected;
+            }
+        }
+    }
+}
+
+/// A [`HyperStack`](crate::HyperStack) stand-in for application unit tests.
+///
+/// Views behave as on a live client except that their data comes from
+/// [`ViewHandle::push`](crate::ViewHandle::push) and
+/// [`StateView::set`](crate::StateView::set), and reads never wait for
+/// initial data.
+pub struct MockHyperStack<S: Stack> {
+    connection: ConnectionManager,
+    store: SharedStore,
+    recorder: Arc<CommandRecorder>,
+    pub views: S::Views,
+    _stack: PhantomData<S>,
+}
+
+impl<S: Stack> MockHyperStack<S> {
+    pub fn new() -> Self {
+        let recorder = Arc::new(CommandRecorder::new());
+        let connection = ConnectionManager::offline(recorder.clone());
+        let store = SharedStore::new();
+        let views = S::Views::from_builder(ViewBuilder::new(
+            connection.clone(),
+            store.clone(),
+            Duration::ZERO,
+        ));
+
+        Self {
+            connection,
+            store,
+            recorder,
+            views,
+            _stack: PhantomData,
+        }
+    }
+
+    pub async fn connection_state(&self) -> ConnectionState {
+        self.connection.state().await
+    }
+
+    pub fn store(&self) -> &SharedStore {
+        &self.store
+    }
+
+    /// Create a [`StreamScope`] bound to the mock connection.
+    pub fn scope(&self) -> StreamScope {
+        StreamScope::new(self.connection.clone())
+    }
+
+    /// Create a [`MergeBuilder`] bound to the mock connection.
+    pub fn merge_streams<E: Send + 'static>(&self) -> MergeBuilder<E> {
+        MergeBuilder::new(self.connection.clone(), self.store.clone())
+    }
+
+    /// Every subscription opened so far, in order.
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.recorder.log.borrow().subscriptions.clone()
+    }
+
+    /// Every unsubscription sent so far, in order.
+    pub fn unsubscriptions(&self) -> Vec<Unsubscription> {
+        self.recorder.log.borrow().unsubscriptions.clone()
+    }
+
+    /// Every refresh requested so far, in order.
+    pub fn refreshes(&self) -> Vec<Unsubscription> {
+        self.recorder.log.borrow().refreshes.clone()
+    }
+
+    /// Whether a subscription to `view` has been opened.
+    pub fn is_subscribed(&self, view: &str) -> bool {
+        self.recorder
+            .log
+            .borrow()
+            .subscriptions
+            .iter()
+            .any(|sub| sub.view == view)
+    }
+
+    /// Wait until something subscribes to `view`.
+    ///
+    /// Streams start receiving updates before they subscribe, so once this
+    /// returns, entities pushed to `view` reach them.
+    pub async fn wait_for_subscription(&self, view: &str) -> Subscription {
+        let mut rx = self.recorder.log.subscribe();
+        let log = rx
+            .wait_for(|log| log.subscriptions.iter().any(|sub| sub.view == view))
+            .await
+            .expect("recorder is owned by the mock");
+        log.subscriptions
+            .iter()
+            .find(|sub| sub.view == view)
+            .cloned()
+            .expect("wait_for matched a subscription")
+    }
+}
+
+impl<S: Stack> Default for MockHyperStack<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::SortOrder;
+    use crate::sorted::{FieldKind, ListChange, SortField};
+    use crate::subscription::{EntityFilter, FilterOp};
+    use crate::view::{StateView, ViewHandle};
+    use futures_util::StreamExt;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Round {
+        id: u64,
+    }
+
+    struct RoundViews {
+        latest: ViewHandle<Round>,
+        state: StateView<Round>,
+    }
+
+    impl Views for RoundViews {
+        fn from_builder(builder: ViewBuilder) -> Self {
+            Self {
+                latest: builder.view("Round/latest"),
+                state: StateView::new(
+                    builder.connection().clone(),
+                    builder.store().clone(),
+                    "Round/state".to_string(),
+                    builder.initial_data_timeout(),
+                ),
+            }
+        }
+    }
+
+    struct RoundStack;
+
+    impl Stack for RoundStack {
+        type Views = RoundViews;
+
+        fn name() -> &'static str {
+            "round"
+        }
+
+        fn url() -> &'static str {
+            "ws://unused"
+        }
+    }
+
+    #[tokio::test]
+    async fn pushed_entities_reach_listeners() {
+        let mock = MockHyperStack::<RoundStack>::new();
+        let mut stream = mock.views.latest.listen();
+
+        let next = tokio::spawn(async move { stream.next().await });
+        mock.wait_for_subscription("Round/latest").await;
+        mock.views.latest.push(Round { id: 7 }).await;
+
+        assert_eq!(next.await.unwrap(), Some(Round { id: 7 }));
+        assert_eq!(mock.views.latest.get().await, vec![Round { id: 7 }]);
+    }
+
+    #[tokio::test]
+    async fn sorted_windows_follow_pushed_entities() {
+        let mock = MockHyperStack::<RoundStack>::new();
+        for id in [3, 9, 5] {
+            mock.views
+                .latest
+                .push_keyed(id.to_string(), Round { id })
+                .await;
+        }
+
+        let mut top = mock
+            .views
+            .latest
+            .sorted_by(SortField::new(&["id"], FieldKind::Numeric), SortOrder::Desc)
+            .window(0..2)
+            .listen();
+
+        let mut items = Vec::new();
+        for change in top.next().await.unwrap() {
+            change.apply(&mut items);
+        }
+        assert_eq!(items, vec![Round { id: 9 }, Round { id: 5 }]);
+
+        mock.views.latest.push_keyed("3", Round { id: 7 }).await;
+        let changes = top.next().await.unwrap();
+        assert!(matches!(
+            changes[..],
+            [
+                ListChange::Remove { index: 1, .. },
+                ListChange::Insert { index: 1, .. }
+            ]
+        ));
+        for change in changes {
+            change.apply(&mut items);
+        }
+        assert_eq!(items, vec![Round { id: 9 }, Round { id: 7 }]);
+    }
+
+    #[tokio::test]
+    async fn state_views_serve_set_entities() {
+        let mock = MockHyperStack::<RoundStack>::new();
+        assert_eq!(mock.views.state.get("a").await, None);
+
+        mock.views.state.set("a", Round { id: 1 }).await;
+        assert_eq!(mock.views.state.get("a").await, Some(Round { id: 1 }));
+
+        mock.views.state.remove("a").await;
+        assert_eq!(mock.views.state.get("a").await, None);
+
+        let subscriptions = mock.subscriptions();
+        assert_eq!(subscriptions.len(), 1, "repeat gets reuse the subscription");
+        assert_eq!(subscriptions[0].view, "Round/state");
+        assert_eq!(subscriptions[0].key.as_deref(), Some("a"));
+    }
+
+    #[tokio::test]
+    async fn filtered_watches_send_their_filter() {
+        let mock = MockHyperStack::<RoundStack>::new();
+        let mut stream = mock.views.latest.watch().filter("id", FilterOp::Gte, 5);
+
+        let _next = tokio::spawn(async move { stream.next().await });
+        let subscription = mock.wait_for_subscription("Round/latest").await;
+        assert_eq!(
+            subscription.filter,
+            Some(Box::new(EntityFilter::new("id", FilterOp::Gte, 5)))
+        );
+        assert_eq!(
+            serde_json::to_value(&subscription).unwrap()["filter"],
+            serde_json::json!({ "field": "id", "op": "gte", "value": 5 })
+        );
+    }
+
+    #[tokio::test]
+    async fn delta_watches_ask_for_deltas() {
+        let mock = MockHyperStack::<RoundStack>::new();
+        let mut stream = mock.views.latest.watch().delta(true);
+
+        let _next = tokio::spawn(async move { stream.next().await });
+        let subscription = mock.wait_for_subscription("Round/latest").await;
+        assert_eq!(subscription.delta, Some(true));
+        assert_eq!(
+            serde_json::to_value(&subscription).unwrap()["delta"],
+            serde_json::json!(true)
+        );
+    }
+}
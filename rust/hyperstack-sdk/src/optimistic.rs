@@ -0,0 +1,229 @@
+//! Local optimistic updates on state entities.
+//!
+//! [`StateView::optimistic`](crate::StateView::optimistic) overlays a patch on
+//! the stored entity right after a transaction is sent, so the UI shows the
+//! expected state before the chain confirms it. Reads and streams of the key
+//! see the overlaid value, flagged with `"optimistic": true`.
+//!
+//! An overlay stays until one of:
+//!
+//! - a server frame for the key arrives from a slot after
+//!   [`OptimisticOptions::after_slot`] (any server frame, without a
+//!   threshold). The overlay is dropped and the patch is compared with the
+//!   server value: [`Reconciliation::Confirmed`] when every patched field
+//!   matches, [`Reconciliation::Conflict`] otherwise;
+//! - the [`OptimisticOptions::timeout`] elapses: [`Reconciliation::Expired`];
+//! - the guard is cancelled: [`Reconciliation::Cancelled`].
+//!
+//! Several overlays on one key are applied in the order they were created,
+//! so a later patch wins where two patches touch the same field.
+
+use crate::store::{deep_merge_with_append, SharedStore};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// How long an overlay waits for a confirming server frame by default
+pub const DEFAULT_OPTIMISTIC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Field marking an entity value that includes optimistic overlays
+pub const OPTIMISTIC_FIELD: &str = "optimistic";
+
+/// How an optimistic overlay was resolved
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reconciliation {
+    /// The server value agrees with every field of the patch
+    Confirmed,
+    /// The server value disagrees with the patch
+    Conflict {
+        /// The server value that settled the overlay; `None` when deleted
+        server: Option<Value>,
+    },
+    /// No settling server frame arrived before the timeout
+    Expired,
+    /// The overlay was removed through its guard
+    Cancelled,
+}
+
+type ReconcileCallback = Box<dyn FnOnce(Reconciliation) + Send + Sync>;
+
+/// Options for [`StateView::optimistic_with`](crate::StateView::optimistic_with)
+pub struct OptimisticOptions {
+    pub(crate) after_slot: Option<u64>,
+    pub(crate) timeout: Duration,
+    pub(crate) on_reconcile: Option<ReconcileCallback>,
+}
+
+impl Default for OptimisticOptions {
+    fn default() -> Self {
+        Self {
+            after_slot: None,
+            timeout: DEFAULT_OPTIMISTIC_TIMEOUT,
+            on_reconcile: None,
+        }
+    }
+}
+
+impl OptimisticOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only let server frames from a slot after `slot` settle the overlay,
+    /// typically the slot the transaction was sent at
+    pub fn after_slot(mut self, slot: u64) -> Self {
+        self.after_slot = Some(slot);
+        self
+    }
+
+    /// Drop the overlay as expired after `timeout` (default: 30s)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Call `callback` with the result once the overlay is resolved
+    pub fn on_reconcile<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(Reconciliation) + Send + Sync + 'static,
+    {
+        self.on_reconcile = Some(Box::new(callback));
+        self
+    }
+}
+
+pub(crate) struct Overlay {
+    pub(crate) id: u64,
+    pub(crate) patch: Value,
+    after_slot: Option<u64>,
+    on_reconcile: Option<ReconcileCallback>,
+    result_tx: Option<oneshot::Sender<Reconciliation>>,
+}
+
+impl Overlay {
+    pub(crate) fn new(
+        id: u64,
+        patch: Value,
+        options: OptimisticOptions,
+    ) -> (Self, oneshot::Receiver<Reconciliation>) {
+        let (result_tx, result_rx) = oneshot::channel();
+        let overlay = Self {
+            id,
+            patch,
+            after_slot: options.after_slot,
+            on_reconcile: options.on_reconcile,
+            result_tx: Some(result_tx),
+        };
+        (overlay, result_rx)
+    }
+
+    /// Whether a server frame from `slot` settles the overlay
+    pub(crate) fn settled_by(&self, slot: Option<u64>) -> bool {
+        match self.after_slot {
+            None => true,
+            Some(threshold) => slot.is_some_and(|slot| slot > threshold),
+        }
+    }
+
+    /// Compare the patch with the server value that settled the overlay
+    pub(crate) fn reconcile(&self, server: Option<&Value>) -> Reconciliation {
+        if agrees(server, &self.patch) {
+            Reconciliation::Confirmed
+        } else {
+            Reconciliation::Conflict {
+                server: server.cloned(),
+            }
+        }
+    }
+
+    pub(crate) fn resolve(mut self, result: Reconciliation) {
+        if let Some(callback) = self.on_reconcile.take() {
+            callback(result.clone());
+        }
+        if let Some(result_tx) = self.result_tx.take() {
+            let _ = result_tx.send(result);
+        }
+    }
+}
+
+/// Whether every field of `patch` has the same value in `server`
+fn agrees(server: Option<&Value>, patch: &Value) -> bool {
+    match (server, patch) {
+        (Some(Value::Object(server)), Value::Object(patch)) => patch
+            .iter()
+            .all(|(field, value)| agrees(server.get(field), value)),
+        (Some(server), patch) => server == patch,
+        (None, patch) => patch.is_null(),
+    }
+}
+
+/// Apply `overlays` to `server` in creation order and flag the result
+pub(crate) fn compose(server: Option<&Value>, overlays: &[Overlay]) -> Option<Value> {
+    if overlays.is_empty() {
+        return server.cloned();
+    }
+    let mut value = server.cloned().unwrap_or_else(|| serde_json::json!({}));
+    for overlay in overlays {
+        deep_merge_with_append(&mut value, &overlay.patch, &[], "");
+    }
+    if let Value::Object(map) = &mut value {
+        map.insert(OPTIMISTIC_FIELD.to_string(), Value::Bool(true));
+    }
+    Some(value)
+}
+
+/// Handle to an optimistic overlay.
+///
+/// Dropping the guard leaves the overlay in place until it is reconciled or
+/// expires; use [`cancel`](Self::cancel) to remove it early.
+pub struct OptimisticGuard {
+    store: SharedStore,
+    view: String,
+    key: String,
+    id: u64,
+    result_rx: oneshot::Receiver<Reconciliation>,
+}
+
+impl OptimisticGuard {
+    pub(crate) fn new(
+        store: SharedStore,
+        view: String,
+        key: String,
+        id: u64,
+        result_rx: oneshot::Receiver<Reconciliation>,
+    ) -> Self {
+        Self {
+            store,
+            view,
+            key,
+            id,
+            result_rx,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Remove the overlay unless it was already resolved
+    pub async fn cancel(self) {
+        self.store
+            .resolve_overlay(&self.view, &self.key, self.id, Reconciliation::Cancelled)
+            .await;
+    }
+
+    /// Wait until the overlay is resolved
+    pub async fn reconciled(self) -> Reconciliation {
+        self.result_rx.await.unwrap_or(Reconciliation::Cancelled)
+    }
+}
+
+impl std::fmt::Debug for OptimisticGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OptimisticGuard")
+            .field("view", &self.view)
+            .field("key", &self.key)
+            .field("id", &self.id)
+            .finish()
+    }
+}
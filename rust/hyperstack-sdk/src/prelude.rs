@@ -1,8 +1,14 @@
 pub use crate::{
-    AuthConfig, AuthErrorCode, AuthToken, EntityStream, FilterMapStream, FilteredStream,
-    HyperStack, HyperStackBuilder, HyperStackError, MapStream, RichEntityStream, RichUpdate,
-    RichWatchBuilder, SocketIssue, Stack, StateView, TokenTransport, Update, UseBuilder, UseStream,
-    ViewBuilder, ViewHandle, Views, WatchBuilder,
+    AppendBuilder, AppendItem, AuthConfig, AuthErrorCode, AuthToken, ConnectionEvent, EntityKey,
+    EntityStream, FieldKind, FilterMapStream, FilteredStream, GetOptions, HyperStack,
+    HyperStackBuilder, HyperStackError, ListChange, MapStream, MergedUpdate, OptimisticGuard,
+    OptimisticOptions, Reconciliation, Resolvable, RichEntityStream, RichUpdate, RichWatchBuilder,
+    SocketIssue, SortField, SortOrder, SortedBuilder, SortedWindowStream, Stack, StateView,
+    StreamScope, TokenTransport, Update, UpdateDelivery, UpdateKind, UseBuilder, UseStream,
+    ViewBuilder, ViewHandle, Views, WatchBuilder, WatchContext,
 };
 
 pub use futures_util::StreamExt;
+
+#[cfg(feature = "test-util")]
+pub use crate::MockHyperStack;
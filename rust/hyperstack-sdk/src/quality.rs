@@ -0,0 +1,414 @@
+//! Connection quality tracking and subscription downgrades on poor links.
+//!
+//! Every ping interval the connection samples heartbeat round trips, frame
+//! gaps and bytes received into a [`QualityTier`]. When a new tier holds for
+//! [`QualityPolicy::hold_samples`] samples in a row, list subscriptions are
+//! renegotiated with the options the policy maps that tier to.
+
+use crate::subscription::Subscription;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// How well the connection is keeping up, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum QualityTier {
+    #[default]
+    Good,
+    Degraded,
+    Poor,
+}
+
+/// Signals behind the current [`QualityTier`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ConnectionQuality {
+    pub tier: QualityTier,
+    /// Round trip of the latest heartbeat, or how long the outstanding one
+    /// has gone unanswered when that is longer
+    pub rtt: Option<Duration>,
+    /// Frame gaps seen during the last sample
+    pub recent_gaps: u64,
+    /// Bytes received per second during the last sample
+    pub bytes_per_sec: f64,
+}
+
+/// A change of [`QualityTier`], after hysteresis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityChange {
+    pub from: QualityTier,
+    pub to: QualityTier,
+    pub quality: ConnectionQuality,
+}
+
+/// Subscription options that replace the application's own while a tier is
+/// in effect. Unset options keep the application's value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubscriptionOverrides {
+    pub take: Option<u32>,
+    pub with_snapshot: Option<bool>,
+    pub snapshot_limit: Option<usize>,
+}
+
+impl SubscriptionOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver at most `take` entities
+    pub fn take(mut self, take: u32) -> Self {
+        self.take = Some(take);
+        self
+    }
+
+    /// Skip the snapshot when the subscription is renegotiated, so only live
+    /// updates are delivered
+    pub fn with_snapshot(mut self, with_snapshot: bool) -> Self {
+        self.with_snapshot = Some(with_snapshot);
+        self
+    }
+
+    /// Cap the snapshot sent when the subscription is renegotiated
+    pub fn snapshot_limit(mut self, limit: usize) -> Self {
+        self.snapshot_limit = Some(limit);
+        self
+    }
+
+    fn apply(&self, sub: &Subscription) -> Subscription {
+        let mut sub = sub.clone();
+        if let Some(take) = self.take {
+            sub.take = Some(sub.take.map_or(take, |own| own.min(take)));
+        }
+        if let Some(with_snapshot) = self.with_snapshot {
+            sub.with_snapshot = Some(with_snapshot);
+        }
+        if let Some(limit) = self.snapshot_limit {
+            sub.snapshot_limit = Some(sub.snapshot_limit.map_or(limit, |own| own.min(limit)));
+        }
+        sub
+    }
+}
+
+/// Thresholds for classifying the connection and the subscription options
+/// to use in each tier.
+///
+/// Without any [`on`](Self::on) entries the connection quality is still
+/// tracked and reported, but subscriptions are never renegotiated.
+#[derive(Debug, Clone)]
+pub struct QualityPolicy {
+    /// Heartbeat round trip at which the connection counts as degraded
+    pub degraded_rtt: Duration,
+    /// Heartbeat round trip at which the connection counts as poor
+    pub poor_rtt: Duration,
+    /// Frame gaps within one sample at which the connection counts as degraded
+    pub degraded_gaps: u64,
+    /// Frame gaps within one sample at which the connection counts as poor
+    pub poor_gaps: u64,
+    /// Samples in a row a new tier must hold before it takes effect
+    pub hold_samples: u32,
+    overrides: BTreeMap<QualityTier, SubscriptionOverrides>,
+}
+
+impl Default for QualityPolicy {
+    fn default() -> Self {
+        Self {
+            degraded_rtt: Duration::from_millis(500),
+            poor_rtt: Duration::from_millis(2000),
+            degraded_gaps: 1,
+            poor_gaps: 3,
+            hold_samples: 3,
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl QualityPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renegotiate list subscriptions with `overrides` while in `tier`
+    pub fn on(mut self, tier: QualityTier, overrides: SubscriptionOverrides) -> Self {
+        self.overrides.insert(tier, overrides);
+        self
+    }
+
+    pub fn rtt_thresholds(mut self, degraded: Duration, poor: Duration) -> Self {
+        self.degraded_rtt = degraded;
+        self.poor_rtt = poor;
+        self
+    }
+
+    pub fn gap_thresholds(mut self, degraded: u64, poor: u64) -> Self {
+        self.degraded_gaps = degraded;
+        self.poor_gaps = poor;
+        self
+    }
+
+    pub fn hold_samples(mut self, samples: u32) -> Self {
+        self.hold_samples = samples.max(1);
+        self
+    }
+
+    pub fn overrides(&self, tier: QualityTier) -> Option<&SubscriptionOverrides> {
+        self.overrides.get(&tier)
+    }
+
+    /// The subscription to send while in `tier`. Only list subscriptions
+    /// (those without a key) are downgraded.
+    pub(crate) fn negotiate(&self, tier: QualityTier, sub: &Subscription) -> Subscription {
+        match self.overrides(tier) {
+            Some(overrides) if sub.key.is_none() => overrides.apply(sub),
+            _ => sub.clone(),
+        }
+    }
+
+    /// Whether moving between the tiers changes what list subscriptions ask for
+    pub(crate) fn renegotiates(&self, from: QualityTier, to: QualityTier) -> bool {
+        self.overrides(from) != self.overrides(to)
+    }
+
+    fn classify(&self, rtt: Option<Duration>, gaps: u64) -> QualityTier {
+        let by_rtt = match rtt {
+            Some(rtt) if rtt >= self.poor_rtt => QualityTier::Poor,
+            Some(rtt) if rtt >= self.degraded_rtt => QualityTier::Degraded,
+            _ => QualityTier::Good,
+        };
+        let by_gaps = if gaps >= self.poor_gaps {
+            QualityTier::Poor
+        } else if gaps >= self.degraded_gaps {
+            QualityTier::Degraded
+        } else {
+            QualityTier::Good
+        };
+        by_rtt.max(by_gaps)
+    }
+}
+
+/// Collects quality signals for one client and turns them into tier changes.
+#[derive(Debug)]
+pub(crate) struct QualityMonitor {
+    policy: QualityPolicy,
+    quality: ConnectionQuality,
+    candidate: Option<(QualityTier, u32)>,
+    ping_sent_at: Option<Instant>,
+    gaps_at_last_sample: u64,
+    window_bytes: u64,
+    window_start: Instant,
+}
+
+impl QualityMonitor {
+    pub(crate) fn new(policy: QualityPolicy, now: Instant) -> Self {
+        Self {
+            policy,
+            quality: ConnectionQuality::default(),
+            candidate: None,
+            ping_sent_at: None,
+            gaps_at_last_sample: 0,
+            window_bytes: 0,
+            window_start: now,
+        }
+    }
+
+    pub(crate) fn policy(&self) -> &QualityPolicy {
+        &self.policy
+    }
+
+    pub(crate) fn tier(&self) -> QualityTier {
+        self.quality.tier
+    }
+
+    pub(crate) fn quality(&self) -> ConnectionQuality {
+        self.quality
+    }
+
+    /// Forget the heartbeat in flight, e.g. when a new connection starts.
+    /// The tier carries over: a poor network usually stays poor.
+    pub(crate) fn reset_connection(&mut self, now: Instant, total_gaps: u64) {
+        self.ping_sent_at = None;
+        self.gaps_at_last_sample = total_gaps;
+        self.window_bytes = 0;
+        self.window_start = now;
+    }
+
+    /// Whether a heartbeat should go out now; only one is kept in flight
+    pub(crate) fn ping_sent(&mut self, now: Instant) -> bool {
+        if self.ping_sent_at.is_some() {
+            return false;
+        }
+        self.ping_sent_at = Some(now);
+        true
+    }
+
+    pub(crate) fn pong_received(&mut self, now: Instant) {
+        if let Some(sent_at) = self.ping_sent_at.take() {
+            self.record_rtt(now.saturating_duration_since(sent_at));
+        }
+    }
+
+    pub(crate) fn record_rtt(&mut self, rtt: Duration) {
+        self.quality.rtt = Some(rtt);
+    }
+
+    pub(crate) fn record_bytes(&mut self, bytes: usize) {
+        self.window_bytes += bytes as u64;
+    }
+
+    /// Close the current sample. `total_gaps` is the connection's running
+    /// gap count. Returns the tier change once a new tier has held for
+    /// `hold_samples` samples.
+    pub(crate) fn sample(&mut self, total_gaps: u64, now: Instant) -> Option<QualityChange> {
+        let elapsed = now.saturating_duration_since(self.window_start);
+        self.quality.bytes_per_sec = if elapsed.is_zero() {
+            0.0
+        } else {
+            self.window_bytes as f64 / elapsed.as_secs_f64()
+        };
+        self.quality.recent_gaps = total_gaps.saturating_sub(self.gaps_at_last_sample);
+        self.gaps_at_last_sample = total_gaps;
+        self.window_bytes = 0;
+        self.window_start = now;
+
+        // A heartbeat that is taking longer than the last round trip is the
+        // better estimate of the link right now
+        if let Some(sent_at) = self.ping_sent_at {
+            let waiting = now.saturating_duration_since(sent_at);
+            if self.quality.rtt.is_none_or(|rtt| waiting > rtt) {
+                self.quality.rtt = Some(waiting);
+            }
+        }
+
+        let observed = self
+            .policy
+            .classify(self.quality.rtt, self.quality.recent_gaps);
+        if observed == self.quality.tier {
+            self.candidate = None;
+            return None;
+        }
+
+        let held = match self.candidate {
+            Some((tier, held)) if tier == observed => held + 1,
+            _ => 1,
+        };
+        if held < self.policy.hold_samples {
+            self.candidate = Some((observed, held));
+            return None;
+        }
+
+        self.candidate = None;
+        let from = self.quality.tier;
+        self.quality.tier = observed;
+        Some(QualityChange {
+            from,
+            to: observed,
+            quality: self.quality,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(hold_samples: u32) -> (QualityMonitor, Instant) {
+        let now = Instant::now();
+        let policy = QualityPolicy::new().hold_samples(hold_samples);
+        (QualityMonitor::new(policy, now), now)
+    }
+
+    fn step(now: &mut Instant) -> Instant {
+        *now += Duration::from_secs(1);
+        *now
+    }
+
+    #[test]
+    fn tier_changes_only_after_holding() {
+        let (mut monitor, mut now) = monitor(3);
+
+        monitor.record_rtt(Duration::from_millis(800));
+        assert_eq!(monitor.sample(0, step(&mut now)), None);
+        assert_eq!(monitor.sample(0, step(&mut now)), None);
+        let change = monitor.sample(0, step(&mut now)).unwrap();
+        assert_eq!(
+            (change.from, change.to),
+            (QualityTier::Good, QualityTier::Degraded)
+        );
+
+        // Staying degraded reports nothing more
+        assert_eq!(monitor.sample(0, step(&mut now)), None);
+        assert_eq!(monitor.tier(), QualityTier::Degraded);
+    }
+
+    #[test]
+    fn flapping_signal_does_not_change_tier() {
+        let (mut monitor, mut now) = monitor(2);
+
+        for _ in 0..5 {
+            monitor.record_rtt(Duration::from_millis(3000));
+            assert_eq!(monitor.sample(0, step(&mut now)), None);
+            monitor.record_rtt(Duration::from_millis(20));
+            assert_eq!(monitor.sample(0, step(&mut now)), None);
+        }
+        assert_eq!(monitor.tier(), QualityTier::Good);
+    }
+
+    #[test]
+    fn gaps_count_per_sample() {
+        let (mut monitor, mut now) = monitor(1);
+
+        let change = monitor.sample(4, step(&mut now)).unwrap();
+        assert_eq!(change.to, QualityTier::Poor);
+        assert_eq!(change.quality.recent_gaps, 4);
+
+        // No new gaps since the last sample
+        let change = monitor.sample(4, step(&mut now)).unwrap();
+        assert_eq!(change.to, QualityTier::Good);
+    }
+
+    #[test]
+    fn unanswered_heartbeat_counts_as_round_trip() {
+        let (mut monitor, mut now) = monitor(1);
+
+        monitor.record_rtt(Duration::from_millis(10));
+        assert!(monitor.ping_sent(now));
+        assert!(!monitor.ping_sent(now), "one heartbeat in flight at a time");
+
+        now += Duration::from_millis(2500);
+        let change = monitor.sample(0, now).unwrap();
+        assert_eq!(change.to, QualityTier::Poor);
+
+        monitor.pong_received(now + Duration::from_millis(1));
+        assert_eq!(monitor.quality().rtt, Some(Duration::from_millis(2501)));
+        assert!(monitor.ping_sent(now));
+    }
+
+    #[test]
+    fn bytes_per_second_covers_the_sample() {
+        let (mut monitor, now) = monitor(1);
+
+        monitor.record_bytes(3000);
+        monitor.sample(0, now + Duration::from_secs(2));
+        assert_eq!(monitor.quality().bytes_per_sec, 1500.0);
+    }
+
+    #[test]
+    fn overrides_apply_to_list_subscriptions_only() {
+        let policy = QualityPolicy::new().on(
+            QualityTier::Poor,
+            SubscriptionOverrides::new().take(10).with_snapshot(false),
+        );
+
+        let list = Subscription::new("Token/list").with_take(50);
+        let downgraded = policy.negotiate(QualityTier::Poor, &list);
+        assert_eq!(downgraded.take, Some(10));
+        assert_eq!(downgraded.with_snapshot, Some(false));
+        assert_eq!(policy.negotiate(QualityTier::Good, &list), list);
+
+        let narrow = Subscription::new("Token/list").with_take(5);
+        assert_eq!(policy.negotiate(QualityTier::Poor, &narrow).take, Some(5));
+
+        let state = Subscription::new("Token/state").with_key("mint");
+        assert_eq!(policy.negotiate(QualityTier::Poor, &state), state);
+
+        assert!(policy.renegotiates(QualityTier::Good, QualityTier::Poor));
+        assert!(!policy.renegotiates(QualityTier::Good, QualityTier::Degraded));
+    }
+}
@@ -0,0 +1,545 @@
+//! Low-level frame client for integrations that bring their own store.
+//!
+//! [`RawClient`] speaks the HyperStack WebSocket protocol and nothing else:
+//! it sends subscribe, unsubscribe and resync messages for explicit
+//! [`SubscriptionId`]s and yields every decoded [`Frame`] as a [`RawEvent`].
+//! There is no store, no typed entities and no automatic reconnect. The typed
+//! [`HyperStack`](crate::HyperStack) client runs on the same implementation.
+//!
+//! ```rust,ignore
+//! use futures_util::StreamExt;
+//! use hyperstack_sdk::raw::{RawClient, RawEvent};
+//! use hyperstack_sdk::Subscription;
+//!
+//! let mut client = RawClient::connect("wss://example.stack.usehyperstack.com").await?;
+//! client.send_subscribe(&Subscription::new("Token/list")).await?;
+//!
+//! while let Some(event) = client.next().await {
+//!     match event {
+//!         RawEvent::Frame { frame, .. } => publish(frame).await?,
+//!         RawEvent::Gap { sub, .. } => client.send_resync(&sub).await?,
+//!         RawEvent::Closed(closed) => {
+//!             tokio::time::sleep(closed.retry_after.unwrap_or(Duration::from_secs(1))).await;
+//!             client.reconnect().await?;
+//!             client.send_subscribe(&Subscription::new("Token/list")).await?;
+//!         }
+//!         _ => {}
+//!     }
+//! }
+//! ```
+//!
+//! # Protocol invariants
+//!
+//! - **Ordering.** Events are yielded in the order the server sent them.
+//!   Frames of one subscription are in order; frames of different
+//!   subscriptions may interleave.
+//! - **Acks.** The server answers every subscribe with a frame whose
+//!   operation is [`Subscribed`](crate::Operation::Subscribed) before any
+//!   snapshot or live frame of that subscription.
+//! - **Sequences.** Frames carry a [`FrameSequence`] numbering them per
+//!   subscription from 1, the ack included. When a number is missing the
+//!   client yields one [`RawEvent::Gap`] before the frame that revealed it,
+//!   and reports no further gaps for the subscription until its sequence
+//!   starts over. Whether to resync is up to the caller.
+//! - **Resync markers.** After [`send_resync`](RawClient::send_resync) the
+//!   server resends the ack and snapshot with `resync` set on the first
+//!   frame, which starts the sequence over at 1. State built from earlier
+//!   frames of the subscription should be discarded. The answer to
+//!   [`send_refresh`](RawClient::send_refresh) also sets `refresh`.
+//! - **Connections.** Subscriptions and sequences belong to one connection.
+//!   After [`RawEvent::Closed`] the stream ends; call
+//!   [`reconnect`](RawClient::reconnect) and subscribe again.
+//! - **Compression.** Frames arrive raw, gzipped, or as zstd once
+//!   [`accept_compression`](RawClient::accept_compression) answers a
+//!   [`RawEvent::CompressionOffered`]; they are decoded either way.
+
+use crate::error::{AuthErrorCode, HyperStackError, SocketIssue, SocketIssuePayload};
+use crate::frame::{
+    is_zstd, parse_message, Frame, FrameSequence, SequenceTracker, ZstdDictionaries,
+};
+use crate::subscription::{ClientMessage, Subscription, Unsubscription};
+use futures_util::{SinkExt, Stream, StreamExt};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::{generate_key, Request};
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+/// How long to wait for a compression dictionary before going without
+const DICTIONARY_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A subscription as the server identifies it, `view:key` or `view:*`,
+/// followed by `#id` for a subscription made with an id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(String);
+
+impl SubscriptionId {
+    pub fn new(view: &str, key: Option<&str>) -> Self {
+        Self(format!("{}:{}", view, key.unwrap_or("*")))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn unsubscription(&self) -> Option<Unsubscription> {
+        Unsubscription::from_sub_key(&self.0)
+    }
+
+    fn malformed(&self) -> HyperStackError {
+        HyperStackError::SubscriptionFailed(format!("Malformed subscription id '{}'", self.0))
+    }
+}
+
+impl From<&Subscription> for SubscriptionId {
+    fn from(sub: &Subscription) -> Self {
+        Self(Unsubscription::from(sub).sub_key())
+    }
+}
+
+impl From<&Unsubscription> for SubscriptionId {
+    fn from(unsub: &Unsubscription) -> Self {
+        Self(unsub.sub_key())
+    }
+}
+
+impl fmt::Display for SubscriptionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Something the server sent, decoded
+#[derive(Debug)]
+pub enum RawEvent {
+    /// A data frame or subscription ack, with its place in the
+    /// subscription's sequence when the server stamped one
+    Frame {
+        frame: Frame,
+        sequence: Option<FrameSequence>,
+    },
+    /// Frames of `sub` went missing: `received` arrived where `expected`
+    /// should have
+    Gap {
+        sub: SubscriptionId,
+        expected: u64,
+        received: u64,
+    },
+    /// The server reported a problem with the socket. Fatal issues are
+    /// followed by the server closing it
+    SocketIssue(SocketIssue),
+    /// The server accepted a token sent with
+    /// [`send_refresh_auth`](RawClient::send_refresh_auth)
+    AuthRefreshed { expires_at: Option<u64> },
+    /// The server rejected a refreshed token
+    AuthRefreshFailed(HyperStackError),
+    /// Reply to [`send_heartbeat`](RawClient::send_heartbeat)
+    Pong,
+    /// The server offers zstd frames, compressed with the dictionary
+    /// `dictionary` when it has one. Frames stay gzip unless the offer is
+    /// taken with [`accept_compression`](RawClient::accept_compression)
+    CompressionOffered { dictionary: Option<String> },
+    /// The connection ended. No events follow until a reconnect
+    Closed(Closed),
+}
+
+/// How a connection ended
+#[derive(Debug, Default)]
+pub struct Closed {
+    /// Why, when the server said or the socket failed; `None` for a clean close
+    pub error: Option<HyperStackError>,
+    /// Delay the server asked for before reconnecting
+    pub retry_after: Option<Duration>,
+}
+
+/// A handshake that failed
+#[derive(Debug, thiserror::Error)]
+#[error("{error}")]
+pub struct ConnectError {
+    pub error: HyperStackError,
+    /// Delay the server asked for before trying again, when it refused the
+    /// handshake to shed load
+    pub retry_after: Option<Duration>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct HelloMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    compression: HelloCompression,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct HelloCompression {
+    zstd: bool,
+    dictionary: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct RefreshAuthResponseMessage {
+    success: bool,
+    error: Option<String>,
+    expires_at: Option<u64>,
+}
+
+/// One WebSocket connection to a HyperStack server, yielding decoded frames.
+///
+/// Events are read through its [`Stream`] implementation, which is cancel
+/// safe: dropping a pending `next()` loses nothing. Pings from the server are
+/// answered by the socket while it is being read.
+pub struct RawClient {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    request: Request,
+    sequences: SequenceTracker,
+    pending: VecDeque<RawEvent>,
+    bytes_received: u64,
+    closed: bool,
+    /// Kept across reconnects, so each dictionary is downloaded once
+    dictionaries: ZstdDictionaries,
+}
+
+impl RawClient {
+    /// Open a connection. Authentication goes in the request, as a query
+    /// parameter or `Authorization` header. There is no timeout; wrap the
+    /// call in one if needed.
+    pub async fn connect(request: impl IntoClientRequest) -> Result<Self, ConnectError> {
+        let request = request
+            .into_client_request()
+            .map_err(|error| ConnectError {
+                error: HyperStackError::ConnectionFailed(error.to_string()),
+                retry_after: None,
+            })?;
+        let ws = open(copy_request(&request)).await?;
+
+        Ok(Self {
+            ws,
+            request,
+            sequences: SequenceTracker::default(),
+            pending: VecDeque::new(),
+            bytes_received: 0,
+            closed: false,
+            dictionaries: ZstdDictionaries::default(),
+        })
+    }
+
+    /// Replace the connection with a new one to the same server, closing the
+    /// old one if it is still open. Nothing is resubscribed.
+    pub async fn reconnect(&mut self) -> Result<(), ConnectError> {
+        if !self.closed {
+            let _ = self.ws.close(None).await;
+        }
+        self.ws = open(copy_request(&self.request)).await?;
+        self.sequences = SequenceTracker::default();
+        self.pending.clear();
+        self.bytes_received = 0;
+        self.closed = false;
+        Ok(())
+    }
+
+    /// Subscribe, returning the id the server will stamp on its frames
+    pub async fn send_subscribe(
+        &mut self,
+        sub: &Subscription,
+    ) -> Result<SubscriptionId, HyperStackError> {
+        self.send(&ClientMessage::Subscribe(sub.clone())).await?;
+        Ok(SubscriptionId::from(sub))
+    }
+
+    pub async fn send_unsubscribe(&mut self, id: &SubscriptionId) -> Result<(), HyperStackError> {
+        let unsub = id.unsubscription().ok_or_else(|| id.malformed())?;
+        self.sequences.forget(id.as_str());
+        self.send(&ClientMessage::Unsubscribe(unsub)).await
+    }
+
+    /// Ask the server to restart a subscription from a fresh snapshot
+    pub async fn send_resync(&mut self, id: &SubscriptionId) -> Result<(), HyperStackError> {
+        let resync = id.unsubscription().ok_or_else(|| id.malformed())?;
+        self.send(&ClientMessage::Resync(resync)).await
+    }
+
+    /// Ask the server for a fresh snapshot of a subscription. It answers as
+    /// for a resync, with `refresh` also set on the first frame. Refreshes
+    /// of one subscription closer together than the server allows are
+    /// refused with a [`RawEvent::SocketIssue`].
+    pub async fn send_refresh(&mut self, id: &SubscriptionId) -> Result<(), HyperStackError> {
+        let refresh = id.unsubscription().ok_or_else(|| id.malformed())?;
+        self.send(&ClientMessage::Refresh(refresh)).await
+    }
+
+    /// Hand the server a new token before the current one expires. The
+    /// answer arrives as [`RawEvent::AuthRefreshed`] or
+    /// [`RawEvent::AuthRefreshFailed`]
+    pub async fn send_refresh_auth(&mut self, token: String) -> Result<(), HyperStackError> {
+        self.send(&ClientMessage::RefreshAuth { token }).await
+    }
+
+    /// Ask for zstd frames, compressed with the `dictionary` of a
+    /// [`RawEvent::CompressionOffered`].
+    ///
+    /// The dictionary is downloaded from the server the first time. When
+    /// that fails the server is asked for zstd without a dictionary.
+    pub async fn accept_compression(
+        &mut self,
+        dictionary: Option<&str>,
+    ) -> Result<(), HyperStackError> {
+        let dictionary = match dictionary {
+            Some(id) if self.dictionaries.contains(id) => Some(id),
+            Some(id) => match fetch_dictionary(&self.request, id).await {
+                Ok(bytes) => {
+                    self.dictionaries.insert(id, &bytes);
+                    Some(id)
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        "Failed to download compression dictionary {}, using zstd without it: {}",
+                        id,
+                        error
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+        self.send(&ClientMessage::Compression {
+            codec: "zstd".to_string(),
+            dictionary: dictionary.map(str::to_string),
+        })
+        .await
+    }
+
+    /// Protocol keepalive, which tells the server the client is still there
+    pub async fn send_keepalive(&mut self) -> Result<(), HyperStackError> {
+        self.send(&ClientMessage::Ping).await
+    }
+
+    /// WebSocket ping, answered by the server's socket with a
+    /// [`RawEvent::Pong`] queued behind any frames already sent
+    pub async fn send_heartbeat(&mut self) -> Result<(), HyperStackError> {
+        self.ws
+            .send(Message::Ping(Vec::new()))
+            .await
+            .map_err(HyperStackError::from_tungstenite)
+    }
+
+    pub async fn close(&mut self) -> Result<(), HyperStackError> {
+        self.ws
+            .close(None)
+            .await
+            .map_err(HyperStackError::from_tungstenite)
+    }
+
+    /// Bytes of messages received on this connection
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    async fn send(&mut self, message: &ClientMessage) -> Result<(), HyperStackError> {
+        let text = serde_json::to_string(message)?;
+        self.ws
+            .send(Message::Text(text))
+            .await
+            .map_err(HyperStackError::from_tungstenite)
+    }
+
+    fn receive(&mut self, message: Option<Result<Message, tungstenite::Error>>) {
+        match message {
+            Some(Ok(Message::Binary(bytes))) => {
+                self.bytes_received += bytes.len() as u64;
+                self.decode(&bytes);
+            }
+            Some(Ok(Message::Text(text))) => {
+                self.bytes_received += text.len() as u64;
+                if let Some(issue) = parse_socket_issue_message(&text) {
+                    self.pending.push_back(RawEvent::SocketIssue(issue));
+                } else if let Some(hello) = parse_hello(&text) {
+                    if hello.compression.zstd {
+                        self.pending.push_back(RawEvent::CompressionOffered {
+                            dictionary: hello.compression.dictionary,
+                        });
+                    }
+                } else if let Some(response) = parse_refresh_auth_response(&text) {
+                    self.pending.push_back(if response.success {
+                        RawEvent::AuthRefreshed {
+                            expires_at: response.expires_at,
+                        }
+                    } else {
+                        RawEvent::AuthRefreshFailed(refresh_response_error(response))
+                    });
+                } else {
+                    self.decode(text.as_bytes());
+                }
+            }
+            Some(Ok(Message::Pong(_))) => self.pending.push_back(RawEvent::Pong),
+            Some(Ok(Message::Close(frame))) => {
+                let closed = match frame {
+                    Some(frame) => Closed {
+                        error: HyperStackError::from_close_reason(&frame.reason),
+                        retry_after: HyperStackError::close_reason_retry_hint(&frame.reason),
+                    },
+                    None => Closed::default(),
+                };
+                self.finish(closed);
+            }
+            Some(Ok(_)) => {}
+            Some(Err(error)) => self.finish(Closed {
+                error: Some(HyperStackError::from_tungstenite(error)),
+                retry_after: None,
+            }),
+            None => self.finish(Closed::default()),
+        }
+    }
+
+    fn decode(&mut self, bytes: &[u8]) {
+        let bytes = if is_zstd(bytes) {
+            match self.dictionaries.decompress(bytes) {
+                Ok(decompressed) => Cow::Owned(decompressed),
+                Err(error) => {
+                    tracing::warn!("Dropping zstd frame that failed to decompress: {}", error);
+                    return;
+                }
+            }
+        } else {
+            Cow::Borrowed(bytes)
+        };
+        let (sequence, frame) = parse_message(&bytes);
+        if let Some(sequence) = &sequence {
+            if let Some(expected) = self.sequences.observe(sequence) {
+                self.pending.push_back(RawEvent::Gap {
+                    sub: SubscriptionId(sequence.sub.clone()),
+                    expected,
+                    received: sequence.frame_seq,
+                });
+            }
+        }
+        if let Some(frame) = frame {
+            self.pending.push_back(RawEvent::Frame { frame, sequence });
+        }
+    }
+
+    fn finish(&mut self, closed: Closed) {
+        self.closed = true;
+        self.pending.push_back(RawEvent::Closed(closed));
+    }
+}
+
+impl Stream for RawClient {
+    type Item = RawEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<RawEvent>> {
+        let this = &mut *self;
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+            if this.closed {
+                return Poll::Ready(None);
+            }
+            let message = std::task::ready!(this.ws.poll_next_unpin(cx));
+            this.receive(message);
+        }
+    }
+}
+
+async fn open(
+    request: Request,
+) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, ConnectError> {
+    match connect_async(request).await {
+        Ok((ws, _)) => Ok(ws),
+        Err(error) => {
+            let retry_after = match &error {
+                tungstenite::Error::Http(response) => HyperStackError::http_retry_hint(response),
+                _ => None,
+            };
+            Err(ConnectError {
+                error: HyperStackError::from_tungstenite(error),
+                retry_after,
+            })
+        }
+    }
+}
+
+/// A copy of `request` for a new handshake, with a fresh key
+fn copy_request(request: &Request) -> Request {
+    let mut copy = Request::new(());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+    if let Ok(key) = HeaderValue::from_str(&generate_key()) {
+        copy.headers_mut().insert("Sec-WebSocket-Key", key);
+    }
+    copy
+}
+
+fn parse_socket_issue_message(text: &str) -> Option<SocketIssue> {
+    let payload = serde_json::from_str::<SocketIssuePayload>(text).ok()?;
+    if payload.is_socket_issue() {
+        Some(payload.into_socket_issue())
+    } else {
+        None
+    }
+}
+
+fn parse_hello(text: &str) -> Option<HelloMessage> {
+    serde_json::from_str::<HelloMessage>(text)
+        .ok()
+        .filter(|hello| hello.kind == "hello")
+}
+
+/// Download the dictionary `id` from `/dictionaries/<id>` next to the
+/// WebSocket route, with the connection's credentials
+async fn fetch_dictionary(request: &Request, id: &str) -> Result<Vec<u8>, HyperStackError> {
+    let mut url = url::Url::parse(&request.uri().to_string())
+        .map_err(|error| HyperStackError::ConnectionFailed(error.to_string()))?;
+    let scheme = if url.scheme() == "wss" {
+        "https"
+    } else {
+        "http"
+    };
+    let _ = url.set_scheme(scheme);
+    let path = format!("{}/dictionaries/{}", url.path().trim_end_matches('/'), id);
+    url.set_path(&path);
+
+    let mut fetch = reqwest::Client::builder()
+        .timeout(DICTIONARY_FETCH_TIMEOUT)
+        .build()
+        .map_err(|error| HyperStackError::ConnectionFailed(error.to_string()))?
+        .get(url);
+    if let Some(authorization) = request
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+    {
+        fetch = fetch.header("Authorization", authorization);
+    }
+    let response = fetch
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|error| HyperStackError::ConnectionFailed(error.to_string()))?;
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|error| HyperStackError::ConnectionFailed(error.to_string()))?;
+    Ok(bytes.to_vec())
+}
+
+fn parse_refresh_auth_response(text: &str) -> Option<RefreshAuthResponseMessage> {
+    serde_json::from_str::<RefreshAuthResponseMessage>(text).ok()
+}
+
+fn refresh_response_error(response: RefreshAuthResponseMessage) -> HyperStackError {
+    let code = response.error.as_deref().and_then(AuthErrorCode::from_wire);
+    let message = response
+        .error
+        .unwrap_or_else(|| "Authentication refresh failed".to_string());
+
+    HyperStackError::WebSocket { message, code }
+}
@@ -0,0 +1,80 @@
+//! Resolver-backed fields that can be pending.
+//!
+//! A `#[resolve(..., placeholder = "pending")]` field holds the marker
+//! `{"status": "pending"}` until its resolver returns, then the resolved
+//! value. Generated SDK types wrap such fields in [`Resolvable`].
+//!
+//! ```ignore
+//! match &token.metadata {
+//!     Some(Resolvable::Resolved(metadata)) => println!("{}", metadata.name),
+//!     Some(Resolvable::Pending(_)) => println!("resolving..."),
+//!     None => {}
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// The placeholder a field carries while its resolver is in flight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PendingMarker {
+    pub status: String,
+}
+
+/// A resolver target: either its placeholder or the resolved value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Resolvable<T> {
+    Pending(PendingMarker),
+    Resolved(T),
+}
+
+impl<T> Resolvable<T> {
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Resolvable::Pending(_))
+    }
+
+    pub fn resolved(&self) -> Option<&T> {
+        match self {
+            Resolvable::Resolved(value) => Some(value),
+            Resolvable::Pending(_) => None,
+        }
+    }
+
+    pub fn into_resolved(self) -> Option<T> {
+        match self {
+            Resolvable::Resolved(value) => Some(value),
+            Resolvable::Pending(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Metadata {
+        name: String,
+        status: Option<String>,
+    }
+
+    #[test]
+    fn pending_marker_then_resolved_value() {
+        let pending: Resolvable<Metadata> =
+            serde_json::from_value(json!({ "status": "pending" })).unwrap();
+        assert!(pending.is_pending());
+        assert_eq!(pending.resolved(), None);
+
+        let resolved: Resolvable<Metadata> =
+            serde_json::from_value(json!({ "name": "Ore", "status": "live" })).unwrap();
+        assert_eq!(
+            resolved.into_resolved(),
+            Some(Metadata {
+                name: "Ore".to_string(),
+                status: Some("live".to_string()),
+            })
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Structured concurrency for stream consumers.
+//!
+//! A [`StreamScope`] owns a set of watch tasks and tears all of them down
+//! together, so one failing consumer cannot leave the others running.
+//!
+//! ```ignore
+//! let mut scope = hs.scope();
+//!
+//! scope.spawn_watch(&hs.views.ore_round.latest(), |ctx| async move {
+//!     if let Some(round) = ctx.entity {
+//!         println!("round {:?}", round.id.round_id);
+//!     }
+//!     Ok::<_, anyhow::Error>(())
+//! });
+//!
+//! scope.join().await?;
+//! ```
+
+use crate::connection::{ConnectionManager, ConnectionState};
+use crate::error::HyperStackError;
+use crate::stream::Update;
+use crate::subscription::Unsubscription;
+use crate::view::ViewHandle;
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_util::sync::CancellationToken;
+
+const CONNECTION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The kind of change that produced a [`WatchContext`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateKind {
+    Upsert,
+    Patch,
+    Delete,
+}
+
+/// Everything a scoped handler receives for a single update.
+#[derive(Debug, Clone)]
+pub struct WatchContext<T> {
+    pub key: String,
+    /// Full entity state after the update; `None` for deletes.
+    pub entity: Option<T>,
+    pub kind: UpdateKind,
+    /// Cancelled when the scope shuts down. Long-running handlers should
+    /// select on it.
+    pub cancellation: CancellationToken,
+}
+
+impl<T> WatchContext<T> {
+    fn new(update: Update<T>, cancellation: CancellationToken) -> Self {
+        let (key, entity, kind) = match update {
+            Update::Upsert { key, data } => (key, Some(data), UpdateKind::Upsert),
+            Update::Patch { key, data } => (key, Some(data), UpdateKind::Patch),
+            Update::Delete { key } => (key, None, UpdateKind::Delete),
+        };
+        Self {
+            key,
+            entity,
+            kind,
+            cancellation,
+        }
+    }
+}
+
+/// A group of stream consumers that share one lifetime.
+///
+/// All registered streams are cancelled, and their view subscriptions
+/// dropped, when the scope is dropped, when a handler fails (with
+/// [`fail_fast`](Self::fail_fast), the default) or when the connection
+/// closes permanently. Views subscribed through a scope are unsubscribed
+/// even if other code still watches them.
+pub struct StreamScope {
+    connection: ConnectionManager,
+    token: CancellationToken,
+    tasks: JoinSet<()>,
+    first_error: Arc<Mutex<Option<HyperStackError>>>,
+    fail_fast: bool,
+    views: Vec<String>,
+    connection_watch: Option<JoinHandle<()>>,
+}
+
+impl StreamScope {
+    pub(crate) fn new(connection: ConnectionManager) -> Self {
+        Self {
+            connection,
+            token: CancellationToken::new(),
+            tasks: JoinSet::new(),
+            first_error: Arc::new(Mutex::new(None)),
+            fail_fast: true,
+            views: Vec::new(),
+            connection_watch: None,
+        }
+    }
+
+    /// Cancel every stream as soon as one handler fails (default: true).
+    ///
+    /// When disabled, the remaining handlers keep running and
+    /// [`join`](Self::join) still reports the first error.
+    pub fn fail_fast(mut self, enabled: bool) -> Self {
+        self.fail_fast = enabled;
+        self
+    }
+
+    /// Token cancelled when the scope shuts down.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Cancel all streams in the scope.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Watch `view` and call `handler` for each update until the scope is
+    /// cancelled or the stream ends.
+    pub fn spawn_watch<T, F, Fut, E>(&mut self, view: &ViewHandle<T>, mut handler: F)
+    where
+        T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+        F: FnMut(WatchContext<T>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display + Send + 'static,
+    {
+        self.ensure_connection_watch();
+
+        let view_path = view.view_path().to_string();
+        if !self.views.contains(&view_path) {
+            self.views.push(view_path);
+        }
+
+        let mut stream = view.watch();
+        let token = self.token.clone();
+        let first_error = self.first_error.clone();
+        let fail_fast = self.fail_fast;
+
+        self.tasks.spawn(async move {
+            loop {
+                let update = tokio::select! {
+                    biased;
+                    _ = token.cancelled() => return,
+                    update = stream.next() => update,
+                };
+                let Some(update) = update else {
+                    return;
+                };
+
+                if let Err(error) = handler(WatchContext::new(update, token.clone())).await {
+                    record_error(
+                        &first_error,
+                        HyperStackError::HandlerFailed(error.to_string()),
+                    );
+                    if fail_fast {
+                        token.cancel();
+                    }
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Wait for every handler to finish and return the first error.
+    ///
+    /// Subscriptions opened by the scope are dropped before returning.
+    pub async fn join(mut self) -> Result<(), HyperStackError> {
+        while let Some(result) = self.tasks.join_next().await {
+            if let Err(join_error) = result {
+                if join_error.is_panic() {
+                    record_error(
+                        &self.first_error,
+                        HyperStackError::HandlerFailed("stream handler panicked".to_string()),
+                    );
+                    if self.fail_fast {
+                        self.token.cancel();
+                    }
+                }
+            }
+        }
+
+        self.shutdown();
+
+        match self.first_error.lock() {
+            Ok(mut slot) => slot.take().map_or(Ok(()), Err),
+            Err(poisoned) => poisoned.into_inner().take().map_or(Ok(()), Err),
+        }
+    }
+
+    fn ensure_connection_watch(&mut self) {
+        if self.connection_watch.is_some() {
+            return;
+        }
+
+        let connection = self.connection.clone();
+        let token = self.token.clone();
+        let first_error = self.first_error.clone();
+
+        self.connection_watch = Some(tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => return,
+                    _ = tokio::time::sleep(CONNECTION_POLL_INTERVAL) => {}
+                }
+
+                if matches!(
+                    connection.state().await,
+                    ConnectionState::Error | ConnectionState::Disconnected
+                ) {
+                    let error = connection
+                        .last_error()
+                        .await
+                        .map(|error| (*error).clone())
+                        .unwrap_or(HyperStackError::ConnectionClosed);
+                    record_error(&first_error, error);
+                    token.cancel();
+                    return;
+                }
+            }
+        }));
+    }
+
+    fn shutdown(&mut self) {
+        self.token.cancel();
+        self.tasks.abort_all();
+        if let Some(watch) = self.connection_watch.take() {
+            watch.abort();
+        }
+
+        if self.views.is_empty() {
+            return;
+        }
+        let views = std::mem::take(&mut self.views);
+        let connection = self.connection.clone();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                for view in views {
+                    connection.unsubscribe(Unsubscription::new(view)).await;
+                }
+            });
+        }
+    }
+}
+
+impl Drop for StreamScope {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn record_error(slot: &Mutex<Option<HyperStackError>>, error: HyperStackError) {
+    let mut slot = match slot.lock() {
+        Ok(slot) => slot,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if slot.is_none() {
+        *slot = Some(error);
+    }
+}
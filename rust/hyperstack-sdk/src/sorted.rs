@@ -0,0 +1,745 @@
+//! Client-side sorting and windowing over a list view.
+//!
+//! A [`SortedWindowStream`] keeps the entities of one view ordered by a
+//! [`SortField`] and emits [`ListChange`]s for the slice of that order you
+//! asked for, as entities are created, updated and removed.
+//!
+//! ```ignore
+//! let mut top = hs.views.ore_miner.list()
+//!     .sorted_by(OreMiner::fields().rewards_sol(), SortOrder::Desc)
+//!     .window(0..50)
+//!     .listen();
+//!
+//! let mut miners = Vec::new();
+//! while let Some(changes) = top.next().await {
+//!     for change in changes {
+//!         change.apply(&mut miners);
+//!     }
+//! }
+//! ```
+
+use crate::connection::ConnectionManager;
+use crate::frame::{Operation, SortOrder};
+use crate::store::{SharedStore, StoreUpdate};
+use futures_util::Stream;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio_stream::wrappers::BroadcastStream;
+
+/// How values of a field compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// Integers and floats, including integers sent as strings
+    Numeric,
+    /// Lexicographic string comparison
+    Text,
+    /// `false` before `true`
+    Bool,
+}
+
+/// A field to sort by, as produced by the generated `Entity::fields()`
+/// accessors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortField {
+    path: Vec<String>,
+    kind: FieldKind,
+}
+
+impl SortField {
+    pub fn new(path: &[&str], kind: FieldKind) -> Self {
+        Self {
+            path: path.iter().map(|s| s.to_string()).collect(),
+            kind,
+        }
+    }
+
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    pub fn kind(&self) -> FieldKind {
+        self.kind
+    }
+
+    fn extract(&self, entity: &Value) -> Option<SortValue> {
+        let mut current = entity;
+        for segment in &self.path {
+            current = current.get(segment)?;
+        }
+
+        match (self.kind, current) {
+            (FieldKind::Numeric, Value::Number(n)) => match n.as_i64() {
+                Some(i) => Some(SortValue::Int(i as i128)),
+                None => match n.as_u64() {
+                    Some(u) => Some(SortValue::Int(u as i128)),
+                    None => n.as_f64().map(SortValue::Float),
+                },
+            },
+            (FieldKind::Numeric, Value::String(s)) => s
+                .parse::<i128>()
+                .map(SortValue::Int)
+                .or_else(|_| s.parse::<f64>().map(SortValue::Float))
+                .ok(),
+            (FieldKind::Text, Value::String(s)) => Some(SortValue::Text(s.clone())),
+            (FieldKind::Text, Value::Number(n)) => Some(SortValue::Text(n.to_string())),
+            (FieldKind::Bool, Value::Bool(b)) => Some(SortValue::Bool(*b)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SortValue {
+    Int(i128),
+    Float(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl Eq for SortValue {}
+
+impl Ord for SortValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortValue::Int(a), SortValue::Int(b)) => a.cmp(b),
+            (SortValue::Int(a), SortValue::Float(b)) => (*a as f64).total_cmp(b),
+            (SortValue::Float(a), SortValue::Int(b)) => a.total_cmp(&(*b as f64)),
+            (SortValue::Float(a), SortValue::Float(b)) => a.total_cmp(b),
+            (SortValue::Text(a), SortValue::Text(b)) => a.cmp(b),
+            (SortValue::Bool(a), SortValue::Bool(b)) => a.cmp(b),
+            // A field has a single kind, so mixed variants only differ by rank
+            _ => self.variant_rank().cmp(&other.variant_rank()),
+        }
+    }
+}
+
+impl PartialOrd for SortValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl SortValue {
+    fn variant_rank(&self) -> u8 {
+        match self {
+            SortValue::Int(_) | SortValue::Float(_) => 0,
+            SortValue::Text(_) => 1,
+            SortValue::Bool(_) => 2,
+        }
+    }
+}
+
+/// Position of an entity in the sorted order. Variants are declared so
+/// nulls sort last in either direction, and entities with equal values
+/// keep a stable order by key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum RankValue {
+    Asc(SortValue),
+    Desc(Reverse<SortValue>),
+    Null,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Rank {
+    value: RankValue,
+    key: String,
+}
+
+/// One step in turning the previous window into the current one. Apply the
+/// changes of an update in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListChange<T> {
+    /// An entity entered the window at `index`
+    Insert { index: usize, key: String, data: T },
+    /// The entity at `index` changed but kept its position
+    Update { index: usize, key: String, data: T },
+    /// An entity moved from `from` to `to`, with its current data
+    Move {
+        from: usize,
+        to: usize,
+        key: String,
+        data: T,
+    },
+    /// The entity at `index` left the window
+    Remove { index: usize, key: String },
+}
+
+impl<T> ListChange<T> {
+    pub fn key(&self) -> &str {
+        match self {
+            ListChange::Insert { key, .. }
+            | ListChange::Update { key, .. }
+            | ListChange::Move { key, .. }
+            | ListChange::Remove { key, .. } => key,
+        }
+    }
+
+    /// Apply this change to a local copy of the window.
+    pub fn apply(self, items: &mut Vec<T>) {
+        match self {
+            ListChange::Insert { index, data, .. } => items.insert(index, data),
+            ListChange::Update { index, data, .. } => items[index] = data,
+            ListChange::Move { from, to, data, .. } => {
+                items.remove(from);
+                items.insert(to, data);
+            }
+            ListChange::Remove { index, .. } => {
+                items.remove(index);
+            }
+        }
+    }
+}
+
+struct Entry<T> {
+    raw: Value,
+    data: T,
+    rank: Rank,
+}
+
+/// Sorted index over one view's entities plus the window currently shown.
+pub(crate) struct SortedWindow<T> {
+    field: SortField,
+    order: SortOrder,
+    range: Range<usize>,
+    entries: HashMap<String, Entry<T>>,
+    ranks: BTreeSet<Rank>,
+    window: Vec<String>,
+}
+
+impl<T: DeserializeOwned + Clone> SortedWindow<T> {
+    pub(crate) fn new(field: SortField, order: SortOrder, range: Range<usize>) -> Self {
+        Self {
+            field,
+            order,
+            range,
+            entries: HashMap::new(),
+            ranks: BTreeSet::new(),
+            window: Vec::new(),
+        }
+    }
+
+    fn rank(&self, key: &str, entity: &Value) -> Rank {
+        let value = match (self.field.extract(entity), self.order) {
+            (None, _) => RankValue::Null,
+            (Some(value), SortOrder::Asc) => RankValue::Asc(value),
+            (Some(value), SortOrder::Desc) => RankValue::Desc(Reverse(value)),
+        };
+        Rank {
+            value,
+            key: key.to_string(),
+        }
+    }
+
+    /// Record the current state of an entity. Returns `true` if it changed.
+    fn set(&mut self, key: &str, raw: Value) -> bool {
+        if self.entries.get(key).is_some_and(|entry| entry.raw == raw) {
+            return false;
+        }
+
+        let data = match serde_json::from_value::<T>(raw.clone()) {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::warn!(
+                    key = %key,
+                    error = %e,
+                    "SortedWindowStream: failed to deserialize entity, skipping"
+                );
+                return self.unset(key);
+            }
+        };
+
+        let rank = self.rank(key, &raw);
+        if let Some(previous) = self.entries.get(key) {
+            self.ranks.remove(&previous.rank);
+        }
+        self.ranks.insert(rank.clone());
+        self.entries
+            .insert(key.to_string(), Entry { raw, data, rank });
+        true
+    }
+
+    fn unset(&mut self, key: &str) -> bool {
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.ranks.remove(&entry.rank);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn upsert(&mut self, key: &str, raw: Value) -> Vec<ListChange<T>> {
+        if !self.set(key, raw) {
+            return Vec::new();
+        }
+        self.refresh(&HashSet::from([key.to_string()]))
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) -> Vec<ListChange<T>> {
+        if !self.unset(key) {
+            return Vec::new();
+        }
+        self.refresh(&HashSet::new())
+    }
+
+    /// Replace every entity, e.g. after a snapshot or a lagged broadcast.
+    pub(crate) fn reset(&mut self, entities: HashMap<String, Value>) -> Vec<ListChange<T>> {
+        let stale: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|key| !entities.contains_key(*key))
+            .cloned()
+            .collect();
+        for key in &stale {
+            self.unset(key);
+        }
+
+        let mut changed = HashSet::new();
+        for (key, raw) in entities {
+            if self.set(&key, raw) {
+                changed.insert(key);
+            }
+        }
+        self.refresh(&changed)
+    }
+
+    /// Entities in the window, in order.
+    #[cfg(test)]
+    pub(crate) fn items(&self) -> Vec<T> {
+        self.window
+            .iter()
+            .map(|key| self.entries[key].data.clone())
+            .collect()
+    }
+
+    /// Diff the previous window against the current one. Only `changed`
+    /// entities can have moved relative to the others, so they are the ones
+    /// moved out of the way.
+    fn refresh(&mut self, changed: &HashSet<String>) -> Vec<ListChange<T>> {
+        let len = self.range.end.saturating_sub(self.range.start);
+        let target: Vec<String> = self
+            .ranks
+            .iter()
+            .skip(self.range.start)
+            .take(len)
+            .map(|rank| rank.key.clone())
+            .collect();
+        let target_index: HashMap<&str, usize> = target
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.as_str(), i))
+            .collect();
+
+        let data = |key: &str| self.entries[key].data.clone();
+        let mut changes = Vec::new();
+        let mut current = std::mem::take(&mut self.window);
+        // Moves carry the entity's data, so these need no separate update
+        let mut moved = HashSet::new();
+
+        for index in (0..current.len()).rev() {
+            if !target_index.contains_key(current[index].as_str()) {
+                let key = current.remove(index);
+                changes.push(ListChange::Remove { index, key });
+            }
+        }
+
+        let mut index = 0;
+        while index < target.len() {
+            let key = &target[index];
+            match current.get(index) {
+                Some(existing) if existing == key => {
+                    if changed.contains(key) && !moved.contains(key) {
+                        changes.push(ListChange::Update {
+                            index,
+                            key: key.clone(),
+                            data: data(key),
+                        });
+                    }
+                    index += 1;
+                }
+                // Move an entity that changed out to where it belongs
+                Some(existing) if changed.contains(existing) => {
+                    let from = index;
+                    let to = target_index[existing.as_str()].min(current.len() - 1);
+                    let key = current.remove(from);
+                    current.insert(to, key.clone());
+                    moved.insert(key.clone());
+                    changes.push(ListChange::Move {
+                        from,
+                        to,
+                        data: data(&key),
+                        key,
+                    });
+                }
+                _ => {
+                    match current.iter().position(|existing| existing == key) {
+                        Some(from) => {
+                            current.remove(from);
+                            moved.insert(key.clone());
+                            changes.push(ListChange::Move {
+                                from,
+                                to: index,
+                                key: key.clone(),
+                                data: data(key),
+                            });
+                        }
+                        None => changes.push(ListChange::Insert {
+                            index,
+                            key: key.clone(),
+                            data: data(key),
+                        }),
+                    }
+                    current.insert(index, key.clone());
+                    index += 1;
+                }
+            }
+        }
+
+        debug_assert_eq!(current, target);
+        self.window = target;
+        changes
+    }
+}
+
+type SyncFuture = Pin<Box<dyn Future<Output = HashMap<String, Value>> + Send>>;
+
+/// A stream of changes to a sorted window over a view.
+///
+/// The first item inserts the window's initial contents. Each later item
+/// holds the changes caused by one store update; updates that don't touch
+/// the window are not emitted.
+pub struct SortedWindowStream<T> {
+    state: SortedWindowState,
+    store: SharedStore,
+    view: String,
+    window: SortedWindow<T>,
+}
+
+enum SortedWindowState {
+    Lazy {
+        connection: ConnectionManager,
+        initial_data_timeout: Duration,
+    },
+    Syncing {
+        fut: SyncFuture,
+        inner: BroadcastStream<StoreUpdate>,
+    },
+    Active {
+        inner: BroadcastStream<StoreUpdate>,
+    },
+    Invalid,
+}
+
+impl<T: DeserializeOwned + Clone + Send + 'static> SortedWindowStream<T> {
+    pub(crate) fn new_lazy(
+        connection: ConnectionManager,
+        store: SharedStore,
+        view: String,
+        initial_data_timeout: Duration,
+        window: SortedWindow<T>,
+    ) -> Self {
+        Self {
+            state: SortedWindowState::Lazy {
+                connection,
+                initial_data_timeout,
+            },
+            store,
+            view,
+            window,
+        }
+    }
+
+    fn load_all(&self) -> SyncFuture {
+        let store = self.store.clone();
+        let view = self.view.clone();
+        Box::pin(async move { store.all_raw(&view).await })
+    }
+}
+
+impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for SortedWindowStream<T> {
+    type Item = Vec<ListChange<T>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match &mut this.state {
+                SortedWindowState::Lazy { .. } => {
+                    let SortedWindowState::Lazy {
+                        connection,
+                        initial_data_timeout,
+                    } = std::mem::replace(&mut this.state, SortedWindowState::Invalid)
+                    else {
+                        unreachable!()
+                    };
+
+                    // Subscribe to broadcast BEFORE reading the store so no
+                    // update falls between the two
+                    let inner = BroadcastStream::new(this.store.subscribe());
+
+                    let store = this.store.clone();
+                    let view = this.view.clone();
+                    let fut = Box::pin(async move {
+                        connection.ensure_subscription(&view, None).await;
+                        store.wait_for_view_ready(&view, initial_data_timeout).await;
+                        store.all_raw(&view).await
+                    });
+
+                    this.state = SortedWindowState::Syncing { fut, inner };
+                    continue;
+                }
+                SortedWindowState::Syncing { fut, .. } => match fut.as_mut().poll(cx) {
+                    Poll::Ready(entities) => {
+                        let SortedWindowState::Syncing { inner, .. } =
+                            std::mem::replace(&mut this.state, SortedWindowState::Invalid)
+                        else {
+                            unreachable!()
+                        };
+                        this.state = SortedWindowState::Active { inner };
+
+                        let changes = this.window.reset(entities);
+                        if !changes.is_empty() {
+                            return Poll::Ready(Some(changes));
+                        }
+                        continue;
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                SortedWindowState::Active { inner } => match Pin::new(inner).poll_next(cx) {
+                    Poll::Ready(Some(Ok(update))) => {
+                        if update.view != this.view {
+                            continue;
+                        }
+
+                        let changes = match (update.operation, update.data) {
+                            (Operation::Delete, _) => this.window.remove(&update.key),
+                            (
+                                Operation::Upsert
+                                | Operation::Create
+                                | Operation::Patch
+                                | Operation::History,
+                                Some(data),
+                            ) => this.window.upsert(&update.key, data),
+                            _ => continue,
+                        };
+                        if !changes.is_empty() {
+                            return Poll::Ready(Some(changes));
+                        }
+                    }
+                    Poll::Ready(Some(Err(_lagged))) => {
+                        tracing::warn!("SortedWindowStream lagged behind, resyncing from store");
+                        let fut = this.load_all();
+                        let SortedWindowState::Active { inner } =
+                            std::mem::replace(&mut this.state, SortedWindowState::Invalid)
+                        else {
+                            unreachable!()
+                        };
+                        this.state = SortedWindowState::Syncing { fut, inner };
+                    }
+                    Poll::Ready(None) => return Poll::Ready(None),
+                    Poll::Pending => return Poll::Pending,
+                },
+                SortedWindowState::Invalid => {
+                    panic!("SortedWindowStream in invalid state");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Clone, PartialEq, Deserialize)]
+    struct Miner {
+        name: String,
+        rewards: Option<Value>,
+    }
+
+    fn miner(name: &str, rewards: Value) -> Value {
+        json!({ "name": name, "rewards": rewards })
+    }
+
+    fn rewards() -> SortField {
+        SortField::new(&["rewards"], FieldKind::Numeric)
+    }
+
+    /// Stable sort by key, then by field, with nulls last
+    fn naive(
+        entities: &HashMap<String, Value>,
+        field: &SortField,
+        order: SortOrder,
+        range: Range<usize>,
+    ) -> Vec<String> {
+        let mut keys: Vec<&String> = entities.keys().collect();
+        keys.sort();
+        keys.sort_by(
+            |a, b| match (field.extract(&entities[*a]), field.extract(&entities[*b])) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(x), Some(y)) => match order {
+                    SortOrder::Asc => x.cmp(&y),
+                    SortOrder::Desc => y.cmp(&x),
+                },
+            },
+        );
+        keys.into_iter()
+            .skip(range.start)
+            .take(range.end - range.start)
+            .cloned()
+            .collect()
+    }
+
+    fn names(items: &[Miner]) -> Vec<String> {
+        items.iter().map(|m| m.name.clone()).collect()
+    }
+
+    #[test]
+    fn orders_numbers_and_numeric_strings_with_nulls_last() {
+        let mut window = SortedWindow::<Miner>::new(rewards(), SortOrder::Desc, 0..10);
+        window.reset(HashMap::from([
+            ("a".to_string(), miner("a", json!(5))),
+            ("b".to_string(), miner("b", json!("18446744073709551615"))),
+            ("c".to_string(), miner("c", Value::Null)),
+            ("d".to_string(), miner("d", json!(7.5))),
+            ("e".to_string(), miner("e", json!(5))),
+        ]));
+        assert_eq!(names(&window.items()), vec!["b", "d", "a", "e", "c"]);
+
+        let mut window = SortedWindow::<Miner>::new(rewards(), SortOrder::Asc, 0..10);
+        window.reset(HashMap::from([
+            ("a".to_string(), miner("a", json!(5))),
+            ("c".to_string(), miner("c", Value::Null)),
+            ("e".to_string(), miner("e", json!(5))),
+            ("f".to_string(), miner("f", json!(-1))),
+        ]));
+        assert_eq!(names(&window.items()), vec!["f", "a", "e", "c"]);
+    }
+
+    #[test]
+    fn text_fields_compare_as_strings() {
+        let field = SortField::new(&["name"], FieldKind::Text);
+        let mut window = SortedWindow::<Miner>::new(field, SortOrder::Asc, 0..10);
+        window.reset(HashMap::from([
+            ("1".to_string(), miner("carol", json!(1))),
+            ("2".to_string(), miner("alice", json!(2))),
+            ("3".to_string(), miner("bob", json!(3))),
+        ]));
+        assert_eq!(names(&window.items()), vec!["alice", "bob", "carol"]);
+    }
+
+    #[test]
+    fn single_move_is_one_change() {
+        let mut window = SortedWindow::<Miner>::new(rewards(), SortOrder::Desc, 0..10);
+        let entities: HashMap<String, Value> = (0..6)
+            .map(|i| (format!("m{i}"), miner(&format!("m{i}"), json!(100 - i))))
+            .collect();
+        let mut items = Vec::new();
+        for change in window.reset(entities) {
+            change.apply(&mut items);
+        }
+
+        // The top miner drops to fifth place
+        let changes = window.upsert("m0", miner("m0", json!(95)));
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(
+            changes[0],
+            ListChange::Move { from: 0, to: 4, .. }
+        ));
+        for change in changes {
+            change.apply(&mut items);
+        }
+        assert_eq!(items, window.items());
+
+        // An unchanged upsert emits nothing
+        assert!(window.upsert("m0", miner("m0", json!(95))).is_empty());
+    }
+
+    #[test]
+    fn window_matches_naive_sort_over_mutating_store() {
+        for (order, range) in [
+            (SortOrder::Desc, 0..5),
+            (SortOrder::Asc, 3..9),
+            (SortOrder::Desc, 0..100),
+        ] {
+            let mut window = SortedWindow::<Miner>::new(rewards(), order, range.clone());
+            let mut store: HashMap<String, Value> = HashMap::new();
+            let mut items: Vec<Miner> = Vec::new();
+
+            // Deterministic pseudo-random mutations
+            let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+            let mut next = || {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed
+            };
+
+            for step in 0..500 {
+                let key = format!("m{}", next() % 20);
+                let changes = match next() % 5 {
+                    0 => {
+                        store.remove(&key);
+                        window.remove(&key)
+                    }
+                    1 => {
+                        let value = miner(&key, Value::Null);
+                        store.insert(key.clone(), value.clone());
+                        window.upsert(&key, value)
+                    }
+                    _ => {
+                        // Few distinct values so ties are common
+                        let value = miner(&key, json!(next() % 8));
+                        store.insert(key.clone(), value.clone());
+                        window.upsert(&key, value)
+                    }
+                };
+                for change in changes {
+                    change.apply(&mut items);
+                }
+
+                let expected = naive(&store, &rewards(), order, range.clone());
+                assert_eq!(names(&items), expected, "step {step}");
+            }
+        }
+    }
+
+    #[test]
+    fn reset_diffs_against_previous_window() {
+        let mut window = SortedWindow::<Miner>::new(rewards(), SortOrder::Asc, 0..3);
+        let mut items = Vec::new();
+        let first = HashMap::from([
+            ("a".to_string(), miner("a", json!(1))),
+            ("b".to_string(), miner("b", json!(2))),
+            ("c".to_string(), miner("c", json!(3))),
+        ]);
+        for change in window.reset(first) {
+            change.apply(&mut items);
+        }
+
+        let second = HashMap::from([
+            ("b".to_string(), miner("b", json!(2))),
+            ("c".to_string(), miner("c", json!(0))),
+            ("d".to_string(), miner("d", json!(5))),
+        ]);
+        let changes = window.reset(second);
+        assert!(!changes
+            .iter()
+            .any(|c| c.key() == "b" && matches!(c, ListChange::Update { .. })));
+        for change in changes {
+            change.apply(&mut items);
+        }
+        assert_eq!(names(&items), vec!["c", "b", "d"]);
+    }
+}
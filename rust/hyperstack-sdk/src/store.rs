@@ -1,11 +1,15 @@
 use crate::frame::{
-    parse_snapshot_entities, Frame, Operation, SortConfig, SortOrder, SubscribedFrame,
+    entity_slot, parse_history_items, parse_snapshot_entities, seq_slot, Continuation,
+    DeprecationNotice, Frame, FrameTooLarge, Mode, Operation, RetentionNotice, SnapshotEntity,
+    SortConfig, SortOrder, SubscribedFrame, SubscriptionDiagnostics,
 };
+use crate::optimistic::{self, OptimisticGuard, OptimisticOptions, Overlay, Reconciliation};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
-use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
 use tokio::sync::{broadcast, watch, RwLock};
 
 /// Default maximum number of entries per view before LRU eviction kicks in.
@@ -19,16 +23,66 @@ pub struct StoreConfig {
     /// are evicted using LRU (Least Recently Used) strategy.
     /// Set to `None` to disable size limiting (not recommended for long-running clients).
     pub max_entries_per_view: Option<usize>,
+    /// What happens to a snapshot holding entities that don't decode into
+    /// their view's type
+    pub decode_mode: DecodeMode,
 }
 
 impl Default for StoreConfig {
     fn default() -> Self {
         Self {
             max_entries_per_view: Some(DEFAULT_MAX_ENTRIES_PER_VIEW),
+            decode_mode: DecodeMode::default(),
         }
     }
 }
 
+/// How snapshots with entities that fail to decode are applied
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Skip the entities that fail to decode and apply the rest
+    #[default]
+    Lenient,
+    /// Apply none of the snapshot, and fail reads of the view until a
+    /// snapshot decodes whole. Meant for tests.
+    Strict,
+}
+
+/// An entity the store left out of a view because it didn't decode into the
+/// view's type
+#[derive(Debug, Clone)]
+pub struct DecodeError {
+    pub view: String,
+    pub key: String,
+    pub error: Arc<serde_json::Error>,
+    /// The entity as the server sent it
+    pub raw: Value,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} in {} failed to decode: {}",
+            self.key, self.view, self.error
+        )
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Checks that an entity decodes into its view's type
+type Decoder = Arc<dyn Fn(&Value) -> Result<(), serde_json::Error> + Send + Sync>;
+
+/// Entities that failed to decode, per view
+#[derive(Default)]
+struct DecodeFailures {
+    /// Number of entities left out so far
+    counts: HashMap<String, u64>,
+    /// The first failure of the view's latest snapshot, in strict mode
+    rejected: HashMap<String, DecodeError>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct SortKey {
     sort_value: SortValue,
@@ -112,8 +166,50 @@ struct ViewData {
     access_order: VecDeque<String>,
     sort_config: Option<SortConfig>,
     sorted_keys: BTreeMap<SortKey, ()>,
+    /// Optimistic overlays per key, in creation order
+    overlays: HashMap<String, Vec<Overlay>>,
+}
+
+/// Frames the server split into several, or left out to stay under its
+/// frame size limit
+#[derive(Default)]
+struct SplitFrames {
+    /// Parts received so far of split entity frames, per view and key
+    partial: HashMap<(String, String), Frame>,
+    /// Entities left out, per view and key, until a later frame for the key
+    /// arrives
+    oversized: HashMap<String, HashMap<String, FrameTooLarge>>,
+    /// Snapshots per view sent in pages whose last page hasn't arrived
+    snapshots: HashMap<String, PendingSnapshot>,
 }
 
+/// A snapshot sent in pages, until its last page arrives
+#[derive(Default)]
+struct PendingSnapshot {
+    /// Entities of the pages received so far
+    entities: Vec<SnapshotEntity>,
+    /// Frames for the view that arrived between pages, applied after the
+    /// snapshot
+    queued: Vec<Frame>,
+}
+
+/// What the latest subscription ack reported about each view
+#[derive(Default)]
+struct AckDetails {
+    /// Diagnostics, when the subscription asked for them
+    diagnostics: HashMap<String, SubscriptionDiagnostics>,
+    /// Fields the server limits the view to
+    fields: HashMap<String, Vec<String>>,
+    /// Deprecation notices, kept so each view's is logged once
+    deprecations: HashMap<String, DeprecationNotice>,
+    /// Keys each list view held when it was subscribed again, until its
+    /// fresh snapshot shows which of them still exist
+    stale: HashMap<String, HashSet<String>>,
+}
+
+/// Merge a patch into an entity. Arrays at `append_paths` are extended and
+/// other values replaced. A field set to `null` in the patch is removed from
+/// the entity, while a field the patch leaves out is kept as it was.
 pub fn deep_merge_with_append(
     target: &mut Value,
     patch: &Value,
@@ -123,6 +219,10 @@ pub fn deep_merge_with_append(
     match (target, patch) {
         (Value::Object(target_map), Value::Object(patch_map)) => {
             for (key, patch_value) in patch_map {
+                if patch_value.is_null() {
+                    target_map.remove(key);
+                    continue;
+                }
                 let field_path = if current_path.is_empty() {
                     key.clone()
                 } else {
@@ -159,15 +259,29 @@ pub struct StoreUpdate {
     /// The raw patch data for Patch operations (before merging into full state).
     /// This allows consumers to see exactly what fields changed without diffing.
     pub patch: Option<serde_json::Value>,
+    /// Slot the server produced the update at, when it stamped one
+    pub slot: Option<u64>,
 }
 
 pub struct SharedStore {
     views: Arc<RwLock<HashMap<String, ViewData>>>,
     view_configs: Arc<RwLock<HashMap<String, SortConfig>>>,
+    /// Latest server retention notice per view
+    retention: Arc<RwLock<HashMap<String, RetentionNotice>>>,
+    ack_details: Arc<RwLock<AckDetails>>,
+    split_frames: Arc<RwLock<SplitFrames>>,
     updates_tx: broadcast::Sender<StoreUpdate>,
     ready_views: Arc<RwLock<HashSet<String>>>,
     ready_tx: watch::Sender<HashSet<String>>,
     ready_rx: watch::Receiver<HashSet<String>>,
+    /// Views the server has acknowledged a subscription to
+    acked_views: watch::Sender<HashSet<String>>,
+    next_overlay_id: Arc<AtomicU64>,
+    /// Type each view's entities are decoded into, registered by typed
+    /// view handles
+    decoders: Arc<Mutex<HashMap<String, Decoder>>>,
+    decode_failures: Arc<RwLock<DecodeFailures>>,
+    decode_errors_tx: broadcast::Sender<DecodeError>,
     config: StoreConfig,
 }
 
@@ -178,6 +292,7 @@ impl ViewData {
             access_order: VecDeque::new(),
             sort_config: None,
             sorted_keys: BTreeMap::new(),
+            overlays: HashMap::new(),
         }
     }
 
@@ -187,6 +302,7 @@ impl ViewData {
             access_order: VecDeque::new(),
             sort_config: Some(sort_config),
             sorted_keys: BTreeMap::new(),
+            overlays: HashMap::new(),
         }
     }
 
@@ -289,6 +405,38 @@ impl ViewData {
         self.entities.len()
     }
 
+    /// Entity value with its optimistic overlays applied
+    fn current(&self, key: &str) -> Option<Value> {
+        match self.overlays.get(key) {
+            Some(overlays) => optimistic::compose(self.entities.get(key), overlays),
+            None => self.entities.get(key).cloned(),
+        }
+    }
+
+    /// Remove the overlays on `key` that a server frame from `slot` settles
+    /// and pair each with its result against the current server value
+    fn settle_overlays(&mut self, key: &str, slot: Option<u64>) -> Vec<(Overlay, Reconciliation)> {
+        let Some(overlays) = self.overlays.get_mut(key) else {
+            return Vec::new();
+        };
+        let (settled, pending): (Vec<_>, Vec<_>) = std::mem::take(overlays)
+            .into_iter()
+            .partition(|overlay| overlay.settled_by(slot));
+        *overlays = pending;
+        if overlays.is_empty() {
+            self.overlays.remove(key);
+        }
+
+        let server = self.entities.get(key);
+        settled
+            .into_iter()
+            .map(|overlay| {
+                let result = overlay.reconcile(server);
+                (overlay, result)
+            })
+            .collect()
+    }
+
     #[allow(dead_code)]
     fn ordered_keys(&self) -> Vec<String> {
         if let Some(ref config) = self.sort_config {
@@ -311,14 +459,17 @@ impl ViewData {
             let values: Vec<serde_json::Value> = self
                 .sorted_keys
                 .keys()
-                .filter_map(|sk| self.entities.get(&sk.entity_key).cloned())
+                .filter_map(|sk| self.current(&sk.entity_key))
                 .collect();
             match config.order {
                 SortOrder::Asc => values,
                 SortOrder::Desc => values.into_iter().rev().collect(),
             }
         } else {
-            self.entities.values().cloned().collect()
+            self.entities
+                .keys()
+                .filter_map(|key| self.current(key))
+                .collect()
         }
     }
 }
@@ -334,10 +485,18 @@ impl SharedStore {
         Self {
             views: Arc::new(RwLock::new(HashMap::new())),
             view_configs: Arc::new(RwLock::new(HashMap::new())),
+            retention: Arc::new(RwLock::new(HashMap::new())),
+            ack_details: Arc::new(RwLock::new(AckDetails::default())),
+            split_frames: Arc::new(RwLock::new(SplitFrames::default())),
             updates_tx,
             ready_views: Arc::new(RwLock::new(HashSet::new())),
             ready_tx,
             ready_rx,
+            acked_views: watch::channel(HashSet::new()).0,
+            next_overlay_id: Arc::new(AtomicU64::new(0)),
+            decoders: Arc::new(Mutex::new(HashMap::new())),
+            decode_failures: Arc::new(RwLock::new(DecodeFailures::default())),
+            decode_errors_tx: broadcast::channel(1000).0,
             config,
         }
     }
@@ -353,6 +512,25 @@ impl SharedStore {
     }
 
     pub async fn apply_frame(&self, frame: Frame) {
+        let frame = match frame.continuation {
+            Some(continuation) => match self.reassemble(frame, continuation).await {
+                Some(frame) => frame,
+                None => return,
+            },
+            None => frame,
+        };
+        let operation = frame.operation();
+        let frame = match operation {
+            Operation::Upsert
+            | Operation::Create
+            | Operation::Patch
+            | Operation::Delete
+            | Operation::History => match self.queue_behind_snapshot(frame).await {
+                Some(frame) => frame,
+                None => return,
+            },
+            _ => frame,
+        };
         let view_path = &frame.entity;
         tracing::debug!(
             "apply_frame: view={}, key={}, op={}",
@@ -361,13 +539,65 @@ impl SharedStore {
             frame.op,
         );
 
-        let operation = frame.operation();
-
         if operation == Operation::Snapshot {
-            self.apply_snapshot(&frame).await;
+            self.apply_snapshot_page(frame).await;
+            return;
+        }
+
+        if operation == Operation::History {
+            self.apply_history(&frame).await;
             return;
         }
 
+        if operation == Operation::Subscribed {
+            // The snapshot starts over after every ack, so pages left from a
+            // dropped connection or a resync are never applied
+            self.split_frames.write().await.snapshots.remove(view_path);
+            match serde_json::from_value::<SubscribedFrame>(frame.data) {
+                Ok(ack) => self.apply_subscribed_frame(ack).await,
+                Err(e) => tracing::warn!("invalid subscription ack for {}: {}", view_path, e),
+            }
+            return;
+        }
+
+        if operation == Operation::RetentionNotice {
+            match serde_json::from_value::<RetentionNotice>(frame.data) {
+                Ok(notice) => {
+                    self.retention
+                        .write()
+                        .await
+                        .insert(view_path.to_string(), notice);
+                }
+                Err(e) => tracing::warn!("invalid retention notice for {}: {}", view_path, e),
+            }
+            return;
+        }
+
+        if operation == Operation::FrameTooLarge {
+            match serde_json::from_value::<FrameTooLarge>(frame.data) {
+                Ok(mut notice) => {
+                    tracing::warn!(
+                        "{} left out of {}: {} byte frame exceeds the {} byte limit",
+                        frame.key,
+                        view_path,
+                        notice.bytes,
+                        notice.max_bytes
+                    );
+                    notice.key = frame.key.clone();
+                    self.split_frames
+                        .write()
+                        .await
+                        .oversized
+                        .entry(view_path.to_string())
+                        .or_default()
+                        .insert(frame.key, notice);
+                }
+                Err(e) => tracing::warn!("invalid frame_too_large for {}: {}", view_path, e),
+            }
+            return;
+        }
+        self.clear_oversized(view_path, &frame.key).await;
+
         let sort_config = self.view_configs.read().await.get(view_path).cloned();
 
         let mut views = self.views.write().await;
@@ -379,9 +609,10 @@ impl SharedStore {
             }
         });
 
-        let previous = view_data.entities.get(&frame.key).cloned();
+        let previous = view_data.current(&frame.key);
+        let slot = frame.slot();
 
-        let (current, patch) = match operation {
+        let (mut current, patch) = match operation {
             Operation::Upsert | Operation::Create => {
                 view_data.insert(frame.key.clone(), frame.data.clone());
                 self.enforce_max_entries(view_data);
@@ -403,9 +634,21 @@ impl SharedStore {
                 view_data.remove(&frame.key);
                 (None, None)
             }
-            Operation::Snapshot | Operation::Subscribed => unreachable!(),
+            Operation::Snapshot
+            | Operation::Subscribed
+            | Operation::RetentionNotice
+            | Operation::FrameTooLarge
+            | Operation::History => {
+                unreachable!()
+            }
         };
 
+        let settled = view_data.settle_overlays(&frame.key, slot);
+        if view_data.overlays.contains_key(&frame.key) {
+            current = view_data.current(&frame.key);
+        }
+        drop(views);
+
         let _ = self.updates_tx.send(StoreUpdate {
             view: view_path.to_string(),
             key: frame.key,
@@ -413,21 +656,187 @@ impl SharedStore {
             data: current,
             previous,
             patch,
+            slot,
         });
+        for (overlay, result) in settled {
+            overlay.resolve(result);
+        }
 
         self.mark_view_ready(view_path).await;
     }
 
-    async fn apply_snapshot(&self, frame: &Frame) {
-        let view_path = &frame.entity;
-        let snapshot_entities = parse_snapshot_entities(&frame.data);
+    /// Collect the parts of a split entity frame, returning the whole frame
+    /// once its last part has arrived.
+    ///
+    /// Parts arrive in order. A part that doesn't follow the previous one
+    /// means frames were lost, and the partial frame is dropped: the
+    /// subscription resyncs on the sequence gap.
+    async fn reassemble(&self, frame: Frame, continuation: Continuation) -> Option<Frame> {
+        let slot = (frame.entity.clone(), frame.key.clone());
+        let mut split_frames = self.split_frames.write().await;
+
+        let mut whole = if continuation.part <= 1 {
+            frame
+        } else {
+            let previous = split_frames.partial.remove(&slot);
+            match previous {
+                Some(mut whole)
+                    if whole.continuation.map(|c| c.part + 1) == Some(continuation.part) =>
+                {
+                    if let (Some(data), serde_json::Value::Object(part)) =
+                        (whole.data.as_object_mut(), frame.data)
+                    {
+                        data.extend(part);
+                    }
+                    whole
+                }
+                _ => {
+                    tracing::warn!(
+                        "dropping out of order part {} of {} for {}/{}",
+                        continuation.part,
+                        continuation.parts,
+                        slot.0,
+                        slot.1
+                    );
+                    return None;
+                }
+            }
+        };
+
+        if continuation.part < continuation.parts {
+            whole.continuation = Some(continuation);
+            split_frames.partial.insert(slot, whole);
+            return None;
+        }
+        whole.continuation = None;
+        Some(whole)
+    }
+
+    /// Forget that `key` was left out of `view`, once a frame for it arrives
+    async fn clear_oversized(&self, view: &str, key: &str) {
+        let known = self
+            .split_frames
+            .read()
+            .await
+            .oversized
+            .get(view)
+            .is_some_and(|keys| keys.contains_key(key));
+        if known {
+            if let Some(keys) = self.split_frames.write().await.oversized.get_mut(view) {
+                keys.remove(key);
+            }
+        }
+    }
+
+    /// Hold back `frame` while its view's snapshot is still arriving in
+    /// pages, returning it when there is none
+    async fn queue_behind_snapshot(&self, frame: Frame) -> Option<Frame> {
+        let mut split_frames = self.split_frames.write().await;
+        match split_frames.snapshots.get_mut(&frame.entity) {
+            Some(snapshot) => {
+                snapshot.queued.push(frame);
+                None
+            }
+            None => Some(frame),
+        }
+    }
+
+    /// Buffer a page of a snapshot. Once the last page arrives, the whole
+    /// snapshot is applied at once, followed by the frames queued behind it.
+    async fn apply_snapshot_page(&self, frame: Frame) {
+        let view_path = frame.entity;
+        let mut entities = parse_snapshot_entities(&frame.data);
+
+        if frame.complete == Some(false) {
+            self.split_frames
+                .write()
+                .await
+                .snapshots
+                .entry(view_path)
+                .or_default()
+                .entities
+                .append(&mut entities);
+            return;
+        }
 
+        let pending = self.split_frames.write().await.snapshots.remove(&view_path);
+        let queued = match pending {
+            Some(mut pending) => {
+                pending.entities.append(&mut entities);
+                entities = pending.entities;
+                pending.queued
+            }
+            None => Vec::new(),
+        };
+        self.apply_snapshot(&view_path, entities).await;
+        for frame in queued {
+            Box::pin(self.apply_frame(frame)).await;
+        }
+    }
+
+    /// Split off the snapshot entities that don't decode into the view's
+    /// type, reporting each of them
+    async fn reject_undecodable(
+        &self,
+        view_path: &str,
+        snapshot_entities: Vec<SnapshotEntity>,
+    ) -> (Vec<SnapshotEntity>, Vec<DecodeError>) {
+        let decoder = self.decoders.lock().unwrap().get(view_path).cloned();
+        let Some(decoder) = decoder else {
+            return (snapshot_entities, Vec::new());
+        };
+
+        let mut decoded = Vec::with_capacity(snapshot_entities.len());
+        let mut rejected = Vec::new();
+        for entity in snapshot_entities {
+            match decoder(&entity.data) {
+                Ok(()) => decoded.push(entity),
+                Err(error) => rejected.push(DecodeError {
+                    view: view_path.to_string(),
+                    key: entity.key,
+                    error: Arc::new(error),
+                    raw: entity.data,
+                }),
+            }
+        }
+
+        if !rejected.is_empty() {
+            let mut failures = self.decode_failures.write().await;
+            *failures.counts.entry(view_path.to_string()).or_default() += rejected.len() as u64;
+            for error in &rejected {
+                tracing::warn!("{}", error);
+                let _ = self.decode_errors_tx.send(error.clone());
+            }
+        }
+        (decoded, rejected)
+    }
+
+    async fn apply_snapshot(&self, view_path: &str, snapshot_entities: Vec<SnapshotEntity>) {
         tracing::debug!(
             "apply_snapshot: view={}, count={}",
             view_path,
             snapshot_entities.len()
         );
 
+        let (snapshot_entities, rejected) =
+            self.reject_undecodable(view_path, snapshot_entities).await;
+        {
+            let mut failures = self.decode_failures.write().await;
+            match rejected.first() {
+                Some(first) if self.config.decode_mode == DecodeMode::Strict => {
+                    failures
+                        .rejected
+                        .insert(view_path.to_string(), first.clone());
+                    drop(failures);
+                    self.mark_view_ready(view_path).await;
+                    return;
+                }
+                _ => {
+                    failures.rejected.remove(view_path);
+                }
+            }
+        }
+
         let sort_config = self.view_configs.read().await.get(view_path).cloned();
 
         let mut views = self.views.write().await;
@@ -439,17 +848,130 @@ impl SharedStore {
             }
         });
 
+        let stale = self.ack_details.write().await.stale.remove(view_path);
+        let mut removed = Vec::new();
+        let mut settled = Vec::new();
+        if let Some(mut stale) = stale {
+            for entity in &snapshot_entities {
+                stale.remove(&entity.key);
+            }
+            for key in stale {
+                let previous = view_data.current(&key);
+                if view_data.remove(&key).is_none() {
+                    continue;
+                }
+                settled.extend(view_data.settle_overlays(&key, None));
+                removed.push((key, previous));
+            }
+        }
+        // An entity that no longer decodes is removed rather than left at
+        // its previous value
+        for error in rejected {
+            let previous = view_data.current(&error.key);
+            if view_data.remove(&error.key).is_some() {
+                settled.extend(view_data.settle_overlays(&error.key, None));
+                removed.push((error.key, previous));
+            }
+        }
+
         for entity in snapshot_entities {
-            let previous = view_data.entities.get(&entity.key).cloned();
+            let previous = view_data.current(&entity.key);
+            let slot = entity_slot(&entity.data);
             view_data.insert(entity.key.clone(), entity.data.clone());
 
+            settled.extend(view_data.settle_overlays(&entity.key, slot));
+            let data = if view_data.overlays.contains_key(&entity.key) {
+                view_data.current(&entity.key)
+            } else {
+                Some(entity.data)
+            };
+
             let _ = self.updates_tx.send(StoreUpdate {
                 view: view_path.to_string(),
                 key: entity.key,
                 operation: Operation::Upsert,
-                data: Some(entity.data),
+                data,
+                previous,
+                patch: None,
+                slot,
+            });
+        }
+
+        // Entities removed while the view was away are deleted rather than
+        // left behind
+        for (key, previous) in removed {
+            let data = if view_data.overlays.contains_key(&key) {
+                view_data.current(&key)
+            } else {
+                None
+            };
+            let _ = self.updates_tx.send(StoreUpdate {
+                view: view_path.to_string(),
+                key,
+                operation: Operation::Delete,
+                data,
                 previous,
                 patch: None,
+                slot: None,
+            });
+        }
+
+        self.enforce_max_entries(view_data);
+        drop(views);
+        for (overlay, result) in settled {
+            overlay.resolve(result);
+        }
+        self.mark_view_ready(view_path).await;
+    }
+
+    /// Replay append history. Each item is emitted with its raw data in
+    /// `patch`. Items are only merged into entities the store didn't hold
+    /// before the frame, since a snapshot already includes their effect.
+    async fn apply_history(&self, frame: &Frame) {
+        let view_path = &frame.entity;
+        let items = parse_history_items(&frame.data);
+
+        tracing::debug!("apply_history: view={}, count={}", view_path, items.len());
+
+        let sort_config = self.view_configs.read().await.get(view_path).cloned();
+
+        let mut views = self.views.write().await;
+        let view_data = views.entry(view_path.to_string()).or_insert_with(|| {
+            if let Some(config) = sort_config {
+                ViewData::with_sort_config(config)
+            } else {
+                ViewData::new()
+            }
+        });
+
+        let known: HashSet<String> = items
+            .iter()
+            .filter(|item| view_data.entities.contains_key(&item.key))
+            .map(|item| item.key.clone())
+            .collect();
+
+        for item in items {
+            let current = if known.contains(&item.key) {
+                view_data.entities.get(&item.key).cloned()
+            } else {
+                let entry = view_data
+                    .entities
+                    .entry(item.key.clone())
+                    .or_insert_with(|| serde_json::json!({}));
+                deep_merge_with_append(entry, &item.data, &item.append, "");
+                let merged = entry.clone();
+                view_data.touch(&item.key);
+                Some(merged)
+            };
+
+            let _ = self.updates_tx.send(StoreUpdate {
+                view: view_path.to_string(),
+                key: item.key,
+                operation: Operation::History,
+                data: current,
+                previous: None,
+                patch: Some(item.data),
+                slot: item.seq.as_deref().and_then(seq_slot),
             });
         }
 
@@ -495,13 +1017,21 @@ impl SharedStore {
         }
     }
 
+    /// Wait until a subscription to `view` has been acknowledged, returning
+    /// false if no ack arrives within `timeout`.
+    pub async fn wait_for_ack(&self, view: &str, timeout: std::time::Duration) -> bool {
+        let mut rx = self.acked_views.subscribe();
+        tokio::time::timeout(timeout, rx.wait_for(|acked| acked.contains(view)))
+            .await
+            .is_ok_and(|result| result.is_ok())
+    }
+
     pub async fn get<T: DeserializeOwned>(&self, view: &str, key: &str) -> Option<T> {
         let views = self.views.read().await;
         views
             .get(view)?
-            .entities
-            .get(key)
-            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .current(key)
+            .and_then(|v| serde_json::from_value(v).ok())
     }
 
     pub async fn list<T: DeserializeOwned>(&self, view: &str) -> Vec<T> {
@@ -537,9 +1067,8 @@ impl SharedStore {
         let views = self.views.try_read().ok()?;
         views
             .get(view)?
-            .entities
-            .get(key)
-            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .current(key)
+            .and_then(|v| serde_json::from_value(v).ok())
     }
 
     /// Synchronously get all entities from a view.
@@ -569,6 +1098,111 @@ impl SharedStore {
         self.updates_tx.subscribe()
     }
 
+    /// Overlay `patch` on the entity under `key` until it is reconciled with
+    /// the server. See [`crate::optimistic`].
+    pub async fn apply_optimistic(
+        &self,
+        view: &str,
+        key: &str,
+        patch: Value,
+        options: OptimisticOptions,
+    ) -> OptimisticGuard {
+        let id = self
+            .next_overlay_id
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let timeout = options.timeout;
+        let (overlay, result_rx) = Overlay::new(id, patch.clone(), options);
+
+        let sort_config = self.view_configs.read().await.get(view).cloned();
+
+        let mut views = self.views.write().await;
+        let view_data = views.entry(view.to_string()).or_insert_with(|| {
+            if let Some(config) = sort_config {
+                ViewData::with_sort_config(config)
+            } else {
+                ViewData::new()
+            }
+        });
+        let previous = view_data.current(key);
+        view_data
+            .overlays
+            .entry(key.to_string())
+            .or_default()
+            .push(overlay);
+        let current = view_data.current(key);
+        drop(views);
+
+        let _ = self.updates_tx.send(StoreUpdate {
+            view: view.to_string(),
+            key: key.to_string(),
+            operation: Operation::Patch,
+            data: current,
+            previous,
+            patch: Some(patch),
+            slot: None,
+        });
+
+        let store = self.clone();
+        let (expire_view, expire_key) = (view.to_string(), key.to_string());
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            store
+                .resolve_overlay(&expire_view, &expire_key, id, Reconciliation::Expired)
+                .await;
+        });
+
+        OptimisticGuard::new(
+            self.clone(),
+            view.to_string(),
+            key.to_string(),
+            id,
+            result_rx,
+        )
+    }
+
+    /// Remove overlay `id` with `result`, unless it was already resolved
+    pub(crate) async fn resolve_overlay(
+        &self,
+        view: &str,
+        key: &str,
+        id: u64,
+        result: Reconciliation,
+    ) {
+        let mut views = self.views.write().await;
+        let Some(view_data) = views.get_mut(view) else {
+            return;
+        };
+        let previous = view_data.current(key);
+        let Some(overlays) = view_data.overlays.get_mut(key) else {
+            return;
+        };
+        let Some(index) = overlays.iter().position(|overlay| overlay.id == id) else {
+            return;
+        };
+        let overlay = overlays.remove(index);
+        if overlays.is_empty() {
+            view_data.overlays.remove(key);
+        }
+        let current = view_data.current(key);
+        drop(views);
+
+        let operation = if current.is_some() {
+            Operation::Upsert
+        } else {
+            Operation::Delete
+        };
+        let _ = self.updates_tx.send(StoreUpdate {
+            view: view.to_string(),
+            key: key.to_string(),
+            operation,
+            data: current,
+            previous,
+            patch: None,
+            slot: None,
+        });
+        overlay.resolve(result);
+    }
+
     pub async fn apply_subscribed_frame(&self, frame: SubscribedFrame) {
         let view_path = &frame.view;
         tracing::debug!(
@@ -578,6 +1212,63 @@ impl SharedStore {
             frame.sort,
         );
 
+        let resubscribed = !self
+            .acked_views
+            .send_if_modified(|acked| acked.insert(view_path.to_string()));
+
+        if let Some(notice) = frame.retention {
+            self.retention
+                .write()
+                .await
+                .insert(view_path.to_string(), notice);
+        }
+
+        // A list view subscribed again reconciles with its fresh snapshot
+        let stale: Option<HashSet<String>> = if resubscribed && frame.mode == Mode::List {
+            self.views
+                .read()
+                .await
+                .get(view_path)
+                .map(|view_data| view_data.entities.keys().cloned().collect())
+        } else {
+            None
+        };
+
+        {
+            let mut details = self.ack_details.write().await;
+            match stale {
+                Some(keys) => details.stale.insert(view_path.to_string(), keys),
+                None => details.stale.remove(view_path),
+            };
+            if let Some(diagnostics) = frame.diagnostics {
+                details
+                    .diagnostics
+                    .insert(view_path.to_string(), diagnostics);
+            }
+            // Every ack replaces the fields, so a plan that stopped limiting
+            // them clears them
+            match frame.fields {
+                Some(fields) => details.fields.insert(view_path.to_string(), fields),
+                None => details.fields.remove(view_path),
+            };
+            match frame.deprecation {
+                Some(notice) => {
+                    if details.deprecations.get(view_path) != Some(&notice) {
+                        tracing::warn!(
+                            view = %view_path,
+                            replacement = %notice.replacement,
+                            sunset_at = ?notice.sunset_at,
+                            "View is deprecated, subscribe to its replacement instead"
+                        );
+                    }
+                    details.deprecations.insert(view_path.to_string(), notice);
+                }
+                None => {
+                    details.deprecations.remove(view_path);
+                }
+            }
+        }
+
         if let Some(sort_config) = frame.sort {
             self.view_configs
                 .write()
@@ -589,6 +1280,90 @@ impl SharedStore {
                 view_data.set_sort_config(sort_config);
             }
         }
+
+        // A later ack starts the view over, after a resync or reconnect.
+        // Entity streams skip it, merged streams report it.
+        if resubscribed {
+            let _ = self.updates_tx.send(StoreUpdate {
+                view: view_path.to_string(),
+                key: String::new(),
+                operation: Operation::Subscribed,
+                data: None,
+                previous: None,
+                patch: None,
+                slot: None,
+            });
+        }
+    }
+
+    /// Latest retention notice received for a view, if the server reported one
+    pub async fn retention(&self, view: &str) -> Option<RetentionNotice> {
+        self.retention.read().await.get(view).cloned()
+    }
+
+    /// Entities of a view the server left out for exceeding its frame size
+    /// limit, to be fetched another way
+    pub async fn oversized(&self, view: &str) -> Vec<FrameTooLarge> {
+        self.split_frames
+            .read()
+            .await
+            .oversized
+            .get(view)
+            .map(|keys| keys.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Check the entities of `view`'s snapshots against `T`, leaving out
+    /// the ones that don't decode into it
+    pub fn decode_as<T: DeserializeOwned>(&self, view: &str) {
+        let decoder: Decoder = Arc::new(|value| T::deserialize(value).map(drop));
+        self.decoders
+            .lock()
+            .unwrap()
+            .insert(view.to_string(), decoder);
+    }
+
+    /// Entities of any view left out because they didn't decode
+    pub fn decode_errors(&self) -> broadcast::Receiver<DecodeError> {
+        self.decode_errors_tx.subscribe()
+    }
+
+    /// Number of entities of a view left out so far because they didn't
+    /// decode
+    pub async fn decode_failures(&self, view: &str) -> u64 {
+        let failures = self.decode_failures.read().await;
+        failures.counts.get(view).copied().unwrap_or_default()
+    }
+
+    /// In strict mode, the failure that kept a view's latest snapshot from
+    /// being applied
+    pub async fn rejected_snapshot(&self, view: &str) -> Option<DecodeError> {
+        self.decode_failures
+            .read()
+            .await
+            .rejected
+            .get(view)
+            .cloned()
+    }
+
+    /// Diagnostics from the latest subscription ack for a view, if requested
+    pub async fn diagnostics(&self, view: &str) -> Option<SubscriptionDiagnostics> {
+        self.ack_details.read().await.diagnostics.get(view).cloned()
+    }
+
+    /// The deprecation notice from the latest subscription ack for a view
+    pub async fn deprecation(&self, view: &str) -> Option<DeprecationNotice> {
+        self.ack_details
+            .read()
+            .await
+            .deprecations
+            .get(view)
+            .cloned()
+    }
+
+    /// Fields the server limits a view to, `None` when it sends them all
+    pub async fn allowed_fields(&self, view: &str) -> Option<Vec<String>> {
+        self.ack_details.read().await.fields.get(view).cloned()
     }
 
     pub async fn get_view_sort_config(&self, view: &str) -> Option<SortConfig> {
@@ -619,10 +1394,18 @@ impl Clone for SharedStore {
         Self {
             views: self.views.clone(),
             view_configs: self.view_configs.clone(),
+            retention: self.retention.clone(),
+            ack_details: self.ack_details.clone(),
+            split_frames: self.split_frames.clone(),
             updates_tx: self.updates_tx.clone(),
             ready_views: self.ready_views.clone(),
             ready_tx: self.ready_tx.clone(),
             ready_rx: self.ready_rx.clone(),
+            acked_views: self.acked_views.clone(),
+            next_overlay_id: self.next_overlay_id.clone(),
+            decoders: self.decoders.clone(),
+            decode_failures: self.decode_failures.clone(),
+            decode_errors_tx: self.decode_errors_tx.clone(),
             config: self.config.clone(),
         }
     }
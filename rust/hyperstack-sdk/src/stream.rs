@@ -1,14 +1,17 @@
 use crate::connection::{ConnectionManager, SubscriptionOptions};
+use crate::error::HyperStackError;
 use crate::frame::Operation;
 use crate::store::{SharedStore, StoreUpdate};
+use crate::subscription::{EntityFilter, UpdateDelivery};
 use futures_util::Stream;
 use pin_project_lite::pin_project;
 use serde::de::DeserializeOwned;
 use std::collections::HashSet;
-use std::future::Future;
+use std::future::{poll_fn, Future};
 use std::marker::PhantomData;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
 use tokio::sync::broadcast;
 use tokio_stream::wrappers::BroadcastStream;
 
@@ -171,6 +174,7 @@ pub struct EntityStream<T> {
     _marker: PhantomData<T>,
 }
 
+#[allow(clippy::large_enum_variant)]
 enum EntityStreamState<T> {
     Lazy {
         connection: ConnectionManager,
@@ -182,6 +186,9 @@ enum EntityStreamState<T> {
         with_snapshot: Option<bool>,
         after: Option<String>,
         snapshot_limit: Option<usize>,
+        delivery: Option<UpdateDelivery>,
+        filter: Option<Box<EntityFilter>>,
+        delta: Option<bool>,
     },
     Active {
         inner: BroadcastStream<StoreUpdate>,
@@ -280,6 +287,9 @@ impl<T: DeserializeOwned + Clone + Send + 'static> EntityStream<T> {
                 with_snapshot,
                 after,
                 snapshot_limit,
+                delivery: None,
+                filter: None,
+                delta: None,
             },
             view: entity_name,
             key_filter,
@@ -287,6 +297,31 @@ impl<T: DeserializeOwned + Clone + Send + 'static> EntityStream<T> {
         }
     }
 
+    /// Ask for `delivery` of live updates. Has no effect once subscribed.
+    pub fn with_delivery(mut self, delivery: Option<UpdateDelivery>) -> Self {
+        if let EntityStreamState::Lazy { delivery: lazy, .. } = &mut self.state {
+            *lazy = delivery;
+        }
+        self
+    }
+
+    /// Only receive entities matching `filter`. Has no effect once subscribed.
+    pub fn with_filter(mut self, filter: Option<EntityFilter>) -> Self {
+        if let EntityStreamState::Lazy { filter: lazy, .. } = &mut self.state {
+            *lazy = filter.map(Box::new);
+        }
+        self
+    }
+
+    /// Ask for live updates trimmed to the fields that changed. Has no effect
+    /// once subscribed.
+    pub fn with_delta(mut self, delta: Option<bool>) -> Self {
+        if let EntityStreamState::Lazy { delta: lazy, .. } = &mut self.state {
+            *lazy = delta;
+        }
+        self
+    }
+
     pub fn filter<F>(self, predicate: F) -> FilteredStream<Self, Update<T>, F>
     where
         F: FnMut(&Update<T>) -> bool,
@@ -309,14 +344,47 @@ impl<T: DeserializeOwned + Clone + Send + 'static> EntityStream<T> {
     }
 }
 
-impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for EntityStream<T> {
-    type Item = Update<T>;
+impl<T: DeserializeOwned + Clone + Send + 'static> EntityStream<T> {
+    /// Subscribe now and wait for the server to acknowledge the subscription,
+    /// for up to the client's `subscribe_ack_timeout`.
+    ///
+    /// Only establishing the stream is bounded; once it is established the
+    /// stream waits for updates indefinitely.
+    pub async fn established(mut self) -> Result<Self, HyperStackError> {
+        self.establish(None).await?;
+        Ok(self)
+    }
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.get_mut();
+    /// Like [`established`](Self::established), waiting up to `timeout`.
+    pub async fn established_within(mut self, timeout: Duration) -> Result<Self, HyperStackError> {
+        self.establish(Some(timeout)).await?;
+        Ok(self)
+    }
+
+    pub(crate) async fn establish(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<(), HyperStackError> {
+        let EntityStreamState::Lazy {
+            connection,
+            store,
+            subscription_view,
+            ..
+        } = &self.state
+        else {
+            return Ok(());
+        };
+        let (connection, store, view) =
+            (connection.clone(), store.clone(), subscription_view.clone());
+
+        poll_fn(|cx| self.poll_subscribed(cx)).await;
+        connection.wait_for_ack(&store, &view, timeout).await
+    }
 
+    /// Send the subscription without consuming any update.
+    fn poll_subscribed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
         loop {
-            match &mut this.state {
+            match &mut self.state {
                 EntityStreamState::Lazy { .. } => {
                     let EntityStreamState::Lazy {
                         connection,
@@ -328,7 +396,10 @@ impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for EntityStre
                         with_snapshot,
                         after,
                         snapshot_limit,
-                    } = std::mem::replace(&mut this.state, EntityStreamState::Invalid)
+                        delivery,
+                        filter,
+                        delta,
+                    } = std::mem::replace(&mut self.state, EntityStreamState::Invalid)
                     else {
                         unreachable!()
                     };
@@ -347,26 +418,48 @@ impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for EntityStre
                             with_snapshot,
                             after,
                             snapshot_limit,
+                            history: None,
+                            delivery,
+                            filter: filter.map(|filter| *filter),
+                            delta,
                         };
                         conn.ensure_subscription_with_opts(&view, key.as_deref(), opts)
                             .await;
                     });
 
-                    this.state = EntityStreamState::Subscribing { fut, inner };
+                    self.state = EntityStreamState::Subscribing { fut, inner };
                     continue;
                 }
                 EntityStreamState::Subscribing { fut, .. } => match fut.as_mut().poll(cx) {
                     Poll::Ready(()) => {
                         let EntityStreamState::Subscribing { inner, .. } =
-                            std::mem::replace(&mut this.state, EntityStreamState::Invalid)
+                            std::mem::replace(&mut self.state, EntityStreamState::Invalid)
                         else {
                             unreachable!()
                         };
-                        this.state = EntityStreamState::Active { inner };
-                        continue;
+                        self.state = EntityStreamState::Active { inner };
+                        return Poll::Ready(());
                     }
                     Poll::Pending => return Poll::Pending,
                 },
+                _ => return Poll::Ready(()),
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for EntityStream<T> {
+    type Item = Update<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match &mut this.state {
+                EntityStreamState::Lazy { .. } | EntityStreamState::Subscribing { .. } => {
+                    ready!(this.poll_subscribed(cx));
+                    continue;
+                }
                 EntityStreamState::Active { inner } => match Pin::new(inner).poll_next(cx) {
                     Poll::Ready(Some(Ok(update))) => {
                         if update.view != this.view {
@@ -411,7 +504,10 @@ impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for EntityStre
                                     }
                                 }
                             }
-                            Operation::Subscribed => {
+                            Operation::Subscribed
+                            | Operation::RetentionNotice
+                            | Operation::FrameTooLarge
+                            | Operation::History => {
                                 continue;
                             }
                         }
@@ -443,6 +539,7 @@ pub struct RichEntityStream<T> {
     _marker: PhantomData<T>,
 }
 
+#[allow(clippy::large_enum_variant)]
 enum RichEntityStreamState<T> {
     Lazy {
         connection: ConnectionManager,
@@ -454,6 +551,9 @@ enum RichEntityStreamState<T> {
         with_snapshot: Option<bool>,
         after: Option<String>,
         snapshot_limit: Option<usize>,
+        delivery: Option<UpdateDelivery>,
+        filter: Option<Box<EntityFilter>>,
+        delta: Option<bool>,
     },
     Active {
         inner: BroadcastStream<StoreUpdate>,
@@ -537,22 +637,83 @@ impl<T: DeserializeOwned + Clone + Send + 'static> RichEntityStream<T> {
                 with_snapshot,
                 after,
                 snapshot_limit,
+                delivery: None,
+                filter: None,
+                delta: None,
             },
             view: entity_name,
             key_filter,
             _marker: PhantomData,
         }
     }
+
+    /// Ask for `delivery` of live updates. Has no effect once subscribed.
+    pub fn with_delivery(mut self, delivery: Option<UpdateDelivery>) -> Self {
+        if let RichEntityStreamState::Lazy { delivery: lazy, .. } = &mut self.state {
+            *lazy = delivery;
+        }
+        self
+    }
+
+    /// Only receive entities matching `filter`. Has no effect once subscribed.
+    pub fn with_filter(mut self, filter: Option<EntityFilter>) -> Self {
+        if let RichEntityStreamState::Lazy { filter: lazy, .. } = &mut self.state {
+            *lazy = filter.map(Box::new);
+        }
+        self
+    }
+
+    /// Ask for live updates trimmed to the fields that changed. Has no effect
+    /// once subscribed.
+    pub fn with_delta(mut self, delta: Option<bool>) -> Self {
+        if let RichEntityStreamState::Lazy { delta: lazy, .. } = &mut self.state {
+            *lazy = delta;
+        }
+        self
+    }
 }
 
-impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for RichEntityStream<T> {
-    type Item = RichUpdate<T>;
+impl<T: DeserializeOwned + Clone + Send + 'static> RichEntityStream<T> {
+    /// Subscribe now and wait for the server to acknowledge the subscription,
+    /// for up to the client's `subscribe_ack_timeout`.
+    ///
+    /// Only establishing the stream is bounded; once it is established the
+    /// stream waits for updates indefinitely.
+    pub async fn established(mut self) -> Result<Self, HyperStackError> {
+        self.establish(None).await?;
+        Ok(self)
+    }
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.get_mut();
+    /// Like [`established`](Self::established), waiting up to `timeout`.
+    pub async fn established_within(mut self, timeout: Duration) -> Result<Self, HyperStackError> {
+        self.establish(Some(timeout)).await?;
+        Ok(self)
+    }
 
+    pub(crate) async fn establish(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<(), HyperStackError> {
+        let RichEntityStreamState::Lazy {
+            connection,
+            store,
+            subscription_view,
+            ..
+        } = &self.state
+        else {
+            return Ok(());
+        };
+        let (connection, store, view) =
+            (connection.clone(), store.clone(), subscription_view.clone());
+
+        poll_fn(|cx| self.poll_subscribed(cx)).await;
+        connection.wait_for_ack(&store, &view, timeout).await
+    }
+
+    /// Send the subscription without consuming any update.
+    fn poll_subscribed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
         loop {
-            match &mut this.state {
+            match &mut self.state {
                 RichEntityStreamState::Lazy { .. } => {
                     let RichEntityStreamState::Lazy {
                         connection,
@@ -564,7 +725,10 @@ impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for RichEntity
                         with_snapshot,
                         after,
                         snapshot_limit,
-                    } = std::mem::replace(&mut this.state, RichEntityStreamState::Invalid)
+                        delivery,
+                        filter,
+                        delta,
+                    } = std::mem::replace(&mut self.state, RichEntityStreamState::Invalid)
                     else {
                         unreachable!()
                     };
@@ -583,26 +747,48 @@ impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for RichEntity
                             with_snapshot,
                             after,
                             snapshot_limit,
+                            history: None,
+                            delivery,
+                            filter: filter.map(|filter| *filter),
+                            delta,
                         };
                         conn.ensure_subscription_with_opts(&view, key.as_deref(), opts)
                             .await;
                     });
 
-                    this.state = RichEntityStreamState::Subscribing { fut, inner };
+                    self.state = RichEntityStreamState::Subscribing { fut, inner };
                     continue;
                 }
                 RichEntityStreamState::Subscribing { fut, .. } => match fut.as_mut().poll(cx) {
                     Poll::Ready(()) => {
                         let RichEntityStreamState::Subscribing { inner, .. } =
-                            std::mem::replace(&mut this.state, RichEntityStreamState::Invalid)
+                            std::mem::replace(&mut self.state, RichEntityStreamState::Invalid)
                         else {
                             unreachable!()
                         };
-                        this.state = RichEntityStreamState::Active { inner };
-                        continue;
+                        self.state = RichEntityStreamState::Active { inner };
+                        return Poll::Ready(());
                     }
                     Poll::Pending => return Poll::Pending,
                 },
+                _ => return Poll::Ready(()),
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for RichEntityStream<T> {
+    type Item = RichUpdate<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match &mut this.state {
+                RichEntityStreamState::Lazy { .. } | RichEntityStreamState::Subscribing { .. } => {
+                    ready!(this.poll_subscribed(cx));
+                    continue;
+                }
                 RichEntityStreamState::Active { inner } => match Pin::new(inner).poll_next(cx) {
                     Poll::Ready(Some(Ok(update))) => {
                         if update.view != this.view {
@@ -662,7 +848,10 @@ impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for RichEntity
                                     }
                                 }
                             }
-                            Operation::Subscribed => {
+                            Operation::Subscribed
+                            | Operation::RetentionNotice
+                            | Operation::FrameTooLarge
+                            | Operation::History => {
                                 continue;
                             }
                         }
@@ -926,6 +1115,7 @@ pub struct UseStream<T> {
     _marker: PhantomData<T>,
 }
 
+#[allow(clippy::large_enum_variant)]
 enum UseStreamState<T> {
     Lazy {
         connection: ConnectionManager,
@@ -937,6 +1127,9 @@ enum UseStreamState<T> {
         with_snapshot: Option<bool>,
         after: Option<String>,
         snapshot_limit: Option<usize>,
+        delivery: Option<UpdateDelivery>,
+        filter: Option<Box<EntityFilter>>,
+        delta: Option<bool>,
     },
     Active {
         inner: BroadcastStream<StoreUpdate>,
@@ -1020,6 +1213,9 @@ impl<T: DeserializeOwned + Clone + Send + 'static> UseStream<T> {
                 with_snapshot,
                 after,
                 snapshot_limit,
+                delivery: None,
+                filter: None,
+                delta: None,
             },
             view: entity_name,
             key_filter,
@@ -1027,6 +1223,31 @@ impl<T: DeserializeOwned + Clone + Send + 'static> UseStream<T> {
         }
     }
 
+    /// Ask for `delivery` of live updates. Has no effect once subscribed.
+    pub fn with_delivery(mut self, delivery: Option<UpdateDelivery>) -> Self {
+        if let UseStreamState::Lazy { delivery: lazy, .. } = &mut self.state {
+            *lazy = delivery;
+        }
+        self
+    }
+
+    /// Only receive entities matching `filter`. Has no effect once subscribed.
+    pub fn with_filter(mut self, filter: Option<EntityFilter>) -> Self {
+        if let UseStreamState::Lazy { filter: lazy, .. } = &mut self.state {
+            *lazy = filter.map(Box::new);
+        }
+        self
+    }
+
+    /// Ask for live updates trimmed to the fields that changed. Has no effect
+    /// once subscribed.
+    pub fn with_delta(mut self, delta: Option<bool>) -> Self {
+        if let UseStreamState::Lazy { delta: lazy, .. } = &mut self.state {
+            *lazy = delta;
+        }
+        self
+    }
+
     /// Filter the stream to only emit items matching the predicate.
     pub fn filter<F>(self, predicate: F) -> FilteredStream<Self, T, F>
     where
@@ -1052,14 +1273,47 @@ impl<T: DeserializeOwned + Clone + Send + 'static> UseStream<T> {
     }
 }
 
-impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for UseStream<T> {
-    type Item = T;
+impl<T: DeserializeOwned + Clone + Send + 'static> UseStream<T> {
+    /// Subscribe now and wait for the server to acknowledge the subscription,
+    /// for up to the client's `subscribe_ack_timeout`.
+    ///
+    /// Only establishing the stream is bounded; once it is established the
+    /// stream waits for updates indefinitely.
+    pub async fn established(mut self) -> Result<Self, HyperStackError> {
+        self.establish(None).await?;
+        Ok(self)
+    }
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.get_mut();
+    /// Like [`established`](Self::established), waiting up to `timeout`.
+    pub async fn established_within(mut self, timeout: Duration) -> Result<Self, HyperStackError> {
+        self.establish(Some(timeout)).await?;
+        Ok(self)
+    }
+
+    pub(crate) async fn establish(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<(), HyperStackError> {
+        let UseStreamState::Lazy {
+            connection,
+            store,
+            subscription_view,
+            ..
+        } = &self.state
+        else {
+            return Ok(());
+        };
+        let (connection, store, view) =
+            (connection.clone(), store.clone(), subscription_view.clone());
+
+        poll_fn(|cx| self.poll_subscribed(cx)).await;
+        connection.wait_for_ack(&store, &view, timeout).await
+    }
 
+    /// Send the subscription without consuming any update.
+    fn poll_subscribed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
         loop {
-            match &mut this.state {
+            match &mut self.state {
                 UseStreamState::Lazy { .. } => {
                     let UseStreamState::Lazy {
                         connection,
@@ -1071,7 +1325,10 @@ impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for UseStream<
                         with_snapshot,
                         after,
                         snapshot_limit,
-                    } = std::mem::replace(&mut this.state, UseStreamState::Invalid)
+                        delivery,
+                        filter,
+                        delta,
+                    } = std::mem::replace(&mut self.state, UseStreamState::Invalid)
                     else {
                         unreachable!()
                     };
@@ -1089,26 +1346,48 @@ impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for UseStream<
                             with_snapshot,
                             after,
                             snapshot_limit,
+                            history: None,
+                            delivery,
+                            filter: filter.map(|filter| *filter),
+                            delta,
                         };
                         conn.ensure_subscription_with_opts(&view, key.as_deref(), opts)
                             .await;
                     });
 
-                    this.state = UseStreamState::Subscribing { fut, inner };
+                    self.state = UseStreamState::Subscribing { fut, inner };
                     continue;
                 }
                 UseStreamState::Subscribing { fut, .. } => match fut.as_mut().poll(cx) {
                     Poll::Ready(()) => {
                         let UseStreamState::Subscribing { inner, .. } =
-                            std::mem::replace(&mut this.state, UseStreamState::Invalid)
+                            std::mem::replace(&mut self.state, UseStreamState::Invalid)
                         else {
                             unreachable!()
                         };
-                        this.state = UseStreamState::Active { inner };
-                        continue;
+                        self.state = UseStreamState::Active { inner };
+                        return Poll::Ready(());
                     }
                     Poll::Pending => return Poll::Pending,
                 },
+                _ => return Poll::Ready(()),
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for UseStream<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match &mut this.state {
+                UseStreamState::Lazy { .. } | UseStreamState::Subscribing { .. } => {
+                    ready!(this.poll_subscribed(cx));
+                    continue;
+                }
                 UseStreamState::Active { inner } => match Pin::new(inner).poll_next(cx) {
                     Poll::Ready(Some(Ok(update))) => {
                         if update.view != this.view {
@@ -1145,7 +1424,10 @@ impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for UseStream<
                                     }
                                 }
                             }
-                            Operation::Subscribed => {
+                            Operation::Subscribed
+                            | Operation::RetentionNotice
+                            | Operation::FrameTooLarge
+                            | Operation::History => {
                                 continue;
                             }
                         }
@@ -1169,3 +1451,223 @@ impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for UseStream<
         }
     }
 }
+
+/// An item from an append view.
+#[derive(Debug, Clone)]
+pub struct AppendItem<T> {
+    pub key: String,
+    pub data: T,
+    /// `true` for items replayed from the server's history on subscribe
+    pub history: bool,
+}
+
+/// A stream of items appended to a view, optionally starting with the most
+/// recent items the server kept.
+///
+/// Each item is the appended data itself, not the merged entity state.
+pub struct AppendStream<T> {
+    state: AppendStreamState<T>,
+    view: String,
+    key_filter: KeyFilter,
+    _marker: PhantomData<T>,
+}
+
+enum AppendStreamState<T> {
+    Lazy {
+        connection: ConnectionManager,
+        store: SharedStore,
+        subscription_key: Option<String>,
+        history: Option<usize>,
+    },
+    Active {
+        inner: BroadcastStream<StoreUpdate>,
+    },
+    Subscribing {
+        fut: Pin<Box<dyn Future<Output = ()> + Send>>,
+        inner: BroadcastStream<StoreUpdate>,
+    },
+    Invalid,
+    _Phantom(PhantomData<T>),
+}
+
+impl<T: DeserializeOwned + Clone + Send + 'static> AppendStream<T> {
+    pub fn new_lazy(
+        connection: ConnectionManager,
+        store: SharedStore,
+        view: String,
+        key_filter: KeyFilter,
+        subscription_key: Option<String>,
+        history: Option<usize>,
+    ) -> Self {
+        Self {
+            state: AppendStreamState::Lazy {
+                connection,
+                store,
+                subscription_key,
+                history,
+            },
+            view,
+            key_filter,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Clone + Send + 'static> AppendStream<T> {
+    /// Subscribe now and wait for the server to acknowledge the subscription,
+    /// for up to the client's `subscribe_ack_timeout`.
+    ///
+    /// Only establishing the stream is bounded; once it is established the
+    /// stream waits for updates indefinitely.
+    pub async fn established(mut self) -> Result<Self, HyperStackError> {
+        self.establish(None).await?;
+        Ok(self)
+    }
+
+    /// Like [`established`](Self::established), waiting up to `timeout`.
+    pub async fn established_within(mut self, timeout: Duration) -> Result<Self, HyperStackError> {
+        self.establish(Some(timeout)).await?;
+        Ok(self)
+    }
+
+    pub(crate) async fn establish(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<(), HyperStackError> {
+        let AppendStreamState::Lazy {
+            connection, store, ..
+        } = &self.state
+        else {
+            return Ok(());
+        };
+        let (connection, store) = (connection.clone(), store.clone());
+        let view = self.view.clone();
+
+        poll_fn(|cx| self.poll_subscribed(cx)).await;
+        connection.wait_for_ack(&store, &view, timeout).await
+    }
+
+    /// Send the subscription without consuming any update.
+    fn poll_subscribed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            match &mut self.state {
+                AppendStreamState::Lazy { .. } => {
+                    let AppendStreamState::Lazy {
+                        connection,
+                        store,
+                        subscription_key,
+                        history,
+                    } = std::mem::replace(&mut self.state, AppendStreamState::Invalid)
+                    else {
+                        unreachable!()
+                    };
+
+                    // Subscribe to broadcast BEFORE sending subscription to server
+                    let inner = BroadcastStream::new(store.subscribe());
+
+                    let view = self.view.clone();
+                    let fut = Box::pin(async move {
+                        // Earlier appends arrive as history, not as a snapshot
+                        let opts = SubscriptionOptions {
+                            with_snapshot: Some(false),
+                            history,
+                            ..Default::default()
+                        };
+                        connection
+                            .ensure_subscription_with_opts(&view, subscription_key.as_deref(), opts)
+                            .await;
+                    });
+
+                    self.state = AppendStreamState::Subscribing { fut, inner };
+                    continue;
+                }
+                AppendStreamState::Subscribing { fut, .. } => match fut.as_mut().poll(cx) {
+                    Poll::Ready(()) => {
+                        let AppendStreamState::Subscribing { inner, .. } =
+                            std::mem::replace(&mut self.state, AppendStreamState::Invalid)
+                        else {
+                            unreachable!()
+                        };
+                        self.state = AppendStreamState::Active { inner };
+                        return Poll::Ready(());
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                _ => return Poll::Ready(()),
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Clone + Send + Unpin + 'static> Stream for AppendStream<T> {
+    type Item = AppendItem<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match &mut this.state {
+                AppendStreamState::Lazy { .. } | AppendStreamState::Subscribing { .. } => {
+                    ready!(this.poll_subscribed(cx));
+                    continue;
+                }
+                AppendStreamState::Active { inner } => match Pin::new(inner).poll_next(cx) {
+                    Poll::Ready(Some(Ok(update))) => {
+                        if update.view != this.view {
+                            continue;
+                        }
+
+                        if !this.key_filter.matches(&update.key) {
+                            continue;
+                        }
+
+                        let history = match update.operation {
+                            Operation::History => true,
+                            Operation::Upsert | Operation::Create | Operation::Patch => false,
+                            Operation::Delete
+                            | Operation::Snapshot
+                            | Operation::Subscribed
+                            | Operation::RetentionNotice
+                            | Operation::FrameTooLarge => continue,
+                        };
+
+                        let Some(data) = update.patch.or(update.data) else {
+                            continue;
+                        };
+                        match serde_json::from_value::<T>(data) {
+                            Ok(data) => {
+                                return Poll::Ready(Some(AppendItem {
+                                    key: update.key,
+                                    data,
+                                    history,
+                                }));
+                            }
+                            Err(e) => {
+                                tracing::warn!(
+                                    key = %update.key,
+                                    error = %e,
+                                    "AppendStream: failed to deserialize item, skipping"
+                                );
+                                continue;
+                            }
+                        }
+                    }
+                    Poll::Ready(Some(Err(_lagged))) => {
+                        tracing::warn!("AppendStream lagged behind, some messages were dropped");
+                        continue;
+                    }
+                    Poll::Ready(None) => {
+                        return Poll::Ready(None);
+                    }
+                    Poll::Pending => {
+                        return Poll::Pending;
+                    }
+                },
+                AppendStreamState::Invalid => {
+                    panic!("AppendStream in invalid state");
+                }
+                AppendStreamState::_Phantom(_) => unreachable!(),
+            }
+        }
+    }
+}
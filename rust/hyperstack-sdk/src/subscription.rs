@@ -8,10 +8,23 @@ pub enum ClientMessage {
     Subscribe(Subscription),
     #[serde(rename = "unsubscribe")]
     Unsubscribe(Unsubscription),
+    /// Ask the server to restart a subscription from a fresh snapshot
+    #[serde(rename = "resync")]
+    Resync(Unsubscription),
+    /// Ask the server to replace a subscription's data with a fresh snapshot
+    #[serde(rename = "refresh")]
+    Refresh(Unsubscription),
     #[serde(rename = "ping")]
     Ping,
     #[serde(rename = "refresh_auth")]
     RefreshAuth { token: String },
+    /// Accept the compression offered in the server's `hello`
+    #[serde(rename = "compression")]
+    Compression {
+        codec: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dictionary: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -20,6 +33,10 @@ pub struct Subscription {
     pub view: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
+    /// Name for the subscription, to hold several on the same view and key
+    /// with different options. See [`with_id`](Self::with_id).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub partition: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -37,6 +54,79 @@ pub struct Subscription {
     /// Maximum number of entities to include in snapshot (pagination hint)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub snapshot_limit: Option<usize>,
+    /// Number of recent items to replay from an append view's history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<usize>,
+    /// Ask the server for [`SubscriptionDiagnostics`](crate::SubscriptionDiagnostics)
+    /// in the subscription ack
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<bool>,
+    /// Receive live updates as patches (the default) or as the whole entity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<UpdateDelivery>,
+    /// Only receive these fields of each entity, as dotted paths
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+    /// Only receive entities matching this filter (list views only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Box<EntityFilter>>,
+    /// Receive live updates trimmed to the fields that changed (list views
+    /// only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<bool>,
+}
+
+/// How the server delivers live updates to a subscription
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateDelivery {
+    /// Only the fields that changed, merged into the stored entity
+    #[default]
+    Patch,
+    /// The whole entity after each update, replacing the stored one.
+    /// State and list views only; append views always receive their items.
+    FullState,
+}
+
+/// Comparison a filter makes between a field and its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// The field is an array holding the value, or a string holding it
+    Contains,
+}
+
+/// A predicate the server checks each entity of a list view against.
+///
+/// Numbers compare by value, including big integers sent as strings. The
+/// snapshot holds only matching entities; one that stops matching arrives
+/// as a delete, and one that starts matching arrives whole.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntityFilter {
+    /// Dotted path of the field, e.g. `state.total_deployed`
+    pub field: String,
+    pub op: FilterOp,
+    pub value: serde_json::Value,
+}
+
+impl EntityFilter {
+    pub fn new(
+        field: impl Into<String>,
+        op: FilterOp,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            op,
+            value: value.into(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -44,6 +134,8 @@ pub struct Unsubscription {
     pub view: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
 }
 
 impl Unsubscription {
@@ -51,6 +143,7 @@ impl Unsubscription {
         Self {
             view: view.into(),
             key: None,
+            id: None,
         }
     }
 
@@ -59,8 +152,35 @@ impl Unsubscription {
         self
     }
 
+    /// Unsubscribe the subscription made with this `id`
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// `view:key`, or `view:*` without a key, then `#id` for a named
+    /// subscription. The server stamps each frame with it as `sub`.
     pub fn sub_key(&self) -> String {
-        format!("{}:{}", self.view, self.key.as_deref().unwrap_or("*"),)
+        let key = self.key.as_deref().unwrap_or("*");
+        match &self.id {
+            Some(id) => format!("{}:{}#{}", self.view, key, id),
+            None => format!("{}:{}", self.view, key),
+        }
+    }
+
+    /// The inverse of [`sub_key`](Self::sub_key). View names never contain
+    /// `:` and keys never contain `#`.
+    pub(crate) fn from_sub_key(sub_key: &str) -> Option<Self> {
+        let (view, rest) = sub_key.split_once(':')?;
+        let (key, id) = match rest.split_once('#') {
+            Some((key, id)) => (key, Some(id.to_string())),
+            None => (rest, None),
+        };
+        Some(Self {
+            view: view.to_string(),
+            key: (key != "*").then(|| key.to_string()),
+            id,
+        })
     }
 }
 
@@ -69,6 +189,7 @@ impl From<&Subscription> for Unsubscription {
         Self {
             view: sub.view.clone(),
             key: sub.key.clone(),
+            id: sub.id.clone(),
         }
     }
 }
@@ -78,6 +199,7 @@ impl Subscription {
         Self {
             view: view.into(),
             key: None,
+            id: None,
             partition: None,
             filters: None,
             take: None,
@@ -85,6 +207,12 @@ impl Subscription {
             with_snapshot: None,
             after: None,
             snapshot_limit: None,
+            history: None,
+            diagnostics: None,
+            delivery: None,
+            fields: None,
+            filter: None,
+            delta: None,
         }
     }
 
@@ -93,6 +221,14 @@ impl Subscription {
         self
     }
 
+    /// Name the subscription, so it runs alongside others on the same view
+    /// and key instead of replacing them. Its frames arrive stamped with
+    /// `view:key#id`, and it is unsubscribed with the same id.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
     pub fn with_filters(mut self, filters: HashMap<String, String>) -> Self {
         self.filters = Some(filters);
         self
@@ -126,6 +262,42 @@ impl Subscription {
         self
     }
 
+    /// Replay up to `n` recent items before live updates (append views only)
+    pub fn with_history(mut self, n: usize) -> Self {
+        self.history = Some(n);
+        self
+    }
+
+    /// Ask the server to report what the initial load cost in the ack
+    pub fn with_diagnostics(mut self, diagnostics: bool) -> Self {
+        self.diagnostics = Some(diagnostics);
+        self
+    }
+
+    /// Receive live updates as `delivery`
+    pub fn with_delivery(mut self, delivery: UpdateDelivery) -> Self {
+        self.delivery = Some(delivery);
+        self
+    }
+
+    /// Only receive `fields` of each entity, e.g. `["price", "trades.count"]`
+    pub fn with_fields(mut self, fields: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.fields = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only receive entities matching `filter`
+    pub fn with_filter(mut self, filter: EntityFilter) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Receive live updates as patches of only the fields that changed
+    pub fn with_delta(mut self, delta: bool) -> Self {
+        self.delta = Some(delta);
+        self
+    }
+
     pub fn sub_key(&self) -> String {
         let filters_str = self
             .filters
@@ -142,6 +314,9 @@ impl Subscription {
     }
 }
 
+/// Subscriptions by the id the server knows them by, as in
+/// [`Unsubscription::sub_key`]. Adding one under an id already in use
+/// replaces it, as the server does.
 #[derive(Debug, Default)]
 pub struct SubscriptionRegistry {
     subscriptions: HashMap<String, Subscription>,
@@ -153,20 +328,24 @@ impl SubscriptionRegistry {
     }
 
     pub fn add(&mut self, sub: Subscription) {
-        let key = sub.sub_key();
+        let key = Unsubscription::from(&sub).sub_key();
         self.subscriptions.insert(key, sub);
     }
 
-    pub fn remove(&mut self, sub: &Subscription) {
-        let key = sub.sub_key();
-        self.subscriptions.remove(&key);
+    pub fn remove(&mut self, unsub: &Unsubscription) {
+        self.subscriptions.remove(&unsub.sub_key());
     }
 
     pub fn contains(&self, sub: &Subscription) -> bool {
-        let key = sub.sub_key();
+        let key = Unsubscription::from(sub).sub_key();
         self.subscriptions.contains_key(&key)
     }
 
+    /// Whether the subscription `unsub` would end is open
+    pub fn has(&self, unsub: &Unsubscription) -> bool {
+        self.subscriptions.contains_key(&unsub.sub_key())
+    }
+
     pub fn all(&self) -> Vec<Subscription> {
         self.subscriptions.values().cloned().collect()
     }
@@ -176,3 +355,36 @@ impl SubscriptionRegistry {
         self.subscriptions.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sub_key_round_trips_with_an_id() {
+        let unsub = Unsubscription::new("Token/list").with_id("prices");
+        assert_eq!(unsub.sub_key(), "Token/list:*#prices");
+        assert_eq!(Unsubscription::from_sub_key(&unsub.sub_key()), Some(unsub));
+
+        let keyed = Unsubscription::new("Token/state").with_key("abc");
+        assert_eq!(Unsubscription::from_sub_key("Token/state:abc"), Some(keyed));
+    }
+
+    #[test]
+    fn registry_keeps_subscriptions_apart_by_id() {
+        let mut registry = SubscriptionRegistry::new();
+        registry.add(Subscription::new("Token/list").with_fields(["price"]));
+        registry.add(
+            Subscription::new("Token/list")
+                .with_id("volume")
+                .with_fields(["volume"]),
+        );
+        registry.add(Subscription::new("Token/list").with_fields(["name"]));
+        assert_eq!(registry.all().len(), 2);
+
+        registry.remove(&Unsubscription::new("Token/list").with_id("volume"));
+        let remaining = registry.all();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].fields, Some(vec!["name".to_string()]));
+    }
+}
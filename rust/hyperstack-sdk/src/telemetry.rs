@@ -0,0 +1,236 @@
+//! Spans and events describing the client's connection and subscriptions,
+//! emitted with the `tracing` feature.
+//!
+//! Everything is recorded under the `hyperstack_sdk::telemetry` target with
+//! the names and fields below. They are kept stable across releases so
+//! dashboards and log queries can rely on them; new fields may be added.
+//!
+//! | Name | Kind | Level | Fields |
+//! |------|------|-------|--------|
+//! | `hyperstack.connect` | span | INFO | `url`, `attempt`, `outcome` |
+//! | `hyperstack.subscription` | span | INFO | `view`, `key`, `outcome` |
+//! | `hyperstack.snapshot` | event | INFO | `view`, `entities`, `bytes` |
+//! | `hyperstack.gap` | event | WARN | `sub`, `expected`, `received` |
+//! | `hyperstack.resync` | event | INFO | `sub` |
+//! | `hyperstack.refresh` | event | INFO | `sub` |
+//! | `hyperstack.frame` | event | DEBUG | `view`, `op`, `duration_us` |
+//!
+//! - `hyperstack.connect` covers one connection attempt, from resolving the
+//!   auth token to the finished handshake. `attempt` counts from 0 and resets
+//!   after a successful connect. `outcome` is `connected`, `failed`,
+//!   `timeout` or `auth_failed`.
+//! - `hyperstack.subscription` runs from sending a subscribe request to its
+//!   acknowledgement. `key` is `*` for list subscriptions. `outcome` is
+//!   `acked`, or `disconnected` when the socket closed first.
+//! - `hyperstack.snapshot` is a snapshot frame as received: `entities` in
+//!   it and `bytes` on the wire.
+//! - `hyperstack.gap` is a missing frame in a subscription's sequence,
+//!   which requests a resync. `sub` is `view:key`.
+//! - `hyperstack.resync` is the server starting a subscription's sequence
+//!   over in answer to a resync request.
+//! - `hyperstack.refresh` is the same in answer to a refresh request, from
+//!   [`ViewHandle::refresh`](crate::ViewHandle::refresh).
+//! - `hyperstack.frame` is the time taken to apply a frame to the store, for
+//!   the share of frames set by
+//!   [`frame_sample_rate`](crate::HyperStackBuilder::frame_sample_rate).
+//!
+//! Without the feature every hook here is empty. With it, a hook costs a
+//! callsite interest check until a subscriber enables the target.
+
+use crate::frame::Frame;
+#[cfg(feature = "tracing")]
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+/// Target of every span and event
+pub const TARGET: &str = "hyperstack_sdk::telemetry";
+
+/// How a connection attempt ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConnectOutcome {
+    Connected,
+    Failed,
+    Timeout,
+    AuthFailed,
+}
+
+impl ConnectOutcome {
+    #[cfg(feature = "tracing")]
+    fn as_str(self) -> &'static str {
+        match self {
+            ConnectOutcome::Connected => "connected",
+            ConnectOutcome::Failed => "failed",
+            ConnectOutcome::Timeout => "timeout",
+            ConnectOutcome::AuthFailed => "auth_failed",
+        }
+    }
+}
+
+/// The `hyperstack.connect` span of one connection attempt
+pub(crate) struct ConnectSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl ConnectSpan {
+    pub(crate) fn start(url: &str, attempt: u32) -> Self {
+        #[cfg(not(feature = "tracing"))]
+        let _ = (url, attempt);
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                target: TARGET,
+                "hyperstack.connect",
+                url,
+                attempt,
+                outcome = tracing::field::Empty,
+            ),
+        }
+    }
+
+    pub(crate) fn finish(self, outcome: ConnectOutcome) {
+        #[cfg(feature = "tracing")]
+        self.span.record("outcome", outcome.as_str());
+        #[cfg(not(feature = "tracing"))]
+        let _ = outcome;
+    }
+}
+
+/// `hyperstack.subscription` spans waiting for their acknowledgement, by view
+#[derive(Default)]
+pub(crate) struct SubscriptionSpans {
+    #[cfg(feature = "tracing")]
+    pending: HashMap<String, VecDeque<tracing::Span>>,
+}
+
+impl SubscriptionSpans {
+    pub(crate) fn requested(&mut self, view: &str, key: Option<&str>) {
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::info_span!(
+                target: TARGET,
+                "hyperstack.subscription",
+                view,
+                key = key.unwrap_or("*"),
+                outcome = tracing::field::Empty,
+            );
+            if !span.is_disabled() {
+                self.pending
+                    .entry(view.to_string())
+                    .or_default()
+                    .push_back(span);
+            }
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = (view, key);
+    }
+
+    /// Close the oldest span waiting on `view`. Acks name only the view, and
+    /// the server answers a view's requests in order.
+    pub(crate) fn acked(&mut self, view: &str) {
+        #[cfg(feature = "tracing")]
+        if let Some(span) = self.pending.get_mut(view).and_then(VecDeque::pop_front) {
+            span.record("outcome", "acked");
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = view;
+    }
+
+    /// Close every waiting span, the socket having closed
+    pub(crate) fn disconnected(&mut self) {
+        #[cfg(feature = "tracing")]
+        for span in self.pending.drain().flat_map(|(_, spans)| spans) {
+            span.record("outcome", "disconnected");
+        }
+    }
+}
+
+pub(crate) fn snapshot_received(frame: &Frame, bytes: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        target: TARGET,
+        view = %frame.entity,
+        entities = frame.data.as_array().map_or(0, Vec::len),
+        bytes,
+        "hyperstack.snapshot"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = (frame, bytes);
+}
+
+pub(crate) fn gap_detected(sub: &str, expected: u64, received: u64) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(target: TARGET, sub, expected, received, "hyperstack.gap");
+    #[cfg(not(feature = "tracing"))]
+    let _ = (sub, expected, received);
+}
+
+pub(crate) fn resync_started(sub: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(target: TARGET, sub, "hyperstack.resync");
+    #[cfg(not(feature = "tracing"))]
+    let _ = sub;
+}
+
+pub(crate) fn refresh_started(sub: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(target: TARGET, sub, "hyperstack.refresh");
+    #[cfg(not(feature = "tracing"))]
+    let _ = sub;
+}
+
+/// Picks the frames whose processing time is recorded as `hyperstack.frame`
+pub(crate) struct FrameSampler {
+    #[cfg(feature = "tracing")]
+    every: u64,
+    #[cfg(feature = "tracing")]
+    seen: u64,
+}
+
+impl FrameSampler {
+    /// Sample `rate` of frames, from 0.0 for none to 1.0 for all
+    pub(crate) fn new(rate: f64) -> Self {
+        #[cfg(not(feature = "tracing"))]
+        let _ = rate;
+        Self {
+            #[cfg(feature = "tracing")]
+            every: if rate > 0.0 {
+                (1.0 / rate.min(1.0)).round() as u64
+            } else {
+                0
+            },
+            #[cfg(feature = "tracing")]
+            seen: 0,
+        }
+    }
+
+    /// Start timing the next frame, if it is sampled
+    pub(crate) fn start(&mut self) -> Option<Instant> {
+        #[cfg(feature = "tracing")]
+        {
+            if self.every == 0 || !tracing::enabled!(target: TARGET, tracing::Level::DEBUG) {
+                return None;
+            }
+            let sampled = self.seen.is_multiple_of(self.every);
+            self.seen += 1;
+            if sampled {
+                return Some(Instant::now());
+            }
+        }
+        None
+    }
+}
+
+/// Record a sampled frame's processing time, from `started`
+pub(crate) fn frame_processed(started: Instant, view: &str, op: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        target: TARGET,
+        view,
+        op,
+        duration_us = started.elapsed().as_micros() as u64,
+        "hyperstack.frame"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = (started, view, op);
+}
@@ -31,16 +31,85 @@
 //! ```
 
 use crate::connection::ConnectionManager;
-use crate::store::SharedStore;
-use crate::stream::{EntityStream, KeyFilter, RichEntityStream, Update, UseStream};
-use futures_util::Stream;
+use crate::entity::EntityKey;
+use crate::error::{HyperStackError, TimedOperation};
+use crate::frame::{
+    DeprecationNotice, FrameTooLarge, RetentionNotice, SortOrder, SubscriptionDiagnostics,
+};
+#[cfg(feature = "test-util")]
+use crate::frame::{Frame, Mode};
+use crate::optimistic::{OptimisticGuard, OptimisticOptions};
+use crate::sorted::{SortField, SortedWindow, SortedWindowStream};
+use crate::store::{DecodeError, SharedStore};
+use crate::stream::{
+    AppendItem, AppendStream, EntityStream, KeyFilter, RichEntityStream, Update, UseStream,
+};
+use crate::subscription::{EntityFilter, FilterOp, UpdateDelivery};
+use futures_util::{Stream, StreamExt};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::collections::HashMap;
+use serde_json::Value;
 use std::marker::PhantomData;
+use std::ops::Range;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
+use tokio_stream::wrappers::BroadcastStream;
+
+/// Per-call timeouts for [`ViewHandle::get_with`] and [`StateView::get_with`].
+///
+/// Unset timeouts fall back to the client's `snapshot_timeout` and
+/// `subscribe_ack_timeout`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GetOptions {
+    timeout: Option<Duration>,
+    ack_timeout: Option<Duration>,
+}
+
+impl GetOptions {
+    /// Wait up to `timeout` for the view's initial data.
+    pub fn timeout(timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..Self::default()
+        }
+    }
+
+    /// Wait up to `timeout` for the server to acknowledge the subscription.
+    pub fn ack_timeout(mut self, timeout: Duration) -> Self {
+        self.ack_timeout = Some(timeout);
+        self
+    }
+}
+
+/// Wait for the subscription to `view` to be acknowledged and its initial data
+/// to arrive. Offline clients have nothing to wait for.
+async fn wait_for_initial_data(
+    connection: &ConnectionManager,
+    store: &SharedStore,
+    view: &str,
+    snapshot_timeout: Duration,
+    options: GetOptions,
+) -> Result<(), HyperStackError> {
+    if connection.is_offline() {
+        return Ok(());
+    }
+
+    connection
+        .wait_for_ack(store, view, options.ack_timeout)
+        .await?;
+
+    let timeout = options.timeout.unwrap_or(snapshot_timeout);
+    if store.wait_for_view_ready(view, timeout).await {
+        Ok(())
+    } else {
+        Err(HyperStackError::timeout(
+            TimedOperation::Snapshot,
+            Some(view),
+            timeout,
+        ))
+    }
+}
 
 /// A handle to a view that provides get/watch operations.
 ///
@@ -73,6 +142,27 @@ where
         self.store.list::<T>(&self.view_path).await
     }
 
+    /// Like [`get`](Self::get), but fails with [`HyperStackError::Timeout`]
+    /// if the subscription isn't acknowledged or the view's initial data
+    /// doesn't arrive in time, instead of returning whatever is cached.
+    pub async fn get_with(&self, options: GetOptions) -> Result<Vec<T>, HyperStackError> {
+        self.connection
+            .ensure_subscription(&self.view_path, None)
+            .await;
+        wait_for_initial_data(
+            &self.connection,
+            &self.store,
+            &self.view_path,
+            self.initial_data_timeout,
+            options,
+        )
+        .await?;
+        if let Some(error) = self.store.rejected_snapshot(&self.view_path).await {
+            return Err(error.into());
+        }
+        Ok(self.store.list::<T>(&self.view_path).await)
+    }
+
     /// Synchronously get all items from cached data.
     ///
     /// Returns cached data immediately without waiting for subscription.
@@ -81,6 +171,86 @@ where
         self.store.list_sync::<T>(&self.view_path)
     }
 
+    /// Replace this view's data with a fresh snapshot from the server,
+    /// without unsubscribing.
+    ///
+    /// The server restarts the subscription from a snapshot, and the store
+    /// swaps it in whole once it arrives, dropping entities it no longer
+    /// holds. No update older than the snapshot follows it. A view that
+    /// isn't subscribed yet is subscribed instead. Servers refuse refreshes
+    /// of the same view closer together than they allow, reported through
+    /// [`subscribe_socket_issues`](crate::HyperStack::subscribe_socket_issues).
+    pub async fn refresh(&self) {
+        self.connection.refresh(&self.view_path, None).await;
+    }
+
+    pub(crate) fn view_path(&self) -> &str {
+        &self.view_path
+    }
+
+    /// Server retention state for this view.
+    ///
+    /// `Some` once the server reports that the view is at its entity cap and
+    /// evicting older entities, i.e. the list only holds the most recent
+    /// `cap` items.
+    pub async fn retention(&self) -> Option<RetentionNotice> {
+        self.store.retention(&self.view_path).await
+    }
+
+    /// What the server reported about this view's initial load.
+    ///
+    /// `Some` once a subscription ack arrives on a client built with
+    /// [`diagnostics(true)`](crate::HyperStackBuilder::diagnostics).
+    pub async fn diagnostics(&self) -> Option<SubscriptionDiagnostics> {
+        self.store.diagnostics(&self.view_path).await
+    }
+
+    /// The only fields the server sends for this view, as dotted paths.
+    ///
+    /// `Some` when the client's plan or the subscription limits them, e.g.
+    /// to hide UI for data the plan doesn't include. Taken from the latest
+    /// subscription ack.
+    pub async fn allowed_fields(&self) -> Option<Vec<String>> {
+        self.store.allowed_fields(&self.view_path).await
+    }
+
+    /// The server's notice that this view is deprecated, naming the view to
+    /// move to and when this one stops being served. Taken from the latest
+    /// subscription ack; the notice is also logged once per view.
+    pub async fn deprecation(&self) -> Option<DeprecationNotice> {
+        self.store.deprecation(&self.view_path).await
+    }
+
+    /// Entities the server left out of this view because a frame carrying
+    /// them exceeded its frame size limit. Fetch them another way, e.g. over
+    /// REST. An entity drops off the list once a frame for it arrives.
+    pub async fn oversized(&self) -> Vec<FrameTooLarge> {
+        self.store.oversized(&self.view_path).await
+    }
+
+    /// Stream of the entities left out of this view from now on because
+    /// they didn't decode into `T`, with the serde error and the entity as
+    /// the server sent it. Errors a slow consumer falls too far behind on
+    /// are skipped.
+    ///
+    /// In the default [`DecodeMode::Lenient`](crate::DecodeMode::Lenient)
+    /// the rest of the snapshot is still applied; in
+    /// [`DecodeMode::Strict`](crate::DecodeMode::Strict) none of it is, and
+    /// [`get_with`](Self::get_with) fails until a snapshot decodes whole.
+    pub fn errors(&self) -> impl Stream<Item = DecodeError> + Send + 'static {
+        let view = self.view_path.clone();
+        BroadcastStream::new(self.store.decode_errors()).filter_map(move |error| {
+            let error = error.ok().filter(|error| error.view == view);
+            async move { error }
+        })
+    }
+
+    /// Number of entities left out of this view so far because they didn't
+    /// decode into `T`
+    pub async fn decode_failures(&self) -> u64 {
+        self.store.decode_failures(&self.view_path).await
+    }
+
     /// Stream merged entities directly (simplest API - filters out deletes).
     ///
     /// Emits `T` after each change. Patches are merged to give full entity state.
@@ -123,6 +293,38 @@ where
         )
     }
 
+    /// Stream items appended to this view. Chain `.with_history(n)` to start
+    /// with the most recent items the server kept.
+    pub fn appends(&self) -> AppendBuilder<T>
+    where
+        T: Unpin,
+    {
+        AppendBuilder::new(
+            self.connection.clone(),
+            self.store.clone(),
+            self.view_path.clone(),
+        )
+    }
+
+    /// Keep this view sorted locally by `field`, independent of any server
+    /// sort. Chain `.window(range)` to follow a slice of the order, then
+    /// `.listen()` for changes to it.
+    pub fn sorted_by(&self, field: SortField, order: SortOrder) -> SortedBuilder<T>
+    where
+        T: Unpin,
+    {
+        SortedBuilder {
+            connection: self.connection.clone(),
+            store: self.store.clone(),
+            view_path: self.view_path.clone(),
+            initial_data_timeout: self.initial_data_timeout,
+            field,
+            order,
+            range: 0..usize::MAX,
+            _marker: PhantomData,
+        }
+    }
+
     /// Watch for updates filtered to specific keys.
     pub fn watch_keys(&self, keys: &[&str]) -> WatchBuilder<T>
     where
@@ -148,10 +350,12 @@ where
     key_filter: KeyFilter,
     take: Option<u32>,
     skip: Option<u32>,
-    filters: Option<HashMap<String, String>>,
+    filter: Option<EntityFilter>,
     with_snapshot: Option<bool>,
     after: Option<String>,
     snapshot_limit: Option<usize>,
+    delivery: Option<UpdateDelivery>,
+    delta: Option<bool>,
     stream: Option<UseStream<T>>,
 }
 
@@ -172,10 +376,12 @@ where
             key_filter,
             take: None,
             skip: None,
-            filters: None,
+            filter: None,
             with_snapshot: None,
             after: None,
             snapshot_limit: None,
+            delivery: None,
+            delta: None,
             stream: None,
         }
     }
@@ -192,11 +398,16 @@ where
         self
     }
 
-    /// Add a server-side filter.
-    pub fn filter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
-        self.filters
-            .get_or_insert_with(HashMap::new)
-            .insert(key.into(), value.into());
+    /// Only receive entities whose `field`, a dotted path, compares to
+    /// `value` by `op`. The server filters the snapshot and live updates,
+    /// and deletes entities that stop matching. List views only.
+    pub fn filter(
+        mut self,
+        field: impl Into<String>,
+        op: FilterOp,
+        value: impl Into<Value>,
+    ) -> Self {
+        self.filter = Some(EntityFilter::new(field, op, value));
         self
     }
 
@@ -217,6 +428,63 @@ where
         self.snapshot_limit = Some(limit);
         self
     }
+
+    /// Choose how live updates arrive. [`UpdateDelivery::FullState`] sends the
+    /// whole entity on every update instead of a patch.
+    pub fn with_delivery(mut self, delivery: UpdateDelivery) -> Self {
+        self.delivery = Some(delivery);
+        self
+    }
+
+    /// Receive live updates trimmed to the fields that changed since the
+    /// last frame of each entity. List views only.
+    pub fn delta(mut self, delta: bool) -> Self {
+        self.delta = Some(delta);
+        self
+    }
+}
+
+impl<T> UseBuilder<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    /// Subscribe now and wait for the server to acknowledge the subscription,
+    /// for up to the client's `subscribe_ack_timeout`. Call it after setting
+    /// the subscription's options.
+    ///
+    /// Only establishing the stream is bounded; once it is established the
+    /// stream waits for updates indefinitely.
+    pub async fn established(mut self) -> Result<Self, HyperStackError> {
+        self.stream().establish(None).await?;
+        Ok(self)
+    }
+
+    /// Like [`established`](Self::established), waiting up to `timeout`.
+    pub async fn established_within(mut self, timeout: Duration) -> Result<Self, HyperStackError> {
+        self.stream().establish(Some(timeout)).await?;
+        Ok(self)
+    }
+
+    fn stream(&mut self) -> &mut UseStream<T> {
+        self.stream.get_or_insert_with(|| {
+            UseStream::new_lazy_with_opts(
+                self.connection.clone(),
+                self.store.clone(),
+                self.view_path.clone(),
+                self.view_path.clone(),
+                self.key_filter.clone(),
+                None,
+                self.take,
+                self.skip,
+                self.with_snapshot,
+                self.after.clone(),
+                self.snapshot_limit,
+            )
+            .with_delivery(self.delivery)
+            .with_filter(self.filter.clone())
+            .with_delta(self.delta)
+        })
+    }
 }
 
 impl<T> Stream for UseBuilder<T>
@@ -226,25 +494,127 @@ where
     type Item = T;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.get_mut();
-
-        if this.stream.is_none() {
-            this.stream = Some(UseStream::new_lazy_with_opts(
-                this.connection.clone(),
-                this.store.clone(),
-                this.view_path.clone(),
-                this.view_path.clone(),
-                this.key_filter.clone(),
-                None,
-                this.take,
-                this.skip,
-                this.with_snapshot,
-                this.after.clone(),
-                this.snapshot_limit,
-            ));
+        Pin::new(self.get_mut().stream()).poll_next(cx)
+    }
+}
+
+/// Builder for append view subscriptions. Implements `Stream`.
+pub struct AppendBuilder<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    connection: ConnectionManager,
+    store: SharedStore,
+    view_path: String,
+    history: Option<usize>,
+    stream: Option<AppendStream<T>>,
+}
+
+impl<T> AppendBuilder<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    fn new(connection: ConnectionManager, store: SharedStore, view_path: String) -> Self {
+        Self {
+            connection,
+            store,
+            view_path,
+            history: None,
+            stream: None,
         }
+    }
+
+    /// Start with up to `n` of the most recent items, capped by the view's
+    /// configured history. Replayed items have `history` set and are never
+    /// delivered again live.
+    ///
+    /// Has no effect if the view is already subscribed on this connection.
+    pub fn with_history(mut self, n: usize) -> Self {
+        self.history = Some(n);
+        self
+    }
+}
+
+impl<T> AppendBuilder<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    /// Subscribe now and wait for the server to acknowledge the subscription,
+    /// for up to the client's `subscribe_ack_timeout`. Call it after setting
+    /// the subscription's options.
+    ///
+    /// Only establishing the stream is bounded; once it is established the
+    /// stream waits for updates indefinitely.
+    pub async fn established(mut self) -> Result<Self, HyperStackError> {
+        self.stream().establish(None).await?;
+        Ok(self)
+    }
+
+    /// Like [`established`](Self::established), waiting up to `timeout`.
+    pub async fn established_within(mut self, timeout: Duration) -> Result<Self, HyperStackError> {
+        self.stream().establish(Some(timeout)).await?;
+        Ok(self)
+    }
+
+    fn stream(&mut self) -> &mut AppendStream<T> {
+        self.stream.get_or_insert_with(|| {
+            AppendStream::new_lazy(
+                self.connection.clone(),
+                self.store.clone(),
+                self.view_path.clone(),
+                KeyFilter::None,
+                None,
+                self.history,
+            )
+        })
+    }
+}
+
+impl<T> Stream for AppendBuilder<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    type Item = AppendItem<T>;
 
-        Pin::new(this.stream.as_mut().unwrap()).poll_next(cx)
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(self.get_mut().stream()).poll_next(cx)
+    }
+}
+
+/// Builder for a locally sorted window over a view.
+pub struct SortedBuilder<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    connection: ConnectionManager,
+    store: SharedStore,
+    view_path: String,
+    initial_data_timeout: Duration,
+    field: SortField,
+    order: SortOrder,
+    range: Range<usize>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> SortedBuilder<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    /// Only follow positions `range` of the sorted order (defaults to all).
+    pub fn window(mut self, range: Range<usize>) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Stream changes to the window, starting with its current contents.
+    pub fn listen(self) -> SortedWindowStream<T> {
+        SortedWindowStream::new_lazy(
+            self.connection,
+            self.store,
+            self.view_path,
+            self.initial_data_timeout,
+            SortedWindow::new(self.field, self.order, self.range),
+        )
     }
 }
 
@@ -259,10 +629,12 @@ where
     key_filter: KeyFilter,
     take: Option<u32>,
     skip: Option<u32>,
-    filters: Option<HashMap<String, String>>,
+    filter: Option<EntityFilter>,
     with_snapshot: Option<bool>,
     after: Option<String>,
     snapshot_limit: Option<usize>,
+    delivery: Option<UpdateDelivery>,
+    delta: Option<bool>,
     stream: Option<EntityStream<T>>,
 }
 
@@ -283,10 +655,12 @@ where
             key_filter,
             take: None,
             skip: None,
-            filters: None,
+            filter: None,
             with_snapshot: None,
             after: None,
             snapshot_limit: None,
+            delivery: None,
+            delta: None,
             stream: None,
         }
     }
@@ -303,11 +677,16 @@ where
         self
     }
 
-    /// Add a server-side filter.
-    pub fn filter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
-        self.filters
-            .get_or_insert_with(HashMap::new)
-            .insert(key.into(), value.into());
+    /// Only receive entities whose `field`, a dotted path, compares to
+    /// `value` by `op`. The server filters the snapshot and live updates,
+    /// and deletes entities that stop matching. List views only.
+    pub fn filter(
+        mut self,
+        field: impl Into<String>,
+        op: FilterOp,
+        value: impl Into<Value>,
+    ) -> Self {
+        self.filter = Some(EntityFilter::new(field, op, value));
         self
     }
 
@@ -329,6 +708,20 @@ where
         self
     }
 
+    /// Choose how live updates arrive. [`UpdateDelivery::FullState`] sends the
+    /// whole entity on every update instead of a patch.
+    pub fn with_delivery(mut self, delivery: UpdateDelivery) -> Self {
+        self.delivery = Some(delivery);
+        self
+    }
+
+    /// Receive live updates trimmed to the fields that changed since the
+    /// last frame of each entity. List views only.
+    pub fn delta(mut self, delta: bool) -> Self {
+        self.delta = Some(delta);
+        self
+    }
+
     /// Get a rich stream with before/after diffs instead.
     pub fn rich(self) -> RichEntityStream<T> {
         RichEntityStream::new_lazy_with_opts(
@@ -344,6 +737,52 @@ where
             self.after,
             self.snapshot_limit,
         )
+        .with_delivery(self.delivery)
+        .with_filter(self.filter)
+        .with_delta(self.delta)
+    }
+}
+
+impl<T> WatchBuilder<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    /// Subscribe now and wait for the server to acknowledge the subscription,
+    /// for up to the client's `subscribe_ack_timeout`. Call it after setting
+    /// the subscription's options.
+    ///
+    /// Only establishing the stream is bounded; once it is established the
+    /// stream waits for updates indefinitely.
+    pub async fn established(mut self) -> Result<Self, HyperStackError> {
+        self.stream().establish(None).await?;
+        Ok(self)
+    }
+
+    /// Like [`established`](Self::established), waiting up to `timeout`.
+    pub async fn established_within(mut self, timeout: Duration) -> Result<Self, HyperStackError> {
+        self.stream().establish(Some(timeout)).await?;
+        Ok(self)
+    }
+
+    fn stream(&mut self) -> &mut EntityStream<T> {
+        self.stream.get_or_insert_with(|| {
+            EntityStream::new_lazy_with_opts(
+                self.connection.clone(),
+                self.store.clone(),
+                self.view_path.clone(),
+                self.view_path.clone(),
+                self.key_filter.clone(),
+                None,
+                self.take,
+                self.skip,
+                self.with_snapshot,
+                self.after.clone(),
+                self.snapshot_limit,
+            )
+            .with_delivery(self.delivery)
+            .with_filter(self.filter.clone())
+            .with_delta(self.delta)
+        })
     }
 }
 
@@ -354,25 +793,7 @@ where
     type Item = Update<T>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.get_mut();
-
-        if this.stream.is_none() {
-            this.stream = Some(EntityStream::new_lazy_with_opts(
-                this.connection.clone(),
-                this.store.clone(),
-                this.view_path.clone(),
-                this.view_path.clone(),
-                this.key_filter.clone(),
-                None,
-                this.take,
-                this.skip,
-                this.with_snapshot,
-                this.after.clone(),
-                this.snapshot_limit,
-            ));
-        }
-
-        Pin::new(this.stream.as_mut().unwrap()).poll_next(cx)
+        Pin::new(self.get_mut().stream()).poll_next(cx)
     }
 }
 
@@ -387,10 +808,12 @@ where
     key_filter: KeyFilter,
     take: Option<u32>,
     skip: Option<u32>,
-    filters: Option<HashMap<String, String>>,
+    filter: Option<EntityFilter>,
     with_snapshot: Option<bool>,
     after: Option<String>,
     snapshot_limit: Option<usize>,
+    delivery: Option<UpdateDelivery>,
+    delta: Option<bool>,
     stream: Option<RichEntityStream<T>>,
 }
 
@@ -411,10 +834,12 @@ where
             key_filter,
             take: None,
             skip: None,
-            filters: None,
+            filter: None,
             with_snapshot: None,
             after: None,
             snapshot_limit: None,
+            delivery: None,
+            delta: None,
             stream: None,
         }
     }
@@ -429,10 +854,16 @@ where
         self
     }
 
-    pub fn filter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
-        self.filters
-            .get_or_insert_with(HashMap::new)
-            .insert(key.into(), value.into());
+    /// Only receive entities whose `field`, a dotted path, compares to
+    /// `value` by `op`. The server filters the snapshot and live updates,
+    /// and deletes entities that stop matching. List views only.
+    pub fn filter(
+        mut self,
+        field: impl Into<String>,
+        op: FilterOp,
+        value: impl Into<Value>,
+    ) -> Self {
+        self.filter = Some(EntityFilter::new(field, op, value));
         self
     }
 
@@ -453,6 +884,63 @@ where
         self.snapshot_limit = Some(limit);
         self
     }
+
+    /// Choose how live updates arrive. [`UpdateDelivery::FullState`] sends the
+    /// whole entity on every update instead of a patch.
+    pub fn with_delivery(mut self, delivery: UpdateDelivery) -> Self {
+        self.delivery = Some(delivery);
+        self
+    }
+
+    /// Receive live updates trimmed to the fields that changed since the
+    /// last frame of each entity. List views only.
+    pub fn delta(mut self, delta: bool) -> Self {
+        self.delta = Some(delta);
+        self
+    }
+}
+
+impl<T> RichWatchBuilder<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    /// Subscribe now and wait for the server to acknowledge the subscription,
+    /// for up to the client's `subscribe_ack_timeout`. Call it after setting
+    /// the subscription's options.
+    ///
+    /// Only establishing the stream is bounded; once it is established the
+    /// stream waits for updates indefinitely.
+    pub async fn established(mut self) -> Result<Self, HyperStackError> {
+        self.stream().establish(None).await?;
+        Ok(self)
+    }
+
+    /// Like [`established`](Self::established), waiting up to `timeout`.
+    pub async fn established_within(mut self, timeout: Duration) -> Result<Self, HyperStackError> {
+        self.stream().establish(Some(timeout)).await?;
+        Ok(self)
+    }
+
+    fn stream(&mut self) -> &mut RichEntityStream<T> {
+        self.stream.get_or_insert_with(|| {
+            RichEntityStream::new_lazy_with_opts(
+                self.connection.clone(),
+                self.store.clone(),
+                self.view_path.clone(),
+                self.view_path.clone(),
+                self.key_filter.clone(),
+                None,
+                self.take,
+                self.skip,
+                self.with_snapshot,
+                self.after.clone(),
+                self.snapshot_limit,
+            )
+            .with_delivery(self.delivery)
+            .with_filter(self.filter.clone())
+            .with_delta(self.delta)
+        })
+    }
 }
 
 impl<T> Stream for RichWatchBuilder<T>
@@ -462,25 +950,7 @@ where
     type Item = crate::stream::RichUpdate<T>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.get_mut();
-
-        if this.stream.is_none() {
-            this.stream = Some(RichEntityStream::new_lazy_with_opts(
-                this.connection.clone(),
-                this.store.clone(),
-                this.view_path.clone(),
-                this.view_path.clone(),
-                this.key_filter.clone(),
-                None,
-                this.take,
-                this.skip,
-                this.with_snapshot,
-                this.after.clone(),
-                this.snapshot_limit,
-            ));
-        }
-
-        Pin::new(this.stream.as_mut().unwrap()).poll_next(cx)
+        Pin::new(self.get_mut().stream()).poll_next(cx)
     }
 }
 
@@ -524,6 +994,7 @@ impl ViewBuilder {
     where
         T: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
     {
+        self.store.decode_as::<T>(view_path);
         ViewHandle {
             connection: self.connection.clone(),
             store: self.store.clone(),
@@ -558,6 +1029,7 @@ where
         view_path: String,
         initial_data_timeout: Duration,
     ) -> Self {
+        store.decode_as::<T>(&view_path);
         Self {
             connection,
             store,
@@ -568,57 +1040,218 @@ where
     }
 
     /// Get an entity by key.
-    pub async fn get(&self, key: &str) -> Option<T> {
+    ///
+    /// Accepts a raw key string or a generated typed key (e.g. `OreMinerKey`).
+    pub async fn get(&self, key: impl EntityKey) -> Option<T> {
+        let key = key.to_key_string();
         self.connection
-            .ensure_subscription(&self.view_path, Some(key))
+            .ensure_subscription(&self.view_path, Some(&key))
             .await;
         self.store
             .wait_for_view_ready(&self.view_path, self.initial_data_timeout)
             .await;
-        self.store.get::<T>(&self.view_path, key).await
+        self.store.get::<T>(&self.view_path, &key).await
+    }
+
+    /// Like [`get`](Self::get), but fails with [`HyperStackError::Timeout`]
+    /// if the subscription isn't acknowledged or the view's initial data
+    /// doesn't arrive in time.
+    ///
+    /// ```ignore
+    /// let miner = hs.views.ore_miner.state()
+    ///     .get_with(key, GetOptions::timeout(Duration::from_secs(5)))
+    ///     .await?;
+    /// ```
+    pub async fn get_with(
+        &self,
+        key: impl EntityKey,
+        options: GetOptions,
+    ) -> Result<Option<T>, HyperStackError> {
+        let key = key.to_key_string();
+        self.connection
+            .ensure_subscription(&self.view_path, Some(&key))
+            .await;
+        wait_for_initial_data(
+            &self.connection,
+            &self.store,
+            &self.view_path,
+            self.initial_data_timeout,
+            options,
+        )
+        .await?;
+        Ok(self.store.get::<T>(&self.view_path, &key).await)
     }
 
     /// Synchronously get an entity from cached data.
-    pub fn get_sync(&self, key: &str) -> Option<T> {
-        self.store.get_sync::<T>(&self.view_path, key)
+    pub fn get_sync(&self, key: impl EntityKey) -> Option<T> {
+        self.store
+            .get_sync::<T>(&self.view_path, &key.to_key_string())
     }
 
     /// Stream merged entity values directly (simplest API - filters out deletes).
-    pub fn listen(&self, key: &str) -> UseStream<T>
+    pub fn listen(&self, key: impl EntityKey) -> UseStream<T>
     where
         T: Unpin,
     {
+        let key = key.to_key_string();
         UseStream::new_lazy(
             self.connection.clone(),
             self.store.clone(),
             self.view_path.clone(),
             self.view_path.clone(),
-            KeyFilter::Single(key.to_string()),
-            Some(key.to_string()),
+            KeyFilter::Single(key.clone()),
+            Some(key),
         )
     }
 
     /// Watch for updates to a specific key.
-    pub fn watch(&self, key: &str) -> EntityStream<T> {
+    pub fn watch(&self, key: impl EntityKey) -> EntityStream<T> {
+        let key = key.to_key_string();
         EntityStream::new_lazy(
             self.connection.clone(),
             self.store.clone(),
             self.view_path.clone(),
             self.view_path.clone(),
-            KeyFilter::Single(key.to_string()),
-            Some(key.to_string()),
+            KeyFilter::Single(key.clone()),
+            Some(key),
         )
     }
 
     /// Watch for updates with before/after diffs.
-    pub fn watch_rich(&self, key: &str) -> RichEntityStream<T> {
+    pub fn watch_rich(&self, key: impl EntityKey) -> RichEntityStream<T> {
+        let key = key.to_key_string();
         RichEntityStream::new_lazy(
             self.connection.clone(),
             self.store.clone(),
             self.view_path.clone(),
             self.view_path.clone(),
-            KeyFilter::Single(key.to_string()),
-            Some(key.to_string()),
+            KeyFilter::Single(key.clone()),
+            Some(key),
         )
     }
+
+    /// Overlay `patch` on the entity under `key` until the server confirms it.
+    ///
+    /// The overlay is settled by the next server frame for the key, or expires
+    /// after 30 seconds. See [`optimistic`](crate::optimistic) for details.
+    pub async fn optimistic(
+        &self,
+        key: impl EntityKey,
+        patch: serde_json::Value,
+    ) -> OptimisticGuard {
+        self.optimistic_with(key, patch, OptimisticOptions::default())
+            .await
+    }
+
+    /// Like [`optimistic`](Self::optimistic), with a slot threshold, timeout or
+    /// reconciliation callback.
+    pub async fn optimistic_with(
+        &self,
+        key: impl EntityKey,
+        patch: serde_json::Value,
+        options: OptimisticOptions,
+    ) -> OptimisticGuard {
+        let key = key.to_key_string();
+        self.connection
+            .ensure_subscription(&self.view_path, Some(&key))
+            .await;
+        self.store
+            .apply_optimistic(&self.view_path, &key, patch, options)
+            .await
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl<T> ViewHandle<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    /// Add `item` to the view as a new entity, as if the server sent it.
+    ///
+    /// Only meaningful on views of a [`MockHyperStack`](crate::MockHyperStack).
+    /// Use [`push_keyed`](Self::push_keyed) to update an entity in place.
+    pub async fn push(&self, item: T) {
+        let existing = self.store.all_raw(&self.view_path).await;
+        let key = (existing.len()..)
+            .map(|index| index.to_string())
+            .find(|key| !existing.contains_key(key))
+            .expect("an unused key exists");
+        self.push_keyed(key, item).await;
+    }
+
+    /// Upsert `item` under `key`, as if the server sent it.
+    pub async fn push_keyed(&self, key: impl EntityKey, item: T) {
+        apply_mock_upsert(&self.store, Mode::List, &self.view_path, key, &item).await;
+    }
+
+    /// Delete the entity under `key`, as if the server sent it.
+    pub async fn remove(&self, key: impl EntityKey) {
+        apply_mock_delete(&self.store, Mode::List, &self.view_path, key).await;
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl<T> StateView<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    /// Set the entity under `key`, as if the server sent it.
+    ///
+    /// Only meaningful on views of a [`MockHyperStack`](crate::MockHyperStack).
+    pub async fn set(&self, key: impl EntityKey, item: T) {
+        apply_mock_upsert(&self.store, Mode::State, &self.view_path, key, &item).await;
+    }
+
+    /// Delete the entity under `key`, as if the server sent it.
+    pub async fn remove(&self, key: impl EntityKey) {
+        apply_mock_delete(&self.store, Mode::State, &self.view_path, key).await;
+    }
+}
+
+#[cfg(feature = "test-util")]
+async fn apply_mock_upsert<T: Serialize>(
+    store: &SharedStore,
+    mode: Mode,
+    view_path: &str,
+    key: impl EntityKey,
+    item: &T,
+) {
+    let data = serde_json::to_value(item).expect("mock entity should serialize to json");
+    store
+        .apply_frame(mock_frame(mode, view_path, "upsert", key, data))
+        .await;
+}
+
+#[cfg(feature = "test-util")]
+async fn apply_mock_delete(store: &SharedStore, mode: Mode, view_path: &str, key: impl EntityKey) {
+    store
+        .apply_frame(mock_frame(
+            mode,
+            view_path,
+            "delete",
+            key,
+            serde_json::Value::Null,
+        ))
+        .await;
+}
+
+#[cfg(feature = "test-util")]
+fn mock_frame(
+    mode: Mode,
+    view_path: &str,
+    op: &str,
+    key: impl EntityKey,
+    data: serde_json::Value,
+) -> Frame {
+    Frame {
+        mode,
+        entity: view_path.to_string(),
+        op: op.to_string(),
+        key: key.to_key_string(),
+        data,
+        append: Vec::new(),
+        seq: None,
+        continuation: None,
+        complete: None,
+    }
 }
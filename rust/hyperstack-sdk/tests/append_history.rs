@@ -0,0 +1,120 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::{HyperStack, Stack, ViewBuilder, ViewHandle, Views};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+struct TestViews {
+    trades: ViewHandle<Value>,
+}
+
+impl Views for TestViews {
+    fn from_builder(builder: ViewBuilder) -> Self {
+        Self {
+            trades: builder.view("Trade/append"),
+        }
+    }
+}
+
+struct TestStack;
+
+impl Stack for TestStack {
+    type Views = TestViews;
+
+    fn name() -> &'static str {
+        "test-stack"
+    }
+
+    fn url() -> &'static str {
+        "ws://127.0.0.1:1"
+    }
+}
+
+/// Accepts one client and answers its subscribe with two history items
+/// followed by one live item. Returns the subscribe message it received.
+async fn spawn_append_server() -> (String, oneshot::Receiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (subscribe_tx, subscribe_rx) = oneshot::channel();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut write, mut read) = accept_async(stream).await.unwrap().split();
+
+        while let Some(Ok(message)) = read.next().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let payload: Value = serde_json::from_str(&text).unwrap();
+            if payload["type"] != "subscribe" {
+                continue;
+            }
+
+            let frames = [
+                json!({
+                    "mode": "append",
+                    "entity": "Trade/append",
+                    "op": "history",
+                    "data": [
+                        { "key": "t1", "data": { "slot": 1 }, "seq": "1:0" },
+                        { "key": "t2", "data": { "slot": 2 }, "seq": "2:0" },
+                    ],
+                }),
+                json!({
+                    "mode": "append",
+                    "entity": "Trade/append",
+                    "op": "patch",
+                    "key": "t3",
+                    "data": { "slot": 3 },
+                    "seq": "3:0",
+                }),
+            ];
+            for frame in frames {
+                let _ = write.send(Message::Text(frame.to_string())).await;
+            }
+            let _ = subscribe_tx.send(payload);
+            break;
+        }
+        std::future::pending::<()>().await;
+    });
+
+    (format!("ws://{addr}"), subscribe_rx)
+}
+
+#[tokio::test]
+async fn appends_with_history_yields_replayed_then_live_items() {
+    let (url, subscribe) = spawn_append_server().await;
+    let hs = HyperStack::<TestStack>::builder()
+        .url(&url)
+        .connect()
+        .await
+        .expect("client should connect");
+
+    let mut appends = hs.views.trades.appends().with_history(50);
+    let mut items = Vec::new();
+    for _ in 0..3 {
+        let item = timeout(Duration::from_secs(3), appends.next())
+            .await
+            .expect("item should arrive")
+            .expect("stream should stay open");
+        items.push((item.key, item.data["slot"].as_u64().unwrap(), item.history));
+    }
+
+    assert_eq!(
+        items,
+        vec![
+            ("t1".to_string(), 1, true),
+            ("t2".to_string(), 2, true),
+            ("t3".to_string(), 3, false),
+        ]
+    );
+
+    let subscribe = subscribe.await.unwrap();
+    assert_eq!(subscribe["view"], json!("Trade/append"));
+    assert_eq!(subscribe["history"], json!(50));
+    assert_eq!(subscribe["withSnapshot"], json!(false));
+
+    hs.disconnect().await;
+}
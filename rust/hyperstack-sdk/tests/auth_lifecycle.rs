@@ -224,6 +224,7 @@ async fn exposes_socket_issues_via_public_api() {
             code: Some(hyperstack_sdk::AuthErrorCode::SubscriptionLimitExceeded),
             retryable: false,
             retry_after: None,
+            retry_after_ms: None,
             suggested_action: Some("unsubscribe first".to_string()),
             docs_url: None,
             fatal: false,
@@ -0,0 +1,85 @@
+use hyperstack_sdk::{Frame, Mode, SharedStore};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const VIEW: &str = "Automation/list";
+const KEY: &str = "automation-1";
+
+fn frame(op: &str, data: Value) -> Frame {
+    Frame {
+        mode: Mode::List,
+        entity: VIEW.to_string(),
+        op: op.to_string(),
+        key: KEY.to_string(),
+        data,
+        append: Vec::new(),
+        seq: None,
+        continuation: None,
+        complete: None,
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Automation {
+    executor: Option<String>,
+    amount: u64,
+}
+
+#[tokio::test]
+async fn null_in_a_patch_removes_the_field() {
+    let store = SharedStore::new();
+    let mut updates = store.subscribe();
+
+    store
+        .apply_frame(frame(
+            "upsert",
+            json!({ "executor": "exec-a", "amount": 3, "status": "active" }),
+        ))
+        .await;
+    store
+        .apply_frame(frame("patch", json!({ "executor": null })))
+        .await;
+
+    // Fields left out of the patch are kept; the cleared one is gone
+    assert_eq!(
+        store.get::<Value>(VIEW, KEY).await,
+        Some(json!({ "amount": 3, "status": "active" }))
+    );
+    assert_eq!(
+        store.get::<Automation>(VIEW, KEY).await,
+        Some(Automation {
+            executor: None,
+            amount: 3,
+        })
+    );
+
+    updates.recv().await.unwrap();
+    let update = updates.recv().await.unwrap();
+    assert_eq!(update.patch, Some(json!({ "executor": null })));
+}
+
+#[tokio::test]
+async fn field_cleared_then_set_again_holds_the_new_value() {
+    let store = SharedStore::new();
+
+    store
+        .apply_frame(frame(
+            "upsert",
+            json!({ "executor": "exec-a", "amount": 3 }),
+        ))
+        .await;
+    store
+        .apply_frame(frame("patch", json!({ "executor": null })))
+        .await;
+    store
+        .apply_frame(frame("patch", json!({ "executor": "exec-b" })))
+        .await;
+
+    assert_eq!(
+        store.get::<Automation>(VIEW, KEY).await,
+        Some(Automation {
+            executor: Some("exec-b".to_string()),
+            amount: 3,
+        })
+    );
+}
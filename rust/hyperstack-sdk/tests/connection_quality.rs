@@ -0,0 +1,178 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::{
+    HyperStack, QualityChange, QualityPolicy, QualityTier, Stack, SubscriptionOverrides,
+    ViewBuilder, ViewHandle, Views,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+const SUB: &str = "Token/list:*";
+
+struct TestViews {
+    tokens: ViewHandle<Value>,
+}
+
+impl Views for TestViews {
+    fn from_builder(builder: ViewBuilder) -> Self {
+        Self {
+            tokens: builder.view("Token/list"),
+        }
+    }
+}
+
+struct TestStack;
+
+impl Stack for TestStack {
+    type Views = TestViews;
+
+    fn name() -> &'static str {
+        "test-stack"
+    }
+
+    fn url() -> &'static str {
+        "ws://127.0.0.1:1"
+    }
+}
+
+fn stamped(frame_seq: u64, resync: bool, mut frame: Value) -> Message {
+    frame["sub"] = json!(SUB);
+    frame["frameSeq"] = json!(frame_seq);
+    if resync {
+        frame["resync"] = json!(true);
+    }
+    Message::Binary(frame.to_string().into_bytes())
+}
+
+fn subscribed(resync: bool) -> Message {
+    stamped(
+        1,
+        resync,
+        json!({ "op": "subscribed", "view": "Token/list", "mode": "list" }),
+    )
+}
+
+fn upsert(frame_seq: u64, id: &str) -> Message {
+    stamped(
+        frame_seq,
+        false,
+        json!({
+            "mode": "list",
+            "entity": "Token/list",
+            "op": "upsert",
+            "key": id,
+            "data": { "id": id },
+        }),
+    )
+}
+
+/// Accepts one client and keeps dropping frames from its list subscription,
+/// every resync included, until the client subscribes with a `take`. From
+/// then on frames arrive intact. Every subscribe and unsubscribe received is
+/// forwarded.
+async fn spawn_lossy_server() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (command_tx, command_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut write, mut read) = accept_async(stream).await.unwrap().split();
+        let mut lossy = true;
+
+        while let Some(Ok(message)) = read.next().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let payload: Value = serde_json::from_str(&text).unwrap();
+            let resync = match payload["type"].as_str() {
+                Some("subscribe") => {
+                    lossy &= payload.get("take").is_none();
+                    let _ = command_tx.send(payload);
+                    false
+                }
+                Some("unsubscribe") => {
+                    let _ = command_tx.send(payload);
+                    continue;
+                }
+                Some("resync") => true,
+                _ => continue,
+            };
+
+            let mut frames = vec![subscribed(resync), upsert(2, "a")];
+            if lossy {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                // Frame 3 was lost
+                frames.push(upsert(4, "b"));
+            }
+            for frame in frames {
+                let _ = write.send(frame).await;
+            }
+        }
+    });
+
+    (format!("ws://{addr}"), command_rx)
+}
+
+async fn next_change(changes: &mut broadcast::Receiver<QualityChange>) -> QualityChange {
+    timeout(Duration::from_secs(3), changes.recv())
+        .await
+        .expect("the tier should change")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn list_subscription_is_renegotiated_once_per_tier_change() {
+    let (url, mut commands) = spawn_lossy_server().await;
+    let policy = QualityPolicy::new()
+        .gap_thresholds(1, u64::MAX)
+        .hold_samples(2)
+        .on(QualityTier::Degraded, SubscriptionOverrides::new().take(10));
+    let hs = HyperStack::<TestStack>::builder()
+        .url(&url)
+        .ping_interval(Duration::from_millis(50))
+        .quality_policy(policy)
+        .connect()
+        .await
+        .expect("client should connect");
+    let mut changes = hs.subscribe_quality_changes();
+
+    let mut tokens = hs.views.tokens.listen();
+    tokio::spawn(async move { while tokens.next().await.is_some() {} });
+
+    let degraded = next_change(&mut changes).await;
+    assert_eq!(
+        (degraded.from, degraded.to),
+        (QualityTier::Good, QualityTier::Degraded)
+    );
+    assert!(degraded.quality.recent_gaps >= 1);
+    let recovered = next_change(&mut changes).await;
+    assert_eq!(
+        (recovered.from, recovered.to),
+        (QualityTier::Degraded, QualityTier::Good)
+    );
+
+    // Settled: no further tier changes or renegotiations
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(changes.try_recv().is_err(), "expected exactly two changes");
+
+    let mut received = Vec::new();
+    while let Ok(command) = commands.try_recv() {
+        received.push(command);
+    }
+    assert_eq!(
+        received,
+        vec![
+            json!({ "type": "subscribe", "view": "Token/list" }),
+            json!({ "type": "unsubscribe", "view": "Token/list" }),
+            json!({ "type": "subscribe", "view": "Token/list", "take": 10 }),
+            json!({ "type": "unsubscribe", "view": "Token/list" }),
+            json!({ "type": "subscribe", "view": "Token/list" }),
+        ]
+    );
+    assert_eq!(hs.connection_quality().await.tier, QualityTier::Good);
+
+    hs.disconnect().await;
+}
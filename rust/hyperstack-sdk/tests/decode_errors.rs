@@ -0,0 +1,116 @@
+use hyperstack_sdk::{DecodeMode, Frame, Mode, Operation, SharedStore, StoreConfig};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const VIEW: &str = "Token/list";
+const BAD: &str = "token-17";
+
+#[derive(Debug, Clone, Deserialize)]
+struct Token {
+    name: String,
+    supply: u64,
+}
+
+fn snapshot(entities: Vec<Value>) -> Frame {
+    Frame {
+        mode: Mode::List,
+        entity: VIEW.to_string(),
+        op: "snapshot".to_string(),
+        key: String::new(),
+        data: json!(entities),
+        append: Vec::new(),
+        seq: None,
+        continuation: None,
+        complete: None,
+    }
+}
+
+/// Fifty tokens, one of them with a supply that isn't a number
+fn fixture() -> Frame {
+    let entities = (0..50)
+        .map(|i| {
+            let key = format!("token-{i}");
+            let supply = if key == BAD { json!("lots") } else { json!(i) };
+            json!({ "key": key, "data": { "name": key, "supply": supply } })
+        })
+        .collect();
+    snapshot(entities)
+}
+
+fn store(decode_mode: DecodeMode) -> SharedStore {
+    let store = SharedStore::with_config(StoreConfig {
+        decode_mode,
+        ..Default::default()
+    });
+    store.decode_as::<Token>(VIEW);
+    store
+}
+
+#[tokio::test]
+async fn undecodable_entity_is_skipped_and_reported() {
+    let store = store(DecodeMode::Lenient);
+    let mut errors = store.decode_errors();
+
+    store.apply_frame(fixture()).await;
+
+    let tokens = store.list::<Token>(VIEW).await;
+    assert_eq!(tokens.len(), 49);
+    assert!(tokens.iter().all(|token| token.name != BAD));
+    assert_eq!(
+        tokens.iter().map(|token| token.supply).sum::<u64>(),
+        1225 - 17
+    );
+    assert!(!store.all_raw(VIEW).await.contains_key(BAD));
+    assert_eq!(store.decode_failures(VIEW).await, 1);
+    assert!(store.rejected_snapshot(VIEW).await.is_none());
+
+    let error = errors.try_recv().unwrap();
+    assert_eq!(error.view, VIEW);
+    assert_eq!(error.key, BAD);
+    assert!(error.error.to_string().contains("invalid type"), "{error}");
+    assert_eq!(error.raw["supply"], json!("lots"));
+    assert!(errors.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn entity_that_stops_decoding_is_removed() {
+    let store = store(DecodeMode::Lenient);
+    store
+        .apply_frame(snapshot(vec![
+            json!({ "key": BAD, "data": { "name": BAD, "supply": 1 } }),
+        ]))
+        .await;
+    assert_eq!(store.list::<Token>(VIEW).await.len(), 1);
+    let mut updates = store.subscribe();
+
+    store.apply_frame(fixture()).await;
+
+    assert!(!store.all_raw(VIEW).await.contains_key(BAD));
+    let deleted = std::iter::from_fn(|| updates.try_recv().ok())
+        .find(|update| update.key == BAD)
+        .expect("the entity should be deleted");
+    assert_eq!(deleted.operation, Operation::Delete);
+}
+
+#[tokio::test]
+async fn strict_mode_rejects_the_whole_snapshot() {
+    let store = store(DecodeMode::Strict);
+    let mut errors = store.decode_errors();
+
+    store.apply_frame(fixture()).await;
+
+    assert!(store.all_raw(VIEW).await.is_empty());
+    assert_eq!(store.decode_failures(VIEW).await, 1);
+    let rejected = store.rejected_snapshot(VIEW).await.unwrap();
+    assert_eq!(rejected.key, BAD);
+    assert_eq!(errors.try_recv().unwrap().key, BAD);
+
+    // A snapshot that decodes whole is applied and clears the failure
+    store
+        .apply_frame(snapshot(vec![
+            json!({ "key": "token-1", "data": { "name": "token-1", "supply": 1 } }),
+        ]))
+        .await;
+    assert_eq!(store.list::<Token>(VIEW).await.len(), 1);
+    assert!(store.rejected_snapshot(VIEW).await.is_none());
+}
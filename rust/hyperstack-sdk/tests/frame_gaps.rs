@@ -0,0 +1,154 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::{HyperStack, Stack, ViewBuilder, ViewHandle, Views};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+const SUB: &str = "Token/list:*";
+
+struct TestViews {
+    tokens: ViewHandle<Value>,
+}
+
+impl Views for TestViews {
+    fn from_builder(builder: ViewBuilder) -> Self {
+        Self {
+            tokens: builder.view("Token/list"),
+        }
+    }
+}
+
+struct TestStack;
+
+impl Stack for TestStack {
+    type Views = TestViews;
+
+    fn name() -> &'static str {
+        "test-stack"
+    }
+
+    fn url() -> &'static str {
+        "ws://127.0.0.1:1"
+    }
+}
+
+fn stamped(frame_seq: u64, resync: bool, mut frame: Value) -> Message {
+    frame["sub"] = json!(SUB);
+    frame["frameSeq"] = json!(frame_seq);
+    if resync {
+        frame["resync"] = json!(true);
+    }
+    Message::Binary(frame.to_string().into_bytes())
+}
+
+fn subscribed(frame_seq: u64, resync: bool) -> Message {
+    stamped(
+        frame_seq,
+        resync,
+        json!({ "op": "subscribed", "view": "Token/list", "mode": "list" }),
+    )
+}
+
+fn upsert(frame_seq: u64, id: &str) -> Message {
+    stamped(
+        frame_seq,
+        false,
+        json!({
+            "mode": "list",
+            "entity": "Token/list",
+            "op": "upsert",
+            "key": id,
+            "data": { "id": id },
+        }),
+    )
+}
+
+/// Accepts one client and streams a list subscription whose fourth frame
+/// goes missing. Frames keep coming until the client asks for a resync, which
+/// is answered with a restarted sequence ending in a `done` entity. Every
+/// resync request received is forwarded.
+async fn spawn_gappy_server() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (resync_tx, resync_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut write, mut read) = accept_async(stream).await.unwrap().split();
+        let mut resynced = false;
+
+        while let Some(Ok(message)) = read.next().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let payload: Value = serde_json::from_str(&text).unwrap();
+            match payload["type"].as_str() {
+                Some("subscribe") => {
+                    let frames = [
+                        subscribed(1, false),
+                        upsert(2, "a"),
+                        upsert(3, "b"),
+                        // Frame 4 was lost
+                        upsert(5, "c"),
+                        upsert(6, "d"),
+                        upsert(8, "e"),
+                    ];
+                    for frame in frames {
+                        let _ = write.send(frame).await;
+                    }
+                }
+                Some("resync") => {
+                    let _ = resync_tx.send(payload);
+                    if !resynced {
+                        resynced = true;
+                        let frames = [
+                            subscribed(1, true),
+                            upsert(2, "a"),
+                            upsert(3, "c"),
+                            upsert(4, "done"),
+                        ];
+                        for frame in frames {
+                            let _ = write.send(frame).await;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+
+    (format!("ws://{addr}"), resync_rx)
+}
+
+#[tokio::test]
+async fn gap_in_frame_sequence_requests_one_resync() {
+    let (url, mut resyncs) = spawn_gappy_server().await;
+    let hs = HyperStack::<TestStack>::builder()
+        .url(&url)
+        .connect()
+        .await
+        .expect("client should connect");
+
+    let mut tokens = hs.views.tokens.listen();
+    timeout(Duration::from_secs(3), async {
+        while let Some(token) = tokens.next().await {
+            if token["id"] == "done" {
+                break;
+            }
+        }
+    })
+    .await
+    .expect("the resynced sequence should arrive");
+
+    let resync = resyncs.recv().await.unwrap();
+    assert_eq!(resync, json!({ "type": "resync", "view": "Token/list" }));
+
+    // Neither the frames after the gap nor the restarted sequence ask again
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(resyncs.try_recv().is_err(), "expected exactly one resync");
+    assert_eq!(hs.frame_gaps(), 1);
+
+    hs.disconnect().await;
+}
@@ -0,0 +1,88 @@
+use hyperstack_sdk::{Continuation, Frame, FrameTooLarge, Mode, SharedStore};
+use serde_json::{json, Value};
+
+const VIEW: &str = "Pool/list";
+const KEY: &str = "pool-1";
+
+fn frame(op: &str, data: Value, continuation: Option<Continuation>) -> Frame {
+    Frame {
+        mode: Mode::List,
+        entity: VIEW.to_string(),
+        op: op.to_string(),
+        key: KEY.to_string(),
+        data,
+        append: Vec::new(),
+        seq: None,
+        continuation,
+        complete: None,
+    }
+}
+
+fn part(part: u32, data: Value) -> Frame {
+    frame("patch", data, Some(Continuation { part, parts: 3 }))
+}
+
+#[tokio::test]
+async fn split_patch_is_applied_once_every_part_arrives() {
+    let store = SharedStore::new();
+    let mut updates = store.subscribe();
+
+    store.apply_frame(part(1, json!({ "name": "pool" }))).await;
+    store
+        .apply_frame(part(2, json!({ "capture_a": "a" })))
+        .await;
+    assert!(store.get::<Value>(VIEW, KEY).await.is_none());
+    assert!(updates.try_recv().is_err());
+
+    store
+        .apply_frame(part(3, json!({ "capture_b": "b" })))
+        .await;
+    assert_eq!(
+        store.get::<Value>(VIEW, KEY).await,
+        Some(json!({ "name": "pool", "capture_a": "a", "capture_b": "b" }))
+    );
+    assert!(updates.try_recv().is_ok());
+    assert!(updates.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn out_of_order_part_drops_the_partial_frame() {
+    let store = SharedStore::new();
+
+    store.apply_frame(part(1, json!({ "name": "pool" }))).await;
+    store
+        .apply_frame(part(3, json!({ "capture_b": "b" })))
+        .await;
+    store
+        .apply_frame(part(2, json!({ "capture_a": "a" })))
+        .await;
+
+    assert!(store.get::<Value>(VIEW, KEY).await.is_none());
+}
+
+#[tokio::test]
+async fn frame_too_large_is_recorded_until_the_entity_arrives() {
+    let store = SharedStore::new();
+
+    store
+        .apply_frame(frame(
+            "frame_too_large",
+            json!({ "bytes": 10_120, "max_bytes": 4096 }),
+            None,
+        ))
+        .await;
+    assert_eq!(
+        store.oversized(VIEW).await,
+        vec![FrameTooLarge {
+            key: KEY.to_string(),
+            bytes: 10_120,
+            max_bytes: 4096,
+        }]
+    );
+    assert!(store.get::<Value>(VIEW, KEY).await.is_none());
+
+    store
+        .apply_frame(frame("patch", json!({ "name": "pool" }), None))
+        .await;
+    assert!(store.oversized(VIEW).await.is_empty());
+}
@@ -0,0 +1,156 @@
+//! Merged streams: updates from two views come out in slot order, a view
+//! that starts over is marked in the stream, and a full reorder buffer
+//! releases its earliest update.
+
+use futures_util::StreamExt;
+use hyperstack_sdk::{Frame, MergedStream, MergedUpdate, Mode, SharedStore, Update};
+use serde_json::{json, Value};
+use tokio::time::{timeout, Duration};
+
+const TRADES: &str = "Trade/list";
+const QUOTES: &str = "Quote/list";
+
+#[derive(Debug)]
+enum Market {
+    Trade(Update<Value>),
+    Quote(Update<Value>),
+}
+
+fn upsert(view: &str, key: &str, slot: u64) -> Frame {
+    Frame {
+        mode: Mode::List,
+        entity: view.to_string(),
+        op: "upsert".to_string(),
+        key: key.to_string(),
+        data: json!({ "id": key }),
+        append: Vec::new(),
+        seq: Some(format!("{slot}:000000000000")),
+        continuation: None,
+        complete: None,
+    }
+}
+
+fn subscribed(view: &str) -> Frame {
+    Frame {
+        mode: Mode::List,
+        entity: view.to_string(),
+        op: "subscribed".to_string(),
+        key: String::new(),
+        data: json!({ "op": "subscribed", "view": view, "mode": "list" }),
+        append: Vec::new(),
+        seq: None,
+        continuation: None,
+        complete: None,
+    }
+}
+
+fn merged(store: &SharedStore) -> MergedStream<Market> {
+    MergedStream::new(store.subscribe())
+        .source(TRADES, Market::Trade)
+        .source(QUOTES, Market::Quote)
+}
+
+async fn next(stream: &mut MergedStream<Market>) -> MergedUpdate<Market> {
+    timeout(Duration::from_secs(5), stream.next())
+        .await
+        .expect("an update should be released")
+        .expect("stream is open")
+}
+
+/// `(view, slot)` of an update, `(view, None)` for a resync marker
+fn position(update: &MergedUpdate<Market>) -> (String, Option<u64>) {
+    match update {
+        MergedUpdate::Update { view, slot, .. } => (view.clone(), *slot),
+        MergedUpdate::Resync { view } => (view.clone(), None),
+    }
+}
+
+#[tokio::test]
+async fn updates_from_two_views_come_out_in_slot_order() {
+    let store = SharedStore::new();
+    let mut stream = merged(&store);
+
+    for frame in [
+        upsert(TRADES, "t1", 10),
+        upsert(QUOTES, "q1", 8),
+        upsert(TRADES, "t2", 12),
+        upsert("Other/list", "o1", 9),
+        upsert(QUOTES, "q2", 11),
+    ] {
+        store.apply_frame(frame).await;
+    }
+
+    let mut released = Vec::new();
+    for _ in 0..4 {
+        released.push(position(&next(&mut stream).await));
+    }
+    assert_eq!(
+        released,
+        vec![
+            (QUOTES.to_string(), Some(8)),
+            (TRADES.to_string(), Some(10)),
+            (QUOTES.to_string(), Some(11)),
+            (TRADES.to_string(), Some(12)),
+        ]
+    );
+
+    // Items are mapped by their view's source
+    store.apply_frame(upsert(QUOTES, "q3", 13)).await;
+    store.apply_frame(upsert(TRADES, "t3", 14)).await;
+    match next(&mut stream).await.into_item() {
+        Some(Market::Quote(Update::Upsert { key, data })) => {
+            assert_eq!(key, "q3");
+            assert_eq!(data, json!({ "id": "q3" }));
+        }
+        other => panic!("expected the quote upsert, got {other:?}"),
+    }
+    match next(&mut stream).await.into_item() {
+        Some(Market::Trade(update)) => assert_eq!(update.key(), "t3"),
+        other => panic!("expected the trade upsert, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn a_resync_releases_earlier_updates_before_its_marker() {
+    let store = SharedStore::new();
+    store.apply_frame(subscribed(TRADES)).await;
+    let mut stream = merged(&store);
+
+    store.apply_frame(upsert(TRADES, "t1", 20)).await;
+    store.apply_frame(upsert(QUOTES, "q1", 15)).await;
+    // The second ack starts the view over, with a snapshot of older slots
+    store.apply_frame(subscribed(TRADES)).await;
+    store.apply_frame(upsert(TRADES, "t1", 5)).await;
+
+    let mut released = Vec::new();
+    for _ in 0..4 {
+        released.push(position(&next(&mut stream).await));
+    }
+    assert_eq!(
+        released,
+        vec![
+            (QUOTES.to_string(), Some(15)),
+            (TRADES.to_string(), Some(20)),
+            (TRADES.to_string(), None),
+            (TRADES.to_string(), Some(5)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn a_full_buffer_releases_its_earliest_update_early() {
+    let store = SharedStore::new();
+    let mut stream = merged(&store)
+        .reorder_window(Duration::from_secs(3600))
+        .max_buffered(2);
+
+    store.apply_frame(upsert(TRADES, "t1", 30)).await;
+    store.apply_frame(upsert(QUOTES, "q1", 31)).await;
+    store.apply_frame(upsert(QUOTES, "q2", 29)).await;
+
+    let first = timeout(Duration::from_secs(1), stream.next())
+        .await
+        .expect("a full buffer should not wait out the window")
+        .unwrap();
+    assert_eq!(position(&first), (TRADES.to_string(), Some(30)));
+}
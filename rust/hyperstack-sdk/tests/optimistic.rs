@@ -0,0 +1,170 @@
+use hyperstack_sdk::{Frame, Mode, OptimisticOptions, Reconciliation, SharedStore};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tokio::time::{timeout, Duration};
+
+const VIEW: &str = "Position/state";
+const KEY: &str = "position-1";
+
+fn patch_frame(slot: u64, data: Value) -> Frame {
+    Frame {
+        mode: Mode::State,
+        entity: VIEW.to_string(),
+        op: "patch".to_string(),
+        key: KEY.to_string(),
+        data,
+        append: Vec::new(),
+        seq: Some(format!("{slot}:000000000000")),
+        continuation: None,
+        complete: None,
+    }
+}
+
+async fn position(store: &SharedStore) -> Value {
+    store
+        .get::<Value>(VIEW, KEY)
+        .await
+        .expect("position exists")
+}
+
+async fn store_with_position() -> SharedStore {
+    let store = SharedStore::new();
+    store
+        .apply_frame(patch_frame(100, json!({ "size": 1, "side": "long" })))
+        .await;
+    store
+}
+
+#[tokio::test]
+async fn confirmed_overlay_is_replaced_by_the_server_value() {
+    let store = store_with_position().await;
+    let mut updates = store.subscribe();
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let recorded = results.clone();
+
+    let guard = store
+        .apply_optimistic(
+            VIEW,
+            KEY,
+            json!({ "size": 5 }),
+            OptimisticOptions::new()
+                .after_slot(110)
+                .on_reconcile(move |result| recorded.lock().unwrap().push(result)),
+        )
+        .await;
+
+    let overlaid = updates.recv().await.unwrap();
+    assert_eq!(overlaid.data.as_ref().unwrap()["size"], json!(5));
+    assert_eq!(overlaid.data.as_ref().unwrap()["optimistic"], json!(true));
+    assert_eq!(position(&store).await["size"], json!(5));
+    assert_eq!(position(&store).await["side"], json!("long"));
+
+    // Frames up to the threshold predate the transaction
+    store
+        .apply_frame(patch_frame(110, json!({ "side": "long" })))
+        .await;
+    let stale = updates.recv().await.unwrap();
+    assert_eq!(stale.data.as_ref().unwrap()["optimistic"], json!(true));
+    assert!(results.lock().unwrap().is_empty());
+
+    store
+        .apply_frame(patch_frame(111, json!({ "size": 5 })))
+        .await;
+    let confirmed = updates.recv().await.unwrap();
+    assert_eq!(confirmed.data.as_ref().unwrap()["size"], json!(5));
+    assert!(confirmed.data.as_ref().unwrap().get("optimistic").is_none());
+
+    assert_eq!(guard.reconciled().await, Reconciliation::Confirmed);
+    assert_eq!(*results.lock().unwrap(), vec![Reconciliation::Confirmed]);
+    assert!(position(&store).await.get("optimistic").is_none());
+}
+
+#[tokio::test]
+async fn conflicting_server_data_clears_the_overlay() {
+    let store = store_with_position().await;
+    let guard = store
+        .apply_optimistic(
+            VIEW,
+            KEY,
+            json!({ "size": 5 }),
+            OptimisticOptions::new().after_slot(110),
+        )
+        .await;
+
+    store
+        .apply_frame(patch_frame(112, json!({ "size": 3 })))
+        .await;
+
+    match guard.reconciled().await {
+        Reconciliation::Conflict { server } => {
+            assert_eq!(server.unwrap()["size"], json!(3));
+        }
+        other => panic!("expected a conflict, got {other:?}"),
+    }
+    assert_eq!(position(&store).await["size"], json!(3));
+    assert!(position(&store).await.get("optimistic").is_none());
+}
+
+#[tokio::test]
+async fn overlay_expires_without_a_settling_frame() {
+    let store = store_with_position().await;
+    let guard = store
+        .apply_optimistic(
+            VIEW,
+            KEY,
+            json!({ "size": 5 }),
+            OptimisticOptions::new()
+                .after_slot(110)
+                .timeout(Duration::from_millis(50)),
+        )
+        .await;
+
+    let result = timeout(Duration::from_secs(2), guard.reconciled())
+        .await
+        .expect("overlay should expire");
+    assert_eq!(result, Reconciliation::Expired);
+    assert_eq!(position(&store).await["size"], json!(1));
+    assert!(position(&store).await.get("optimistic").is_none());
+}
+
+#[tokio::test]
+async fn overlays_on_one_key_apply_in_creation_order() {
+    let store = store_with_position().await;
+    let first = store
+        .apply_optimistic(
+            VIEW,
+            KEY,
+            json!({ "size": 5, "side": "short" }),
+            OptimisticOptions::new().after_slot(110),
+        )
+        .await;
+    let second = store
+        .apply_optimistic(
+            VIEW,
+            KEY,
+            json!({ "size": 7 }),
+            OptimisticOptions::new().after_slot(120),
+        )
+        .await;
+
+    let stacked = position(&store).await;
+    assert_eq!(stacked["size"], json!(7));
+    assert_eq!(stacked["side"], json!("short"));
+
+    first.cancel().await;
+    let remaining = position(&store).await;
+    assert_eq!(remaining["size"], json!(7));
+    assert_eq!(remaining["side"], json!("long"));
+    assert_eq!(remaining["optimistic"], json!(true));
+
+    // Settles the second overlay only once the server passes its threshold
+    store
+        .apply_frame(patch_frame(115, json!({ "size": 6 })))
+        .await;
+    assert_eq!(position(&store).await["size"], json!(7));
+    store
+        .apply_frame(patch_frame(121, json!({ "size": 7 })))
+        .await;
+    assert_eq!(second.reconciled().await, Reconciliation::Confirmed);
+    assert!(position(&store).await.get("optimistic").is_none());
+}
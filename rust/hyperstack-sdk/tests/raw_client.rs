@@ -0,0 +1,225 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::raw::{RawClient, RawEvent, SubscriptionId};
+use hyperstack_sdk::{ConnectionConfig, ConnectionManager, Frame, Subscription};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+const SUB: &str = "Token/list:*";
+
+fn stamped(frame_seq: u64, resync: bool, mut frame: Value) -> Message {
+    frame["sub"] = json!(SUB);
+    frame["frameSeq"] = json!(frame_seq);
+    if resync {
+        frame["resync"] = json!(true);
+    }
+    Message::Binary(frame.to_string().into_bytes())
+}
+
+fn subscribed(frame_seq: u64, resync: bool) -> Message {
+    stamped(
+        frame_seq,
+        resync,
+        json!({ "op": "subscribed", "view": "Token/list", "mode": "list" }),
+    )
+}
+
+fn upsert(frame_seq: u64, id: &str) -> Message {
+    stamped(
+        frame_seq,
+        false,
+        json!({
+            "mode": "list",
+            "entity": "Token/list",
+            "op": "upsert",
+            "key": id,
+            "data": { "id": id },
+        }),
+    )
+}
+
+/// Accepts one client and streams a list subscription whose fourth frame
+/// goes missing, answering the first resync with a restarted sequence that
+/// ends in a `done` entity. Every message the client sends, other than
+/// keepalives, is forwarded.
+async fn spawn_gappy_server() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (received_tx, received_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut write, mut read) = accept_async(stream).await.unwrap().split();
+        let mut resynced = false;
+
+        while let Some(Ok(message)) = read.next().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let payload: Value = serde_json::from_str(&text).unwrap();
+            let frames = match payload["type"].as_str() {
+                Some("subscribe") => vec![
+                    subscribed(1, false),
+                    upsert(2, "a"),
+                    upsert(3, "b"),
+                    // Frame 4 was lost
+                    upsert(5, "c"),
+                    upsert(6, "d"),
+                ],
+                Some("resync") if !resynced => {
+                    resynced = true;
+                    vec![subscribed(1, true), upsert(2, "a"), upsert(3, "done")]
+                }
+                Some("ping") => continue,
+                _ => Vec::new(),
+            };
+            let _ = received_tx.send(payload);
+            for frame in frames {
+                let _ = write.send(frame).await;
+            }
+        }
+    });
+
+    (format!("ws://{addr}"), received_rx)
+}
+
+fn summary(frame: &Frame) -> (String, String) {
+    (frame.op.clone(), frame.key.clone())
+}
+
+/// Frames up to and including the `done` entity, resyncing on every gap
+async fn read_raw(client: &mut RawClient) -> (Vec<(String, String)>, Vec<RawEvent>) {
+    let mut frames = Vec::new();
+    let mut gaps = Vec::new();
+    while let Some(event) = client.next().await {
+        match event {
+            RawEvent::Frame { frame, .. } => {
+                frames.push(summary(&frame));
+                if frame.key == "done" {
+                    break;
+                }
+            }
+            RawEvent::Gap { ref sub, .. } => {
+                client.send_resync(sub).await.unwrap();
+                gaps.push(event);
+            }
+            _ => {}
+        }
+    }
+    (frames, gaps)
+}
+
+fn drain(received: &mut mpsc::UnboundedReceiver<Value>) -> Vec<Value> {
+    std::iter::from_fn(|| received.try_recv().ok()).collect()
+}
+
+#[tokio::test]
+async fn raw_client_reports_gaps_and_resync_markers() {
+    let (url, mut received) = spawn_gappy_server().await;
+    let mut client = RawClient::connect(url.as_str()).await.unwrap();
+
+    let id = client
+        .send_subscribe(&Subscription::new("Token/list"))
+        .await
+        .unwrap();
+    assert_eq!(id, SubscriptionId::new("Token/list", None));
+    assert_eq!(id.as_str(), SUB);
+
+    let mut events = Vec::new();
+    while let Some(event) = timeout(Duration::from_secs(3), client.next())
+        .await
+        .expect("events should arrive")
+    {
+        let done = matches!(&event, RawEvent::Frame { frame, .. } if frame.key == "done");
+        if let RawEvent::Gap { sub, .. } = &event {
+            client.send_resync(sub).await.unwrap();
+        }
+        events.push(event);
+        if done {
+            break;
+        }
+    }
+
+    // The gap is reported once, right before the frame that revealed it
+    let gap = events
+        .iter()
+        .position(|event| matches!(event, RawEvent::Gap { .. }))
+        .unwrap();
+    assert!(matches!(
+        &events[gap],
+        RawEvent::Gap { sub, expected: 4, received: 5 } if sub == &id
+    ));
+    assert!(matches!(
+        &events[gap + 1],
+        RawEvent::Frame { sequence: Some(sequence), .. } if sequence.frame_seq == 5
+    ));
+    let gaps = events
+        .iter()
+        .filter(|event| matches!(event, RawEvent::Gap { .. }))
+        .count();
+    assert_eq!(gaps, 1);
+
+    // The restarted sequence opens with a resync-marked ack
+    let resync = events
+        .iter()
+        .find_map(|event| match event {
+            RawEvent::Frame {
+                frame,
+                sequence: Some(sequence),
+            } if sequence.resync => Some((frame.op.clone(), sequence.frame_seq)),
+            _ => None,
+        })
+        .expect("the resync should restart the sequence");
+    assert_eq!(resync, ("subscribed".to_string(), 1));
+
+    assert_eq!(
+        drain(&mut received),
+        vec![
+            json!({ "type": "subscribe", "view": "Token/list" }),
+            json!({ "type": "resync", "view": "Token/list" }),
+        ]
+    );
+
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn typed_connection_speaks_the_raw_protocol() {
+    let (raw_url, mut raw_received) = spawn_gappy_server().await;
+    let mut client = RawClient::connect(raw_url.as_str()).await.unwrap();
+    client
+        .send_subscribe(&Subscription::new("Token/list"))
+        .await
+        .unwrap();
+    let (raw_frames, raw_gaps) = timeout(Duration::from_secs(3), read_raw(&mut client))
+        .await
+        .expect("raw frames should arrive");
+    assert_eq!(raw_gaps.len(), 1);
+
+    let (typed_url, mut typed_received) = spawn_gappy_server().await;
+    let (frame_tx, mut frame_rx) = mpsc::channel(100);
+    let manager = ConnectionManager::new(typed_url, ConnectionConfig::default(), frame_tx)
+        .await
+        .unwrap();
+    manager.subscribe(Subscription::new("Token/list")).await;
+    let typed_frames = timeout(Duration::from_secs(3), async {
+        let mut frames = Vec::new();
+        while let Some(frame) = frame_rx.recv().await {
+            frames.push(summary(&frame));
+            if frame.key == "done" {
+                break;
+            }
+        }
+        frames
+    })
+    .await
+    .expect("typed frames should arrive");
+
+    assert_eq!(typed_frames, raw_frames);
+    assert_eq!(manager.frame_gaps(), 1);
+    assert_eq!(drain(&mut typed_received), drain(&mut raw_received));
+
+    manager.disconnect().await;
+}
@@ -0,0 +1,198 @@
+use futures_util::SinkExt;
+use hyperstack_sdk::{HyperStack, Stack, ViewBuilder, Views};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration, Instant};
+use tokio_tungstenite::{
+    accept_async, accept_hdr_async,
+    tungstenite::{
+        handshake::server::{Callback, ErrorResponse, Request, Response},
+        http::StatusCode,
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    },
+};
+
+#[derive(Clone)]
+struct TestViews;
+
+impl Views for TestViews {
+    fn from_builder(_: ViewBuilder) -> Self {
+        Self
+    }
+}
+
+struct TestStack;
+
+impl Stack for TestStack {
+    type Views = TestViews;
+
+    fn name() -> &'static str {
+        "test-stack"
+    }
+
+    fn url() -> &'static str {
+        "ws://127.0.0.1:1"
+    }
+}
+
+/// How the test server treats the first connection attempt.
+#[derive(Clone, Copy)]
+enum FirstAttempt {
+    /// Accept, then close with a JSON reason carrying `retry_after_ms`.
+    CloseWithHint(u64),
+    /// Refuse the handshake with a 503 whose body carries `retry_after_ms`.
+    RejectWithHint(u64),
+}
+
+/// Refuses the handshake with a 503 whose body carries `retry_after_ms`.
+struct Overloaded {
+    retry_after_ms: u64,
+}
+
+impl Callback for Overloaded {
+    fn on_request(self, _: &Request, _: Response) -> Result<Response, ErrorResponse> {
+        let body = format!(
+            r#"{{"error":"overloaded","message":"Too many clients","code":"overloaded","retryable":true,"retry_after_ms":{}}}"#,
+            self.retry_after_ms
+        );
+        let mut response = ErrorResponse::new(Some(body));
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        Err(response)
+    }
+}
+
+struct HintServer {
+    url: String,
+    /// When the server finished turning away the first attempt
+    turned_away_at: Arc<Mutex<Option<Instant>>>,
+    /// When the second attempt arrived
+    reconnected_at: Arc<Mutex<Option<Instant>>>,
+    join_handle: JoinHandle<()>,
+}
+
+impl HintServer {
+    async fn wait_for_reconnect(&self) -> Duration {
+        timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(reconnected_at) = *self.reconnected_at.lock().unwrap() {
+                    let turned_away_at = self.turned_away_at.lock().unwrap().unwrap();
+                    return reconnected_at - turned_away_at;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("client should reconnect")
+    }
+}
+
+async fn spawn_hint_server(first: FirstAttempt) -> HintServer {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let turned_away_at = Arc::new(Mutex::new(None));
+    let reconnected_at = Arc::new(Mutex::new(None));
+
+    let join_handle = tokio::spawn({
+        let turned_away_at = turned_away_at.clone();
+        let reconnected_at = reconnected_at.clone();
+        async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            match first {
+                FirstAttempt::CloseWithHint(retry_after_ms) => {
+                    let mut ws = accept_async(stream).await.unwrap();
+                    let reason =
+                        format!(r#"{{"code":"backpressure","retry_after_ms":{retry_after_ms}}}"#);
+                    ws.send(Message::Close(Some(CloseFrame {
+                        code: CloseCode::Again,
+                        reason: reason.into(),
+                    })))
+                    .await
+                    .unwrap();
+                }
+                FirstAttempt::RejectWithHint(retry_after_ms) => {
+                    let _ = accept_hdr_async(stream, Overloaded { retry_after_ms }).await;
+                }
+            }
+            *turned_away_at.lock().unwrap() = Some(Instant::now());
+
+            let (stream, _) = listener.accept().await.unwrap();
+            *reconnected_at.lock().unwrap() = Some(Instant::now());
+            let _ws = accept_async(stream).await.unwrap();
+            std::future::pending::<()>().await;
+        }
+    });
+
+    HintServer {
+        url,
+        turned_away_at,
+        reconnected_at,
+        join_handle,
+    }
+}
+
+#[tokio::test]
+async fn waits_for_close_frame_retry_hint_before_reconnecting() {
+    let server = spawn_hint_server(FirstAttempt::CloseWithHint(300)).await;
+
+    let client = HyperStack::<TestStack>::builder()
+        .url(&server.url)
+        .reconnect_intervals(vec![Duration::from_millis(10)])
+        .connect()
+        .await
+        .expect("client should connect");
+
+    let waited = server.wait_for_reconnect().await;
+    assert!(
+        waited >= Duration::from_millis(300),
+        "reconnected after {waited:?}, before the 300ms hint"
+    );
+
+    client.disconnect().await;
+    server.join_handle.abort();
+}
+
+#[tokio::test]
+async fn waits_for_handshake_retry_hint_before_reconnecting() {
+    let server = spawn_hint_server(FirstAttempt::RejectWithHint(300)).await;
+
+    let client = HyperStack::<TestStack>::builder()
+        .url(&server.url)
+        .reconnect_intervals(vec![Duration::from_millis(10)])
+        .connect()
+        .await
+        .expect("client should connect on the second attempt");
+
+    let waited = server.wait_for_reconnect().await;
+    assert!(
+        waited >= Duration::from_millis(300),
+        "reconnected after {waited:?}, before the 300ms hint"
+    );
+
+    client.disconnect().await;
+    server.join_handle.abort();
+}
+
+#[tokio::test]
+async fn caps_retry_hint_at_max_retry_hint() {
+    let server = spawn_hint_server(FirstAttempt::CloseWithHint(60_000)).await;
+
+    let client = HyperStack::<TestStack>::builder()
+        .url(&server.url)
+        .reconnect_intervals(vec![Duration::from_millis(10)])
+        .max_retry_hint(Duration::from_millis(200))
+        .connect()
+        .await
+        .expect("client should connect");
+
+    let waited = server.wait_for_reconnect().await;
+    assert!(waited >= Duration::from_millis(200));
+    assert!(
+        waited < Duration::from_secs(1),
+        "hint should be capped, waited {waited:?}"
+    );
+
+    client.disconnect().await;
+    server.join_handle.abort();
+}
@@ -0,0 +1,188 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::{
+    ConnectionEvent, HyperStack, ReconnectBackoff, Stack, Update, ViewBuilder, ViewHandle, Views,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+struct TestViews {
+    tokens: ViewHandle<Value>,
+}
+
+impl Views for TestViews {
+    fn from_builder(builder: ViewBuilder) -> Self {
+        Self {
+            tokens: builder.view("Token/list"),
+        }
+    }
+}
+
+struct TestStack;
+
+impl Stack for TestStack {
+    type Views = TestViews;
+
+    fn name() -> &'static str {
+        "test-stack"
+    }
+
+    fn url() -> &'static str {
+        "ws://127.0.0.1:1"
+    }
+}
+
+fn frame(frame: Value) -> Message {
+    Message::Binary(frame.to_string().into_bytes())
+}
+
+fn subscribed() -> Message {
+    frame(json!({ "op": "subscribed", "view": "Token/list", "mode": "list" }))
+}
+
+fn snapshot(tokens: &[(&str, u64)]) -> Message {
+    let entities: Vec<Value> = tokens
+        .iter()
+        .map(|(key, supply)| json!({ "key": key, "data": { "id": key, "supply": supply } }))
+        .collect();
+    frame(json!({
+        "mode": "list",
+        "entity": "Token/list",
+        "op": "snapshot",
+        "key": "",
+        "data": entities,
+    }))
+}
+
+/// Serves `Token/list` with tokens `a` and `b` until `drop_rx` fires, then
+/// closes the socket. The client's next connection finds `b` gone and `a`
+/// changed.
+async fn spawn_server(drop_rx: oneshot::Receiver<()>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let mut drop_rx = Some(drop_rx);
+        let snapshots = [snapshot(&[("a", 1), ("b", 1)]), snapshot(&[("a", 2)])];
+
+        for snapshot in snapshots {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (mut write, mut read) = accept_async(stream).await.unwrap().split();
+            while let Some(Ok(message)) = read.next().await {
+                let Message::Text(text) = message else {
+                    continue;
+                };
+                let payload: Value = serde_json::from_str(&text).unwrap();
+                if payload["type"] == "subscribe" {
+                    write.send(subscribed()).await.unwrap();
+                    write.send(snapshot).await.unwrap();
+                    break;
+                }
+            }
+
+            match drop_rx.take() {
+                Some(drop_rx) => {
+                    let _ = drop_rx.await;
+                    let _ = write.close().await;
+                }
+                None => std::future::pending::<()>().await,
+            }
+        }
+    });
+
+    format!("ws://{addr}")
+}
+
+#[tokio::test]
+async fn reconnect_resubscribes_and_reconciles_the_store() {
+    let (drop_tx, drop_rx) = oneshot::channel();
+    let url = spawn_server(drop_rx).await;
+    let hs = HyperStack::<TestStack>::builder()
+        .url(&url)
+        .reconnect_backoff(ReconnectBackoff::new(
+            Duration::from_millis(10),
+            Duration::from_millis(50),
+        ))
+        .connect()
+        .await
+        .expect("client should connect");
+    let mut events = Box::pin(hs.connection_events());
+
+    let mut updates = hs.views.tokens.watch();
+    let mut first = Vec::new();
+    for _ in 0..2 {
+        match timeout(Duration::from_secs(3), updates.next()).await {
+            Ok(Some(Update::Upsert { key, .. })) => first.push(key),
+            other => panic!("expected the first snapshot, got {other:?}"),
+        }
+    }
+    first.sort();
+    assert_eq!(first, ["a", "b"]);
+
+    drop_tx.send(()).unwrap();
+
+    let mut seen = Vec::new();
+    timeout(Duration::from_secs(3), async {
+        while let Some(event) = events.next().await {
+            let connected = event == ConnectionEvent::Connected;
+            seen.push(event);
+            if connected {
+                break;
+            }
+        }
+    })
+    .await
+    .expect("client should reconnect");
+    assert_eq!(seen[0], ConnectionEvent::Disconnected);
+    assert!(matches!(
+        seen[1],
+        ConnectionEvent::Reconnecting { attempt: 1, delay } if delay <= Duration::from_millis(10)
+    ));
+    assert_eq!(seen.last(), Some(&ConnectionEvent::Connected));
+
+    let mut upserted = None;
+    let mut deleted = None;
+    timeout(Duration::from_secs(3), async {
+        while upserted.is_none() || deleted.is_none() {
+            match updates.next().await {
+                Some(Update::Upsert { key, data }) => upserted = Some((key, data)),
+                Some(Update::Delete { key }) => deleted = Some(key),
+                other => panic!("unexpected update {other:?}"),
+            }
+        }
+    })
+    .await
+    .expect("reconnect snapshot should reconcile the store");
+    assert_eq!(
+        upserted,
+        Some(("a".to_string(), json!({ "id": "a", "supply": 2 })))
+    );
+    assert_eq!(deleted.as_deref(), Some("b"));
+
+    let mut keys: Vec<String> = hs.store().all_raw("Token/list").await.into_keys().collect();
+    keys.sort();
+    assert_eq!(keys, ["a"]);
+}
+
+#[test]
+fn backoff_doubles_up_to_its_max_less_jitter() {
+    let backoff = ReconnectBackoff::new(Duration::from_millis(100), Duration::from_secs(1));
+    let ceilings: Vec<Duration> = (0..6).map(|attempt| backoff.ceiling(attempt)).collect();
+    assert_eq!(
+        ceilings,
+        [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis)
+    );
+    assert_eq!(backoff.ceiling(u32::MAX), Duration::from_secs(1));
+
+    for attempt in 0..6 {
+        let delay = backoff.delay(attempt);
+        assert!(delay <= ceilings[attempt as usize]);
+        assert!(delay >= ceilings[attempt as usize].mul_f64(0.8));
+    }
+    assert_eq!(
+        backoff.with_jitter(0.0).delay(2),
+        Duration::from_millis(400)
+    );
+}
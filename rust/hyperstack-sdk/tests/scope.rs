@@ -0,0 +1,189 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::{HyperStack, HyperStackError, Stack, ViewBuilder, ViewHandle, Views};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+struct TestViews {
+    alpha: ViewHandle<Value>,
+    beta: ViewHandle<Value>,
+}
+
+impl Views for TestViews {
+    fn from_builder(builder: ViewBuilder) -> Self {
+        Self {
+            alpha: builder.view("Alpha/list"),
+            beta: builder.view("Beta/list"),
+        }
+    }
+}
+
+struct TestStack;
+
+impl Stack for TestStack {
+    type Views = TestViews;
+
+    fn name() -> &'static str {
+        "test-stack"
+    }
+
+    fn url() -> &'static str {
+        "ws://127.0.0.1:1"
+    }
+}
+
+/// Accepts one client, answers every subscribe with a single upsert for that
+/// view, and forwards all client messages to the returned channel.
+async fn spawn_view_server() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("view server should bind");
+    let addr = listener
+        .local_addr()
+        .expect("view server should have an address");
+    let (message_tx, message_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .expect("view server should accept a connection");
+        let ws_stream = accept_async(stream)
+            .await
+            .expect("view server handshake should succeed");
+        let (mut write, mut read) = ws_stream.split();
+
+        while let Some(Ok(message)) = read.next().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let payload: Value =
+                serde_json::from_str(&text).expect("client payload should be valid json");
+            if payload["type"] == "subscribe" {
+                let frame = json!({
+                    "mode": "list",
+                    "entity": payload["view"],
+                    "op": "upsert",
+                    "key": "1",
+                    "data": { "id": 1 },
+                });
+                let _ = write.send(Message::Text(frame.to_string())).await;
+            }
+            let _ = message_tx.send(payload);
+        }
+    });
+
+    (format!("ws://{addr}"), message_rx)
+}
+
+async fn next_message_of_type(rx: &mut mpsc::UnboundedReceiver<Value>, kind: &str) -> Value {
+    loop {
+        let message = timeout(Duration::from_secs(3), rx.recv())
+            .await
+            .unwrap_or_else(|_| panic!("expected a {kind} message"))
+            .expect("view server channel should stay open");
+        if message["type"] == kind {
+            return message;
+        }
+    }
+}
+
+#[tokio::test]
+async fn fail_fast_cancels_siblings_after_recording_first_error() {
+    let (url, mut messages) = spawn_view_server().await;
+    let hs = HyperStack::<TestStack>::builder()
+        .url(&url)
+        .connect()
+        .await
+        .expect("client should connect");
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut scope = hs.scope();
+
+    let beta_events = events.clone();
+    let (beta_started_tx, mut beta_started_rx) = mpsc::unbounded_channel();
+    scope.spawn_watch(&hs.views.beta, move |ctx| {
+        let events = beta_events.clone();
+        let _ = beta_started_tx.send(());
+        async move {
+            ctx.cancellation.cancelled().await;
+            events.lock().unwrap().push("beta cancelled");
+            Err::<(), _>("beta failed after cancellation")
+        }
+    });
+    timeout(Duration::from_secs(3), beta_started_rx.recv())
+        .await
+        .expect("beta handler should start");
+
+    let alpha_events = events.clone();
+    scope.spawn_watch(&hs.views.alpha, move |_ctx| {
+        let events = alpha_events.clone();
+        async move {
+            events.lock().unwrap().push("alpha failed");
+            Err::<(), _>("alpha failed")
+        }
+    });
+
+    let result = timeout(Duration::from_secs(3), scope.join())
+        .await
+        .expect("scope should finish once alpha fails");
+
+    match result {
+        Err(HyperStackError::HandlerFailed(message)) => assert_eq!(message, "alpha failed"),
+        other => panic!("expected alpha's handler error, got {other:?}"),
+    }
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec!["alpha failed", "beta cancelled"]
+    );
+
+    let mut unsubscribed = vec![
+        next_message_of_type(&mut messages, "unsubscribe").await["view"].clone(),
+        next_message_of_type(&mut messages, "unsubscribe").await["view"].clone(),
+    ];
+    unsubscribed.sort_by_key(|view| view.to_string());
+    assert_eq!(unsubscribed, vec![json!("Alpha/list"), json!("Beta/list")]);
+}
+
+#[tokio::test]
+async fn dropping_scope_unsubscribes_its_views() {
+    let (url, mut messages) = spawn_view_server().await;
+    let hs = HyperStack::<TestStack>::builder()
+        .url(&url)
+        .connect()
+        .await
+        .expect("client should connect");
+
+    let (seen_tx, mut seen_rx) = mpsc::unbounded_channel();
+    let mut scope = hs.scope();
+    let cancellation = scope.cancellation_token();
+    scope.spawn_watch(&hs.views.alpha, move |ctx| {
+        let seen_tx = seen_tx.clone();
+        async move {
+            let _ = seen_tx.send(ctx.key);
+            Ok::<_, HyperStackError>(())
+        }
+    });
+
+    let subscribe = next_message_of_type(&mut messages, "subscribe").await;
+    assert_eq!(subscribe["view"], "Alpha/list");
+    let key = timeout(Duration::from_secs(3), seen_rx.recv())
+        .await
+        .expect("handler should see the upsert");
+    assert_eq!(key.as_deref(), Some("1"));
+
+    drop(scope);
+
+    assert!(cancellation.is_cancelled());
+    let unsubscribe = next_message_of_type(&mut messages, "unsubscribe").await;
+    assert_eq!(unsubscribe["view"], "Alpha/list");
+    assert!(
+        timeout(Duration::from_secs(1), seen_rx.recv())
+            .await
+            .map_or(true, |message| message.is_none()),
+        "handler should be dropped with the scope"
+    );
+}
@@ -0,0 +1,112 @@
+use hyperstack_sdk::{Frame, Mode, SharedStore};
+use serde_json::{json, Value};
+
+const VIEW: &str = "Token/list";
+
+fn frame(op: &str, key: &str, data: Value, complete: Option<bool>) -> Frame {
+    Frame {
+        mode: Mode::List,
+        entity: VIEW.to_string(),
+        op: op.to_string(),
+        key: key.to_string(),
+        data,
+        append: Vec::new(),
+        seq: None,
+        continuation: None,
+        complete,
+    }
+}
+
+fn page(keys: &[&str], complete: bool) -> Frame {
+    let entities: Vec<Value> = keys
+        .iter()
+        .map(|key| json!({ "key": key, "data": { "name": key } }))
+        .collect();
+    frame("snapshot", "", json!(entities), Some(complete))
+}
+
+fn subscribed() -> Frame {
+    frame(
+        "subscribed",
+        "",
+        json!({ "op": "subscribed", "view": VIEW, "mode": "list" }),
+        None,
+    )
+}
+
+async fn keys(store: &SharedStore) -> Vec<String> {
+    let mut keys: Vec<String> = store.all_raw(VIEW).await.into_keys().collect();
+    keys.sort();
+    keys
+}
+
+#[tokio::test]
+async fn snapshot_is_applied_once_its_last_page_arrives() {
+    let store = SharedStore::new();
+    let mut updates = store.subscribe();
+
+    store.apply_frame(subscribed()).await;
+    store.apply_frame(page(&["a", "b"], false)).await;
+    store.apply_frame(page(&["c", "d"], false)).await;
+    assert!(keys(&store).await.is_empty());
+    assert!(updates.try_recv().is_err());
+
+    store.apply_frame(page(&["e"], true)).await;
+    assert_eq!(keys(&store).await, ["a", "b", "c", "d", "e"]);
+    for _ in 0..5 {
+        assert!(updates.try_recv().is_ok());
+    }
+    assert!(updates.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn updates_between_pages_are_applied_after_the_snapshot() {
+    let store = SharedStore::new();
+
+    store.apply_frame(subscribed()).await;
+    store.apply_frame(page(&["a"], false)).await;
+    store
+        .apply_frame(frame("patch", "a", json!({ "name": "a2" }), None))
+        .await;
+    store
+        .apply_frame(frame("upsert", "z", json!({ "name": "z" }), None))
+        .await;
+    assert!(keys(&store).await.is_empty());
+
+    store.apply_frame(page(&["b"], true)).await;
+    assert_eq!(keys(&store).await, ["a", "b", "z"]);
+    assert_eq!(
+        store.get::<Value>(VIEW, "a").await,
+        Some(json!({ "name": "a2" }))
+    );
+
+    store
+        .apply_frame(frame("delete", "z", Value::Null, None))
+        .await;
+    assert_eq!(keys(&store).await, ["a", "b"]);
+}
+
+#[tokio::test]
+async fn pages_from_before_a_new_ack_are_discarded() {
+    let store = SharedStore::new();
+
+    // The connection drops mid-snapshot, and the resubscription starts over
+    store.apply_frame(subscribed()).await;
+    store.apply_frame(page(&["stale"], false)).await;
+    store.apply_frame(subscribed()).await;
+    store.apply_frame(page(&["a"], false)).await;
+    store.apply_frame(page(&["b"], true)).await;
+
+    assert_eq!(keys(&store).await, ["a", "b"]);
+}
+
+#[tokio::test]
+async fn snapshots_without_pages_apply_at_once() {
+    let store = SharedStore::new();
+
+    let mut single = page(&["a"], true);
+    single.complete = None;
+    store.apply_frame(single).await;
+
+    assert_eq!(keys(&store).await, ["a"]);
+}
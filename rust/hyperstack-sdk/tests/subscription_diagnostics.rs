@@ -0,0 +1,146 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::{HyperStack, Stack, SubscriptionDiagnostics, ViewBuilder, ViewHandle, Views};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+struct TestViews {
+    tokens: ViewHandle<Value>,
+}
+
+impl Views for TestViews {
+    fn from_builder(builder: ViewBuilder) -> Self {
+        Self {
+            tokens: builder.view("Token/list"),
+        }
+    }
+}
+
+struct TestStack;
+
+impl Stack for TestStack {
+    type Views = TestViews;
+
+    fn name() -> &'static str {
+        "test-stack"
+    }
+
+    fn url() -> &'static str {
+        "ws://127.0.0.1:1"
+    }
+}
+
+/// Accepts one client and answers its subscription with an ack carrying
+/// diagnostics when they're asked for, followed by a single entity. Every
+/// subscribe request received is forwarded.
+async fn spawn_server() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (subscribe_tx, subscribe_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut write, mut read) = accept_async(stream).await.unwrap().split();
+
+        while let Some(Ok(message)) = read.next().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let payload: Value = serde_json::from_str(&text).unwrap();
+            if payload["type"] != "subscribe" {
+                continue;
+            }
+
+            let mut ack = json!({ "op": "subscribed", "view": "Token/list", "mode": "list" });
+            if payload["diagnostics"] == json!(true) {
+                ack["diagnostics"] = json!({
+                    "cache_entries": 1200,
+                    "snapshot_micros": 850,
+                    "snapshot_bytes": 96000,
+                    "compressed_bytes": 14000,
+                    "queue_wait_micros": 1300,
+                });
+            }
+            let upsert = json!({
+                "mode": "list",
+                "entity": "Token/list",
+                "op": "upsert",
+                "key": "a",
+                "data": { "id": "a" },
+            });
+            let _ = subscribe_tx.send(payload);
+            for frame in [ack, upsert] {
+                let _ = write
+                    .send(Message::Binary(frame.to_string().into_bytes()))
+                    .await;
+            }
+        }
+    });
+
+    (format!("ws://{addr}"), subscribe_rx)
+}
+
+async fn first_token(hs: &HyperStack<TestStack>) {
+    let mut tokens = hs.views.tokens.listen();
+    timeout(Duration::from_secs(3), tokens.next())
+        .await
+        .expect("the token should arrive")
+        .expect("the stream should stay open");
+}
+
+#[tokio::test]
+async fn diagnostics_are_requested_and_exposed_per_view() {
+    let (url, mut subscribes) = spawn_server().await;
+    let hs = HyperStack::<TestStack>::builder()
+        .url(&url)
+        .diagnostics(true)
+        .connect()
+        .await
+        .expect("client should connect");
+
+    first_token(&hs).await;
+
+    let subscribe = subscribes.recv().await.unwrap();
+    assert_eq!(subscribe["diagnostics"], json!(true));
+
+    let diagnostics = hs
+        .views
+        .tokens
+        .diagnostics()
+        .await
+        .expect("the ack carried diagnostics");
+    assert_eq!(
+        diagnostics,
+        SubscriptionDiagnostics {
+            cache_entries: 1200,
+            snapshot_micros: 850,
+            snapshot_bytes: 96000,
+            compressed_bytes: 14000,
+            queue_wait_micros: 1300,
+        }
+    );
+    assert!(diagnostics.compressed_bytes <= diagnostics.snapshot_bytes);
+    assert!(diagnostics.snapshot_micros <= diagnostics.queue_wait_micros);
+
+    hs.disconnect().await;
+}
+
+#[tokio::test]
+async fn diagnostics_are_off_by_default() {
+    let (url, mut subscribes) = spawn_server().await;
+    let hs = HyperStack::<TestStack>::builder()
+        .url(&url)
+        .connect()
+        .await
+        .expect("client should connect");
+
+    first_token(&hs).await;
+
+    let subscribe = subscribes.recv().await.unwrap();
+    assert!(subscribe.get("diagnostics").is_none(), "{subscribe}");
+    assert!(hs.views.tokens.diagnostics().await.is_none());
+
+    hs.disconnect().await;
+}
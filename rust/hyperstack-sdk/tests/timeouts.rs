@@ -0,0 +1,220 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::{
+    GetOptions, HyperStack, HyperStackError, Stack, StateView, TimedOperation, ViewBuilder,
+    ViewHandle, Views,
+};
+use serde_json::{json, Value};
+use std::future::Future;
+use tokio::net::TcpListener;
+use tokio::time::{timeout, Duration, Instant};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+/// Short enough to keep the tests quick, far below `BOUND`
+const TIMEOUT: Duration = Duration::from_millis(200);
+/// Every call under test must complete within this
+const BOUND: Duration = Duration::from_secs(3);
+
+struct TestViews {
+    tokens: ViewHandle<Value>,
+    token: StateView<Value>,
+}
+
+impl Views for TestViews {
+    fn from_builder(builder: ViewBuilder) -> Self {
+        Self {
+            tokens: builder.view("Token/list"),
+            token: StateView::new(
+                builder.connection().clone(),
+                builder.store().clone(),
+                "Token/state".to_string(),
+                builder.initial_data_timeout(),
+            ),
+        }
+    }
+}
+
+struct TestStack;
+
+impl Stack for TestStack {
+    type Views = TestViews;
+
+    fn name() -> &'static str {
+        "test-stack"
+    }
+
+    fn url() -> &'static str {
+        "ws://127.0.0.1:1"
+    }
+}
+
+/// Where the mock server stops responding
+#[derive(Clone, Copy)]
+enum Silent {
+    /// Accepts TCP connections but never completes the WebSocket handshake
+    Handshake,
+    /// Completes the handshake but never acknowledges a subscription
+    Ack,
+    /// Acknowledges subscriptions but never sends their data
+    Snapshot,
+}
+
+async fn spawn_server(silent: Silent) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                if let Silent::Handshake = silent {
+                    // Hold the connection open without answering
+                    let _held = stream;
+                    return std::future::pending().await;
+                }
+
+                let (mut write, mut read) = accept_async(stream).await.unwrap().split();
+                while let Some(Ok(message)) = read.next().await {
+                    let Message::Text(text) = message else {
+                        continue;
+                    };
+                    let payload: Value = serde_json::from_str(&text).unwrap();
+                    if payload["type"] != "subscribe" || matches!(silent, Silent::Ack) {
+                        continue;
+                    }
+
+                    let ack =
+                        json!({ "op": "subscribed", "view": payload["view"], "mode": "list" });
+                    let _ = write.send(Message::Text(ack.to_string())).await;
+                }
+            });
+        }
+    });
+
+    format!("ws://{addr}")
+}
+
+async fn connect(silent: Silent) -> Result<HyperStack<TestStack>, HyperStackError> {
+    let url = spawn_server(silent).await;
+    HyperStack::<TestStack>::builder()
+        .url(&url)
+        .auto_reconnect(false)
+        .connect_timeout(TIMEOUT)
+        .subscribe_ack_timeout(TIMEOUT)
+        .snapshot_timeout(TIMEOUT)
+        .connect()
+        .await
+}
+
+/// Run `future`, failing the test if it doesn't complete within `BOUND`
+async fn bounded<F: Future>(future: F) -> F::Output {
+    timeout(BOUND, future)
+        .await
+        .expect("call should complete in bounded time")
+}
+
+fn assert_timeout(error: HyperStackError, expected: TimedOperation, expected_view: Option<&str>) {
+    match error {
+        HyperStackError::Timeout {
+            operation, view, ..
+        } => {
+            assert_eq!(operation, expected);
+            assert_eq!(view.as_deref(), expected_view);
+        }
+        other => panic!("expected a {expected} timeout, got {other}"),
+    }
+}
+
+#[tokio::test]
+async fn connect_times_out_when_handshake_never_completes() {
+    let started = Instant::now();
+    let error = match bounded(connect(Silent::Handshake)).await {
+        Ok(_) => panic!("connect should time out"),
+        Err(error) => error,
+    };
+
+    assert_timeout(error, TimedOperation::Connect, None);
+    assert!(started.elapsed() >= TIMEOUT);
+}
+
+#[tokio::test]
+async fn get_with_times_out_without_subscription_ack() {
+    let hs = connect(Silent::Ack).await.expect("client should connect");
+
+    let error = bounded(hs.views.tokens.get_with(GetOptions::default()))
+        .await
+        .unwrap_err();
+    assert_timeout(error, TimedOperation::SubscribeAck, Some("Token/list"));
+
+    let error = bounded(hs.views.token.get_with(
+        "a",
+        GetOptions::default().ack_timeout(Duration::from_millis(50)),
+    ))
+    .await
+    .unwrap_err();
+    assert_timeout(error, TimedOperation::SubscribeAck, Some("Token/state"));
+}
+
+#[tokio::test]
+async fn get_with_times_out_without_snapshot() {
+    let hs = connect(Silent::Snapshot)
+        .await
+        .expect("client should connect");
+
+    let error = bounded(hs.views.tokens.get_with(GetOptions::default()))
+        .await
+        .unwrap_err();
+    assert_timeout(error, TimedOperation::Snapshot, Some("Token/list"));
+
+    let error = bounded(
+        hs.views
+            .token
+            .get_with("a", GetOptions::timeout(Duration::from_millis(50))),
+    )
+    .await
+    .unwrap_err();
+    assert_timeout(error, TimedOperation::Snapshot, Some("Token/state"));
+
+    // The infallible reads give up after the snapshot timeout too
+    assert!(bounded(hs.views.tokens.get()).await.is_empty());
+    assert!(bounded(hs.views.token.get("a")).await.is_none());
+}
+
+#[tokio::test]
+async fn stream_establishment_times_out_without_ack() {
+    let hs = connect(Silent::Ack).await.expect("client should connect");
+
+    let error = match bounded(hs.views.tokens.watch().established()).await {
+        Ok(_) => panic!("watch should not be established"),
+        Err(error) => error,
+    };
+    assert_timeout(error, TimedOperation::SubscribeAck, Some("Token/list"));
+
+    let error = match bounded(hs.views.tokens.listen().established_within(TIMEOUT)).await {
+        Ok(_) => panic!("listen should not be established"),
+        Err(error) => error,
+    };
+    assert_timeout(error, TimedOperation::SubscribeAck, Some("Token/list"));
+
+    let error = match bounded(hs.views.token.watch("a").established()).await {
+        Ok(_) => panic!("state watch should not be established"),
+        Err(error) => error,
+    };
+    assert_timeout(error, TimedOperation::SubscribeAck, Some("Token/state"));
+}
+
+#[tokio::test]
+async fn established_streams_never_time_out() {
+    let hs = connect(Silent::Snapshot)
+        .await
+        .expect("client should connect");
+
+    let mut watch = bounded(hs.views.tokens.watch().established())
+        .await
+        .expect("ack should establish the stream");
+    let mut appends = bounded(hs.views.tokens.appends().established())
+        .await
+        .expect("ack should establish the stream");
+
+    // Well past every configured timeout, the streams are still waiting
+    assert!(timeout(3 * TIMEOUT, watch.next()).await.is_err());
+    assert!(timeout(TIMEOUT, appends.next()).await.is_err());
+}
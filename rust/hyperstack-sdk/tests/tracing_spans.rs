@@ -0,0 +1,276 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::telemetry::TARGET;
+use hyperstack_sdk::{HyperStack, Stack, ViewBuilder, ViewHandle, Views};
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+const SUB: &str = "Token/list:*";
+
+struct TestViews {
+    tokens: ViewHandle<Value>,
+}
+
+impl Views for TestViews {
+    fn from_builder(builder: ViewBuilder) -> Self {
+        Self {
+            tokens: builder.view("Token/list"),
+        }
+    }
+}
+
+struct TestStack;
+
+impl Stack for TestStack {
+    type Views = TestViews;
+
+    fn name() -> &'static str {
+        "test-stack"
+    }
+
+    fn url() -> &'static str {
+        "ws://127.0.0.1:1"
+    }
+}
+
+/// Writes each telemetry span opened, field recorded and event emitted as
+/// a line of `kind name field=value...`
+#[derive(Clone, Default)]
+struct Capture {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+/// Fields as ` name=value` pairs, with an event's message kept apart
+#[derive(Default)]
+struct Fields {
+    message: String,
+    pairs: String,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.pairs.push_str(&format!(" {}={}", field.name(), value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.pairs
+                .push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+}
+
+impl Capture {
+    fn push(&self, kind: &str, name: &str, fields: Fields) {
+        self.lines
+            .lock()
+            .unwrap()
+            .push(format!("{kind} {name}{}", fields.pairs));
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().clone()
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+        if attrs.metadata().target() == TARGET {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            self.push("open", attrs.metadata().name(), fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if span.metadata().target() == TARGET {
+            let mut fields = Fields::default();
+            values.record(&mut fields);
+            self.push("record", span.name(), fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() == TARGET {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            self.push("event", &fields.message.clone(), fields);
+        }
+    }
+}
+
+fn stamped(frame_seq: u64, resync: bool, mut frame: Value) -> Message {
+    frame["sub"] = json!(SUB);
+    frame["frameSeq"] = json!(frame_seq);
+    if resync {
+        frame["resync"] = json!(true);
+    }
+    Message::Binary(frame.to_string().into_bytes())
+}
+
+fn subscribed(frame_seq: u64, resync: bool) -> Message {
+    stamped(
+        frame_seq,
+        resync,
+        json!({ "op": "subscribed", "view": "Token/list", "mode": "list" }),
+    )
+}
+
+fn snapshot(frame_seq: u64, ids: &[&str]) -> Message {
+    let entities: Vec<Value> = ids
+        .iter()
+        .map(|id| json!({ "key": id, "data": { "id": id } }))
+        .collect();
+    stamped(
+        frame_seq,
+        false,
+        json!({
+            "mode": "list",
+            "entity": "Token/list",
+            "op": "snapshot",
+            "data": entities,
+        }),
+    )
+}
+
+fn upsert(frame_seq: u64, id: &str) -> Message {
+    stamped(
+        frame_seq,
+        false,
+        json!({
+            "mode": "list",
+            "entity": "Token/list",
+            "op": "upsert",
+            "key": id,
+            "data": { "id": id },
+        }),
+    )
+}
+
+/// Accepts one client and answers its subscription with an ack and a
+/// snapshot, then loses a frame. The resync is answered with a restarted
+/// sequence ending in a `done` entity.
+async fn spawn_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut write, mut read) = accept_async(stream).await.unwrap().split();
+
+        while let Some(Ok(message)) = read.next().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let payload: Value = serde_json::from_str(&text).unwrap();
+            let frames = match payload["type"].as_str() {
+                Some("subscribe") => vec![
+                    subscribed(1, false),
+                    snapshot(2, &["a", "b"]),
+                    // Frame 3 was lost
+                    upsert(4, "c"),
+                ],
+                Some("resync") => vec![subscribed(1, true), upsert(2, "done")],
+                _ => continue,
+            };
+            for frame in frames {
+                let _ = write.send(frame).await;
+            }
+        }
+    });
+
+    format!("ws://{addr}")
+}
+
+/// Position of the first line starting with `prefix`
+fn position(lines: &[String], prefix: &str) -> usize {
+    lines
+        .iter()
+        .position(|line| line.starts_with(prefix))
+        .unwrap_or_else(|| panic!("no `{prefix}` in {lines:#?}"))
+}
+
+/// Stream the server's frames into a client sampling `frame_sample_rate`
+/// of frames, returning the server's url and the captured lines.
+///
+/// Needs a current thread runtime: the client's tasks then run on the test
+/// thread, where the subscriber is set.
+async fn capture_session(frame_sample_rate: f64) -> (String, Vec<String>) {
+    let capture = Capture::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+
+    let url = spawn_server().await;
+    let hs = HyperStack::<TestStack>::builder()
+        .url(&url)
+        .frame_sample_rate(frame_sample_rate)
+        .connect()
+        .await
+        .expect("client should connect");
+
+    let mut tokens = hs.views.tokens.listen();
+    timeout(Duration::from_secs(3), async {
+        while let Some(token) = tokens.next().await {
+            if token["id"] == "done" {
+                break;
+            }
+        }
+    })
+    .await
+    .expect("the resynced sequence should arrive");
+    hs.disconnect().await;
+
+    (url, capture.lines())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn lifecycle_spans_and_events_fire_in_order() {
+    let (url, lines) = capture_session(1.0).await;
+
+    let order = [
+        format!("open hyperstack.connect url={url} attempt=0"),
+        "record hyperstack.connect outcome=connected".to_string(),
+        "open hyperstack.subscription view=Token/list key=*".to_string(),
+        "record hyperstack.subscription outcome=acked".to_string(),
+        "event hyperstack.snapshot view=Token/list entities=2 bytes=".to_string(),
+        format!("event hyperstack.gap sub={SUB} expected=3 received=4"),
+        format!("event hyperstack.resync sub={SUB}"),
+    ];
+    let positions: Vec<usize> = order.iter().map(|line| position(&lines, line)).collect();
+    assert!(
+        positions.windows(2).all(|pair| pair[0] < pair[1]),
+        "out of order: {lines:#?}"
+    );
+
+    let frames = lines
+        .iter()
+        .filter(|line| line.starts_with("event hyperstack.frame view=Token/list"))
+        .count();
+    assert!(frames >= 4, "every frame should be sampled: {lines:#?}");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn frames_are_not_timed_without_a_sample_rate() {
+    let (_, lines) = capture_session(0.0).await;
+
+    assert!(lines
+        .iter()
+        .any(|line| line.contains("hyperstack.snapshot")));
+    assert!(
+        !lines.iter().any(|line| line.contains("hyperstack.frame")),
+        "{lines:#?}"
+    );
+}
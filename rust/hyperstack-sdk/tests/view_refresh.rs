@@ -0,0 +1,178 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::{HyperStack, Stack, ViewBuilder, ViewHandle, Views};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+const SUB: &str = "Token/list:*";
+
+struct TestViews {
+    tokens: ViewHandle<Value>,
+}
+
+impl Views for TestViews {
+    fn from_builder(builder: ViewBuilder) -> Self {
+        Self {
+            tokens: builder.view("Token/list"),
+        }
+    }
+}
+
+struct TestStack;
+
+impl Stack for TestStack {
+    type Views = TestViews;
+
+    fn name() -> &'static str {
+        "test-stack"
+    }
+
+    fn url() -> &'static str {
+        "ws://127.0.0.1:1"
+    }
+}
+
+fn stamped(frame_seq: u64, mut frame: Value) -> Message {
+    frame["sub"] = json!(SUB);
+    frame["frameSeq"] = json!(frame_seq);
+    Message::Binary(frame.to_string().into_bytes())
+}
+
+fn subscribed() -> Value {
+    json!({ "op": "subscribed", "view": "Token/list", "mode": "list" })
+}
+
+fn snapshot(entities: Value) -> Value {
+    json!({
+        "mode": "list",
+        "entity": "Token/list",
+        "op": "snapshot",
+        "data": entities,
+    })
+}
+
+/// Accepts one client and answers its subscription with tokens `a` and `b`.
+/// A refresh is answered with a snapshot in which `a` is gone, `b` changed
+/// and `c` appeared, followed by an update to `c`. Refresh requests are
+/// forwarded.
+async fn spawn_server() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (refresh_tx, refresh_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut write, mut read) = accept_async(stream).await.unwrap().split();
+
+        while let Some(Ok(message)) = read.next().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let payload: Value = serde_json::from_str(&text).unwrap();
+            let frames = match payload["type"].as_str() {
+                Some("subscribe") => vec![
+                    stamped(1, subscribed()),
+                    stamped(
+                        2,
+                        snapshot(json!([
+                            { "key": "a", "data": { "id": "a", "supply": 1 } },
+                            { "key": "b", "data": { "id": "b", "supply": 1 } },
+                        ])),
+                    ),
+                ],
+                Some("refresh") => {
+                    let _ = refresh_tx.send(payload);
+                    let mut ack = subscribed();
+                    ack["resync"] = json!(true);
+                    ack["refresh"] = json!(true);
+                    vec![
+                        stamped(1, ack),
+                        stamped(
+                            2,
+                            snapshot(json!([
+                                { "key": "b", "data": { "id": "b", "supply": 2 } },
+                                { "key": "c", "data": { "id": "c", "supply": 1 } },
+                            ])),
+                        ),
+                        stamped(
+                            3,
+                            json!({
+                                "mode": "list",
+                                "entity": "Token/list",
+                                "op": "patch",
+                                "key": "c",
+                                "data": { "supply": 3 },
+                            }),
+                        ),
+                    ]
+                }
+                _ => continue,
+            };
+            for frame in frames {
+                let _ = write.send(frame).await;
+            }
+        }
+    });
+
+    (format!("ws://{addr}"), refresh_rx)
+}
+
+fn supplies(tokens: &[Value]) -> Vec<(String, u64)> {
+    let mut supplies: Vec<_> = tokens
+        .iter()
+        .map(|token| {
+            (
+                token["id"].as_str().unwrap().to_string(),
+                token["supply"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    supplies.sort();
+    supplies
+}
+
+#[tokio::test]
+async fn refresh_swaps_in_the_fresh_snapshot() {
+    let (url, mut refreshes) = spawn_server().await;
+    let hs = HyperStack::<TestStack>::builder()
+        .url(&url)
+        .connect()
+        .await
+        .expect("client should connect");
+
+    let tokens = hs.views.tokens.get().await;
+    assert_eq!(
+        supplies(&tokens),
+        [("a".to_string(), 1), ("b".to_string(), 1)]
+    );
+
+    hs.views.tokens.refresh().await;
+    let refresh = timeout(Duration::from_secs(3), refreshes.recv())
+        .await
+        .expect("the refresh should be sent")
+        .unwrap();
+    assert_eq!(refresh, json!({ "type": "refresh", "view": "Token/list" }));
+
+    timeout(Duration::from_secs(3), async {
+        loop {
+            let tokens = hs.views.tokens.get_sync();
+            if tokens.iter().any(|token| token["supply"] == 3) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the refreshed view should arrive");
+
+    assert_eq!(
+        supplies(&hs.views.tokens.get_sync()),
+        [("b".to_string(), 2), ("c".to_string(), 3)]
+    );
+    // The refreshed sequence restarting at 1 isn't a gap
+    assert_eq!(hs.frame_gaps(), 0);
+
+    hs.disconnect().await;
+}
@@ -0,0 +1,140 @@
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_sdk::raw::{RawClient, RawEvent};
+use hyperstack_sdk::Subscription;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+const DICTIONARY_ID: &str = "schema-0123456789abcdef";
+
+fn upsert(id: u64) -> Vec<u8> {
+    json!({
+        "mode": "list",
+        "entity": "Token/list",
+        "op": "upsert",
+        "key": id.to_string(),
+        "data": { "id": id, "supply": id * 1_000, "symbol": format!("TOK{id}") },
+    })
+    .to_string()
+    .into_bytes()
+}
+
+fn train_dictionary() -> Vec<u8> {
+    let samples: Vec<_> = (0..500).map(upsert).collect();
+    zstd::dict::from_samples(&samples, 8 * 1024).unwrap()
+}
+
+/// Answer a plain HTTP request for the dictionary
+async fn serve_dictionary(mut stream: TcpStream, dictionary: &[u8]) {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+    }
+    let head = String::from_utf8_lossy(&request);
+    assert!(
+        head.starts_with(&format!(
+            "GET /stream/dictionaries/{DICTIONARY_ID}?token=t "
+        )),
+        "{head}"
+    );
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        dictionary.len()
+    );
+    stream.write_all(response.as_bytes()).await.unwrap();
+    stream.write_all(dictionary).await.unwrap();
+}
+
+/// Offers zstd with a dictionary, serves the dictionary over HTTP on the
+/// same port, and once the client has accepted sends its subscription frames
+/// compressed with it. Every message the client sends is forwarded.
+async fn spawn_zstd_server() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (received_tx, received_rx) = mpsc::unbounded_channel();
+    let dictionary = train_dictionary();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut write, mut read) = accept_async(stream).await.unwrap().split();
+        let hello = json!({
+            "type": "hello",
+            "compression": { "zstd": true, "dictionary": DICTIONARY_ID },
+        });
+        write.send(Message::Text(hello.to_string())).await.unwrap();
+
+        let (stream, _) = listener.accept().await.unwrap();
+        serve_dictionary(stream, &dictionary).await;
+
+        let mut compressor = zstd::bulk::Compressor::with_dictionary(3, &dictionary).unwrap();
+        while let Some(Ok(Message::Text(text))) = read.next().await {
+            let payload: Value = serde_json::from_str(&text).unwrap();
+            let frames = match payload["type"].as_str() {
+                Some("subscribe") => vec![
+                    json!({ "op": "subscribed", "view": "Token/list", "mode": "list" })
+                        .to_string()
+                        .into_bytes(),
+                    upsert(9_001),
+                ],
+                _ => Vec::new(),
+            };
+            let _ = received_tx.send(payload);
+            for frame in frames {
+                let compressed = compressor.compress(&frame).unwrap();
+                let _ = write.send(Message::Binary(compressed)).await;
+            }
+        }
+    });
+
+    (format!("ws://{addr}/stream?token=t"), received_rx)
+}
+
+#[tokio::test]
+async fn raw_client_decodes_frames_compressed_with_a_downloaded_dictionary() {
+    let (url, mut received) = spawn_zstd_server().await;
+    let mut client = RawClient::connect(url.as_str()).await.unwrap();
+
+    let offered = timeout(Duration::from_secs(3), client.next())
+        .await
+        .expect("hello should arrive");
+    let Some(RawEvent::CompressionOffered { dictionary }) = offered else {
+        panic!("expected a compression offer, got {offered:?}");
+    };
+    assert_eq!(dictionary.as_deref(), Some(DICTIONARY_ID));
+
+    client
+        .accept_compression(dictionary.as_deref())
+        .await
+        .unwrap();
+    client
+        .send_subscribe(&Subscription::new("Token/list"))
+        .await
+        .unwrap();
+
+    let mut frames = Vec::new();
+    while frames.len() < 2 {
+        match timeout(Duration::from_secs(3), client.next())
+            .await
+            .expect("frames should arrive")
+        {
+            Some(RawEvent::Frame { frame, .. }) => frames.push(frame),
+            Some(other) => panic!("unexpected event {other:?}"),
+            None => panic!("connection closed"),
+        }
+    }
+    assert_eq!(frames[0].op, "subscribed");
+    assert_eq!(frames[1].key, "9001");
+    assert_eq!(frames[1].data["symbol"], json!("TOK9001"));
+
+    assert_eq!(
+        received.recv().await.unwrap(),
+        json!({ "type": "compression", "codec": "zstd", "dictionary": DICTIONARY_ID })
+    );
+
+    client.close().await.unwrap();
+}
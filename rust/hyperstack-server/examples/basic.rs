@@ -68,11 +68,8 @@
 //!     let ws_config = WebSocketConfig::new("[::]:8877".parse()?);
 //!     
 //!     // Configure Yellowstone gRPC connection (if needed)
-//!     let yellowstone_config = YellowstoneConfig {
-//!         endpoint: std::env::var("YELLOWSTONE_ENDPOINT")
-//!             .unwrap_or_else(|_| "http://localhost:10000".into()),
-//!         x_token: std::env::var("YELLOWSTONE_X_TOKEN").ok(),
-//!     };
+//!     let yellowstone_config = YellowstoneConfig::from_env()
+//!         .unwrap_or_else(|_| YellowstoneConfig::new("http://localhost:10000"));
 //!
 //!     // Configure health monitoring (optional)
 //!     let health_config = HealthConfig::new()
@@ -0,0 +1,672 @@
+//! Bounded log of recent items for `Append` views.
+//!
+//! A view with [`Delivery::history`](crate::view::Delivery) set keeps its most
+//! recent items so a late subscriber can ask for them on subscribe, and a
+//! reconnecting subscriber can resume after the last item it saw. Each view
+//! keeps one ring for keyless subscriptions and one ring per key.
+//!
+//! Every item gets a cursor when it is appended: cursors of a view start at 1
+//! and grow by one per item, so they order the log and survive eviction. A
+//! ring holds `(cursor, serialized item)` entries and is bounded by an item
+//! count, a byte budget and optionally an age. Entries leave from the front
+//! only, so what remains is the tail of the view's log, minus any item too
+//! large for the byte budget on its own, which is never held.
+//!
+//! Limits come from the view's [`Delivery`] and are capped by the
+//! server-wide [`AppendLogConfig`].
+
+use crate::cache::cmp_seq;
+use crate::view::Delivery;
+use crate::websocket::frame::HistoryItem;
+use bytes::Bytes;
+use lru::LruCache;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+/// Bytes retained per history ring when a view doesn't set
+/// `history_max_bytes`
+pub const DEFAULT_HISTORY_MAX_BYTES: usize = 256 * 1024;
+
+/// Keys with their own history ring, per view
+const MAX_KEYED_RINGS: usize = 500;
+
+/// Server-wide caps applied to the limits of every view's log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppendLogConfig {
+    /// Most items a ring keeps, whatever the view asks for
+    pub max_items: usize,
+    /// Most bytes a ring keeps, whatever the view asks for
+    pub max_bytes: usize,
+    /// Age after which items are evicted from every view
+    pub max_age: Option<Duration>,
+}
+
+impl Default for AppendLogConfig {
+    fn default() -> Self {
+        Self {
+            max_items: 10_000,
+            max_bytes: 16 * 1024 * 1024,
+            max_age: None,
+        }
+    }
+}
+
+impl AppendLogConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_items(mut self, items: usize) -> Self {
+        self.max_items = items;
+        self
+    }
+
+    pub fn with_max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    pub fn with_max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+}
+
+/// Count, byte and age bounds for one ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogLimits {
+    pub items: usize,
+    pub bytes: usize,
+    pub max_age: Option<Duration>,
+}
+
+impl LogLimits {
+    pub fn from_delivery(delivery: &Delivery) -> Self {
+        Self {
+            items: delivery.history,
+            bytes: delivery
+                .history_max_bytes
+                .unwrap_or(DEFAULT_HISTORY_MAX_BYTES),
+            max_age: delivery.history_max_age,
+        }
+    }
+
+    /// The stricter of these limits and the server-wide caps
+    pub fn capped(self, config: &AppendLogConfig) -> Self {
+        let max_age = match (self.max_age, config.max_age) {
+            (Some(view), Some(server)) => Some(view.min(server)),
+            (view, server) => view.or(server),
+        };
+        Self {
+            items: self.items.min(config.max_items),
+            bytes: self.bytes.min(config.max_bytes),
+            max_age,
+        }
+    }
+}
+
+/// One retained item
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub cursor: u64,
+    pub seq: Option<String>,
+    /// The item serialized as JSON
+    pub payload: Bytes,
+    pub appended_at: Instant,
+}
+
+impl LogEntry {
+    /// Bytes counted against the ring's budget
+    pub fn bytes(&self) -> usize {
+        self.payload.len()
+    }
+
+    pub fn item(&self) -> serde_json::Result<HistoryItem> {
+        serde_json::from_slice(&self.payload)
+    }
+}
+
+/// Items evicted from a view's log, by the limit they exceeded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Evictions {
+    pub over_items: u64,
+    pub over_bytes: u64,
+    pub expired: u64,
+}
+
+impl Evictions {
+    pub fn total(&self) -> u64 {
+        self.over_items + self.over_bytes + self.expired
+    }
+
+    fn since(&self, before: Evictions) -> Evictions {
+        Evictions {
+            over_items: self.over_items - before.over_items,
+            over_bytes: self.over_bytes - before.over_bytes,
+            expired: self.expired - before.expired,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Reason {
+    Items,
+    Bytes,
+    Age,
+}
+
+/// Result of [`AppendLog::append`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Appended {
+    pub cursor: u64,
+    /// Items the append evicted from the view's ring
+    pub evicted: Evictions,
+    /// Bytes held by the view's ring afterwards
+    pub bytes: usize,
+    /// Age of the oldest item still held
+    pub oldest_age: Option<Duration>,
+}
+
+/// Entries returned by a range read, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct LogRead {
+    pub entries: Vec<LogEntry>,
+    /// Items after the requested position were evicted before the read
+    pub missed: bool,
+    /// More entries follow the last one returned
+    pub more: bool,
+}
+
+impl LogRead {
+    /// The entries as history items, skipping any that fail to decode
+    pub fn items(&self) -> Vec<HistoryItem> {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.item().ok())
+            .collect()
+    }
+}
+
+/// Retention of one view's log, as reported on `/stats`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppendLogStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub keys: usize,
+    pub first_cursor: Option<u64>,
+    pub last_cursor: u64,
+    pub oldest_age_ms: Option<u64>,
+    pub evictions: Evictions,
+}
+
+#[derive(Debug, Default)]
+struct Ring {
+    entries: VecDeque<LogEntry>,
+    bytes: usize,
+    max_age: Option<Duration>,
+    /// Highest cursor evicted so far
+    evicted_through: u64,
+    /// Highest `seq` evicted so far
+    evicted_seq: Option<String>,
+    evictions: Evictions,
+}
+
+impl Ring {
+    fn push(&mut self, entry: LogEntry, limits: LogLimits, now: Instant) {
+        self.max_age = limits.max_age;
+
+        // An item over the whole budget would evict everything and still not fit
+        if limits.items == 0 || entry.bytes() > limits.bytes {
+            let reason = if limits.items == 0 {
+                Reason::Items
+            } else {
+                Reason::Bytes
+            };
+            self.record_eviction(&entry, reason);
+        } else {
+            self.bytes += entry.bytes();
+            self.entries.push_back(entry);
+            while self.entries.len() > limits.items {
+                self.evict_front(Reason::Items);
+            }
+            while self.bytes > limits.bytes {
+                self.evict_front(Reason::Bytes);
+            }
+        }
+        self.expire(now);
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let Some(max_age) = self.max_age else {
+            return;
+        };
+        while self
+            .entries
+            .front()
+            .is_some_and(|entry| now.saturating_duration_since(entry.appended_at) > max_age)
+        {
+            self.evict_front(Reason::Age);
+        }
+    }
+
+    fn evict_front(&mut self, reason: Reason) {
+        if let Some(entry) = self.entries.pop_front() {
+            self.bytes -= entry.bytes();
+            self.record_eviction(&entry, reason);
+        }
+    }
+
+    fn record_eviction(&mut self, entry: &LogEntry, reason: Reason) {
+        self.evicted_through = self.evicted_through.max(entry.cursor);
+        if let Some(seq) = &entry.seq {
+            let newer = self
+                .evicted_seq
+                .as_deref()
+                .is_none_or(|evicted| cmp_seq(seq, evicted).is_gt());
+            if newer {
+                self.evicted_seq = Some(seq.clone());
+            }
+        }
+        match reason {
+            Reason::Items => self.evictions.over_items += 1,
+            Reason::Bytes => self.evictions.over_bytes += 1,
+            Reason::Age => self.evictions.expired += 1,
+        }
+    }
+
+    fn recent(&self, n: usize) -> Vec<LogEntry> {
+        let skip = self.entries.len().saturating_sub(n);
+        self.entries.iter().skip(skip).cloned().collect()
+    }
+
+    /// Up to `limit` entries from index `start` on
+    fn read_from(&self, start: usize, limit: usize, missed: bool) -> LogRead {
+        let entries: Vec<LogEntry> = self
+            .entries
+            .iter()
+            .skip(start)
+            .take(limit)
+            .cloned()
+            .collect();
+        LogRead {
+            more: start + entries.len() < self.entries.len(),
+            entries,
+            missed,
+        }
+    }
+
+    fn read_after(&self, cursor: u64, limit: usize) -> LogRead {
+        let start = self.entries.partition_point(|entry| entry.cursor <= cursor);
+        self.read_from(start, limit, self.evicted_through > cursor)
+    }
+
+    fn read_after_seq(&self, seq: &str, limit: usize) -> LogRead {
+        let is_after = |entry_seq: Option<&str>| entry_seq.is_some_and(|s| cmp_seq(s, seq).is_gt());
+        let start = self
+            .entries
+            .iter()
+            .position(|entry| is_after(entry.seq.as_deref()))
+            .unwrap_or(self.entries.len());
+        self.read_from(start, limit, is_after(self.evicted_seq.as_deref()))
+    }
+
+    fn oldest_age(&self, now: Instant) -> Option<Duration> {
+        self.entries
+            .front()
+            .map(|entry| now.saturating_duration_since(entry.appended_at))
+    }
+}
+
+/// Recent items for one view. See the [module docs](self).
+#[derive(Debug)]
+pub struct AppendLog {
+    all: Ring,
+    by_key: LruCache<String, Ring>,
+    last_cursor: u64,
+}
+
+impl Default for AppendLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AppendLog {
+    pub fn new() -> Self {
+        Self {
+            all: Ring::default(),
+            by_key: LruCache::new(NonZeroUsize::new(MAX_KEYED_RINGS).unwrap()),
+            last_cursor: 0,
+        }
+    }
+
+    /// Record an item that is about to be published and return its cursor.
+    pub fn append(
+        &mut self,
+        item: &HistoryItem,
+        limits: LogLimits,
+        now: Instant,
+    ) -> serde_json::Result<Appended> {
+        let payload = Bytes::from(serde_json::to_vec(item)?);
+        self.last_cursor += 1;
+        let entry = LogEntry {
+            cursor: self.last_cursor,
+            seq: item.seq.clone(),
+            payload,
+            appended_at: now,
+        };
+
+        self.by_key
+            .get_or_insert_mut(item.key.clone(), Ring::default)
+            .push(entry.clone(), limits, now);
+        let before = self.all.evictions;
+        self.all.push(entry, limits, now);
+
+        Ok(Appended {
+            cursor: self.last_cursor,
+            evicted: self.all.evictions.since(before),
+            bytes: self.all.bytes,
+            oldest_age: self.all.oldest_age(now),
+        })
+    }
+
+    /// The ring for `key`, or the view's ring, after dropping expired items
+    fn ring(&mut self, key: Option<&str>, now: Instant) -> Option<&mut Ring> {
+        let ring = match key {
+            Some(key) => self.by_key.get_mut(key)?,
+            None => &mut self.all,
+        };
+        ring.expire(now);
+        Some(ring)
+    }
+
+    /// Up to `n` of the most recent entries, oldest first. `key` limits the
+    /// entries to a single entity.
+    pub fn recent(&mut self, key: Option<&str>, n: usize, now: Instant) -> Vec<LogEntry> {
+        self.ring(key, now)
+            .map(|ring| ring.recent(n))
+            .unwrap_or_default()
+    }
+
+    /// Up to `limit` entries with a cursor after `cursor`, oldest first.
+    ///
+    /// Reading from cursor 0 starts at the oldest retained entry. Pass the
+    /// cursor of the last returned entry to read the next page.
+    pub fn read_after(
+        &mut self,
+        key: Option<&str>,
+        cursor: u64,
+        limit: usize,
+        now: Instant,
+    ) -> LogRead {
+        self.ring(key, now)
+            .map(|ring| ring.read_after(cursor, limit))
+            .unwrap_or_default()
+    }
+
+    /// Up to `limit` entries published after the item with `seq`, oldest
+    /// first. Entries without a `seq` are never returned.
+    pub fn read_after_seq(
+        &mut self,
+        key: Option<&str>,
+        seq: &str,
+        limit: usize,
+        now: Instant,
+    ) -> LogRead {
+        self.ring(key, now)
+            .map(|ring| ring.read_after_seq(seq, limit))
+            .unwrap_or_default()
+    }
+
+    /// Cursor of the last appended item, `0` before the first append
+    pub fn last_cursor(&self) -> u64 {
+        self.last_cursor
+    }
+
+    /// Bytes held by the view's ring. Keyed rings share its payloads.
+    pub fn bytes(&self) -> usize {
+        self.all.bytes
+    }
+
+    pub fn stats(&mut self, now: Instant) -> AppendLogStats {
+        self.all.expire(now);
+        AppendLogStats {
+            entries: self.all.entries.len(),
+            bytes: self.all.bytes,
+            keys: self.by_key.len(),
+            first_cursor: self.all.entries.front().map(|entry| entry.cursor),
+            last_cursor: self.last_cursor,
+            oldest_age_ms: self.all.oldest_age(now).map(|age| age.as_millis() as u64),
+            evictions: self.all.evictions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn item(key: &str, n: u64) -> HistoryItem {
+        HistoryItem {
+            key: key.to_string(),
+            data: json!({ "n": n }),
+            append: vec![],
+            seq: Some(format!("{n}:000000000000")),
+        }
+    }
+
+    fn limits(items: usize, bytes: usize) -> LogLimits {
+        LogLimits {
+            items,
+            bytes,
+            max_age: None,
+        }
+    }
+
+    fn numbers(entries: &[LogEntry]) -> Vec<u64> {
+        entries
+            .iter()
+            .map(|entry| entry.item().unwrap().data["n"].as_u64().unwrap())
+            .collect()
+    }
+
+    fn size(key: &str, n: u64) -> usize {
+        serde_json::to_vec(&item(key, n)).unwrap().len()
+    }
+
+    #[test]
+    fn keeps_most_recent_items_up_to_count() {
+        let now = Instant::now();
+        let mut log = AppendLog::new();
+        for n in 0..5 {
+            log.append(&item("a", n), limits(3, 1024), now).unwrap();
+        }
+
+        assert_eq!(numbers(&log.recent(None, 10, now)), vec![2, 3, 4]);
+        assert_eq!(numbers(&log.recent(None, 2, now)), vec![3, 4]);
+        assert_eq!(numbers(&log.recent(Some("a"), 10, now)), vec![2, 3, 4]);
+        assert_eq!(log.stats(now).evictions.over_items, 2);
+    }
+
+    #[test]
+    fn evicts_oldest_items_over_byte_budget() {
+        let now = Instant::now();
+        let budget = size("a", 0) * 2;
+        let mut log = AppendLog::new();
+        for n in 0..5 {
+            log.append(&item("a", n), limits(100, budget), now).unwrap();
+        }
+
+        assert_eq!(numbers(&log.recent(None, 100, now)), vec![3, 4]);
+
+        // An item larger than the whole budget is skipped, not kept alone
+        let mut large = item("a", 5);
+        large.data = json!({ "n": 5, "pad": "x".repeat(budget) });
+        let appended = log.append(&large, limits(100, budget), now).unwrap();
+        assert_eq!(appended.cursor, 6);
+        assert_eq!(appended.evicted.over_bytes, 1);
+        assert_eq!(numbers(&log.recent(None, 100, now)), vec![3, 4]);
+        assert_eq!(log.bytes(), budget);
+    }
+
+    #[test]
+    fn keyed_history_only_holds_that_key() {
+        let now = Instant::now();
+        let mut log = AppendLog::new();
+        log.append(&item("a", 0), limits(10, 1024), now).unwrap();
+        log.append(&item("b", 1), limits(10, 1024), now).unwrap();
+        log.append(&item("a", 2), limits(10, 1024), now).unwrap();
+
+        assert_eq!(numbers(&log.recent(Some("a"), 10, now)), vec![0, 2]);
+        assert_eq!(numbers(&log.recent(Some("b"), 10, now)), vec![1]);
+        assert!(log.recent(Some("c"), 10, now).is_empty());
+        assert_eq!(numbers(&log.recent(None, 10, now)), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn expires_items_older_than_max_age() {
+        let start = Instant::now();
+        let limits = LogLimits {
+            items: 10,
+            bytes: 1024,
+            max_age: Some(Duration::from_secs(10)),
+        };
+        let mut log = AppendLog::new();
+        log.append(&item("a", 0), limits, start).unwrap();
+        log.append(&item("a", 1), limits, start + Duration::from_secs(5))
+            .unwrap();
+
+        let later = start + Duration::from_secs(12);
+        assert_eq!(numbers(&log.recent(None, 10, later)), vec![1]);
+        let stats = log.stats(later);
+        assert_eq!(stats.evictions.expired, 1);
+        assert_eq!(stats.first_cursor, Some(2));
+        assert_eq!(stats.oldest_age_ms, Some(7_000));
+
+        let read = log.read_after(None, 0, 10, later);
+        assert!(read.missed);
+        assert_eq!(numbers(&read.entries), vec![1]);
+    }
+
+    #[test]
+    fn reads_after_a_seq() {
+        let now = Instant::now();
+        let mut log = AppendLog::new();
+        for n in 0..6 {
+            log.append(&item("a", n), limits(4, 1024), now).unwrap();
+        }
+
+        let read = log.read_after_seq(None, "3:000000000000", 10, now);
+        assert_eq!(numbers(&read.entries), vec![4, 5]);
+        assert!(!read.missed);
+
+        let read = log.read_after_seq(None, "0:000000000000", 10, now);
+        assert_eq!(numbers(&read.entries), vec![2, 3, 4, 5]);
+        assert!(read.missed);
+    }
+
+    #[test]
+    fn server_caps_tighten_view_limits() {
+        let delivery = Delivery {
+            history: 100,
+            history_max_age: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let config = AppendLogConfig::new()
+            .with_max_items(10)
+            .with_max_age(Duration::from_secs(30));
+
+        assert_eq!(
+            LogLimits::from_delivery(&delivery).capped(&config),
+            LogLimits {
+                items: 10,
+                bytes: DEFAULT_HISTORY_MAX_BYTES,
+                max_age: Some(Duration::from_secs(30)),
+            }
+        );
+    }
+
+    /// xorshift64, so failures reproduce from the seed
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+    }
+
+    #[test]
+    fn range_reads_match_a_model_of_the_log() {
+        for seed in 1..200u64 {
+            let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            let limits = LogLimits {
+                items: 1 + rng.below(20) as usize,
+                bytes: 64 + rng.below(2048) as usize,
+                max_age: (rng.below(2) == 0).then(|| Duration::from_millis(rng.below(500))),
+            };
+            let mut now = Instant::now();
+            let mut log = AppendLog::new();
+            // Cursor of every appended item
+            let mut model: Vec<u64> = Vec::new();
+
+            for n in 0..300 {
+                now += Duration::from_millis(rng.below(20));
+                let mut next = item("k", n);
+                next.data["pad"] = json!("x".repeat(rng.below(200) as usize));
+
+                let appended = log.append(&next, limits, now).unwrap();
+                assert_eq!(appended.cursor, n + 1, "seed {seed}: cursors are gapless");
+                model.push(appended.cursor);
+
+                let held = log.recent(None, usize::MAX, now);
+                let cursors: Vec<u64> = held.iter().map(|entry| entry.cursor).collect();
+                assert!(
+                    cursors.windows(2).all(|pair| pair[0] < pair[1]),
+                    "seed {seed}: retained cursors increase"
+                );
+                assert!(held.len() <= limits.items, "seed {seed}");
+                assert!(log.bytes() <= limits.bytes, "seed {seed}");
+                assert_eq!(
+                    log.bytes(),
+                    held.iter().map(LogEntry::bytes).sum::<usize>(),
+                    "seed {seed}"
+                );
+                if let Some(max_age) = limits.max_age {
+                    assert!(
+                        held.iter().all(|entry| now - entry.appended_at <= max_age),
+                        "seed {seed}"
+                    );
+                }
+
+                let cursor = rng.below(n + 2);
+                let limit = 1 + rng.below(10) as usize;
+                let read = log.read_after(None, cursor, limit, now);
+                let expected: Vec<u64> = cursors
+                    .iter()
+                    .copied()
+                    .filter(|c| *c > cursor)
+                    .take(limit)
+                    .collect();
+                let got: Vec<u64> = read.entries.iter().map(|entry| entry.cursor).collect();
+                assert_eq!(got, expected, "seed {seed}: read after {cursor}");
+
+                let evicted_after = model.iter().any(|c| *c > cursor && !cursors.contains(c));
+                assert_eq!(
+                    read.missed, evicted_after,
+                    "seed {seed}: missed after {cursor}"
+                );
+                assert_eq!(
+                    read.more,
+                    cursors.iter().filter(|c| **c > cursor).count() > limit,
+                    "seed {seed}"
+                );
+            }
+        }
+    }
+}
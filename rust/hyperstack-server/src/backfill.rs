@@ -0,0 +1,366 @@
+//! Fetching state entities the server has never seen from RPC.
+//!
+//! An entity created before the server started has no cached state, so a
+//! `State` subscription for its key would get an empty snapshot until the
+//! account changes again. With an [`RpcBackfillConfig`] the subscription
+//! instead triggers a one-shot `getAccountInfo` for the key:
+//!
+//! 1. the account is fetched at the RPC's current slot;
+//! 2. the spec's [`AccountBackfillFn`] decodes it with the program's account
+//!    parser and runs it through the VM as a synthetic account update, so its
+//!    mutations reach the projector like any streamed update;
+//! 3. the subscription waits for the projector to store the entity, up to
+//!    [`RpcBackfillConfig::snapshot_wait`], and serves it as the snapshot.
+//!
+//! Concurrent subscriptions for the same key share one fetch. Keys that are
+//! missing on chain or fail to fetch are not retried for
+//! [`RpcBackfillConfig::negative_ttl`], and fetches are rate-limited across
+//! the server.
+
+use crate::websocket::rate_limiter::{RateLimitBucket, RateLimitResult, RateLimitWindow};
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Configuration for fetching unknown state entities from RPC
+#[derive(Debug, Clone)]
+pub struct RpcBackfillConfig {
+    /// JSON-RPC endpoint serving `getAccountInfo`
+    pub rpc_url: String,
+    /// Entity name to the account type whose address is the entity key.
+    /// Entities without an entry are never backfilled.
+    pub accounts: HashMap<String, String>,
+    /// Commitment passed to `getAccountInfo`
+    pub commitment: String,
+    /// Timeout for a single RPC request
+    pub request_timeout: Duration,
+    /// How long a key that could not be backfilled is skipped
+    pub negative_ttl: Duration,
+    /// Fetches allowed across the server
+    pub rate_limit: RateLimitWindow,
+    /// How long a subscription waits for the backfilled entity before it is
+    /// answered without a snapshot
+    pub snapshot_wait: Duration,
+}
+
+impl RpcBackfillConfig {
+    pub fn new(rpc_url: impl Into<String>) -> Self {
+        Self {
+            rpc_url: rpc_url.into(),
+            accounts: HashMap::new(),
+            commitment: "confirmed".to_string(),
+            request_timeout: Duration::from_secs(5),
+            negative_ttl: Duration::from_secs(30),
+            rate_limit: RateLimitWindow::new(20, Duration::from_secs(1)),
+            snapshot_wait: Duration::from_secs(2),
+        }
+    }
+
+    /// Backfill `entity` keys as addresses of `account_type` accounts
+    pub fn with_account(
+        mut self,
+        entity: impl Into<String>,
+        account_type: impl Into<String>,
+    ) -> Self {
+        self.accounts.insert(entity.into(), account_type.into());
+        self
+    }
+
+    pub fn with_commitment(mut self, commitment: impl Into<String>) -> Self {
+        self.commitment = commitment.into();
+        self
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn with_negative_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_ttl = ttl;
+        self
+    }
+
+    pub fn with_rate_limit(mut self, window: RateLimitWindow) -> Self {
+        self.rate_limit = window;
+        self
+    }
+
+    pub fn with_snapshot_wait(mut self, wait: Duration) -> Self {
+        self.snapshot_wait = wait;
+        self
+    }
+}
+
+/// An account fetched from RPC, handed to the spec for processing
+#[derive(Debug, Clone)]
+pub struct BackfillAccount {
+    /// Account type from [`RpcBackfillConfig::accounts`]
+    pub account_type: String,
+    /// Base58 account address
+    pub address: String,
+    /// Base58 owner program
+    pub owner: String,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    /// Slot of the RPC context the account was read at
+    pub slot: u64,
+}
+
+/// Type alias for the spec's account backfill function.
+///
+/// Decodes the account and runs it through the VM, sending the resulting
+/// mutations to the projector. Resolves to `false` when no parser of the
+/// spec recognized the account.
+pub type AccountBackfillFn = Arc<
+    dyn Fn(BackfillAccount) -> Pin<Box<dyn Future<Output = Result<bool>> + Send>> + Send + Sync,
+>;
+
+/// Result of a backfill attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackfillOutcome {
+    /// The account was fetched and its mutations sent to the projector
+    Applied,
+    /// The entity has no account mapping, or the key recently failed
+    Skipped,
+    /// The fetch was refused by the rate limit
+    RateLimited,
+    /// No account exists at the key
+    NotFound,
+    /// Fetching or processing the account failed
+    Failed(String),
+}
+
+type EntityKey = (String, String);
+type PendingFetch = Shared<BoxFuture<'static, BackfillOutcome>>;
+
+struct BackfillInner {
+    config: RpcBackfillConfig,
+    client: reqwest::Client,
+    apply: AccountBackfillFn,
+    in_flight: Mutex<HashMap<EntityKey, PendingFetch>>,
+    negative: Mutex<HashMap<EntityKey, Instant>>,
+    limiter: Mutex<RateLimitBucket>,
+}
+
+/// Fetches unknown state entities from RPC. See the [module docs](self).
+#[derive(Clone)]
+pub struct RpcBackfill {
+    inner: Arc<BackfillInner>,
+}
+
+impl RpcBackfill {
+    pub fn new(config: RpcBackfillConfig, apply: AccountBackfillFn) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()
+            .unwrap_or_default();
+        Self {
+            inner: Arc::new(BackfillInner {
+                limiter: Mutex::new(RateLimitBucket::new(config.rate_limit)),
+                config,
+                client,
+                apply,
+                in_flight: Mutex::new(HashMap::new()),
+                negative: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &RpcBackfillConfig {
+        &self.inner.config
+    }
+
+    /// Fetch the account behind `key` of `entity` and process it.
+    ///
+    /// Callers asking for a key that is already being fetched wait for that
+    /// fetch instead of starting another.
+    pub async fn backfill(&self, entity: &str, key: &str) -> BackfillOutcome {
+        let Some(account_type) = self.inner.config.accounts.get(entity) else {
+            return BackfillOutcome::Skipped;
+        };
+        let id = (entity.to_string(), key.to_string());
+
+        {
+            let mut negative = self
+                .inner
+                .negative
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            match negative.get(&id) {
+                Some(until) if *until > Instant::now() => return BackfillOutcome::Skipped,
+                Some(_) => {
+                    negative.remove(&id);
+                }
+                None => {}
+            }
+        }
+
+        let pending = {
+            let mut in_flight = self
+                .inner
+                .in_flight
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            in_flight
+                .entry(id.clone())
+                .or_insert_with(|| {
+                    self.clone()
+                        .fetch_and_apply(id, account_type.clone())
+                        .boxed()
+                        .shared()
+                })
+                .clone()
+        };
+        pending.await
+    }
+
+    async fn fetch_and_apply(self, id: EntityKey, account_type: String) -> BackfillOutcome {
+        let outcome = self.try_fetch_and_apply(&id.1, account_type).await;
+        match &outcome {
+            BackfillOutcome::Applied => debug!(entity = %id.0, key = %id.1, "Backfilled entity"),
+            BackfillOutcome::NotFound | BackfillOutcome::Failed(_) => {
+                if let BackfillOutcome::Failed(error) = &outcome {
+                    warn!(entity = %id.0, key = %id.1, %error, "Backfill failed");
+                }
+                let until = Instant::now() + self.inner.config.negative_ttl;
+                self.inner
+                    .negative
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(id.clone(), until);
+            }
+            BackfillOutcome::Skipped | BackfillOutcome::RateLimited => {}
+        }
+        self.inner
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        outcome
+    }
+
+    async fn try_fetch_and_apply(&self, address: &str, account_type: String) -> BackfillOutcome {
+        let allowed = self
+            .inner
+            .limiter
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .check_and_record(Instant::now());
+        if let RateLimitResult::Denied { .. } = allowed {
+            return BackfillOutcome::RateLimited;
+        }
+
+        let account = match self.get_account_info(address, account_type).await {
+            Ok(Some(account)) => account,
+            Ok(None) => return BackfillOutcome::NotFound,
+            Err(error) => return BackfillOutcome::Failed(format!("{:#}", error)),
+        };
+        let account_type = account.account_type.clone();
+
+        match (self.inner.apply)(account).await {
+            Ok(true) => BackfillOutcome::Applied,
+            Ok(false) => {
+                BackfillOutcome::Failed(format!("account could not be decoded as {}", account_type))
+            }
+            Err(error) => BackfillOutcome::Failed(format!("{:#}", error)),
+        }
+    }
+
+    async fn get_account_info(
+        &self,
+        address: &str,
+        account_type: String,
+    ) -> Result<Option<BackfillAccount>> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getAccountInfo",
+            "params": [
+                address,
+                { "encoding": "base64", "commitment": self.inner.config.commitment },
+            ],
+        });
+        let response: RpcResponse = self
+            .inner
+            .client
+            .post(&self.inner.config.rpc_url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if let Some(error) = response.error {
+            bail!("RPC error {}: {}", error.code, error.message);
+        }
+        let result = response
+            .result
+            .ok_or_else(|| anyhow!("RPC response has neither result nor error"))?;
+        let Some(value) = result.value else {
+            return Ok(None);
+        };
+
+        let (encoded, encoding) = value.data;
+        if encoding != "base64" {
+            bail!("unexpected account data encoding {}", encoding);
+        }
+        let data = base64::engine::general_purpose::STANDARD.decode(encoded)?;
+
+        Ok(Some(BackfillAccount {
+            account_type,
+            address: address.to_string(),
+            owner: value.owner,
+            lamports: value.lamports,
+            data,
+            executable: value.executable,
+            rent_epoch: value.rent_epoch,
+            slot: result.context.slot,
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    result: Option<AccountInfoResult>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct AccountInfoResult {
+    context: RpcContext,
+    value: Option<AccountInfo>,
+}
+
+#[derive(Deserialize)]
+struct RpcContext {
+    slot: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountInfo {
+    data: (String, String),
+    owner: String,
+    lamports: u64,
+    executable: bool,
+    #[serde(default)]
+    rent_epoch: u64,
+}
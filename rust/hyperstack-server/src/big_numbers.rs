@@ -0,0 +1,183 @@
+//! Encoding of 64-bit integers in the frames sent to clients.
+//!
+//! Entity data is encoded in the server's [`BigNumberMode`], `Auto` unless
+//! set with [`ServerBuilder::big_numbers`](crate::ServerBuilder::big_numbers),
+//! as frames are serialized: once per view for live patches, once per
+//! subscription for snapshots. `String` mode needs the fields typed as 64-bit
+//! integers, which are read from the bytecode of the spec being served.
+//!
+//! A client can ask for a stricter mode with `bigNumbers` in its subscribe
+//! message, e.g. a JavaScript client that wants one type per field. The
+//! frames of that subscription are re-encoded before they are sent, and the
+//! `subscribed` ack carries the mode it got. Asking for a looser mode than
+//! the server's gets the server's: integers already sent as strings can't be
+//! told apart from string fields. The Rust SDK reads integer fields in
+//! either representation.
+
+use crate::reload::Live;
+use hyperstack_interpreter::big_numbers::BigNumberMode;
+use hyperstack_interpreter::compiler::MultiEntityBytecode;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Fields typed as 64-bit integers, by entity
+type WideFields = HashMap<String, Arc<HashSet<String>>>;
+
+/// The server's integer encoding, shared by the projector and the
+/// WebSocket server
+#[derive(Clone)]
+pub struct BigNumbers {
+    mode: BigNumberMode,
+    fields: Live<WideFields>,
+}
+
+impl Default for BigNumbers {
+    fn default() -> Self {
+        Self::new(BigNumberMode::default())
+    }
+}
+
+impl BigNumbers {
+    pub fn new(mode: BigNumberMode) -> Self {
+        Self {
+            mode,
+            fields: Live::new(WideFields::new()),
+        }
+    }
+
+    pub fn mode(&self) -> BigNumberMode {
+        self.mode
+    }
+
+    /// Encode the entities of `bytecode` from now on
+    pub fn track(&self, bytecode: &MultiEntityBytecode) {
+        let fields = bytecode
+            .entities
+            .iter()
+            .map(|(name, entity)| (name.clone(), Arc::new(entity.wide_integer_fields.clone())))
+            .collect();
+        self.fields.store(Arc::new(fields));
+    }
+
+    /// Dotted paths of `entity`'s fields typed as 64-bit integers
+    pub fn fields(&self, entity: &str) -> Arc<HashSet<String>> {
+        self.fields.load().get(entity).cloned().unwrap_or_default()
+    }
+
+    /// Encode entity data of `entity` in the server's mode
+    pub fn encode(&self, entity: &str, data: &mut Value) {
+        match self.mode {
+            BigNumberMode::String => self.mode.apply(data, &self.fields(entity)),
+            mode => mode.apply(data, &HashSet::new()),
+        }
+    }
+
+    /// The mode a subscription asking for `requested` gets
+    pub fn negotiate(&self, requested: Option<BigNumberMode>) -> BigNumberMode {
+        requested.map_or(self.mode, |requested| requested.max(self.mode))
+    }
+}
+
+/// Encode the entity data of a serialized frame in `mode`.
+///
+/// Returns `None` for frames without entity data, which are sent unchanged.
+pub(crate) fn encode_frame(
+    mode: BigNumberMode,
+    wide_fields: &HashSet<String>,
+    frame: &[u8],
+) -> Option<Vec<u8>> {
+    let mut fields = serde_json::from_slice::<Map<String, Value>>(frame).ok()?;
+    match fields.get("op").and_then(Value::as_str)? {
+        "snapshot" | "history" => {
+            let Some(Value::Array(entities)) = fields.get_mut("data") else {
+                return None;
+            };
+            for entity in entities.iter_mut() {
+                if let Some(data) = entity.get_mut("data") {
+                    mode.apply(data, wide_fields);
+                }
+            }
+        }
+        "patch" | "upsert" | "create" => mode.apply(fields.get_mut("data")?, wide_fields),
+        _ => return None,
+    }
+    serde_json::to_vec(&fields).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_fields() -> HashSet<String> {
+        ["state.total_deployed".to_string()].into_iter().collect()
+    }
+
+    #[test]
+    fn subscriptions_get_the_stricter_of_their_mode_and_the_servers() {
+        let auto = BigNumbers::new(BigNumberMode::Auto);
+        assert_eq!(auto.negotiate(None), BigNumberMode::Auto);
+        assert_eq!(
+            auto.negotiate(Some(BigNumberMode::String)),
+            BigNumberMode::String
+        );
+        assert_eq!(
+            auto.negotiate(Some(BigNumberMode::Number)),
+            BigNumberMode::Auto
+        );
+        assert_eq!(
+            BigNumbers::new(BigNumberMode::Number).negotiate(Some(BigNumberMode::Number)),
+            BigNumberMode::Number
+        );
+    }
+
+    #[test]
+    fn frames_are_encoded_per_entity() {
+        let patch = json!({
+            "mode": "list",
+            "entity": "Round/list",
+            "op": "patch",
+            "key": "1",
+            "data": { "state": { "total_deployed": 5, "count": 3 } },
+        });
+        let encoded = encode_frame(
+            BigNumberMode::String,
+            &round_fields(),
+            &serde_json::to_vec(&patch).unwrap(),
+        )
+        .unwrap();
+        let encoded: Value = serde_json::from_slice(&encoded).unwrap();
+        assert_eq!(
+            encoded["data"],
+            json!({ "state": { "total_deployed": "5", "count": 3 } })
+        );
+
+        let snapshot = json!({
+            "mode": "list",
+            "entity": "Round/list",
+            "op": "snapshot",
+            "data": [
+                { "key": "1", "data": { "state": { "total_deployed": 5 } } },
+                { "key": "2", "data": { "state": { "total_deployed": 6 } } },
+            ],
+        });
+        let encoded = encode_frame(
+            BigNumberMode::String,
+            &round_fields(),
+            &serde_json::to_vec(&snapshot).unwrap(),
+        )
+        .unwrap();
+        let encoded: Value = serde_json::from_slice(&encoded).unwrap();
+        assert_eq!(encoded["data"][0]["data"]["state"]["total_deployed"], "5");
+        assert_eq!(encoded["data"][1]["data"]["state"]["total_deployed"], "6");
+
+        let delete = json!({ "op": "delete", "key": "1", "data": null });
+        assert!(encode_frame(
+            BigNumberMode::String,
+            &round_fields(),
+            &serde_json::to_vec(&delete).unwrap(),
+        )
+        .is_none());
+    }
+}
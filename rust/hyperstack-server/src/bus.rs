@@ -1,7 +1,12 @@
+use crate::append_log::{AppendLog, AppendLogConfig, AppendLogStats, Appended, LogLimits, LogRead};
+use crate::websocket::frame::HistoryItem;
+use crate::websocket::subscription::UpdateDelivery;
 use bytes::Bytes;
-use std::collections::HashMap;
+use dashmap::DashMap;
+use hyperstack_interpreter::clock::{system_clock, SharedClock};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
-use tokio::sync::{broadcast, watch, RwLock};
+use tokio::sync::{broadcast, watch, Mutex, RwLock};
 
 /// Message sent through the event bus
 #[derive(Debug, Clone)]
@@ -9,13 +14,60 @@ pub struct BusMessage {
     pub key: String,
     pub entity: String,
     pub payload: Arc<Bytes>,
+    /// `upsert` frame with the whole entity after the update, published
+    /// while the view has [`UpdateDelivery::FullState`] subscribers
+    pub full_state: Option<Arc<Bytes>>,
+    /// `_seq` of the update, for tracking how far subscribers got
+    pub seq: Option<Arc<str>>,
+}
+
+impl BusMessage {
+    pub fn new(key: String, entity: String, payload: Arc<Bytes>) -> Self {
+        Self {
+            key,
+            entity,
+            payload,
+            full_state: None,
+            seq: None,
+        }
+    }
+
+    pub fn with_full_state(mut self, full_state: Option<Arc<Bytes>>) -> Self {
+        self.full_state = full_state;
+        self
+    }
+
+    pub fn with_seq(mut self, seq: Option<&str>) -> Self {
+        self.seq = seq.map(Arc::from);
+        self
+    }
+
+    /// Frame to send a subscriber asking for `delivery`, falling back to the
+    /// patch for messages without a full state
+    pub fn payload_for(&self, delivery: UpdateDelivery) -> &Arc<Bytes> {
+        match (delivery, &self.full_state) {
+            (UpdateDelivery::FullState, Some(full_state)) => full_state,
+            _ => &self.payload,
+        }
+    }
+}
+
+impl Default for BusMessage {
+    fn default() -> Self {
+        Self::new(String::new(), String::new(), Arc::new(Bytes::new()))
+    }
 }
 
 #[derive(Clone)]
 #[allow(clippy::type_complexity)]
 pub struct BusManager {
-    state_buses: Arc<RwLock<HashMap<(String, String), watch::Sender<Arc<Bytes>>>>>,
+    state_buses: Arc<RwLock<HashMap<(String, String), watch::Sender<Arc<BusMessage>>>>>,
     list_buses: Arc<RwLock<HashMap<String, broadcast::Sender<Arc<BusMessage>>>>>,
+    append_logs: Arc<Mutex<HashMap<String, AppendLog>>>,
+    append_log_config: AppendLogConfig,
+    /// Live full-state subscriptions per view
+    full_state_interest: Arc<DashMap<String, usize>>,
+    clock: SharedClock,
     broadcast_capacity: usize,
 }
 
@@ -28,26 +80,39 @@ impl BusManager {
         Self {
             state_buses: Arc::new(RwLock::new(HashMap::new())),
             list_buses: Arc::new(RwLock::new(HashMap::new())),
+            append_logs: Arc::new(Mutex::new(HashMap::new())),
+            append_log_config: AppendLogConfig::default(),
+            full_state_interest: Arc::new(DashMap::new()),
+            clock: system_clock(),
             broadcast_capacity: capacity,
         }
     }
 
+    /// Cap the append log limits of every view with `config`
+    pub fn with_append_log(mut self, config: AppendLogConfig) -> Self {
+        self.append_log_config = config;
+        self
+    }
+
+    /// Read append log ages from `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Get or create a state bus (latest-value semantics)
     /// Each (view_id, key) pair gets its own watch channel
     pub async fn get_or_create_state_bus(
         &self,
         view_id: &str,
         key: &str,
-    ) -> watch::Receiver<Arc<Bytes>> {
+    ) -> watch::Receiver<Arc<BusMessage>> {
         let mut buses = self.state_buses.write().await;
         let entry = (view_id.to_string(), key.to_string());
 
         let tx = buses
             .entry(entry)
-            .or_insert_with(|| {
-                let empty = Arc::new(Bytes::new());
-                watch::channel(empty).0
-            })
+            .or_insert_with(|| watch::channel(Arc::new(BusMessage::default())).0)
             .clone();
 
         tx.subscribe()
@@ -68,10 +133,10 @@ impl BusManager {
     }
 
     /// Publish to a state bus (latest-value)
-    pub async fn publish_state(&self, view_id: &str, key: &str, frame: Arc<Bytes>) {
+    pub async fn publish_state(&self, view_id: &str, message: Arc<BusMessage>) {
         let buses = self.state_buses.read().await;
-        if let Some(tx) = buses.get(&(view_id.to_string(), key.to_string())) {
-            let _ = tx.send(frame);
+        if let Some(tx) = buses.get(&(view_id.to_string(), message.key.clone())) {
+            let _ = tx.send(message);
         }
     }
 
@@ -82,6 +147,118 @@ impl BusManager {
         }
     }
 
+    /// Publish to a list bus and append the item to the view's log.
+    ///
+    /// `limits` are capped by the bus's [`AppendLogConfig`]. Appending and
+    /// publishing happen under the log lock, so a concurrent
+    /// [`subscribe_list_with_history`](Self::subscribe_list_with_history)
+    /// sees the item either in its snapshot or on its receiver, never both.
+    pub async fn publish_list_with_history(
+        &self,
+        view_id: &str,
+        message: Arc<BusMessage>,
+        item: &HistoryItem,
+        limits: LogLimits,
+    ) -> serde_json::Result<Appended> {
+        let mut logs = self.append_logs.lock().await;
+        let appended = logs.entry(view_id.to_string()).or_default().append(
+            item,
+            limits.capped(&self.append_log_config),
+            self.clock.now_instant(),
+        );
+        self.publish_list(view_id, message).await;
+        appended
+    }
+
+    /// Subscribe to a list bus along with up to `n` recent items, oldest first.
+    pub async fn subscribe_list_with_history(
+        &self,
+        view_id: &str,
+        key: Option<&str>,
+        n: usize,
+    ) -> (broadcast::Receiver<Arc<BusMessage>>, Vec<HistoryItem>) {
+        let mut logs = self.append_logs.lock().await;
+        let rx = self.get_or_create_list_bus(view_id).await;
+        let items = logs
+            .get_mut(view_id)
+            .map(|log| {
+                log.recent(key, n, self.clock.now_instant())
+                    .iter()
+                    .filter_map(|entry| entry.item().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        (rx, items)
+    }
+
+    /// Subscribe to a list bus along with up to `limit` items published after
+    /// the item with `seq`, oldest first.
+    pub async fn subscribe_list_after(
+        &self,
+        view_id: &str,
+        key: Option<&str>,
+        seq: &str,
+        limit: usize,
+    ) -> (broadcast::Receiver<Arc<BusMessage>>, LogRead) {
+        let mut logs = self.append_logs.lock().await;
+        let rx = self.get_or_create_list_bus(view_id).await;
+        let read = logs
+            .get_mut(view_id)
+            .map(|log| log.read_after_seq(key, seq, limit, self.clock.now_instant()))
+            .unwrap_or_default();
+        (rx, read)
+    }
+
+    /// Whether a view's log still holds every item published after `seq`
+    pub async fn append_log_covers(&self, view_id: &str, key: Option<&str>, seq: &str) -> bool {
+        let mut logs = self.append_logs.lock().await;
+        logs.get_mut(view_id).is_none_or(|log| {
+            !log.read_after_seq(key, seq, 0, self.clock.now_instant())
+                .missed
+        })
+    }
+
+    /// Up to `limit` entries of a view's log with a cursor after `cursor`.
+    /// See [`AppendLog::read_after`].
+    pub async fn read_append_log(
+        &self,
+        view_id: &str,
+        key: Option<&str>,
+        cursor: u64,
+        limit: usize,
+    ) -> LogRead {
+        let mut logs = self.append_logs.lock().await;
+        logs.get_mut(view_id)
+            .map(|log| log.read_after(key, cursor, limit, self.clock.now_instant()))
+            .unwrap_or_default()
+    }
+
+    /// Retention of every view's append log
+    pub async fn append_log_stats(&self) -> BTreeMap<String, AppendLogStats> {
+        let now = self.clock.now_instant();
+        let mut logs = self.append_logs.lock().await;
+        logs.iter_mut()
+            .map(|(view_id, log)| (view_id.clone(), log.stats(now)))
+            .collect()
+    }
+
+    /// Ask for full-state frames on `view_id` until the returned guard drops
+    pub fn register_full_state(&self, view_id: &str) -> FullStateInterest {
+        *self
+            .full_state_interest
+            .entry(view_id.to_string())
+            .or_default() += 1;
+        FullStateInterest {
+            view_id: view_id.to_string(),
+            interest: self.full_state_interest.clone(),
+        }
+    }
+
+    /// Whether any subscription to `view_id` wants full-state frames
+    pub fn wants_full_state(&self, view_id: &str) -> bool {
+        self.full_state_interest.contains_key(view_id)
+    }
+
     pub async fn cleanup_stale_state_buses(&self) -> usize {
         let mut buses = self.state_buses.write().await;
         let before = buses.len();
@@ -107,6 +284,21 @@ impl BusManager {
     }
 }
 
+/// Registration of a full-state subscription, released on drop
+pub struct FullStateInterest {
+    view_id: String,
+    interest: Arc<DashMap<String, usize>>,
+}
+
+impl Drop for FullStateInterest {
+    fn drop(&mut self) {
+        self.interest.remove_if_mut(&self.view_id, |_, count| {
+            *count -= 1;
+            *count == 0
+        });
+    }
+}
+
 impl Default for BusManager {
     fn default() -> Self {
         Self::new()
@@ -3,18 +3,30 @@
 //! This module provides an `EntityCache` that maintains full projected entities
 //! in memory with LRU eviction. When a new client subscribes, they receive
 //! cached snapshots immediately rather than waiting for the next live mutation.
+//!
+//! Once a view reaches `max_entities_per_view`, every new entity evicts the
+//! least recently updated one. The cache reports this through
+//! [`RetentionNotice`]s so subscribers can tell their lists are truncated.
+//!
+//! With [`persistence`](crate::persistence) enabled the cache is saved in
+//! snapshots and restored from the latest one on startup.
 
+use crate::persistence::PersistedEntity;
+use hyperstack_interpreter::clock::{system_clock, SharedClock};
 use lru::LruCache;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
 const DEFAULT_MAX_ENTITIES_PER_VIEW: usize = 500;
 const DEFAULT_MAX_ARRAY_LENGTH: usize = 100;
 const DEFAULT_INITIAL_SNAPSHOT_BATCH_SIZE: usize = 50;
 const DEFAULT_SUBSEQUENT_SNAPSHOT_BATCH_SIZE: usize = 100;
+const DEFAULT_RETENTION_NOTICE_INTERVAL: Duration = Duration::from_secs(30);
 
 /// Compare two `_seq` values numerically.
 /// `_seq` format is "{slot}:{offset}" where slot is not zero-padded.
@@ -40,6 +52,8 @@ pub struct EntityCacheConfig {
     pub initial_snapshot_batch_size: usize,
     /// Number of entities to send in subsequent snapshot batches
     pub subsequent_snapshot_batch_size: usize,
+    /// Minimum time between retention notices while a view keeps evicting entities
+    pub retention_notice_interval: Duration,
 }
 
 impl Default for EntityCacheConfig {
@@ -49,6 +63,78 @@ impl Default for EntityCacheConfig {
             max_array_length: DEFAULT_MAX_ARRAY_LENGTH,
             initial_snapshot_batch_size: DEFAULT_INITIAL_SNAPSHOT_BATCH_SIZE,
             subsequent_snapshot_batch_size: DEFAULT_SUBSEQUENT_SNAPSHOT_BATCH_SIZE,
+            retention_notice_interval: DEFAULT_RETENTION_NOTICE_INTERVAL,
+        }
+    }
+}
+
+/// Sent to subscribers of a view whose cache is full and evicting entities.
+///
+/// Issued when eviction begins and then at most once per
+/// `retention_notice_interval` while evictions continue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetentionNotice {
+    /// Maximum number of entities the server retains for the view
+    pub cap: usize,
+    /// Evictions per second since the previous notice
+    pub eviction_rate: f64,
+    /// Unix time (ms) of the last update to the oldest retained entity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oldest_retained_at: Option<i64>,
+}
+
+struct CachedEntity {
+    data: Value,
+    updated_at: i64,
+}
+
+/// Entities cached for one view, plus its eviction bookkeeping
+struct ViewCache {
+    entities: LruCache<String, CachedEntity>,
+    evictions_since_notice: u64,
+    last_notice: Option<Instant>,
+    eviction_rate: f64,
+}
+
+impl ViewCache {
+    fn new(cap: NonZeroUsize) -> Self {
+        Self {
+            entities: LruCache::new(cap),
+            evictions_since_notice: 0,
+            last_notice: None,
+            eviction_rate: 0.0,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.entities.len() == self.entities.cap().get()
+    }
+
+    /// Count an eviction and return a notice if one is due
+    fn record_eviction(&mut self, now: Instant, interval: Duration) -> Option<RetentionNotice> {
+        self.evictions_since_notice += 1;
+
+        // The first eviction always produces a notice
+        let window = match self.last_notice {
+            Some(last) if now.duration_since(last) < interval => return None,
+            Some(last) => now.duration_since(last),
+            None => Duration::ZERO,
+        };
+
+        self.eviction_rate = self.evictions_since_notice as f64 / window.as_secs_f64().max(1.0);
+        self.evictions_since_notice = 0;
+        self.last_notice = Some(now);
+        Some(self.notice())
+    }
+
+    fn notice(&self) -> RetentionNotice {
+        RetentionNotice {
+            cap: self.entities.cap().get(),
+            eviction_rate: self.eviction_rate,
+            oldest_retained_at: self
+                .entities
+                .peek_lru()
+                .map(|(_, entity)| entity.updated_at),
         }
     }
 }
@@ -61,8 +147,9 @@ impl Default for EntityCacheConfig {
 #[derive(Clone)]
 pub struct EntityCache {
     /// view_id -> LRU<entity_key, full_projected_entity>
-    caches: Arc<RwLock<HashMap<String, LruCache<String, Value>>>>,
+    caches: Arc<RwLock<HashMap<String, ViewCache>>>,
     config: EntityCacheConfig,
+    clock: SharedClock,
 }
 
 impl EntityCache {
@@ -76,39 +163,128 @@ impl EntityCache {
         Self {
             caches: Arc::new(RwLock::new(HashMap::new())),
             config,
+            clock: system_clock(),
         }
     }
 
-    pub async fn upsert(&self, view_id: &str, key: &str, patch: Value) {
-        self.upsert_with_append(view_id, key, patch, &[]).await;
+    /// Read update times and retention intervals from `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub async fn upsert(&self, view_id: &str, key: &str, patch: Value) -> Option<RetentionNotice> {
+        self.upsert_with_append(view_id, key, patch, &[]).await
     }
 
+    /// Merge `patch` into the cached entity, inserting it if absent.
+    ///
+    /// Returns a [`RetentionNotice`] when the insert evicted an entity and a
+    /// notice is due for the view.
     pub async fn upsert_with_append(
         &self,
         view_id: &str,
         key: &str,
         patch: Value,
         append_paths: &[String],
-    ) {
+    ) -> Option<RetentionNotice> {
         let mut caches = self.caches.write().await;
 
-        let cache = caches.entry(view_id.to_string()).or_insert_with(|| {
-            LruCache::new(
-                NonZeroUsize::new(self.config.max_entities_per_view)
-                    .expect("max_entities_per_view must be > 0"),
-            )
-        });
+        let cache = caches
+            .entry(view_id.to_string())
+            .or_insert_with(|| self.new_view_cache());
 
         let max_array_length = self.config.max_array_length;
+        let updated_at = self.clock.unix_millis();
+
+        if let Some(entity) = cache.entities.get_mut(key) {
+            deep_merge_with_append(&mut entity.data, patch, append_paths, max_array_length);
+            entity.updated_at = updated_at;
+            return None;
+        }
 
-        if let Some(entity) = cache.get_mut(key) {
-            deep_merge_with_append(entity, patch, append_paths, max_array_length);
+        let data = truncate_arrays_if_needed(patch, max_array_length);
+        // The key is new, so anything handed back by push was evicted
+        let evicted = cache
+            .entities
+            .push(key.to_string(), CachedEntity { data, updated_at });
+        if evicted.is_some() {
+            cache.record_eviction(
+                self.clock.now_instant(),
+                self.config.retention_notice_interval,
+            )
         } else {
-            let new_entity = truncate_arrays_if_needed(patch, max_array_length);
-            cache.put(key.to_string(), new_entity);
+            None
         }
     }
 
+    fn new_view_cache(&self) -> ViewCache {
+        ViewCache::new(
+            NonZeroUsize::new(self.config.max_entities_per_view)
+                .expect("max_entities_per_view must be > 0"),
+        )
+    }
+
+    /// Every cached entity by view, least recently updated first
+    pub async fn export_entities(&self) -> BTreeMap<String, Vec<PersistedEntity>> {
+        let caches = self.caches.read().await;
+        caches
+            .iter()
+            .map(|(view_id, cache)| {
+                let entities = cache
+                    .entities
+                    .iter()
+                    .rev()
+                    .map(|(key, entity)| PersistedEntity {
+                        key: key.clone(),
+                        data: entity.data.clone(),
+                        updated_at: entity.updated_at,
+                    })
+                    .collect();
+                (view_id.clone(), entities)
+            })
+            .collect()
+    }
+
+    /// Insert `entities` exported by [`export_entities`](Self::export_entities)
+    /// into a view, keeping their update times and order. Replaces cached
+    /// entities with the same keys; evictions issue no retention notices.
+    pub async fn restore_entities(&self, view_id: &str, entities: Vec<PersistedEntity>) {
+        let mut caches = self.caches.write().await;
+        let cache = caches
+            .entry(view_id.to_string())
+            .or_insert_with(|| self.new_view_cache());
+        for entity in entities {
+            cache.entities.push(
+                entity.key,
+                CachedEntity {
+                    data: entity.data,
+                    updated_at: entity.updated_at,
+                },
+            );
+        }
+    }
+
+    /// Current retention state of a view, if its cache is at capacity.
+    ///
+    /// Included in subscription acks so clients learn about truncation
+    /// without waiting for the next notice.
+    pub async fn retention(&self, view_id: &str) -> Option<RetentionNotice> {
+        let caches = self.caches.read().await;
+        caches
+            .get(view_id)
+            .filter(|cache| cache.is_full())
+            .map(ViewCache::notice)
+    }
+
+    /// IDs of every view with cached entities, sorted
+    pub async fn view_ids(&self) -> Vec<String> {
+        let caches = self.caches.read().await;
+        let mut ids: Vec<String> = caches.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
     /// Get all cached entities for a view.
     ///
     /// Returns a vector of (key, entity) pairs for sending as snapshots
@@ -118,7 +294,13 @@ impl EntityCache {
 
         caches
             .get(view_id)
-            .map(|cache| cache.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+            .map(|cache| {
+                cache
+                    .entities
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.data.clone()))
+                    .collect()
+            })
             .unwrap_or_default()
     }
 
@@ -137,15 +319,17 @@ impl EntityCache {
 
         if let Some(cache) = caches.get(view_id) {
             let mut results: Vec<(String, Value)> = cache
+                .entities
                 .iter()
                 .filter(|(_, entity)| {
                     entity
+                        .data
                         .get("_seq")
                         .and_then(|s| s.as_str())
                         .map(|seq| cmp_seq(seq, cursor) == std::cmp::Ordering::Greater)
                         .unwrap_or(false)
                 })
-                .map(|(k, v)| (k.clone(), v.clone()))
+                .map(|(k, v)| (k.clone(), v.data.clone()))
                 .collect();
 
             // Sort by _seq (ascending)
@@ -171,13 +355,42 @@ impl EntityCache {
         let caches = self.caches.read().await;
         caches
             .get(view_id)
-            .and_then(|cache| cache.peek(key).cloned())
+            .and_then(|cache| cache.entities.peek(key))
+            .map(|entity| entity.data.clone())
+    }
+
+    /// Remove a deleted entity, returning it if it was cached
+    pub async fn remove(&self, view_id: &str, key: &str) -> Option<Value> {
+        let mut caches = self.caches.write().await;
+        caches
+            .get_mut(view_id)
+            .and_then(|cache| cache.entities.pop(key))
+            .map(|entity| entity.data)
+    }
+
+    /// Number of cached entities of a view updated after `cursor`, what
+    /// [`get_after`](Self::get_after) would return without a limit
+    pub async fn count_after(&self, view_id: &str, cursor: &str) -> usize {
+        let caches = self.caches.read().await;
+        caches.get(view_id).map_or(0, |cache| {
+            cache
+                .entities
+                .iter()
+                .filter(|(_, entity)| {
+                    entity
+                        .data
+                        .get("_seq")
+                        .and_then(|s| s.as_str())
+                        .is_some_and(|seq| cmp_seq(seq, cursor).is_gt())
+                })
+                .count()
+        })
     }
 
     /// Get the number of cached entities for a view
     pub async fn len(&self, view_id: &str) -> usize {
         let caches = self.caches.read().await;
-        caches.get(view_id).map(|c| c.len()).unwrap_or(0)
+        caches.get(view_id).map(|c| c.entities.len()).unwrap_or(0)
     }
 
     /// Check if the cache for a view is empty
@@ -197,7 +410,7 @@ impl EntityCache {
     pub async fn clear(&self, view_id: &str) {
         let mut caches = self.caches.write().await;
         if let Some(cache) = caches.get_mut(view_id) {
-            cache.clear();
+            *cache = ViewCache::new(cache.entities.cap());
         }
     }
 
@@ -212,7 +425,7 @@ impl EntityCache {
         let mut views = Vec::new();
 
         for (view_id, cache) in caches.iter() {
-            let count = cache.len();
+            let count = cache.entities.len();
             total_entities += count;
             views.push((view_id.clone(), count));
         }
@@ -225,6 +438,15 @@ impl EntityCache {
             top_views: views.into_iter().take(5).collect(),
         }
     }
+
+    /// Entities cached per view
+    pub async fn view_sizes(&self) -> BTreeMap<String, usize> {
+        let caches = self.caches.read().await;
+        caches
+            .iter()
+            .map(|(view_id, cache)| (view_id.clone(), cache.entities.len()))
+            .collect()
+    }
 }
 
 #[derive(Debug)]
@@ -246,6 +468,11 @@ impl Default for EntityCache {
     }
 }
 
+/// Merge `patch` into `base`. Objects keep their keys sorted, so an entity
+/// serializes the same whatever order its fields arrived in.
+///
+/// A field set to `null` in the patch is removed from `base`, while a field
+/// the patch leaves out is kept as it was. Handlers clear fields this way.
 fn deep_merge_with_append(
     base: &mut Value,
     patch: Value,
@@ -265,6 +492,11 @@ fn deep_merge_with_append_inner(
     match (base, patch) {
         (Value::Object(base_map), Value::Object(patch_map)) => {
             for (key, patch_value) in patch_map {
+                if patch_value.is_null() {
+                    base_map.remove(&key);
+                    continue;
+                }
+
                 let child_path = if current_path.is_empty() {
                     key.clone()
                 } else {
@@ -339,6 +571,8 @@ fn truncate_arrays_if_needed(value: Value, max_array_length: usize) -> Value {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use hyperstack_interpreter::clock::Clock;
+    use hyperstack_interpreter::testkit::ManualClock;
     use serde_json::json;
 
     #[tokio::test]
@@ -354,6 +588,33 @@ mod tests {
         assert_eq!(entity.unwrap()["name"], "Test Token");
     }
 
+    #[tokio::test]
+    async fn test_merged_entity_serializes_the_same_in_any_arrival_order() {
+        let patches = [
+            json!({"z": 1}),
+            json!({"a": {"y": 1}}),
+            json!({"a": {"b": 2}, "m": true}),
+        ];
+        let cache = EntityCache::new();
+        for patch in &patches {
+            cache.upsert("tokens/list", "abc123", patch.clone()).await;
+        }
+        let reversed = EntityCache::new();
+        for patch in patches.iter().rev() {
+            reversed
+                .upsert("tokens/list", "abc123", patch.clone())
+                .await;
+        }
+
+        let serialized = |entity: Option<Value>| serde_json::to_string(&entity.unwrap()).unwrap();
+        let entity = serialized(cache.get("tokens/list", "abc123").await);
+        assert_eq!(entity, r#"{"a":{"b":2,"y":1},"m":true,"z":1}"#);
+        assert_eq!(
+            serialized(reversed.get("tokens/list", "abc123").await),
+            entity
+        );
+    }
+
     #[tokio::test]
     async fn test_deep_merge_objects() {
         let cache = EntityCache::new();
@@ -505,6 +766,109 @@ mod tests {
         assert!(cache.get("tokens/list", "key3").await.is_some());
     }
 
+    #[tokio::test]
+    async fn test_retention_notice_when_eviction_begins() {
+        let config = EntityCacheConfig {
+            max_entities_per_view: 2,
+            retention_notice_interval: Duration::from_secs(3600),
+            ..Default::default()
+        };
+        let cache = EntityCache::with_config(config);
+
+        assert!(cache
+            .upsert("tokens/list", "key1", json!({"id": 1}))
+            .await
+            .is_none());
+        assert!(cache
+            .upsert("tokens/list", "key2", json!({"id": 2}))
+            .await
+            .is_none());
+        // Updating a cached entity never evicts
+        assert!(cache
+            .upsert("tokens/list", "key1", json!({"id": 1}))
+            .await
+            .is_none());
+
+        let notice = cache
+            .upsert("tokens/list", "key3", json!({"id": 3}))
+            .await
+            .expect("first eviction should produce a notice");
+        assert_eq!(notice.cap, 2);
+        assert_eq!(notice.eviction_rate, 1.0);
+        assert!(notice.oldest_retained_at.is_some());
+        assert!(cache.get("tokens/list", "key2").await.is_none());
+
+        // Further evictions inside the interval stay quiet
+        assert!(cache
+            .upsert("tokens/list", "key4", json!({"id": 4}))
+            .await
+            .is_none());
+        assert!(cache
+            .upsert("tokens/list", "key5", json!({"id": 5}))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_retention_notice_repeats_after_interval() {
+        let config = EntityCacheConfig {
+            max_entities_per_view: 1,
+            retention_notice_interval: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let clock = ManualClock::default();
+        let cache = EntityCache::with_config(config).with_clock(Arc::new(clock.clone()));
+
+        cache.upsert("tokens/list", "key1", json!({"id": 1})).await;
+        assert!(cache
+            .upsert("tokens/list", "key2", json!({"id": 2}))
+            .await
+            .is_some());
+        clock.advance(Duration::from_millis(49));
+        assert!(cache
+            .upsert("tokens/list", "key3", json!({"id": 3}))
+            .await
+            .is_none());
+
+        clock.advance(Duration::from_millis(1));
+
+        // Both evictions since the last notice are counted, over at least one second
+        let notice = cache
+            .upsert("tokens/list", "key4", json!({"id": 4}))
+            .await
+            .expect("notice should repeat once the interval has passed");
+        assert_eq!(notice.eviction_rate, 2.0);
+        assert_eq!(notice.oldest_retained_at, Some(clock.unix_millis()));
+
+        // Views track their notices independently
+        cache.upsert("games/list", "key1", json!({"id": 1})).await;
+        assert!(cache
+            .upsert("games/list", "key2", json!({"id": 2}))
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_retention_reported_only_at_capacity() {
+        let config = EntityCacheConfig {
+            max_entities_per_view: 2,
+            ..Default::default()
+        };
+        let cache = EntityCache::with_config(config);
+
+        cache.upsert("tokens/list", "key1", json!({"id": 1})).await;
+        assert!(cache.retention("tokens/list").await.is_none());
+        assert!(cache.retention("games/list").await.is_none());
+
+        cache.upsert("tokens/list", "key2", json!({"id": 2})).await;
+        let notice = cache.retention("tokens/list").await.unwrap();
+        assert_eq!(notice.cap, 2);
+        assert_eq!(notice.eviction_rate, 0.0);
+
+        cache.clear("tokens/list").await;
+        assert!(cache.retention("tokens/list").await.is_none());
+    }
+
     #[tokio::test]
     async fn test_get_all() {
         let cache = EntityCache::new();
@@ -574,6 +938,26 @@ mod tests {
         assert_eq!(base["arr"][1], 5);
     }
 
+    #[test]
+    fn test_deep_merge_null_removes_field() {
+        let mut base = json!({
+            "automation": {"executor": "abc", "amount": 3},
+            "status": "active"
+        });
+
+        let patch = json!({
+            "automation": {"executor": null},
+            "missing": null
+        });
+
+        deep_merge_with_append(&mut base, patch, &[], 100);
+
+        assert_eq!(
+            base,
+            json!({"automation": {"amount": 3}, "status": "active"})
+        );
+    }
+
     #[test]
     fn test_deep_merge_nested_append() {
         let mut base = json!({
@@ -655,6 +1039,12 @@ mod tests {
         assert_eq!(after.len(), 2);
         assert_eq!(after[0].0, "key3");
         assert_eq!(after[1].0, "key4");
+        assert_eq!(
+            cache.count_after("tokens/list", "100:000000000002").await,
+            2
+        );
+        assert_eq!(cache.count_after("tokens/list", "0:000000000000").await, 4);
+        assert_eq!(cache.count_after("other/list", "0:000000000000").await, 0);
     }
 
     #[tokio::test]
@@ -0,0 +1,328 @@
+//! Adaptive coalescing window for the projector.
+//!
+//! With coalescing enabled the projector holds the mutation batches that
+//! arrive within a window and merges patches to the same entity before
+//! publishing, so a hot entity produces one frame per window instead of one
+//! per update. A short window keeps latency low when traffic is light; a long
+//! one cuts the frame rate when traffic is heavy.
+//!
+//! [`AdaptiveWindow`] moves between the two bounds of a [`CoalesceConfig`]
+//! after every pass:
+//!
+//! - it grows (doubles) while the projector is busy or client send queues are
+//!   filling up, as long as a pass of twice the size would still fit the
+//!   processing budget;
+//! - it shrinks (halves) while the projector is mostly idle and client queues
+//!   are drained, or when a pass overran the budget.
+//!
+//! Between those conditions the window holds, and a change needs
+//! [`CoalesceConfig::hysteresis`] consecutive passes voting the same way, so
+//! bursty traffic does not make the window flap.
+
+use std::time::Duration;
+
+/// Smallest step the window grows by when the minimum is zero
+const MIN_STEP: Duration = Duration::from_millis(1);
+
+/// Configuration for the projector's coalescing window
+#[derive(Debug, Clone)]
+pub struct CoalesceConfig {
+    /// Shortest window, used while the projector keeps up easily
+    pub min_window: Duration,
+    /// Longest window, used under sustained pressure
+    pub max_window: Duration,
+    /// Target processing time for one coalesced pass
+    pub batch_budget: Duration,
+    /// Consecutive passes voting the same way before the window changes
+    pub hysteresis: u32,
+    /// Client queue fill ratio at or above which the window grows
+    pub queue_high_watermark: f64,
+    /// Client queue fill ratio at or below which the window may shrink
+    pub queue_low_watermark: f64,
+}
+
+impl Default for CoalesceConfig {
+    fn default() -> Self {
+        Self {
+            min_window: Duration::from_millis(1),
+            max_window: Duration::from_millis(50),
+            batch_budget: Duration::from_millis(10),
+            hysteresis: 3,
+            queue_high_watermark: 0.5,
+            queue_low_watermark: 0.1,
+        }
+    }
+}
+
+impl CoalesceConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Always coalesce over `window`, regardless of load
+    pub fn fixed(window: Duration) -> Self {
+        Self::default().with_window_bounds(window, window)
+    }
+
+    pub fn with_window_bounds(mut self, min: Duration, max: Duration) -> Self {
+        self.min_window = min;
+        self.max_window = max.max(min);
+        self
+    }
+
+    pub fn with_batch_budget(mut self, budget: Duration) -> Self {
+        self.batch_budget = budget;
+        self
+    }
+
+    pub fn with_hysteresis(mut self, passes: u32) -> Self {
+        self.hysteresis = passes.max(1);
+        self
+    }
+
+    pub fn with_queue_watermarks(mut self, low: f64, high: f64) -> Self {
+        self.queue_low_watermark = low;
+        self.queue_high_watermark = high.max(low);
+        self
+    }
+}
+
+/// What the projector observed during one coalesced pass
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PassStats {
+    /// Time spent processing the pass
+    pub busy: Duration,
+    /// Time spent waiting for the first batch of the pass
+    pub idle: Duration,
+    /// Fill ratio of the fullest client send queue after the pass
+    pub queue_pressure: f64,
+}
+
+impl PassStats {
+    /// Share of the time the projector was working rather than waiting
+    pub fn utilization(&self) -> f64 {
+        let total = self.busy + self.idle;
+        if total.is_zero() {
+            return 0.0;
+        }
+        self.busy.as_secs_f64() / total.as_secs_f64()
+    }
+}
+
+/// Utilization at or above which the projector counts as saturated
+const BUSY_UTILIZATION: f64 = 0.8;
+/// Utilization at or below which the projector counts as idle
+const IDLE_UTILIZATION: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Vote {
+    Grow,
+    Shrink,
+    Hold,
+}
+
+/// Coalescing window that adapts to downstream pressure
+#[derive(Debug, Clone)]
+pub struct AdaptiveWindow {
+    config: CoalesceConfig,
+    current: Duration,
+    streak: (Vote, u32),
+}
+
+impl AdaptiveWindow {
+    /// Start at the minimum window
+    pub fn new(config: CoalesceConfig) -> Self {
+        Self {
+            current: config.min_window,
+            config,
+            streak: (Vote::Hold, 0),
+        }
+    }
+
+    /// The window the next pass should gather batches for
+    pub fn current(&self) -> Duration {
+        self.current
+    }
+
+    pub fn config(&self) -> &CoalesceConfig {
+        &self.config
+    }
+
+    /// Feed the stats of a finished pass and return the next window
+    pub fn observe(&mut self, stats: PassStats) -> Duration {
+        let vote = self.vote(&stats);
+        self.streak = match self.streak {
+            (previous, count) if previous == vote => (vote, count.saturating_add(1)),
+            _ => (vote, 1),
+        };
+
+        if self.streak.1 >= self.config.hysteresis {
+            let next = match vote {
+                Vote::Grow => self.grown(),
+                Vote::Shrink => self.shrunk(),
+                Vote::Hold => self.current,
+            };
+            if next != self.current {
+                self.current = next;
+                self.streak = (Vote::Hold, 0);
+            }
+        }
+        self.current
+    }
+
+    fn vote(&self, stats: &PassStats) -> Vote {
+        let config = &self.config;
+        let utilization = stats.utilization();
+
+        if stats.busy > config.batch_budget {
+            return Vote::Shrink;
+        }
+        let pressured =
+            utilization >= BUSY_UTILIZATION || stats.queue_pressure >= config.queue_high_watermark;
+        if pressured && stats.busy * 2 <= config.batch_budget {
+            return Vote::Grow;
+        }
+        let relaxed =
+            utilization <= IDLE_UTILIZATION && stats.queue_pressure <= config.queue_low_watermark;
+        if relaxed {
+            return Vote::Shrink;
+        }
+        Vote::Hold
+    }
+
+    fn grown(&self) -> Duration {
+        (self.current * 2)
+            .max(MIN_STEP)
+            .clamp(self.config.min_window, self.config.max_window)
+    }
+
+    fn shrunk(&self) -> Duration {
+        let half = self.current / 2;
+        if half < MIN_STEP {
+            return self.config.min_window;
+        }
+        half.clamp(self.config.min_window, self.config.max_window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CoalesceConfig {
+        CoalesceConfig::new()
+            .with_window_bounds(Duration::from_millis(1), Duration::from_millis(16))
+            .with_batch_budget(Duration::from_millis(10))
+            .with_hysteresis(3)
+    }
+
+    fn busy() -> PassStats {
+        PassStats {
+            busy: Duration::from_millis(2),
+            idle: Duration::ZERO,
+            queue_pressure: 0.0,
+        }
+    }
+
+    fn idle() -> PassStats {
+        PassStats {
+            busy: Duration::from_micros(100),
+            idle: Duration::from_millis(20),
+            queue_pressure: 0.0,
+        }
+    }
+
+    #[test]
+    fn grows_to_the_maximum_under_sustained_load() {
+        let mut window = AdaptiveWindow::new(config());
+        assert_eq!(window.current(), Duration::from_millis(1));
+
+        for _ in 0..2 {
+            assert_eq!(window.observe(busy()), Duration::from_millis(1));
+        }
+        assert_eq!(window.observe(busy()), Duration::from_millis(2));
+
+        for _ in 0..20 {
+            window.observe(busy());
+        }
+        assert_eq!(window.current(), Duration::from_millis(16));
+    }
+
+    #[test]
+    fn shrinks_back_to_the_minimum_when_idle() {
+        let mut window = AdaptiveWindow::new(config());
+        for _ in 0..20 {
+            window.observe(busy());
+        }
+        for _ in 0..20 {
+            window.observe(idle());
+        }
+        assert_eq!(window.current(), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn alternating_load_does_not_move_the_window() {
+        let mut window = AdaptiveWindow::new(config());
+        for _ in 0..6 {
+            window.observe(busy());
+        }
+        let settled = window.current();
+
+        for _ in 0..20 {
+            window.observe(busy());
+            window.observe(idle());
+        }
+        assert_eq!(window.current(), settled);
+    }
+
+    #[test]
+    fn client_queue_pressure_grows_an_idle_projector() {
+        let mut window = AdaptiveWindow::new(config());
+        let backed_up = PassStats {
+            queue_pressure: 0.9,
+            ..idle()
+        };
+        for _ in 0..3 {
+            window.observe(backed_up);
+        }
+        assert_eq!(window.current(), Duration::from_millis(2));
+    }
+
+    #[test]
+    fn stops_growing_before_passes_exceed_the_budget() {
+        let mut window = AdaptiveWindow::new(config());
+        let heavy = PassStats {
+            busy: Duration::from_millis(6),
+            ..busy()
+        };
+        for _ in 0..20 {
+            window.observe(heavy);
+        }
+        assert_eq!(window.current(), Duration::from_millis(1));
+
+        let over_budget = PassStats {
+            busy: Duration::from_millis(12),
+            ..busy()
+        };
+        let mut window = AdaptiveWindow::new(config());
+        for _ in 0..20 {
+            window.observe(busy());
+        }
+        for _ in 0..3 {
+            window.observe(over_budget);
+        }
+        assert_eq!(window.current(), Duration::from_millis(8));
+    }
+
+    #[test]
+    fn fixed_window_never_moves() {
+        let mut window = AdaptiveWindow::new(CoalesceConfig::fixed(Duration::from_millis(5)));
+        for _ in 0..10 {
+            window.observe(busy());
+        }
+        for _ in 0..10 {
+            window.observe(idle());
+        }
+        assert_eq!(window.current(), Duration::from_millis(5));
+    }
+}
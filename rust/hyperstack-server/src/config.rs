@@ -1,8 +1,24 @@
+use crate::websocket::{InboundLimits, SessionConfig, SnapshotQueueConfig};
+use hyperstack_interpreter::big_numbers::BigNumberMode;
+use hyperstack_interpreter::clock::{system_clock, SharedClock};
 use std::net::SocketAddr;
 use std::time::Duration;
 
+pub use crate::append_log::AppendLogConfig;
+pub use crate::backfill::RpcBackfillConfig;
+pub use crate::coalesce::CoalesceConfig;
+pub use crate::dead_letter::DeadLetterConfig;
+pub use crate::debug_bundle::DebugBundleConfig;
+pub use crate::dictionary::DictionarySource;
+pub use crate::flags::FlagsConfig;
+#[cfg(feature = "grpc")]
+pub use crate::grpc::GrpcConfig;
 pub use crate::health::HealthConfig;
 pub use crate::http_health::HttpHealthConfig;
+pub use crate::load_shed::LoadShedConfig;
+pub use crate::materialized_view::ViewBudget;
+pub use crate::persistence::PersistenceConfig;
+pub use crate::webhook::WebhookConfig;
 
 /// Configuration for gRPC stream reconnection with exponential backoff
 #[derive(Clone, Debug)]
@@ -73,32 +89,125 @@ impl ReconnectionConfig {
 #[derive(Clone, Debug)]
 pub struct WebSocketConfig {
     pub bind_address: SocketAddr,
+    /// Largest message accepted from a client, in bytes (None = codec default).
+    /// Checked against the frame header before the payload is read.
+    pub max_inbound_message_bytes: Option<usize>,
+    /// Sustained messages per second accepted from a client (None = unlimited)
+    pub max_inbound_messages_per_sec: Option<u32>,
+    /// Largest frame sent to a client, in bytes before compression
+    /// (None = unlimited). Larger frames are split, see
+    /// [`frame_size`](crate::websocket::frame_size).
+    pub max_frame_bytes: Option<usize>,
+    /// How many list snapshots are built at once and how many may wait, see
+    /// [`snapshot_queue`](crate::websocket::snapshot_queue)
+    pub snapshot_queue: SnapshotQueueConfig,
+    /// Keep disconnected clients' subscriptions so they can resume them
+    /// (None = disabled), see [`session`](crate::websocket::session)
+    pub sessions: Option<SessionConfig>,
+    /// Entities per snapshot frame (None = the entity cache's batch sizes,
+    /// 50 in the first frame and 100 after). Each frame carries its `page`
+    /// and `total_pages`, and the last one `complete`.
+    pub snapshot_page_size: Option<usize>,
 }
 
 impl Default for WebSocketConfig {
     fn default() -> Self {
-        Self {
-            bind_address: "[::]:8877".parse().expect("valid socket address"),
-        }
+        Self::new(
+            "[::]:8877"
+                .parse::<SocketAddr>()
+                .expect("valid socket address"),
+        )
     }
 }
 
 impl WebSocketConfig {
     pub fn new(bind_address: impl Into<SocketAddr>) -> Self {
+        let inbound = InboundLimits::default();
         Self {
             bind_address: bind_address.into(),
+            max_inbound_message_bytes: inbound.max_message_bytes,
+            max_inbound_messages_per_sec: inbound.max_messages_per_sec,
+            max_frame_bytes: None,
+            snapshot_queue: SnapshotQueueConfig::default(),
+            sessions: None,
+            snapshot_page_size: None,
+        }
+    }
+
+    pub fn with_max_inbound_message_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_inbound_message_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_max_inbound_messages_per_sec(mut self, max_per_sec: Option<u32>) -> Self {
+        self.max_inbound_messages_per_sec = max_per_sec;
+        self
+    }
+
+    pub fn with_max_frame_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_frame_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_snapshot_queue(mut self, config: SnapshotQueueConfig) -> Self {
+        self.snapshot_queue = config;
+        self
+    }
+
+    pub fn with_sessions(mut self, config: SessionConfig) -> Self {
+        self.sessions = Some(config);
+        self
+    }
+
+    pub fn with_snapshot_page_size(mut self, page_size: Option<usize>) -> Self {
+        self.snapshot_page_size = page_size;
+        self
+    }
+
+    /// The per-client limits enforced on inbound messages
+    pub fn inbound_limits(&self) -> InboundLimits {
+        InboundLimits {
+            max_message_bytes: self.max_inbound_message_bytes,
+            max_messages_per_sec: self.max_inbound_messages_per_sec,
         }
     }
 }
 
-/// Yellowstone gRPC configuration
-#[derive(Clone, Debug)]
-pub struct YellowstoneConfig {
+/// How the runtime consumes a list of Yellowstone endpoints
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum YellowstoneStrategy {
+    /// Consume from one endpoint at a time, moving to the next one in the
+    /// list whenever the current stream ends
+    #[default]
+    Failover,
+    /// Consume from every endpoint at once and drop the updates already
+    /// seen from another endpoint
+    Race,
+}
+
+impl std::str::FromStr for YellowstoneStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "failover" => Ok(Self::Failover),
+            "race" => Ok(Self::Race),
+            other => Err(anyhow::anyhow!(
+                "Unknown Yellowstone strategy '{}' (expected 'failover' or 'race')",
+                other
+            )),
+        }
+    }
+}
+
+/// A single Yellowstone gRPC endpoint
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct YellowstoneEndpoint {
     pub endpoint: String,
     pub x_token: Option<String>,
 }
 
-impl YellowstoneConfig {
+impl YellowstoneEndpoint {
     pub fn new(endpoint: impl Into<String>) -> Self {
         Self {
             endpoint: endpoint.into(),
@@ -112,14 +221,155 @@ impl YellowstoneConfig {
     }
 }
 
+/// Default number of recently seen updates remembered when racing endpoints
+pub const DEFAULT_DEDUP_CAPACITY: usize = 100_000;
+
+/// Yellowstone gRPC configuration
+///
+/// Endpoints are listed in priority order. With
+/// [`YellowstoneStrategy::Failover`] the first endpoint is the primary.
+#[derive(Clone, Debug)]
+pub struct YellowstoneConfig {
+    pub endpoints: Vec<YellowstoneEndpoint>,
+    pub strategy: YellowstoneStrategy,
+    /// Updates remembered per kind (accounts, instructions) for deduplication
+    /// when racing
+    pub dedup_capacity: usize,
+}
+
+impl YellowstoneConfig {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoints: vec![YellowstoneEndpoint::new(endpoint)],
+            strategy: YellowstoneStrategy::default(),
+            dedup_capacity: DEFAULT_DEDUP_CAPACITY,
+        }
+    }
+
+    /// Set the x-token of the most recently added endpoint
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        if let Some(last) = self.endpoints.last_mut() {
+            last.x_token = Some(token.into());
+        }
+        self
+    }
+
+    /// Add a fallback endpoint
+    pub fn with_endpoint(mut self, endpoint: YellowstoneEndpoint) -> Self {
+        self.endpoints.push(endpoint);
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: YellowstoneStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_dedup_capacity(mut self, capacity: usize) -> Self {
+        self.dedup_capacity = capacity;
+        self
+    }
+
+    /// Read the configuration from the environment.
+    ///
+    /// `YELLOWSTONE_ENDPOINTS` takes a comma-separated list and falls back to
+    /// `YELLOWSTONE_ENDPOINT`. `YELLOWSTONE_X_TOKENS` gives one token per
+    /// endpoint, in the same order. Otherwise `YELLOWSTONE_X_TOKEN` is used for
+    /// every endpoint. `YELLOWSTONE_STRATEGY` is `failover` (the default) or
+    /// `race`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let split = |value: String| -> Vec<String> {
+            value
+                .split(',')
+                .map(|part| part.trim().to_string())
+                .collect()
+        };
+
+        let urls: Vec<String> = lookup("YELLOWSTONE_ENDPOINTS")
+            .map(split)
+            .or_else(|| lookup("YELLOWSTONE_ENDPOINT").map(|url| vec![url.trim().to_string()]))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|url| !url.is_empty())
+            .collect();
+
+        if urls.is_empty() {
+            return Err(anyhow::anyhow!(
+                "YELLOWSTONE_ENDPOINT (or YELLOWSTONE_ENDPOINTS) environment variable must be set.\n\
+                 Example: export YELLOWSTONE_ENDPOINT=http://localhost:10000"
+            ));
+        }
+
+        let tokens = lookup("YELLOWSTONE_X_TOKENS").map(split);
+        let shared_token = lookup("YELLOWSTONE_X_TOKEN");
+
+        let endpoints = urls
+            .into_iter()
+            .enumerate()
+            .map(|(i, endpoint)| {
+                let x_token = match &tokens {
+                    Some(tokens) => tokens.get(i).filter(|t| !t.is_empty()).cloned(),
+                    None => shared_token.clone(),
+                };
+                YellowstoneEndpoint { endpoint, x_token }
+            })
+            .collect();
+
+        let strategy = match lookup("YELLOWSTONE_STRATEGY") {
+            Some(value) => value.parse()?,
+            None => YellowstoneStrategy::default(),
+        };
+
+        Ok(Self {
+            endpoints,
+            strategy,
+            dedup_capacity: DEFAULT_DEDUP_CAPACITY,
+        })
+    }
+}
+
 /// Main server configuration
 #[derive(Clone, Debug, Default)]
 pub struct ServerConfig {
     pub websocket: Option<WebSocketConfig>,
+    /// gRPC streaming endpoint, serving the same views as the WebSocket server
+    #[cfg(feature = "grpc")]
+    pub grpc: Option<GrpcConfig>,
     pub yellowstone: Option<YellowstoneConfig>,
     pub health: Option<HealthConfig>,
     pub http_health: Option<HttpHealthConfig>,
     pub reconnection: Option<ReconnectionConfig>,
+    /// Projector coalescing window, every batch is published on its own when unset
+    pub coalesce: Option<CoalesceConfig>,
+    /// RPC backfill for state subscriptions to keys the server has never seen
+    pub backfill: Option<RpcBackfillConfig>,
+    /// Server-wide caps on the append logs of `Append` views
+    pub append_log: Option<AppendLogConfig>,
+    /// Evaluation budget of derived views that don't set their own
+    pub view_budget: Option<ViewBudget>,
+    /// Shed low-priority entity updates under memory or queue pressure
+    pub load_shedding: Option<LoadShedConfig>,
+    /// Size limits of debug bundles, the defaults when unset
+    pub debug_bundle: Option<DebugBundleConfig>,
+    /// Webhooks posted when updated entities match a predicate
+    pub webhooks: Option<WebhookConfig>,
+    /// Keep the events the VM fails to process, dropped when unset
+    pub dead_letter: Option<DeadLetterConfig>,
+    /// How 64-bit integers are encoded in the frames sent to clients
+    pub big_numbers: BigNumberMode,
+    /// Offer clients zstd with a dictionary from this source, gzip only when
+    /// unset
+    pub compression_dictionary: Option<DictionarySource>,
+    /// Initial feature flags and who may change them on `/admin/flags`
+    pub flags: FlagsConfig,
+    /// Snapshot the entity cache and VM state, and restore them on startup
+    pub persistence: Option<PersistenceConfig>,
+    /// Time source for caches and health monitoring, the system clock when unset
+    pub clock: Option<SharedClock>,
 }
 
 impl ServerConfig {
@@ -132,6 +382,12 @@ impl ServerConfig {
         self
     }
 
+    #[cfg(feature = "grpc")]
+    pub fn with_grpc(mut self, config: GrpcConfig) -> Self {
+        self.grpc = Some(config);
+        self
+    }
+
     pub fn with_yellowstone(mut self, config: YellowstoneConfig) -> Self {
         self.yellowstone = Some(config);
         self
@@ -151,4 +407,135 @@ impl ServerConfig {
         self.reconnection = Some(config);
         self
     }
+
+    pub fn with_coalesce(mut self, config: CoalesceConfig) -> Self {
+        self.coalesce = Some(config);
+        self
+    }
+
+    pub fn with_backfill(mut self, config: RpcBackfillConfig) -> Self {
+        self.backfill = Some(config);
+        self
+    }
+
+    pub fn with_append_log(mut self, config: AppendLogConfig) -> Self {
+        self.append_log = Some(config);
+        self
+    }
+
+    pub fn with_view_budget(mut self, budget: ViewBudget) -> Self {
+        self.view_budget = Some(budget);
+        self
+    }
+
+    pub fn with_load_shedding(mut self, config: LoadShedConfig) -> Self {
+        self.load_shedding = Some(config);
+        self
+    }
+
+    pub fn with_debug_bundle(mut self, config: DebugBundleConfig) -> Self {
+        self.debug_bundle = Some(config);
+        self
+    }
+
+    pub fn with_webhooks(mut self, config: WebhookConfig) -> Self {
+        self.webhooks = Some(config);
+        self
+    }
+
+    pub fn with_dead_letter(mut self, config: DeadLetterConfig) -> Self {
+        self.dead_letter = Some(config);
+        self
+    }
+
+    pub fn with_big_numbers(mut self, mode: BigNumberMode) -> Self {
+        self.big_numbers = mode;
+        self
+    }
+
+    pub fn with_compression_dictionary(mut self, source: DictionarySource) -> Self {
+        self.compression_dictionary = Some(source);
+        self
+    }
+
+    pub fn with_flags(mut self, config: FlagsConfig) -> Self {
+        self.flags = config;
+        self
+    }
+
+    pub fn with_persistence(mut self, config: PersistenceConfig) -> Self {
+        self.persistence = Some(config);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// The configured clock, or the system clock
+    pub fn clock(&self) -> SharedClock {
+        self.clock.clone().unwrap_or_else(system_clock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config_from(vars: &[(&str, &str)]) -> anyhow::Result<YellowstoneConfig> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        YellowstoneConfig::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn single_endpoint_env_keeps_working() {
+        let config = config_from(&[
+            ("YELLOWSTONE_ENDPOINT", "http://primary:10000"),
+            ("YELLOWSTONE_X_TOKEN", "secret"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.endpoints,
+            vec![YellowstoneEndpoint::new("http://primary:10000").with_token("secret")]
+        );
+        assert_eq!(config.strategy, YellowstoneStrategy::Failover);
+    }
+
+    #[test]
+    fn endpoint_list_pairs_tokens_by_position() {
+        let config = config_from(&[
+            (
+                "YELLOWSTONE_ENDPOINTS",
+                "http://primary:10000, http://backup:10000",
+            ),
+            ("YELLOWSTONE_X_TOKENS", ",backup-token"),
+            ("YELLOWSTONE_STRATEGY", "Race"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.endpoints,
+            vec![
+                YellowstoneEndpoint::new("http://primary:10000"),
+                YellowstoneEndpoint::new("http://backup:10000").with_token("backup-token"),
+            ]
+        );
+        assert_eq!(config.strategy, YellowstoneStrategy::Race);
+    }
+
+    #[test]
+    fn missing_endpoint_or_unknown_strategy_is_an_error() {
+        assert!(config_from(&[]).is_err());
+        assert!(config_from(&[
+            ("YELLOWSTONE_ENDPOINT", "http://primary:10000"),
+            ("YELLOWSTONE_STRATEGY", "roundrobin"),
+        ])
+        .is_err());
+    }
 }
@@ -0,0 +1,303 @@
+//! Dead-letter queue of the events the VM failed to process.
+//!
+//! Without it, an event whose handler errors is dropped and only shows in the
+//! [`HealthMonitor`](crate::HealthMonitor)'s error counts. Enabled with
+//! [`ServerBuilder::dead_letter`](crate::ServerBuilder::dead_letter), every
+//! failed event is kept as a [`FailedEvent`], with the decoded event, so it can
+//! be inspected or replayed:
+//!
+//! - `GET /admin/failed-events` lists the retained events, oldest first
+//! - `POST /admin/failed-events` lists them and empties the queue
+//! - [`DeadLetterConfig::on_failed_event`] is called with each one as it fails
+//!
+//! The queue keeps the last [`DeadLetterConfig::capacity`] events, dropping
+//! the oldest. Each one is retained, but failures are logged at most once per
+//! [`DeadLetterConfig::log_interval`] for each event type, with the number of
+//! failures left out of the log since.
+
+use hyperstack_interpreter::clock::{system_clock, SharedClock};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::error;
+
+/// Default number of failed events retained
+pub const DEFAULT_FAILED_EVENTS: usize = 1024;
+
+/// Default interval between two logs of the same event type's failures
+pub const DEFAULT_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Called with each event as it fails
+pub type FailedEventFn = Arc<dyn Fn(FailedEvent) + Send + Sync>;
+
+/// An event the VM failed to process
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedEvent {
+    pub event_type: String,
+    /// The decoded account or instruction, as handed to the VM
+    pub event_value: Value,
+    pub error: String,
+    pub slot: u64,
+    pub signature: Option<String>,
+    pub failed_at_ms: i64,
+}
+
+impl FailedEvent {
+    pub fn new(
+        event_type: impl Into<String>,
+        event_value: Value,
+        error: impl fmt::Display,
+        slot: u64,
+        signature: Option<String>,
+    ) -> Self {
+        Self {
+            event_type: event_type.into(),
+            event_value,
+            error: error.to_string(),
+            slot,
+            signature,
+            failed_at_ms: 0,
+        }
+    }
+}
+
+/// How many failed events are retained and how often they are logged
+#[derive(Clone)]
+pub struct DeadLetterConfig {
+    pub capacity: usize,
+    /// Interval between two logs of the same event type's failures
+    pub log_interval: Duration,
+    pub on_failed_event: Option<FailedEventFn>,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_FAILED_EVENTS,
+            log_interval: DEFAULT_LOG_INTERVAL,
+            on_failed_event: None,
+        }
+    }
+}
+
+impl fmt::Debug for DeadLetterConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeadLetterConfig")
+            .field("capacity", &self.capacity)
+            .field("log_interval", &self.log_interval)
+            .field("on_failed_event", &self.on_failed_event.is_some())
+            .finish()
+    }
+}
+
+impl DeadLetterConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn with_log_interval(mut self, interval: Duration) -> Self {
+        self.log_interval = interval;
+        self
+    }
+
+    /// Call `hook` with each event as it fails, on the parser's task, so it
+    /// should return quickly
+    pub fn on_failed_event(mut self, hook: impl Fn(FailedEvent) + Send + Sync + 'static) -> Self {
+        self.on_failed_event = Some(Arc::new(hook));
+        self
+    }
+}
+
+/// What `/admin/failed-events` reports
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedEventsReport {
+    pub capacity: usize,
+    /// Events dropped to make room for newer ones
+    pub dropped: u64,
+    /// Oldest first
+    pub events: Vec<FailedEvent>,
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<FailedEvent>,
+    dropped: u64,
+    /// When each event type was last logged, and its failures since
+    logged: HashMap<String, (Instant, u64)>,
+}
+
+/// The failed events of a server, shared by the parser and the HTTP server
+#[derive(Clone)]
+pub struct FailedEvents {
+    queue: Arc<Mutex<Queue>>,
+    config: DeadLetterConfig,
+    clock: SharedClock,
+}
+
+impl FailedEvents {
+    pub fn new(config: DeadLetterConfig) -> Self {
+        Self {
+            queue: Arc::new(Mutex::new(Queue::default())),
+            config,
+            clock: system_clock(),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Retain `event`, dropping the oldest one when full, and hand it to the
+    /// hook
+    pub fn record(&self, mut event: FailedEvent) {
+        event.failed_at_ms = self.clock.unix_millis();
+        let now = self.clock.now_instant();
+        {
+            let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+            let suppressed = match queue.logged.get_mut(&event.event_type) {
+                Some((logged_at, suppressed))
+                    if now.duration_since(*logged_at) < self.config.log_interval =>
+                {
+                    *suppressed += 1;
+                    None
+                }
+                Some((logged_at, suppressed)) => {
+                    *logged_at = now;
+                    Some(std::mem::take(suppressed))
+                }
+                None => {
+                    queue.logged.insert(event.event_type.clone(), (now, 0));
+                    Some(0)
+                }
+            };
+            if let Some(suppressed) = suppressed {
+                error!(
+                    event_type = %event.event_type,
+                    slot = event.slot,
+                    signature = event.signature.as_deref().unwrap_or_default(),
+                    suppressed,
+                    "Failed to process event: {}",
+                    event.error
+                );
+            }
+
+            if self.config.capacity == 0 {
+                queue.dropped += 1;
+            } else {
+                if queue.events.len() == self.config.capacity {
+                    queue.events.pop_front();
+                    queue.dropped += 1;
+                }
+                queue.events.push_back(event.clone());
+            }
+        }
+
+        if let Some(hook) = &self.config.on_failed_event {
+            hook(event);
+        }
+    }
+
+    /// The retained events, oldest first
+    pub fn recent(&self) -> Vec<FailedEvent> {
+        let queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.events.iter().cloned().collect()
+    }
+
+    /// Remove and return the retained events, oldest first
+    pub fn drain(&self) -> Vec<FailedEvent> {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.events.drain(..).collect()
+    }
+
+    pub fn report(&self, drain: bool) -> FailedEventsReport {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        let events = if drain {
+            queue.events.drain(..).collect()
+        } else {
+            queue.events.iter().cloned().collect()
+        };
+        FailedEventsReport {
+            capacity: self.config.capacity,
+            dropped: queue.dropped,
+            events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperstack_interpreter::testkit::ManualClock;
+    use serde_json::json;
+
+    fn failed(event_type: &str, slot: u64) -> FailedEvent {
+        FailedEvent::new(
+            event_type,
+            json!({ "slot": slot }),
+            "null key",
+            slot,
+            Some(format!("sig-{slot}")),
+        )
+    }
+
+    #[test]
+    fn keeps_the_latest_events_up_to_capacity() {
+        let events = FailedEvents::new(DeadLetterConfig::new().with_capacity(2));
+        for slot in 1..=3 {
+            events.record(failed("RoundState", slot));
+        }
+
+        let report = events.report(false);
+        assert_eq!(report.dropped, 1);
+        let slots: Vec<_> = report.events.iter().map(|event| event.slot).collect();
+        assert_eq!(slots, [2, 3]);
+
+        assert_eq!(events.drain().len(), 2);
+        assert!(events.recent().is_empty());
+        assert_eq!(events.report(false).dropped, 1);
+    }
+
+    #[test]
+    fn hook_sees_every_failure_while_logs_are_rate_limited() {
+        let clock = ManualClock::at_unix_secs(1_700_000_000);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let hook_seen = seen.clone();
+        let events = FailedEvents::new(
+            DeadLetterConfig::new()
+                .with_log_interval(Duration::from_secs(10))
+                .on_failed_event(move |event| hook_seen.lock().unwrap().push(event.slot)),
+        )
+        .with_clock(Arc::new(clock.clone()));
+
+        for slot in 1..=3 {
+            events.record(failed("RoundState", slot));
+        }
+        events.record(failed("MinerState", 4));
+        {
+            let queue = events.queue.lock().unwrap();
+            assert_eq!(queue.logged["RoundState"].1, 2, "two failures suppressed");
+            assert_eq!(queue.logged["MinerState"].1, 0);
+        }
+
+        clock.advance(Duration::from_secs(10));
+        events.record(failed("RoundState", 5));
+        assert_eq!(events.queue.lock().unwrap().logged["RoundState"].1, 0);
+
+        assert_eq!(*seen.lock().unwrap(), [1, 2, 3, 4, 5]);
+        let recent = events.recent();
+        assert_eq!(recent.len(), 5);
+        assert_eq!(recent[0].failed_at_ms, 1_700_000_000_000);
+        assert_eq!(recent[4].failed_at_ms, 1_700_000_010_000);
+    }
+}
@@ -0,0 +1,309 @@
+//! zstd compression with a dictionary trained on the stack's own frames.
+//!
+//! Frames repeat the same field names and envelope over and over, which a
+//! general-purpose compressor has to learn again in every frame. A zstd
+//! dictionary trained on sample frames carries that vocabulary, so even
+//! small live patches compress well.
+//!
+//! The dictionary comes from a [`DictionarySource`]: a file trained offline
+//! with `hs stack train-dict` from frames recorded by `hs stream --save`, or
+//! the first frames the projector publishes after startup. Its id is the
+//! schema hash of the spec being served followed by a hash of the dictionary,
+//! so clients can cache it by id for good.
+//!
+//! ## Negotiation
+//!
+//! With a dictionary source configured, the server opens every connection
+//! with a `hello` message advertising zstd and the current dictionary, unset
+//! until a sampled dictionary is trained:
+//!
+//! ```json
+//! {"type":"hello","compression":{"zstd":true,"dictionary":"5c0d…-9a1e…"}}
+//! ```
+//!
+//! Clients that don't know the message ignore it and keep getting gzip. A
+//! client fetches the dictionary from `/dictionaries/<id>`, served next to
+//! the WebSocket route by the embeddable router and on the HTTP health port
+//! by the standalone server, then answers once:
+//!
+//! ```json
+//! {"type":"compression","codec":"zstd","dictionary":"5c0d…-9a1e…"}
+//! ```
+//!
+//! From then on, subscriptions made before the answer included, every frame
+//! worth compressing is sent as zstd with the dictionary. A client that names no dictionary, or one the server no
+//! longer has, gets zstd without a dictionary instead. Clients tell the two
+//! apart by the dictionary id in the zstd frame header, `0` for none.
+
+use crate::compression::CompressorPool;
+use crate::reload::Live;
+use bytes::Bytes;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// Size of trained dictionaries. Frames are small, so a larger dictionary
+/// buys little and costs every client the download.
+pub const DICTIONARY_SIZE: usize = 32 * 1024;
+
+/// Where the server gets its compression dictionary
+#[derive(Debug, Clone)]
+pub enum DictionarySource {
+    /// A dictionary trained offline with `hs stack train-dict`
+    File(PathBuf),
+    /// Train on the first `frames` frames published after startup. Clients
+    /// connecting before then get zstd without a dictionary.
+    Sampled { frames: usize },
+}
+
+/// A trained zstd dictionary, ready to compress with
+pub struct CompressionDictionary {
+    id: String,
+    bytes: Bytes,
+    compressors: CompressorPool,
+}
+
+impl CompressionDictionary {
+    /// Wrap the dictionary `bytes` trained for the schema `schema_hash`
+    pub fn new(schema_hash: &str, bytes: impl Into<Bytes>) -> Self {
+        let bytes = bytes.into();
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        Self {
+            id: format!("{}-{:016x}", schema_hash, hasher.finish()),
+            compressors: CompressorPool::new(Some(bytes.clone())),
+            bytes,
+        }
+    }
+
+    /// Train a dictionary of at most `max_size` bytes on `samples`
+    pub fn train<S: AsRef<[u8]>>(
+        schema_hash: &str,
+        samples: &[S],
+        max_size: usize,
+    ) -> io::Result<Self> {
+        zstd::dict::from_samples(samples, max_size).map(|bytes| Self::new(schema_hash, bytes))
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The dictionary as served to clients
+    pub fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    /// Compress `data` into a zstd frame that needs this dictionary to decode
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        self.compressors.compress(data)
+    }
+}
+
+impl std::fmt::Debug for CompressionDictionary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompressionDictionary")
+            .field("id", &self.id)
+            .field("size", &self.bytes.len())
+            .finish()
+    }
+}
+
+/// Frames gathered for training
+struct Sampler {
+    target: usize,
+    frames: Vec<Bytes>,
+    /// Set once the frames are handed to training
+    done: bool,
+}
+
+/// The server's compression dictionary, shared by the projector, the
+/// WebSocket server and the dictionary endpoint
+#[derive(Clone)]
+pub struct Dictionaries {
+    schema_hash: Arc<str>,
+    current: Live<Option<Arc<CompressionDictionary>>>,
+    sampler: Option<Arc<Mutex<Sampler>>>,
+}
+
+impl Dictionaries {
+    /// Load or start sampling the dictionary of a spec with `schema_hash`.
+    ///
+    /// A dictionary file that can't be read is logged and skipped: clients
+    /// still get zstd, without a dictionary.
+    pub fn new(source: &DictionarySource, schema_hash: &str) -> Self {
+        let (current, sampler) = match source {
+            DictionarySource::File(path) => match std::fs::read(path) {
+                Ok(bytes) => {
+                    let dictionary = CompressionDictionary::new(schema_hash, bytes);
+                    info!(id = %dictionary.id(), path = %path.display(), "Loaded compression dictionary");
+                    (Some(Arc::new(dictionary)), None)
+                }
+                Err(e) => {
+                    warn!(path = %path.display(), "Failed to read compression dictionary: {}", e);
+                    (None, None)
+                }
+            },
+            DictionarySource::Sampled { frames } => {
+                let sampler = Sampler {
+                    target: *frames,
+                    frames: Vec::with_capacity(*frames),
+                    done: false,
+                };
+                (None, Some(Arc::new(Mutex::new(sampler))))
+            }
+        };
+
+        Self {
+            schema_hash: Arc::from(schema_hash),
+            current: Live::new(current),
+            sampler,
+        }
+    }
+
+    /// The dictionary advertised to new connections, once there is one
+    pub fn current(&self) -> Option<Arc<CompressionDictionary>> {
+        self.current.load().as_ref().clone()
+    }
+
+    /// The dictionary with `id`, if it is the current one
+    pub fn get(&self, id: &str) -> Option<Arc<CompressionDictionary>> {
+        self.current().filter(|dictionary| dictionary.id() == id)
+    }
+
+    /// Offer a published frame for training. Once enough frames are in, the
+    /// dictionary is trained on a blocking thread.
+    pub fn sample(&self, frame: &Bytes) {
+        let Some(sampler) = &self.sampler else {
+            return;
+        };
+        let samples = {
+            let mut sampler = sampler.lock().unwrap_or_else(|e| e.into_inner());
+            if sampler.done {
+                return;
+            }
+            sampler.frames.push(frame.clone());
+            if sampler.frames.len() < sampler.target {
+                return;
+            }
+            sampler.done = true;
+            std::mem::take(&mut sampler.frames)
+        };
+
+        let dictionaries = self.clone();
+        tokio::task::spawn_blocking(move || dictionaries.train(&samples));
+    }
+
+    fn train(&self, samples: &[Bytes]) {
+        match CompressionDictionary::train(&self.schema_hash, samples, DICTIONARY_SIZE) {
+            Ok(dictionary) => {
+                info!(
+                    id = %dictionary.id(),
+                    samples = samples.len(),
+                    size = dictionary.bytes().len(),
+                    "Trained compression dictionary"
+                );
+                self.current.store(Arc::new(Some(Arc::new(dictionary))));
+            }
+            Err(e) => warn!(
+                samples = samples.len(),
+                "Failed to train compression dictionary, serving zstd without one: {}", e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::maybe_compress;
+    use serde_json::json;
+
+    /// Small live patches, the frames a dictionary is meant for
+    fn patch_frame(i: u64) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "mode": "list",
+            "entity": "OreRound/latest",
+            "op": "patch",
+            "key": format!("{}", 90_000 + i),
+            "data": {
+                "state": { "total_deployed": i * 7_919, "motherlode": i.is_multiple_of(13) },
+                "results": { "winning_square": i % 25, "top_miner": format!("Miner{:04}", i * 31 % 997) }
+            },
+            "append": [],
+            "seq": format!("{}:{:06}", 300_000_000 + i, i % 400),
+            "_seq": { "sub": "OreRound/latest:*", "frameSeq": i + 1 }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn trained_dictionary_beats_plain_compression_on_small_frames() {
+        let training: Vec<_> = (0..1_000).map(patch_frame).collect();
+        let dictionary =
+            CompressionDictionary::train("schema", &training, DICTIONARY_SIZE).unwrap();
+
+        let held_out: Vec<_> = (5_000..5_200).map(patch_frame).collect();
+        let raw: usize = held_out.iter().map(Vec::len).sum();
+        let gzip: usize = held_out
+            .iter()
+            .map(|frame| maybe_compress(frame).as_bytes().len())
+            .sum();
+        let zstd: usize = held_out
+            .iter()
+            .map(|frame| zstd::bulk::compress(frame, 3).unwrap().len())
+            .sum();
+        let with_dictionary: usize = held_out
+            .iter()
+            .map(|frame| dictionary.compress(frame).unwrap().len())
+            .sum();
+
+        assert!(
+            with_dictionary * 2 < zstd.min(gzip),
+            "raw {raw}, gzip {gzip}, zstd {zstd}, zstd with dictionary {with_dictionary}"
+        );
+    }
+
+    #[test]
+    fn dictionary_frames_name_their_dictionary_and_round_trip() {
+        let training: Vec<_> = (0..1_000).map(patch_frame).collect();
+        let dictionary =
+            CompressionDictionary::train("schema", &training, DICTIONARY_SIZE).unwrap();
+        assert!(dictionary.id().starts_with("schema-"));
+
+        let frame = patch_frame(7_777);
+        let compressed = dictionary.compress(&frame).unwrap();
+        assert_eq!(
+            zstd::zstd_safe::get_dict_id_from_frame(&compressed),
+            zstd::zstd_safe::get_dict_id(dictionary.bytes())
+        );
+        let decompressed = zstd::bulk::Decompressor::with_dictionary(dictionary.bytes())
+            .unwrap()
+            .decompress(&compressed, frame.len())
+            .unwrap();
+        assert_eq!(decompressed, frame);
+    }
+
+    #[tokio::test]
+    async fn sampled_dictionary_is_trained_once_enough_frames_are_published() {
+        let dictionaries = Dictionaries::new(&DictionarySource::Sampled { frames: 500 }, "schema");
+        for i in 0..499 {
+            dictionaries.sample(&Bytes::from(patch_frame(i)));
+        }
+        assert!(dictionaries.current().is_none());
+        dictionaries.sample(&Bytes::from(patch_frame(499)));
+
+        for _ in 0..200 {
+            if let Some(dictionary) = dictionaries.current() {
+                assert!(dictionaries.get(dictionary.id()).is_some());
+                assert!(dictionaries.get("schema-0").is_none());
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("dictionary should be trained");
+    }
+}
@@ -0,0 +1,305 @@
+//! Connection draining for rolling restarts behind a load balancer.
+//!
+//! Draining a server (`POST /admin/drain?grace=120s`, or SIGTERM when
+//! [`Runtime::with_drain_on_shutdown`](crate::Runtime::with_drain_on_shutdown)
+//! is set) does the following:
+//!
+//! - Readiness endpoints report `503 DRAINING`, so the load balancer stops
+//!   routing new connections here.
+//! - New WebSocket upgrades are refused with `503` and `X-Error-Code: draining`.
+//!   Clients should retry right away, and the retry lands on another instance.
+//! - Every connected client receives a [`MigrateSoonMessage`]. Its suggested
+//!   reconnect delay is spread across the first half of the grace period, so
+//!   clients don't all reconnect at once.
+//! - When the grace period ends, remaining clients are sent a `1001 Going Away`
+//!   close frame whose reason carries the same reconnect delay.
+//!
+//! Draining cannot be cancelled. A second request keeps the original deadline.
+
+use crate::websocket::client_manager::ClientManager;
+use crate::websocket::subscription::CloseReason;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Grace period used when a drain request doesn't specify one
+pub const DEFAULT_DRAIN_GRACE: Duration = Duration::from_secs(30);
+
+/// How long a client gets to acknowledge the close frame before its
+/// connection is dropped
+pub(crate) const CLOSE_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Server-sent notice that the connection will be closed for a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrateSoonMessage {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Suggested delay before opening a replacement connection
+    pub reconnect_after_ms: u64,
+    /// Time left before the server closes this connection
+    pub close_in_ms: u64,
+}
+
+/// Snapshot of the drain state, returned by the admin endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct DrainStatus {
+    pub draining: bool,
+    /// Time left before remaining clients are closed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_ms: Option<u64>,
+    pub connections: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Drain {
+    started_at: Instant,
+    deadline: Instant,
+}
+
+struct DrainInner {
+    drain: watch::Sender<Option<Drain>>,
+    connections: watch::Sender<usize>,
+}
+
+/// Shared drain switch for a server's WebSocket connections.
+///
+/// Cloning is cheap and every clone controls the same server.
+#[derive(Clone)]
+pub struct DrainController {
+    inner: Arc<DrainInner>,
+}
+
+impl Default for DrainController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DrainController {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(DrainInner {
+                drain: watch::channel(None).0,
+                connections: watch::channel(0).0,
+            }),
+        }
+    }
+
+    /// Start draining with the given grace period.
+    ///
+    /// Does nothing if the server is already draining.
+    pub fn start(&self, grace: Duration) -> DrainStatus {
+        let started = self.inner.drain.send_if_modified(|drain| {
+            if drain.is_some() {
+                return false;
+            }
+            let now = Instant::now();
+            *drain = Some(Drain {
+                started_at: now,
+                deadline: now + grace,
+            });
+            true
+        });
+
+        if started {
+            info!(
+                "Draining {} WebSocket connections over {:?}",
+                self.connection_count(),
+                grace
+            );
+        }
+
+        self.status()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.inner.drain.borrow().is_some()
+    }
+
+    /// Time left before remaining clients are closed, or `None` when not draining.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.inner
+            .drain
+            .borrow()
+            .map(|drain| drain.deadline.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn connection_count(&self) -> usize {
+        *self.inner.connections.borrow()
+    }
+
+    pub fn status(&self) -> DrainStatus {
+        DrainStatus {
+            draining: self.is_draining(),
+            remaining_ms: self
+                .remaining()
+                .map(|remaining| remaining.as_millis() as u64),
+            connections: self.connection_count(),
+        }
+    }
+
+    /// Wait until draining has started and every connection has closed.
+    pub async fn drained(&self) {
+        let mut drain = self.inner.drain.subscribe();
+        let _ = drain.wait_for(Option::is_some).await;
+        let mut connections = self.inner.connections.subscribe();
+        let _ = connections.wait_for(|count| *count == 0).await;
+    }
+
+    /// Count a connection as open until the returned guard is dropped.
+    pub(crate) fn track_connection(&self) -> ConnectionGuard {
+        self.inner.connections.send_modify(|count| *count += 1);
+        ConnectionGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Drive one client through a drain.
+    ///
+    /// Waits for draining to start, sends the client a [`MigrateSoonMessage`],
+    /// and sends a close frame at the deadline. Resolves once the client has
+    /// had [`CLOSE_ACK_TIMEOUT`] to acknowledge the close, at which point the
+    /// connection should be dropped.
+    pub(crate) async fn drain_client(&self, client_id: Uuid, client_manager: &ClientManager) {
+        let mut drain = self.inner.drain.subscribe();
+        let Ok(Some(Drain {
+            started_at,
+            deadline,
+        })) = drain.wait_for(Option::is_some).await.map(|state| *state)
+        else {
+            return std::future::pending().await;
+        };
+
+        let close_in = deadline.saturating_duration_since(Instant::now());
+        let message = MigrateSoonMessage {
+            kind: "migrate_soon".to_string(),
+            reconnect_after_ms: reconnect_after(client_id, deadline - started_at).as_millis()
+                as u64,
+            close_in_ms: close_in.as_millis() as u64,
+        };
+        match serde_json::to_string(&message) {
+            Ok(json) => {
+                let _ = client_manager.send_text_to_client(client_id, json).await;
+            }
+            Err(error) => {
+                warn!(error = %error, client_id = %client_id, "failed to serialize migrate_soon message");
+            }
+        }
+
+        tokio::time::sleep_until(deadline).await;
+        debug!("Drain deadline reached, closing client {}", client_id);
+        let reason = CloseReason::new(
+            "draining",
+            Some(reconnect_after(client_id, deadline - started_at)),
+        );
+        let close = Message::Close(Some(reason.into_close_frame(CloseCode::Away)));
+        if let Some(sender) = client_manager.client_sender(client_id) {
+            let _ = sender.send(close).await;
+        }
+
+        tokio::time::sleep(CLOSE_ACK_TIMEOUT).await;
+    }
+}
+
+/// Keeps a connection counted by its [`DrainController`].
+pub(crate) struct ConnectionGuard {
+    inner: Arc<DrainInner>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.inner.connections.send_modify(|count| *count -= 1);
+    }
+}
+
+/// Spread reconnects across the first half of the grace period, so clients
+/// have time to move before the close frames go out.
+fn reconnect_after(client_id: Uuid, grace: Duration) -> Duration {
+    let window_ms = (grace.as_millis() / 2) as u64;
+    if window_ms == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis((client_id.as_u128() % window_ms as u128) as u64)
+}
+
+/// Parse a `grace` query value such as `120s`, `2m`, `500ms`, or `120` (seconds).
+pub(crate) fn parse_grace(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+
+    match unit {
+        "ms" => Some(Duration::from_millis(amount)),
+        "" | "s" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_secs(amount * 60)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_grace_units() {
+        assert_eq!(parse_grace("120s"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_grace("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_grace("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_grace("45"), Some(Duration::from_secs(45)));
+        assert_eq!(parse_grace("soon"), None);
+        assert_eq!(parse_grace("10h"), None);
+    }
+
+    #[test]
+    fn reconnects_land_in_first_half_of_grace() {
+        let grace = Duration::from_secs(10);
+        for _ in 0..100 {
+            assert!(reconnect_after(Uuid::new_v4(), grace) < Duration::from_secs(5));
+        }
+        assert_eq!(
+            reconnect_after(Uuid::new_v4(), Duration::ZERO),
+            Duration::ZERO
+        );
+    }
+
+    #[tokio::test]
+    async fn second_start_keeps_original_deadline() {
+        let drain = DrainController::new();
+        assert!(!drain.is_draining());
+
+        drain.start(Duration::from_secs(10));
+        drain.start(Duration::from_secs(1000));
+
+        assert!(drain.is_draining());
+        assert!(drain.remaining().unwrap() <= Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn drained_waits_for_tracked_connections() {
+        let drain = DrainController::new();
+        let guard = drain.track_connection();
+        drain.start(Duration::ZERO);
+
+        let waiter = tokio::spawn({
+            let drain = drain.clone();
+            async move { drain.drained().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(guard);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("drain should complete once connections close")
+            .unwrap();
+    }
+}
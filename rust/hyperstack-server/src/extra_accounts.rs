@@ -0,0 +1,329 @@
+//! Accounts of programs the stack has no IDL for.
+//!
+//! A stack can watch accounts owned by another program, such as the SPL token
+//! accounts holding its vaults, with
+//! `#[hyperstack(extra_accounts = ["<program>:(owner=<pubkey>)"])]`. The macro
+//! generates an account parser for the program that decodes its accounts with
+//! a builtin layout, and entities map them like any IDL account (the SPL token
+//! layout is exposed as `spl_token_sdk::accounts::TokenAccount`).
+//!
+//! Only the SPL Token program has a builtin layout. Vixen's prefilter can't
+//! match on account data, so `owner` and `mint` filters subscribe to every
+//! account of the program and are applied by the parser, while a list of
+//! `address` filters subscribes to just those accounts.
+
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+use yellowstone_vixen_core::{AccountUpdate, ParseError, ParseResult, Prefilter, Pubkey};
+
+/// The SPL Token program
+pub const SPL_TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+/// Event type of a decoded SPL token account
+pub const TOKEN_ACCOUNT_EVENT: &str = "spl_token::TokenAccountState";
+
+/// Size of an SPL token account
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+
+/// Which accounts of a watched program reach the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccountFilter {
+    /// The account at this address
+    Address(Pubkey),
+    /// Token accounts owned by this wallet or PDA
+    Owner(Pubkey),
+    /// Token accounts of this mint
+    Mint(Pubkey),
+}
+
+impl AccountFilter {
+    /// Whether the token account at `address` holding `data` passes the filter
+    pub fn matches(&self, address: &[u8], data: &[u8]) -> bool {
+        match self {
+            Self::Address(key) => key.as_slice() == address,
+            Self::Owner(key) => data.get(32..64) == Some(key.as_slice()),
+            Self::Mint(key) => data.get(0..32) == Some(key.as_slice()),
+        }
+    }
+}
+
+impl FromStr for AccountFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, key) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Account filter '{}' should be kind=<pubkey>", s))?;
+        let key = Pubkey::from_str(key.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid pubkey in account filter '{}': {}", s, e))?;
+
+        match kind.trim() {
+            "address" => Ok(Self::Address(key)),
+            "owner" => Ok(Self::Owner(key)),
+            "mint" => Ok(Self::Mint(key)),
+            other => Err(anyhow::anyhow!(
+                "Unknown account filter '{}' (expected 'address', 'owner' or 'mint')",
+                other
+            )),
+        }
+    }
+}
+
+/// The Yellowstone subscription for SPL token accounts passing any of `filters`
+pub fn token_account_prefilter(filters: &[AccountFilter]) -> Prefilter {
+    let addresses: Vec<[u8; 32]> = filters
+        .iter()
+        .filter_map(|filter| match filter {
+            AccountFilter::Address(key) => Some(key.0),
+            _ => None,
+        })
+        .collect();
+
+    // Owners and addresses of one prefilter must both match, so data filters
+    // subscribe to the whole program and check addresses while parsing
+    let builder = if addresses.len() == filters.len() {
+        Prefilter::builder().accounts(addresses)
+    } else {
+        let program = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).expect("SPL Token program id");
+        Prefilter::builder().account_owners([program.0])
+    };
+
+    builder
+        .build()
+        .expect("token account prefilter should build")
+}
+
+/// Decode an SPL token account update passing any of `filters`.
+///
+/// Updates of other accounts of the program (mints, multisigs, uninitialized
+/// accounts) and accounts no filter matches are filtered out.
+pub fn parse_token_account(
+    update: &AccountUpdate,
+    filters: &[AccountFilter],
+) -> ParseResult<TokenAccount> {
+    let account = update
+        .account
+        .as_ref()
+        .ok_or(ParseError::from("No account data"))?;
+
+    let program = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).expect("SPL Token program id");
+    if account.owner.as_slice() != program.as_slice()
+        || !filters
+            .iter()
+            .any(|filter| filter.matches(&account.pubkey, &account.data))
+    {
+        return Err(ParseError::Filtered);
+    }
+
+    TokenAccount::unpack(&account.data).ok_or(ParseError::Filtered)
+}
+
+/// An SPL token account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenAccount {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub delegate: Option<Pubkey>,
+    /// 1 when initialized, 2 when frozen
+    pub state: u8,
+    /// Rent-exempt reserve of a wrapped SOL account
+    pub is_native: Option<u64>,
+    pub delegated_amount: u64,
+    pub close_authority: Option<Pubkey>,
+}
+
+impl TokenAccount {
+    /// Decode the account data, or `None` if it isn't an initialized token
+    /// account.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() != TOKEN_ACCOUNT_LEN || data[108] == 0 {
+            return None;
+        }
+
+        Some(Self {
+            mint: pubkey_at(data, 0),
+            owner: pubkey_at(data, 32),
+            amount: u64_at(data, 64),
+            delegate: coption_at(data, 72).map(|offset| pubkey_at(data, offset)),
+            state: data[108],
+            is_native: coption_at(data, 109).map(|offset| u64_at(data, offset)),
+            delegated_amount: u64_at(data, 121),
+            close_authority: coption_at(data, 129).map(|offset| pubkey_at(data, offset)),
+        })
+    }
+
+    pub fn event_type(&self) -> &'static str {
+        TOKEN_ACCOUNT_EVENT
+    }
+
+    /// Always false: closed token accounts are filtered out by
+    /// [`parse_token_account`]
+    pub fn is_closed(&self) -> bool {
+        false
+    }
+
+    /// The account as JSON, with the field names of the builtin layout
+    pub fn to_value(&self) -> Value {
+        json!({
+            "mint": self.mint.to_string(),
+            "owner": self.owner.to_string(),
+            "amount": self.amount,
+            "delegate": self.delegate.map(|key| key.to_string()),
+            "state": self.state,
+            "is_native": self.is_native,
+            "delegated_amount": self.delegated_amount,
+            "close_authority": self.close_authority.map(|key| key.to_string()),
+        })
+    }
+}
+
+fn pubkey_at(data: &[u8], offset: usize) -> Pubkey {
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&data[offset..offset + 32]);
+    Pubkey::new(key)
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Offset of the value of the `COption` at `offset`, if it is set
+fn coption_at(data: &[u8], offset: usize) -> Option<usize> {
+    (data[offset..offset + 4] == [1, 0, 0, 0]).then_some(offset + 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yellowstone_vixen_core::AccountUpdateInfo;
+
+    const VAULT: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    const MINT: &str = "So11111111111111111111111111111111111111112";
+
+    fn key(s: &str) -> Pubkey {
+        Pubkey::from_str(s).unwrap()
+    }
+
+    fn token_account_data(owner: &str, amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+        data[0..32].copy_from_slice(key(MINT).as_slice());
+        data[32..64].copy_from_slice(key(owner).as_slice());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[108] = 1;
+        data[109..113].copy_from_slice(&[1, 0, 0, 0]);
+        data[113..121].copy_from_slice(&2_039_280u64.to_le_bytes());
+        data
+    }
+
+    fn update(address: [u8; 32], owner: &str, data: Vec<u8>) -> AccountUpdate {
+        AccountUpdate {
+            account: Some(AccountUpdateInfo {
+                pubkey: address.to_vec(),
+                lamports: 0,
+                owner: key(owner).to_vec(),
+                executable: false,
+                rent_epoch: 0,
+                data,
+                write_version: 0,
+                txn_signature: None,
+            }),
+            slot: 1,
+            is_startup: false,
+        }
+    }
+
+    #[test]
+    fn test_unpack_token_account() {
+        let account = TokenAccount::unpack(&token_account_data(VAULT, 42)).unwrap();
+
+        assert_eq!(account.mint, key(MINT));
+        assert_eq!(account.owner, key(VAULT));
+        assert_eq!(account.amount, 42);
+        assert_eq!(account.delegate, None);
+        assert_eq!(account.is_native, Some(2_039_280));
+        assert_eq!(
+            account.to_value(),
+            json!({
+                "mint": MINT,
+                "owner": VAULT,
+                "amount": 42,
+                "delegate": null,
+                "state": 1,
+                "is_native": 2_039_280,
+                "delegated_amount": 0,
+                "close_authority": null,
+            })
+        );
+
+        // Mints and uninitialized accounts aren't token accounts
+        assert_eq!(TokenAccount::unpack(&[0u8; 82]), None);
+        assert_eq!(TokenAccount::unpack(&[0u8; TOKEN_ACCOUNT_LEN]), None);
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            format!("owner={VAULT}").parse::<AccountFilter>().unwrap(),
+            AccountFilter::Owner(key(VAULT))
+        );
+        assert_eq!(
+            format!(" mint = {MINT}").parse::<AccountFilter>().unwrap(),
+            AccountFilter::Mint(key(MINT))
+        );
+        assert!("owner".parse::<AccountFilter>().is_err());
+        assert!(format!("lamports={VAULT}")
+            .parse::<AccountFilter>()
+            .is_err());
+        assert!("address=not-a-key".parse::<AccountFilter>().is_err());
+    }
+
+    #[test]
+    fn test_prefilter() {
+        let address = AccountFilter::Address(key(VAULT));
+        let prefilter = token_account_prefilter(&[address]);
+        let accounts = prefilter.account.unwrap();
+        assert_eq!(
+            accounts.accounts.into_iter().collect::<Vec<_>>(),
+            [key(VAULT)]
+        );
+        assert!(accounts.owners.is_empty());
+
+        let prefilter = token_account_prefilter(&[address, AccountFilter::Owner(key(VAULT))]);
+        let accounts = prefilter.account.unwrap();
+        assert!(accounts.accounts.is_empty());
+        assert_eq!(
+            accounts.owners.into_iter().collect::<Vec<_>>(),
+            [key(SPL_TOKEN_PROGRAM_ID)]
+        );
+    }
+
+    #[test]
+    fn test_parse_token_account_applies_filters() {
+        let filters = [AccountFilter::Owner(key(VAULT))];
+
+        let parsed = parse_token_account(
+            &update([7; 32], SPL_TOKEN_PROGRAM_ID, token_account_data(VAULT, 5)),
+            &filters,
+        )
+        .unwrap();
+        assert_eq!(parsed.amount, 5);
+
+        // Another wallet's token account
+        let other = update([7; 32], SPL_TOKEN_PROGRAM_ID, token_account_data(MINT, 5));
+        assert!(matches!(
+            parse_token_account(&other, &filters),
+            Err(ParseError::Filtered)
+        ));
+
+        // Not owned by the token program
+        let foreign = update([7; 32], MINT, token_account_data(VAULT, 5));
+        assert!(matches!(
+            parse_token_account(&foreign, &filters),
+            Err(ParseError::Filtered)
+        ));
+    }
+}
@@ -0,0 +1,348 @@
+//! Feature flags that switch optional behaviors at runtime.
+//!
+//! Each [`Flag`] has a default, overridden by [`FlagsConfig`] (in code, or
+//! `HYPERSTACK_FLAGS=suppress_noop_patches=on,compression=off` through
+//! [`FlagsConfig::from_env`]). The server shares one [`Flags`] between its
+//! components, which read a flag each time they reach the decision it
+//! controls, so a change applies from the next frame on:
+//!
+//! - `suppress_noop_patches` (off): the projector drops patches that leave
+//!   the cached entity as it was, instead of publishing them;
+//! - `load_shedding` (on): the projector passes mutations through the load
+//!   shedder, when one is configured (see the [`load_shed`](crate::load_shed)
+//!   module);
+//! - `compression` (on): frames are compressed for the clients that accept
+//!   it, and sent as they are when off.
+//!
+//! `GET /admin/flags` lists the flags and `PATCH /admin/flags` with a JSON
+//! object like `{"compression": false}` changes them. Changing flags takes a
+//! token accepted by [`FlagsConfig::with_admin_auth`], and is refused when no
+//! admin auth is configured. Every change is logged on the
+//! `hyperstack::audit` target with the subject of the token that made it, and
+//! the last [`RECENT_CHANGES`] are reported in `/stats`.
+
+use crate::websocket::auth::{AuthDecision, ConnectionAuthRequest, WebSocketAuthPlugin};
+use hyperstack_interpreter::clock::{system_clock, SharedClock};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// Flag changes kept for `/stats`
+pub const RECENT_CHANGES: usize = 20;
+
+/// An optional behavior that can be switched at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Flag {
+    SuppressNoopPatches,
+    LoadShedding,
+    Compression,
+}
+
+impl Flag {
+    pub const ALL: [Flag; 3] = [
+        Flag::SuppressNoopPatches,
+        Flag::LoadShedding,
+        Flag::Compression,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Flag::SuppressNoopPatches => "suppress_noop_patches",
+            Flag::LoadShedding => "load_shedding",
+            Flag::Compression => "compression",
+        }
+    }
+
+    /// Whether the behavior is on when the config doesn't say
+    pub fn default_enabled(self) -> bool {
+        match self {
+            Flag::SuppressNoopPatches => false,
+            Flag::LoadShedding | Flag::Compression => true,
+        }
+    }
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Flag {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        Flag::ALL
+            .into_iter()
+            .find(|flag| flag.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown feature flag '{}' (expected one of {})",
+                    name,
+                    Flag::ALL.map(Flag::name).join(", ")
+                )
+            })
+    }
+}
+
+/// Initial values of the flags and who may change them
+#[derive(Clone, Default)]
+pub struct FlagsConfig {
+    /// Flags set at startup, the others keep their default
+    pub initial: BTreeMap<Flag, bool>,
+    /// Authorizes `PATCH /admin/flags`, which is refused when unset
+    pub admin_auth: Option<Arc<dyn WebSocketAuthPlugin>>,
+}
+
+impl fmt::Debug for FlagsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlagsConfig")
+            .field("initial", &self.initial)
+            .field("admin_auth", &self.admin_auth.is_some())
+            .finish()
+    }
+}
+
+impl FlagsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_flag(mut self, flag: Flag, enabled: bool) -> Self {
+        self.initial.insert(flag, enabled);
+        self
+    }
+
+    /// Accept admin requests that `plugin` authorizes, e.g. a
+    /// [`StaticTokenAuthPlugin`](crate::websocket::auth::StaticTokenAuthPlugin)
+    /// with tokens kept apart from the ones clients use
+    pub fn with_admin_auth(mut self, plugin: Arc<dyn WebSocketAuthPlugin>) -> Self {
+        self.admin_auth = Some(plugin);
+        self
+    }
+
+    /// Read the initial flags from `HYPERSTACK_FLAGS`, a comma-separated list
+    /// of `name=on|off` (or `true|false`, `1|0`)
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        let Some(value) = lookup("HYPERSTACK_FLAGS") else {
+            return Ok(config);
+        };
+
+        for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, enabled) = entry.split_once('=').ok_or_else(|| {
+                anyhow::anyhow!("Feature flag '{}' has no value (expected name=on)", entry)
+            })?;
+            let enabled = match enabled.trim().to_ascii_lowercase().as_str() {
+                "on" | "true" | "1" => true,
+                "off" | "false" | "0" => false,
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "Invalid value '{}' for feature flag '{}' (expected on or off)",
+                        other,
+                        name.trim()
+                    ))
+                }
+            };
+            config.initial.insert(name.parse()?, enabled);
+        }
+        Ok(config)
+    }
+}
+
+/// A flag change, as logged and reported in `/stats`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlagChange {
+    pub flag: &'static str,
+    pub enabled: bool,
+    /// Subject of the admin token that made the change
+    pub changed_by: String,
+    pub changed_at_ms: i64,
+}
+
+/// Snapshot of the flags for `/stats` and `/admin/flags`
+#[derive(Debug, Clone, Serialize)]
+pub struct FlagStats {
+    pub flags: BTreeMap<&'static str, bool>,
+    pub recent_changes: Vec<FlagChange>,
+}
+
+/// Why a flags update was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlagUpdateError {
+    /// No admin auth is configured
+    Disabled,
+    /// The request's token was missing or rejected
+    Unauthorized(String),
+    /// The body isn't an object of flag names to booleans
+    Invalid(String),
+}
+
+/// The current value of every flag, shared by the server's components
+pub struct Flags {
+    values: [AtomicBool; Flag::ALL.len()],
+    admin_auth: Option<Arc<dyn WebSocketAuthPlugin>>,
+    changes: Mutex<VecDeque<FlagChange>>,
+    clock: SharedClock,
+}
+
+impl fmt::Debug for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(Flag::ALL.map(|flag| (flag.name(), self.enabled(flag))))
+            .finish()
+    }
+}
+
+impl Default for Flags {
+    fn default() -> Self {
+        Self::new(&FlagsConfig::default())
+    }
+}
+
+impl Flags {
+    pub fn new(config: &FlagsConfig) -> Self {
+        Self {
+            values: Flag::ALL.map(|flag| {
+                AtomicBool::new(
+                    config
+                        .initial
+                        .get(&flag)
+                        .copied()
+                        .unwrap_or(flag.default_enabled()),
+                )
+            }),
+            admin_auth: config.admin_auth.clone(),
+            changes: Mutex::new(VecDeque::new()),
+            clock: system_clock(),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn enabled(&self, flag: Flag) -> bool {
+        self.values[flag as usize].load(Ordering::Relaxed)
+    }
+
+    /// Set `flag`, logging the change on behalf of `changed_by`. Returns
+    /// whether the value changed.
+    pub fn set(&self, flag: Flag, enabled: bool, changed_by: &str) -> bool {
+        let previous = self.values[flag as usize].swap(enabled, Ordering::Relaxed);
+        if previous == enabled {
+            return false;
+        }
+
+        info!(
+            target: "hyperstack::audit",
+            flag = flag.name(),
+            from = previous,
+            to = enabled,
+            changed_by,
+            "Feature flag changed"
+        );
+        let mut changes = self.changes.lock().unwrap_or_else(|e| e.into_inner());
+        if changes.len() == RECENT_CHANGES {
+            changes.pop_front();
+        }
+        changes.push_back(FlagChange {
+            flag: flag.name(),
+            enabled,
+            changed_by: changed_by.to_string(),
+            changed_at_ms: self.clock.unix_millis(),
+        });
+        true
+    }
+
+    pub fn stats(&self) -> FlagStats {
+        FlagStats {
+            flags: Flag::ALL
+                .into_iter()
+                .map(|flag| (flag.name(), self.enabled(flag)))
+                .collect(),
+            recent_changes: self
+                .changes
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Apply a `PATCH /admin/flags` body once `request` is authorized.
+    ///
+    /// The body is validated as a whole before any flag changes.
+    pub async fn update(
+        &self,
+        request: &ConnectionAuthRequest,
+        body: &[u8],
+    ) -> Result<FlagStats, FlagUpdateError> {
+        let plugin = self.admin_auth.as_ref().ok_or(FlagUpdateError::Disabled)?;
+        let changed_by = match plugin.authorize(request).await {
+            AuthDecision::Allow(context) => context.subject,
+            AuthDecision::Deny(deny) => return Err(FlagUpdateError::Unauthorized(deny.reason)),
+        };
+
+        let updates: BTreeMap<String, bool> = serde_json::from_slice(body)
+            .map_err(|e| FlagUpdateError::Invalid(format!("expected {{\"flag\": bool}}: {e}")))?;
+        let updates = updates
+            .into_iter()
+            .map(|(name, enabled)| Ok((name.parse::<Flag>()?, enabled)))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(|e| FlagUpdateError::Invalid(e.to_string()))?;
+
+        for (flag, enabled) in updates {
+            self.set(flag, enabled, &changed_by);
+        }
+        Ok(self.stats())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config_from(value: &str) -> anyhow::Result<FlagsConfig> {
+        let vars = HashMap::from([("HYPERSTACK_FLAGS".to_string(), value.to_string())]);
+        FlagsConfig::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn env_overrides_the_defaults_of_the_flags_it_names() {
+        let flags = Flags::new(&config_from("suppress_noop_patches=on, Compression=off").unwrap());
+
+        assert!(flags.enabled(Flag::SuppressNoopPatches));
+        assert!(!flags.enabled(Flag::Compression));
+        assert!(flags.enabled(Flag::LoadShedding));
+
+        assert!(config_from("compression").is_err());
+        assert!(config_from("compression=maybe").is_err());
+        assert!(config_from("delta_frames=on").is_err());
+    }
+
+    #[test]
+    fn changes_are_recorded_with_who_made_them() {
+        let flags = Flags::default();
+
+        assert!(flags.set(Flag::Compression, false, "ops"));
+        assert!(!flags.set(Flag::Compression, false, "ops"));
+
+        let stats = flags.stats();
+        assert!(!stats.flags["compression"]);
+        assert_eq!(stats.recent_changes.len(), 1);
+        assert_eq!(stats.recent_changes[0].flag, "compression");
+        assert_eq!(stats.recent_changes[0].changed_by, "ops");
+    }
+}
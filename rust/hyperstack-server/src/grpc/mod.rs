@@ -0,0 +1,330 @@
+//! gRPC server-streaming endpoint, behind the `grpc` feature.
+//!
+//! `Subscribe` on the `hyperstack.stream.v1.Stream` service (see
+//! `proto/stream.proto`) streams the entity updates of one view as
+//! [`EntityFrame`] messages. They come from the same [`BusManager`] the
+//! WebSocket server reads, so both transports see identical data: a
+//! snapshot of the cached entities, then every frame the projector publishes,
+//! with its data as JSON.
+//!
+//! A stream reads straight from the view's bus. A client that reads too
+//! slowly falls behind on the bus and skips its oldest updates, as WebSocket
+//! subscribers do, rather than holding up the projector. The next frame's
+//! `skipped` tells how many were dropped. State views keep only the latest
+//! update of their entity.
+//!
+//! Derived views aren't served over gRPC. There is no authentication either,
+//! so bind the endpoint where only trusted consumers can reach it.
+
+pub mod proto;
+
+pub use proto::stream_client::StreamClient;
+pub use proto::stream_server::{Stream, StreamServer};
+pub use proto::{EntityFrame, SubscribeRequest};
+
+use crate::bus::{BusManager, BusMessage};
+use crate::cache::EntityCache;
+use crate::reload::LiveViews;
+use crate::websocket::frame::Mode;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
+use tokio_util::sync::CancellationToken;
+use tonic::{Request, Response, Status};
+use tracing::{debug, info, warn};
+
+/// Configuration for the gRPC server
+#[derive(Clone, Debug)]
+pub struct GrpcConfig {
+    pub bind_address: SocketAddr,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "[::]:8878".parse().expect("valid socket address"),
+        }
+    }
+}
+
+impl GrpcConfig {
+    pub fn new(bind_address: impl Into<SocketAddr>) -> Self {
+        Self {
+            bind_address: bind_address.into(),
+        }
+    }
+}
+
+/// Serves the `Stream` service on its own port
+pub struct GrpcServer {
+    bind_addr: SocketAddr,
+    service: StreamService,
+}
+
+impl GrpcServer {
+    pub fn new(
+        bind_addr: SocketAddr,
+        bus_manager: BusManager,
+        entity_cache: EntityCache,
+        view_index: impl Into<LiveViews>,
+    ) -> Self {
+        Self {
+            bind_addr,
+            service: StreamService {
+                bus_manager,
+                entity_cache,
+                view_index: view_index.into(),
+                shutdown: CancellationToken::new(),
+            },
+        }
+    }
+
+    pub fn bind_addr(&self) -> SocketAddr {
+        self.bind_addr
+    }
+
+    /// Serve until `shutdown` is cancelled, which also ends open streams
+    pub async fn start_until(self, shutdown: CancellationToken) -> anyhow::Result<()> {
+        info!("Starting gRPC server on {}", self.bind_addr);
+        let service = StreamService {
+            shutdown: shutdown.clone(),
+            ..self.service
+        };
+        tonic::transport::Server::builder()
+            .add_service(StreamServer::new(service))
+            .serve_with_shutdown(self.bind_addr, shutdown.cancelled_owned())
+            .await?;
+        info!("gRPC server on {} stopped", self.bind_addr);
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct StreamService {
+    bus_manager: BusManager,
+    entity_cache: EntityCache,
+    view_index: LiveViews,
+    shutdown: CancellationToken,
+}
+
+type FrameStream = Pin<Box<dyn futures_util::Stream<Item = Result<EntityFrame, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Stream for StreamService {
+    type SubscribeStream = FrameStream;
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<FrameStream>, Status> {
+        let request = request.into_inner();
+        let view_spec = self
+            .view_index
+            .load()
+            .get_view(&request.view)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("unknown view {}", request.view)))?;
+        if view_spec.is_derived() {
+            return Err(Status::unimplemented(format!(
+                "derived view {} isn't served over gRPC",
+                request.view
+            )));
+        }
+        let with_snapshot = request.with_snapshot.unwrap_or(true);
+
+        // Subscribed before reading the cache, so no update falls in between
+        let (live, snapshot) = match view_spec.mode {
+            Mode::State => {
+                let key = request.key.clone().ok_or_else(|| {
+                    Status::invalid_argument(format!("state view {} needs a key", request.view))
+                })?;
+                let mut rx = self
+                    .bus_manager
+                    .get_or_create_state_bus(&request.view, &key)
+                    .await;
+                let cached = if with_snapshot {
+                    self.entity_cache.get(&request.view, &key).await
+                } else {
+                    None
+                };
+                // The latest update still goes out when nothing is cached
+                if cached.is_some() || !with_snapshot {
+                    rx.borrow_and_update();
+                }
+                (Live::State(rx), cached.map(|data| vec![(key, data)]))
+            }
+            Mode::List | Mode::Append => {
+                let rx = self.bus_manager.get_or_create_list_bus(&request.view).await;
+                let snapshot = if with_snapshot {
+                    Some(self.entity_cache.get_all(&request.view).await)
+                } else {
+                    None
+                };
+                (Live::List(rx), snapshot)
+            }
+        };
+
+        let snapshot = snapshot
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(key, _)| request.key.as_ref().is_none_or(|wanted| wanted == key))
+            .map(|(key, data)| EntityFrame {
+                view: request.view.clone(),
+                key,
+                op: "snapshot".to_string(),
+                data: data.to_string(),
+                ..Default::default()
+            })
+            .collect();
+
+        debug!(view = %request.view, key = ?request.key, "gRPC subscription");
+        let subscription = Subscription {
+            view: request.view,
+            key: request.key,
+            snapshot,
+            live,
+            shutdown: self.shutdown.clone(),
+            skipped: 0,
+        };
+        let stream = futures_util::stream::unfold(subscription, |mut subscription| async move {
+            let frame = subscription.next().await?;
+            Some((Ok(frame), subscription))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+enum Live {
+    List(broadcast::Receiver<Arc<BusMessage>>),
+    State(watch::Receiver<Arc<BusMessage>>),
+}
+
+/// One client's stream of a view
+struct Subscription {
+    view: String,
+    key: Option<String>,
+    snapshot: VecDeque<EntityFrame>,
+    live: Live,
+    shutdown: CancellationToken,
+    /// Updates dropped since the last frame sent
+    skipped: u64,
+}
+
+impl Subscription {
+    async fn next(&mut self) -> Option<EntityFrame> {
+        if let Some(frame) = self.snapshot.pop_front() {
+            return Some(frame);
+        }
+        let shutdown = self.shutdown.clone();
+        loop {
+            let message = tokio::select! {
+                _ = shutdown.cancelled() => return None,
+                message = self.recv() => message?,
+            };
+            // An empty key reaches whole-view subscribers, like retention notices
+            if self.key.as_ref().is_some_and(|key| *key != message.key) {
+                continue;
+            }
+            match entity_frame(&self.view, &message) {
+                Some(mut frame) => {
+                    frame.skipped = std::mem::take(&mut self.skipped);
+                    return Some(frame);
+                }
+                None => warn!(view = %self.view, "Skipping a bus message that isn't a frame"),
+            }
+        }
+    }
+
+    /// The next bus message, counting the ones the client fell behind on
+    async fn recv(&mut self) -> Option<Arc<BusMessage>> {
+        match &mut self.live {
+            Live::List(rx) => loop {
+                match rx.recv().await {
+                    Ok(message) => return Some(message),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!(view = %self.view, "gRPC subscription lagged by {} updates", missed);
+                        self.skipped += missed;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            },
+            Live::State(rx) => loop {
+                rx.changed().await.ok()?;
+                let message = rx.borrow_and_update().clone();
+                if !message.payload.is_empty() {
+                    return Some(message);
+                }
+            },
+        }
+    }
+}
+
+/// The parts of a published frame carried by [`EntityFrame`]
+#[derive(Deserialize)]
+struct PublishedFrame {
+    op: String,
+    #[serde(default)]
+    data: Value,
+    #[serde(default)]
+    append: Vec<String>,
+    seq: Option<String>,
+}
+
+fn entity_frame(view: &str, message: &BusMessage) -> Option<EntityFrame> {
+    let frame: PublishedFrame = serde_json::from_slice(&message.payload).ok()?;
+    let slot = frame
+        .seq
+        .as_deref()
+        .and_then(|seq| seq.split(':').next())
+        .and_then(|slot| slot.parse().ok());
+    Some(EntityFrame {
+        view: view.to_string(),
+        key: message.key.clone(),
+        op: frame.op,
+        data: frame.data.to_string(),
+        append: frame.append,
+        slot,
+        seq: frame.seq,
+        skipped: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use serde_json::json;
+
+    #[test]
+    fn published_frame_maps_to_an_entity_frame() {
+        let payload = json!({
+            "mode": "list",
+            "entity": "Token/list",
+            "op": "patch",
+            "key": "a",
+            "data": { "trades": [1] },
+            "append": ["trades"],
+            "seq": "42:000000000007",
+        });
+        let message = BusMessage::new(
+            "a".to_string(),
+            "Token/list".to_string(),
+            Arc::new(Bytes::from(payload.to_string())),
+        );
+
+        let frame = entity_frame("Token/list", &message).unwrap();
+        assert_eq!(frame.key, "a");
+        assert_eq!(frame.op, "patch");
+        assert_eq!(
+            serde_json::from_str::<Value>(&frame.data).unwrap(),
+            json!({ "trades": [1] })
+        );
+        assert_eq!(frame.append, ["trades"]);
+        assert_eq!(frame.slot, Some(42));
+        assert_eq!(frame.seq.as_deref(), Some("42:000000000007"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Messages, client and server for `proto/stream.proto`.
+//!
+//! Written in the shape `tonic-prost-build` generates, so the crate builds
+//! without `protoc`. Keep it in sync with the proto file.
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscribeRequest {
+    /// View id, e.g. "OreRound/list"
+    #[prost(string, tag = "1")]
+    pub view: ::prost::alloc::string::String,
+    /// Only this entity. Required for state views.
+    #[prost(string, optional, tag = "2")]
+    pub key: ::core::option::Option<::prost::alloc::string::String>,
+    /// Send the cached entities first, true when unset
+    #[prost(bool, optional, tag = "3")]
+    pub with_snapshot: ::core::option::Option<bool>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EntityFrame {
+    #[prost(string, tag = "1")]
+    pub view: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub key: ::prost::alloc::string::String,
+    /// "snapshot" for cached entities, then the WebSocket frame op
+    #[prost(string, tag = "3")]
+    pub op: ::prost::alloc::string::String,
+    /// The entity for snapshots, otherwise the frame's data, as JSON
+    #[prost(string, tag = "4")]
+    pub data: ::prost::alloc::string::String,
+    /// Paths whose arrays are appended to rather than replaced
+    #[prost(string, repeated, tag = "5")]
+    pub append: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Slot of the update, unset for snapshots
+    #[prost(uint64, optional, tag = "6")]
+    pub slot: ::core::option::Option<u64>,
+    /// `_seq` cursor of the update
+    #[prost(string, optional, tag = "7")]
+    pub seq: ::core::option::Option<::prost::alloc::string::String>,
+    /// Updates dropped before this frame because the client read too slowly
+    #[prost(uint64, tag = "8")]
+    pub skipped: u64,
+}
+
+pub mod stream_client {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value
+    )]
+    use tonic::codegen::*;
+
+    #[derive(Debug, Clone)]
+    pub struct StreamClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+
+    impl StreamClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+
+    impl<T> StreamClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::Body>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + std::marker::Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + std::marker::Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+
+        pub async fn subscribe(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SubscribeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::EntityFrame>>,
+            tonic::Status,
+        > {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(SUBSCRIBE_PATH);
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new(
+                super::stream_server::SERVICE_NAME,
+                "Subscribe",
+            ));
+            self.inner.server_streaming(req, path, codec).await
+        }
+    }
+
+    const SUBSCRIBE_PATH: &str = "/hyperstack.stream.v1.Stream/Subscribe";
+}
+
+pub mod stream_server {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value
+    )]
+    use tonic::codegen::*;
+
+    /// gRPC methods of the `Stream` service
+    #[async_trait]
+    pub trait Stream: std::marker::Send + std::marker::Sync + 'static {
+        /// Server streaming response type for the Subscribe method.
+        type SubscribeStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::EntityFrame, tonic::Status>,
+            > + std::marker::Send
+            + 'static;
+
+        async fn subscribe(
+            &self,
+            request: tonic::Request<super::SubscribeRequest>,
+        ) -> std::result::Result<tonic::Response<Self::SubscribeStream>, tonic::Status>;
+    }
+
+    #[derive(Debug)]
+    pub struct StreamServer<T> {
+        inner: Arc<T>,
+    }
+
+    impl<T> StreamServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self {
+                inner: Arc::new(inner),
+            }
+        }
+    }
+
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for StreamServer<T>
+    where
+        T: Stream,
+        B: Body + std::marker::Send + 'static,
+        B::Error: Into<StdError> + std::marker::Send + 'static,
+    {
+        type Response = http::Response<tonic::body::Body>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            match req.uri().path() {
+                "/hyperstack.stream.v1.Stream/Subscribe" => {
+                    #[allow(non_camel_case_types)]
+                    struct SubscribeSvc<T: Stream>(pub Arc<T>);
+                    impl<T: Stream> tonic::server::ServerStreamingService<super::SubscribeRequest> for SubscribeSvc<T> {
+                        type Response = super::EntityFrame;
+                        type ResponseStream = T::SubscribeStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SubscribeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Stream>::subscribe(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SubscribeSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec);
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    let mut response = http::Response::new(tonic::body::Body::default());
+                    let headers = response.headers_mut();
+                    headers.insert(
+                        tonic::Status::GRPC_STATUS,
+                        (tonic::Code::Unimplemented as i32).into(),
+                    );
+                    headers.insert(
+                        http::header::CONTENT_TYPE,
+                        tonic::metadata::GRPC_CONTENT_TYPE,
+                    );
+                    Ok(response)
+                }),
+            }
+        }
+    }
+
+    impl<T> Clone for StreamServer<T> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+
+    /// gRPC service name
+    pub const SERVICE_NAME: &str = "hyperstack.stream.v1.Stream";
+
+    impl<T> tonic::server::NamedService for StreamServer<T> {
+        const NAME: &'static str = SERVICE_NAME;
+    }
+}
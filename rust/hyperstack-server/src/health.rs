@@ -1,9 +1,11 @@
-use std::collections::HashMap;
+use crate::dead_letter::{FailedEvent, FailedEvents};
+use hyperstack_interpreter::clock::{system_clock, SharedClock};
+use hyperstack_interpreter::VmError;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant, SystemTime};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tokio::time::interval;
 use tracing::{error, info, warn};
 
 /// Tracks the last processed slot for stream resumption after reconnection.
@@ -108,6 +110,9 @@ pub enum StreamStatus {
 pub struct HealthConfig {
     pub heartbeat_interval: Duration,
     pub health_check_timeout: Duration,
+    /// Strict-mode VM errors tolerated per event type before the stream is
+    /// marked as errored
+    pub vm_error_budget: u32,
 }
 
 impl Default for HealthConfig {
@@ -115,6 +120,7 @@ impl Default for HealthConfig {
         Self {
             heartbeat_interval: Duration::from_secs(30),
             health_check_timeout: Duration::from_secs(10),
+            vm_error_budget: 0,
         }
     }
 }
@@ -133,15 +139,51 @@ impl HealthConfig {
         self.health_check_timeout = timeout;
         self
     }
+
+    pub fn with_vm_error_budget(mut self, budget: u32) -> Self {
+        self.vm_error_budget = budget;
+        self
+    }
+}
+
+/// The stream of one program of a multi-program spec
+#[derive(Clone)]
+struct ProgramStream {
+    program_id: String,
+    status: Arc<RwLock<StreamStatus>>,
+    last_event_time: Arc<RwLock<Option<Instant>>>,
+    connection_start_time: Arc<RwLock<Option<Instant>>>,
+}
+
+impl ProgramStream {
+    fn new(program_id: &str) -> Self {
+        Self {
+            program_id: program_id.to_string(),
+            status: Arc::new(RwLock::new(StreamStatus::Disconnected)),
+            last_event_time: Arc::new(RwLock::new(None)),
+            connection_start_time: Arc::new(RwLock::new(None)),
+        }
+    }
 }
 
 /// Health monitor for tracking stream status and connectivity
 pub struct HealthMonitor {
     config: HealthConfig,
     stream_status: Arc<RwLock<StreamStatus>>,
-    last_event_time: Arc<RwLock<Option<SystemTime>>>,
+    last_event_time: Arc<RwLock<Option<Instant>>>,
     error_count: Arc<RwLock<u32>>,
     connection_start_time: Arc<RwLock<Option<Instant>>>,
+    /// Streams of the programs tracked separately, see
+    /// [`HealthMonitor::track_programs`]
+    programs: Arc<RwLock<Vec<ProgramStream>>>,
+    /// Set on a monitor recording the stream of a single program
+    program: Option<String>,
+    vm_errors: Arc<RwLock<HashMap<String, u32>>>,
+    handler_panics: Arc<AtomicU64>,
+    endpoints: Arc<RwLock<Vec<(String, StreamStatus)>>>,
+    notes: Arc<RwLock<BTreeMap<String, String>>>,
+    failed_events: Option<FailedEvents>,
+    clock: SharedClock,
 }
 
 impl HealthMonitor {
@@ -152,32 +194,92 @@ impl HealthMonitor {
             last_event_time: Arc::new(RwLock::new(None)),
             error_count: Arc::new(RwLock::new(0)),
             connection_start_time: Arc::new(RwLock::new(None)),
+            programs: Arc::new(RwLock::new(Vec::new())),
+            program: None,
+            vm_errors: Arc::new(RwLock::new(HashMap::new())),
+            handler_panics: Arc::new(AtomicU64::new(0)),
+            endpoints: Arc::new(RwLock::new(Vec::new())),
+            notes: Arc::new(RwLock::new(BTreeMap::new())),
+            failed_events: None,
+            clock: system_clock(),
         }
     }
 
+    /// Time heartbeats and stall detection with `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Keep the events the VM fails to process in `failed_events`.
+    ///
+    /// Their failures are then logged by the queue, rate-limited per event
+    /// type, rather than by [`HealthMonitor::record_vm_error`].
+    pub fn with_failed_events(mut self, failed_events: FailedEvents) -> Self {
+        self.failed_events = Some(failed_events);
+        self
+    }
+
+    /// The dead-letter queue, when attached
+    pub fn failed_events(&self) -> Option<&FailedEvents> {
+        self.failed_events.as_ref()
+    }
+
+    /// Monitors recording the stream of each of `program_ids` separately,
+    /// in the same order, replacing the programs tracked so far.
+    ///
+    /// The stream is healthy only while each program's stream is, so one
+    /// stalled program shows even when the others keep flowing. A single
+    /// program is recorded as the stream itself.
+    pub async fn track_programs(&self, program_ids: &[String]) -> Vec<HealthMonitor> {
+        let mut programs = self.programs.write().await;
+        programs.clear();
+        if program_ids.len() < 2 {
+            return program_ids.iter().map(|_| self.clone()).collect();
+        }
+
+        program_ids
+            .iter()
+            .map(|program_id| {
+                let stream = ProgramStream::new(program_id);
+                programs.push(stream.clone());
+                Self {
+                    stream_status: stream.status,
+                    last_event_time: stream.last_event_time,
+                    connection_start_time: stream.connection_start_time,
+                    program: Some(stream.program_id),
+                    ..self.clone()
+                }
+            })
+            .collect()
+    }
+
     /// Start the health monitoring background task
     pub async fn start(&self) -> tokio::task::JoinHandle<()> {
+        self.spawn()
+    }
+
+    /// Spawn the health monitoring background task on the current tokio runtime
+    pub fn spawn(&self) -> tokio::task::JoinHandle<()> {
         let monitor = self.clone();
 
         tokio::spawn(async move {
-            let mut interval = interval(monitor.config.heartbeat_interval);
-
             loop {
-                interval.tick().await;
                 monitor.check_health().await;
+                monitor.clock.sleep(monitor.config.heartbeat_interval).await;
             }
         })
     }
 
     /// Record that an event was received from the stream
     pub async fn record_event(&self) {
-        *self.last_event_time.write().await = Some(SystemTime::now());
+        *self.last_event_time.write().await = Some(self.clock.now_instant());
     }
 
     /// Record that the stream connection was established
     pub async fn record_connection(&self) {
         *self.stream_status.write().await = StreamStatus::Connected;
-        *self.connection_start_time.write().await = Some(Instant::now());
+        *self.connection_start_time.write().await = Some(self.clock.now_instant());
         info!("Stream connection established");
     }
 
@@ -201,26 +303,135 @@ impl HealthMonitor {
         error!("Stream error: {}", error);
     }
 
+    /// Record a strict-mode VM error raised while handling `event_type`.
+    ///
+    /// The stream is marked as errored once the event type has failed more
+    /// than [`HealthConfig::vm_error_budget`] times.
+    pub async fn record_vm_error(&self, event_type: &str, vm_error: &VmError) {
+        let failures = {
+            let mut vm_errors = self.vm_errors.write().await;
+            let failures = vm_errors.entry(event_type.to_string()).or_insert(0);
+            *failures += 1;
+            *failures
+        };
+        *self.error_count.write().await += 1;
+        if matches!(vm_error, VmError::HandlerPanicked { .. }) {
+            self.handler_panics.fetch_add(1, Ordering::Relaxed);
+        }
+        if self.failed_events.is_none() {
+            error!(
+                entity = vm_error.entity().unwrap_or_default(),
+                event_type,
+                kind = vm_error.kind(),
+                failures,
+                "VM error: {}",
+                vm_error
+            );
+        }
+
+        if failures > self.config.vm_error_budget {
+            *self.stream_status.write().await = StreamStatus::Error(format!(
+                "VM error budget exceeded for {}: {}",
+                event_type, vm_error
+            ));
+        }
+    }
+
+    /// Keep an event the VM failed to process, when a dead-letter queue is
+    /// attached. Health is still recorded with
+    /// [`HealthMonitor::record_vm_error`] or [`HealthMonitor::record_error`].
+    pub fn record_failed_event(&self, event: FailedEvent) {
+        if let Some(failed_events) = &self.failed_events {
+            failed_events.record(event);
+        }
+    }
+
+    /// Record the status of one Yellowstone endpoint.
+    ///
+    /// Endpoints are reported in the order they were first recorded. This
+    /// doesn't change the overall stream status.
+    pub async fn record_endpoint_status(&self, endpoint: &str, status: StreamStatus) {
+        let mut endpoints = self.endpoints.write().await;
+        match endpoints.iter_mut().find(|(name, _)| name == endpoint) {
+            Some((_, current)) => *current = status,
+            None => endpoints.push((endpoint.to_string(), status)),
+        }
+    }
+
+    /// Attach a note from `source` to the status report, replacing its
+    /// previous one. Notes don't change the overall health.
+    pub async fn set_note(&self, source: &str, note: impl Into<String>) {
+        self.notes
+            .write()
+            .await
+            .insert(source.to_string(), note.into());
+    }
+
+    /// Remove the note from `source`
+    pub async fn clear_note(&self, source: &str) {
+        self.notes.write().await.remove(source);
+    }
+
+    /// Current notes, ordered by source
+    pub async fn notes(&self) -> Vec<String> {
+        self.notes.read().await.values().cloned().collect()
+    }
+
     /// Check if the stream is currently healthy
     pub async fn is_healthy(&self) -> bool {
-        let status = self.stream_status.read().await;
-        let last_event_time = *self.last_event_time.read().await;
+        let programs = self.tracked_programs().await;
+        if programs.is_empty() {
+            return self
+                .stream_is_healthy(
+                    &self.stream_status,
+                    &self.last_event_time,
+                    &self.connection_start_time,
+                )
+                .await;
+        }
+
+        // Only VM errors are recorded on the stream shared by the programs
+        if matches!(*self.stream_status.read().await, StreamStatus::Error(_)) {
+            return false;
+        }
+        for program in &programs {
+            let healthy = self
+                .stream_is_healthy(
+                    &program.status,
+                    &program.last_event_time,
+                    &program.connection_start_time,
+                )
+                .await;
+            if !healthy {
+                return false;
+            }
+        }
+        true
+    }
+
+    async fn stream_is_healthy(
+        &self,
+        status: &RwLock<StreamStatus>,
+        last_event_time: &RwLock<Option<Instant>>,
+        connection_start_time: &RwLock<Option<Instant>>,
+    ) -> bool {
+        let status = status.read().await;
+        let last_event_time = *last_event_time.read().await;
+        let now = self.clock.now_instant();
 
         match *status {
             StreamStatus::Connected => {
                 // Check if we've received events recently
                 if let Some(last_event) = last_event_time {
-                    let time_since_last_event = SystemTime::now()
-                        .duration_since(last_event)
-                        .unwrap_or(Duration::from_secs(u64::MAX));
+                    let time_since_last_event = now.saturating_duration_since(last_event);
 
                     // Consider unhealthy if no events for 2x heartbeat interval
                     time_since_last_event < (self.config.heartbeat_interval * 2)
                 } else {
                     // No events yet, but connected - might be waiting for first event
-                    let connection_time = self.connection_start_time.read().await;
+                    let connection_time = connection_start_time.read().await;
                     if let Some(start_time) = *connection_time {
-                        let time_since_connection = start_time.elapsed();
+                        let time_since_connection = now.saturating_duration_since(start_time);
                         // Give it some time to receive first event
                         time_since_connection < Duration::from_secs(60)
                     } else {
@@ -233,9 +444,44 @@ impl HealthMonitor {
         }
     }
 
-    /// Get the current stream status
+    /// Get the current stream status. With programs tracked separately,
+    /// the status of the first one that isn't connected.
     pub async fn status(&self) -> StreamStatus {
-        self.stream_status.read().await.clone()
+        let status = self.stream_status.read().await.clone();
+        if matches!(status, StreamStatus::Error(_)) {
+            return status;
+        }
+        let programs = self.tracked_programs().await;
+        if programs.is_empty() {
+            return status;
+        }
+        for program in &programs {
+            let status = program.status.read().await.clone();
+            if !matches!(status, StreamStatus::Connected) {
+                return status;
+            }
+        }
+        StreamStatus::Connected
+    }
+
+    /// Status of each program tracked separately, in the order they were
+    /// passed to [`HealthMonitor::track_programs`]
+    pub async fn program_statuses(&self) -> Vec<(String, StreamStatus)> {
+        let mut statuses = Vec::new();
+        for program in self.tracked_programs().await {
+            let status = program.status.read().await.clone();
+            statuses.push((program.program_id, status));
+        }
+        statuses
+    }
+
+    /// Programs whose streams make up this monitor's, none when it records
+    /// a single stream
+    async fn tracked_programs(&self) -> Vec<ProgramStream> {
+        if self.program.is_some() {
+            return Vec::new();
+        }
+        self.programs.read().await.clone()
     }
 
     /// Get the current error count
@@ -243,9 +489,25 @@ impl HealthMonitor {
         *self.error_count.read().await
     }
 
+    /// Strict-mode VM error counts per event type
+    pub async fn vm_error_counts(&self) -> HashMap<String, u32> {
+        self.vm_errors.read().await.clone()
+    }
+
+    /// Handlers and computed field evaluators that panicked, across all
+    /// event types
+    pub fn handler_panic_count(&self) -> u64 {
+        self.handler_panics.load(Ordering::Relaxed)
+    }
+
+    /// Status of each Yellowstone endpoint, in the order they were first recorded
+    pub async fn endpoint_statuses(&self) -> Vec<(String, StreamStatus)> {
+        self.endpoints.read().await.clone()
+    }
+
     async fn check_health(&self) {
         let is_healthy = self.is_healthy().await;
-        let status = self.stream_status.read().await.clone();
+        let status = self.status().await;
 
         if !is_healthy {
             match status {
@@ -274,6 +536,161 @@ impl Clone for HealthMonitor {
             last_event_time: Arc::clone(&self.last_event_time),
             error_count: Arc::clone(&self.error_count),
             connection_start_time: Arc::clone(&self.connection_start_time),
+            programs: Arc::clone(&self.programs),
+            program: self.program.clone(),
+            vm_errors: Arc::clone(&self.vm_errors),
+            handler_panics: Arc::clone(&self.handler_panics),
+            endpoints: Arc::clone(&self.endpoints),
+            notes: Arc::clone(&self.notes),
+            failed_events: self.failed_events.clone(),
+            clock: Arc::clone(&self.clock),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperstack_interpreter::testkit::ManualClock;
+
+    fn null_key(event_type: &str) -> VmError {
+        VmError::NullKey {
+            entity: "Round".to_string(),
+            event_type: event_type.to_string(),
+            register: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn vm_errors_flip_health_once_an_event_type_exceeds_its_budget() {
+        let monitor = HealthMonitor::new(HealthConfig::new().with_vm_error_budget(1));
+        monitor.record_connection().await;
+        monitor.record_event().await;
+
+        monitor
+            .record_vm_error("RoundState", &null_key("RoundState"))
+            .await;
+        monitor
+            .record_vm_error("MinerState", &null_key("MinerState"))
+            .await;
+        assert!(
+            monitor.is_healthy().await,
+            "each event type is within budget"
+        );
+
+        monitor
+            .record_vm_error("RoundState", &null_key("RoundState"))
+            .await;
+        assert!(!monitor.is_healthy().await);
+        assert!(matches!(monitor.status().await, StreamStatus::Error(_)));
+        assert_eq!(monitor.error_count().await, 3);
+
+        let counts = monitor.vm_error_counts().await;
+        assert_eq!(counts.get("RoundState"), Some(&2));
+        assert_eq!(counts.get("MinerState"), Some(&1));
+        assert_eq!(monitor.handler_panic_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn handler_panics_are_counted_against_the_vm_error_budget() {
+        let monitor = HealthMonitor::new(HealthConfig::new());
+        monitor.record_connection().await;
+        monitor.record_event().await;
+
+        let panicked = VmError::HandlerPanicked {
+            entity: "Round".to_string(),
+            event_type: "RoundState".to_string(),
+            message: "index out of bounds".to_string(),
+        };
+        monitor.record_vm_error("RoundState", &panicked).await;
+
+        assert_eq!(monitor.handler_panic_count(), 1);
+        assert!(!monitor.is_healthy().await);
+    }
+
+    #[tokio::test]
+    async fn notes_are_reported_without_changing_health() {
+        let monitor = HealthMonitor::new(HealthConfig::new());
+        monitor.record_connection().await;
+        monitor.record_event().await;
+
+        monitor.set_note("load_shedding", "shedding").await;
+        monitor.set_note("load_shedding", "shedding more").await;
+        assert_eq!(monitor.notes().await, vec!["shedding more".to_string()]);
+        assert!(monitor.is_healthy().await);
+
+        monitor.clear_note("load_shedding").await;
+        assert!(monitor.notes().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stream_goes_stale_without_events_for_two_heartbeats() {
+        let clock = ManualClock::default();
+        let monitor = HealthMonitor::new(
+            HealthConfig::new().with_heartbeat_interval(Duration::from_secs(30)),
+        )
+        .with_clock(Arc::new(clock.clone()));
+
+        monitor.record_connection().await;
+        clock.advance(Duration::from_secs(59));
+        assert!(monitor.is_healthy().await, "waiting for the first event");
+        clock.advance(Duration::from_secs(1));
+        assert!(!monitor.is_healthy().await);
+
+        monitor.record_event().await;
+        clock.advance(Duration::from_secs(59));
+        assert!(monitor.is_healthy().await);
+        clock.advance(Duration::from_secs(1));
+        assert!(!monitor.is_healthy().await, "no events for two heartbeats");
+    }
+
+    #[tokio::test]
+    async fn each_program_stream_must_be_healthy() {
+        let monitor = HealthMonitor::new(HealthConfig::new());
+        let programs = monitor
+            .track_programs(&["pump".to_string(), "amm".to_string()])
+            .await;
+        for program in &programs {
+            program.record_connection().await;
+            program.record_event().await;
+        }
+        assert!(monitor.is_healthy().await);
+
+        programs[1].record_disconnection().await;
+        assert!(!monitor.is_healthy().await, "amm stopped while pump flows");
+        assert!(programs[0].is_healthy().await);
+        assert!(matches!(monitor.status().await, StreamStatus::Disconnected));
+        let statuses = monitor.program_statuses().await;
+        assert_eq!(statuses[0].0, "pump");
+        assert!(matches!(statuses[0].1, StreamStatus::Connected));
+        assert!(matches!(statuses[1].1, StreamStatus::Disconnected));
+
+        // Back to a single program, recorded on the monitor itself
+        let programs = monitor.track_programs(&["pump".to_string()]).await;
+        programs[0].record_connection().await;
+        programs[0].record_event().await;
+        assert!(monitor.is_healthy().await);
+        assert!(monitor.program_statuses().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn health_checks_run_once_per_heartbeat() {
+        let clock = ManualClock::default();
+        let monitor = HealthMonitor::new(
+            HealthConfig::new().with_heartbeat_interval(Duration::from_secs(30)),
+        )
+        .with_clock(Arc::new(clock.clone()));
+
+        let task = monitor.spawn();
+        for _ in 0..3 {
+            while clock.pending_sleeps() == 0 {
+                tokio::task::yield_now().await;
+            }
+            clock.advance(Duration::from_secs(29));
+            assert_eq!(clock.pending_sleeps(), 1, "the next check isn't due yet");
+            clock.advance(Duration::from_secs(1));
         }
+
+        task.abort();
     }
 }
@@ -1,16 +1,32 @@
-use crate::health::HealthMonitor;
+use crate::cache::EntityCache;
+use crate::dead_letter::FailedEvents;
+use crate::debug_bundle::{BundleState, DebugBundles};
+use crate::dictionary::Dictionaries;
+use crate::drain::{parse_grace, DrainController, DEFAULT_DRAIN_GRACE};
+use crate::flags::{FlagUpdateError, Flags};
+use crate::health::{HealthMonitor, SlotTracker};
+use crate::shadow::ShadowDiff;
+use crate::websocket::auth::ConnectionAuthRequest;
+use crate::websocket::client_manager::ClientManager;
+use crate::VmStatsFn;
 use anyhow::Result;
-use http_body_util::Full;
+use http_body_util::{BodyExt, Full, Limited};
 use hyper::body::Bytes;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
-use hyper::{Request, Response, StatusCode};
+use hyper::{Method, Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
+use serde_json::{json, Value};
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
-use tracing::{error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// How long `/stats` waits for the VM to report before leaving it out
+const VM_STATS_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Configuration for the HTTP health server
 #[derive(Clone, Debug)]
@@ -34,10 +50,98 @@ impl HttpHealthConfig {
     }
 }
 
+/// What `/stats` on the health server reports: the VM's memory and pending
+/// queue stats, the entities cached per view, connected clients,
+/// subscriptions per view, frames sent and the last processed slot.
+///
+/// Each part is read as a snapshot. The cache and subscription maps are read
+/// one table at a time, and the VM computes its stats on its own thread.
+#[derive(Clone)]
+pub struct RuntimeStats {
+    entity_cache: EntityCache,
+    slots: SlotTracker,
+    clients: Option<ClientManager>,
+    vm_stats: Option<VmStatsFn>,
+}
+
+impl RuntimeStats {
+    pub fn new(entity_cache: EntityCache, slots: SlotTracker) -> Self {
+        Self {
+            entity_cache,
+            slots,
+            clients: None,
+            vm_stats: None,
+        }
+    }
+
+    /// Report these clients and their subscriptions
+    pub fn with_clients(mut self, clients: ClientManager) -> Self {
+        self.clients = Some(clients);
+        self
+    }
+
+    /// Report these stats of the spec's VM under `vm`
+    pub fn with_vm_stats(mut self, vm_stats: VmStatsFn) -> Self {
+        self.vm_stats = Some(vm_stats);
+        self
+    }
+
+    pub async fn snapshot(&self) -> Value {
+        let (clients, subscriptions, frames_sent) = match &self.clients {
+            Some(clients) => (
+                Some(clients.client_count()),
+                Some(clients.subscriptions_per_view().await),
+                Some(clients.frames_sent()),
+            ),
+            None => (None, None, None),
+        };
+        let views = self.entity_cache.view_sizes().await;
+
+        json!({
+            "last_slot": self.slots.get(),
+            "clients": clients,
+            "subscriptions": subscriptions,
+            "frames_sent": frames_sent,
+            "cache": {
+                "total_entities": views.values().sum::<usize>(),
+                "views": views,
+            },
+            "vm": vm_stats_snapshot(self.vm_stats.clone()).await,
+        })
+    }
+}
+
+/// Read the VM's stats on a blocking thread, since they wait for the VM
+/// thread, giving up after [`VM_STATS_TIMEOUT`].
+///
+/// Shared by the standalone health server and the embedded router.
+pub(crate) async fn vm_stats_snapshot(vm_stats: Option<VmStatsFn>) -> Option<Value> {
+    let vm_stats = vm_stats?;
+    match tokio::time::timeout(
+        VM_STATS_TIMEOUT,
+        tokio::task::spawn_blocking(move || vm_stats()),
+    )
+    .await
+    {
+        Ok(stats) => stats.ok().flatten(),
+        Err(_) => {
+            warn!("VM stats not ready within {:?}", VM_STATS_TIMEOUT);
+            None
+        }
+    }
+}
+
 /// HTTP server that exposes health endpoints
 pub struct HttpHealthServer {
     bind_addr: SocketAddr,
     health_monitor: Option<HealthMonitor>,
+    shadow_diff: Option<ShadowDiff>,
+    drain: Option<DrainController>,
+    debug_bundles: Option<DebugBundles>,
+    failed_events: Option<FailedEvents>,
+    dictionaries: Option<Dictionaries>,
+    flags: Option<Arc<Flags>>,
+    stats: Option<RuntimeStats>,
 }
 
 impl HttpHealthServer {
@@ -45,6 +149,13 @@ impl HttpHealthServer {
         Self {
             bind_addr,
             health_monitor: None,
+            shadow_diff: None,
+            drain: None,
+            debug_bundles: None,
+            failed_events: None,
+            dictionaries: None,
+            flags: None,
+            stats: None,
         }
     }
 
@@ -53,24 +164,113 @@ impl HttpHealthServer {
         self
     }
 
+    /// Serve the shadow deployment diff report at `/admin/shadow`
+    pub fn with_shadow_diff(mut self, diff: ShadowDiff) -> Self {
+        self.shadow_diff = Some(diff);
+        self
+    }
+
+    /// Serve `/admin/drain` and report readiness as false while draining
+    pub fn with_drain_controller(mut self, drain: DrainController) -> Self {
+        self.drain = Some(drain);
+        self
+    }
+
+    /// Serve `/admin/debug-bundle`
+    pub fn with_debug_bundles(mut self, bundles: DebugBundles) -> Self {
+        self.debug_bundles = Some(bundles);
+        self
+    }
+
+    /// Serve `/admin/failed-events`
+    pub fn with_failed_events(mut self, failed_events: FailedEvents) -> Self {
+        self.failed_events = Some(failed_events);
+        self
+    }
+
+    /// Serve the compression dictionary at `/dictionaries/<id>`
+    pub fn with_dictionaries(mut self, dictionaries: Dictionaries) -> Self {
+        self.dictionaries = Some(dictionaries);
+        self
+    }
+
+    /// Serve `/admin/flags`
+    pub fn with_flags(mut self, flags: Arc<Flags>) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
+    /// Serve `/stats`
+    pub fn with_stats(mut self, stats: RuntimeStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
     pub async fn start(self) -> Result<()> {
+        self.start_until(CancellationToken::new()).await
+    }
+
+    /// Serve requests until `shutdown` is cancelled
+    pub async fn start_until(self, shutdown: CancellationToken) -> Result<()> {
         info!("Starting HTTP health server on {}", self.bind_addr);
 
         let listener = TcpListener::bind(&self.bind_addr).await?;
         info!("HTTP health server listening on {}", self.bind_addr);
 
         let health_monitor = Arc::new(self.health_monitor);
+        let shadow_diff = Arc::new(self.shadow_diff);
+        let drain = Arc::new(self.drain);
+        let debug_bundles = Arc::new(self.debug_bundles);
+        let failed_events = Arc::new(self.failed_events);
+        let dictionaries = Arc::new(self.dictionaries);
+        let flags = Arc::new(self.flags);
+        let stats = Arc::new(self.stats);
 
         loop {
-            match listener.accept().await {
-                Ok((stream, _addr)) => {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = shutdown.cancelled() => {
+                    info!("HTTP health server on {} stopped", self.bind_addr);
+                    return Ok(());
+                }
+            };
+            match accepted {
+                Ok((stream, remote_addr)) => {
                     let io = TokioIo::new(stream);
                     let monitor = health_monitor.clone();
+                    let shadow_diff = shadow_diff.clone();
+                    let drain = drain.clone();
+                    let debug_bundles = debug_bundles.clone();
+                    let failed_events = failed_events.clone();
+                    let dictionaries = dictionaries.clone();
+                    let flags = flags.clone();
+                    let stats = stats.clone();
 
                     tokio::spawn(async move {
                         let service = service_fn(move |req| {
                             let monitor = monitor.clone();
-                            async move { handle_request(req, monitor).await }
+                            let shadow_diff = shadow_diff.clone();
+                            let drain = drain.clone();
+                            let debug_bundles = debug_bundles.clone();
+                            let failed_events = failed_events.clone();
+                            let dictionaries = dictionaries.clone();
+                            let flags = flags.clone();
+                            let stats = stats.clone();
+                            async move {
+                                handle_request(
+                                    req,
+                                    remote_addr,
+                                    monitor,
+                                    shadow_diff,
+                                    drain,
+                                    debug_bundles,
+                                    failed_events,
+                                    dictionaries,
+                                    flags,
+                                    stats,
+                                )
+                                .await
+                            }
                         });
 
                         if let Err(e) = http1::Builder::new().serve_connection(io, service).await {
@@ -86,11 +286,279 @@ impl HttpHealthServer {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_request(
     req: Request<hyper::body::Incoming>,
+    remote_addr: SocketAddr,
     health_monitor: Arc<Option<HealthMonitor>>,
+    shadow_diff: Arc<Option<ShadowDiff>>,
+    drain: Arc<Option<DrainController>>,
+    debug_bundles: Arc<Option<DebugBundles>>,
+    failed_events: Arc<Option<FailedEvents>>,
+    dictionaries: Arc<Option<Dictionaries>>,
+    flags: Arc<Option<Arc<Flags>>>,
+    stats: Arc<Option<RuntimeStats>>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    if let (Some(diff), "/admin/shadow") = (shadow_diff.as_ref(), req.uri().path()) {
+        let report_json = serde_json::to_string(&diff.report()).unwrap_or_default();
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Full::new(Bytes::from(report_json)))
+            .unwrap());
+    }
+
+    if let (Some(stats), "/stats") = (stats.as_ref(), req.uri().path()) {
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Full::new(Bytes::from(stats.snapshot().await.to_string())))
+            .unwrap());
+    }
+
+    if let (Some(drain), "/admin/drain") = (drain.as_ref(), req.uri().path()) {
+        return Ok(if req.method() == Method::POST {
+            drain_response(req.uri().query(), drain)
+        } else {
+            drain_status_response(drain)
+        });
+    }
+
+    if let Some(bundles) = debug_bundles.as_ref() {
+        match (req.method(), req.uri().path()) {
+            (&Method::POST, "/admin/debug-bundle") => {
+                return Ok(debug_bundle_start_response(bundles))
+            }
+            (_, "/admin/debug-bundle") => return Ok(debug_bundle_status_response(bundles)),
+            (_, "/admin/debug-bundle/download") => {
+                return Ok(debug_bundle_download_response(bundles))
+            }
+            _ => {}
+        }
+    }
+
+    if let (Some(failed_events), "/admin/failed-events") =
+        (failed_events.as_ref(), req.uri().path())
+    {
+        return Ok(failed_events_response(
+            failed_events,
+            req.method() == Method::POST,
+        ));
+    }
+
+    if let (Some(flags), "/admin/flags") = (flags.as_ref(), req.uri().path()) {
+        if req.method() != Method::PATCH {
+            return Ok(flags_response(flags));
+        }
+        let auth_request = ConnectionAuthRequest::from_http_request(remote_addr, &req);
+        let body = match Limited::new(req.into_body(), MAX_FLAGS_BODY_BYTES)
+            .collect()
+            .await
+        {
+            Ok(body) => body.to_bytes(),
+            Err(_) => Bytes::new(),
+        };
+        return Ok(flags_update_response(flags, &auth_request, &body).await);
+    }
+
+    if let (Some(dictionaries), Some(id)) = (
+        dictionaries.as_ref(),
+        req.uri().path().strip_prefix("/dictionaries/"),
+    ) {
+        return Ok(dictionary_response(Some(dictionaries), id));
+    }
+
+    health_response(
+        req.uri().path(),
+        health_monitor.as_ref().as_ref(),
+        drain.as_ref().as_ref(),
+    )
+    .await
+}
+
+/// Start draining with the `grace` query parameter and report the drain state.
+///
+/// Shared by the standalone health server and the embedded router.
+pub(crate) fn drain_response(
+    query: Option<&str>,
+    drain: &DrainController,
+) -> Response<Full<Bytes>> {
+    let grace = query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .filter_map(|pair| pair.split_once('='))
+        .find_map(|(key, value)| (key == "grace").then_some(value));
+    let grace = match grace.map(parse_grace) {
+        None => DEFAULT_DRAIN_GRACE,
+        Some(Some(grace)) => grace,
+        Some(None) => {
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header("Content-Type", "text/plain")
+                .body(Full::new(Bytes::from(
+                    "Invalid grace period (expected e.g. 120s, 2m or 500ms)",
+                )))
+                .unwrap();
+        }
+    };
+
+    let status = drain.start(grace);
+    Response::builder()
+        .status(StatusCode::ACCEPTED)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(
+            serde_json::to_string(&status).unwrap_or_default(),
+        )))
+        .unwrap()
+}
+
+/// Report the drain state without changing it.
+pub(crate) fn drain_status_response(drain: &DrainController) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(
+            serde_json::to_string(&drain.status()).unwrap_or_default(),
+        )))
+        .unwrap()
+}
+
+/// List the failed events, emptying the queue when `drain` is set.
+///
+/// Shared by the standalone health server and the embedded router.
+pub(crate) fn failed_events_response(
+    failed_events: &FailedEvents,
+    drain: bool,
+) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(
+            serde_json::to_string(&failed_events.report(drain)).unwrap_or_default(),
+        )))
+        .unwrap()
+}
+
+/// Start assembling a debug bundle and report its progress.
+pub(crate) fn debug_bundle_start_response(bundles: &DebugBundles) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::ACCEPTED)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(
+            serde_json::to_string(&bundles.start()).unwrap_or_default(),
+        )))
+        .unwrap()
+}
+
+/// Report the progress of the last debug bundle.
+pub(crate) fn debug_bundle_status_response(bundles: &DebugBundles) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(
+            serde_json::to_string(&bundles.status()).unwrap_or_default(),
+        )))
+        .unwrap()
+}
+
+/// Largest `PATCH /admin/flags` body read, larger ones are rejected as invalid
+pub(crate) const MAX_FLAGS_BODY_BYTES: usize = 64 * 1024;
+
+/// Report the feature flags and their recent changes.
+pub(crate) fn flags_response(flags: &Flags) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(
+            serde_json::to_string(&flags.stats()).unwrap_or_default(),
+        )))
+        .unwrap()
+}
+
+/// Change the feature flags in `body` if `request` carries an admin token,
+/// and report them.
+pub(crate) async fn flags_update_response(
+    flags: &Flags,
+    request: &ConnectionAuthRequest,
+    body: &[u8],
+) -> Response<Full<Bytes>> {
+    let (status, message) = match flags.update(request, body).await {
+        Ok(_) => return flags_response(flags),
+        Err(FlagUpdateError::Disabled) => (
+            StatusCode::FORBIDDEN,
+            "Feature flags can't be changed without admin auth".to_string(),
+        ),
+        Err(FlagUpdateError::Unauthorized(reason)) => (StatusCode::UNAUTHORIZED, reason),
+        Err(FlagUpdateError::Invalid(reason)) => (StatusCode::BAD_REQUEST, reason),
+    };
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "text/plain")
+        .body(Full::new(Bytes::from(message)))
+        .unwrap()
+}
+
+/// Serve the compression dictionary `id`. Ids name one dictionary for good,
+/// so clients may cache it forever.
+pub(crate) fn dictionary_response(
+    dictionaries: Option<&Dictionaries>,
+    id: &str,
+) -> Response<Full<Bytes>> {
+    match dictionaries.and_then(|dictionaries| dictionaries.get(id)) {
+        Some(dictionary) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/octet-stream")
+            .header("Cache-Control", "private, max-age=31536000, immutable")
+            .body(Full::new(dictionary.bytes().clone()))
+            .unwrap(),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header("Content-Type", "text/plain")
+            .body(Full::new(Bytes::from("Unknown compression dictionary")))
+            .unwrap(),
+    }
+}
+
+/// Serve the last debug bundle, or its progress with `409 Conflict` while it
+/// isn't ready.
+pub(crate) fn debug_bundle_download_response(bundles: &DebugBundles) -> Response<Full<Bytes>> {
+    match bundles.archive() {
+        Some(archive) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/gzip")
+            .header(
+                "Content-Disposition",
+                "attachment; filename=\"hyperstack-debug.tar.gz\"",
+            )
+            .body(Full::new(archive))
+            .unwrap(),
+        None => {
+            let status = bundles.status();
+            let code = match status.state {
+                BundleState::Idle => StatusCode::NOT_FOUND,
+                _ => StatusCode::CONFLICT,
+            };
+            Response::builder()
+                .status(code)
+                .header("Content-Type", "application/json")
+                .body(Full::new(Bytes::from(
+                    serde_json::to_string(&status).unwrap_or_default(),
+                )))
+                .unwrap()
+        }
+    }
+}
+
+/// Build the response for a health endpoint path.
+///
+/// Shared by the standalone health server and the routes mounted by
+/// [`Runtime::into_router`](crate::Runtime::into_router).
+pub(crate) async fn health_response(
+    path: &str,
+    health_monitor: Option<&HealthMonitor>,
+    drain: Option<&DrainController>,
 ) -> Result<Response<Full<Bytes>>, Infallible> {
-    let path = req.uri().path();
+    let draining = drain.is_some_and(DrainController::is_draining);
 
     match path {
         "/health" | "/healthz" => {
@@ -101,9 +569,15 @@ async fn handle_request(
                 .body(Full::new(Bytes::from("OK")))
                 .unwrap())
         }
-        "/ready" | "/readiness" => {
-            // Readiness check - check if stream is healthy
-            if let Some(monitor) = health_monitor.as_ref() {
+        "/ready" | "/readiness" | "/readyz" => {
+            // Readiness check - not ready while draining or if the stream is unhealthy
+            if draining {
+                Ok(Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .header("Content-Type", "text/plain")
+                    .body(Full::new(Bytes::from("DRAINING")))
+                    .unwrap())
+            } else if let Some(monitor) = health_monitor {
                 if monitor.is_healthy().await {
                     Ok(Response::builder()
                         .status(StatusCode::OK)
@@ -128,15 +602,44 @@ async fn handle_request(
         }
         "/status" => {
             // Detailed status endpoint
-            if let Some(monitor) = health_monitor.as_ref() {
+            if let Some(monitor) = health_monitor {
                 let status = monitor.status().await;
                 let error_count = monitor.error_count().await;
+                let vm_errors = monitor.vm_error_counts().await;
                 let is_healthy = monitor.is_healthy().await;
+                let endpoints: Vec<_> = monitor
+                    .endpoint_statuses()
+                    .await
+                    .into_iter()
+                    .map(|(endpoint, status)| {
+                        serde_json::json!({
+                            "endpoint": endpoint,
+                            "status": format!("{:?}", status),
+                        })
+                    })
+                    .collect();
+                let programs: Vec<_> = monitor
+                    .program_statuses()
+                    .await
+                    .into_iter()
+                    .map(|(program_id, status)| {
+                        serde_json::json!({
+                            "program_id": program_id,
+                            "status": format!("{:?}", status),
+                        })
+                    })
+                    .collect();
 
                 let status_json = serde_json::json!({
                     "healthy": is_healthy,
                     "status": format!("{:?}", status),
-                    "error_count": error_count
+                    "error_count": error_count,
+                    "vm_errors": vm_errors,
+                    "handler_panics": monitor.handler_panic_count(),
+                    "endpoints": endpoints,
+                    "programs": programs,
+                    "notes": monitor.notes().await,
+                    "draining": draining
                 });
 
                 let status_code = if is_healthy {
@@ -154,7 +657,8 @@ async fn handle_request(
                 let status_json = serde_json::json!({
                     "healthy": true,
                     "status": "no_monitor",
-                    "error_count": 0
+                    "error_count": 0,
+                    "draining": draining
                 });
 
                 Ok(Response::builder()
@@ -26,53 +26,163 @@
 //! }
 //! ```
 //!
+//! ## Embedding in an axum application
+//!
+//! Instead of binding its own ports, the server can be mounted on an existing
+//! [`axum::Router`]; the application stays in charge of the listener:
+//!
+//! ```rust,ignore
+//! let (stream_router, background) = Server::builder()
+//!     .spec(my_spec())
+//!     .build()?
+//!     .into_router_parts();
+//! let background = background.spawn_background(&tokio::runtime::Handle::current());
+//!
+//! let app = axum::Router::new().nest("/stream", stream_router);
+//! let listener = tokio::net::TcpListener::bind("[::]:8080").await?;
+//! axum::serve(
+//!     listener,
+//!     app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+//! )
+//! .await?;
+//! background.shutdown();
+//! ```
+//!
+//! See the [`router`] module for the available routes and shutdown behaviour.
+//!
+//! ## Stopping the server
+//!
+//! [`ServerBuilder::start_with_shutdown`] runs the server in the background
+//! and returns a [`ServerHandle`] that stops it:
+//!
+//! ```rust,ignore
+//! let server = Server::builder()
+//!     .spec(my_spec())
+//!     .websocket()
+//!     .shutdown_grace(std::time::Duration::from_secs(5))
+//!     .start_with_shutdown()
+//!     .await?;
+//!
+//! // ... when the application shuts down
+//! let report = server.shutdown().await?;
+//! if !report.graceful {
+//!     tracing::warn!("{} connections were cut off", report.open_connections);
+//! }
+//! ```
+//!
+//! See the [`shutdown`] module for the order things stop in.
+//!
 //! ## Feature Flags
 //!
 //! - `otel` - OpenTelemetry integration for metrics and distributed tracing
+//! - `grpc` - gRPC server-streaming endpoint next to the WebSocket server
 
+pub mod append_log;
+pub mod backfill;
+pub mod big_numbers;
 pub mod bus;
 pub mod cache;
+pub mod coalesce;
 pub mod compression;
 pub mod config;
+pub mod dead_letter;
+pub mod debug_bundle;
+pub mod dictionary;
+pub mod drain;
+pub mod extra_accounts;
+pub mod flags;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod health;
 pub mod http_health;
+pub mod load_shed;
 pub mod materialized_view;
 #[cfg(feature = "otel")]
 pub mod metrics;
 pub mod mutation_batch;
+pub mod persistence;
 pub mod projector;
+pub mod reload;
+pub mod router;
 pub mod runtime;
+pub mod shadow;
+pub mod shutdown;
 pub mod sorted_cache;
+pub mod sources;
 pub mod telemetry;
+pub mod txn_group;
 pub mod view;
+pub mod view_coalesce;
+pub mod webhook;
 pub mod websocket;
 
+pub use append_log::{AppendLog, AppendLogConfig, AppendLogStats, LogLimits};
+pub use backfill::{
+    AccountBackfillFn, BackfillAccount, BackfillOutcome, RpcBackfill, RpcBackfillConfig,
+};
+pub use big_numbers::BigNumbers;
 pub use bus::{BusManager, BusMessage};
-pub use cache::{EntityCache, EntityCacheConfig};
+pub use cache::{EntityCache, EntityCacheConfig, RetentionNotice};
+pub use coalesce::{AdaptiveWindow, CoalesceConfig, PassStats};
 pub use config::{
     HealthConfig, HttpHealthConfig, ReconnectionConfig, ServerConfig, WebSocketConfig,
-    YellowstoneConfig,
+    YellowstoneConfig, YellowstoneEndpoint, YellowstoneStrategy,
 };
+pub use dead_letter::{DeadLetterConfig, FailedEvent, FailedEventFn, FailedEvents};
+pub use debug_bundle::{
+    DeadLetter, DeadLetters, DebugBundleConfig, DebugBundleStatus, DebugBundles, VmSnapshotFn,
+};
+pub use dictionary::{CompressionDictionary, Dictionaries, DictionarySource};
+pub use drain::{DrainController, DrainStatus, MigrateSoonMessage};
+pub use extra_accounts::{AccountFilter, TokenAccount};
+pub use flags::{Flag, FlagChange, FlagStats, FlagUpdateError, Flags, FlagsConfig};
+#[cfg(feature = "grpc")]
+pub use grpc::{GrpcConfig, GrpcServer};
 pub use health::{HealthMonitor, SlotTracker, StreamStatus};
-pub use http_health::HttpHealthServer;
+pub use http_health::{HttpHealthServer, RuntimeStats};
 pub use hyperstack_auth::{AsyncVerifier, KeyLoader, Limits, TokenVerifier, VerifyingKey};
-pub use materialized_view::{MaterializedView, MaterializedViewRegistry, ViewEffect};
+pub use hyperstack_interpreter::big_numbers::BigNumberMode;
+pub use hyperstack_interpreter::clock::{Clock, SharedClock, SystemClock};
+pub use hyperstack_interpreter::testkit;
+pub use load_shed::{
+    LevelChange, LoadShedConfig, LoadShedStats, LoadShedder, PressureSample, ShedController,
+    ShedCounts, ShedLevel,
+};
+pub use materialized_view::{
+    EvaluationCost, Maintained, MaterializedView, MaterializedViewRegistry, Quarantine,
+    QuarantinedViews, ViewBudget, ViewEffect, ViewEvaluationStats, ViewFailure, ViewPipeline,
+    ViewPredicate,
+};
 #[cfg(feature = "otel")]
 pub use metrics::Metrics;
 pub use mutation_batch::{EventContext, MutationBatch, SlotContext};
+pub use persistence::{
+    FileStatePersistence, PersistedEntity, PersistenceConfig, StatePersistence, StateSnapshot,
+    VmStateExportFn, VmStateHooks, VmStateImportFn,
+};
 pub use projector::Projector;
+pub use reload::{
+    Live, LiveBytecode, LiveViews, ReloadError, ReloadNotice, ReloadReport, SpecReloader,
+};
+pub use router::{BackgroundHandle, BackgroundTasks};
 pub use runtime::Runtime;
+pub use shadow::{ShadowConfig, ShadowDiff, ShadowReport};
+pub use shutdown::{ServerHandle, ShutdownReport, StopReason, DEFAULT_SHUTDOWN_GRACE};
+pub use sources::{run_sources, RecentSet, SourceDedup, UpdateDeduper};
 pub use telemetry::{init as init_telemetry, TelemetryConfig};
 #[cfg(feature = "otel")]
 pub use telemetry::{init_with_otel, TelemetryGuard};
-pub use view::{Delivery, Filters, Projection, ViewIndex, ViewSpec};
+pub use txn_group::{InstructionMutations, TransactionGroupConfig, TransactionGrouper};
+pub use view::{Delivery, Filters, Projection, ViewDeprecation, ViewIndex, ViewSpec};
+pub use webhook::{RateLimit, WebhookConfig, WebhookRule, WebhookStats, Webhooks};
 pub use websocket::{
     AllowAllAuthPlugin, AuthContext, AuthDecision, AuthDeny, AuthErrorDetails, ChannelUsageEmitter,
-    ClientInfo, ClientManager, ConnectionAuthRequest, ErrorResponse, Frame, HttpUsageEmitter, Mode,
-    RateLimitConfig, RateLimitResult, RateLimiterConfig, RefreshAuthRequest, RefreshAuthResponse,
-    RetryPolicy, SignedSessionAuthPlugin, SocketIssueMessage, StaticTokenAuthPlugin, Subscription,
-    WebSocketAuthPlugin, WebSocketRateLimiter, WebSocketServer, WebSocketUsageBatch,
-    WebSocketUsageEmitter, WebSocketUsageEnvelope, WebSocketUsageEvent,
+    ClientInfo, ClientManager, CloseReason, ConnectionAuthRequest, DeprecationNotice, EntityFilter, ErrorResponse, FieldAuthorizer, FieldMask, FilterOp, Frame,
+    HistoryFrame, HistoryItem, HttpUsageEmitter, InboundLimits, Mode, OversizedFrameCounts, RateLimitConfig,
+    RateLimitResult, RateLimiterConfig, RefreshAuthRequest, RefreshAuthResponse, RetryPolicy, SequenceStart,
+    SessionConfig, SessionStats, SessionStore, SignedSessionAuthPlugin, SnapshotQueueConfig, SnapshotQueueStats, SocketIssueMessage, StaticFieldAuthorizer, StaticTokenAuthPlugin, Subscription,
+    SubscriptionDiagnostics, SubscriptionStatus, UpdateDelivery, WebSocketAuthPlugin, WebSocketRateLimiter, WebSocketServer,
+    WebSocketUsageBatch, WebSocketUsageEmitter, WebSocketUsageEnvelope, WebSocketUsageEvent,
 };
 
 use anyhow::Result;
@@ -91,13 +201,39 @@ pub type ParserSetupFn = Arc<
         + Sync,
 >;
 
+/// Stats of the spec's VM for `/stats`, `None` until the parser runtime has
+/// started
+pub type VmStatsFn = Arc<dyn Fn() -> Option<serde_json::Value> + Send + Sync>;
+
+/// Hands the server's [`ServerConfig::clock`] to the spec's VM before its
+/// parser starts
+pub type VmClockFn = Arc<dyn Fn(SharedClock) + Send + Sync>;
+
+/// A program added with [`Spec::add_program`] and the parser feeding its
+/// events to the spec's VM
+#[derive(Clone)]
+pub struct ProgramParser {
+    pub program_id: String,
+    pub setup: ParserSetupFn,
+}
+
 /// Specification for a HyperStack server
 /// Contains bytecode, parsers, and program information
 pub struct Spec {
     pub bytecode: hyperstack_interpreter::compiler::MultiEntityBytecode,
     pub program_ids: Vec<String>,
+    /// Parser of the first program
     pub parser_setup: Option<ParserSetupFn>,
+    /// Parsers of the programs added with [`Spec::add_program`]
+    pub programs: Vec<ProgramParser>,
+    pub account_backfill: Option<AccountBackfillFn>,
+    pub vm_snapshot: Option<VmSnapshotFn>,
+    pub vm_stats: Option<VmStatsFn>,
+    pub vm_state: Option<VmStateHooks>,
+    pub vm_clock: Option<VmClockFn>,
     pub views: Vec<ViewDef>,
+    /// Bytecode the parser reads, swapped by [`SpecReloader::reload_spec`]
+    pub live_bytecode: Option<LiveBytecode>,
 }
 
 impl Spec {
@@ -109,7 +245,14 @@ impl Spec {
             bytecode,
             program_ids: vec![program_id.into()],
             parser_setup: None,
+            programs: Vec::new(),
+            account_backfill: None,
+            vm_snapshot: None,
+            vm_stats: None,
+            vm_state: None,
+            vm_clock: None,
             views: Vec::new(),
+            live_bytecode: None,
         }
     }
 
@@ -118,10 +261,116 @@ impl Spec {
         self
     }
 
+    /// Also process `program_id`, whose parser runs alongside the others
+    /// and feeds the same VM, so one entity can correlate several programs.
+    /// Health monitoring tracks each program's stream separately.
+    pub fn add_program(
+        mut self,
+        program_id: impl Into<String>,
+        parser_setup: ParserSetupFn,
+    ) -> Self {
+        let program_id = program_id.into();
+        self.program_ids.push(program_id.clone());
+        self.programs.push(ProgramParser {
+            program_id,
+            setup: parser_setup,
+        });
+        self
+    }
+
+    /// The parser of each program, the first program's first
+    pub fn parsers(&self) -> Vec<ProgramParser> {
+        let first = self.parser_setup.clone().map(|setup| ProgramParser {
+            program_id: self
+                .program_ids
+                .first()
+                .cloned()
+                .unwrap_or_else(|| "unknown".to_string()),
+            setup,
+        });
+        first
+            .into_iter()
+            .chain(self.programs.iter().cloned())
+            .collect()
+    }
+
+    /// Process accounts fetched by [`RpcBackfill`] through this spec's VM
+    pub fn with_account_backfill(mut self, backfill_fn: AccountBackfillFn) -> Self {
+        self.account_backfill = Some(backfill_fn);
+        self
+    }
+
+    /// Include this snapshot of the spec's VM in debug bundles
+    pub fn with_vm_snapshot(mut self, snapshot_fn: VmSnapshotFn) -> Self {
+        self.vm_snapshot = Some(snapshot_fn);
+        self
+    }
+
+    /// Report these stats of the spec's VM under `vm` in `/stats`
+    pub fn with_vm_stats(mut self, stats_fn: VmStatsFn) -> Self {
+        self.vm_stats = Some(stats_fn);
+        self
+    }
+
+    /// Save and restore this spec's VM state along with the entity cache
+    /// when [`ServerBuilder::persistence`] is set
+    pub fn with_vm_state(mut self, export: VmStateExportFn, import: VmStateImportFn) -> Self {
+        self.vm_state = Some(VmStateHooks { export, import });
+        self
+    }
+
+    /// Let the spec's VM read the time from the server's clock
+    pub fn with_vm_clock(mut self, clock_fn: VmClockFn) -> Self {
+        self.vm_clock = Some(clock_fn);
+        self
+    }
+
     pub fn with_views(mut self, views: Vec<ViewDef>) -> Self {
         self.views = views;
         self
     }
+
+    /// Let reloads swap the bytecode read by this spec's parser
+    pub fn with_live_bytecode(mut self, bytecode: LiveBytecode) -> Self {
+        self.live_bytecode = Some(bytecode);
+        self
+    }
+}
+
+/// Views served for `spec`: the list, state and append views of each entity,
+/// then the spec's derived views
+pub(crate) fn spec_views(spec: &Spec) -> Vec<ViewSpec> {
+    let mut views = Vec::new();
+    for entity_name in spec.bytecode.entities.keys() {
+        for (suffix, mode) in [
+            ("list", Mode::List),
+            ("state", Mode::State),
+            ("append", Mode::Append),
+        ] {
+            views.push(ViewSpec {
+                id: format!("{}/{}", entity_name, suffix),
+                export: entity_name.clone(),
+                mode,
+                projection: Projection::all(),
+                filters: Filters::all(),
+                delivery: Delivery::default(),
+                pipeline: None,
+                source_view: None,
+            });
+        }
+    }
+    views.extend(spec.views.iter().map(derived_view_spec));
+    views
+}
+
+fn derived_view_spec(view_def: &ViewDef) -> ViewSpec {
+    let export = match &view_def.source {
+        hyperstack_interpreter::ast::ViewSource::Entity { name } => name.clone(),
+        hyperstack_interpreter::ast::ViewSource::View { id } => {
+            id.split('/').next().unwrap_or(id).to_string()
+        }
+    };
+    ViewSpec::from_view_def(view_def, &export)
 }
 
 /// Main server interface with fluent builder API
@@ -137,13 +386,19 @@ impl Server {
 /// Builder for configuring and creating a HyperStack server
 pub struct ServerBuilder {
     spec: Option<Spec>,
+    shadow_spec: Option<Spec>,
+    shadow_config: Option<ShadowConfig>,
     views: Option<ViewIndex>,
+    view_deprecations: Vec<ViewDeprecation>,
     materialized_views: Option<MaterializedViewRegistry>,
     config: ServerConfig,
     websocket_auth_plugin: Option<Arc<dyn WebSocketAuthPlugin>>,
+    websocket_field_authorizer: Option<Arc<dyn FieldAuthorizer>>,
     websocket_usage_emitter: Option<Arc<dyn WebSocketUsageEmitter>>,
     websocket_max_clients: Option<usize>,
     websocket_rate_limit_config: Option<crate::websocket::client_manager::RateLimitConfig>,
+    drain_on_shutdown: Option<std::time::Duration>,
+    shutdown_grace: Option<std::time::Duration>,
     #[cfg(feature = "otel")]
     metrics: Option<Arc<Metrics>>,
 }
@@ -152,13 +407,19 @@ impl ServerBuilder {
     fn new() -> Self {
         Self {
             spec: None,
+            shadow_spec: None,
+            shadow_config: None,
             views: None,
+            view_deprecations: Vec::new(),
             materialized_views: None,
             config: ServerConfig::new(),
             websocket_auth_plugin: None,
+            websocket_field_authorizer: None,
             websocket_usage_emitter: None,
             websocket_max_clients: None,
             websocket_rate_limit_config: None,
+            drain_on_shutdown: None,
+            shutdown_grace: None,
             #[cfg(feature = "otel")]
             metrics: None,
         }
@@ -170,12 +431,34 @@ impl ServerBuilder {
         self
     }
 
+    /// Run a candidate spec in shadow mode next to the production spec.
+    ///
+    /// Shadow output is never served to clients; it is diffed against
+    /// production and reported at `/admin/shadow`.
+    pub fn shadow_spec(mut self, spec: Spec) -> Self {
+        self.shadow_spec = Some(spec);
+        self
+    }
+
+    /// Configure resource limits and reporting for the shadow spec
+    pub fn shadow_config(mut self, config: ShadowConfig) -> Self {
+        self.shadow_config = Some(config);
+        self
+    }
+
     /// Set custom view index
     pub fn views(mut self, views: ViewIndex) -> Self {
         self.views = Some(views);
         self
     }
 
+    /// Serve subscriptions to a deprecated view id from its replacement
+    /// until its sunset, see [`ViewDeprecation`]
+    pub fn deprecate_view(mut self, deprecation: ViewDeprecation) -> Self {
+        self.view_deprecations.push(deprecation);
+        self
+    }
+
     /// Enable metrics collection (requires 'otel' feature)
     #[cfg(feature = "otel")]
     pub fn metrics(mut self, metrics: Metrics) -> Self {
@@ -201,6 +484,14 @@ impl ServerBuilder {
         self
     }
 
+    /// Limit the fields of each view that clients receive, by auth claims.
+    ///
+    /// See the [`field_mask`](crate::websocket::field_mask) module.
+    pub fn websocket_field_authorizer(mut self, authorizer: Arc<dyn FieldAuthorizer>) -> Self {
+        self.websocket_field_authorizer = Some(authorizer);
+        self
+    }
+
     /// Set an async usage emitter for billing-grade websocket usage events.
     pub fn websocket_usage_emitter(mut self, emitter: Arc<dyn WebSocketUsageEmitter>) -> Self {
         self.websocket_usage_emitter = Some(emitter);
@@ -226,6 +517,48 @@ impl ServerBuilder {
         self
     }
 
+    /// Drain WebSocket connections for `grace` on SIGTERM before stopping.
+    ///
+    /// See [`Runtime::with_drain_on_shutdown`].
+    pub fn drain_on_shutdown(mut self, grace: std::time::Duration) -> Self {
+        self.drain_on_shutdown = Some(grace);
+        self
+    }
+
+    /// Time a shutdown through a [`ServerHandle`] may take before the tasks
+    /// still running are aborted.
+    ///
+    /// See [`Runtime::with_shutdown_grace`].
+    pub fn shutdown_grace(mut self, grace: std::time::Duration) -> Self {
+        self.shutdown_grace = Some(grace);
+        self
+    }
+
+    /// Enable the gRPC streaming endpoint with default configuration (port 8878)
+    #[cfg(feature = "grpc")]
+    pub fn grpc(mut self) -> Self {
+        self.config.grpc = Some(GrpcConfig::default());
+        self
+    }
+
+    /// Configure the gRPC streaming endpoint
+    #[cfg(feature = "grpc")]
+    pub fn grpc_config(mut self, config: GrpcConfig) -> Self {
+        self.config.grpc = Some(config);
+        self
+    }
+
+    /// Set the bind address for the gRPC streaming endpoint
+    #[cfg(feature = "grpc")]
+    pub fn grpc_bind(mut self, addr: impl Into<SocketAddr>) -> Self {
+        if let Some(grpc_config) = &mut self.config.grpc {
+            grpc_config.bind_address = addr.into();
+        } else {
+            self.config.grpc = Some(GrpcConfig::new(addr.into()));
+        }
+        self
+    }
+
     /// Set the bind address for WebSocket server
     pub fn bind(mut self, addr: impl Into<SocketAddr>) -> Self {
         if let Some(ws_config) = &mut self.config.websocket {
@@ -266,6 +599,130 @@ impl ServerBuilder {
         self
     }
 
+    /// Coalesce projector output over an adaptive window.
+    ///
+    /// See the [`coalesce`] module for how the window reacts to load.
+    pub fn coalescing(mut self, config: CoalesceConfig) -> Self {
+        self.config.coalesce = Some(config);
+        self
+    }
+
+    /// Fetch state entities the server has never seen from RPC.
+    ///
+    /// Needs a spec with an account backfill function; see the [`backfill`]
+    /// module.
+    pub fn rpc_backfill(mut self, config: RpcBackfillConfig) -> Self {
+        self.config.backfill = Some(config);
+        self
+    }
+
+    /// Cap the retention of every `Append` view's log.
+    ///
+    /// Views keep what their [`Delivery`] asks for, up to these limits; see
+    /// the [`append_log`] module.
+    pub fn append_log(mut self, config: AppendLogConfig) -> Self {
+        self.config.append_log = Some(config);
+        self
+    }
+
+    /// Bound the cost of keeping each derived view up to date.
+    ///
+    /// See the [`materialized_view`] module for what happens to a view that
+    /// exceeds it.
+    pub fn view_budget(mut self, budget: ViewBudget) -> Self {
+        self.config.view_budget = Some(budget);
+        self
+    }
+
+    /// Maintain the views of `registry` along with the spec's derived views.
+    ///
+    /// Subscribing to one also needs a derived [`ViewSpec`] with the same id
+    /// in [`views`](Self::views).
+    pub fn materialized_views(mut self, registry: MaterializedViewRegistry) -> Self {
+        self.materialized_views = Some(registry);
+        self
+    }
+
+    /// Shed updates of low-priority entities under memory or queue pressure.
+    ///
+    /// Entities set their priority with `#[entity(priority = ...)]`; see the
+    /// [`load_shed`] module for the thresholds and what shedding does.
+    pub fn load_shedding(mut self, config: LoadShedConfig) -> Self {
+        self.config.load_shedding = Some(config);
+        self
+    }
+
+    /// Limit the size of the bundles served on `/admin/debug-bundle`.
+    ///
+    /// See the [`debug_bundle`] module for what a bundle contains.
+    pub fn debug_bundle(mut self, config: DebugBundleConfig) -> Self {
+        self.config.debug_bundle = Some(config);
+        self
+    }
+
+    /// Keep the events the VM fails to process instead of dropping them.
+    ///
+    /// See the [`dead_letter`] module for how to read them back.
+    pub fn dead_letter(mut self, config: DeadLetterConfig) -> Self {
+        self.config.dead_letter = Some(config);
+        self
+    }
+
+    /// POST to webhooks when updated entities match a rule's predicate.
+    ///
+    /// See the [`webhook`] module for dedupe, rate limits and retries.
+    pub fn webhooks(mut self, config: WebhookConfig) -> Self {
+        self.config.webhooks = Some(config);
+        self
+    }
+
+    /// Encode 64-bit integers in the frames sent to clients in `mode`,
+    /// `Auto` by default.
+    ///
+    /// See the [`big_numbers`] module for what clients can negotiate.
+    pub fn big_numbers(mut self, mode: BigNumberMode) -> Self {
+        self.config.big_numbers = mode;
+        self
+    }
+
+    /// Offer clients zstd compression with a dictionary trained on the
+    /// stack's frames, loaded from a file or trained on the first frames
+    /// published.
+    ///
+    /// See the [`dictionary`] module for how clients negotiate it.
+    pub fn compression_dictionary(mut self, source: DictionarySource) -> Self {
+        self.config.compression_dictionary = Some(source);
+        self
+    }
+
+    /// Start with feature flags set as in `config`, and let the admin tokens it
+    /// accepts change them on `/admin/flags`.
+    ///
+    /// See the [`flags`] module for the flags and what they switch.
+    pub fn flags(mut self, config: FlagsConfig) -> Self {
+        self.config.flags = config;
+        self
+    }
+
+    /// Snapshot the entity cache, and the VM state when the spec provides
+    /// it, every `config.interval` and on shutdown, and restore the latest
+    /// snapshot on startup.
+    ///
+    /// See the [`persistence`] module for where snapshots are kept.
+    pub fn persistence(mut self, config: PersistenceConfig) -> Self {
+        self.config.persistence = Some(config);
+        self
+    }
+
+    /// Read the time from `clock` instead of the system clock.
+    ///
+    /// Tests can pass a [`testkit::ManualClock`] to drive retention notices
+    /// and stall detection without real sleeps.
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.config.clock = Some(clock);
+        self
+    }
+
     /// Enable HTTP health server with default configuration (port 8081)
     pub fn http_health(mut self) -> Self {
         self.config.http_health = Some(HttpHealthConfig::default());
@@ -289,8 +746,12 @@ impl ServerBuilder {
     }
 
     pub async fn start(self) -> Result<()> {
-        let (view_index, materialized_registry) =
-            Self::build_view_index_and_registry(self.views, self.materialized_views, &self.spec);
+        let (view_index, materialized_registry) = Self::build_view_index_and_registry(
+            self.views,
+            self.view_deprecations,
+            self.materialized_views,
+            &self.spec,
+        );
 
         #[cfg(feature = "otel")]
         let mut runtime = Runtime::new(self.config, view_index, self.metrics);
@@ -301,6 +762,10 @@ impl ServerBuilder {
             runtime = runtime.with_websocket_auth_plugin(plugin);
         }
 
+        if let Some(authorizer) = self.websocket_field_authorizer {
+            runtime = runtime.with_websocket_field_authorizer(authorizer);
+        }
+
         if let Some(emitter) = self.websocket_usage_emitter {
             runtime = runtime.with_websocket_usage_emitter(emitter);
         }
@@ -313,6 +778,14 @@ impl ServerBuilder {
             runtime = runtime.with_websocket_rate_limit_config(rate_limit_config);
         }
 
+        if let Some(grace) = self.drain_on_shutdown {
+            runtime = runtime.with_drain_on_shutdown(grace);
+        }
+
+        if let Some(grace) = self.shutdown_grace {
+            runtime = runtime.with_shutdown_grace(grace);
+        }
+
         if let Some(registry) = materialized_registry {
             runtime = runtime.with_materialized_views(registry);
         }
@@ -321,88 +794,83 @@ impl ServerBuilder {
             runtime = runtime.with_spec(spec);
         }
 
+        if let Some(spec) = self.shadow_spec {
+            runtime = runtime.with_shadow_spec(spec);
+        }
+
+        if let Some(config) = self.shadow_config {
+            runtime = runtime.with_shadow_config(config);
+        }
+
         runtime.run().await
     }
 
+    /// Start the server in the background and return a handle that stops it.
+    ///
+    /// Unlike [`ServerBuilder::start`], the server doesn't stop on SIGINT or
+    /// SIGTERM. See the [`shutdown`] module.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub async fn start_with_shutdown(self) -> Result<ServerHandle> {
+        let runtime = self.build()?;
+        let shutdown = runtime.shutdown_token();
+        let task = tokio::spawn(runtime.run_until_shutdown());
+        Ok(ServerHandle::new(shutdown, task))
+    }
+
     fn build_view_index_and_registry(
         views: Option<ViewIndex>,
+        deprecations: Vec<ViewDeprecation>,
         materialized_views: Option<MaterializedViewRegistry>,
         spec: &Option<Spec>,
     ) -> (ViewIndex, Option<MaterializedViewRegistry>) {
         let mut index = views.unwrap_or_default();
+        for deprecation in deprecations {
+            index.deprecate(deprecation);
+        }
         let mut registry = materialized_views;
 
         if let Some(ref spec) = spec {
-            for entity_name in spec.bytecode.entities.keys() {
-                index.add_spec(ViewSpec {
-                    id: format!("{}/list", entity_name),
-                    export: entity_name.clone(),
-                    mode: Mode::List,
-                    projection: Projection::all(),
-                    filters: Filters::all(),
-                    delivery: Delivery::default(),
-                    pipeline: None,
-                    source_view: None,
-                });
-
-                index.add_spec(ViewSpec {
-                    id: format!("{}/state", entity_name),
-                    export: entity_name.clone(),
-                    mode: Mode::State,
-                    projection: Projection::all(),
-                    filters: Filters::all(),
-                    delivery: Delivery::default(),
-                    pipeline: None,
-                    source_view: None,
-                });
-
-                index.add_spec(ViewSpec {
-                    id: format!("{}/append", entity_name),
-                    export: entity_name.clone(),
-                    mode: Mode::Append,
-                    projection: Projection::all(),
-                    filters: Filters::all(),
-                    delivery: Delivery::default(),
-                    pipeline: None,
-                    source_view: None,
-                });
+            for view_spec in spec_views(spec) {
+                if view_spec.is_derived() {
+                    tracing::debug!(
+                        view_id = %view_spec.id,
+                        source = %view_spec.source_view.as_deref().unwrap_or_default(),
+                        "Registering derived view"
+                    );
+                }
+                index.add_spec(view_spec);
             }
 
             if !spec.views.is_empty() {
                 let reg = registry.get_or_insert_with(MaterializedViewRegistry::new);
 
                 for view_def in &spec.views {
-                    let export = match &view_def.source {
-                        hyperstack_interpreter::ast::ViewSource::Entity { name } => name.clone(),
-                        hyperstack_interpreter::ast::ViewSource::View { id } => {
-                            id.split('/').next().unwrap_or(id).to_string()
-                        }
-                    };
-
-                    let view_spec = ViewSpec::from_view_def(view_def, &export);
-                    let pipeline = view_spec.pipeline.clone().unwrap_or_default();
-                    let source_id = view_spec.source_view.clone().unwrap_or_default();
-                    tracing::debug!(
-                        view_id = %view_def.id,
-                        source = %source_id,
-                        "Registering derived view"
+                    let view_spec = derived_view_spec(view_def);
+                    let materialized = MaterializedView::new(
+                        view_def.id.clone(),
+                        view_spec.source_view.unwrap_or_default(),
+                        view_spec.pipeline.unwrap_or_default(),
                     );
-
-                    index.add_spec(view_spec);
-
-                    let materialized =
-                        MaterializedView::new(view_def.id.clone(), source_id, pipeline);
                     reg.register(materialized);
                 }
             }
         }
 
+        let registry =
+            registry.map(|registry| registry.with_quarantine(index.quarantined_views().clone()));
         (index, registry)
     }
 
     pub fn build(self) -> Result<Runtime> {
-        let (view_index, materialized_registry) =
-            Self::build_view_index_and_registry(self.views, self.materialized_views, &self.spec);
+        let (view_index, materialized_registry) = Self::build_view_index_and_registry(
+            self.views,
+            self.view_deprecations,
+            self.materialized_views,
+            &self.spec,
+        );
 
         #[cfg(feature = "otel")]
         let mut runtime = Runtime::new(self.config, view_index, self.metrics);
@@ -413,10 +881,30 @@ impl ServerBuilder {
             runtime = runtime.with_websocket_auth_plugin(plugin);
         }
 
+        if let Some(authorizer) = self.websocket_field_authorizer {
+            runtime = runtime.with_websocket_field_authorizer(authorizer);
+        }
+
+        if let Some(emitter) = self.websocket_usage_emitter {
+            runtime = runtime.with_websocket_usage_emitter(emitter);
+        }
+
         if let Some(max_clients) = self.websocket_max_clients {
             runtime = runtime.with_websocket_max_clients(max_clients);
         }
 
+        if let Some(rate_limit_config) = self.websocket_rate_limit_config {
+            runtime = runtime.with_websocket_rate_limit_config(rate_limit_config);
+        }
+
+        if let Some(grace) = self.drain_on_shutdown {
+            runtime = runtime.with_drain_on_shutdown(grace);
+        }
+
+        if let Some(grace) = self.shutdown_grace {
+            runtime = runtime.with_shutdown_grace(grace);
+        }
+
         if let Some(registry) = materialized_registry {
             runtime = runtime.with_materialized_views(registry);
         }
@@ -424,6 +912,14 @@ impl ServerBuilder {
         if let Some(spec) = self.spec {
             runtime = runtime.with_spec(spec);
         }
+
+        if let Some(spec) = self.shadow_spec {
+            runtime = runtime.with_shadow_spec(spec);
+        }
+
+        if let Some(config) = self.shadow_config {
+            runtime = runtime.with_shadow_config(config);
+        }
         Ok(runtime)
     }
 }
@@ -448,4 +944,32 @@ mod tests {
             Some("test_program")
         );
     }
+
+    #[tokio::test]
+    async fn test_spec_vm_receives_the_server_clock() {
+        let vm_clock: Arc<std::sync::Mutex<Option<SharedClock>>> = Arc::default();
+        let setup: ParserSetupFn = Arc::new(|_mutations_tx, _health, _reconnection| {
+            Box::pin(std::future::pending())
+        });
+        let spec = Spec::new(
+            hyperstack_interpreter::compiler::MultiEntityBytecode::new().build(),
+            "test_program",
+        )
+        .with_parser_setup(setup)
+        .with_vm_clock({
+            let vm_clock = vm_clock.clone();
+            Arc::new(move |clock| *vm_clock.lock().unwrap() = Some(clock))
+        });
+
+        let clock = testkit::ManualClock::at_unix_secs(1_000);
+        let _parts = Server::builder()
+            .spec(spec)
+            .clock(Arc::new(clock))
+            .build()
+            .unwrap()
+            .into_router_parts();
+
+        let vm_clock = vm_clock.lock().unwrap().clone().expect("VM clock set");
+        assert_eq!(vm_clock.unix_secs(), 1_000);
+    }
 }
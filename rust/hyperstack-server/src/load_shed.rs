@@ -0,0 +1,631 @@
+//! Load shedding under memory and mutation queue pressure.
+//!
+//! Under extreme bursts the projector falls behind the parser, and queued
+//! mutation batches pile up until the process runs out of memory. With load
+//! shedding enabled the runtime samples the process's resident memory and the
+//! depth of the mutation channel every [`LoadShedConfig::sample_interval`].
+//! Pressure is the larger of the two, each relative to its limit, and the
+//! projector gives up work by entity priority
+//! (`#[entity(priority = low|normal|high)]`):
+//!
+//! - at [`LoadShedConfig::shed_low_at`], `low` entities are shed;
+//! - at [`LoadShedConfig::shed_normal_at`], `normal` entities are shed too;
+//! - `high` entities are never shed.
+//!
+//! Shedding an entity drops its mutations when it feeds an `Append` view,
+//! whose items can't be merged, and debounces the others: patches to the same
+//! entity are merged and published together once per
+//! [`LoadShedConfig::debounce`]. Patches with append paths are still published
+//! as they arrive.
+//!
+//! Shedding starts as soon as pressure crosses a threshold. Easing off a level
+//! takes [`LoadShedConfig::recovery_samples`] consecutive samples below the
+//! threshold minus [`LoadShedConfig::hysteresis`], so a burst hovering around
+//! a threshold doesn't make shedding flap. While shedding, the health monitor
+//! carries a note shown at `/status`, and `/stats` counts the dropped and
+//! debounced mutations of each entity.
+
+use crate::mutation_batch::{merge_patch, SlotContext};
+use hyperstack_interpreter::ast::EntityPriority;
+use hyperstack_interpreter::Mutation;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Configuration for load shedding
+#[derive(Debug, Clone)]
+pub struct LoadShedConfig {
+    /// Resident memory in bytes that counts as full pressure, memory is not
+    /// measured when unset
+    pub memory_limit: Option<u64>,
+    /// Queued mutation batches that count as full pressure
+    pub queue_limit: usize,
+    /// Pressure at or above which low-priority entities are shed
+    pub shed_low_at: f64,
+    /// Pressure at or above which normal-priority entities are shed as well
+    pub shed_normal_at: f64,
+    /// How far below a level's threshold pressure must fall to ease off
+    pub hysteresis: f64,
+    /// Consecutive samples below the recovery point before easing off a level
+    pub recovery_samples: u32,
+    /// How often memory and queue depth are measured
+    pub sample_interval: Duration,
+    /// How long patches to a shed entity are merged before publishing
+    pub debounce: Duration,
+}
+
+impl Default for LoadShedConfig {
+    fn default() -> Self {
+        Self {
+            memory_limit: None,
+            queue_limit: crate::runtime::MUTATION_CHANNEL_CAPACITY,
+            shed_low_at: 0.75,
+            shed_normal_at: 0.9,
+            hysteresis: 0.15,
+            recovery_samples: 5,
+            sample_interval: Duration::from_secs(1),
+            debounce: Duration::from_secs(1),
+        }
+    }
+}
+
+impl LoadShedConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_memory_limit(mut self, bytes: u64) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    pub fn with_queue_limit(mut self, batches: usize) -> Self {
+        self.queue_limit = batches.max(1);
+        self
+    }
+
+    pub fn with_thresholds(mut self, shed_low_at: f64, shed_normal_at: f64) -> Self {
+        self.shed_low_at = shed_low_at;
+        self.shed_normal_at = shed_normal_at.max(shed_low_at);
+        self
+    }
+
+    pub fn with_hysteresis(mut self, margin: f64, recovery_samples: u32) -> Self {
+        self.hysteresis = margin.max(0.0);
+        self.recovery_samples = recovery_samples.max(1);
+        self
+    }
+
+    pub fn with_sample_interval(mut self, interval: Duration) -> Self {
+        self.sample_interval = interval;
+        self
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+}
+
+/// Which entities are being shed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShedLevel {
+    Off,
+    /// Low-priority entities are shed
+    Low,
+    /// Low- and normal-priority entities are shed
+    Normal,
+}
+
+impl ShedLevel {
+    /// Whether entities of `priority` are shed at this level
+    pub fn sheds(self, priority: EntityPriority) -> bool {
+        match priority {
+            EntityPriority::Low => self >= ShedLevel::Low,
+            EntityPriority::Normal => self >= ShedLevel::Normal,
+            EntityPriority::High => false,
+        }
+    }
+
+    fn below(self) -> Self {
+        match self {
+            ShedLevel::Normal => ShedLevel::Low,
+            ShedLevel::Low | ShedLevel::Off => ShedLevel::Off,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            2 => ShedLevel::Normal,
+            1 => ShedLevel::Low,
+            _ => ShedLevel::Off,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            ShedLevel::Off => "not shedding",
+            ShedLevel::Low => "shedding low-priority entities",
+            ShedLevel::Normal => "shedding low- and normal-priority entities",
+        }
+    }
+}
+
+/// One measurement of the pressure on the process
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PressureSample {
+    /// Resident memory in bytes, when measured
+    pub memory_bytes: Option<u64>,
+    /// Mutation batches waiting for the projector
+    pub queue_depth: usize,
+}
+
+impl PressureSample {
+    /// Pressure relative to the configured limits, 1.0 at either limit
+    pub fn pressure(&self, config: &LoadShedConfig) -> f64 {
+        let queue = self.queue_depth as f64 / config.queue_limit.max(1) as f64;
+        let memory = match (self.memory_bytes, config.memory_limit) {
+            (Some(bytes), Some(limit)) if limit > 0 => bytes as f64 / limit as f64,
+            _ => 0.0,
+        };
+        queue.max(memory)
+    }
+}
+
+/// Resident set size of this process, read from `/proc/self/status` on Linux
+pub fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Shed level with recovery hysteresis, fed one pressure reading per sample
+#[derive(Debug, Clone)]
+pub struct ShedController {
+    config: LoadShedConfig,
+    level: ShedLevel,
+    calm_samples: u32,
+}
+
+impl ShedController {
+    pub fn new(config: LoadShedConfig) -> Self {
+        Self {
+            config,
+            level: ShedLevel::Off,
+            calm_samples: 0,
+        }
+    }
+
+    pub fn level(&self) -> ShedLevel {
+        self.level
+    }
+
+    /// Feed a pressure reading and return the level to shed at
+    pub fn observe(&mut self, pressure: f64) -> ShedLevel {
+        let target = if pressure >= self.config.shed_normal_at {
+            ShedLevel::Normal
+        } else if pressure >= self.config.shed_low_at {
+            ShedLevel::Low
+        } else {
+            ShedLevel::Off
+        };
+
+        if target >= self.level {
+            self.level = target;
+            self.calm_samples = 0;
+            return self.level;
+        }
+
+        let recovery_point = self.threshold(self.level) - self.config.hysteresis;
+        if pressure < recovery_point {
+            self.calm_samples += 1;
+        } else {
+            self.calm_samples = 0;
+        }
+        // Ease off one level at a time
+        if self.calm_samples >= self.config.recovery_samples {
+            self.level = self.level.below();
+            self.calm_samples = 0;
+        }
+        self.level
+    }
+
+    fn threshold(&self, level: ShedLevel) -> f64 {
+        match level {
+            ShedLevel::Normal => self.config.shed_normal_at,
+            ShedLevel::Low | ShedLevel::Off => self.config.shed_low_at,
+        }
+    }
+}
+
+/// A change of the shed level after a sample
+#[derive(Debug, Clone, Copy)]
+pub struct LevelChange {
+    pub from: ShedLevel,
+    pub to: ShedLevel,
+    pub pressure: f64,
+}
+
+impl LevelChange {
+    /// Note for the health status while shedding, `None` once recovered
+    pub fn health_note(&self) -> Option<String> {
+        (self.to != ShedLevel::Off)
+            .then(|| format!("{} (pressure {:.2})", self.to.describe(), self.pressure))
+    }
+}
+
+/// Mutations of one entity given up while shedding
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ShedCounts {
+    /// Mutations to `Append` views that were dropped
+    pub dropped: u64,
+    /// Mutations merged into a later publish
+    pub debounced: u64,
+}
+
+/// Snapshot of the load shedder for `/stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadShedStats {
+    pub level: ShedLevel,
+    pub pressure: f64,
+    #[serde(flatten)]
+    pub sample: PressureSample,
+    /// Entities with a debounced patch waiting to be published
+    pub held: usize,
+    pub entities: BTreeMap<String, ShedCounts>,
+}
+
+type Held = (Mutation, Option<SlotContext>);
+
+#[derive(Default)]
+struct Debounced {
+    held: HashMap<(String, String), Held>,
+    next_release: Option<Instant>,
+    /// Level the held patches were last released at
+    released_at: Option<ShedLevel>,
+}
+
+struct Inner {
+    config: LoadShedConfig,
+    priorities: HashMap<String, EntityPriority>,
+    level: AtomicU8,
+    controller: Mutex<ShedController>,
+    last_sample: Mutex<(PressureSample, f64)>,
+    counts: Mutex<BTreeMap<String, ShedCounts>>,
+    debounced: Mutex<Debounced>,
+}
+
+/// Decides which mutations the projector sheds, shared between the sampler
+/// and the projector
+#[derive(Clone)]
+pub struct LoadShedder {
+    inner: Arc<Inner>,
+}
+
+impl LoadShedder {
+    /// Shed by `priorities`, keyed by entity name. Entities without one are
+    /// normal priority.
+    pub fn new(config: LoadShedConfig, priorities: HashMap<String, EntityPriority>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                controller: Mutex::new(ShedController::new(config.clone())),
+                config,
+                priorities,
+                level: AtomicU8::new(ShedLevel::Off as u8),
+                last_sample: Mutex::new((PressureSample::default(), 0.0)),
+                counts: Mutex::new(BTreeMap::new()),
+                debounced: Mutex::new(Debounced::default()),
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &LoadShedConfig {
+        &self.inner.config
+    }
+
+    pub fn level(&self) -> ShedLevel {
+        ShedLevel::from_u8(self.inner.level.load(Ordering::Relaxed))
+    }
+
+    pub fn priority(&self, entity: &str) -> EntityPriority {
+        self.inner
+            .priorities
+            .get(entity)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Measure memory, if limited, next to the given mutation queue depth
+    pub fn sample(&self, queue_depth: usize) -> Option<LevelChange> {
+        let memory_bytes = self
+            .inner
+            .config
+            .memory_limit
+            .and_then(|_| resident_memory_bytes());
+        self.observe(PressureSample {
+            memory_bytes,
+            queue_depth,
+        })
+    }
+
+    /// Feed a pressure sample and return the level change it caused, if any
+    pub fn observe(&self, sample: PressureSample) -> Option<LevelChange> {
+        let pressure = sample.pressure(&self.inner.config);
+        *lock(&self.inner.last_sample) = (sample, pressure);
+
+        let from = self.level();
+        let to = lock(&self.inner.controller).observe(pressure);
+        self.inner.level.store(to as u8, Ordering::Relaxed);
+        (from != to).then_some(LevelChange { from, to, pressure })
+    }
+
+    /// Pass `mutation` through the shedder, pushing what should be published
+    /// now onto `admitted`. Returns false when the mutation was dropped or
+    /// held back.
+    ///
+    /// `feeds_append` tells whether the entity has an `Append` view.
+    /// Tombstones are never shed, so a deleted entity can't linger.
+    pub fn admit(
+        &self,
+        mutation: Mutation,
+        slot_context: Option<SlotContext>,
+        feeds_append: bool,
+        admitted: &mut Vec<Held>,
+    ) -> bool {
+        let shed = !mutation.tombstone && self.level().sheds(self.priority(&mutation.export));
+        if shed && feeds_append {
+            self.count(&mutation.export, |counts| counts.dropped += 1);
+            return false;
+        }
+
+        let entity = (mutation.export.clone(), mutation.key.to_string());
+        let mut debounced = lock(&self.inner.debounced);
+        if !shed || !mutation.append.is_empty() {
+            // A held patch is older, so it goes out first
+            admitted.extend(debounced.held.remove(&entity));
+            admitted.push((mutation, slot_context));
+            return true;
+        }
+
+        self.count(&mutation.export, |counts| counts.debounced += 1);
+        match debounced.held.get_mut(&entity) {
+            Some((held, held_slot)) => {
+                merge_patch(&mut held.patch, &mutation.patch, &[]);
+                *held_slot = slot_context.or(*held_slot);
+            }
+            None => {
+                debounced
+                    .next_release
+                    .get_or_insert_with(|| Instant::now() + self.inner.config.debounce);
+                debounced.held.insert(entity, (mutation, slot_context));
+            }
+        }
+        false
+    }
+
+    /// Take the held patches that are due: all of them once the debounce
+    /// elapsed, otherwise those of entities no longer shed
+    pub fn release_due(&self, now: Instant) -> Vec<Held> {
+        let level = self.level();
+        let mut debounced = lock(&self.inner.debounced);
+        if debounced.held.is_empty() {
+            return Vec::new();
+        }
+
+        if debounced.next_release.is_some_and(|at| now >= at) {
+            debounced.next_release = None;
+            debounced.released_at = Some(level);
+            return debounced.held.drain().map(|(_, held)| held).collect();
+        }
+        if debounced.released_at == Some(level) {
+            return Vec::new();
+        }
+        debounced.released_at = Some(level);
+
+        let recovered: Vec<_> = debounced
+            .held
+            .keys()
+            .filter(|(entity, _)| !level.sheds(self.priority(entity)))
+            .cloned()
+            .collect();
+        recovered
+            .into_iter()
+            .filter_map(|entity| debounced.held.remove(&entity))
+            .collect()
+    }
+
+    /// Take every held patch, e.g. when the projector stops
+    pub fn release_all(&self) -> Vec<Held> {
+        let mut debounced = lock(&self.inner.debounced);
+        debounced.next_release = None;
+        debounced.held.drain().map(|(_, held)| held).collect()
+    }
+
+    /// Dropped and debounced mutations per entity
+    pub fn counts(&self) -> BTreeMap<String, ShedCounts> {
+        lock(&self.inner.counts).clone()
+    }
+
+    pub fn stats(&self) -> LoadShedStats {
+        let (sample, pressure) = *lock(&self.inner.last_sample);
+        LoadShedStats {
+            level: self.level(),
+            pressure,
+            sample,
+            held: lock(&self.inner.debounced).held.len(),
+            entities: self.counts(),
+        }
+    }
+
+    fn count(&self, entity: &str, update: impl FnOnce(&mut ShedCounts)) {
+        let mut counts = lock(&self.inner.counts);
+        match counts.get_mut(entity) {
+            Some(entry) => update(entry),
+            None => update(counts.entry(entity.to_string()).or_default()),
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> LoadShedConfig {
+        LoadShedConfig::new()
+            .with_thresholds(0.5, 0.8)
+            .with_hysteresis(0.1, 3)
+    }
+
+    fn mutation(export: &str, key: &str, patch: serde_json::Value) -> Mutation {
+        Mutation::new(export, json!(key), patch)
+    }
+
+    #[test]
+    fn levels_shed_low_priority_first_and_never_high() {
+        assert!(!ShedLevel::Off.sheds(EntityPriority::Low));
+        assert!(ShedLevel::Low.sheds(EntityPriority::Low));
+        assert!(!ShedLevel::Low.sheds(EntityPriority::Normal));
+        assert!(ShedLevel::Normal.sheds(EntityPriority::Normal));
+        assert!(!ShedLevel::Normal.sheds(EntityPriority::High));
+    }
+
+    #[test]
+    fn pressure_is_the_larger_of_memory_and_queue() {
+        let config = config().with_queue_limit(100).with_memory_limit(1000);
+        let sample = PressureSample {
+            memory_bytes: Some(900),
+            queue_depth: 40,
+        };
+        assert_eq!(sample.pressure(&config), 0.9);
+
+        let unmeasured = PressureSample {
+            memory_bytes: None,
+            queue_depth: 40,
+        };
+        assert_eq!(unmeasured.pressure(&config), 0.4);
+    }
+
+    #[test]
+    fn controller_escalates_immediately() {
+        let mut controller = ShedController::new(config());
+        assert_eq!(controller.observe(0.4), ShedLevel::Off);
+        assert_eq!(controller.observe(0.6), ShedLevel::Low);
+        assert_eq!(controller.observe(0.95), ShedLevel::Normal);
+    }
+
+    #[test]
+    fn controller_eases_off_one_level_after_sustained_calm() {
+        let mut controller = ShedController::new(config());
+        controller.observe(0.95);
+
+        // Below the normal threshold but inside the hysteresis margin
+        for _ in 0..10 {
+            assert_eq!(controller.observe(0.75), ShedLevel::Normal);
+        }
+
+        assert_eq!(controller.observe(0.1), ShedLevel::Normal);
+        assert_eq!(controller.observe(0.1), ShedLevel::Normal);
+        assert_eq!(controller.observe(0.1), ShedLevel::Low);
+        assert_eq!(controller.observe(0.1), ShedLevel::Low);
+        assert_eq!(controller.observe(0.1), ShedLevel::Low);
+        assert_eq!(controller.observe(0.1), ShedLevel::Off);
+    }
+
+    #[test]
+    fn a_spike_resets_recovery() {
+        let mut controller = ShedController::new(config());
+        controller.observe(0.6);
+        controller.observe(0.1);
+        controller.observe(0.1);
+        assert_eq!(controller.observe(0.55), ShedLevel::Low);
+        controller.observe(0.1);
+        controller.observe(0.1);
+        assert_eq!(controller.level(), ShedLevel::Low);
+        assert_eq!(controller.observe(0.1), ShedLevel::Off);
+    }
+
+    #[test]
+    fn shed_state_patches_merge_until_released() {
+        let shedder = LoadShedder::new(
+            config().with_queue_limit(10),
+            HashMap::from([("Pool".to_string(), EntityPriority::Low)]),
+        );
+        let change = shedder
+            .observe(PressureSample {
+                memory_bytes: None,
+                queue_depth: 6,
+            })
+            .unwrap();
+        assert_eq!((change.from, change.to), (ShedLevel::Off, ShedLevel::Low));
+        assert!(change.health_note().unwrap().contains("low-priority"));
+
+        let mut admitted = Vec::new();
+        assert!(!shedder.admit(
+            mutation("Pool", "a", json!({ "x": 1 })),
+            None,
+            false,
+            &mut admitted
+        ));
+        assert!(!shedder.admit(
+            mutation("Pool", "a", json!({ "y": 2 })),
+            None,
+            false,
+            &mut admitted
+        ));
+        // Normal priority is not shed yet
+        assert!(shedder.admit(
+            mutation("Trade", "a", json!({})),
+            None,
+            false,
+            &mut admitted
+        ));
+        assert_eq!(admitted.len(), 1);
+        assert_eq!(shedder.counts()["Pool"].debounced, 2);
+
+        let released = shedder.release_due(Instant::now() + Duration::from_secs(2));
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].0.patch, json!({ "x": 1, "y": 2 }));
+    }
+
+    #[test]
+    fn recovery_releases_held_patches_before_newer_ones() {
+        let shedder = LoadShedder::new(
+            config().with_queue_limit(10).with_hysteresis(0.1, 1),
+            HashMap::from([("Pool".to_string(), EntityPriority::Low)]),
+        );
+        shedder.observe(PressureSample {
+            memory_bytes: None,
+            queue_depth: 6,
+        });
+        let mut admitted = Vec::new();
+        shedder.admit(
+            mutation("Pool", "a", json!({ "x": 1 })),
+            None,
+            false,
+            &mut admitted,
+        );
+
+        shedder.observe(PressureSample::default());
+        assert_eq!(shedder.level(), ShedLevel::Off);
+        assert!(shedder.admit(
+            mutation("Pool", "a", json!({ "x": 2 })),
+            None,
+            false,
+            &mut admitted
+        ));
+        let patches: Vec<_> = admitted.iter().map(|(m, _)| m.patch.clone()).collect();
+        assert_eq!(patches, vec![json!({ "x": 1 }), json!({ "x": 2 })]);
+        assert!(shedder.release_all().is_empty());
+    }
+}
@@ -2,12 +2,45 @@
 //!
 //! This module handles the runtime evaluation of ViewDef pipelines,
 //! maintaining materialized results that update as source data changes.
+//!
+//! ## Evaluation cost
+//!
+//! Every evaluation of a view, whether a full pass over its source or the
+//! effect of a single update, is counted per view: evaluations, items
+//! scanned, items emitted, cumulative and slowest single evaluation time.
+//! [`MaterializedViewRegistry::stats`] reports them at `/stats` under
+//! `"views"`, and the projector records them as otel histograms labeled by
+//! view id.
+//!
+//! A [`ViewBudget`] bounds a single live evaluation. A view that exceeds it
+//! logs a warning and, with [`ViewBudget::fallback_to_refresh`], stops being
+//! maintained on every update; it is re-evaluated from the entity cache every
+//! [`ViewBudget::refresh_interval`] instead.
+//!
+//! ## Quarantine
+//!
+//! Evaluations run behind `catch_unwind`, so a pipeline that panics fails
+//! only its own view. After [`ViewBudget::max_failures`] consecutive
+//! failures the view is quarantined: it is no longer maintained or
+//! refreshed, subscriptions to it are refused with `view_unavailable` and
+//! its subscribers are told it is gone. Quarantined views and the panic that
+//! took them out are listed at `/stats` under `"quarantined_views"`, and
+//! `POST /admin/unquarantine/<view>` re-evaluates a view from the entity
+//! cache and puts it back in service if that succeeds.
 
 use crate::cache::EntityCache;
+use futures_util::FutureExt;
+use hyperstack_interpreter::clock::{system_clock, SharedClock};
+use serde::Serialize;
 use serde_json::Value;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use tracing::{error, warn};
 
 /// Result of evaluating whether an update affects a materialized view
 #[derive(Debug, Clone, PartialEq)]
@@ -42,6 +75,173 @@ pub enum CompareOp {
     Lte,
 }
 
+/// Limit on the cost of keeping a view up to date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewBudget {
+    /// Longest a single live evaluation may take
+    pub max_evaluation: Duration,
+    /// Stop live maintenance of a view that exceeds `max_evaluation`
+    pub fallback_to_refresh: bool,
+    /// How often views without live maintenance are re-evaluated
+    pub refresh_interval: Duration,
+    /// Consecutive failed evaluations after which a view is quarantined
+    pub max_failures: u32,
+}
+
+impl Default for ViewBudget {
+    fn default() -> Self {
+        Self {
+            max_evaluation: Duration::from_millis(5),
+            fallback_to_refresh: false,
+            refresh_interval: Duration::from_secs(30),
+            max_failures: 3,
+        }
+    }
+}
+
+impl ViewBudget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_evaluation(mut self, max: Duration) -> Self {
+        self.max_evaluation = max;
+        self
+    }
+
+    pub fn with_fallback_to_refresh(mut self, interval: Duration) -> Self {
+        self.fallback_to_refresh = true;
+        self.refresh_interval = interval;
+        self
+    }
+
+    pub fn with_max_failures(mut self, failures: u32) -> Self {
+        self.max_failures = failures.max(1);
+        self
+    }
+}
+
+/// Work done by one evaluation of a view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvaluationCost {
+    /// Source entities the pipeline looked at
+    pub scanned: u64,
+    /// Entities the evaluation put in (or kept in) the result
+    pub emitted: u64,
+    pub elapsed: Duration,
+}
+
+/// Outcome of maintaining a view for one source update
+#[derive(Debug, Clone, PartialEq)]
+pub struct Maintained {
+    pub effect: ViewEffect,
+    pub cost: EvaluationCost,
+    /// The evaluation took longer than the view's budget
+    pub over_budget: bool,
+}
+
+/// An evaluation of a view that panicked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewFailure {
+    /// The panic message
+    pub error: String,
+    /// This failure quarantined the view
+    pub quarantined: bool,
+}
+
+/// Why a view was taken out of service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Quarantine {
+    /// The panic message of the last failed evaluation
+    pub error: String,
+    /// Consecutive failed evaluations, counting the last one
+    pub failures: u32,
+    /// When the view was quarantined, in Unix milliseconds
+    pub since_ms: u64,
+}
+
+/// Views quarantined after their pipeline kept failing, by view id.
+///
+/// Shared by the [`MaterializedViewRegistry`] that quarantines views and the
+/// [`ViewIndex`](crate::ViewIndex) subscriptions are checked against.
+#[derive(Debug, Clone, Default)]
+pub struct QuarantinedViews(Arc<std::sync::RwLock<HashMap<String, Quarantine>>>);
+
+impl QuarantinedViews {
+    pub fn get(&self, view_id: &str) -> Option<Quarantine> {
+        self.0.read().ok()?.get(view_id).cloned()
+    }
+
+    pub fn contains(&self, view_id: &str) -> bool {
+        self.0.read().is_ok_and(|views| views.contains_key(view_id))
+    }
+
+    /// Every quarantined view, by id
+    pub fn all(&self) -> BTreeMap<String, Quarantine> {
+        self.0
+            .read()
+            .map(|views| {
+                views
+                    .iter()
+                    .map(|(id, q)| (id.clone(), q.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn insert(&self, view_id: &str, quarantine: Quarantine) {
+        if let Ok(mut views) = self.0.write() {
+            views.insert(view_id.to_string(), quarantine);
+        }
+    }
+
+    fn remove(&self, view_id: &str) -> bool {
+        self.0
+            .write()
+            .is_ok_and(|mut views| views.remove(view_id).is_some())
+    }
+}
+
+/// Cumulative evaluation counters of a view
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ViewEvaluationStats {
+    pub evaluations: u64,
+    pub items_scanned: u64,
+    pub items_emitted: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+    /// Live evaluations that exceeded the view's budget
+    pub over_budget: u64,
+    /// Evaluations that panicked
+    pub failures: u64,
+    /// Whether the view is maintained on every update
+    pub live: bool,
+}
+
+#[derive(Debug, Default)]
+struct EvaluationCounters {
+    evaluations: AtomicU64,
+    items_scanned: AtomicU64,
+    items_emitted: AtomicU64,
+    total_micros: AtomicU64,
+    max_micros: AtomicU64,
+    over_budget: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl EvaluationCounters {
+    fn record(&self, cost: &EvaluationCost) {
+        let micros = cost.elapsed.as_micros() as u64;
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        self.items_scanned
+            .fetch_add(cost.scanned, Ordering::Relaxed);
+        self.items_emitted
+            .fetch_add(cost.emitted, Ordering::Relaxed);
+        self.total_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+}
+
 /// A materialized view that tracks a subset of entities based on a pipeline
 #[derive(Debug)]
 pub struct MaterializedView {
@@ -53,6 +253,13 @@ pub struct MaterializedView {
     current_keys: Arc<RwLock<HashSet<String>>>,
     /// Pipeline configuration (simplified for now)
     pipeline: ViewPipeline,
+    /// Overrides the registry's budget for this view
+    budget: Option<ViewBudget>,
+    counters: EvaluationCounters,
+    /// Cleared once the view falls back to periodic refresh
+    live: AtomicBool,
+    /// Failed evaluations since the last one that succeeded
+    consecutive_failures: AtomicU32,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -63,6 +270,28 @@ pub struct ViewPipeline {
     pub sort: Option<SortConfig>,
     /// Limit (take N) - if Some(1), treated as single-result view for Replace effects
     pub limit: Option<usize>,
+    /// Predicate entities must also satisfy, for views defined in Rust
+    pub predicate: Option<ViewPredicate>,
+}
+
+/// Filter predicate given as a Rust closure
+#[derive(Clone)]
+pub struct ViewPredicate(Arc<dyn Fn(&Value) -> bool + Send + Sync>);
+
+impl ViewPredicate {
+    pub fn new(predicate: impl Fn(&Value) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(predicate))
+    }
+
+    pub fn matches(&self, entity: &Value) -> bool {
+        (self.0)(entity)
+    }
+}
+
+impl std::fmt::Debug for ViewPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ViewPredicate")
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -86,9 +315,53 @@ impl MaterializedView {
             source_id,
             current_keys: Arc::new(RwLock::new(HashSet::new())),
             pipeline,
+            budget: None,
+            counters: EvaluationCounters::default(),
+            live: AtomicBool::new(true),
+            consecutive_failures: AtomicU32::new(0),
         }
     }
 
+    /// Hold this view to `budget` instead of the registry's
+    pub fn with_budget(mut self, budget: ViewBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Whether the view is maintained on every source update
+    pub fn is_live(&self) -> bool {
+        self.live.load(Ordering::Relaxed)
+    }
+
+    pub fn stats(&self) -> ViewEvaluationStats {
+        let counters = &self.counters;
+        ViewEvaluationStats {
+            evaluations: counters.evaluations.load(Ordering::Relaxed),
+            items_scanned: counters.items_scanned.load(Ordering::Relaxed),
+            items_emitted: counters.items_emitted.load(Ordering::Relaxed),
+            total_micros: counters.total_micros.load(Ordering::Relaxed),
+            max_micros: counters.max_micros.load(Ordering::Relaxed),
+            over_budget: counters.over_budget.load(Ordering::Relaxed),
+            failures: counters.failures.load(Ordering::Relaxed),
+            live: self.is_live(),
+        }
+    }
+
+    /// Warn about an evaluation over `budget`, stopping live maintenance
+    /// when the budget says so
+    fn exceeded(&self, budget: &ViewBudget, cost: &EvaluationCost) {
+        self.counters.over_budget.fetch_add(1, Ordering::Relaxed);
+        let fall_back = budget.fallback_to_refresh && self.live.swap(false, Ordering::Relaxed);
+        warn!(
+            view_id = %self.id,
+            elapsed_us = cost.elapsed.as_micros() as u64,
+            budget_us = budget.max_evaluation.as_micros() as u64,
+            scanned = cost.scanned,
+            fall_back,
+            "View evaluation exceeded its budget"
+        );
+    }
+
     /// Get current keys in the view
     pub async fn get_keys(&self) -> HashSet<String> {
         self.current_keys.read().await.clone()
@@ -102,10 +375,16 @@ impl MaterializedView {
 
     /// Evaluate pipeline on a set of entities
     async fn evaluate_pipeline(&self, mut entities: Vec<(String, Value)>) -> Vec<(String, Value)> {
+        let started = Instant::now();
+        let scanned = entities.len() as u64;
+
         // Apply filter
         if let Some(ref filter) = self.pipeline.filter {
             entities.retain(|(_, v)| self.matches_filter(v, filter));
         }
+        if let Some(ref predicate) = self.pipeline.predicate {
+            entities.retain(|(_, v)| predicate.matches(v));
+        }
 
         // Apply sort
         if let Some(ref sort) = self.pipeline.sort {
@@ -129,6 +408,11 @@ impl MaterializedView {
         let keys: HashSet<String> = entities.iter().map(|(k, _)| k.clone()).collect();
         *self.current_keys.write().await = keys;
 
+        self.counters.record(&EvaluationCost {
+            scanned,
+            emitted: entities.len() as u64,
+            elapsed: started.elapsed(),
+        });
         entities
     }
 
@@ -156,6 +440,29 @@ impl MaterializedView {
         new_value: Option<&Value>,
         _cache: &EntityCache,
     ) -> ViewEffect {
+        self.evaluate_effect(key, new_value).await.0
+    }
+
+    async fn evaluate_effect(
+        &self,
+        key: &str,
+        new_value: Option<&Value>,
+    ) -> (ViewEffect, EvaluationCost) {
+        let started = Instant::now();
+        let effect = self.effect_of(key, new_value).await;
+        let cost = EvaluationCost {
+            scanned: 1,
+            emitted: match effect {
+                ViewEffect::NoEffect | ViewEffect::Remove { .. } => 0,
+                _ => 1,
+            },
+            elapsed: started.elapsed(),
+        };
+        self.counters.record(&cost);
+        (effect, cost)
+    }
+
+    async fn effect_of(&self, key: &str, new_value: Option<&Value>) -> ViewEffect {
         let current_keys = self.current_keys.read().await;
         let was_in_view = current_keys.contains(key);
         drop(current_keys);
@@ -163,11 +470,16 @@ impl MaterializedView {
         // Check if entity now matches filter
         let matches_now = match new_value {
             Some(v) => {
-                if let Some(ref filter) = self.pipeline.filter {
-                    self.matches_filter(v, filter)
-                } else {
-                    true
-                }
+                let matches_filter = match self.pipeline.filter {
+                    Some(ref filter) => self.matches_filter(v, filter),
+                    None => true,
+                };
+                matches_filter
+                    && self
+                        .pipeline
+                        .predicate
+                        .as_ref()
+                        .is_none_or(|predicate| predicate.matches(v))
             }
             None => false, // Deleted
         };
@@ -218,8 +530,16 @@ impl MaterializedView {
     }
 }
 
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "view evaluation panicked".to_string())
+}
+
 /// Extract a field value from a JSON object using a path
-fn extract_field(value: &Value, path: &[String]) -> Value {
+pub(crate) fn extract_field(value: &Value, path: &[String]) -> Value {
     let mut current = value;
     for segment in path {
         match current.get(segment) {
@@ -245,11 +565,27 @@ fn compare_values(a: &Value, b: &Value) -> std::cmp::Ordering {
 }
 
 /// Registry of materialized views
-#[derive(Default)]
 pub struct MaterializedViewRegistry {
     views: HashMap<String, Arc<MaterializedView>>,
     /// Map from source view ID to dependent materialized views
     dependencies: HashMap<String, Vec<String>>,
+    /// Budget of views that don't set their own
+    budget: Option<ViewBudget>,
+    quarantined: QuarantinedViews,
+    /// Stamps when a view was quarantined
+    clock: SharedClock,
+}
+
+impl Default for MaterializedViewRegistry {
+    fn default() -> Self {
+        Self {
+            views: HashMap::new(),
+            dependencies: HashMap::new(),
+            budget: None,
+            quarantined: QuarantinedViews::default(),
+            clock: system_clock(),
+        }
+    }
 }
 
 impl MaterializedViewRegistry {
@@ -257,6 +593,32 @@ impl MaterializedViewRegistry {
         Self::default()
     }
 
+    /// Hold every view without its own budget to `budget`
+    pub fn with_budget(mut self, budget: ViewBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Record quarantined views in `quarantined`, shared with the view index
+    pub fn with_quarantine(mut self, quarantined: QuarantinedViews) -> Self {
+        self.quarantined = quarantined;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn quarantined(&self) -> &QuarantinedViews {
+        &self.quarantined
+    }
+
+    /// How often views that fell back to periodic refresh are re-evaluated
+    pub fn refresh_interval(&self) -> Duration {
+        self.budget.unwrap_or_default().refresh_interval
+    }
+
     /// Register a materialized view
     pub fn register(&mut self, view: MaterializedView) {
         let view_id = view.id.clone();
@@ -286,11 +648,158 @@ impl MaterializedViewRegistry {
             })
             .unwrap_or_default()
     }
+
+    /// Compute and apply the effect of a source update on view `view_id`.
+    ///
+    /// Returns `None` for unknown and quarantined views and views that fell
+    /// back to periodic refresh, and the failure when the evaluation panicked.
+    pub async fn maintain(
+        &self,
+        view_id: &str,
+        key: &str,
+        new_value: Option<&Value>,
+    ) -> Option<Result<Maintained, ViewFailure>> {
+        let view = self.views.get(view_id)?;
+        if !view.is_live() || self.quarantined.contains(view_id) {
+            return None;
+        }
+
+        let (effect, cost) = match self
+            .isolated(view, view.evaluate_effect(key, new_value))
+            .await
+        {
+            Ok(evaluated) => evaluated,
+            Err(failure) => return Some(Err(failure)),
+        };
+        view.apply_effect(&effect).await;
+
+        let over_budget = match view.budget.or(self.budget) {
+            Some(budget) if cost.elapsed > budget.max_evaluation => {
+                view.exceeded(&budget, &cost);
+                true
+            }
+            _ => false,
+        };
+        Some(Ok(Maintained {
+            effect,
+            cost,
+            over_budget,
+        }))
+    }
+
+    /// Re-evaluate every view without live maintenance from `cache`
+    pub async fn refresh(&self, cache: &EntityCache) -> usize {
+        let mut refreshed = 0;
+        for view in self.views.values().filter(|view| !view.is_live()) {
+            if self.quarantined.contains(&view.id) {
+                continue;
+            }
+            if self
+                .isolated(view, view.evaluate_initial(cache))
+                .await
+                .is_ok()
+            {
+                refreshed += 1;
+            }
+        }
+        refreshed
+    }
+
+    /// Re-evaluate quarantined view `view_id` from `cache` and put it back
+    /// in service, returning the number of entities in its result.
+    ///
+    /// `None` when the view isn't quarantined. A view that fails again stays
+    /// quarantined, with the new failure recorded.
+    pub async fn unquarantine(
+        &self,
+        view_id: &str,
+        cache: &EntityCache,
+    ) -> Option<Result<usize, ViewFailure>> {
+        let view = self.views.get(view_id)?;
+        let quarantine = self.quarantined.get(view_id)?;
+
+        let result = AssertUnwindSafe(view.evaluate_initial(cache))
+            .catch_unwind()
+            .await;
+        match result {
+            Ok(entities) => {
+                view.consecutive_failures.store(0, Ordering::Relaxed);
+                self.quarantined.remove(view_id);
+                tracing::info!(view_id, "View taken out of quarantine");
+                Some(Ok(entities.len()))
+            }
+            Err(panic) => {
+                view.counters.failures.fetch_add(1, Ordering::Relaxed);
+                let error = panic_message(panic.as_ref());
+                self.quarantined.insert(
+                    view_id,
+                    Quarantine {
+                        error: error.clone(),
+                        failures: quarantine.failures.saturating_add(1),
+                        ..quarantine
+                    },
+                );
+                Some(Err(ViewFailure {
+                    error,
+                    quarantined: true,
+                }))
+            }
+        }
+    }
+
+    /// Run an evaluation of `view`, turning a panic into a failure that
+    /// quarantines the view once it has failed too many times in a row
+    async fn isolated<T>(
+        &self,
+        view: &MaterializedView,
+        evaluation: impl Future<Output = T>,
+    ) -> Result<T, ViewFailure> {
+        let panic = match AssertUnwindSafe(evaluation).catch_unwind().await {
+            Ok(evaluated) => {
+                view.consecutive_failures.store(0, Ordering::Relaxed);
+                return Ok(evaluated);
+            }
+            Err(panic) => panic,
+        };
+
+        view.counters.failures.fetch_add(1, Ordering::Relaxed);
+        let failures = view.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        let max_failures = view.budget.or(self.budget).unwrap_or_default().max_failures;
+        let error = panic_message(panic.as_ref());
+        let quarantined = failures >= max_failures;
+        error!(
+            view_id = %view.id,
+            failures,
+            quarantined,
+            "View evaluation panicked: {}",
+            error
+        );
+        if quarantined {
+            self.quarantined.insert(
+                &view.id,
+                Quarantine {
+                    error: error.clone(),
+                    failures,
+                    since_ms: self.clock.unix_millis().max(0) as u64,
+                },
+            );
+        }
+        Err(ViewFailure { error, quarantined })
+    }
+
+    /// Evaluation counters of every view, by view id
+    pub fn stats(&self) -> BTreeMap<String, ViewEvaluationStats> {
+        self.views
+            .iter()
+            .map(|(id, view)| (id.clone(), view.stats()))
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use hyperstack_interpreter::testkit::ManualClock;
     use serde_json::json;
 
     #[tokio::test]
@@ -303,6 +812,7 @@ mod tests {
             }),
             sort: None,
             limit: None,
+            predicate: None,
         };
 
         let view =
@@ -329,6 +839,7 @@ mod tests {
                 order: SortOrder::Desc,
             }),
             limit: Some(2),
+            predicate: None,
         };
 
         let view =
@@ -345,4 +856,234 @@ mod tests {
         assert_eq!(result[0].0, "2"); // value: 30
         assert_eq!(result[1].0, "3"); // value: 20
     }
+
+    fn active_filter() -> ViewPipeline {
+        ViewPipeline {
+            filter: Some(FilterConfig {
+                field_path: vec!["history".to_string()],
+                op: CompareOp::Ne,
+                value: Value::Null,
+            }),
+            sort: None,
+            limit: None,
+            predicate: None,
+        }
+    }
+
+    /// An entity whose filtered field is large enough that every evaluation
+    /// takes well over a millisecond
+    fn expensive_entity() -> Value {
+        json!({ "history": (0..1_000_000).collect::<Vec<u64>>() })
+    }
+
+    #[tokio::test]
+    async fn test_evaluations_are_counted() {
+        let view = MaterializedView::new(
+            "test/active".to_string(),
+            "test/list".to_string(),
+            active_filter(),
+        );
+
+        let entities = vec![
+            ("1".to_string(), json!({ "history": [1] })),
+            ("2".to_string(), json!({})),
+            ("3".to_string(), expensive_entity()),
+        ];
+        view.evaluate_pipeline(entities).await;
+
+        let cache = EntityCache::new();
+        view.compute_effect("4", Some(&json!({ "history": [] })), &cache)
+            .await;
+        view.compute_effect("5", Some(&json!({})), &cache).await;
+
+        let stats = view.stats();
+        assert_eq!(stats.evaluations, 3);
+        assert_eq!(stats.items_scanned, 5);
+        assert_eq!(stats.items_emitted, 3);
+        assert!(stats.max_micros > 0);
+        assert!(stats.total_micros >= stats.max_micros);
+        assert_eq!(stats.over_budget, 0);
+        assert!(stats.live);
+    }
+
+    #[tokio::test]
+    async fn test_view_over_budget_falls_back_to_refresh() {
+        let mut registry = MaterializedViewRegistry::new().with_budget(
+            ViewBudget::new()
+                .with_max_evaluation(Duration::from_millis(1))
+                .with_fallback_to_refresh(Duration::from_secs(5)),
+        );
+        registry.register(MaterializedView::new(
+            "test/active".to_string(),
+            "test/list".to_string(),
+            active_filter(),
+        ));
+        assert_eq!(registry.refresh_interval(), Duration::from_secs(5));
+
+        let expensive = expensive_entity();
+        let maintained = registry
+            .maintain("test/active", "1", Some(&expensive))
+            .await
+            .expect("view is live")
+            .expect("evaluation succeeds");
+        assert_eq!(
+            maintained.effect,
+            ViewEffect::Add {
+                key: "1".to_string()
+            }
+        );
+        assert!(maintained.over_budget);
+
+        // Later updates are left to the periodic refresh
+        assert!(registry
+            .maintain("test/active", "3", Some(&json!({ "history": [3] })))
+            .await
+            .is_none());
+
+        let stats = &registry.stats()["test/active"];
+        assert_eq!(stats.evaluations, 1);
+        assert_eq!(stats.over_budget, 1);
+        assert!(!stats.live);
+
+        let cache = EntityCache::new();
+        cache
+            .upsert("test/list", "3", json!({ "history": [3] }))
+            .await;
+        cache.upsert("test/list", "4", json!({})).await;
+        assert_eq!(registry.refresh(&cache).await, 1);
+
+        let view = registry.get("test/active").unwrap();
+        assert_eq!(view.get_keys().await, HashSet::from(["3".to_string()]));
+        assert_eq!(view.stats().evaluations, 2);
+    }
+
+    #[tokio::test]
+    async fn test_view_budget_overrides_the_registry() {
+        let mut registry = MaterializedViewRegistry::new()
+            .with_budget(ViewBudget::new().with_fallback_to_refresh(Duration::from_secs(5)));
+        registry.register(
+            MaterializedView::new(
+                "test/active".to_string(),
+                "test/list".to_string(),
+                active_filter(),
+            )
+            .with_budget(ViewBudget::new().with_max_evaluation(Duration::from_millis(1))),
+        );
+
+        let expensive = expensive_entity();
+        let maintained = registry
+            .maintain("test/active", "1", Some(&expensive))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(maintained.over_budget);
+
+        // The view's own budget only warns
+        assert!(registry.get("test/active").unwrap().is_live());
+    }
+
+    /// A pipeline whose predicate panics on entities marked `poison`
+    fn poisoned_pipeline() -> ViewPipeline {
+        ViewPipeline {
+            predicate: Some(ViewPredicate::new(|entity| {
+                assert!(entity.get("poison").is_none(), "poisoned entity");
+                true
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicking_view_is_quarantined_alone() {
+        let mut registry = MaterializedViewRegistry::new()
+            .with_budget(ViewBudget::new().with_max_failures(2))
+            .with_clock(Arc::new(ManualClock::at_unix_secs(1_000)));
+        registry.register(MaterializedView::new(
+            "test/poisoned".to_string(),
+            "test/list".to_string(),
+            poisoned_pipeline(),
+        ));
+        registry.register(MaterializedView::new(
+            "test/active".to_string(),
+            "test/list".to_string(),
+            active_filter(),
+        ));
+
+        let poison = json!({ "history": [1], "poison": true });
+        for (attempt, key) in ["1", "2"].into_iter().enumerate() {
+            let failure = registry
+                .maintain("test/poisoned", key, Some(&poison))
+                .await
+                .expect("view is maintained")
+                .expect_err("evaluation panics");
+            assert_eq!(failure.error, "poisoned entity");
+            assert_eq!(failure.quarantined, attempt == 1);
+
+            let maintained = registry
+                .maintain("test/active", key, Some(&poison))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                maintained.effect,
+                ViewEffect::Add {
+                    key: key.to_string()
+                }
+            );
+        }
+
+        // The quarantined view is no longer maintained or refreshed
+        assert!(registry
+            .maintain("test/poisoned", "3", Some(&json!({})))
+            .await
+            .is_none());
+        let quarantine = registry.quarantined().get("test/poisoned").unwrap();
+        assert_eq!(quarantine.error, "poisoned entity");
+        assert_eq!(quarantine.failures, 2);
+        assert_eq!(quarantine.since_ms, 1_000_000);
+        assert!(!registry.quarantined().contains("test/active"));
+        assert_eq!(registry.stats()["test/poisoned"].failures, 2);
+
+        // Unquarantining fails while the cache still holds the poison
+        let cache = EntityCache::new();
+        cache.upsert("test/list", "1", poison.clone()).await;
+        let failure = registry
+            .unquarantine("test/poisoned", &cache)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(failure.quarantined);
+        assert_eq!(
+            registry
+                .quarantined()
+                .get("test/poisoned")
+                .unwrap()
+                .failures,
+            3
+        );
+
+        let healthy = EntityCache::new();
+        healthy
+            .upsert("test/list", "1", json!({ "history": [1] }))
+            .await;
+        healthy.upsert("test/list", "4", json!({})).await;
+        assert_eq!(
+            registry
+                .unquarantine("test/poisoned", &healthy)
+                .await
+                .unwrap()
+                .unwrap(),
+            2
+        );
+        assert!(registry.quarantined().all().is_empty());
+        assert!(registry
+            .maintain("test/poisoned", "5", Some(&json!({})))
+            .await
+            .unwrap()
+            .is_ok());
+        assert!(registry
+            .unquarantine("test/poisoned", &healthy)
+            .await
+            .is_none());
+    }
 }
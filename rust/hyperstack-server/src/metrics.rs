@@ -41,6 +41,7 @@ pub struct Metrics {
     pub ws_connections_total: Counter<u64>,
     pub ws_connections_active: UpDownCounter<i64>,
     pub ws_messages_received: Counter<u64>,
+    pub ws_inbound_rejected: Counter<u64>,
     pub ws_messages_sent: Counter<u64>,
     pub ws_connection_duration: Histogram<f64>,
     pub ws_subscriptions_active: UpDownCounter<i64>,
@@ -49,6 +50,18 @@ pub struct Metrics {
     pub projector_mutations_processed: Counter<u64>,
     pub projector_frames_published: Counter<u64>,
     pub projector_processing_latency: Histogram<f64>,
+    pub projector_coalesce_window: Gauge<f64>,
+
+    // Append log metrics
+    pub append_log_evictions: Counter<u64>,
+    pub append_log_bytes: Gauge<i64>,
+    pub append_log_oldest_age: Gauge<f64>,
+
+    // Derived view metrics
+    pub view_evaluation_duration: Histogram<f64>,
+    pub view_items_scanned: Histogram<u64>,
+    pub view_items_emitted: Histogram<u64>,
+    pub view_over_budget: Counter<u64>,
 
     // Stream/Parser metrics
     pub stream_events_received: Counter<u64>,
@@ -119,6 +132,11 @@ impl Metrics {
             .with_description("Total WebSocket messages received from clients")
             .init();
 
+        let ws_inbound_rejected = meter
+            .u64_counter("hyperstack.ws.inbound.rejected")
+            .with_description("Client messages refused for size or rate, by reason")
+            .init();
+
         let ws_messages_sent = meter
             .u64_counter("hyperstack.ws.messages.sent")
             .with_description("Total WebSocket messages sent to clients")
@@ -150,6 +168,48 @@ impl Metrics {
             .with_description("Latency of mutation processing in milliseconds")
             .init();
 
+        let projector_coalesce_window = meter
+            .f64_gauge("hyperstack.projector.coalesce_window")
+            .with_description("Current projector coalescing window in milliseconds")
+            .init();
+
+        // Append log metrics
+        let append_log_evictions = meter
+            .u64_counter("hyperstack.append_log.evictions")
+            .with_description("Items evicted from append view logs, by view and reason")
+            .init();
+
+        let append_log_bytes = meter
+            .i64_gauge("hyperstack.append_log.bytes")
+            .with_description("Bytes held by each append view's log")
+            .init();
+
+        let append_log_oldest_age = meter
+            .f64_gauge("hyperstack.append_log.oldest_age")
+            .with_description("Age of the oldest item in each append view's log in seconds")
+            .init();
+
+        // Derived view metrics
+        let view_evaluation_duration = meter
+            .f64_histogram("hyperstack.view.evaluation.duration")
+            .with_description("Time to evaluate a derived view for one update in microseconds")
+            .init();
+
+        let view_items_scanned = meter
+            .u64_histogram("hyperstack.view.evaluation.items_scanned")
+            .with_description("Source entities a derived view evaluation looked at")
+            .init();
+
+        let view_items_emitted = meter
+            .u64_histogram("hyperstack.view.evaluation.items_emitted")
+            .with_description("Entities a derived view evaluation put in the result")
+            .init();
+
+        let view_over_budget = meter
+            .u64_counter("hyperstack.view.evaluation.over_budget")
+            .with_description("Derived view evaluations that exceeded the view budget")
+            .init();
+
         // Stream metrics
         let stream_events_received = meter
             .u64_counter("hyperstack.stream.events.received")
@@ -335,12 +395,21 @@ impl Metrics {
             ws_connections_total,
             ws_connections_active,
             ws_messages_received,
+            ws_inbound_rejected,
             ws_messages_sent,
             ws_connection_duration,
             ws_subscriptions_active,
             projector_mutations_processed,
             projector_frames_published,
             projector_processing_latency,
+            projector_coalesce_window,
+            append_log_evictions,
+            append_log_bytes,
+            append_log_oldest_age,
+            view_evaluation_duration,
+            view_items_scanned,
+            view_items_emitted,
+            view_over_budget,
             stream_events_received,
             stream_errors_total,
             vm_instructions_executed,
@@ -420,6 +489,12 @@ impl Metrics {
         );
     }
 
+    /// Record a client message refused before parsing, e.g. for size or rate
+    pub fn record_ws_inbound_rejected(&self, reason: &str) {
+        self.ws_inbound_rejected
+            .add(1, &[KeyValue::new("reason", reason.to_string())]);
+    }
+
     /// Record a WebSocket message sent
     pub fn record_ws_message_sent(&self) {
         self.ws_messages_sent.add(1, &[]);
@@ -491,6 +566,49 @@ impl Metrics {
         self.projector_processing_latency.record(latency_ms, &[]);
     }
 
+    /// Record the projector's current coalescing window
+    pub fn record_coalesce_window(&self, window: std::time::Duration) {
+        self.projector_coalesce_window
+            .record(window.as_secs_f64() * 1000.0, &[]);
+    }
+
+    /// Record evictions and retention of a view's append log after an append
+    pub fn record_append_log(&self, view_id: &str, appended: &crate::append_log::Appended) {
+        let view = KeyValue::new("view", view_id.to_string());
+        for (reason, count) in [
+            ("items", appended.evicted.over_items),
+            ("bytes", appended.evicted.over_bytes),
+            ("age", appended.evicted.expired),
+        ] {
+            if count > 0 {
+                self.append_log_evictions
+                    .add(count, &[view.clone(), KeyValue::new("reason", reason)]);
+            }
+        }
+        self.append_log_bytes
+            .record(appended.bytes as i64, std::slice::from_ref(&view));
+        let oldest_age = appended.oldest_age.unwrap_or_default();
+        self.append_log_oldest_age
+            .record(oldest_age.as_secs_f64(), &[view]);
+    }
+
+    /// Record the cost of maintaining a derived view for one update
+    pub fn record_view_evaluation(
+        &self,
+        view_id: &str,
+        maintained: &crate::materialized_view::Maintained,
+    ) {
+        let view = [KeyValue::new("view", view_id.to_string())];
+        let cost = &maintained.cost;
+        self.view_evaluation_duration
+            .record(cost.elapsed.as_secs_f64() * 1_000_000.0, &view);
+        self.view_items_scanned.record(cost.scanned, &view);
+        self.view_items_emitted.record(cost.emitted, &view);
+        if maintained.over_budget {
+            self.view_over_budget.add(1, &view);
+        }
+    }
+
     // ==================== Stream Helpers ====================
 
     /// Record an event received from the stream
@@ -1,6 +1,7 @@
 //! MutationBatch - Envelope type for propagating trace context across async boundaries.
 
 use hyperstack_interpreter::Mutation;
+use serde_json::Value;
 use smallvec::SmallVec;
 use tracing::Span;
 
@@ -50,6 +51,9 @@ pub struct EventContext {
     pub event_type: String,
     pub account: Option<String>,
     pub accounts_count: Option<usize>,
+    /// Instruction types of the transaction, in order, when its mutations
+    /// were grouped into one batch
+    pub instructions: Vec<String>,
 }
 
 impl MutationBatch {
@@ -96,3 +100,37 @@ impl MutationBatch {
         self.mutations.is_empty()
     }
 }
+
+/// Apply a mutation patch the way the entity cache does: objects merge
+/// recursively, arrays listed in `append` are extended, everything else is
+/// replaced. Unlike the cache, nulls are kept rather than removing the field,
+/// so patches merged together still clear the fields they cleared alone.
+pub(crate) fn merge_patch(base: &mut Value, patch: &Value, append: &[String]) {
+    merge_patch_inner(base, patch, append, "");
+}
+
+fn merge_patch_inner(base: &mut Value, patch: &Value, append: &[String], path: &str) {
+    match (base, patch) {
+        (Value::Object(base_map), Value::Object(patch_map)) => {
+            for (key, patch_value) in patch_map {
+                let child_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match base_map.get_mut(key) {
+                    Some(base_value) => {
+                        merge_patch_inner(base_value, patch_value, append, &child_path)
+                    }
+                    None => {
+                        base_map.insert(key.clone(), patch_value.clone());
+                    }
+                }
+            }
+        }
+        (Value::Array(base_arr), Value::Array(patch_arr)) if append.iter().any(|p| p == path) => {
+            base_arr.extend(patch_arr.iter().cloned());
+        }
+        (base, patch) => *base = patch.clone(),
+    }
+}
@@ -0,0 +1,420 @@
+//! Snapshots of the server's state, so restarts don't lose entities.
+//!
+//! With [`ServerBuilder::persistence`](crate::ServerBuilder::persistence)
+//! set, the runtime saves a [`StateSnapshot`] every
+//! [`PersistenceConfig::interval`] and once more when it shuts down. A
+//! snapshot holds every entity of the [`EntityCache`] and, when the spec
+//! provides [`VmStateHooks`], the VM's state tables from
+//! [`VmContext::export_state`](hyperstack_interpreter::vm::VmContext::export_state).
+//!
+//! On startup the runtime loads the latest snapshot before the parser starts,
+//! so list and state views serve the restored entities to their first
+//! subscribers and handlers build on the restored VM state. A snapshot taken
+//! with another [`MultiEntityBytecode::schema_hash`], or whose VM state has
+//! tables of other entities than the spec's, is skipped with a warning so
+//! state never loads into the wrong entity tables.
+//!
+//! [`FileStatePersistence`] keeps snapshots as JSON files in a directory.
+//! Files that fail to parse, or were written with another
+//! [`SNAPSHOT_VERSION`], are skipped with a warning in favour of the next
+//! older one. Implement [`StatePersistence`] to keep snapshots elsewhere.
+
+use crate::cache::EntityCache;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use hyperstack_interpreter::clock::SharedClock;
+use hyperstack_interpreter::compiler::MultiEntityBytecode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, info_span, warn, Instrument};
+
+/// Format version of [`StateSnapshot`], bumped when it changes
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Default time between snapshots
+pub const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default number of snapshot files a [`FileStatePersistence`] keeps
+pub const DEFAULT_SNAPSHOTS_KEPT: usize = 3;
+
+const FILE_PREFIX: &str = "snapshot-";
+const FILE_SUFFIX: &str = ".json";
+
+/// Export of the VM's state, `None` until the parser runtime has started
+pub type VmStateExportFn = Arc<dyn Fn() -> Option<Value> + Send + Sync>;
+
+/// Restore exported VM state into the VM the parser runtime creates
+pub type VmStateImportFn = Arc<dyn Fn(Value) + Send + Sync>;
+
+/// How a spec's VM state is saved and restored
+#[derive(Clone)]
+pub struct VmStateHooks {
+    /// Called from a blocking thread while the server runs
+    pub export: VmStateExportFn,
+    /// Called before the parser runtime starts
+    pub import: VmStateImportFn,
+}
+
+/// One cached entity of a snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedEntity {
+    pub key: String,
+    pub data: Value,
+    /// Unix time (ms) of the entity's last update
+    pub updated_at: i64,
+}
+
+/// The server's state at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub version: u32,
+    /// Unix time (ms) the snapshot was taken
+    pub created_at: i64,
+    /// [`MultiEntityBytecode::schema_hash`] of the spec the snapshot was taken with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_hash: Option<String>,
+    /// Cached entities by view, least recently updated first
+    pub views: BTreeMap<String, Vec<PersistedEntity>>,
+    /// [`VmContext::export_state`](hyperstack_interpreter::vm::VmContext::export_state)
+    /// of the spec's VM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vm_state: Option<Value>,
+}
+
+impl StateSnapshot {
+    pub fn entity_count(&self) -> usize {
+        self.views.values().map(Vec::len).sum()
+    }
+}
+
+/// Where snapshots are kept
+#[async_trait]
+pub trait StatePersistence: Send + Sync {
+    /// Store `snapshot` as the latest one
+    async fn save(&self, snapshot: &StateSnapshot) -> Result<()>;
+
+    /// The latest usable snapshot, `None` when there is none
+    async fn load(&self) -> Result<Option<StateSnapshot>>;
+}
+
+/// Snapshots as JSON files in a directory, named after the time they were
+/// taken. Files are written under a temporary name and renamed into place,
+/// so a crash mid-write never leaves a truncated snapshot behind.
+#[derive(Debug, Clone)]
+pub struct FileStatePersistence {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl FileStatePersistence {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            keep: DEFAULT_SNAPSHOTS_KEPT,
+        }
+    }
+
+    /// Keep the `keep` latest snapshots, at least one, deleting older ones
+    pub fn with_keep(mut self, keep: usize) -> Self {
+        self.keep = keep.max(1);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Snapshot files in the directory, latest first
+    fn snapshot_files(dir: &Path) -> Result<Vec<PathBuf>> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", dir.display())),
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX)
+                    })
+            })
+            .collect();
+        // Names embed zero-padded timestamps, so they sort by age
+        files.sort_by(|a, b| b.cmp(a));
+        Ok(files)
+    }
+
+    fn read(path: &Path) -> Result<StateSnapshot> {
+        let bytes = std::fs::read(path)?;
+        let value: Value = serde_json::from_slice(&bytes)?;
+        let version = value.get("version").and_then(Value::as_u64);
+        if version != Some(u64::from(SNAPSHOT_VERSION)) {
+            bail!(
+                "snapshot version {} is not supported, expected {}",
+                version.map_or_else(|| "(missing)".to_string(), |v| v.to_string()),
+                SNAPSHOT_VERSION
+            );
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+#[async_trait]
+impl StatePersistence for FileStatePersistence {
+    async fn save(&self, snapshot: &StateSnapshot) -> Result<()> {
+        let bytes = serde_json::to_vec(snapshot)?;
+        let dir = self.dir.clone();
+        let keep = self.keep;
+        let name = format!(
+            "{}{:020}{}",
+            FILE_PREFIX,
+            snapshot.created_at.max(0),
+            FILE_SUFFIX
+        );
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("creating {}", dir.display()))?;
+            let path = dir.join(&name);
+            let tmp = dir.join(format!("{}.tmp", name));
+            std::fs::write(&tmp, &bytes).with_context(|| format!("writing {}", tmp.display()))?;
+            std::fs::rename(&tmp, &path)
+                .with_context(|| format!("renaming {} to {}", tmp.display(), path.display()))?;
+
+            for old in Self::snapshot_files(&dir)?.into_iter().skip(keep) {
+                if let Err(e) = std::fs::remove_file(&old) {
+                    warn!(path = %old.display(), error = %e, "Failed to delete an old state snapshot");
+                }
+            }
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn load(&self) -> Result<Option<StateSnapshot>> {
+        let dir = self.dir.clone();
+        tokio::task::spawn_blocking(move || {
+            for path in Self::snapshot_files(&dir)? {
+                match Self::read(&path) {
+                    Ok(snapshot) => return Ok(Some(snapshot)),
+                    Err(e) => warn!(
+                        path = %path.display(),
+                        error = %e,
+                        "Skipping unusable state snapshot"
+                    ),
+                }
+            }
+            Ok(None)
+        })
+        .await?
+    }
+}
+
+/// How and how often the server's state is snapshotted
+#[derive(Clone)]
+pub struct PersistenceConfig {
+    pub store: Arc<dyn StatePersistence>,
+    /// Time between snapshots
+    pub interval: Duration,
+    /// Include the VM's state tables when the spec provides [`VmStateHooks`]
+    pub vm_state: bool,
+}
+
+impl PersistenceConfig {
+    /// Keep snapshots as files in `dir`, see [`FileStatePersistence`]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self::with_store(Arc::new(FileStatePersistence::new(dir)))
+    }
+
+    /// Keep snapshots in `store`
+    pub fn with_store(store: Arc<dyn StatePersistence>) -> Self {
+        Self {
+            store,
+            interval: DEFAULT_SNAPSHOT_INTERVAL,
+            vm_state: true,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_vm_state(mut self, enabled: bool) -> Self {
+        self.vm_state = enabled;
+        self
+    }
+}
+
+impl fmt::Debug for PersistenceConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PersistenceConfig")
+            .field("interval", &self.interval)
+            .field("vm_state", &self.vm_state)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Saves and restores the state of one runtime
+#[derive(Clone)]
+pub(crate) struct Persister {
+    config: PersistenceConfig,
+    cache: EntityCache,
+    vm: Option<VmStateHooks>,
+    schema_hash: Option<String>,
+    /// Entity of each of the spec's state tables, by state id
+    state_tables: BTreeMap<u32, String>,
+    clock: SharedClock,
+}
+
+impl Persister {
+    pub(crate) fn new(
+        config: PersistenceConfig,
+        cache: EntityCache,
+        vm: Option<VmStateHooks>,
+        bytecode: Option<&MultiEntityBytecode>,
+        clock: SharedClock,
+    ) -> Self {
+        let vm = vm.filter(|_| config.vm_state);
+        Self {
+            config,
+            cache,
+            vm,
+            schema_hash: bytecode.map(MultiEntityBytecode::schema_hash),
+            state_tables: bytecode
+                .map(MultiEntityBytecode::state_tables)
+                .unwrap_or_default(),
+            clock,
+        }
+    }
+
+    /// Why the snapshot can't be restored into this spec, if it can't
+    fn check_schema(&self, snapshot: &StateSnapshot) -> Result<()> {
+        if snapshot.schema_hash != self.schema_hash {
+            bail!(
+                "taken with schema {}, the spec's is {}",
+                snapshot.schema_hash.as_deref().unwrap_or("(missing)"),
+                self.schema_hash.as_deref().unwrap_or("(none)")
+            );
+        }
+        let tables = snapshot
+            .vm_state
+            .as_ref()
+            .and_then(|state| state.get("states"))
+            .and_then(Value::as_object);
+        for (state_id, table) in tables.into_iter().flatten() {
+            let entity_name = table.get("entity_name").and_then(Value::as_str);
+            let expected = state_id
+                .parse::<u32>()
+                .ok()
+                .and_then(|state_id| self.state_tables.get(&state_id));
+            if entity_name != expected.map(String::as_str) {
+                bail!(
+                    "VM state table {} holds {}, the spec's holds {}",
+                    state_id,
+                    entity_name.unwrap_or("(missing)"),
+                    expected.map_or("nothing", String::as_str)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the latest snapshot into the cache and the VM. Failures are
+    /// logged, the server then starts empty.
+    pub(crate) async fn restore(&self) {
+        let snapshot = match self.config.store.load().await {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => {
+                info!("No state snapshot to restore");
+                return;
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to load a state snapshot, starting empty");
+                return;
+            }
+        };
+
+        if let Err(e) = self.check_schema(&snapshot) {
+            warn!(
+                error = %e,
+                created_at = snapshot.created_at,
+                "Skipping a state snapshot of another stack, starting empty"
+            );
+            return;
+        }
+
+        let entities = snapshot.entity_count();
+        for (view_id, view_entities) in snapshot.views {
+            self.cache.restore_entities(&view_id, view_entities).await;
+        }
+        let vm_state = match (&self.vm, snapshot.vm_state) {
+            (Some(hooks), Some(state)) => {
+                (hooks.import)(state);
+                true
+            }
+            _ => false,
+        };
+        info!(
+            entities,
+            vm_state,
+            created_at = snapshot.created_at,
+            "Restored state snapshot"
+        );
+    }
+
+    pub(crate) async fn snapshot(&self) -> StateSnapshot {
+        let vm_state = match self.vm.clone() {
+            // Exporting waits for the VM thread, keep it off the async workers
+            Some(hooks) => tokio::task::spawn_blocking(move || (hooks.export)())
+                .await
+                .ok()
+                .flatten(),
+            None => None,
+        };
+        StateSnapshot {
+            version: SNAPSHOT_VERSION,
+            created_at: self.clock.unix_millis(),
+            schema_hash: self.schema_hash.clone(),
+            views: self.cache.export_entities().await,
+            vm_state,
+        }
+    }
+
+    /// Take and store a snapshot, logging failures
+    pub(crate) async fn save(&self) {
+        let snapshot = self.snapshot().await;
+        match self.config.store.save(&snapshot).await {
+            Ok(()) => info!(
+                entities = snapshot.entity_count(),
+                vm_state = snapshot.vm_state.is_some(),
+                "Saved state snapshot"
+            ),
+            Err(e) => warn!(error = %e, "Failed to save a state snapshot"),
+        }
+    }
+
+    /// Save a snapshot every interval
+    pub(crate) fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(
+            async move {
+                let mut interval = tokio::time::interval(self.config.interval);
+                // The first tick completes immediately
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    self.save().await;
+                }
+            }
+            .instrument(info_span!("state.persistence")),
+        )
+    }
+}
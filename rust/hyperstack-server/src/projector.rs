@@ -1,13 +1,28 @@
+use crate::append_log::LogLimits;
+use crate::big_numbers::BigNumbers;
 use crate::bus::{BusManager, BusMessage};
-use crate::cache::EntityCache;
-use crate::mutation_batch::{MutationBatch, SlotContext};
-use crate::view::{ViewIndex, ViewSpec};
-use crate::websocket::frame::{transform_large_u64_to_strings, Frame, Mode};
+use crate::cache::{EntityCache, RetentionNotice};
+use crate::coalesce::{AdaptiveWindow, CoalesceConfig, PassStats};
+use crate::debug_bundle::DeadLetters;
+use crate::dictionary::Dictionaries;
+use crate::flags::{Flag, Flags};
+use crate::health::SlotTracker;
+use crate::load_shed::LoadShedder;
+use crate::materialized_view::MaterializedViewRegistry;
+use crate::mutation_batch::{merge_patch, MutationBatch, SlotContext};
+use crate::reload::LiveViews;
+use crate::view::ViewSpec;
+use crate::view_coalesce::{HeldPatch, HeldPatches};
+use crate::webhook::Webhooks;
+use crate::websocket::client_manager::ClientManager;
+use crate::websocket::frame::{Frame, HistoryItem, Mode};
 use bytes::Bytes;
-use hyperstack_interpreter::CanonicalLog;
+use hyperstack_interpreter::{CanonicalLog, Mutation};
 use serde_json::Value;
 use smallvec::SmallVec;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::{debug, error, instrument};
 
@@ -15,10 +30,22 @@ use tracing::{debug, error, instrument};
 use crate::metrics::Metrics;
 
 pub struct Projector {
-    view_index: Arc<ViewIndex>,
+    view_index: LiveViews,
     bus_manager: BusManager,
     entity_cache: EntityCache,
     mutations_rx: mpsc::Receiver<MutationBatch>,
+    coalesce: Option<AdaptiveWindow>,
+    /// Patches held by views that coalesce on their own
+    held: Mutex<HeldPatches>,
+    clients: Option<ClientManager>,
+    materialized_views: Option<Arc<MaterializedViewRegistry>>,
+    load_shedder: Option<LoadShedder>,
+    dead_letters: Option<DeadLetters>,
+    webhooks: Option<Webhooks>,
+    big_numbers: BigNumbers,
+    dictionaries: Option<Dictionaries>,
+    flags: Arc<Flags>,
+    slots: Option<SlotTracker>,
     #[cfg(feature = "otel")]
     metrics: Option<Arc<Metrics>>,
 }
@@ -26,94 +53,530 @@ pub struct Projector {
 impl Projector {
     #[cfg(feature = "otel")]
     pub fn new(
-        view_index: Arc<ViewIndex>,
+        view_index: impl Into<LiveViews>,
         bus_manager: BusManager,
         entity_cache: EntityCache,
         mutations_rx: mpsc::Receiver<MutationBatch>,
         metrics: Option<Arc<Metrics>>,
     ) -> Self {
         Self {
-            view_index,
+            view_index: view_index.into(),
             bus_manager,
             entity_cache,
             mutations_rx,
+            coalesce: None,
+            held: Mutex::default(),
+            clients: None,
+            materialized_views: None,
+            load_shedder: None,
+            dead_letters: None,
+            webhooks: None,
+            big_numbers: BigNumbers::default(),
+            dictionaries: None,
+            flags: Arc::default(),
+            slots: None,
             metrics,
         }
     }
 
     #[cfg(not(feature = "otel"))]
     pub fn new(
-        view_index: Arc<ViewIndex>,
+        view_index: impl Into<LiveViews>,
         bus_manager: BusManager,
         entity_cache: EntityCache,
         mutations_rx: mpsc::Receiver<MutationBatch>,
     ) -> Self {
         Self {
-            view_index,
+            view_index: view_index.into(),
             bus_manager,
             entity_cache,
             mutations_rx,
+            coalesce: None,
+            held: Mutex::default(),
+            clients: None,
+            materialized_views: None,
+            load_shedder: None,
+            dead_letters: None,
+            webhooks: None,
+            big_numbers: BigNumbers::default(),
+            dictionaries: None,
+            flags: Arc::default(),
+            slots: None,
         }
     }
 
+    /// Gather the batches arriving within an adaptive window and merge
+    /// patches to the same entity before publishing.
+    ///
+    /// See the [`coalesce`](crate::coalesce) module.
+    pub fn with_coalescing(mut self, config: CoalesceConfig) -> Self {
+        self.coalesce = Some(AdaptiveWindow::new(config));
+        self
+    }
+
+    /// Let the fill level of these clients' send queues widen the window
+    pub fn with_queue_pressure(mut self, clients: ClientManager) -> Self {
+        self.clients = Some(clients);
+        self
+    }
+
+    /// Evaluate these views' pipelines as their source views update.
+    ///
+    /// See the [`materialized_view`](crate::materialized_view) module for
+    /// the evaluation budget.
+    pub fn with_materialized_views(mut self, registry: Arc<MaterializedViewRegistry>) -> Self {
+        self.materialized_views = Some(registry);
+        self
+    }
+
+    /// Shed mutations of low-priority entities under pressure.
+    ///
+    /// See the [`load_shed`](crate::load_shed) module.
+    pub fn with_load_shedding(mut self, shedder: LoadShedder) -> Self {
+        self.load_shedder = Some(shedder);
+        self
+    }
+
+    /// Keep the mutations that fail to publish for debug bundles
+    pub fn with_dead_letters(mut self, dead_letters: DeadLetters) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    /// Check updated entities against these webhook rules.
+    ///
+    /// See the [`webhook`](crate::webhook) module.
+    pub fn with_webhooks(mut self, webhooks: Webhooks) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// Encode 64-bit integers in published frames the way `big_numbers`
+    /// says, rather than the default `Auto` mode.
+    ///
+    /// See the [`big_numbers`](crate::big_numbers) module.
+    pub fn with_big_numbers(mut self, big_numbers: BigNumbers) -> Self {
+        self.big_numbers = big_numbers;
+        self
+    }
+
+    /// Offer published frames to `dictionaries` for training.
+    ///
+    /// See the [`dictionary`](crate::dictionary) module.
+    pub fn with_dictionaries(mut self, dictionaries: Dictionaries) -> Self {
+        self.dictionaries = Some(dictionaries);
+        self
+    }
+
+    /// Read `suppress_noop_patches` and `load_shedding` from `flags` as each
+    /// mutation is published.
+    ///
+    /// See the [`flags`](crate::flags) module.
+    pub fn with_flags(mut self, flags: Arc<Flags>) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Record the slot of every mutation processed in `slots`
+    pub fn with_slot_tracker(mut self, slots: SlotTracker) -> Self {
+        self.slots = Some(slots);
+        self
+    }
+
     pub async fn run(mut self) {
         debug!("Projector started");
 
         let mut json_buffer = Vec::with_capacity(4096);
+        let mut release_tick = self
+            .load_shedder
+            .as_ref()
+            .map(|shedder| tokio::time::interval(shedder.config().debounce));
 
-        while let Some(batch) = self.mutations_rx.recv().await {
-            let _span_guard = batch.span.enter();
+        if let Some(window) = self.coalesce.take() {
+            self.run_coalescing(window, &mut release_tick, &mut json_buffer)
+                .await;
+        } else {
+            while let Some(batch) = self.next_batch(&mut release_tick, &mut json_buffer).await {
+                self.process_batch(batch, &mut json_buffer).await;
+                self.release_debounced(false, &mut json_buffer).await;
+                self.release_held(false, &mut json_buffer).await;
+            }
+        }
 
-            let mut log = CanonicalLog::new();
-            log.set("phase", "projector");
+        // Nothing held back is lost when the pipeline stops
+        self.release_debounced(true, &mut json_buffer).await;
+        self.release_held(true, &mut json_buffer).await;
+        debug!("Projector stopped");
+    }
 
-            let batch_size = batch.len();
-            let slot_context = batch.slot_context;
-            let mut frames_published = 0u32;
-            let mut errors = 0u32;
-
-            if let Some(ctx) = batch.event_context.as_ref() {
-                log.set("program", &ctx.program)
-                    .set("event_kind", &ctx.event_kind)
-                    .set("event_type", &ctx.event_type)
-                    .set("account", &ctx.account)
-                    .set("accounts_count", ctx.accounts_count);
+    /// Wait for the next batch, publishing debounced and held patches as
+    /// they come due
+    async fn next_batch(
+        &mut self,
+        release_tick: &mut Option<tokio::time::Interval>,
+        json_buffer: &mut Vec<u8>,
+    ) -> Option<MutationBatch> {
+        loop {
+            let held_due = self.held().next_due();
+            tokio::select! {
+                batch = self.mutations_rx.recv() => return batch,
+                _ = Self::tick(release_tick) => self.release_debounced(false, json_buffer).await,
+                _ = Self::sleep_until(held_due) => self.release_held(false, json_buffer).await,
             }
+        }
+    }
 
-            for mutation in batch.mutations.into_iter() {
-                #[cfg(feature = "otel")]
-                let export = mutation.export.clone();
-
-                match self
-                    .process_mutation(mutation, slot_context, &mut json_buffer)
-                    .await
-                {
-                    Ok(count) => frames_published += count,
-                    Err(e) => {
-                        error!("Failed to process mutation: {}", e);
-                        errors += 1;
+    async fn tick(interval: &mut Option<tokio::time::Interval>) {
+        match interval {
+            Some(interval) => {
+                interval.tick().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    async fn sleep_until(deadline: Option<Instant>) {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Pass mutations through the load shedder, returning those to publish
+    /// now and how many were dropped or held back
+    fn admit(
+        &self,
+        mutations: impl IntoIterator<Item = Mutation>,
+        slot_context: Option<SlotContext>,
+    ) -> (Vec<(Mutation, Option<SlotContext>)>, usize) {
+        let mut admitted = Vec::new();
+        let mut shed = 0;
+        let load_shedder = self
+            .load_shedder
+            .as_ref()
+            .filter(|_| self.flags.enabled(Flag::LoadShedding));
+        for mutation in mutations {
+            match load_shedder {
+                Some(shedder) => {
+                    let feeds_append = self.feeds_append(&mutation.export);
+                    if !shedder.admit(mutation, slot_context, feeds_append, &mut admitted) {
+                        shed += 1;
+                    }
+                }
+                None => admitted.push((mutation, slot_context)),
+            }
+        }
+        (admitted, shed)
+    }
+
+    /// Publish the debounced patches that are due, or all of them
+    async fn release_debounced(&self, all: bool, json_buffer: &mut Vec<u8>) {
+        let Some(shedder) = &self.load_shedder else {
+            return;
+        };
+        let released = if all {
+            shedder.release_all()
+        } else {
+            shedder.release_due(Instant::now())
+        };
+        for (mutation, slot_context) in released {
+            if let Err(e) = self
+                .process_mutation(mutation, slot_context, json_buffer)
+                .await
+            {
+                error!("Failed to process debounced mutation: {}", e);
+            }
+        }
+    }
+
+    fn held(&self) -> MutexGuard<'_, HeldPatches> {
+        self.held.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Publish the patches held by coalescing views that are due, or all of
+    /// them
+    async fn release_held(&self, all: bool, json_buffer: &mut Vec<u8>) {
+        let released = if all {
+            self.held().release_all()
+        } else {
+            self.held().release_due(Instant::now())
+        };
+        if released.is_empty() {
+            return;
+        }
+
+        let views = self.view_index.load();
+        for patch in released {
+            // The view may be gone after a reload
+            let Some(spec) = views.get_view(&patch.view) else {
+                continue;
+            };
+            let key = patch.key.clone();
+            if let Err(e) = self.publish_held(spec, patch, json_buffer).await {
+                error!("Failed to publish coalesced patch: {}", e);
+                if let Some(dead_letters) = &self.dead_letters {
+                    dead_letters.record(spec.export.clone(), key, e);
+                }
+            }
+        }
+    }
+
+    /// Publish a patch a coalescing view held, unless it no longer changes
+    /// the cached entity
+    async fn publish_held(
+        &self,
+        spec: &ViewSpec,
+        held: HeldPatch,
+        json_buffer: &mut Vec<u8>,
+    ) -> anyhow::Result<u32> {
+        if held.append.is_empty() && self.flags.enabled(Flag::SuppressNoopPatches) {
+            if let Some(entity) = self.entity_cache.get(&spec.id, &held.key).await {
+                if Self::is_noop(&held.patch, &entity, true) {
+                    return Ok(0);
+                }
+            }
+        }
+        self.publish_patch(
+            spec,
+            &held.key,
+            held.patch,
+            held.append,
+            held.slot_context,
+            json_buffer,
+        )
+        .await
+    }
+
+    fn feeds_append(&self, export: &str) -> bool {
+        self.view_index
+            .load()
+            .by_export(export)
+            .iter()
+            .any(|spec| spec.mode == Mode::Append)
+    }
+
+    async fn process_batch(&self, batch: MutationBatch, json_buffer: &mut Vec<u8>) {
+        let _span_guard = batch.span.enter();
+
+        let mut log = CanonicalLog::new();
+        log.set("phase", "projector");
+
+        let batch_size = batch.len();
+        let slot_context = batch.slot_context;
+        let mut frames_published = 0u32;
+        let mut errors = 0u32;
+
+        if let Some(ctx) = batch.event_context.as_ref() {
+            log.set("program", &ctx.program)
+                .set("event_kind", &ctx.event_kind)
+                .set("event_type", &ctx.event_type)
+                .set("account", &ctx.account)
+                .set("accounts_count", ctx.accounts_count);
+            if ctx.instructions.len() > 1 {
+                log.set("instructions", ctx.instructions.join(","));
+            }
+        }
+
+        #[cfg(feature = "otel")]
+        if let Some(ref metrics) = self.metrics {
+            for mutation in &batch.mutations {
+                metrics.record_mutation_processed(&mutation.export);
+            }
+        }
+
+        let (admitted, shed) = self.admit(batch.mutations, slot_context);
+        for (mutation, slot_context) in admitted {
+            match self
+                .process_mutation(mutation, slot_context, json_buffer)
+                .await
+            {
+                Ok(count) => frames_published += count,
+                Err(e) => {
+                    error!("Failed to process mutation: {}", e);
+                    errors += 1;
+                }
+            }
+        }
+
+        log.set("batch_size", batch_size)
+            .set("shed", shed)
+            .set("frames_published", frames_published)
+            .set("errors", errors);
+
+        #[cfg(feature = "otel")]
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_projector_latency(log.duration_ms());
+        }
+
+        log.emit();
+    }
+
+    async fn run_coalescing(
+        &mut self,
+        mut window: AdaptiveWindow,
+        release_tick: &mut Option<tokio::time::Interval>,
+        json_buffer: &mut Vec<u8>,
+    ) {
+        #[cfg(feature = "otel")]
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_coalesce_window(window.current());
+        }
+
+        loop {
+            let waiting = Instant::now();
+            let Some(first) = self.next_batch(release_tick, json_buffer).await else {
+                break;
+            };
+            let idle = waiting.elapsed();
+
+            // Batches already queued are taken even when the window is zero
+            let mut batches = vec![first];
+            let mut closed = false;
+            let deadline = tokio::time::Instant::now() + window.current();
+            loop {
+                match tokio::time::timeout_at(deadline, self.mutations_rx.recv()).await {
+                    Ok(Some(batch)) => batches.push(batch),
+                    Ok(None) => {
+                        closed = true;
+                        break;
                     }
+                    Err(_) => break,
                 }
+            }
+
+            let started = Instant::now();
+            self.process_pass(batches, window.current(), json_buffer)
+                .await;
+            self.release_debounced(false, json_buffer).await;
+            self.release_held(false, json_buffer).await;
+            let stats = PassStats {
+                busy: started.elapsed(),
+                idle,
+                queue_pressure: self
+                    .clients
+                    .as_ref()
+                    .map_or(0.0, ClientManager::queue_pressure),
+            };
 
+            let previous = window.current();
+            let next = window.observe(stats);
+            if next != previous {
+                debug!(
+                    "Coalescing window {:?} -> {:?} (utilization {:.2}, queue pressure {:.2})",
+                    previous,
+                    next,
+                    stats.utilization(),
+                    stats.queue_pressure
+                );
                 #[cfg(feature = "otel")]
                 if let Some(ref metrics) = self.metrics {
-                    metrics.record_mutation_processed(&export);
+                    metrics.record_coalesce_window(next);
                 }
             }
 
-            log.set("batch_size", batch_size)
-                .set("frames_published", frames_published)
-                .set("errors", errors);
+            if closed {
+                break;
+            }
+        }
+    }
 
+    /// Merge and publish the mutations of the batches gathered in one window
+    async fn process_pass(
+        &self,
+        batches: Vec<MutationBatch>,
+        window: Duration,
+        json_buffer: &mut Vec<u8>,
+    ) {
+        let mut log = CanonicalLog::new();
+        log.set("phase", "projector");
+
+        let batch_count = batches.len();
+        let mut received = 0usize;
+        let mut shed = 0usize;
+        let mut pending: Vec<(Mutation, Option<SlotContext>)> = Vec::new();
+        // Entity -> index in `pending` of the patch later updates may merge into
+        let mut open: HashMap<(String, String), usize> = HashMap::new();
+
+        for batch in batches {
+            received += batch.len();
             #[cfg(feature = "otel")]
             if let Some(ref metrics) = self.metrics {
-                metrics.record_projector_latency(log.duration_ms());
+                for mutation in &batch.mutations {
+                    metrics.record_mutation_processed(&mutation.export);
+                }
             }
+            let (admitted, batch_shed) = self.admit(batch.mutations, batch.slot_context);
+            shed += batch_shed;
+            for (mutation, slot_context) in admitted {
+                self.coalesce_into(&mut pending, &mut open, mutation, slot_context);
+            }
+        }
 
-            log.emit();
+        let published = pending.len();
+        let mut frames_published = 0u32;
+        let mut errors = 0u32;
+        for (mutation, slot_context) in pending {
+            match self
+                .process_mutation(mutation, slot_context, json_buffer)
+                .await
+            {
+                Ok(count) => frames_published += count,
+                Err(e) => {
+                    error!("Failed to process mutation: {}", e);
+                    errors += 1;
+                }
+            }
         }
 
-        debug!("Projector stopped");
+        log.set("batches", batch_count)
+            .set("batch_size", received)
+            .set("shed", shed)
+            .set("coalesced", (received - shed).saturating_sub(published))
+            .set("window_ms", window.as_secs_f64() * 1000.0)
+            .set("frames_published", frames_published)
+            .set("errors", errors);
+
+        #[cfg(feature = "otel")]
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_projector_latency(log.duration_ms());
+        }
+
+        log.emit();
+    }
+
+    /// Queue `mutation`, merging it into an earlier patch to the same entity
+    /// when the result is indistinguishable for subscribers.
+    ///
+    /// Append views see every item, and patches with append paths cannot be
+    /// folded without changing what the client appends, so those are queued
+    /// as they are and close the entity to further merging, as do tombstones.
+    fn coalesce_into(
+        &self,
+        pending: &mut Vec<(Mutation, Option<SlotContext>)>,
+        open: &mut HashMap<(String, String), usize>,
+        mutation: Mutation,
+        slot_context: Option<SlotContext>,
+    ) {
+        let entity = (mutation.export.clone(), Self::extract_key(&mutation.key));
+        let mergeable = mutation.append.is_empty()
+            && !mutation.tombstone
+            && !self.feeds_append(&mutation.export);
+
+        if !mergeable {
+            open.remove(&entity);
+            pending.push((mutation, slot_context));
+            return;
+        }
+
+        if let Some(&index) = open.get(&entity) {
+            let (merged, merged_slot) = &mut pending[index];
+            merge_patch(&mut merged.patch, &mutation.patch, &[]);
+            *merged_slot = slot_context.or(*merged_slot);
+            return;
+        }
+
+        open.insert(entity, pending.len());
+        pending.push((mutation, slot_context));
     }
 
     #[instrument(
@@ -127,15 +590,25 @@ impl Projector {
         slot_context: Option<SlotContext>,
         json_buffer: &mut Vec<u8>,
     ) -> anyhow::Result<u32> {
-        let specs = self.view_index.by_export(&mutation.export);
+        if let (Some(slots), Some(ctx)) = (&self.slots, slot_context) {
+            slots.record(ctx.slot);
+        }
+
+        let views = self.view_index.load();
+        let specs = views.by_export(&mutation.export);
 
         if specs.is_empty() {
             return Ok(0);
         }
 
         let key = Self::extract_key(&mutation.key);
+        // Provenance is for operators and never reaches client frames
         let hyperstack_interpreter::Mutation {
-            mut patch, append, ..
+            export,
+            mut patch,
+            append,
+            tombstone,
+            ..
         } = mutation;
 
         // Inject _seq for recency sorting if slot context is available
@@ -155,65 +628,271 @@ impl Projector {
             return Ok(0);
         }
 
+        if tombstone {
+            let published = self
+                .publish_tombstone(&matching_specs, &key, slot_context, json_buffer)
+                .await;
+            if let (Err(e), Some(dead_letters)) = (&published, &self.dead_letters) {
+                dead_letters.record(export, key, e);
+            }
+            return published;
+        }
+
+        let suppress_noop = append.is_empty() && self.flags.enabled(Flag::SuppressNoopPatches);
+        let published: anyhow::Result<u32> = async {
+            let mut frames_published = 0u32;
+
+            for (i, spec) in matching_specs.into_iter().enumerate() {
+                let is_last = i == match_count - 1;
+                let patch_data = if is_last {
+                    std::mem::take(&mut patch)
+                } else {
+                    patch.clone()
+                };
+
+                let mut projected = spec.projection.apply(patch_data);
+                self.big_numbers.encode(&spec.export, &mut projected);
+
+                // Held patches are checked for no-ops once released, against
+                // the entity as it is then
+                let coalesce = spec
+                    .delivery
+                    .coalesce_window()
+                    .filter(|_| spec.mode != Mode::Append);
+                if let Some(window) = coalesce {
+                    let held =
+                        HeldPatch::new(&spec.id, &key, projected, append.clone(), slot_context);
+                    let released = self.held().hold(held, Instant::now() + window);
+                    if let Some(released) = released {
+                        frames_published += self.publish_held(spec, released, json_buffer).await?;
+                    }
+                    continue;
+                }
+
+                if suppress_noop {
+                    if let Some(entity) = self.entity_cache.get(&spec.id, &key).await {
+                        if Self::is_noop(&projected, &entity, true) {
+                            continue;
+                        }
+                    }
+                }
+
+                frames_published += self
+                    .publish_patch(
+                        spec,
+                        &key,
+                        projected,
+                        append.clone(),
+                        slot_context,
+                        json_buffer,
+                    )
+                    .await?;
+            }
+
+            Ok(frames_published)
+        }
+        .await;
+
+        if let (Err(e), Some(dead_letters)) = (&published, &self.dead_letters) {
+            dead_letters.record(export, key, e);
+        }
+        published
+    }
+
+    /// Publish a projected patch to one view: update its cache and derived
+    /// views, then send the frame to its subscribers
+    async fn publish_patch(
+        &self,
+        spec: &ViewSpec,
+        key: &str,
+        data: Value,
+        append: Vec<String>,
+        slot_context: Option<SlotContext>,
+        json_buffer: &mut Vec<u8>,
+    ) -> anyhow::Result<u32> {
         let mut frames_published = 0u32;
 
-        for (i, spec) in matching_specs.into_iter().enumerate() {
-            let is_last = i == match_count - 1;
-            let patch_data = if is_last {
-                std::mem::take(&mut patch)
-            } else {
-                patch.clone()
-            };
+        // Extract _seq from the patch data to include in the frame
+        let seq = slot_context.map(|ctx| ctx.to_seq_string());
+
+        let frame = Frame {
+            mode: spec.mode,
+            export: spec.id.clone(),
+            op: "patch",
+            key: key.to_string(),
+            data,
+            append,
+            seq,
+        };
 
-            let mut projected = spec.projection.apply(patch_data);
-            transform_large_u64_to_strings(&mut projected);
+        json_buffer.clear();
+        serde_json::to_writer(&mut *json_buffer, &frame)?;
+        let payload = Arc::new(Bytes::copy_from_slice(json_buffer));
+        if let Some(dictionaries) = &self.dictionaries {
+            dictionaries.sample(&payload);
+        }
 
-            // Extract _seq from the patch data to include in the frame
-            let seq = slot_context.map(|ctx| ctx.to_seq_string());
+        let retention = self
+            .entity_cache
+            .upsert_with_append(&spec.id, key, frame.data.clone(), &frame.append)
+            .await;
 
-            let frame = Frame {
-                mode: spec.mode,
-                export: spec.id.clone(),
-                op: "patch",
-                key: key.clone(),
-                data: projected,
-                append: append.clone(),
-                seq,
+        if spec.mode == Mode::List {
+            self.update_derived_view_caches(&spec.id, key).await;
+        }
+
+        if let Some(webhooks) = self.webhooks.as_ref().filter(|w| w.watches(&spec.id)) {
+            if let Some(entity) = self.entity_cache.get(&spec.id, key).await {
+                webhooks.observe(&spec.id, key, &entity);
+            }
+        }
+
+        let full_state = if spec.mode != Mode::Append && self.bus_manager.wants_full_state(&spec.id)
+        {
+            self.full_state_frame(spec, key, frame.seq.clone(), json_buffer)
+                .await?
+        } else {
+            None
+        };
+
+        let message = Arc::new(
+            BusMessage::new(key.to_string(), spec.id.clone(), payload)
+                .with_full_state(full_state)
+                .with_seq(frame.seq.as_deref()),
+        );
+
+        if spec.mode == Mode::Append && spec.delivery.history > 0 {
+            let item = HistoryItem {
+                key: frame.key,
+                data: frame.data,
+                append: frame.append,
+                seq: frame.seq,
             };
+            let appended = self
+                .bus_manager
+                .publish_list_with_history(
+                    &spec.id,
+                    message,
+                    &item,
+                    LogLimits::from_delivery(&spec.delivery),
+                )
+                .await?;
 
-            json_buffer.clear();
-            serde_json::to_writer(&mut *json_buffer, &frame)?;
-            let payload = Arc::new(Bytes::copy_from_slice(json_buffer));
+            #[cfg(feature = "otel")]
+            if let Some(ref metrics) = self.metrics {
+                metrics.record_append_log(&spec.id, &appended);
+            }
+            #[cfg(not(feature = "otel"))]
+            let _ = appended;
+        } else {
+            self.publish_frame(spec, message).await;
+        }
+        frames_published += 1;
 
-            self.entity_cache
-                .upsert_with_append(&spec.id, &key, frame.data.clone(), &frame.append)
-                .await;
+        if let (Some(notice), Mode::List) = (retention, spec.mode) {
+            self.publish_retention_notice(spec, &notice, json_buffer)
+                .await?;
+            frames_published += 1;
+        }
 
+        #[cfg(feature = "otel")]
+        if let Some(ref metrics) = self.metrics {
+            let mode_str = match spec.mode {
+                Mode::List => "list",
+                Mode::State => "state",
+                Mode::Append => "append",
+            };
+            metrics.record_frame_published(mode_str, &spec.export);
+        }
+
+        Ok(frames_published)
+    }
+
+    /// Drop a deleted entity from the caches of `specs` and send their
+    /// subscribers a delete frame. Append views keep the entity's items.
+    async fn publish_tombstone(
+        &self,
+        specs: &[&ViewSpec],
+        key: &str,
+        slot_context: Option<SlotContext>,
+        json_buffer: &mut Vec<u8>,
+    ) -> anyhow::Result<u32> {
+        let mut frames_published = 0u32;
+        for spec in specs.iter().filter(|spec| spec.mode != Mode::Append) {
+            // A coalesced patch held for the entity would bring it back
+            self.held().discard(&spec.id, key);
+            self.entity_cache.remove(&spec.id, key).await;
             if spec.mode == Mode::List {
-                self.update_derived_view_caches(&spec.id, &key).await;
+                self.remove_from_derived_views(&spec.id, key).await;
             }
 
-            let message = Arc::new(BusMessage {
-                key: key.clone(),
-                entity: spec.id.clone(),
-                payload,
-            });
-
+            let frame = Frame {
+                mode: spec.mode,
+                export: spec.id.clone(),
+                op: "delete",
+                key: key.to_string(),
+                data: Value::Null,
+                append: vec![],
+                seq: slot_context.map(|ctx| ctx.to_seq_string()),
+            };
+            json_buffer.clear();
+            serde_json::to_writer(&mut *json_buffer, &frame)?;
+            let message = Arc::new(
+                BusMessage::new(
+                    key.to_string(),
+                    spec.id.clone(),
+                    Arc::new(Bytes::copy_from_slice(json_buffer)),
+                )
+                .with_seq(frame.seq.as_deref()),
+            );
             self.publish_frame(spec, message).await;
             frames_published += 1;
+        }
+        Ok(frames_published)
+    }
 
-            #[cfg(feature = "otel")]
-            if let Some(ref metrics) = self.metrics {
-                let mode_str = match spec.mode {
-                    Mode::List => "list",
-                    Mode::State => "state",
-                    Mode::Append => "append",
-                };
-                metrics.record_frame_published(mode_str, &spec.export);
+    /// Whether merging `patch` into `entity` would leave it unchanged. The
+    /// `_seq` of a top-level patch only orders updates and is ignored.
+    fn is_noop(patch: &Value, entity: &Value, top_level: bool) -> bool {
+        match (patch, entity) {
+            (Value::Object(patch), Value::Object(entity)) => {
+                patch.iter().all(|(field, value)| {
+                    (top_level && field == "_seq")
+                        || entity
+                            .get(field)
+                            .is_some_and(|current| Self::is_noop(value, current, false))
+                })
             }
+            _ => patch == entity,
         }
+    }
 
-        Ok(frames_published)
+    /// Serialize the entity at `key` as cached after the latest patch, for
+    /// subscriptions that replace their copy instead of merging patches
+    async fn full_state_frame(
+        &self,
+        spec: &ViewSpec,
+        key: &str,
+        seq: Option<String>,
+        json_buffer: &mut Vec<u8>,
+    ) -> anyhow::Result<Option<Arc<Bytes>>> {
+        let Some(mut data) = self.entity_cache.get(&spec.id, key).await else {
+            return Ok(None);
+        };
+        self.big_numbers.encode(&spec.export, &mut data);
+
+        let frame = Frame {
+            mode: spec.mode,
+            export: spec.id.clone(),
+            op: "upsert",
+            key: key.to_string(),
+            data,
+            append: vec![],
+            seq,
+        };
+        json_buffer.clear();
+        serde_json::to_writer(&mut *json_buffer, &frame)?;
+        Ok(Some(Arc::new(Bytes::copy_from_slice(json_buffer))))
     }
 
     fn extract_key(key: &serde_json::Value) -> String {
@@ -238,7 +917,8 @@ impl Projector {
     }
 
     async fn update_derived_view_caches(&self, source_view_id: &str, entity_key: &str) {
-        let derived_views = self.view_index.get_derived_views_for_source(source_view_id);
+        let views = self.view_index.load();
+        let derived_views = views.get_derived_views_for_source(source_view_id);
         if derived_views.is_empty() {
             return;
         }
@@ -248,10 +928,29 @@ impl Projector {
             None => return,
         };
 
-        let sorted_caches = self.view_index.sorted_caches();
+        if let Some(ref registry) = self.materialized_views {
+            for derived_spec in &derived_views {
+                let maintained = registry
+                    .maintain(&derived_spec.id, entity_key, Some(&entity_data))
+                    .await;
+
+                #[cfg(feature = "otel")]
+                if let (Some(ref metrics), Some(Ok(maintained))) = (&self.metrics, &maintained) {
+                    metrics.record_view_evaluation(&derived_spec.id, maintained);
+                }
+                #[cfg(not(feature = "otel"))]
+                let _ = maintained;
+            }
+        }
+
+        let sorted_caches = views.sorted_caches();
         let mut caches = sorted_caches.write().await;
 
+        let quarantined = views.quarantined_views();
         for derived_spec in derived_views {
+            if quarantined.contains(&derived_spec.id) {
+                continue;
+            }
             if let Some(cache) = caches.get_mut(&derived_spec.id) {
                 cache.upsert(entity_key.to_string(), entity_data.clone());
                 debug!(
@@ -262,6 +961,55 @@ impl Projector {
         }
     }
 
+    /// Remove a deleted entity from the views derived from `source_view_id`.
+    /// Their subscriptions send the delete when the source frame arrives.
+    async fn remove_from_derived_views(&self, source_view_id: &str, entity_key: &str) {
+        let views = self.view_index.load();
+        let derived_views = views.get_derived_views_for_source(source_view_id);
+        if derived_views.is_empty() {
+            return;
+        }
+
+        if let Some(ref registry) = self.materialized_views {
+            for derived_spec in &derived_views {
+                registry.maintain(&derived_spec.id, entity_key, None).await;
+            }
+        }
+
+        let sorted_caches = views.sorted_caches();
+        let mut caches = sorted_caches.write().await;
+        for derived_spec in derived_views {
+            if let Some(cache) = caches.get_mut(&derived_spec.id) {
+                cache.remove(entity_key);
+            }
+        }
+    }
+
+    async fn publish_retention_notice(
+        &self,
+        spec: &ViewSpec,
+        notice: &RetentionNotice,
+        json_buffer: &mut Vec<u8>,
+    ) -> anyhow::Result<()> {
+        debug!(
+            "View {} is evicting entities (cap {}, {:.2}/s)",
+            spec.id, notice.cap, notice.eviction_rate
+        );
+
+        let frame = Frame::retention_notice(spec.mode, &spec.id, notice);
+        json_buffer.clear();
+        serde_json::to_writer(&mut *json_buffer, &frame)?;
+
+        // An empty key reaches every whole-view subscriber but no keyed ones
+        let message = Arc::new(BusMessage::new(
+            String::new(),
+            spec.id.clone(),
+            Arc::new(Bytes::copy_from_slice(json_buffer)),
+        ));
+        self.publish_frame(spec, message).await;
+        Ok(())
+    }
+
     #[instrument(
         name = "projector.publish",
         skip(self, spec, message),
@@ -270,9 +1018,7 @@ impl Projector {
     async fn publish_frame(&self, spec: &ViewSpec, message: Arc<BusMessage>) {
         match spec.mode {
             Mode::State => {
-                self.bus_manager
-                    .publish_state(&spec.id, &message.key, message.payload.clone())
-                    .await;
+                self.bus_manager.publish_state(&spec.id, message).await;
             }
             Mode::List | Mode::Append => {
                 self.bus_manager.publish_list(&spec.id, message).await;
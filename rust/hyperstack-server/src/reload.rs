@@ -0,0 +1,623 @@
+//! Replacing the spec of a running server without dropping its connections.
+//!
+//! [`SpecReloader::reload_spec`], on a handle taken with
+//! [`Runtime::reload_handle`](crate::Runtime::reload_handle), does the
+//! following:
+//!
+//! - Entities of the new spec are checked against the live caches. An
+//!   entity with cached entities must keep its VM state id, so the state
+//!   built up under the old bytecode stays reachable; otherwise the reload
+//!   is refused with [`ReloadError::SchemaEvolution`] and nothing changes.
+//! - The new bytecode is stored in the spec's [`LiveBytecode`]. VM handlers
+//!   load it once per event, so an event in flight finishes on the bytecode
+//!   it started with and the next one runs on the new bytecode.
+//! - Views are registered again. Views new to the spec are served from then
+//!   on. Subscribers of a view the spec dropped receive a
+//!   [`ReloadNotice::ViewRemoved`] and lose the subscription, and the view's
+//!   caches are cleared.
+//! - Subscribers of the views of an entity whose handlers or fields changed
+//!   receive a [`ReloadNotice::SchemaChanged`] carrying its new schema hash.
+//!
+//! Connections stay open throughout. A spec subscribing to other program
+//! ids restarts the parser, which reconnects its stream the way it does
+//! after losing it and starts from fresh VM state.
+//!
+//! Views given to [`ServerBuilder::views`](crate::ServerBuilder::views) are
+//! kept as they are, and view budgets only apply to the derived views of the
+//! spec the server started with.
+
+use crate::big_numbers::BigNumbers;
+use crate::cache::EntityCache;
+use crate::config::ReconnectionConfig;
+use crate::router::ParserTask;
+use crate::sorted_cache::SortedViewCache;
+use crate::view::{ViewIndex, ViewSpec};
+use crate::websocket::client_manager::ClientManager;
+use crate::Spec;
+use hyperstack_interpreter::clock::SharedClock;
+use hyperstack_interpreter::compiler::{EntityBytecode, MultiEntityBytecode};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// A value readers load a snapshot of, replaced as a whole on reload.
+///
+/// Loading hands out an `Arc` to the current value, which stays valid for as
+/// long as the reader holds it even if a reload replaces the value meanwhile.
+pub struct Live<T>(Arc<RwLock<Arc<T>>>);
+
+/// Bytecode shared between a spec's VM handlers and the reloader
+pub type LiveBytecode = Live<MultiEntityBytecode>;
+
+/// View index shared between the projector, the WebSocket server and the
+/// reloader
+pub type LiveViews = Live<ViewIndex>;
+
+impl<T> Live<T> {
+    pub fn new(value: T) -> Self {
+        Self::from(Arc::new(value))
+    }
+
+    /// The current value
+    pub fn load(&self) -> Arc<T> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub(crate) fn store(&self, value: Arc<T>) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = value;
+    }
+}
+
+impl<T> Clone for Live<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> From<Arc<T>> for Live<T> {
+    fn from(value: Arc<T>) -> Self {
+        Self(Arc::new(RwLock::new(value)))
+    }
+}
+
+impl From<ViewIndex> for LiveViews {
+    fn from(views: ViewIndex) -> Self {
+        Self::new(views)
+    }
+}
+
+/// Server-sent notice about a view affected by a reload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReloadNotice {
+    /// The view is no longer served and the client's subscriptions to it
+    /// were ended
+    ViewRemoved { view: String },
+    /// The entity behind the view changed; frames from now on follow the
+    /// schema with this hash
+    SchemaChanged {
+        view: String,
+        entity: String,
+        schema_hash: String,
+    },
+}
+
+/// What a reload changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ReloadReport {
+    pub added_views: Vec<String>,
+    pub removed_views: Vec<String>,
+    /// Entities whose schema hash changed
+    pub schema_changes: Vec<String>,
+    /// The program ids changed and the parser was restarted
+    pub parser_restarted: bool,
+}
+
+/// Why a reload was refused. The running spec is left untouched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReloadError {
+    /// The server the reloader belongs to has not been started
+    NotStarted,
+    /// The new spec can't take over the live state of `entity`
+    SchemaEvolution { entity: String, reason: String },
+    /// The program ids changed but the new spec has no parser setup
+    MissingParser,
+}
+
+impl std::fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReloadError::NotStarted => write!(f, "server not started"),
+            ReloadError::SchemaEvolution { entity, reason } => {
+                write!(f, "cannot reload entity {}: {}", entity, reason)
+            }
+            ReloadError::MissingParser => {
+                write!(f, "program ids changed but the spec has no parser setup")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EntitySchema {
+    state_id: u32,
+    hash: String,
+}
+
+/// What the reloader knows about the spec being served
+#[derive(Clone, Default)]
+struct Loaded {
+    entities: BTreeMap<String, EntitySchema>,
+    program_ids: Vec<String>,
+    /// IDs of the views derived from the spec
+    views: BTreeSet<String>,
+    bytecode: Option<LiveBytecode>,
+}
+
+impl Loaded {
+    fn from_spec(spec: &Spec) -> Self {
+        Self {
+            entities: entity_schemas(&spec.bytecode),
+            program_ids: spec.program_ids.clone(),
+            views: crate::spec_views(spec)
+                .into_iter()
+                .map(|view| view.id)
+                .collect(),
+            bytecode: spec.live_bytecode.clone(),
+        }
+    }
+}
+
+/// Handles of the running server a reload acts on
+pub(crate) struct Attached {
+    pub(crate) entity_cache: EntityCache,
+    pub(crate) clients: Option<ClientManager>,
+    pub(crate) parsers: mpsc::UnboundedSender<ParserTask>,
+    pub(crate) reconnection_config: ReconnectionConfig,
+    pub(crate) big_numbers: BigNumbers,
+    pub(crate) clock: SharedClock,
+}
+
+struct Inner {
+    views: LiveViews,
+    loaded: Mutex<Loaded>,
+    attached: OnceLock<Attached>,
+    /// Held for the duration of a reload, so reloads apply one at a time
+    reloading: tokio::sync::Mutex<()>,
+}
+
+/// Handle for replacing the spec of a running server.
+///
+/// See the [module docs](self) for what a reload does.
+#[derive(Clone)]
+pub struct SpecReloader {
+    inner: Arc<Inner>,
+}
+
+impl SpecReloader {
+    pub(crate) fn new(views: LiveViews) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                views,
+                loaded: Mutex::new(Loaded::default()),
+                attached: OnceLock::new(),
+                reloading: tokio::sync::Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Track `spec` as the spec being served
+    pub(crate) fn track(&self, spec: &Spec) {
+        *self.inner.loaded.lock().unwrap_or_else(|e| e.into_inner()) = Loaded::from_spec(spec);
+    }
+
+    pub(crate) fn attach(&self, attached: Attached) {
+        if self.inner.attached.set(attached).is_err() {
+            warn!("Spec reloader attached twice - keeping the first server");
+        }
+    }
+
+    /// Serve `spec` in place of the current spec.
+    pub async fn reload_spec(&self, spec: Spec) -> Result<ReloadReport, ReloadError> {
+        let _reloading = self.inner.reloading.lock().await;
+        let attached = self.inner.attached.get().ok_or(ReloadError::NotStarted)?;
+        let loaded = self
+            .inner
+            .loaded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        let entities = entity_schemas(&spec.bytecode);
+        check_evolution(&loaded.entities, &entities, &attached.entity_cache).await?;
+
+        let parser_restarted = spec.program_ids != loaded.program_ids;
+        let parser = if parser_restarted {
+            Some(
+                ParserTask::for_spec(
+                    &spec,
+                    attached.reconnection_config.clone(),
+                    attached.clock.clone(),
+                )
+                .ok_or(ReloadError::MissingParser)?,
+            )
+        } else {
+            None
+        };
+
+        let views = crate::spec_views(&spec);
+        let mut report = ReloadReport {
+            added_views: views
+                .iter()
+                .filter(|view| !loaded.views.contains(&view.id))
+                .map(|view| view.id.clone())
+                .collect(),
+            removed_views: loaded
+                .views
+                .iter()
+                .filter(|id| !views.iter().any(|view| &view.id == *id))
+                .cloned()
+                .collect(),
+            schema_changes: entities
+                .iter()
+                .filter(|(name, schema)| {
+                    loaded
+                        .entities
+                        .get(*name)
+                        .is_some_and(|old| old.hash != schema.hash)
+                })
+                .map(|(name, _)| name.clone())
+                .collect(),
+            parser_restarted,
+        };
+
+        attached.big_numbers.track(&spec.bytecode);
+        let bytecode = match (parser, &loaded.bytecode) {
+            (Some(parser), _) => {
+                // The new parser reads its own spec's bytecode
+                let _ = attached.parsers.send(parser);
+                spec.live_bytecode.clone()
+            }
+            (None, Some(live)) => {
+                live.store(Arc::new(spec.bytecode));
+                Some(live.clone())
+            }
+            (None, None) => None,
+        };
+
+        self.swap_views(&loaded, &views, &report, &attached.entity_cache)
+            .await;
+
+        *self.inner.loaded.lock().unwrap_or_else(|e| e.into_inner()) = Loaded {
+            entities: entities.clone(),
+            program_ids: spec.program_ids,
+            views: views.iter().map(|view| view.id.clone()).collect(),
+            bytecode,
+        };
+
+        if let Some(clients) = &attached.clients {
+            for view in &report.removed_views {
+                notify_removed(clients, view).await;
+            }
+            for view in &views {
+                if report.schema_changes.contains(&view.export) {
+                    let notice = ReloadNotice::SchemaChanged {
+                        view: view.id.clone(),
+                        entity: view.export.clone(),
+                        schema_hash: entities[&view.export].hash.clone(),
+                    };
+                    for (client_id, _) in clients.view_subscriptions(&view.id).await {
+                        send_notice(clients, client_id, &notice).await;
+                    }
+                }
+            }
+        }
+
+        report.added_views.sort();
+        info!(
+            added = report.added_views.len(),
+            removed = report.removed_views.len(),
+            schema_changes = report.schema_changes.len(),
+            parser_restarted = report.parser_restarted,
+            "Spec reloaded"
+        );
+        Ok(report)
+    }
+
+    /// Register `views` in place of the views of the loaded spec
+    async fn swap_views(
+        &self,
+        loaded: &Loaded,
+        views: &[ViewSpec],
+        report: &ReloadReport,
+        entity_cache: &EntityCache,
+    ) {
+        let current = self.inner.views.load();
+        let mut index = current.sharing_caches();
+        for view in current.views() {
+            if !loaded.views.contains(&view.id) {
+                index.insert_spec(view.clone());
+            }
+        }
+        for view in views {
+            index.insert_spec(view.clone());
+        }
+
+        // The projector waits on the sorted caches to update derived views,
+        // so none of its updates land between filling a new cache and
+        // serving it
+        let sorted_caches = current.sorted_caches();
+        let mut caches = sorted_caches.write().await;
+        for view in views {
+            if !report.added_views.contains(&view.id) {
+                continue;
+            }
+            let (Some(sort), Some(source)) = (
+                view.pipeline.as_ref().and_then(|p| p.sort.as_ref()),
+                view.source_view.as_ref(),
+            ) else {
+                continue;
+            };
+            let mut cache =
+                SortedViewCache::new(view.id.clone(), sort.field_path.clone(), sort.order.into());
+            for (key, entity) in entity_cache.get_all(source).await {
+                cache.upsert(key, entity);
+            }
+            caches.insert(view.id.clone(), cache);
+        }
+        for view in &report.removed_views {
+            caches.remove(view);
+        }
+        self.inner.views.store(Arc::new(index));
+        drop(caches);
+
+        for view in &report.removed_views {
+            entity_cache.clear(view).await;
+        }
+    }
+}
+
+/// Schema of each entity of `bytecode`, by name
+fn entity_schemas(bytecode: &MultiEntityBytecode) -> BTreeMap<String, EntitySchema> {
+    bytecode
+        .entities
+        .iter()
+        .map(|(name, entity)| {
+            let schema = EntitySchema {
+                state_id: entity.state_id,
+                hash: schema_hash(entity),
+            };
+            (name.clone(), schema)
+        })
+        .collect()
+}
+
+/// Hash of the handlers and fields of `entity`.
+///
+/// Computed fields are evaluated by a closure, so a change to a computed
+/// expression alone keeps the hash.
+fn schema_hash(entity: &EntityBytecode) -> String {
+    let handlers: BTreeMap<_, _> = entity.handlers.iter().collect();
+    let non_emitted: BTreeSet<_> = entity.non_emitted_fields.iter().collect();
+
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", handlers).hash(&mut hasher);
+    entity.computed_paths.hash(&mut hasher);
+    non_emitted.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Hash of the schemas of all `entities`, which versions artifacts derived
+/// from a spec's frames such as its compression dictionary
+pub(crate) fn stack_schema_hash(entities: &HashMap<String, EntityBytecode>) -> String {
+    let schemas: BTreeMap<_, _> = entities
+        .iter()
+        .map(|(name, entity)| (name, schema_hash(entity)))
+        .collect();
+
+    let mut hasher = DefaultHasher::new();
+    schemas.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+async fn cached_entities(entity_cache: &EntityCache, entity: &str) -> usize {
+    let mut cached = 0;
+    for mode in ["list", "state", "append"] {
+        cached += entity_cache.len(&format!("{}/{}", entity, mode)).await;
+    }
+    cached
+}
+
+/// Refuse to move the VM state of an entity with cached entities
+async fn check_evolution(
+    old: &BTreeMap<String, EntitySchema>,
+    new: &BTreeMap<String, EntitySchema>,
+    entity_cache: &EntityCache,
+) -> Result<(), ReloadError> {
+    for (name, old_schema) in old {
+        let cached = cached_entities(entity_cache, name).await;
+        if cached == 0 {
+            continue;
+        }
+
+        if let Some(new_schema) = new.get(name) {
+            if new_schema.state_id != old_schema.state_id {
+                return Err(ReloadError::SchemaEvolution {
+                    entity: name.clone(),
+                    reason: format!(
+                        "state id changed from {} to {} with {} entities cached",
+                        old_schema.state_id, new_schema.state_id, cached
+                    ),
+                });
+            }
+        }
+
+        if let Some((other, _)) = new
+            .iter()
+            .find(|(other, schema)| *other != name && schema.state_id == old_schema.state_id)
+        {
+            return Err(ReloadError::SchemaEvolution {
+                entity: other.clone(),
+                reason: format!(
+                    "reuses state id {} of {}, which has {} entities cached",
+                    old_schema.state_id, name, cached
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// End the subscriptions to `view` and tell their clients why
+async fn notify_removed(clients: &ClientManager, view: &str) {
+    let notice = ReloadNotice::ViewRemoved {
+        view: view.to_string(),
+    };
+    for (client_id, sub_keys) in clients.view_subscriptions(view).await {
+        for sub_key in sub_keys {
+            clients
+                .remove_client_subscription(client_id, &sub_key)
+                .await;
+        }
+        send_notice(clients, client_id, &notice).await;
+    }
+}
+
+async fn send_notice(clients: &ClientManager, client_id: Uuid, notice: &ReloadNotice) {
+    match serde_json::to_string(notice) {
+        Ok(json) => {
+            let _ = clients.send_text_to_client(client_id, json).await;
+        }
+        Err(error) => {
+            warn!(error = %error, client_id = %client_id, "failed to serialize reload notice");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperstack_interpreter::compiler::OpCode;
+    use std::collections::HashSet;
+
+    fn entity(state_id: u32, handlers: Vec<(&str, Vec<OpCode>)>) -> EntityBytecode {
+        EntityBytecode {
+            state_id,
+            handlers: handlers
+                .into_iter()
+                .map(|(event, ops)| (event.to_string(), ops))
+                .collect(),
+            entity_name: "Round".to_string(),
+            when_events: HashSet::new(),
+            non_emitted_fields: HashSet::new(),
+            wide_integer_fields: HashSet::new(),
+            computed_paths: vec![],
+            trace_fields: vec![],
+            priority: Default::default(),
+            delete_on: None,
+            event_schemas: Default::default(),
+            computed_fields_evaluator: None,
+        }
+    }
+
+    #[test]
+    fn schema_hash_ignores_handler_order() {
+        let a = entity(
+            0,
+            vec![
+                (
+                    "A",
+                    vec![OpCode::AbortIfNullKey {
+                        key: 0,
+                        is_account_event: true,
+                    }],
+                ),
+                ("B", vec![]),
+            ],
+        );
+        let b = entity(
+            0,
+            vec![
+                ("B", vec![]),
+                (
+                    "A",
+                    vec![OpCode::AbortIfNullKey {
+                        key: 0,
+                        is_account_event: true,
+                    }],
+                ),
+            ],
+        );
+        assert_eq!(schema_hash(&a), schema_hash(&b));
+
+        let c = entity(0, vec![("A", vec![]), ("B", vec![])]);
+        assert_ne!(schema_hash(&a), schema_hash(&c));
+    }
+
+    #[tokio::test]
+    async fn cached_entities_pin_their_state_id() {
+        let old = BTreeMap::from([
+            (
+                "Round".to_string(),
+                EntitySchema {
+                    state_id: 0,
+                    hash: "a".to_string(),
+                },
+            ),
+            (
+                "Miner".to_string(),
+                EntitySchema {
+                    state_id: 1,
+                    hash: "b".to_string(),
+                },
+            ),
+        ]);
+        let moved = BTreeMap::from([(
+            "Round".to_string(),
+            EntitySchema {
+                state_id: 2,
+                hash: "a".to_string(),
+            },
+        )]);
+        let cache = EntityCache::new();
+
+        // Nothing cached, nothing to keep
+        assert!(check_evolution(&old, &moved, &cache).await.is_ok());
+
+        cache
+            .upsert("Round/list", "1", serde_json::json!({ "id": 1 }))
+            .await;
+        let error = check_evolution(&old, &moved, &cache).await.unwrap_err();
+        assert!(matches!(
+            error,
+            ReloadError::SchemaEvolution { ref entity, .. } if entity == "Round"
+        ));
+
+        let reused = BTreeMap::from([(
+            "Pool".to_string(),
+            EntitySchema {
+                state_id: 0,
+                hash: "c".to_string(),
+            },
+        )]);
+        let error = check_evolution(&old, &reused, &cache).await.unwrap_err();
+        assert!(error.to_string().contains("Pool"));
+        assert!(error.to_string().contains("reuses state id 0 of Round"));
+    }
+
+    #[test]
+    fn loads_outlive_stores() {
+        let live = Live::new(1);
+        let before = live.load();
+        live.store(Arc::new(2));
+        assert_eq!(*before, 1);
+        assert_eq!(*live.load(), 2);
+    }
+}
@@ -0,0 +1,560 @@
+//! Embeddable axum router for serving HyperStack from an existing application.
+//!
+//! [`Runtime::into_router`](crate::Runtime::into_router) and
+//! [`Runtime::into_router_parts`](crate::Runtime::into_router_parts) produce a
+//! [`Router`] that the caller mounts and serves on its own listener, instead of
+//! the runtime binding ports itself.
+//!
+//! ## Routes
+//!
+//! - `/` - WebSocket upgrade endpoint (same protocol as the standalone server)
+//! - `/sub/<view>` - WebSocket upgrade straight into one view's frames (see
+//!   [`Subscription::from_path`](crate::websocket::Subscription::from_path))
+//! - `/health`, `/healthz`, `/ready`, `/readiness`, `/readyz`, `/status` - health endpoints
+//! - `/dictionaries/<id>` - zstd compression dictionary (only with a
+//!   dictionary source, see the [`dictionary`](crate::dictionary) module)
+//! - `/stats` - JSON snapshot of connected clients, the last processed slot,
+//!   data frames sent, buses, cache sizes,
+//!   append log retention, derived view evaluation cost, quarantined views,
+//!   load shedding, oversized frames, webhook deliveries, feature flags and the snapshot
+//!   queue (see the [`snapshot_queue`](crate::websocket::snapshot_queue)
+//!   module)
+//! - `/admin/shadow` - shadow deployment diff report (only with a shadow spec)
+//! - `/admin/drain` - `POST ?grace=120s` starts draining connections, `GET`
+//!   reports progress (see the [`drain`](crate::drain) module)
+//! - `/admin/debug-bundle` - `POST` starts assembling a redacted `tar.gz` of
+//!   the server state, `GET` reports progress and
+//!   `/admin/debug-bundle/download` serves it (see the
+//!   [`debug_bundle`](crate::debug_bundle) module)
+//! - `/admin/flags` - `GET` lists the feature flags, `PATCH` with an admin
+//!   token changes them (see the [`flags`](crate::flags) module)
+//! - `/admin/failed-events` - `GET` lists the events the VM failed to
+//!   process, `POST` also empties the queue (only with a dead-letter queue,
+//!   see the [`dead_letter`](crate::dead_letter) module)
+//! - `/admin/unquarantine/<view>` - `POST` re-evaluates a quarantined derived
+//!   view and puts it back in service (see the
+//!   [`materialized_view`](crate::materialized_view) module)
+//!
+//! ## Lifetime and shutdown
+//!
+//! The router only answers requests; entity updates are produced by the
+//! background tasks (projector, parser, bus cleanup, stale client sweeper,
+//! health heartbeat). Those run until [`BackgroundHandle::shutdown`] is called
+//! or the tokio runtime they were spawned on shuts down. Dropping a
+//! [`BackgroundHandle`] does not stop them.
+//!
+//! Shutting down the background tasks stops new updates but does not close
+//! WebSocket connections that were already upgraded, since those are owned by
+//! the application's HTTP server. Stop serving the router first (e.g. with
+//! `axum::serve(..).with_graceful_shutdown(..)`) and then shut down the
+//! background tasks. To hand clients over to another instance first, drain
+//! them via [`BackgroundHandle::drain_controller`] and wait for
+//! [`DrainController::drained`].
+//!
+//! Per-IP connection limits need the peer address, so serve the application
+//! with `into_make_service_with_connect_info::<SocketAddr>()`. Without it all
+//! clients are attributed to an unspecified address.
+
+use crate::bus::BusManager;
+use crate::cache::EntityCache;
+use crate::dead_letter::FailedEvents;
+use crate::debug_bundle::DebugBundles;
+use crate::drain::DrainController;
+use crate::health::{HealthMonitor, SlotTracker};
+use crate::http_health::{
+    debug_bundle_download_response, debug_bundle_start_response, debug_bundle_status_response,
+    drain_response, drain_status_response, failed_events_response, flags_response,
+    flags_update_response, health_response, vm_stats_snapshot, MAX_FLAGS_BODY_BYTES,
+};
+use crate::load_shed::LoadShedder;
+use crate::materialized_view::MaterializedViewRegistry;
+use crate::mutation_batch::MutationBatch;
+use crate::projector::Projector;
+use crate::shadow::{ShadowDeployment, ShadowDiff};
+use crate::webhook::Webhooks;
+use crate::websocket::auth::ConnectionAuthRequest;
+use crate::websocket::server::ConnectionHandler;
+use crate::websocket::session::SessionStore;
+use crate::{ProgramParser, ReconnectionConfig, Spec, VmStatsFn};
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Path, RawQuery, Request, State};
+use axum::http::{header::CONTENT_TYPE, StatusCode};
+use axum::response::Response;
+use axum::routing::{get, post};
+use axum::Router;
+use futures_util::future::select_all;
+use hyperstack_interpreter::clock::SharedClock;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{error, info, info_span, Instrument};
+
+/// Address reported for clients when the router is served without connect info
+const UNKNOWN_REMOTE_ADDR: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0);
+
+const HEALTH_PATHS: [&str; 6] = [
+    "/health",
+    "/healthz",
+    "/ready",
+    "/readiness",
+    "/readyz",
+    "/status",
+];
+
+#[derive(Clone)]
+struct RouterState {
+    handler: ConnectionHandler,
+    bus_manager: BusManager,
+    entity_cache: EntityCache,
+    health_monitor: Option<HealthMonitor>,
+    shadow_diff: Option<ShadowDiff>,
+    materialized_views: Option<Arc<MaterializedViewRegistry>>,
+    load_shedder: Option<LoadShedder>,
+    webhooks: Option<Webhooks>,
+    debug_bundles: DebugBundles,
+    failed_events: Option<FailedEvents>,
+    vm_stats: Option<VmStatsFn>,
+    slots: SlotTracker,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn build_router(
+    handler: ConnectionHandler,
+    bus_manager: BusManager,
+    entity_cache: EntityCache,
+    health_monitor: Option<HealthMonitor>,
+    shadow_diff: Option<ShadowDiff>,
+    materialized_views: Option<Arc<MaterializedViewRegistry>>,
+    load_shedder: Option<LoadShedder>,
+    webhooks: Option<Webhooks>,
+    debug_bundles: DebugBundles,
+    failed_events: Option<FailedEvents>,
+    vm_stats: Option<VmStatsFn>,
+    slots: SlotTracker,
+) -> Router {
+    let has_shadow = shadow_diff.is_some();
+    let has_failed_events = failed_events.is_some();
+    let has_dictionaries = handler.client_manager.dictionaries().is_some();
+    let state = RouterState {
+        handler,
+        bus_manager,
+        entity_cache,
+        health_monitor,
+        shadow_diff,
+        materialized_views,
+        load_shedder,
+        webhooks,
+        debug_bundles,
+        failed_events,
+        vm_stats,
+        slots,
+    };
+
+    let mut router = Router::new()
+        .route("/", get(websocket_upgrade))
+        .route("/sub/{*view}", get(websocket_upgrade))
+        .route("/stats", get(stats))
+        .route("/admin/drain", get(drain_status).post(start_drain))
+        .route(
+            "/admin/debug-bundle",
+            get(debug_bundle_status).post(start_debug_bundle),
+        )
+        .route("/admin/debug-bundle/download", get(download_debug_bundle))
+        .route("/admin/flags", get(flags).patch(update_flags))
+        .route("/admin/unquarantine/{*view}", post(unquarantine_view));
+
+    if has_shadow {
+        router = router.route("/admin/shadow", get(shadow_report));
+    }
+    if has_dictionaries {
+        router = router.route("/dictionaries/{id}", get(dictionary));
+    }
+    if has_failed_events {
+        router = router.route(
+            "/admin/failed-events",
+            get(list_failed_events).post(drain_failed_events),
+        );
+    }
+
+    for path in HEALTH_PATHS {
+        router = router.route(
+            path,
+            get(move |State(state): State<RouterState>| async move {
+                health_response(
+                    path,
+                    state.health_monitor.as_ref(),
+                    Some(&state.handler.drain),
+                )
+                .await
+                .unwrap_or_else(|never| match never {})
+                .map(Body::new)
+            }),
+        );
+    }
+
+    router.with_state(state)
+}
+
+fn remote_addr(request: &Request) -> SocketAddr {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr)
+        .unwrap_or(UNKNOWN_REMOTE_ADDR)
+}
+
+async fn websocket_upgrade(State(state): State<RouterState>, request: Request) -> Response {
+    let remote_addr = remote_addr(&request);
+    state.handler.upgrade(request, remote_addr).await
+}
+
+async fn dictionary(
+    State(state): State<RouterState>,
+    Path(id): Path<String>,
+    request: Request,
+) -> Response {
+    let remote_addr = remote_addr(&request);
+    state.handler.dictionary(request, remote_addr, &id).await
+}
+
+async fn stats(State(state): State<RouterState>) -> Response {
+    let (state_buses, list_buses) = state.bus_manager.bus_counts().await;
+    let cache_stats = state.entity_cache.stats().await;
+    let append_logs = state.bus_manager.append_log_stats().await;
+    let views = state
+        .materialized_views
+        .as_ref()
+        .map(|views| views.stats())
+        .unwrap_or_default();
+    let vm = vm_stats_snapshot(state.vm_stats.clone()).await;
+
+    let stats_json = serde_json::json!({
+        "clients": state.handler.client_manager.client_count(),
+        "last_slot": state.slots.get(),
+        "frames_sent": state.handler.client_manager.frames_sent(),
+        "state_buses": state_buses,
+        "list_buses": list_buses,
+        "cache": {
+            "view_count": cache_stats.view_count,
+            "total_entities": cache_stats.total_entities,
+            "top_views": cache_stats.top_views,
+        },
+        "append_logs": append_logs,
+        "views": views,
+        "quarantined_views": state.handler.view_index.load().quarantined_views().all(),
+        "load_shedding": state.load_shedder.as_ref().map(LoadShedder::stats),
+        "webhooks": state.webhooks.as_ref().map(Webhooks::stats),
+        "oversized_frames": state.handler.client_manager.oversized_frame_stats(),
+        "flags": state.handler.client_manager.flags().stats(),
+        "snapshot_queue": state.handler.client_manager.snapshot_queue().stats(),
+        "sessions": state.handler.client_manager.sessions().map(SessionStore::stats),
+        "vm": vm,
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(stats_json.to_string()))
+        .expect("stats response should build")
+}
+
+async fn shadow_report(State(state): State<RouterState>) -> Response {
+    let report = state.shadow_diff.as_ref().map(ShadowDiff::report);
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::to_string(&report).expect("shadow report should serialize"),
+        ))
+        .expect("shadow report response should build")
+}
+
+async fn start_drain(State(state): State<RouterState>, RawQuery(query): RawQuery) -> Response {
+    drain_response(query.as_deref(), &state.handler.drain).map(Body::new)
+}
+
+async fn drain_status(State(state): State<RouterState>) -> Response {
+    drain_status_response(&state.handler.drain).map(Body::new)
+}
+
+async fn start_debug_bundle(State(state): State<RouterState>) -> Response {
+    debug_bundle_start_response(&state.debug_bundles).map(Body::new)
+}
+
+async fn debug_bundle_status(State(state): State<RouterState>) -> Response {
+    debug_bundle_status_response(&state.debug_bundles).map(Body::new)
+}
+
+async fn download_debug_bundle(State(state): State<RouterState>) -> Response {
+    debug_bundle_download_response(&state.debug_bundles).map(Body::new)
+}
+
+async fn list_failed_events(State(state): State<RouterState>) -> Response {
+    failed_events_route(&state, false)
+}
+
+async fn drain_failed_events(State(state): State<RouterState>) -> Response {
+    failed_events_route(&state, true)
+}
+
+/// Only routed with a dead-letter queue
+fn failed_events_route(state: &RouterState, drain: bool) -> Response {
+    match &state.failed_events {
+        Some(failed_events) => failed_events_response(failed_events, drain).map(Body::new),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .expect("not found response should build"),
+    }
+}
+
+async fn flags(State(state): State<RouterState>) -> Response {
+    flags_response(state.handler.client_manager.flags()).map(Body::new)
+}
+
+async fn update_flags(State(state): State<RouterState>, request: Request) -> Response {
+    let auth_request = ConnectionAuthRequest::from_http_request(remote_addr(&request), &request);
+    let body = axum::body::to_bytes(request.into_body(), MAX_FLAGS_BODY_BYTES)
+        .await
+        .unwrap_or_default();
+    flags_update_response(state.handler.client_manager.flags(), &auth_request, &body)
+        .await
+        .map(Body::new)
+}
+
+/// Re-evaluate quarantined view `view` from the entity cache and, if that
+/// succeeds, put it back in service with its sorted cache refilled
+async fn unquarantine_view(State(state): State<RouterState>, Path(view): Path<String>) -> Response {
+    let unquarantined = match &state.materialized_views {
+        Some(views) => views.unquarantine(&view, &state.entity_cache).await,
+        None => None,
+    };
+    let (status, body) = match unquarantined {
+        None => (
+            StatusCode::NOT_FOUND,
+            serde_json::json!({ "view": view, "error": "view is not quarantined" }),
+        ),
+        Some(Err(failure)) => (
+            StatusCode::CONFLICT,
+            serde_json::json!({ "view": view, "error": failure.error }),
+        ),
+        Some(Ok(entities)) => {
+            let views = state.handler.view_index.load();
+            if let Some(source) = views
+                .get_view(&view)
+                .and_then(|spec| spec.source_view.as_deref())
+            {
+                let sorted_caches = views.sorted_caches();
+                let mut caches = sorted_caches.write().await;
+                if let Some(cache) = caches.get_mut(&view) {
+                    for (key, entity) in state.entity_cache.get_all(source).await {
+                        cache.upsert(key, entity);
+                    }
+                }
+            }
+            (
+                StatusCode::OK,
+                serde_json::json!({ "view": view, "entities": entities }),
+            )
+        }
+    };
+
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("unquarantine response should build")
+}
+
+/// The parsers of a spec's programs, run concurrently
+pub(crate) struct ParserTask {
+    pub(crate) programs: Vec<ProgramParser>,
+    pub(crate) reconnection_config: ReconnectionConfig,
+}
+
+impl ParserTask {
+    /// `None` when the spec has no parser. Hands `clock` to the spec's VM.
+    pub(crate) fn for_spec(
+        spec: &Spec,
+        reconnection_config: ReconnectionConfig,
+        clock: SharedClock,
+    ) -> Option<Self> {
+        let programs = spec.parsers();
+        if programs.is_empty() {
+            return None;
+        }
+        if let Some(set_clock) = &spec.vm_clock {
+            set_clock(clock);
+        }
+        Some(Self {
+            programs,
+            reconnection_config,
+        })
+    }
+}
+
+/// Background work backing an embedded router, not yet started.
+///
+/// Call [`BackgroundTasks::spawn_background`] once the tokio runtime that should
+/// drive the pipeline is available.
+pub struct BackgroundTasks {
+    pub(crate) projector: Projector,
+    pub(crate) parser: Option<ParserTask>,
+    /// Parsers started by spec reloads
+    pub(crate) parser_replacements: mpsc::UnboundedReceiver<ParserTask>,
+    pub(crate) shadow: Option<ShadowDeployment>,
+    pub(crate) mutations_tx: mpsc::Sender<MutationBatch>,
+    pub(crate) bus_manager: BusManager,
+    pub(crate) entity_cache: EntityCache,
+    pub(crate) handler: ConnectionHandler,
+    pub(crate) health_monitor: Option<HealthMonitor>,
+    /// Handed to the parser, carrying the dead-letter queue
+    pub(crate) parser_health_monitor: Option<HealthMonitor>,
+    pub(crate) materialized_views: Option<Arc<MaterializedViewRegistry>>,
+    pub(crate) load_shedder: Option<LoadShedder>,
+    pub(crate) webhooks: Option<Webhooks>,
+}
+
+impl BackgroundTasks {
+    /// Spawn the projector, parser, and maintenance tasks on `handle`.
+    pub fn spawn_background(self, handle: &tokio::runtime::Handle) -> BackgroundHandle {
+        let _guard = handle.enter();
+        let mut tasks = Vec::new();
+        let drain = self.handler.drain.clone();
+
+        if let Some(monitor) = &self.health_monitor {
+            tasks.push(("health monitor", monitor.spawn()));
+            info!("Health monitoring enabled");
+        }
+
+        if let Some(shedder) = self.load_shedder {
+            tasks.push((
+                "load shedding",
+                crate::runtime::spawn_load_shedding(
+                    shedder,
+                    self.mutations_tx.downgrade(),
+                    self.health_monitor.clone(),
+                ),
+            ));
+        }
+
+        if let Some(webhooks) = &self.webhooks {
+            tasks.push((
+                "webhook delivery",
+                webhooks.spawn_delivery(self.health_monitor.clone()),
+            ));
+        }
+
+        let projector = self.projector;
+        // Hold a sender so the projector keeps running when no parser is configured
+        let keepalive_tx = self.mutations_tx.clone();
+        tasks.push((
+            "projector",
+            tokio::spawn(
+                async move {
+                    let _keepalive_tx = keepalive_tx;
+                    projector.run().await;
+                }
+                .instrument(info_span!("projector")),
+            ),
+        ));
+
+        let parser_tx = match self.shadow {
+            Some(shadow) => {
+                let (parser_tx, shadow_tasks) = shadow.spawn(self.mutations_tx);
+                tasks.extend(shadow_tasks);
+                parser_tx
+            }
+            None => self.mutations_tx,
+        };
+
+        tasks.push((
+            "parser runtime",
+            crate::runtime::spawn_reloadable_parser(
+                self.parser,
+                parser_tx,
+                self.parser_health_monitor,
+                self.parser_replacements,
+            ),
+        ));
+
+        tasks.push((
+            "client cleanup",
+            self.handler.client_manager.start_cleanup_task(),
+        ));
+        tasks.push((
+            "bus cleanup",
+            crate::runtime::spawn_bus_cleanup(self.bus_manager.clone()),
+        ));
+        tasks.push((
+            "stats reporter",
+            crate::runtime::spawn_stats_reporter(self.bus_manager, self.entity_cache.clone()),
+        ));
+        if let Some(views) = self.materialized_views {
+            tasks.push((
+                "view refresh",
+                crate::runtime::spawn_view_refresh(views, self.entity_cache),
+            ));
+        }
+
+        BackgroundHandle { tasks, drain }
+    }
+}
+
+/// Wait until any of `tasks` exits, removing it and returning its name.
+///
+/// Never resolves when there are no tasks.
+pub(crate) async fn wait_any(tasks: &mut Vec<(&'static str, JoinHandle<()>)>) -> &'static str {
+    if tasks.is_empty() {
+        return std::future::pending().await;
+    }
+
+    let handles = tasks.iter_mut().map(|(_, handle)| handle);
+    let (result, index, _) = select_all(handles).await;
+    let name = tasks.remove(index).0;
+    match result {
+        Ok(()) => info!("{} task completed", name),
+        Err(e) => error!("{} task failed: {}", name, e),
+    }
+    name
+}
+
+/// Handle to the background tasks of an embedded router.
+///
+/// Dropping the handle leaves the tasks running; call
+/// [`BackgroundHandle::shutdown`] to stop them.
+pub struct BackgroundHandle {
+    tasks: Vec<(&'static str, JoinHandle<()>)>,
+    drain: DrainController,
+}
+
+impl BackgroundHandle {
+    /// Handle for draining the router's WebSocket connections before shutdown.
+    pub fn drain_controller(&self) -> DrainController {
+        self.drain.clone()
+    }
+
+    /// Wait until any background task exits, returning its name.
+    ///
+    /// The tasks run forever under normal operation, so completion means the
+    /// pipeline stopped (e.g. the parser gave up reconnecting).
+    pub async fn wait(&mut self) -> &'static str {
+        wait_any(&mut self.tasks).await
+    }
+
+    /// Returns true once any background task has exited.
+    pub fn is_finished(&self) -> bool {
+        self.tasks.iter().any(|(_, handle)| handle.is_finished())
+    }
+
+    /// Abort all background tasks.
+    pub fn shutdown(self) {
+        info!("Shutting down HyperStack background tasks");
+        for (_, handle) in self.tasks {
+            handle.abort();
+        }
+    }
+}
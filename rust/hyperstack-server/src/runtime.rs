@@ -1,28 +1,55 @@
+use crate::backfill::RpcBackfill;
+use crate::big_numbers::BigNumbers;
 use crate::bus::BusManager;
 use crate::cache::EntityCache;
-use crate::config::ServerConfig;
-use crate::health::HealthMonitor;
-use crate::http_health::HttpHealthServer;
+use crate::config::{HealthConfig, ServerConfig, WebSocketConfig};
+use crate::dead_letter::FailedEvents;
+use crate::debug_bundle::{config_summary, BundleSources, DeadLetters, DebugBundles};
+use crate::dictionary::Dictionaries;
+use crate::drain::{DrainController, CLOSE_ACK_TIMEOUT};
+use crate::flags::Flags;
+use crate::health::{HealthMonitor, SlotTracker};
+use crate::http_health::{HttpHealthServer, RuntimeStats};
+use crate::load_shed::LoadShedder;
 use crate::materialized_view::MaterializedViewRegistry;
 use crate::mutation_batch::MutationBatch;
+use crate::persistence::Persister;
 use crate::projector::Projector;
+use crate::reload::{stack_schema_hash, Attached, LiveViews, SpecReloader};
+use crate::router::{build_router, wait_any, BackgroundTasks, ParserTask};
+use crate::shadow::{ShadowConfig, ShadowDeployment, ShadowDiff};
+use crate::shutdown::{ShutdownReport, StopReason, DEFAULT_SHUTDOWN_GRACE};
 use crate::view::ViewIndex;
-use crate::websocket::client_manager::RateLimitConfig;
+use crate::webhook::Webhooks;
+use crate::websocket::client_manager::{ClientManager, RateLimitConfig};
 use crate::websocket::WebSocketServer;
-use crate::Spec;
-use crate::WebSocketAuthPlugin;
 use crate::WebSocketUsageEmitter;
+use crate::{FieldAuthorizer, WebSocketAuthPlugin};
+use crate::{ProgramParser, Spec};
 use anyhow::Result;
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
-use tracing::{error, info, info_span, Instrument};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 #[cfg(feature = "otel")]
 use crate::metrics::Metrics;
 
+/// Mutation batches the parser can queue ahead of the projector
+pub(crate) const MUTATION_CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShutdownSignal {
+    Interrupt,
+    Terminate,
+}
+
 /// Wait for shutdown signal (SIGINT on all platforms, SIGTERM on Unix)
-async fn shutdown_signal() {
+async fn shutdown_signal() -> ShutdownSignal {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
             .await
@@ -43,22 +70,35 @@ async fn shutdown_signal() {
     tokio::select! {
         _ = ctrl_c => {
             info!("Received SIGINT (Ctrl+C), initiating shutdown");
+            ShutdownSignal::Interrupt
         }
         _ = terminate => {
             info!("Received SIGTERM, initiating graceful shutdown");
+            ShutdownSignal::Terminate
         }
     }
 }
 
 pub struct Runtime {
     config: ServerConfig,
-    view_index: Arc<ViewIndex>,
+    view_index: LiveViews,
+    reloader: SpecReloader,
+    big_numbers: BigNumbers,
+    flags: Arc<Flags>,
+    /// Last slot the projector processed, reported by `/stats`
+    slots: SlotTracker,
     spec: Option<Spec>,
-    materialized_views: Option<MaterializedViewRegistry>,
+    shadow_spec: Option<Spec>,
+    shadow_config: ShadowConfig,
+    materialized_views: Option<Arc<MaterializedViewRegistry>>,
     websocket_auth_plugin: Option<Arc<dyn WebSocketAuthPlugin>>,
+    websocket_field_authorizer: Option<Arc<dyn FieldAuthorizer>>,
     websocket_usage_emitter: Option<Arc<dyn WebSocketUsageEmitter>>,
     websocket_max_clients: Option<usize>,
     websocket_rate_limit_config: Option<RateLimitConfig>,
+    drain_on_shutdown: Option<Duration>,
+    shutdown: CancellationToken,
+    shutdown_grace: Duration,
     #[cfg(feature = "otel")]
     metrics: Option<Arc<Metrics>>,
 }
@@ -66,40 +106,100 @@ pub struct Runtime {
 impl Runtime {
     #[cfg(feature = "otel")]
     pub fn new(config: ServerConfig, view_index: ViewIndex, metrics: Option<Arc<Metrics>>) -> Self {
+        let view_index = LiveViews::new(view_index);
+        let big_numbers = BigNumbers::new(config.big_numbers);
+        let flags = Arc::new(Flags::new(&config.flags).with_clock(config.clock()));
         Self {
             config,
-            view_index: Arc::new(view_index),
+            reloader: SpecReloader::new(view_index.clone()),
+            view_index,
+            big_numbers,
+            flags,
+            slots: SlotTracker::new(),
             spec: None,
+            shadow_spec: None,
+            shadow_config: ShadowConfig::default(),
             materialized_views: None,
             websocket_auth_plugin: None,
+            websocket_field_authorizer: None,
             websocket_usage_emitter: None,
             websocket_max_clients: None,
             websocket_rate_limit_config: None,
+            drain_on_shutdown: None,
+            shutdown: CancellationToken::new(),
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
             metrics,
         }
     }
 
     #[cfg(not(feature = "otel"))]
     pub fn new(config: ServerConfig, view_index: ViewIndex) -> Self {
+        let view_index = LiveViews::new(view_index);
+        let big_numbers = BigNumbers::new(config.big_numbers);
+        let flags = Arc::new(Flags::new(&config.flags).with_clock(config.clock()));
         Self {
             config,
-            view_index: Arc::new(view_index),
+            reloader: SpecReloader::new(view_index.clone()),
+            view_index,
+            big_numbers,
+            flags,
+            slots: SlotTracker::new(),
             spec: None,
+            shadow_spec: None,
+            shadow_config: ShadowConfig::default(),
             materialized_views: None,
             websocket_auth_plugin: None,
+            websocket_field_authorizer: None,
             websocket_usage_emitter: None,
             websocket_max_clients: None,
             websocket_rate_limit_config: None,
+            drain_on_shutdown: None,
+            shutdown: CancellationToken::new(),
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
         }
     }
 
     pub fn with_spec(mut self, spec: Spec) -> Self {
+        self.reloader.track(&spec);
+        self.big_numbers.track(&spec.bytecode);
         self.spec = Some(spec);
         self
     }
 
+    /// Handle for replacing the spec once the runtime is started.
+    ///
+    /// Reloads fail with [`ReloadError::NotStarted`](crate::ReloadError::NotStarted)
+    /// until [`Runtime::run`] or [`Runtime::into_router_parts`] is called. See
+    /// the [`reload`](crate::reload) module.
+    pub fn reload_handle(&self) -> SpecReloader {
+        self.reloader.clone()
+    }
+
+    /// Run `spec` as a shadow deployment next to the production spec.
+    ///
+    /// The shadow spec processes the same program with its own parser and VM
+    /// state. Its output is never served; it is diffed against production and
+    /// reported at `/admin/shadow`. See the [`shadow`](crate::shadow) module.
+    pub fn with_shadow_spec(mut self, spec: Spec) -> Self {
+        self.shadow_spec = Some(spec);
+        self
+    }
+
+    /// Configure resource limits and reporting for the shadow deployment.
+    pub fn with_shadow_config(mut self, config: ShadowConfig) -> Self {
+        self.shadow_config = config;
+        self
+    }
+
+    /// Maintain `registry`'s views as their sources update, held to the
+    /// configured view budget.
     pub fn with_materialized_views(mut self, registry: MaterializedViewRegistry) -> Self {
-        self.materialized_views = Some(registry);
+        let registry = match self.config.view_budget {
+            Some(budget) => registry.with_budget(budget),
+            None => registry,
+        }
+        .with_clock(self.config.clock());
+        self.materialized_views = Some(Arc::new(registry));
         self
     }
 
@@ -111,6 +211,14 @@ impl Runtime {
         self
     }
 
+    pub fn with_websocket_field_authorizer(
+        mut self,
+        websocket_field_authorizer: Arc<dyn FieldAuthorizer>,
+    ) -> Self {
+        self.websocket_field_authorizer = Some(websocket_field_authorizer);
+        self
+    }
+
     pub fn with_websocket_usage_emitter(
         mut self,
         websocket_usage_emitter: Arc<dyn WebSocketUsageEmitter>,
@@ -134,131 +242,597 @@ impl Runtime {
         self
     }
 
-    pub async fn run(self) -> Result<()> {
-        info!("Starting HyperStack runtime");
+    /// Drain WebSocket connections for `grace` on SIGTERM before stopping.
+    ///
+    /// Clients are told to migrate and closed once `grace` elapses, as with
+    /// `POST /admin/drain`. SIGINT still stops immediately. See the
+    /// [`drain`](crate::drain) module.
+    pub fn with_drain_on_shutdown(mut self, grace: Duration) -> Self {
+        self.drain_on_shutdown = Some(grace);
+        self
+    }
 
-        let (mutations_tx, mutations_rx) = mpsc::channel::<MutationBatch>(1024);
+    /// Time a shutdown through [`Runtime::shutdown_token`] may take before
+    /// the tasks still running are aborted.
+    ///
+    /// Defaults to [`DEFAULT_SHUTDOWN_GRACE`]. See the
+    /// [`shutdown`](crate::shutdown) module.
+    pub fn with_shutdown_grace(mut self, grace: Duration) -> Self {
+        self.shutdown_grace = grace;
+        self
+    }
 
-        let bus_manager = BusManager::new();
-        let entity_cache = EntityCache::new();
+    /// Token that stops [`Runtime::run`] when cancelled.
+    ///
+    /// The runtime flushes queued mutations and closes its WebSocket clients
+    /// before returning. See the [`shutdown`](crate::shutdown) module.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
 
-        let health_monitor = if let Some(health_config) = &self.config.health {
-            let monitor = HealthMonitor::new(health_config.clone());
-            let _health_task = monitor.start().await;
-            info!("Health monitoring enabled");
-            Some(monitor)
-        } else {
-            None
+    /// Build an axum [`Router`](axum::Router) serving the WebSocket endpoint,
+    /// health routes, and stats, and spawn the background tasks on the current
+    /// tokio runtime.
+    ///
+    /// The caller binds and serves the router, e.g. nested under `/stream` in an
+    /// existing application. The `websocket` and `http_health` bind addresses in
+    /// the config are ignored. Background tasks run until the tokio runtime
+    /// shuts down; use [`Runtime::into_router_parts`] to control their lifetime.
+    /// See the [`router`](crate::router) module for the shutdown interplay.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn into_router(self) -> axum::Router {
+        let (router, background) = self.into_router_parts();
+        let _background = background.spawn_background(&tokio::runtime::Handle::current());
+        router
+    }
+
+    /// Build an axum [`Router`](axum::Router) without starting any tasks.
+    ///
+    /// The returned [`BackgroundTasks`] must be started with
+    /// [`BackgroundTasks::spawn_background`], otherwise the router accepts
+    /// clients but never delivers updates.
+    pub fn into_router_parts(self) -> (axum::Router, BackgroundTasks) {
+        let (mutations_tx, mutations_rx) =
+            mpsc::channel::<MutationBatch>(MUTATION_CHANNEL_CAPACITY);
+
+        let clock = self.config.clock();
+        let bus_manager = BusManager::new()
+            .with_clock(clock.clone())
+            .with_append_log(self.config.append_log.unwrap_or_default());
+        let entity_cache = EntityCache::new().with_clock(clock.clone());
+        let health_monitor = self
+            .config
+            .health
+            .clone()
+            .map(|config| HealthMonitor::new(config).with_clock(clock));
+        let failed_events = self.failed_events();
+        let parser_health_monitor =
+            self.parser_health_monitor(health_monitor.as_ref(), failed_events.clone());
+        if self.config.persistence.is_some() {
+            warn!("State persistence is not supported by embedded routers - skipping");
+        }
+        #[cfg(feature = "grpc")]
+        if self.config.grpc.is_some() {
+            warn!("The gRPC endpoint is not served by embedded routers - skipping");
+        }
+
+        let bind_addr = self
+            .config
+            .websocket
+            .as_ref()
+            .map(|ws_config| ws_config.bind_address)
+            .unwrap_or_else(|| WebSocketConfig::default().bind_address);
+        let handler = self
+            .websocket_server(bind_addr, bus_manager.clone(), entity_cache.clone())
+            .into_handler();
+        let load_shedder = self.load_shedder();
+        let webhooks = self.webhooks();
+        let dead_letters = DeadLetters::default().with_clock(self.config.clock());
+        let projector = self.projector(
+            bus_manager.clone(),
+            entity_cache.clone(),
+            mutations_rx,
+            Some(handler.client_manager.clone()),
+            load_shedder.clone(),
+            webhooks.clone(),
+            dead_letters.clone(),
+        );
+        let parser = self.parser_task();
+        let (replacements_tx, parser_replacements) = mpsc::unbounded_channel();
+        self.reloader.attach(Attached {
+            entity_cache: entity_cache.clone(),
+            clients: Some(handler.client_manager.clone()),
+            parsers: replacements_tx,
+            reconnection_config: self.config.reconnection.clone().unwrap_or_default(),
+            big_numbers: self.big_numbers.clone(),
+            clock: self.config.clock(),
+        });
+        let shadow = self.shadow_deployment();
+        let debug_bundles =
+            self.debug_bundles(entity_cache.clone(), bus_manager.clone(), dead_letters);
+
+        let router = build_router(
+            handler.clone(),
+            bus_manager.clone(),
+            entity_cache.clone(),
+            health_monitor.clone(),
+            shadow.as_ref().map(|shadow| shadow.diff.clone()),
+            self.materialized_views.clone(),
+            load_shedder.clone(),
+            webhooks.clone(),
+            debug_bundles,
+            failed_events,
+            self.spec.as_ref().and_then(|spec| spec.vm_stats.clone()),
+            self.slots.clone(),
+        );
+
+        let background = BackgroundTasks {
+            projector,
+            parser,
+            parser_replacements,
+            shadow,
+            mutations_tx,
+            bus_manager,
+            entity_cache,
+            handler,
+            health_monitor,
+            parser_health_monitor,
+            materialized_views: self.materialized_views.clone(),
+            load_shedder,
+            webhooks,
         };
 
+        (router, background)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn projector(
+        &self,
+        bus_manager: BusManager,
+        entity_cache: EntityCache,
+        mutations_rx: mpsc::Receiver<MutationBatch>,
+        clients: Option<ClientManager>,
+        load_shedder: Option<LoadShedder>,
+        webhooks: Option<Webhooks>,
+        dead_letters: DeadLetters,
+    ) -> Projector {
         #[cfg(feature = "otel")]
-        let projector = Projector::new(
+        let mut projector = Projector::new(
             self.view_index.clone(),
-            bus_manager.clone(),
-            entity_cache.clone(),
+            bus_manager,
+            entity_cache,
             mutations_rx,
             self.metrics.clone(),
         );
         #[cfg(not(feature = "otel"))]
-        let projector = Projector::new(
+        let mut projector = Projector::new(
             self.view_index.clone(),
-            bus_manager.clone(),
-            entity_cache.clone(),
+            bus_manager,
+            entity_cache,
             mutations_rx,
         );
 
-        let projector_handle = tokio::spawn(
-            async move {
-                projector.run().await;
+        projector = projector
+            .with_big_numbers(self.big_numbers.clone())
+            .with_flags(self.flags.clone())
+            .with_slot_tracker(self.slots.clone());
+        if let Some(dictionaries) = clients.as_ref().and_then(ClientManager::dictionaries) {
+            projector = projector.with_dictionaries(dictionaries.clone());
+        }
+        if let Some(views) = self.materialized_views.clone() {
+            projector = projector.with_materialized_views(views);
+        }
+        if let Some(config) = self.config.coalesce.clone() {
+            projector = projector.with_coalescing(config);
+            if let Some(clients) = clients {
+                projector = projector.with_queue_pressure(clients);
             }
-            .instrument(info_span!("projector")),
-        );
+        }
+        if let Some(shedder) = load_shedder {
+            projector = projector.with_load_shedding(shedder);
+        }
+        if let Some(webhooks) = webhooks {
+            projector = projector.with_webhooks(webhooks);
+        }
+        projector.with_dead_letters(dead_letters)
+    }
 
-        let ws_handle = if let Some(ws_config) = &self.config.websocket {
-            #[cfg(feature = "otel")]
-            let mut ws_server = WebSocketServer::new(
-                ws_config.bind_address,
-                bus_manager.clone(),
-                entity_cache.clone(),
-                self.view_index.clone(),
-                self.metrics.clone(),
-            );
-            #[cfg(not(feature = "otel"))]
-            let mut ws_server = WebSocketServer::new(
-                ws_config.bind_address,
-                bus_manager.clone(),
-                entity_cache.clone(),
-                self.view_index.clone(),
-            );
+    fn debug_bundles(
+        &self,
+        entity_cache: EntityCache,
+        bus_manager: BusManager,
+        dead_letters: DeadLetters,
+    ) -> DebugBundles {
+        DebugBundles::new(
+            self.config.debug_bundle.clone().unwrap_or_default(),
+            BundleSources {
+                entity_cache,
+                bus_manager,
+                view_index: self.view_index.clone(),
+                dead_letters,
+                vm_snapshot: self.spec.as_ref().and_then(|spec| spec.vm_snapshot.clone()),
+                server_config: config_summary(&self.config),
+                clock: self.config.clock(),
+            },
+        )
+    }
 
-            if let Some(max_clients) = self.websocket_max_clients {
-                ws_server = ws_server.with_max_clients(max_clients);
-            }
+    fn load_shedder(&self) -> Option<LoadShedder> {
+        let config = self.config.load_shedding.clone()?;
+        let priorities = self
+            .spec
+            .iter()
+            .flat_map(|spec| &spec.bytecode.entities)
+            .map(|(name, entity)| (name.clone(), entity.priority))
+            .collect();
+        info!("Load shedding enabled");
+        Some(LoadShedder::new(config, priorities))
+    }
 
-            if let Some(plugin) = self.websocket_auth_plugin.clone() {
-                ws_server = ws_server.with_auth_plugin(plugin);
-            }
+    fn persister(&self, entity_cache: EntityCache) -> Option<Persister> {
+        let config = self.config.persistence.clone()?;
+        info!("State persistence enabled every {:?}", config.interval);
+        let vm_state = self.spec.as_ref().and_then(|spec| spec.vm_state.clone());
+        Some(Persister::new(
+            config,
+            entity_cache,
+            vm_state,
+            self.spec.as_ref().map(|spec| &spec.bytecode),
+            self.config.clock(),
+        ))
+    }
 
-            if let Some(emitter) = self.websocket_usage_emitter.clone() {
-                ws_server = ws_server.with_usage_emitter(emitter);
+    fn failed_events(&self) -> Option<FailedEvents> {
+        let config = self.config.dead_letter.clone()?;
+        info!("Keeping up to {} failed events", config.capacity);
+        Some(FailedEvents::new(config).with_clock(self.config.clock()))
+    }
+
+    /// The monitor handed to the parser, carrying the dead-letter queue.
+    ///
+    /// Without health monitoring, a monitor that is never started carries it,
+    /// so health checks stay off.
+    fn parser_health_monitor(
+        &self,
+        health_monitor: Option<&HealthMonitor>,
+        failed_events: Option<FailedEvents>,
+    ) -> Option<HealthMonitor> {
+        match (health_monitor, failed_events) {
+            (Some(monitor), Some(failed_events)) => {
+                Some(monitor.clone().with_failed_events(failed_events))
             }
+            (Some(monitor), None) => Some(monitor.clone()),
+            (None, Some(failed_events)) => Some(
+                HealthMonitor::new(HealthConfig::default())
+                    .with_clock(self.config.clock())
+                    .with_failed_events(failed_events),
+            ),
+            (None, None) => None,
+        }
+    }
+
+    fn webhooks(&self) -> Option<Webhooks> {
+        let config = self.config.webhooks.clone()?;
+        info!("Webhooks enabled for {} rules", config.rules.len());
+        Some(Webhooks::new(config).with_clock(self.config.clock()))
+    }
+
+    fn websocket_server(
+        &self,
+        bind_addr: SocketAddr,
+        bus_manager: BusManager,
+        entity_cache: EntityCache,
+    ) -> WebSocketServer {
+        #[cfg(feature = "otel")]
+        let mut ws_server = WebSocketServer::new(
+            bind_addr,
+            bus_manager,
+            entity_cache,
+            self.view_index.clone(),
+            self.metrics.clone(),
+        );
+        #[cfg(not(feature = "otel"))]
+        let mut ws_server = WebSocketServer::new(
+            bind_addr,
+            bus_manager,
+            entity_cache,
+            self.view_index.clone(),
+        );
+
+        if let Some(max_clients) = self.websocket_max_clients {
+            ws_server = ws_server.with_max_clients(max_clients);
+        }
+
+        if let Some(plugin) = self.websocket_auth_plugin.clone() {
+            ws_server = ws_server.with_auth_plugin(plugin);
+        }
+
+        if let Some(emitter) = self.websocket_usage_emitter.clone() {
+            ws_server = ws_server.with_usage_emitter(emitter);
+        }
+
+        if let Some(rate_limit_config) = self.websocket_rate_limit_config.clone() {
+            ws_server = ws_server.with_rate_limit_config(rate_limit_config);
+        }
+
+        if let Some(authorizer) = self.websocket_field_authorizer.clone() {
+            ws_server = ws_server.with_field_authorizer(authorizer);
+        }
+
+        ws_server = ws_server
+            .with_big_numbers(self.big_numbers.clone())
+            .with_flags(self.flags.clone())
+            .with_clock(self.config.clock());
 
-            if let Some(rate_limit_config) = self.websocket_rate_limit_config {
-                ws_server = ws_server.with_rate_limit_config(rate_limit_config);
+        if let Some(dictionaries) = self.dictionaries() {
+            ws_server = ws_server.with_dictionaries(dictionaries.clone());
+        }
+
+        if let Some(ws_config) = &self.config.websocket {
+            ws_server = ws_server
+                .with_inbound_limits(ws_config.inbound_limits())
+                .with_max_frame_bytes(ws_config.max_frame_bytes)
+                .with_snapshot_queue(ws_config.snapshot_queue)
+                .with_snapshot_page_size(ws_config.snapshot_page_size);
+            if let Some(sessions) = ws_config.sessions {
+                ws_server = ws_server.with_sessions(sessions);
             }
+        }
 
-            let bind_addr = ws_config.bind_address;
-            Some(tokio::spawn(
-                async move {
-                    if let Err(e) = ws_server.start().await {
-                        error!("WebSocket server error: {}", e);
-                    }
-                }
-                .instrument(info_span!("ws.server", %bind_addr)),
-            ))
+        if let Some(backfill) = self.rpc_backfill() {
+            ws_server = ws_server.with_backfill(backfill);
+        }
+
+        ws_server
+    }
+
+    /// The compression dictionary of the spec being served, when configured
+    fn dictionaries(&self) -> Option<Dictionaries> {
+        let source = self.config.compression_dictionary.as_ref()?;
+        let schema_hash = match &self.spec {
+            Some(spec) => stack_schema_hash(&spec.bytecode.entities),
+            None => stack_schema_hash(&HashMap::new()),
+        };
+        Some(Dictionaries::new(source, &schema_hash))
+    }
+
+    fn rpc_backfill(&self) -> Option<RpcBackfill> {
+        let config = self.config.backfill.clone()?;
+        let Some(apply) = self
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.account_backfill.clone())
+        else {
+            warn!("RPC backfill configured but the spec has no account backfill - skipping");
+            return None;
+        };
+        info!(rpc_url = %config.rpc_url, "RPC backfill enabled for unknown state keys");
+        Some(RpcBackfill::new(config, apply))
+    }
+
+    fn parser_task(&self) -> Option<ParserTask> {
+        let Some(spec) = &self.spec else {
+            info!("No spec provided - running in websocket-only mode");
+            return None;
+        };
+
+        let parser = self.parser_task_for(spec);
+        if parser.is_none() {
+            info!("Spec provided but no parser_setup configured - skipping Vixen runtime");
+        }
+        parser
+    }
+
+    fn shadow_deployment(&self) -> Option<ShadowDeployment> {
+        let spec = self.shadow_spec.as_ref()?;
+        info!("Shadow spec provided - diffing its output against production");
+
+        Some(ShadowDeployment {
+            parser: self.parser_task_for(spec),
+            diff: ShadowDiff::new(self.shadow_config.clone()),
+            summary_interval: self.shadow_config.summary_interval,
+        })
+    }
+
+    fn parser_task_for(&self, spec: &Spec) -> Option<ParserTask> {
+        ParserTask::for_spec(
+            spec,
+            self.config.reconnection.clone().unwrap_or_default(),
+            self.config.clock(),
+        )
+    }
+
+    /// Run until SIGINT, SIGTERM, [`Runtime::shutdown_token`] is cancelled,
+    /// or a background task exits.
+    pub async fn run(self) -> Result<()> {
+        self.serve(true).await.map(|_| ())
+    }
+
+    /// Run until [`Runtime::shutdown_token`] is cancelled or a background task
+    /// exits, ignoring signals.
+    pub(crate) async fn run_until_shutdown(self) -> Result<ShutdownReport> {
+        self.serve(false).await
+    }
+
+    async fn serve(self, handle_signals: bool) -> Result<ShutdownReport> {
+        info!("Starting HyperStack runtime");
+
+        let (mutations_tx, mutations_rx) =
+            mpsc::channel::<MutationBatch>(MUTATION_CHANNEL_CAPACITY);
+
+        let clock = self.config.clock();
+        let bus_manager = BusManager::new()
+            .with_clock(clock.clone())
+            .with_append_log(self.config.append_log.unwrap_or_default());
+        let entity_cache = EntityCache::new().with_clock(clock.clone());
+
+        // Restored before the parser starts, so handlers build on the
+        // restored VM state and the first subscribers see the restored entities
+        let persister = self.persister(entity_cache.clone());
+        if let Some(persister) = &persister {
+            persister.restore().await;
+        }
+
+        // Watched: the runtime stops when one of them exits
+        let mut tasks: Vec<(&'static str, JoinHandle<()>)> = Vec::new();
+        // Stopped along with the runtime
+        let mut auxiliary: Vec<JoinHandle<()>> = Vec::new();
+
+        let health_monitor = if let Some(health_config) = &self.config.health {
+            let monitor = HealthMonitor::new(health_config.clone()).with_clock(clock);
+            auxiliary.push(monitor.start().await);
+            info!("Health monitoring enabled");
+            Some(monitor)
         } else {
             None
         };
+        let failed_events = self.failed_events();
 
-        let parser_handle = if let Some(spec) = self.spec {
-            if let Some(parser_setup) = spec.parser_setup {
-                let program_id = spec
-                    .program_ids
-                    .first()
-                    .cloned()
-                    .unwrap_or_else(|| "unknown".to_string());
-                info!("Starting Vixen parser runtime for program: {}", program_id);
-                let tx = mutations_tx.clone();
-                let health = health_monitor.clone();
-                let reconnection_config = self.config.reconnection.clone().unwrap_or_default();
-                Some(tokio::spawn(
+        let ws_server = self.config.websocket.as_ref().map(|ws_config| {
+            self.websocket_server(
+                ws_config.bind_address,
+                bus_manager.clone(),
+                entity_cache.clone(),
+            )
+        });
+        let load_shedder = self.load_shedder();
+        let webhooks = self.webhooks();
+        let dead_letters = DeadLetters::default().with_clock(self.config.clock());
+        let clients = ws_server.as_ref().map(WebSocketServer::client_manager);
+        let projector = self.projector(
+            bus_manager.clone(),
+            entity_cache.clone(),
+            mutations_rx,
+            clients.clone(),
+            load_shedder.clone(),
+            webhooks.clone(),
+            dead_letters.clone(),
+        );
+
+        tasks.push((
+            "projector",
+            tokio::spawn(
+                async move {
+                    projector.run().await;
+                }
+                .instrument(info_span!("projector")),
+            ),
+        ));
+
+        let mut drain = None;
+        if let Some(ws_server) = ws_server {
+            drain = Some(ws_server.drain_controller());
+
+            let bind_addr = ws_server.bind_addr();
+            let shutdown = self.shutdown.clone();
+            tasks.push((
+                "websocket server",
+                tokio::spawn(
                     async move {
-                        if let Err(e) = parser_setup(tx, health, reconnection_config).await {
-                            error!("Vixen parser runtime error: {}", e);
+                        if let Err(e) = ws_server.start_until(shutdown).await {
+                            error!("WebSocket server error: {}", e);
                         }
                     }
-                    .instrument(info_span!("vixen.parser", %program_id)),
-                ))
-            } else {
-                info!("Spec provided but no parser_setup configured - skipping Vixen runtime");
-                None
-            }
-        } else {
-            info!("No spec provided - running in websocket-only mode");
-            None
+                    .instrument(info_span!("ws.server", %bind_addr)),
+                ),
+            ));
+        }
+
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_config) = &self.config.grpc {
+            let grpc_server = crate::grpc::GrpcServer::new(
+                grpc_config.bind_address,
+                bus_manager.clone(),
+                entity_cache.clone(),
+                self.view_index.clone(),
+            );
+            let bind_addr = grpc_server.bind_addr();
+            let shutdown = self.shutdown.clone();
+            tasks.push((
+                "grpc server",
+                tokio::spawn(
+                    async move {
+                        if let Err(e) = grpc_server.start_until(shutdown).await {
+                            error!("gRPC server error: {}", e);
+                        }
+                    }
+                    .instrument(info_span!("grpc.server", %bind_addr)),
+                ),
+            ));
+        }
+
+        let shadow = self.shadow_deployment();
+        let shadow_diff = shadow.as_ref().map(|shadow| shadow.diff.clone());
+        let (parser_tx, shadow_tasks) = match shadow {
+            Some(shadow) => shadow.spawn(mutations_tx.clone()),
+            None => (mutations_tx.clone(), Vec::new()),
         };
+        auxiliary.extend(shadow_tasks.into_iter().map(|(_, task)| task));
+
+        let dictionaries = clients
+            .as_ref()
+            .and_then(ClientManager::dictionaries)
+            .cloned();
+        let mut stats = RuntimeStats::new(entity_cache.clone(), self.slots.clone());
+        if let Some(clients) = clients.clone() {
+            stats = stats.with_clients(clients);
+        }
+        if let Some(vm_stats) = self.spec.as_ref().and_then(|spec| spec.vm_stats.clone()) {
+            stats = stats.with_vm_stats(vm_stats);
+        }
+        let (replacements_tx, replacements) = mpsc::unbounded_channel();
+        self.reloader.attach(Attached {
+            entity_cache: entity_cache.clone(),
+            clients,
+            parsers: replacements_tx,
+            reconnection_config: self.config.reconnection.clone().unwrap_or_default(),
+            big_numbers: self.big_numbers.clone(),
+            clock: self.config.clock(),
+        });
+        tasks.push((
+            "parser runtime",
+            spawn_reloadable_parser(
+                self.parser_task(),
+                parser_tx,
+                self.parser_health_monitor(health_monitor.as_ref(), failed_events.clone()),
+                replacements,
+            ),
+        ));
 
         // Run the HTTP health server on a dedicated OS thread with its own single-threaded
         // tokio runtime. This isolates it from the main runtime so that liveness probes
         // always respond even when the event processing pipeline saturates worker threads
         // (e.g. due to std::sync::Mutex contention on VmContext under high throughput).
         let _http_health_handle = if let Some(http_health_config) = &self.config.http_health {
-            let mut http_server = HttpHealthServer::new(http_health_config.bind_address);
+            let mut http_server = HttpHealthServer::new(http_health_config.bind_address)
+                .with_flags(self.flags.clone())
+                .with_stats(stats);
             if let Some(monitor) = health_monitor.clone() {
                 http_server = http_server.with_health_monitor(monitor);
             }
+            if let Some(diff) = shadow_diff {
+                http_server = http_server.with_shadow_diff(diff);
+            }
+            if let Some(drain) = drain.clone() {
+                http_server = http_server.with_drain_controller(drain);
+            }
+            if let Some(dictionaries) = dictionaries {
+                http_server = http_server.with_dictionaries(dictionaries);
+            }
+            if let Some(failed_events) = failed_events {
+                http_server = http_server.with_failed_events(failed_events);
+            }
+            http_server = http_server.with_debug_bundles(self.debug_bundles(
+                entity_cache.clone(),
+                bus_manager.clone(),
+                dead_letters,
+            ));
 
             let bind_addr = http_health_config.bind_address;
+            let shutdown = self.shutdown.clone();
             let join_handle = std::thread::Builder::new()
                 .name("health-server".into())
                 .spawn(move || {
@@ -268,7 +842,7 @@ impl Runtime {
                         .expect("Failed to create health server runtime");
                     rt.block_on(async move {
                         let _span = info_span!("http.health", %bind_addr).entered();
-                        if let Err(e) = http_server.start().await {
+                        if let Err(e) = http_server.start_until(shutdown).await {
                             error!("HTTP health server error: {}", e);
                         }
                     });
@@ -283,79 +857,335 @@ impl Runtime {
             None
         };
 
-        let bus_cleanup_handle = {
-            let bus = bus_manager.clone();
-            tokio::spawn(
-                async move {
-                    let mut interval = tokio::time::interval(Duration::from_secs(60));
-                    loop {
-                        interval.tick().await;
-                        let state_cleaned = bus.cleanup_stale_state_buses().await;
-                        let list_cleaned = bus.cleanup_stale_list_buses().await;
-                        if state_cleaned > 0 || list_cleaned > 0 {
-                            let (state_count, list_count) = bus.bus_counts().await;
-                            info!(
-                                "Bus cleanup: removed {} state, {} list buses. Current: {} state, {} list",
-                                state_cleaned, list_cleaned, state_count, list_count
-                            );
-                        }
-                    }
-                }
-                .instrument(info_span!("bus.cleanup")),
-            )
-        };
+        tasks.push(("bus cleanup", spawn_bus_cleanup(bus_manager.clone())));
 
-        let stats_handle = {
-            let bus = bus_manager.clone();
-            let cache = entity_cache.clone();
-            tokio::spawn(
-                async move {
-                    let mut interval = tokio::time::interval(Duration::from_secs(30));
-                    loop {
-                        interval.tick().await;
-                        let (_state_buses, _list_buses) = bus.bus_counts().await;
-                        let _cache_stats = cache.stats().await;
-                    }
-                }
-                .instrument(info_span!("stats.reporter")),
-            )
-        };
+        tasks.push((
+            "stats reporter",
+            spawn_stats_reporter(bus_manager.clone(), entity_cache.clone()),
+        ));
 
-        info!("HyperStack runtime is running. Press Ctrl+C to stop.");
+        auxiliary.extend(
+            self.materialized_views
+                .clone()
+                .map(|views| spawn_view_refresh(views, entity_cache.clone())),
+        );
 
-        // Wait for any task to complete (or handle shutdown signals)
-        tokio::select! {
-            _ = async {
-                if let Some(handle) = ws_handle {
-                    handle.await
+        auxiliary.extend(load_shedder.map(|shedder| {
+            spawn_load_shedding(shedder, mutations_tx.downgrade(), health_monitor.clone())
+        }));
+
+        auxiliary.extend(webhooks.map(|webhooks| webhooks.spawn_delivery(health_monitor.clone())));
+
+        auxiliary.extend(persister.clone().map(Persister::spawn));
+
+        if handle_signals {
+            info!("HyperStack runtime is running. Press Ctrl+C to stop.");
+        } else {
+            info!("HyperStack runtime is running");
+        }
+
+        // Wait for a shutdown request, any task to complete, or a signal
+        let mut signal = None;
+        // Checked first: the listeners watch the same token, so they may
+        // already have exited by the time this is polled
+        let reason = tokio::select! {
+            biased;
+            _ = self.shutdown.cancelled() => StopReason::Requested,
+            name = wait_any(&mut tasks) => StopReason::TaskExited(name),
+            received = async {
+                if handle_signals {
+                    shutdown_signal().await
                 } else {
                     std::future::pending().await
                 }
             } => {
-                info!("WebSocket server task completed");
+                signal = Some(received);
+                StopReason::Signal
+            }
+        };
+        let stopped_at = Instant::now();
+        let mut graceful = true;
+
+        if let (Some(ShutdownSignal::Terminate), Some(grace), Some(drain)) =
+            (signal, self.drain_on_shutdown, &drain)
+        {
+            drain.start(grace);
+            let limit = grace + CLOSE_ACK_TIMEOUT + Duration::from_secs(1);
+            if tokio::time::timeout(limit, drain.drained()).await.is_err() {
+                graceful = false;
+                warn!(
+                    "{} WebSocket connections still open after drain",
+                    drain.connection_count()
+                );
             }
-            _ = projector_handle => {
-                info!("Projector task completed");
+        }
+
+        if reason == StopReason::Requested {
+            info!(
+                "Shutdown requested, stopping within {:?}",
+                self.shutdown_grace
+            );
+            let stop = stop_gracefully(&mut tasks, mutations_tx, drain.as_ref());
+            if tokio::time::timeout(self.shutdown_grace, stop)
+                .await
+                .is_err()
+            {
+                graceful = false;
+                warn!(
+                    "Shutdown took longer than {:?}, aborting the remaining tasks",
+                    self.shutdown_grace
+                );
             }
-            _ = async {
-                if let Some(handle) = parser_handle {
-                    handle.await
-                } else {
-                    std::future::pending().await
+        }
+
+        if let Some(persister) = &persister {
+            persister.save().await;
+        }
+
+        info!("Shutting down HyperStack runtime");
+        self.shutdown.cancel();
+        for (_, task) in tasks {
+            task.abort();
+        }
+        for task in auxiliary {
+            task.abort();
+        }
+
+        Ok(ShutdownReport {
+            reason,
+            graceful,
+            open_connections: drain.as_ref().map_or(0, DrainController::connection_count),
+            elapsed: stopped_at.elapsed(),
+        })
+    }
+}
+
+/// Stop the parser, publish the mutations already queued, then close the
+/// WebSocket clients. The listeners stop on their own once the shutdown
+/// token is cancelled.
+async fn stop_gracefully(
+    tasks: &mut Vec<(&'static str, JoinHandle<()>)>,
+    mutations_tx: mpsc::Sender<MutationBatch>,
+    drain: Option<&DrainController>,
+) {
+    finish(tasks, "websocket server").await;
+
+    if let Some((_, parser)) = tasks.iter().find(|(name, _)| *name == "parser runtime") {
+        parser.abort();
+    }
+    finish(tasks, "parser runtime").await;
+
+    // The projector exits once every sender is gone and the queue is empty
+    drop(mutations_tx);
+    finish(tasks, "projector").await;
+
+    if let Some(drain) = drain {
+        drain.start(Duration::ZERO);
+        drain.drained().await;
+    }
+}
+
+/// Wait for the task called `name` to exit, if it hasn't yet
+async fn finish(tasks: &mut Vec<(&'static str, JoinHandle<()>)>, name: &str) {
+    if let Some(index) = tasks.iter().position(|(task, _)| *task == name) {
+        let _ = (&mut tasks[index].1).await;
+        tasks.remove(index);
+    }
+}
+
+/// Run the parser of each of the task's programs, all publishing to
+/// `mutations_tx`.
+///
+/// Completes as soon as one of them does, stopping the others.
+pub(crate) fn spawn_parser(
+    parser: ParserTask,
+    mutations_tx: mpsc::Sender<MutationBatch>,
+    health_monitor: Option<HealthMonitor>,
+) -> JoinHandle<()> {
+    let ParserTask {
+        programs,
+        reconnection_config,
+    } = parser;
+
+    tokio::spawn(async move {
+        let program_ids: Vec<_> = programs
+            .iter()
+            .map(|program| program.program_id.clone())
+            .collect();
+        let monitors = match &health_monitor {
+            Some(monitor) => monitor
+                .track_programs(&program_ids)
+                .await
+                .into_iter()
+                .map(Some)
+                .collect(),
+            None => vec![None; programs.len()],
+        };
+
+        // Aborts the remaining parsers when dropped
+        let mut parsers = JoinSet::new();
+        for (program, health_monitor) in programs.into_iter().zip(monitors) {
+            let ProgramParser { program_id, setup } = program;
+            info!("Starting Vixen parser runtime for program: {}", program_id);
+            let mutations_tx = mutations_tx.clone();
+            let reconnection_config = reconnection_config.clone();
+            parsers.spawn(
+                async move {
+                    if let Err(e) = setup(mutations_tx, health_monitor, reconnection_config).await {
+                        error!("Vixen parser runtime error: {}", e);
+                    }
                 }
-            } => {
-                info!("Parser runtime task completed");
+                .instrument(info_span!("vixen.parser", %program_id)),
+            );
+        }
+        parsers.join_next().await;
+    })
+}
+
+/// Run `parser`, replacing it with each parser a reload sends.
+///
+/// Completes when the running parser does; without one it waits for the
+/// first replacement.
+pub(crate) fn spawn_reloadable_parser(
+    parser: Option<ParserTask>,
+    mutations_tx: mpsc::Sender<MutationBatch>,
+    health_monitor: Option<HealthMonitor>,
+    mut replacements: mpsc::UnboundedReceiver<ParserTask>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut running = parser.map(|parser| {
+            RunningParser(spawn_parser(
+                parser,
+                mutations_tx.clone(),
+                health_monitor.clone(),
+            ))
+        });
+        loop {
+            tokio::select! {
+                Some(parser) = replacements.recv() => {
+                    if running.take().is_some() {
+                        info!("Restarting the parser for a reloaded spec");
+                    }
+                    running = Some(RunningParser(spawn_parser(
+                        parser,
+                        mutations_tx.clone(),
+                        health_monitor.clone(),
+                    )));
+                }
+                _ = async {
+                    match running.as_mut() {
+                        Some(RunningParser(handle)) => {
+                            let _ = handle.await;
+                        }
+                        None => std::future::pending().await,
+                    }
+                } => return,
             }
-            _ = bus_cleanup_handle => {
-                info!("Bus cleanup task completed");
+        }
+    })
+}
+
+/// Aborts the parser when dropped, so aborting the task that runs it stops
+/// the parser and releases its mutation sender
+struct RunningParser(JoinHandle<()>);
+
+impl Drop for RunningParser {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+pub(crate) fn spawn_bus_cleanup(bus: BusManager) -> JoinHandle<()> {
+    tokio::spawn(
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let state_cleaned = bus.cleanup_stale_state_buses().await;
+                let list_cleaned = bus.cleanup_stale_list_buses().await;
+                if state_cleaned > 0 || list_cleaned > 0 {
+                    let (state_count, list_count) = bus.bus_counts().await;
+                    info!(
+                        "Bus cleanup: removed {} state, {} list buses. Current: {} state, {} list",
+                        state_cleaned, list_cleaned, state_count, list_count
+                    );
+                }
             }
-            _ = stats_handle => {
-                info!("Stats reporter task completed");
+        }
+        .instrument(info_span!("bus.cleanup")),
+    )
+}
+
+/// Re-evaluate the views that fell back from live maintenance
+pub(crate) fn spawn_view_refresh(
+    views: Arc<MaterializedViewRegistry>,
+    cache: EntityCache,
+) -> JoinHandle<()> {
+    tokio::spawn(
+        async move {
+            let mut interval = tokio::time::interval(views.refresh_interval());
+            loop {
+                interval.tick().await;
+                let refreshed = views.refresh(&cache).await;
+                if refreshed > 0 {
+                    debug!("Refreshed {} views without live maintenance", refreshed);
+                }
             }
-            _ = shutdown_signal() => {}
         }
+        .instrument(info_span!("view.refresh")),
+    )
+}
 
-        info!("Shutting down HyperStack runtime");
-        Ok(())
-    }
+/// Sample memory and mutation queue pressure for the load shedder
+pub(crate) fn spawn_load_shedding(
+    shedder: LoadShedder,
+    mutations_tx: mpsc::WeakSender<MutationBatch>,
+    health_monitor: Option<HealthMonitor>,
+) -> JoinHandle<()> {
+    tokio::spawn(
+        async move {
+            let mut interval = tokio::time::interval(shedder.config().sample_interval);
+            loop {
+                interval.tick().await;
+                let Some(tx) = mutations_tx.upgrade() else {
+                    break;
+                };
+                let queue_depth = tx.max_capacity() - tx.capacity();
+                drop(tx);
+
+                let Some(change) = shedder.sample(queue_depth) else {
+                    continue;
+                };
+                match change.health_note() {
+                    Some(note) => {
+                        warn!("Load shedding: {}", note);
+                        if let Some(monitor) = &health_monitor {
+                            monitor.set_note("load_shedding", note).await;
+                        }
+                    }
+                    None => {
+                        info!("Load shedding stopped (pressure {:.2})", change.pressure);
+                        if let Some(monitor) = &health_monitor {
+                            monitor.clear_note("load_shedding").await;
+                        }
+                    }
+                }
+            }
+        }
+        .instrument(info_span!("load.shedding")),
+    )
+}
+
+pub(crate) fn spawn_stats_reporter(bus: BusManager, cache: EntityCache) -> JoinHandle<()> {
+    tokio::spawn(
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(30));
+            loop {
+                interval.tick().await;
+                let (_state_buses, _list_buses) = bus.bus_counts().await;
+                let _cache_stats = cache.stats().await;
+            }
+        }
+        .instrument(info_span!("stats.reporter")),
+    )
 }
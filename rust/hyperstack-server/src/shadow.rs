@@ -0,0 +1,581 @@
+//! Shadow deployments: run a candidate spec next to production and diff its output.
+//!
+//! The shadow spec gets its own parser and VM, and therefore its own state
+//! tables. Its mutations only feed a [`ShadowDiff`] and never reach the
+//! projector, so clients always see production output.
+//!
+//! Both sides are folded into per-key entity state. A key is compared once
+//! both pipelines have processed the slot of its latest mutation, so a lagging
+//! shadow stream does not show up as divergence. The report is served at
+//! `/admin/shadow` and summarized in the logs every
+//! [`ShadowConfig::summary_interval`].
+
+use crate::mutation_batch::{merge_patch, MutationBatch, SlotContext};
+use crate::router::ParserTask;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{error, info, info_span, warn, Instrument};
+
+/// Field name used when an entity exists on only one side
+const ENTITY_PRESENCE_FIELD: &str = "(entity)";
+
+/// Number of diverging keys listed per entity in the report
+const MAX_REPORTED_KEYS: usize = 20;
+
+/// Configuration for shadow deployments
+#[derive(Clone, Debug)]
+pub struct ShadowConfig {
+    /// Maximum number of entity keys whose state is kept for diffing, across
+    /// both sides. Mutations for keys beyond the cap are counted but not diffed.
+    pub max_tracked_keys: usize,
+    /// Example values kept per diverging field
+    pub max_examples_per_field: usize,
+    /// How often the diff summary is logged
+    pub summary_interval: Duration,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            max_tracked_keys: 50_000,
+            max_examples_per_field: 5,
+            summary_interval: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadowSide {
+    Production,
+    Shadow,
+}
+
+impl ShadowSide {
+    fn index(self) -> usize {
+        match self {
+            ShadowSide::Production => 0,
+            ShadowSide::Shadow => 1,
+        }
+    }
+}
+
+/// Snapshot of the differences between production and shadow output
+#[derive(Clone, Debug, Serialize)]
+pub struct ShadowReport {
+    pub uptime_secs: u64,
+    pub tracked_keys: usize,
+    pub max_tracked_keys: usize,
+    pub untracked_mutations: u64,
+    pub entities: BTreeMap<String, EntityDiffReport>,
+}
+
+impl ShadowReport {
+    pub fn diverging_key_count(&self) -> usize {
+        self.entities
+            .values()
+            .map(|entity| entity.diverging_key_count)
+            .sum()
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct EntityDiffReport {
+    pub production_mutations: u64,
+    pub shadow_mutations: u64,
+    /// Shadow mutation count minus production mutation count
+    pub mutation_delta: i64,
+    pub diverging_key_count: usize,
+    /// Up to 20 diverging keys
+    pub diverging_keys: Vec<String>,
+    pub fields: BTreeMap<String, FieldDiffReport>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FieldDiffReport {
+    /// Number of keys currently diverging on this field
+    pub diverging_keys: usize,
+    /// Most recent differing values, newest last
+    pub examples: Vec<FieldExample>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FieldExample {
+    pub key: String,
+    pub production: Value,
+    pub shadow: Value,
+}
+
+/// Rolling diff between production and shadow mutations.
+///
+/// Cheap to clone; all clones share the same state.
+#[derive(Clone)]
+pub struct ShadowDiff {
+    inner: Arc<Mutex<DiffState>>,
+}
+
+struct DiffState {
+    config: ShadowConfig,
+    started_at: Instant,
+    watermarks: [Option<SlotContext>; 2],
+    entities: HashMap<String, EntityState>,
+    pending: BTreeMap<(u64, u64), Vec<(String, String)>>,
+    tracked_keys: usize,
+    untracked_mutations: u64,
+}
+
+#[derive(Default)]
+struct EntityState {
+    mutations: [u64; 2],
+    keys: HashMap<String, KeyState>,
+    fields: BTreeMap<String, FieldState>,
+    diverging: BTreeSet<String>,
+}
+
+#[derive(Default)]
+struct KeyState {
+    sides: [Option<Value>; 2],
+    /// Position of the newest mutation for this key on either side
+    latest_seq: Option<(u64, u64)>,
+    diverging_fields: BTreeSet<String>,
+}
+
+#[derive(Default)]
+struct FieldState {
+    diverging_keys: usize,
+    examples: VecDeque<FieldExample>,
+}
+
+impl ShadowDiff {
+    pub fn new(config: ShadowConfig) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(DiffState {
+                config,
+                started_at: Instant::now(),
+                watermarks: [None, None],
+                entities: HashMap::new(),
+                pending: BTreeMap::new(),
+                tracked_keys: 0,
+                untracked_mutations: 0,
+            })),
+        }
+    }
+
+    /// Fold a batch produced by one side into the diff.
+    pub fn record(&self, side: ShadowSide, batch: &MutationBatch) {
+        self.lock().record(side, batch);
+    }
+
+    pub fn report(&self) -> ShadowReport {
+        self.lock().report()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DiffState> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl DiffState {
+    fn record(&mut self, side: ShadowSide, batch: &MutationBatch) {
+        let index = side.index();
+        let seq = batch.slot_context.map(|ctx| (ctx.slot, ctx.slot_index));
+
+        for mutation in &batch.mutations {
+            let key = key_string(&mutation.key);
+            let is_new_key = !self
+                .entities
+                .get(&mutation.export)
+                .is_some_and(|entity| entity.keys.contains_key(&key));
+            let at_capacity = self.tracked_keys >= self.config.max_tracked_keys;
+
+            let entity = self.entities.entry(mutation.export.clone()).or_default();
+            entity.mutations[index] += 1;
+
+            if is_new_key && at_capacity {
+                self.untracked_mutations += 1;
+                continue;
+            }
+            if is_new_key {
+                self.tracked_keys += 1;
+            }
+
+            let state = entity.keys.entry(key.clone()).or_default();
+            let current =
+                state.sides[index].get_or_insert_with(|| Value::Object(Default::default()));
+            merge_patch(current, &mutation.patch, &mutation.append);
+            state.latest_seq = state.latest_seq.max(seq);
+
+            match seq {
+                Some(seq) => self
+                    .pending
+                    .entry(seq)
+                    .or_default()
+                    .push((mutation.export.clone(), key)),
+                None => self.compare(&mutation.export.clone(), &key),
+            }
+        }
+
+        if let Some(ctx) = batch.slot_context {
+            let watermark = &mut self.watermarks[index];
+            if watermark.is_none_or(|current| {
+                (ctx.slot, ctx.slot_index) > (current.slot, current.slot_index)
+            }) {
+                *watermark = Some(ctx);
+            }
+        }
+
+        self.settle();
+    }
+
+    /// Compare every key whose latest mutation both sides have processed.
+    fn settle(&mut self) {
+        let [Some(production), Some(shadow)] = self.watermarks else {
+            return;
+        };
+        let settled_through =
+            (production.slot, production.slot_index).min((shadow.slot, shadow.slot_index));
+
+        while let Some(entry) = self.pending.first_entry() {
+            if *entry.key() > settled_through {
+                break;
+            }
+            for (entity, key) in entry.remove() {
+                // Skip keys with newer mutations; they are compared once those settle
+                let latest_seq = self
+                    .entities
+                    .get(&entity)
+                    .and_then(|entity| entity.keys.get(&key))
+                    .and_then(|state| state.latest_seq);
+                if latest_seq.is_none_or(|seq| seq <= settled_through) {
+                    self.compare(&entity, &key);
+                }
+            }
+        }
+    }
+
+    fn compare(&mut self, entity_name: &str, key: &str) {
+        let max_examples = self.config.max_examples_per_field;
+        let Some(entity) = self.entities.get_mut(entity_name) else {
+            return;
+        };
+        let Some(state) = entity.keys.get_mut(key) else {
+            return;
+        };
+
+        let mut differing = BTreeMap::new();
+        match (&state.sides[0], &state.sides[1]) {
+            (Some(production), Some(shadow)) => {
+                diff_values(production, shadow, "", &mut differing);
+            }
+            (production, shadow) => {
+                differing.insert(
+                    ENTITY_PRESENCE_FIELD.to_string(),
+                    (
+                        production.clone().unwrap_or(Value::Null),
+                        shadow.clone().unwrap_or(Value::Null),
+                    ),
+                );
+            }
+        }
+
+        let now_diverging: BTreeSet<String> = differing.keys().cloned().collect();
+        for field in state.diverging_fields.difference(&now_diverging) {
+            if let Some(field_state) = entity.fields.get_mut(field) {
+                field_state.diverging_keys = field_state.diverging_keys.saturating_sub(1);
+            }
+        }
+        for (field, (production, shadow)) in differing {
+            let field_state = entity.fields.entry(field.clone()).or_default();
+            if !state.diverging_fields.contains(&field) {
+                field_state.diverging_keys += 1;
+            }
+            field_state.examples.push_back(FieldExample {
+                key: key.to_string(),
+                production,
+                shadow,
+            });
+            while field_state.examples.len() > max_examples {
+                field_state.examples.pop_front();
+            }
+        }
+
+        if now_diverging.is_empty() {
+            entity.diverging.remove(key);
+        } else {
+            entity.diverging.insert(key.to_string());
+        }
+        state.diverging_fields = now_diverging;
+    }
+
+    fn report(&self) -> ShadowReport {
+        let entities = self
+            .entities
+            .iter()
+            .map(|(name, entity)| {
+                let fields = entity
+                    .fields
+                    .iter()
+                    .filter(|(_, field)| field.diverging_keys > 0)
+                    .map(|(path, field)| {
+                        (
+                            path.clone(),
+                            FieldDiffReport {
+                                diverging_keys: field.diverging_keys,
+                                examples: field.examples.iter().cloned().collect(),
+                            },
+                        )
+                    })
+                    .collect();
+                let report = EntityDiffReport {
+                    production_mutations: entity.mutations[0],
+                    shadow_mutations: entity.mutations[1],
+                    mutation_delta: entity.mutations[1] as i64 - entity.mutations[0] as i64,
+                    diverging_key_count: entity.diverging.len(),
+                    diverging_keys: entity
+                        .diverging
+                        .iter()
+                        .take(MAX_REPORTED_KEYS)
+                        .cloned()
+                        .collect(),
+                    fields,
+                };
+                (name.clone(), report)
+            })
+            .collect();
+
+        ShadowReport {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            tracked_keys: self.tracked_keys,
+            max_tracked_keys: self.config.max_tracked_keys,
+            untracked_mutations: self.untracked_mutations,
+            entities,
+        }
+    }
+}
+
+fn key_string(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Collect leaf paths whose values differ. Arrays are compared as a whole.
+fn diff_values(
+    production: &Value,
+    shadow: &Value,
+    path: &str,
+    differing: &mut BTreeMap<String, (Value, Value)>,
+) {
+    if let (Value::Object(production_map), Value::Object(shadow_map)) = (production, shadow) {
+        let keys: BTreeSet<&String> = production_map.keys().chain(shadow_map.keys()).collect();
+        for key in keys {
+            let child_path = join_path(path, key);
+            match (production_map.get(key), shadow_map.get(key)) {
+                (Some(p), Some(s)) => diff_values(p, s, &child_path, differing),
+                (p, s) => {
+                    differing.insert(
+                        child_path,
+                        (
+                            p.cloned().unwrap_or(Value::Null),
+                            s.cloned().unwrap_or(Value::Null),
+                        ),
+                    );
+                }
+            }
+        }
+    } else if production != shadow {
+        differing.insert(path.to_string(), (production.clone(), shadow.clone()));
+    }
+}
+
+fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+/// Shadow pipeline of a runtime, not yet started.
+pub(crate) struct ShadowDeployment {
+    pub(crate) parser: Option<ParserTask>,
+    pub(crate) diff: ShadowDiff,
+    pub(crate) summary_interval: Duration,
+}
+
+impl ShadowDeployment {
+    /// Spawn the shadow parser, the production tap, and the summary logger.
+    ///
+    /// Returns the sender the production parser should publish to; batches
+    /// sent there are recorded and then forwarded to `mutations_tx`.
+    pub(crate) fn spawn(
+        self,
+        mutations_tx: mpsc::Sender<MutationBatch>,
+    ) -> (
+        mpsc::Sender<MutationBatch>,
+        Vec<(&'static str, JoinHandle<()>)>,
+    ) {
+        let mut tasks = Vec::new();
+
+        let (production_tx, production_rx) = mpsc::channel::<MutationBatch>(1024);
+        tasks.push((
+            "shadow production tap",
+            spawn_production_tap(self.diff.clone(), production_rx, mutations_tx),
+        ));
+
+        if let Some(parser) = self.parser {
+            let (shadow_tx, shadow_rx) = mpsc::channel::<MutationBatch>(1024);
+            tasks.push((
+                "shadow parser runtime",
+                crate::runtime::spawn_parser(parser, shadow_tx, None),
+            ));
+            tasks.push((
+                "shadow recorder",
+                spawn_shadow_recorder(self.diff.clone(), shadow_rx),
+            ));
+        } else {
+            warn!("Shadow spec has no parser_setup - shadow output will stay empty");
+        }
+
+        tasks.push((
+            "shadow summary",
+            spawn_summary_logger(self.diff, self.summary_interval),
+        ));
+
+        (production_tx, tasks)
+    }
+}
+
+fn spawn_production_tap(
+    diff: ShadowDiff,
+    mut production_rx: mpsc::Receiver<MutationBatch>,
+    mutations_tx: mpsc::Sender<MutationBatch>,
+) -> JoinHandle<()> {
+    tokio::spawn(
+        async move {
+            while let Some(batch) = production_rx.recv().await {
+                diff.record(ShadowSide::Production, &batch);
+                if mutations_tx.send(batch).await.is_err() {
+                    error!("Projector channel closed, stopping shadow production tap");
+                    break;
+                }
+            }
+        }
+        .instrument(info_span!("shadow.tap")),
+    )
+}
+
+fn spawn_shadow_recorder(
+    diff: ShadowDiff,
+    mut shadow_rx: mpsc::Receiver<MutationBatch>,
+) -> JoinHandle<()> {
+    tokio::spawn(
+        async move {
+            while let Some(batch) = shadow_rx.recv().await {
+                diff.record(ShadowSide::Shadow, &batch);
+            }
+        }
+        .instrument(info_span!("shadow.recorder")),
+    )
+}
+
+fn spawn_summary_logger(diff: ShadowDiff, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(
+        async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                log_summary(&diff.report());
+            }
+        }
+        .instrument(info_span!("shadow.summary")),
+    )
+}
+
+fn log_summary(report: &ShadowReport) {
+    info!(
+        diverging_keys = report.diverging_key_count(),
+        tracked_keys = report.tracked_keys,
+        untracked_mutations = report.untracked_mutations,
+        "Shadow deployment diff summary"
+    );
+    for (entity, diff) in &report.entities {
+        if diff.diverging_key_count == 0 && diff.mutation_delta == 0 {
+            continue;
+        }
+        let fields: Vec<&str> = diff.fields.keys().map(String::as_str).collect();
+        warn!(
+            entity = %entity,
+            diverging_keys = diff.diverging_key_count,
+            mutation_delta = diff.mutation_delta,
+            fields = ?fields,
+            "Shadow output diverges from production"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperstack_interpreter::Mutation;
+    use serde_json::json;
+    use smallvec::smallvec;
+
+    fn batch(slot: u64, key: &str, patch: Value) -> MutationBatch {
+        MutationBatch::with_slot_context(
+            smallvec![Mutation::new("Token", json!(key), patch)],
+            SlotContext::new(slot, 0),
+        )
+    }
+
+    #[test]
+    fn waits_for_lagging_side_before_comparing() {
+        let diff = ShadowDiff::new(ShadowConfig::default());
+
+        diff.record(ShadowSide::Production, &batch(1, "a", json!({"price": 1})));
+        diff.record(ShadowSide::Production, &batch(2, "a", json!({"price": 2})));
+        diff.record(ShadowSide::Shadow, &batch(1, "a", json!({"price": 1})));
+        assert_eq!(diff.report().diverging_key_count(), 0);
+
+        diff.record(ShadowSide::Shadow, &batch(2, "a", json!({"price": 3})));
+        let report = diff.report();
+        let token = &report.entities["Token"];
+        assert_eq!(token.diverging_keys, vec!["a".to_string()]);
+        let price = &token.fields["price"];
+        assert_eq!(price.diverging_keys, 1);
+        assert_eq!(price.examples[0].production, json!(2));
+        assert_eq!(price.examples[0].shadow, json!(3));
+
+        diff.record(ShadowSide::Production, &batch(3, "a", json!({"price": 4})));
+        diff.record(ShadowSide::Shadow, &batch(3, "a", json!({"price": 4})));
+        let report = diff.report();
+        assert_eq!(report.diverging_key_count(), 0);
+        assert!(report.entities["Token"].fields.is_empty());
+    }
+
+    #[test]
+    fn caps_tracked_keys() {
+        let diff = ShadowDiff::new(ShadowConfig {
+            max_tracked_keys: 1,
+            ..ShadowConfig::default()
+        });
+
+        diff.record(ShadowSide::Production, &batch(1, "a", json!({"price": 1})));
+        diff.record(ShadowSide::Production, &batch(1, "b", json!({"price": 1})));
+        diff.record(ShadowSide::Shadow, &batch(1, "b", json!({"price": 2})));
+
+        let report = diff.report();
+        assert_eq!(report.tracked_keys, 1);
+        assert_eq!(report.untracked_mutations, 2);
+        assert_eq!(report.entities["Token"].production_mutations, 2);
+        assert_eq!(report.entities["Token"].mutation_delta, -1);
+    }
+}
@@ -0,0 +1,95 @@
+//! Programmatic shutdown for a server started with
+//! [`ServerBuilder::start_with_shutdown`](crate::ServerBuilder::start_with_shutdown).
+//!
+//! [`ServerHandle::shutdown`] stops the server in order:
+//!
+//! - The WebSocket and HTTP health listeners stop accepting connections.
+//! - The parser stops, which ends the Yellowstone stream.
+//! - The projector publishes every mutation already queued, then exits.
+//! - Connected clients are drained with no grace period: a `migrate_soon`
+//!   notice, then a `1001 Going Away` close frame (see the
+//!   [`drain`](crate::drain) module).
+//! - The remaining background tasks stop.
+//!
+//! All of it shares one grace period, [`DEFAULT_SHUTDOWN_GRACE`] unless set
+//! with [`ServerBuilder::shutdown_grace`](crate::ServerBuilder::shutdown_grace).
+//! Whatever is still running when it ends is aborted, and the
+//! [`ShutdownReport`] says so.
+//!
+//! A server started this way doesn't listen for SIGINT or SIGTERM; the
+//! application decides when it stops.
+
+use anyhow::Result;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Default time a shutdown may take before remaining tasks are aborted
+pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// Why the runtime stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// [`ServerHandle::shutdown`] was called or its token cancelled
+    Requested,
+    /// SIGINT or SIGTERM, for a runtime started with [`Runtime::run`](crate::Runtime::run)
+    Signal,
+    /// A background task exited on its own, e.g. the WebSocket listener
+    /// failed to bind
+    TaskExited(&'static str),
+}
+
+/// How a runtime stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownReport {
+    pub reason: StopReason,
+    /// Whether every task stopped within the grace period. When false, the
+    /// tasks still running were aborted.
+    pub graceful: bool,
+    /// WebSocket connections still open when the runtime stopped
+    pub open_connections: usize,
+    /// Time from the stop to the last task exiting
+    pub elapsed: Duration,
+}
+
+/// Handle to a server started with
+/// [`ServerBuilder::start_with_shutdown`](crate::ServerBuilder::start_with_shutdown).
+///
+/// Dropping the handle leaves the server running. See the
+/// [module docs](self).
+pub struct ServerHandle {
+    shutdown: CancellationToken,
+    task: JoinHandle<Result<ShutdownReport>>,
+}
+
+impl ServerHandle {
+    pub(crate) fn new(
+        shutdown: CancellationToken,
+        task: JoinHandle<Result<ShutdownReport>>,
+    ) -> Self {
+        Self { shutdown, task }
+    }
+
+    /// Token that stops the server when cancelled, for stopping it from
+    /// another task while this one waits
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Stop the server and wait until it has stopped
+    pub async fn shutdown(self) -> Result<ShutdownReport> {
+        self.shutdown.cancel();
+        self.wait().await
+    }
+
+    /// Wait until the server stops, through [`ServerHandle::shutdown`], its
+    /// token, or a background task exiting
+    pub async fn wait(self) -> Result<ShutdownReport> {
+        self.task.await?
+    }
+
+    /// Returns true once the server has stopped
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
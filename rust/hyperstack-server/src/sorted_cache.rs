@@ -237,7 +237,10 @@ impl SortedViewCache {
         match (base, patch) {
             (Value::Object(mut base_map), Value::Object(patch_map)) => {
                 for (key, patch_value) in patch_map {
-                    if let Some(base_value) = base_map.remove(&key) {
+                    // A null clears the field, as in the entity cache
+                    if patch_value.is_null() {
+                        base_map.remove(&key);
+                    } else if let Some(base_value) = base_map.remove(&key) {
                         base_map.insert(key, Self::deep_merge(base_value, patch_value));
                     } else {
                         base_map.insert(key, patch_value);
@@ -0,0 +1,351 @@
+//! Consuming Yellowstone from several endpoints.
+//!
+//! [`run_sources`] keeps the configured endpoints connected according to the
+//! [`YellowstoneStrategy`]:
+//!
+//! - `Failover` streams from one endpoint at a time. When the stream ends it
+//!   moves straight on to the next endpoint. It only backs off once every
+//!   endpoint has been tried.
+//! - `Race` streams from every endpoint at once, each with its own reconnect
+//!   loop. Every connection gets a [`SourceDedup`], which the handler checks
+//!   before an update reaches the VM. An update already delivered by another
+//!   endpoint is dropped.
+//!
+//! Each endpoint's status is reported through
+//! [`HealthMonitor::record_endpoint_status`]. The overall stream counts as
+//! connected while at least one endpoint is.
+
+use crate::config::{
+    ReconnectionConfig, YellowstoneConfig, YellowstoneEndpoint, YellowstoneStrategy,
+};
+use crate::health::{HealthMonitor, StreamStatus};
+use futures_util::future::join_all;
+use lru::LruCache;
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info, warn};
+
+/// Signatures per connection whose instruction count is remembered
+const MAX_TRACKED_SIGNATURES: usize = 10_000;
+
+/// A set that only remembers its most recent `capacity` keys
+#[derive(Debug)]
+pub struct RecentSet<K> {
+    capacity: usize,
+    order: VecDeque<K>,
+    seen: HashSet<K>,
+}
+
+impl<K: Hash + Eq + Clone> RecentSet<K> {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            seen: HashSet::with_capacity(capacity),
+        }
+    }
+
+    /// Insert `key` and return whether it was new.
+    ///
+    /// Once full, the oldest key is forgotten to make room.
+    pub fn insert(&mut self, key: K) -> bool {
+        if self.seen.contains(&key) {
+            return false;
+        }
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.seen.insert(key);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+/// `(pubkey, slot, write_version)`
+type AccountKey = (Vec<u8>, u64, u64);
+/// `(signature, index)`
+type InstructionKey = (Vec<u8>, u32);
+
+/// Updates already delivered by any of the raced endpoints.
+///
+/// Account updates are keyed by `(pubkey, slot, write_version)`, and
+/// instructions by `(signature, index)`.
+#[derive(Clone)]
+pub struct UpdateDeduper {
+    accounts: Arc<Mutex<RecentSet<AccountKey>>>,
+    instructions: Arc<Mutex<RecentSet<InstructionKey>>>,
+}
+
+impl UpdateDeduper {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            accounts: Arc::new(Mutex::new(RecentSet::new(capacity))),
+            instructions: Arc::new(Mutex::new(RecentSet::new(capacity))),
+        }
+    }
+
+    /// Whether this account write hasn't been seen before
+    pub fn account(&self, pubkey: &[u8], slot: u64, write_version: u64) -> bool {
+        self.accounts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert((pubkey.to_vec(), slot, write_version))
+    }
+
+    /// Whether the instruction at `index` within its transaction hasn't been
+    /// seen before
+    pub fn instruction(&self, signature: &[u8], index: u32) -> bool {
+        self.instructions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert((signature.to_vec(), index))
+    }
+
+    /// A view for one connection, which numbers that connection's instructions
+    pub fn source(&self) -> SourceDedup {
+        SourceDedup {
+            shared: self.clone(),
+            instruction_counts: Arc::new(Mutex::new(LruCache::new(
+                NonZeroUsize::new(MAX_TRACKED_SIGNATURES).expect("non-zero capacity"),
+            ))),
+        }
+    }
+}
+
+/// Deduplication for the updates of a single connection.
+///
+/// Instruction updates don't carry their position in the transaction. Every
+/// endpoint delivers a transaction's instructions in the same order, though,
+/// so this view counts the instructions seen per signature and uses the count
+/// as the index. A fresh view is made for each connection, so a reconnect that
+/// replays a transaction numbers it from zero again.
+#[derive(Clone)]
+pub struct SourceDedup {
+    shared: UpdateDeduper,
+    instruction_counts: Arc<Mutex<LruCache<Vec<u8>, u32>>>,
+}
+
+impl SourceDedup {
+    /// Whether this account write hasn't been delivered by any endpoint yet
+    pub fn account(&self, pubkey: &[u8], slot: u64, write_version: u64) -> bool {
+        self.shared.account(pubkey, slot, write_version)
+    }
+
+    /// Whether the next instruction of this transaction hasn't been delivered
+    /// by any endpoint yet
+    pub fn instruction(&self, signature: &[u8]) -> bool {
+        let index = {
+            let mut counts = self
+                .instruction_counts
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            let count = counts.get_or_insert_mut(signature.to_vec(), || 0);
+            let index = *count;
+            *count += 1;
+            index
+        };
+        self.shared.instruction(signature, index)
+    }
+}
+
+/// Keep the configured endpoints connected until they give up.
+///
+/// `connect` runs one connection to the given endpoint and returns when its
+/// stream ends. It receives a [`SourceDedup`] when racing and `None` otherwise.
+/// An error is returned once the reconnection attempts of every endpoint are
+/// exhausted.
+pub async fn run_sources<F, Fut>(
+    config: &YellowstoneConfig,
+    reconnection: &ReconnectionConfig,
+    health: Option<HealthMonitor>,
+    connect: F,
+) -> anyhow::Result<()>
+where
+    F: Fn(YellowstoneEndpoint, Option<SourceDedup>) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    if config.endpoints.is_empty() {
+        return Err(anyhow::anyhow!("No Yellowstone endpoints configured"));
+    }
+
+    let connected = AtomicUsize::new(0);
+    let supervisor = Supervisor {
+        reconnection,
+        health: health.as_ref(),
+        connected: &connected,
+        connect: &connect,
+    };
+
+    let result = match config.strategy {
+        YellowstoneStrategy::Failover => supervisor.run(&config.endpoints, None).await,
+        YellowstoneStrategy::Race => {
+            let dedup = UpdateDeduper::new(config.dedup_capacity);
+            info!(
+                "Racing {} Yellowstone endpoints with deduplication",
+                config.endpoints.len()
+            );
+            join_all(
+                config
+                    .endpoints
+                    .iter()
+                    .map(|endpoint| supervisor.run(std::slice::from_ref(endpoint), Some(&dedup))),
+            )
+            .await
+            .into_iter()
+            .collect::<anyhow::Result<()>>()
+        }
+    };
+
+    if let (Err(e), Some(health)) = (&result, &health) {
+        health.record_error(e.to_string()).await;
+    }
+    result
+}
+
+struct Supervisor<'a, F> {
+    reconnection: &'a ReconnectionConfig,
+    health: Option<&'a HealthMonitor>,
+    /// Endpoints currently streaming, across every loop
+    connected: &'a AtomicUsize,
+    connect: &'a F,
+}
+
+impl<F, Fut> Supervisor<'_, F>
+where
+    F: Fn(YellowstoneEndpoint, Option<SourceDedup>) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    /// Connect to `endpoints` in turn until the reconnection attempts run out
+    async fn run(
+        &self,
+        endpoints: &[YellowstoneEndpoint],
+        dedup: Option<&UpdateDeduper>,
+    ) -> anyhow::Result<()> {
+        let mut index = 0;
+        let mut attempt = 0u32;
+        let mut backoff = self.reconnection.initial_delay;
+
+        loop {
+            let endpoint = &endpoints[index];
+            let name = endpoint.endpoint.as_str();
+
+            if let Some(health) = self.health {
+                health
+                    .record_endpoint_status(name, StreamStatus::Reconnecting)
+                    .await;
+                if self.connected.load(Ordering::SeqCst) == 0 {
+                    health.record_reconnecting().await;
+                }
+            }
+
+            if self.connected.fetch_add(1, Ordering::SeqCst) == 0 {
+                if let Some(health) = self.health {
+                    health.record_connection().await;
+                }
+            }
+            if let Some(health) = self.health {
+                health
+                    .record_endpoint_status(name, StreamStatus::Connected)
+                    .await;
+            }
+
+            info!("Streaming from Yellowstone endpoint {}", name);
+            let result = (self.connect)(endpoint.clone(), dedup.map(UpdateDeduper::source)).await;
+
+            let endpoint_status = match result {
+                Ok(()) => StreamStatus::Disconnected,
+                Err(e) => {
+                    error!("Yellowstone endpoint {} failed: {:?}", name, e);
+                    StreamStatus::Error(e.to_string())
+                }
+            };
+            if let Some(health) = self.health {
+                health.record_endpoint_status(name, endpoint_status).await;
+            }
+            let last_connected = self.connected.fetch_sub(1, Ordering::SeqCst) == 1;
+
+            attempt += 1;
+
+            if let Some(max) = self.reconnection.max_attempts {
+                if attempt >= max {
+                    error!(
+                        "Max reconnection attempts ({}) reached for {}, giving up",
+                        max, name
+                    );
+                    if let (Some(health), true) = (self.health, last_connected) {
+                        health.record_disconnection().await;
+                    }
+                    return Err(anyhow::anyhow!("Max reconnection attempts reached"));
+                }
+            }
+
+            if let (Some(health), true) = (self.health, last_connected) {
+                health.record_disconnection().await;
+            }
+
+            index = (index + 1) % endpoints.len();
+            if index == 0 {
+                warn!(
+                    "gRPC stream disconnected. Reconnecting in {:?} (attempt {})",
+                    backoff, attempt
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = self.reconnection.next_backoff(backoff);
+            } else {
+                warn!(
+                    "gRPC stream from {} disconnected. Failing over to {}",
+                    name, endpoints[index].endpoint
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_set_forgets_the_oldest_key_once_full() {
+        let mut set = RecentSet::new(2);
+        assert!(set.insert(1));
+        assert!(set.insert(2));
+        assert!(!set.insert(1));
+        assert!(set.insert(3));
+        assert_eq!(set.len(), 2);
+        assert!(set.insert(1), "1 was evicted by 3");
+        assert!(!set.insert(3));
+    }
+
+    #[test]
+    fn instructions_are_numbered_per_connection() {
+        let dedup = UpdateDeduper::new(16);
+        let first = dedup.source();
+        let second = dedup.source();
+
+        assert!(first.instruction(b"sig"));
+        assert!(first.instruction(b"sig"));
+        assert!(!second.instruction(b"sig"));
+        assert!(!second.instruction(b"sig"));
+        assert!(second.instruction(b"sig"), "third instruction is new");
+
+        assert!(first.account(b"pubkey", 10, 1));
+        assert!(!second.account(b"pubkey", 10, 1));
+        assert!(second.account(b"pubkey", 10, 2));
+    }
+}
@@ -0,0 +1,345 @@
+//! Publishing the mutations of one transaction together.
+//!
+//! A transaction with several instructions for the program reaches the VM
+//! handler as one instruction update per instruction, and each used to be
+//! published as its own [`MutationBatch`], so clients could render the state
+//! between two instructions of the same transaction. [`TransactionGrouper`]
+//! collects the mutations of a transaction signature and sends them as one
+//! batch, in the order the instructions were handled, with an
+//! [`EventContext`] listing every instruction type involved.
+//!
+//! Instructions of a transaction arrive one after the other, so a group is
+//! sent as soon as an instruction of another transaction arrives. Otherwise
+//! it is sent once [`TransactionGroupConfig::window`] has passed since its
+//! first instruction, so a transaction whose last instruction is never
+//! followed by another doesn't hold its mutations back.
+
+use crate::mutation_batch::{EventContext, MutationBatch, SlotContext};
+use hyperstack_interpreter::Mutation;
+use smallvec::SmallVec;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Configuration for [`TransactionGrouper`]
+#[derive(Debug, Clone)]
+pub struct TransactionGroupConfig {
+    /// Longest a transaction's mutations are held for its later
+    /// instructions. Zero sends every instruction's mutations on their own.
+    pub window: Duration,
+}
+
+impl Default for TransactionGroupConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(5),
+        }
+    }
+}
+
+impl TransactionGroupConfig {
+    pub fn new(window: Duration) -> Self {
+        Self { window }
+    }
+
+    /// Read the window from `HYPERSTACK_TXN_GROUP_WINDOW_MS`, falling back
+    /// to the default
+    pub fn from_env() -> Self {
+        std::env::var("HYPERSTACK_TXN_GROUP_WINDOW_MS")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .map(|ms| Self::new(Duration::from_millis(ms)))
+            .unwrap_or_default()
+    }
+}
+
+/// One instruction's mutations, as handed to [`TransactionGrouper::add`]
+pub struct InstructionMutations {
+    pub signature: String,
+    pub slot: u64,
+    pub txn_index: u64,
+    pub event_context: EventContext,
+    pub mutations: Vec<Mutation>,
+}
+
+/// The transaction whose instructions are being collected
+struct OpenGroup {
+    signature: String,
+    /// Tells this group from a later one of the same signature
+    generation: u64,
+    slot_context: SlotContext,
+    event_context: EventContext,
+    mutations: Vec<Mutation>,
+}
+
+impl OpenGroup {
+    fn into_batch(self) -> Option<MutationBatch> {
+        if self.mutations.is_empty() {
+            return None;
+        }
+        let batch =
+            MutationBatch::with_slot_context(SmallVec::from_vec(self.mutations), self.slot_context);
+        Some(batch.with_event_context(self.event_context))
+    }
+}
+
+struct Inner {
+    config: TransactionGroupConfig,
+    mutations_tx: mpsc::Sender<MutationBatch>,
+    open: Mutex<(Option<OpenGroup>, u64)>,
+}
+
+/// Collects the mutations of each transaction's instructions into one batch.
+/// Clones share the open group.
+#[derive(Clone)]
+pub struct TransactionGrouper {
+    inner: Arc<Inner>,
+}
+
+impl TransactionGrouper {
+    pub fn new(mutations_tx: mpsc::Sender<MutationBatch>, config: TransactionGroupConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                mutations_tx,
+                open: Mutex::new((None, 0)),
+            }),
+        }
+    }
+
+    /// Add an instruction's mutations to its transaction's batch, sending
+    /// the batch of the previous transaction first
+    pub async fn add(&self, instruction: InstructionMutations) {
+        if self.inner.config.window.is_zero() {
+            let group = Self::open(instruction, 0);
+            self.send(group).await;
+            return;
+        }
+
+        let previous = {
+            let mut open = self.lock();
+            let (group, generation) = &mut *open;
+            match group {
+                Some(group) if group.signature == instruction.signature => {
+                    let context = &mut group.event_context;
+                    context
+                        .instructions
+                        .push(instruction.event_context.event_type);
+                    context.accounts_count = match (
+                        context.accounts_count,
+                        instruction.event_context.accounts_count,
+                    ) {
+                        (Some(total), Some(count)) => Some(total + count),
+                        (total, count) => total.or(count),
+                    };
+                    group.mutations.extend(instruction.mutations);
+                    return;
+                }
+                _ => {
+                    *generation += 1;
+                    self.flush_after_window(*generation);
+                    group.replace(Self::open(instruction, *generation))
+                }
+            }
+        };
+
+        if let Some(previous) = previous {
+            self.send(previous).await;
+        }
+    }
+
+    /// Send the open group, if any
+    pub async fn flush(&self) {
+        let group = self.lock().0.take();
+        if let Some(group) = group {
+            self.send(group).await;
+        }
+    }
+
+    fn open(instruction: InstructionMutations, generation: u64) -> OpenGroup {
+        let mut event_context = instruction.event_context;
+        event_context.instructions = vec![event_context.event_type.clone()];
+        OpenGroup {
+            signature: instruction.signature,
+            generation,
+            slot_context: SlotContext::new(instruction.slot, instruction.txn_index),
+            event_context,
+            mutations: instruction.mutations,
+        }
+    }
+
+    /// Send the group of `generation` once the window has passed, unless an
+    /// instruction of another transaction sent it already
+    fn flush_after_window(&self, generation: u64) {
+        let grouper = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(grouper.inner.config.window).await;
+            let group = {
+                let mut open = grouper.lock();
+                match &open.0 {
+                    Some(group) if group.generation == generation => open.0.take(),
+                    _ => None,
+                }
+            };
+            if let Some(group) = group {
+                grouper.send(group).await;
+            }
+        });
+    }
+
+    async fn send(&self, group: OpenGroup) {
+        if let Some(batch) = group.into_batch() {
+            let _ = self.inner.mutations_tx.send(batch).await;
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, (Option<OpenGroup>, u64)> {
+        self.inner.open.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn mutation(key: &str, patch: serde_json::Value) -> Mutation {
+        Mutation::new("Round", json!(key), patch)
+    }
+
+    fn instruction(
+        signature: &str,
+        event_type: &str,
+        mutations: Vec<Mutation>,
+    ) -> InstructionMutations {
+        InstructionMutations {
+            signature: signature.to_string(),
+            slot: 100,
+            txn_index: 7,
+            event_context: EventContext {
+                program: "ore".to_string(),
+                event_kind: "instruction".to_string(),
+                event_type: event_type.to_string(),
+                account: None,
+                accounts_count: Some(3),
+                instructions: Vec::new(),
+            },
+            mutations,
+        }
+    }
+
+    fn grouper(window: Duration) -> (TransactionGrouper, mpsc::Receiver<MutationBatch>) {
+        let (tx, rx) = mpsc::channel(16);
+        (
+            TransactionGrouper::new(tx, TransactionGroupConfig::new(window)),
+            rx,
+        )
+    }
+
+    /// A transaction deploying to a round, checkpointing it and claiming
+    fn transaction(signature: &str) -> Vec<InstructionMutations> {
+        vec![
+            instruction(
+                signature,
+                "ore::DeployIxState",
+                vec![mutation("round-1", json!({ "deployed": 10 }))],
+            ),
+            instruction(
+                signature,
+                "ore::CheckpointIxState",
+                vec![
+                    mutation("round-1", json!({ "checkpointed": true })),
+                    mutation("round-2", json!({ "id": 2 })),
+                ],
+            ),
+            instruction(signature, "ore::LogIxState", vec![]),
+            instruction(
+                signature,
+                "ore::ClaimIxState",
+                vec![mutation("round-1", json!({ "deployed": 0 }))],
+            ),
+        ]
+    }
+
+    #[tokio::test]
+    async fn transaction_is_sent_as_one_batch_in_instruction_order() {
+        let (grouper, mut rx) = grouper(Duration::from_millis(5));
+        for instruction in transaction("sig-1") {
+            grouper.add(instruction).await;
+        }
+        assert!(rx.try_recv().is_err());
+
+        let batch = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("the window should send the batch")
+            .unwrap();
+        let patches: Vec<_> = batch
+            .mutations
+            .iter()
+            .map(|mutation| (mutation.key.clone(), mutation.patch.clone()))
+            .collect();
+        assert_eq!(
+            patches,
+            [
+                (json!("round-1"), json!({ "deployed": 10 })),
+                (json!("round-1"), json!({ "checkpointed": true })),
+                (json!("round-2"), json!({ "id": 2 })),
+                (json!("round-1"), json!({ "deployed": 0 })),
+            ]
+        );
+        let context = batch.event_context.unwrap();
+        assert_eq!(
+            context.instructions,
+            [
+                "ore::DeployIxState",
+                "ore::CheckpointIxState",
+                "ore::LogIxState",
+                "ore::ClaimIxState"
+            ]
+        );
+        assert_eq!(context.event_type, "ore::DeployIxState");
+        assert_eq!(context.accounts_count, Some(12));
+        let slot_context = batch.slot_context.unwrap();
+        assert_eq!((slot_context.slot, slot_context.slot_index), (100, 7));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn next_transaction_sends_the_previous_one_first() {
+        let (grouper, mut rx) = grouper(Duration::from_secs(60));
+        for instruction in transaction("sig-1") {
+            grouper.add(instruction).await;
+        }
+        grouper
+            .add(instruction(
+                "sig-2",
+                "ore::ResetIxState",
+                vec![mutation("round-2", json!({ "reset": true }))],
+            ))
+            .await;
+
+        let first = rx.try_recv().expect("sig-1 should be sent");
+        assert_eq!(first.len(), 4);
+        assert!(rx.try_recv().is_err());
+
+        grouper.flush().await;
+        let second = rx.try_recv().expect("sig-2 should be sent");
+        assert_eq!(
+            second.event_context.unwrap().instructions,
+            ["ore::ResetIxState"]
+        );
+    }
+
+    #[tokio::test]
+    async fn zero_window_sends_each_instruction_on_its_own() {
+        let (grouper, mut rx) = grouper(Duration::ZERO);
+        for instruction in transaction("sig-1") {
+            grouper.add(instruction).await;
+        }
+
+        let sizes: Vec<usize> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|batch| batch.len())
+            .collect();
+        assert_eq!(sizes, [1, 2, 1]);
+    }
+}
@@ -1,5 +1,6 @@
+use crate::materialized_view::QuarantinedViews;
 use crate::sorted_cache::{SortOrder, SortedViewCache};
-use crate::view::ViewSpec;
+use crate::view::{ViewDeprecation, ViewSpec};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -11,6 +12,9 @@ pub struct ViewIndex {
     sorted_caches: Arc<RwLock<HashMap<String, SortedViewCache>>>,
     /// Map from source view ID to derived view IDs
     derived_by_source: HashMap<String, Vec<String>>,
+    /// Deprecated view ids, served from their replacements
+    deprecations: HashMap<String, ViewDeprecation>,
+    quarantined: QuarantinedViews,
 }
 
 impl ViewIndex {
@@ -20,17 +24,23 @@ impl ViewIndex {
             by_id: HashMap::new(),
             sorted_caches: Arc::new(RwLock::new(HashMap::new())),
             derived_by_source: HashMap::new(),
+            deprecations: HashMap::new(),
+            quarantined: QuarantinedViews::default(),
         }
     }
 
-    pub fn add_spec(&mut self, spec: ViewSpec) {
-        if let Some(ref source) = spec.source_view {
-            self.derived_by_source
-                .entry(source.clone())
-                .or_default()
-                .push(spec.id.clone());
+    /// An index without views sharing this index's sorted caches,
+    /// deprecations and quarantined views
+    pub(crate) fn sharing_caches(&self) -> Self {
+        Self {
+            sorted_caches: self.sorted_caches.clone(),
+            deprecations: self.deprecations.clone(),
+            quarantined: self.quarantined.clone(),
+            ..Self::new()
         }
+    }
 
+    pub fn add_spec(&mut self, spec: ViewSpec) {
         if let Some(ref pipeline) = spec.pipeline {
             if let Some(ref sort_config) = pipeline.sort {
                 self.init_sorted_cache_sync(
@@ -41,6 +51,18 @@ impl ViewIndex {
             }
         }
 
+        self.insert_spec(spec);
+    }
+
+    /// Register `spec` without creating its sorted cache
+    pub(crate) fn insert_spec(&mut self, spec: ViewSpec) {
+        if let Some(ref source) = spec.source_view {
+            self.derived_by_source
+                .entry(source.clone())
+                .or_default()
+                .push(spec.id.clone());
+        }
+
         // Only add non-derived views to by_export.
         // Derived views receive updates via their source_view subscription,
         // not directly from the projector.
@@ -53,6 +75,17 @@ impl ViewIndex {
         self.by_id.insert(spec.id.clone(), spec);
     }
 
+    /// Serve subscriptions to `deprecation.id` from its replacement, see
+    /// [`ViewDeprecation`]
+    pub fn deprecate(&mut self, deprecation: ViewDeprecation) {
+        self.deprecations
+            .insert(deprecation.id.clone(), deprecation);
+    }
+
+    pub fn deprecation(&self, id: &str) -> Option<&ViewDeprecation> {
+        self.deprecations.get(id)
+    }
+
     pub fn by_export(&self, entity: &str) -> &[ViewSpec] {
         self.by_export
             .get(entity)
@@ -64,6 +97,11 @@ impl ViewIndex {
         self.by_id.get(id)
     }
 
+    /// Every registered view, derived views included
+    pub fn views(&self) -> impl Iterator<Item = &ViewSpec> {
+        self.by_id.values()
+    }
+
     pub fn get_derived_views(&self) -> Vec<&ViewSpec> {
         self.by_id.values().filter(|s| s.is_derived()).collect()
     }
@@ -75,6 +113,12 @@ impl ViewIndex {
             .unwrap_or_default()
     }
 
+    /// Derived views taken out of service, see the
+    /// [`materialized_view`](crate::materialized_view) module
+    pub fn quarantined_views(&self) -> &QuarantinedViews {
+        &self.quarantined
+    }
+
     pub fn sorted_caches(&self) -> Arc<RwLock<HashMap<String, SortedViewCache>>> {
         self.sorted_caches.clone()
     }
@@ -1,5 +1,6 @@
 use crate::materialized_view::{CompareOp, FilterConfig, SortConfig, SortOrder, ViewPipeline};
-use crate::websocket::frame::Mode;
+use crate::websocket::frame::{DeprecationNotice, Mode};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // # View System Architecture
 //
@@ -77,7 +78,30 @@ impl Filters {
 
 #[derive(Clone, Debug, Default)]
 pub struct Delivery {
+    /// Milliseconds the projector holds this view's patches to an entity,
+    /// merging them into one frame. Unset or `0` publishes every patch as
+    /// it arrives. See the [`view_coalesce`](crate::view_coalesce) module.
     pub coalesce_ms: Option<u64>,
+    /// Number of recent items an `Append` view retains for subscribers that
+    /// ask for history. `0` disables history.
+    pub history: usize,
+    /// Bytes retained per history ring, defaulting to
+    /// [`DEFAULT_HISTORY_MAX_BYTES`](crate::append_log::DEFAULT_HISTORY_MAX_BYTES)
+    pub history_max_bytes: Option<usize>,
+    /// Age after which history items are evicted, unbounded when unset
+    pub history_max_age: Option<std::time::Duration>,
+    /// Replay the whole history to subscriptions that don't set `history`,
+    /// so a late subscriber catches up without asking
+    pub replay_on_subscribe: bool,
+}
+
+impl Delivery {
+    /// How long patches are held for coalescing, if they are
+    pub fn coalesce_window(&self) -> Option<Duration> {
+        self.coalesce_ms
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis)
+    }
 }
 
 impl ViewSpec {
@@ -120,6 +144,7 @@ impl ViewSpec {
             filter: None,
             sort: None,
             limit: None,
+            predicate: None,
         };
 
         for transform in transforms {
@@ -176,3 +201,50 @@ impl ViewSpec {
         pipeline
     }
 }
+
+/// A view scheduled for removal, e.g. after a rename.
+///
+/// Subscriptions to the deprecated id keep working until the sunset: they
+/// are served from the replacement view, with frames labeled with the
+/// deprecated id, and their ack carries a [`DeprecationNotice`]. After the
+/// sunset they are rejected with a `view-sunset` error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ViewDeprecation {
+    /// The deprecated view id
+    pub id: String,
+    /// The view serving the deprecated id's subscriptions
+    pub replacement: String,
+    /// When subscriptions to the deprecated id start being rejected
+    pub sunset: Option<SystemTime>,
+}
+
+impl ViewDeprecation {
+    pub fn new(id: impl Into<String>, replacement: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            replacement: replacement.into(),
+            sunset: None,
+        }
+    }
+
+    pub fn with_sunset(mut self, sunset: SystemTime) -> Self {
+        self.sunset = Some(sunset);
+        self
+    }
+
+    pub fn is_sunset(&self, now: SystemTime) -> bool {
+        self.sunset.is_some_and(|sunset| now >= sunset)
+    }
+
+    /// What subscribers of the deprecated id are told in their ack
+    pub fn notice(&self) -> DeprecationNotice {
+        DeprecationNotice {
+            replacement: self.replacement.clone(),
+            sunset_at: self.sunset.map(|sunset| {
+                sunset
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since| since.as_millis() as u64)
+            }),
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Per-view coalescing of published patches.
+//!
+//! A view with [`Delivery::coalesce_ms`](crate::view::Delivery::coalesce_ms)
+//! set doesn't publish each patch as it arrives. The projector holds the
+//! projected patch of each entity key for up to that many milliseconds and
+//! merges later patches to the key into it: later fields win and arrays at
+//! append paths are concatenated. Once the window elapses a single frame goes
+//! out, carrying the latest slot. Views left at zero publish every patch
+//! immediately, so a latency sensitive view can sit next to a bulky list that
+//! coalesces.
+//!
+//! Held patches are released in the order their entity was first held, so
+//! frames for different keys keep their relative order. A patch whose append
+//! paths differ from the held patch's can't be folded into it without
+//! changing what clients append, so the held patch is released first.
+//!
+//! Unlike the [`coalesce`](crate::coalesce) window, which merges mutations
+//! before they fan out to views, this applies to each view on its own.
+
+use crate::mutation_batch::{merge_patch, SlotContext};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+/// A view's merged patch for one entity, waiting for its window to elapse
+#[derive(Debug, Clone)]
+pub struct HeldPatch {
+    pub view: String,
+    pub key: String,
+    pub patch: Value,
+    pub append: Vec<String>,
+    /// Slot context of the latest patch merged in
+    pub slot_context: Option<SlotContext>,
+    due: Instant,
+}
+
+impl HeldPatch {
+    pub fn new(
+        view: impl Into<String>,
+        key: impl Into<String>,
+        patch: Value,
+        append: Vec<String>,
+        slot_context: Option<SlotContext>,
+    ) -> Self {
+        Self {
+            view: view.into(),
+            key: key.into(),
+            patch,
+            append,
+            slot_context,
+            due: Instant::now(),
+        }
+    }
+}
+
+/// Patches held by coalescing views, per view and entity key
+#[derive(Debug, Default)]
+pub struct HeldPatches {
+    held: HashMap<(String, String), HeldPatch>,
+    /// Entities in the order they were first held
+    order: VecDeque<(String, String)>,
+}
+
+impl HeldPatches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold `patch` until `due`, merging it into the patch already held for
+    /// its entity, whose due time is kept. Returns the held patch when the
+    /// two can't be merged, to be published before this one.
+    pub fn hold(&mut self, mut patch: HeldPatch, due: Instant) -> Option<HeldPatch> {
+        let entity = (patch.view.clone(), patch.key.clone());
+        let mut released = None;
+        if let Some(held) = self.held.get_mut(&entity) {
+            if held.append == patch.append {
+                merge_patch(&mut held.patch, &patch.patch, &patch.append);
+                held.slot_context = patch.slot_context.or(held.slot_context);
+                return None;
+            }
+            released = self.remove(&entity);
+        }
+
+        patch.due = due;
+        self.order.push_back(entity.clone());
+        self.held.insert(entity, patch);
+        released
+    }
+
+    /// Take the held patches whose window has elapsed, in the order they
+    /// were first held
+    pub fn release_due(&mut self, now: Instant) -> Vec<HeldPatch> {
+        let mut released = Vec::new();
+        let held = &mut self.held;
+        self.order.retain(|entity| match held.get(entity) {
+            Some(patch) if patch.due <= now => {
+                released.extend(held.remove(entity));
+                false
+            }
+            Some(_) => true,
+            None => false,
+        });
+        released
+    }
+
+    /// Take every held patch, in the order they were first held
+    pub fn release_all(&mut self) -> Vec<HeldPatch> {
+        let held = &mut self.held;
+        self.order
+            .drain(..)
+            .filter_map(|entity| held.remove(&entity))
+            .collect()
+    }
+
+    /// Drop the patch held for an entity, e.g. once it is deleted
+    pub fn discard(&mut self, view: &str, key: &str) -> Option<HeldPatch> {
+        self.remove(&(view.to_string(), key.to_string()))
+    }
+
+    /// When the next held patch is due
+    pub fn next_due(&self) -> Option<Instant> {
+        self.held.values().map(|patch| patch.due).min()
+    }
+
+    pub fn len(&self) -> usize {
+        self.held.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.held.is_empty()
+    }
+
+    fn remove(&mut self, entity: &(String, String)) -> Option<HeldPatch> {
+        let removed = self.held.remove(entity)?;
+        self.order.retain(|held| held != entity);
+        Some(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    const WINDOW: Duration = Duration::from_millis(50);
+
+    fn patch(view: &str, key: &str, patch: Value, append: &[&str], slot: u64) -> HeldPatch {
+        HeldPatch::new(
+            view,
+            key,
+            patch,
+            append.iter().map(|path| path.to_string()).collect(),
+            Some(SlotContext::new(slot, 0)),
+        )
+    }
+
+    #[test]
+    fn later_fields_win_and_appended_arrays_concatenate() {
+        let mut held = HeldPatches::new();
+        let due = Instant::now() + WINDOW;
+
+        let first = json!({ "price": 1, "stats": { "volume": 10, "count": 1 }, "trades": [1] });
+        assert!(held
+            .hold(patch("Token/list", "a", first, &["trades"], 5), due)
+            .is_none());
+        let second = json!({ "price": 2, "stats": { "volume": 20 }, "trades": [2, 3] });
+        assert!(held
+            .hold(patch("Token/list", "a", second, &["trades"], 7), due)
+            .is_none());
+
+        let released = held.release_all();
+        assert_eq!(released.len(), 1);
+        assert_eq!(
+            released[0].patch,
+            json!({ "price": 2, "stats": { "volume": 20, "count": 1 }, "trades": [1, 2, 3] })
+        );
+        assert_eq!(released[0].append, ["trades"]);
+        assert_eq!(released[0].slot_context.map(|ctx| ctx.slot), Some(7));
+    }
+
+    #[test]
+    fn replaced_arrays_are_not_concatenated() {
+        let mut held = HeldPatches::new();
+        let due = Instant::now() + WINDOW;
+
+        held.hold(
+            patch("Token/list", "a", json!({ "holders": [1] }), &[], 1),
+            due,
+        );
+        held.hold(
+            patch("Token/list", "a", json!({ "holders": [2] }), &[], 2),
+            due,
+        );
+
+        assert_eq!(held.release_all()[0].patch, json!({ "holders": [2] }));
+    }
+
+    #[test]
+    fn patch_with_other_append_paths_releases_the_held_one() {
+        let mut held = HeldPatches::new();
+        let due = Instant::now() + WINDOW;
+
+        held.hold(
+            patch("Token/list", "a", json!({ "trades": [1] }), &[], 1),
+            due,
+        );
+        let released = held
+            .hold(
+                patch("Token/list", "a", json!({ "trades": [2] }), &["trades"], 2),
+                due,
+            )
+            .expect("the held patch should be released");
+        assert_eq!(released.patch, json!({ "trades": [1] }));
+        assert!(released.append.is_empty());
+
+        let remaining = held.release_all();
+        assert_eq!(remaining[0].patch, json!({ "trades": [2] }));
+        assert_eq!(remaining[0].append, ["trades"]);
+    }
+
+    #[test]
+    fn entities_are_released_in_the_order_first_held() {
+        let mut held = HeldPatches::new();
+        let now = Instant::now();
+
+        for (slot, key) in ["b", "a", "c", "b", "a"].into_iter().enumerate() {
+            let value = json!({ "slot": slot });
+            held.hold(
+                patch("Token/list", key, value, &[], slot as u64),
+                now + WINDOW,
+            );
+        }
+        // A slower view's patch isn't due yet
+        held.hold(
+            patch("Token/slow", "a", json!({}), &[], 9),
+            now + WINDOW * 4,
+        );
+
+        assert!(held.release_due(now).is_empty());
+        assert_eq!(held.next_due(), Some(now + WINDOW));
+
+        let released = held.release_due(now + WINDOW);
+        let keys: Vec<&str> = released.iter().map(|patch| patch.key.as_str()).collect();
+        assert_eq!(keys, ["b", "a", "c"]);
+        assert_eq!(released[0].patch, json!({ "slot": 3 }));
+        assert_eq!(held.len(), 1);
+        assert_eq!(held.release_due(now + WINDOW * 4)[0].view, "Token/slow");
+        assert!(held.is_empty());
+    }
+}
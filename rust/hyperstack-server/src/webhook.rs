@@ -0,0 +1,633 @@
+//! Webhooks fired when an entity update matches a predicate.
+//!
+//! A [`WebhookRule`] watches one view. After the projector applies an update
+//! to the entity cache, the entity as cached is checked against the rule's
+//! [`Predicate`], the same AST view filters are written in, and a match queues
+//! a JSON payload to be POSTed to the rule's URL:
+//!
+//! - an entity that keeps matching is posted at most once per
+//!   [`WebhookRule::dedupe_window`];
+//! - a [`RateLimit`] caps the posts of a rule, dropping matches beyond it;
+//! - every rule has its own queue of [`WebhookRule::queue_capacity`]
+//!   payloads, delivered one at a time. Matches that find the queue full are
+//!   dropped, so a slow or failing receiver never holds up projection;
+//! - a POST that fails, or answers with anything but a 2xx status, is retried
+//!   with exponential backoff up to [`WebhookConfig::max_attempts`] times.
+//!
+//! `/stats` counts each rule's matches and outcomes under `"webhooks"`. A rule
+//! whose last delivery failed carries a health note shown at `/status` until
+//! one succeeds.
+//!
+//! ## Templates
+//!
+//! Without a template the payload is
+//! `{"rule": ..., "view": ..., "key": ..., "entity": {...}}`. A template is any
+//! JSON value in which strings may refer to those fields by dotted path:
+//! a string that is exactly `"{{entity.state.motherlode}}"` is replaced by
+//! the value, keeping its type, and placeholders inside longer strings are
+//! replaced by its text. Paths that don't resolve render as `null`.
+//!
+//! ```rust,ignore
+//! WebhookRule::new("motherlode", "OreRound/list", predicate, slack_url)
+//!     .with_template(json!({
+//!         "text": "Round {{key}} motherlode reached {{entity.state.motherlode}}"
+//!     }))
+//!     .with_dedupe_window(Duration::from_secs(600))
+//! ```
+
+use crate::health::HealthMonitor;
+use crate::materialized_view::extract_field;
+use hyperstack_interpreter::ast::{CompareOp, Predicate, PredicateValue};
+use hyperstack_interpreter::clock::{system_clock, SharedClock};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info_span, warn, Instrument};
+
+/// Posts allowed per rule within a period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max: u32,
+    pub per: Duration,
+}
+
+/// A webhook posted when an entity of a view matches a predicate
+#[derive(Debug, Clone)]
+pub struct WebhookRule {
+    /// Identifies the rule in `/stats` and health notes
+    pub name: String,
+    /// View whose entities are checked, e.g. `OreRound/list`
+    pub view: String,
+    pub predicate: Predicate,
+    pub url: String,
+    /// Payload template, see the [module docs](self#templates)
+    pub template: Option<Value>,
+    pub rate_limit: Option<RateLimit>,
+    /// How long an entity that matched stays quiet while it keeps matching
+    pub dedupe_window: Duration,
+    /// Payloads waiting for delivery before further matches are dropped
+    pub queue_capacity: usize,
+}
+
+impl WebhookRule {
+    pub fn new(
+        name: impl Into<String>,
+        view: impl Into<String>,
+        predicate: Predicate,
+        url: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            view: view.into(),
+            predicate,
+            url: url.into(),
+            template: None,
+            rate_limit: None,
+            dedupe_window: Duration::from_secs(60),
+            queue_capacity: 100,
+        }
+    }
+
+    pub fn with_template(mut self, template: Value) -> Self {
+        self.template = Some(template);
+        self
+    }
+
+    pub fn with_rate_limit(mut self, max: u32, per: Duration) -> Self {
+        self.rate_limit = Some(RateLimit { max, per });
+        self
+    }
+
+    pub fn with_dedupe_window(mut self, window: Duration) -> Self {
+        self.dedupe_window = window;
+        self
+    }
+
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity.max(1);
+        self
+    }
+}
+
+/// Configuration for entity webhooks
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub rules: Vec<WebhookRule>,
+    /// Attempts per payload, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each one after
+    pub retry_backoff: Duration,
+    /// Time allowed for a single POST
+    pub timeout: Duration,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            max_attempts: 3,
+            retry_backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rule(mut self, rule: WebhookRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn with_retries(mut self, max_attempts: u32, backoff: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.retry_backoff = backoff;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Matches and delivery outcomes of one rule, for `/stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WebhookStats {
+    pub matched: u64,
+    pub delivered: u64,
+    /// Payloads given up on after every attempt failed
+    pub failed: u64,
+    pub retries: u64,
+    /// Matches of an entity already posted within the dedupe window
+    pub deduped: u64,
+    pub rate_limited: u64,
+    /// Matches that found the rule's queue full
+    pub dropped: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct Limits {
+    /// Entity key -> when it was last queued
+    last_posted: HashMap<String, Instant>,
+    window_start: Option<Instant>,
+    window_posts: u32,
+}
+
+struct RuleState {
+    rule: WebhookRule,
+    queue: mpsc::Sender<Value>,
+    receiver: Mutex<Option<mpsc::Receiver<Value>>>,
+    limits: Mutex<Limits>,
+    stats: Mutex<WebhookStats>,
+}
+
+struct Inner {
+    config: WebhookConfig,
+    rules: Vec<RuleState>,
+    /// View id -> indices into `rules`
+    by_view: HashMap<String, Vec<usize>>,
+}
+
+/// Evaluates webhook rules for the projector and delivers their payloads
+#[derive(Clone)]
+pub struct Webhooks {
+    inner: Arc<Inner>,
+    clock: SharedClock,
+}
+
+impl Webhooks {
+    pub fn new(config: WebhookConfig) -> Self {
+        let mut by_view: HashMap<String, Vec<usize>> = HashMap::new();
+        let rules = config
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                by_view.entry(rule.view.clone()).or_default().push(index);
+                let (queue, receiver) = mpsc::channel(rule.queue_capacity.max(1));
+                RuleState {
+                    rule: rule.clone(),
+                    queue,
+                    receiver: Mutex::new(Some(receiver)),
+                    limits: Mutex::new(Limits::default()),
+                    stats: Mutex::new(WebhookStats::default()),
+                }
+            })
+            .collect();
+
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                rules,
+                by_view,
+            }),
+            clock: system_clock(),
+        }
+    }
+
+    /// Time dedupe windows, rate limits and retries with `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Whether any rule watches `view`
+    pub fn watches(&self, view: &str) -> bool {
+        self.inner.by_view.contains_key(view)
+    }
+
+    /// Check the entity at `key` of `view`, as cached after an update,
+    /// against the rules watching the view, queueing a payload for each match
+    pub fn observe(&self, view: &str, key: &str, entity: &Value) {
+        let Some(indices) = self.inner.by_view.get(view) else {
+            return;
+        };
+        let now = self.clock.now_instant();
+
+        for &index in indices {
+            let state = &self.inner.rules[index];
+            if !matches(&state.rule.predicate, entity) {
+                continue;
+            }
+
+            let mut stats = state.stats.lock().unwrap();
+            stats.matched += 1;
+            let admitted = state.limits.lock().unwrap().admit(&state.rule, key, now);
+            match admitted {
+                Err(Throttled::Deduped) => stats.deduped += 1,
+                Err(Throttled::RateLimited) => stats.rate_limited += 1,
+                Ok(()) => {
+                    let payload = render_payload(&state.rule, view, key, entity);
+                    if state.queue.try_send(payload).is_err() {
+                        stats.dropped += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Outcomes of each rule, keyed by rule name
+    pub fn stats(&self) -> BTreeMap<String, WebhookStats> {
+        self.inner
+            .rules
+            .iter()
+            .map(|state| (state.rule.name.clone(), state.stats.lock().unwrap().clone()))
+            .collect()
+    }
+
+    /// Spawn the task delivering queued payloads, noting failing rules on
+    /// `health_monitor`. Only the first call delivers anything.
+    pub fn spawn_delivery(&self, health_monitor: Option<HealthMonitor>) -> JoinHandle<()> {
+        let webhooks = self.clone();
+        tokio::spawn(
+            async move {
+                let client = reqwest::Client::builder()
+                    .timeout(webhooks.inner.config.timeout)
+                    .build()
+                    .unwrap_or_default();
+                let deliveries = webhooks.inner.rules.iter().filter_map(|state| {
+                    let receiver = state.receiver.lock().unwrap().take()?;
+                    Some(webhooks.deliver(state, receiver, &client, health_monitor.as_ref()))
+                });
+                futures_util::future::join_all(deliveries).await;
+            }
+            .instrument(info_span!("webhooks")),
+        )
+    }
+
+    async fn deliver(
+        &self,
+        state: &RuleState,
+        mut receiver: mpsc::Receiver<Value>,
+        client: &reqwest::Client,
+        health_monitor: Option<&HealthMonitor>,
+    ) {
+        let note_source = format!("webhook:{}", state.rule.name);
+        let mut failing = false;
+
+        while let Some(payload) = receiver.recv().await {
+            match self.post_with_retries(state, client, &payload).await {
+                Ok(()) => {
+                    state.stats.lock().unwrap().delivered += 1;
+                    if std::mem::take(&mut failing) {
+                        if let Some(monitor) = health_monitor {
+                            monitor.clear_note(&note_source).await;
+                        }
+                    }
+                }
+                Err(error) => {
+                    warn!("Webhook {} failed: {}", state.rule.name, error);
+                    let note = {
+                        let mut stats = state.stats.lock().unwrap();
+                        stats.failed += 1;
+                        stats.last_error = Some(error.clone());
+                        format!(
+                            "webhook {} failing ({} payloads lost): {}",
+                            state.rule.name, stats.failed, error
+                        )
+                    };
+                    failing = true;
+                    if let Some(monitor) = health_monitor {
+                        monitor.set_note(&note_source, note).await;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn post_with_retries(
+        &self,
+        state: &RuleState,
+        client: &reqwest::Client,
+        payload: &Value,
+    ) -> Result<(), String> {
+        let config = &self.inner.config;
+        let mut backoff = config.retry_backoff;
+        let mut attempt = 1;
+        loop {
+            let error = match client.post(&state.rule.url).json(payload).send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => format!("receiver answered {}", response.status()),
+                Err(error) => error.to_string(),
+            };
+            if attempt >= config.max_attempts {
+                return Err(error);
+            }
+            state.stats.lock().unwrap().retries += 1;
+            self.clock.sleep(backoff).await;
+            backoff = backoff.saturating_mul(2);
+            attempt += 1;
+        }
+    }
+}
+
+enum Throttled {
+    Deduped,
+    RateLimited,
+}
+
+impl Limits {
+    fn admit(&mut self, rule: &WebhookRule, key: &str, now: Instant) -> Result<(), Throttled> {
+        if let Some(&last) = self.last_posted.get(key) {
+            if now.saturating_duration_since(last) < rule.dedupe_window {
+                return Err(Throttled::Deduped);
+            }
+        }
+
+        if let Some(limit) = rule.rate_limit {
+            let window_expired = self
+                .window_start
+                .is_none_or(|start| now.saturating_duration_since(start) >= limit.per);
+            if window_expired {
+                self.window_start = Some(now);
+                self.window_posts = 0;
+            }
+            if self.window_posts >= limit.max {
+                return Err(Throttled::RateLimited);
+            }
+            self.window_posts += 1;
+        }
+
+        self.last_posted
+            .retain(|_, last| now.saturating_duration_since(*last) < rule.dedupe_window);
+        self.last_posted.insert(key.to_string(), now);
+        Ok(())
+    }
+}
+
+/// Whether `entity` satisfies `predicate`.
+///
+/// Ordering comparisons only match values of the same kind, except that
+/// numbers compare with numeric strings, the form large integers are cached
+/// in. Comparisons with `Dynamic` values, which only view pipelines resolve,
+/// never match.
+pub fn matches(predicate: &Predicate, entity: &Value) -> bool {
+    match predicate {
+        Predicate::Compare { field, op, value } => {
+            let actual = extract_field(entity, &field.segments);
+            let expected = match value {
+                PredicateValue::Literal(value) => value.clone(),
+                PredicateValue::Field(path) => extract_field(entity, &path.segments),
+                PredicateValue::Dynamic(_) => return false,
+            };
+            let ordering = compare(&actual, &expected);
+            let equal = actual == expected || ordering == Some(Ordering::Equal);
+            match op {
+                CompareOp::Eq => equal,
+                CompareOp::Ne => !equal,
+                CompareOp::Gt => ordering == Some(Ordering::Greater),
+                CompareOp::Gte => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                CompareOp::Lt => ordering == Some(Ordering::Less),
+                CompareOp::Lte => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            }
+        }
+        Predicate::And(predicates) => predicates.iter().all(|p| matches(p, entity)),
+        Predicate::Or(predicates) => predicates.iter().any(|p| matches(p, entity)),
+        Predicate::Not(predicate) => !matches(predicate, entity),
+        Predicate::Exists { field } => !extract_field(entity, &field.segments).is_null(),
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::Number(b)) => a.parse::<f64>().ok()?.partial_cmp(&b.as_f64()?),
+        (Value::Number(a), Value::String(b)) => a.as_f64()?.partial_cmp(&b.parse::<f64>().ok()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn render_payload(rule: &WebhookRule, view: &str, key: &str, entity: &Value) -> Value {
+    let context = serde_json::json!({
+        "rule": rule.name,
+        "view": view,
+        "key": key,
+        "entity": entity,
+    });
+    match &rule.template {
+        Some(template) => render(template, &context),
+        None => context,
+    }
+}
+
+/// Fill the `{{path}}` placeholders of `template` from `context`
+fn render(template: &Value, context: &Value) -> Value {
+    match template {
+        Value::String(text) => {
+            if let Some(path) = whole_placeholder(text) {
+                return lookup(context, path);
+            }
+            let mut rendered = String::with_capacity(text.len());
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("{{") {
+                let Some(end) = rest[start..].find("}}") else {
+                    break;
+                };
+                rendered.push_str(&rest[..start]);
+                match lookup(context, rest[start + 2..start + end].trim()) {
+                    Value::String(value) => rendered.push_str(&value),
+                    value => rendered.push_str(&value.to_string()),
+                }
+                rest = &rest[start + end + 2..];
+            }
+            rendered.push_str(rest);
+            Value::String(rendered)
+        }
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|item| render(item, context)).collect())
+        }
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), render(value, context)))
+                .collect::<Map<_, _>>(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// The path of a string that is nothing but one placeholder
+fn whole_placeholder(text: &str) -> Option<&str> {
+    let inner = text.strip_prefix("{{")?.strip_suffix("}}")?;
+    (!inner.contains("{{")).then(|| inner.trim())
+}
+
+fn lookup(context: &Value, path: &str) -> Value {
+    let segments: Vec<String> = path.split('.').map(str::to_string).collect();
+    extract_field(context, &segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperstack_interpreter::ast::FieldPath;
+    use hyperstack_interpreter::testkit::ManualClock;
+    use serde_json::json;
+
+    fn above(field: &[&str], threshold: Value) -> Predicate {
+        Predicate::Compare {
+            field: FieldPath::new(field),
+            op: CompareOp::Gt,
+            value: PredicateValue::Literal(threshold),
+        }
+    }
+
+    #[test]
+    fn test_predicates_compare_cached_values() {
+        let round = json!({ "state": { "motherlode": "18446744073709551615", "done": false } });
+
+        assert!(matches(
+            &above(&["state", "motherlode"], json!(1000)),
+            &round
+        ));
+        assert!(!matches(&above(&["state", "missing"], json!(0)), &round));
+        assert!(!matches(
+            &Predicate::Compare {
+                field: FieldPath::new(&["state", "missing"]),
+                op: CompareOp::Gte,
+                value: PredicateValue::Literal(json!(0)),
+            },
+            &round
+        ));
+        assert!(matches(
+            &Predicate::And(vec![
+                Predicate::Exists {
+                    field: FieldPath::new(&["state", "done"]),
+                },
+                Predicate::Not(Box::new(Predicate::Compare {
+                    field: FieldPath::new(&["state", "done"]),
+                    op: CompareOp::Eq,
+                    value: PredicateValue::Literal(json!(true)),
+                })),
+            ]),
+            &round
+        ));
+    }
+
+    #[test]
+    fn test_templates_keep_whole_values_and_interpolate_text() {
+        let template = json!({
+            "text": "Round {{key}} motherlode {{ entity.state.motherlode }}",
+            "motherlode": "{{entity.state.motherlode}}",
+            "missing": "{{entity.nope}}",
+            "tags": ["{{rule}}", 1],
+        });
+        let context = json!({
+            "rule": "motherlode",
+            "key": "42",
+            "entity": { "state": { "motherlode": 1500 } },
+        });
+
+        assert_eq!(
+            render(&template, &context),
+            json!({
+                "text": "Round 42 motherlode 1500",
+                "motherlode": 1500,
+                "missing": null,
+                "tags": ["motherlode", 1],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_matches_are_deduped_and_rate_limited() {
+        let clock = Arc::new(ManualClock::at_unix_secs(1_700_000_000));
+        let webhooks = Webhooks::new(
+            WebhookConfig::new().with_rule(
+                WebhookRule::new("big", "Round/list", above(&["n"], json!(10)), "http://x")
+                    .with_dedupe_window(Duration::from_secs(60))
+                    .with_rate_limit(2, Duration::from_secs(10)),
+            ),
+        )
+        .with_clock(clock.clone());
+
+        webhooks.observe("Round/list", "a", &json!({ "n": 5 }));
+        webhooks.observe("Round/list", "a", &json!({ "n": 11 }));
+        webhooks.observe("Round/list", "a", &json!({ "n": 12 }));
+        webhooks.observe("Round/list", "b", &json!({ "n": 11 }));
+        webhooks.observe("Round/list", "c", &json!({ "n": 11 }));
+        webhooks.observe("Other/list", "a", &json!({ "n": 11 }));
+
+        let stats = &webhooks.stats()["big"];
+        assert_eq!(
+            (stats.matched, stats.deduped, stats.rate_limited),
+            (4, 1, 1)
+        );
+
+        // A new rate window admits `c`, the dedupe window still holds back `a`
+        clock.advance(Duration::from_secs(10));
+        webhooks.observe("Round/list", "c", &json!({ "n": 11 }));
+        webhooks.observe("Round/list", "a", &json!({ "n": 11 }));
+        clock.advance(Duration::from_secs(60));
+        webhooks.observe("Round/list", "a", &json!({ "n": 11 }));
+
+        let stats = &webhooks.stats()["big"];
+        assert_eq!(
+            (stats.matched, stats.deduped, stats.rate_limited),
+            (7, 2, 1)
+        );
+
+        let mut queued = webhooks.inner.rules[0]
+            .receiver
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap();
+        let keys: Vec<Value> = std::iter::from_fn(|| queued.try_recv().ok())
+            .map(|payload| payload["key"].clone())
+            .collect();
+        assert_eq!(keys, vec![json!("a"), json!("b"), json!("c"), json!("a")]);
+    }
+}
@@ -227,6 +227,10 @@ impl AuthDeny {
                 RetryPolicy::RetryAfter(d) => Some(d.as_secs()),
                 _ => None,
             },
+            retry_after_ms: match self.retry_policy {
+                RetryPolicy::RetryAfter(d) => Some(d.as_millis() as u64),
+                _ => None,
+            },
             suggested_action: self.details.suggested_action.clone(),
             docs_url: self.details.docs_url.clone(),
         }
@@ -242,6 +246,9 @@ pub struct ErrorResponse {
     pub retryable: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub retry_after: Option<u64>,
+    /// Millisecond-precision `retry_after`, which clients should prefer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub suggested_action: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -1316,6 +1323,7 @@ mod tests {
         assert!(response.message.contains("30s"));
         assert!(response.retryable);
         assert_eq!(response.retry_after, Some(30));
+        assert_eq!(response.retry_after_ms, Some(30_000));
     }
 
     #[test]
@@ -1,5 +1,21 @@
-use super::subscription::Subscription;
-use crate::compression::CompressedPayload;
+use super::delta::SubscriptionDelta;
+use super::field_mask::{FieldAuthorizer, FieldMask, MaskedFrames, SubscriptionFields};
+use super::filter::{EntityFilter, SubscriptionFilter};
+use super::frame::{minimal_frame, relabel_frame, stamp_frame_sequence, SequenceStart};
+use super::frame_size::{FrameSizeGuard, OversizedFrameCounts};
+use super::session::{RetainedSubscription, SessionConfig, SessionStore};
+use super::snapshot_queue::{SnapshotQueue, SnapshotQueueConfig};
+use super::subscription::{
+    sub_key_view, CloseReason, CompressionCodec, CompressionRequest, HelloMessage, Subscription,
+    UpdateDelivery,
+};
+use crate::big_numbers::{encode_frame, BigNumbers};
+use crate::bus::BusMessage;
+use crate::cache::{cmp_seq, EntityCache, SnapshotBatchConfig};
+use crate::compression::{maybe_compress, CompressedPayload, FrameCodec};
+use crate::dictionary::Dictionaries;
+use crate::flags::{Flag, Flags};
+use crate::view::ViewDeprecation;
 use crate::websocket::auth::{AuthContext, AuthDeny};
 use crate::websocket::rate_limiter::{RateLimitResult, WebSocketRateLimiter};
 use bytes::Bytes;
@@ -7,18 +23,36 @@ use dashmap::DashMap;
 use futures_util::stream::SplitSink;
 use futures_util::SinkExt;
 use hyperstack_auth::Limits;
-use std::collections::HashMap;
+use hyperstack_interpreter::big_numbers::BigNumberMode;
+use hyperstack_interpreter::clock::{system_clock, SharedClock};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::net::SocketAddr;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime};
-use tokio::net::TcpStream;
-use tokio::sync::{mpsc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Utf8Bytes;
 use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-pub type WebSocketSender = SplitSink<WebSocketStream<TcpStream>, Message>;
+/// Byte stream underneath a client WebSocket.
+///
+/// Connections accepted by the standalone listener wrap a `TcpStream`, while
+/// connections upgraded through an embedded router wrap the upgraded HTTP
+/// connection, so the transport is type-erased.
+pub trait WebSocketIo: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> WebSocketIo for T {}
+
+pub type WebSocketTransport = Box<dyn WebSocketIo>;
+
+pub type WebSocketSender = SplitSink<WebSocketStream<WebSocketTransport>, Message>;
 
 /// Error type for send operations
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -29,6 +63,8 @@ pub enum SendError {
     ClientBackpressured,
     /// Client's channel is closed - client was disconnected
     ClientDisconnected,
+    /// The subscription was cancelled or restarted since the sender was made
+    SubscriptionEnded,
 }
 
 impl std::fmt::Display for SendError {
@@ -37,6 +73,7 @@ impl std::fmt::Display for SendError {
             SendError::ClientNotFound => write!(f, "client not found"),
             SendError::ClientBackpressured => write!(f, "client backpressured and disconnected"),
             SendError::ClientDisconnected => write!(f, "client disconnected"),
+            SendError::SubscriptionEnded => write!(f, "subscription ended"),
         }
     }
 }
@@ -125,6 +162,31 @@ impl EgressTracker {
         self.maybe_reset_window();
         self.bytes_this_minute
     }
+
+    /// Time until the current window resets
+    fn window_remaining(&self) -> Duration {
+        Duration::from_secs(60).saturating_sub(self.window_start.elapsed().unwrap_or_default())
+    }
+}
+
+/// Where one subscription is in its frame sequence
+#[derive(Debug)]
+struct FrameSequence {
+    /// Distinguishes this sequence from earlier ones for the same key
+    generation: u64,
+    /// Number of the last frame sent
+    last: u64,
+    /// How the next frame is marked, if it is the first of the sequence
+    start: SequenceStart,
+    /// `_seq` of the latest entity sent, where a resumed session picks up
+    cursor: Option<String>,
+}
+
+/// Frame sequences of a client's subscriptions, by subscription key
+#[derive(Debug, Default)]
+struct FrameSequences {
+    next_generation: u64,
+    by_sub: HashMap<String, FrameSequence>,
 }
 
 /// Information about a connected client
@@ -137,12 +199,28 @@ pub struct ClientInfo {
     subscriptions: Arc<RwLock<HashMap<String, CancellationToken>>>,
     /// Authentication context for this client
     pub auth_context: Option<AuthContext>,
+    /// Bumped whenever `auth_context` changes, so field masks get resolved again
+    auth_epoch: Arc<AtomicU64>,
     /// Client's IP address for rate limiting
     pub remote_addr: SocketAddr,
     /// Egress tracking for rate limiting
     egress_tracker: std::sync::Mutex<EgressTracker>,
     /// Inbound message-rate tracking for rate limiting
     message_rate_tracker: std::sync::Mutex<MessageRateTracker>,
+    /// Tells the sender task to close the socket ahead of any queued messages
+    close_tx: std::sync::Mutex<Option<oneshot::Sender<CloseFrame>>>,
+    /// Frame sequence numbers of the subscriptions
+    frame_sequences: Arc<std::sync::Mutex<FrameSequences>>,
+    /// Connected through a view's URL path: frames go out uncompressed in the
+    /// minimal envelope, without sequence stamps
+    minimal_frames: bool,
+    /// Compression the client negotiated, gzip when unset
+    codec: Arc<OnceLock<FrameCodec>>,
+    /// Token its session is kept under once it disconnects, issued in the
+    /// `hello` when sessions are enabled
+    session: Option<String>,
+    /// When each subscription was last refreshed, by subscription key
+    refreshed_at: std::sync::Mutex<HashMap<String, Instant>>,
 }
 
 impl ClientInfo {
@@ -159,9 +237,16 @@ impl ClientInfo {
             sender,
             subscriptions: Arc::new(RwLock::new(HashMap::new())),
             auth_context,
+            auth_epoch: Arc::new(AtomicU64::new(0)),
             remote_addr,
             egress_tracker: std::sync::Mutex::new(EgressTracker::new()),
             message_rate_tracker: std::sync::Mutex::new(MessageRateTracker::new()),
+            close_tx: std::sync::Mutex::new(None),
+            frame_sequences: Arc::new(std::sync::Mutex::new(FrameSequences::default())),
+            minimal_frames: false,
+            codec: Arc::new(OnceLock::new()),
+            session: None,
+            refreshed_at: std::sync::Mutex::new(HashMap::new()),
         }
     }
 
@@ -183,6 +268,14 @@ impl ClientInfo {
         None
     }
 
+    /// Time until the egress window resets
+    pub fn egress_window_remaining(&self) -> Duration {
+        self.egress_tracker
+            .lock()
+            .map(|tracker| tracker.window_remaining())
+            .unwrap_or_default()
+    }
+
     /// Record an inbound client message, returning true if within limit.
     pub fn record_inbound_message(&self) -> Option<u32> {
         if let Ok(mut tracker) = self.message_rate_tracker.lock() {
@@ -210,6 +303,8 @@ impl ClientInfo {
         self.last_seen.elapsed().unwrap_or(Duration::MAX) > timeout
     }
 
+    /// Track the subscription under `sub_key`, cancelling any subscription
+    /// already using it. Returns false when one was replaced.
     pub async fn add_subscription(&self, sub_key: String, token: CancellationToken) -> bool {
         let mut subs = self.subscriptions.write().await;
         if let Some(old_token) = subs.insert(sub_key.clone(), token) {
@@ -225,6 +320,11 @@ impl ClientInfo {
         let mut subs = self.subscriptions.write().await;
         if let Some(token) = subs.remove(sub_key) {
             token.cancel();
+            self.frame_sequences
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .by_sub
+                .remove(sub_key);
             debug!("Cancelled subscription: {}", sub_key);
             true
         } else {
@@ -244,6 +344,49 @@ impl ClientInfo {
     pub async fn subscription_count(&self) -> usize {
         self.subscriptions.read().await.len()
     }
+
+    /// Start the frame sequence of a subscription over, returning its generation.
+    ///
+    /// Senders of any earlier sequence for the same key stop being able to send.
+    fn start_frame_sequence(&self, sub_key: &str, start: SequenceStart) -> u64 {
+        self.insert_frame_sequence(sub_key, 0, start, None)
+    }
+
+    /// Carry on the frame sequence of a subscription restored from a
+    /// session, returning its generation
+    fn resume_frame_sequence(&self, sub_key: &str, retained: &RetainedSubscription) -> u64 {
+        self.insert_frame_sequence(
+            sub_key,
+            retained.last_frame,
+            SequenceStart::Subscribe,
+            retained.cursor.clone(),
+        )
+    }
+
+    fn insert_frame_sequence(
+        &self,
+        sub_key: &str,
+        last: u64,
+        start: SequenceStart,
+        cursor: Option<String>,
+    ) -> u64 {
+        let mut sequences = self
+            .frame_sequences
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        sequences.next_generation += 1;
+        let generation = sequences.next_generation;
+        sequences.by_sub.insert(
+            sub_key.to_string(),
+            FrameSequence {
+                generation,
+                last,
+                start,
+                cursor,
+            },
+        );
+        generation
+    }
 }
 
 /// Configuration for rate limiting in ClientManager
@@ -271,6 +414,11 @@ pub struct RateLimitConfig {
     /// Default limits applied when auth token doesn't specify limits
     /// These act as server-wide fallback limits for all connections
     pub default_limits: Option<Limits>,
+    /// Reconnect delay suggested to clients shed because they fell behind
+    /// or the server is full
+    pub overload_retry_after: Duration,
+    /// Shortest time between two refreshes of the same subscription
+    pub min_refresh_interval: Duration,
 }
 
 impl Default for RateLimitConfig {
@@ -285,6 +433,8 @@ impl Default for RateLimitConfig {
             message_rate_window: Duration::from_secs(60),
             egress_rate_window: Duration::from_secs(60),
             default_limits: None,
+            overload_retry_after: Duration::from_secs(5),
+            min_refresh_interval: Duration::from_secs(1),
         }
     }
 }
@@ -299,6 +449,8 @@ impl RateLimitConfig {
     /// - `HYPERSTACK_WS_CLIENT_TIMEOUT_SECS` - Client timeout in seconds (default: 300)
     /// - `HYPERSTACK_WS_MESSAGE_QUEUE_SIZE` - Message queue size per client (default: 512)
     /// - `HYPERSTACK_WS_RATE_LIMIT_WINDOW_SECS` - Rate limit window in seconds (default: 60)
+    /// - `HYPERSTACK_WS_OVERLOAD_RETRY_AFTER_MS` - Reconnect delay suggested to shed clients (default: 5000)
+    /// - `HYPERSTACK_WS_MIN_REFRESH_INTERVAL_MS` - Shortest time between refreshes of a subscription (default: 1000)
     /// - `HYPERSTACK_WS_DEFAULT_MAX_CONNECTIONS` - Default max connections per subject (fallback when token has no limit)
     /// - `HYPERSTACK_WS_DEFAULT_MAX_SUBSCRIPTIONS` - Default max subscriptions per connection (fallback when token has no limit)
     /// - `HYPERSTACK_WS_DEFAULT_MAX_SNAPSHOT_ROWS` - Default max snapshot rows per request (fallback when token has no limit)
@@ -344,6 +496,18 @@ impl RateLimitConfig {
             }
         }
 
+        if let Ok(val) = std::env::var("HYPERSTACK_WS_OVERLOAD_RETRY_AFTER_MS") {
+            if let Ok(ms) = val.parse() {
+                config.overload_retry_after = Duration::from_millis(ms);
+            }
+        }
+
+        if let Ok(val) = std::env::var("HYPERSTACK_WS_MIN_REFRESH_INTERVAL_MS") {
+            if let Ok(ms) = val.parse() {
+                config.min_refresh_interval = Duration::from_millis(ms);
+            }
+        }
+
         // Load default limits from environment (fallback when auth token doesn't specify limits)
         let mut default_limits = Limits::default();
         let mut has_default_limits = false;
@@ -423,6 +587,12 @@ impl RateLimitConfig {
         self.default_limits = Some(limits);
         self
     }
+
+    /// Set the reconnect delay suggested to clients shed for overload
+    pub fn with_overload_retry_after(mut self, delay: Duration) -> Self {
+        self.overload_retry_after = delay;
+        self
+    }
 }
 
 /// Manages all connected WebSocket clients using lock-free DashMap.
@@ -439,6 +609,26 @@ pub struct ClientManager {
     rate_limit_config: RateLimitConfig,
     /// Optional WebSocket rate limiter for granular rate control
     rate_limiter: Option<Arc<WebSocketRateLimiter>>,
+    /// Splits subscription frames larger than the configured limit
+    frame_size_guard: Option<FrameSizeGuard>,
+    /// Decides which fields of a view each client may see
+    field_authorizer: Option<Arc<dyn FieldAuthorizer>>,
+    masked_frames: MaskedFrames,
+    big_numbers: BigNumbers,
+    /// Offer zstd with this dictionary, gzip only when unset
+    dictionaries: Option<Dictionaries>,
+    /// Frames are sent uncompressed while `compression` is off
+    flags: Arc<Flags>,
+    /// Takes turns building list snapshots
+    snapshot_queue: SnapshotQueue,
+    /// Sessions of disconnected clients, when resuming is enabled
+    sessions: Option<SessionStore>,
+    /// Entities per snapshot page, overriding the entity cache's batch sizes
+    snapshot_page_size: Option<usize>,
+    /// Data frames queued for clients since startup
+    frames_sent: Arc<AtomicU64>,
+    /// Decides when deprecated views reach their sunset
+    clock: SharedClock,
 }
 
 impl ClientManager {
@@ -452,6 +642,17 @@ impl ClientManager {
             clients: Arc::new(DashMap::new()),
             rate_limit_config: config,
             rate_limiter: None,
+            frame_size_guard: None,
+            field_authorizer: None,
+            masked_frames: MaskedFrames::default(),
+            big_numbers: BigNumbers::default(),
+            dictionaries: None,
+            flags: Arc::default(),
+            snapshot_queue: SnapshotQueue::default(),
+            sessions: None,
+            snapshot_page_size: None,
+            frames_sent: Arc::default(),
+            clock: system_clock(),
         }
     }
 
@@ -496,12 +697,138 @@ impl ClientManager {
         self
     }
 
+    /// Set the reconnect delay suggested to clients shed for overload
+    pub fn with_overload_retry_after(mut self, delay: Duration) -> Self {
+        self.rate_limit_config.overload_retry_after = delay;
+        self
+    }
+
+    /// Set the shortest time between two refreshes of a subscription
+    pub fn with_min_refresh_interval(mut self, interval: Duration) -> Self {
+        self.rate_limit_config.min_refresh_interval = interval;
+        self
+    }
+
     /// Set a WebSocket rate limiter for granular rate control
     pub fn with_rate_limiter(mut self, rate_limiter: Arc<WebSocketRateLimiter>) -> Self {
         self.rate_limiter = Some(rate_limiter);
         self
     }
 
+    /// Split subscription frames larger than `max_bytes` (None = no limit).
+    ///
+    /// See the [`frame_size`](crate::websocket::frame_size) module.
+    pub fn with_max_frame_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.frame_size_guard = max_bytes.map(FrameSizeGuard::new);
+        self
+    }
+
+    /// Split and dropped frame counts per view, empty without a frame limit
+    /// Mask the fields clients receive according to their auth context.
+    ///
+    /// See the [`field_mask`](crate::websocket::field_mask) module.
+    pub fn with_field_authorizer(mut self, authorizer: Arc<dyn FieldAuthorizer>) -> Self {
+        self.field_authorizer = Some(authorizer);
+        self
+    }
+
+    /// Encode 64-bit integers the way `big_numbers` says.
+    ///
+    /// See the [`big_numbers`](crate::big_numbers) module.
+    pub fn with_big_numbers(mut self, big_numbers: BigNumbers) -> Self {
+        self.big_numbers = big_numbers;
+        self
+    }
+
+    pub fn big_numbers(&self) -> &BigNumbers {
+        &self.big_numbers
+    }
+
+    /// Offer clients zstd compression with the dictionary of `dictionaries`.
+    ///
+    /// See the [`dictionary`](crate::dictionary) module.
+    pub fn with_dictionaries(mut self, dictionaries: Dictionaries) -> Self {
+        self.dictionaries = Some(dictionaries);
+        self
+    }
+
+    pub fn dictionaries(&self) -> Option<&Dictionaries> {
+        self.dictionaries.as_ref()
+    }
+
+    /// Check the `compression` flag of `flags` before compressing a frame.
+    ///
+    /// See the [`flags`](crate::flags) module.
+    pub fn with_flags(mut self, flags: Arc<Flags>) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn flags(&self) -> &Arc<Flags> {
+        &self.flags
+    }
+
+    /// Read the time from `clock` when checking view sunsets
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The current time on the manager's clock
+    pub fn now(&self) -> SystemTime {
+        UNIX_EPOCH + self.clock.now_unix()
+    }
+
+    /// Build list snapshots in turns within the limits of `config`.
+    ///
+    /// See the [`snapshot_queue`](crate::websocket::snapshot_queue) module.
+    pub fn with_snapshot_queue(mut self, config: SnapshotQueueConfig) -> Self {
+        self.snapshot_queue = SnapshotQueue::new(config);
+        self
+    }
+
+    pub fn snapshot_queue(&self) -> &SnapshotQueue {
+        &self.snapshot_queue
+    }
+
+    /// Keep the subscriptions of disconnected clients for them to resume,
+    /// within the limits of `config`.
+    ///
+    /// See the [`session`](crate::websocket::session) module.
+    pub fn with_sessions(mut self, config: SessionConfig) -> Self {
+        self.sessions = Some(SessionStore::new(config));
+        self
+    }
+
+    pub fn sessions(&self) -> Option<&SessionStore> {
+        self.sessions.as_ref()
+    }
+
+    /// Send snapshots in pages of `page_size` entities (None = the batch
+    /// sizes of the entity cache)
+    pub fn with_snapshot_page_size(mut self, page_size: Option<usize>) -> Self {
+        self.snapshot_page_size = page_size.map(|size| size.max(1));
+        self
+    }
+
+    /// How snapshots taken from `cache` are split into pages
+    pub fn snapshot_batch_config(&self, cache: &EntityCache) -> SnapshotBatchConfig {
+        match self.snapshot_page_size {
+            Some(size) => SnapshotBatchConfig {
+                initial_batch_size: size,
+                subsequent_batch_size: size,
+            },
+            None => cache.snapshot_config(),
+        }
+    }
+
+    pub fn oversized_frame_stats(&self) -> BTreeMap<String, OversizedFrameCounts> {
+        self.frame_size_guard
+            .as_ref()
+            .map(FrameSizeGuard::stats)
+            .unwrap_or_default()
+    }
+
     /// Get the rate limiter if configured
     pub fn rate_limiter(&self) -> Option<&WebSocketRateLimiter> {
         self.rate_limiter.as_ref().map(|r| r.as_ref())
@@ -526,14 +853,30 @@ impl ClientManager {
     ) {
         let (client_tx, mut client_rx) =
             mpsc::channel::<Message>(self.rate_limit_config.message_queue_size);
+        let (close_tx, mut close_rx) = oneshot::channel::<CloseFrame>();
         let client_info = ClientInfo::new(client_id, client_tx, auth_context, remote_addr);
+        *client_info.close_tx.lock().unwrap() = Some(close_tx);
 
         let clients_ref = self.clients.clone();
         tokio::spawn(async move {
-            while let Some(message) = client_rx.recv().await {
-                if let Err(e) = ws_sender.send(message).await {
-                    warn!("Failed to send message to client {}: {}", client_id, e);
-                    break;
+            loop {
+                tokio::select! {
+                    biased;
+                    Ok(frame) = &mut close_rx => {
+                        if let Err(e) = ws_sender.send(Message::Close(Some(frame))).await {
+                            debug!("Failed to send close frame to client {}: {}", client_id, e);
+                        }
+                        break;
+                    }
+                    message = client_rx.recv() => {
+                        let Some(message) = message else {
+                            break;
+                        };
+                        if let Err(e) = ws_sender.send(message).await {
+                            warn!("Failed to send message to client {}: {}", client_id, e);
+                            break;
+                        }
+                    }
                 }
             }
             clients_ref.remove(&client_id);
@@ -551,12 +894,36 @@ impl ClientManager {
         }
     }
 
+    /// Remove a client and close its socket with `reason`, skipping any
+    /// messages still queued for it.
+    pub(crate) fn shed_client(&self, client_id: Uuid, close_code: CloseCode, reason: CloseReason) {
+        let Some((_, client)) = self.clients.remove(&client_id) else {
+            return;
+        };
+        let close_tx = client.close_tx.lock().ok().and_then(|mut tx| tx.take());
+        if let Some(close_tx) = close_tx {
+            let _ = close_tx.send(reason.into_close_frame(close_code));
+        }
+    }
+
+    /// Disconnect a client whose egress budget is spent, telling it to
+    /// come back when the window resets.
+    fn shed_for_egress(&self, client_id: Uuid, retry_after: Duration) {
+        warn!("Client {} exceeded egress limit, disconnecting", client_id);
+        self.shed_client(
+            client_id,
+            CloseCode::Policy,
+            CloseReason::new("egress-limit-exceeded", Some(retry_after)),
+        );
+    }
+
     /// Update the auth context for a client.
     ///
     /// Used for in-band auth refresh without reconnecting.
     pub fn update_client_auth(&self, client_id: Uuid, auth_context: AuthContext) -> bool {
         if let Some(mut client) = self.clients.get_mut(&client_id) {
             client.auth_context = Some(auth_context);
+            client.auth_epoch.fetch_add(1, Ordering::Release);
             debug!("Updated auth context for client {}", client_id);
             true
         } else {
@@ -596,6 +963,53 @@ impl ClientManager {
         self.clients.len()
     }
 
+    /// Active subscriptions per view, across all clients
+    pub async fn subscriptions_per_view(&self) -> BTreeMap<String, usize> {
+        // Collected first, so no client map shard stays locked across awaits
+        let subscriptions: Vec<_> = self
+            .clients
+            .iter()
+            .map(|client| client.subscriptions.clone())
+            .collect();
+
+        let mut per_view = BTreeMap::new();
+        for subscriptions in subscriptions {
+            for sub_key in subscriptions.read().await.keys() {
+                *per_view
+                    .entry(sub_key_view(sub_key).to_string())
+                    .or_insert(0) += 1;
+            }
+        }
+        per_view
+    }
+
+    /// Data frames queued for clients since startup
+    pub fn frames_sent(&self) -> u64 {
+        self.frames_sent.load(Ordering::Relaxed)
+    }
+
+    fn count_frame(&self, sent: Result<(), SendError>) -> Result<(), SendError> {
+        if sent.is_ok() {
+            self.frames_sent.fetch_add(1, Ordering::Relaxed);
+        }
+        sent
+    }
+
+    /// Fill ratio of the fullest client send queue, from 0.0 (all empty) to
+    /// 1.0 (at least one client about to be dropped as too slow).
+    pub fn queue_pressure(&self) -> f64 {
+        self.clients
+            .iter()
+            .map(|client| {
+                let max = client.sender.max_capacity();
+                if max == 0 {
+                    return 0.0;
+                }
+                (max - client.sender.capacity()) as f64 / max as f64
+            })
+            .fold(0.0, f64::max)
+    }
+
     /// Send data to a specific client (non-blocking).
     ///
     /// This method NEVER blocks. If the client's queue is full, the client is
@@ -613,31 +1027,39 @@ impl ClientManager {
         // Check egress limits
         if let Some(client) = self.clients.get(&client_id) {
             if client.record_egress(data.len()).is_none() {
-                warn!("Client {} exceeded egress limit, disconnecting", client_id);
-                self.clients.remove(&client_id);
+                let retry_after = client.egress_window_remaining();
+                drop(client);
+                self.shed_for_egress(client_id, retry_after);
                 return Err(SendError::ClientDisconnected);
             }
         } else {
             return Err(SendError::ClientNotFound);
         }
 
-        let sender = {
+        let (sender, minimal_frames) = {
             let client = self
                 .clients
                 .get(&client_id)
                 .ok_or(SendError::ClientNotFound)?;
-            client.sender.clone()
+            (client.sender.clone(), client.minimal_frames)
         };
 
-        let msg = Message::Binary((*data).clone());
+        let msg = frame_message((*data).clone(), minimal_frames);
         match sender.try_send(msg) {
-            Ok(()) => Ok(()),
+            Ok(()) => self.count_frame(Ok(())),
             Err(mpsc::error::TrySendError::Full(_)) => {
                 warn!(
                     "Client {} backpressured (queue full), disconnecting",
                     client_id
                 );
-                self.clients.remove(&client_id);
+                self.shed_client(
+                    client_id,
+                    CloseCode::Again,
+                    CloseReason::new(
+                        "backpressure",
+                        Some(self.rate_limit_config.overload_retry_after),
+                    ),
+                );
                 Err(SendError::ClientBackpressured)
             }
             Err(mpsc::error::TrySendError::Closed(_)) => {
@@ -669,27 +1091,29 @@ impl ClientManager {
         // Check egress limits
         if let Some(client) = self.clients.get(&client_id) {
             if client.record_egress(data.len()).is_none() {
-                warn!("Client {} exceeded egress limit, disconnecting", client_id);
-                self.clients.remove(&client_id);
+                let retry_after = client.egress_window_remaining();
+                drop(client);
+                self.shed_for_egress(client_id, retry_after);
                 return Err(SendError::ClientDisconnected);
             }
         } else {
             return Err(SendError::ClientNotFound);
         }
 
-        let sender = {
+        let (sender, minimal_frames) = {
             let client = self
                 .clients
                 .get(&client_id)
                 .ok_or(SendError::ClientNotFound)?;
-            client.sender.clone()
+            (client.sender.clone(), client.minimal_frames)
         };
 
-        let msg = Message::Binary((*data).clone());
-        sender
+        let msg = frame_message((*data).clone(), minimal_frames);
+        let sent = sender
             .send(msg)
             .await
-            .map_err(|_| SendError::ClientDisconnected)
+            .map_err(|_| SendError::ClientDisconnected);
+        self.count_frame(sent)
     }
 
     /// Send a text message to a specific client (async).
@@ -721,9 +1145,16 @@ impl ClientManager {
             .map_err(|_| SendError::ClientDisconnected)
     }
 
+    /// Get the outbound queue of a client, for control frames such as close.
+    pub fn client_sender(&self, client_id: Uuid) -> Option<mpsc::Sender<Message>> {
+        self.clients
+            .get(&client_id)
+            .map(|client| client.sender.clone())
+    }
+
     /// Send a potentially compressed payload to a client (async).
     ///
-    /// Compressed payloads are sent as binary frames (raw gzip).
+    /// Compressed payloads are sent as binary frames (raw gzip or zstd).
     /// Uncompressed payloads are sent as text frames (JSON).
     pub async fn send_compressed_async(
         &self,
@@ -735,7 +1166,7 @@ impl ClientManager {
             return Err(SendError::ClientDisconnected);
         }
 
-        let (sender, bytes_to_record) = {
+        let (sender, bytes_to_record, minimal_frames) = {
             let client = self
                 .clients
                 .get(&client_id)
@@ -746,7 +1177,7 @@ impl ClientManager {
                 CompressedPayload::Uncompressed(bytes) => bytes.len(),
             };
 
-            (client.sender.clone(), bytes)
+            (client.sender.clone(), bytes, client.minimal_frames)
         };
 
         // Check egress limits
@@ -760,12 +1191,13 @@ impl ClientManager {
 
         let msg = match payload {
             CompressedPayload::Compressed(bytes) => Message::Binary(bytes),
-            CompressedPayload::Uncompressed(bytes) => Message::Binary(bytes),
+            CompressedPayload::Uncompressed(bytes) => frame_message(bytes, minimal_frames),
         };
-        sender
+        let sent = sender
             .send(msg)
             .await
-            .map_err(|_| SendError::ClientDisconnected)
+            .map_err(|_| SendError::ClientDisconnected);
+        self.count_frame(sent)
     }
 
     /// Update the subscription for a client.
@@ -785,6 +1217,121 @@ impl ClientManager {
     }
 
     /// Update the last_seen timestamp for a client.
+    /// Send this client's frames in the minimal envelope of path
+    /// subscriptions (see [`minimal_frame`]), uncompressed.
+    pub fn use_minimal_frames(&self, client_id: Uuid) -> bool {
+        if let Some(mut client) = self.clients.get_mut(&client_id) {
+            client.minimal_frames = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Advertise zstd and the current dictionary to a new client, when the
+    /// server offers them
+    pub async fn send_hello(&self, client_id: Uuid) -> Result<(), SendError> {
+        let session = self.sessions.as_ref().map(SessionStore::issue);
+        let hello = match &self.dictionaries {
+            Some(dictionaries) => HelloMessage::new(
+                dictionaries
+                    .current()
+                    .map(|dictionary| dictionary.id().to_string()),
+            ),
+            None if session.is_some() => HelloMessage::gzip_only(),
+            None => return Ok(()),
+        };
+        if let Some(mut client) = self.clients.get_mut(&client_id) {
+            client.session = session.clone();
+        }
+        let hello = serde_json::to_string(&hello.with_session(session))
+            .expect("hello message should serialize");
+        self.send_text_to_client(client_id, hello).await
+    }
+
+    /// Keep a disconnecting client's `subscriptions`, and how far each got,
+    /// under the session token it was issued.
+    ///
+    /// Does nothing without sessions enabled or for clients that were never
+    /// sent a token.
+    pub fn retain_session<'a>(
+        &self,
+        client_id: Uuid,
+        subscriptions: impl IntoIterator<Item = &'a Subscription>,
+    ) {
+        let Some(sessions) = &self.sessions else {
+            return;
+        };
+        let Some(client) = self.clients.get(&client_id) else {
+            return;
+        };
+        let Some(token) = client.session.clone() else {
+            return;
+        };
+        let subject = client.auth_context.as_ref().map(|ctx| ctx.subject.clone());
+        let retained = {
+            let sequences = client
+                .frame_sequences
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            subscriptions
+                .into_iter()
+                .map(|subscription| {
+                    let sequence = sequences.by_sub.get(&subscription.sub_key());
+                    RetainedSubscription {
+                        subscription: subscription.clone(),
+                        cursor: sequence.and_then(|sequence| sequence.cursor.clone()),
+                        last_frame: sequence.map_or(0, |sequence| sequence.last),
+                    }
+                })
+                .collect()
+        };
+        drop(client);
+        sessions.retain(token, subject, retained, Instant::now());
+    }
+
+    /// Take the session kept under `token` for a client resuming it, if it
+    /// is still kept and was the same subject's
+    pub(crate) fn resume_session(
+        &self,
+        client_id: Uuid,
+        token: &str,
+    ) -> Option<Vec<RetainedSubscription>> {
+        let sessions = self.sessions.as_ref()?;
+        let subject = self
+            .get_auth_context(client_id)
+            .map(|ctx| ctx.subject.clone());
+        sessions.resume(token, subject.as_deref(), Instant::now())
+    }
+
+    /// Compress the client's frames the way it asked, falling back to zstd
+    /// without a dictionary when it names one the server doesn't have.
+    ///
+    /// A client negotiates once; returns the codec it got, or `None` when
+    /// the request is ignored.
+    pub fn negotiate_compression(
+        &self,
+        client_id: Uuid,
+        request: &CompressionRequest,
+    ) -> Option<FrameCodec> {
+        let dictionaries = self.dictionaries.as_ref()?;
+        let codec = match request.codec {
+            CompressionCodec::Gzip => FrameCodec::Gzip,
+            CompressionCodec::Zstd => FrameCodec::Zstd(
+                request
+                    .dictionary
+                    .as_deref()
+                    .and_then(|id| dictionaries.get(id)),
+            ),
+        };
+        let client = self.clients.get(&client_id)?;
+        if client.minimal_frames {
+            return None;
+        }
+        client.codec.set(codec.clone()).ok()?;
+        Some(codec)
+    }
+
     pub fn update_client_last_seen(&self, client_id: Uuid) {
         if let Some(mut client) = self.clients.get_mut(&client_id) {
             client.update_last_seen();
@@ -823,6 +1370,38 @@ impl ClientManager {
         }
     }
 
+    /// Check whether a client may refresh a subscription now, recording the
+    /// refresh if so. Refusals leave the connection open.
+    #[allow(clippy::result_large_err)]
+    pub fn check_refresh_allowed(&self, client_id: Uuid, sub_key: &str) -> Result<(), AuthDeny> {
+        let Some(client) = self.clients.get(&client_id) else {
+            return Err(AuthDeny::new(
+                crate::websocket::auth::AuthErrorCode::InternalError,
+                "Client not found",
+            ));
+        };
+
+        let interval = self.rate_limit_config.min_refresh_interval;
+        let mut refreshed_at = client
+            .refreshed_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if let Some(last) = refreshed_at.get(sub_key) {
+            let wait = interval.saturating_sub(now.duration_since(*last));
+            if !wait.is_zero() {
+                return Err(
+                    AuthDeny::rate_limited(wait, "subscription refreshes").with_context(format!(
+                        "client {} refreshed {} too often",
+                        client_id, sub_key
+                    )),
+                );
+            }
+        }
+        refreshed_at.insert(sub_key.to_string(), now);
+        Ok(())
+    }
+
     /// Get the subscription for a client.
     pub fn get_subscription(&self, client_id: Uuid) -> Option<Subscription> {
         self.clients
@@ -856,6 +1435,89 @@ impl ClientManager {
         }
     }
 
+    /// Start numbering the frames of a subscription from 1.
+    ///
+    /// Called whenever a subscription is attached. Unless `start` is
+    /// [`SequenceStart::Subscribe`], the first frame tells the client that
+    /// the sequence was restarted on its request. Returns `None` for an
+    /// unknown client.
+    pub fn subscription_sender(
+        &self,
+        client_id: Uuid,
+        sub_key: &str,
+        start: SequenceStart,
+    ) -> Option<SubscriptionSender> {
+        let client = self.clients.get(&client_id)?;
+        let generation = client.start_frame_sequence(sub_key, start);
+        Some(self.sender_for(&client, sub_key, generation))
+    }
+
+    /// Number the frames of a subscription restored from a session on from
+    /// where they stopped, so a client that lost frames in flight sees the
+    /// gap. Returns `None` for an unknown client.
+    pub(crate) fn resumed_subscription_sender(
+        &self,
+        client_id: Uuid,
+        retained: &RetainedSubscription,
+    ) -> Option<SubscriptionSender> {
+        let client = self.clients.get(&client_id)?;
+        let sub_key = retained.subscription.sub_key();
+        let generation = client.resume_frame_sequence(&sub_key, retained);
+        Some(self.sender_for(&client, &sub_key, generation))
+    }
+
+    fn sender_for(
+        &self,
+        client: &ClientInfo,
+        sub_key: &str,
+        generation: u64,
+    ) -> SubscriptionSender {
+        SubscriptionSender {
+            client_manager: self.clone(),
+            client_id: client.id,
+            sub_key: Arc::from(sub_key),
+            generation,
+            sequences: client.frame_sequences.clone(),
+            minimal: client.minimal_frames,
+            frame_size_guard: self.frame_size_guard.clone(),
+            auth_epoch: client.auth_epoch.clone(),
+            fields: None,
+            numbers: None,
+            filter: None,
+            delta: None,
+            deprecation: None,
+            codec: client.codec.clone(),
+        }
+    }
+
+    /// Clients subscribed to `view_id`, with the keys of those subscriptions
+    pub(crate) async fn view_subscriptions(&self, view_id: &str) -> Vec<(Uuid, Vec<String>)> {
+        let clients: Vec<_> = self
+            .clients
+            .iter()
+            .map(|entry| (*entry.key(), entry.subscriptions.clone()))
+            .collect();
+
+        let mut subscribed = Vec::new();
+        for (client_id, subscriptions) in clients {
+            let sub_keys: Vec<String> = subscriptions
+                .read()
+                .await
+                .keys()
+                .filter(|sub_key| {
+                    sub_key
+                        .strip_prefix(view_id)
+                        .is_some_and(|rest| rest.starts_with(':'))
+                })
+                .cloned()
+                .collect();
+            if !sub_keys.is_empty() {
+                subscribed.push((client_id, sub_keys));
+            }
+        }
+        subscribed
+    }
+
     pub async fn cancel_all_client_subscriptions(&self, client_id: Uuid) {
         if let Some(client) = self.clients.get(&client_id) {
             client.cancel_all_subscriptions().await;
@@ -883,7 +1545,7 @@ impl ClientManager {
     }
 
     /// Start a background task that periodically cleans up stale clients.
-    pub fn start_cleanup_task(&self) {
+    pub fn start_cleanup_task(&self) -> tokio::task::JoinHandle<()> {
         let client_manager = self.clone();
 
         tokio::spawn(async move {
@@ -896,7 +1558,7 @@ impl ClientManager {
                     info!("Cleaned up {} stale clients", removed);
                 }
             }
-        });
+        })
     }
 
     /// ENFORCEMENT HOOKS
@@ -1167,12 +1829,347 @@ impl ClientManager {
     }
 }
 
+/// Message carrying an uncompressed frame. Frames are sent as binary
+/// messages, except to clients in the minimal envelope of path
+/// subscriptions, which get text they can use without decoding.
+fn frame_message(frame: Bytes, minimal_frames: bool) -> Message {
+    if minimal_frames {
+        if let Ok(text) = Utf8Bytes::try_from(frame.clone()) {
+            return Message::Text(text);
+        }
+    }
+    Message::Binary(frame)
+}
+
 impl Default for ClientManager {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Sends the frames of one subscription, numbering them as they go out.
+///
+/// Every frame is stamped with the subscription key and the next number in
+/// the subscription's sequence (see [`stamp_frame_sequence`]), so the client
+/// can tell when one went missing. Numbers are assigned at send time: updates
+/// coalesced before sending take up a single number. Clients connected through
+/// a view's URL path get the [`minimal_frame`] envelope instead.
+///
+/// A sender belongs to one run of the subscription. Once the subscription is
+/// removed or restarted, its sends fail with [`SendError::SubscriptionEnded`].
+#[derive(Clone)]
+pub struct SubscriptionSender {
+    client_manager: ClientManager,
+    client_id: Uuid,
+    sub_key: Arc<str>,
+    generation: u64,
+    sequences: Arc<std::sync::Mutex<FrameSequences>>,
+    minimal: bool,
+    frame_size_guard: Option<FrameSizeGuard>,
+    auth_epoch: Arc<AtomicU64>,
+    fields: Option<Arc<SubscriptionFields>>,
+    numbers: Option<Arc<SubscriptionNumbers>>,
+    filter: Option<Arc<SubscriptionFilter>>,
+    delta: Option<Arc<SubscriptionDelta>>,
+    deprecation: Option<Arc<ViewDeprecation>>,
+    codec: Arc<OnceLock<FrameCodec>>,
+}
+
+/// The integer encoding a subscription negotiated
+struct SubscriptionNumbers {
+    mode: BigNumberMode,
+    /// Whether the client asked for an encoding
+    requested: bool,
+    /// The entity's fields typed as 64-bit integers
+    wide_fields: Arc<HashSet<String>>,
+}
+
+impl SubscriptionSender {
+    pub fn client_id(&self) -> Uuid {
+        self.client_id
+    }
+
+    pub fn sub_key(&self) -> &str {
+        &self.sub_key
+    }
+
+    /// Mask the entity frames sent for `view_id` to the fields the client's
+    /// auth context allows, intersected with `requested` when given.
+    ///
+    /// See the [`field_mask`](crate::websocket::field_mask) module.
+    pub fn with_fields(mut self, view_id: &str, requested: Option<&[String]>) -> Self {
+        let requested = requested.map(|fields| FieldMask::new(fields.iter().cloned()));
+        if self.client_manager.field_authorizer.is_some() || requested.is_some() {
+            self.fields = Some(Arc::new(SubscriptionFields::new(
+                self.client_manager.field_authorizer.clone(),
+                view_id,
+                requested,
+                self.auth_epoch.clone(),
+            )));
+        }
+        self
+    }
+
+    /// Encode the 64-bit integers in the frames sent for `entity` in the
+    /// `requested` mode, or the server's when that is stricter.
+    ///
+    /// See the [`big_numbers`](crate::big_numbers) module.
+    pub fn with_big_numbers(mut self, entity: &str, requested: Option<BigNumberMode>) -> Self {
+        let big_numbers = &self.client_manager.big_numbers;
+        self.numbers = Some(Arc::new(SubscriptionNumbers {
+            mode: big_numbers.negotiate(requested),
+            requested: requested.is_some(),
+            wide_fields: big_numbers.fields(entity),
+        }));
+        self
+    }
+
+    /// The integer encoding the subscription got, when the client asked for
+    /// one
+    pub fn negotiated_big_numbers(&self) -> Option<BigNumberMode> {
+        self.numbers
+            .as_ref()
+            .filter(|numbers| numbers.requested)
+            .map(|numbers| numbers.mode)
+    }
+
+    /// Only send the entities matching `filter`.
+    ///
+    /// See the [`filter`](crate::websocket::filter) module.
+    pub fn with_filter(mut self, filter: Option<EntityFilter>) -> Self {
+        self.filter = filter.map(|filter| Arc::new(SubscriptionFilter::new(filter)));
+        self
+    }
+
+    /// Trim live updates to the fields the client doesn't have yet.
+    ///
+    /// See the [`delta`](crate::websocket::delta) module.
+    pub fn with_delta(mut self, delta: bool) -> Self {
+        self.delta = delta.then(|| Arc::new(SubscriptionDelta::default()));
+        self
+    }
+
+    pub fn is_delta(&self) -> bool {
+        self.delta.is_some()
+    }
+
+    /// Serve a deprecated view from its replacement: frames of the
+    /// replacement go out labelled with the deprecated id.
+    pub fn with_deprecation(mut self, deprecation: ViewDeprecation) -> Self {
+        self.deprecation = Some(Arc::new(deprecation));
+        self
+    }
+
+    /// The deprecation of the view the client subscribed to, if any
+    pub fn deprecation(&self) -> Option<&ViewDeprecation> {
+        self.deprecation.as_deref()
+    }
+
+    pub fn is_filtered(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    /// Whether the subscription's filter lets `entity` through
+    pub fn filter_matches(&self, entity: &serde_json::Value) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(entity))
+    }
+
+    /// Note that the snapshot sent `data` as the entity at `key`, for the
+    /// filter to tell when it stops matching and for deltas to build on
+    pub fn note_snapshot_entity(&self, key: &str, data: &serde_json::Value) {
+        if let Some(filter) = &self.filter {
+            filter.note_sent(key);
+        }
+        if let Some(delta) = &self.delta {
+            delta.note_sent(key, data);
+        }
+    }
+
+    /// The frame to send for a live update, `None` when the filter holds it
+    /// back
+    pub fn filter_update<'a>(
+        &self,
+        message: &'a BusMessage,
+        delivery: UpdateDelivery,
+    ) -> Option<Cow<'a, [u8]>> {
+        let payload = match &self.filter {
+            Some(filter) => filter.route(message, delivery)?,
+            None => Cow::Borrowed(&message.payload_for(delivery)[..]),
+        };
+        Some(match &self.delta {
+            Some(delta) => delta.trim(message, payload),
+            None => payload,
+        })
+    }
+
+    /// Encode the integers of entity data serialized for this subscription
+    pub fn encode_numbers(&self, data: &mut serde_json::Value) {
+        match &self.numbers {
+            Some(numbers) => numbers.mode.apply(data, &numbers.wide_fields),
+            None => self
+                .client_manager
+                .big_numbers
+                .mode()
+                .apply(data, &HashSet::new()),
+        }
+    }
+
+    /// Fields the subscription currently receives, `None` when unmasked
+    pub fn field_mask(&self) -> Option<FieldMask> {
+        self.fields
+            .as_ref()?
+            .mask(|| self.client_manager.get_auth_context(self.client_id))
+    }
+
+    /// Send a frame without blocking, like [`ClientManager::send_to_client`].
+    /// Only clients that negotiated zstd get it compressed.
+    pub fn send(&self, frame: &[u8]) -> Result<(), SendError> {
+        let relabeled = self.relabeled(frame);
+        let frame = relabeled.as_deref().unwrap_or(frame);
+        let masked = self.masked(frame);
+        let frame = masked.as_deref().unwrap_or(frame);
+        let encoded = self.encoded(frame);
+        let frame = encoded.as_deref().unwrap_or(frame);
+        // Held until the frame is queued, so a restart can't slip in between
+        let mut sequences = self.sequences.lock().unwrap_or_else(|e| e.into_inner());
+        for frame in self.size_guarded(frame) {
+            let stamped = self.stamp(&mut sequences, &frame)?;
+            let payload = match self.compression() {
+                Some(codec) if codec.compresses_live_frames() => {
+                    codec.compress(&stamped).into_bytes()
+                }
+                _ => Bytes::from(stamped),
+            };
+            self.client_manager
+                .send_to_client(self.client_id, Arc::new(payload))?;
+        }
+        Ok(())
+    }
+
+    /// Send a frame, compressing it when worthwhile and waiting for queue
+    /// space, like [`ClientManager::send_compressed_async`].
+    ///
+    /// Returns the number of bytes sent.
+    pub async fn send_compressed(&self, frame: &[u8]) -> Result<usize, SendError> {
+        let relabeled = self.relabeled(frame);
+        let frame = relabeled.as_deref().unwrap_or(frame);
+        let masked = self.masked(frame);
+        let frame = masked.as_deref().unwrap_or(frame);
+        let encoded = self.encoded(frame);
+        let frame = encoded.as_deref().unwrap_or(frame);
+        let stamped = {
+            let mut sequences = self.sequences.lock().unwrap_or_else(|e| e.into_inner());
+            self.size_guarded(frame)
+                .iter()
+                .map(|frame| self.stamp(&mut sequences, frame))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let mut len = 0;
+        let compress = !self.minimal && self.client_manager.flags.enabled(Flag::Compression);
+        for stamped in stamped {
+            let payload = match (compress, self.codec.get()) {
+                (false, _) => CompressedPayload::Uncompressed(Bytes::from(stamped)),
+                (true, Some(codec)) => codec.compress(&stamped),
+                (true, None) => maybe_compress(&stamped),
+            };
+            len += payload.as_bytes().len();
+            self.client_manager
+                .send_compressed_async(self.client_id, payload)
+                .await?;
+        }
+        Ok(len)
+    }
+
+    /// The codec negotiated by the client, unless the `compression` flag is off
+    fn compression(&self) -> Option<&FrameCodec> {
+        self.codec
+            .get()
+            .filter(|_| self.client_manager.flags.enabled(Flag::Compression))
+    }
+
+    /// `frame` of the replacement view labelled with the deprecated id the
+    /// client subscribed to. `None` to send it unchanged.
+    fn relabeled(&self, frame: &[u8]) -> Option<Vec<u8>> {
+        let deprecation = self.deprecation.as_ref()?;
+        relabel_frame(frame, &deprecation.replacement, &deprecation.id)
+    }
+
+    /// `frame` masked to the subscription's fields, shared with the other
+    /// subscriptions that have the same mask. `None` to send it unchanged.
+    fn masked(&self, frame: &[u8]) -> Option<Bytes> {
+        let mask = self.field_mask()?;
+        self.client_manager.masked_frames.mask(&mask, frame)
+    }
+
+    /// `frame` re-encoded in the mode the subscription negotiated. `None`
+    /// when that is the mode it was serialized in.
+    fn encoded(&self, frame: &[u8]) -> Option<Vec<u8>> {
+        let numbers = self.numbers.as_ref()?;
+        if numbers.mode == self.client_manager.big_numbers.mode() {
+            return None;
+        }
+        encode_frame(numbers.mode, &numbers.wide_fields, frame)
+    }
+
+    /// The frames to send for `frame`, split when it exceeds the frame limit
+    fn size_guarded<'a>(&self, frame: &'a [u8]) -> Vec<Cow<'a, [u8]>> {
+        match &self.frame_size_guard {
+            Some(guard) => guard.apply(frame),
+            None => vec![Cow::Borrowed(frame)],
+        }
+    }
+
+    /// Note that the subscription has been sent the entity with `seq`, for
+    /// its session to resume after the latest one
+    pub fn advance_cursor(&self, seq: &str) {
+        let mut sequences = self.sequences.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sequence) = self.current(&mut sequences) {
+            let newer = sequence
+                .cursor
+                .as_deref()
+                .is_none_or(|cursor| cmp_seq(seq, cursor).is_gt());
+            if newer {
+                sequence.cursor = Some(seq.to_string());
+            }
+        }
+    }
+
+    /// Use up a number without sending a frame, for updates dropped before
+    /// they reached the client. The client sees the gap and resyncs.
+    pub fn skip(&self) {
+        let mut sequences = self.sequences.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sequence) = self.current(&mut sequences) {
+            sequence.last += 1;
+        }
+    }
+
+    fn current<'a>(&self, sequences: &'a mut FrameSequences) -> Option<&'a mut FrameSequence> {
+        sequences
+            .by_sub
+            .get_mut(&*self.sub_key)
+            .filter(|sequence| sequence.generation == self.generation)
+    }
+
+    fn stamp(&self, sequences: &mut FrameSequences, frame: &[u8]) -> Result<Vec<u8>, SendError> {
+        let sequence = self
+            .current(sequences)
+            .ok_or(SendError::SubscriptionEnded)?;
+        sequence.last += 1;
+        let start = std::mem::take(&mut sequence.start);
+        if self.minimal {
+            return Ok(minimal_frame(frame));
+        }
+        Ok(stamp_frame_sequence(
+            frame,
+            &self.sub_key,
+            sequence.last,
+            start,
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1273,6 +2270,28 @@ mod tests {
         assert_eq!(client.record_inbound_message(), None);
     }
 
+    #[tokio::test]
+    async fn test_queue_pressure_reports_fullest_client() {
+        let manager = ClientManager::new();
+        assert_eq!(manager.queue_pressure(), 0.0);
+
+        let mut receivers = Vec::new();
+        for queued in [1, 3] {
+            let (tx, rx) = mpsc::channel(4);
+            for _ in 0..queued {
+                tx.try_send(Message::Text("frame".into())).unwrap();
+            }
+            let id = Uuid::new_v4();
+            let addr = create_test_socket_addr("127.0.0.1");
+            manager
+                .clients
+                .insert(id, ClientInfo::new(id, tx, None, addr));
+            receivers.push(rx);
+        }
+
+        assert_eq!(manager.queue_pressure(), 0.75);
+    }
+
     #[tokio::test]
     async fn test_no_limits() {
         let manager = ClientManager::new();
@@ -1327,6 +2346,7 @@ mod tests {
         assert!(config.max_reconnect_attempts.is_none());
         assert_eq!(config.message_rate_window, Duration::from_secs(60));
         assert_eq!(config.egress_rate_window, Duration::from_secs(60));
+        assert_eq!(config.overload_retry_after, Duration::from_secs(5));
     }
 
     #[test]
@@ -1335,13 +2355,15 @@ mod tests {
             .with_max_connections_per_ip(10)
             .with_timeout(Duration::from_secs(600))
             .with_message_queue_size(1024)
-            .with_rate_limit_window(Duration::from_secs(120));
+            .with_rate_limit_window(Duration::from_secs(120))
+            .with_overload_retry_after(Duration::from_millis(1500));
 
         assert_eq!(config.max_connections_per_ip, Some(10));
         assert_eq!(config.client_timeout, Duration::from_secs(600));
         assert_eq!(config.message_queue_size, 1024);
         assert_eq!(config.message_rate_window, Duration::from_secs(120));
         assert_eq!(config.egress_rate_window, Duration::from_secs(120));
+        assert_eq!(config.overload_retry_after, Duration::from_millis(1500));
     }
 
     #[tokio::test]
@@ -1483,4 +2505,96 @@ mod tests {
             .await
             .is_ok());
     }
+
+    fn register_queue_client(manager: &ClientManager) -> (Uuid, mpsc::Receiver<Message>) {
+        let client_id = Uuid::new_v4();
+        let (tx, rx) = mpsc::channel(16);
+        let addr = create_test_socket_addr("127.0.0.1");
+        manager
+            .clients
+            .insert(client_id, ClientInfo::new(client_id, tx, None, addr));
+        (client_id, rx)
+    }
+
+    fn next_frame(rx: &mut mpsc::Receiver<Message>) -> serde_json::Value {
+        match rx.try_recv().expect("a queued frame") {
+            Message::Binary(bytes) => serde_json::from_slice(&bytes).unwrap(),
+            other => panic!("expected a binary frame, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn subscription_sender_numbers_frames_per_subscription() {
+        let manager = ClientManager::new();
+        let (client_id, mut rx) = register_queue_client(&manager);
+
+        let list = manager
+            .subscription_sender(client_id, "tokens/list:*", SequenceStart::Subscribe)
+            .unwrap();
+        let state = manager
+            .subscription_sender(client_id, "tokens/state:abc", SequenceStart::Subscribe)
+            .unwrap();
+
+        list.send(br#"{"op":"upsert"}"#).unwrap();
+        state.send(br#"{"op":"patch"}"#).unwrap();
+        list.send(br#"{"op":"upsert"}"#).unwrap();
+        list.skip();
+        list.send_compressed(br#"{"op":"delete"}"#).await.unwrap();
+
+        let frames: Vec<_> = (0..4).map(|_| next_frame(&mut rx)).collect();
+        let numbered: Vec<_> = frames
+            .iter()
+            .map(|frame| {
+                (
+                    frame["sub"].as_str().unwrap(),
+                    frame["frameSeq"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            numbered,
+            vec![
+                ("tokens/list:*", 1),
+                ("tokens/state:abc", 1),
+                ("tokens/list:*", 2),
+                ("tokens/list:*", 4),
+            ]
+        );
+        assert_eq!(frames[3]["op"], "delete");
+        assert!(frames.iter().all(|frame| frame.get("resync").is_none()));
+    }
+
+    #[tokio::test]
+    async fn restarting_a_sequence_retires_the_old_sender() {
+        let manager = ClientManager::new();
+        let (client_id, mut rx) = register_queue_client(&manager);
+        let sub_key = "tokens/list:*";
+
+        let old = manager
+            .subscription_sender(client_id, sub_key, SequenceStart::Subscribe)
+            .unwrap();
+        old.send(b"{}").unwrap();
+        old.send(b"{}").unwrap();
+        let _ = (next_frame(&mut rx), next_frame(&mut rx));
+
+        let resynced = manager
+            .subscription_sender(client_id, sub_key, SequenceStart::Resync)
+            .unwrap();
+        assert_eq!(old.send(b"{}"), Err(SendError::SubscriptionEnded));
+
+        resynced.send(b"{}").unwrap();
+        resynced.send(b"{}").unwrap();
+        let first = next_frame(&mut rx);
+        let second = next_frame(&mut rx);
+        assert_eq!(first["frameSeq"], 1);
+        assert_eq!(first["resync"], true);
+        assert_eq!(second["frameSeq"], 2);
+        assert!(second.get("resync").is_none());
+
+        manager
+            .add_client_subscription(client_id, sub_key.to_string(), CancellationToken::new())
+            .await;
+        manager.remove_client_subscription(client_id, sub_key).await;
+        assert_eq!(resynced.send(b"{}"), Err(SendError::SubscriptionEnded));
+    }
 }
@@ -0,0 +1,363 @@
+//! Delta delivery for list subscriptions.
+//!
+//! A subscription to a `List` view may ask for deltas:
+//!
+//! ```json
+//! {"type":"subscribe","view":"OreMiner/list","delta":true}
+//! ```
+//!
+//! The server then remembers, per entity, what it has sent the client, and
+//! trims each live update down to the leaf fields that differ from it.
+//! Entities that update often while most of their fields stay put, such as
+//! a bonding curve written several times a slot, cost a fraction of the
+//! bytes.
+//!
+//! - an entity the client has never been sent is sent whole, as an
+//!   `upsert`, when the projector serialized it, and untrimmed otherwise;
+//! - `patch` frames keep only the fields that changed. Arrays and other
+//!   non-object values are compared whole, and fields at append paths or
+//!   set to `null` are always kept;
+//! - `upsert` frames of [`UpdateDelivery::FullState`](super::UpdateDelivery)
+//!   subscriptions become `patch` frames, with `null` for the fields the
+//!   entity no longer has;
+//! - an update that changes nothing is still sent, with empty data, so
+//!   frame sequences and resume cursors move on as usual.
+//!
+//! Clients merge the trimmed frames like any other patch. Deltas are
+//! ignored for `State`, `Append` and derived views.
+
+use super::frame::Mode;
+use crate::bus::BusMessage;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// An entity frame as the projector serializes it
+#[derive(Serialize, Deserialize)]
+struct EntityFrame {
+    mode: Mode,
+    entity: String,
+    op: String,
+    key: String,
+    data: Value,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    append: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    seq: Option<String>,
+}
+
+/// What one delta subscription has sent its client. See the
+/// [module docs](self).
+#[derive(Default)]
+pub(crate) struct SubscriptionDelta {
+    /// Each entity as the client holds it, without its append paths
+    sent: Mutex<HashMap<String, Value>>,
+}
+
+impl SubscriptionDelta {
+    /// Note that the entity at `key` was sent whole as `data`
+    pub(crate) fn note_sent(&self, key: &str, data: &Value) {
+        self.sent().insert(key.to_string(), data.clone());
+    }
+
+    /// `payload`, the frame routed for `message`, trimmed to what the client
+    /// doesn't have yet
+    pub(crate) fn trim<'a>(
+        &self,
+        message: &'a BusMessage,
+        payload: Cow<'a, [u8]>,
+    ) -> Cow<'a, [u8]> {
+        let Ok(mut frame) = serde_json::from_slice::<EntityFrame>(&payload) else {
+            return payload;
+        };
+        let mut sent = self.sent();
+
+        match frame.op.as_str() {
+            "delete" => {
+                sent.remove(&frame.key);
+                return payload;
+            }
+            "patch" | "upsert" => {}
+            _ => return payload,
+        }
+
+        let Some(known) = sent.get_mut(&frame.key) else {
+            // The first frame of an entity carries all of it
+            if frame.op == "patch" {
+                if let Some(full_state) = message.full_state.as_deref() {
+                    if let Ok(whole) = serde_json::from_slice::<EntityFrame>(full_state) {
+                        sent.insert(frame.key, whole.data);
+                        return Cow::Borrowed(full_state.as_ref());
+                    }
+                }
+            }
+            let mut data = frame.data;
+            strip_paths(&mut data, &frame.append);
+            sent.insert(frame.key, data);
+            return payload;
+        };
+
+        let replace = frame.op == "upsert";
+        let delta = changed(known, &frame.data, "", &frame.append, replace)
+            .unwrap_or_else(|| Value::Object(Map::new()));
+        if replace {
+            *known = frame.data;
+            strip_paths(known, &frame.append);
+        } else {
+            merge(known, &frame.data, "", &frame.append);
+        }
+
+        frame.op = "patch".to_string();
+        frame.data = delta;
+        match serde_json::to_vec(&frame) {
+            Ok(trimmed) => Cow::Owned(trimmed),
+            Err(_) => payload,
+        }
+    }
+
+    fn sent(&self) -> std::sync::MutexGuard<'_, HashMap<String, Value>> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn child_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+/// The part of `update` that changes `known`, `None` when nothing does.
+///
+/// Objects are compared field by field and anything else as a whole. With
+/// `replace`, `update` is the whole value and the fields it lacks are
+/// cleared with `null`.
+fn changed(
+    known: &Value,
+    update: &Value,
+    path: &str,
+    append: &[String],
+    replace: bool,
+) -> Option<Value> {
+    if update.is_null() || append.iter().any(|append| append == path) {
+        return Some(update.clone());
+    }
+
+    match (known, update) {
+        (Value::Object(known), Value::Object(update)) => {
+            let mut delta = Map::new();
+            for (field, value) in update {
+                let field_delta = match known.get(field) {
+                    Some(known) => changed(known, value, &child_path(path, field), append, replace),
+                    None => Some(value.clone()),
+                };
+                if let Some(field_delta) = field_delta {
+                    delta.insert(field.clone(), field_delta);
+                }
+            }
+            if replace {
+                for field in known.keys() {
+                    if !update.contains_key(field) {
+                        delta.insert(field.clone(), Value::Null);
+                    }
+                }
+            }
+            (!delta.is_empty()).then_some(Value::Object(delta))
+        }
+        (known, update) if known == update => None,
+        _ => Some(update.clone()),
+    }
+}
+
+/// Merge `patch` into `known` the way clients do, leaving out append paths
+fn merge(known: &mut Value, patch: &Value, path: &str, append: &[String]) {
+    match (known, patch) {
+        (Value::Object(known), Value::Object(patch)) => {
+            for (field, value) in patch {
+                let field_path = child_path(path, field);
+                if value.is_null() || append.contains(&field_path) {
+                    known.remove(field);
+                    continue;
+                }
+                match known.get_mut(field) {
+                    Some(known) => merge(known, value, &field_path, append),
+                    None => {
+                        let mut value = value.clone();
+                        strip_paths_inner(&mut value, &field_path, append);
+                        known.insert(field.clone(), value);
+                    }
+                }
+            }
+        }
+        (known, patch) => *known = patch.clone(),
+    }
+}
+
+/// Remove the fields at `paths` from `data`
+fn strip_paths(data: &mut Value, paths: &[String]) {
+    strip_paths_inner(data, "", paths);
+}
+
+fn strip_paths_inner(data: &mut Value, path: &str, paths: &[String]) {
+    if paths.is_empty() {
+        return;
+    }
+    if let Value::Object(fields) = data {
+        fields.retain(|field, _| !paths.contains(&child_path(path, field)));
+        for (field, value) in fields.iter_mut() {
+            strip_paths_inner(value, &child_path(path, field), paths);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn frame(op: &str, key: &str, data: Value, append: &[&str]) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "mode": "list",
+            "entity": "OreMiner/list",
+            "op": op,
+            "key": key,
+            "data": data,
+            "append": append,
+        }))
+        .unwrap()
+    }
+
+    fn message(key: &str, payload: Vec<u8>, full_state: Option<Vec<u8>>) -> BusMessage {
+        BusMessage::new(
+            key.to_string(),
+            "OreMiner/list".to_string(),
+            Arc::new(Bytes::from(payload)),
+        )
+        .with_full_state(full_state.map(|full_state| Arc::new(Bytes::from(full_state))))
+    }
+
+    /// Route `payload` for `key` through `delta`, returning the sent frame
+    fn send(delta: &SubscriptionDelta, key: &str, payload: Vec<u8>) -> Value {
+        let message = message(key, payload, None);
+        let trimmed = delta.trim(&message, Cow::Borrowed(message.payload.as_ref()));
+        serde_json::from_slice(&trimmed).unwrap()
+    }
+
+    fn miner() -> Value {
+        json!({
+            "id": { "authority": "miner-1" },
+            "state": { "rewards_sol": 10, "rewards_ore": 20, "lifetime_deployed": 300 },
+            "automation": { "amount": 5 },
+        })
+    }
+
+    #[test]
+    fn a_single_changed_field_is_sent_alone() {
+        let delta = SubscriptionDelta::default();
+        delta.note_sent("miner-1", &miner());
+
+        let mut update = miner();
+        update["state"]["rewards_sol"] = json!(11);
+        let full_patch = frame("patch", "miner-1", update, &[]);
+        let sent = send(&delta, "miner-1", full_patch.clone());
+
+        assert_eq!(sent["op"], json!("patch"));
+        assert_eq!(sent["data"], json!({ "state": { "rewards_sol": 11 } }));
+        let trimmed = serde_json::to_vec(&sent).unwrap();
+        assert!(trimmed.len() < full_patch.len());
+    }
+
+    #[test]
+    fn unchanged_updates_are_sent_empty() {
+        let delta = SubscriptionDelta::default();
+        delta.note_sent("miner-1", &miner());
+
+        let sent = send(&delta, "miner-1", frame("patch", "miner-1", miner(), &[]));
+        assert_eq!(sent["data"], json!({}));
+    }
+
+    #[test]
+    fn unseen_entities_are_sent_whole() {
+        let delta = SubscriptionDelta::default();
+        let patch = frame(
+            "patch",
+            "miner-2",
+            json!({ "state": { "rewards_sol": 1 } }),
+            &[],
+        );
+        let full_state = frame("upsert", "miner-2", miner(), &[]);
+        let message = message("miner-2", patch, Some(full_state));
+
+        let sent = delta.trim(&message, Cow::Borrowed(message.payload.as_ref()));
+        let sent: Value = serde_json::from_slice(&sent).unwrap();
+        assert_eq!(sent["op"], json!("upsert"));
+        assert_eq!(sent["data"], miner());
+
+        // Later updates are trimmed against the whole entity
+        let mut update = miner();
+        update["automation"]["amount"] = json!(6);
+        let sent = send(&delta, "miner-2", frame("patch", "miner-2", update, &[]));
+        assert_eq!(sent["data"], json!({ "automation": { "amount": 6 } }));
+    }
+
+    #[test]
+    fn append_paths_and_nulls_are_always_sent() {
+        let delta = SubscriptionDelta::default();
+        let first = frame(
+            "patch",
+            "round",
+            json!({ "deploys": [1], "total": 1 }),
+            &["deploys"],
+        );
+        send(&delta, "round", first);
+
+        let sent = send(
+            &delta,
+            "round",
+            frame(
+                "patch",
+                "round",
+                json!({ "deploys": [1], "total": 1, "winner": null }),
+                &["deploys"],
+            ),
+        );
+        assert_eq!(sent["data"], json!({ "deploys": [1], "winner": null }));
+        assert_eq!(sent["append"], json!(["deploys"]));
+    }
+
+    #[test]
+    fn full_state_updates_become_patches_clearing_removed_fields() {
+        let delta = SubscriptionDelta::default();
+        delta.note_sent("miner-1", &miner());
+
+        let mut update = miner();
+        update["state"]["rewards_ore"] = json!(21);
+        update.as_object_mut().unwrap().remove("automation");
+        let sent = send(&delta, "miner-1", frame("upsert", "miner-1", update, &[]));
+
+        assert_eq!(sent["op"], json!("patch"));
+        assert_eq!(
+            sent["data"],
+            json!({ "state": { "rewards_ore": 21 }, "automation": null })
+        );
+    }
+
+    #[test]
+    fn deleted_entities_are_forgotten() {
+        let delta = SubscriptionDelta::default();
+        delta.note_sent("miner-1", &miner());
+        send(
+            &delta,
+            "miner-1",
+            frame("delete", "miner-1", Value::Null, &[]),
+        );
+
+        let sent = send(&delta, "miner-1", frame("patch", "miner-1", miner(), &[]));
+        assert_eq!(sent["data"], miner());
+    }
+}
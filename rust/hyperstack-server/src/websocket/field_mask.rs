@@ -0,0 +1,432 @@
+//! Per-view field masks for authorization.
+//!
+//! A [`FieldAuthorizer`] decides from a client's auth claims which fields of
+//! a view it may see, e.g. a free plan that gets prices but not the trades
+//! array. [`StaticFieldAuthorizer`] reads the masks per plan from a config
+//! file.
+//!
+//! The mask is applied to every entity frame of a subscription, snapshots
+//! and patches alike, right before the frame is sent. A subscription that
+//! asks for specific `fields` gets the intersection of those and the mask.
+//! Clients without a mask keep sending the frames the projector serialized
+//! once; masked frames are re-encoded once per distinct mask and shared by
+//! every subscription with that mask.
+//!
+//! Masks are resolved again on the first frame after a client's auth context
+//! changes, e.g. after an in-band `refresh_auth` moves it to another plan.
+//! Entities the client already holds keep the fields they had.
+
+use super::auth::AuthContext;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Distinct masks whose latest masked frame is kept for sharing
+const MAX_SHARED_MASKS: usize = 1024;
+
+/// Fields of an entity a subscription may see.
+///
+/// Fields are dotted paths: `"price"` allows the whole `price` section,
+/// `"trades.count"` only that field of `trades`. Top-level fields starting
+/// with `_`, such as `_seq`, are always kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "Vec<String>", into = "Vec<String>")]
+pub struct FieldMask {
+    paths: Arc<BTreeSet<String>>,
+}
+
+impl FieldMask {
+    pub fn new(paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            paths: Arc::new(paths.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.paths.iter().map(String::as_str)
+    }
+
+    /// Whether `path` is one of the mask's fields or nested inside one
+    pub fn allows(&self, path: &str) -> bool {
+        self.paths.iter().any(|allowed| {
+            path.strip_prefix(allowed.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+    }
+
+    /// Fields allowed by both masks
+    pub fn intersect(&self, other: &FieldMask) -> FieldMask {
+        let paths = self
+            .paths()
+            .filter(|path| other.allows(path))
+            .chain(other.paths().filter(|path| self.allows(path)));
+        FieldMask::new(paths)
+    }
+
+    /// Remove the fields the mask doesn't allow from entity data
+    pub fn apply(&self, data: &mut Value) {
+        if let Value::Object(fields) = data {
+            self.retain_allowed(fields, "");
+        }
+    }
+
+    /// Mask the entity data of a serialized frame.
+    ///
+    /// Returns `None` for frames without entity data, such as `delete` or
+    /// `retention_notice`, which are sent unchanged.
+    pub fn apply_frame(&self, frame: &[u8]) -> Option<Vec<u8>> {
+        let mut fields = serde_json::from_slice::<Map<String, Value>>(frame).ok()?;
+        match fields.get("op").and_then(Value::as_str)? {
+            "snapshot" | "history" => {
+                let Some(Value::Array(entities)) = fields.get_mut("data") else {
+                    return None;
+                };
+                for entity in entities.iter_mut().filter_map(Value::as_object_mut) {
+                    self.mask_entity(entity);
+                }
+            }
+            "patch" | "upsert" | "create" => self.mask_entity(&mut fields),
+            _ => return None,
+        }
+        serde_json::to_vec(&fields).ok()
+    }
+
+    /// Mask the `data` and `append` of a frame or of one entity in a batch
+    fn mask_entity(&self, entity: &mut Map<String, Value>) {
+        if let Some(Value::Object(data)) = entity.get_mut("data") {
+            self.retain_allowed(data, "");
+        }
+        if let Some(Value::Array(append)) = entity.get_mut("append") {
+            append.retain(|path| path.as_str().is_some_and(|path| self.allows(path)));
+        }
+    }
+
+    fn retain_allowed(&self, fields: &mut Map<String, Value>, prefix: &str) {
+        fields.retain(|name, value| {
+            if prefix.is_empty() && name.starts_with('_') {
+                return true;
+            }
+            let path = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{prefix}.{name}")
+            };
+            if self.allows(&path) {
+                return true;
+            }
+
+            let nested = format!("{path}.");
+            if !self
+                .paths
+                .iter()
+                .any(|allowed| allowed.starts_with(&nested))
+            {
+                return false;
+            }
+            match value {
+                Value::Object(inner) => {
+                    self.retain_allowed(inner, &path);
+                    !inner.is_empty()
+                }
+                _ => false,
+            }
+        });
+    }
+}
+
+impl From<Vec<String>> for FieldMask {
+    fn from(paths: Vec<String>) -> Self {
+        FieldMask::new(paths)
+    }
+}
+
+impl From<FieldMask> for Vec<String> {
+    fn from(mask: FieldMask) -> Self {
+        mask.paths().map(str::to_string).collect()
+    }
+}
+
+/// Decides which fields of a view a client may see
+pub trait FieldAuthorizer: Send + Sync {
+    /// Fields of `view_id` a client with `auth` may see, or `None` for all
+    fn allowed_fields(&self, auth: Option<&AuthContext>, view_id: &str) -> Option<FieldMask>;
+}
+
+/// Field masks per plan, as read from a JSON config file.
+///
+/// ```json
+/// {
+///   "default_plan": "free",
+///   "plans": {
+///     "free": { "Token/list": ["name", "price"] },
+///     "pro": {}
+///   }
+/// }
+/// ```
+///
+/// Clients use the masks of their token's `plan`, or of `default_plan` when
+/// they have no plan or one the config doesn't list. Views a plan doesn't
+/// list, and clients left without a plan, are not masked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StaticFieldAuthorizer {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_plan: Option<String>,
+    /// Allowed fields by plan, then by view
+    #[serde(default)]
+    pub plans: HashMap<String, HashMap<String, FieldMask>>,
+}
+
+impl StaticFieldAuthorizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json)
+            .map_err(|e| anyhow::anyhow!("invalid field mask config {}: {e}", path.display()))
+    }
+
+    pub fn with_default_plan(mut self, plan: impl Into<String>) -> Self {
+        self.default_plan = Some(plan.into());
+        self
+    }
+
+    /// Limit clients on `plan` to `fields` of `view_id`
+    pub fn with_view_fields(
+        mut self,
+        plan: impl Into<String>,
+        view_id: impl Into<String>,
+        fields: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.plans
+            .entry(plan.into())
+            .or_default()
+            .insert(view_id.into(), FieldMask::new(fields));
+        self
+    }
+
+    fn plan_views(&self, auth: Option<&AuthContext>) -> Option<&HashMap<String, FieldMask>> {
+        auth.and_then(|auth| auth.plan.as_ref())
+            .and_then(|plan| self.plans.get(plan))
+            .or_else(|| {
+                self.default_plan
+                    .as_ref()
+                    .and_then(|plan| self.plans.get(plan))
+            })
+    }
+}
+
+impl FieldAuthorizer for StaticFieldAuthorizer {
+    fn allowed_fields(&self, auth: Option<&AuthContext>, view_id: &str) -> Option<FieldMask> {
+        self.plan_views(auth)?.get(view_id).cloned()
+    }
+}
+
+/// Latest masked frame per mask, so that subscriptions with the same mask
+/// encode each frame once between them
+#[derive(Clone, Default)]
+pub(crate) struct MaskedFrames {
+    latest: Arc<Mutex<HashMap<FieldMask, MaskedFrame>>>,
+}
+
+struct MaskedFrame {
+    source: Bytes,
+    /// `None` when the frame is sent unchanged
+    masked: Option<Bytes>,
+}
+
+impl MaskedFrames {
+    /// `frame` masked with `mask`, or `None` if masking leaves it unchanged
+    pub(crate) fn mask(&self, mask: &FieldMask, frame: &[u8]) -> Option<Bytes> {
+        {
+            let latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(cached) = latest.get(mask).filter(|cached| cached.source == frame) {
+                return cached.masked.clone();
+            }
+        }
+
+        let masked = mask.apply_frame(frame).map(Bytes::from);
+        let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+        if latest.len() >= MAX_SHARED_MASKS && !latest.contains_key(mask) {
+            latest.clear();
+        }
+        latest.insert(
+            mask.clone(),
+            MaskedFrame {
+                source: Bytes::copy_from_slice(frame),
+                masked: masked.clone(),
+            },
+        );
+        masked
+    }
+}
+
+/// The mask of one subscription, resolved again whenever the client's auth
+/// context changes
+pub(crate) struct SubscriptionFields {
+    authorizer: Option<Arc<dyn FieldAuthorizer>>,
+    view_id: String,
+    requested: Option<FieldMask>,
+    auth_epoch: Arc<AtomicU64>,
+    /// Mask along with the auth epoch it was resolved at
+    resolved: Mutex<Option<(u64, Option<FieldMask>)>>,
+}
+
+impl SubscriptionFields {
+    pub(crate) fn new(
+        authorizer: Option<Arc<dyn FieldAuthorizer>>,
+        view_id: &str,
+        requested: Option<FieldMask>,
+        auth_epoch: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            authorizer,
+            view_id: view_id.to_string(),
+            requested,
+            auth_epoch,
+            resolved: Mutex::new(None),
+        }
+    }
+
+    /// The current mask, calling `auth` for the client's auth context only
+    /// when it changed since the mask was last resolved
+    pub(crate) fn mask(&self, auth: impl FnOnce() -> Option<AuthContext>) -> Option<FieldMask> {
+        let epoch = self.auth_epoch.load(Ordering::Acquire);
+        let mut resolved = self.resolved.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((resolved_at, mask)) = resolved.as_ref() {
+            if *resolved_at == epoch {
+                return mask.clone();
+            }
+        }
+
+        let allowed = self
+            .authorizer
+            .as_ref()
+            .and_then(|authorizer| authorizer.allowed_fields(auth().as_ref(), &self.view_id));
+        let mask = match (allowed, &self.requested) {
+            (Some(allowed), Some(requested)) => Some(allowed.intersect(requested)),
+            (allowed, requested) => allowed.or_else(|| requested.clone()),
+        };
+        *resolved = Some((epoch, mask.clone()));
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn mask_keeps_allowed_paths_and_meta_fields() {
+        let mask = FieldMask::new(["price", "trades.count"]);
+        let mut data = json!({
+            "_seq": "1",
+            "price": { "last": 3, "open": 2 },
+            "trades": { "count": 4, "history": [1, 2] },
+            "holders": { "count": 9 },
+        });
+        mask.apply(&mut data);
+
+        assert_eq!(
+            data,
+            json!({
+                "_seq": "1",
+                "price": { "last": 3, "open": 2 },
+                "trades": { "count": 4 },
+            })
+        );
+    }
+
+    #[test]
+    fn intersection_keeps_the_narrower_path() {
+        let allowed = FieldMask::new(["price", "trades.count"]);
+        let requested = FieldMask::new(["price.last", "trades", "holders"]);
+
+        assert_eq!(
+            allowed.intersect(&requested),
+            FieldMask::new(["price.last", "trades.count"])
+        );
+    }
+
+    #[test]
+    fn frames_without_entity_data_are_left_alone() {
+        let mask = FieldMask::new(["price"]);
+        let patch = json!({
+            "op": "patch",
+            "key": "a",
+            "data": { "price": 1, "trades": [] },
+            "append": ["trades"],
+        });
+        let masked: Value =
+            serde_json::from_slice(&mask.apply_frame(patch.to_string().as_bytes()).unwrap())
+                .unwrap();
+        assert_eq!(masked["data"], json!({ "price": 1 }));
+        assert_eq!(masked["append"], json!([]));
+
+        let delete = json!({ "op": "delete", "key": "a", "data": null });
+        assert!(mask.apply_frame(delete.to_string().as_bytes()).is_none());
+    }
+
+    #[test]
+    fn subscriptions_with_the_same_mask_share_the_masked_frame() {
+        let frames = MaskedFrames::default();
+        let patch = json!({ "op": "patch", "key": "a", "data": { "price": 1, "trades": [] } });
+        let patch = patch.to_string();
+
+        let first = frames
+            .mask(&FieldMask::new(["price"]), patch.as_bytes())
+            .unwrap();
+        let second = frames
+            .mask(&FieldMask::new(["price"]), patch.as_bytes())
+            .unwrap();
+        assert_eq!(first.as_ptr(), second.as_ptr());
+
+        let other = frames
+            .mask(&FieldMask::new(["trades"]), patch.as_bytes())
+            .unwrap();
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn static_authorizer_falls_back_to_the_default_plan() {
+        let authorizer = StaticFieldAuthorizer::from_json(
+            r#"{
+                "default_plan": "free",
+                "plans": { "free": { "Token/list": ["price"] }, "pro": {} }
+            }"#,
+        )
+        .unwrap();
+        let plan = |plan: &str| {
+            AuthContext::from_claims(
+                hyperstack_auth::SessionClaims::builder("iss", "sub", "aud")
+                    .with_plan(plan)
+                    .build(),
+            )
+        };
+
+        assert_eq!(
+            authorizer.allowed_fields(None, "Token/list"),
+            Some(FieldMask::new(["price"]))
+        );
+        assert_eq!(
+            authorizer.allowed_fields(Some(&plan("unknown")), "Token/list"),
+            Some(FieldMask::new(["price"]))
+        );
+        assert_eq!(
+            authorizer.allowed_fields(Some(&plan("pro")), "Token/list"),
+            None
+        );
+        assert_eq!(authorizer.allowed_fields(None, "Token/state"), None);
+    }
+}
@@ -0,0 +1,308 @@
+//! Server-side filters on list subscriptions.
+//!
+//! A subscription to a `List` view may carry a filter on one field of the
+//! entity, as a dotted path:
+//!
+//! ```json
+//! {"type":"subscribe","view":"Round/list",
+//!  "filter":{"field":"state.total_deployed","op":"gte","value":1000000}}
+//! ```
+//!
+//! Only entities matching the filter are sent, in the snapshot and live.
+//! Live updates are checked against the whole entity after the update, which
+//! the projector serializes for filtered subscriptions:
+//!
+//! - an entity the client holds gets its patches as usual while it matches;
+//! - an entity that starts matching is sent whole, as an `upsert`;
+//! - an entity that stops matching is sent as a `delete`, so the client
+//!   drops it from its store.
+//!
+//! Operators are `eq`, `ne`, `gt`, `gte`, `lt`, `lte` and `contains`.
+//! Numbers compare by value, including integers the server encodes as
+//! strings (see [`big_numbers`](crate::big_numbers)); other values compare
+//! as JSON. `contains` matches an array holding the value or a string
+//! holding the substring. A missing field is `null`, so it matches `ne`
+//! and `eq: null` only.
+//!
+//! Filters are ignored for `State`, `Append` and derived views.
+
+use super::frame::{Frame, Mode};
+use super::subscription::UpdateDelivery;
+use crate::bus::BusMessage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Comparison a filter makes between a field and its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Contains,
+}
+
+/// A predicate on one field of an entity. See the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityFilter {
+    /// Dotted path of the field, e.g. `state.total_deployed`
+    pub field: String,
+    pub op: FilterOp,
+    pub value: Value,
+}
+
+impl EntityFilter {
+    pub fn new(field: impl Into<String>, op: FilterOp, value: impl Into<Value>) -> Self {
+        Self {
+            field: field.into(),
+            op,
+            value: value.into(),
+        }
+    }
+
+    /// Whether `entity` passes the filter
+    pub fn matches(&self, entity: &Value) -> bool {
+        let field = self
+            .field
+            .split('.')
+            .try_fold(entity, |value, segment| value.get(segment))
+            .unwrap_or(&Value::Null);
+        let ordering = || compare(field, &self.value);
+
+        match self.op {
+            FilterOp::Eq => equal(field, &self.value),
+            FilterOp::Ne => !equal(field, &self.value),
+            FilterOp::Gt => ordering() == Some(Ordering::Greater),
+            FilterOp::Gte => matches!(ordering(), Some(Ordering::Greater | Ordering::Equal)),
+            FilterOp::Lt => ordering() == Some(Ordering::Less),
+            FilterOp::Lte => matches!(ordering(), Some(Ordering::Less | Ordering::Equal)),
+            FilterOp::Contains => match (field, &self.value) {
+                (Value::Array(items), value) => items.iter().any(|item| equal(item, value)),
+                (Value::String(text), Value::String(part)) => text.contains(part.as_str()),
+                _ => false,
+            },
+        }
+    }
+}
+
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(number) => number
+                .as_i64()
+                .map(i128::from)
+                .or_else(|| number.as_u64().map(i128::from))
+                .map(Number::Int)
+                .or_else(|| number.as_f64().map(Number::Float)),
+            Value::String(text) => text
+                .parse::<i128>()
+                .map(Number::Int)
+                .ok()
+                .or_else(|| text.parse::<f64>().ok().map(Number::Float)),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match *self {
+            Number::Int(int) => int as f64,
+            Number::Float(float) => float,
+        }
+    }
+}
+
+/// Order of two values: numerically when either is a number and the other
+/// reads as one, otherwise only between two strings
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    if a.is_number() || b.is_number() {
+        return match (Number::of(a)?, Number::of(b)?) {
+            (Number::Int(a), Number::Int(b)) => Some(a.cmp(&b)),
+            (a, b) => a.as_f64().partial_cmp(&b.as_f64()),
+        };
+    }
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    a == b || compare(a, b) == Some(Ordering::Equal)
+}
+
+/// A list subscription's filter, along with the entities it let through
+pub(crate) struct SubscriptionFilter {
+    filter: EntityFilter,
+    /// Keys of the entities sent to the client and not deleted since
+    sent: Mutex<HashSet<String>>,
+}
+
+#[derive(Deserialize)]
+struct FullState {
+    data: Value,
+}
+
+impl SubscriptionFilter {
+    pub fn new(filter: EntityFilter) -> Self {
+        Self {
+            filter,
+            sent: Mutex::default(),
+        }
+    }
+
+    pub fn matches(&self, entity: &Value) -> bool {
+        self.filter.matches(entity)
+    }
+
+    /// Note that the snapshot included `key`
+    pub fn note_sent(&self, key: &str) {
+        self.sent().insert(key.to_string());
+    }
+
+    /// The frame to send for a live update: the update itself while the
+    /// entity keeps matching, the whole entity when it starts matching, a
+    /// `delete` when it stops, and nothing for entities that never matched.
+    pub fn route<'a>(
+        &self,
+        message: &'a BusMessage,
+        delivery: UpdateDelivery,
+    ) -> Option<Cow<'a, [u8]>> {
+        let matches = message.full_state.as_ref().map(|full_state| {
+            serde_json::from_slice::<FullState>(full_state)
+                .is_ok_and(|full_state| self.filter.matches(&full_state.data))
+        });
+        let mut sent = self.sent();
+        let was_sent = sent.contains(&message.key);
+
+        match (matches, was_sent) {
+            // Without the entity to check, clients keep what they hold
+            (None, true) | (Some(true), true) => {
+                Some(Cow::Borrowed(message.payload_for(delivery).as_ref()))
+            }
+            (Some(true), false) => {
+                sent.insert(message.key.clone());
+                Some(Cow::Borrowed(message.full_state.as_deref()?.as_ref()))
+            }
+            (Some(false), true) => {
+                sent.remove(&message.key);
+                let delete = Frame {
+                    mode: Mode::List,
+                    export: message.entity.clone(),
+                    op: "delete",
+                    key: message.key.clone(),
+                    data: Value::Null,
+                    append: vec![],
+                    seq: message.seq.as_deref().map(str::to_string),
+                };
+                serde_json::to_vec(&delete).ok().map(Cow::Owned)
+            }
+            (None | Some(false), false) => None,
+        }
+    }
+
+    fn sent(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn round(total: Value) -> Value {
+        json!({ "state": { "total_deployed": total, "tags": ["hot", "new"], "name": "round-7" } })
+    }
+
+    #[test]
+    fn filters_compare_fields_by_dotted_path() {
+        let gte = EntityFilter::new("state.total_deployed", FilterOp::Gte, 1_000_000);
+        assert!(gte.matches(&round(json!(1_000_000))));
+        assert!(gte.matches(&round(json!("18446744073709551615"))));
+        assert!(!gte.matches(&round(json!(999_999.5))));
+        assert!(!gte.matches(&json!({ "state": {} })));
+
+        let lt = EntityFilter::new("state.total_deployed", FilterOp::Lt, 10);
+        assert!(lt.matches(&round(json!(9))));
+        assert!(!lt.matches(&round(json!("ten"))));
+
+        let eq = EntityFilter::new("state.name", FilterOp::Eq, "round-7");
+        assert!(eq.matches(&round(json!(0))));
+        let ne = EntityFilter::new("state.winner", FilterOp::Ne, "alice");
+        assert!(ne.matches(&round(json!(0))), "missing fields are null");
+
+        let tagged = EntityFilter::new("state.tags", FilterOp::Contains, "hot");
+        assert!(tagged.matches(&round(json!(0))));
+        let named = EntityFilter::new("state.name", FilterOp::Contains, "und-");
+        assert!(named.matches(&round(json!(0))));
+    }
+
+    #[test]
+    fn filters_deserialize_from_subscriptions() {
+        let filter: EntityFilter = serde_json::from_value(
+            json!({ "field": "state.total_deployed", "op": "gte", "value": 1_000_000 }),
+        )
+        .unwrap();
+        assert_eq!(
+            filter,
+            EntityFilter::new("state.total_deployed", FilterOp::Gte, 1_000_000)
+        );
+    }
+
+    fn message(key: &str, total: u64) -> BusMessage {
+        let full_state = json!({
+            "mode": "list", "entity": "Round/list", "op": "upsert", "key": key,
+            "data": round(json!(total)),
+        });
+        BusMessage::new(
+            key.to_string(),
+            "Round/list".to_string(),
+            Arc::new(Bytes::from_static(b"patch")),
+        )
+        .with_full_state(Some(Arc::new(Bytes::from(full_state.to_string()))))
+    }
+
+    fn op(frame: &[u8]) -> String {
+        serde_json::from_slice::<Value>(frame).map_or_else(
+            |_| String::from_utf8_lossy(frame).into_owned(),
+            |frame| frame["op"].as_str().unwrap().to_string(),
+        )
+    }
+
+    #[test]
+    fn entities_entering_and_leaving_the_filter_are_upserted_and_deleted() {
+        let filter = SubscriptionFilter::new(EntityFilter::new(
+            "state.total_deployed",
+            FilterOp::Gte,
+            100,
+        ));
+        let route = |message: &BusMessage| {
+            filter
+                .route(message, UpdateDelivery::Patch)
+                .map(|frame| op(&frame))
+        };
+
+        assert_eq!(route(&message("a", 50)), None);
+        assert_eq!(route(&message("a", 150)).as_deref(), Some("upsert"));
+        assert_eq!(route(&message("a", 200)).as_deref(), Some("patch"));
+        assert_eq!(route(&message("a", 10)).as_deref(), Some("delete"));
+        assert_eq!(route(&message("a", 20)), None);
+
+        filter.note_sent("b");
+        assert_eq!(route(&message("b", 500)).as_deref(), Some("patch"));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::cache::RetentionNotice;
+use hyperstack_interpreter::big_numbers::BigNumberMode;
 use serde::{Deserialize, Serialize};
 
 /// Streaming mode for different data access patterns
@@ -41,6 +43,70 @@ pub struct SubscribedFrame {
     /// Sort configuration if this is a sorted view
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sort: Option<SortConfig>,
+    /// Present when the view is already at its entity cap
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub retention: Option<RetentionNotice>,
+    /// Present when the client subscribed with `diagnostics: true`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub diagnostics: Option<SubscriptionDiagnostics>,
+    /// The only fields the subscription receives, when they are limited by
+    /// the client's auth context or its request
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fields: Option<Vec<String>>,
+    /// The integer encoding the subscription got, present when the client
+    /// asked for one
+    #[serde(
+        rename = "bigNumbers",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub big_numbers: Option<BigNumberMode>,
+    /// Present when the snapshot doesn't follow the ack right away
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub status: Option<SubscriptionStatus>,
+    /// Present when the view is deprecated, see
+    /// [`ViewDeprecation`](crate::view::ViewDeprecation)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub deprecation: Option<DeprecationNotice>,
+}
+
+/// Tells subscribers of a deprecated view what replaces it and until when
+/// it is served
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeprecationNotice {
+    /// The view to subscribe to instead
+    pub replacement: String,
+    /// Unix time (ms) after which subscriptions are rejected
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sunset_at: Option<u64>,
+}
+
+/// Why a subscription's snapshot doesn't follow its ack right away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionStatus {
+    /// The snapshot waits its turn in the
+    /// [`snapshot_queue`](crate::websocket::snapshot_queue) and is sent
+    /// later, before any update
+    SnapshotPending,
+}
+
+/// Where the time and bytes of a subscription's initial load went.
+///
+/// Only sent to clients that ask for it, to tell slow snapshot assembly,
+/// compression and network apart.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionDiagnostics {
+    /// Entities in the view's cache when the snapshot was taken
+    pub cache_entries: usize,
+    /// Time spent assembling the snapshot from the cache
+    pub snapshot_micros: u64,
+    /// Serialized size of the snapshot frames
+    pub snapshot_bytes: u64,
+    /// Size of the snapshot frames after compression
+    pub compressed_bytes: u64,
+    /// Time from receiving the subscribe request to queueing the ack
+    pub queue_wait_micros: u64,
 }
 
 impl SubscribedFrame {
@@ -50,8 +116,44 @@ impl SubscribedFrame {
             view,
             mode,
             sort,
+            retention: None,
+            diagnostics: None,
+            fields: None,
+            big_numbers: None,
+            status: None,
+            deprecation: None,
         }
     }
+
+    pub fn with_retention(mut self, retention: Option<RetentionNotice>) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn with_diagnostics(mut self, diagnostics: Option<SubscriptionDiagnostics>) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    pub fn with_fields(mut self, fields: Option<Vec<String>>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    pub fn with_big_numbers(mut self, big_numbers: Option<BigNumberMode>) -> Self {
+        self.big_numbers = big_numbers;
+        self
+    }
+
+    pub fn with_status(mut self, status: Option<SubscriptionStatus>) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_deprecation(mut self, deprecation: Option<DeprecationNotice>) -> Self {
+        self.deprecation = deprecation;
+        self
+    }
 }
 
 /// Data frame sent over WebSocket
@@ -90,46 +192,175 @@ pub struct SnapshotFrame {
     /// When `true`, the snapshot is complete and live streaming begins.
     #[serde(default = "default_complete")]
     pub complete: bool,
+    /// Number of this batch, counting from 1
+    #[serde(default = "default_page")]
+    pub page: u32,
+    /// Number of batches the snapshot was split into
+    #[serde(default = "default_page")]
+    pub total_pages: u32,
 }
 
 fn default_complete() -> bool {
     true
 }
 
+fn default_page() -> u32 {
+    1
+}
+
+/// One append item replayed from a view's history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryItem {
+    pub key: String,
+    pub data: serde_json::Value,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub append: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<String>,
+}
+
+/// Recent append items sent once on subscribe, before any live frame.
+///
+/// Items are ordered oldest first. Every item in the frame was published
+/// before the subscription started, so none of them is delivered again live.
+/// `replayed` is always set, so clients can tell history from live frames
+/// without knowing every op.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryFrame {
+    pub mode: Mode,
+    #[serde(rename = "entity")]
+    pub export: String,
+    pub op: &'static str,
+    pub data: Vec<HistoryItem>,
+    #[serde(default)]
+    pub replayed: bool,
+}
+
+impl HistoryFrame {
+    pub fn new(mode: Mode, view_id: &str, items: Vec<HistoryItem>) -> Self {
+        Self {
+            mode,
+            export: view_id.to_string(),
+            op: "history",
+            data: items,
+            replayed: true,
+        }
+    }
+}
+
+/// `frame` labeled with view id `to` instead of `from`, in its `entity`
+/// field or, for acks, its `view` field. `None` when it names no view `from`.
+///
+/// The label is a top-level field ahead of the entity data in every frame
+/// the server serializes, so the first match is the label.
+pub(crate) fn relabel_frame(frame: &[u8], from: &str, to: &str) -> Option<Vec<u8>> {
+    let from = serde_json::to_string(from).ok()?;
+    let to = serde_json::to_string(to).ok()?;
+    let (at, field) = ["\"entity\":", "\"view\":"].iter().find_map(|field| {
+        let needle = [field.as_bytes(), from.as_bytes()].concat();
+        frame
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .map(|at| (at, field.len()))
+    })?;
+    let start = at + field;
+    let mut relabeled = Vec::with_capacity(frame.len() + to.len());
+    relabeled.extend_from_slice(&frame[..start]);
+    relabeled.extend_from_slice(to.as_bytes());
+    relabeled.extend_from_slice(&frame[start + from.len()..]);
+    Some(relabeled)
+}
+
 /// Transform large u64 values to strings for JavaScript compatibility.
 /// JavaScript's Number.MAX_SAFE_INTEGER is 2^53 - 1 (9007199254740991).
 /// Values larger than this will lose precision in JavaScript.
+///
+/// This is [`BigNumberMode::Auto`]; see [`BigNumbers`](crate::BigNumbers)
+/// for the configurable encoding frames are sent with.
 pub fn transform_large_u64_to_strings(value: &mut serde_json::Value) {
-    const MAX_SAFE_INTEGER: u64 = 9007199254740991; // 2^53 - 1
+    hyperstack_interpreter::big_numbers::stringify_unsafe_integers(value);
+}
 
-    match value {
-        serde_json::Value::Object(map) => {
-            for (_, v) in map.iter_mut() {
-                transform_large_u64_to_strings(v);
-            }
-        }
-        serde_json::Value::Array(arr) => {
-            for v in arr.iter_mut() {
-                transform_large_u64_to_strings(v);
-            }
-        }
-        serde_json::Value::Number(n) => {
-            if let Some(n_u64) = n.as_u64() {
-                if n_u64 > MAX_SAFE_INTEGER {
-                    *value = serde_json::Value::String(n_u64.to_string());
-                }
-            } else if let Some(n_i64) = n.as_i64() {
-                const MIN_SAFE_INTEGER: i64 = -(MAX_SAFE_INTEGER as i64);
-                if n_i64 < MIN_SAFE_INTEGER {
-                    *value = serde_json::Value::String(n_i64.to_string());
-                }
-            }
-        }
-        _ => {}
+/// Why a subscription's frame sequence starts, marked on its first frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SequenceStart {
+    /// The subscription is new, or was restored from a session
+    #[default]
+    Subscribe,
+    /// The client asked for a resync after finding a gap
+    Resync,
+    /// The client asked for a fresh snapshot in place of what it holds
+    Refresh,
+}
+
+/// Stamp a serialized frame with its position in a subscription's sequence.
+///
+/// The fields are spliced in ahead of the frame's own, so payloads
+/// serialized once and shared between subscribers aren't re-encoded:
+/// `{"op":...}` becomes `{"sub":"view:key","frameSeq":7,"op":...}`. The first
+/// frame after a resync also carries `"resync":true`, and after a refresh
+/// `"resync":true,"refresh":true`, so clients unaware of refreshes handle
+/// them as resyncs.
+pub fn stamp_frame_sequence(
+    frame: &[u8],
+    sub: &str,
+    frame_seq: u64,
+    start: SequenceStart,
+) -> Vec<u8> {
+    let Some(fields) = frame.strip_prefix(b"{") else {
+        return frame.to_vec();
+    };
+
+    let mut stamped = Vec::with_capacity(frame.len() + sub.len() + 48);
+    stamped.extend_from_slice(b"{\"sub\":");
+    serde_json::to_writer(&mut stamped, sub).expect("a string always serializes");
+    stamped.extend_from_slice(format!(",\"frameSeq\":{frame_seq}").as_bytes());
+    match start {
+        SequenceStart::Subscribe => {}
+        SequenceStart::Resync => stamped.extend_from_slice(b",\"resync\":true"),
+        SequenceStart::Refresh => stamped.extend_from_slice(b",\"resync\":true,\"refresh\":true"),
     }
+    if !fields.starts_with(b"}") {
+        stamped.push(b',');
+    }
+    stamped.extend_from_slice(fields);
+    stamped
+}
+
+/// Fields a client subscribed through its URL path has no use for: it
+/// receives a single view and doesn't track frame sequences.
+const PATH_SUBSCRIPTION_OMITTED_FIELDS: [&str; 3] = ["mode", "entity", "seq"];
+
+/// Reduce a serialized frame to the minimal envelope sent to path
+/// subscribers, e.g. `{"data":{...},"key":"42","op":"patch"}`.
+///
+/// Frames that aren't JSON objects are passed through unchanged.
+pub fn minimal_frame(frame: &[u8]) -> Vec<u8> {
+    let Ok(mut fields) =
+        serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(frame)
+    else {
+        return frame.to_vec();
+    };
+    for field in PATH_SUBSCRIPTION_OMITTED_FIELDS {
+        fields.remove(field);
+    }
+    serde_json::to_vec(&fields).unwrap_or_else(|_| frame.to_vec())
 }
 
 impl Frame {
+    /// View-level frame telling subscribers that older entities are being evicted
+    pub fn retention_notice(mode: Mode, view_id: &str, notice: &RetentionNotice) -> Self {
+        Self {
+            mode,
+            export: view_id.to_string(),
+            op: "retention_notice",
+            key: String::new(),
+            data: serde_json::to_value(notice).unwrap_or_default(),
+            append: vec![],
+            seq: None,
+        }
+    }
+
     pub fn entity(&self) -> &str {
         &self.export
     }
@@ -222,11 +453,15 @@ mod tests {
                 data: serde_json::json!({"id": "abc"}),
             }],
             complete: false,
+            page: 1,
+            total_pages: 3,
         };
 
         let json = serde_json::to_value(&frame).unwrap();
         assert_eq!(json["complete"], false);
         assert_eq!(json["op"], "snapshot");
+        assert_eq!(json["page"], 1);
+        assert_eq!(json["total_pages"], 3);
     }
 
     #[test]
@@ -265,6 +500,8 @@ mod tests {
             op: "snapshot",
             data: vec![],
             complete: false,
+            page: 1,
+            total_pages: 2,
         };
 
         let final_batch = SnapshotFrame {
@@ -273,9 +510,121 @@ mod tests {
             op: "snapshot",
             data: vec![],
             complete: true,
+            page: 2,
+            total_pages: 2,
         };
 
         assert!(!first_batch.complete);
         assert!(final_batch.complete);
     }
+
+    #[test]
+    fn test_retention_notice_serialization() {
+        let notice = RetentionNotice {
+            cap: 500,
+            eviction_rate: 2.5,
+            oldest_retained_at: Some(1_700_000_000_000),
+        };
+
+        let frame = Frame::retention_notice(Mode::List, "tokens/list", &notice);
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(json["op"], "retention_notice");
+        assert_eq!(json["entity"], "tokens/list");
+        assert_eq!(json["key"], "");
+        assert_eq!(json["data"]["cap"], 500);
+        assert_eq!(json["data"]["eviction_rate"], 2.5);
+        assert_eq!(json["data"]["oldest_retained_at"], 1_700_000_000_000i64);
+
+        let ack = SubscribedFrame::new("tokens/list".to_string(), Mode::List, None);
+        assert!(serde_json::to_value(&ack)
+            .unwrap()
+            .get("retention")
+            .is_none());
+
+        let ack = ack.with_retention(Some(notice));
+        let json = serde_json::to_value(&ack).unwrap();
+        assert_eq!(json["retention"]["cap"], 500);
+    }
+
+    #[test]
+    fn test_stamp_frame_sequence() {
+        let frame = Frame {
+            mode: Mode::List,
+            export: "tokens/list".to_string(),
+            op: "upsert",
+            key: "abc".to_string(),
+            data: serde_json::json!({"price": 1}),
+            append: vec![],
+            seq: Some("10:0".to_string()),
+        };
+        let payload = serde_json::to_vec(&frame).unwrap();
+
+        let stamped = stamp_frame_sequence(&payload, "tokens/list:*", 7, SequenceStart::Subscribe);
+        let json: serde_json::Value = serde_json::from_slice(&stamped).unwrap();
+        assert_eq!(json["sub"], "tokens/list:*");
+        assert_eq!(json["frameSeq"], 7);
+        assert_eq!(json["seq"], "10:0");
+        assert_eq!(json["data"]["price"], 1);
+        assert!(json.get("resync").is_none());
+
+        let stamped = stamp_frame_sequence(b"{}", "a\"b:*", 1, SequenceStart::Resync);
+        assert_eq!(
+            stamped,
+            br#"{"sub":"a\"b:*","frameSeq":1,"resync":true}"#.to_vec()
+        );
+
+        let stamped =
+            stamp_frame_sequence(b"{\"op\":\"subscribed\"}", "a:*", 1, SequenceStart::Refresh);
+        assert_eq!(
+            stamped,
+            br#"{"sub":"a:*","frameSeq":1,"resync":true,"refresh":true,"op":"subscribed"}"#
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn test_relabel_frame() {
+        let frame = Frame {
+            mode: Mode::List,
+            export: "Token/list".to_string(),
+            op: "upsert",
+            key: "1".to_string(),
+            data: serde_json::json!({"entity": "Token/list"}),
+            append: vec![],
+            seq: None,
+        };
+        let payload = serde_json::to_vec(&frame).unwrap();
+        let relabeled = relabel_frame(&payload, "Token/list", "Token/all").unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&relabeled).unwrap();
+        assert_eq!(json["entity"], "Token/all");
+        assert_eq!(json["data"]["entity"], "Token/list");
+
+        let ack = SubscribedFrame::new("Token/list".to_string(), Mode::List, None);
+        let payload = serde_json::to_vec(&ack).unwrap();
+        let relabeled = relabel_frame(&payload, "Token/list", "Token/all").unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&relabeled).unwrap();
+        assert_eq!(json["view"], "Token/all");
+
+        assert_eq!(relabel_frame(&payload, "Token/other", "Token/all"), None);
+    }
+
+    #[test]
+    fn test_minimal_frame() {
+        let frame = Frame {
+            mode: Mode::State,
+            export: "OreRound/state".to_string(),
+            op: "patch",
+            key: "42".to_string(),
+            data: serde_json::json!({"total": 1}),
+            append: vec![],
+            seq: Some("10:0".to_string()),
+        };
+        let payload = serde_json::to_vec(&frame).unwrap();
+
+        assert_eq!(
+            minimal_frame(&payload),
+            br#"{"data":{"total":1},"key":"42","op":"patch"}"#.to_vec()
+        );
+        assert_eq!(minimal_frame(b"not json"), b"not json".to_vec());
+    }
 }
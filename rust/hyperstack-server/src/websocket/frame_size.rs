@@ -0,0 +1,367 @@
+//! Limit on the size of frames sent to clients.
+//!
+//! Some proxies reject large WebSocket messages and drop the connection with
+//! them. A [`FrameSizeGuard`] checks every subscription frame against
+//! `max_frame_bytes` before it is stamped and compressed, and splits the ones
+//! that don't fit:
+//!
+//! - `snapshot` and `history` frames are split between entities into several
+//!   frames of the same kind. Only the last snapshot frame keeps `complete`.
+//! - Entity frames (`upsert`, `patch`, `create`) are split between the
+//!   top-level fields of their data. Every part carries
+//!   `"continuation": {"part": n, "parts": total}` and clients merge the parts
+//!   back into one frame before applying it.
+//!
+//! Anything that can't be split below the limit, such as a single field or
+//! entity larger than the limit, is replaced by a `frame_too_large` frame
+//! carrying the entity key, so the client can fetch the entity another way.
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Operation of the frame that replaces an entity too large to send
+pub const FRAME_TOO_LARGE_OP: &str = "frame_too_large";
+
+/// Frames that got split or dropped for one view
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct OversizedFrameCounts {
+    /// Frames sent as several smaller frames
+    pub split: u64,
+    /// Entities replaced by a `frame_too_large` frame
+    pub too_large: u64,
+}
+
+/// Splits outgoing frames larger than `max_frame_bytes` and counts them per view
+#[derive(Debug, Clone)]
+pub struct FrameSizeGuard {
+    max_frame_bytes: usize,
+    counts: Arc<Mutex<BTreeMap<String, OversizedFrameCounts>>>,
+}
+
+impl FrameSizeGuard {
+    pub fn new(max_frame_bytes: usize) -> Self {
+        Self {
+            max_frame_bytes,
+            counts: Arc::default(),
+        }
+    }
+
+    pub fn max_frame_bytes(&self) -> usize {
+        self.max_frame_bytes
+    }
+
+    /// The frames to send in place of `frame`: the frame itself when it fits
+    pub fn apply<'a>(&self, frame: &'a [u8]) -> Vec<Cow<'a, [u8]>> {
+        if frame.len() <= self.max_frame_bytes {
+            return vec![Cow::Borrowed(frame)];
+        }
+        let Ok(fields) = serde_json::from_slice::<Map<String, Value>>(frame) else {
+            return vec![Cow::Borrowed(frame)];
+        };
+
+        let view = fields
+            .get("entity")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let split = split_frame(fields, frame.len(), self.max_frame_bytes);
+        warn!(
+            view = %view,
+            bytes = frame.len(),
+            max_frame_bytes = self.max_frame_bytes,
+            frames = split.frames.len(),
+            too_large = split.too_large,
+            "Oversized frame split before sending"
+        );
+
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let counts = counts.entry(view).or_default();
+        if split.frames.len() > split.too_large {
+            counts.split += 1;
+        }
+        counts.too_large += split.too_large as u64;
+
+        split.frames.into_iter().map(Cow::Owned).collect()
+    }
+
+    /// Oversized frame counts per view
+    pub fn stats(&self) -> BTreeMap<String, OversizedFrameCounts> {
+        self.counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// Frames an oversized frame was split into
+struct SplitFrames {
+    frames: Vec<Vec<u8>>,
+    /// How many of the frames are `frame_too_large` replacements
+    too_large: usize,
+}
+
+impl SplitFrames {
+    fn push(&mut self, fields: &Map<String, Value>) {
+        self.frames
+            .push(serde_json::to_vec(fields).expect("a JSON map always serializes"));
+    }
+
+    fn push_too_large(&mut self, fields: &Map<String, Value>, key: &str, bytes: usize, max: usize) {
+        self.push(&too_large_frame(fields, key, bytes, max));
+        self.too_large += 1;
+    }
+}
+
+fn split_frame(mut fields: Map<String, Value>, bytes: usize, max: usize) -> SplitFrames {
+    let mut split = SplitFrames {
+        frames: Vec::new(),
+        too_large: 0,
+    };
+    let op = fields
+        .get("op")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let key = fields
+        .get("key")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+
+    match (op.as_str(), fields.remove("data")) {
+        ("snapshot" | "history", Some(Value::Array(items))) => {
+            split_items(&mut split, fields, items, max);
+        }
+        ("upsert" | "patch" | "create", Some(Value::Object(data))) => {
+            if !split_sections(&mut split, &fields, data, max) {
+                split.push_too_large(&fields, &key, bytes, max);
+            }
+        }
+        _ => split.push_too_large(&fields, &key, bytes, max),
+    }
+
+    split
+}
+
+/// Split a `snapshot` or `history` frame between its entities
+fn split_items(
+    split: &mut SplitFrames,
+    mut envelope: Map<String, Value>,
+    items: Vec<Value>,
+    max: usize,
+) {
+    let complete = envelope.remove("complete");
+    let is_snapshot = complete.is_some();
+    envelope.insert("data".to_string(), Value::Array(Vec::new()));
+    if is_snapshot {
+        envelope.insert("complete".to_string(), Value::Bool(false));
+    }
+    let base = encoded_len(&Value::Object(envelope.clone()));
+
+    let mut batches: Vec<Vec<Value>> = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_len = base;
+    for item in items {
+        let item_len = encoded_len(&item);
+        if base + item_len > max {
+            let key = item.get("key").and_then(Value::as_str).unwrap_or_default();
+            split.push_too_large(&envelope, key, base + item_len, max);
+            continue;
+        }
+        if !batch.is_empty() && batch_len + item_len + 1 > max {
+            batches.push(std::mem::take(&mut batch));
+            batch_len = base;
+        }
+        batch_len += item_len + usize::from(!batch.is_empty());
+        batch.push(item);
+    }
+    if !batch.is_empty() || batches.is_empty() {
+        batches.push(batch);
+    }
+
+    let last = batches.len() - 1;
+    for (index, batch) in batches.into_iter().enumerate() {
+        envelope.insert("data".to_string(), Value::Array(batch));
+        if index == last {
+            if let Some(complete) = &complete {
+                envelope.insert("complete".to_string(), complete.clone());
+            }
+        }
+        split.push(&envelope);
+    }
+}
+
+/// Split an entity frame between the top-level fields of its data.
+///
+/// Returns false when a single field doesn't fit in a frame.
+fn split_sections(
+    split: &mut SplitFrames,
+    envelope: &Map<String, Value>,
+    data: Map<String, Value>,
+    max: usize,
+) -> bool {
+    let mut part_envelope = envelope.clone();
+    part_envelope.insert("data".to_string(), json!({}));
+    // Room for the largest continuation marker this frame could carry
+    part_envelope.insert(
+        "continuation".to_string(),
+        json!({ "part": u32::MAX, "parts": u32::MAX }),
+    );
+    let base = encoded_len(&Value::Object(part_envelope.clone()));
+
+    let mut parts: Vec<Map<String, Value>> = Vec::new();
+    let mut part = Map::new();
+    let mut part_len = base;
+    for (field, value) in data {
+        let field_len = encoded_len(&Value::String(field.clone())) + 1 + encoded_len(&value);
+        if base + field_len > max {
+            return false;
+        }
+        if !part.is_empty() && part_len + field_len + 1 > max {
+            parts.push(std::mem::take(&mut part));
+            part_len = base;
+        }
+        part_len += field_len + usize::from(!part.is_empty());
+        part.insert(field, value);
+    }
+    parts.push(part);
+
+    let total = parts.len();
+    for (index, data) in parts.into_iter().enumerate() {
+        part_envelope.insert("data".to_string(), Value::Object(data));
+        part_envelope.insert(
+            "continuation".to_string(),
+            json!({ "part": index + 1, "parts": total }),
+        );
+        split.push(&part_envelope);
+    }
+    true
+}
+
+/// The frame sent in place of an entity that can't be sent under the limit
+fn too_large_frame(
+    envelope: &Map<String, Value>,
+    key: &str,
+    bytes: usize,
+    max: usize,
+) -> Map<String, Value> {
+    let mut frame = Map::new();
+    for field in ["mode", "entity"] {
+        if let Some(value) = envelope.get(field) {
+            frame.insert(field.to_string(), value.clone());
+        }
+    }
+    frame.insert(
+        "op".to_string(),
+        Value::String(FRAME_TOO_LARGE_OP.to_string()),
+    );
+    frame.insert("key".to_string(), Value::String(key.to_string()));
+    frame.insert(
+        "data".to_string(),
+        json!({ "bytes": bytes, "max_bytes": max }),
+    );
+    frame
+}
+
+fn encoded_len(value: &Value) -> usize {
+    serde_json::to_vec(value)
+        .map(|bytes| bytes.len())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(frames: &[Cow<'_, [u8]>]) -> Vec<Value> {
+        frames
+            .iter()
+            .map(|frame| serde_json::from_slice(frame).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn frames_under_the_limit_pass_through() {
+        let guard = FrameSizeGuard::new(1024);
+        let frame = br#"{"mode":"list","entity":"Pool/list","op":"patch","key":"1","data":{}}"#;
+
+        let frames = guard.apply(frame);
+
+        assert_eq!(frames.len(), 1);
+        assert!(matches!(frames[0], Cow::Borrowed(_)));
+        assert!(guard.stats().is_empty());
+    }
+
+    #[test]
+    fn snapshot_splits_between_entities() {
+        let guard = FrameSizeGuard::new(200);
+        let entities: Vec<Value> = (0..6)
+            .map(|i| json!({ "key": i.to_string(), "data": { "blob": "x".repeat(40) } }))
+            .collect();
+        let frame = serde_json::to_vec(&json!({
+            "mode": "list",
+            "entity": "Pool/list",
+            "op": "snapshot",
+            "data": entities,
+            "complete": true,
+        }))
+        .unwrap();
+
+        let frames = guard.apply(&frame);
+
+        assert!(frames.len() > 1);
+        assert!(frames.iter().all(|frame| frame.len() <= 200));
+        let frames = decode(&frames);
+        let keys: Vec<Value> = frames
+            .iter()
+            .flat_map(|frame| frame["data"].as_array().unwrap().clone())
+            .map(|entity| entity["key"].clone())
+            .collect();
+        assert_eq!(
+            keys,
+            (0..6).map(|i| json!(i.to_string())).collect::<Vec<_>>()
+        );
+        let (last, rest) = frames.split_last().unwrap();
+        assert!(rest.iter().all(|frame| frame["complete"] == json!(false)));
+        assert_eq!(last["complete"], json!(true));
+        assert_eq!(
+            guard.stats()["Pool/list"],
+            OversizedFrameCounts {
+                split: 1,
+                too_large: 0
+            }
+        );
+    }
+
+    #[test]
+    fn oversized_field_becomes_frame_too_large() {
+        let guard = FrameSizeGuard::new(200);
+        let frame = serde_json::to_vec(&json!({
+            "mode": "state",
+            "entity": "Pool/state",
+            "op": "upsert",
+            "key": "pool-1",
+            "data": { "name": "pool", "capture": "x".repeat(500) },
+        }))
+        .unwrap();
+
+        let frames = decode(&guard.apply(&frame));
+
+        assert_eq!(
+            frames,
+            vec![json!({
+                "mode": "state",
+                "entity": "Pool/state",
+                "op": "frame_too_large",
+                "key": "pool-1",
+                "data": { "bytes": frame.len(), "max_bytes": 200 },
+            })]
+        );
+        assert_eq!(guard.stats()["Pool/state"].too_large, 1);
+        assert_eq!(guard.stats()["Pool/state"].split, 0);
+    }
+}
@@ -0,0 +1,310 @@
+//! Guards applied to client messages before they are parsed.
+//!
+//! Each connection gets an [`InboundGuard`] that rejects oversized messages
+//! and clients sending faster than their token bucket refills. Oversized
+//! messages are usually caught by the WebSocket codec from the frame header,
+//! before their payload is buffered; the guard checks the size again for
+//! messages assembled from several frames.
+
+use crate::websocket::subscription::SocketIssueMessage;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig as WsProtocolConfig;
+
+/// How often a connection may log a message it failed to parse
+const PARSE_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Limits on what a single client may send
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InboundLimits {
+    /// Largest message accepted from a client, in bytes (None = codec default)
+    pub max_message_bytes: Option<usize>,
+    /// Sustained messages per second accepted from a client (None = unlimited,
+    /// the default). Clients may burst up to one second's worth of messages,
+    /// so the limit must leave room for a client resubscribing every view it
+    /// holds when it reconnects.
+    pub max_messages_per_sec: Option<u32>,
+}
+
+impl Default for InboundLimits {
+    fn default() -> Self {
+        Self {
+            max_message_bytes: Some(64 * 1024),
+            max_messages_per_sec: None,
+        }
+    }
+}
+
+impl InboundLimits {
+    /// Limits that accept anything the WebSocket codec does
+    pub fn unlimited() -> Self {
+        Self {
+            max_message_bytes: None,
+            max_messages_per_sec: None,
+        }
+    }
+
+    /// Codec settings that reject oversized messages from the frame header
+    pub(crate) fn protocol_config(&self) -> Option<WsProtocolConfig> {
+        let max_bytes = self.max_message_bytes?;
+        Some(
+            WsProtocolConfig::default()
+                .max_message_size(Some(max_bytes))
+                .max_frame_size(Some(max_bytes)),
+        )
+    }
+}
+
+/// Why a client message was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InboundViolation {
+    MessageTooLarge { size: usize, limit: usize },
+    RateExceeded { limit: u32 },
+}
+
+impl InboundViolation {
+    /// Metric label for the violation
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            Self::MessageTooLarge { .. } => "message_too_large",
+            Self::RateExceeded { .. } => "rate_exceeded",
+        }
+    }
+
+    /// The error frame sent to the client before it is disconnected
+    pub(crate) fn socket_issue(&self) -> SocketIssueMessage {
+        let (error, code, retryable, retry_after_ms, suggested_action) = match self {
+            Self::MessageTooLarge { .. } => (
+                "Message too large",
+                "message-too-large",
+                false,
+                None,
+                "Send smaller messages",
+            ),
+            Self::RateExceeded { .. } => (
+                "Rate limit exceeded",
+                "rate-limit-exceeded",
+                true,
+                Some(1000),
+                "Slow down and reconnect",
+            ),
+        };
+
+        SocketIssueMessage {
+            kind: "error".to_string(),
+            error: error.to_string(),
+            message: self.to_string(),
+            code: code.to_string(),
+            retryable,
+            retry_after: retry_after_ms.map(|ms| ms / 1000),
+            retry_after_ms,
+            suggested_action: Some(suggested_action.to_string()),
+            docs_url: None,
+            fatal: true,
+        }
+    }
+}
+
+impl fmt::Display for InboundViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MessageTooLarge { size, limit } => {
+                write!(
+                    f,
+                    "message of {} bytes exceeds the {} byte limit",
+                    size, limit
+                )
+            }
+            Self::RateExceeded { limit } => {
+                write!(f, "more than {} messages per second", limit)
+            }
+        }
+    }
+}
+
+/// Token bucket refilling at `rate` tokens per second up to `rate` tokens
+#[derive(Debug)]
+struct TokenBucket {
+    rate: u32,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u32, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: f64::from(rate),
+            refilled_at: now,
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * f64::from(self.rate)).min(f64::from(self.rate));
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Per-connection enforcement of [`InboundLimits`]
+#[derive(Debug)]
+pub(crate) struct InboundGuard {
+    max_message_bytes: Option<usize>,
+    bucket: Option<TokenBucket>,
+    parse_errors_logged_at: Option<Instant>,
+    parse_errors_suppressed: u64,
+}
+
+impl InboundGuard {
+    pub(crate) fn new(limits: InboundLimits, now: Instant) -> Self {
+        Self {
+            max_message_bytes: limits.max_message_bytes,
+            bucket: limits
+                .max_messages_per_sec
+                .map(|rate| TokenBucket::new(rate, now)),
+            parse_errors_logged_at: None,
+            parse_errors_suppressed: 0,
+        }
+    }
+
+    /// Admit a message of `size` bytes, or say why it must be refused
+    pub(crate) fn check(&mut self, size: usize, now: Instant) -> Result<(), InboundViolation> {
+        if let Some(limit) = self.max_message_bytes {
+            if size > limit {
+                return Err(InboundViolation::MessageTooLarge { size, limit });
+            }
+        }
+
+        if let Some(bucket) = &mut self.bucket {
+            if !bucket.try_take(now) {
+                return Err(InboundViolation::RateExceeded { limit: bucket.rate });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Note a message that failed to parse.
+    ///
+    /// Returns how many were suppressed since the last one logged when this
+    /// one should be logged, or `None` to stay quiet.
+    pub(crate) fn parse_error(&mut self, now: Instant) -> Option<u64> {
+        let due = self.parse_errors_logged_at.is_none_or(|logged_at| {
+            now.saturating_duration_since(logged_at) >= PARSE_ERROR_LOG_INTERVAL
+        });
+
+        if due {
+            self.parse_errors_logged_at = Some(now);
+            Some(std::mem::take(&mut self.parse_errors_suppressed))
+        } else {
+            self.parse_errors_suppressed += 1;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(max_message_bytes: Option<usize>, max_messages_per_sec: Option<u32>) -> InboundGuard {
+        InboundGuard::new(
+            InboundLimits {
+                max_message_bytes,
+                max_messages_per_sec,
+            },
+            Instant::now(),
+        )
+    }
+
+    #[test]
+    fn oversized_messages_are_refused() {
+        let mut guard = guard(Some(16), None);
+        let now = Instant::now();
+
+        assert_eq!(guard.check(16, now), Ok(()));
+        assert_eq!(
+            guard.check(17, now),
+            Err(InboundViolation::MessageTooLarge {
+                size: 17,
+                limit: 16
+            })
+        );
+    }
+
+    #[test]
+    fn bursts_beyond_the_rate_are_refused_until_the_bucket_refills() {
+        let start = Instant::now();
+        let mut guard = InboundGuard::new(
+            InboundLimits {
+                max_message_bytes: None,
+                max_messages_per_sec: Some(4),
+            },
+            start,
+        );
+
+        for _ in 0..4 {
+            assert_eq!(guard.check(1, start), Ok(()));
+        }
+        assert_eq!(
+            guard.check(1, start),
+            Err(InboundViolation::RateExceeded { limit: 4 })
+        );
+
+        let later = start + Duration::from_millis(500);
+        assert_eq!(guard.check(1, later), Ok(()));
+        assert_eq!(guard.check(1, later), Ok(()));
+        assert!(guard.check(1, later).is_err());
+    }
+
+    #[test]
+    fn unlimited_guard_admits_everything() {
+        let mut guard = guard(None, None);
+        let now = Instant::now();
+        for _ in 0..10_000 {
+            assert_eq!(guard.check(usize::MAX, now), Ok(()));
+        }
+        assert!(InboundLimits::unlimited().protocol_config().is_none());
+    }
+
+    #[test]
+    fn parse_errors_are_logged_at_most_once_per_interval() {
+        let mut guard = guard(None, None);
+        let start = Instant::now();
+
+        assert_eq!(guard.parse_error(start), Some(0));
+        assert_eq!(guard.parse_error(start + Duration::from_secs(1)), None);
+        assert_eq!(guard.parse_error(start + Duration::from_secs(2)), None);
+        assert_eq!(guard.parse_error(start + PARSE_ERROR_LOG_INTERVAL), Some(2));
+    }
+
+    #[test]
+    fn violations_become_fatal_error_frames() {
+        let issue = InboundViolation::MessageTooLarge {
+            size: 2048,
+            limit: 1024,
+        }
+        .socket_issue();
+        assert_eq!(issue.code, "message-too-large");
+        assert!(issue.fatal);
+        assert!(!issue.retryable);
+        assert_eq!(
+            issue.message,
+            "message of 2048 bytes exceeds the 1024 byte limit"
+        );
+
+        let issue = InboundViolation::RateExceeded { limit: 5 }.socket_issue();
+        assert_eq!(issue.code, "rate-limit-exceeded");
+        assert!(issue.retryable);
+        assert_eq!(issue.retry_after_ms, Some(1000));
+    }
+}
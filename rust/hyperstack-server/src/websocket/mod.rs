@@ -1,8 +1,15 @@
 pub mod auth;
 pub mod client_manager;
+pub mod delta;
+pub mod field_mask;
+pub mod filter;
 pub mod frame;
+pub mod frame_size;
+pub mod inbound;
 pub mod rate_limiter;
 pub mod server;
+pub mod session;
+pub mod snapshot_queue;
 pub mod subscription;
 pub mod usage;
 
@@ -11,15 +18,26 @@ pub use auth::{
     ConnectionAuthRequest, ErrorResponse, RetryPolicy, SignedSessionAuthPlugin,
     StaticTokenAuthPlugin, WebSocketAuthPlugin,
 };
-pub use client_manager::{ClientInfo, ClientManager, RateLimitConfig, SendError, WebSocketSender};
+pub use client_manager::{
+    ClientInfo, ClientManager, RateLimitConfig, SendError, WebSocketIo, WebSocketSender,
+    WebSocketTransport,
+};
+pub use field_mask::{FieldAuthorizer, FieldMask, StaticFieldAuthorizer};
+pub use filter::{EntityFilter, FilterOp};
 pub use frame::{
-    Frame, Mode, SnapshotEntity, SnapshotFrame, SortConfig, SortOrder, SubscribedFrame,
+    DeprecationNotice, Frame, HistoryFrame, HistoryItem, Mode, SequenceStart, SnapshotEntity, SnapshotFrame, SortConfig,
+    SortOrder, SubscribedFrame, SubscriptionDiagnostics, SubscriptionStatus,
 };
+pub use frame_size::{FrameSizeGuard, OversizedFrameCounts, FRAME_TOO_LARGE_OP};
+pub use inbound::InboundLimits;
 pub use rate_limiter::{RateLimitResult, RateLimitWindow, RateLimiterConfig, WebSocketRateLimiter};
 pub use server::WebSocketServer;
+pub use session::{SessionConfig, SessionStats, SessionStore};
+pub use snapshot_queue::{SnapshotQueue, SnapshotQueueConfig, SnapshotQueueStats};
 pub use subscription::{
-    ClientMessage, RefreshAuthRequest, RefreshAuthResponse, SocketIssueMessage, Subscription,
-    Unsubscription,
+    ClientHello, ClientMessage, CloseReason, CompressionCodec, CompressionRequest,
+    HelloCompression, HelloMessage, RefreshAuthRequest, RefreshAuthResponse, SessionMessage,
+    SocketIssueMessage, Subscription, Unsubscription, UpdateDelivery,
 };
 pub use usage::{
     ChannelUsageEmitter, HttpUsageEmitter, WebSocketUsageBatch, WebSocketUsageEmitter,
@@ -54,7 +54,7 @@ pub enum RateLimitResult {
 
 /// A single rate limit bucket using sliding window algorithm
 #[derive(Debug)]
-struct RateLimitBucket {
+pub(crate) struct RateLimitBucket {
     /// Request timestamps in the current window
     requests: Vec<Instant>,
     /// Window configuration
@@ -62,7 +62,7 @@ struct RateLimitBucket {
 }
 
 impl RateLimitBucket {
-    fn new(window: RateLimitWindow) -> Self {
+    pub(crate) fn new(window: RateLimitWindow) -> Self {
         Self {
             requests: Vec::with_capacity((window.max_requests + window.burst) as usize),
             window,
@@ -75,7 +75,7 @@ impl RateLimitBucket {
     }
 
     /// Check if a request is allowed and record it
-    fn check_and_record(&mut self, now: Instant) -> RateLimitResult {
+    pub(crate) fn check_and_record(&mut self, now: Instant) -> RateLimitResult {
         self.prune_expired(now);
 
         let limit = self.window.max_requests + self.window.burst;
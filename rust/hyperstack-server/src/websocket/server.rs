@@ -1,34 +1,54 @@
-use crate::bus::BusManager;
+use crate::backfill::{BackfillOutcome, RpcBackfill};
+use crate::big_numbers::BigNumbers;
+use crate::bus::{BusManager, BusMessage};
 use crate::cache::{cmp_seq, EntityCache, SnapshotBatchConfig};
 use crate::compression::maybe_compress;
-use crate::view::{ViewIndex, ViewSpec};
+use crate::dictionary::Dictionaries;
+use crate::drain::DrainController;
+use crate::flags::Flags;
+use crate::http_health::dictionary_response;
+use crate::materialized_view::Quarantine;
+use crate::reload::LiveViews;
+use crate::view::{ViewDeprecation, ViewIndex, ViewSpec};
 use crate::websocket::auth::{
     AuthContext, AuthDecision, AuthDeny, ConnectionAuthRequest, WebSocketAuthPlugin,
 };
-use crate::websocket::client_manager::{ClientManager, RateLimitConfig};
+use crate::websocket::client_manager::{
+    ClientManager, RateLimitConfig, SubscriptionSender, WebSocketTransport,
+};
+use crate::websocket::field_mask::FieldAuthorizer;
 use crate::websocket::frame::{
-    transform_large_u64_to_strings, Frame, Mode, SnapshotEntity, SnapshotFrame, SortConfig,
-    SortOrder, SubscribedFrame,
+    Frame, HistoryFrame, HistoryItem, Mode, SequenceStart, SnapshotEntity, SnapshotFrame,
+    SortConfig, SortOrder, SubscribedFrame, SubscriptionDiagnostics, SubscriptionStatus,
 };
+use crate::websocket::inbound::{InboundGuard, InboundLimits, InboundViolation};
+use crate::websocket::session::{RetainedSubscription, SessionConfig};
+use crate::websocket::snapshot_queue::{QueuedSnapshot, SnapshotQueueConfig, SnapshotTurn};
 use crate::websocket::subscription::{
-    ClientMessage, RefreshAuthRequest, RefreshAuthResponse, SocketIssueMessage, Subscription,
+    ClientMessage, CloseReason, RefreshAuthRequest, RefreshAuthResponse, SessionMessage,
+    SocketIssueMessage, Subscription, UpdateDelivery,
 };
 use crate::websocket::usage::{WebSocketUsageEmitter, WebSocketUsageEvent};
 use anyhow::Result;
-use bytes::Bytes;
 use futures_util::StreamExt;
+use hyperstack_interpreter::clock::SharedClock;
 use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::sync::Arc;
-#[cfg(feature = "otel")]
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime};
 
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
 use tokio_tungstenite::{
-    accept_hdr_async,
+    accept_hdr_async_with_config,
     tungstenite::{
+        error::CapacityError,
+        handshake::derive_accept_key,
         handshake::server::{ErrorResponse as HandshakeErrorResponse, Request, Response},
         http::{header::CONTENT_TYPE, StatusCode},
+        protocol::frame::coding::CloseCode,
+        protocol::Role,
+        protocol::WebSocketConfig as WsProtocolConfig,
         Error as WsError,
     },
 };
@@ -141,6 +161,28 @@ async fn send_socket_issue(
     }
 }
 
+/// Tell a client why its message was refused before it is disconnected
+async fn reject_inbound(
+    client_id: Uuid,
+    client_manager: &ClientManager,
+    violation: &InboundViolation,
+) {
+    warn!(
+        "Inbound message rejected for client {} ({}): {}",
+        client_id,
+        violation.reason(),
+        violation
+    );
+    match serde_json::to_string(&violation.socket_issue()) {
+        Ok(json) => {
+            let _ = client_manager.send_text_to_client(client_id, json).await;
+        }
+        Err(error) => {
+            warn!(error = %error, client_id = %client_id, "failed to serialize socket issue message");
+        }
+    }
+}
+
 fn auth_deny_from_subscription_error(reason: &str) -> Option<AuthDeny> {
     if reason.starts_with("Snapshot limit exceeded:") {
         Some(AuthDeny::new(
@@ -217,22 +259,71 @@ struct SubscriptionContext<'a> {
     client_manager: &'a ClientManager,
     bus_manager: &'a BusManager,
     entity_cache: &'a EntityCache,
-    view_index: &'a ViewIndex,
+    view_index: &'a LiveViews,
     usage_emitter: &'a Option<Arc<dyn WebSocketUsageEmitter>>,
+    backfill: Option<&'a RpcBackfill>,
     #[cfg(feature = "otel")]
     metrics: Option<Arc<Metrics>>,
 }
 
+impl SubscriptionContext<'_> {
+    /// A copy of the context for a subscription's task to keep
+    fn to_owned(&self) -> OwnedSubscriptionContext {
+        OwnedSubscriptionContext {
+            client_id: self.client_id,
+            client_manager: self.client_manager.clone(),
+            bus_manager: self.bus_manager.clone(),
+            entity_cache: self.entity_cache.clone(),
+            view_index: self.view_index.clone(),
+            usage_emitter: self.usage_emitter.clone(),
+            backfill: self.backfill.cloned(),
+            #[cfg(feature = "otel")]
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+/// [`SubscriptionContext`] owned by a subscription's task
+struct OwnedSubscriptionContext {
+    client_id: Uuid,
+    client_manager: ClientManager,
+    bus_manager: BusManager,
+    entity_cache: EntityCache,
+    view_index: LiveViews,
+    usage_emitter: Option<Arc<dyn WebSocketUsageEmitter>>,
+    backfill: Option<RpcBackfill>,
+    #[cfg(feature = "otel")]
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl OwnedSubscriptionContext {
+    fn borrow(&self) -> SubscriptionContext<'_> {
+        SubscriptionContext {
+            client_id: self.client_id,
+            client_manager: &self.client_manager,
+            bus_manager: &self.bus_manager,
+            entity_cache: &self.entity_cache,
+            view_index: &self.view_index,
+            usage_emitter: &self.usage_emitter,
+            backfill: self.backfill.as_ref(),
+            #[cfg(feature = "otel")]
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
 pub struct WebSocketServer {
     bind_addr: SocketAddr,
     client_manager: ClientManager,
     bus_manager: BusManager,
     entity_cache: EntityCache,
-    view_index: Arc<ViewIndex>,
+    view_index: LiveViews,
     max_clients: usize,
     auth_plugin: Arc<dyn WebSocketAuthPlugin>,
     usage_emitter: Option<Arc<dyn WebSocketUsageEmitter>>,
-    rate_limit_config: Option<RateLimitConfig>,
+    inbound_limits: InboundLimits,
+    backfill: Option<RpcBackfill>,
+    drain: DrainController,
     #[cfg(feature = "otel")]
     metrics: Option<Arc<Metrics>>,
 }
@@ -243,7 +334,7 @@ impl WebSocketServer {
         bind_addr: SocketAddr,
         bus_manager: BusManager,
         entity_cache: EntityCache,
-        view_index: Arc<ViewIndex>,
+        view_index: impl Into<LiveViews>,
         metrics: Option<Arc<Metrics>>,
     ) -> Self {
         Self {
@@ -251,11 +342,13 @@ impl WebSocketServer {
             client_manager: ClientManager::new(),
             bus_manager,
             entity_cache,
-            view_index,
+            view_index: view_index.into(),
             max_clients: 10000,
             auth_plugin: Arc::new(crate::websocket::auth::AllowAllAuthPlugin),
             usage_emitter: None,
-            rate_limit_config: None,
+            inbound_limits: InboundLimits::default(),
+            backfill: None,
+            drain: DrainController::new(),
             metrics,
         }
     }
@@ -265,18 +358,20 @@ impl WebSocketServer {
         bind_addr: SocketAddr,
         bus_manager: BusManager,
         entity_cache: EntityCache,
-        view_index: Arc<ViewIndex>,
+        view_index: impl Into<LiveViews>,
     ) -> Self {
         Self {
             bind_addr,
             client_manager: ClientManager::new(),
             bus_manager,
             entity_cache,
-            view_index,
+            view_index: view_index.into(),
             max_clients: 10000,
             auth_plugin: Arc::new(crate::websocket::auth::AllowAllAuthPlugin),
             usage_emitter: None,
-            rate_limit_config: None,
+            inbound_limits: InboundLimits::default(),
+            backfill: None,
+            drain: DrainController::new(),
         }
     }
 
@@ -301,36 +396,149 @@ impl WebSocketServer {
     /// such as maximum connections per IP, timeouts, and rate windows.
     /// Per-subject limits are controlled via AuthContext.Limits from the auth token.
     pub fn with_rate_limit_config(mut self, config: RateLimitConfig) -> Self {
-        self.rate_limit_config = Some(config);
+        self.client_manager = ClientManager::with_config(config);
+        self
+    }
+
+    /// Limit the size and rate of messages each client may send.
+    ///
+    /// Clients that exceed either limit receive an error frame and are
+    /// disconnected.
+    pub fn with_inbound_limits(mut self, limits: InboundLimits) -> Self {
+        self.inbound_limits = limits;
+        self
+    }
+
+    /// Mask the fields each client receives according to its auth context.
+    ///
+    /// See the [`field_mask`](crate::websocket::field_mask) module.
+    pub fn with_field_authorizer(mut self, authorizer: Arc<dyn FieldAuthorizer>) -> Self {
+        self.client_manager = self.client_manager.with_field_authorizer(authorizer);
+        self
+    }
+
+    /// Split frames sent to clients that are larger than `max_bytes`.
+    ///
+    /// See the [`frame_size`](crate::websocket::frame_size) module.
+    pub fn with_max_frame_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.client_manager = self.client_manager.with_max_frame_bytes(max_bytes);
+        self
+    }
+
+    /// Build list snapshots in turns within the limits of `config`.
+    ///
+    /// See the [`snapshot_queue`](crate::websocket::snapshot_queue) module.
+    pub fn with_snapshot_queue(mut self, config: SnapshotQueueConfig) -> Self {
+        self.client_manager = self.client_manager.with_snapshot_queue(config);
+        self
+    }
+
+    /// Let clients resume their subscriptions after reconnecting, keeping
+    /// them within the limits of `config`.
+    ///
+    /// See the [`session`](crate::websocket::session) module.
+    pub fn with_sessions(mut self, config: SessionConfig) -> Self {
+        self.client_manager = self.client_manager.with_sessions(config);
+        self
+    }
+
+    /// Send snapshots in pages of `page_size` entities (None = the batch
+    /// sizes of the entity cache).
+    pub fn with_snapshot_page_size(mut self, page_size: Option<usize>) -> Self {
+        self.client_manager = self.client_manager.with_snapshot_page_size(page_size);
+        self
+    }
+
+    /// Encode 64-bit integers in snapshots and negotiated subscriptions the
+    /// way `big_numbers` says.
+    ///
+    /// See the [`big_numbers`](crate::big_numbers) module.
+    pub fn with_big_numbers(mut self, big_numbers: BigNumbers) -> Self {
+        self.client_manager = self.client_manager.with_big_numbers(big_numbers);
+        self
+    }
+
+    /// Offer clients zstd compression, with the dictionary of `dictionaries`
+    /// once there is one.
+    ///
+    /// See the [`dictionary`](crate::dictionary) module.
+    pub fn with_dictionaries(mut self, dictionaries: Dictionaries) -> Self {
+        self.client_manager = self.client_manager.with_dictionaries(dictionaries);
+        self
+    }
+
+    /// Send frames uncompressed while the `compression` flag is off.
+    ///
+    /// See the [`flags`](crate::flags) module.
+    pub fn with_flags(mut self, flags: Arc<Flags>) -> Self {
+        self.client_manager = self.client_manager.with_flags(flags);
+        self
+    }
+
+    /// Read the time from `clock` when checking view sunsets
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.client_manager = self.client_manager.with_clock(clock);
+        self
+    }
+
+    /// Handle for draining this server's connections, e.g. before a restart.
+    ///
+    /// See the [`drain`](crate::drain) module.
+    /// Fetch unknown keys of `State` subscriptions from RPC
+    pub fn with_backfill(mut self, backfill: RpcBackfill) -> Self {
+        self.backfill = Some(backfill);
         self
     }
 
+    pub fn drain_controller(&self) -> DrainController {
+        self.drain.clone()
+    }
+
+    pub fn bind_addr(&self) -> SocketAddr {
+        self.bind_addr
+    }
+
+    /// Registry of the clients this server will accept
+    pub(crate) fn client_manager(&self) -> ClientManager {
+        self.client_manager.clone()
+    }
+
     pub async fn start(self) -> Result<()> {
+        self.start_until(CancellationToken::new()).await
+    }
+
+    /// Accept connections until `shutdown` is cancelled.
+    ///
+    /// Connections already open are left to the [drain controller](Self::drain_controller).
+    pub async fn start_until(self, shutdown: CancellationToken) -> Result<()> {
         info!(
             "Starting WebSocket server on {} (max_clients: {})",
             self.bind_addr, self.max_clients
         );
 
-        let listener = TcpListener::bind(&self.bind_addr).await?;
-        info!("WebSocket server listening on {}", self.bind_addr);
-
-        // Apply rate limit configuration if provided
-        let client_manager = if let Some(config) = self.rate_limit_config {
-            ClientManager::with_config(config)
-        } else {
-            self.client_manager
-        };
+        let bind_addr = self.bind_addr;
+        let listener = TcpListener::bind(&bind_addr).await?;
+        info!("WebSocket server listening on {}", bind_addr);
 
-        client_manager.start_cleanup_task();
+        let handler = self.into_handler();
+        let cleanup_task = handler.client_manager.start_cleanup_task();
 
         loop {
-            match listener.accept().await {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = shutdown.cancelled() => {
+                    info!("WebSocket server on {} stopped accepting connections", bind_addr);
+                    cleanup_task.abort();
+                    return Ok(());
+                }
+            };
+            match accepted {
                 Ok((stream, addr)) => {
-                    let client_count = client_manager.client_count();
-                    if client_count >= self.max_clients {
+                    let client_count = handler.client_manager.client_count();
+                    if client_count >= handler.max_clients {
                         warn!(
                             "Rejecting connection from {} - max clients ({}) reached",
-                            addr, self.max_clients
+                            addr, handler.max_clients
                         );
                         drop(stream);
                         continue;
@@ -340,48 +548,14 @@ impl WebSocketServer {
                         "New WebSocket connection from {} ({}/{} clients)",
                         addr,
                         client_count + 1,
-                        self.max_clients
+                        handler.max_clients
                     );
-                    let client_manager = client_manager.clone();
-                    let bus_manager = self.bus_manager.clone();
-                    let entity_cache = self.entity_cache.clone();
-                    let view_index = self.view_index.clone();
-                    #[cfg(feature = "otel")]
-                    let metrics = self.metrics.clone();
-
-                    let auth_plugin = self.auth_plugin.clone();
-                    let usage_emitter = self.usage_emitter.clone();
+                    let handler = handler.clone();
 
                     tokio::spawn(
                         async move {
-                            #[cfg(feature = "otel")]
-                            let result = handle_connection(
-                                stream,
-                                client_manager,
-                                bus_manager,
-                                entity_cache,
-                                view_index,
-                                addr,
-                                auth_plugin,
-                                usage_emitter,
-                                metrics,
-                            )
-                            .await;
-                            #[cfg(not(feature = "otel"))]
-                            let result = handle_connection(
-                                stream,
-                                client_manager,
-                                bus_manager,
-                                entity_cache,
-                                view_index,
-                                addr,
-                                auth_plugin,
-                                usage_emitter,
-                            )
-                            .await;
-
-                            if let Err(e) = result {
-                                error!("WebSocket connection error: {}", e);
+                            if let Err(e) = handler.serve_tcp(stream, addr).await {
+                                error!("WebSocket handshake error: {}", e);
                             }
                         }
                         .instrument(info_span!("ws.connection", %addr)),
@@ -393,6 +567,326 @@ impl WebSocketServer {
             }
         }
     }
+
+    /// Convert the server into a connection handler without binding a listener.
+    ///
+    /// The handler owns the client registry, so its cleanup task must be started
+    /// separately via [`ClientManager::start_cleanup_task`].
+    pub(crate) fn into_handler(self) -> ConnectionHandler {
+        ConnectionHandler {
+            client_manager: self.client_manager,
+            bus_manager: self.bus_manager,
+            entity_cache: self.entity_cache,
+            view_index: self.view_index,
+            max_clients: self.max_clients,
+            auth_plugin: self.auth_plugin,
+            usage_emitter: self.usage_emitter,
+            inbound_limits: self.inbound_limits,
+            backfill: self.backfill,
+            drain: self.drain,
+            #[cfg(feature = "otel")]
+            metrics: self.metrics,
+        }
+    }
+}
+
+/// Shared per-connection state, independent of how the socket was accepted.
+///
+/// Used by the standalone listener in [`WebSocketServer::start`] and by the
+/// upgrade route of an embedded router.
+#[derive(Clone)]
+pub(crate) struct ConnectionHandler {
+    pub(crate) client_manager: ClientManager,
+    bus_manager: BusManager,
+    entity_cache: EntityCache,
+    pub(crate) view_index: LiveViews,
+    max_clients: usize,
+    auth_plugin: Arc<dyn WebSocketAuthPlugin>,
+    usage_emitter: Option<Arc<dyn WebSocketUsageEmitter>>,
+    inbound_limits: InboundLimits,
+    backfill: Option<RpcBackfill>,
+    pub(crate) drain: DrainController,
+    #[cfg(feature = "otel")]
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl ConnectionHandler {
+    /// Perform the WebSocket handshake on a raw TCP stream and serve the client.
+    async fn serve_tcp(self, stream: TcpStream, remote_addr: SocketAddr) -> Result<()> {
+        let Some((ws_stream, auth_context, path_subscription)) = accept_authorized_connection(
+            Box::new(stream),
+            remote_addr,
+            self.auth_plugin.clone(),
+            self.client_manager.clone(),
+            self.view_index.clone(),
+            self.drain.clone(),
+            self.inbound_limits.protocol_config(),
+        )
+        .await?
+        else {
+            return Ok(());
+        };
+
+        self.serve(ws_stream, auth_context, path_subscription, remote_addr)
+            .await;
+        Ok(())
+    }
+
+    /// Answer an HTTP upgrade request routed through axum.
+    ///
+    /// Authorization and connection limits are checked before switching
+    /// protocols, so rejected clients receive the same JSON error responses as
+    /// with the standalone listener. The connection itself is served on a
+    /// spawned task once hyper hands over the upgraded socket.
+    pub(crate) async fn upgrade(
+        self,
+        mut request: axum::extract::Request,
+        remote_addr: SocketAddr,
+    ) -> axum::response::Response {
+        use axum::body::Body;
+        use axum::http::header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE};
+
+        let is_upgrade = request.method() == axum::http::Method::GET
+            && header_contains_token(request.headers(), CONNECTION, "upgrade")
+            && header_contains_token(request.headers(), UPGRADE, "websocket");
+        let Some(accept_key) = request
+            .headers()
+            .get(SEC_WEBSOCKET_KEY)
+            .filter(|_| is_upgrade)
+            .map(|key| derive_accept_key(key.as_bytes()))
+        else {
+            return plain_response(StatusCode::UPGRADE_REQUIRED, "Expected WebSocket upgrade");
+        };
+
+        if self.drain.is_draining() {
+            return reject_upgrade(remote_addr, &HandshakeReject::draining());
+        }
+
+        let client_count = self.client_manager.client_count();
+        if client_count >= self.max_clients {
+            warn!(
+                "Rejecting connection from {} - max clients ({}) reached",
+                remote_addr, self.max_clients
+            );
+            let retry_after = self.client_manager.rate_limit_config().overload_retry_after;
+            return reject_upgrade(remote_addr, &HandshakeReject::overloaded(retry_after));
+        }
+
+        let connection_request = ConnectionAuthRequest::from_http_request(remote_addr, &request);
+        let auth_context = match self.auth_plugin.authorize(&connection_request).await {
+            AuthDecision::Allow(ctx) => match self
+                .client_manager
+                .check_connection_allowed(remote_addr, &Some(ctx.clone()))
+                .await
+            {
+                Ok(()) => ctx,
+                Err(deny) => {
+                    return reject_upgrade(remote_addr, &HandshakeReject::from_deny(&deny))
+                }
+            },
+            AuthDecision::Deny(deny) => {
+                return reject_upgrade(remote_addr, &HandshakeReject::from_deny(&deny));
+            }
+        };
+
+        let path_subscription = match path_subscription(
+            &self.view_index.load(),
+            request.uri().path(),
+            request.uri().query(),
+            self.client_manager.now(),
+        ) {
+            Ok(subscription) => subscription,
+            Err(reject) => return reject_upgrade(remote_addr, &reject),
+        };
+
+        info!(
+            "New WebSocket connection from {} ({}/{} clients)",
+            remote_addr,
+            client_count + 1,
+            self.max_clients
+        );
+
+        let on_upgrade = hyper::upgrade::on(&mut request);
+        tokio::spawn(
+            async move {
+                let upgraded = match on_upgrade.await {
+                    Ok(upgraded) => upgraded,
+                    Err(e) => {
+                        warn!("WebSocket upgrade failed for {}: {}", remote_addr, e);
+                        return;
+                    }
+                };
+                let transport: WebSocketTransport =
+                    Box::new(hyper_util::rt::TokioIo::new(upgraded));
+                let ws_stream = tokio_tungstenite::WebSocketStream::from_raw_socket(
+                    transport,
+                    Role::Server,
+                    self.inbound_limits.protocol_config(),
+                )
+                .await;
+                info!("WebSocket connection authorized for {}", remote_addr);
+
+                self.serve(ws_stream, auth_context, path_subscription, remote_addr)
+                    .await;
+            }
+            .instrument(info_span!("ws.connection", addr = %remote_addr)),
+        );
+
+        axum::response::Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(CONNECTION, "Upgrade")
+            .header(UPGRADE, "websocket")
+            .header(SEC_WEBSOCKET_ACCEPT, accept_key)
+            .body(Body::empty())
+            .expect("upgrade response should build")
+    }
+
+    /// Serve the compression dictionary `id` over HTTP.
+    ///
+    /// Dictionaries are trained on frames, so they are only served to
+    /// clients the auth plugin would let connect.
+    pub(crate) async fn dictionary(
+        &self,
+        request: axum::extract::Request,
+        remote_addr: SocketAddr,
+        id: &str,
+    ) -> axum::response::Response {
+        let connection_request = ConnectionAuthRequest::from_http_request(remote_addr, &request);
+        if let AuthDecision::Deny(deny) = self.auth_plugin.authorize(&connection_request).await {
+            return build_handshake_error_response(
+                &Response::new(()),
+                &HandshakeReject::from_deny(&deny),
+            )
+            .map(|body| axum::body::Body::from(body.unwrap_or_default()));
+        }
+
+        dictionary_response(self.client_manager.dictionaries(), id).map(axum::body::Body::new)
+    }
+
+    async fn serve(
+        self,
+        ws_stream: tokio_tungstenite::WebSocketStream<WebSocketTransport>,
+        auth_context: AuthContext,
+        path_subscription: Option<Subscription>,
+        remote_addr: SocketAddr,
+    ) {
+        #[cfg(feature = "otel")]
+        let result = handle_connection(
+            ws_stream,
+            auth_context,
+            path_subscription,
+            self.client_manager,
+            self.bus_manager,
+            self.entity_cache,
+            self.view_index,
+            remote_addr,
+            self.auth_plugin,
+            self.usage_emitter,
+            self.inbound_limits,
+            self.backfill,
+            self.drain,
+            self.metrics,
+        )
+        .await;
+        #[cfg(not(feature = "otel"))]
+        let result = handle_connection(
+            ws_stream,
+            auth_context,
+            path_subscription,
+            self.client_manager,
+            self.bus_manager,
+            self.entity_cache,
+            self.view_index,
+            remote_addr,
+            self.auth_plugin,
+            self.usage_emitter,
+            self.inbound_limits,
+            self.backfill,
+            self.drain,
+        )
+        .await;
+
+        if let Err(e) = result {
+            error!("WebSocket connection error: {}", e);
+        }
+    }
+}
+
+fn header_contains_token(
+    headers: &axum::http::HeaderMap,
+    name: axum::http::HeaderName,
+    token: &str,
+) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|part| part.trim().eq_ignore_ascii_case(token))
+}
+
+fn plain_response(status: StatusCode, message: &'static str) -> axum::response::Response {
+    axum::response::Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(axum::body::Body::from(message))
+        .expect("plain response should build")
+}
+
+fn reject_upgrade(remote_addr: SocketAddr, reject: &HandshakeReject) -> axum::response::Response {
+    warn!(
+        "WebSocket connection rejected during handshake for {}: {}",
+        remote_addr, reject.body.message
+    );
+    build_handshake_error_response(&Response::new(()), reject)
+        .map(|body| axum::body::Body::from(body.unwrap_or_default()))
+}
+
+/// Subscription a client asks for through the URL it connects to (see
+/// [`Subscription::from_path`]), checked against the views the server has.
+/// Connections to any other path speak the JSON protocol and get `None`.
+#[allow(clippy::result_large_err)]
+fn path_subscription(
+    view_index: &ViewIndex,
+    path: &str,
+    query: Option<&str>,
+    now: SystemTime,
+) -> std::result::Result<Option<Subscription>, HandshakeReject> {
+    let Some(subscription) = Subscription::from_path(path, query) else {
+        return Ok(None);
+    };
+    let view_id = match view_index.deprecation(&subscription.view) {
+        Some(deprecation) if deprecation.is_sunset(now) => {
+            return Err(HandshakeReject::bad_path(
+                StatusCode::GONE,
+                "view-sunset",
+                format!(
+                    "View '{}' has been removed, use '{}' instead",
+                    deprecation.id, deprecation.replacement
+                ),
+            ));
+        }
+        Some(deprecation) => &deprecation.replacement,
+        None => &subscription.view,
+    };
+    let Some(view) = view_index.get_view(view_id) else {
+        return Err(HandshakeReject::bad_path(
+            StatusCode::NOT_FOUND,
+            "unknown-view",
+            format!("Unknown view '{}'", subscription.view),
+        ));
+    };
+    if view.mode == Mode::State && subscription.key.is_none() {
+        return Err(HandshakeReject::bad_path(
+            StatusCode::BAD_REQUEST,
+            "missing-key",
+            format!(
+                "View '{}' is a state view; pass the entity key as ?key=",
+                subscription.view
+            ),
+        ));
+    }
+    Ok(Some(subscription))
 }
 
 #[derive(Debug, Clone)]
@@ -417,6 +911,64 @@ impl HandshakeReject {
             retry_after_secs,
         }
     }
+
+    /// Refusal sent while the server drains, so the client retries elsewhere.
+    fn draining() -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            body: crate::websocket::auth::ErrorResponse {
+                error: "draining".to_string(),
+                message: "Server is draining connections for a restart".to_string(),
+                code: "draining".to_string(),
+                retryable: true,
+                retry_after: Some(0),
+                retry_after_ms: Some(0),
+                suggested_action: Some("Reconnect to another instance".to_string()),
+                docs_url: None,
+            },
+            error_code: "draining".to_string(),
+            retry_after_secs: Some(0),
+        }
+    }
+
+    /// Refusal of a path subscription the server can't serve.
+    fn bad_path(status: StatusCode, code: &str, message: String) -> Self {
+        Self {
+            status,
+            body: crate::websocket::auth::ErrorResponse {
+                error: code.to_string(),
+                message,
+                code: code.to_string(),
+                retryable: false,
+                retry_after: None,
+                retry_after_ms: None,
+                suggested_action: None,
+                docs_url: None,
+            },
+            error_code: code.to_string(),
+            retry_after_secs: None,
+        }
+    }
+
+    /// Refusal sent when the server is at its client limit.
+    fn overloaded(retry_after: Duration) -> Self {
+        let retry_after_secs = retry_after.as_millis().div_ceil(1000) as u64;
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            body: crate::websocket::auth::ErrorResponse {
+                error: "overloaded".to_string(),
+                message: "Too many clients".to_string(),
+                code: "overloaded".to_string(),
+                retryable: true,
+                retry_after: Some(retry_after_secs),
+                retry_after_ms: Some(retry_after.as_millis() as u64),
+                suggested_action: Some("Retry after the suggested delay".to_string()),
+                docs_url: None,
+            },
+            error_code: "overloaded".to_string(),
+            retry_after_secs: Some(retry_after_secs),
+        }
+    }
 }
 
 fn build_handshake_error_response(
@@ -450,7 +1002,6 @@ fn build_handshake_error_response(
 mod tests {
     use super::*;
     use crate::websocket::auth::{AuthDeny, AuthErrorCode};
-    use std::time::Duration;
 
     #[test]
     fn handshake_error_response_serializes_json_and_retry_after() {
@@ -490,51 +1041,137 @@ mod tests {
         assert_eq!(handshake_response.status(), StatusCode::FORBIDDEN);
         assert!(handshake_response.headers().get("Retry-After").is_none());
     }
+
+    #[test]
+    fn overloaded_reject_rounds_retry_after_up() {
+        let response = Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .body(())
+            .unwrap();
+        let reject = HandshakeReject::overloaded(Duration::from_millis(2500));
+
+        let handshake_response = build_handshake_error_response(&response, &reject);
+        assert_eq!(handshake_response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            handshake_response.headers().get("X-Error-Code").unwrap(),
+            "overloaded"
+        );
+        assert_eq!(
+            handshake_response.headers().get("Retry-After").unwrap(),
+            "3"
+        );
+
+        let body: serde_json::Value =
+            serde_json::from_str(&handshake_response.into_body().unwrap()).unwrap();
+        assert_eq!(body["retry_after_ms"], 2500);
+    }
+
+    #[test]
+    fn snapshots_are_split_into_numbered_pages() {
+        let entities = (0..5)
+            .map(|index| SnapshotEntity {
+                key: format!("token-{index}"),
+                data: serde_json::json!({ "index": index }),
+            })
+            .collect();
+        let config = SnapshotBatchConfig {
+            initial_batch_size: 2,
+            subsequent_batch_size: 2,
+        };
+
+        let pages: Vec<serde_json::Value> =
+            encode_snapshot_batches(entities, Mode::List, "Token/list", &config)
+                .iter()
+                .map(|batch| serde_json::from_slice(&batch.payload).unwrap())
+                .collect();
+        let fields: Vec<_> = pages
+            .iter()
+            .map(|page| {
+                (
+                    page["page"].as_u64().unwrap(),
+                    page["total_pages"].as_u64().unwrap(),
+                    page["complete"].as_bool().unwrap(),
+                    page["data"].as_array().unwrap().len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            [(1, 3, false, 2), (2, 3, false, 2), (3, 3, true, 1)]
+        );
+    }
 }
 
 #[allow(clippy::result_large_err)]
 async fn accept_authorized_connection(
-    stream: TcpStream,
+    stream: WebSocketTransport,
     remote_addr: SocketAddr,
     auth_plugin: Arc<dyn WebSocketAuthPlugin>,
     client_manager: ClientManager,
-) -> Result<Option<(tokio_tungstenite::WebSocketStream<TcpStream>, AuthContext)>> {
+    view_index: LiveViews,
+    drain: DrainController,
+    protocol_config: Option<WsProtocolConfig>,
+) -> Result<
+    Option<(
+        tokio_tungstenite::WebSocketStream<WebSocketTransport>,
+        AuthContext,
+        Option<Subscription>,
+    )>,
+> {
     use std::sync::Mutex;
 
-    let auth_result_capture: Arc<Mutex<Option<Result<AuthContext, HandshakeReject>>>> =
-        Arc::new(Mutex::new(None));
+    type AuthResult = Result<(AuthContext, Option<Subscription>), HandshakeReject>;
+    let auth_result_capture: Arc<Mutex<Option<AuthResult>>> = Arc::new(Mutex::new(None));
     let auth_result_ref = auth_result_capture.clone();
     let auth_plugin_ref = auth_plugin.clone();
     let client_manager_for_auth = client_manager.clone();
 
-    let handshake_result = accept_hdr_async(stream, move |request: &Request, response| {
-        let connection_request = ConnectionAuthRequest::from_http_request(remote_addr, request);
-
-        let auth_result = tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                match auth_plugin_ref.authorize(&connection_request).await {
-                    AuthDecision::Allow(ctx) => {
-                        match client_manager_for_auth
-                            .check_connection_allowed(remote_addr, &Some(ctx.clone()))
-                            .await
-                        {
-                            Ok(()) => Ok(ctx),
-                            Err(deny) => Err(HandshakeReject::from_deny(&deny)),
+    let handshake_result = accept_hdr_async_with_config(
+        stream,
+        move |request: &Request, response| {
+            let connection_request = ConnectionAuthRequest::from_http_request(remote_addr, request);
+
+            let auth_result = if drain.is_draining() {
+                Err(HandshakeReject::draining())
+            } else {
+                tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current().block_on(async {
+                        match auth_plugin_ref.authorize(&connection_request).await {
+                            AuthDecision::Allow(ctx) => {
+                                match client_manager_for_auth
+                                    .check_connection_allowed(remote_addr, &Some(ctx.clone()))
+                                    .await
+                                {
+                                    Ok(()) => Ok(ctx),
+                                    Err(deny) => Err(HandshakeReject::from_deny(&deny)),
+                                }
+                            }
+                            AuthDecision::Deny(deny) => Err(HandshakeReject::from_deny(&deny)),
                         }
-                    }
-                    AuthDecision::Deny(deny) => Err(HandshakeReject::from_deny(&deny)),
-                }
-            })
-        });
+                    })
+                })
+                .and_then(|ctx| {
+                    let uri = request.uri();
+                    path_subscription(
+                        &view_index.load(),
+                        uri.path(),
+                        uri.query(),
+                        client_manager_for_auth.now(),
+                    )
+                    .map(|subscription| (ctx, subscription))
+                })
+            };
 
-        let mut capture_lock = auth_result_ref.lock().expect("capture lock poisoned");
-        *capture_lock = Some(auth_result.clone());
+            let mut capture_lock = auth_result_ref.lock().expect("capture lock poisoned");
+            *capture_lock = Some(auth_result.clone());
 
-        match auth_result {
-            Ok(_) => Ok(response),
-            Err(reject) => Err(build_handshake_error_response(&response, &reject)),
-        }
-    })
+            match auth_result {
+                Ok(_) => Ok(response),
+                Err(reject) => Err(build_handshake_error_response(&response, &reject)),
+            }
+        },
+        protocol_config,
+    )
     .await;
 
     let auth_result = {
@@ -544,9 +1181,9 @@ async fn accept_authorized_connection(
 
     match handshake_result {
         Ok(ws_stream) => match auth_result {
-            Some(Ok(ctx)) => {
+            Some(Ok((ctx, path_subscription))) => {
                 info!("WebSocket connection authorized for {}", remote_addr);
-                Ok(Some((ws_stream, ctx)))
+                Ok(Some((ws_stream, ctx, path_subscription)))
             }
             Some(Err(reject)) => Err(anyhow::anyhow!(
                 "handshake unexpectedly succeeded after rejection: {}",
@@ -585,28 +1222,23 @@ async fn accept_authorized_connection(
 }
 
 #[cfg(feature = "otel")]
+#[allow(clippy::too_many_arguments)]
 async fn handle_connection(
-    stream: TcpStream,
+    ws_stream: tokio_tungstenite::WebSocketStream<WebSocketTransport>,
+    auth_context: AuthContext,
+    path_subscription: Option<Subscription>,
     client_manager: ClientManager,
     bus_manager: BusManager,
     entity_cache: EntityCache,
-    view_index: Arc<ViewIndex>,
+    view_index: LiveViews,
     remote_addr: std::net::SocketAddr,
     auth_plugin: Arc<dyn WebSocketAuthPlugin>,
     usage_emitter: Option<Arc<dyn WebSocketUsageEmitter>>,
+    inbound_limits: InboundLimits,
+    backfill: Option<RpcBackfill>,
+    drain: DrainController,
     metrics: Option<Arc<Metrics>>,
 ) -> Result<()> {
-    let Some((ws_stream, auth_context)) = accept_authorized_connection(
-        stream,
-        remote_addr,
-        auth_plugin.clone(),
-        client_manager.clone(),
-    )
-    .await?
-    else {
-        return Ok(());
-    };
-
     let client_id = Uuid::new_v4();
     let connection_start = Instant::now();
 
@@ -643,6 +1275,9 @@ async fn handle_connection(
 
     // Add client with auth context and IP tracking
     client_manager.add_client(client_id, ws_sender, auth_context, remote_addr);
+    if path_subscription.is_none() {
+        let _ = client_manager.send_hello(client_id).await;
+    }
 
     let ctx = SubscriptionContext {
         client_id,
@@ -651,23 +1286,62 @@ async fn handle_connection(
         entity_cache: &entity_cache,
         view_index: &view_index,
         usage_emitter: &usage_emitter,
+        backfill: backfill.as_ref(),
         metrics: metrics.clone(),
     };
 
-    let mut active_subscriptions: HashMap<String, String> = HashMap::new();
+    let mut active_subscriptions: HashMap<String, Subscription> = HashMap::new();
+    let mut inbound_guard = InboundGuard::new(inbound_limits, Instant::now());
+
+    if let Some(subscription) = path_subscription {
+        let view_id = subscription.view.clone();
+        if let Some(sub_key) = open_path_subscription(&ctx, &subscription).await {
+            active_subscriptions.insert(sub_key, subscription);
+            emit_usage_event(
+                &usage_emitter,
+                WebSocketUsageEvent::SubscriptionCreated {
+                    client_id: client_id.to_string(),
+                    deployment_id: usage_deployment_id.clone(),
+                    metering_key: usage_metering_key.clone(),
+                    subject: usage_subject.clone(),
+                    view_id,
+                },
+            );
+        }
+    }
+
+    let _connection = drain.track_connection();
+    let drained = drain.drain_client(client_id, &client_manager);
+    tokio::pin!(drained);
+    let mut client_closed = false;
 
     loop {
         tokio::select! {
+            _ = &mut drained => {
+                info!("Client {} closed by drain", client_id);
+                break;
+            }
             ws_msg = ws_receiver.next() => {
                 match ws_msg {
                     Some(Ok(msg)) => {
                         if msg.is_close() {
                             info!("Client {} requested close", client_id);
+                            client_closed = true;
                             break;
                         }
 
                         client_manager.update_client_last_seen(client_id);
 
+                        if msg.is_text() || msg.is_binary() {
+                            if let Err(violation) = inbound_guard.check(msg.len(), Instant::now()) {
+                                reject_inbound(client_id, &client_manager, &violation).await;
+                                if let Some(ref m) = metrics {
+                                    m.record_ws_inbound_rejected(violation.reason());
+                                }
+                                break;
+                            }
+                        }
+
                         if msg.is_text() {
                             if let Err(deny) = client_manager.check_inbound_message_allowed(client_id) {
                                 warn!("Inbound message rejected for client {}: {}", client_id, deny.reason);
@@ -689,6 +1363,7 @@ async fn handle_connection(
                                 if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(text) {
                                     match client_msg {
                                         ClientMessage::Subscribe(subscription) => {
+                                            let received_at = Instant::now();
                                             let view_id = subscription.view.clone();
                                             let sub_key = subscription.sub_key();
 
@@ -709,11 +1384,14 @@ async fn handle_connection(
                                             ).await;
 
                                             if !is_new {
-                                                debug!("Client {} already subscribed to {}, ignoring duplicate", client_id, sub_key);
-                                                continue;
+                                                info!("Client {} replaced its subscription {}", client_id, sub_key);
                                             }
 
-                                            if let Err(err) = attach_client_to_bus(&ctx, subscription, cancel_token).await {
+                                            let Some(sender) = client_manager.subscription_sender(client_id, &sub_key, SequenceStart::Subscribe) else {
+                                                continue;
+                                            };
+
+                                            if let Err(err) = attach_client_to_bus(&ctx, subscription.clone(), sender, cancel_token, received_at).await {
                                                 warn!(
                                                     "Subscription rejected for client {} on {}: {}",
                                                     client_id, view_id, err
@@ -727,24 +1405,26 @@ async fn handle_connection(
                                                 continue;
                                             }
 
-                                            if let Some(ref m) = metrics {
+                                            if let Some(m) = metrics.as_ref().filter(|_| is_new) {
                                                 if let Some(ref mk) = metering_key {
                                                     m.record_subscription_created_with_metering(&view_id, mk);
                                                 } else {
                                                     m.record_subscription_created(&view_id);
                                                 }
                                             }
-                                            active_subscriptions.insert(sub_key, view_id.clone());
-                                            emit_usage_event(
-                                                &usage_emitter,
-                                                WebSocketUsageEvent::SubscriptionCreated {
-                                                    client_id: client_id.to_string(),
-                                                    deployment_id: usage_deployment_id.clone(),
-                                                    metering_key: usage_metering_key.clone(),
-                                                    subject: usage_subject.clone(),
-                                                    view_id,
-                                                },
-                                            );
+                                            active_subscriptions.insert(sub_key, subscription);
+                                            if is_new {
+                                                emit_usage_event(
+                                                    &usage_emitter,
+                                                    WebSocketUsageEvent::SubscriptionCreated {
+                                                        client_id: client_id.to_string(),
+                                                        deployment_id: usage_deployment_id.clone(),
+                                                        metering_key: usage_metering_key.clone(),
+                                                        subject: usage_subject.clone(),
+                                                        view_id,
+                                                    },
+                                                );
+                                            }
                                         }
                                         ClientMessage::Unsubscribe(unsub) => {
                                             let sub_key = unsub.sub_key();
@@ -774,6 +1454,39 @@ async fn handle_connection(
                                                 );
                                             }
                                         }
+                                        ClientMessage::Resync(resync) => {
+                                            let sub_key = resync.sub_key();
+                                            match active_subscriptions.get(&sub_key) {
+                                                Some(subscription) => {
+                                                    info!("Client {} requested a resync of {}", client_id, sub_key);
+                                                    if let Err(err) = resync_subscription(&ctx, subscription, SequenceStart::Resync).await {
+                                                        warn!("Resync failed for client {} on {}: {}", client_id, sub_key, err);
+                                                    }
+                                                }
+                                                None => {
+                                                    debug!("Client {} requested a resync of unknown subscription {}", client_id, sub_key);
+                                                }
+                                            }
+                                        }
+                                        ClientMessage::Refresh(refresh) => {
+                                            let sub_key = refresh.sub_key();
+                                            match active_subscriptions.get(&sub_key) {
+                                                Some(subscription) => {
+                                                    if let Err(deny) = client_manager.check_refresh_allowed(client_id, &sub_key) {
+                                                        debug!("Refresh of {} refused for client {}: {}", sub_key, client_id, deny.reason);
+                                                        send_socket_issue(client_id, &client_manager, &deny, false).await;
+                                                        continue;
+                                                    }
+                                                    info!("Client {} requested a refresh of {}", client_id, sub_key);
+                                                    if let Err(err) = resync_subscription(&ctx, subscription, SequenceStart::Refresh).await {
+                                                        warn!("Refresh failed for client {} on {}: {}", client_id, sub_key, err);
+                                                    }
+                                                }
+                                                None => {
+                                                    debug!("Client {} requested a refresh of unknown subscription {}", client_id, sub_key);
+                                                }
+                                            }
+                                        }
                                         ClientMessage::Ping => {
                                             debug!("Received ping from client {}", client_id);
                                         }
@@ -781,8 +1494,41 @@ async fn handle_connection(
                                             debug!("Received refresh_auth from client {}", client_id);
                                             handle_refresh_auth(client_id, &refresh_req, &client_manager, &auth_plugin).await;
                                         }
+                                        ClientMessage::Compression(request) => {
+                                            match client_manager.negotiate_compression(client_id, &request) {
+                                                Some(codec) => debug!("Client {} negotiated {:?}", client_id, codec),
+                                                None => debug!("Ignored compression request from client {}", client_id),
+                                            }
+                                        }
+                                        ClientMessage::Hello(hello) => {
+                                            let Some(token) = hello.session else {
+                                                continue;
+                                            };
+                                            for (sub_key, subscription) in resume_session(&ctx, &token, &active_subscriptions).await {
+                                                let view_id = subscription.view.clone();
+                                                if let Some(ref m) = metrics {
+                                                    if let Some(ref mk) = metering_key {
+                                                        m.record_subscription_created_with_metering(&view_id, mk);
+                                                    } else {
+                                                        m.record_subscription_created(&view_id);
+                                                    }
+                                                }
+                                                active_subscriptions.insert(sub_key, subscription);
+                                                emit_usage_event(
+                                                    &usage_emitter,
+                                                    WebSocketUsageEvent::SubscriptionCreated {
+                                                        client_id: client_id.to_string(),
+                                                        deployment_id: usage_deployment_id.clone(),
+                                                        metering_key: usage_metering_key.clone(),
+                                                        subject: usage_subject.clone(),
+                                                        view_id,
+                                                    },
+                                                );
+                                            }
+                                        }
                                     }
                                 } else if let Ok(subscription) = serde_json::from_str::<Subscription>(text) {
+                                    let received_at = Instant::now();
                                     let view_id = subscription.view.clone();
                                     let sub_key = subscription.sub_key();
 
@@ -802,11 +1548,14 @@ async fn handle_connection(
                                     ).await;
 
                                     if !is_new {
-                                        debug!("Client {} already subscribed to {}, ignoring duplicate", client_id, sub_key);
-                                        continue;
+                                        info!("Client {} replaced its subscription {}", client_id, sub_key);
                                     }
 
-                                    if let Err(err) = attach_client_to_bus(&ctx, subscription, cancel_token).await {
+                                    let Some(sender) = client_manager.subscription_sender(client_id, &sub_key, SequenceStart::Subscribe) else {
+                                        continue;
+                                    };
+
+                                    if let Err(err) = attach_client_to_bus(&ctx, subscription.clone(), sender, cancel_token, received_at).await {
                                         warn!(
                                             "Subscription rejected for client {} on {}: {}",
                                             client_id, view_id, err
@@ -820,30 +1569,43 @@ async fn handle_connection(
                                         continue;
                                     }
 
-                                    if let Some(ref m) = metrics {
+                                    if let Some(m) = metrics.as_ref().filter(|_| is_new) {
                                         if let Some(ref mk) = metering_key {
                                             m.record_subscription_created_with_metering(&view_id, mk);
                                         } else {
                                             m.record_subscription_created(&view_id);
                                         }
                                     }
-                                    active_subscriptions.insert(sub_key, view_id.clone());
-                                    emit_usage_event(
-                                        &usage_emitter,
-                                        WebSocketUsageEvent::SubscriptionCreated {
-                                            client_id: client_id.to_string(),
-                                            deployment_id: usage_deployment_id.clone(),
-                                            metering_key: usage_metering_key.clone(),
-                                            subject: usage_subject.clone(),
-                                            view_id,
-                                        },
+                                    active_subscriptions.insert(sub_key, subscription);
+                                    if is_new {
+                                        emit_usage_event(
+                                            &usage_emitter,
+                                            WebSocketUsageEvent::SubscriptionCreated {
+                                                client_id: client_id.to_string(),
+                                                deployment_id: usage_deployment_id.clone(),
+                                                metering_key: usage_metering_key.clone(),
+                                                subject: usage_subject.clone(),
+                                                view_id,
+                                            },
+                                        );
+                                    }
+                                } else if let Some(suppressed) = inbound_guard.parse_error(Instant::now()) {
+                                    warn!(
+                                        "Ignoring unparseable message from client {} ({} bytes, {} more suppressed)",
+                                        client_id, text.len(), suppressed
                                     );
-                                } else {
-                                    debug!("Received non-subscription message from client {}: {}", client_id, text);
                                 }
                             }
                         }
                     }
+                    Some(Err(WsError::Capacity(CapacityError::MessageTooLong { size, max_size }))) => {
+                        let violation = InboundViolation::MessageTooLarge { size, limit: max_size };
+                        reject_inbound(client_id, &client_manager, &violation).await;
+                        if let Some(ref m) = metrics {
+                            m.record_ws_inbound_rejected(violation.reason());
+                        }
+                        break;
+                    }
                     Some(Err(e)) => {
                         warn!("WebSocket error for client {}: {}", client_id, e);
                         break;
@@ -857,6 +1619,10 @@ async fn handle_connection(
         }
     }
 
+    // A client that closed the connection itself won't resume it
+    if !client_closed {
+        client_manager.retain_session(client_id, active_subscriptions.values());
+    }
     client_manager
         .cancel_all_client_subscriptions(client_id)
         .await;
@@ -869,18 +1635,18 @@ async fn handle_connection(
         let duration_secs = connection_start.elapsed().as_secs_f64();
         if let Some(ref mk) = metering_key {
             m.record_ws_disconnection_with_metering(duration_secs, mk);
-            for view_id in active_subscriptions.values() {
-                m.record_subscription_removed_with_metering(view_id, mk);
+            for subscription in active_subscriptions.values() {
+                m.record_subscription_removed_with_metering(&subscription.view, mk);
             }
         } else {
             m.record_ws_disconnection(duration_secs);
-            for view_id in active_subscriptions.values() {
-                m.record_subscription_removed(view_id);
+            for subscription in active_subscriptions.values() {
+                m.record_subscription_removed(&subscription.view);
             }
         }
     }
 
-    for view_id in active_subscriptions.values() {
+    for subscription in active_subscriptions.values() {
         emit_usage_event(
             &usage_emitter,
             WebSocketUsageEvent::SubscriptionRemoved {
@@ -888,7 +1654,7 @@ async fn handle_connection(
                 deployment_id: usage_deployment_id.clone(),
                 metering_key: usage_metering_key.clone(),
                 subject: usage_subject.clone(),
-                view_id: view_id.clone(),
+                view_id: subscription.view.clone(),
             },
         );
     }
@@ -913,26 +1679,20 @@ async fn handle_connection(
 #[cfg(not(feature = "otel"))]
 #[allow(clippy::too_many_arguments)]
 async fn handle_connection(
-    stream: TcpStream,
+    ws_stream: tokio_tungstenite::WebSocketStream<WebSocketTransport>,
+    auth_context: AuthContext,
+    path_subscription: Option<Subscription>,
     client_manager: ClientManager,
     bus_manager: BusManager,
     entity_cache: EntityCache,
-    view_index: Arc<ViewIndex>,
+    view_index: LiveViews,
     remote_addr: std::net::SocketAddr,
     auth_plugin: Arc<dyn WebSocketAuthPlugin>,
     usage_emitter: Option<Arc<dyn WebSocketUsageEmitter>>,
+    inbound_limits: InboundLimits,
+    backfill: Option<RpcBackfill>,
+    drain: DrainController,
 ) -> Result<()> {
-    let Some((ws_stream, auth_context)) = accept_authorized_connection(
-        stream,
-        remote_addr,
-        auth_plugin.clone(),
-        client_manager.clone(),
-    )
-    .await?
-    else {
-        return Ok(());
-    };
-
     let client_id = Uuid::new_v4();
     let auth_context_ref = Some(&auth_context);
     let (usage_metering_key, usage_subject, usage_key_class, usage_deployment_id) =
@@ -958,6 +1718,9 @@ async fn handle_connection(
 
     // Add client with auth context and IP tracking
     client_manager.add_client(client_id, ws_sender, auth_context, remote_addr);
+    if path_subscription.is_none() {
+        let _ = client_manager.send_hello(client_id).await;
+    }
 
     let ctx = SubscriptionContext {
         client_id,
@@ -966,22 +1729,58 @@ async fn handle_connection(
         entity_cache: &entity_cache,
         view_index: &view_index,
         usage_emitter: &usage_emitter,
+        backfill: backfill.as_ref(),
     };
 
-    let mut active_subscriptions: HashMap<String, String> = HashMap::new();
+    let mut active_subscriptions: HashMap<String, Subscription> = HashMap::new();
+    let mut inbound_guard = InboundGuard::new(inbound_limits, Instant::now());
+
+    if let Some(subscription) = path_subscription {
+        let view_id = subscription.view.clone();
+        if let Some(sub_key) = open_path_subscription(&ctx, &subscription).await {
+            active_subscriptions.insert(sub_key, subscription);
+            emit_usage_event(
+                &usage_emitter,
+                WebSocketUsageEvent::SubscriptionCreated {
+                    client_id: client_id.to_string(),
+                    deployment_id: usage_deployment_id.clone(),
+                    metering_key: usage_metering_key.clone(),
+                    subject: usage_subject.clone(),
+                    view_id,
+                },
+            );
+        }
+    }
+
+    let _connection = drain.track_connection();
+    let drained = drain.drain_client(client_id, &client_manager);
+    tokio::pin!(drained);
+    let mut client_closed = false;
 
     loop {
         tokio::select! {
+            _ = &mut drained => {
+                info!("Client {} closed by drain", client_id);
+                break;
+            }
             ws_msg = ws_receiver.next() => {
                 match ws_msg {
                     Some(Ok(msg)) => {
                         if msg.is_close() {
                             info!("Client {} requested close", client_id);
+                            client_closed = true;
                             break;
                         }
 
                         client_manager.update_client_last_seen(client_id);
 
+                        if msg.is_text() || msg.is_binary() {
+                            if let Err(violation) = inbound_guard.check(msg.len(), Instant::now()) {
+                                reject_inbound(client_id, &client_manager, &violation).await;
+                                break;
+                            }
+                        }
+
                         if msg.is_text() {
                             if let Err(deny) = client_manager.check_inbound_message_allowed(client_id) {
                                 warn!("Inbound message rejected for client {}: {}", client_id, deny.reason);
@@ -995,6 +1794,7 @@ async fn handle_connection(
                                 if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(text) {
                                     match client_msg {
                                         ClientMessage::Subscribe(subscription) => {
+                                            let received_at = Instant::now();
                                             let view_id = subscription.view.clone();
                                             if let Err(deny) = client_manager.check_subscription_allowed(client_id).await {
                                                 warn!("Subscription rejected for client {}: {}", client_id, deny.reason);
@@ -1013,11 +1813,14 @@ async fn handle_connection(
                                             ).await;
 
                                             if !is_new {
-                                                debug!("Client {} already subscribed to {}, ignoring duplicate", client_id, sub_key);
-                                                continue;
+                                                info!("Client {} replaced its subscription {}", client_id, sub_key);
                                             }
 
-                                            if let Err(err) = attach_client_to_bus(&ctx, subscription, cancel_token).await {
+                                            let Some(sender) = client_manager.subscription_sender(client_id, &sub_key, SequenceStart::Subscribe) else {
+                                                continue;
+                                            };
+
+                                            if let Err(err) = attach_client_to_bus(&ctx, subscription.clone(), sender, cancel_token, received_at).await {
                                                 warn!(
                                                     "Subscription rejected for client {} on {}: {}",
                                                     client_id,
@@ -1031,17 +1834,19 @@ async fn handle_connection(
                                                     .remove_client_subscription(client_id, &sub_key)
                                                     .await;
                                             } else {
-                                                active_subscriptions.insert(sub_key, view_id.clone());
-                                                emit_usage_event(
-                                                    &usage_emitter,
-                                                    WebSocketUsageEvent::SubscriptionCreated {
-                                                        client_id: client_id.to_string(),
-                                                        deployment_id: usage_deployment_id.clone(),
-                                                        metering_key: usage_metering_key.clone(),
-                                                        subject: usage_subject.clone(),
-                                                        view_id,
-                                                    },
-                                                );
+                                                active_subscriptions.insert(sub_key, subscription);
+                                                if is_new {
+                                                    emit_usage_event(
+                                                        &usage_emitter,
+                                                        WebSocketUsageEvent::SubscriptionCreated {
+                                                            client_id: client_id.to_string(),
+                                                            deployment_id: usage_deployment_id.clone(),
+                                                            metering_key: usage_metering_key.clone(),
+                                                            subject: usage_subject.clone(),
+                                                            view_id,
+                                                        },
+                                                    );
+                                                }
                                             }
                                         }
                                         ClientMessage::Unsubscribe(unsub) => {
@@ -1065,6 +1870,39 @@ async fn handle_connection(
                                                 );
                                             }
                                         }
+                                        ClientMessage::Resync(resync) => {
+                                            let sub_key = resync.sub_key();
+                                            match active_subscriptions.get(&sub_key) {
+                                                Some(subscription) => {
+                                                    info!("Client {} requested a resync of {}", client_id, sub_key);
+                                                    if let Err(err) = resync_subscription(&ctx, subscription, SequenceStart::Resync).await {
+                                                        warn!("Resync failed for client {} on {}: {}", client_id, sub_key, err);
+                                                    }
+                                                }
+                                                None => {
+                                                    debug!("Client {} requested a resync of unknown subscription {}", client_id, sub_key);
+                                                }
+                                            }
+                                        }
+                                        ClientMessage::Refresh(refresh) => {
+                                            let sub_key = refresh.sub_key();
+                                            match active_subscriptions.get(&sub_key) {
+                                                Some(subscription) => {
+                                                    if let Err(deny) = client_manager.check_refresh_allowed(client_id, &sub_key) {
+                                                        debug!("Refresh of {} refused for client {}: {}", sub_key, client_id, deny.reason);
+                                                        send_socket_issue(client_id, &client_manager, &deny, false).await;
+                                                        continue;
+                                                    }
+                                                    info!("Client {} requested a refresh of {}", client_id, sub_key);
+                                                    if let Err(err) = resync_subscription(&ctx, subscription, SequenceStart::Refresh).await {
+                                                        warn!("Refresh failed for client {} on {}: {}", client_id, sub_key, err);
+                                                    }
+                                                }
+                                                None => {
+                                                    debug!("Client {} requested a refresh of unknown subscription {}", client_id, sub_key);
+                                                }
+                                            }
+                                        }
                                         ClientMessage::Ping => {
                                             debug!("Received ping from client {}", client_id);
                                         }
@@ -1072,8 +1910,34 @@ async fn handle_connection(
                                             debug!("Received refresh_auth from client {}", client_id);
                                             handle_refresh_auth(client_id, &refresh_req, &client_manager, &auth_plugin).await;
                                         }
+                                        ClientMessage::Compression(request) => {
+                                            match client_manager.negotiate_compression(client_id, &request) {
+                                                Some(codec) => debug!("Client {} negotiated {:?}", client_id, codec),
+                                                None => debug!("Ignored compression request from client {}", client_id),
+                                            }
+                                        }
+                                        ClientMessage::Hello(hello) => {
+                                            let Some(token) = hello.session else {
+                                                continue;
+                                            };
+                                            for (sub_key, subscription) in resume_session(&ctx, &token, &active_subscriptions).await {
+                                                let view_id = subscription.view.clone();
+                                                active_subscriptions.insert(sub_key, subscription);
+                                                emit_usage_event(
+                                                    &usage_emitter,
+                                                    WebSocketUsageEvent::SubscriptionCreated {
+                                                        client_id: client_id.to_string(),
+                                                        deployment_id: usage_deployment_id.clone(),
+                                                        metering_key: usage_metering_key.clone(),
+                                                        subject: usage_subject.clone(),
+                                                        view_id,
+                                                    },
+                                                );
+                                            }
+                                        }
                                     }
                                 } else if let Ok(subscription) = serde_json::from_str::<Subscription>(text) {
+                                    let received_at = Instant::now();
                                     let view_id = subscription.view.clone();
                                     if let Err(deny) = client_manager.check_subscription_allowed(client_id).await {
                                         warn!("Subscription rejected for client {}: {}", client_id, deny.reason);
@@ -1092,11 +1956,14 @@ async fn handle_connection(
                                     ).await;
 
                                     if !is_new {
-                                        debug!("Client {} already subscribed to {}, ignoring duplicate", client_id, sub_key);
-                                        continue;
+                                        info!("Client {} replaced its subscription {}", client_id, sub_key);
                                     }
 
-                                    if let Err(err) = attach_client_to_bus(&ctx, subscription, cancel_token).await {
+                                    let Some(sender) = client_manager.subscription_sender(client_id, &sub_key, SequenceStart::Subscribe) else {
+                                        continue;
+                                    };
+
+                                    if let Err(err) = attach_client_to_bus(&ctx, subscription.clone(), sender, cancel_token, received_at).await {
                                         warn!(
                                             "Subscription rejected for client {} on {}: {}",
                                             client_id,
@@ -1110,24 +1977,34 @@ async fn handle_connection(
                                             .remove_client_subscription(client_id, &sub_key)
                                             .await;
                                     } else {
-                                        active_subscriptions.insert(sub_key, view_id.clone());
-                                        emit_usage_event(
-                                            &usage_emitter,
-                                            WebSocketUsageEvent::SubscriptionCreated {
-                                                client_id: client_id.to_string(),
-                                                deployment_id: usage_deployment_id.clone(),
-                                                metering_key: usage_metering_key.clone(),
-                                                subject: usage_subject.clone(),
-                                                view_id,
-                                            },
-                                        );
+                                        active_subscriptions.insert(sub_key, subscription);
+                                        if is_new {
+                                            emit_usage_event(
+                                                &usage_emitter,
+                                                WebSocketUsageEvent::SubscriptionCreated {
+                                                    client_id: client_id.to_string(),
+                                                    deployment_id: usage_deployment_id.clone(),
+                                                    metering_key: usage_metering_key.clone(),
+                                                    subject: usage_subject.clone(),
+                                                    view_id,
+                                                },
+                                            );
+                                        }
                                     }
-                                } else {
-                                    debug!("Received non-subscription message from client {}: {}", client_id, text);
+                                } else if let Some(suppressed) = inbound_guard.parse_error(Instant::now()) {
+                                    warn!(
+                                        "Ignoring unparseable message from client {} ({} bytes, {} more suppressed)",
+                                        client_id, text.len(), suppressed
+                                    );
                                 }
                             }
                         }
                     }
+                    Some(Err(WsError::Capacity(CapacityError::MessageTooLong { size, max_size }))) => {
+                        let violation = InboundViolation::MessageTooLarge { size, limit: max_size };
+                        reject_inbound(client_id, &client_manager, &violation).await;
+                        break;
+                    }
                     Some(Err(e)) => {
                         warn!("WebSocket error for client {}: {}", client_id, e);
                         break;
@@ -1141,6 +2018,10 @@ async fn handle_connection(
         }
     }
 
+    // A client that closed the connection itself won't resume it
+    if !client_closed {
+        client_manager.retain_session(client_id, active_subscriptions.values());
+    }
     client_manager
         .cancel_all_client_subscriptions(client_id)
         .await;
@@ -1149,7 +2030,7 @@ async fn handle_connection(
         rate_limiter.remove_client_buckets(client_id).await;
     }
 
-    for view_id in active_subscriptions.values() {
+    for subscription in active_subscriptions.values() {
         emit_usage_event(
             &usage_emitter,
             WebSocketUsageEvent::SubscriptionRemoved {
@@ -1157,7 +2038,7 @@ async fn handle_connection(
                 deployment_id: usage_deployment_id.clone(),
                 metering_key: usage_metering_key.clone(),
                 subject: usage_subject.clone(),
-                view_id: view_id.clone(),
+                view_id: subscription.view.clone(),
             },
         );
     }
@@ -1179,78 +2060,169 @@ async fn handle_connection(
     Ok(())
 }
 
-async fn send_snapshot_batches(
-    client_id: Uuid,
-    entities: &[SnapshotEntity],
+/// Subscribe a client that connected through a view's URL path, as if it had
+/// sent the subscribe message itself, and switch it to the minimal frame
+/// envelope. Subscription limits apply as usual; a client refused its only
+/// subscription is disconnected with the reason in the close frame.
+///
+/// Returns the subscription key once attached.
+async fn open_path_subscription(
+    ctx: &SubscriptionContext<'_>,
+    subscription: &Subscription,
+) -> Option<String> {
+    let client_id = ctx.client_id;
+    let client_manager = ctx.client_manager;
+    client_manager.use_minimal_frames(client_id);
+
+    let sub_key = subscription.sub_key();
+    let result = match client_manager.check_subscription_allowed(client_id).await {
+        Ok(()) => {
+            client_manager.update_subscription(client_id, subscription.clone());
+            let cancel_token = CancellationToken::new();
+            client_manager
+                .add_client_subscription(client_id, sub_key.clone(), cancel_token.clone())
+                .await;
+            match client_manager.subscription_sender(client_id, &sub_key, SequenceStart::Subscribe)
+            {
+                Some(sender) => attach_client_to_bus(
+                    ctx,
+                    subscription.clone(),
+                    sender,
+                    cancel_token,
+                    Instant::now(),
+                )
+                .await
+                .map_err(|err| {
+                    let reason = err.to_string();
+                    auth_deny_from_subscription_error(&reason).unwrap_or_else(|| {
+                        AuthDeny::new(crate::websocket::auth::AuthErrorCode::InternalError, reason)
+                    })
+                }),
+                None => return None,
+            }
+        }
+        Err(deny) => Err(deny),
+    };
+
+    match result {
+        Ok(()) => {
+            info!("Client {} subscribed to {} by path", client_id, sub_key);
+            Some(sub_key)
+        }
+        Err(deny) => {
+            warn!(
+                "Path subscription rejected for client {} on {}: {}",
+                client_id, sub_key, deny.reason
+            );
+            let _ = client_manager
+                .remove_client_subscription(client_id, &sub_key)
+                .await;
+            let retry_after = match deny.retry_policy {
+                crate::websocket::auth::RetryPolicy::RetryAfter(duration) => Some(duration),
+                _ => None,
+            };
+            client_manager.shed_client(
+                client_id,
+                CloseCode::Policy,
+                CloseReason::new(deny.code.as_str(), retry_after),
+            );
+            None
+        }
+    }
+}
+
+/// A snapshot batch serialized for sending
+struct SnapshotBatch {
+    payload: Vec<u8>,
+    rows: u32,
+}
+
+/// Split a snapshot into numbered pages and serialize them
+fn encode_snapshot_batches(
+    entities: Vec<SnapshotEntity>,
     mode: Mode,
     view_id: &str,
-    client_manager: &ClientManager,
-    usage_emitter: &Option<Arc<dyn WebSocketUsageEmitter>>,
     batch_config: &SnapshotBatchConfig,
-    #[cfg(feature = "otel")] metrics: Option<&Arc<Metrics>>,
-) -> Result<()> {
+) -> Vec<SnapshotBatch> {
     let total = entities.len();
-    if total == 0 {
-        return Ok(());
-    }
-
+    let mut entities = entities.into_iter();
+    let mut pages = Vec::new();
     let mut offset = 0;
-    let mut batch_num = 0;
 
     while offset < total {
-        let batch_size = if batch_num == 0 {
+        let batch_size = if pages.is_empty() {
             batch_config.initial_batch_size
         } else {
             batch_config.subsequent_batch_size
         };
 
-        let end = (offset + batch_size).min(total);
-        let batch_data: Vec<SnapshotEntity> = entities[offset..end].to_vec();
-        let rows_in_batch = batch_data.len() as u32;
-        let is_complete = end >= total;
-
-        let snapshot_frame = SnapshotFrame {
-            mode,
-            export: view_id.to_string(),
-            op: "snapshot",
-            data: batch_data,
-            complete: is_complete,
-        };
+        let end = (offset + batch_size.max(1)).min(total);
+        pages.push(entities.by_ref().take(end - offset).collect::<Vec<_>>());
+        offset = end;
+    }
 
-        if let Ok(json_payload) = serde_json::to_vec(&snapshot_frame) {
-            let payload = maybe_compress(&json_payload);
-            let payload_bytes = payload.as_bytes().len() as u64;
-            if client_manager
-                .send_compressed_async(client_id, payload)
-                .await
-                .is_err()
-            {
-                return Err(anyhow::anyhow!("Failed to send snapshot batch"));
-            }
-            #[cfg(feature = "otel")]
-            if let Some(m) = metrics {
-                m.record_ws_message_sent();
-            }
+    let total_pages = pages.len() as u32;
+    pages
+        .into_iter()
+        .zip(1..)
+        .filter_map(|(data, page)| {
+            let rows = data.len() as u32;
+            let snapshot_frame = SnapshotFrame {
+                mode,
+                export: view_id.to_string(),
+                op: "snapshot",
+                data,
+                complete: page == total_pages,
+                page,
+                total_pages,
+            };
+            let payload = serde_json::to_vec(&snapshot_frame).ok()?;
+            Some(SnapshotBatch { payload, rows })
+        })
+        .collect()
+}
 
-            let auth_context = client_manager.get_auth_context(client_id);
-            let (metering_key, subject, _, deployment_id) = usage_identity(auth_context.as_ref());
-            emit_usage_event(
-                usage_emitter,
-                WebSocketUsageEvent::SnapshotSent {
-                    client_id: client_id.to_string(),
-                    deployment_id,
-                    metering_key,
-                    subject,
-                    view_id: view_id.to_string(),
-                    rows: rows_in_batch,
-                    messages: 1,
-                    bytes: payload_bytes,
-                },
-            );
+async fn send_snapshot_batches(
+    sender: &SubscriptionSender,
+    batches: Vec<SnapshotBatch>,
+    view_id: &str,
+    client_manager: &ClientManager,
+    usage_emitter: &Option<Arc<dyn WebSocketUsageEmitter>>,
+    #[cfg(feature = "otel")] metrics: Option<&Arc<Metrics>>,
+) -> Result<()> {
+    let client_id = sender.client_id();
+    if batches.is_empty() {
+        return Ok(());
+    }
+
+    let batch_num = batches.len();
+    let mut total = 0;
+
+    for batch in batches {
+        let Ok(payload_bytes) = sender.send_compressed(&batch.payload).await else {
+            return Err(anyhow::anyhow!("Failed to send snapshot batch"));
+        };
+        #[cfg(feature = "otel")]
+        if let Some(m) = metrics {
+            m.record_ws_message_sent();
         }
+        total += batch.rows as usize;
 
-        offset = end;
-        batch_num += 1;
+        let auth_context = client_manager.get_auth_context(client_id);
+        let (metering_key, subject, _, deployment_id) = usage_identity(auth_context.as_ref());
+        emit_usage_event(
+            usage_emitter,
+            WebSocketUsageEvent::SnapshotSent {
+                client_id: client_id.to_string(),
+                deployment_id,
+                metering_key,
+                subject,
+                view_id: view_id.to_string(),
+                rows: batch.rows,
+                messages: 1,
+                bytes: payload_bytes as u64,
+            },
+        );
     }
 
     debug!(
@@ -1258,7 +2230,277 @@ async fn send_snapshot_batches(
         batch_num, total, view_id, client_id
     );
 
-    Ok(())
+    Ok(())
+}
+
+/// An initial snapshot taken for a new subscription
+struct InitialSnapshot {
+    entities: Vec<SnapshotEntity>,
+    /// Entities in the cache the snapshot was taken from
+    cache_entries: usize,
+    /// When assembling the snapshot started
+    started: Instant,
+}
+
+/// Measures a subscription's initial load for clients that subscribed with
+/// `diagnostics: true`.
+struct LoadDiagnostics {
+    received_at: Instant,
+    diagnostics: SubscriptionDiagnostics,
+}
+
+impl LoadDiagnostics {
+    fn start(subscription: &Subscription, received_at: Instant) -> Option<Self> {
+        subscription.diagnostics.unwrap_or(false).then(|| Self {
+            received_at,
+            diagnostics: SubscriptionDiagnostics::default(),
+        })
+    }
+
+    /// Record a snapshot once it's encoded into `batches`.
+    ///
+    /// Compression happens per frame as batches are sent, so the compressed
+    /// size is measured up front on the unstamped payloads.
+    fn record_snapshot(
+        &mut self,
+        cache_entries: usize,
+        started: Instant,
+        batches: &[SnapshotBatch],
+    ) {
+        self.diagnostics.cache_entries = cache_entries;
+        self.diagnostics.snapshot_micros = started.elapsed().as_micros() as u64;
+        for batch in batches {
+            self.diagnostics.snapshot_bytes += batch.payload.len() as u64;
+            self.diagnostics.compressed_bytes +=
+                maybe_compress(&batch.payload).as_bytes().len() as u64;
+        }
+    }
+
+    fn finish(mut self) -> SubscriptionDiagnostics {
+        self.diagnostics.queue_wait_micros = self.received_at.elapsed().as_micros() as u64;
+        self.diagnostics
+    }
+}
+
+/// Send the subscription ack, followed by the initial snapshot if there is one.
+///
+/// The snapshot is assembled and encoded before the ack goes out, so that the
+/// ack can carry diagnostics about it.
+async fn send_ack_and_snapshot(
+    ctx: &SubscriptionContext<'_>,
+    sender: &SubscriptionSender,
+    view_id: &str,
+    view_spec: &ViewSpec,
+    snapshot: Option<InitialSnapshot>,
+    mut load: Option<LoadDiagnostics>,
+) -> Result<()> {
+    let batch_config = ctx.client_manager.snapshot_batch_config(ctx.entity_cache);
+    let snapshot = snapshot.map(|snapshot| {
+        let rows = snapshot.entities.len();
+        let batches =
+            encode_snapshot_batches(snapshot.entities, view_spec.mode, view_id, &batch_config);
+        if let Some(load) = &mut load {
+            load.record_snapshot(snapshot.cache_entries, snapshot.started, &batches);
+        }
+        (rows, batches)
+    });
+
+    send_subscribed_frame(
+        sender,
+        view_id,
+        view_spec,
+        ctx.entity_cache,
+        ctx.client_manager,
+        ctx.usage_emitter,
+        load.map(LoadDiagnostics::finish),
+        None,
+    )
+    .await?;
+
+    if let Some((rows, batches)) = snapshot {
+        send_encoded_snapshot(ctx, sender, view_id, rows, batches).await?;
+    }
+
+    Ok(())
+}
+
+/// Send the ack of a subscription whose snapshot waits in the snapshot queue
+async fn send_pending_ack(
+    ctx: &SubscriptionContext<'_>,
+    sender: &SubscriptionSender,
+    view_id: &str,
+    view_spec: &ViewSpec,
+    load: Option<LoadDiagnostics>,
+) -> Result<()> {
+    send_subscribed_frame(
+        sender,
+        view_id,
+        view_spec,
+        ctx.entity_cache,
+        ctx.client_manager,
+        ctx.usage_emitter,
+        load.map(LoadDiagnostics::finish),
+        Some(SubscriptionStatus::SnapshotPending),
+    )
+    .await
+}
+
+async fn send_encoded_snapshot(
+    ctx: &SubscriptionContext<'_>,
+    sender: &SubscriptionSender,
+    view_id: &str,
+    rows: usize,
+    batches: Vec<SnapshotBatch>,
+) -> Result<()> {
+    if rows > 0 {
+        enforce_snapshot_limit(ctx, rows)?;
+    }
+    send_snapshot_batches(
+        sender,
+        batches,
+        view_id,
+        ctx.client_manager,
+        ctx.usage_emitter,
+        #[cfg(feature = "otel")]
+        ctx.metrics.as_ref(),
+    )
+    .await
+}
+
+/// Assemble the initial snapshot of a `List` or `Append` subscription
+async fn list_snapshot(
+    entity_cache: &EntityCache,
+    subscription: &Subscription,
+    sender: &SubscriptionSender,
+) -> InitialSnapshot {
+    let view_id = &subscription.view;
+    let started = Instant::now();
+
+    // Determine which entities to send based on cursor
+    let mut snapshots = if let Some(ref cursor) = subscription.after {
+        entity_cache
+            .get_after(view_id, cursor, subscription.snapshot_limit)
+            .await
+    } else {
+        entity_cache.get_all(view_id).await
+    };
+
+    if sender.is_filtered() {
+        snapshots.retain(|(_, data)| sender.filter_matches(data));
+    }
+
+    // Sort by _seq descending only when there is no cursor (to get most-recent N from full cache)
+    if let Some(limit) = subscription.snapshot_limit {
+        if subscription.after.is_none() {
+            snapshots.sort_by(|a, b| {
+                let sa = a.1.get("_seq").and_then(|s| s.as_str()).unwrap_or("");
+                let sb = b.1.get("_seq").and_then(|s| s.as_str()).unwrap_or("");
+                cmp_seq(sb, sa) // descending: most-recent N
+            });
+            snapshots.truncate(limit);
+        }
+    }
+
+    let snapshot_entities: Vec<SnapshotEntity> = snapshots
+        .into_iter()
+        .filter(|(key, _)| subscription.matches_key(key))
+        .map(|(key, mut data)| {
+            if let Some(seq) = data.get("_seq").and_then(|seq| seq.as_str()) {
+                sender.advance_cursor(seq);
+            }
+            sender.encode_numbers(&mut data);
+            sender.note_snapshot_entity(&key, &data);
+            SnapshotEntity { key, data }
+        })
+        .collect();
+
+    InitialSnapshot {
+        entities: snapshot_entities,
+        cache_entries: entity_cache.len(view_id).await,
+        started,
+    }
+}
+
+/// Drop a subscription whose queued snapshot failed, its ack having already
+/// gone out
+async fn abandon_pending_snapshot(
+    client_id: Uuid,
+    client_manager: &ClientManager,
+    subscription: &Subscription,
+    cancel_token: &CancellationToken,
+    err: anyhow::Error,
+) {
+    if cancel_token.is_cancelled() {
+        return;
+    }
+    warn!(
+        "Queued snapshot failed for client {} on {}: {}",
+        client_id, subscription.view, err
+    );
+    if let Some(deny) = auth_deny_from_subscription_error(&err.to_string()) {
+        send_socket_issue(client_id, client_manager, &deny, false).await;
+    }
+    let _ = client_manager
+        .remove_client_subscription(client_id, &subscription.sub_key())
+        .await;
+}
+
+/// A list snapshot waiting for its turn in the snapshot queue.
+///
+/// The subscription's task waits for the turn, then sends the snapshot and
+/// any history before the updates that arrived meanwhile.
+struct PendingSnapshot {
+    queued: QueuedSnapshot,
+    ctx: OwnedSubscriptionContext,
+    subscription: Subscription,
+    mode: Mode,
+    history: Option<Vec<HistoryItem>>,
+}
+
+impl PendingSnapshot {
+    /// Wait for the snapshot's turn and send it. Updates the subscription
+    /// receives from `rx` meanwhile are held back in `held`, for the caller
+    /// to send once the snapshot is out.
+    async fn deliver(
+        self,
+        sender: &SubscriptionSender,
+        rx: &mut broadcast::Receiver<Arc<BusMessage>>,
+        held: &mut Vec<Arc<BusMessage>>,
+    ) -> Result<()> {
+        let turn = self.queued.wait();
+        tokio::pin!(turn);
+        let _permit = loop {
+            tokio::select! {
+                permit = &mut turn => break permit,
+                result = rx.recv() => match result {
+                    Ok(envelope) => {
+                        if self.subscription.matches(&envelope.entity, &envelope.key) {
+                            held.push(envelope);
+                        }
+                    }
+                    // The snapshot isn't taken yet, so it covers whatever was missed
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return Err(anyhow::anyhow!("Bus for {} closed", self.subscription.view));
+                    }
+                },
+            }
+        };
+
+        let ctx = self.ctx.borrow();
+        let view_id = &self.subscription.view;
+        let snapshot = list_snapshot(ctx.entity_cache, &self.subscription, sender).await;
+        let rows = snapshot.entities.len();
+        let batch_config = ctx.client_manager.snapshot_batch_config(ctx.entity_cache);
+        let batches = encode_snapshot_batches(snapshot.entities, self.mode, view_id, &batch_config);
+        send_encoded_snapshot(&ctx, sender, view_id, rows, batches).await?;
+
+        if let Some(items) = self.history {
+            send_history_frame(&ctx, sender, view_id, self.mode, items).await?;
+        }
+
+        Ok(())
+    }
 }
 
 fn extract_sort_config(view_spec: &ViewSpec) -> Option<SortConfig> {
@@ -1282,23 +2524,39 @@ fn extract_sort_config(view_spec: &ViewSpec) -> Option<SortConfig> {
     None
 }
 
-fn send_subscribed_frame(
-    client_id: Uuid,
+#[allow(clippy::too_many_arguments)]
+async fn send_subscribed_frame(
+    sender: &SubscriptionSender,
     view_id: &str,
     view_spec: &ViewSpec,
+    entity_cache: &EntityCache,
     client_manager: &ClientManager,
     usage_emitter: &Option<Arc<dyn WebSocketUsageEmitter>>,
+    diagnostics: Option<SubscriptionDiagnostics>,
+    status: Option<SubscriptionStatus>,
 ) -> Result<()> {
     let sort_config = extract_sort_config(view_spec);
-    let subscribed_frame = SubscribedFrame::new(view_id.to_string(), view_spec.mode, sort_config);
+    // Clients joining a list that is already evicting learn about it up front
+    let retention = if view_spec.mode == Mode::List {
+        entity_cache.retention(view_id).await
+    } else {
+        None
+    };
+    let subscribed_frame = SubscribedFrame::new(view_id.to_string(), view_spec.mode, sort_config)
+        .with_retention(retention)
+        .with_diagnostics(diagnostics)
+        .with_status(status)
+        .with_fields(sender.field_mask().map(Vec::from))
+        .with_big_numbers(sender.negotiated_big_numbers())
+        .with_deprecation(sender.deprecation().map(ViewDeprecation::notice));
 
     let json_payload = serde_json::to_vec(&subscribed_frame)?;
     let payload_bytes = json_payload.len() as u64;
-    let payload = Arc::new(Bytes::from(json_payload));
-    client_manager
-        .send_to_client(client_id, payload)
+    sender
+        .send(&json_payload)
         .map_err(|e| anyhow::anyhow!("Failed to send subscribed frame: {:?}", e))?;
 
+    let client_id = sender.client_id();
     let auth_context = client_manager.get_auth_context(client_id);
     let (metering_key, subject, _, deployment_id) = usage_identity(auth_context.as_ref());
     emit_usage_event(
@@ -1317,6 +2575,273 @@ fn send_subscribed_frame(
     Ok(())
 }
 
+/// Refuse a subscription to a view quarantined after its evaluation kept
+/// failing, telling the client why
+async fn reject_quarantined_view(ctx: &SubscriptionContext<'_>, view_id: &str) -> Result<()> {
+    let Some(quarantine) = ctx.view_index.load().quarantined_views().get(view_id) else {
+        return Ok(());
+    };
+    send_view_unavailable(ctx.client_manager, ctx.client_id, view_id, &quarantine).await;
+    Err(anyhow::anyhow!(
+        "View {} is quarantined: {}",
+        view_id,
+        quarantine.error
+    ))
+}
+
+async fn send_view_unavailable(
+    client_manager: &ClientManager,
+    client_id: Uuid,
+    view_id: &str,
+    quarantine: &Quarantine,
+) {
+    let issue = SocketIssueMessage {
+        kind: "error".to_string(),
+        error: "View unavailable".to_string(),
+        message: format!(
+            "View '{}' was taken out of service after its evaluation failed: {}",
+            view_id, quarantine.error
+        ),
+        code: "view_unavailable".to_string(),
+        retryable: true,
+        retry_after: None,
+        retry_after_ms: None,
+        suggested_action: None,
+        docs_url: None,
+        fatal: false,
+    };
+    if let Ok(json) = serde_json::to_string(&issue) {
+        let _ = client_manager.send_text_to_client(client_id, json).await;
+    }
+}
+
+/// Point a subscription to a deprecated view at its replacement, whose
+/// frames the sender labels with the deprecated id. Past the sunset the
+/// client is told the view is gone and the subscription fails.
+async fn resolve_deprecated_view(
+    ctx: &SubscriptionContext<'_>,
+    mut subscription: Subscription,
+    sender: SubscriptionSender,
+) -> Result<(Subscription, SubscriptionSender)> {
+    let Some(deprecation) = ctx
+        .view_index
+        .load()
+        .deprecation(&subscription.view)
+        .cloned()
+    else {
+        return Ok((subscription, sender));
+    };
+
+    if deprecation.is_sunset(ctx.client_manager.now()) {
+        let issue = SocketIssueMessage {
+            kind: "error".to_string(),
+            error: "View sunset".to_string(),
+            message: format!(
+                "View '{}' has been removed, subscribe to '{}' instead",
+                deprecation.id, deprecation.replacement
+            ),
+            code: "view-sunset".to_string(),
+            retryable: false,
+            retry_after: None,
+            retry_after_ms: None,
+            suggested_action: Some(format!("Subscribe to '{}'", deprecation.replacement)),
+            docs_url: None,
+            fatal: false,
+        };
+        if let Ok(json) = serde_json::to_string(&issue) {
+            let _ = ctx
+                .client_manager
+                .send_text_to_client(ctx.client_id, json)
+                .await;
+        }
+        return Err(anyhow::anyhow!(
+            "View {} was sunset in favour of {}",
+            deprecation.id,
+            deprecation.replacement
+        ));
+    }
+
+    subscription.view = deprecation.replacement.clone();
+    Ok((subscription, sender.with_deprecation(deprecation)))
+}
+
+/// Restart a subscription from a fresh snapshot.
+///
+/// Clients ask for this when they find a gap in the subscription's frame
+/// sequence, or to refresh what they hold. The running subscription is
+/// cancelled and its sequence starts over, with the first frame marked by
+/// `start`. Cursors are ignored, since the client can no longer trust what
+/// it already has. Frames of the cancelled subscription can't be sent once
+/// the new sequence has started, so none older than the snapshot follow it.
+async fn resync_subscription(
+    ctx: &SubscriptionContext<'_>,
+    subscription: &Subscription,
+    start: SequenceStart,
+) -> Result<()> {
+    let received_at = Instant::now();
+    let sub_key = subscription.sub_key();
+    let cancel_token = CancellationToken::new();
+    ctx.client_manager
+        .add_client_subscription(ctx.client_id, sub_key.clone(), cancel_token.clone())
+        .await;
+    let sender = ctx
+        .client_manager
+        .subscription_sender(ctx.client_id, &sub_key, start)
+        .ok_or_else(|| anyhow::anyhow!("Client {} is gone", ctx.client_id))?;
+
+    let subscription = Subscription {
+        with_snapshot: Some(true),
+        after: None,
+        ..subscription.clone()
+    };
+    attach_client_to_bus(ctx, subscription, sender, cancel_token, received_at).await
+}
+
+/// Restore the subscriptions of the session kept under `token`, apart from
+/// any the client has already made again. Returns those attached, by sub key.
+///
+/// See the [`session`](crate::websocket::session) module.
+async fn resume_session(
+    ctx: &SubscriptionContext<'_>,
+    token: &str,
+    active_subscriptions: &HashMap<String, Subscription>,
+) -> Vec<(String, Subscription)> {
+    let session = ctx.client_manager.resume_session(ctx.client_id, token);
+    let resumed = session.is_some();
+    let retained: Vec<RetainedSubscription> = session
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|retained| !active_subscriptions.contains_key(&retained.subscription.sub_key()))
+        .collect();
+
+    let message = SessionMessage::new(
+        resumed,
+        retained
+            .iter()
+            .map(|retained| retained.subscription.sub_key())
+            .collect(),
+    );
+    let message = serde_json::to_string(&message).expect("session message should serialize");
+    if ctx
+        .client_manager
+        .send_text_to_client(ctx.client_id, message)
+        .await
+        .is_err()
+    {
+        return Vec::new();
+    }
+    if !resumed {
+        debug!(
+            "Client {} presented an unknown or expired session",
+            ctx.client_id
+        );
+        return Vec::new();
+    }
+    info!(
+        "Client {} resumed a session with {} subscriptions",
+        ctx.client_id,
+        retained.len()
+    );
+
+    let mut restored = Vec::new();
+    for retained in retained {
+        let sub_key = retained.subscription.sub_key();
+        if let Err(deny) = ctx
+            .client_manager
+            .check_subscription_allowed(ctx.client_id)
+            .await
+        {
+            warn!(
+                "Resumed subscription rejected for client {}: {}",
+                ctx.client_id, deny.reason
+            );
+            send_socket_issue(ctx.client_id, ctx.client_manager, &deny, false).await;
+            break;
+        }
+
+        ctx.client_manager
+            .update_subscription(ctx.client_id, retained.subscription.clone());
+        let cancel_token = CancellationToken::new();
+        ctx.client_manager
+            .add_client_subscription(ctx.client_id, sub_key.clone(), cancel_token.clone())
+            .await;
+        let Some(sender) = ctx
+            .client_manager
+            .resumed_subscription_sender(ctx.client_id, &retained)
+        else {
+            break;
+        };
+
+        let subscription = resumed_subscription(ctx, &retained).await;
+        match attach_client_to_bus(ctx, subscription, sender, cancel_token, Instant::now()).await {
+            Ok(()) => restored.push((sub_key, retained.subscription)),
+            Err(err) => {
+                warn!(
+                    "Resumed subscription failed for client {} on {}: {}",
+                    ctx.client_id, sub_key, err
+                );
+                let _ = ctx
+                    .client_manager
+                    .remove_client_subscription(ctx.client_id, &sub_key)
+                    .await;
+            }
+        }
+    }
+    restored
+}
+
+/// A subscription restored from a session, resuming after where it left off
+/// when its view can send just what changed since, and that is no more than
+/// the session's delta allows. Otherwise it starts over from a snapshot.
+async fn resumed_subscription(
+    ctx: &SubscriptionContext<'_>,
+    retained: &RetainedSubscription,
+) -> Subscription {
+    let mut subscription = Subscription {
+        after: None,
+        ..retained.subscription.clone()
+    };
+    let Some(cursor) = &retained.cursor else {
+        return subscription;
+    };
+    let Some(view_spec) = ctx.view_index.load().get_view(&subscription.view).cloned() else {
+        return subscription;
+    };
+
+    let covered = match view_spec.mode {
+        Mode::State => false,
+        _ if view_spec.is_derived() => false,
+        Mode::Append if view_spec.delivery.history > 0 => {
+            ctx.bus_manager
+                .append_log_covers(&view_spec.id, subscription.key.as_deref(), cursor)
+                .await
+        }
+        Mode::List | Mode::Append => true,
+    };
+    if !covered {
+        return subscription;
+    }
+
+    let max_delta = ctx
+        .client_manager
+        .sessions()
+        .map_or(0, |sessions| sessions.config().max_delta);
+    let max_delta = subscription
+        .snapshot_limit
+        .map_or(max_delta, |limit| limit.min(max_delta));
+    if ctx.entity_cache.count_after(&view_spec.id, cursor).await > max_delta {
+        debug!(
+            "Client {} resumed {} with a snapshot, too much changed since {}",
+            ctx.client_id, view_spec.id, cursor
+        );
+        return subscription;
+    }
+
+    subscription.after = Some(cursor.clone());
+    subscription.with_snapshot = Some(true);
+    subscription
+}
+
 fn enforce_snapshot_limit(ctx: &SubscriptionContext<'_>, rows: usize) -> Result<()> {
     let requested_rows = u32::try_from(rows).unwrap_or(u32::MAX);
     ctx.client_manager
@@ -1324,28 +2849,140 @@ fn enforce_snapshot_limit(ctx: &SubscriptionContext<'_>, rows: usize) -> Result<
         .map_err(|deny| anyhow::anyhow!(deny.reason))
 }
 
+/// Subscribe to a list bus, snapshotting the view's append history when the
+/// client asked for it, or didn't say and the view replays on subscribe. The
+/// snapshot and receiver never share an item.
+///
+/// An `Append` subscription resuming with `after` gets every logged item
+/// published after that `seq` instead of the most recent ones.
+async fn subscribe_list_bus(
+    ctx: &SubscriptionContext<'_>,
+    subscription: &Subscription,
+    view_spec: &ViewSpec,
+) -> (
+    broadcast::Receiver<Arc<BusMessage>>,
+    Option<Vec<HistoryItem>>,
+) {
+    let view_id = &subscription.view;
+    let key = subscription.key.as_deref();
+    let logged = match view_spec.mode {
+        Mode::Append => view_spec.delivery.history,
+        _ => 0,
+    };
+
+    if let (Some(after), true) = (&subscription.after, logged > 0) {
+        let (rx, read) = ctx
+            .bus_manager
+            .subscribe_list_after(view_id, key, after, logged)
+            .await;
+        if read.missed {
+            debug!(
+                "Client {} resumed {} after {}, but items since were evicted",
+                ctx.client_id, view_id, after
+            );
+        }
+        return (rx, Some(read.items()));
+    }
+
+    let history = match subscription.history {
+        Some(history) => history.min(logged),
+        None if view_spec.delivery.replay_on_subscribe => logged,
+        None => 0,
+    };
+    if history == 0 {
+        return (ctx.bus_manager.get_or_create_list_bus(view_id).await, None);
+    }
+
+    let (rx, items) = ctx
+        .bus_manager
+        .subscribe_list_with_history(view_id, key, history)
+        .await;
+    (rx, Some(items))
+}
+
+/// Fetch a `State` entity the cache has never seen and wait for the
+/// projector to store it. `rx` is the key's state bus, which the projector
+/// publishes to after updating the cache.
+async fn backfill_state_entity(
+    ctx: &SubscriptionContext<'_>,
+    view_spec: &ViewSpec,
+    key: &str,
+    rx: &mut tokio::sync::watch::Receiver<Arc<BusMessage>>,
+) -> Option<serde_json::Value> {
+    let backfill = ctx.backfill?;
+    if key.is_empty() || backfill.backfill(&view_spec.export, key).await != BackfillOutcome::Applied
+    {
+        return None;
+    }
+    let _ = tokio::time::timeout(backfill.config().snapshot_wait, rx.changed()).await;
+    ctx.entity_cache.get(&view_spec.id, key).await
+}
+
+async fn send_history_frame(
+    ctx: &SubscriptionContext<'_>,
+    sender: &SubscriptionSender,
+    view_id: &str,
+    mode: Mode,
+    items: Vec<HistoryItem>,
+) -> Result<()> {
+    enforce_snapshot_limit(ctx, items.len())?;
+    for seq in items.iter().filter_map(|item| item.seq.as_deref()) {
+        sender.advance_cursor(seq);
+    }
+    let rows = items.len() as u32;
+    let history_frame = HistoryFrame::new(mode, view_id, items);
+
+    let json_payload = serde_json::to_vec(&history_frame)?;
+    let payload_bytes = sender
+        .send_compressed(&json_payload)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to send history frame: {:?}", e))?
+        as u64;
+    #[cfg(feature = "otel")]
+    if let Some(ref m) = ctx.metrics {
+        m.record_ws_message_sent();
+    }
+
+    let auth_context = ctx.client_manager.get_auth_context(ctx.client_id);
+    let (metering_key, subject, _, deployment_id) = usage_identity(auth_context.as_ref());
+    emit_usage_event(
+        ctx.usage_emitter,
+        WebSocketUsageEvent::SnapshotSent {
+            client_id: ctx.client_id.to_string(),
+            deployment_id,
+            metering_key,
+            subject,
+            view_id: view_id.to_string(),
+            rows,
+            messages: 1,
+            bytes: payload_bytes,
+        },
+    );
+
+    Ok(())
+}
+
 #[cfg(feature = "otel")]
 async fn attach_client_to_bus(
     ctx: &SubscriptionContext<'_>,
     subscription: Subscription,
+    sender: SubscriptionSender,
     cancel_token: CancellationToken,
+    received_at: Instant,
 ) -> Result<()> {
+    let (subscription, sender) = resolve_deprecated_view(ctx, subscription, sender).await?;
+    reject_quarantined_view(ctx, &subscription.view).await?;
     let view_id = &subscription.view;
+    let sender = sender.with_fields(view_id, subscription.fields.as_deref());
 
-    let view_spec = match ctx.view_index.get_view(view_id) {
+    let view_spec = match ctx.view_index.load().get_view(view_id) {
         Some(spec) => spec.clone(),
         None => {
             return Err(anyhow::anyhow!("Unknown view ID: {}", view_id));
         }
     };
-
-    send_subscribed_frame(
-        ctx.client_id,
-        view_id,
-        &view_spec,
-        ctx.client_manager,
-        ctx.usage_emitter,
-    )?;
+    let sender = sender.with_big_numbers(&view_spec.export, subscription.big_numbers);
+    let load = LoadDiagnostics::start(&subscription, received_at);
 
     let is_derived_with_sort = view_spec.is_derived()
         && view_spec
@@ -1355,8 +2992,15 @@ async fn attach_client_to_bus(
             .unwrap_or(false);
 
     if is_derived_with_sort {
-        return attach_derived_view_subscription_otel(ctx, subscription, view_spec, cancel_token)
-            .await;
+        return attach_derived_view_subscription_otel(
+            ctx,
+            subscription,
+            view_spec,
+            sender,
+            cancel_token,
+            load,
+        )
+        .await;
     }
 
     match view_spec.mode {
@@ -1364,40 +3008,46 @@ async fn attach_client_to_bus(
             let key = subscription.key.as_deref().unwrap_or("");
 
             let mut rx = ctx.bus_manager.get_or_create_state_bus(view_id, key).await;
+            let delivery = subscription.delivery();
+            let full_state_interest = (delivery == UpdateDelivery::FullState)
+                .then(|| ctx.bus_manager.register_full_state(view_id));
 
             // Check if we should send snapshot (defaults to true for backward compatibility)
             let should_send_snapshot = subscription.with_snapshot.unwrap_or(true);
 
+            let started = Instant::now();
+            let mut cached_entity = if should_send_snapshot {
+                ctx.entity_cache.get(view_id, key).await
+            } else {
+                None
+            };
+            if cached_entity.is_none() && should_send_snapshot {
+                cached_entity = backfill_state_entity(ctx, &view_spec, key, &mut rx).await;
+            }
+            let sent_snapshot = cached_entity.is_some();
+            let snapshot = match cached_entity {
+                Some(mut data) => {
+                    sender.encode_numbers(&mut data);
+                    Some(InitialSnapshot {
+                        entities: vec![SnapshotEntity {
+                            key: key.to_string(),
+                            data,
+                        }],
+                        cache_entries: ctx.entity_cache.len(view_id).await,
+                        started,
+                    })
+                }
+                None => None,
+            };
+            send_ack_and_snapshot(ctx, &sender, view_id, &view_spec, snapshot, load).await?;
+
             if should_send_snapshot {
-                if let Some(mut cached_entity) = ctx.entity_cache.get(view_id, key).await {
-                    transform_large_u64_to_strings(&mut cached_entity);
-                    let snapshot_entities = vec![SnapshotEntity {
-                        key: key.to_string(),
-                        data: cached_entity,
-                    }];
-                    enforce_snapshot_limit(ctx, snapshot_entities.len())?;
-                    let batch_config = ctx.entity_cache.snapshot_config();
-                    send_snapshot_batches(
-                        ctx.client_id,
-                        &snapshot_entities,
-                        view_spec.mode,
-                        view_id,
-                        ctx.client_manager,
-                        ctx.usage_emitter,
-                        &batch_config,
-                        #[cfg(feature = "otel")]
-                        ctx.metrics.as_ref(),
-                    )
-                    .await?;
+                if sent_snapshot {
                     rx.borrow_and_update();
-                } else if !rx.borrow().is_empty() {
-                    let data = rx.borrow_and_update().clone();
+                } else if !rx.borrow().payload.is_empty() {
+                    let data = rx.borrow_and_update().payload_for(delivery).clone();
                     let data_len = data.len();
-                    if ctx
-                        .client_manager
-                        .send_to_client(ctx.client_id, data)
-                        .is_ok()
-                    {
+                    if sender.send(&data).is_ok() {
                         emit_update_sent_for_client(
                             ctx.usage_emitter,
                             ctx.client_manager,
@@ -1424,6 +3074,7 @@ async fn attach_client_to_bus(
             let key_clone = key.to_string();
             tokio::spawn(
                 async move {
+                    let _full_state_interest = full_state_interest;
                     loop {
                         tokio::select! {
                             _ = cancel_token.cancelled() => {
@@ -1434,9 +3085,9 @@ async fn attach_client_to_bus(
                                 if result.is_err() {
                                     break;
                                 }
-                                let data = rx.borrow().clone();
+                                let data = rx.borrow().payload_for(delivery).clone();
                                 let data_len = data.len();
-                                if client_mgr.send_to_client(client_id, data).is_err() {
+                                if sender.send(&data).is_err() {
                                     break;
                                 }
                                 if let Some(ref m) = metrics_clone {
@@ -1457,64 +3108,61 @@ async fn attach_client_to_bus(
             );
         }
         Mode::List | Mode::Append => {
-            let mut rx = ctx.bus_manager.get_or_create_list_bus(view_id).await;
+            let sender = match view_spec.mode {
+                Mode::List => sender
+                    .with_filter(subscription.filter.as_deref().cloned())
+                    .with_delta(subscription.delta.unwrap_or(false)),
+                _ => sender,
+            };
+            // Filters check updates against the whole entity, and deltas
+            // send it to start off entities the client doesn't have
+            let delivery = subscription.delivery();
+            let full_state_interest = (delivery == UpdateDelivery::FullState
+                || sender.is_filtered()
+                || sender.is_delta())
+            .then(|| ctx.bus_manager.register_full_state(view_id));
+            let (mut rx, history) = subscribe_list_bus(ctx, &subscription, &view_spec).await;
 
             // Check if we should send snapshot (defaults to true for backward compatibility)
             let should_send_snapshot = subscription.with_snapshot.unwrap_or(true);
 
-            if should_send_snapshot {
-                // Determine which entities to send based on cursor
-                let mut snapshots = if let Some(ref cursor) = subscription.after {
-                    ctx.entity_cache
-                        .get_after(view_id, cursor, subscription.snapshot_limit)
-                        .await
-                } else {
-                    ctx.entity_cache.get_all(view_id).await
-                };
-
-                // Sort by _seq descending only when there is no cursor (to get most-recent N from full cache)
-                if let Some(limit) = subscription.snapshot_limit {
-                    if subscription.after.is_none() {
-                        snapshots.sort_by(|a, b| {
-                            let sa = a.1.get("_seq").and_then(|s| s.as_str()).unwrap_or("");
-                            let sb = b.1.get("_seq").and_then(|s| s.as_str()).unwrap_or("");
-                            cmp_seq(sb, sa) // descending: most-recent N
-                        });
-                        snapshots.truncate(limit);
-                    }
+            let turn = should_send_snapshot.then(|| ctx.client_manager.snapshot_queue().admit());
+            let pending = match turn {
+                Some(None) => {
+                    return Err(anyhow::anyhow!(
+                        "Snapshot queue is full, retry the subscription later"
+                    ));
                 }
-
-                let snapshot_entities: Vec<SnapshotEntity> = snapshots
-                    .into_iter()
-                    .filter(|(key, _)| subscription.matches_key(key))
-                    .map(|(key, mut data)| {
-                        transform_large_u64_to_strings(&mut data);
-                        SnapshotEntity { key, data }
+                Some(Some(SnapshotTurn::Queued(queued))) => {
+                    send_pending_ack(ctx, &sender, view_id, &view_spec, load).await?;
+                    Some(PendingSnapshot {
+                        queued,
+                        ctx: ctx.to_owned(),
+                        subscription: subscription.clone(),
+                        mode: view_spec.mode,
+                        history,
                     })
-                    .collect();
-
-                if !snapshot_entities.is_empty() {
-                    enforce_snapshot_limit(ctx, snapshot_entities.len())?;
-                    let batch_config = ctx.entity_cache.snapshot_config();
-                    send_snapshot_batches(
-                        ctx.client_id,
-                        &snapshot_entities,
-                        view_spec.mode,
-                        view_id,
-                        ctx.client_manager,
-                        ctx.usage_emitter,
-                        &batch_config,
-                        #[cfg(feature = "otel")]
-                        ctx.metrics.as_ref(),
-                    )
-                    .await?;
                 }
-            } else {
-                info!(
-                    "Client {} subscribed to {} without snapshot",
-                    ctx.client_id, view_id
-                );
-            }
+                _permit => {
+                    // Any turn taken is held until the snapshot is sent
+                    let snapshot = if should_send_snapshot {
+                        Some(list_snapshot(ctx.entity_cache, &subscription, &sender).await)
+                    } else {
+                        info!(
+                            "Client {} subscribed to {} without snapshot",
+                            ctx.client_id, view_id
+                        );
+                        None
+                    };
+                    send_ack_and_snapshot(ctx, &sender, view_id, &view_spec, snapshot, load)
+                        .await?;
+
+                    if let Some(items) = history {
+                        send_history_frame(ctx, &sender, view_id, view_spec.mode, items).await?;
+                    }
+                    None
+                }
+            };
 
             let client_id = ctx.client_id;
             let client_mgr = ctx.client_manager.clone();
@@ -1526,6 +3174,44 @@ async fn attach_client_to_bus(
             let mode = view_spec.mode;
             tokio::spawn(
                 async move {
+                    let _full_state_interest = full_state_interest;
+                    let forward = |envelope: &BusMessage| {
+                        let Some(payload) = sender.filter_update(envelope, delivery) else {
+                            return true;
+                        };
+                        if sender.send(&payload).is_err() {
+                            return false;
+                        }
+                        if let Some(seq) = &envelope.seq {
+                            sender.advance_cursor(seq);
+                        }
+                        if let Some(ref m) = metrics_clone {
+                            m.record_ws_message_sent();
+                        }
+                        emit_update_sent_for_client(
+                            &usage_emitter,
+                            &client_mgr,
+                            client_id,
+                            &view_id_clone,
+                            payload.len(),
+                        );
+                        true
+                    };
+                    if let Some(pending) = pending {
+                        let mut held = Vec::new();
+                        tokio::select! {
+                            _ = cancel_token.cancelled() => return,
+                            result = pending.deliver(&sender, &mut rx, &mut held) => {
+                                if let Err(err) = result {
+                                    abandon_pending_snapshot(client_id, &client_mgr, &sub, &cancel_token, err).await;
+                                    return;
+                                }
+                            }
+                        }
+                        if !held.iter().all(|envelope| forward(envelope)) {
+                            return;
+                        }
+                    }
                     loop {
                         tokio::select! {
                             _ = cancel_token.cancelled() => {
@@ -1535,32 +3221,24 @@ async fn attach_client_to_bus(
                             result = rx.recv() => {
                                 match result {
                                     Ok(envelope) => {
-                                        if sub.matches(&envelope.entity, &envelope.key) {
-                                            if client_mgr
-                                                .send_to_client(client_id, envelope.payload.clone())
-                                                .is_err()
-                                            {
-                                                break;
-                                            }
-                                            if let Some(ref m) = metrics_clone {
-                                                m.record_ws_message_sent();
-                                            }
-                                            emit_update_sent_for_client(
-                                                &usage_emitter,
-                                                &client_mgr,
-                                                client_id,
-                                                &view_id_clone,
-                                                envelope.payload.len(),
-                                            );
+                                        if sub.matches(&envelope.entity, &envelope.key) && !forward(&envelope) {
+                                            break;
                                         }
                                     }
-                                    Err(_) => break,
+                                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                                        // The client sees the gap in its frame sequence and resyncs
+                                        warn!("List subscription for client {} lagged by {} updates", client_id, missed);
+                                        sender.skip();
+                                    }
+                                    Err(broadcast::error::RecvError::Closed) => break,
                                 }
                             }
                         }
                     }
                 }
-                .instrument(info_span!("ws.subscribe.list", %client_id, view = %view_id_span, mode = ?mode)),
+                .instrument(
+                    info_span!("ws.subscribe.list", %client_id, view = %view_id_span, mode = ?mode),
+                ),
             );
         }
     }
@@ -1578,7 +3256,9 @@ async fn attach_derived_view_subscription_otel(
     ctx: &SubscriptionContext<'_>,
     subscription: Subscription,
     view_spec: ViewSpec,
+    sender: SubscriptionSender,
     cancel_token: CancellationToken,
+    load: Option<LoadDiagnostics>,
 ) -> Result<()> {
     let view_id = &subscription.view;
     let pipeline_limit = view_spec
@@ -1600,42 +3280,33 @@ async fn attach_derived_view_subscription_otel(
         }
     };
 
-    let sorted_caches = ctx.view_index.sorted_caches();
-    let initial_window: Vec<(String, serde_json::Value)> = {
+    let sorted_caches = ctx.view_index.load().sorted_caches();
+    let started = Instant::now();
+    let (initial_window, cache_entries): (Vec<(String, serde_json::Value)>, usize) = {
         let mut caches = sorted_caches.write().await;
         if let Some(cache) = caches.get_mut(view_id) {
-            cache.get_window(skip, take)
+            (cache.get_window(skip, take), cache.len())
         } else {
             warn!("No sorted cache for derived view {}", view_id);
-            vec![]
+            (vec![], 0)
         }
     };
 
     let initial_keys: HashSet<String> = initial_window.iter().map(|(k, _)| k.clone()).collect();
 
-    if !initial_window.is_empty() {
-        let snapshot_entities: Vec<SnapshotEntity> = initial_window
-            .into_iter()
-            .map(|(key, mut data)| {
-                transform_large_u64_to_strings(&mut data);
-                SnapshotEntity { key, data }
-            })
-            .collect();
-
-        enforce_snapshot_limit(ctx, snapshot_entities.len())?;
-        let batch_config = ctx.entity_cache.snapshot_config();
-        send_snapshot_batches(
-            ctx.client_id,
-            &snapshot_entities,
-            view_spec.mode,
-            view_id,
-            ctx.client_manager,
-            ctx.usage_emitter,
-            &batch_config,
-            ctx.metrics.as_ref(),
-        )
-        .await?;
-    }
+    let snapshot_entities: Vec<SnapshotEntity> = initial_window
+        .into_iter()
+        .map(|(key, mut data)| {
+            sender.encode_numbers(&mut data);
+            SnapshotEntity { key, data }
+        })
+        .collect();
+    let snapshot = InitialSnapshot {
+        entities: snapshot_entities,
+        cache_entries,
+        started,
+    };
+    send_ack_and_snapshot(ctx, &sender, view_id, &view_spec, Some(snapshot), load).await?;
 
     let mut rx = ctx
         .bus_manager
@@ -1650,6 +3321,8 @@ async fn attach_derived_view_subscription_otel(
     let sorted_caches_clone = sorted_caches;
     let metrics_clone = ctx.metrics.clone();
     let frame_mode = view_spec.mode;
+    let quarantined = ctx.view_index.load().quarantined_views().clone();
+    let sub_key = subscription.sub_key();
 
     tokio::spawn(
         async move {
@@ -1664,6 +3337,19 @@ async fn attach_derived_view_subscription_otel(
                     result = rx.recv() => {
                         match result {
                             Ok(_envelope) => {
+                                if let Some(quarantine) = quarantined.get(&view_id_clone) {
+                                    send_view_unavailable(
+                                        &client_mgr,
+                                        client_id,
+                                        &view_id_clone,
+                                        &quarantine,
+                                    )
+                                    .await;
+                                    let _ = client_mgr
+                                        .remove_client_subscription(client_id, &sub_key)
+                                        .await;
+                                    break;
+                                }
                                 let new_window: Vec<(String, serde_json::Value)> = {
                                     let mut caches = sorted_caches_clone.write().await;
                                     if let Some(cache) = caches.get_mut(&view_id_clone) {
@@ -1689,9 +3375,8 @@ async fn attach_derived_view_subscription_otel(
                                                 append: vec![],
                                             };
                                             if let Ok(json) = serde_json::to_vec(&delete_frame) {
-                                                let payload = Arc::new(Bytes::from(json));
-                                                let payload_len = payload.len();
-                                                if client_mgr.send_to_client(client_id, payload).is_err() {
+                                                let payload_len = json.len();
+                                                if sender.send(&json).is_err() {
                                                     return;
                                                 }
                                                 if let Some(ref m) = metrics_clone {
@@ -1708,7 +3393,7 @@ async fn attach_derived_view_subscription_otel(
                                         }
 
                                         let mut transformed_data = data.clone();
-                                        transform_large_u64_to_strings(&mut transformed_data);
+                                        sender.encode_numbers(&mut transformed_data);
                                         let frame = Frame {
                                             seq: None,
                                             mode: frame_mode,
@@ -1720,9 +3405,8 @@ async fn attach_derived_view_subscription_otel(
                                         };
 
                                         if let Ok(json) = serde_json::to_vec(&frame) {
-                                            let payload = Arc::new(Bytes::from(json));
-                                            let payload_len = payload.len();
-                                            if client_mgr.send_to_client(client_id, payload).is_err() {
+                                            let payload_len = json.len();
+                                            if sender.send(&json).is_err() {
                                                 return;
                                             }
                                             if let Some(ref m) = metrics_clone {
@@ -1749,9 +3433,8 @@ async fn attach_derived_view_subscription_otel(
                                             append: vec![],
                                         };
                                         if let Ok(json) = serde_json::to_vec(&delete_frame) {
-                                            let payload = Arc::new(Bytes::from(json));
-                                            let payload_len = payload.len();
-                                            if client_mgr.send_to_client(client_id, payload).is_err() {
+                                            let payload_len = json.len();
+                                            if sender.send(&json).is_err() {
                                                 return;
                                             }
                                             if let Some(ref m) = metrics_clone {
@@ -1769,7 +3452,7 @@ async fn attach_derived_view_subscription_otel(
 
                                     for (key, data) in &new_window {
                                         let mut transformed_data = data.clone();
-                                        transform_large_u64_to_strings(&mut transformed_data);
+                                        sender.encode_numbers(&mut transformed_data);
                                         let frame = Frame {
                                             seq: None,
                                             mode: frame_mode,
@@ -1780,9 +3463,8 @@ async fn attach_derived_view_subscription_otel(
                                             append: vec![],
                                         };
                                         if let Ok(json) = serde_json::to_vec(&frame) {
-                                            let payload = Arc::new(Bytes::from(json));
-                                            let payload_len = payload.len();
-                                            if client_mgr.send_to_client(client_id, payload).is_err() {
+                                            let payload_len = json.len();
+                                            if sender.send(&json).is_err() {
                                                 return;
                                             }
                                             if let Some(ref m) = metrics_clone {
@@ -1822,24 +3504,23 @@ async fn attach_derived_view_subscription_otel(
 async fn attach_client_to_bus(
     ctx: &SubscriptionContext<'_>,
     subscription: Subscription,
+    sender: SubscriptionSender,
     cancel_token: CancellationToken,
+    received_at: Instant,
 ) -> Result<()> {
+    let (subscription, sender) = resolve_deprecated_view(ctx, subscription, sender).await?;
+    reject_quarantined_view(ctx, &subscription.view).await?;
     let view_id = &subscription.view;
+    let sender = sender.with_fields(view_id, subscription.fields.as_deref());
 
-    let view_spec = match ctx.view_index.get_view(view_id) {
+    let view_spec = match ctx.view_index.load().get_view(view_id) {
         Some(spec) => spec.clone(),
         None => {
             return Err(anyhow::anyhow!("Unknown view ID: {}", view_id));
         }
     };
-
-    send_subscribed_frame(
-        ctx.client_id,
-        view_id,
-        &view_spec,
-        ctx.client_manager,
-        ctx.usage_emitter,
-    )?;
+    let sender = sender.with_big_numbers(&view_spec.export, subscription.big_numbers);
+    let load = LoadDiagnostics::start(&subscription, received_at);
 
     let is_derived_with_sort = view_spec.is_derived()
         && view_spec
@@ -1849,7 +3530,15 @@ async fn attach_client_to_bus(
             .unwrap_or(false);
 
     if is_derived_with_sort {
-        return attach_derived_view_subscription(ctx, subscription, view_spec, cancel_token).await;
+        return attach_derived_view_subscription(
+            ctx,
+            subscription,
+            view_spec,
+            sender,
+            cancel_token,
+            load,
+        )
+        .await;
     }
 
     match view_spec.mode {
@@ -1857,38 +3546,46 @@ async fn attach_client_to_bus(
             let key = subscription.key.as_deref().unwrap_or("");
 
             let mut rx = ctx.bus_manager.get_or_create_state_bus(view_id, key).await;
+            let delivery = subscription.delivery();
+            let full_state_interest = (delivery == UpdateDelivery::FullState)
+                .then(|| ctx.bus_manager.register_full_state(view_id));
 
             // Check if we should send snapshot (defaults to true for backward compatibility)
             let should_send_snapshot = subscription.with_snapshot.unwrap_or(true);
 
+            let started = Instant::now();
+            let mut cached_entity = if should_send_snapshot {
+                ctx.entity_cache.get(view_id, key).await
+            } else {
+                None
+            };
+            if cached_entity.is_none() && should_send_snapshot {
+                cached_entity = backfill_state_entity(ctx, &view_spec, key, &mut rx).await;
+            }
+            let sent_snapshot = cached_entity.is_some();
+            let snapshot = match cached_entity {
+                Some(mut data) => {
+                    sender.encode_numbers(&mut data);
+                    Some(InitialSnapshot {
+                        entities: vec![SnapshotEntity {
+                            key: key.to_string(),
+                            data,
+                        }],
+                        cache_entries: ctx.entity_cache.len(view_id).await,
+                        started,
+                    })
+                }
+                None => None,
+            };
+            send_ack_and_snapshot(ctx, &sender, view_id, &view_spec, snapshot, load).await?;
+
             if should_send_snapshot {
-                if let Some(mut cached_entity) = ctx.entity_cache.get(view_id, key).await {
-                    transform_large_u64_to_strings(&mut cached_entity);
-                    let snapshot_entities = vec![SnapshotEntity {
-                        key: key.to_string(),
-                        data: cached_entity,
-                    }];
-                    enforce_snapshot_limit(ctx, snapshot_entities.len())?;
-                    let batch_config = ctx.entity_cache.snapshot_config();
-                    send_snapshot_batches(
-                        ctx.client_id,
-                        &snapshot_entities,
-                        view_spec.mode,
-                        view_id,
-                        ctx.client_manager,
-                        ctx.usage_emitter,
-                        &batch_config,
-                    )
-                    .await?;
+                if sent_snapshot {
                     rx.borrow_and_update();
-                } else if !rx.borrow().is_empty() {
-                    let data = rx.borrow_and_update().clone();
+                } else if !rx.borrow().payload.is_empty() {
+                    let data = rx.borrow_and_update().payload_for(delivery).clone();
                     let data_len = data.len();
-                    if ctx
-                        .client_manager
-                        .send_to_client(ctx.client_id, data)
-                        .is_ok()
-                    {
+                    if sender.send(&data).is_ok() {
                         emit_update_sent_for_client(
                             ctx.usage_emitter,
                             ctx.client_manager,
@@ -1914,6 +3611,7 @@ async fn attach_client_to_bus(
             let key_clone = key.to_string();
             tokio::spawn(
                 async move {
+                    let _full_state_interest = full_state_interest;
                     loop {
                         tokio::select! {
                             _ = cancel_token.cancelled() => {
@@ -1924,9 +3622,9 @@ async fn attach_client_to_bus(
                                 if result.is_err() {
                                     break;
                                 }
-                                let data = rx.borrow().clone();
+                                let data = rx.borrow().payload_for(delivery).clone();
                                 let data_len = data.len();
-                                if client_mgr.send_to_client(client_id, data).is_err() {
+                                if sender.send(&data).is_err() {
                                     break;
                                 }
                                 emit_update_sent_for_client(
@@ -1944,62 +3642,61 @@ async fn attach_client_to_bus(
             );
         }
         Mode::List | Mode::Append => {
-            let mut rx = ctx.bus_manager.get_or_create_list_bus(view_id).await;
+            let sender = match view_spec.mode {
+                Mode::List => sender
+                    .with_filter(subscription.filter.as_deref().cloned())
+                    .with_delta(subscription.delta.unwrap_or(false)),
+                _ => sender,
+            };
+            // Filters check updates against the whole entity, and deltas
+            // send it to start off entities the client doesn't have
+            let delivery = subscription.delivery();
+            let full_state_interest = (delivery == UpdateDelivery::FullState
+                || sender.is_filtered()
+                || sender.is_delta())
+            .then(|| ctx.bus_manager.register_full_state(view_id));
+            let (mut rx, history) = subscribe_list_bus(ctx, &subscription, &view_spec).await;
 
             // Check if we should send snapshot (defaults to true for backward compatibility)
             let should_send_snapshot = subscription.with_snapshot.unwrap_or(true);
 
-            if should_send_snapshot {
-                // Determine which entities to send based on cursor
-                let mut snapshots = if let Some(ref cursor) = subscription.after {
-                    ctx.entity_cache
-                        .get_after(view_id, cursor, subscription.snapshot_limit)
-                        .await
-                } else {
-                    ctx.entity_cache.get_all(view_id).await
-                };
-
-                // Sort by _seq descending only when there is no cursor (to get most-recent N from full cache)
-                if let Some(limit) = subscription.snapshot_limit {
-                    if subscription.after.is_none() {
-                        snapshots.sort_by(|a, b| {
-                            let sa = a.1.get("_seq").and_then(|s| s.as_str()).unwrap_or("");
-                            let sb = b.1.get("_seq").and_then(|s| s.as_str()).unwrap_or("");
-                            cmp_seq(sb, sa) // descending: most-recent N
-                        });
-                        snapshots.truncate(limit);
-                    }
+            let turn = should_send_snapshot.then(|| ctx.client_manager.snapshot_queue().admit());
+            let pending = match turn {
+                Some(None) => {
+                    return Err(anyhow::anyhow!(
+                        "Snapshot queue is full, retry the subscription later"
+                    ));
                 }
-
-                let snapshot_entities: Vec<SnapshotEntity> = snapshots
-                    .into_iter()
-                    .filter(|(key, _)| subscription.matches_key(key))
-                    .map(|(key, mut data)| {
-                        transform_large_u64_to_strings(&mut data);
-                        SnapshotEntity { key, data }
+                Some(Some(SnapshotTurn::Queued(queued))) => {
+                    send_pending_ack(ctx, &sender, view_id, &view_spec, load).await?;
+                    Some(PendingSnapshot {
+                        queued,
+                        ctx: ctx.to_owned(),
+                        subscription: subscription.clone(),
+                        mode: view_spec.mode,
+                        history,
                     })
-                    .collect();
-
-                if !snapshot_entities.is_empty() {
-                    enforce_snapshot_limit(ctx, snapshot_entities.len())?;
-                    let batch_config = ctx.entity_cache.snapshot_config();
-                    send_snapshot_batches(
-                        ctx.client_id,
-                        &snapshot_entities,
-                        view_spec.mode,
-                        view_id,
-                        ctx.client_manager,
-                        ctx.usage_emitter,
-                        &batch_config,
-                    )
-                    .await?;
                 }
-            } else {
-                info!(
-                    "Client {} subscribed to {} without snapshot",
-                    ctx.client_id, view_id
-                );
-            }
+                _permit => {
+                    // Any turn taken is held until the snapshot is sent
+                    let snapshot = if should_send_snapshot {
+                        Some(list_snapshot(ctx.entity_cache, &subscription, &sender).await)
+                    } else {
+                        info!(
+                            "Client {} subscribed to {} without snapshot",
+                            ctx.client_id, view_id
+                        );
+                        None
+                    };
+                    send_ack_and_snapshot(ctx, &sender, view_id, &view_spec, snapshot, load)
+                        .await?;
+
+                    if let Some(items) = history {
+                        send_history_frame(ctx, &sender, view_id, view_spec.mode, items).await?;
+                    }
+                    None
+                }
+            };
 
             let client_id = ctx.client_id;
             let client_mgr = ctx.client_manager.clone();
@@ -2010,6 +3707,41 @@ async fn attach_client_to_bus(
             let mode = view_spec.mode;
             tokio::spawn(
                 async move {
+                    let _full_state_interest = full_state_interest;
+                    let forward = |envelope: &BusMessage| {
+                        let Some(payload) = sender.filter_update(envelope, delivery) else {
+                            return true;
+                        };
+                        if sender.send(&payload).is_err() {
+                            return false;
+                        }
+                        if let Some(seq) = &envelope.seq {
+                            sender.advance_cursor(seq);
+                        }
+                        emit_update_sent_for_client(
+                            &usage_emitter,
+                            &client_mgr,
+                            client_id,
+                            &view_id_clone,
+                            payload.len(),
+                        );
+                        true
+                    };
+                    if let Some(pending) = pending {
+                        let mut held = Vec::new();
+                        tokio::select! {
+                            _ = cancel_token.cancelled() => return,
+                            result = pending.deliver(&sender, &mut rx, &mut held) => {
+                                if let Err(err) = result {
+                                    abandon_pending_snapshot(client_id, &client_mgr, &sub, &cancel_token, err).await;
+                                    return;
+                                }
+                            }
+                        }
+                        if !held.iter().all(|envelope| forward(envelope)) {
+                            return;
+                        }
+                    }
                     loop {
                         tokio::select! {
                             _ = cancel_token.cancelled() => {
@@ -2019,23 +3751,16 @@ async fn attach_client_to_bus(
                             result = rx.recv() => {
                                 match result {
                                     Ok(envelope) => {
-                                        if sub.matches(&envelope.entity, &envelope.key)
-                                            && client_mgr
-                                                .send_to_client(client_id, envelope.payload.clone())
-                                                .is_err()
-                                        {
+                                        if sub.matches(&envelope.entity, &envelope.key) && !forward(&envelope) {
                                             break;
-                                        } else if sub.matches(&envelope.entity, &envelope.key) {
-                                            emit_update_sent_for_client(
-                                                &usage_emitter,
-                                                &client_mgr,
-                                                client_id,
-                                                &view_id_clone,
-                                                envelope.payload.len(),
-                                            );
                                         }
                                     }
-                                    Err(_) => break,
+                                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                                        // The client sees the gap in its frame sequence and resyncs
+                                        warn!("List subscription for client {} lagged by {} updates", client_id, missed);
+                                        sender.skip();
+                                    }
+                                    Err(broadcast::error::RecvError::Closed) => break,
                                 }
                             }
                         }
@@ -2061,7 +3786,9 @@ async fn attach_derived_view_subscription(
     ctx: &SubscriptionContext<'_>,
     subscription: Subscription,
     view_spec: ViewSpec,
+    sender: SubscriptionSender,
     cancel_token: CancellationToken,
+    load: Option<LoadDiagnostics>,
 ) -> Result<()> {
     let view_id = &subscription.view;
     let pipeline_limit = view_spec
@@ -2083,41 +3810,33 @@ async fn attach_derived_view_subscription(
         }
     };
 
-    let sorted_caches = ctx.view_index.sorted_caches();
-    let initial_window: Vec<(String, serde_json::Value)> = {
+    let sorted_caches = ctx.view_index.load().sorted_caches();
+    let started = Instant::now();
+    let (initial_window, cache_entries): (Vec<(String, serde_json::Value)>, usize) = {
         let mut caches = sorted_caches.write().await;
         if let Some(cache) = caches.get_mut(view_id) {
-            cache.get_window(skip, take)
+            (cache.get_window(skip, take), cache.len())
         } else {
             warn!("No sorted cache for derived view {}", view_id);
-            vec![]
+            (vec![], 0)
         }
     };
 
     let initial_keys: HashSet<String> = initial_window.iter().map(|(k, _)| k.clone()).collect();
 
-    if !initial_window.is_empty() {
-        let snapshot_entities: Vec<SnapshotEntity> = initial_window
-            .into_iter()
-            .map(|(key, mut data)| {
-                transform_large_u64_to_strings(&mut data);
-                SnapshotEntity { key, data }
-            })
-            .collect();
-
-        enforce_snapshot_limit(ctx, snapshot_entities.len())?;
-        let batch_config = ctx.entity_cache.snapshot_config();
-        send_snapshot_batches(
-            ctx.client_id,
-            &snapshot_entities,
-            view_spec.mode,
-            view_id,
-            ctx.client_manager,
-            ctx.usage_emitter,
-            &batch_config,
-        )
-        .await?;
-    }
+    let snapshot_entities: Vec<SnapshotEntity> = initial_window
+        .into_iter()
+        .map(|(key, mut data)| {
+            sender.encode_numbers(&mut data);
+            SnapshotEntity { key, data }
+        })
+        .collect();
+    let snapshot = InitialSnapshot {
+        entities: snapshot_entities,
+        cache_entries,
+        started,
+    };
+    send_ack_and_snapshot(ctx, &sender, view_id, &view_spec, Some(snapshot), load).await?;
 
     let mut rx = ctx
         .bus_manager
@@ -2131,6 +3850,8 @@ async fn attach_derived_view_subscription(
     let view_id_span = view_id.clone();
     let sorted_caches_clone = sorted_caches;
     let frame_mode = view_spec.mode;
+    let quarantined = ctx.view_index.load().quarantined_views().clone();
+    let sub_key = subscription.sub_key();
 
     tokio::spawn(
         async move {
@@ -2145,6 +3866,19 @@ async fn attach_derived_view_subscription(
                     result = rx.recv() => {
                         match result {
                             Ok(_envelope) => {
+                                if let Some(quarantine) = quarantined.get(&view_id_clone) {
+                                    send_view_unavailable(
+                                        &client_mgr,
+                                        client_id,
+                                        &view_id_clone,
+                                        &quarantine,
+                                    )
+                                    .await;
+                                    let _ = client_mgr
+                                        .remove_client_subscription(client_id, &sub_key)
+                                        .await;
+                                    break;
+                                }
                                 let new_window: Vec<(String, serde_json::Value)> = {
                                     let mut caches = sorted_caches_clone.write().await;
                                     if let Some(cache) = caches.get_mut(&view_id_clone) {
@@ -2170,9 +3904,8 @@ async fn attach_derived_view_subscription(
                                                 append: vec![],
                                             };
                                             if let Ok(json) = serde_json::to_vec(&delete_frame) {
-                                                let payload = Arc::new(Bytes::from(json));
-                                                let payload_len = payload.len();
-                                                if client_mgr.send_to_client(client_id, payload).is_err() {
+                                                let payload_len = json.len();
+                                                if sender.send(&json).is_err() {
                                                     return;
                                                 }
                                                 emit_update_sent_for_client(
@@ -2186,7 +3919,7 @@ async fn attach_derived_view_subscription(
                                         }
 
                                         let mut transformed_data = data.clone();
-                                        transform_large_u64_to_strings(&mut transformed_data);
+                                        sender.encode_numbers(&mut transformed_data);
                                         let frame = Frame {
                                             seq: None,
                                             mode: frame_mode,
@@ -2197,9 +3930,8 @@ async fn attach_derived_view_subscription(
                                             append: vec![],
                                         };
                                         if let Ok(json) = serde_json::to_vec(&frame) {
-                                            let payload = Arc::new(Bytes::from(json));
-                                            let payload_len = payload.len();
-                                            if client_mgr.send_to_client(client_id, payload).is_err() {
+                                            let payload_len = json.len();
+                                            if sender.send(&json).is_err() {
                                                 return;
                                             }
                                             emit_update_sent_for_client(
@@ -2223,9 +3955,8 @@ async fn attach_derived_view_subscription(
                                             append: vec![],
                                         };
                                         if let Ok(json) = serde_json::to_vec(&delete_frame) {
-                                            let payload = Arc::new(Bytes::from(json));
-                                            let payload_len = payload.len();
-                                            if client_mgr.send_to_client(client_id, payload).is_err() {
+                                            let payload_len = json.len();
+                                            if sender.send(&json).is_err() {
                                                 return;
                                             }
                                             emit_update_sent_for_client(
@@ -2240,7 +3971,7 @@ async fn attach_derived_view_subscription(
 
                                     for (key, data) in &new_window {
                                         let mut transformed_data = data.clone();
-                                        transform_large_u64_to_strings(&mut transformed_data);
+                                        sender.encode_numbers(&mut transformed_data);
                                         let frame = Frame {
                                             seq: None,
                                             mode: frame_mode,
@@ -2251,9 +3982,8 @@ async fn attach_derived_view_subscription(
                                             append: vec![],
                                         };
                                         if let Ok(json) = serde_json::to_vec(&frame) {
-                                            let payload = Arc::new(Bytes::from(json));
-                                            let payload_len = payload.len();
-                                            if client_mgr.send_to_client(client_id, payload).is_err() {
+                                            let payload_len = json.len();
+                                            if sender.send(&json).is_err() {
                                                 return;
                                             }
                                             emit_update_sent_for_client(
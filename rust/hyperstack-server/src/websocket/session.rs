@@ -0,0 +1,401 @@
+//! Resumable client sessions.
+//!
+//! With sessions enabled, the server's `hello` carries a session token:
+//!
+//! ```json
+//! {"type":"hello","compression":{"zstd":false,"dictionary":null},"session":"3f2a…"}
+//! ```
+//!
+//! When the connection drops, the server keeps the client's subscriptions
+//! for [`SessionConfig::ttl`], along with how far each one got. A client
+//! that reconnects within that window presents the token from its previous
+//! connection in its own `hello`, and the server restores every
+//! subscription without the client sending them again:
+//!
+//! ```json
+//! {"type":"hello","session":"3f2a…"}
+//! {"type":"session","resumed":true,"subscriptions":["Token/list:*"]}
+//! ```
+//!
+//! Restored subscriptions are acked as usual and pick up where they left off:
+//!
+//! - `List` views send a snapshot of only the entities updated since the
+//!   last frame, unless more than [`SessionConfig::max_delta`] were;
+//! - `Append` views with history replay the items logged since, unless
+//!   some were already evicted from the log;
+//! - `State` and derived views, and resumes past those limits, send a full
+//!   snapshot.
+//!
+//! Frame numbers carry on from the old connection, so a client that lost
+//! frames in flight sees the gap and resyncs that subscription.
+//!
+//! A token is good for one resume, and only by a client with the same auth
+//! subject. An unknown or expired token gets `"resumed":false`, and the
+//! client subscribes again as usual. Each new connection gets a new token.
+//!
+//! Retained sessions are bounded by [`SessionConfig::max_sessions`] and
+//! [`SessionConfig::max_subscriptions`]: past either, the oldest sessions
+//! are evicted first. [`SessionStore::evict_all`] frees them all, e.g.
+//! under memory pressure. Counters are reported under `sessions` on
+//! `/stats`.
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::subscription::Subscription;
+
+/// Default time a disconnected client's session is kept
+pub const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(30);
+/// Default number of sessions kept at once
+pub const DEFAULT_MAX_SESSIONS: usize = 10_000;
+/// Default number of subscriptions kept across all sessions
+pub const DEFAULT_MAX_RETAINED_SUBSCRIPTIONS: usize = 100_000;
+/// Default number of updated entities a resumed list subscription is sent
+/// instead of a full snapshot
+pub const DEFAULT_MAX_DELTA: usize = 1_000;
+
+/// How long and how many sessions are kept. See the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionConfig {
+    /// Time a session is kept after its connection drops
+    pub ttl: Duration,
+    /// Sessions kept at once
+    pub max_sessions: usize,
+    /// Subscriptions kept across all sessions
+    pub max_subscriptions: usize,
+    /// Updated entities a resumed list subscription is sent before falling
+    /// back to a full snapshot
+    pub max_delta: usize,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            ttl: DEFAULT_SESSION_TTL,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            max_subscriptions: DEFAULT_MAX_RETAINED_SUBSCRIPTIONS,
+            max_delta: DEFAULT_MAX_DELTA,
+        }
+    }
+}
+
+impl SessionConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = max_sessions;
+        self
+    }
+
+    pub fn with_max_subscriptions(mut self, max_subscriptions: usize) -> Self {
+        self.max_subscriptions = max_subscriptions;
+        self
+    }
+
+    pub fn with_max_delta(mut self, max_delta: usize) -> Self {
+        self.max_delta = max_delta;
+        self
+    }
+}
+
+/// Session counters, as reported on `/stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SessionStats {
+    /// Sessions kept now
+    pub retained: usize,
+    /// Subscriptions kept across those sessions
+    pub subscriptions: usize,
+    /// Sessions resumed since startup
+    pub resumed: u64,
+    /// Resumes refused since startup, for unknown, expired or foreign tokens
+    pub refused: u64,
+    /// Sessions evicted to stay within bounds since startup
+    pub evicted: u64,
+    /// Sessions that expired unused since startup
+    pub expired: u64,
+}
+
+/// A subscription as its session kept it
+#[derive(Debug, Clone)]
+pub(crate) struct RetainedSubscription {
+    pub subscription: Subscription,
+    /// `_seq` of the latest entity sent, `None` when none had a seq
+    pub cursor: Option<String>,
+    /// Number of the last frame sent
+    pub last_frame: u64,
+}
+
+struct RetainedSession {
+    subject: Option<String>,
+    subscriptions: Vec<RetainedSubscription>,
+    retained_at: Instant,
+}
+
+#[derive(Default)]
+struct Sessions {
+    by_token: HashMap<String, RetainedSession>,
+    /// Tokens oldest first. Entries whose session was resumed or kept again
+    /// since are skipped.
+    order: VecDeque<(Instant, String)>,
+    subscriptions: usize,
+    stats: SessionStats,
+}
+
+impl Sessions {
+    fn remove(&mut self, token: &str) -> Option<RetainedSession> {
+        let session = self.by_token.remove(token)?;
+        self.subscriptions -= session.subscriptions.len();
+        Some(session)
+    }
+
+    /// Drop the oldest session, returning false once there is none
+    fn pop_oldest(&mut self, now: Instant, ttl: Duration) -> bool {
+        while let Some((retained_at, token)) = self.order.pop_front() {
+            let current = self
+                .by_token
+                .get(&token)
+                .is_some_and(|session| session.retained_at == retained_at);
+            if !current {
+                continue;
+            }
+            self.remove(&token);
+            if now.saturating_duration_since(retained_at) >= ttl {
+                self.stats.expired += 1;
+            } else {
+                self.stats.evicted += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    fn expire(&mut self, now: Instant, ttl: Duration) {
+        while let Some((retained_at, _)) = self.order.front() {
+            if now.saturating_duration_since(*retained_at) < ttl {
+                break;
+            }
+            self.pop_oldest(now, ttl);
+        }
+    }
+}
+
+/// Disconnected clients' sessions, until resumed or expired. See the
+/// [module docs](self).
+#[derive(Clone)]
+pub struct SessionStore {
+    config: SessionConfig,
+    sessions: Arc<Mutex<Sessions>>,
+}
+
+impl SessionStore {
+    pub fn new(config: SessionConfig) -> Self {
+        Self {
+            config,
+            sessions: Arc::default(),
+        }
+    }
+
+    pub fn config(&self) -> SessionConfig {
+        self.config
+    }
+
+    /// A new token for a connection
+    pub(crate) fn issue(&self) -> String {
+        Uuid::new_v4().simple().to_string()
+    }
+
+    /// Keep a disconnected client's subscriptions under `token`, evicting
+    /// the oldest sessions to stay within bounds
+    pub(crate) fn retain(
+        &self,
+        token: String,
+        subject: Option<String>,
+        subscriptions: Vec<RetainedSubscription>,
+        now: Instant,
+    ) {
+        if subscriptions.is_empty() || subscriptions.len() > self.config.max_subscriptions {
+            return;
+        }
+        let mut sessions = self.lock();
+        sessions.expire(now, self.config.ttl);
+        sessions.remove(&token);
+
+        sessions.subscriptions += subscriptions.len();
+        sessions.by_token.insert(
+            token.clone(),
+            RetainedSession {
+                subject,
+                subscriptions,
+                retained_at: now,
+            },
+        );
+        sessions.order.push_back((now, token));
+
+        while sessions.by_token.len() > self.config.max_sessions
+            || sessions.subscriptions > self.config.max_subscriptions
+        {
+            if !sessions.pop_oldest(now, self.config.ttl) {
+                break;
+            }
+        }
+    }
+
+    /// Take the session kept under `token`, unless it expired or belongs to
+    /// another subject
+    pub(crate) fn resume(
+        &self,
+        token: &str,
+        subject: Option<&str>,
+        now: Instant,
+    ) -> Option<Vec<RetainedSubscription>> {
+        let mut sessions = self.lock();
+        sessions.expire(now, self.config.ttl);
+
+        let owned = sessions
+            .by_token
+            .get(token)
+            .is_some_and(|session| session.subject.as_deref() == subject);
+        if !owned {
+            sessions.stats.refused += 1;
+            return None;
+        }
+        let session = sessions.remove(token)?;
+        sessions.stats.resumed += 1;
+        Some(session.subscriptions)
+    }
+
+    /// Drop every session kept, returning how many there were
+    pub fn evict_all(&self) -> usize {
+        let mut sessions = self.lock();
+        let evicted = sessions.by_token.len();
+        sessions.by_token.clear();
+        sessions.order.clear();
+        sessions.subscriptions = 0;
+        sessions.stats.evicted += evicted as u64;
+        evicted
+    }
+
+    pub fn stats(&self) -> SessionStats {
+        let mut sessions = self.lock();
+        sessions.expire(Instant::now(), self.config.ttl);
+        SessionStats {
+            retained: sessions.by_token.len(),
+            subscriptions: sessions.subscriptions,
+            ..sessions.stats.clone()
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Sessions> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(max_sessions: usize, max_subscriptions: usize) -> SessionStore {
+        SessionStore::new(
+            SessionConfig::new()
+                .with_ttl(Duration::from_secs(10))
+                .with_max_sessions(max_sessions)
+                .with_max_subscriptions(max_subscriptions),
+        )
+    }
+
+    fn subscriptions(views: &[&str]) -> Vec<RetainedSubscription> {
+        views
+            .iter()
+            .map(|view| RetainedSubscription {
+                subscription: serde_json::from_value(serde_json::json!({ "view": view })).unwrap(),
+                cursor: None,
+                last_frame: 3,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sessions_resume_once_within_the_ttl() {
+        let store = store(10, 10);
+        let start = Instant::now();
+        store.retain("a".into(), None, subscriptions(&["Token/list"]), start);
+        store.retain("b".into(), None, subscriptions(&["Token/list"]), start);
+
+        let resumed = store
+            .resume("a", None, start + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(resumed[0].subscription.view, "Token/list");
+        assert_eq!(resumed[0].last_frame, 3);
+        assert!(
+            store.resume("a", None, start).is_none(),
+            "tokens resume once"
+        );
+        assert!(store
+            .resume("b", None, start + Duration::from_secs(10))
+            .is_none());
+
+        let stats = store.stats();
+        assert_eq!((stats.retained, stats.subscriptions), (0, 0));
+        assert_eq!((stats.resumed, stats.refused, stats.expired), (1, 2, 1));
+    }
+
+    #[test]
+    fn sessions_resume_only_for_their_subject() {
+        let store = store(10, 10);
+        let now = Instant::now();
+        store.retain(
+            "a".into(),
+            Some("alice".into()),
+            subscriptions(&["Token/list"]),
+            now,
+        );
+
+        assert!(store.resume("a", Some("mallory"), now).is_none());
+        assert!(store.resume("a", None, now).is_none());
+        assert!(store.resume("a", Some("alice"), now).is_some());
+    }
+
+    #[test]
+    fn oldest_sessions_are_evicted_past_the_bounds() {
+        let store = store(2, 3);
+        let now = Instant::now();
+        store.retain("a".into(), None, subscriptions(&["A/list"]), now);
+        store.retain("b".into(), None, subscriptions(&["B/list"]), now);
+        store.retain("c".into(), None, subscriptions(&["C/list"]), now);
+        assert!(store.resume("a", None, now).is_none(), "over max_sessions");
+
+        store.retain("d".into(), None, subscriptions(&["D/list", "D/state"]), now);
+        store.retain("e".into(), None, subscriptions(&["E/list", "E/state"]), now);
+        assert!(store.resume("b", None, now).is_none());
+        assert!(store.resume("c", None, now).is_none());
+        assert!(
+            store.resume("d", None, now).is_none(),
+            "over max_subscriptions"
+        );
+
+        let stats = store.stats();
+        assert_eq!((stats.retained, stats.subscriptions), (1, 2));
+        assert_eq!(stats.evicted, 4);
+
+        store.retain(
+            "f".into(),
+            None,
+            subscriptions(&["F/1", "F/2", "F/3", "F/4"]),
+            now,
+        );
+        assert_eq!(store.stats().retained, 1, "larger than the bound itself");
+
+        assert_eq!(store.evict_all(), 1);
+        assert_eq!(store.stats().retained, 0);
+    }
+}
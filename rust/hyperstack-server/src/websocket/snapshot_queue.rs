@@ -0,0 +1,293 @@
+//! Fair scheduling between snapshot assembly and live broadcast.
+//!
+//! Building a list snapshot reads and serializes the whole view, which
+//! competes with live updates for the runtime's workers. A burst of new
+//! subscribers would otherwise build all their snapshots at once and delay
+//! the frames of every client already connected. Instead, list and append
+//! snapshots take turns through a [`SnapshotQueue`]: at most
+//! `max_concurrent` are built at a time and up to `max_queued` more wait in
+//! arrival order.
+//!
+//! A subscriber that has to wait gets its ack straight away, with
+//! `"status": "snapshot_pending"`, and its snapshot once its turn comes. Its
+//! live updates are held back until the snapshot is sent, so they never
+//! arrive ahead of it. Subscriptions beyond `max_queued` are refused.
+//!
+//! Queue depth and wait times are reported under `snapshot_queue` on
+//! `/stats`.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Default number of snapshots built at the same time
+pub const DEFAULT_MAX_CONCURRENT_SNAPSHOTS: usize = 4;
+/// Default number of snapshots waiting for their turn
+pub const DEFAULT_MAX_QUEUED_SNAPSHOTS: usize = 4096;
+
+/// Limits of the [`SnapshotQueue`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotQueueConfig {
+    /// Snapshots built at the same time, at least one
+    pub max_concurrent: usize,
+    /// Snapshots waiting for their turn before new subscriptions are refused
+    pub max_queued: usize,
+}
+
+impl Default for SnapshotQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: DEFAULT_MAX_CONCURRENT_SNAPSHOTS,
+            max_queued: DEFAULT_MAX_QUEUED_SNAPSHOTS,
+        }
+    }
+}
+
+impl SnapshotQueueConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = max_concurrent.max(1);
+        self
+    }
+
+    pub fn with_max_queued(mut self, max_queued: usize) -> Self {
+        self.max_queued = max_queued;
+        self
+    }
+}
+
+/// Snapshot queue counters, as reported on `/stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SnapshotQueueStats {
+    pub max_concurrent: usize,
+    pub max_queued: usize,
+    /// Snapshots being built or sent
+    pub running: usize,
+    /// Snapshots waiting for their turn
+    pub queued: usize,
+    /// Snapshots that had to wait for their turn since startup
+    pub waited: u64,
+    /// Mean time those snapshots waited
+    pub mean_wait_micros: u64,
+    /// Longest time a snapshot waited
+    pub max_wait_micros: u64,
+    /// Subscriptions refused because the queue was full
+    pub rejected: u64,
+}
+
+/// Hands out turns to build snapshots. See the [module docs](self).
+#[derive(Clone)]
+pub struct SnapshotQueue {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: SnapshotQueueConfig,
+    permits: Arc<Semaphore>,
+    queued: AtomicUsize,
+    waited: AtomicU64,
+    total_wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// Whether a snapshot can be built now or has to wait
+pub(crate) enum SnapshotTurn {
+    /// Build it now. Only held, so the turn is given back when it drops
+    Ready(#[allow(dead_code)] SnapshotPermit),
+    /// Wait with [`QueuedSnapshot::wait`]
+    Queued(QueuedSnapshot),
+}
+
+/// A turn to build a snapshot, given back when dropped
+pub(crate) struct SnapshotPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// A place in the queue
+pub(crate) struct QueuedSnapshot {
+    queue: SnapshotQueue,
+    queued_at: Instant,
+}
+
+impl Default for SnapshotQueue {
+    fn default() -> Self {
+        Self::new(SnapshotQueueConfig::default())
+    }
+}
+
+impl SnapshotQueue {
+    pub fn new(config: SnapshotQueueConfig) -> Self {
+        let config = SnapshotQueueConfig {
+            max_concurrent: config.max_concurrent.max(1),
+            ..config
+        };
+        Self {
+            inner: Arc::new(Inner {
+                permits: Arc::new(Semaphore::new(config.max_concurrent)),
+                config,
+                queued: AtomicUsize::new(0),
+                waited: AtomicU64::new(0),
+                total_wait_micros: AtomicU64::new(0),
+                max_wait_micros: AtomicU64::new(0),
+                rejected: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub fn config(&self) -> SnapshotQueueConfig {
+        self.inner.config
+    }
+
+    /// Take a turn now if one is free, a place in the queue otherwise, or
+    /// nothing when the queue is full.
+    ///
+    /// Turns given back go to the queue first, so a new snapshot never
+    /// overtakes one that is waiting.
+    pub(crate) fn admit(&self) -> Option<SnapshotTurn> {
+        if let Ok(permit) = self.inner.permits.clone().try_acquire_owned() {
+            return Some(SnapshotTurn::Ready(SnapshotPermit { _permit: permit }));
+        }
+
+        let max_queued = self.inner.config.max_queued;
+        let reserved = self
+            .inner
+            .queued
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |queued| {
+                (queued < max_queued).then_some(queued + 1)
+            })
+            .is_ok();
+        if !reserved {
+            self.inner.rejected.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        Some(SnapshotTurn::Queued(QueuedSnapshot {
+            queue: self.clone(),
+            queued_at: Instant::now(),
+        }))
+    }
+
+    pub fn stats(&self) -> SnapshotQueueStats {
+        let inner = &self.inner;
+        let waited = inner.waited.load(Ordering::Relaxed);
+        SnapshotQueueStats {
+            max_concurrent: inner.config.max_concurrent,
+            max_queued: inner.config.max_queued,
+            running: inner.config.max_concurrent - inner.permits.available_permits(),
+            queued: inner.queued.load(Ordering::Relaxed),
+            waited,
+            mean_wait_micros: inner
+                .total_wait_micros
+                .load(Ordering::Relaxed)
+                .checked_div(waited)
+                .unwrap_or(0),
+            max_wait_micros: inner.max_wait_micros.load(Ordering::Relaxed),
+            rejected: inner.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl QueuedSnapshot {
+    /// Wait for this snapshot's turn
+    pub(crate) async fn wait(self) -> SnapshotPermit {
+        let permit = self
+            .queue
+            .inner
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the snapshot semaphore is never closed");
+
+        let inner = &self.queue.inner;
+        let wait_micros = self.queued_at.elapsed().as_micros() as u64;
+        inner.waited.fetch_add(1, Ordering::Relaxed);
+        inner
+            .total_wait_micros
+            .fetch_add(wait_micros, Ordering::Relaxed);
+        inner
+            .max_wait_micros
+            .fetch_max(wait_micros, Ordering::Relaxed);
+
+        SnapshotPermit { _permit: permit }
+    }
+}
+
+impl Drop for QueuedSnapshot {
+    fn drop(&mut self) {
+        self.queue.inner.queued.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn queue(max_concurrent: usize, max_queued: usize) -> SnapshotQueue {
+        SnapshotQueue::new(
+            SnapshotQueueConfig::new()
+                .with_max_concurrent(max_concurrent)
+                .with_max_queued(max_queued),
+        )
+    }
+
+    #[tokio::test]
+    async fn snapshots_beyond_the_limit_wait_in_order() {
+        let queue = queue(1, 2);
+        let Some(SnapshotTurn::Ready(running)) = queue.admit() else {
+            panic!("the first snapshot should start right away");
+        };
+        let Some(SnapshotTurn::Queued(first)) = queue.admit() else {
+            panic!("the second snapshot should wait");
+        };
+        let Some(SnapshotTurn::Queued(second)) = queue.admit() else {
+            panic!("the third snapshot should wait");
+        };
+        assert!(queue.admit().is_none(), "the queue is full");
+
+        let stats = queue.stats();
+        assert_eq!((stats.running, stats.queued, stats.rejected), (1, 2, 1));
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        for (name, queued) in [("first", first), ("second", second)] {
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let _permit = queued.wait().await;
+                order_tx.send(name).unwrap();
+            });
+            tokio::task::yield_now().await;
+        }
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(order_rx.try_recv().is_err(), "no turn is free yet");
+        drop(running);
+        assert_eq!(order_rx.recv().await, Some("first"));
+        assert_eq!(order_rx.recv().await, Some("second"));
+
+        let stats = queue.stats();
+        assert_eq!((stats.running, stats.queued, stats.waited), (0, 0, 2));
+        assert!(stats.max_wait_micros >= stats.mean_wait_micros);
+        assert!(stats.max_wait_micros >= 5_000);
+    }
+
+    #[tokio::test]
+    async fn abandoned_places_leave_the_queue() {
+        let queue = queue(1, 1);
+        let Some(SnapshotTurn::Ready(_running)) = queue.admit() else {
+            panic!("the first snapshot should start right away");
+        };
+        let queued = queue.admit();
+        assert_eq!(queue.stats().queued, 1);
+
+        drop(queued);
+        assert_eq!(queue.stats().queued, 0);
+        assert!(matches!(queue.admit(), Some(SnapshotTurn::Queued(_))));
+    }
+}
@@ -1,6 +1,11 @@
+use hyperstack_interpreter::big_numbers::BigNumberMode;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 
 use crate::websocket::auth::AuthDeny;
+use crate::websocket::filter::EntityFilter;
 
 /// Client message types for subscription management
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,10 +15,115 @@ pub enum ClientMessage {
     Subscribe(Subscription),
     /// Unsubscribe from a view
     Unsubscribe(Unsubscription),
+    /// Restart a subscription from a fresh snapshot after a gap in its frames
+    Resync(Unsubscription),
+    /// Replace what the client holds of a subscription with a fresh snapshot
+    Refresh(Unsubscription),
     /// Keep-alive ping (no response needed)
     Ping,
     /// Refresh authentication token without reconnecting
     RefreshAuth(RefreshAuthRequest),
+    /// Choose how frames are compressed, answering the server's `hello`
+    Compression(CompressionRequest),
+    /// Resume the session of an earlier connection
+    Hello(ClientHello),
+}
+
+/// A client's `hello`, presenting the session token of its previous
+/// connection.
+///
+/// See the [`session`](super::session) module.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientHello {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+}
+
+/// Answer to a [`ClientHello`]: whether the session was resumed, and the
+/// subscriptions being restored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMessage {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub resumed: bool,
+    pub subscriptions: Vec<String>,
+}
+
+impl SessionMessage {
+    pub fn new(resumed: bool, subscriptions: Vec<String>) -> Self {
+        Self {
+            kind: "session".to_string(),
+            resumed,
+            subscriptions,
+        }
+    }
+}
+
+/// Frame compression a client asks for.
+///
+/// See the [`dictionary`](crate::dictionary) module for the negotiation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionRequest {
+    pub codec: CompressionCodec,
+    /// Id of the dictionary the client holds, from the server's `hello`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dictionary: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionCodec {
+    Gzip,
+    Zstd,
+}
+
+/// First message of a connection on servers that offer zstd or resumable
+/// sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HelloMessage {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub compression: HelloCompression,
+    /// Token to resume this connection's session with, see the
+    /// [`session`](super::session) module
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HelloCompression {
+    pub zstd: bool,
+    /// Id of the current dictionary, served at `/dictionaries/<id>`
+    pub dictionary: Option<String>,
+}
+
+impl HelloMessage {
+    pub fn new(dictionary: Option<String>) -> Self {
+        Self {
+            kind: "hello".to_string(),
+            compression: HelloCompression {
+                zstd: true,
+                dictionary,
+            },
+            session: None,
+        }
+    }
+
+    /// Hello from a server that only offers gzip
+    pub fn gzip_only() -> Self {
+        Self {
+            compression: HelloCompression {
+                zstd: false,
+                dictionary: None,
+            },
+            ..Self::new(None)
+        }
+    }
+
+    pub fn with_session(mut self, session: Option<String>) -> Self {
+        self.session = session;
+        self
+    }
 }
 
 /// Request to refresh authentication token
@@ -45,6 +155,8 @@ pub struct SocketIssueMessage {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub retry_after: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub suggested_action: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub docs_url: Option<String>,
@@ -61,6 +173,7 @@ impl SocketIssueMessage {
             code: response.code,
             retryable: response.retryable,
             retry_after: response.retry_after,
+            retry_after_ms: response.retry_after_ms,
             suggested_action: response.suggested_action,
             docs_url: response.docs_url,
             fatal,
@@ -68,13 +181,56 @@ impl SocketIssueMessage {
     }
 }
 
-/// Client subscription to a specific view
+/// JSON reason carried by close frames the server sends on purpose.
+///
+/// `retry_after_ms` tells the client how long to wait before reconnecting,
+/// overriding its own backoff for that attempt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloseReason {
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+}
+
+impl CloseReason {
+    pub fn new(code: impl Into<String>, retry_after: Option<Duration>) -> Self {
+        Self {
+            code: code.into(),
+            retry_after_ms: retry_after.map(|delay| delay.as_millis() as u64),
+        }
+    }
+
+    pub fn into_close_frame(self, close_code: CloseCode) -> CloseFrame {
+        let reason = serde_json::to_string(&self).unwrap_or(self.code);
+        CloseFrame {
+            code: close_code,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// URL path prefix under which a client connects straight to one view
+pub const PATH_SUBSCRIPTION_PREFIX: &str = "/sub/";
+
+/// Client subscription to a specific view.
+///
+/// A subscription is identified by its [`sub_key`](Self::sub_key): the view
+/// and key, plus the client's `id` when it sets one. Subscriptions with
+/// different sub keys are independent, even on the same view and key: each
+/// keeps its own options, its frames are stamped with its own `sub`, and
+/// unsubscribing one leaves the others running. Subscribing again with a
+/// sub key the client already uses replaces that subscription, restarting
+/// it with the new options.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Subscription {
     pub view: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
+    /// Client-chosen id, for holding several subscriptions to the same view
+    /// and key with different options
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub partition: Option<String>,
     /// Number of items to return (for windowed subscriptions)
@@ -88,6 +244,8 @@ pub struct Subscription {
     pub with_snapshot: Option<bool>,
     /// Cursor for resuming from a specific point (_seq value).
     /// Note: Ignored for State mode subscriptions (single entity, no pagination).
+    /// Note: For Append mode views with history enabled, the logged items
+    /// published after this point are replayed in a history frame.
     /// Note: Not supported for derived views (windowed aggregations with sort). Derived views
     /// always emit `seq: None` in live update frames, so cursor-based reconnection is unavailable.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -96,6 +254,50 @@ pub struct Subscription {
     /// Note: Ignored for State mode subscriptions (single entity).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub snapshot_limit: Option<usize>,
+    /// Number of recent items to replay before live frames.
+    /// Note: Only honored for Append mode views with history enabled, and
+    /// capped at the view's configured history.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<usize>,
+    /// Include [`SubscriptionDiagnostics`](super::SubscriptionDiagnostics) in
+    /// the subscription ack
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<bool>,
+    /// How live updates are delivered, as patches by default.
+    /// Note: Append mode and derived views always receive their usual frames.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<UpdateDelivery>,
+    /// Only receive these fields of each entity, as dotted paths.
+    /// Note: Narrowed further to the fields the client's auth context allows,
+    /// see [`field_mask`](super::field_mask).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+    /// How 64-bit integers are encoded, e.g. `string` for clients that want
+    /// one type per field. The server's mode is used when it is stricter,
+    /// see [`big_numbers`](crate::big_numbers).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub big_numbers: Option<BigNumberMode>,
+    /// Only receive entities matching this filter, see [`filter`](super::filter).
+    /// Note: Only applied to List mode views that aren't derived.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Box<EntityFilter>>,
+    /// Only receive the fields of each update that changed from what was
+    /// sent before, see [`delta`](super::delta).
+    /// Note: Only applied to List mode views that aren't derived.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<bool>,
+}
+
+/// How a subscription receives live updates to its entities
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateDelivery {
+    /// `patch` frames carrying only what changed
+    #[default]
+    Patch,
+    /// `upsert` frames carrying the whole entity as cached after the update,
+    /// which clients replace rather than merge
+    FullState,
 }
 
 /// Client unsubscription request
@@ -104,18 +306,33 @@ pub struct Unsubscription {
     pub view: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
+    /// The `id` the subscription was made with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
 }
 
 impl Unsubscription {
     /// Generate the subscription key used for tracking
     pub fn sub_key(&self) -> String {
-        match &self.key {
-            Some(k) => format!("{}:{}", self.view, k),
-            None => format!("{}:*", self.view),
-        }
+        sub_key(&self.view, self.key.as_deref(), self.id.as_deref())
     }
 }
 
+/// `view:key`, or `view:*` without a key, followed by `#id` when the client
+/// named the subscription
+fn sub_key(view: &str, key: Option<&str>, id: Option<&str>) -> String {
+    let key = key.unwrap_or("*");
+    match id {
+        Some(id) => format!("{view}:{key}#{id}"),
+        None => format!("{view}:{key}"),
+    }
+}
+
+/// The view of a key built by [`Subscription::sub_key`]
+pub(crate) fn sub_key_view(sub_key: &str) -> &str {
+    sub_key.split_once(':').map_or(sub_key, |(view, _)| view)
+}
+
 impl Subscription {
     pub fn matches_view(&self, view_id: &str) -> bool {
         self.view == view_id
@@ -130,10 +347,54 @@ impl Subscription {
     }
 
     pub fn sub_key(&self) -> String {
-        match &self.key {
-            Some(k) => format!("{}:{}", self.view, k),
-            None => format!("{}:*", self.view),
-        }
+        sub_key(&self.view, self.key.as_deref(), self.id.as_deref())
+    }
+
+    pub fn delivery(&self) -> UpdateDelivery {
+        self.delivery.unwrap_or_default()
+    }
+
+    /// Subscription requested by the URL a client connected to, e.g.
+    /// `/sub/OreRound/latest` or `/sub/OreRound/state?key=42`.
+    ///
+    /// The query may set `key`, `partition`, `take`, `skip`, `delivery`
+    /// (`patch` or `full_state`), `fields` (comma-separated),
+    /// `big_numbers` (`number`, `auto` or `string`) and `delta` (`true`);
+    /// everything else keeps its default. Returns `None` for paths outside
+    /// [`PATH_SUBSCRIPTION_PREFIX`], which speak the JSON protocol.
+    pub fn from_path(path: &str, query: Option<&str>) -> Option<Self> {
+        let view = path
+            .strip_prefix(PATH_SUBSCRIPTION_PREFIX)?
+            .trim_end_matches('/');
+        let param = |name: &str| {
+            query?
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find_map(|(k, v)| (k == name && !v.is_empty()).then(|| v.to_string()))
+        };
+
+        Some(Self {
+            view: view.to_string(),
+            key: param("key"),
+            id: None,
+            partition: param("partition"),
+            take: param("take").and_then(|take| take.parse().ok()),
+            skip: param("skip").and_then(|skip| skip.parse().ok()),
+            with_snapshot: None,
+            after: None,
+            snapshot_limit: None,
+            history: None,
+            diagnostics: None,
+            delivery: param("delivery").and_then(|delivery| match delivery.as_str() {
+                "patch" => Some(UpdateDelivery::Patch),
+                "full_state" => Some(UpdateDelivery::FullState),
+                _ => None,
+            }),
+            fields: param("fields").map(|fields| fields.split(',').map(str::to_string).collect()),
+            big_numbers: param("big_numbers").and_then(|mode| mode.parse().ok()),
+            filter: None,
+            delta: param("delta").and_then(|delta| delta.parse().ok()),
+        })
     }
 }
 
@@ -171,12 +432,20 @@ mod tests {
         let sub = Subscription {
             view: "SettlementGame/list".to_string(),
             key: Some("835".to_string()),
+            id: None,
             partition: None,
             take: None,
             skip: None,
             with_snapshot: None,
             after: None,
             snapshot_limit: None,
+            history: None,
+            diagnostics: None,
+            delivery: None,
+            fields: None,
+            big_numbers: None,
+            filter: None,
+            delta: None,
         };
 
         assert!(sub.matches("SettlementGame/list", "835"));
@@ -189,12 +458,20 @@ mod tests {
         let sub = Subscription {
             view: "SettlementGame/list".to_string(),
             key: None,
+            id: None,
             partition: None,
             take: None,
             skip: None,
             with_snapshot: None,
             after: None,
             snapshot_limit: None,
+            history: None,
+            diagnostics: None,
+            delivery: None,
+            fields: None,
+            big_numbers: None,
+            filter: None,
+            delta: None,
         };
 
         assert!(sub.matches("SettlementGame/list", "835"));
@@ -238,6 +515,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_client_message_resync_parse() {
+        let json = json!({
+            "type": "resync",
+            "view": "SettlementGame/list"
+        });
+
+        let msg: ClientMessage = serde_json::from_value(json).unwrap();
+        match msg {
+            ClientMessage::Resync(resync) => {
+                assert_eq!(resync.sub_key(), "SettlementGame/list:*");
+            }
+            _ => panic!("Expected Resync"),
+        }
+    }
+
+    #[test]
+    fn test_client_message_refresh_parse() {
+        let json = json!({
+            "type": "refresh",
+            "view": "SettlementGame/state",
+            "key": "835"
+        });
+
+        let msg: ClientMessage = serde_json::from_value(json).unwrap();
+        match msg {
+            ClientMessage::Refresh(refresh) => {
+                assert_eq!(refresh.sub_key(), "SettlementGame/state:835");
+            }
+            _ => panic!("Expected Refresh"),
+        }
+    }
+
     #[test]
     fn test_client_message_ping_parse() {
         let json = json!({ "type": "ping" });
@@ -262,6 +572,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_client_message_hello_parse() {
+        let json = json!({ "type": "hello", "session": "3f2a" });
+
+        let msg: ClientMessage = serde_json::from_value(json).unwrap();
+        match msg {
+            ClientMessage::Hello(hello) => assert_eq!(hello.session.as_deref(), Some("3f2a")),
+            _ => panic!("Expected Hello"),
+        }
+
+        let msg: ClientMessage = serde_json::from_value(json!({ "type": "hello" })).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::Hello(ClientHello { session: None })
+        ));
+    }
+
     #[test]
     fn test_legacy_subscription_parse_as_subscribe() {
         let json = json!({
@@ -279,12 +606,20 @@ mod tests {
         let sub = Subscription {
             view: "SettlementGame/list".to_string(),
             key: Some("835".to_string()),
+            id: None,
             partition: None,
             take: None,
             skip: None,
             with_snapshot: None,
             after: None,
             snapshot_limit: None,
+            history: None,
+            diagnostics: None,
+            delivery: None,
+            fields: None,
+            big_numbers: None,
+            filter: None,
+            delta: None,
         };
         assert_eq!(sub.sub_key(), "SettlementGame/list:835");
     }
@@ -294,12 +629,20 @@ mod tests {
         let sub = Subscription {
             view: "SettlementGame/list".to_string(),
             key: None,
+            id: None,
             partition: None,
             take: None,
             skip: None,
             with_snapshot: None,
             after: None,
             snapshot_limit: None,
+            history: None,
+            diagnostics: None,
+            delivery: None,
+            fields: None,
+            big_numbers: None,
+            filter: None,
+            delta: None,
         };
         assert_eq!(sub.sub_key(), "SettlementGame/list:*");
     }
@@ -309,16 +652,47 @@ mod tests {
         let unsub = Unsubscription {
             view: "SettlementGame/list".to_string(),
             key: Some("835".to_string()),
+            id: None,
         };
         assert_eq!(unsub.sub_key(), "SettlementGame/list:835");
 
         let unsub_all = Unsubscription {
             view: "SettlementGame/list".to_string(),
             key: None,
+            id: None,
         };
         assert_eq!(unsub_all.sub_key(), "SettlementGame/list:*");
     }
 
+    #[test]
+    fn test_sub_key_with_id() {
+        let sub: Subscription = serde_json::from_value(json!({
+            "view": "SettlementGame/list",
+            "id": "prices"
+        }))
+        .unwrap();
+        assert_eq!(sub.sub_key(), "SettlementGame/list:*#prices");
+
+        let unsub = Unsubscription {
+            view: "SettlementGame/list".to_string(),
+            key: None,
+            id: Some("prices".to_string()),
+        };
+        assert_eq!(unsub.sub_key(), sub.sub_key());
+    }
+
+    #[test]
+    fn test_sub_key_view() {
+        assert_eq!(
+            sub_key_view("SettlementGame/list:835"),
+            "SettlementGame/list"
+        );
+        assert_eq!(
+            sub_key_view("SettlementGame/list:*#prices"),
+            "SettlementGame/list"
+        );
+    }
+
     #[test]
     fn test_subscription_with_take_skip() {
         let json = json!({
@@ -407,6 +781,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_subscription_with_delivery() {
+        let json = json!({
+            "type": "subscribe",
+            "view": "SettlementGame/list",
+            "delivery": "full_state"
+        });
+
+        let msg: ClientMessage = serde_json::from_value(json).unwrap();
+        match msg {
+            ClientMessage::Subscribe(sub) => {
+                assert_eq!(sub.delivery(), UpdateDelivery::FullState);
+            }
+            _ => panic!("Expected Subscribe"),
+        }
+
+        let sub = Subscription::from_path("/sub/OreRound/latest", None).unwrap();
+        assert_eq!(sub.delivery(), UpdateDelivery::Patch);
+    }
+
     #[test]
     fn test_socket_issue_message_from_auth_deny() {
         let deny = AuthDeny::new(
@@ -421,4 +815,35 @@ mod tests {
         assert_eq!(issue.suggested_action.as_deref(), Some("unsubscribe first"));
         assert!(!issue.fatal);
     }
+
+    #[test]
+    fn test_close_reason_carries_retry_hint() {
+        let frame = CloseReason::new("backpressure", Some(std::time::Duration::from_secs(5)))
+            .into_close_frame(CloseCode::Again);
+
+        assert_eq!(frame.code, CloseCode::Again);
+        assert_eq!(
+            frame.reason.as_str(),
+            r#"{"code":"backpressure","retry_after_ms":5000}"#
+        );
+    }
+
+    #[test]
+    fn test_subscription_from_path() {
+        let sub = Subscription::from_path("/sub/OreRound/latest", None).unwrap();
+        assert_eq!(sub.view, "OreRound/latest");
+        assert_eq!(sub.sub_key(), "OreRound/latest:*");
+
+        let sub = Subscription::from_path(
+            "/sub/OreRound/state/",
+            Some("key=42&take=5&delivery=full_state&hs_token=t"),
+        )
+        .unwrap();
+        assert_eq!(sub.view, "OreRound/state");
+        assert_eq!(sub.key.as_deref(), Some("42"));
+        assert_eq!(sub.take, Some(5));
+        assert_eq!(sub.delivery, Some(UpdateDelivery::FullState));
+
+        assert!(Subscription::from_path("/", Some("key=42")).is_none());
+    }
 }
@@ -0,0 +1,192 @@
+mod common;
+
+use common::Client;
+use hyperstack_server::{
+    BackgroundHandle, Delivery, Mode, MutationBatch, Server, SlotContext, ViewIndex, ViewSpec,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+type Trades = mpsc::UnboundedSender<MutationBatch>;
+
+fn trade(slot: u64) -> MutationBatch {
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..common::batch("Trade", &format!("trade-{slot}"), json!({ "slot": slot }))
+    }
+}
+
+async fn serve(history: usize) -> (SocketAddr, BackgroundHandle, Trades) {
+    serve_with(Delivery {
+        history,
+        ..Default::default()
+    })
+    .await
+}
+
+/// A server whose parser has sent five trades, and the channel feeding it
+async fn serve_with(delivery: Delivery) -> (SocketAddr, BackgroundHandle, Trades) {
+    let (spec, trades) = common::forwarding_spec();
+    for slot in 0..5 {
+        trades.send(trade(slot)).unwrap();
+    }
+
+    let mut views = ViewIndex::new();
+    views.add_spec(ViewSpec {
+        delivery,
+        ..common::view("Trade/append", "Trade", Mode::Append)
+    });
+
+    let (addr, background) = common::serve(Server::builder().spec(spec).views(views)).await;
+    (addr, background, trades)
+}
+
+async fn wait_for_trades(addr: SocketAddr, count: u64) {
+    common::wait_for_stats(
+        addr,
+        &format!("projector should process {count} trades"),
+        |stats| stats["cache"]["total_entities"] == json!(count),
+    )
+    .await;
+    // The cache is written just before the item is published
+    tokio::time::sleep(Duration::from_millis(50)).await;
+}
+
+async fn subscribe(addr: SocketAddr, subscription: Value) -> Client {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    let mut message = json!({ "type": "subscribe", "view": "Trade/append", "withSnapshot": false });
+    message
+        .as_object_mut()
+        .unwrap()
+        .extend(subscription.as_object().unwrap().clone());
+    common::send(&mut ws, message).await;
+
+    let subscribed = common::next_json(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"));
+    ws
+}
+
+fn slots(frame: &Value) -> Vec<u64> {
+    frame["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["data"]["slot"].as_u64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn late_subscriber_gets_history_then_only_new_items() {
+    let (addr, background, trades) = serve(3).await;
+    wait_for_trades(addr, 5).await;
+
+    // Asking for more than the view keeps is capped at the view's history
+    let mut ws = subscribe(addr, json!({ "history": 10 })).await;
+
+    let history = common::next_json(&mut ws).await;
+    assert_eq!(history["op"], json!("history"));
+    assert_eq!(history["entity"], json!("Trade/append"));
+    assert_eq!(slots(&history), vec![2, 3, 4]);
+    assert_eq!(history["data"][2]["key"], json!("trade-4"));
+
+    trades.send(trade(5)).unwrap();
+    let live = common::next_json(&mut ws).await;
+    assert_eq!(live["op"], json!("patch"));
+    assert_eq!(live["key"], json!("trade-5"));
+    assert_eq!(live["data"]["slot"], json!(5));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn views_replaying_on_subscribe_send_history_unasked() {
+    let delivery = Delivery {
+        history: 4,
+        replay_on_subscribe: true,
+        ..Default::default()
+    };
+    let (addr, background, trades) = serve_with(delivery).await;
+    wait_for_trades(addr, 5).await;
+
+    let mut ws = subscribe(addr, json!({})).await;
+    let history = common::next_json(&mut ws).await;
+    assert_eq!(history["op"], json!("history"));
+    assert_eq!(history["replayed"], json!(true));
+    assert_eq!(slots(&history), vec![1, 2, 3, 4]);
+
+    trades.send(trade(5)).unwrap();
+    let live = common::next_json(&mut ws).await;
+    assert_eq!(live["op"], json!("patch"));
+    assert_eq!(live["key"], json!("trade-5"));
+    assert!(live.get("replayed").is_none());
+
+    // Asking for no history still opts out
+    let mut plain = subscribe(addr, json!({ "history": 0 })).await;
+    assert!(
+        common::try_next_json(&mut plain, Duration::from_millis(200))
+            .await
+            .is_none(),
+        "no history frame should be sent"
+    );
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn keyed_history_and_opt_out() {
+    let (addr, background, trades) = serve(3).await;
+    wait_for_trades(addr, 5).await;
+
+    let mut keyed = subscribe(addr, json!({ "key": "trade-1", "history": 3 })).await;
+    let history = common::next_json(&mut keyed).await;
+    assert_eq!(history["op"], json!("history"));
+    assert_eq!(slots(&history), vec![1]);
+
+    // Without `history` the subscriber only sees live items
+    let mut plain = subscribe(addr, json!({})).await;
+    trades.send(trade(5)).unwrap();
+    let live = common::next_json(&mut plain).await;
+    assert_eq!(live["op"], json!("patch"));
+    assert_eq!(live["key"], json!("trade-5"));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn resuming_subscriber_gets_items_after_its_seq() {
+    let (addr, background, trades) = serve(3).await;
+    wait_for_trades(addr, 5).await;
+
+    let mut ws = subscribe(addr, json!({ "after": "2:000000000000" })).await;
+    let history = common::next_json(&mut ws).await;
+    assert_eq!(history["op"], json!("history"));
+    assert_eq!(slots(&history), vec![3, 4]);
+
+    // Items older than the log are gone; the rest is replayed
+    let mut behind = subscribe(addr, json!({ "after": "0:000000000000" })).await;
+    assert_eq!(slots(&common::next_json(&mut behind).await), vec![2, 3, 4]);
+
+    trades.send(trade(5)).unwrap();
+    let live = common::next_json(&mut ws).await;
+    assert_eq!(live["key"], json!("trade-5"));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn stats_report_append_log_retention() {
+    let (addr, background, _trades) = serve(3).await;
+    wait_for_trades(addr, 5).await;
+
+    let stats = common::http_get_json(addr, "/stream/stats").await;
+    let log = &stats["append_logs"]["Trade/append"];
+    assert_eq!(log["entries"], json!(3));
+    assert_eq!(log["first_cursor"], json!(3));
+    assert_eq!(log["last_cursor"], json!(5));
+    assert_eq!(log["evictions"]["over_items"], json!(2));
+    assert!(log["oldest_age_ms"].is_u64());
+
+    background.shutdown();
+}
@@ -0,0 +1,142 @@
+//! Append log invariants under concurrent appends, reads and evictions.
+
+use bytes::Bytes;
+use hyperstack_server::{AppendLogConfig, BusManager, BusMessage, HistoryItem, LogLimits};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+const VIEW: &str = "Trade/append";
+const WRITERS: u64 = 8;
+const PER_WRITER: u64 = 250;
+
+fn limits() -> LogLimits {
+    LogLimits {
+        items: 64,
+        bytes: 4 * 1024,
+        max_age: None,
+    }
+}
+
+async fn append(bus: &BusManager, writer: u64, n: u64) -> u64 {
+    let key = format!("writer-{writer}");
+    let item = HistoryItem {
+        key: key.clone(),
+        data: json!({ "writer": writer, "n": n }),
+        append: vec![],
+        seq: None,
+    };
+    let message = Arc::new(BusMessage::new(
+        key,
+        VIEW.to_string(),
+        Arc::new(Bytes::from(serde_json::to_vec(&item).unwrap())),
+    ));
+    bus.publish_list_with_history(VIEW, message, &item, limits())
+        .await
+        .unwrap()
+        .cursor
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn cursors_and_range_reads_hold_under_concurrent_appends() {
+    let bus = BusManager::new().with_append_log(AppendLogConfig::new().with_max_items(32));
+
+    let writers: Vec<_> = (0..WRITERS)
+        .map(|writer| {
+            let bus = bus.clone();
+            tokio::spawn(async move {
+                let mut cursors = Vec::new();
+                for n in 0..PER_WRITER {
+                    cursors.push(append(&bus, writer, n).await);
+                    tokio::task::yield_now().await;
+                }
+                cursors
+            })
+        })
+        .collect();
+
+    let reader = {
+        let bus = bus.clone();
+        tokio::spawn(async move {
+            let mut reads = 0;
+            let mut cursor = 0;
+            while cursor < WRITERS * PER_WRITER {
+                let read = bus.read_append_log(VIEW, None, cursor, 16).await;
+                let cursors: Vec<u64> = read.entries.iter().map(|entry| entry.cursor).collect();
+                assert!(cursors.iter().all(|c| *c > cursor));
+                assert!(cursors.windows(2).all(|pair| pair[0] < pair[1]));
+                if let Some(last) = cursors.last() {
+                    // Nothing is lost between pages unless it was evicted
+                    if !read.missed {
+                        assert_eq!(cursors[0], cursor + 1);
+                    }
+                    cursor = *last;
+                }
+                reads += 1;
+                tokio::task::yield_now().await;
+            }
+            reads
+        })
+    };
+
+    let mut all_cursors = Vec::new();
+    for writer in writers {
+        let cursors = writer.await.unwrap();
+        // Each writer sees its own appends in order
+        assert!(cursors.windows(2).all(|pair| pair[0] < pair[1]));
+        all_cursors.extend(cursors);
+    }
+    assert!(
+        tokio::time::timeout(Duration::from_secs(10), reader)
+            .await
+            .expect("reader should catch up")
+            .unwrap()
+            > 0
+    );
+
+    // Every append got its own cursor, with no gaps
+    all_cursors.sort_unstable();
+    let expected: Vec<u64> = (1..=WRITERS * PER_WRITER).collect();
+    assert_eq!(all_cursors, expected);
+
+    // The server cap wins over the view's own limit
+    let stats = bus.append_log_stats().await;
+    let view = &stats[VIEW];
+    assert_eq!(view.entries, 32);
+    assert_eq!(view.last_cursor, WRITERS * PER_WRITER);
+    assert_eq!(view.first_cursor, Some(WRITERS * PER_WRITER - 31));
+    assert_eq!(view.evictions.over_items, WRITERS * PER_WRITER - 32);
+    assert_eq!(view.evictions.total(), view.evictions.over_items);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn history_and_live_items_neither_overlap_nor_skip() {
+    let bus = BusManager::new();
+    let writer = {
+        let bus = bus.clone();
+        tokio::spawn(async move {
+            for n in 0..500 {
+                append(&bus, 0, n).await;
+                tokio::task::yield_now().await;
+            }
+        })
+    };
+
+    // Subscribe while the writer is running
+    tokio::time::sleep(Duration::from_millis(1)).await;
+    let (mut rx, history) = bus.subscribe_list_with_history(VIEW, None, 16).await;
+    writer.await.unwrap();
+
+    let mut seen: Vec<u64> = history
+        .iter()
+        .map(|item| item.data["n"].as_u64().unwrap())
+        .collect();
+    while let Ok(message) = rx.try_recv() {
+        let item: HistoryItem = serde_json::from_slice(&message.payload).unwrap();
+        seen.push(item.data["n"].as_u64().unwrap());
+    }
+
+    let first = seen[0];
+    let expected: Vec<u64> = (first..500).collect();
+    assert_eq!(seen, expected);
+}
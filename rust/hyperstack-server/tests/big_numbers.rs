@@ -0,0 +1,135 @@
+//! Encoding of 64-bit integers, per server and per subscription.
+//!
+//! The spec is built from hand-written bytecode whose `Round` entity types
+//! `state.total` as a `u64`. Its parser forwards the batches the test sends,
+//! so the test plays the VM.
+
+mod common;
+
+use common::Client;
+use hyperstack_interpreter::compiler::EntityBytecode;
+use hyperstack_server::{BackgroundHandle, BigNumberMode, MutationBatch, Server};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+
+/// One past `Number.MAX_SAFE_INTEGER`
+const UNSAFE: u64 = 1 << 53;
+
+async fn serve(
+    mode: BigNumberMode,
+) -> (
+    SocketAddr,
+    BackgroundHandle,
+    mpsc::UnboundedSender<MutationBatch>,
+) {
+    let round = EntityBytecode {
+        state_id: 0,
+        handlers: Default::default(),
+        entity_name: "Round".to_string(),
+        when_events: Default::default(),
+        non_emitted_fields: Default::default(),
+        wide_integer_fields: ["state.total".to_string()].into_iter().collect(),
+        computed_paths: vec![],
+        trace_fields: vec![],
+        priority: Default::default(),
+        delete_on: None,
+        event_schemas: Default::default(),
+        computed_fields_evaluator: None,
+    };
+    let (mut spec, parser_tx) = common::forwarding_spec();
+    spec.bytecode.entities.insert("Round".to_string(), round);
+
+    let (addr, background) = common::serve(Server::builder().spec(spec).big_numbers(mode)).await;
+    (addr, background, parser_tx)
+}
+
+fn round_batch(patch: Value) -> MutationBatch {
+    common::batch("Round", "1", patch)
+}
+
+/// The next frame that is not a snapshot
+async fn next_update(ws: &mut Client) -> Value {
+    loop {
+        let frame = common::next_json(ws).await;
+        if frame["op"] != json!("snapshot") {
+            return frame;
+        }
+    }
+}
+
+/// Subscribe to `Round/list`, returning the client and its ack
+async fn subscribe(addr: SocketAddr, subscription: Value) -> (Client, Value) {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    let mut message = json!({ "type": "subscribe", "view": "Round/list" });
+    message
+        .as_object_mut()
+        .unwrap()
+        .extend(subscription.as_object().unwrap().clone());
+    common::send(&mut ws, message).await;
+
+    let subscribed = common::next_json(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"), "{subscribed}");
+    (ws, subscribed)
+}
+
+#[tokio::test]
+async fn subscriptions_can_ask_for_typed_integers_as_strings() {
+    let (addr, background, parser_tx) = serve(BigNumberMode::Auto).await;
+
+    let (mut plain, ack) = subscribe(addr, json!({})).await;
+    assert!(ack.get("bigNumbers").is_none());
+    let (mut strings, ack) = subscribe(addr, json!({ "bigNumbers": "string" })).await;
+    assert_eq!(ack["bigNumbers"], json!("string"));
+    let (_, ack) = subscribe(addr, json!({ "bigNumbers": "number" })).await;
+    assert_eq!(
+        ack["bigNumbers"],
+        json!("auto"),
+        "looser modes are not granted"
+    );
+
+    parser_tx
+        .send(round_batch(
+            json!({ "state": { "total": 5, "deployed": UNSAFE } }),
+        ))
+        .unwrap();
+
+    let frame = next_update(&mut plain).await;
+    assert_eq!(
+        frame["data"],
+        json!({ "state": { "total": 5, "deployed": "9007199254740992" } })
+    );
+    let frame = next_update(&mut strings).await;
+    assert_eq!(
+        frame["data"],
+        json!({ "state": { "total": "5", "deployed": "9007199254740992" } })
+    );
+
+    // Snapshots of later subscribers are encoded the same way
+    let (mut late, _) = subscribe(
+        addr,
+        json!({ "bigNumbers": "string", "withSnapshot": true }),
+    )
+    .await;
+    let snapshot = common::next_json(&mut late).await;
+    assert_eq!(snapshot["op"], json!("snapshot"));
+    assert_eq!(snapshot["data"][0]["data"]["state"]["total"], json!("5"));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn number_mode_sends_integers_as_they_are() {
+    let (addr, background, parser_tx) = serve(BigNumberMode::Number).await;
+    let (mut client, ack) = subscribe(addr, json!({ "bigNumbers": "number" })).await;
+    assert_eq!(ack["bigNumbers"], json!("number"));
+
+    parser_tx
+        .send(round_batch(json!({ "state": { "total": UNSAFE } })))
+        .unwrap();
+
+    let frame = next_update(&mut client).await;
+    assert_eq!(frame["data"], json!({ "state": { "total": UNSAFE } }));
+
+    background.shutdown();
+}
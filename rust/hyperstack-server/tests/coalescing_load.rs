@@ -0,0 +1,157 @@
+//! Load tests for the projector's coalescing window at three traffic levels.
+//!
+//! Each run feeds `Price` mutations for a fixed set of entities into a
+//! projector and reads the published frames off the list bus. Patches carry
+//! the time they were sent, so the latency of a frame is the age of the
+//! newest update it contains.
+
+mod common;
+
+use hyperstack_server::{
+    BusManager, CoalesceConfig, EntityCache, Mode, MutationBatch, Projector, ViewIndex,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+
+const VIEW: &str = "Price/list";
+const ENTITIES: u64 = 32;
+const MIN_WINDOW: Duration = Duration::from_millis(1);
+const MAX_WINDOW: Duration = Duration::from_millis(40);
+
+fn adaptive() -> CoalesceConfig {
+    CoalesceConfig::new()
+        .with_window_bounds(MIN_WINDOW, MAX_WINDOW)
+        .with_batch_budget(Duration::from_millis(20))
+}
+
+fn fixed_large() -> CoalesceConfig {
+    CoalesceConfig::fixed(MAX_WINDOW)
+}
+
+struct Run {
+    latencies: Vec<Duration>,
+    elapsed: Duration,
+    mutations: u64,
+}
+
+impl Run {
+    fn p99(&self) -> Duration {
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        sorted[(sorted.len() * 99 / 100).min(sorted.len() - 1)]
+    }
+
+    fn throughput(&self) -> f64 {
+        self.mutations as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Send `total` mutations, `per_tick` of them every `tick` (all at once when
+/// `tick` is zero), and wait until the last one has been published.
+async fn run(config: CoalesceConfig, total: u64, per_tick: u64, tick: Duration) -> Run {
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view(VIEW, "Price", Mode::List));
+
+    let bus_manager = BusManager::new();
+    let mut frames = bus_manager.get_or_create_list_bus(VIEW).await;
+    let (mutations_tx, mutations_rx) = mpsc::channel::<MutationBatch>(1024);
+    let projector = Projector::new(
+        Arc::new(views),
+        bus_manager.clone(),
+        EntityCache::new(),
+        mutations_rx,
+    )
+    .with_coalescing(config);
+    tokio::spawn(projector.run());
+
+    let epoch = Instant::now();
+    let producer = tokio::spawn(async move {
+        let mut ticker = (!tick.is_zero()).then(|| tokio::time::interval(tick));
+        for n in 0..total {
+            if n % per_tick == 0 {
+                if let Some(ticker) = ticker.as_mut() {
+                    ticker.tick().await;
+                }
+            }
+            let batch = common::batch(
+                "Price",
+                &format!("asset-{}", n % ENTITIES),
+                json!({ "n": n, "sent_us": epoch.elapsed().as_micros() as u64 }),
+            );
+            mutations_tx.send(batch).await.unwrap();
+        }
+    });
+
+    let mut latencies = Vec::new();
+    let deadline = Duration::from_secs(30);
+    loop {
+        let message = match tokio::time::timeout(deadline, frames.recv()).await {
+            Ok(Ok(message)) => message,
+            Ok(Err(RecvError::Lagged(_))) => continue,
+            Ok(Err(RecvError::Closed)) | Err(_) => panic!("projector stopped publishing"),
+        };
+        let frame: Value = serde_json::from_slice(&message.payload).unwrap();
+        let sent_us = frame["data"]["sent_us"].as_u64().unwrap();
+        latencies.push(epoch.elapsed() - Duration::from_micros(sent_us));
+        if frame["data"]["n"] == json!(total - 1) {
+            break;
+        }
+    }
+    producer.await.unwrap();
+
+    Run {
+        latencies,
+        elapsed: epoch.elapsed(),
+        mutations: total,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn low_volume_latency_stays_near_the_minimum_window() {
+    let adaptive = run(adaptive(), 200, 1, Duration::from_millis(5)).await;
+    let fixed = run(fixed_large(), 200, 1, Duration::from_millis(5)).await;
+
+    assert!(
+        adaptive.p99() < MAX_WINDOW / 4,
+        "adaptive p99 {:?} should stay near the {:?} minimum",
+        adaptive.p99(),
+        MIN_WINDOW
+    );
+    assert!(
+        fixed.p99() >= MAX_WINDOW / 2,
+        "fixed window p99 {:?} should reflect the {:?} window",
+        fixed.p99(),
+        MAX_WINDOW
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn medium_volume_keeps_up_within_the_maximum_window() {
+    // About 4000 mutations per second for half a second
+    let adaptive = run(adaptive(), 2_000, 4, Duration::from_millis(1)).await;
+
+    assert!(
+        adaptive.p99() < MAX_WINDOW * 2,
+        "adaptive p99 {:?} should stay within the window bounds",
+        adaptive.p99()
+    );
+    assert!(adaptive.elapsed < Duration::from_secs(5));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn high_volume_throughput_matches_the_fixed_large_window() {
+    let adaptive = run(adaptive(), 50_000, 1, Duration::ZERO).await;
+    let fixed = run(fixed_large(), 50_000, 1, Duration::ZERO).await;
+
+    assert!(
+        adaptive.throughput() >= fixed.throughput() * 0.7,
+        "adaptive throughput {:.0}/s should match the fixed window's {:.0}/s",
+        adaptive.throughput(),
+        fixed.throughput()
+    );
+    // Merging kept the frame count far below the mutation count
+    assert!(adaptive.latencies.len() < 50_000 / 4);
+}
@@ -0,0 +1,174 @@
+//! Harness shared by the WebSocket integration tests: a spec whose parser is
+//! fed from a channel, a server on an ephemeral port and frame helpers.
+
+// Each test crate uses its own subset of the helpers
+#![allow(dead_code)]
+
+use axum::Router;
+use flate2::read::GzDecoder;
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_interpreter::compiler::MultiEntityBytecode;
+use hyperstack_interpreter::Mutation;
+use hyperstack_server::{
+    BackgroundHandle, Delivery, Filters, Mode, MutationBatch, ParserSetupFn, Projection, Runtime,
+    ServerBuilder, Spec, ViewSpec,
+};
+use serde_json::{json, Value};
+use smallvec::smallvec;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+pub type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// How long [`next_json`] waits for a frame
+pub const FRAME_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A batch with one mutation patching `export`'s entity `key`
+pub fn batch(export: &str, key: &str, patch: Value) -> MutationBatch {
+    MutationBatch::new(smallvec![Mutation::new(export, json!(key), patch)])
+}
+
+/// A view over every field of `export`
+pub fn view(id: &str, export: &str, mode: Mode) -> ViewSpec {
+    ViewSpec {
+        id: id.to_string(),
+        export: export.to_string(),
+        mode,
+        projection: Projection::all(),
+        filters: Filters::all(),
+        delivery: Delivery::default(),
+        pipeline: None,
+        source_view: None,
+    }
+}
+
+/// A spec whose parser forwards the batches sent on the returned channel.
+/// A restarted parser picks up where the previous one stopped.
+pub fn forwarding_spec() -> (Spec, mpsc::UnboundedSender<MutationBatch>) {
+    let (batches_tx, batches_rx) = mpsc::unbounded_channel();
+    let batches_rx = Arc::new(tokio::sync::Mutex::new(batches_rx));
+    let setup: ParserSetupFn = Arc::new(move |mutations_tx, _health, _reconnection| {
+        let batches_rx = batches_rx.clone();
+        Box::pin(async move {
+            let mut batches_rx = batches_rx.lock().await;
+            while let Some(batch) = batches_rx.recv().await {
+                mutations_tx.send(batch).await?;
+            }
+            std::future::pending::<()>().await;
+            Ok(())
+        })
+    });
+
+    let spec =
+        Spec::new(MultiEntityBytecode::new().build(), "test_program").with_parser_setup(setup);
+    (spec, batches_tx)
+}
+
+/// Serve the server built by `builder` under `/stream` on an ephemeral port
+pub async fn serve(builder: ServerBuilder) -> (SocketAddr, BackgroundHandle) {
+    serve_runtime(builder.build().expect("runtime should build")).await
+}
+
+/// Serve `runtime` under `/stream` on an ephemeral port
+pub async fn serve_runtime(runtime: Runtime) -> (SocketAddr, BackgroundHandle) {
+    let (stream_router, background) = runtime.into_router_parts();
+    let background = background.spawn_background(&tokio::runtime::Handle::current());
+
+    let app = Router::new().nest("/stream", stream_router);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+
+    (addr, background)
+}
+
+/// GET `path` from the server at `addr` and parse the body as JSON
+pub async fn http_get_json(addr: SocketAddr, path: &str) -> Value {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (_, body) = response
+        .split_once("\r\n\r\n")
+        .expect("response should have a body");
+    serde_json::from_str(body).expect("body should be json")
+}
+
+/// Poll `/stream/stats` until `ready` holds, failing with `what` after 5s
+pub async fn wait_for_stats(addr: SocketAddr, what: &str, ready: impl Fn(&Value) -> bool) {
+    for _ in 0..250 {
+        let stats = http_get_json(addr, "/stream/stats").await;
+        if ready(&stats) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("{what}");
+}
+
+/// Open a WebSocket to `url`, waiting for the server to start listening
+pub async fn connect(url: &str) -> Client {
+    for _ in 0..100 {
+        if let Ok((ws, _)) = tokio_tungstenite::connect_async(url).await {
+            return ws;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("server should accept a websocket at {url}");
+}
+
+pub async fn send(ws: &mut Client, message: Value) {
+    ws.send(Message::Text(message.to_string().into()))
+        .await
+        .unwrap();
+}
+
+/// The next data frame as JSON, gunzipped if compressed, or `None` if none
+/// arrives within `wait`. Control frames are skipped.
+pub async fn try_next_json(ws: &mut Client, wait: Duration) -> Option<Value> {
+    loop {
+        let message = tokio::time::timeout(wait, ws.next())
+            .await
+            .ok()?
+            .unwrap()
+            .unwrap();
+        let bytes = match message {
+            Message::Binary(bytes) => bytes.to_vec(),
+            Message::Text(text) => text.as_bytes().to_vec(),
+            _ => continue,
+        };
+        let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(bytes.as_slice())
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            decompressed
+        } else {
+            bytes
+        };
+        return Some(serde_json::from_slice(&bytes).unwrap());
+    }
+}
+
+/// The next data frame as JSON, failing the test if none arrives
+pub async fn next_json(ws: &mut Client) -> Value {
+    try_next_json(ws, FRAME_TIMEOUT)
+        .await
+        .expect("frame should arrive")
+}
@@ -0,0 +1,239 @@
+//! zstd compression with a trained dictionary, negotiated per connection.
+//!
+//! The spec is built from hand-written bytecode with a single `Round`
+//! entity. Its parser forwards the batches the test sends, so the test plays
+//! the VM. The dictionary is trained by the test on frames like the ones the
+//! server sends and loaded from a file.
+
+mod common;
+
+use common::Client;
+use flate2::read::GzDecoder;
+use futures_util::StreamExt;
+use hyperstack_interpreter::compiler::EntityBytecode;
+use hyperstack_server::{BackgroundHandle, DictionarySource, MutationBatch, Server};
+use serde_json::{json, Value};
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+fn round_patch(round: u64) -> Value {
+    json!({
+        "state": { "total_deployed": round * 7_919, "motherlode": round.is_multiple_of(13) },
+        "results": { "winning_square": round % 25, "top_miner": format!("Miner{:04}", round * 31 % 997) }
+    })
+}
+
+/// A dictionary trained on frames shaped like the server's `Round` patches
+fn train_dictionary() -> PathBuf {
+    let samples: Vec<Vec<u8>> = (0..1_000)
+        .map(|round| {
+            json!({
+                "mode": "list",
+                "entity": "Round/list",
+                "op": "patch",
+                "key": round.to_string(),
+                "data": round_patch(round),
+                "append": [],
+                "sub": "Round/list:*",
+                "frameSeq": round + 2,
+            })
+            .to_string()
+            .into_bytes()
+        })
+        .collect();
+    let dictionary = zstd::dict::from_samples(&samples, 16 * 1024).unwrap();
+    let path = std::env::temp_dir().join(format!(
+        "hyperstack-dictionary-test-{}.dict",
+        uuid::Uuid::new_v4()
+    ));
+    std::fs::write(&path, dictionary).unwrap();
+    path
+}
+
+async fn serve() -> (
+    SocketAddr,
+    BackgroundHandle,
+    mpsc::UnboundedSender<MutationBatch>,
+) {
+    let round = EntityBytecode {
+        state_id: 0,
+        handlers: Default::default(),
+        entity_name: "Round".to_string(),
+        when_events: Default::default(),
+        non_emitted_fields: Default::default(),
+        wide_integer_fields: Default::default(),
+        computed_paths: vec![],
+        trace_fields: vec![],
+        priority: Default::default(),
+        delete_on: None,
+        event_schemas: Default::default(),
+        computed_fields_evaluator: None,
+    };
+    let (mut spec, parser_tx) = common::forwarding_spec();
+    spec.bytecode.entities.insert("Round".to_string(), round);
+
+    let (addr, background) = common::serve(
+        Server::builder()
+            .spec(spec)
+            .compression_dictionary(DictionarySource::File(train_dictionary())),
+    )
+    .await;
+    (addr, background, parser_tx)
+}
+
+fn round_batch(round: u64, patch: Value) -> MutationBatch {
+    common::batch("Round", &round.to_string(), patch)
+}
+
+/// How a frame arrived
+#[derive(Debug, PartialEq)]
+enum Encoding {
+    Plain,
+    Gzip,
+    /// zstd, with the id of the dictionary it needs
+    Zstd(Option<u32>),
+}
+
+/// The next message, decompressed, and how it was encoded
+async fn next_message(ws: &mut Client, dictionary: &[u8]) -> (Value, Encoding) {
+    let message = tokio::time::timeout(common::FRAME_TIMEOUT, ws.next())
+        .await
+        .expect("message should arrive")
+        .unwrap()
+        .unwrap();
+    let bytes = match message {
+        Message::Binary(bytes) => bytes.to_vec(),
+        Message::Text(text) => text.as_bytes().to_vec(),
+        other => panic!("expected a frame, got {other:?}"),
+    };
+
+    let (bytes, encoding) = if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        (decompressed, Encoding::Gzip)
+    } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        let dict_id = zstd::zstd_safe::get_dict_id_from_frame(&bytes).map(|id| id.get());
+        let mut decompressor = match dict_id {
+            Some(_) => zstd::bulk::Decompressor::with_dictionary(dictionary).unwrap(),
+            None => zstd::bulk::Decompressor::new().unwrap(),
+        };
+        let decompressed = decompressor.decompress(&bytes, 1 << 20).unwrap();
+        (decompressed, Encoding::Zstd(dict_id))
+    } else {
+        (bytes, Encoding::Plain)
+    };
+    (serde_json::from_slice(&bytes).unwrap(), encoding)
+}
+
+/// The next frame that is not a snapshot
+async fn next_update(ws: &mut Client, dictionary: &[u8]) -> (Value, Encoding) {
+    loop {
+        let (frame, encoding) = next_message(ws, dictionary).await;
+        if frame["op"] != json!("snapshot") {
+            return (frame, encoding);
+        }
+    }
+}
+
+/// Connect, returning the client and the `hello` it opened with
+async fn connect(addr: SocketAddr) -> (Client, Value) {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    let (hello, encoding) = next_message(&mut ws, &[]).await;
+    assert_eq!(hello["type"], json!("hello"), "{hello}");
+    assert_eq!(encoding, Encoding::Plain);
+    (ws, hello)
+}
+
+async fn subscribe(ws: &mut Client, dictionary: &[u8]) {
+    common::send(ws, json!({ "type": "subscribe", "view": "Round/list" })).await;
+    let (subscribed, _) = next_message(ws, dictionary).await;
+    assert_eq!(subscribed["op"], json!("subscribed"), "{subscribed}");
+}
+
+/// Send a request and return the status code and body
+async fn request(addr: SocketAddr, path: &str) -> (u16, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .expect("response should have a body");
+    let head = String::from_utf8_lossy(&response[..split]);
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, response[split + 4..].to_vec())
+}
+
+#[tokio::test]
+async fn clients_that_fetch_the_dictionary_get_zstd_frames_compressed_with_it() {
+    let (addr, background, parser_tx) = serve().await;
+
+    let (mut client, hello) = connect(addr).await;
+    assert_eq!(hello["compression"]["zstd"], json!(true));
+    let id = hello["compression"]["dictionary"]
+        .as_str()
+        .expect("hello should name the dictionary")
+        .to_string();
+
+    let (status, dictionary) = request(addr, &format!("/stream/dictionaries/{id}")).await;
+    assert_eq!(status, 200);
+    let dict_id = zstd::zstd_safe::get_dict_id(&dictionary).map(|id| id.get());
+    assert!(dict_id.is_some());
+    let (status, _) = request(addr, "/stream/dictionaries/not-a-dictionary").await;
+    assert_eq!(status, 404);
+
+    common::send(
+        &mut client,
+        json!({ "type": "compression", "codec": "zstd", "dictionary": id }),
+    )
+    .await;
+    subscribe(&mut client, &dictionary).await;
+
+    let (mut legacy, _) = connect(addr).await;
+    subscribe(&mut legacy, &[]).await;
+
+    parser_tx.send(round_batch(7, round_patch(7))).unwrap();
+
+    let (frame, encoding) = next_update(&mut client, &dictionary).await;
+    assert_eq!(encoding, Encoding::Zstd(dict_id));
+    assert_eq!(frame["data"], round_patch(7));
+
+    // Clients that don't negotiate keep getting small frames as they are
+    let (frame, encoding) = next_update(&mut legacy, &[]).await;
+    assert_eq!(encoding, Encoding::Plain);
+    assert_eq!(frame["data"], round_patch(7));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn unknown_dictionaries_fall_back_to_zstd_without_one() {
+    let (addr, background, parser_tx) = serve().await;
+
+    let (mut client, _) = connect(addr).await;
+    common::send(
+        &mut client,
+        json!({ "type": "compression", "codec": "zstd", "dictionary": "stale-0" }),
+    )
+    .await;
+    subscribe(&mut client, &[]).await;
+
+    let large = json!({ "notes": "the round went on and on ".repeat(100) });
+    parser_tx.send(round_batch(8, large.clone())).unwrap();
+
+    let (frame, encoding) = next_update(&mut client, &[]).await;
+    assert_eq!(encoding, Encoding::Zstd(None));
+    assert_eq!(frame["data"], large);
+
+    background.shutdown();
+}
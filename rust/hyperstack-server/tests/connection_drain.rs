@@ -0,0 +1,114 @@
+mod common;
+
+use futures_util::StreamExt;
+use hyperstack_server::Server;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+async fn http_request(addr: SocketAddr, method: &str, path: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request =
+        format!("{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let status = response[9..12]
+        .parse()
+        .expect("response should have a status");
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+    (status, body)
+}
+
+#[tokio::test]
+async fn client_is_told_to_migrate_and_closed_after_grace() {
+    let (addr, background) = common::serve(Server::builder()).await;
+    let drain = background.drain_controller();
+
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    for _ in 0..100 {
+        if drain.connection_count() == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(drain.connection_count(), 1);
+
+    let (status, body) = http_request(addr, "POST", "/stream/admin/drain?grace=1s").await;
+    let drain_started = Instant::now();
+    assert_eq!(status, 202);
+    let report: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["draining"], json!(true));
+    assert_eq!(report["connections"], json!(1));
+
+    let (status, body) = http_request(addr, "GET", "/stream/ready").await;
+    assert_eq!(status, 503);
+    assert_eq!(body, "DRAINING");
+
+    match tokio_tungstenite::connect_async(format!("ws://{addr}/stream")).await {
+        Err(WsError::Http(response)) => {
+            assert_eq!(response.status().as_u16(), 503);
+            assert_eq!(response.headers()["X-Error-Code"], "draining");
+        }
+        other => panic!("upgrade should be refused while draining, got {other:?}"),
+    }
+
+    let notice = common::try_next_json(&mut ws, Duration::from_secs(1))
+        .await
+        .expect("migrate_soon should arrive promptly");
+    assert_eq!(notice["type"], json!("migrate_soon"));
+    assert!(notice["reconnect_after_ms"].as_u64().unwrap() < 500);
+    assert!(notice["close_in_ms"].as_u64().unwrap() <= 1000);
+
+    // The client stays connected for the whole grace period
+    let close = tokio::time::timeout(Duration::from_secs(3), ws.next())
+        .await
+        .expect("client should be closed after the grace period")
+        .unwrap()
+        .unwrap();
+    assert!(drain_started.elapsed() >= Duration::from_millis(900));
+    match close {
+        Message::Close(Some(frame)) => {
+            assert_eq!(frame.code, CloseCode::Away);
+            let reason: Value = serde_json::from_str(frame.reason.as_str()).unwrap();
+            assert_eq!(reason["code"], json!("draining"));
+            assert_eq!(reason["retry_after_ms"], notice["reconnect_after_ms"]);
+        }
+        other => panic!("expected a close frame, got {other:?}"),
+    }
+
+    // Reading past the close frame sends the client's acknowledgement
+    assert!(ws.next().await.is_none());
+    tokio::time::timeout(Duration::from_secs(1), drain.drained())
+        .await
+        .expect("drain should finish once the client acknowledges the close");
+
+    let (status, body) = http_request(addr, "GET", "/stream/admin/drain").await;
+    assert_eq!(status, 200);
+    let report: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["connections"], json!(0));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn rejects_invalid_grace_period() {
+    let (addr, background) = common::serve(Server::builder()).await;
+
+    let (status, _) = http_request(addr, "POST", "/stream/admin/drain?grace=soon").await;
+    assert_eq!(status, 400);
+    assert!(!background.drain_controller().is_draining());
+
+    let (status, _) = http_request(addr, "GET", "/stream/ready").await;
+    assert_eq!(status, 200);
+
+    background.shutdown();
+}
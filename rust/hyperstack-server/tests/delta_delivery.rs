@@ -0,0 +1,85 @@
+//! Delta subscriptions to a list view whose entity is rewritten whole on
+//! every update: the first frame carries the entity, later frames only the
+//! fields that changed.
+
+mod common;
+
+use common::Client;
+use futures_util::StreamExt;
+use hyperstack_server::{Mode, Server, ViewIndex};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use tokio_tungstenite::tungstenite::Message;
+
+/// The curve as the parser writes it, whole, on every update
+fn curve_writes() -> Vec<Value> {
+    vec![
+        json!({ "mint": "mint-1", "reserves": { "sol": 10, "token": 900 }, "complete": false }),
+        json!({ "mint": "mint-1", "reserves": { "sol": 12, "token": 900 }, "complete": false }),
+        json!({ "mint": "mint-1", "reserves": { "sol": 12, "token": 900 }, "complete": false }),
+    ]
+}
+
+fn views() -> ViewIndex {
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view("Curve/list", "Curve", Mode::List));
+    views
+}
+
+/// The next data frame and its size on the wire
+async fn next_frame(ws: &mut Client) -> (Value, usize) {
+    let message = tokio::time::timeout(common::FRAME_TIMEOUT, ws.next())
+        .await
+        .expect("frame should arrive")
+        .unwrap()
+        .unwrap();
+    match message {
+        Message::Binary(bytes) => (serde_json::from_slice(&bytes).unwrap(), bytes.len()),
+        Message::Text(text) => (serde_json::from_str(text.as_str()).unwrap(), text.len()),
+        other => panic!("expected a data frame, got {other:?}"),
+    }
+}
+
+async fn subscribe(addr: SocketAddr, subscription: Value) -> Client {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    let mut message = json!({ "type": "subscribe", "view": "Curve/list", "withSnapshot": false });
+    message
+        .as_object_mut()
+        .unwrap()
+        .extend(subscription.as_object().unwrap().clone());
+    common::send(&mut ws, message).await;
+
+    let (subscribed, _) = next_frame(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"));
+    ws
+}
+
+#[tokio::test]
+async fn delta_subscribers_only_get_changed_fields() {
+    let (spec, batches) = common::forwarding_spec();
+    let (addr, background) = common::serve(Server::builder().spec(spec).views(views())).await;
+
+    let mut plain = subscribe(addr, json!({})).await;
+    let mut delta = subscribe(addr, json!({ "delta": true })).await;
+    for patch in curve_writes() {
+        batches
+            .send(common::batch("Curve", "curve-1", patch))
+            .unwrap();
+    }
+
+    next_frame(&mut plain).await;
+    let (first, _) = next_frame(&mut delta).await;
+    assert_eq!(first["key"], json!("curve-1"));
+    assert_eq!(first["data"], curve_writes()[0]);
+
+    let expected = [json!({ "reserves": { "sol": 12 } }), json!({})];
+    for expected in expected {
+        let (_, plain_size) = next_frame(&mut plain).await;
+        let (frame, delta_size) = next_frame(&mut delta).await;
+        assert_eq!(frame["op"], json!("patch"));
+        assert_eq!(frame["data"], expected);
+        assert!(delta_size < plain_size, "{delta_size} >= {plain_size}");
+    }
+
+    background.shutdown();
+}
@@ -0,0 +1,172 @@
+//! A client's subscriptions to the same view with different ids run side by
+//! side, each with its own projection and stamped with its own `sub`.
+
+mod common;
+
+use common::{
+    batch, connect, forwarding_spec, next_json, send, serve, try_next_json, view, Client,
+};
+use hyperstack_server::{BackgroundHandle, Mode, Server, Spec, ViewIndex};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+async fn serve_views(spec: Spec) -> (SocketAddr, BackgroundHandle) {
+    let mut views = ViewIndex::new();
+    views.add_spec(view("Token/list", "Token", Mode::List));
+    views.add_spec(view("Trade/append", "Trade", Mode::Append));
+    serve(Server::builder().spec(spec).views(views)).await
+}
+
+async fn subscribe(ws: &mut Client, message: Value) {
+    let mut subscribe = json!({ "type": "subscribe", "withSnapshot": false });
+    subscribe
+        .as_object_mut()
+        .unwrap()
+        .extend(message.as_object().unwrap().clone());
+    send(ws, subscribe).await;
+    let ack = next_json(ws).await;
+    assert_eq!(ack["op"], json!("subscribed"), "{ack}");
+}
+
+/// The data of the next `count` frames, by the subscription they belong to
+async fn frames_by_sub(ws: &mut Client, count: usize) -> HashMap<String, Value> {
+    let mut frames = HashMap::new();
+    for _ in 0..count {
+        let frame = next_json(ws).await;
+        let sub = frame["sub"]
+            .as_str()
+            .expect("frames carry their sub")
+            .to_string();
+        assert!(
+            frames.insert(sub.clone(), frame["data"].clone()).is_none(),
+            "two frames for {sub}"
+        );
+    }
+    frames
+}
+
+#[tokio::test]
+async fn overlapping_subscriptions_keep_their_own_projections() {
+    let (spec, batches) = forwarding_spec();
+    let (addr, background) = serve_views(spec).await;
+    let mut ws = connect(&format!("ws://{addr}/stream")).await;
+
+    subscribe(
+        &mut ws,
+        json!({ "view": "Token/list", "fields": ["price"] }),
+    )
+    .await;
+    subscribe(
+        &mut ws,
+        json!({ "view": "Token/list", "id": "names", "fields": ["name"] }),
+    )
+    .await;
+    subscribe(
+        &mut ws,
+        json!({ "view": "Trade/append", "id": "sizes", "fields": ["size"] }),
+    )
+    .await;
+    subscribe(
+        &mut ws,
+        json!({ "view": "Trade/append", "id": "sides", "fields": ["side"] }),
+    )
+    .await;
+
+    batches
+        .send(batch(
+            "Token",
+            "token-1",
+            json!({ "name": "Token", "price": 1 }),
+        ))
+        .unwrap();
+    batches
+        .send(batch(
+            "Trade",
+            "trade-1",
+            json!({ "size": 5, "side": "buy" }),
+        ))
+        .unwrap();
+    let frames = frames_by_sub(&mut ws, 4).await;
+    assert_eq!(frames["Token/list:*"], json!({ "price": 1 }));
+    assert_eq!(frames["Token/list:*#names"], json!({ "name": "Token" }));
+    assert_eq!(frames["Trade/append:*#sizes"], json!({ "size": 5 }));
+    assert_eq!(frames["Trade/append:*#sides"], json!({ "side": "buy" }));
+
+    // Unsubscribing one id leaves the other subscription to the view running
+    send(
+        &mut ws,
+        json!({ "type": "unsubscribe", "view": "Token/list", "id": "names" }),
+    )
+    .await;
+    send(
+        &mut ws,
+        json!({ "type": "unsubscribe", "view": "Trade/append", "id": "sizes" }),
+    )
+    .await;
+    // Messages are handled in order, so this ack follows the unsubscribes
+    subscribe(&mut ws, json!({ "view": "Token/list", "key": "unused" })).await;
+    batches
+        .send(batch(
+            "Token",
+            "token-1",
+            json!({ "name": "Renamed", "price": 2 }),
+        ))
+        .unwrap();
+    batches
+        .send(batch(
+            "Trade",
+            "trade-2",
+            json!({ "size": 6, "side": "sell" }),
+        ))
+        .unwrap();
+    let frames = frames_by_sub(&mut ws, 2).await;
+    assert_eq!(frames["Token/list:*"], json!({ "price": 2 }));
+    assert_eq!(frames["Trade/append:*#sides"], json!({ "side": "sell" }));
+    assert!(
+        try_next_json(&mut ws, Duration::from_millis(200))
+            .await
+            .is_none(),
+        "unsubscribed ids should stay quiet"
+    );
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn subscribing_again_with_the_same_id_replaces_the_options() {
+    let (spec, batches) = forwarding_spec();
+    let (addr, background) = serve_views(spec).await;
+    let mut ws = connect(&format!("ws://{addr}/stream")).await;
+
+    subscribe(
+        &mut ws,
+        json!({ "view": "Token/list", "id": "ticker", "fields": ["price"] }),
+    )
+    .await;
+    subscribe(
+        &mut ws,
+        json!({ "view": "Token/list", "id": "ticker", "fields": ["name"] }),
+    )
+    .await;
+
+    batches
+        .send(batch(
+            "Token",
+            "token-1",
+            json!({ "name": "Token", "price": 1 }),
+        ))
+        .unwrap();
+    let frame = next_json(&mut ws).await;
+    assert_eq!(frame["sub"], json!("Token/list:*#ticker"));
+    assert_eq!(frame["data"], json!({ "name": "Token" }));
+    assert!(
+        try_next_json(&mut ws, Duration::from_millis(200))
+            .await
+            .is_none(),
+        "the replaced subscription should stay quiet"
+    );
+
+    background.shutdown();
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use axum::routing::get;
+use axum::Router;
+use hyperstack_server::{BackgroundHandle, Server};
+use serde_json::json;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Mount the HyperStack router under `/stream` in an application-owned router
+async fn serve_app() -> (SocketAddr, BackgroundHandle) {
+    let (stream_router, background) = Server::builder()
+        .build()
+        .expect("runtime should build")
+        .into_router_parts();
+    let background = background.spawn_background(&tokio::runtime::Handle::current());
+
+    let app = Router::new()
+        .route("/", get(|| async { "app" }))
+        .nest("/stream", stream_router);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+
+    (addr, background)
+}
+
+async fn http_get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn serves_health_and_stats_under_mount_path() {
+    let (addr, background) = serve_app().await;
+
+    let app_response = http_get(addr, "/").await;
+    assert!(app_response.starts_with("HTTP/1.1 200"));
+    assert!(app_response.ends_with("app"));
+
+    let health_response = http_get(addr, "/stream/health").await;
+    assert!(health_response.starts_with("HTTP/1.1 200"));
+    assert!(health_response.ends_with("OK"));
+
+    let ready_response = http_get(addr, "/stream/ready").await;
+    assert!(ready_response.ends_with("READY"));
+
+    let stats_response = http_get(addr, "/stream/stats").await;
+    assert!(stats_response.starts_with("HTTP/1.1 200"));
+    assert!(stats_response.contains(r#""clients":0"#));
+
+    let not_upgrade = http_get(addr, "/stream").await;
+    assert!(not_upgrade.starts_with("HTTP/1.1 426"));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn accepts_websocket_clients_under_mount_path() {
+    let (addr, background) = serve_app().await;
+
+    let (mut ws, response) = tokio_tungstenite::connect_async(format!("ws://{addr}/stream"))
+        .await
+        .expect("websocket handshake should succeed");
+    assert_eq!(response.status().as_u16(), 101);
+
+    common::send(&mut ws, json!({ "type": "ping" })).await;
+    common::wait_for_stats(
+        addr,
+        "client should be registered with the runtime",
+        |stats| stats["clients"] == json!(1),
+    )
+    .await;
+
+    ws.close(None).await.unwrap();
+    background.shutdown();
+}
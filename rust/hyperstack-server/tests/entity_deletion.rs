@@ -0,0 +1,133 @@
+mod common;
+
+use common::Client;
+use hyperstack_server::materialized_view::{SortConfig, SortOrder};
+use hyperstack_server::{
+    BackgroundHandle, Mode, MutationBatch, Server, SlotContext, ViewIndex, ViewPipeline, ViewSpec,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+
+fn batch(slot: u64, key: &str, patch: Value, tombstone: bool) -> MutationBatch {
+    let mut batch = common::batch("Token", key, patch);
+    batch.mutations[0].tombstone = tombstone;
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..batch
+    }
+}
+
+fn view(id: &str, mode: Mode, pipeline: Option<ViewPipeline>) -> ViewSpec {
+    ViewSpec {
+        source_view: pipeline.as_ref().map(|_| "Token/list".to_string()),
+        pipeline,
+        ..common::view(id, "Token", mode)
+    }
+}
+
+/// A server whose parser has sent tokens `a` and `b`, and the channel
+/// feeding it
+async fn serve() -> (
+    SocketAddr,
+    BackgroundHandle,
+    mpsc::UnboundedSender<MutationBatch>,
+) {
+    let (spec, batches) = common::forwarding_spec();
+    batches
+        .send(batch(1, "a", json!({ "supply": 1 }), false))
+        .unwrap();
+    batches
+        .send(batch(2, "b", json!({ "supply": 2 }), false))
+        .unwrap();
+
+    let top = ViewPipeline {
+        sort: Some(SortConfig {
+            field_path: vec!["supply".to_string()],
+            order: SortOrder::Desc,
+        }),
+        ..Default::default()
+    };
+    let mut views = ViewIndex::new();
+    views.add_spec(view("Token/list", Mode::List, None));
+    views.add_spec(view("Token/state", Mode::State, None));
+    views.add_spec(view("Token/top", Mode::List, Some(top)));
+
+    let (addr, background) = common::serve(Server::builder().spec(spec).views(views)).await;
+    (addr, background, batches)
+}
+
+async fn wait_for_entities(addr: SocketAddr, count: u64) {
+    common::wait_for_stats(
+        addr,
+        &format!("cache never held {count} entities"),
+        |stats| stats["cache"]["total_entities"] == json!(count),
+    )
+    .await;
+}
+
+/// Read frames until one matches `found`
+async fn frame_where(ws: &mut Client, found: impl Fn(&Value) -> bool) -> Value {
+    loop {
+        let frame = common::next_json(ws).await;
+        if found(&frame) {
+            return frame;
+        }
+    }
+}
+
+async fn subscribe(addr: SocketAddr, subscription: Value) -> Client {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    let mut message = json!({ "type": "subscribe" });
+    message
+        .as_object_mut()
+        .unwrap()
+        .extend(subscription.as_object().unwrap().clone());
+    common::send(&mut ws, message).await;
+    ws
+}
+
+fn snapshot_keys(snapshot: &Value) -> Vec<&str> {
+    snapshot["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entity| entity["key"].as_str().unwrap())
+        .collect()
+}
+
+fn is_delete_of(frame: &Value, key: &str) -> bool {
+    frame["op"] == json!("delete") && frame["key"] == json!(key)
+}
+
+#[tokio::test]
+async fn tombstone_deletes_the_entity_from_every_view() {
+    let (addr, background, batches) = serve().await;
+    // Both tokens in the list and state caches
+    wait_for_entities(addr, 4).await;
+
+    let mut list = subscribe(addr, json!({ "view": "Token/list" })).await;
+    let snapshot = frame_where(&mut list, |frame| frame["op"] == json!("snapshot")).await;
+    assert_eq!(snapshot_keys(&snapshot).len(), 2);
+    let mut state = subscribe(addr, json!({ "view": "Token/state", "key": "a" })).await;
+    frame_where(&mut state, |frame| frame["op"] == json!("snapshot")).await;
+    let mut top = subscribe(addr, json!({ "view": "Token/top" })).await;
+    let snapshot = frame_where(&mut top, |frame| frame["op"] == json!("snapshot")).await;
+    assert_eq!(snapshot_keys(&snapshot), ["b", "a"]);
+
+    batches.send(batch(3, "a", json!({}), true)).unwrap();
+
+    let deleted = frame_where(&mut list, |frame| frame["op"] != json!("snapshot")).await;
+    assert!(is_delete_of(&deleted, "a"), "{deleted}");
+    assert_eq!(deleted["data"], Value::Null);
+    let deleted = frame_where(&mut state, |frame| frame["op"] != json!("snapshot")).await;
+    assert!(is_delete_of(&deleted, "a"), "{deleted}");
+    frame_where(&mut top, |frame| is_delete_of(frame, "a")).await;
+
+    wait_for_entities(addr, 2).await;
+    let mut late = subscribe(addr, json!({ "view": "Token/list" })).await;
+    let snapshot = frame_where(&mut late, |frame| frame["op"] == json!("snapshot")).await;
+    assert_eq!(snapshot_keys(&snapshot), ["b"]);
+
+    background.shutdown();
+}
@@ -0,0 +1,113 @@
+//! Events the parser fails to process are kept by the dead-letter queue and
+//! served on `/admin/failed-events`.
+
+mod common;
+
+use hyperstack_interpreter::compiler::MultiEntityBytecode;
+use hyperstack_server::{
+    BackgroundHandle, DeadLetterConfig, FailedEvent, ParserSetupFn, Server, Spec,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Serves a runtime whose parser fails three `RoundState` events. Health
+/// monitoring is off, so the parser's monitor only carries the queue.
+async fn serve(config: DeadLetterConfig) -> (SocketAddr, BackgroundHandle) {
+    let setup: ParserSetupFn = Arc::new(move |_mutations_tx, health, _reconnection| {
+        Box::pin(async move {
+            let health = health.expect("the parser gets a monitor carrying the queue");
+            for slot in 1..=3 {
+                health.record_failed_event(FailedEvent::new(
+                    "RoundState",
+                    json!({ "round": slot }),
+                    "null key",
+                    slot,
+                    Some(format!("sig-{slot}")),
+                ));
+            }
+
+            std::future::pending::<()>().await;
+            Ok(())
+        })
+    });
+    let spec =
+        Spec::new(MultiEntityBytecode::new().build(), "test_program").with_parser_setup(setup);
+
+    common::serve(Server::builder().spec(spec).dead_letter(config)).await
+}
+
+/// Send a request and return the status code and body
+async fn request(addr: SocketAddr, method: &str, path: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .expect("response should have a body");
+    let head = String::from_utf8_lossy(&response[..split]);
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    let body = serde_json::from_slice(&response[split + 4..]).unwrap_or(Value::Null);
+    (status, body)
+}
+
+#[tokio::test]
+async fn failed_events_are_listed_and_drained() {
+    let hooked = Arc::new(Mutex::new(Vec::new()));
+    let hook_seen = hooked.clone();
+    let config = DeadLetterConfig::new()
+        .with_capacity(2)
+        .on_failed_event(move |event| hook_seen.lock().unwrap().push(event.slot));
+    let (addr, background) = serve(config).await;
+
+    let mut report = Value::Null;
+    for _ in 0..100 {
+        let (status, body) = request(addr, "GET", "/stream/admin/failed-events").await;
+        assert_eq!(status, 200);
+        report = body;
+        if report["dropped"] == json!(1) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(report["capacity"], json!(2));
+    assert_eq!(report["dropped"], json!(1), "{report}");
+    let events = report["events"].as_array().unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["eventType"], json!("RoundState"));
+    assert_eq!(events[0]["eventValue"], json!({ "round": 2 }));
+    assert_eq!(events[0]["error"], json!("null key"));
+    assert_eq!(events[0]["slot"], json!(2));
+    assert_eq!(events[0]["signature"], json!("sig-2"));
+    assert_eq!(events[1]["slot"], json!(3));
+    // The hook sees every failure, including the one the queue dropped
+    assert_eq!(*hooked.lock().unwrap(), [1, 2, 3]);
+
+    let (status, drained) = request(addr, "POST", "/stream/admin/failed-events").await;
+    assert_eq!(status, 200);
+    assert_eq!(drained["events"].as_array().unwrap().len(), 2);
+    let (_, after) = request(addr, "GET", "/stream/admin/failed-events").await;
+    assert_eq!(after["events"], json!([]));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn route_is_absent_without_a_dead_letter_queue() {
+    let spec = Spec::new(MultiEntityBytecode::new().build(), "test_program");
+    let (addr, background) = common::serve(Server::builder().spec(spec)).await;
+
+    let (status, _) = request(addr, "GET", "/stream/admin/failed-events").await;
+    assert_eq!(status, 404);
+
+    background.shutdown();
+}
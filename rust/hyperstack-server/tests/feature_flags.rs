@@ -0,0 +1,250 @@
+//! Feature flags switched on `/admin/flags` while clients are connected.
+//!
+//! The spec is built from hand-written bytecode with a single `Round`
+//! entity. Its parser forwards the batches the test sends, so the test plays
+//! the VM. The server offers zstd, with a dictionary that is never trained, so
+//! that live frames are compressed for clients that negotiate it.
+
+mod common;
+
+use common::Client;
+use futures_util::StreamExt;
+use hyperstack_interpreter::compiler::EntityBytecode;
+use hyperstack_server::{
+    BackgroundHandle, DictionarySource, FlagsConfig, MutationBatch, Server, StaticTokenAuthPlugin,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+const ADMIN_TOKEN: &str = "ops-secret";
+
+async fn serve(
+    flags: FlagsConfig,
+) -> (
+    SocketAddr,
+    BackgroundHandle,
+    mpsc::UnboundedSender<MutationBatch>,
+) {
+    let round = EntityBytecode {
+        state_id: 0,
+        handlers: Default::default(),
+        entity_name: "Round".to_string(),
+        when_events: Default::default(),
+        non_emitted_fields: Default::default(),
+        wide_integer_fields: Default::default(),
+        computed_paths: vec![],
+        trace_fields: vec![],
+        priority: Default::default(),
+        delete_on: None,
+        event_schemas: Default::default(),
+        computed_fields_evaluator: None,
+    };
+    let (mut spec, parser_tx) = common::forwarding_spec();
+    spec.bytecode.entities.insert("Round".to_string(), round);
+
+    let (addr, background) = common::serve(
+        Server::builder()
+            .spec(spec)
+            .flags(flags)
+            .compression_dictionary(DictionarySource::Sampled { frames: 10_000 }),
+    )
+    .await;
+    (addr, background, parser_tx)
+}
+
+fn admin_flags() -> FlagsConfig {
+    FlagsConfig::new().with_admin_auth(Arc::new(StaticTokenAuthPlugin::new([
+        ADMIN_TOKEN.to_string()
+    ])))
+}
+
+fn round_batch(round: u64, patch: Value) -> MutationBatch {
+    common::batch("Round", &round.to_string(), patch)
+}
+
+/// The next message, decompressed, and whether it came compressed
+async fn next_message(ws: &mut Client) -> (Value, bool) {
+    let message = tokio::time::timeout(common::FRAME_TIMEOUT, ws.next())
+        .await
+        .expect("message should arrive")
+        .unwrap()
+        .unwrap();
+    let (frame, compressed) = match message {
+        Message::Binary(bytes) if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) => {
+            (zstd::decode_all(bytes.as_ref()).unwrap(), true)
+        }
+        Message::Binary(bytes) => (bytes.to_vec(), false),
+        Message::Text(text) => (text.as_bytes().to_vec(), false),
+        other => panic!("expected a frame, got {other:?}"),
+    };
+    (serde_json::from_slice(&frame).unwrap(), compressed)
+}
+
+/// The next patch frame
+async fn next_update(ws: &mut Client) -> (Value, bool) {
+    loop {
+        let (frame, compressed) = next_message(ws).await;
+        if frame["op"] == json!("patch") {
+            return (frame, compressed);
+        }
+    }
+}
+
+async fn subscribe(ws: &mut Client) {
+    common::send(ws, json!({ "type": "subscribe", "view": "Round/list" })).await;
+    loop {
+        let (message, _) = next_message(ws).await;
+        if message["op"] == json!("subscribed") {
+            return;
+        }
+    }
+}
+
+/// Send a request and return the status code and body
+async fn request(
+    addr: SocketAddr,
+    method: &str,
+    path: &str,
+    token: Option<&str>,
+    body: &str,
+) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let authorization = token
+        .map(|token| format!("Authorization: Bearer {token}\r\n"))
+        .unwrap_or_default();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\n{authorization}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .expect("response should have a body");
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+#[tokio::test]
+async fn turning_compression_off_sends_the_next_frames_as_they_are() {
+    let (addr, background, parser_tx) = serve(admin_flags()).await;
+
+    let mut client = common::connect(&format!("ws://{addr}/stream")).await;
+    common::send(
+        &mut client,
+        json!({ "type": "compression", "codec": "zstd" }),
+    )
+    .await;
+    subscribe(&mut client).await;
+
+    let large =
+        |round: u64| json!({ "notes": format!("round {round} went on and on ").repeat(100) });
+    parser_tx.send(round_batch(1, large(1))).unwrap();
+    let (frame, compressed) = next_update(&mut client).await;
+    assert!(compressed);
+    assert_eq!(frame["data"], large(1));
+
+    let (status, _) = request(
+        addr,
+        "PATCH",
+        "/stream/admin/flags",
+        None,
+        r#"{"compression": false}"#,
+    )
+    .await;
+    assert_eq!(status, 401);
+    let (status, body) = request(
+        addr,
+        "PATCH",
+        "/stream/admin/flags",
+        Some(ADMIN_TOKEN),
+        r#"{"compression": false}"#,
+    )
+    .await;
+    assert_eq!(status, 200, "{body}");
+
+    parser_tx.send(round_batch(2, large(2))).unwrap();
+    let (frame, compressed) = next_update(&mut client).await;
+    assert!(!compressed);
+    assert_eq!(frame["data"], large(2));
+
+    let (status, body) = request(addr, "GET", "/stream/stats", None, "").await;
+    assert_eq!(status, 200);
+    let stats: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["flags"]["flags"]["compression"], json!(false));
+    assert_eq!(
+        stats["flags"]["recent_changes"][0]["changed_by"],
+        json!("static:ops-secr")
+    );
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn suppressing_noop_patches_drops_repeats_of_the_cached_entity() {
+    let (addr, background, parser_tx) = serve(admin_flags()).await;
+
+    let mut client = common::connect(&format!("ws://{addr}/stream")).await;
+    subscribe(&mut client).await;
+
+    let patch = json!({ "state": { "total_deployed": 7_919, "motherlode": false } });
+    parser_tx.send(round_batch(1, patch.clone())).unwrap();
+    parser_tx.send(round_batch(1, patch.clone())).unwrap();
+    for _ in 0..2 {
+        let (frame, _) = next_update(&mut client).await;
+        assert_eq!(frame["data"], patch);
+    }
+
+    let (status, body) = request(
+        addr,
+        "PATCH",
+        "/stream/admin/flags",
+        Some(ADMIN_TOKEN),
+        r#"{"suppress_noop_patches": true}"#,
+    )
+    .await;
+    assert_eq!(status, 200, "{body}");
+
+    let partial = json!({ "state": { "motherlode": false } });
+    let changed = json!({ "state": { "motherlode": true } });
+    parser_tx.send(round_batch(1, patch.clone())).unwrap();
+    parser_tx.send(round_batch(1, partial)).unwrap();
+    parser_tx.send(round_batch(1, changed.clone())).unwrap();
+    let (frame, _) = next_update(&mut client).await;
+    assert_eq!(frame["data"], changed);
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn flags_cannot_change_without_admin_auth() {
+    let (addr, background, _parser_tx) = serve(FlagsConfig::new()).await;
+
+    let (status, _) = request(
+        addr,
+        "PATCH",
+        "/stream/admin/flags",
+        Some(ADMIN_TOKEN),
+        r#"{"compression": false}"#,
+    )
+    .await;
+    assert_eq!(status, 403);
+
+    let (status, body) = request(addr, "GET", "/stream/admin/flags", None, "").await;
+    assert_eq!(status, 200);
+    let flags: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        flags["flags"],
+        json!({ "compression": true, "load_shedding": true, "suppress_noop_patches": false })
+    );
+
+    background.shutdown();
+}
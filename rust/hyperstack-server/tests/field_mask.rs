@@ -0,0 +1,135 @@
+//! Subscriptions only receive the fields their plan allows, re-evaluated on
+//! the next frame after an in-band auth refresh.
+
+mod common;
+
+use common::{connect, forwarding_spec, next_json, send, serve, view, Client};
+use hyperstack_auth::{SessionClaims, SigningKey, TokenSigner, TokenVerifier};
+use hyperstack_interpreter::Mutation;
+use hyperstack_server::{
+    BackgroundHandle, Mode, MutationBatch, Server, SignedSessionAuthPlugin, Spec,
+    StaticFieldAuthorizer, ViewIndex,
+};
+use serde_json::{json, Value};
+use smallvec::smallvec;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+const ISSUER: &str = "test-issuer";
+const AUDIENCE: &str = "test-audience";
+
+fn token_batch(patch: Value) -> MutationBatch {
+    MutationBatch::new(smallvec![Mutation::new("Token", json!("token-1"), patch)])
+}
+
+fn views() -> ViewIndex {
+    let mut views = ViewIndex::new();
+    views.add_spec(view("Token/list", "Token", Mode::List));
+    views
+}
+
+async fn serve_with_plans(spec: Spec, verifier: TokenVerifier) -> (SocketAddr, BackgroundHandle) {
+    let authorizer = StaticFieldAuthorizer::from_json(
+        r#"{
+            "default_plan": "free",
+            "plans": { "free": { "Token/list": ["name", "price"] }, "pro": {} }
+        }"#,
+    )
+    .unwrap();
+    serve(
+        Server::builder()
+            .spec(spec)
+            .views(views())
+            .websocket()
+            .websocket_auth_plugin(Arc::new(SignedSessionAuthPlugin::new(verifier)))
+            .websocket_field_authorizer(Arc::new(authorizer)),
+    )
+    .await
+}
+
+fn token(signer: &TokenSigner, subject: &str, plan: &str) -> String {
+    let claims = SessionClaims::builder(ISSUER, subject, AUDIENCE)
+        .with_plan(plan)
+        .build();
+    signer.sign(claims).unwrap()
+}
+
+/// Connect and subscribe to the token list, returning the ack
+async fn subscribe(addr: SocketAddr, token: &str, extra: Value) -> (Client, Value) {
+    let mut ws = connect(&format!("ws://{addr}/stream?hs_token={token}")).await;
+    let mut message = json!({ "type": "subscribe", "view": "Token/list" });
+    message
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    send(&mut ws, message).await;
+
+    let ack = next_json(&mut ws).await;
+    assert_eq!(ack["op"], json!("subscribed"));
+    (ws, ack)
+}
+
+#[tokio::test]
+async fn fields_are_masked_by_plan_until_the_plan_changes() {
+    let signing_key = SigningKey::generate();
+    let verifier = TokenVerifier::new(signing_key.verifying_key(), ISSUER, AUDIENCE);
+    let signer = TokenSigner::new(signing_key, ISSUER);
+    let (spec, batches) = forwarding_spec();
+    let (addr, background) = serve_with_plans(spec, verifier).await;
+
+    let no_snapshot = json!({ "withSnapshot": false });
+    let (mut free, ack) =
+        subscribe(addr, &token(&signer, "free", "free"), no_snapshot.clone()).await;
+    assert_eq!(ack["fields"], json!(["name", "price"]));
+    let (mut pro, ack) = subscribe(addr, &token(&signer, "pro", "pro"), no_snapshot).await;
+    assert!(ack.get("fields").is_none());
+
+    batches
+        .send(token_batch(json!({
+            "name": "Token",
+            "price": 1,
+            "trades": [{ "size": 5 }],
+        })))
+        .unwrap();
+    let frame = next_json(&mut free).await;
+    assert_eq!(frame["op"], json!("patch"));
+    assert_eq!(frame["data"], json!({ "name": "Token", "price": 1 }));
+    let frame = next_json(&mut pro).await;
+    assert_eq!(frame["data"]["trades"], json!([{ "size": 5 }]));
+
+    // A snapshot is masked too, and requested fields narrow the mask further
+    let (mut narrow, ack) = subscribe(
+        addr,
+        &token(&signer, "narrow", "free"),
+        json!({ "fields": ["price", "trades"] }),
+    )
+    .await;
+    assert_eq!(ack["fields"], json!(["price"]));
+    let snapshot = next_json(&mut narrow).await;
+    assert_eq!(snapshot["op"], json!("snapshot"));
+    assert_eq!(snapshot["data"][0]["data"], json!({ "price": 1 }));
+
+    // Upgrading mid-connection takes effect on the next frame
+    send(
+        &mut free,
+        json!({ "type": "refresh_auth", "token": token(&signer, "free", "pro") }),
+    )
+    .await;
+    let refreshed = next_json(&mut free).await;
+    assert_eq!(refreshed["success"], json!(true));
+
+    batches
+        .send(token_batch(
+            json!({ "price": 2, "trades": [{ "size": 7 }] }),
+        ))
+        .unwrap();
+    let frame = next_json(&mut free).await;
+    assert_eq!(
+        frame["data"],
+        json!({ "price": 2, "trades": [{ "size": 7 }] })
+    );
+    let frame = next_json(&mut narrow).await;
+    assert_eq!(frame["data"], json!({ "price": 2 }));
+
+    background.shutdown();
+}
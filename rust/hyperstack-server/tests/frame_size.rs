@@ -0,0 +1,166 @@
+//! Frames over `max_frame_bytes` are split, or replaced by `frame_too_large`
+//! when they can't be.
+
+mod common;
+
+use common::Client;
+use flate2::read::GzDecoder;
+use futures_util::StreamExt;
+use hyperstack_server::{
+    BackgroundHandle, Mode, MutationBatch, Server, ViewIndex, WebSocketConfig,
+};
+use serde_json::{json, Map, Value};
+use std::io::Read;
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+const MAX_FRAME_BYTES: usize = 4096;
+
+/// A pool whose captures only fit in a frame one at a time
+fn split_pool() -> Value {
+    json!({
+        "name": "split",
+        "capture_a": "a".repeat(3000),
+        "capture_b": "b".repeat(3000),
+    })
+}
+
+/// A pool with a single capture larger than any frame
+fn huge_pool() -> Value {
+    json!({ "name": "huge", "capture": "z".repeat(10_000) })
+}
+
+fn small_pool(name: &str) -> Value {
+    json!({ "name": name, "capture": "s".repeat(1500) })
+}
+
+/// Every pool, in the order the parser sends them
+fn pool_batches() -> Vec<MutationBatch> {
+    let mut batches = vec![
+        common::batch("Pool", "split", split_pool()),
+        common::batch("Pool", "huge", huge_pool()),
+    ];
+    for name in ["small-1", "small-2", "small-3", "small-4"] {
+        batches.push(common::batch("Pool", name, small_pool(name)));
+    }
+    batches
+}
+
+async fn serve() -> (
+    SocketAddr,
+    BackgroundHandle,
+    mpsc::UnboundedSender<MutationBatch>,
+) {
+    let (spec, batches) = common::forwarding_spec();
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view("Pool/list", "Pool", Mode::List));
+
+    let (addr, background) =
+        common::serve(Server::builder().spec(spec).views(views).websocket_config(
+            WebSocketConfig::default().with_max_frame_bytes(Some(MAX_FRAME_BYTES)),
+        ))
+        .await;
+    (addr, background, batches)
+}
+
+/// The next data frame, decompressed, along with its size on the wire
+async fn next_frame(ws: &mut Client) -> (Value, usize) {
+    let message = tokio::time::timeout(common::FRAME_TIMEOUT, ws.next())
+        .await
+        .expect("frame should arrive")
+        .unwrap()
+        .unwrap();
+    let bytes = match message {
+        Message::Binary(bytes) => bytes.to_vec(),
+        Message::Text(text) => text.as_bytes().to_vec(),
+        other => panic!("expected a data frame, got {other:?}"),
+    };
+    let wire_len = bytes.len();
+    let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        decompressed
+    } else {
+        bytes
+    };
+    (serde_json::from_slice(&bytes).unwrap(), wire_len)
+}
+
+async fn subscribe(addr: SocketAddr, with_snapshot: bool) -> Client {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    let message =
+        json!({ "type": "subscribe", "view": "Pool/list", "withSnapshot": with_snapshot });
+    common::send(&mut ws, message).await;
+
+    let (subscribed, _) = next_frame(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"));
+    ws
+}
+
+#[tokio::test]
+async fn oversized_frames_are_split_or_reported() {
+    let (addr, background, batches) = serve().await;
+
+    let mut live = subscribe(addr, false).await;
+    for batch in pool_batches() {
+        batches.send(batch).unwrap();
+    }
+
+    // The split pool arrives as two parts that merge back into the patch
+    let mut merged = Map::new();
+    for part in 1..=2 {
+        let (frame, wire_len) = next_frame(&mut live).await;
+        assert!(wire_len <= MAX_FRAME_BYTES + 128, "{wire_len} bytes");
+        assert_eq!(frame["op"], json!("patch"));
+        assert_eq!(frame["key"], json!("split"));
+        assert_eq!(frame["continuation"], json!({ "part": part, "parts": 2 }));
+        merged.extend(frame["data"].as_object().unwrap().clone());
+    }
+    assert_eq!(Value::Object(merged), split_pool());
+
+    // The huge pool can't be split by field
+    let (frame, _) = next_frame(&mut live).await;
+    assert_eq!(frame["op"], json!("frame_too_large"));
+    assert_eq!(frame["entity"], json!("Pool/list"));
+    assert_eq!(frame["key"], json!("huge"));
+    assert_eq!(frame["data"]["max_bytes"], json!(MAX_FRAME_BYTES));
+    assert!(frame["data"]["bytes"].as_u64().unwrap() > MAX_FRAME_BYTES as u64);
+
+    for _ in 0..4 {
+        let (frame, _) = next_frame(&mut live).await;
+        assert_eq!(frame["op"], json!("patch"));
+        assert!(frame.get("continuation").is_none());
+    }
+
+    // A snapshot splits between entities and reports the ones that don't fit
+    let mut snapshot = subscribe(addr, true).await;
+    let mut keys = Vec::new();
+    let mut too_large = Vec::new();
+    let mut snapshot_frames = 0;
+    loop {
+        let (frame, _) = next_frame(&mut snapshot).await;
+        match frame["op"].as_str().unwrap() {
+            "frame_too_large" => too_large.push(frame["key"].as_str().unwrap().to_string()),
+            "snapshot" => {
+                snapshot_frames += 1;
+                for entity in frame["data"].as_array().unwrap() {
+                    keys.push(entity["key"].as_str().unwrap().to_string());
+                }
+                if frame["complete"] == json!(true) {
+                    break;
+                }
+            }
+            op => panic!("unexpected {op} frame"),
+        }
+    }
+    keys.sort();
+    too_large.sort();
+    assert!(snapshot_frames > 1);
+    assert_eq!(keys, ["small-1", "small-2", "small-3", "small-4"]);
+    assert_eq!(too_large, ["huge", "split"]);
+
+    background.shutdown();
+}
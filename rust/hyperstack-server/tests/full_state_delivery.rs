@@ -0,0 +1,151 @@
+//! Patch and full-state delivery of the same mutation sequence.
+//!
+//! A `full_state` subscriber gets an `upsert` frame with the whole cached
+//! entity after every mutation, where a default subscriber gets the patch.
+
+mod common;
+
+use common::Client;
+use hyperstack_server::{
+    BusManager, BusMessage, EntityCache, Mode, MutationBatch, Projector, Server, UpdateDelivery,
+    ViewIndex,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+fn patches() -> Vec<Value> {
+    vec![
+        json!({ "name": "pool", "liquidity": 10 }),
+        json!({ "liquidity": 12 }),
+        json!({ "volume": 5 }),
+    ]
+}
+
+fn full_states() -> Vec<Value> {
+    vec![
+        json!({ "name": "pool", "liquidity": 10 }),
+        json!({ "name": "pool", "liquidity": 12 }),
+        json!({ "name": "pool", "liquidity": 12, "volume": 5 }),
+    ]
+}
+
+fn pool_batch(patch: Value) -> MutationBatch {
+    common::batch("Pool", "pool-1", patch)
+}
+
+fn views() -> ViewIndex {
+    let mut views = ViewIndex::new();
+    for (id, mode) in [("Pool/list", Mode::List), ("Pool/state", Mode::State)] {
+        views.add_spec(common::view(id, "Pool", mode));
+    }
+    views
+}
+
+/// Run the projector over the mutation sequence and collect what it
+/// published on the list bus, plus the latest state bus message.
+async fn project(full_state: bool) -> (Vec<Arc<BusMessage>>, Arc<BusMessage>) {
+    let bus_manager = BusManager::new();
+    let mut list = bus_manager.get_or_create_list_bus("Pool/list").await;
+    let state = bus_manager
+        .get_or_create_state_bus("Pool/state", "pool-1")
+        .await;
+    let _interest = full_state.then(|| {
+        (
+            bus_manager.register_full_state("Pool/list"),
+            bus_manager.register_full_state("Pool/state"),
+        )
+    });
+
+    let (mutations_tx, mutations_rx) = mpsc::channel(8);
+    for patch in patches() {
+        mutations_tx.send(pool_batch(patch)).await.unwrap();
+    }
+    drop(mutations_tx);
+    Projector::new(
+        Arc::new(views()),
+        bus_manager.clone(),
+        EntityCache::new(),
+        mutations_rx,
+    )
+    .run()
+    .await;
+
+    let mut messages = Vec::new();
+    while let Ok(message) = list.try_recv() {
+        messages.push(message);
+    }
+    let latest = state.borrow().clone();
+    (messages, latest)
+}
+
+fn frame(payload: &[u8]) -> Value {
+    serde_json::from_slice(payload).unwrap()
+}
+
+#[tokio::test]
+async fn projector_publishes_full_state_only_when_asked() {
+    let (messages, latest) = project(false).await;
+    assert_eq!(messages.len(), 3);
+    assert!(messages.iter().all(|message| message.full_state.is_none()));
+    let latest = frame(latest.payload_for(UpdateDelivery::FullState));
+    assert_eq!(latest["op"], json!("patch"));
+    assert_eq!(latest["data"], json!({ "volume": 5 }));
+
+    let (messages, latest) = project(true).await;
+    for ((message, patch), full) in messages.iter().zip(patches()).zip(full_states()) {
+        let patch_frame = frame(&message.payload);
+        assert_eq!(patch_frame["op"], json!("patch"));
+        assert_eq!(patch_frame["data"], patch);
+
+        let full_frame = frame(message.full_state.as_deref().unwrap());
+        assert_eq!(full_frame["op"], json!("upsert"));
+        assert_eq!(full_frame["entity"], json!("Pool/list"));
+        assert_eq!(full_frame["key"], json!("pool-1"));
+        assert_eq!(full_frame["data"], full);
+    }
+    let latest = frame(latest.payload_for(UpdateDelivery::FullState));
+    assert_eq!(latest["op"], json!("upsert"));
+    assert_eq!(latest["entity"], json!("Pool/state"));
+    assert_eq!(latest["data"], full_states()[2]);
+}
+
+async fn subscribe(addr: SocketAddr, subscription: Value) -> Client {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    let mut message = json!({ "type": "subscribe", "view": "Pool/list", "withSnapshot": false });
+    message
+        .as_object_mut()
+        .unwrap()
+        .extend(subscription.as_object().unwrap().clone());
+    common::send(&mut ws, message).await;
+
+    let subscribed = common::next_json(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"));
+    ws
+}
+
+#[tokio::test]
+async fn subscribers_get_the_delivery_they_asked_for() {
+    let (spec, batches) = common::forwarding_spec();
+    let (addr, background) = common::serve(Server::builder().spec(spec).views(views())).await;
+
+    let mut patched = subscribe(addr, json!({})).await;
+    let mut full = subscribe(addr, json!({ "delivery": "full_state" })).await;
+    for patch in patches() {
+        batches.send(pool_batch(patch)).unwrap();
+    }
+
+    for (patch, state) in patches().into_iter().zip(full_states()) {
+        let frame = common::next_json(&mut patched).await;
+        assert_eq!(frame["op"], json!("patch"));
+        assert_eq!(frame["data"], patch);
+
+        let frame = common::next_json(&mut full).await;
+        assert_eq!(frame["op"], json!("upsert"));
+        assert_eq!(frame["key"], json!("pool-1"));
+        assert_eq!(frame["data"], state);
+    }
+
+    background.shutdown();
+}
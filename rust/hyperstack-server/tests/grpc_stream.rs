@@ -0,0 +1,225 @@
+#![cfg(feature = "grpc")]
+
+mod common;
+
+use common::{batch, connect, forwarding_spec, next_json, send, view, Client};
+use futures_util::StreamExt;
+use hyperstack_server::grpc::{EntityFrame, StreamClient, SubscribeRequest};
+use hyperstack_server::{Mode, MutationBatch, Server, ServerHandle, SlotContext, ViewIndex};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tonic::transport::Channel;
+use tonic::Code;
+
+const VIEW: &str = "Token/list";
+
+fn token(slot: u64, key: &str, patch: Value) -> MutationBatch {
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..batch("Token", key, patch)
+    }
+}
+
+fn views() -> ViewIndex {
+    let mut views = ViewIndex::new();
+    views.add_spec(view(VIEW, "Token", Mode::List));
+    views
+}
+
+fn free_addr() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+struct Running {
+    server: ServerHandle,
+    ws_addr: SocketAddr,
+    grpc_addr: SocketAddr,
+    parser: mpsc::UnboundedSender<MutationBatch>,
+}
+
+async fn start() -> Running {
+    let (spec, parser) = forwarding_spec();
+    let ws_addr = free_addr();
+    let grpc_addr = free_addr();
+    let server = Server::builder()
+        .spec(spec)
+        .views(views())
+        .websocket()
+        .bind(ws_addr)
+        .grpc()
+        .grpc_bind(grpc_addr)
+        .start_with_shutdown()
+        .await
+        .expect("server should start");
+    Running {
+        server,
+        ws_addr,
+        grpc_addr,
+        parser,
+    }
+}
+
+/// Connect once the server is listening
+async fn grpc_client(addr: SocketAddr) -> StreamClient<Channel> {
+    for _ in 0..100 {
+        if let Ok(client) = StreamClient::connect(format!("http://{addr}")).await {
+            return client;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("gRPC server should listen on {addr}");
+}
+
+async fn grpc_subscribe(addr: SocketAddr, view: &str) -> tonic::Streaming<EntityFrame> {
+    grpc_client(addr)
+        .await
+        .subscribe(SubscribeRequest {
+            view: view.to_string(),
+            ..Default::default()
+        })
+        .await
+        .expect("subscription should be accepted")
+        .into_inner()
+}
+
+async fn next_grpc(stream: &mut tonic::Streaming<EntityFrame>) -> EntityFrame {
+    tokio::time::timeout(Duration::from_secs(5), stream.next())
+        .await
+        .expect("frame should arrive")
+        .expect("stream should stay open")
+        .unwrap()
+}
+
+async fn ws_subscribe(addr: SocketAddr) -> Client {
+    let mut ws = connect(&format!("ws://{addr}")).await;
+    send(&mut ws, json!({ "type": "subscribe", "view": VIEW })).await;
+    ws
+}
+
+/// The next WebSocket patch frame, skipping acks and snapshots
+async fn next_ws_patch(ws: &mut Client) -> Value {
+    loop {
+        let frame = next_json(ws).await;
+        if frame["op"] == json!("patch") {
+            return frame;
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn grpc_and_websocket_subscribers_see_the_same_updates() {
+    let running = start().await;
+    running
+        .parser
+        .send(token(1, "a", json!({ "supply": 1 })))
+        .unwrap();
+
+    // Wait for the first update to be cached, then subscribe to both
+    let mut first = grpc_subscribe(running.grpc_addr, VIEW).await;
+    let update = next_grpc(&mut first).await;
+    assert_eq!(update.key, "a");
+
+    let mut grpc = grpc_subscribe(running.grpc_addr, VIEW).await;
+    let snapshot = next_grpc(&mut grpc).await;
+    assert_eq!(snapshot.op, "snapshot");
+    assert_eq!(snapshot.key, "a");
+    assert_eq!(
+        serde_json::from_str::<Value>(&snapshot.data).unwrap()["supply"],
+        json!(1)
+    );
+    assert_eq!(snapshot.slot, None);
+    let mut ws = ws_subscribe(running.ws_addr).await;
+    // The WebSocket subscription is registered once its snapshot went out
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    running
+        .parser
+        .send(token(7, "b", json!({ "supply": 5 })))
+        .unwrap();
+    running
+        .parser
+        .send(token(9, "a", json!({ "supply": 2 })))
+        .unwrap();
+
+    for _ in 0..2 {
+        let grpc_frame = next_grpc(&mut grpc).await;
+        let ws_frame = next_ws_patch(&mut ws).await;
+        assert_eq!(grpc_frame.view, VIEW);
+        assert_eq!(grpc_frame.op, "patch");
+        assert_eq!(json!(grpc_frame.key), ws_frame["key"]);
+        assert_eq!(
+            serde_json::from_str::<Value>(&grpc_frame.data).unwrap(),
+            ws_frame["data"]
+        );
+        assert_eq!(json!(grpc_frame.seq), ws_frame["seq"]);
+        assert_eq!(grpc_frame.skipped, 0);
+    }
+
+    running.server.shutdown().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn slow_grpc_client_skips_its_oldest_updates() {
+    const UPDATES: u64 = 5_000;
+    let running = start().await;
+
+    let mut slow = grpc_subscribe(running.grpc_addr, VIEW).await;
+    let mut eager = grpc_subscribe(running.grpc_addr, VIEW).await;
+    // Large enough to overflow the transport's buffers as well as the bus
+    let padding = "x".repeat(1024);
+    for slot in 1..=UPDATES {
+        let key = format!("token-{slot}");
+        running
+            .parser
+            .send(token(
+                slot,
+                &key,
+                json!({ "slot": slot, "padding": padding }),
+            ))
+            .unwrap();
+    }
+
+    // The projector publishes everything while the slow client reads nothing
+    let last = format!("token-{UPDATES}");
+    while next_grpc(&mut eager).await.key != last {}
+
+    let mut slots = Vec::new();
+    let mut skipped = 0u64;
+    loop {
+        let frame = next_grpc(&mut slow).await;
+        skipped += frame.skipped;
+        if frame.op == "patch" {
+            slots.push(frame.slot.unwrap());
+        }
+        if frame.key == last {
+            break;
+        }
+    }
+    assert!(skipped > 0, "the slow client should fall behind");
+    assert!(slots.len() < UPDATES as usize);
+    // What is left arrives in order, up to the latest update
+    assert!(slots.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(slots.last(), Some(&UPDATES));
+
+    running.server.shutdown().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn unknown_view_is_rejected() {
+    let running = start().await;
+
+    let status = grpc_client(running.grpc_addr)
+        .await
+        .subscribe(SubscribeRequest {
+            view: "Token/missing".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    running.server.shutdown().await.unwrap();
+}
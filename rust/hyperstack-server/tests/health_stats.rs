@@ -0,0 +1,104 @@
+//! `/stats` on the standalone health server: a snapshot of the VM, the
+//! entity cache, connected clients and their subscriptions, and the last
+//! processed slot.
+
+mod common;
+
+use hyperstack_server::{Mode, MutationBatch, Server, ServerHandle, SlotContext, Spec, ViewIndex};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+const VIEW: &str = "Token/list";
+const TOKENS: u64 = 3;
+
+/// A spec whose parser writes `TOKENS` tokens at slots 100, 101 and 102
+fn token_spec() -> Spec {
+    let (spec, batches) = common::forwarding_spec();
+    for index in 0..TOKENS {
+        let batch = common::batch(
+            "Token",
+            &format!("token-{index}"),
+            json!({ "index": index }),
+        );
+        batches
+            .send(MutationBatch {
+                slot_context: Some(SlotContext::new(100 + index, 0)),
+                ..batch
+            })
+            .unwrap();
+    }
+
+    spec.with_vm_stats(Arc::new(|| {
+        Some(json!({
+            "memory": {
+                "Token": { "state_table_entity_count": TOKENS, "pending_queue_stats": null },
+            },
+        }))
+    }))
+}
+
+fn views() -> ViewIndex {
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view(VIEW, "Token", Mode::List));
+    views
+}
+
+fn free_addr() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+async fn start(ws_addr: SocketAddr, health_addr: SocketAddr) -> ServerHandle {
+    Server::builder()
+        .spec(token_spec())
+        .views(views())
+        .websocket()
+        .bind(ws_addr)
+        .health_bind(health_addr)
+        .start_with_shutdown()
+        .await
+        .expect("server should start")
+}
+
+async fn stats(health_addr: SocketAddr) -> Value {
+    reqwest::get(format!("http://{health_addr}/stats"))
+        .await
+        .expect("stats should be served")
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn health_server_reports_runtime_stats() {
+    let (ws_addr, health_addr) = (free_addr(), free_addr());
+    let server = start(ws_addr, health_addr).await;
+
+    let mut ws = common::connect(&format!("ws://{ws_addr}")).await;
+    common::send(&mut ws, json!({ "type": "subscribe", "view": VIEW })).await;
+
+    // Wait until every token is cached and the subscription is registered
+    let mut snapshot = Value::Null;
+    for _ in 0..100 {
+        snapshot = stats(health_addr).await;
+        if snapshot["last_slot"] == json!(102) && snapshot["subscriptions"][VIEW] == json!(1) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    assert_eq!(snapshot["last_slot"], json!(102), "{snapshot}");
+    assert_eq!(snapshot["clients"], json!(1));
+    assert_eq!(snapshot["subscriptions"], json!({ VIEW: 1 }));
+    assert_eq!(snapshot["cache"]["views"][VIEW], json!(TOKENS));
+    assert_eq!(snapshot["cache"]["total_entities"], json!(TOKENS));
+    assert_eq!(
+        snapshot["vm"]["memory"]["Token"]["state_table_entity_count"],
+        json!(TOKENS)
+    );
+
+    drop(ws);
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use common::{send, try_next_json, view, Client, FRAME_TIMEOUT};
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_server::{BackgroundHandle, Mode, Server, ViewIndex, WebSocketConfig};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+async fn serve_app(config: WebSocketConfig) -> (SocketAddr, BackgroundHandle) {
+    common::serve(Server::builder().websocket_config(config)).await
+}
+
+fn limited_config() -> WebSocketConfig {
+    WebSocketConfig::default()
+        .with_max_inbound_message_bytes(Some(1024))
+        .with_max_inbound_messages_per_sec(Some(5))
+}
+
+async fn connect(addr: SocketAddr) -> Client {
+    common::connect(&format!("ws://{addr}/stream")).await
+}
+
+/// Read the next text frame as JSON, skipping anything else
+async fn next_json(ws: &mut Client) -> Option<Value> {
+    loop {
+        let msg = tokio::time::timeout(Duration::from_secs(2), ws.next())
+            .await
+            .expect("server should answer promptly")?;
+        match msg {
+            Ok(Message::Text(text)) => return Some(serde_json::from_str(&text).unwrap()),
+            Ok(Message::Close(_)) | Err(_) => return None,
+            Ok(_) => continue,
+        }
+    }
+}
+
+/// Assert the server answers a well-formed request from this client
+async fn assert_responsive(ws: &mut Client) {
+    send(ws, json!({"type": "refresh_auth", "token": "not-a-token"})).await;
+    let response = next_json(ws).await.expect("client should get a response");
+    assert_eq!(response["success"], json!(false));
+}
+
+/// Assert the client gets one error frame with `code` and is then disconnected
+async fn assert_rejected_with(ws: &mut Client, code: &str) {
+    let issue = next_json(ws)
+        .await
+        .expect("client should get an error frame");
+    assert_eq!(issue["type"], json!("error"));
+    assert_eq!(issue["code"], json!(code));
+    assert_eq!(issue["fatal"], json!(true));
+    assert!(
+        next_json(ws).await.is_none(),
+        "connection should close after a single error frame"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn oversized_message_is_rejected_without_affecting_other_clients() {
+    let (addr, _background) = serve_app(limited_config()).await;
+    let mut bystander = connect(addr).await;
+    let mut offender = connect(addr).await;
+
+    let oversized = format!(
+        r#"{{"type":"subscribe","view":"{}"}}"#,
+        "x".repeat(4 * 1024 * 1024)
+    );
+    let _ = offender.send(Message::text(oversized)).await;
+    assert_rejected_with(&mut offender, "message-too-large").await;
+
+    assert_responsive(&mut bystander).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rapid_fire_messages_are_rate_limited_without_affecting_other_clients() {
+    let (addr, _background) = serve_app(limited_config()).await;
+    let mut bystander = connect(addr).await;
+    let mut offender = connect(addr).await;
+
+    for _ in 0..200 {
+        if offender
+            .send(Message::text(json!({"type": "ping"}).to_string()))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+    assert_rejected_with(&mut offender, "rate-limit-exceeded").await;
+
+    assert_responsive(&mut bystander).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn garbage_within_limits_keeps_the_connection_open() {
+    let (addr, _background) = serve_app(limited_config()).await;
+    let mut client = connect(addr).await;
+
+    // Four malformed texts plus one binary frame stay within the burst of five
+    let junk = [
+        "{".to_string(),
+        "\u{0}\u{1}\u{2}".to_string(),
+        r#"{"type":"subscribe","view":42}"#.to_string(),
+        "[".repeat(1000),
+    ];
+    for payload in junk {
+        client.send(Message::text(payload)).await.unwrap();
+    }
+    client.send(Message::binary(vec![0xff; 512])).await.unwrap();
+
+    // Let the bucket refill after the burst spent above
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_responsive(&mut client).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn limits_can_be_disabled() {
+    let config = WebSocketConfig::default()
+        .with_max_inbound_message_bytes(None)
+        .with_max_inbound_messages_per_sec(None);
+    let (addr, _background) = serve_app(config).await;
+    let mut client = connect(addr).await;
+
+    for _ in 0..200 {
+        send(&mut client, json!({"type": "ping"})).await;
+    }
+    client
+        .send(Message::text(format!(
+            r#"{{"pad":"{}"}}"#,
+            "x".repeat(256 * 1024)
+        )))
+        .await
+        .unwrap();
+
+    assert_responsive(&mut client).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconnecting_with_many_subscriptions_is_not_rate_limited_by_default() {
+    const SUBSCRIPTIONS: usize = 120;
+    let mut views = ViewIndex::new();
+    views.add_spec(view("Token/state", "Token", Mode::State));
+    let (addr, _background) = common::serve(
+        Server::builder()
+            .views(views)
+            .websocket_config(WebSocketConfig::default()),
+    )
+    .await;
+
+    // Each connection resubscribes everything at once, as the SDK does
+    for _ in 0..3 {
+        let mut client = connect(addr).await;
+        for n in 0..SUBSCRIPTIONS {
+            send(
+                &mut client,
+                json!({"type": "subscribe", "view": "Token/state", "key": format!("token-{n}")}),
+            )
+            .await;
+        }
+
+        let mut acked = 0;
+        while acked < SUBSCRIPTIONS {
+            let frame = try_next_json(&mut client, FRAME_TIMEOUT)
+                .await
+                .expect("client should stay connected");
+            assert_ne!(frame["type"], json!("error"), "{frame}");
+            if frame["op"] == json!("subscribed") {
+                acked += 1;
+            }
+        }
+    }
+}
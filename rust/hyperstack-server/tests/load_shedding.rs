@@ -0,0 +1,179 @@
+//! Load shedding order under a backlog of mutations.
+//!
+//! The projector is held back while batches queue up, as if it had slowed
+//! down under a burst. The shedder samples the queue depth, then the projector
+//! drains the backlog and the frames published for each entity show which
+//! priorities were shed.
+
+mod common;
+
+use hyperstack_interpreter::ast::EntityPriority;
+use hyperstack_interpreter::Mutation;
+use hyperstack_server::{
+    BusManager, EntityCache, LoadShedConfig, LoadShedder, Mode, MutationBatch, Projector,
+    ShedCounts, ShedLevel, ViewIndex,
+};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const QUEUE: usize = 20;
+
+/// Entity, priority and the mode of its only view
+const ENTITIES: [(&str, EntityPriority, Mode); 4] = [
+    ("Trade", EntityPriority::Low, Mode::Append),
+    ("Candle", EntityPriority::Low, Mode::List),
+    ("Pool", EntityPriority::Normal, Mode::List),
+    ("Vault", EntityPriority::High, Mode::List),
+];
+
+struct Drained {
+    level: ShedLevel,
+    /// Patches published per entity
+    published: BTreeMap<&'static str, Vec<Value>>,
+    counts: BTreeMap<String, ShedCounts>,
+}
+
+/// Queue `backlog` batches, each updating every entity once, sample the
+/// pressure and let the projector drain them.
+async fn drain_backlog(backlog: usize) -> Drained {
+    let mut views = ViewIndex::new();
+    for (entity, _, mode) in ENTITIES {
+        views.add_spec(common::view(&format!("{entity}/view"), entity, mode));
+    }
+
+    let bus_manager = BusManager::new();
+    let mut buses = Vec::new();
+    for (entity, _, _) in ENTITIES {
+        let bus = bus_manager
+            .get_or_create_list_bus(&format!("{entity}/view"))
+            .await;
+        buses.push((entity, bus));
+    }
+
+    let shedder = LoadShedder::new(
+        LoadShedConfig::new()
+            .with_queue_limit(QUEUE)
+            .with_thresholds(0.5, 0.9)
+            .with_debounce(Duration::from_secs(60)),
+        ENTITIES
+            .iter()
+            .map(|(entity, priority, _)| (entity.to_string(), *priority))
+            .collect::<HashMap<_, _>>(),
+    );
+
+    let (mutations_tx, mutations_rx) = mpsc::channel::<MutationBatch>(QUEUE);
+    for n in 0..backlog {
+        let mutations = ENTITIES
+            .iter()
+            .map(|(entity, _, _)| {
+                Mutation::new(
+                    *entity,
+                    json!("k"),
+                    json!({ "n": n, entity.to_lowercase(): n }),
+                )
+            })
+            .collect();
+        mutations_tx
+            .send(MutationBatch::new(mutations))
+            .await
+            .unwrap();
+    }
+    shedder.sample(QUEUE - mutations_tx.capacity());
+    let level = shedder.level();
+    drop(mutations_tx);
+
+    let projector = Projector::new(
+        Arc::new(views),
+        bus_manager.clone(),
+        EntityCache::new(),
+        mutations_rx,
+    )
+    .with_load_shedding(shedder.clone());
+    projector.run().await;
+
+    let mut published = BTreeMap::new();
+    for (entity, mut bus) in buses {
+        let mut patches = Vec::new();
+        while let Ok(message) = bus.try_recv() {
+            let frame: Value = serde_json::from_slice(&message.payload).unwrap();
+            patches.push(frame["data"].clone());
+        }
+        published.insert(entity, patches);
+    }
+
+    Drained {
+        level,
+        published,
+        counts: shedder.counts(),
+    }
+}
+
+fn published_count(drained: &Drained, entity: &str) -> usize {
+    drained.published[entity].len()
+}
+
+#[tokio::test]
+async fn light_backlog_sheds_nothing() {
+    let drained = drain_backlog(5).await;
+
+    assert_eq!(drained.level, ShedLevel::Off);
+    for (entity, _, _) in ENTITIES {
+        assert_eq!(published_count(&drained, entity), 5, "{entity}");
+    }
+    assert!(drained.counts.is_empty());
+}
+
+#[tokio::test]
+async fn low_priority_entities_are_shed_first() {
+    let drained = drain_backlog(12).await;
+
+    assert_eq!(drained.level, ShedLevel::Low);
+    // Append items are dropped, state patches merged into one publish
+    assert_eq!(published_count(&drained, "Trade"), 0);
+    assert_eq!(
+        drained.published["Candle"],
+        vec![json!({ "n": 11, "candle": 11 })]
+    );
+    assert_eq!(published_count(&drained, "Pool"), 12);
+    assert_eq!(published_count(&drained, "Vault"), 12);
+
+    assert_eq!(
+        drained.counts,
+        BTreeMap::from([
+            (
+                "Candle".to_string(),
+                ShedCounts {
+                    dropped: 0,
+                    debounced: 12
+                }
+            ),
+            (
+                "Trade".to_string(),
+                ShedCounts {
+                    dropped: 12,
+                    debounced: 0
+                }
+            ),
+        ])
+    );
+}
+
+#[tokio::test]
+async fn normal_priority_entities_are_shed_next_and_high_never() {
+    let drained = drain_backlog(QUEUE).await;
+
+    assert_eq!(drained.level, ShedLevel::Normal);
+    assert_eq!(published_count(&drained, "Trade"), 0);
+    assert_eq!(published_count(&drained, "Candle"), 1);
+    assert_eq!(
+        drained.published["Pool"],
+        vec![json!({ "n": QUEUE - 1, "pool": QUEUE - 1 })]
+    );
+    assert_eq!(published_count(&drained, "Vault"), QUEUE);
+
+    assert_eq!(drained.counts["Pool"].debounced, QUEUE as u64);
+    assert!(!drained.counts.contains_key("Vault"));
+}
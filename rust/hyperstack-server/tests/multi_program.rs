@@ -0,0 +1,120 @@
+//! Specs with several programs: each program's parser runs alongside the
+//! others, feeding the same projector, and health tracks each program's
+//! stream separately.
+
+mod common;
+
+use hyperstack_interpreter::compiler::MultiEntityBytecode;
+use hyperstack_server::{
+    BackgroundHandle, Mode, MutationBatch, ParserSetupFn, Server, SlotContext, Spec, ViewIndex,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const TOKENS: u64 = 3;
+
+fn token(program: &str, slot: u64) -> MutationBatch {
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..common::batch(
+            "Token",
+            &format!("{program}-{slot}"),
+            json!({ "program": program, "slot": slot }),
+        )
+    }
+}
+
+/// A parser publishing `TOKENS` tokens for `program`, then either staying
+/// connected or losing its stream
+fn parser(program: &'static str, disconnects: bool) -> ParserSetupFn {
+    Arc::new(move |mutations_tx, health, _reconnection| {
+        Box::pin(async move {
+            let health = health.expect("health monitoring should be enabled");
+            health.record_connection().await;
+            for slot in 0..TOKENS {
+                mutations_tx.send(token(program, slot)).await?;
+                health.record_event().await;
+            }
+            if disconnects {
+                health.record_disconnection().await;
+            }
+
+            std::future::pending::<()>().await;
+            Ok(())
+        })
+    })
+}
+
+async fn serve() -> (SocketAddr, BackgroundHandle) {
+    let spec = Spec::new(MultiEntityBytecode::new().build(), "pump")
+        .with_parser_setup(parser("pump", false))
+        .add_program("amm", parser("amm", true));
+
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view("Token/list", "Token", Mode::List));
+
+    common::serve(
+        Server::builder()
+            .spec(spec)
+            .views(views)
+            .health_monitoring(),
+    )
+    .await
+}
+
+/// Send a GET request and return the status code and body
+async fn get(addr: SocketAddr, path: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .expect("response should have a body");
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+#[tokio::test]
+async fn every_program_feeds_the_projector() {
+    let (addr, background) = serve().await;
+
+    common::wait_for_stats(
+        addr,
+        "projector should process the tokens of both programs",
+        |stats| stats["cache"]["total_entities"] == json!(2 * TOKENS),
+    )
+    .await;
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn one_dead_program_stream_makes_the_server_unhealthy() {
+    let (addr, background) = serve().await;
+
+    let expected = json!([
+        { "program_id": "pump", "status": "Connected" },
+        { "program_id": "amm", "status": "Disconnected" },
+    ]);
+    let mut last = Value::Null;
+    for _ in 0..100 {
+        let (status, body) = get(addr, "/stream/status").await;
+        last = serde_json::from_str(&body).unwrap();
+        if last["programs"] == expected {
+            assert_eq!(status, 503, "{last}");
+            assert_eq!(last["healthy"], json!(false), "{last}");
+            assert_eq!(last["status"], json!("Disconnected"), "{last}");
+            background.shutdown();
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("amm should be reported disconnected: {last}");
+}
@@ -0,0 +1,127 @@
+//! Subscribing to a view through the URL path of the WebSocket connection.
+
+mod common;
+
+use common::Client;
+use futures_util::StreamExt;
+use hyperstack_server::{BackgroundHandle, Mode, MutationBatch, Server, SlotContext, ViewIndex};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+const TOKENS: u64 = 3;
+
+fn token(slot: u64) -> MutationBatch {
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..common::batch("Token", &format!("token-{slot}"), json!({ "slot": slot }))
+    }
+}
+
+async fn serve() -> (SocketAddr, BackgroundHandle) {
+    let (spec, tokens) = common::forwarding_spec();
+    for slot in 0..TOKENS {
+        tokens.send(token(slot)).unwrap();
+    }
+
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view("Token/list", "Token", Mode::List));
+    views.add_spec(common::view("Token/state", "Token", Mode::State));
+
+    let (addr, background) = common::serve(Server::builder().spec(spec).views(views)).await;
+    // Each of the two views caches every token
+    common::wait_for_stats(
+        addr,
+        &format!("projector should process {TOKENS} tokens"),
+        |stats| stats["cache"]["total_entities"] == json!(2 * TOKENS),
+    )
+    .await;
+    (addr, background)
+}
+
+/// Connect to `path` and expect the handshake to be refused, returning the
+/// status and the JSON body
+async fn rejected(addr: SocketAddr, path: &str) -> (u16, Value) {
+    match tokio_tungstenite::connect_async(format!("ws://{addr}/stream{path}")).await {
+        Err(WsError::Http(response)) => {
+            let status = response.status().as_u16();
+            let body = response.into_body().expect("rejection should have a body");
+            (status, serde_json::from_slice(&body).unwrap())
+        }
+        Ok(_) => panic!("handshake to {path} should be refused"),
+        Err(error) => panic!("unexpected handshake error: {error}"),
+    }
+}
+
+/// Read the next frame, which must be text
+async fn next_text(ws: &mut Client) -> Value {
+    let message = tokio::time::timeout(common::FRAME_TIMEOUT, ws.next())
+        .await
+        .expect("frame should arrive")
+        .unwrap()
+        .unwrap();
+    match message {
+        Message::Text(text) => serde_json::from_str(text.as_str()).unwrap(),
+        other => panic!("expected a text frame, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn unknown_view_is_refused_with_404() {
+    let (addr, background) = serve().await;
+
+    let (status, body) = rejected(addr, "/sub/Token/missing").await;
+    assert_eq!(status, 404);
+    assert_eq!(body["code"], json!("unknown-view"));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn state_view_without_key_is_refused() {
+    let (addr, background) = serve().await;
+
+    let (status, body) = rejected(addr, "/sub/Token/state").await;
+    assert_eq!(status, 400);
+    assert_eq!(body["code"], json!("missing-key"));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn list_view_streams_minimal_frames() {
+    let (addr, background) = serve().await;
+
+    let mut ws = common::connect(&format!("ws://{addr}/stream/sub/Token/list")).await;
+
+    let ack = next_text(&mut ws).await;
+    assert_eq!(ack["op"], json!("subscribed"));
+    assert_eq!(ack["view"], json!("Token/list"));
+
+    let snapshot = next_text(&mut ws).await;
+    assert_eq!(snapshot["op"], json!("snapshot"));
+    assert_eq!(snapshot["data"].as_array().unwrap().len(), TOKENS as usize);
+    for field in ["sub", "frameSeq", "entity", "mode"] {
+        assert!(snapshot.get(field).is_none(), "{field} should be omitted");
+    }
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn state_view_streams_the_keyed_entity() {
+    let (addr, background) = serve().await;
+
+    let mut ws = common::connect(&format!("ws://{addr}/stream/sub/Token/state?key=token-1")).await;
+
+    let ack = next_text(&mut ws).await;
+    assert_eq!(ack["op"], json!("subscribed"));
+
+    let snapshot = next_text(&mut ws).await;
+    let entities = snapshot["data"].as_array().unwrap();
+    assert_eq!(entities.len(), 1);
+    assert_eq!(entities[0]["key"], json!("token-1"));
+    assert_eq!(entities[0]["data"]["slot"], json!(1));
+
+    background.shutdown();
+}
@@ -0,0 +1,219 @@
+//! Backfilling state entities the server has never seen from a mock RPC.
+//!
+//! The mock answers `getAccountInfo` for `KNOWN` and returns no account for
+//! any other address. The spec's account backfill turns a fetched account
+//! into a `Token` mutation the way a generated spec runs it through the VM.
+
+mod common;
+
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use hyperstack_server::websocket::RateLimitWindow;
+use hyperstack_server::{
+    AccountBackfillFn, BackfillOutcome, BackgroundHandle, Mode, MutationBatch, RpcBackfill,
+    RpcBackfillConfig, Server, SlotContext, Spec, ViewIndex,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+const KNOWN: &str = "So11111111111111111111111111111111111111112";
+const MISSING: &str = "11111111111111111111111111111111";
+const RPC_SLOT: u64 = 4242;
+
+/// Serve `getAccountInfo` and return the url and the number of requests seen
+async fn mock_rpc() -> (String, Arc<AtomicUsize>) {
+    async fn get_account_info(
+        State(requests): State<Arc<AtomicUsize>>,
+        Json(request): Json<Value>,
+    ) -> Json<Value> {
+        requests.fetch_add(1, Ordering::SeqCst);
+        // Slow enough for concurrent subscriptions to overlap
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let value = if request["params"][0] == json!(KNOWN) {
+            json!({
+                // base64 of [1, 2, 3, 4]
+                "data": ["AQIDBA==", "base64"],
+                "owner": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                "lamports": 2039280,
+                "executable": false,
+                "rentEpoch": 361,
+            })
+        } else {
+            Value::Null
+        };
+        Json(json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": { "context": { "slot": RPC_SLOT }, "value": value },
+        }))
+    }
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let app = Router::new()
+        .route("/", post(get_account_info))
+        .with_state(requests.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (format!("http://{addr}"), requests)
+}
+
+/// An account backfill sending a `Token` mutation through the parser
+fn token_backfill(batches: mpsc::UnboundedSender<MutationBatch>) -> AccountBackfillFn {
+    Arc::new(move |account| {
+        let batch = MutationBatch {
+            slot_context: Some(SlotContext::new(account.slot, 0)),
+            ..common::batch(
+                "Token",
+                &account.address,
+                json!({
+                    "lamports": account.lamports,
+                    "size": account.data.len(),
+                    "slot": account.slot,
+                }),
+            )
+        };
+        let sent = batches.send(batch);
+        Box::pin(async move {
+            sent?;
+            Ok(true)
+        })
+    })
+}
+
+fn token_spec() -> Spec {
+    let (spec, batches) = common::forwarding_spec();
+    spec.with_account_backfill(token_backfill(batches))
+}
+
+async fn serve(rpc_url: &str) -> (SocketAddr, BackgroundHandle) {
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view("Token/state", "Token", Mode::State));
+
+    common::serve(
+        Server::builder()
+            .spec(token_spec())
+            .views(views)
+            .rpc_backfill(RpcBackfillConfig::new(rpc_url).with_account("Token", "TokenAccount")),
+    )
+    .await
+}
+
+/// Subscribe to the state of `key` and return the frame after the ack
+async fn subscribe_state(addr: SocketAddr, key: &str) -> Value {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    let message = json!({ "type": "subscribe", "view": "Token/state", "key": key });
+    common::send(&mut ws, message).await;
+
+    let ack = common::next_json(&mut ws).await;
+    assert_eq!(ack["op"], json!("subscribed"));
+    common::next_json(&mut ws).await
+}
+
+#[tokio::test]
+async fn unknown_state_key_is_served_from_rpc() {
+    let (rpc_url, requests) = mock_rpc().await;
+    let (addr, background) = serve(&rpc_url).await;
+
+    let snapshot = subscribe_state(addr, KNOWN).await;
+    assert_eq!(snapshot["op"], json!("snapshot"), "{snapshot}");
+    assert_eq!(snapshot["data"][0]["key"], json!(KNOWN));
+    let token = &snapshot["data"][0]["data"];
+    assert_eq!(token["lamports"], json!(2039280));
+    assert_eq!(token["size"], json!(4));
+    assert_eq!(token["slot"], json!(RPC_SLOT));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // The entity is cached now, so the next subscription needs no fetch
+    let snapshot = subscribe_state(addr, KNOWN).await;
+    assert_eq!(snapshot["data"][0]["data"]["slot"], json!(RPC_SLOT));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    background.shutdown();
+}
+
+fn recording_backfill(config: RpcBackfillConfig) -> (RpcBackfill, Arc<AtomicUsize>) {
+    let applied = Arc::new(AtomicUsize::new(0));
+    let counter = applied.clone();
+    let apply: AccountBackfillFn = Arc::new(move |account| {
+        assert_eq!(account.data, vec![1, 2, 3, 4]);
+        assert_eq!(account.slot, RPC_SLOT);
+        counter.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async { Ok(true) })
+    });
+    (RpcBackfill::new(config, apply), applied)
+}
+
+#[tokio::test]
+async fn concurrent_requests_for_a_key_share_one_fetch() {
+    let (rpc_url, requests) = mock_rpc().await;
+    let (backfill, applied) =
+        recording_backfill(RpcBackfillConfig::new(rpc_url).with_account("Token", "TokenAccount"));
+
+    let outcomes =
+        futures_util::future::join_all((0..8).map(|_| backfill.backfill("Token", KNOWN))).await;
+
+    assert!(outcomes.iter().all(|o| *o == BackfillOutcome::Applied));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert_eq!(applied.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn missing_accounts_are_cached_negatively() {
+    let (rpc_url, requests) = mock_rpc().await;
+    let (backfill, applied) = recording_backfill(
+        RpcBackfillConfig::new(rpc_url)
+            .with_account("Token", "TokenAccount")
+            .with_negative_ttl(Duration::from_millis(200)),
+    );
+
+    assert_eq!(
+        backfill.backfill("Token", MISSING).await,
+        BackfillOutcome::NotFound
+    );
+    assert_eq!(
+        backfill.backfill("Token", MISSING).await,
+        BackfillOutcome::Skipped
+    );
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(
+        backfill.backfill("Token", MISSING).await,
+        BackfillOutcome::NotFound
+    );
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert_eq!(applied.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn fetches_are_rate_limited_and_unmapped_entities_skipped() {
+    let (rpc_url, requests) = mock_rpc().await;
+    let (backfill, _) = recording_backfill(
+        RpcBackfillConfig::new(rpc_url)
+            .with_account("Token", "TokenAccount")
+            .with_rate_limit(RateLimitWindow::new(1, Duration::from_secs(60))),
+    );
+
+    assert_eq!(
+        backfill.backfill("Pool", KNOWN).await,
+        BackfillOutcome::Skipped
+    );
+    assert_eq!(
+        backfill.backfill("Token", MISSING).await,
+        BackfillOutcome::NotFound
+    );
+    assert_eq!(
+        backfill.backfill("Token", KNOWN).await,
+        BackfillOutcome::RateLimited
+    );
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
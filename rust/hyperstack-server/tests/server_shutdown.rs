@@ -0,0 +1,231 @@
+mod common;
+
+use common::Client;
+use flate2::read::GzDecoder;
+use futures_util::StreamExt;
+use hyperstack_interpreter::compiler::MultiEntityBytecode;
+use hyperstack_server::{
+    Mode, MutationBatch, ParserSetupFn, Server, ServerHandle, Spec, StopReason, ViewIndex,
+};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+
+const VIEW: &str = "Token/list";
+const BATCHES: usize = 200;
+
+/// Sends its sender when dropped, to tell that the parser stopped
+struct DropSignal(Option<oneshot::Sender<()>>);
+
+impl Drop for DropSignal {
+    fn drop(&mut self) {
+        if let Some(tx) = self.0.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+struct Parser {
+    /// Queue `BATCHES` updates at once when sent
+    go: oneshot::Sender<()>,
+    /// Resolves once they are queued
+    queued: oneshot::Receiver<()>,
+    /// Resolves once the parser is dropped
+    stopped: oneshot::Receiver<()>,
+}
+
+/// A spec whose parser queues a burst of updates on request, then idles
+fn bursting_spec() -> (Spec, Parser) {
+    let (go_tx, go_rx) = oneshot::channel();
+    let (queued_tx, queued_rx) = oneshot::channel();
+    let (stopped_tx, stopped_rx) = oneshot::channel();
+    let state = Arc::new(Mutex::new(Some((go_rx, queued_tx, stopped_tx))));
+
+    let setup: ParserSetupFn = Arc::new(move |mutations_tx, _health, _reconnection| {
+        let state = state.lock().unwrap().take();
+        Box::pin(async move {
+            let Some((go, queued, stopped)) = state else {
+                return Ok(());
+            };
+            let _stopped = DropSignal(Some(stopped));
+            if go.await.is_ok() {
+                for index in 0..BATCHES {
+                    mutations_tx.send(token(index)).await?;
+                }
+                let _ = queued.send(());
+            }
+            std::future::pending::<()>().await;
+            Ok(())
+        })
+    });
+
+    let spec =
+        Spec::new(MultiEntityBytecode::new().build(), "test_program").with_parser_setup(setup);
+    let parser = Parser {
+        go: go_tx,
+        queued: queued_rx,
+        stopped: stopped_rx,
+    };
+    (spec, parser)
+}
+
+fn token(index: usize) -> MutationBatch {
+    common::batch(
+        "Token",
+        &format!("token-{index}"),
+        json!({ "index": index }),
+    )
+}
+
+fn views() -> ViewIndex {
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view(VIEW, "Token", Mode::List));
+    views
+}
+
+fn free_addr() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+async fn start(spec: Spec, addr: SocketAddr, grace: Duration) -> ServerHandle {
+    Server::builder()
+        .spec(spec)
+        .views(views())
+        .websocket()
+        .bind(addr)
+        .shutdown_grace(grace)
+        .start_with_shutdown()
+        .await
+        .expect("server should start")
+}
+
+async fn next_message(ws: &mut Client) -> Message {
+    tokio::time::timeout(Duration::from_secs(10), ws.next())
+        .await
+        .expect("message should arrive")
+        .expect("connection should still be open")
+        .unwrap()
+}
+
+fn decode(message: Message) -> Value {
+    let bytes = match message {
+        Message::Binary(bytes) => bytes.to_vec(),
+        Message::Text(text) => text.as_bytes().to_vec(),
+        other => panic!("expected a data frame, got {other:?}"),
+    };
+    let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        decompressed
+    } else {
+        bytes
+    };
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+async fn subscribe(ws: &mut Client) {
+    let subscribe = json!({ "type": "subscribe", "view": VIEW, "withSnapshot": false });
+    common::send(ws, subscribe).await;
+    let ack = common::next_json(ws).await;
+    assert_eq!(ack["op"], json!("subscribed"), "{ack}");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shutdown_delivers_queued_updates_then_closes_clients() {
+    let (spec, parser) = bursting_spec();
+    let addr = free_addr();
+    let server = start(spec, addr, Duration::from_secs(10)).await;
+
+    let mut ws = common::connect(&format!("ws://{addr}")).await;
+    subscribe(&mut ws).await;
+
+    parser.go.send(()).unwrap();
+    parser.queued.await.unwrap();
+    let shutdown = tokio::spawn(server.shutdown());
+
+    let mut keys = HashSet::new();
+    let close = loop {
+        match next_message(&mut ws).await {
+            Message::Close(frame) => break frame,
+            message => {
+                let frame = decode(message);
+                if let Some(key) = frame["key"].as_str() {
+                    keys.insert(key.to_string());
+                }
+            }
+        }
+    };
+    assert_eq!(keys.len(), BATCHES, "every queued update is delivered");
+    assert_eq!(close.expect("close frame").code, CloseCode::Away);
+
+    // Reading past the close frame sends the client's acknowledgement
+    assert!(ws.next().await.is_none());
+
+    let report = tokio::time::timeout(Duration::from_secs(5), shutdown)
+        .await
+        .expect("shutdown should finish once the client is closed")
+        .unwrap()
+        .unwrap();
+    assert_eq!(report.reason, StopReason::Requested);
+    assert!(report.graceful);
+    assert_eq!(report.open_connections, 0);
+
+    tokio::time::timeout(Duration::from_secs(1), parser.stopped)
+        .await
+        .expect("the parser should be stopped")
+        .unwrap();
+    assert!(
+        TcpStream::connect(addr).await.is_err(),
+        "the listener should be closed"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shutdown_past_the_grace_period_is_reported_as_forced() {
+    let (spec, _parser) = bursting_spec();
+    let addr = free_addr();
+    let server = start(spec, addr, Duration::from_millis(300)).await;
+
+    // Never reads, so never acknowledges the close frame
+    let _ws = common::connect(&format!("ws://{addr}")).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let report = tokio::time::timeout(Duration::from_secs(2), server.shutdown())
+        .await
+        .expect("shutdown should give up after the grace period")
+        .unwrap();
+    assert_eq!(report.reason, StopReason::Requested);
+    assert!(!report.graceful);
+    assert_eq!(report.open_connections, 1);
+    assert!(report.elapsed >= Duration::from_millis(300));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shutdown_token_stops_the_server_while_another_task_waits() {
+    let (spec, _parser) = bursting_spec();
+    let server = start(spec, free_addr(), Duration::from_secs(1)).await;
+
+    let token = server.shutdown_token();
+    let waiting = tokio::spawn(server.wait());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!waiting.is_finished());
+
+    token.cancel();
+    let report = tokio::time::timeout(Duration::from_secs(2), waiting)
+        .await
+        .expect("the server should stop")
+        .unwrap()
+        .unwrap();
+    assert_eq!(report.reason, StopReason::Requested);
+    assert!(report.graceful);
+}
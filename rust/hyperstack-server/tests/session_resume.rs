@@ -0,0 +1,167 @@
+//! Resumable sessions: a client that drops its connection and comes back
+//! with its session token gets its subscriptions back, along with only the
+//! entities updated while it was away. Once the session expires, the token
+//! is refused and the client starts over.
+
+mod common;
+
+use common::{
+    batch, forwarding_spec, http_get_json, next_json, send, view, wait_for_stats, Client,
+};
+use hyperstack_server::{
+    BackgroundHandle, Mode, MutationBatch, Server, SessionConfig, SlotContext, ViewIndex,
+    WebSocketConfig,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const VIEW: &str = "Token/list";
+
+fn token(key: &str, slot: u64) -> MutationBatch {
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..batch("Token", key, json!({ "slot": slot }))
+    }
+}
+
+async fn serve(
+    sessions: SessionConfig,
+) -> (
+    SocketAddr,
+    BackgroundHandle,
+    mpsc::UnboundedSender<MutationBatch>,
+) {
+    let (spec, batches) = forwarding_spec();
+    let mut views = ViewIndex::new();
+    views.add_spec(view(VIEW, "Token", Mode::List));
+
+    let (addr, background) = common::serve(
+        Server::builder()
+            .spec(spec)
+            .views(views)
+            .websocket_config(WebSocketConfig::default().with_sessions(sessions)),
+    )
+    .await;
+    (addr, background, batches)
+}
+
+/// Connect and read the session token from the server's hello
+async fn connect(addr: SocketAddr) -> (Client, String) {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    let hello = next_json(&mut ws).await;
+    assert_eq!(hello["type"], json!("hello"), "{hello}");
+    let session = hello["session"]
+        .as_str()
+        .expect("hello should carry a session token")
+        .to_string();
+    (ws, session)
+}
+
+fn keys(snapshot: &Value) -> Vec<&str> {
+    let mut keys: Vec<&str> = snapshot["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entity| entity["key"].as_str().unwrap())
+        .collect();
+    keys.sort();
+    keys
+}
+
+/// Subscribe to the whole list with `a` and `b` cached, then drop the
+/// connection without a close frame
+async fn subscribe_and_drop(
+    addr: SocketAddr,
+    batches: &mpsc::UnboundedSender<MutationBatch>,
+) -> String {
+    batches.send(token("a", 1)).unwrap();
+    batches.send(token("b", 2)).unwrap();
+    wait_for_stats(addr, "projector should cache two entities", |stats| {
+        stats["cache"]["total_entities"] == json!(2)
+    })
+    .await;
+
+    let (mut ws, session) = connect(addr).await;
+    send(&mut ws, json!({ "type": "subscribe", "view": VIEW })).await;
+    let subscribed = next_json(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"), "{subscribed}");
+    let snapshot = next_json(&mut ws).await;
+    assert_eq!(snapshot["op"], json!("snapshot"), "{snapshot}");
+    assert_eq!(keys(&snapshot), ["a", "b"]);
+
+    drop(ws);
+    wait_for_stats(
+        addr,
+        "the dropped client's session should be kept",
+        |stats| stats["sessions"]["retained"] == json!(1),
+    )
+    .await;
+    session
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn resuming_within_the_ttl_sends_only_what_changed() {
+    let (addr, _background, batches) = serve(SessionConfig::new()).await;
+    let session = subscribe_and_drop(addr, &batches).await;
+
+    batches.send(token("b", 3)).unwrap();
+    batches.send(token("c", 4)).unwrap();
+    wait_for_stats(addr, "projector should cache the new entity", |stats| {
+        stats["cache"]["total_entities"] == json!(3)
+    })
+    .await;
+
+    let (mut ws, _) = connect(addr).await;
+    send(&mut ws, json!({ "type": "hello", "session": session })).await;
+    let resumed = next_json(&mut ws).await;
+    assert_eq!(
+        resumed,
+        json!({ "type": "session", "resumed": true, "subscriptions": [format!("{VIEW}:*")] })
+    );
+    let subscribed = next_json(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"), "{subscribed}");
+    let delta = next_json(&mut ws).await;
+    assert_eq!(delta["op"], json!("snapshot"), "{delta}");
+    assert_eq!(keys(&delta), ["b", "c"], "only entities updated since");
+
+    batches.send(token("a", 5)).unwrap();
+    let live = next_json(&mut ws).await;
+    assert_eq!(live["key"], json!("a"), "{live}");
+
+    send(&mut ws, json!({ "type": "hello", "session": session })).await;
+    let reused = next_json(&mut ws).await;
+    assert_eq!(reused["resumed"], json!(false), "tokens resume once");
+
+    let stats = http_get_json(addr, "/stream/stats").await;
+    assert_eq!(stats["sessions"]["resumed"], json!(1));
+    assert_eq!(stats["sessions"]["refused"], json!(1));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn resuming_after_the_ttl_is_refused() {
+    let (addr, _background, batches) =
+        serve(SessionConfig::new().with_ttl(Duration::from_millis(200))).await;
+    let session = subscribe_and_drop(addr, &batches).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let (mut ws, _) = connect(addr).await;
+    send(&mut ws, json!({ "type": "hello", "session": session })).await;
+    let refused = next_json(&mut ws).await;
+    assert_eq!(
+        refused,
+        json!({ "type": "session", "resumed": false, "subscriptions": [] })
+    );
+
+    // Nothing was restored: the client subscribes again from scratch
+    send(&mut ws, json!({ "type": "subscribe", "view": VIEW })).await;
+    let subscribed = next_json(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"), "{subscribed}");
+    let snapshot = next_json(&mut ws).await;
+    assert_eq!(keys(&snapshot), ["a", "b"]);
+
+    let stats = http_get_json(addr, "/stream/stats").await;
+    assert_eq!(stats["sessions"]["retained"], json!(0));
+    assert_eq!(stats["sessions"]["expired"], json!(1));
+}
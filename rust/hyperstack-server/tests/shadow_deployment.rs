@@ -0,0 +1,102 @@
+mod common;
+
+use hyperstack_interpreter::ast::{BinaryOp, ComputedExpr, ComputedFieldSpec};
+use hyperstack_interpreter::vm::VmContext;
+use hyperstack_server::{
+    BackgroundHandle, Mode, MutationBatch, Server, SlotContext, Spec, ViewIndex,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// `price.notional = price.amount * multiplier`
+fn notional_spec(multiplier: i64) -> ComputedFieldSpec {
+    ComputedFieldSpec {
+        target_path: "price.notional".to_string(),
+        expression: ComputedExpr::Binary {
+            op: BinaryOp::Mul,
+            left: Box::new(ComputedExpr::FieldRef {
+                path: "price.amount".to_string(),
+            }),
+            right: Box::new(ComputedExpr::Literal {
+                value: json!(multiplier),
+            }),
+        },
+        result_type: "u64".to_string(),
+        time_dependent: false,
+    }
+}
+
+/// A spec whose parser replays the same three account updates through its own
+/// VM, evaluating `price.notional` with the given multiplier.
+fn spec_with_multiplier(multiplier: i64) -> Spec {
+    let (spec, batches) = common::forwarding_spec();
+    let vm = VmContext::new();
+    let computed = [notional_spec(multiplier)];
+    let updates = [(1, "mint-a", 10), (2, "mint-b", 20), (3, "mint-a", 15)];
+
+    let mut states = std::collections::HashMap::new();
+    for (slot, key, amount) in updates {
+        let state = states.entry(key).or_insert_with(|| json!({ "price": {} }));
+        state["price"]["amount"] = json!(amount);
+        vm.evaluate_computed_fields_from_ast(state, &computed)
+            .expect("computed fields should evaluate");
+
+        let batch = MutationBatch {
+            slot_context: Some(SlotContext::new(slot, 0)),
+            ..common::batch("Token", key, state.clone())
+        };
+        batches.send(batch).unwrap();
+    }
+
+    spec
+}
+
+async fn serve(production: Spec, shadow: Spec) -> (SocketAddr, BackgroundHandle) {
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view("Token/list", "Token", Mode::List));
+
+    common::serve(
+        Server::builder()
+            .spec(production)
+            .views(views)
+            .shadow_spec(shadow),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn reports_changed_computed_field_without_serving_shadow_output() {
+    let (addr, background) = serve(spec_with_multiplier(2), spec_with_multiplier(3)).await;
+
+    let mut report = Value::Null;
+    for _ in 0..100 {
+        report = common::http_get_json(addr, "/stream/admin/shadow").await;
+        if report["entities"]["Token"]["diverging_key_count"] == json!(2) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let token = &report["entities"]["Token"];
+    assert_eq!(token["diverging_key_count"], json!(2), "report: {report}");
+    assert_eq!(token["diverging_keys"], json!(["mint-a", "mint-b"]));
+    assert_eq!(token["production_mutations"], json!(3));
+    assert_eq!(token["shadow_mutations"], json!(3));
+    assert_eq!(token["mutation_delta"], json!(0));
+
+    let fields = token["fields"].as_object().unwrap();
+    assert_eq!(fields.keys().collect::<Vec<_>>(), vec!["price.notional"]);
+    let notional = &fields["price.notional"];
+    assert_eq!(notional["diverging_keys"], json!(2));
+    let latest = notional["examples"].as_array().unwrap().last().unwrap();
+    assert_eq!(latest["key"], json!("mint-a"));
+    assert_eq!(latest["production"], json!(30));
+    assert_eq!(latest["shadow"], json!(45));
+
+    // Production mutations still reach the projector through the shadow tap
+    let stats = common::http_get_json(addr, "/stream/stats").await;
+    assert_eq!(stats["cache"]["total_entities"], json!(2));
+
+    background.shutdown();
+}
@@ -0,0 +1,234 @@
+//! Load test for the snapshot queue: a burst of new list subscribers takes
+//! turns building snapshots, so live updates keep reaching the clients that
+//! were already connected.
+//!
+//! A ticker sends an update every few milliseconds carrying the time it was
+//! sent, and an existing client measures how late each one arrives, first
+//! on a quiet server and then while 500 clients subscribe to a full list at
+//! once.
+
+mod common;
+
+use common::{
+    batch, connect, forwarding_spec, http_get_json, send, serve, try_next_json, view,
+    wait_for_stats, Client,
+};
+use hyperstack_server::{
+    BackgroundHandle, Mode, MutationBatch, Server, SnapshotQueueConfig, Spec, ViewIndex,
+    WebSocketConfig,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+
+const VIEW: &str = "Token/list";
+const ENTITIES: usize = 500;
+const SUBSCRIBERS: usize = 500;
+const TICKS: u64 = 200;
+const TICK: Duration = Duration::from_millis(5);
+
+fn token(key: &str, patch: Value) -> MutationBatch {
+    batch("Token", key, patch)
+}
+
+async fn serve_queued(spec: Spec, queue: SnapshotQueueConfig) -> (SocketAddr, BackgroundHandle) {
+    let mut views = ViewIndex::new();
+    views.add_spec(view(VIEW, "Token", Mode::List));
+    serve(
+        Server::builder()
+            .spec(spec)
+            .views(views)
+            .websocket_config(WebSocketConfig::default().with_snapshot_queue(queue)),
+    )
+    .await
+}
+
+/// The next frame, allowing for a server busy with the burst
+async fn next_json(ws: &mut Client) -> Value {
+    try_next_json(ws, Duration::from_secs(30))
+        .await
+        .expect("frame should arrive")
+}
+
+async fn subscribe(ws: &mut Client, message: Value) -> Value {
+    let mut subscribe = json!({ "type": "subscribe", "view": VIEW });
+    subscribe
+        .as_object_mut()
+        .unwrap()
+        .extend(message.as_object().unwrap().clone());
+    send(ws, subscribe).await;
+    let ack = next_json(ws).await;
+    assert_eq!(ack["op"], json!("subscribed"), "{ack}");
+    ack
+}
+
+/// Fill the view with entities large enough to make snapshots expensive
+async fn fill_view(addr: SocketAddr, batches: &mpsc::UnboundedSender<MutationBatch>) {
+    let description = "x".repeat(400);
+    for index in 0..ENTITIES {
+        batches
+            .send(token(
+                &format!("token-{index}"),
+                json!({ "index": index, "description": description, "tags": ["a", "b", "c"] }),
+            ))
+            .unwrap();
+    }
+    wait_for_stats(addr, "projector should cache every entity", |stats| {
+        stats["cache"]["total_entities"] == json!(ENTITIES)
+    })
+    .await;
+}
+
+/// Send `TICKS` updates to the `ticker` entity and measure how late each
+/// reaches `ws`, which subscribed to it alone
+async fn measure_ticks(
+    ws: &mut Client,
+    batches: &mpsc::UnboundedSender<MutationBatch>,
+    epoch: Instant,
+) -> Duration {
+    let ticker = batches.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        for tick in 0..TICKS {
+            interval.tick().await;
+            let sent = epoch.elapsed().as_micros() as u64;
+            let _ = ticker.send(token("ticker", json!({ "tick": tick, "sent": sent })));
+        }
+    });
+
+    let mut latencies = Vec::new();
+    loop {
+        let frame = next_json(ws).await;
+        let received = epoch.elapsed();
+        let data = &frame["data"];
+        let sent = Duration::from_micros(data["sent"].as_u64().expect("ticks carry `sent`"));
+        latencies.push(received.saturating_sub(sent));
+        if data["tick"] == json!(TICKS - 1) {
+            break;
+        }
+    }
+
+    latencies.sort();
+    latencies[(latencies.len() * 99 / 100).min(latencies.len() - 1)]
+}
+
+/// What a burst subscriber saw
+struct BurstResult {
+    pending: bool,
+    snapshot_rows: usize,
+    /// Whether only snapshot frames came before the last one
+    snapshot_first: bool,
+}
+
+/// Connect `SUBSCRIBERS` clients, subscribe them all at once to the whole
+/// view and read each one's snapshot, on a runtime of their own so that
+/// reading them doesn't compete with the server
+fn burst(
+    addr: SocketAddr,
+    connected: oneshot::Sender<()>,
+) -> std::thread::JoinHandle<Vec<BurstResult>> {
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            let mut clients = Vec::new();
+            for _ in 0..SUBSCRIBERS {
+                clients.push(connect(&format!("ws://{addr}/stream")).await);
+            }
+            connected.send(()).unwrap();
+
+            let tasks: Vec<_> = clients
+                .into_iter()
+                .map(|mut ws| {
+                    tokio::spawn(async move {
+                        let ack = subscribe(&mut ws, json!({})).await;
+                        let mut result = BurstResult {
+                            pending: ack["status"] == json!("snapshot_pending"),
+                            snapshot_rows: 0,
+                            snapshot_first: true,
+                        };
+                        loop {
+                            let frame = next_json(&mut ws).await;
+                            if frame["op"] != json!("snapshot") {
+                                result.snapshot_first = false;
+                                continue;
+                            }
+                            result.snapshot_rows += frame["data"].as_array().unwrap().len();
+                            if frame["complete"] == json!(true) {
+                                break;
+                            }
+                        }
+                        (result, ws)
+                    })
+                })
+                .collect();
+
+            let mut results = Vec::new();
+            // Connections stay open until every snapshot is in
+            let mut open = Vec::new();
+            for task in tasks {
+                let (result, ws) = task.await.unwrap();
+                results.push(result);
+                open.push(ws);
+            }
+            results
+        })
+    })
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn live_latency_holds_while_a_burst_of_subscribers_waits_for_snapshots() {
+    let (spec, batches) = forwarding_spec();
+    let (addr, background) =
+        serve_queued(spec, SnapshotQueueConfig::new().with_max_concurrent(1)).await;
+    fill_view(addr, &batches).await;
+
+    let epoch = Instant::now();
+    let mut live = connect(&format!("ws://{addr}/stream")).await;
+    subscribe(&mut live, json!({ "key": "ticker", "withSnapshot": false })).await;
+
+    let quiet_p99 = measure_ticks(&mut live, &batches, epoch).await;
+
+    // Start ticking as the subscribe requests go out
+    let (connected_tx, connected_rx) = oneshot::channel();
+    let burst = burst(addr, connected_tx);
+    connected_rx.await.unwrap();
+    let burst_p99 = measure_ticks(&mut live, &batches, epoch).await;
+    let results = tokio::task::spawn_blocking(move || burst.join().unwrap())
+        .await
+        .unwrap();
+
+    assert!(
+        burst_p99 <= quiet_p99 * 3 + Duration::from_millis(25),
+        "live p99 went from {quiet_p99:?} to {burst_p99:?} during the burst"
+    );
+
+    assert_eq!(results.len(), SUBSCRIBERS);
+    let pending = results.iter().filter(|result| result.pending).count();
+    assert!(pending > 0, "some snapshots should have waited their turn");
+    for result in &results {
+        assert_eq!(result.snapshot_rows, ENTITIES);
+        if result.pending {
+            assert!(
+                result.snapshot_first,
+                "updates should not arrive before a pending snapshot"
+            );
+        }
+    }
+
+    let stats = http_get_json(addr, "/stream/stats").await;
+    let queue = &stats["snapshot_queue"];
+    assert_eq!(queue["max_concurrent"], json!(1));
+    assert_eq!(queue["queued"], json!(0));
+    assert_eq!(queue["rejected"], json!(0));
+    assert_eq!(queue["waited"], json!(pending));
+    assert!(
+        queue["max_wait_micros"].as_u64().unwrap() >= queue["mean_wait_micros"].as_u64().unwrap()
+    );
+
+    background.shutdown();
+}
@@ -0,0 +1,484 @@
+//! Reloading the spec of a served router while clients stay connected.
+//!
+//! The specs are built from hand-written bytecode: only the entity names,
+//! state ids and handler event types matter to a reload. Their parsers hand
+//! the mutation sender to the test, which plays the VM.
+
+mod common;
+
+use common::Client;
+use hyperstack_interpreter::ast::{
+    FieldPath, SortOrder, ViewDef, ViewOutput, ViewSource, ViewTransform,
+};
+use hyperstack_interpreter::compiler::{EntityBytecode, MultiEntityBytecode, OpCode};
+use hyperstack_server::{
+    BackgroundHandle, LiveBytecode, MutationBatch, ParserSetupFn, ReloadError, ReloadReport,
+    Server, Spec, SpecReloader,
+};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// A parser's program id and mutation sender
+type Started = (String, mpsc::Sender<MutationBatch>);
+
+/// Parsers started so far
+#[derive(Clone, Default)]
+struct Parsers {
+    senders: Arc<Mutex<Vec<Started>>>,
+    /// Set when a parser's task is aborted
+    stopped: Arc<AtomicUsize>,
+}
+
+impl Parsers {
+    fn setup(&self, program_id: &str) -> ParserSetupFn {
+        let parsers = self.clone();
+        let program_id = program_id.to_string();
+        Arc::new(move |mutations_tx, _health, _reconnection| {
+            parsers
+                .senders
+                .lock()
+                .unwrap()
+                .push((program_id.clone(), mutations_tx));
+            let stopped = StopGuard(parsers.stopped.clone());
+            Box::pin(async move {
+                let _stopped = stopped;
+                std::future::pending::<()>().await;
+                Ok(())
+            })
+        })
+    }
+
+    /// The sender of the latest parser, once it has started
+    async fn latest(&self) -> Started {
+        for _ in 0..100 {
+            if let Some(latest) = self.senders.lock().unwrap().last().cloned() {
+                return latest;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("parser should start");
+    }
+}
+
+struct StopGuard(Arc<AtomicUsize>);
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn entity(name: &str, state_id: u32, events: &[&str]) -> EntityBytecode {
+    EntityBytecode {
+        state_id,
+        handlers: events
+            .iter()
+            .map(|event| {
+                let handler = vec![OpCode::AbortIfNullKey {
+                    key: 0,
+                    is_account_event: true,
+                }];
+                (event.to_string(), handler)
+            })
+            .collect(),
+        entity_name: name.to_string(),
+        when_events: Default::default(),
+        non_emitted_fields: Default::default(),
+        wide_integer_fields: Default::default(),
+        computed_paths: vec![],
+        trace_fields: vec![],
+        priority: Default::default(),
+        delete_on: None,
+        event_schemas: Default::default(),
+        computed_fields_evaluator: None,
+    }
+}
+
+fn bytecode(entities: Vec<EntityBytecode>) -> MultiEntityBytecode {
+    let mut bytecode = MultiEntityBytecode::new().build();
+    for entity in entities {
+        bytecode.entities.insert(entity.entity_name.clone(), entity);
+    }
+    bytecode
+}
+
+fn spec(parsers: &Parsers, program_id: &str, entities: Vec<EntityBytecode>) -> Spec {
+    Spec::new(bytecode(entities), program_id).with_parser_setup(parsers.setup(program_id))
+}
+
+/// `Round/top`: rounds by descending motherlode
+fn top_rounds() -> ViewDef {
+    ViewDef {
+        id: "Round/top".to_string(),
+        source: ViewSource::Entity {
+            name: "Round".to_string(),
+        },
+        pipeline: vec![ViewTransform::Sort {
+            key: FieldPath::new(&["motherlode"]),
+            order: SortOrder::Desc,
+        }],
+        output: ViewOutput::Collection,
+    }
+}
+
+async fn serve(spec: Spec) -> (SocketAddr, BackgroundHandle, SpecReloader) {
+    let runtime = Server::builder()
+        .spec(spec)
+        .build()
+        .expect("runtime should build");
+    let reloader = runtime.reload_handle();
+    let (addr, background) = common::serve_runtime(runtime).await;
+    (addr, background, reloader)
+}
+
+/// The next frame that is not a snapshot
+async fn next_update(ws: &mut Client) -> Value {
+    loop {
+        let frame = common::next_json(ws).await;
+        if frame["op"] != json!("snapshot") {
+            return frame;
+        }
+    }
+}
+
+async fn subscribe(addr: SocketAddr, view: &str) -> Client {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    common::send(&mut ws, json!({ "type": "subscribe", "view": view })).await;
+
+    let subscribed = common::next_json(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"), "{subscribed}");
+    ws
+}
+
+#[tokio::test]
+async fn views_are_added_and_removed_under_active_subscriptions() {
+    let parsers = Parsers::default();
+    let (addr, background, reloader) = serve(spec(
+        &parsers,
+        "ore",
+        vec![
+            entity("Round", 0, &["RoundState"]),
+            entity("Miner", 1, &["MinerState"]),
+        ],
+    ))
+    .await;
+    let (_, parser_tx) = parsers.latest().await;
+
+    let mut rounds = subscribe(addr, "Round/list").await;
+    let mut miners = subscribe(addr, "Miner/list").await;
+    parser_tx
+        .send(common::batch("Round", "1", json!({ "motherlode": 10 })))
+        .await
+        .unwrap();
+    parser_tx
+        .send(common::batch("Miner", "m1", json!({ "rewards": 1 })))
+        .await
+        .unwrap();
+    assert_eq!(next_update(&mut rounds).await["key"], json!("1"));
+    assert_eq!(next_update(&mut miners).await["key"], json!("m1"));
+
+    // Updates keep flowing while the spec is swapped
+    let streaming = tokio::spawn({
+        let parser_tx = parser_tx.clone();
+        async move {
+            for n in 2..=50 {
+                parser_tx
+                    .send(common::batch(
+                        "Round",
+                        &n.to_string(),
+                        json!({ "motherlode": n * 10 }),
+                    ))
+                    .await
+                    .unwrap();
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }
+    });
+
+    let report = reloader
+        .reload_spec(
+            spec(&parsers, "ore", vec![entity("Round", 0, &["RoundState"])])
+                .with_views(vec![top_rounds()]),
+        )
+        .await
+        .expect("reload should apply");
+    assert_eq!(
+        report,
+        ReloadReport {
+            added_views: vec!["Round/top".to_string()],
+            removed_views: vec![
+                "Miner/append".to_string(),
+                "Miner/list".to_string(),
+                "Miner/state".to_string(),
+            ],
+            schema_changes: vec![],
+            parser_restarted: false,
+        }
+    );
+
+    // The removed view's subscriber is told, and its connection stays usable
+    let notice = next_update(&mut miners).await;
+    assert_eq!(
+        notice,
+        json!({ "type": "view_removed", "view": "Miner/list" })
+    );
+    parser_tx
+        .send(common::batch("Miner", "m2", json!({ "rewards": 2 })))
+        .await
+        .unwrap();
+    let resubscribe = json!({ "type": "subscribe", "view": "Round/list", "key": "1" });
+    common::send(&mut miners, resubscribe).await;
+    assert_eq!(
+        common::next_json(&mut miners).await["op"],
+        json!("subscribed")
+    );
+
+    // The surviving subscription misses none of the streamed rounds
+    streaming.await.unwrap();
+    let mut seen = HashSet::new();
+    while seen.len() < 49 {
+        let frame = next_update(&mut rounds).await;
+        assert_ne!(frame["type"], json!("view_removed"), "{frame}");
+        seen.insert(frame["key"].as_str().unwrap().to_string());
+    }
+    assert!((2..=50).all(|n| seen.contains(&n.to_string())));
+
+    // The added view is served from the rounds cached before the reload
+    let mut top = subscribe(addr, "Round/top").await;
+    let snapshot = common::next_json(&mut top).await;
+    assert_eq!(snapshot["op"], json!("snapshot"), "{snapshot}");
+    assert_eq!(snapshot["data"][0]["key"], json!("50"));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn changed_entities_announce_their_schema() {
+    let parsers = Parsers::default();
+    let (addr, background, reloader) = serve(spec(
+        &parsers,
+        "ore",
+        vec![
+            entity("Round", 0, &["RoundState"]),
+            entity("Miner", 1, &["MinerState"]),
+        ],
+    ))
+    .await;
+
+    let mut rounds = subscribe(addr, "Round/list").await;
+    let report = reloader
+        .reload_spec(spec(
+            &parsers,
+            "ore",
+            vec![
+                entity("Round", 0, &["RoundState", "Checkpoint"]),
+                entity("Miner", 1, &["MinerState"]),
+            ],
+        ))
+        .await
+        .unwrap();
+    assert_eq!(report.schema_changes, vec!["Round".to_string()]);
+    assert!(report.added_views.is_empty() && report.removed_views.is_empty());
+
+    let notice = next_update(&mut rounds).await;
+    assert_eq!(notice["type"], json!("schema_changed"));
+    assert_eq!(notice["view"], json!("Round/list"));
+    assert_eq!(notice["entity"], json!("Round"));
+    assert_eq!(notice["schema_hash"].as_str().unwrap().len(), 16);
+
+    // Reloading the same spec again changes nothing
+    let report = reloader
+        .reload_spec(spec(
+            &parsers,
+            "ore",
+            vec![
+                entity("Round", 0, &["Checkpoint", "RoundState"]),
+                entity("Miner", 1, &["MinerState"]),
+            ],
+        ))
+        .await
+        .unwrap();
+    assert_eq!(report, ReloadReport::default());
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn cached_state_cannot_move_to_another_state_id() {
+    let parsers = Parsers::default();
+    let (addr, background, reloader) = serve(spec(
+        &parsers,
+        "ore",
+        vec![entity("Round", 0, &["RoundState"])],
+    ))
+    .await;
+    let (_, parser_tx) = parsers.latest().await;
+
+    let mut rounds = subscribe(addr, "Round/list").await;
+    parser_tx
+        .send(common::batch("Round", "1", json!({ "motherlode": 10 })))
+        .await
+        .unwrap();
+    next_update(&mut rounds).await;
+
+    let error = reloader
+        .reload_spec(spec(
+            &parsers,
+            "ore",
+            vec![entity("Round", 3, &["RoundState"])],
+        ))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, ReloadError::SchemaEvolution { entity, .. } if entity == "Round"),
+        "{error}"
+    );
+
+    // Nothing changed: the subscription still gets updates
+    parser_tx
+        .send(common::batch("Round", "2", json!({ "motherlode": 20 })))
+        .await
+        .unwrap();
+    assert_eq!(next_update(&mut rounds).await["key"], json!("2"));
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn new_program_ids_restart_the_parser() {
+    let parsers = Parsers::default();
+    let (addr, background, reloader) = serve(spec(
+        &parsers,
+        "ore",
+        vec![entity("Round", 0, &["RoundState"])],
+    ))
+    .await;
+    parsers.latest().await;
+    let mut rounds = subscribe(addr, "Round/list").await;
+
+    let report = reloader
+        .reload_spec(spec(
+            &parsers,
+            "ore-v2",
+            vec![entity("Round", 0, &["RoundState"])],
+        ))
+        .await
+        .unwrap();
+    assert!(report.parser_restarted);
+
+    let (program_id, parser_tx) = loop {
+        let latest = parsers.latest().await;
+        if latest.0 == "ore-v2" {
+            break latest;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    assert_eq!(program_id, "ore-v2");
+    assert_eq!(parsers.stopped.load(Ordering::SeqCst), 1);
+
+    // The new parser feeds the connection opened before the reload
+    parser_tx
+        .send(common::batch("Round", "1", json!({ "motherlode": 10 })))
+        .await
+        .unwrap();
+    assert_eq!(next_update(&mut rounds).await["key"], json!("1"));
+
+    // A spec without a parser can't take over new program ids
+    let error = reloader
+        .reload_spec(Spec::new(
+            bytecode(vec![entity("Round", 0, &["RoundState"])]),
+            "ore-v3",
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(error, ReloadError::MissingParser);
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn reloads_need_a_started_server() {
+    let parsers = Parsers::default();
+    let runtime = Server::builder()
+        .spec(spec(
+            &parsers,
+            "ore",
+            vec![entity("Round", 0, &["RoundState"])],
+        ))
+        .build()
+        .unwrap();
+
+    let error = runtime
+        .reload_handle()
+        .reload_spec(spec(&parsers, "ore", vec![]))
+        .await
+        .unwrap_err();
+    assert_eq!(error, ReloadError::NotStarted);
+}
+
+/// Event handlers running while reloads swap the bytecode, checking that
+/// every event sees a single spec from start to finish
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn events_in_flight_keep_the_bytecode_they_loaded() {
+    // Every entity of version `n` handles `Event{n}`
+    fn version(n: usize) -> Vec<EntityBytecode> {
+        let event = format!("Event{n}");
+        vec![
+            entity("Round", 0, &[event.as_str()]),
+            entity("Miner", 1, &[event.as_str()]),
+        ]
+    }
+    fn event_of(bytecode: &MultiEntityBytecode, entity: &str) -> String {
+        bytecode.entities[entity]
+            .handlers
+            .keys()
+            .next()
+            .unwrap()
+            .clone()
+    }
+
+    let parsers = Parsers::default();
+    let live = LiveBytecode::new(bytecode(version(0)));
+    let (_, background, reloader) =
+        serve(spec(&parsers, "ore", version(0)).with_live_bytecode(live.clone())).await;
+
+    let reloading = Arc::new(AtomicBool::new(true));
+    let handlers: Vec<_> = (0..8)
+        .map(|_| {
+            let live = live.clone();
+            let reloading = reloading.clone();
+            tokio::spawn(async move {
+                let mut events = 0;
+                while reloading.load(Ordering::SeqCst) {
+                    // One load per event, as the generated VM handlers do
+                    let bytecode = live.load();
+                    let round = event_of(&bytecode, "Round");
+                    tokio::task::yield_now().await;
+                    assert_eq!(event_of(&bytecode, "Miner"), round);
+                    events += 1;
+                }
+                events
+            })
+        })
+        .collect();
+
+    for n in 1..=100 {
+        reloader
+            .reload_spec(spec(&parsers, "ore", version(n)))
+            .await
+            .unwrap();
+        // The swap is visible to the next event
+        assert_eq!(event_of(&live.load(), "Round"), format!("Event{n}"));
+    }
+    reloading.store(false, Ordering::SeqCst);
+
+    for handler in handlers {
+        assert!(handler.await.unwrap() > 0);
+    }
+    background.shutdown();
+}
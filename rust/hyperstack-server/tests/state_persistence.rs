@@ -0,0 +1,169 @@
+//! State snapshots: a restarted server serves the entities of the latest
+//! snapshot and hands its VM state to the spec, saves a snapshot on
+//! shutdown, and skips snapshots it can't use.
+
+mod common;
+
+use hyperstack_interpreter::compiler::MultiEntityBytecode;
+use hyperstack_server::persistence::SNAPSHOT_VERSION;
+use hyperstack_server::{
+    FileStatePersistence, Mode, PersistedEntity, PersistenceConfig, Server, Spec, StatePersistence,
+    StateSnapshot, ViewIndex,
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const VIEW: &str = "Token/list";
+
+/// A fresh directory under the system temp dir
+fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("hyperstack-persistence-{}", uuid::Uuid::new_v4()))
+}
+
+fn snapshot(created_at: i64, index: u64) -> StateSnapshot {
+    let entity = PersistedEntity {
+        key: "token-1".to_string(),
+        data: json!({ "index": index }),
+        updated_at: created_at,
+    };
+    StateSnapshot {
+        version: SNAPSHOT_VERSION,
+        created_at,
+        schema_hash: Some(MultiEntityBytecode::new().build().schema_hash()),
+        views: BTreeMap::from([(VIEW.to_string(), vec![entity])]),
+        vm_state: Some(json!({ "tables": index })),
+    }
+}
+
+/// A spec whose parser idles, exporting `exported` as its VM state and
+/// recording the state it is asked to import
+fn idle_spec(exported: Value) -> (Spec, Arc<Mutex<Option<Value>>>) {
+    let (spec, _batches) = common::forwarding_spec();
+    let imported = Arc::new(Mutex::new(None));
+    let recorder = imported.clone();
+    let spec = spec.with_vm_state(
+        Arc::new(move || Some(exported.clone())),
+        Arc::new(move |state| *recorder.lock().unwrap() = Some(state)),
+    );
+    (spec, imported)
+}
+
+fn views() -> ViewIndex {
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view(VIEW, "Token", Mode::List));
+    views
+}
+
+fn free_addr() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn restarted_servers_serve_the_latest_snapshot() {
+    let dir = temp_dir();
+    let store = FileStatePersistence::new(&dir);
+    store.save(&snapshot(1_000, 1)).await.unwrap();
+
+    let (spec, imported) = idle_spec(json!({ "tables": 2 }));
+    let addr = free_addr();
+    let server = Server::builder()
+        .spec(spec)
+        .views(views())
+        .websocket()
+        .bind(addr)
+        .persistence(PersistenceConfig::new(&dir).with_interval(Duration::from_secs(3600)))
+        .start_with_shutdown()
+        .await
+        .expect("server should start");
+
+    let mut ws = common::connect(&format!("ws://{addr}")).await;
+    common::send(&mut ws, json!({ "type": "subscribe", "view": VIEW })).await;
+    let subscribed = common::next_json(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"), "{subscribed}");
+    let restored = common::next_json(&mut ws).await;
+    assert_eq!(restored["op"], json!("snapshot"), "{restored}");
+    assert_eq!(restored["data"][0]["key"], json!("token-1"), "{restored}");
+    assert_eq!(restored["data"][0]["data"]["index"], json!(1), "{restored}");
+    assert_eq!(*imported.lock().unwrap(), Some(json!({ "tables": 1 })));
+
+    // Shutting down saves a snapshot with the VM's current state
+    drop(ws);
+    server.shutdown().await.unwrap();
+    let saved = store
+        .load()
+        .await
+        .unwrap()
+        .expect("a snapshot should be saved");
+    assert!(saved.created_at > 1_000);
+    assert_eq!(saved.views[VIEW][0].key, "token-1");
+    assert_eq!(saved.vm_state, Some(json!({ "tables": 2 })));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn unusable_snapshots_are_skipped() {
+    let dir = temp_dir();
+    let store = FileStatePersistence::new(&dir).with_keep(2);
+    assert_eq!(store.load().await.unwrap(), None, "no directory yet");
+
+    store.save(&snapshot(1_000, 1)).await.unwrap();
+    std::fs::write(
+        dir.join("snapshot-00000000000000002000.json"),
+        json!({ "version": SNAPSHOT_VERSION + 1, "created_at": 2_000 }).to_string(),
+    )
+    .unwrap();
+    std::fs::write(dir.join("snapshot-00000000000000003000.json"), "{ trunc").unwrap();
+
+    let loaded = store.load().await.unwrap();
+    assert_eq!(loaded, Some(snapshot(1_000, 1)));
+
+    // Saving prunes all but the latest `keep` files, usable or not
+    store.save(&snapshot(4_000, 4)).await.unwrap();
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+    assert_eq!(store.load().await.unwrap(), Some(snapshot(4_000, 4)));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn snapshots_of_another_stack_are_not_restored() {
+    let mut other_schema = snapshot(1_000, 1);
+    other_schema.schema_hash = Some("another-stack".to_string());
+    let mut other_tables = snapshot(1_000, 1);
+    other_tables.vm_state = Some(json!({ "states": { "0": { "entity_name": "Miner" } } }));
+
+    for stale in [other_schema, other_tables] {
+        let dir = temp_dir();
+        FileStatePersistence::new(&dir).save(&stale).await.unwrap();
+
+        let (spec, imported) = idle_spec(json!({ "tables": 2 }));
+        let addr = free_addr();
+        let server = Server::builder()
+            .spec(spec)
+            .views(views())
+            .websocket()
+            .bind(addr)
+            .persistence(PersistenceConfig::new(&dir).with_interval(Duration::from_secs(3600)))
+            .start_with_shutdown()
+            .await
+            .expect("server should start");
+
+        // Restoring is done once the server listens
+        let mut ws = common::connect(&format!("ws://{addr}")).await;
+        assert_eq!(*imported.lock().unwrap(), None);
+        common::send(&mut ws, json!({ "type": "subscribe", "view": VIEW })).await;
+        assert_eq!(common::next_json(&mut ws).await["op"], json!("subscribed"));
+        let restored = common::try_next_json(&mut ws, Duration::from_millis(300)).await;
+        assert!(restored.is_none(), "no entity is restored: {restored:?}");
+
+        drop(ws);
+        server.shutdown().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
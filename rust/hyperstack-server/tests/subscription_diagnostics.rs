@@ -0,0 +1,107 @@
+mod common;
+
+use hyperstack_server::{BackgroundHandle, Mode, MutationBatch, Server, SlotContext, ViewIndex};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+
+const TOKENS: u64 = 200;
+
+fn token(slot: u64) -> MutationBatch {
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..common::batch(
+            "Token",
+            &format!("token-{slot}"),
+            json!({ "slot": slot, "name": "a token with a repetitive name" }),
+        )
+    }
+}
+
+/// A server whose parser has sent `TOKENS` tokens, enough for a compressed
+/// snapshot
+async fn serve() -> (SocketAddr, BackgroundHandle) {
+    let (spec, tokens) = common::forwarding_spec();
+    for slot in 0..TOKENS {
+        tokens.send(token(slot)).unwrap();
+    }
+
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view("Token/list", "Token", Mode::List));
+
+    common::serve(Server::builder().spec(spec).views(views)).await
+}
+
+async fn wait_for_tokens(addr: SocketAddr) {
+    common::wait_for_stats(
+        addr,
+        &format!("projector should process {TOKENS} tokens"),
+        |stats| stats["cache"]["total_entities"] == json!(TOKENS),
+    )
+    .await;
+}
+
+/// Subscribe to the token list and return the ack
+async fn subscribe(addr: SocketAddr, subscription: Value) -> Value {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    let mut message = json!({ "type": "subscribe", "view": "Token/list" });
+    message
+        .as_object_mut()
+        .unwrap()
+        .extend(subscription.as_object().unwrap().clone());
+    common::send(&mut ws, message).await;
+
+    let ack = common::next_json(&mut ws).await;
+    assert_eq!(ack["op"], json!("subscribed"));
+    ack
+}
+
+#[tokio::test]
+async fn ack_carries_diagnostics_when_asked() {
+    let (addr, background) = serve().await;
+    wait_for_tokens(addr).await;
+
+    let ack = subscribe(addr, json!({ "diagnostics": true })).await;
+    let diagnostics = &ack["diagnostics"];
+    let number = |field: &str| {
+        diagnostics[field]
+            .as_u64()
+            .unwrap_or_else(|| panic!("{field} should be a number: {diagnostics}"))
+    };
+
+    assert_eq!(number("cache_entries"), TOKENS);
+
+    let snapshot_bytes = number("snapshot_bytes");
+    let compressed_bytes = number("compressed_bytes");
+    assert!(compressed_bytes > 0, "{diagnostics}");
+    assert!(
+        compressed_bytes < snapshot_bytes,
+        "a repetitive snapshot should compress: {diagnostics}"
+    );
+
+    let snapshot_micros = number("snapshot_micros");
+    let queue_wait_micros = number("queue_wait_micros");
+    assert!(queue_wait_micros > 0, "{diagnostics}");
+    assert!(
+        queue_wait_micros >= snapshot_micros,
+        "the ack waits for the snapshot: {diagnostics}"
+    );
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn ack_has_no_diagnostics_by_default() {
+    let (addr, background) = serve().await;
+    wait_for_tokens(addr).await;
+
+    let ack = subscribe(addr, json!({})).await;
+    assert!(ack.get("diagnostics").is_none(), "{ack}");
+
+    // Without a snapshot there is nothing to measure but the wait
+    let ack = subscribe(addr, json!({ "diagnostics": true, "withSnapshot": false })).await;
+    assert_eq!(ack["diagnostics"]["cache_entries"], json!(0));
+    assert_eq!(ack["diagnostics"]["snapshot_bytes"], json!(0));
+    assert!(ack["diagnostics"]["queue_wait_micros"].as_u64().unwrap() > 0);
+
+    background.shutdown();
+}
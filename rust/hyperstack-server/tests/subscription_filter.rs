@@ -0,0 +1,102 @@
+//! Filtered list subscriptions: the snapshot only holds matching entities,
+//! an entity that starts matching arrives whole, and one that stops
+//! matching is deleted from the client.
+
+mod common;
+
+use common::{batch, connect, forwarding_spec, next_json, send, view, wait_for_stats};
+use hyperstack_server::{BackgroundHandle, Mode, MutationBatch, Server, SlotContext, ViewIndex};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+
+const VIEW: &str = "Round/list";
+
+fn round(key: &str, total: u64, slot: u64) -> MutationBatch {
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..batch(
+            "Round",
+            key,
+            json!({ "state": { "total_deployed": total } }),
+        )
+    }
+}
+
+async fn serve() -> (
+    SocketAddr,
+    BackgroundHandle,
+    mpsc::UnboundedSender<MutationBatch>,
+) {
+    let (spec, batches) = forwarding_spec();
+    let mut views = ViewIndex::new();
+    views.add_spec(view(VIEW, "Round", Mode::List));
+
+    let (addr, background) = common::serve(Server::builder().spec(spec).views(views)).await;
+    (addr, background, batches)
+}
+
+async fn wait_for_entities(addr: SocketAddr, count: usize) {
+    wait_for_stats(
+        addr,
+        &format!("projector should cache {count} entities"),
+        |stats| stats["cache"]["total_entities"] == json!(count),
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn filtered_subscribers_only_hold_matching_entities() {
+    let (addr, _background, batches) = serve().await;
+    batches.send(round("small", 50, 1)).unwrap();
+    batches.send(round("large", 500, 2)).unwrap();
+    wait_for_entities(addr, 2).await;
+
+    let mut ws = connect(&format!("ws://{addr}/stream")).await;
+    let subscribe = json!({
+        "type": "subscribe",
+        "view": VIEW,
+        "filter": { "field": "state.total_deployed", "op": "gte", "value": 100 },
+    });
+    send(&mut ws, subscribe).await;
+    let subscribed = next_json(&mut ws).await;
+    assert_eq!(subscribed["op"], json!("subscribed"), "{subscribed}");
+    let snapshot = next_json(&mut ws).await;
+    assert_eq!(snapshot["op"], json!("snapshot"), "{snapshot}");
+    let keys: Vec<&Value> = snapshot["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entity| &entity["key"])
+        .collect();
+    assert_eq!(keys, [&json!("large")]);
+
+    // Entering the filter sends the whole entity
+    batches.send(round("small", 150, 3)).unwrap();
+    let entered = next_json(&mut ws).await;
+    assert_eq!(entered["op"], json!("upsert"), "{entered}");
+    assert_eq!(entered["key"], json!("small"));
+    assert_eq!(
+        entered["data"]["state"]["total_deployed"],
+        json!(150),
+        "{entered}"
+    );
+
+    batches.send(round("large", 600, 4)).unwrap();
+    let patched = next_json(&mut ws).await;
+    assert_eq!(patched["op"], json!("patch"), "{patched}");
+    assert_eq!(patched["key"], json!("large"));
+
+    // Leaving it deletes the entity, and it stays quiet while it doesn't match
+    batches.send(round("large", 20, 5)).unwrap();
+    let left = next_json(&mut ws).await;
+    assert_eq!(left["op"], json!("delete"), "{left}");
+    assert_eq!(left["key"], json!("large"));
+    assert_eq!(left["entity"], json!(VIEW));
+
+    batches.send(round("large", 10, 6)).unwrap();
+    batches.send(round("small", 160, 7)).unwrap();
+    let next = next_json(&mut ws).await;
+    assert_eq!(next["op"], json!("patch"), "{next}");
+    assert_eq!(next["key"], json!("small"));
+}
@@ -0,0 +1,168 @@
+//! A `refresh` restarts a subscription from a fresh snapshot, marked so the
+//! client can swap what it holds, and no update from before the refresh
+//! follows it.
+
+mod common;
+
+use common::{batch, forwarding_spec, next_json, send, view, Client};
+use hyperstack_server::{
+    Mode, MutationBatch, RateLimitConfig, Server, ServerHandle, SlotContext, ViewIndex,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const VIEW: &str = "Token/list";
+
+fn token(slot: u64) -> MutationBatch {
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..batch("Token", "a", json!({ "slot": slot }))
+    }
+}
+
+fn views() -> ViewIndex {
+    let mut views = ViewIndex::new();
+    views.add_spec(view(VIEW, "Token", Mode::List));
+    views
+}
+
+async fn start(
+    min_refresh_interval: Duration,
+) -> (
+    ServerHandle,
+    SocketAddr,
+    mpsc::UnboundedSender<MutationBatch>,
+) {
+    let (spec, parser) = forwarding_spec();
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let server = Server::builder()
+        .spec(spec)
+        .views(views())
+        .websocket()
+        .bind(addr)
+        .websocket_rate_limit_config(RateLimitConfig {
+            min_refresh_interval,
+            ..Default::default()
+        })
+        .start_with_shutdown()
+        .await
+        .expect("server should start");
+    (server, addr, parser)
+}
+
+async fn connect(addr: SocketAddr) -> Client {
+    common::connect(&format!("ws://{addr}")).await
+}
+
+fn slot_of(data: &Value) -> u64 {
+    data["slot"].as_u64().expect("the update carries its slot")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn refresh_sends_a_marked_snapshot_and_only_later_updates() {
+    let (server, addr, parser) = start(Duration::from_secs(1)).await;
+    let mut ws = connect(addr).await;
+    send(&mut ws, json!({ "type": "subscribe", "view": VIEW })).await;
+    let ack = next_json(&mut ws).await;
+    assert_eq!(ack["op"], json!("subscribed"));
+
+    // Updates keep coming while the subscription is refreshed
+    let publisher = tokio::spawn(async move {
+        for slot in 1..=400 {
+            if parser.send(token(slot)).is_err() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    });
+
+    let mut seen = 0;
+    while seen < 50 {
+        let frame = next_json(&mut ws).await;
+        if frame["op"] == json!("patch") || frame["op"] == json!("upsert") {
+            seen = slot_of(&frame["data"]);
+        }
+    }
+    send(&mut ws, json!({ "type": "refresh", "view": VIEW })).await;
+
+    // Frames already on their way may arrive before the marker
+    let marker = loop {
+        let frame = next_json(&mut ws).await;
+        if frame.get("refresh").is_some() {
+            break frame;
+        }
+        assert!(frame.get("resync").is_none(), "{frame}");
+    };
+    assert_eq!(marker["resync"], json!(true));
+    assert_eq!(marker["refresh"], json!(true));
+    assert_eq!(marker["frameSeq"], json!(1));
+    assert_eq!(marker["op"], json!("subscribed"));
+
+    let snapshot = next_json(&mut ws).await;
+    assert_eq!(snapshot["op"], json!("snapshot"));
+    assert_eq!(snapshot["frameSeq"], json!(2));
+    let entities = snapshot["data"].as_array().unwrap();
+    assert_eq!(entities.len(), 1);
+    assert!(slot_of(&entities[0]["data"]) >= seen);
+
+    let mut frame_seq = 2;
+    let mut last = seen;
+    while last < 400 {
+        let frame = next_json(&mut ws).await;
+        frame_seq += 1;
+        assert_eq!(frame["frameSeq"], json!(frame_seq), "{frame}");
+        assert!(frame.get("refresh").is_none(), "{frame}");
+        let slot = slot_of(&frame["data"]);
+        assert!(slot > last, "update {slot} arrived after {last}");
+        last = slot;
+    }
+
+    publisher.await.unwrap();
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn refreshing_too_often_is_refused_without_disconnecting() {
+    let (server, addr, parser) = start(Duration::from_secs(60)).await;
+    parser.send(token(1)).unwrap();
+    let mut ws = connect(addr).await;
+    send(&mut ws, json!({ "type": "subscribe", "view": VIEW })).await;
+    assert_eq!(next_json(&mut ws).await["op"], json!("subscribed"));
+
+    send(&mut ws, json!({ "type": "refresh", "view": VIEW })).await;
+    let marker = loop {
+        let frame = next_json(&mut ws).await;
+        if frame.get("refresh").is_some() {
+            break frame;
+        }
+    };
+    assert_eq!(marker["op"], json!("subscribed"));
+
+    send(&mut ws, json!({ "type": "refresh", "view": VIEW })).await;
+    let issue = loop {
+        let frame = next_json(&mut ws).await;
+        assert!(frame.get("refresh").is_none(), "{frame}");
+        if frame["type"] == json!("error") {
+            break frame;
+        }
+    };
+    assert_eq!(issue["code"], json!("rate-limit-exceeded"));
+    assert_eq!(issue["fatal"], json!(false));
+
+    // The subscription carries on
+    parser.send(token(2)).unwrap();
+    let update = loop {
+        let frame = next_json(&mut ws).await;
+        if frame["op"] == json!("patch") && slot_of(&frame["data"]) == 2 {
+            break frame;
+        }
+    };
+    assert_eq!(update["key"], json!("a"));
+
+    server.shutdown().await.unwrap();
+}
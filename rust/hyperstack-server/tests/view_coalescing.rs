@@ -0,0 +1,184 @@
+use axum::Router;
+use futures_util::{SinkExt, StreamExt};
+use hyperstack_interpreter::compiler::MultiEntityBytecode;
+use hyperstack_interpreter::Mutation;
+use hyperstack_server::{
+    BackgroundHandle, Delivery, Filters, Mode, MutationBatch, ParserSetupFn, Projection, Server,
+    SlotContext, Spec, ViewIndex, ViewSpec,
+};
+use serde_json::{json, Value};
+use smallvec::smallvec;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn batch(slot: u64, key: &str, patch: Value, append: &[&str]) -> MutationBatch {
+    MutationBatch::with_slot_context(
+        smallvec![Mutation {
+            append: append.iter().map(|path| path.to_string()).collect(),
+            ..Mutation::new("Token", json!(key), patch)
+        }],
+        SlotContext::new(slot, 0),
+    )
+}
+
+/// A spec whose parser sends a burst of patches to tokens `a` and `b` once
+/// `start` is notified
+fn burst_spec(start: Arc<Notify>) -> Spec {
+    let setup: ParserSetupFn = Arc::new(move |mutations_tx, _health, _reconnection| {
+        let start = start.clone();
+        Box::pin(async move {
+            start.notified().await;
+            let burst = [
+                batch(10, "a", json!({ "supply": 1, "trades": [1] }), &["trades"]),
+                batch(11, "b", json!({ "supply": 5 }), &[]),
+                batch(12, "a", json!({ "supply": 2, "trades": [2] }), &["trades"]),
+                batch(13, "a", json!({ "name": "A" }), &["trades"]),
+            ];
+            for batch in burst {
+                mutations_tx.send(batch).await?;
+            }
+
+            std::future::pending::<()>().await;
+            Ok(())
+        })
+    });
+
+    Spec::new(MultiEntityBytecode::new().build(), "test_program").with_parser_setup(setup)
+}
+
+fn view(id: &str, coalesce_ms: Option<u64>) -> ViewSpec {
+    ViewSpec {
+        id: id.to_string(),
+        export: "Token".to_string(),
+        mode: Mode::List,
+        projection: Projection::all(),
+        filters: Filters::all(),
+        delivery: Delivery {
+            coalesce_ms,
+            ..Default::default()
+        },
+        pipeline: None,
+        source_view: None,
+    }
+}
+
+async fn serve(spec: Spec) -> (SocketAddr, BackgroundHandle) {
+    let mut views = ViewIndex::new();
+    views.add_spec(view("Token/list", Some(200)));
+    views.add_spec(view("Token/latest", Some(0)));
+
+    let (stream_router, background) = Server::builder()
+        .spec(spec)
+        .views(views)
+        .build()
+        .expect("runtime should build")
+        .into_router_parts();
+    let background = background.spawn_background(&tokio::runtime::Handle::current());
+
+    let app = Router::new().nest("/stream", stream_router);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+
+    (addr, background)
+}
+
+async fn next_frame(ws: &mut Client) -> Value {
+    let message = tokio::time::timeout(Duration::from_secs(2), ws.next())
+        .await
+        .expect("frame should arrive")
+        .unwrap()
+        .unwrap();
+    match message {
+        Message::Binary(bytes) => serde_json::from_slice(&bytes).unwrap(),
+        Message::Text(text) => serde_json::from_str(text.as_str()).unwrap(),
+        other => panic!("expected a data frame, got {other:?}"),
+    }
+}
+
+/// Read the next `count` patch frames
+async fn patches(ws: &mut Client, count: usize) -> Vec<Value> {
+    let mut patches = Vec::new();
+    while patches.len() < count {
+        let frame = next_frame(ws).await;
+        if frame["op"] == json!("patch") {
+            patches.push(frame);
+        }
+    }
+    patches
+}
+
+async fn subscribe(addr: SocketAddr, view: &str) -> Client {
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/stream"))
+        .await
+        .expect("websocket handshake should succeed");
+    let message = json!({ "type": "subscribe", "view": view });
+    ws.send(Message::Text(message.to_string().into()))
+        .await
+        .unwrap();
+    // Wait for the subscription to be registered
+    loop {
+        if next_frame(&mut ws).await["op"] == json!("subscribed") {
+            return ws;
+        }
+    }
+}
+
+fn keys(frames: &[Value]) -> Vec<&str> {
+    frames
+        .iter()
+        .map(|frame| frame["key"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn coalescing_view_merges_a_burst_while_the_other_sends_every_patch() {
+    let start = Arc::new(Notify::new());
+    let (addr, background) = serve(burst_spec(start.clone())).await;
+    let mut list = subscribe(addr, "Token/list").await;
+    let mut latest = subscribe(addr, "Token/latest").await;
+
+    start.notify_one();
+
+    let immediate = patches(&mut latest, 4).await;
+    assert_eq!(keys(&immediate), ["a", "b", "a", "a"]);
+    assert_eq!(immediate[2]["data"]["trades"], json!([2]));
+
+    let coalesced = patches(&mut list, 2).await;
+    // Keys keep the order they were first patched in
+    assert_eq!(keys(&coalesced), ["a", "b"]);
+    let a = &coalesced[0];
+    // Replaced fields take the latest value, appended arrays are concatenated
+    assert_eq!(a["data"]["supply"], json!(2));
+    assert_eq!(a["data"]["name"], json!("A"));
+    assert_eq!(a["data"]["trades"], json!([1, 2]));
+    assert_eq!(a["append"], json!(["trades"]));
+    // The merged frame carries the latest slot
+    let latest_seq = SlotContext::new(13, 0).to_seq_string();
+    assert_eq!(a["seq"], json!(latest_seq));
+    assert_eq!(a["data"]["_seq"], json!(latest_seq));
+    assert_eq!(coalesced[1]["data"]["supply"], json!(5));
+
+    // Nothing else is held back
+    assert!(
+        tokio::time::timeout(Duration::from_millis(400), patches(&mut list, 1))
+            .await
+            .is_err()
+    );
+
+    background.shutdown();
+}
@@ -0,0 +1,162 @@
+//! Deprecated views: subscriptions to the old id are served from the
+//! replacement's pipeline under the old id, with a notice in the ack, and are
+//! refused once the view is past its sunset.
+
+mod common;
+
+use common::{batch, forwarding_spec, next_json, view, wait_for_stats, Client};
+use hyperstack_server::testkit::ManualClock;
+use hyperstack_server::{
+    BackgroundHandle, Clock, Mode, MutationBatch, Server, SlotContext, ViewDeprecation, ViewIndex,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_tungstenite::tungstenite::Error as WsError;
+
+const TOKENS: u64 = 3;
+const VIEW: &str = "Token/list";
+/// Served by `VIEW` until a sunset a day away
+const DEPRECATED: &str = "Token/all";
+/// Served by `VIEW` until a sunset long past
+const SUNSET: &str = "Token/legacy";
+
+fn token(slot: u64) -> MutationBatch {
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..batch("Token", &format!("token-{slot}"), json!({ "slot": slot }))
+    }
+}
+
+/// A server clock starting at the current time
+fn clock() -> ManualClock {
+    ManualClock::new(SystemTime::now().duration_since(UNIX_EPOCH).unwrap())
+}
+
+async fn serve(clock: ManualClock) -> (SocketAddr, BackgroundHandle, SystemTime) {
+    let (spec, batches) = forwarding_spec();
+    for slot in 0..TOKENS {
+        batches.send(token(slot)).unwrap();
+    }
+
+    let mut views = ViewIndex::new();
+    views.add_spec(view(VIEW, "Token", Mode::List));
+
+    let sunset = UNIX_EPOCH + clock.now_unix() + Duration::from_secs(24 * 60 * 60);
+    let (addr, background) = common::serve(
+        Server::builder()
+            .spec(spec)
+            .views(views)
+            .clock(Arc::new(clock))
+            .deprecate_view(ViewDeprecation::new(DEPRECATED, VIEW).with_sunset(sunset))
+            .deprecate_view(
+                ViewDeprecation::new(SUNSET, VIEW).with_sunset(UNIX_EPOCH + Duration::from_secs(1)),
+            ),
+    )
+    .await;
+
+    // Only the replacement caches the tokens
+    wait_for_stats(addr, "projector should process the tokens", |stats| {
+        stats["cache"]["total_entities"] == json!(TOKENS)
+    })
+    .await;
+    (addr, background, sunset)
+}
+
+async fn subscribe(addr: SocketAddr, view: &str) -> Client {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    common::send(&mut ws, json!({ "type": "subscribe", "view": view })).await;
+    ws
+}
+
+/// Connect to `path` and expect the handshake to be refused, returning the
+/// status and the JSON body
+async fn rejected(addr: SocketAddr, path: &str) -> (u16, Value) {
+    match tokio_tungstenite::connect_async(format!("ws://{addr}/stream{path}")).await {
+        Err(WsError::Http(response)) => {
+            let status = response.status().as_u16();
+            let body = response.into_body().expect("rejection should have a body");
+            (status, serde_json::from_slice(&body).unwrap())
+        }
+        Ok(_) => panic!("handshake to {path} should be refused"),
+        Err(error) => panic!("unexpected handshake error: {error}"),
+    }
+}
+
+#[tokio::test]
+async fn deprecated_views_serve_the_replacement_under_their_own_id() {
+    let (addr, background, sunset) = serve(clock()).await;
+
+    let mut ws = subscribe(addr, DEPRECATED).await;
+    let ack = next_json(&mut ws).await;
+    assert_eq!(ack["op"], json!("subscribed"), "{ack}");
+    assert_eq!(ack["view"], json!(DEPRECATED), "{ack}");
+    let sunset_at = sunset.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    assert_eq!(
+        ack["deprecation"],
+        json!({ "replacement": VIEW, "sunset_at": sunset_at }),
+        "{ack}"
+    );
+
+    let snapshot = next_json(&mut ws).await;
+    assert_eq!(snapshot["op"], json!("snapshot"), "{snapshot}");
+    assert_eq!(snapshot["entity"], json!(DEPRECATED), "{snapshot}");
+    let mut keys: Vec<_> = snapshot["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entity| entity["key"].as_str().unwrap().to_string())
+        .collect();
+    keys.sort();
+    assert_eq!(keys, ["token-0", "token-1", "token-2"]);
+
+    // The replacement's own subscribers see no notice
+    let mut ws = subscribe(addr, VIEW).await;
+    let ack = next_json(&mut ws).await;
+    assert_eq!(ack["view"], json!(VIEW), "{ack}");
+    assert!(ack.get("deprecation").is_none(), "{ack}");
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn views_past_their_sunset_are_refused() {
+    let (addr, background, _) = serve(clock()).await;
+
+    let mut ws = subscribe(addr, SUNSET).await;
+    let issue = next_json(&mut ws).await;
+    assert_eq!(issue["type"], json!("error"), "{issue}");
+    assert_eq!(issue["code"], json!("view-sunset"), "{issue}");
+    assert_eq!(issue["retryable"], json!(false), "{issue}");
+    assert!(issue["message"].as_str().unwrap().contains(VIEW), "{issue}");
+
+    let (status, body) = rejected(addr, &format!("/sub/{SUNSET}")).await;
+    assert_eq!(status, 410);
+    assert_eq!(body["code"], json!("view-sunset"));
+
+    // Path subscriptions to a view before its sunset are served as usual
+    let mut ws = common::connect(&format!("ws://{addr}/stream/sub/{DEPRECATED}")).await;
+    let ack = next_json(&mut ws).await;
+    assert_eq!(ack["view"], json!(DEPRECATED), "{ack}");
+
+    background.shutdown();
+}
+
+#[tokio::test]
+async fn sunsets_are_checked_against_the_server_clock() {
+    let clock = clock();
+    let (addr, background, _) = serve(clock.clone()).await;
+
+    clock.advance(Duration::from_secs(2 * 24 * 60 * 60));
+
+    let mut ws = subscribe(addr, DEPRECATED).await;
+    let issue = next_json(&mut ws).await;
+    assert_eq!(issue["code"], json!("view-sunset"), "{issue}");
+
+    let (status, body) = rejected(addr, &format!("/sub/{DEPRECATED}")).await;
+    assert_eq!(status, 410);
+    assert_eq!(body["code"], json!("view-sunset"));
+
+    background.shutdown();
+}
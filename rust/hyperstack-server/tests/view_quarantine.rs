@@ -0,0 +1,206 @@
+mod common;
+
+use common::{batch, forwarding_spec, http_get_json, next_json, wait_for_stats, Client};
+use hyperstack_server::materialized_view::{SortConfig, SortOrder};
+use hyperstack_server::{
+    BackgroundHandle, MaterializedView, MaterializedViewRegistry, Mode, MutationBatch, Server,
+    SlotContext, ViewBudget, ViewIndex, ViewPipeline, ViewPredicate, ViewSpec,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::UnboundedSender;
+
+fn token(slot: u64, key: &str, supply: u64) -> MutationBatch {
+    MutationBatch {
+        slot_context: Some(SlotContext::new(slot, 0)),
+        ..batch("Token", key, json!({ "supply": supply }))
+    }
+}
+
+fn by_supply() -> Option<SortConfig> {
+    Some(SortConfig {
+        field_path: vec!["supply".to_string()],
+        order: SortOrder::Desc,
+    })
+}
+
+/// Sorted by supply, panicking on a supply of 13
+fn poisoned_pipeline() -> ViewPipeline {
+    ViewPipeline {
+        sort: by_supply(),
+        predicate: Some(ViewPredicate::new(|token| {
+            assert_ne!(token["supply"], json!(13), "unlucky supply");
+            true
+        })),
+        ..Default::default()
+    }
+}
+
+fn view(id: &str, pipeline: Option<ViewPipeline>) -> ViewSpec {
+    ViewSpec {
+        source_view: pipeline.as_ref().map(|_| "Token/list".to_string()),
+        pipeline,
+        ..common::view(id, "Token", Mode::List)
+    }
+}
+
+/// Serve tokens `a` and `b`, returning the channel feeding the parser
+async fn serve() -> (SocketAddr, BackgroundHandle, UnboundedSender<MutationBatch>) {
+    let (spec, batches) = forwarding_spec();
+    batches.send(token(1, "a", 1)).unwrap();
+    batches.send(token(2, "b", 2)).unwrap();
+
+    let top = ViewPipeline {
+        sort: by_supply(),
+        ..Default::default()
+    };
+    let mut views = ViewIndex::new();
+    views.add_spec(view("Token/list", None));
+    views.add_spec(view("Token/top", Some(top.clone())));
+    views.add_spec(view("Token/poisoned", Some(poisoned_pipeline())));
+
+    let mut registry = MaterializedViewRegistry::new();
+    for (id, pipeline) in [("Token/top", top), ("Token/poisoned", poisoned_pipeline())] {
+        registry.register(MaterializedView::new(
+            id.to_string(),
+            "Token/list".to_string(),
+            pipeline,
+        ));
+    }
+
+    let (addr, background) = common::serve(
+        Server::builder()
+            .spec(spec)
+            .views(views)
+            .materialized_views(registry)
+            .view_budget(ViewBudget::new().with_max_failures(1)),
+    )
+    .await;
+    (addr, background, batches)
+}
+
+async fn http_request(addr: SocketAddr, method: &str, path: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .expect("response should have a body");
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .expect("response should have a status");
+    (
+        status,
+        serde_json::from_str(body).expect("body should be json"),
+    )
+}
+
+/// Read frames until one matches `found`
+async fn frame_where(ws: &mut Client, found: impl Fn(&Value) -> bool) -> Value {
+    loop {
+        let frame = next_json(ws).await;
+        if found(&frame) {
+            return frame;
+        }
+    }
+}
+
+async fn subscribe(addr: SocketAddr, view: &str) -> Client {
+    let mut ws = common::connect(&format!("ws://{addr}/stream")).await;
+    common::send(&mut ws, json!({ "type": "subscribe", "view": view })).await;
+    ws
+}
+
+fn is_unavailable(frame: &Value) -> bool {
+    frame["type"] == json!("error") && frame["code"] == json!("view_unavailable")
+}
+
+#[tokio::test]
+async fn panicking_view_is_quarantined_while_the_others_keep_updating() {
+    let (addr, background, batches) = serve().await;
+    wait_for_stats(addr, "projector should cache both tokens", |stats| {
+        stats["cache"]["total_entities"] == json!(2)
+    })
+    .await;
+
+    let mut poisoned = subscribe(addr, "Token/poisoned").await;
+    let snapshot = frame_where(&mut poisoned, |frame| frame["op"] == json!("snapshot")).await;
+    assert_eq!(snapshot["data"].as_array().unwrap().len(), 2);
+    let mut top = subscribe(addr, "Token/top").await;
+    frame_where(&mut top, |frame| frame["op"] == json!("snapshot")).await;
+
+    // The poisoned view panics on this supply
+    batches.send(token(3, "bad", 13)).unwrap();
+
+    // Existing subscribers of the quarantined view are told it is gone
+    let issue = frame_where(&mut poisoned, |frame| frame.get("type").is_some()).await;
+    assert!(is_unavailable(&issue), "{issue}");
+    assert!(issue["message"]
+        .as_str()
+        .unwrap()
+        .contains("unlucky supply"));
+
+    // The other view keeps updating
+    frame_where(&mut top, |frame| {
+        frame["op"] == json!("upsert") && frame["key"] == json!("bad")
+    })
+    .await;
+
+    wait_for_stats(addr, "view should be quarantined", |stats| {
+        stats["quarantined_views"]["Token/poisoned"].is_object()
+    })
+    .await;
+    let stats = http_get_json(addr, "/stream/stats").await;
+    let quarantine = &stats["quarantined_views"]["Token/poisoned"];
+    assert!(quarantine["error"]
+        .as_str()
+        .unwrap()
+        .contains("unlucky supply"));
+    assert_eq!(quarantine["failures"], json!(1));
+    assert_eq!(stats["views"]["Token/poisoned"]["failures"], json!(1));
+    assert!(stats["quarantined_views"].get("Token/top").is_none());
+
+    // New subscriptions are refused
+    let mut refused = subscribe(addr, "Token/poisoned").await;
+    let issue = frame_where(&mut refused, |frame| frame.get("type").is_some()).await;
+    assert!(is_unavailable(&issue), "{issue}");
+
+    // The view fails again while the cache still holds the poison
+    let (status, body) =
+        http_request(addr, "POST", "/stream/admin/unquarantine/Token/poisoned").await;
+    assert_eq!(status, 409, "{body}");
+
+    batches.send(token(4, "bad", 3)).unwrap();
+    frame_where(&mut top, |frame| {
+        frame["key"] == json!("bad") && frame["data"]["supply"] == json!(3)
+    })
+    .await;
+
+    let (status, body) =
+        http_request(addr, "POST", "/stream/admin/unquarantine/Token/poisoned").await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["entities"], json!(3));
+    let (status, _) = http_request(addr, "POST", "/stream/admin/unquarantine/Token/top").await;
+    assert_eq!(status, 404);
+
+    let mut restored = subscribe(addr, "Token/poisoned").await;
+    let snapshot = frame_where(&mut restored, |frame| frame["op"] == json!("snapshot")).await;
+    let keys: Vec<&str> = snapshot["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entity| entity["key"].as_str().unwrap())
+        .collect();
+    assert_eq!(keys, ["bad", "b", "a"]);
+
+    background.shutdown();
+}
@@ -0,0 +1,214 @@
+//! Webhook delivery from the projector to a mock receiver.
+//!
+//! Mutations run through a projector with a webhook rule on `Round/list`, and
+//! a local HTTP server records what it is posted, failing the first requests
+//! it gets to exercise retries.
+
+mod common;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use hyperstack_interpreter::ast::{CompareOp, FieldPath, Predicate, PredicateValue};
+use hyperstack_interpreter::Mutation;
+use hyperstack_server::{
+    BusManager, EntityCache, HealthConfig, HealthMonitor, Mode, MutationBatch, Projector,
+    ViewIndex, WebhookConfig, WebhookRule, WebhookStats, Webhooks,
+};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+#[derive(Clone, Default)]
+struct Receiver {
+    /// Requests still to be answered with a 500
+    failures: Arc<Mutex<usize>>,
+    attempts: Arc<Mutex<usize>>,
+    accepted: Arc<Mutex<Vec<Value>>>,
+}
+
+async fn receive(State(receiver): State<Receiver>, Json(payload): Json<Value>) -> StatusCode {
+    *receiver.attempts.lock().unwrap() += 1;
+    let mut failures = receiver.failures.lock().unwrap();
+    if *failures > 0 {
+        *failures -= 1;
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    receiver.accepted.lock().unwrap().push(payload);
+    StatusCode::OK
+}
+
+/// Serve a receiver failing its first `failures` requests, returning its URL
+async fn spawn_receiver(failures: usize) -> (String, Receiver) {
+    let receiver = Receiver {
+        failures: Arc::new(Mutex::new(failures)),
+        ..Default::default()
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new()
+        .route("/hook", post(receive))
+        .with_state(receiver.clone());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{addr}/hook"), receiver)
+}
+
+fn motherlode_rule(url: &str) -> WebhookRule {
+    WebhookRule::new(
+        "motherlode",
+        "Round/list",
+        Predicate::Compare {
+            field: FieldPath::new(&["state", "motherlode"]),
+            op: CompareOp::Gt,
+            value: PredicateValue::Literal(json!(1000)),
+        },
+        url,
+    )
+    .with_template(json!({
+        "text": "Round {{key}} motherlode at {{entity.state.motherlode}}",
+        "motherlode": "{{entity.state.motherlode}}",
+    }))
+}
+
+fn round(key: &str, motherlode: u64) -> Mutation {
+    Mutation::new(
+        "Round",
+        json!(key),
+        json!({ "state": { "motherlode": motherlode } }),
+    )
+}
+
+/// Project `batches` with `webhooks` watching the rounds
+async fn project(webhooks: Webhooks, batches: Vec<Vec<Mutation>>) {
+    let mut views = ViewIndex::new();
+    views.add_spec(common::view("Round/list", "Round", Mode::List));
+
+    let (mutations_tx, mutations_rx) = mpsc::channel(batches.len().max(1));
+    for mutations in batches {
+        mutations_tx
+            .send(MutationBatch::new(mutations.into()))
+            .await
+            .unwrap();
+    }
+    drop(mutations_tx);
+
+    Projector::new(
+        Arc::new(views),
+        BusManager::new(),
+        EntityCache::new(),
+        mutations_rx,
+    )
+    .with_webhooks(webhooks)
+    .run()
+    .await;
+}
+
+/// Wait for the rule's payloads to settle as delivered or failed
+async fn settled(webhooks: &Webhooks, payloads: u64) -> WebhookStats {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let stats = webhooks.stats()["motherlode"].clone();
+            if stats.delivered + stats.failed >= payloads {
+                return stats;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("webhooks should settle")
+}
+
+#[tokio::test]
+async fn matches_are_deduped_and_retried_until_delivered() {
+    let (url, receiver) = spawn_receiver(2).await;
+    let webhooks = Webhooks::new(
+        WebhookConfig::new()
+            .with_rule(motherlode_rule(&url))
+            .with_retries(3, Duration::from_millis(10)),
+    );
+    let delivery = webhooks.spawn_delivery(None);
+
+    project(
+        webhooks.clone(),
+        vec![
+            vec![round("1", 500)],
+            vec![round("1", 1500)],
+            // Still matching within the dedupe window
+            vec![round("1", 1600)],
+            vec![round("2", 2000)],
+        ],
+    )
+    .await;
+
+    let stats = settled(&webhooks, 2).await;
+    assert_eq!(
+        stats,
+        WebhookStats {
+            matched: 3,
+            delivered: 2,
+            retries: 2,
+            deduped: 1,
+            ..Default::default()
+        }
+    );
+    assert_eq!(*receiver.attempts.lock().unwrap(), 4);
+    assert_eq!(
+        *receiver.accepted.lock().unwrap(),
+        vec![
+            json!({ "text": "Round 1 motherlode at 1500", "motherlode": 1500 }),
+            json!({ "text": "Round 2 motherlode at 2000", "motherlode": 2000 }),
+        ]
+    );
+
+    delivery.abort();
+}
+
+#[tokio::test]
+async fn failing_deliveries_are_noted_without_blocking_projection() {
+    let (url, receiver) = spawn_receiver(usize::MAX).await;
+    let health = HealthMonitor::new(HealthConfig::default());
+    let webhooks = Webhooks::new(
+        WebhookConfig::new()
+            .with_rule(motherlode_rule(&url).with_queue_capacity(1))
+            .with_retries(2, Duration::from_millis(10)),
+    );
+    let delivery = webhooks.spawn_delivery(Some(health.clone()));
+
+    // Every round matches, more than the queue holds while the first fails
+    let rounds = (0..20).map(|n| vec![round(&n.to_string(), 5000)]).collect();
+    tokio::time::timeout(Duration::from_secs(2), project(webhooks.clone(), rounds))
+        .await
+        .expect("projection should not wait on deliveries");
+
+    let stats = webhooks.stats()["motherlode"].clone();
+    assert_eq!(stats.matched, 20);
+    assert!(stats.dropped > 0);
+
+    let stats = settled(&webhooks, 20 - stats.dropped).await;
+    assert_eq!(stats.delivered, 0);
+    assert!(stats
+        .last_error
+        .as_deref()
+        .is_some_and(|error| error.contains("500")));
+    assert_eq!(*receiver.attempts.lock().unwrap() as u64, stats.failed * 2);
+
+    // The note follows the failure it reports
+    let notes = tokio::time::timeout(Duration::from_secs(1), async {
+        loop {
+            let notes = health.notes().await;
+            if !notes.is_empty() {
+                return notes;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the failure should be noted");
+    assert_eq!(notes.len(), 1);
+    assert!(notes[0].starts_with("webhook motherlode failing"));
+
+    delivery.abort();
+}
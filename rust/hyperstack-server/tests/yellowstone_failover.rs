@@ -0,0 +1,310 @@
+use hyperstack_server::{
+    run_sources, HealthConfig, HealthMonitor, ReconnectionConfig, SourceDedup, StreamStatus,
+    YellowstoneConfig, YellowstoneEndpoint, YellowstoneStrategy,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const PRIMARY: &str = "http://primary:10000";
+const BACKUP: &str = "http://backup:10000";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Update {
+    Account {
+        pubkey: &'static str,
+        slot: u64,
+        write_version: u64,
+    },
+    Instruction {
+        signature: &'static str,
+    },
+}
+
+fn account(slot: u64, write_version: u64) -> Update {
+    Update::Account {
+        pubkey: "round",
+        slot,
+        write_version,
+    }
+}
+
+fn instruction(signature: &'static str) -> Update {
+    Update::Instruction { signature }
+}
+
+/// Endpoint statuses as seen at the end of each connection
+type Snapshots = Arc<Mutex<Vec<Vec<(String, StreamStatus)>>>>;
+
+/// One scripted connection: the updates it streams, then whether the stream
+/// ends with an error or cleanly
+struct Script {
+    updates: Vec<Update>,
+    dies: bool,
+}
+
+/// A Yellowstone source whose connections follow a script per endpoint
+#[derive(Clone)]
+struct MockSource {
+    scripts: Arc<Mutex<HashMap<&'static str, Vec<Script>>>>,
+    delivered: Arc<Mutex<Vec<(String, Update)>>>,
+    health: HealthMonitor,
+    snapshots: Snapshots,
+}
+
+impl MockSource {
+    fn new(scripts: Vec<(&'static str, Vec<Script>)>, health: HealthMonitor) -> Self {
+        Self {
+            scripts: Arc::new(Mutex::new(scripts.into_iter().collect())),
+            delivered: Arc::new(Mutex::new(Vec::new())),
+            health,
+            snapshots: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Stream the next scripted connection for `endpoint` through the VM-side
+    /// dedup, the way the generated handlers do
+    async fn connect(
+        &self,
+        endpoint: YellowstoneEndpoint,
+        dedup: Option<SourceDedup>,
+    ) -> anyhow::Result<()> {
+        let script = {
+            let mut scripts = self.scripts.lock().unwrap();
+            let remaining = scripts.get_mut(endpoint.endpoint.as_str()).unwrap();
+            assert!(!remaining.is_empty(), "unexpected reconnect");
+            remaining.remove(0)
+        };
+
+        for update in script.updates {
+            let fresh = match (&dedup, &update) {
+                (None, _) => true,
+                (
+                    Some(dedup),
+                    Update::Account {
+                        pubkey,
+                        slot,
+                        write_version,
+                    },
+                ) => dedup.account(pubkey.as_bytes(), *slot, *write_version),
+                (Some(dedup), Update::Instruction { signature }) => {
+                    dedup.instruction(signature.as_bytes())
+                }
+            };
+            if fresh {
+                self.delivered
+                    .lock()
+                    .unwrap()
+                    .push((endpoint.endpoint.clone(), update));
+            }
+            tokio::task::yield_now().await;
+        }
+
+        let statuses = self.health.endpoint_statuses().await;
+        self.snapshots.lock().unwrap().push(statuses);
+
+        if script.dies {
+            Err(anyhow::anyhow!("stream reset by {}", endpoint.endpoint))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn delivered(&self) -> Vec<(String, Update)> {
+        self.delivered.lock().unwrap().clone()
+    }
+}
+
+fn reconnection(max_attempts: u32) -> ReconnectionConfig {
+    ReconnectionConfig::new()
+        .with_initial_delay(Duration::from_millis(1))
+        .with_max_attempts(max_attempts)
+}
+
+#[tokio::test]
+async fn failover_moves_to_the_backup_when_the_primary_dies_mid_stream() {
+    let health = HealthMonitor::new(HealthConfig::new());
+    let source = MockSource::new(
+        vec![
+            (
+                PRIMARY,
+                vec![Script {
+                    updates: vec![account(1, 1), account(2, 1)],
+                    dies: true,
+                }],
+            ),
+            (
+                BACKUP,
+                vec![Script {
+                    updates: vec![account(3, 1)],
+                    dies: true,
+                }],
+            ),
+        ],
+        health.clone(),
+    );
+    let config = YellowstoneConfig::new(PRIMARY).with_endpoint(YellowstoneEndpoint::new(BACKUP));
+
+    let result = run_sources(
+        &config,
+        &reconnection(2),
+        Some(health.clone()),
+        |endpoint, dedup| {
+            assert!(dedup.is_none(), "failover doesn't deduplicate");
+            let source = source.clone();
+            async move { source.connect(endpoint, dedup).await }
+        },
+    )
+    .await;
+
+    assert!(result.is_err(), "both endpoints used their attempts");
+    assert_eq!(
+        source.delivered(),
+        vec![
+            (PRIMARY.to_string(), account(1, 1)),
+            (PRIMARY.to_string(), account(2, 1)),
+            (BACKUP.to_string(), account(3, 1)),
+        ]
+    );
+
+    let snapshots = source.snapshots.lock().unwrap().clone();
+    let while_backup_streamed = &snapshots[1];
+    assert!(matches!(
+        while_backup_streamed.as_slice(),
+        [(primary, StreamStatus::Error(_)), (backup, StreamStatus::Connected)]
+            if primary == PRIMARY && backup == BACKUP
+    ));
+    assert!(matches!(health.status().await, StreamStatus::Error(_)));
+}
+
+#[tokio::test]
+async fn failover_returns_to_the_primary_after_the_backup_dies() {
+    let health = HealthMonitor::new(HealthConfig::new());
+    let source = MockSource::new(
+        vec![
+            (
+                PRIMARY,
+                vec![
+                    Script {
+                        updates: vec![account(1, 1)],
+                        dies: true,
+                    },
+                    Script {
+                        updates: vec![account(3, 1)],
+                        dies: false,
+                    },
+                ],
+            ),
+            (
+                BACKUP,
+                vec![Script {
+                    updates: vec![account(2, 1)],
+                    dies: true,
+                }],
+            ),
+        ],
+        health.clone(),
+    );
+    let config = YellowstoneConfig::new(PRIMARY).with_endpoint(YellowstoneEndpoint::new(BACKUP));
+
+    let _ = run_sources(
+        &config,
+        &reconnection(3),
+        Some(health.clone()),
+        |endpoint, dedup| {
+            let source = source.clone();
+            async move { source.connect(endpoint, dedup).await }
+        },
+    )
+    .await;
+
+    let order: Vec<_> = source
+        .delivered()
+        .into_iter()
+        .map(|(endpoint, _)| endpoint)
+        .collect();
+    assert_eq!(order, vec![PRIMARY, BACKUP, PRIMARY]);
+    assert!(matches!(
+        health.endpoint_statuses().await.as_slice(),
+        [(_, StreamStatus::Disconnected), (_, StreamStatus::Error(_))]
+    ));
+}
+
+#[tokio::test]
+async fn racing_delivers_each_update_once_when_an_endpoint_dies_mid_stream() {
+    let stream = vec![
+        account(1, 1),
+        instruction("tx-a"),
+        instruction("tx-a"),
+        account(1, 2),
+        account(2, 1),
+        instruction("tx-b"),
+    ];
+
+    let health = HealthMonitor::new(HealthConfig::new());
+    let source = MockSource::new(
+        vec![
+            (
+                PRIMARY,
+                vec![
+                    // Dies between the two instructions of tx-a, then replays
+                    // the whole stream after reconnecting
+                    Script {
+                        updates: stream[..2].to_vec(),
+                        dies: true,
+                    },
+                    Script {
+                        updates: stream.clone(),
+                        dies: false,
+                    },
+                ],
+            ),
+            (
+                BACKUP,
+                vec![
+                    Script {
+                        updates: stream.clone(),
+                        dies: false,
+                    },
+                    Script {
+                        updates: Vec::new(),
+                        dies: false,
+                    },
+                ],
+            ),
+        ],
+        health.clone(),
+    );
+    let config = YellowstoneConfig::new(PRIMARY)
+        .with_endpoint(YellowstoneEndpoint::new(BACKUP))
+        .with_strategy(YellowstoneStrategy::Race);
+
+    let result = run_sources(
+        &config,
+        &reconnection(2),
+        Some(health.clone()),
+        |endpoint, dedup| {
+            assert!(dedup.is_some(), "racing deduplicates");
+            let source = source.clone();
+            async move { source.connect(endpoint, dedup).await }
+        },
+    )
+    .await;
+    assert!(result.is_err());
+
+    let mut delivered: Vec<_> = source
+        .delivered()
+        .into_iter()
+        .map(|(_, update)| update)
+        .collect();
+    let mut expected = stream.clone();
+    delivered.sort_by_key(|update| format!("{update:?}"));
+    expected.sort_by_key(|update| format!("{update:?}"));
+    assert_eq!(delivered, expected);
+
+    let snapshots = source.snapshots.lock().unwrap().clone();
+    assert!(
+        snapshots.iter().all(|statuses| statuses.len() == 2),
+        "both endpoints are reported"
+    );
+}
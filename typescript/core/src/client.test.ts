@@ -1,5 +1,9 @@
 import { describe, it, expect } from 'vitest';
 import { parseFrame, isSnapshotFrame } from './frame';
+import type { EntityFrame } from './frame';
+import { FrameProcessor } from './frame-processor';
+import { MemoryAdapter } from './storage/memory-adapter';
+import { EntityStore } from './store';
 import { gzip } from 'pako';
 
 describe('HyperStack SDK', () => {
@@ -87,3 +91,42 @@ describe('Frame parsing', () => {
   });
 
 });
+
+describe('Patch merging', () => {
+  const frames: EntityFrame[] = [
+    {
+      mode: 'list',
+      entity: 'test/list',
+      op: 'upsert',
+      key: '1',
+      data: { automation: { executor: 'abc', fee: 5 }, name: 'Test Entity' },
+    },
+    {
+      mode: 'list',
+      entity: 'test/list',
+      op: 'patch',
+      key: '1',
+      data: { automation: { executor: null } },
+    },
+  ];
+  const cleared = { automation: { fee: 5 }, name: 'Test Entity' };
+
+  it('should remove fields patched to null from storage', () => {
+    const storage = new MemoryAdapter();
+    const processor = new FrameProcessor(storage);
+    frames.forEach((frame) => processor.handleFrame(frame));
+
+    const entity = storage.get<Record<string, unknown>>('test/list', '1');
+    expect(entity).toEqual(cleared);
+    expect(entity?.automation).not.toHaveProperty('executor');
+  });
+
+  it('should remove fields patched to null from the entity store', () => {
+    const store = new EntityStore();
+    frames.forEach((frame) => store.handleFrame(frame));
+
+    const entity = store.get<Record<string, unknown>>('test/list', '1');
+    expect(entity).toEqual(cleared);
+    expect(entity?.automation).not.toHaveProperty('executor');
+  });
+});
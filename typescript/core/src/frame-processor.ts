@@ -43,6 +43,12 @@ function deepMergeWithAppend<T>(
     const targetValue = result[key];
     const fieldPath = currentPath ? `${currentPath}.${key}` : key;
 
+    // An explicit null clears the field
+    if (sourceValue === null) {
+      delete result[key];
+      continue;
+    }
+
     if (Array.isArray(sourceValue) && Array.isArray(targetValue)) {
       if (appendPaths.includes(fieldPath)) {
         result[key] = [...targetValue, ...sourceValue];